@@ -0,0 +1,53 @@
+//! Benchmarks for the momentum detector under a tick storm
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use poly_hft::duration::DurationConfig;
+use poly_hft::feed::PriceTick;
+use poly_hft::signal::{MomentumDetector, Side, DEFAULT_MOMENTUM_MAX_SAMPLES};
+use poly_hft::sim::{SimConfig, SyntheticFeed};
+
+/// Two minutes at 500 ticks/sec
+fn storm() -> Vec<PriceTick> {
+    let config = SimConfig {
+        duration_mins: DurationConfig::from_mins(2),
+        tick_interval_ms: DurationConfig::from_millis(2),
+        jump_probability: 0.0,
+        ..Default::default()
+    };
+    SyntheticFeed::new("BTCUSDT", &config).collect()
+}
+
+/// Feed every tick, reading the signal once a second
+fn run(ticks: &[PriceTick], max_samples: usize) -> MomentumDetector {
+    let mut momentum = MomentumDetector::default().with_max_samples(max_samples);
+    for chunk in ticks.chunks(500) {
+        for tick in chunk {
+            momentum.update(tick.exchange_ts, tick.price);
+        }
+        black_box(momentum.signal(Side::Yes));
+    }
+    momentum
+}
+
+fn benchmark_storm(c: &mut Criterion) {
+    let ticks = storm();
+    for (name, max_samples) in [
+        ("momentum_storm_bounded", DEFAULT_MOMENTUM_MAX_SAMPLES),
+        ("momentum_storm_unbounded", usize::MAX),
+    ] {
+        // Retained samples is the memory footprint
+        let momentum = run(&ticks, max_samples);
+        println!(
+            "{}: {} ticks, {} samples retained, velocity {:?}",
+            name,
+            ticks.len(),
+            momentum.sample_count(),
+            momentum.signal(Side::Yes).map(|s| s.velocity)
+        );
+
+        c.bench_function(name, |b| b.iter(|| run(black_box(&ticks), max_samples)));
+    }
+}
+
+criterion_group!(benches, benchmark_storm);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+//! Benchmarks for flushing captured order books to Parquet
+
+use chrono::{Duration, TimeZone, Utc};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use poly_hft::data::{orderbook_batch, OrderBookRecord, ParquetWriter, CAPTURED_BOOK_LEVELS};
+use poly_hft::orderbook::BookUpdateKind;
+use rust_decimal::Decimal;
+use std::sync::Arc;
+
+const RECORDS: usize = 100_000;
+
+/// Full five-level books on two tokens, 10ms apart
+fn records() -> Vec<OrderBookRecord> {
+    let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    let tokens: [Arc<str>; 2] = [Arc::from("yes-token"), Arc::from("no-token")];
+    (0..RECORDS)
+        .map(|i| {
+            let mid = Decimal::new(5000 + (i % 400) as i64, 4);
+            let tick = Decimal::new(10, 4);
+            let level = |n: usize| Decimal::new(1250 + (n * 37 + i) as i64 % 9000, 2);
+            OrderBookRecord {
+                timestamp: start + Duration::milliseconds(10 * i as i64),
+                token_id: tokens[i % 2].clone(),
+                bids: (0..CAPTURED_BOOK_LEVELS)
+                    .map(|n| (mid - tick * Decimal::from(n + 1), level(n)))
+                    .collect(),
+                asks: (0..CAPTURED_BOOK_LEVELS)
+                    .map(|n| (mid + tick * Decimal::from(n + 1), level(n + 5)))
+                    .collect(),
+                crossed: false,
+                kind: BookUpdateKind::Snapshot,
+            }
+        })
+        .collect()
+}
+
+fn benchmark_flush(c: &mut Criterion) {
+    let records = records();
+    let dir = tempfile::tempdir().unwrap();
+    let writer = ParquetWriter::new(dir.path().to_path_buf(), 3600);
+    let path = dir.path().join("orderbook.parquet");
+
+    let mut group = c.benchmark_group("parquet_flush");
+    group.sample_size(10);
+    group.bench_function("orderbook_batch_100k", |b| {
+        b.iter(|| orderbook_batch(black_box(&records)).unwrap())
+    });
+    group.bench_function("orderbook_flush_100k", |b| {
+        b.iter(|| {
+            writer
+                .write_orderbook_snapshots(&path, black_box(&records))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_flush);
+criterion_main!(benches);
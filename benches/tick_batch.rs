@@ -0,0 +1,73 @@
+//! Benchmarks for per-tick against batched price processing, from the
+//! detection channel through the engine
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use poly_hft::backtest::BacktestEvent;
+use poly_hft::config::Config;
+use poly_hft::duration::DurationConfig;
+use poly_hft::engine::TradingEngine;
+use poly_hft::execution::PaperEngine;
+use poly_hft::feed::{LagAwareReceiver, PriceTick, TickLagConfig};
+use poly_hft::sim::SyntheticFeed;
+use rust_decimal::Decimal;
+use tokio::sync::mpsc;
+
+/// One minute at 1k ticks/sec
+fn ticks(config: &mut Config) -> Vec<PriceTick> {
+    config.sim.duration_mins = DurationConfig::from_mins(1);
+    config.sim.tick_interval_ms = DurationConfig::from_millis(1);
+    SyntheticFeed::new("BTCUSDT", &config.sim).collect()
+}
+
+/// Push `ticks` through a detection channel as fast as the engine takes
+/// them, in batches of up to `max_batch`; 0 takes every tick on its own
+async fn run(config: &Config, ticks: &[PriceTick], max_batch: usize) -> u64 {
+    let mut engine = TradingEngine::new(config, PaperEngine::new(Decimal::ZERO));
+    let (tx, rx) = mpsc::channel(config.feed.lag.channel_capacity);
+    let ticks = ticks.to_vec();
+    tokio::spawn(async move {
+        for tick in ticks {
+            if tx.send(tick).await.is_err() {
+                break;
+            }
+        }
+    });
+    // Synthetic ticks are old by the wall clock; never judge the lag
+    let lag = TickLagConfig {
+        min_samples: usize::MAX,
+        max_batch,
+        ..Default::default()
+    };
+    let mut prices = LagAwareReceiver::new(rx, lag);
+    if max_batch == 0 {
+        while let Some(tick) = prices.recv().await {
+            engine
+                .on_event(tick.exchange_ts, BacktestEvent::PriceTick(tick))
+                .await
+                .unwrap();
+        }
+    } else {
+        while let Some(batch) = prices.recv_batch().await {
+            engine.on_ticks(batch).await.unwrap();
+        }
+    }
+    engine.stats().ticks
+}
+
+fn benchmark_tick_batches(c: &mut Criterion) {
+    let mut config: Config =
+        toml::from_str(include_str!("../config.toml.example")).expect("Invalid example config");
+    let ticks = ticks(&mut config);
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("tick_batch_1k_per_sec");
+    group.sample_size(10);
+    for (name, max_batch) in [("per_tick", 0), ("batch_16", 16), ("batch_256", 256)] {
+        group.bench_function(name, |b| {
+            b.to_async(&rt).iter(|| run(&config, &ticks, max_batch))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_tick_batches);
+criterion_main!(benches);
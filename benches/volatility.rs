@@ -0,0 +1,52 @@
+//! Benchmarks for the volatility estimator under a tick storm
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use poly_hft::duration::DurationConfig;
+use poly_hft::feed::PriceTick;
+use poly_hft::model::{VolatilityEstimator, DEFAULT_MAX_SAMPLES};
+use poly_hft::sim::{SimConfig, SyntheticFeed};
+
+/// Two minutes at 500 ticks/sec
+fn storm() -> Vec<PriceTick> {
+    let config = SimConfig {
+        duration_mins: DurationConfig::from_mins(2),
+        tick_interval_ms: DurationConfig::from_millis(2),
+        jump_probability: 0.0,
+        ..Default::default()
+    };
+    SyntheticFeed::new("BTCUSDT", &config).collect()
+}
+
+fn run(ticks: &[PriceTick], max_samples: usize) -> VolatilityEstimator {
+    let mut estimator =
+        VolatilityEstimator::new(chrono::Duration::minutes(1)).with_max_samples(max_samples);
+    for tick in ticks {
+        estimator.update(tick.exchange_ts, tick.price);
+    }
+    estimator
+}
+
+fn benchmark_storm(c: &mut Criterion) {
+    let ticks = storm();
+    for (name, max_samples) in [
+        ("volatility_storm_bounded", DEFAULT_MAX_SAMPLES),
+        ("volatility_storm_unbounded", usize::MAX),
+    ] {
+        // Retained samples is the memory footprint
+        let estimator = run(&ticks, max_samples);
+        println!(
+            "{}: {} ticks, {} samples retained, estimate {:?}",
+            name,
+            ticks.len(),
+            estimator.sample_count(),
+            estimator.estimate()
+        );
+
+        c.bench_function(name, |b| {
+            b.iter(|| run(black_box(&ticks), max_samples).estimate())
+        });
+    }
+}
+
+criterion_group!(benches, benchmark_storm);
+criterion_main!(benches);
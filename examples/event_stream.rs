@@ -0,0 +1,61 @@
+//! Follow the engine's event stream
+//!
+//! Run the bot with `[stream] enabled = true` and a `token`, then:
+//!
+//! ```sh
+//! cargo run --example event_stream -- 127.0.0.1:9101 <token> "kinds=signal_emitted,hard_halt"
+//! ```
+//!
+//! Every event's data is a journal line, so it parses as a `JournalEntry`.
+
+use poly_hft::journal::JournalEntry;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:9101".to_string());
+    let token = args
+        .next()
+        .or_else(|| std::env::var("POLYHFT_STREAM_TOKEN").ok())
+        .ok_or_else(|| anyhow::anyhow!("usage: event_stream <addr> <token> [query]"))?;
+    let query = args.next().unwrap_or_default();
+
+    let mut socket = TcpStream::connect(&addr).await?;
+    let request = format!(
+        "GET /events?{query} HTTP/1.1\r\nHost: {addr}\r\nAuthorization: Bearer {token}\r\n\r\n"
+    );
+    socket.write_all(request.as_bytes()).await?;
+
+    let mut lines = BufReader::new(socket).lines();
+    let status = lines.next_line().await?.unwrap_or_default();
+    if !status.contains(" 200 ") {
+        anyhow::bail!("stream refused: {}", status);
+    }
+
+    let mut event = String::new();
+    while let Some(line) = lines.next_line().await? {
+        if let Some(name) = line.strip_prefix("event: ") {
+            event = name.to_string();
+        } else if let Some(data) = line.strip_prefix("data: ") {
+            if event == "dropped" {
+                println!("-- slow consumer, entries dropped so far: {}", data);
+                continue;
+            }
+            let entry: JournalEntry = serde_json::from_str(data)?;
+            let market = entry
+                .data
+                .get("market_id")
+                .and_then(|m| m.as_str())
+                .unwrap_or("-");
+            println!(
+                "{} {:<24} {}",
+                entry.ts.format("%H:%M:%S%.3f"),
+                entry.kind,
+                market
+            );
+        }
+    }
+    Ok(())
+}
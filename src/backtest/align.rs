@@ -0,0 +1,293 @@
+//! Alignment of a backtest to market boundaries
+//!
+//! A range starting mid-window would trade a market whose earlier momentum
+//! and books the strategy never saw, which no live bot could have done.
+//! Aligned to markets, trading starts at the first market opening at or
+//! after the range start and stops at the last market closing at or before
+//! the range end. Markets cut by either bound are dropped with their books;
+//! spot ticks before the first open stay, so detectors still warm up.
+
+use super::BacktestEvent;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+
+/// An event and the time it is replayed at
+type TimedEvent = (DateTime<Utc>, BacktestEvent);
+
+/// Which part of the range a backtest trades
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Alignment {
+    /// Only markets whose whole window lies in the range
+    #[default]
+    Market,
+    /// Every market the range touches, cut windows included
+    None,
+}
+
+/// The range a market-aligned backtest traded
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AlignedRange {
+    /// Open of the first complete market
+    pub start: Option<DateTime<Utc>>,
+    /// Close of the last complete market
+    pub end: Option<DateTime<Utc>>,
+    /// Markets excluded for being cut by the range
+    pub partial_windows: usize,
+}
+
+impl fmt::Display for AlignedRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => write!(
+                f,
+                "{} to {}, ",
+                start.format("%Y-%m-%d %H:%M:%S"),
+                end.format("%Y-%m-%d %H:%M:%S")
+            )?,
+            _ => write!(f, "no complete market, ")?,
+        }
+        write!(f, "{} partial windows excluded", self.partial_windows)
+    }
+}
+
+/// Drop the markets of `events` cut by `start` or `end`, with their books,
+/// and everything after the last complete market closes
+///
+/// Missing bounds are the first and last event, so a capture starting
+/// mid-window is aligned too.
+pub fn align_to_markets(
+    events: Vec<TimedEvent>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> (Vec<TimedEvent>, AlignedRange) {
+    let (Some(first), Some(last)) = (events.first(), events.last()) else {
+        return (events, AlignedRange::default());
+    };
+    let from = start.unwrap_or(first.0);
+    let to = end.unwrap_or(last.0);
+
+    let mut range = AlignedRange::default();
+    let mut partial = HashSet::new();
+    for (_, event) in &events {
+        let BacktestEvent::MarketOpen(market) = event else {
+            continue;
+        };
+        if market.open_time < from || market.close_time > to {
+            if partial.insert(market.condition_id.clone()) {
+                tracing::debug!(
+                    market = %market.condition_id,
+                    open_time = %market.open_time,
+                    close_time = %market.close_time,
+                    "Excluding partial market window"
+                );
+                partial.insert(market.yes_token_id.clone());
+                partial.insert(market.no_token_id.clone());
+                range.partial_windows += 1;
+            }
+            continue;
+        }
+        range.start = Some(
+            range
+                .start
+                .map_or(market.open_time, |s| s.min(market.open_time)),
+        );
+        range.end = Some(
+            range
+                .end
+                .map_or(market.close_time, |e| e.max(market.close_time)),
+        );
+    }
+
+    let events = events
+        .into_iter()
+        .filter(|(at, event)| {
+            if range.end.is_some_and(|end| *at > end) {
+                return false;
+            }
+            match event {
+                BacktestEvent::MarketOpen(m) | BacktestEvent::MarketClose(m) => {
+                    !partial.contains(&m.condition_id)
+                }
+                BacktestEvent::OrderBookUpdate(book) => !partial.contains(&book.token_id),
+                BacktestEvent::PriceTick(_) | BacktestEvent::DataGap { .. } => true,
+            }
+        })
+        .collect();
+    tracing::info!(
+        start = ?range.start,
+        end = ?range.end,
+        partial_windows = range.partial_windows,
+        "Aligned backtest to market boundaries"
+    );
+    (events, range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::{BacktestConfig, CorruptFiles, LatencySweep};
+    use crate::data::IncludeCurrent;
+    use crate::feed::{PriceTick, TickSource};
+    use crate::market::Market;
+    use crate::model::GbmModel;
+    use crate::orderbook::{OrderBook, PriceLevel};
+    use chrono::Duration;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::path::PathBuf;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn market(i: i64) -> Market {
+        Market {
+            condition_id: format!("cond-{}", i),
+            asset: "BTC".to_string(),
+            yes_token_id: format!("yes-{}", i),
+            no_token_id: format!("no-{}", i),
+            open_price: dec!(100000),
+            open_time: at(i * 900),
+            close_time: at(i * 900 + 900),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    fn tick(ts: DateTime<Utc>, price: Decimal) -> TimedEvent {
+        (
+            ts,
+            BacktestEvent::PriceTick(PriceTick {
+                symbol: "BTCUSDT".to_string(),
+                asset: "BTC".to_string(),
+                price,
+                timestamp: ts,
+                exchange_ts: ts,
+                source: TickSource::Trade,
+            }),
+        )
+    }
+
+    /// Three back-to-back 15-minute windows, each with a spot rally in its
+    /// first minute and a cheap YES ask right after
+    fn three_windows() -> Vec<TimedEvent> {
+        let mut events = vec![];
+        for i in 0..3 {
+            let market = market(i);
+            let open = market.open_time;
+            events.push((open, BacktestEvent::MarketOpen(market.clone())));
+            for s in 0..60 {
+                let price = dec!(100000) + Decimal::from(s * 50) + Decimal::from(s % 2 * 20);
+                events.push(tick(open + Duration::seconds(s), price));
+            }
+            let mut book = OrderBook::new(&market.yes_token_id);
+            book.bids = vec![PriceLevel {
+                price: dec!(0.39),
+                size: dec!(100),
+            }];
+            book.asks = vec![PriceLevel {
+                price: dec!(0.40),
+                size: dec!(100),
+            }];
+            book.updated_at = open + Duration::seconds(61);
+            events.push((book.updated_at, BacktestEvent::OrderBookUpdate(book)));
+            events.push(tick(market.close_time, dec!(103000)));
+            events.push((market.close_time, BacktestEvent::MarketClose(market)));
+        }
+        events
+    }
+
+    /// `events` as loaded from `start` on: earlier rows cut, and a market
+    /// open before `start` replayed at it
+    fn from(events: Vec<TimedEvent>, start: DateTime<Utc>) -> Vec<TimedEvent> {
+        events
+            .into_iter()
+            .map(|(at, event)| match event {
+                BacktestEvent::MarketOpen(m) if m.open_time < start => {
+                    (start, BacktestEvent::MarketOpen(m))
+                }
+                _ => (at, event),
+            })
+            .filter(|(at, _)| *at >= start)
+            .collect()
+    }
+
+    fn decisions(events: Vec<TimedEvent>) -> usize {
+        let config = BacktestConfig {
+            data_dir: PathBuf::from("./nonexistent"),
+            merge_dirs: vec![],
+            price_history: false,
+            scenario: None,
+            align: Alignment::None,
+            corrupt_files: CorruptFiles::Skip,
+            include_current: IncludeCurrent::Skip,
+            start_time: None,
+            end_time: None,
+            initial_capital: dec!(500),
+            latency_ms: 0,
+            book_staleness_ms: 0,
+            fee_rate: dec!(0.01),
+            bootstrap: Default::default(),
+        };
+        LatencySweep::new(GbmModel::new(), config, events)
+            .run_point(100)
+            .decisions
+    }
+
+    #[test]
+    fn test_mid_window_start_trades_only_complete_windows() {
+        // Start 20s into the first window, rally still underway
+        let start = at(20);
+        let events = from(three_windows(), start);
+        assert_eq!(decisions(events.clone()), 3);
+
+        let (aligned, range) = align_to_markets(events, Some(start), None);
+        assert_eq!(decisions(aligned.clone()), 2);
+        assert_eq!(range.start, Some(at(900)));
+        assert_eq!(range.end, Some(at(2700)));
+        assert_eq!(range.partial_windows, 1);
+
+        // The cut window's spot ticks stay for warm-up, its book does not
+        assert!(aligned
+            .iter()
+            .any(|(at, e)| *at < start + Duration::seconds(40)
+                && matches!(e, BacktestEvent::PriceTick(_))));
+        assert!(!aligned.iter().any(|(_, e)| matches!(
+            e,
+            BacktestEvent::OrderBookUpdate(b) if b.token_id == "yes-0"
+        )));
+        assert_eq!(
+            range.to_string(),
+            "2023-11-14 22:28:20 to 2023-11-14 22:58:20, 1 partial windows excluded"
+        );
+    }
+
+    #[test]
+    fn test_end_before_last_close_drops_the_cut_window() {
+        let end = at(2000);
+        let events: Vec<_> = three_windows()
+            .into_iter()
+            .filter(|(at, _)| *at <= end)
+            .collect();
+        let (aligned, range) = align_to_markets(events, None, Some(end));
+        assert_eq!(range.start, Some(at(0)));
+        assert_eq!(range.end, Some(at(1800)));
+        assert_eq!(range.partial_windows, 1);
+        assert!(aligned.iter().all(|(at, _)| *at <= market(1).close_time));
+        assert_eq!(decisions(aligned), 2);
+
+        // Nothing complete: no range, every market excluded
+        let (aligned, range) = align_to_markets(
+            three_windows().into_iter().take(10).collect(),
+            Some(at(5)),
+            None,
+        );
+        assert_eq!((range.start, range.partial_windows), (None, 1));
+        assert!(!aligned
+            .iter()
+            .any(|(_, e)| matches!(e, BacktestEvent::MarketOpen(_))));
+    }
+}
@@ -1,11 +1,103 @@
 //! Backtest analytics and reporting
+//!
+//! Besides the summary, every closed trade can be written as a row of the
+//! trade tape, a Parquet file for notebooks. Backtests (`--trades-out`)
+//! and live, paper and simulated sessions (`trade_tape.parquet` at
+//! shutdown) share one schema, so their tapes compare directly.
+//!
+//! Trade tape schema, version [`TRADE_TAPE_VERSION`] (stored in the file
+//! metadata under [`TRADE_TAPE_VERSION_KEY`]); decimals are text, times
+//! are UTC microseconds:
+//!
+//! | Column | Type | Meaning |
+//! |---|---|---|
+//! | `position_id` | text | Position identifier |
+//! | `signal_id` | text, null | Signal that opened it; null for recovered orders |
+//! | `market_id` | text | Market condition ID |
+//! | `asset` | text | Market asset, e.g. `BTC` |
+//! | `strategy` | text | Strategy that traded |
+//! | `side` | text | `yes` or `no` |
+//! | `entry_time`, `exit_time` | timestamp | Fill and settlement times |
+//! | `size` | decimal | Shares |
+//! | `entry_price`, `exit_price` | decimal | Fill price, settlement payout |
+//! | `fees` | decimal | Fees paid |
+//! | `realized_pnl` | decimal | P&L after fees |
+//! | `signal_reason` | text, null | Why the signal fired |
+//! | `fair_value`, `market_price` | decimal, null | Model and book price of the side at entry |
+//! | `lag` | decimal, null | `fair_value - market_price`: how far the book lagged |
+//! | `edge` | decimal, null | Edge after costs |
+//! | `momentum_move` | decimal, null | Spot move over the momentum window in the trade's favour |
+//! | `retrace` | decimal, null | Fraction of that move given back |
+//! | `confidence` | decimal, null | Signal confidence |
+//! | `secs_to_close` | int64, null | Seconds from entry to market close |
+//! | `prior_rejections` | uint32 | Rejections in the trail |
+//! | `rejection_times`, `rejection_reasons` | list | The market's rejection trail before entry; reasons are filter codes or limit labels |
+//! | `expected_value_usd` | decimal, null | `(fair_value - entry_price) * size` less the entry fee: what the signal claimed (since version 2) |
+//! | `rejection_contexts` | list | JSON object of the observed value and threshold behind each rejection, null without (since version 3) |
+//! | `flags` | text, null | JSON object of the feature flags evaluated for the signal, each `{"on": bool}` with the `rollout` percentage while rolling out; null without (since version 4) |
+//!
+//! Rejection reasons of older tapes are read back as today's codes, see
+//! [`reason_code`](crate::signal::reason_code).
+//!
+//! A few hundred trades make a noisy point estimate, and trades of the
+//! same hour move together, so [`BlockBootstrap`] resamples whole blocks
+//! of market windows (a trading day by default) rather than trades. Net
+//! P&L, win rate and max drawdown get 5/50/95 percentiles over the
+//! resamples, with the share of resampled months that lose money. The
+//! resampling is seeded by the backtest seed, so a run always reports the
+//! same intervals.
 
+use super::{AlignedRange, ScenarioWindow, SkippedFile};
+use crate::data::{
+    decimal_column, read_batches, str_column, timestamp_column, writer_properties, CaptureSnapshot,
+};
+use crate::fingerprint;
+use crate::flags::FlagDecision;
+use crate::precision::{round_pct, round_usd};
+use crate::risk::{ClosedPosition, Position};
+use crate::signal::{reason_code, Side, Signal};
+use arrow::array::{
+    Array, ArrayRef, Int64Array, ListArray, ListBuilder, StringArray, StringBuilder,
+    TimestampMicrosecondArray, TimestampMicrosecondBuilder, UInt32Array,
+};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Duration, Utc};
+use parquet::arrow::ArrowWriter;
+use parquet::format::KeyValue;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Trade tape schema version; bump on any column change
+pub const TRADE_TAPE_VERSION: u32 = 4;
+
+/// Parquet metadata key holding [`TRADE_TAPE_VERSION`]
+pub const TRADE_TAPE_VERSION_KEY: &str = "poly_hft.trade_tape.version";
+
+/// Trade tape written to a session's data directory at shutdown
+pub const TRADE_TAPE_FILE: &str = "trade_tape.parquet";
+
+/// Default seed of a backtest's randomness
+pub const DEFAULT_BACKTEST_SEED: u64 = 2463;
+
+/// Default length of a bootstrap block: a trading day
+pub const DEFAULT_BOOTSTRAP_BLOCK_SECS: u64 = 86_400;
+
+/// Default resamples behind the block bootstrap
+pub const DEFAULT_BOOTSTRAP_RESAMPLES: usize = 1000;
+
+/// Length of the month the losing-month probability is taken over
+const BOOTSTRAP_MONTH_SECS: i64 = 30 * 86_400;
 
 /// Summary statistics from backtest
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct BacktestSummary {
     /// Total P&L
     pub total_pnl: Decimal,
@@ -29,6 +121,34 @@ pub struct BacktestSummary {
     pub avg_trade_duration_secs: u64,
     /// Average edge captured
     pub avg_edge: Decimal,
+    /// Total number of events replayed
+    pub events_processed: u64,
+    /// Capture rows dropped as identical to a row of another file
+    pub duplicates_removed: u64,
+    /// Capture rows dropped for differing from a higher-priority file's row
+    pub conflicts_resolved: u64,
+    /// Books approximated from price history rather than captured
+    pub approximated_books: u64,
+    /// Whether any book was approximated, so fills are only indicative
+    pub low_fidelity: bool,
+    /// Periods perturbed by an injected scenario
+    pub scenario: Vec<ScenarioWindow>,
+    /// Range trading was aligned to market boundaries, if it was
+    pub aligned: Option<AlignedRange>,
+    /// Unreadable capture files left out, their time replayed as gaps
+    pub skipped_files: Vec<SkippedFile>,
+    /// Seconds of the range excluded as data gaps
+    pub excluded_secs: u64,
+    /// Capture files read, with their checksums, and files left out as
+    /// still being written
+    pub snapshots: Vec<CaptureSnapshot>,
+    /// Estimated memory held by the loaded event batches in bytes, if
+    /// the events were loaded from captures
+    pub loaded_bytes: Option<u64>,
+    /// Fingerprint hash of the config the run used
+    pub config_hash: Option<String>,
+    /// Block bootstrap intervals over the trades; `None` under two blocks
+    pub bootstrap: Option<BlockBootstrap>,
 }
 
 /// Complete backtest results
@@ -36,6 +156,8 @@ pub struct BacktestSummary {
 pub struct BacktestResult {
     /// Summary statistics
     pub summary: BacktestSummary,
+    /// Closed trades, for the trade tape
+    pub trades: Vec<TapeRow>,
     /// Path to trades Parquet file
     pub trades_path: PathBuf,
     /// Path to equity curve Parquet file
@@ -46,6 +168,7 @@ impl Default for BacktestResult {
     fn default() -> Self {
         Self {
             summary: BacktestSummary::default(),
+            trades: vec![],
             trades_path: PathBuf::from("backtest_trades.parquet"),
             equity_path: PathBuf::from("equity_curve.parquet"),
         }
@@ -55,6 +178,56 @@ impl Default for BacktestResult {
 impl BacktestSummary {
     /// Format as table for CLI output
     pub fn format_table(&self) -> String {
+        let loaded = match self.loaded_bytes {
+            Some(bytes) => format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+            None => "n/a".to_string(),
+        };
+        let fidelity = if self.low_fidelity {
+            format!(
+                "LOW, {} books approximated from price history",
+                self.approximated_books
+            )
+        } else {
+            "captured books".to_string()
+        };
+        let scenario = if self.scenario.is_empty() {
+            "none".to_string()
+        } else {
+            format!(
+                "{} perturbations, {} events injected, {} dropped",
+                self.scenario.len(),
+                self.scenario.iter().map(|w| w.injected).sum::<usize>(),
+                self.scenario.iter().map(|w| w.dropped).sum::<usize>()
+            )
+        };
+        let aligned = match &self.aligned {
+            Some(range) => range.to_string(),
+            None => "not aligned".to_string(),
+        };
+        let gaps = if self.skipped_files.is_empty() {
+            "none".to_string()
+        } else {
+            let mut gaps = format!(
+                "!! {} corrupt files skipped, {}s excluded; nothing traded across them",
+                self.skipped_files.len(),
+                self.excluded_secs
+            );
+            for skipped in &self.skipped_files {
+                gaps.push_str(&format!("\n  {}", skipped));
+            }
+            gaps
+        };
+        let files: usize = self.snapshots.iter().map(|s| s.files.len()).sum();
+        let unsealed: usize = self.snapshots.iter().map(|s| s.unsealed()).sum();
+        let in_progress: usize = self.snapshots.iter().map(|s| s.in_progress.len()).sum();
+        let snapshot = format!(
+            "{} sealed files ({} from before manifests), {} being written left out",
+            files, unsealed, in_progress
+        );
+        let confidence = match &self.bootstrap {
+            Some(bootstrap) => bootstrap.to_string(),
+            None => String::new(),
+        };
         format!(
             r#"
 ══════════════════════════════════════════════════════
@@ -72,9 +245,21 @@ Profit Factor:    {:.2}
 
 ACTIVITY
 ───────────────────────────────────────────────────────
+Aligned Range:    {}
 Total Trades:     {}
 Avg Duration:     {}s
 Avg Edge:         {:.2}%
+{}
+RESOURCES
+───────────────────────────────────────────────────────
+Events Processed: {}
+Snapshot:         {}
+Duplicates:       {} removed, {} conflicts resolved
+Fidelity:         {}
+Data Gaps:        {}
+Scenario:         {}
+Loaded Events:    {}
+Config Hash:      {}
 ══════════════════════════════════════════════════════
 "#,
             self.net_pnl,
@@ -85,13 +270,666 @@ Avg Edge:         {:.2}%
             self.max_drawdown_pct * dec!(100),
             self.win_rate * dec!(100),
             self.profit_factor,
+            aligned,
             self.total_trades,
             self.avg_trade_duration_secs,
             self.avg_edge * dec!(100),
+            confidence,
+            self.events_processed,
+            snapshot,
+            self.duplicates_removed,
+            self.conflicts_resolved,
+            fidelity,
+            gaps,
+            scenario,
+            loaded,
+            self.config_hash.as_deref().unwrap_or("n/a"),
         )
     }
 }
 
+/// Resampling of a backtest's trades by blocks of market windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockBootstrapConfig {
+    /// Length of a block of windows; windows are blocked by their first entry
+    pub block: Duration,
+    /// Resamples drawn; 0 skips the bootstrap
+    pub resamples: usize,
+    /// Seed of the resampling
+    pub seed: u64,
+}
+
+impl Default for BlockBootstrapConfig {
+    fn default() -> Self {
+        Self {
+            block: Duration::seconds(DEFAULT_BOOTSTRAP_BLOCK_SECS as i64),
+            resamples: DEFAULT_BOOTSTRAP_RESAMPLES,
+            seed: DEFAULT_BACKTEST_SEED,
+        }
+    }
+}
+
+/// A statistic of the trades as they happened, with its resampled spread
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Percentiles {
+    /// The statistic over the trades as they happened
+    pub point: Decimal,
+    pub p5: Decimal,
+    pub p50: Decimal,
+    pub p95: Decimal,
+}
+
+impl Percentiles {
+    /// `point` with the 5th, 50th and 95th percentiles of `samples`,
+    /// nearest rank; `None` without samples
+    fn new(point: Decimal, mut samples: Vec<Decimal>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        let at = |q: f64| samples[((samples.len() - 1) as f64 * q).round() as usize];
+        Some(Self {
+            point,
+            p5: at(0.05),
+            p50: at(0.50),
+            p95: at(0.95),
+        })
+    }
+}
+
+/// Net P&L, win rate and max drawdown of a run of trades
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct RunStats {
+    net_pnl: Decimal,
+    /// `None` without trades
+    win_rate: Option<Decimal>,
+    /// Largest fall of the cumulative P&L from its peak, starting at zero
+    max_drawdown: Decimal,
+}
+
+impl RunStats {
+    /// Stats of `pnls`, each a trade's P&L after fees in the order closed
+    fn new<'a>(pnls: impl IntoIterator<Item = &'a Decimal>) -> Self {
+        let (mut net, mut peak, mut drawdown) = (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO);
+        let (mut trades, mut wins) = (0u64, 0u64);
+        for pnl in pnls {
+            trades += 1;
+            wins += u64::from(*pnl > Decimal::ZERO);
+            net += pnl;
+            peak = peak.max(net);
+            drawdown = drawdown.max(peak - net);
+        }
+        Self {
+            net_pnl: net,
+            win_rate: (trades > 0).then(|| round_pct(Decimal::from(wins) / Decimal::from(trades))),
+            max_drawdown: drawdown,
+        }
+    }
+}
+
+/// Spread of a backtest's results over resamples of its blocks of windows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockBootstrap {
+    /// Length of a block in seconds
+    pub block_secs: u64,
+    /// Blocks the trades span, those without trades included
+    pub blocks: usize,
+    /// Market windows traded
+    pub windows: usize,
+    /// Resamples drawn
+    pub resamples: usize,
+    pub net_pnl: Percentiles,
+    pub win_rate: Percentiles,
+    pub max_drawdown: Percentiles,
+    /// Share of resampled 30-day months with a net loss
+    pub losing_month_probability: Decimal,
+}
+
+impl BlockBootstrap {
+    /// Bootstrap of `trades`, grouped into windows by market
+    ///
+    /// Each window falls in the block of its first entry, counted from the
+    /// first window; blocks without trades count, as flat periods. A
+    /// resample draws as many blocks as the trades span, with replacement,
+    /// and strings their trades together in the order drawn. A resampled
+    /// month draws 30 days' worth of blocks. `None` when the trades span
+    /// under two blocks or no resamples are asked for.
+    pub fn new(trades: &[TapeRow], config: &BlockBootstrapConfig) -> Option<Self> {
+        let block_secs = config.block.num_seconds();
+        if config.resamples == 0 || block_secs <= 0 {
+            return None;
+        }
+        let mut windows: HashMap<&str, Vec<&TapeRow>> = HashMap::new();
+        for row in trades {
+            windows.entry(&row.market_id).or_default().push(row);
+        }
+        let mut windows: Vec<(DateTime<Utc>, Vec<&TapeRow>)> = windows
+            .into_values()
+            .map(|mut rows| {
+                rows.sort_by_key(|r| (r.exit_time, r.entry_time));
+                let start = rows.iter().map(|r| r.entry_time).min().unwrap();
+                (start, rows)
+            })
+            .collect();
+        windows.sort_by(|a, b| (a.0, &a.1[0].market_id).cmp(&(b.0, &b.1[0].market_id)));
+        let first = windows.first()?.0;
+
+        let mut blocked: BTreeMap<i64, Vec<Decimal>> = BTreeMap::new();
+        for (start, rows) in &windows {
+            let index = (*start - first).num_seconds() / block_secs;
+            blocked
+                .entry(index)
+                .or_default()
+                .extend(rows.iter().map(|r| r.realized_pnl));
+        }
+        let span = *blocked.keys().next_back()? as usize + 1;
+        if span < 2 {
+            return None;
+        }
+        let mut blocks = vec![vec![]; span];
+        for (index, pnls) in blocked {
+            blocks[index as usize] = pnls;
+        }
+
+        let point = RunStats::new(blocks.iter().flatten());
+        let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+        let (mut nets, mut win_rates, mut drawdowns) = (vec![], vec![], vec![]);
+        for _ in 0..config.resamples {
+            let drawn: Vec<&Vec<Decimal>> =
+                (0..span).map(|_| &blocks[rng.gen_range(0..span)]).collect();
+            let stats = RunStats::new(drawn.into_iter().flatten());
+            nets.push(stats.net_pnl);
+            win_rates.extend(stats.win_rate);
+            drawdowns.push(stats.max_drawdown);
+        }
+        let per_month = (BOOTSTRAP_MONTH_SECS as f64 / block_secs as f64).ceil() as usize;
+        let losing = (0..config.resamples)
+            .filter(|_| {
+                let net: Decimal = (0..per_month)
+                    .flat_map(|_| &blocks[rng.gen_range(0..span)])
+                    .sum();
+                net < Decimal::ZERO
+            })
+            .count();
+
+        Some(Self {
+            block_secs: block_secs as u64,
+            blocks: span,
+            windows: windows.len(),
+            resamples: config.resamples,
+            net_pnl: Percentiles::new(point.net_pnl, nets)?,
+            win_rate: Percentiles::new(point.win_rate?, win_rates)?,
+            max_drawdown: Percentiles::new(point.max_drawdown, drawdowns)?,
+            losing_month_probability: round_pct(
+                Decimal::from(losing) / Decimal::from(config.resamples),
+            ),
+        })
+    }
+}
+
+impl std::fmt::Display for BlockBootstrap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f)?;
+        writeln!(
+            f,
+            "CONFIDENCE ({} resamples of {} blocks of {}s, {} windows)",
+            self.resamples, self.blocks, self.block_secs, self.windows
+        )?;
+        writeln!(f, "───────────────────────────────────────────────────────")?;
+        writeln!(
+            f,
+            "Net P&L:          {:+.2}  [5%: {:+.2}  50%: {:+.2}  95%: {:+.2}]",
+            self.net_pnl.point, self.net_pnl.p5, self.net_pnl.p50, self.net_pnl.p95
+        )?;
+        let pct = |d: Decimal| d * dec!(100);
+        writeln!(
+            f,
+            "Win Rate:         {:.1}%  [5%: {:.1}%  50%: {:.1}%  95%: {:.1}%]",
+            pct(self.win_rate.point),
+            pct(self.win_rate.p5),
+            pct(self.win_rate.p50),
+            pct(self.win_rate.p95)
+        )?;
+        writeln!(
+            f,
+            "Max Drawdown:     {:.2}  [5%: {:.2}  50%: {:.2}  95%: {:.2}]",
+            self.max_drawdown.point,
+            self.max_drawdown.p5,
+            self.max_drawdown.p50,
+            self.max_drawdown.p95
+        )?;
+        writeln!(
+            f,
+            "P(losing month):  {:.1}%",
+            pct(self.losing_month_probability)
+        )
+    }
+}
+
+/// A signal rejected in a market before it was entered
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rejection {
+    /// When the signal was rejected
+    pub at: DateTime<Utc>,
+    /// Filter code or limit label, e.g. `edge_too_small`; see
+    /// [`reason_code`](crate::signal::reason_code)
+    pub reason: String,
+    /// The observed value and threshold behind the rejection, or null
+    #[serde(default)]
+    pub context: serde_json::Value,
+}
+
+/// The signal behind a trade, as it was at entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryFeatures {
+    /// Signal identifier
+    pub signal_id: String,
+    /// Why the signal fired
+    pub reason: String,
+    /// Model price of the traded side
+    pub fair_value: Decimal,
+    /// Book price of the traded side
+    pub market_price: Decimal,
+    /// Fair value less book price
+    pub lag: Decimal,
+    /// Edge after costs
+    pub edge: Decimal,
+    /// Spot move over the momentum window in the trade's favour
+    pub momentum_move: Option<Decimal>,
+    /// Fraction of that move given back
+    pub retrace: Option<Decimal>,
+    /// Signal confidence
+    pub confidence: Decimal,
+    /// Seconds from entry to market close
+    pub secs_to_close: i64,
+    /// Signals rejected in the market earlier in its window
+    pub rejections: Vec<Rejection>,
+    /// Feature flags evaluated for the signal, by name
+    #[serde(default)]
+    pub flags: BTreeMap<String, FlagDecision>,
+}
+
+impl EntryFeatures {
+    /// Features of `signal` traded at `now`, after `rejections`
+    pub fn new(signal: &Signal, now: DateTime<Utc>, rejections: Vec<Rejection>) -> Self {
+        Self {
+            signal_id: signal.id.to_string(),
+            reason: format!("{:?}", signal.reason),
+            fair_value: signal.fair_value,
+            market_price: signal.market_price,
+            lag: signal.raw_edge,
+            edge: signal.adjusted_edge,
+            momentum_move: signal.momentum_move,
+            retrace: signal.retrace,
+            confidence: signal.confidence,
+            secs_to_close: (signal.market.close_time - now).num_seconds(),
+            rejections,
+            flags: signal.flags.clone(),
+        }
+    }
+
+    /// What the signal claimed `position` was worth when it filled: the
+    /// model price of the side less the fill price, times the shares, less
+    /// the entry fee
+    pub fn expected_value(&self, position: &Position) -> Decimal {
+        round_usd((self.fair_value - position.entry_price) * position.size - position.entry_fee)
+    }
+}
+
+/// One closed trade of the trade tape
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TapeRow {
+    /// Position identifier
+    pub position_id: String,
+    /// Market condition ID
+    pub market_id: String,
+    /// Market asset
+    pub asset: String,
+    /// Strategy that traded
+    pub strategy: String,
+    /// Side bought
+    pub side: Side,
+    /// Fill time
+    pub entry_time: DateTime<Utc>,
+    /// Settlement time
+    pub exit_time: DateTime<Utc>,
+    /// Shares
+    pub size: Decimal,
+    /// Fill price
+    pub entry_price: Decimal,
+    /// Settlement payout per share
+    pub exit_price: Decimal,
+    /// Fees paid
+    pub fees: Decimal,
+    /// P&L after fees
+    pub realized_pnl: Decimal,
+    /// What the signal claimed the trade was worth; `None` without one
+    #[serde(default)]
+    pub expected_value_usd: Option<Decimal>,
+    /// The signal behind the trade; `None` for a recovered order
+    pub entry: Option<EntryFeatures>,
+}
+
+impl TapeRow {
+    /// Row for `closed`, entered on `entry`
+    pub fn new(closed: &ClosedPosition, entry: Option<EntryFeatures>) -> Self {
+        let position = &closed.position;
+        Self {
+            position_id: position.id.to_string(),
+            market_id: position.market.condition_id.clone(),
+            asset: position.market.asset.clone(),
+            strategy: position.strategy.clone(),
+            side: position.side,
+            entry_time: position.entry_time,
+            exit_time: closed.exit_time,
+            size: position.size,
+            entry_price: position.entry_price,
+            exit_price: closed.exit_price,
+            fees: closed.fees,
+            realized_pnl: closed.realized_pnl,
+            expected_value_usd: entry.as_ref().map(|e| e.expected_value(position)),
+            entry,
+        }
+    }
+}
+
+/// Trade tape Parquet schema, version [`TRADE_TAPE_VERSION`]
+pub fn trade_tape_schema() -> Schema {
+    let text = |name: &str, nullable: bool| Field::new(name, DataType::Utf8, nullable);
+    let time = || DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    Schema::new(vec![
+        text("position_id", false),
+        text("signal_id", true),
+        text("market_id", false),
+        text("asset", false),
+        text("strategy", false),
+        text("side", false),
+        Field::new("entry_time", time(), false),
+        Field::new("exit_time", time(), false),
+        text("size", false),
+        text("entry_price", false),
+        text("exit_price", false),
+        text("fees", false),
+        text("realized_pnl", false),
+        text("signal_reason", true),
+        text("fair_value", true),
+        text("market_price", true),
+        text("lag", true),
+        text("edge", true),
+        text("momentum_move", true),
+        text("retrace", true),
+        text("confidence", true),
+        Field::new("secs_to_close", DataType::Int64, true),
+        Field::new("prior_rejections", DataType::UInt32, false),
+        Field::new(
+            "rejection_times",
+            DataType::List(Arc::new(Field::new_list_field(time(), true))),
+            false,
+        ),
+        Field::new(
+            "rejection_reasons",
+            DataType::List(Arc::new(Field::new_list_field(DataType::Utf8, true))),
+            false,
+        ),
+        text("expected_value_usd", true),
+        Field::new(
+            "rejection_contexts",
+            DataType::List(Arc::new(Field::new_list_field(DataType::Utf8, true))),
+            false,
+        ),
+        text("flags", true),
+    ])
+}
+
+/// Trade tape rows as a batch in [`trade_tape_schema`]
+pub fn trade_tape_batch(rows: &[TapeRow]) -> anyhow::Result<RecordBatch> {
+    let entry = |f: fn(&EntryFeatures) -> Option<Decimal>| {
+        decimal_column(rows, move |r| r.entry.as_ref().and_then(f))
+    };
+    let side = |r: &TapeRow| match r.side {
+        Side::Yes => "yes",
+        Side::No => "no",
+    };
+    let mut times = ListBuilder::new(TimestampMicrosecondBuilder::new().with_timezone("UTC"));
+    let mut reasons = ListBuilder::new(StringBuilder::new());
+    let mut contexts = ListBuilder::new(StringBuilder::new());
+    for rejection in rows
+        .iter()
+        .map(|r| r.entry.as_ref().map(|e| &e.rejections[..]))
+    {
+        for r in rejection.unwrap_or_default() {
+            times.values().append_value(r.at.timestamp_micros());
+            reasons.values().append_value(&r.reason);
+            match &r.context {
+                serde_json::Value::Null => contexts.values().append_null(),
+                context => contexts.values().append_value(context.to_string()),
+            }
+        }
+        times.append(true);
+        reasons.append(true);
+        contexts.append(true);
+    }
+    let columns: Vec<ArrayRef> = vec![
+        str_column(rows, |r| &r.position_id),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.entry.as_ref().map(|e| &e.signal_id)),
+        )),
+        str_column(rows, |r| &r.market_id),
+        str_column(rows, |r| &r.asset),
+        str_column(rows, |r| &r.strategy),
+        str_column(rows, side),
+        timestamp_column(rows, |r| r.entry_time),
+        timestamp_column(rows, |r| r.exit_time),
+        decimal_column(rows, |r| Some(r.size)),
+        decimal_column(rows, |r| Some(r.entry_price)),
+        decimal_column(rows, |r| Some(r.exit_price)),
+        decimal_column(rows, |r| Some(r.fees)),
+        decimal_column(rows, |r| Some(r.realized_pnl)),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.entry.as_ref().map(|e| &e.reason)),
+        )),
+        entry(|e| Some(e.fair_value)),
+        entry(|e| Some(e.market_price)),
+        entry(|e| Some(e.lag)),
+        entry(|e| Some(e.edge)),
+        entry(|e| e.momentum_move),
+        entry(|e| e.retrace),
+        entry(|e| Some(e.confidence)),
+        Arc::new(Int64Array::from_iter(
+            rows.iter()
+                .map(|r| r.entry.as_ref().map(|e| e.secs_to_close)),
+        )),
+        Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| {
+            r.entry.as_ref().map_or(0, |e| e.rejections.len() as u32)
+        }))),
+        Arc::new(times.finish()),
+        Arc::new(reasons.finish()),
+        decimal_column(rows, |r| r.expected_value_usd),
+        Arc::new(contexts.finish()),
+        Arc::new(StringArray::from_iter(
+            rows.iter()
+                .map(|r| {
+                    r.entry
+                        .as_ref()
+                        .filter(|e| !e.flags.is_empty())
+                        .map(|e| serde_json::to_string(&e.flags))
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+    ];
+    Ok(RecordBatch::try_new(
+        Arc::new(trade_tape_schema()),
+        columns,
+    )?)
+}
+
+/// Write `rows` to `path` as a trade tape
+pub fn write_trade_tape(path: &Path, rows: &[TapeRow]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let batch = trade_tape_batch(rows)?;
+    let props = writer_properties(fingerprint::active());
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(props))?;
+    writer.append_key_value_metadata(KeyValue::new(
+        TRADE_TAPE_VERSION_KEY.to_string(),
+        TRADE_TAPE_VERSION.to_string(),
+    ));
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Read back a trade tape written by [`write_trade_tape`]
+pub fn read_trade_tape(path: &Path) -> anyhow::Result<Vec<TapeRow>> {
+    let mut rows = vec![];
+    for batch in read_batches(path)? {
+        rows.extend(trade_tape_rows(&batch)?);
+    }
+    Ok(rows)
+}
+
+/// Trade tape rows of a batch in [`trade_tape_schema`]
+fn trade_tape_rows(batch: &RecordBatch) -> anyhow::Result<Vec<TapeRow>> {
+    use std::str::FromStr;
+
+    fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> anyhow::Result<&'a T> {
+        batch
+            .column_by_name(name)
+            .and_then(|c| c.as_any().downcast_ref::<T>())
+            .ok_or_else(|| anyhow::anyhow!("Invalid {} column", name))
+    }
+    let strings = |name| column::<StringArray>(batch, name);
+    let times = |name| column::<TimestampMicrosecondArray>(batch, name);
+    let time = |micros: i64| {
+        DateTime::from_timestamp_micros(micros).ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))
+    };
+    let decimal = |column: &StringArray, row: usize| -> anyhow::Result<Option<Decimal>> {
+        Ok(match column.is_null(row) {
+            true => None,
+            false => Some(Decimal::from_str(column.value(row))?),
+        })
+    };
+    let required = |column: &StringArray, row: usize| -> anyhow::Result<Decimal> {
+        Ok(Decimal::from_str(column.value(row))?)
+    };
+
+    let ids = strings("position_id")?;
+    let signal_ids = strings("signal_id")?;
+    let market_ids = strings("market_id")?;
+    let assets = strings("asset")?;
+    let strategies = strings("strategy")?;
+    let sides = strings("side")?;
+    let entry_times = times("entry_time")?;
+    let exit_times = times("exit_time")?;
+    let sizes = strings("size")?;
+    let entry_prices = strings("entry_price")?;
+    let exit_prices = strings("exit_price")?;
+    let fees = strings("fees")?;
+    let pnls = strings("realized_pnl")?;
+    let reasons = strings("signal_reason")?;
+    let fair_values = strings("fair_value")?;
+    let market_prices = strings("market_price")?;
+    let lags = strings("lag")?;
+    let edges = strings("edge")?;
+    let moves = strings("momentum_move")?;
+    let retraces = strings("retrace")?;
+    let confidences = strings("confidence")?;
+    let secs_to_close = column::<Int64Array>(batch, "secs_to_close")?;
+    let rejection_times = column::<ListArray>(batch, "rejection_times")?;
+    let rejection_reasons = column::<ListArray>(batch, "rejection_reasons")?;
+    // Version 1 tapes predate it
+    let expected_values = strings("expected_value_usd").ok();
+    // Version 2 tapes predate it
+    let rejection_contexts = column::<ListArray>(batch, "rejection_contexts").ok();
+    // Version 3 tapes predate it
+    let flags = strings("flags").ok();
+
+    let mut rows = Vec::with_capacity(batch.num_rows());
+    for row in 0..batch.num_rows() {
+        let side = match sides.value(row) {
+            "yes" => Side::Yes,
+            "no" => Side::No,
+            other => anyhow::bail!("unknown side: {}", other),
+        };
+        let entry = match signal_ids.is_null(row) {
+            true => None,
+            false => {
+                let at = rejection_times.value(row);
+                let at = at
+                    .as_any()
+                    .downcast_ref::<TimestampMicrosecondArray>()
+                    .ok_or_else(|| anyhow::anyhow!("Invalid rejection_times column"))?;
+                let why = rejection_reasons.value(row);
+                let why = why
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .ok_or_else(|| anyhow::anyhow!("Invalid rejection_reasons column"))?;
+                let context = rejection_contexts.map(|c| c.value(row));
+                let context = context
+                    .as_ref()
+                    .map(|c| {
+                        c.as_any()
+                            .downcast_ref::<StringArray>()
+                            .ok_or_else(|| anyhow::anyhow!("Invalid rejection_contexts column"))
+                    })
+                    .transpose()?;
+                let rejections = (0..at.len())
+                    .map(|i| {
+                        let reason = why.value(i);
+                        Ok(Rejection {
+                            at: time(at.value(i))?,
+                            reason: reason_code(reason).unwrap_or(reason).to_string(),
+                            context: match context {
+                                Some(c) if !c.is_null(i) => serde_json::from_str(c.value(i))?,
+                                _ => serde_json::Value::Null,
+                            },
+                        })
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Some(EntryFeatures {
+                    signal_id: signal_ids.value(row).to_string(),
+                    reason: reasons.value(row).to_string(),
+                    fair_value: required(fair_values, row)?,
+                    market_price: required(market_prices, row)?,
+                    lag: required(lags, row)?,
+                    edge: required(edges, row)?,
+                    momentum_move: decimal(moves, row)?,
+                    retrace: decimal(retraces, row)?,
+                    confidence: required(confidences, row)?,
+                    secs_to_close: secs_to_close.value(row),
+                    rejections,
+                    flags: match flags {
+                        Some(c) if !c.is_null(row) => serde_json::from_str(c.value(row))?,
+                        _ => BTreeMap::new(),
+                    },
+                })
+            }
+        };
+        rows.push(TapeRow {
+            position_id: ids.value(row).to_string(),
+            market_id: market_ids.value(row).to_string(),
+            asset: assets.value(row).to_string(),
+            strategy: strategies.value(row).to_string(),
+            side,
+            entry_time: time(entry_times.value(row))?,
+            exit_time: time(exit_times.value(row))?,
+            size: required(sizes, row)?,
+            entry_price: required(entry_prices, row)?,
+            exit_price: required(exit_prices, row)?,
+            fees: required(fees, row)?,
+            realized_pnl: required(pnls, row)?,
+            expected_value_usd: match expected_values {
+                Some(column) => decimal(column, row)?,
+                None => None,
+            },
+            entry,
+        });
+    }
+    Ok(rows)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -105,6 +943,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_backtest_summary_clone() {
         let mut summary = BacktestSummary::default();
         summary.total_pnl = dec!(100);
@@ -135,10 +974,32 @@ mod tests {
             total_trades: 50,
             avg_trade_duration_secs: 300,
             avg_edge: dec!(0.02),
+            events_processed: 1_000,
+            duplicates_removed: 12,
+            conflicts_resolved: 1,
+            approximated_books: 0,
+            low_fidelity: false,
+            scenario: vec![],
+            aligned: None,
+            skipped_files: vec![],
+            excluded_secs: 0,
+            snapshots: vec![],
+            loaded_bytes: Some(64 * 1024 * 1024),
+            config_hash: Some("abc123".to_string()),
+            bootstrap: None,
         };
 
         let table = summary.format_table();
         assert!(table.contains("BACKTEST RESULTS"));
+        assert!(table.contains("Loaded Events:    64.0 MiB"));
+        assert!(table.contains("Config Hash:      abc123"));
+        assert!(table.contains("Duplicates:       12 removed, 1 conflicts resolved"));
+        assert!(table.contains("Fidelity:         captured books"));
+        assert!(table.contains("Data Gaps:        none"));
+        assert!(table.contains("Scenario:         none"));
+        assert!(table.contains(
+            "Snapshot:         0 sealed files (0 from before manifests), 0 being written left out"
+        ));
         assert!(table.contains("Net P&L"));
         assert!(table.contains("Sharpe Ratio"));
         assert!(table.contains("Total Trades"));
@@ -165,4 +1026,276 @@ mod tests {
         assert_eq!(result.trades_path, cloned.trades_path);
         assert_eq!(result.equity_path, cloned.equity_path);
     }
+
+    const GOLDEN_SCHEMA: &str = "tests/golden/backtest/trade_tape_schema.txt";
+    const GOLDEN_ROWS: &str = "tests/golden/backtest/trade_tape_rows.json";
+
+    fn bless(path: &str, rendered: &str) {
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::create_dir_all(Path::new(path).parent().unwrap()).unwrap();
+            std::fs::write(path, rendered).unwrap();
+        }
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            rendered,
+            "{} is stale; rerun this test with BLESS=1",
+            path
+        );
+    }
+
+    fn ts(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_735_689_600 + secs, 0).unwrap()
+    }
+
+    fn sample_rows() -> Vec<TapeRow> {
+        let traded = TapeRow {
+            position_id: "00000000-0000-0000-0000-000000000001".to_string(),
+            market_id: "0xabc".to_string(),
+            asset: "BTC".to_string(),
+            strategy: "default".to_string(),
+            side: Side::Yes,
+            entry_time: ts(120),
+            exit_time: ts(900),
+            size: dec!(20),
+            entry_price: dec!(0.45),
+            exit_price: dec!(1),
+            fees: dec!(0.045),
+            realized_pnl: dec!(10.955),
+            // (0.58 - 0.45) * 20 less a 0.045 entry fee
+            expected_value_usd: Some(dec!(2.555)),
+            entry: Some(EntryFeatures {
+                signal_id: "00000000-0000-0000-0000-00000000000a".to_string(),
+                reason: "LagDetected".to_string(),
+                fair_value: dec!(0.58),
+                market_price: dec!(0.45),
+                lag: dec!(0.13),
+                edge: dec!(0.11),
+                momentum_move: Some(dec!(42.5)),
+                retrace: Some(dec!(0.2)),
+                confidence: dec!(0.8),
+                secs_to_close: 780,
+                rejections: vec![
+                    Rejection {
+                        at: ts(60),
+                        reason: "edge_too_small".to_string(),
+                        context: serde_json::json!({"edge": "0.003", "min": "0.005"}),
+                    },
+                    Rejection {
+                        at: ts(90),
+                        reason: "rate_cap".to_string(),
+                        context: serde_json::Value::Null,
+                    },
+                ],
+                flags: BTreeMap::from([(
+                    "exit_ladder".to_string(),
+                    FlagDecision {
+                        on: true,
+                        rollout: Some(dec!(25)),
+                    },
+                )]),
+            }),
+        };
+        // A recovered order has no signal behind it
+        let recovered = TapeRow {
+            position_id: "00000000-0000-0000-0000-000000000002".to_string(),
+            side: Side::No,
+            realized_pnl: dec!(-9.045),
+            exit_price: dec!(0),
+            expected_value_usd: None,
+            entry: None,
+            ..traded.clone()
+        };
+        vec![traded, recovered]
+    }
+
+    #[test]
+    fn test_golden_trade_tape_schema() {
+        let mut rendered = format!("version {}\n", TRADE_TAPE_VERSION);
+        for field in trade_tape_schema().fields() {
+            let nullable = if field.is_nullable() { " null" } else { "" };
+            rendered += &format!("{} {}{}\n", field.name(), field.data_type(), nullable);
+        }
+        bless(GOLDEN_SCHEMA, &rendered);
+    }
+
+    #[test]
+    fn test_golden_trade_tape_rows() {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TRADE_TAPE_FILE);
+        write_trade_tape(&path, &sample_rows()).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        let version = reader
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .and_then(|kv| kv.iter().find(|kv| kv.key == TRADE_TAPE_VERSION_KEY))
+            .and_then(|kv| kv.value.clone());
+        assert_eq!(version, Some(TRADE_TAPE_VERSION.to_string()));
+        let batches: Vec<RecordBatch> = reader.build().unwrap().map(|b| b.unwrap()).collect();
+        assert_eq!(batches[0].schema().fields(), trade_tape_schema().fields());
+        assert_eq!(read_trade_tape(&path).unwrap(), sample_rows());
+
+        // Reasons written before the codes read back as codes
+        let mut legacy = sample_rows();
+        let entry = legacy[0].entry.as_mut().unwrap();
+        entry.rejections[0].reason = "EdgeTooSmall(0.003)".to_string();
+        let legacy_path = dir.path().join("legacy.parquet");
+        write_trade_tape(&legacy_path, &legacy).unwrap();
+        assert_eq!(read_trade_tape(&legacy_path).unwrap(), sample_rows());
+
+        // The JSON writer cannot name the UTC zone without chrono-tz, so
+        // times are rendered as epoch microseconds
+        let batch = &batches[0];
+        let micros = |t: &DataType| match t {
+            DataType::Timestamp(..) => DataType::Int64,
+            DataType::List(_) if t.to_string().contains("Timestamp") => {
+                DataType::List(Arc::new(Field::new_list_field(DataType::Int64, true)))
+            }
+            t => t.clone(),
+        };
+        let columns: Vec<ArrayRef> = batch
+            .columns()
+            .iter()
+            .map(|c| arrow::compute::cast(c, &micros(c.data_type())).unwrap())
+            .collect();
+        let rendered = RecordBatch::try_from_iter(
+            batch
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().clone())
+                .zip(columns),
+        )
+        .unwrap();
+        let mut writer = arrow::json::ArrayWriter::new(Vec::new());
+        writer.write(&rendered).unwrap();
+        writer.finish().unwrap();
+        let rows: serde_json::Value = serde_json::from_slice(&writer.into_inner()).unwrap();
+        bless(
+            GOLDEN_ROWS,
+            &format!("{}\n", serde_json::to_string_pretty(&rows).unwrap()),
+        );
+    }
+
+    /// A closed trade of window `market` entered `secs` after the epoch
+    fn tape_trade(market: &str, secs: i64, pnl: Decimal) -> TapeRow {
+        let at = DateTime::from_timestamp(1_767_225_600 + secs, 0).unwrap();
+        TapeRow {
+            position_id: format!("{}-{}", market, secs),
+            market_id: market.to_string(),
+            asset: "BTC".to_string(),
+            strategy: "default".to_string(),
+            side: Side::Yes,
+            entry_time: at,
+            exit_time: at + Duration::minutes(5),
+            size: dec!(10),
+            entry_price: dec!(0.5),
+            exit_price: if pnl > Decimal::ZERO {
+                dec!(1)
+            } else {
+                dec!(0)
+            },
+            fees: dec!(0),
+            realized_pnl: pnl,
+            expected_value_usd: None,
+            entry: None,
+        }
+    }
+
+    /// `days` days of four windows a day, each a win of `win` then a loss of 1
+    fn daily_tape(days: i64, win: impl Fn(i64) -> Decimal) -> Vec<TapeRow> {
+        let mut tape = vec![];
+        for day in 0..days {
+            for window in 0..4 {
+                let market = format!("0x{}-{}", day, window);
+                let start = day * 86_400 + window * 3_600;
+                tape.push(tape_trade(&market, start, win(day)));
+                tape.push(tape_trade(&market, start + 60, dec!(-1)));
+            }
+        }
+        tape
+    }
+
+    #[test]
+    fn test_percentiles_take_the_nearest_rank() {
+        let samples: Vec<Decimal> = (0..=100).rev().map(Decimal::from).collect();
+        let p = Percentiles::new(dec!(42), samples).unwrap();
+        assert_eq!(
+            (p.point, p.p5, p.p50, p.p95),
+            (dec!(42), dec!(5), dec!(50), dec!(95))
+        );
+        assert!(Percentiles::new(dec!(0), vec![]).is_none());
+
+        let stats = RunStats::new(&[dec!(2), dec!(-3), dec!(1), dec!(-1), dec!(4)]);
+        assert_eq!(stats.net_pnl, dec!(3));
+        assert_eq!(stats.win_rate, Some(dec!(0.6)));
+        assert_eq!(stats.max_drawdown, dec!(3));
+    }
+
+    #[test]
+    fn test_identical_days_resample_to_the_point_estimate() {
+        let tape = daily_tape(10, |_| dec!(3));
+        let bootstrap = BlockBootstrap::new(&tape, &BlockBootstrapConfig::default()).unwrap();
+        assert_eq!((bootstrap.blocks, bootstrap.windows), (10, 40));
+        // Every day is +8 on four wins of 3 and four losses of 1
+        for p in [
+            bootstrap.net_pnl.p5,
+            bootstrap.net_pnl.p50,
+            bootstrap.net_pnl.p95,
+        ] {
+            assert_eq!(p, dec!(80));
+        }
+        assert_eq!(bootstrap.net_pnl.point, dec!(80));
+        assert_eq!(bootstrap.win_rate.p5, dec!(0.5));
+        assert_eq!(bootstrap.win_rate.p95, dec!(0.5));
+        assert_eq!(bootstrap.max_drawdown.p95, dec!(1));
+        assert_eq!(bootstrap.losing_month_probability, dec!(0));
+        // A single day spans one block, too few to resample
+        assert!(BlockBootstrap::new(&daily_tape(1, |_| dec!(3)), &Default::default()).is_none());
+    }
+
+    #[test]
+    fn test_mixed_days_spread_around_the_mean_deterministically() {
+        // Good days net +8, bad days -8, alternating: mean zero per day
+        let tape = daily_tape(20, |day| if day % 2 == 0 { dec!(3) } else { dec!(-1) });
+        let config = BlockBootstrapConfig::default();
+        let bootstrap = BlockBootstrap::new(&tape, &config).unwrap();
+        assert_eq!(bootstrap.net_pnl.point, dec!(0));
+        let net = bootstrap.net_pnl;
+        assert!(net.p5 < dec!(-16) && net.p95 > dec!(16), "{:?}", net);
+        assert!(net.p50.abs() <= dec!(16), "{:?}", net);
+        // A 30-day month of fair coin days loses a little under half the time
+        let losing = bootstrap.losing_month_probability;
+        assert!(losing > dec!(0.35) && losing < dec!(0.5), "{}", losing);
+        // Drawdowns are at least a bad day deep
+        assert!(bootstrap.max_drawdown.p5 >= dec!(8));
+
+        assert_eq!(BlockBootstrap::new(&tape, &config), Some(bootstrap.clone()));
+        let reseeded = BlockBootstrapConfig { seed: 7, ..config };
+        assert_ne!(
+            BlockBootstrap::new(&tape, &reseeded),
+            Some(bootstrap.clone())
+        );
+
+        // Blocks without trades count as flat days, not as missing ones
+        let mut sparse = daily_tape(1, |_| dec!(3));
+        sparse.extend(daily_tape(1, |_| dec!(3)).into_iter().map(|mut row| {
+            row.market_id.push_str("-late");
+            row.entry_time += Duration::days(9);
+            row
+        }));
+        let bootstrap = BlockBootstrap::new(&sparse, &config).unwrap();
+        assert_eq!(bootstrap.blocks, 10);
+        assert_eq!(bootstrap.net_pnl.p5, dec!(0));
+
+        let table = BacktestSummary {
+            bootstrap: Some(bootstrap),
+            ..Default::default()
+        }
+        .format_table();
+        assert!(table.contains("P(losing month):"), "{}", table);
+    }
 }
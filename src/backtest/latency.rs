@@ -0,0 +1,1268 @@
+//! Latency sensitivity analysis
+//!
+//! Loads the event stream once and replays it at several order latencies,
+//! changing nothing else. Decisions are made on the book the bot has seen
+//! (optionally delayed by a WS staleness offset); orders are filled against
+//! the book as of `decision_time + latency`, so an edge that disappears while
+//! the order is in flight is missed.
+//!
+//! Fills are not filtered on spot momentum, but each one is tagged with
+//! whether the momentum reversion filter would have skipped it, so the
+//! filter's saved losers and lost winners can be read off the results.
+//!
+//! With book shock exits on, a held fill is sold into the book as of
+//! `shock_time + latency` when its supporting depth evaporates. Each exit
+//! is compared with holding to settlement: P&L it saved on a loser, or the
+//! whipsaw it cost on a winner.
+//!
+//! With an exit ladder, each rung sells its fraction of a fill's remaining
+//! shares into the book as of `rung_time + latency`, and each sale is
+//! scored against holding those shares the same way. [`LatencySweep::compare_exits`]
+//! replays every point with and without the ladder for a side-by-side view.
+//!
+//! With an allocation, at most `max_positions` fills are held at once, and
+//! signals competing for the slots left are ranked by the same
+//! [`Allocator`] the engine uses, so the sweep sees the engine's contention.
+//!
+//! With execution camouflage, each order draws its delay, size jitter and
+//! split from the engine's [`Camouflage`] model, seeded per replay so every
+//! point sees the same draws. The delay adds to the latency, and the clips
+//! fill one after the other against the same top of book.
+//!
+//! Every settled fill is a row of the point's trade tape, as the engine
+//! writes it: one [`TapeRow`] per clip, carrying the signal it was sent on.
+
+use super::{BacktestConfig, BacktestEvent, BookTimeline, EntryFeatures, EventStream, TapeRow};
+use crate::data::features::resolution;
+use crate::engine::{ExitLadder, ExitLadderConfig};
+use crate::execution::{Camouflage, CamouflageConfig};
+use crate::market::Market;
+use crate::model::{FairValueModel, VolatilityEstimator};
+use crate::orderbook::{MarketBooks, OrderBook};
+use crate::precision::{round_size, round_usd};
+use crate::risk::{AllocationConfig, Allocator, Candidate, DEFAULT_STRATEGY};
+use crate::signal::{
+    BookShockConfig, BookShockDetector, MomentumDetector, Side, Signal, SignalDetector,
+    SignalOutcomeTracker, DEFAULT_MAX_MOMENTUM_RETRACE, DEFAULT_MOMENTUM_WINDOW_SECS,
+};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Outcome of replaying the stream at one latency
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyPointResult {
+    /// Simulated order latency in ms
+    pub latency_ms: u64,
+    /// Book staleness offset in ms
+    pub book_staleness_ms: u64,
+    /// Orders the strategy decided to send
+    pub decisions: usize,
+    /// Orders that filled on arrival
+    pub fills: usize,
+    /// fills / decisions
+    pub fill_rate: Decimal,
+    /// Settled P&L after fees
+    pub net_pnl: Decimal,
+    /// Mean of fair value minus fill price over fills
+    pub avg_realized_edge: Decimal,
+    /// Fills in markets that never closed (excluded from P&L)
+    pub unsettled_fills: usize,
+    /// Settled fills the reversion filter would have skipped that won
+    pub reverting_winners: usize,
+    /// Settled fills the reversion filter would have skipped that lost
+    pub reverting_losers: usize,
+    /// Net P&L of the fills the reversion filter would have skipped
+    pub reverting_pnl: Decimal,
+    /// Settled fills sold early on a book shock
+    pub shock_exits: usize,
+    /// P&L those exits gained over holding, on fills that would have lost
+    pub shock_saved: Decimal,
+    /// P&L those exits gave up against holding, on fills that would have won
+    pub shock_whipsaw: Decimal,
+    /// Partial sales of settled fills by the exit ladder
+    pub ladder_exits: usize,
+    /// P&L those sales gained over holding the shares sold
+    pub ladder_saved: Decimal,
+    /// P&L those sales gave up against holding the shares sold
+    pub ladder_whipsaw: Decimal,
+    /// Signals outranked for a position slot
+    pub outranked: usize,
+    /// Settled fills as trade tape rows, in settlement order
+    #[serde(skip)]
+    pub trades: Vec<TapeRow>,
+}
+
+/// One latency point replayed holding to resolution and with the exit ladder
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitPolicyComparison {
+    pub hold: LatencyPointResult,
+    pub ladder: LatencyPointResult,
+}
+
+struct SimFill {
+    /// Key of the fill in the exit ladder
+    id: Uuid,
+    at: DateTime<Utc>,
+    /// The signal the order was sent on
+    entry: EntryFeatures,
+    side: Side,
+    price: Decimal,
+    size: Decimal,
+    reverting: bool,
+    /// Price and shares of each exit ladder sale
+    sold: Vec<(Decimal, Decimal)>,
+    /// Price the rest sold at on a book shock
+    exit: Option<Decimal>,
+}
+
+impl SimFill {
+    /// Shares not yet sold by the ladder
+    fn remaining(&self) -> Decimal {
+        self.size - self.sold.iter().map(|(_, size)| size).sum::<Decimal>()
+    }
+}
+
+/// Replays one loaded event stream at several latencies
+pub struct LatencySweep<M: FairValueModel> {
+    config: BacktestConfig,
+    detector: SignalDetector<M>,
+    events: Vec<(DateTime<Utc>, BacktestEvent)>,
+    timeline: BookTimeline,
+    order_size: Decimal,
+    vol_window: Duration,
+    momentum_window: Duration,
+    max_retrace: Decimal,
+    book_shock: BookShockConfig,
+    /// No-trade window before close, where shocks are ignored
+    shock_quiet: Duration,
+    exit_ladder: ExitLadderConfig,
+    /// Ranking of competing signals and the most fills held at once
+    allocation: Option<(AllocationConfig, usize)>,
+    /// Camouflage of each order and the seed of its draws
+    camouflage: (CamouflageConfig, u64),
+}
+
+impl<M: FairValueModel> LatencySweep<M> {
+    /// Create a sweep over already-loaded events
+    pub fn new(
+        model: M,
+        config: BacktestConfig,
+        events: Vec<(DateTime<Utc>, BacktestEvent)>,
+    ) -> Self {
+        let timeline = BookTimeline::from_events(&events);
+        Self {
+            detector: SignalDetector::new(model, config.fee_rate, Decimal::ZERO),
+            config,
+            events,
+            timeline,
+            order_size: dec!(10),
+            vol_window: Duration::minutes(5),
+            momentum_window: Duration::seconds(DEFAULT_MOMENTUM_WINDOW_SECS as i64),
+            max_retrace: DEFAULT_MAX_MOMENTUM_RETRACE,
+            book_shock: BookShockConfig::default(),
+            shock_quiet: Duration::minutes(1),
+            exit_ladder: ExitLadderConfig::default(),
+            allocation: None,
+            camouflage: (CamouflageConfig::default(), 0),
+        }
+    }
+
+    /// Load the configured data directory once
+    pub fn load(model: M, config: BacktestConfig) -> anyhow::Result<Self> {
+        let mut stream =
+            EventStream::new(config.data_dir.clone(), config.start_time, config.end_time)
+                .with_merge_dirs(config.merge_dirs.clone())
+                .with_price_history(config.price_history)
+                .with_scenario(config.scenario.clone())
+                .with_alignment(config.align)
+                .with_corrupt_files(config.corrupt_files)
+                .with_include_current(config.include_current);
+        stream.load()?;
+        let events = stream.collect();
+        Ok(Self::new(model, config, events))
+    }
+
+    /// Shares per order
+    pub fn with_order_size(mut self, size: Decimal) -> Self {
+        self.order_size = size;
+        self
+    }
+
+    /// Lookback for the realized volatility fed to the detector
+    pub fn with_vol_window(mut self, window: Duration) -> Self {
+        self.vol_window = window;
+        self
+    }
+
+    /// Sell held fills on book shocks, except within `quiet_before_close`
+    /// of the close
+    pub fn with_book_shock(
+        mut self,
+        config: BookShockConfig,
+        quiet_before_close: Duration,
+    ) -> Self {
+        self.book_shock = config;
+        self.shock_quiet = quiet_before_close;
+        self
+    }
+
+    /// Sell held fills in parts near the close, as `config`'s rungs direct
+    pub fn with_exit_ladder(mut self, config: ExitLadderConfig) -> Self {
+        self.exit_ladder = config;
+        self
+    }
+
+    /// Momentum window and retrace threshold used to tag reverting fills
+    pub fn with_momentum(mut self, window: Duration, max_retrace: Decimal) -> Self {
+        self.momentum_window = window;
+        self.max_retrace = max_retrace;
+        self
+    }
+
+    /// Hold at most `max_positions` fills at once, ranking signals that
+    /// compete for the slots left as the engine does
+    pub fn with_allocation(mut self, config: AllocationConfig, max_positions: usize) -> Self {
+        self.allocation = Some((config, max_positions));
+        self
+    }
+
+    /// Jitter each order's timing and size as `config` directs, drawing
+    /// from `seed`; nothing changes while it is disabled
+    pub fn with_camouflage(mut self, config: CamouflageConfig, seed: u64) -> Self {
+        self.camouflage = (config, seed);
+        self
+    }
+
+    /// Number of loaded events
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Replay at every latency, in order
+    pub fn run(&self, latencies_ms: &[u64]) -> Vec<LatencyPointResult> {
+        latencies_ms.iter().map(|l| self.run_point(*l)).collect()
+    }
+
+    /// Replay every latency holding to resolution and again with the exit
+    /// ladder enabled, whatever its configured switch
+    pub fn compare_exits(&self, latencies_ms: &[u64]) -> Vec<ExitPolicyComparison> {
+        let mut hold = self.exit_ladder.clone();
+        hold.enabled = false;
+        let mut ladder = self.exit_ladder.clone();
+        ladder.enabled = true;
+        latencies_ms
+            .iter()
+            .map(|l| ExitPolicyComparison {
+                hold: self.replay(*l, &hold, &mut |_, _| {}),
+                ladder: self.replay(*l, &ladder, &mut |_, _| {}),
+            })
+            .collect()
+    }
+
+    /// Replay the stream with orders delayed by `latency_ms`
+    pub fn run_point(&self, latency_ms: u64) -> LatencyPointResult {
+        self.replay(latency_ms, &self.exit_ladder, &mut |_, _| {})
+    }
+
+    /// [`Self::run_point`], calling `progress` with each event's time and
+    /// the fills so far as the replay reaches it
+    pub fn run_point_with_progress(
+        &self,
+        latency_ms: u64,
+        mut progress: impl FnMut(DateTime<Utc>, usize),
+    ) -> LatencyPointResult {
+        self.replay(latency_ms, &self.exit_ladder, &mut progress)
+    }
+
+    fn replay(
+        &self,
+        latency_ms: u64,
+        exit_ladder: &ExitLadderConfig,
+        progress: &mut dyn FnMut(DateTime<Utc>, usize),
+    ) -> LatencyPointResult {
+        let latency = Duration::milliseconds(latency_ms as i64);
+        let staleness = Duration::milliseconds(self.config.book_staleness_ms as i64);
+
+        let mut volatility = VolatilityEstimator::new(self.vol_window);
+        let mut momentum = MomentumDetector::new(self.momentum_window);
+        let mut spot = None;
+        let mut markets: HashMap<&str, &Market> = HashMap::new();
+        let mut entered: HashSet<&str> = HashSet::new();
+        let mut open: HashMap<&str, Vec<SimFill>> = HashMap::new();
+        let mut shocks = BookShockDetector::new(self.book_shock.clone(), self.shock_quiet);
+        let mut ladder = ExitLadder::new(exit_ladder);
+        // End of the data gap being replayed; nothing is entered before it
+        let mut gap_until = None;
+        let mut allocator = self
+            .allocation
+            .as_ref()
+            .map(|(config, _)| Allocator::<(&Market, Signal)>::new(config.clone()));
+        let max_positions = self.allocation.as_ref().map_or(usize::MAX, |(_, max)| *max);
+        // Win rates of each asset and interval, for the allocator's scores
+        let mut outcomes = SignalOutcomeTracker::new();
+        let (camouflage_config, seed) = &self.camouflage;
+        let mut camouflage = Camouflage::seeded(camouflage_config, *seed);
+
+        let mut result = LatencyPointResult {
+            latency_ms,
+            book_staleness_ms: self.config.book_staleness_ms,
+            ..Default::default()
+        };
+        let mut edge_sum = Decimal::ZERO;
+
+        for (timestamp, event) in &self.events {
+            progress(*timestamp, result.fills);
+            if let Some(allocator) = allocator.as_mut().filter(|a| a.is_due(*timestamp)) {
+                let held = open.values().map(Vec::len).sum::<usize>();
+                let allocation =
+                    allocator.allocate(max_positions.saturating_sub(held), Decimal::MAX);
+                result.outranked += allocation.skipped.len();
+                for Candidate { entry, .. } in allocation.selected {
+                    let (market, signal) = entry;
+                    if !markets.contains_key(market.yes_token_id.as_str())
+                        || !entered.insert(market.condition_id.as_str())
+                    {
+                        continue;
+                    }
+                    let sent = self.send(
+                        &signal,
+                        *timestamp,
+                        latency,
+                        &momentum,
+                        &mut camouflage,
+                        &mut result,
+                    );
+                    if let Some((fills, edge)) = sent {
+                        edge_sum += edge;
+                        open.entry(market.condition_id.as_str())
+                            .or_default()
+                            .extend(fills);
+                    }
+                }
+            }
+            match event {
+                BacktestEvent::PriceTick(tick) => {
+                    spot = Some(tick.price);
+                    volatility.update(*timestamp, tick.price);
+                    momentum.update(*timestamp, tick.price);
+                }
+                BacktestEvent::MarketOpen(market) => {
+                    markets.insert(market.yes_token_id.as_str(), market);
+                }
+                BacktestEvent::OrderBookUpdate(book) => {
+                    let Some(market) = markets.get(book.token_id.as_str()).copied() else {
+                        continue;
+                    };
+                    outcomes.on_book(*timestamp, book);
+                    if let Some(fills) = open.get_mut(market.condition_id.as_str()) {
+                        self.check_shocks(&mut shocks, market, fills, *timestamp, latency);
+                        self.check_ladder(&mut ladder, market, fills, *timestamp, latency);
+                    }
+                    if gap_until.is_some_and(|until| *timestamp < until) {
+                        continue;
+                    }
+                    if entered.contains(market.condition_id.as_str()) {
+                        continue;
+                    }
+                    let (Some(spot), Some(vol)) = (spot, volatility.estimate()) else {
+                        continue;
+                    };
+                    // The bot only sees book updates `staleness` after they happen
+                    let Some(seen) = self
+                        .timeline
+                        .book_at(&book.token_id, *timestamp - staleness)
+                    else {
+                        continue;
+                    };
+                    let no = self
+                        .timeline
+                        .book_at(&market.no_token_id, *timestamp - staleness);
+                    let books = MarketBooks::new(seen).with_no(no);
+                    let Some(signal) = self
+                        .detector
+                        .detect_at(market, spot, vol, books, *timestamp)
+                    else {
+                        continue;
+                    };
+
+                    outcomes.watch(&signal, seen, "traded");
+                    if let Some(allocator) = allocator.as_mut() {
+                        let held = open.values().map(Vec::len).sum::<usize>();
+                        let competitors = markets
+                            .values()
+                            .filter(|m| !entered.contains(m.condition_id.as_str()))
+                            .count();
+                        let slots = max_positions.saturating_sub(held);
+                        if allocator.is_contended(slots, competitors) {
+                            let (signals, wins) = outcomes.bucket_record(market);
+                            let score = allocator.score(
+                                &signal,
+                                self.order_size,
+                                *timestamp,
+                                signals,
+                                wins,
+                            );
+                            allocator.offer(Candidate {
+                                market_id: market.condition_id.clone(),
+                                signal_id: signal.id,
+                                score,
+                                cost: signal.market_price * self.order_size,
+                                offered_at: *timestamp,
+                                entry: (market, signal),
+                            });
+                            continue;
+                        }
+                    }
+
+                    entered.insert(market.condition_id.as_str());
+                    let sent = self.send(
+                        &signal,
+                        *timestamp,
+                        latency,
+                        &momentum,
+                        &mut camouflage,
+                        &mut result,
+                    );
+                    if let Some((fills, edge)) = sent {
+                        edge_sum += edge;
+                        open.entry(market.condition_id.as_str())
+                            .or_default()
+                            .extend(fills);
+                    }
+                }
+                BacktestEvent::DataGap { until } => {
+                    // As after a feed outage: start over once data resumes
+                    spot = None;
+                    volatility.clear();
+                    momentum.clear();
+                    gap_until = Some(*until);
+                }
+                BacktestEvent::MarketClose(market) => {
+                    markets.remove(market.yes_token_id.as_str());
+                    outcomes.close(market);
+                    if let Some(allocator) = allocator.as_mut() {
+                        allocator.withdraw(&market.condition_id);
+                    }
+                    let fills = open
+                        .remove(market.condition_id.as_str())
+                        .unwrap_or_default();
+                    let Some(close_price) = spot else {
+                        result.unsettled_fills += fills.len();
+                        continue;
+                    };
+                    let winner = resolution(market, close_price);
+                    for fill in fills {
+                        let payout = if fill.side == winner {
+                            Decimal::ONE
+                        } else {
+                            Decimal::ZERO
+                        };
+                        let fee = fill.price * fill.size * self.config.fee_rate;
+                        let mut pnl = (payout - fill.price) * fill.size - fee;
+                        let mut fees = fee;
+                        let mut proceeds = Decimal::ZERO;
+                        // Each sale against holding the shares it sold
+                        let against_hold = |price: Decimal, size: Decimal| {
+                            (price - payout) * size - price * size * self.config.fee_rate
+                        };
+                        for (price, size) in &fill.sold {
+                            fees += price * size * self.config.fee_rate;
+                            proceeds += price * size;
+                            let delta = against_hold(*price, *size);
+                            result.ladder_exits += 1;
+                            if delta > Decimal::ZERO {
+                                result.ladder_saved += delta;
+                            } else {
+                                result.ladder_whipsaw -= delta;
+                            }
+                            pnl += delta;
+                        }
+                        let held = fill.exit.unwrap_or(payout) * fill.remaining();
+                        if let Some(exit) = fill.exit {
+                            fees += held * self.config.fee_rate;
+                            let delta = against_hold(exit, fill.remaining());
+                            result.shock_exits += 1;
+                            if delta > Decimal::ZERO {
+                                result.shock_saved += delta;
+                            } else {
+                                result.shock_whipsaw -= delta;
+                            }
+                            pnl += delta;
+                        }
+                        result.net_pnl += pnl;
+                        if fill.reverting {
+                            if pnl > Decimal::ZERO {
+                                result.reverting_winners += 1;
+                            } else {
+                                result.reverting_losers += 1;
+                            }
+                            result.reverting_pnl += pnl;
+                        }
+                        let entry_value = (fill.entry.fair_value - fill.price) * fill.size - fee;
+                        result.trades.push(TapeRow {
+                            position_id: fill.id.to_string(),
+                            market_id: market.condition_id.clone(),
+                            asset: market.asset.clone(),
+                            strategy: DEFAULT_STRATEGY.to_string(),
+                            side: fill.side,
+                            entry_time: fill.at,
+                            exit_time: *timestamp,
+                            size: fill.size,
+                            entry_price: fill.price,
+                            exit_price: (proceeds + held).checked_div(fill.size).unwrap_or(payout),
+                            fees,
+                            realized_pnl: pnl,
+                            expected_value_usd: Some(round_usd(entry_value)),
+                            entry: Some(fill.entry),
+                        });
+                    }
+                }
+            }
+        }
+
+        result.unsettled_fills += open.values().map(Vec::len).sum::<usize>();
+        if result.decisions > 0 {
+            result.fill_rate = Decimal::from(result.fills) / Decimal::from(result.decisions);
+        }
+        if result.fills > 0 {
+            result.avg_realized_edge = edge_sum / Decimal::from(result.fills);
+        }
+        result
+    }
+
+    /// Send `signal`'s order decided at `at`, filled against the book as of
+    /// `at + latency`, plus any camouflage delay, if its price is still
+    /// there; a fill for each clip the top level covers and the edge they
+    /// realized
+    fn send(
+        &self,
+        signal: &Signal,
+        at: DateTime<Utc>,
+        latency: Duration,
+        momentum: &MomentumDetector,
+        camouflage: &mut Camouflage,
+        result: &mut LatencyPointResult,
+    ) -> Option<(Vec<SimFill>, Decimal)> {
+        result.decisions += 1;
+        // Drawn whether or not it fills, so later draws do not depend on it
+        let plan = camouflage.plan(self.order_size);
+        let delay = plan.as_ref().map_or(Duration::zero(), |p| {
+            Duration::milliseconds(p.delay_ms as i64)
+        });
+        let (price, mut available) = self
+            .timeline
+            .book_at(&signal.market.yes_token_id, at + latency + delay)
+            .and_then(|b| executable(b, signal.side))?;
+        if price > signal.market_price {
+            return None;
+        }
+        result.fills += 1;
+        let reverting = momentum
+            .signal(signal.side)
+            .is_some_and(|m| m.is_reverting(self.max_retrace));
+        let clips = plan.map_or_else(|| vec![self.order_size], |p| p.clips);
+        let mut fills = vec![];
+        for (i, clip) in clips.into_iter().enumerate() {
+            let size = clip.min(available);
+            // The first clip took all the level had
+            if i > 0 && size <= Decimal::ZERO {
+                break;
+            }
+            available -= size;
+            fills.push(SimFill {
+                id: Uuid::new_v4(),
+                at: at + latency + delay,
+                entry: EntryFeatures::new(signal, at, vec![]),
+                side: signal.side,
+                price,
+                size,
+                reverting,
+                sold: vec![],
+                exit: None,
+            });
+        }
+        Some((fills, signal.fair_value - price))
+    }
+
+    /// Sell each unsold fill of `market` whose supporting depth, in the
+    /// book the bot sees at `now`, just evaporated
+    fn check_shocks(
+        &self,
+        shocks: &mut BookShockDetector,
+        market: &Market,
+        fills: &mut [SimFill],
+        now: DateTime<Utc>,
+        latency: Duration,
+    ) {
+        if !shocks.is_enabled() {
+            return;
+        }
+        let staleness = Duration::milliseconds(self.config.book_staleness_ms as i64);
+        let Some(seen) = self.timeline.book_at(&market.yes_token_id, now - staleness) else {
+            return;
+        };
+        for (i, fill) in fills.iter_mut().enumerate() {
+            if fill.exit.is_some() {
+                continue;
+            }
+            let key = format!("{}-{}", market.condition_id, i);
+            if shocks
+                .observe(&key, seen, fill.side, market.close_time, now)
+                .is_none()
+            {
+                continue;
+            }
+            fill.exit = self
+                .timeline
+                .book_at(&market.yes_token_id, now + latency)
+                .and_then(|b| match fill.side {
+                    Side::Yes => b.best_bid(),
+                    Side::No => b.best_ask().map(|ask| Decimal::ONE - ask),
+                });
+        }
+    }
+
+    /// Sell the due rung's fraction of each unsold fill of `market` whose
+    /// bid, in the book the bot sees at `now`, shows the rung's profit
+    fn check_ladder(
+        &self,
+        ladder: &mut ExitLadder,
+        market: &Market,
+        fills: &mut [SimFill],
+        now: DateTime<Utc>,
+        latency: Duration,
+    ) {
+        if !ladder.is_enabled() {
+            return;
+        }
+        let staleness = Duration::milliseconds(self.config.book_staleness_ms as i64);
+        let Some(seen) = self.timeline.book_at(&market.yes_token_id, now - staleness) else {
+            return;
+        };
+        let bid = |book: &OrderBook, side: Side| match side {
+            Side::Yes => book.best_bid(),
+            Side::No => book.best_ask().map(|ask| Decimal::ONE - ask),
+        };
+        for fill in fills.iter_mut() {
+            if fill.exit.is_some() {
+                continue;
+            }
+            let Some(seen_bid) = bid(seen, fill.side) else {
+                continue;
+            };
+            let Some(rung) = ladder.check(fill.id, fill.price, seen_bid, market.close_time, now)
+            else {
+                continue;
+            };
+            let size = round_size(fill.remaining() * rung.fraction);
+            let price = self
+                .timeline
+                .book_at(&market.yes_token_id, now + latency)
+                .and_then(|b| bid(b, fill.side));
+            if let Some(price) = price.filter(|_| size > Decimal::ZERO) {
+                fill.sold.push((price, size));
+            }
+        }
+    }
+}
+
+/// Executable price and size for buying `side`, priced off the YES book the
+/// same way the lag detector prices it
+fn executable(book: &OrderBook, side: Side) -> Option<(Decimal, Decimal)> {
+    if book.top_of_book_fault().is_some() {
+        return None;
+    }
+    let top = book.asks.first()?;
+    let price = match side {
+        Side::Yes => top.price,
+        Side::No => Decimal::ONE - top.price,
+    };
+    Some((price, top.size))
+}
+
+/// Format sweep results as a CLI table
+pub fn format_sweep_table(results: &[LatencyPointResult]) -> String {
+    let mut out = String::from(
+        "latency_ms  staleness_ms  decisions  fills  fill_rate  avg_edge    net_pnl  revert_w/l  shock_x  shock_net  ladder_x  ladder_net  outranked\n",
+    );
+    for r in results {
+        out.push_str(&format!(
+            "{:>10}  {:>12}  {:>9}  {:>5}  {:>8.1}%  {:>7.2}%  {:>+9.2}  {:>10}  {:>7}  {:>+9.2}  {:>8}  {:>+10.2}  {:>9}\n",
+            r.latency_ms,
+            r.book_staleness_ms,
+            r.decisions,
+            r.fills,
+            r.fill_rate * dec!(100),
+            r.avg_realized_edge * dec!(100),
+            r.net_pnl,
+            format!("{}/{}", r.reverting_winners, r.reverting_losers),
+            r.shock_exits,
+            r.shock_saved - r.shock_whipsaw,
+            r.ladder_exits,
+            r.ladder_saved - r.ladder_whipsaw,
+            r.outranked
+        ));
+    }
+    out
+}
+
+/// Format hold-to-resolution and exit ladder results side by side
+pub fn format_exit_comparison(rows: &[ExitPolicyComparison]) -> String {
+    let mut out = String::from(
+        "latency_ms  fills   hold_pnl  ladder_pnl   ladder-hold  ladder_x     saved  whipsaw\n",
+    );
+    for row in rows {
+        let (hold, ladder) = (&row.hold, &row.ladder);
+        out.push_str(&format!(
+            "{:>10}  {:>5}  {:>+9.2}  {:>+10.2}  {:>+12.2}  {:>8}  {:>8.2}  {:>7.2}\n",
+            ladder.latency_ms,
+            ladder.fills,
+            hold.net_pnl,
+            ladder.net_pnl,
+            ladder.net_pnl - hold.net_pnl,
+            ladder.ladder_exits,
+            ladder.ladder_saved,
+            ladder.ladder_whipsaw
+        ));
+    }
+    out
+}
+
+/// Write sweep results as CSV
+pub fn write_sweep_csv(path: &Path, results: &[LatencyPointResult]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::File::create(path)?;
+    writeln!(
+        file,
+        "latency_ms,book_staleness_ms,decisions,fills,fill_rate,net_pnl,avg_realized_edge,unsettled_fills,reverting_winners,reverting_losers,reverting_pnl,shock_exits,shock_saved,shock_whipsaw,ladder_exits,ladder_saved,ladder_whipsaw,outranked"
+    )?;
+    for r in results {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            r.latency_ms,
+            r.book_staleness_ms,
+            r.decisions,
+            r.fills,
+            r.fill_rate.normalize(),
+            r.net_pnl.normalize(),
+            r.avg_realized_edge.normalize(),
+            r.unsettled_fills,
+            r.reverting_winners,
+            r.reverting_losers,
+            r.reverting_pnl.normalize(),
+            r.shock_exits,
+            r.shock_saved.normalize(),
+            r.shock_whipsaw.normalize(),
+            r.ladder_exits,
+            r.ladder_saved.normalize(),
+            r.ladder_whipsaw.normalize(),
+            r.outranked
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::{Alignment, CorruptFiles};
+    use crate::data::IncludeCurrent;
+    use crate::feed::{PriceTick, TickSource};
+    use crate::model::GbmModel;
+    use crate::orderbook::PriceLevel;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn config(staleness_ms: u64) -> BacktestConfig {
+        BacktestConfig {
+            data_dir: PathBuf::from("./nonexistent"),
+            merge_dirs: vec![],
+            price_history: false,
+            scenario: None,
+            align: Alignment::None,
+            corrupt_files: CorruptFiles::Skip,
+            include_current: IncludeCurrent::Skip,
+            start_time: None,
+            end_time: None,
+            initial_capital: dec!(500),
+            latency_ms: 0,
+            book_staleness_ms: staleness_ms,
+            fee_rate: dec!(0.01),
+            bootstrap: Default::default(),
+        }
+    }
+
+    fn tick(ts: DateTime<Utc>, price: Decimal) -> (DateTime<Utc>, BacktestEvent) {
+        (
+            ts,
+            BacktestEvent::PriceTick(PriceTick {
+                symbol: "BTCUSDT".to_string(),
+                asset: "BTC".to_string(),
+                price,
+                timestamp: ts,
+                exchange_ts: ts,
+                source: TickSource::Trade,
+            }),
+        )
+    }
+
+    fn book(ts: DateTime<Utc>, ask: Decimal) -> (DateTime<Utc>, BacktestEvent) {
+        let mut book = OrderBook::new("yes");
+        book.bids = vec![PriceLevel {
+            price: ask - dec!(0.01),
+            size: dec!(100),
+        }];
+        book.asks = vec![PriceLevel {
+            price: ask,
+            size: dec!(100),
+        }];
+        book.updated_at = ts;
+        (ts, BacktestEvent::OrderBookUpdate(book))
+    }
+
+    /// Spot rallies well above the open while the YES ask sits at 0.40,
+    /// then the ask reprices to 0.95 exactly 200ms after the decision.
+    fn scenario() -> Vec<(DateTime<Utc>, BacktestEvent)> {
+        let open_time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let market = Market {
+            condition_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time,
+            close_time: open_time + Duration::minutes(15),
+            group_id: None,
+            orientation: Default::default(),
+        };
+
+        let mut events = vec![(open_time, BacktestEvent::MarketOpen(market.clone()))];
+        for i in 0..60 {
+            let price = dec!(100000) + Decimal::from(i * 50) + Decimal::from(i % 2 * 20);
+            events.push(tick(open_time + Duration::seconds(i), price));
+        }
+        let decision = open_time + Duration::seconds(61);
+        events.push(book(decision, dec!(0.40)));
+        events.push(book(decision + Duration::milliseconds(200), dec!(0.95)));
+        events.push(tick(market.close_time, dec!(103000)));
+        events.push((market.close_time, BacktestEvent::MarketClose(market)));
+        events
+    }
+
+    #[test]
+    fn test_edge_disappears_between_latency_points() {
+        let sweep = LatencySweep::new(GbmModel::new(), config(0), scenario());
+        let results = sweep.run(&[100, 199, 200, 500]);
+
+        assert!(results.iter().all(|r| r.decisions == 1));
+
+        // Order arrives before the reprice: filled at 0.40, YES wins
+        let fast = &results[0];
+        assert_eq!(fast.fills, 1);
+        assert_eq!(fast.fill_rate, dec!(1));
+        // 10 shares: (1 - 0.40) * 10 - 0.40 * 10 * 0.01
+        assert_eq!(fast.net_pnl, dec!(5.96));
+        assert!(fast.avg_realized_edge > Decimal::ZERO);
+        assert_eq!(results[1].fills, 1);
+
+        // At and after 200ms the order meets the repriced book and misses
+        for slow in &results[2..] {
+            assert_eq!(slow.fills, 0);
+            assert_eq!(slow.fill_rate, dec!(0));
+            assert_eq!(slow.net_pnl, dec!(0));
+        }
+    }
+
+    #[test]
+    fn test_settled_fills_make_the_trade_tape() {
+        let events = scenario();
+        let close = events.last().unwrap().0;
+        let fast = LatencySweep::new(GbmModel::new(), config(0), events).run_point(100);
+        let [row] = &fast.trades[..] else {
+            panic!("expected one row: {:?}", fast.trades);
+        };
+        assert_eq!((row.market_id.as_str(), row.side), ("cond", Side::Yes));
+        assert_eq!(row.strategy, DEFAULT_STRATEGY);
+        let decision = DateTime::from_timestamp(1_700_000_061, 0).unwrap();
+        assert_eq!(row.entry_time, decision + Duration::milliseconds(100));
+        assert_eq!(row.exit_time, close);
+        assert_eq!(
+            (row.size, row.entry_price, row.exit_price),
+            (dec!(10), dec!(0.40), dec!(1))
+        );
+        assert_eq!((row.fees, row.realized_pnl), (dec!(0.04), fast.net_pnl));
+        let entry = row.entry.as_ref().unwrap();
+        assert_eq!(entry.market_price, dec!(0.40));
+        assert_eq!(entry.secs_to_close, 839);
+
+        // Ladder sales average into the exit price, their fees into the fees
+        let laddered = LatencySweep::new(GbmModel::new(), config(0), reversing_scenario())
+            .with_exit_ladder(ladder())
+            .run_point(100);
+        let row = &laddered.trades[0];
+        assert_eq!(row.exit_price, dec!(0.625));
+        assert_eq!(row.fees, dec!(0.09));
+        assert_eq!(row.realized_pnl, laddered.net_pnl);
+
+        // Nothing filled, nothing on the tape
+        let slow = LatencySweep::new(GbmModel::new(), config(0), scenario()).run_point(500);
+        assert!(slow.trades.is_empty());
+    }
+
+    #[test]
+    fn test_camouflage_delays_jitters_and_splits_orders() {
+        let latencies = [0, 100];
+        let plain = LatencySweep::new(GbmModel::new(), config(0), scenario()).run(&latencies);
+
+        // Disabled, nothing is drawn and the replay is unchanged
+        let disabled = CamouflageConfig {
+            max_delay_ms: crate::duration::DurationConfig::from_millis(150),
+            split_probability: Decimal::ONE,
+            ..Default::default()
+        };
+        let sweep = LatencySweep::new(GbmModel::new(), config(0), scenario())
+            .with_camouflage(disabled.clone(), 3);
+        assert_eq!(
+            serde_json::to_string(&sweep.run(&latencies)).unwrap(),
+            serde_json::to_string(&plain).unwrap()
+        );
+
+        let enabled = CamouflageConfig {
+            enabled: true,
+            ..disabled
+        };
+        let sweep = LatencySweep::new(GbmModel::new(), config(0), scenario())
+            .with_camouflage(enabled.clone(), 3);
+        let results = sweep.run(&latencies);
+        for (result, latency) in results.iter().zip(latencies) {
+            // Each replay draws the same plan from the seed
+            let plan = Camouflage::seeded(&enabled, 3).plan(dec!(10)).unwrap();
+            assert!(plan.is_split());
+            assert_eq!(result.decisions, 1);
+            // The ask reprices 200ms after the decision
+            if latency + plan.delay_ms < 200 {
+                assert_eq!(result.fills, 1);
+                let size = plan.jittered_size();
+                assert_eq!(result.net_pnl, dec!(0.596) * size, "{:?}", plan);
+            } else {
+                assert_eq!(result.fills, 0);
+            }
+        }
+        assert_eq!(
+            serde_json::to_string(&sweep.run(&latencies)).unwrap(),
+            serde_json::to_string(&results).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_concurrent_signals_compete_for_the_position_slot() {
+        // Without the reprice, and a second market alongside whose YES ask
+        // fires first but is only 8 shares deep
+        let mut events = scenario();
+        let decision = events[0].0 + Duration::seconds(61);
+        events.retain(|(ts, _)| *ts != decision + Duration::milliseconds(200));
+        let BacktestEvent::MarketOpen(mut thin) = events[0].1.clone() else {
+            unreachable!()
+        };
+        thin.condition_id = "cond-2".to_string();
+        thin.yes_token_id = "yes-2".to_string();
+        thin.no_token_id = "no-2".to_string();
+        let (_, BacktestEvent::OrderBookUpdate(mut shallow)) = book(decision, dec!(0.40)) else {
+            unreachable!()
+        };
+        shallow.token_id = "yes-2".to_string();
+        shallow.asks[0].size = dec!(8);
+        let at = events.iter().position(|(ts, _)| *ts == decision).unwrap();
+        events.insert(at, (decision, BacktestEvent::OrderBookUpdate(shallow)));
+        events.insert(1, (thin.open_time, BacktestEvent::MarketOpen(thin.clone())));
+        events.push(tick(decision + Duration::seconds(1), dec!(103000)));
+        events.push((thin.close_time, BacktestEvent::MarketClose(thin)));
+        events.sort_by_key(|(ts, _)| *ts);
+
+        let unlimited = LatencySweep::new(GbmModel::new(), config(0), events.clone()).run_point(0);
+        assert_eq!((unlimited.decisions, unlimited.fills), (2, 2));
+
+        // One slot: the deeper book takes it once the window closes,
+        // although the shallow one fired first
+        let one = LatencySweep::new(GbmModel::new(), config(0), events)
+            .with_allocation(AllocationConfig::default(), 1)
+            .run_point(0);
+        assert_eq!((one.decisions, one.fills, one.outranked), (1, 1, 1));
+        // 10 shares of the deep book rather than 8 of the shallow one:
+        // (1 - 0.40) * 10 - 0.40 * 10 * 0.01
+        assert_eq!(one.net_pnl, dec!(5.96));
+    }
+
+    #[test]
+    fn test_nothing_trades_across_a_data_gap() {
+        // A skipped file's hour ends before the rally: the window is rebuilt
+        // from the ticks after it and still trades
+        let mut events = scenario();
+        let open_time = events[0].0;
+        events.insert(
+            1,
+            (
+                open_time,
+                BacktestEvent::DataGap {
+                    until: open_time + Duration::milliseconds(500),
+                },
+            ),
+        );
+        let sweep = LatencySweep::new(GbmModel::new(), config(0), events);
+        assert_eq!(sweep.run_point(100).decisions, 1);
+
+        // Mid-rally to past the cheap ask: no decision on either side of it
+        let mut events = scenario();
+        let gap_at = open_time + Duration::seconds(30);
+        let position = events.iter().position(|(ts, _)| *ts > gap_at).unwrap();
+        events.insert(
+            position,
+            (
+                gap_at,
+                BacktestEvent::DataGap {
+                    until: open_time + Duration::seconds(62),
+                },
+            ),
+        );
+        let result = LatencySweep::new(GbmModel::new(), config(0), events).run_point(100);
+        assert_eq!(result.decisions, 0);
+        assert_eq!(result.fills, 0);
+    }
+
+    #[test]
+    fn test_book_staleness_delays_decision() {
+        // With 100ms staleness the bot first sees the 0.40 book while handling
+        // the next update, so even a 50ms order arrives after the reprice
+        let sweep = LatencySweep::new(GbmModel::new(), config(100), scenario());
+        let result = sweep.run_point(50);
+        assert_eq!(result.book_staleness_ms, 100);
+        assert_eq!(result.decisions, 1);
+        assert_eq!(result.fills, 0);
+    }
+
+    #[test]
+    fn test_reverting_fills_are_tagged() {
+        let results = LatencySweep::new(GbmModel::new(), config(0), scenario()).run_point(100);
+        assert_eq!(
+            (results.reverting_winners, results.reverting_losers),
+            (0, 0)
+        );
+
+        // Spot gives back half the rally just before the decision
+        let mut events = scenario();
+        let decision = events[61].0;
+        let pullback = tick(decision - Duration::milliseconds(500), dec!(101500));
+        events.insert(61, pullback.clone());
+        let result = LatencySweep::new(GbmModel::new(), config(0), events.clone()).run_point(100);
+        assert_eq!(result.fills, 1);
+        assert_eq!((result.reverting_winners, result.reverting_losers), (1, 0));
+        assert_eq!(result.reverting_pnl, dec!(5.96));
+
+        // The same entry loses when spot keeps falling through the open
+        let close = events.len() - 2;
+        events[close] = tick(events[close].0, dec!(99000));
+        let result = LatencySweep::new(GbmModel::new(), config(0), events.clone()).run_point(100);
+        assert_eq!((result.reverting_winners, result.reverting_losers), (0, 1));
+        assert_eq!(result.reverting_pnl, result.net_pnl);
+
+        // A looser threshold lets the entry through untagged
+        let result = LatencySweep::new(GbmModel::new(), config(0), events)
+            .with_momentum(Duration::seconds(60), dec!(0.9))
+            .run_point(100);
+        assert_eq!((result.reverting_winners, result.reverting_losers), (0, 0));
+    }
+
+    #[test]
+    fn test_book_shock_exit_saves_losers_and_costs_winners() {
+        // After the fill the YES bids drain from 100 to 5 within a second
+        let mut events = scenario();
+        let decision = events[61].0;
+        let close = events.len() - 2;
+        for (i, (ms, bids)) in [(1000, dec!(100)), (1300, dec!(20)), (1600, dec!(5))]
+            .into_iter()
+            .enumerate()
+        {
+            let (ts, mut book) = book(decision + Duration::milliseconds(ms), dec!(0.52));
+            if let BacktestEvent::OrderBookUpdate(b) = &mut book {
+                b.bids = vec![PriceLevel {
+                    price: dec!(0.50),
+                    size: bids,
+                }];
+            }
+            events.insert(close + i, (ts, book));
+        }
+        let shock = BookShockConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let run = |events: Vec<(DateTime<Utc>, BacktestEvent)>| {
+            LatencySweep::new(GbmModel::new(), config(0), events)
+                .with_book_shock(shock.clone(), Duration::minutes(1))
+                .run_point(100)
+        };
+
+        // Sold at 0.50: (0.50 - 0.40) * 10 less both fees, against 5.96 held
+        let winner = run(events.clone());
+        assert_eq!(winner.shock_exits, 1);
+        assert_eq!(winner.net_pnl, dec!(0.91));
+        assert_eq!(winner.shock_whipsaw, dec!(5.05));
+        assert_eq!(winner.shock_saved, dec!(0));
+
+        // The same exit ahead of spot falling through the open
+        let close = events.len() - 2;
+        events[close] = tick(events[close].0, dec!(99000));
+        let loser = run(events.clone());
+        assert_eq!(loser.shock_exits, 1);
+        assert_eq!(loser.shock_saved, dec!(4.95));
+        assert_eq!(loser.shock_whipsaw, dec!(0));
+
+        // Off by default
+        let held = LatencySweep::new(GbmModel::new(), config(0), events).run_point(100);
+        assert_eq!(held.shock_exits, 0);
+        assert_eq!(held.net_pnl, dec!(-4.04));
+    }
+
+    /// The scenario's YES fill at 0.40, then a bid that rallies past each
+    /// rung of `ladder()` and reverses after it
+    fn reversing_scenario() -> Vec<(DateTime<Utc>, BacktestEvent)> {
+        let mut events = scenario();
+        let close = events.len() - 2;
+        let close_time = events[close].0;
+        let path = [
+            (170, dec!(0.61)),
+            (120, dec!(0.46)),
+            (80, dec!(0.56)),
+            (60, dec!(0.31)),
+            (40, dec!(0.51)),
+            (20, dec!(0.21)),
+        ];
+        for (i, (secs, ask)) in path.into_iter().enumerate() {
+            events.insert(close + i, book(close_time - Duration::seconds(secs), ask));
+        }
+        events
+    }
+
+    fn ladder() -> ExitLadderConfig {
+        let rung = |secs_before_close, min_profit, fraction| crate::engine::LadderRung {
+            secs_before_close: crate::duration::DurationConfig::from_secs(secs_before_close),
+            min_profit,
+            fraction,
+        };
+        ExitLadderConfig {
+            enabled: true,
+            rungs: vec![
+                rung(180, dec!(0.10), dec!(0.5)),
+                rung(90, dec!(0.10), dec!(0.5)),
+                rung(45, dec!(0.05), dec!(0.5)),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_exit_ladder_sells_each_rung_against_holding() {
+        let events = reversing_scenario();
+        let run = |events: Vec<(DateTime<Utc>, BacktestEvent)>| {
+            LatencySweep::new(GbmModel::new(), config(0), events)
+                .with_exit_ladder(ladder())
+                .run_point(100)
+        };
+
+        // 5 shares at 0.60, 2.5 at 0.55 and 1.25 at 0.50; 1.25 ride to a win
+        let winner = run(events.clone());
+        assert_eq!(winner.ladder_exits, 3);
+        assert_eq!(winner.ladder_saved, dec!(0));
+        // 2.03 + 1.13875 + 0.63125 given up against 5.96 held
+        assert_eq!(winner.ladder_whipsaw, dec!(3.8));
+        assert_eq!(winner.net_pnl, dec!(2.16));
+
+        // Spot falls through the open: the same sales save the loser
+        let mut events = events;
+        let close = events.len() - 2;
+        events[close] = tick(events[close].0, dec!(99000));
+        let loser = run(events.clone());
+        assert_eq!(loser.ladder_exits, 3);
+        assert_eq!(loser.ladder_saved, dec!(4.95));
+        assert_eq!(loser.ladder_whipsaw, dec!(0));
+        // Sold for 5.00 against 4.00 paid, less 0.09 of fees; the rest is lost
+        assert_eq!(loser.net_pnl, dec!(0.91));
+
+        // A rung that misses its profit is skipped, and the next one sells
+        // half of what is left
+        let mut skipped = ladder();
+        skipped.rungs[1].min_profit = dec!(0.20);
+        let result = LatencySweep::new(GbmModel::new(), config(0), events.clone())
+            .with_exit_ladder(skipped)
+            .run_point(100);
+        assert_eq!(result.ladder_exits, 2);
+        // 5 at 0.60 and 2.5 at 0.50
+        assert_eq!(result.ladder_saved, dec!(2.97) + dec!(1.2375));
+
+        // Off by default
+        let held = LatencySweep::new(GbmModel::new(), config(0), events).run_point(100);
+        assert_eq!(held.ladder_exits, 0);
+        assert_eq!(held.net_pnl, dec!(-4.04));
+    }
+
+    #[test]
+    fn test_exit_comparison_sets_policies_side_by_side() {
+        let mut disabled = ladder();
+        disabled.enabled = false;
+        let sweep = LatencySweep::new(GbmModel::new(), config(0), reversing_scenario())
+            .with_exit_ladder(disabled);
+        assert_eq!(sweep.run_point(100).ladder_exits, 0);
+
+        let rows = sweep.compare_exits(&[100, 250]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].hold.net_pnl, dec!(5.96));
+        assert_eq!(rows[0].hold.ladder_exits, 0);
+        assert_eq!(rows[0].ladder.net_pnl, dec!(2.16));
+        // Hold P&L is the ladder's less what its sales saved and gave up
+        let ladder = &rows[0].ladder;
+        assert_eq!(
+            rows[0].hold.net_pnl,
+            ladder.net_pnl - ladder.ladder_saved + ladder.ladder_whipsaw
+        );
+        // Nothing fills at 250ms, under either policy
+        assert_eq!(rows[1].hold.fills, 0);
+        assert_eq!(rows[1].ladder.net_pnl, dec!(0));
+
+        let table = format_exit_comparison(&rows);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("hold_pnl  ladder_pnl"));
+        assert!(lines[1].contains("+5.96"));
+        assert!(lines[1].contains("+2.16"));
+        assert!(lines[1].contains("-3.80"));
+        assert!(lines[1].contains("3.80"));
+    }
+
+    #[test]
+    fn test_sweep_csv_and_table() {
+        let sweep = LatencySweep::new(GbmModel::new(), config(0), scenario());
+        let results = sweep.run(&[100, 250]);
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("latency_sweep.csv");
+        write_sweep_csv(&path, &results).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("latency_ms,book_staleness_ms,decisions,fills"));
+        assert!(lines[1].starts_with("100,0,1,1,1,5.96,"));
+        assert!(lines[2].starts_with("250,0,1,0,0,0,"));
+
+        let table = format_sweep_table(&results);
+        assert_eq!(table.lines().count(), 3);
+        assert!(table.contains("0/0"));
+        assert!(table.contains("+5.96"));
+    }
+}
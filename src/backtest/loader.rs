@@ -0,0 +1,1177 @@
+//! Capture loading for replay
+//!
+//! Reads price ticks and order books from one or more capture directories
+//! and merges them into one time-ordered stream. Captures from different
+//! machines overlap in time, so a row another file already holds is
+//! dropped: an identical row as a duplicate, and a row that differs at the
+//! same timestamp and symbol or token as a conflict, resolved in favour of
+//! the higher-priority file. Sources are in priority order; within one
+//! source, files rank by path.
+//!
+//! Price history backfilled over REST can stand in for books a capture
+//! never had. Each point becomes a one-level book around the historical
+//! price; tokens with any captured book keep their real books only. These
+//! approximated books are counted so results built on them are flagged as
+//! low fidelity.
+//!
+//! Captures written with deduplication on hold a book only when it changed,
+//! plus a `keepalive` row restating it after the keep-alive interval
+//! unchanged. Each book event carries the token's whole merged book, which
+//! holds until the next, so a keep-alive applies as a snapshot and the
+//! frames skipped between rows are forward-filled as they were.
+//!
+//! A capture file that cannot be read, such as one truncated by a crash
+//! mid-write, is found before anything is replayed: every footer is checked
+//! up front. By default such files are skipped and the time each covered,
+//! from its name to the next file's, is replayed as a
+//! [`BacktestEvent::DataGap`] whether or not another source holds rows for
+//! it. In strict mode loading fails instead, listing every unreadable file.
+//!
+//! Each source is read through a [`CaptureSnapshot`]: only files sealed
+//! when loading starts are read, so a capture still writing to the
+//! directory neither hands over a file mid-write nor shifts the replay
+//! under way. Files being written are left out, or waited for with
+//! [`IncludeCurrent::Wait`]; the report lists every file read with its
+//! checksum.
+//!
+//! Markets come from the `market_opened` entries of each source's trade
+//! journal. A market overlapping the range opens at its open time, or at
+//! the range start if it opened earlier, and closes at its close time if
+//! that falls in the range; opens replay before and closes after the rows
+//! sharing their timestamp.
+
+use super::BacktestEvent;
+use crate::data::{
+    orderbooks_from_batch, price_history_from_batch, price_ticks_from_batch, read_batches,
+    validate_capture, CaptureSnapshot, IncludeCurrent, OrderBookRecord, PricePointRecord,
+    PriceTickRecord, DEFAULT_SEAL_WAIT_SECS, PRICE_HISTORY_PREFIX,
+};
+use crate::feed::{PriceTick, TickSource};
+use crate::journal::Journal;
+use crate::market::{Market, DEFAULT_TICK_SIZE};
+use crate::orderbook::{BookUpdateKind, OrderBookManager, PriceLevel};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Prefix of captured price tick files
+const PRICE_TICKS_PREFIX: &str = "price_ticks";
+
+/// Prefix of captured order book files
+const ORDERBOOK_PREFIX: &str = "orderbook";
+
+/// Trade journal in a capture directory, whose markets are replayed
+const TRADE_JOURNAL_FILE: &str = "trade_journal.jsonl";
+
+/// Size on each side of a book approximated from price history
+pub const PRICE_HISTORY_DEPTH: Decimal = dec!(100);
+
+/// An event and the time it is replayed at
+type TimedEvent = (DateTime<Utc>, BacktestEvent);
+
+/// Time range of one capture file, from a scan of its rows
+#[derive(Debug, Clone)]
+pub struct FileRange {
+    /// File path
+    pub path: PathBuf,
+    /// File prefix, `price_ticks`, `orderbook` or `price_history`
+    pub prefix: String,
+    /// Index of the source directory, lower wins conflicts
+    pub source: usize,
+    /// Rows in the file
+    pub rows: usize,
+    /// Earliest row
+    pub first: Option<DateTime<Utc>>,
+    /// Latest row
+    pub last: Option<DateTime<Utc>>,
+}
+
+/// What to do with capture files that cannot be read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum CorruptFiles {
+    /// Leave them out and replay the time they cover as a data gap
+    #[default]
+    Skip,
+    /// Refuse to load, listing every unreadable file
+    Strict,
+}
+
+/// A capture file left out of the replay because it could not be read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    /// File prefix, `price_ticks`, `orderbook` or `price_history`
+    pub prefix: String,
+    /// Index of the source directory
+    pub source: usize,
+    /// Why it could not be read
+    pub error: String,
+    /// Start of the time it covered, within the range; unknown when its
+    /// name carries no time
+    pub from: Option<DateTime<Utc>>,
+    /// End of that time: the next file's start, or the end of the data
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl SkippedFile {
+    /// Time the file covered within the range, zero when unknown
+    pub fn duration(&self) -> Duration {
+        match (self.from, self.to) {
+            (Some(from), Some(to)) if to > from => to - from,
+            _ => Duration::zero(),
+        }
+    }
+}
+
+impl fmt::Display for SkippedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        match (self.from, self.to) {
+            (Some(from), Some(to)) => write!(
+                f,
+                " ({} to {}, {}s)",
+                from.to_rfc3339(),
+                to.to_rfc3339(),
+                self.duration().num_seconds()
+            )?,
+            _ => write!(f, " (time covered unknown)")?,
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+/// Two files of one prefix whose time ranges intersect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    pub prefix: String,
+    pub first: PathBuf,
+    pub second: PathBuf,
+    /// Start of the shared range
+    pub from: DateTime<Utc>,
+    /// End of the shared range
+    pub to: DateTime<Utc>,
+}
+
+/// A row dropped for differing from a higher-priority file's row at the
+/// same timestamp and symbol or token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub prefix: String,
+    pub timestamp: DateTime<Utc>,
+    /// Symbol of a tick, token of a book
+    pub key: String,
+    /// File whose rows were kept
+    pub kept: PathBuf,
+    /// File the row was dropped from
+    pub dropped: PathBuf,
+}
+
+/// What merging the capture files found
+#[derive(Debug, Clone, Default)]
+pub struct MergeReport {
+    /// Every file read, in priority order
+    pub files: Vec<FileRange>,
+    /// Files whose time ranges intersect
+    pub overlaps: Vec<Overlap>,
+    /// Identical rows dropped, by the file they were dropped from
+    pub duplicates: BTreeMap<PathBuf, usize>,
+    /// Differing rows dropped in favour of a higher-priority file
+    pub conflicts: Vec<Conflict>,
+    /// Books approximated from price history rather than captured
+    pub approximated_books: usize,
+    /// Unreadable files left out, replayed as data gaps
+    pub skipped: Vec<SkippedFile>,
+    /// Periods with no data for a skipped file, merged where they overlap
+    pub gaps: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+    /// Sealed files of each source when loading started, in priority order
+    pub snapshots: Vec<CaptureSnapshot>,
+}
+
+impl MergeReport {
+    /// Total time replayed as data gaps
+    pub fn excluded(&self) -> Duration {
+        self.gaps
+            .iter()
+            .fold(Duration::zero(), |total, (from, to)| total + (*to - *from))
+    }
+
+    /// Identical rows dropped across all files
+    pub fn duplicates_removed(&self) -> usize {
+        self.duplicates.values().sum()
+    }
+
+    /// Whether any replayed book was approximated from price history
+    pub fn low_fidelity(&self) -> bool {
+        self.approximated_books > 0
+    }
+
+    /// Log the overlaps and what was dropped from where
+    pub fn log(&self) {
+        for snapshot in &self.snapshots {
+            for path in &snapshot.in_progress {
+                tracing::info!(file = ?path, "Capture file still being written, left out");
+            }
+        }
+        for skipped in &self.skipped {
+            tracing::warn!(
+                file = ?skipped.path,
+                error = %skipped.error,
+                from = ?skipped.from,
+                to = ?skipped.to,
+                "Skipped unreadable capture file, replaying its time as a data gap"
+            );
+        }
+        for overlap in &self.overlaps {
+            tracing::info!(
+                prefix = %overlap.prefix,
+                first = ?overlap.first,
+                second = ?overlap.second,
+                from = %overlap.from,
+                to = %overlap.to,
+                "Capture files overlap"
+            );
+        }
+        for (path, count) in &self.duplicates {
+            tracing::info!(file = ?path, duplicates = count, "Dropped duplicate rows");
+        }
+        for conflict in &self.conflicts {
+            tracing::warn!(
+                prefix = %conflict.prefix,
+                timestamp = %conflict.timestamp,
+                key = %conflict.key,
+                kept = ?conflict.kept,
+                dropped = ?conflict.dropped,
+                "Conflicting rows at one timestamp, kept the higher-priority file"
+            );
+        }
+    }
+}
+
+impl fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} files, {} overlaps, {} duplicates removed, {} conflicts resolved",
+            self.files.len(),
+            self.overlaps.len(),
+            self.duplicates_removed(),
+            self.conflicts.len()
+        )?;
+        if self.low_fidelity() {
+            write!(
+                f,
+                ", {} books approximated from price history",
+                self.approximated_books
+            )?;
+        }
+        if !self.skipped.is_empty() {
+            write!(
+                f,
+                ", {} corrupt files skipped ({}s excluded)",
+                self.skipped.len(),
+                self.excluded().num_seconds()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// One captured row
+#[derive(Debug)]
+enum Row {
+    Tick(PriceTickRecord),
+    Book(OrderBookRecord),
+    Point(PricePointRecord),
+}
+
+/// A row with what it is merged and deduplicated on
+#[derive(Debug)]
+struct Keyed {
+    timestamp: DateTime<Utc>,
+    /// Ticks before books at one timestamp
+    rank: u8,
+    key: Arc<str>,
+    /// Index into the report's files, its priority
+    file: usize,
+    /// Position in the file
+    index: usize,
+    /// Everything else a row carries
+    digest: u64,
+    row: Row,
+}
+
+impl Keyed {
+    fn group(&self) -> (DateTime<Utc>, u8, &str) {
+        (self.timestamp, self.rank, &self.key)
+    }
+}
+
+/// Loads and merges capture directories, in priority order
+#[derive(Debug, Clone)]
+pub struct CaptureLoader {
+    sources: Vec<PathBuf>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    price_history: bool,
+    corrupt_files: CorruptFiles,
+    include_current: IncludeCurrent,
+}
+
+impl CaptureLoader {
+    /// Load `sources`; on conflicting rows the earlier source wins
+    pub fn new(sources: Vec<PathBuf>) -> Self {
+        Self {
+            sources,
+            start: None,
+            end: None,
+            price_history: false,
+            corrupt_files: CorruptFiles::Skip,
+            include_current: IncludeCurrent::Skip,
+        }
+    }
+
+    /// Keep rows from `start` up to and including `end`
+    pub fn with_range(mut self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    /// Approximate books from `price_history` files for tokens with no
+    /// captured books
+    pub fn with_price_history(mut self, enabled: bool) -> Self {
+        self.price_history = enabled;
+        self
+    }
+
+    /// Skip unreadable files as data gaps, or fail on them; see
+    /// [`CorruptFiles`]
+    pub fn with_corrupt_files(mut self, corrupt_files: CorruptFiles) -> Self {
+        self.corrupt_files = corrupt_files;
+        self
+    }
+
+    /// Leave out capture files still being written, or wait for them to
+    /// seal; see [`IncludeCurrent`]
+    pub fn with_include_current(mut self, include_current: IncludeCurrent) -> Self {
+        self.include_current = include_current;
+        self
+    }
+
+    /// Every kept row as an event, oldest first, and what the merge found
+    ///
+    /// Book rows go through an [`OrderBookManager`], so each event carries
+    /// the full book even when the row is a delta.
+    pub fn load(&self) -> anyhow::Result<(Vec<TimedEvent>, MergeReport)> {
+        let mut report = MergeReport::default();
+        let mut sources = vec![];
+        let wait = std::time::Duration::from_secs(DEFAULT_SEAL_WAIT_SECS);
+        for dir in &self.sources {
+            let snapshot = CaptureSnapshot::take(dir, self.include_current, wait)?;
+            let mut files = snapshot.files.clone();
+            report.snapshots.push(snapshot);
+            files.retain(|f| {
+                f.prefix == PRICE_TICKS_PREFIX
+                    || f.prefix == ORDERBOOK_PREFIX
+                    || (self.price_history && f.prefix == PRICE_HISTORY_PREFIX)
+            });
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            sources.push(files);
+        }
+        // Footers first, so a bad file never stops a replay under way
+        let mut unreadable: Vec<(PathBuf, String)> = sources
+            .iter()
+            .flatten()
+            .filter_map(|file| {
+                validate_capture(&file.path)
+                    .err()
+                    .map(|e| (file.path.clone(), format!("{:#}", e)))
+            })
+            .collect();
+        if self.corrupt_files == CorruptFiles::Strict {
+            refuse(&unreadable)?;
+        }
+
+        let mut rows = vec![];
+        for (source, files) in sources.iter().enumerate() {
+            for file in files {
+                if unreadable.iter().any(|(path, _)| *path == file.path) {
+                    continue;
+                }
+                let index = report.files.len();
+                match self.read_file(&file.path, &file.prefix, source, index) {
+                    Ok((range, keyed)) => {
+                        report.files.push(range);
+                        rows.extend(keyed);
+                    }
+                    Err(e) => unreadable.push((file.path.clone(), format!("{:#}", e))),
+                }
+            }
+        }
+        if self.corrupt_files == CorruptFiles::Strict {
+            refuse(&unreadable)?;
+        }
+        let data_end = rows.iter().map(|r| r.timestamp).max();
+        for (source, files) in sources.iter().enumerate() {
+            for (i, file) in files.iter().enumerate() {
+                let Some((_, error)) = unreadable.iter().find(|(path, _)| *path == file.path)
+                else {
+                    continue;
+                };
+                let next = files[i + 1..]
+                    .iter()
+                    .find(|f| f.prefix == file.prefix)
+                    .and_then(|f| file_start(&f.path, &f.prefix));
+                let clamp = |at: DateTime<Utc>| {
+                    let at = self.start.map_or(at, |start| at.max(start));
+                    self.end.map_or(at, |end| at.min(end))
+                };
+                let from = file_start(&file.path, &file.prefix).map(clamp);
+                let to = next.or(self.end).or(data_end).map(clamp);
+                report.skipped.push(SkippedFile {
+                    path: file.path.clone(),
+                    prefix: file.prefix.clone(),
+                    source,
+                    error: error.clone(),
+                    from,
+                    to: to.filter(|to| from.is_some_and(|from| *to > from)).or(from),
+                });
+            }
+        }
+        report.gaps = merge_gaps(&report.skipped);
+        report.overlaps = overlaps(&report.files);
+        let captured: HashSet<Arc<str>> = rows
+            .iter()
+            .filter(|r| matches!(r.row, Row::Book(_)))
+            .map(|r| r.key.clone())
+            .collect();
+        rows.retain(|r| !matches!(r.row, Row::Point(_)) || !captured.contains(&r.key));
+
+        rows.sort_by(|a, b| {
+            a.group()
+                .cmp(&b.group())
+                .then(a.file.cmp(&b.file))
+                .then(a.index.cmp(&b.index))
+        });
+        let mut kept = vec![];
+        let mut rows = rows.into_iter().peekable();
+        while let Some(first) = rows.next() {
+            let mut group = vec![first];
+            while rows.peek().is_some_and(|r| r.group() == group[0].group()) {
+                group.extend(rows.next());
+            }
+            kept.extend(dedup(group, &mut report));
+        }
+
+        let mut books = OrderBookManager::new();
+        let events: Vec<TimedEvent> = kept
+            .into_iter()
+            .map(|keyed| {
+                let event = match keyed.row {
+                    Row::Tick(tick) => BacktestEvent::PriceTick(PriceTick {
+                        symbol: tick.symbol.to_string(),
+                        asset: String::new(),
+                        price: tick.price,
+                        timestamp: tick.timestamp,
+                        exchange_ts: tick.exchange_ts,
+                        source: TickSource::Trade,
+                    }),
+                    Row::Book(book) => {
+                        let levels = |side: &[(_, _)]| -> Vec<PriceLevel> {
+                            side.iter()
+                                .map(|&(price, size)| PriceLevel { price, size })
+                                .collect()
+                        };
+                        let merged = books.merge_update(
+                            &book.token_id,
+                            book.kind,
+                            &levels(&book.bids),
+                            &levels(&book.asks),
+                            book.timestamp,
+                        );
+                        BacktestEvent::OrderBookUpdate(merged.clone())
+                    }
+                    Row::Point(point) => {
+                        report.approximated_books += 1;
+                        let level = |price| PriceLevel {
+                            price,
+                            size: PRICE_HISTORY_DEPTH,
+                        };
+                        let bid = point.price - DEFAULT_TICK_SIZE;
+                        let bids: Vec<_> = (bid > Decimal::ZERO)
+                            .then(|| level(bid))
+                            .into_iter()
+                            .collect();
+                        let merged = books.merge_update(
+                            &point.token_id,
+                            BookUpdateKind::Snapshot,
+                            &bids,
+                            &[level(point.price)],
+                            point.ts,
+                        );
+                        BacktestEvent::OrderBookUpdate(merged.clone())
+                    }
+                };
+                (keyed.timestamp, event)
+            })
+            .collect();
+        let events = with_gaps(self.with_markets(events)?, &report.gaps);
+        Ok((events, report))
+    }
+
+    /// Markets of every source's trade journal, first source winning
+    fn markets(&self) -> anyhow::Result<Vec<Market>> {
+        let mut markets: Vec<Market> = vec![];
+        let mut seen = HashSet::new();
+        for dir in &self.sources {
+            let path = dir.join(TRADE_JOURNAL_FILE);
+            if !path.exists() {
+                continue;
+            }
+            let entries = Journal::read_all(&path)?;
+            for market in crate::report::markets_from_journal(&entries) {
+                if seen.insert(market.condition_id.clone()) {
+                    markets.push(market);
+                }
+            }
+        }
+        Ok(markets)
+    }
+
+    /// `events` with the open and close of each market overlapping the range
+    fn with_markets(&self, events: Vec<TimedEvent>) -> anyhow::Result<Vec<TimedEvent>> {
+        let markets = self.markets()?;
+        if markets.is_empty() {
+            return Ok(events);
+        }
+        // Opens rank before the rows at their timestamp, closes after
+        let mut ranked: Vec<(u8, TimedEvent)> = events.into_iter().map(|e| (1, e)).collect();
+        let in_range = |at: DateTime<Utc>| {
+            self.start.is_none_or(|start| at >= start) && self.end.is_none_or(|end| at <= end)
+        };
+        let mut replayed = 0;
+        for market in markets {
+            let open_at = self
+                .start
+                .map_or(market.open_time, |s| market.open_time.max(s));
+            if market.close_time <= open_at || !in_range(open_at) {
+                continue;
+            }
+            replayed += 1;
+            if in_range(market.close_time) {
+                ranked.push((
+                    2,
+                    (
+                        market.close_time,
+                        BacktestEvent::MarketClose(market.clone()),
+                    ),
+                ));
+            }
+            ranked.push((0, (open_at, BacktestEvent::MarketOpen(market))));
+        }
+        tracing::debug!(markets = replayed, "Replaying journaled markets");
+        ranked.sort_by_key(|(rank, (at, _))| (*at, *rank));
+        Ok(ranked.into_iter().map(|(_, event)| event).collect())
+    }
+
+    fn read_file(
+        &self,
+        path: &Path,
+        prefix: &str,
+        source: usize,
+        file: usize,
+    ) -> anyhow::Result<(FileRange, Vec<Keyed>)> {
+        let mut keyed = vec![];
+        for batch in read_batches(path)? {
+            if prefix == PRICE_TICKS_PREFIX {
+                for tick in price_ticks_from_batch(&batch)? {
+                    // The local receive time differs between machines
+                    let digest = digest(|h| tick.price.hash(h));
+                    keyed.push(Keyed {
+                        timestamp: tick.exchange_ts,
+                        rank: 0,
+                        key: tick.symbol.clone(),
+                        file,
+                        index: keyed.len(),
+                        digest,
+                        row: Row::Tick(tick),
+                    });
+                }
+            } else if prefix == PRICE_HISTORY_PREFIX {
+                for point in price_history_from_batch(&batch)? {
+                    let digest = digest(|h| point.price.hash(h));
+                    keyed.push(Keyed {
+                        timestamp: point.ts,
+                        rank: 1,
+                        key: point.token_id.clone(),
+                        file,
+                        index: keyed.len(),
+                        digest,
+                        row: Row::Point(point),
+                    });
+                }
+            } else {
+                for book in orderbooks_from_batch(&batch)? {
+                    let digest = digest(|h| {
+                        book.kind.as_str().hash(h);
+                        book.bids.hash(h);
+                        book.asks.hash(h);
+                        book.crossed.hash(h);
+                    });
+                    keyed.push(Keyed {
+                        timestamp: book.timestamp,
+                        rank: 1,
+                        key: book.token_id.clone(),
+                        file,
+                        index: keyed.len(),
+                        digest,
+                        row: Row::Book(book),
+                    });
+                }
+            }
+        }
+        let range = FileRange {
+            path: path.to_path_buf(),
+            prefix: prefix.to_string(),
+            source,
+            rows: keyed.len(),
+            first: keyed.iter().map(|k| k.timestamp).min(),
+            last: keyed.iter().map(|k| k.timestamp).max(),
+        };
+        keyed.retain(|k| {
+            self.start.is_none_or(|start| k.timestamp >= start)
+                && self.end.is_none_or(|end| k.timestamp <= end)
+        });
+        Ok((range, keyed))
+    }
+}
+
+/// Fail listing every unreadable file, if there are any
+fn refuse(unreadable: &[(PathBuf, String)]) -> anyhow::Result<()> {
+    if unreadable.is_empty() {
+        return Ok(());
+    }
+    let list: Vec<String> = unreadable
+        .iter()
+        .map(|(path, error)| format!("  {}: {}", path.display(), error))
+        .collect();
+    anyhow::bail!(
+        "{} unreadable capture files, nothing replayed (skip them with --corrupt-files skip):\n{}",
+        unreadable.len(),
+        list.join("\n")
+    )
+}
+
+/// Start time in a capture file's name, `<prefix>_<YYYYMMDD>_<HHMMSS>`
+fn file_start(path: &Path, prefix: &str) -> Option<DateTime<Utc>> {
+    let stem = path.file_stem()?.to_str()?;
+    let time = stem.strip_prefix(prefix)?.strip_prefix('_')?.get(..15)?;
+    NaiveDateTime::parse_from_str(time, "%Y%m%d_%H%M%S")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// Time spans of the skipped files, oldest first, overlapping ones merged
+fn merge_gaps(skipped: &[SkippedFile]) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut spans: Vec<_> = skipped
+        .iter()
+        .filter_map(|s| Some((s.from?, s.to?)))
+        .filter(|(from, to)| to > from)
+        .collect();
+    spans.sort();
+    let mut merged: Vec<(DateTime<Utc>, DateTime<Utc>)> = vec![];
+    for (from, to) in spans {
+        match merged.last_mut() {
+            Some(last) if from <= last.1 => last.1 = last.1.max(to),
+            _ => merged.push((from, to)),
+        }
+    }
+    merged
+}
+
+/// `events` with a [`BacktestEvent::DataGap`] at the start of each gap,
+/// ahead of the events sharing its timestamp
+fn with_gaps(events: Vec<TimedEvent>, gaps: &[(DateTime<Utc>, DateTime<Utc>)]) -> Vec<TimedEvent> {
+    let mut gaps = gaps.iter().peekable();
+    let mut out = Vec::with_capacity(events.len() + gaps.len());
+    for (at, event) in events {
+        while let Some((from, to)) = gaps.next_if(|(from, _)| *from <= at) {
+            out.push((*from, BacktestEvent::DataGap { until: *to }));
+        }
+        out.push((at, event));
+    }
+    out.extend(gaps.map(|(from, to)| (*from, BacktestEvent::DataGap { until: *to })));
+    out
+}
+
+fn digest(write: impl FnOnce(&mut DefaultHasher)) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    write(&mut hasher);
+    hasher.finish()
+}
+
+/// Keep the rows of the highest-priority file in a group sharing one
+/// timestamp and key; rows of other files are duplicates when they match
+/// one of its rows, and conflicts otherwise
+fn dedup(group: Vec<Keyed>, report: &mut MergeReport) -> Vec<Keyed> {
+    let winner = group[0].file;
+    let (kept, others): (Vec<_>, Vec<_>) = group.into_iter().partition(|k| k.file == winner);
+    let digests: Vec<u64> = kept.iter().map(|k| k.digest).collect();
+    // Each kept row absorbs at most one copy from each other file
+    let mut pools: BTreeMap<usize, Vec<u64>> = BTreeMap::new();
+    for row in others {
+        let pool = pools.entry(row.file).or_insert_with(|| digests.clone());
+        let path = report.files[row.file].path.clone();
+        match pool.iter().position(|d| *d == row.digest) {
+            Some(i) => {
+                pool.swap_remove(i);
+                *report.duplicates.entry(path).or_default() += 1;
+            }
+            None => report.conflicts.push(Conflict {
+                prefix: report.files[row.file].prefix.clone(),
+                timestamp: row.timestamp,
+                key: row.key.to_string(),
+                kept: report.files[winner].path.clone(),
+                dropped: path,
+            }),
+        }
+    }
+    kept
+}
+
+/// Pairs of files of one prefix whose time ranges intersect
+fn overlaps(files: &[FileRange]) -> Vec<Overlap> {
+    let mut found = vec![];
+    for (i, a) in files.iter().enumerate() {
+        for b in &files[i + 1..] {
+            let (Some(a_first), Some(a_last), Some(b_first), Some(b_last)) =
+                (a.first, a.last, b.first, b.last)
+            else {
+                continue;
+            };
+            if a.prefix != b.prefix || a_last < b_first || b_last < a_first {
+                continue;
+            }
+            found.push(Overlap {
+                prefix: a.prefix.clone(),
+                first: a.path.clone(),
+                second: b.path.clone(),
+                from: a_first.max(b_first),
+                to: a_last.min(b_last),
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::ParquetWriter;
+    use crate::orderbook::BookUpdateKind;
+    use chrono::Duration;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    /// Ticks at `secs`, received `recv_ms` after the exchange time
+    fn ticks(secs: &[i64], recv_ms: i64) -> Vec<PriceTickRecord> {
+        secs.iter()
+            .map(|&s| {
+                PriceTickRecord::new(
+                    at(s) + Duration::milliseconds(recv_ms),
+                    Arc::from("BTCUSDT"),
+                    Decimal::from(100_000 + s),
+                    at(s),
+                )
+            })
+            .collect()
+    }
+
+    fn book(secs: i64, bid: Decimal) -> OrderBookRecord {
+        OrderBookRecord {
+            timestamp: at(secs),
+            token_id: Arc::from("yes"),
+            bids: vec![(bid, dec!(10))],
+            asks: vec![(dec!(0.60), dec!(10))],
+            crossed: false,
+            kind: BookUpdateKind::Snapshot,
+        }
+    }
+
+    /// A capture directory holding `ticks` and `books` in one file each,
+    /// named after their first row
+    fn capture(
+        ticks: &[PriceTickRecord],
+        books: &[OrderBookRecord],
+    ) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let writer = ParquetWriter::new(dir.path().to_path_buf(), 3600);
+        if let Some(first) = ticks.first() {
+            let path = writer.file_path("price_ticks", first.exchange_ts);
+            writer.write_price_ticks(&path, ticks).unwrap();
+        }
+        if let Some(first) = books.first() {
+            let path = writer.file_path("orderbook", first.timestamp);
+            writer.write_orderbook_snapshots(&path, books).unwrap();
+        }
+        let path = dir.path().to_path_buf();
+        (dir, path)
+    }
+
+    fn summary(events: &[TimedEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|(ts, event)| match event {
+                BacktestEvent::PriceTick(t) => format!("{} tick {}", ts.timestamp(), t.price),
+                BacktestEvent::OrderBookUpdate(b) => {
+                    format!("{} book {}", ts.timestamp(), b.bids[0].price)
+                }
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_non_overlapping_captures_concatenate() {
+        let (_home, home) = capture(&ticks(&[0, 1, 2], 5), &[book(1, dec!(0.50))]);
+        let (_vps, vps) = capture(&ticks(&[3, 4], 9), &[book(4, dec!(0.51))]);
+
+        let (events, report) = CaptureLoader::new(vec![home.clone(), vps.clone()])
+            .load()
+            .unwrap();
+        assert_eq!(
+            summary(&events),
+            vec![
+                "1700000000 tick 100000",
+                "1700000001 tick 100001",
+                "1700000001 book 0.50",
+                "1700000002 tick 100002",
+                "1700000003 tick 100003",
+                "1700000004 tick 100004",
+                "1700000004 book 0.51",
+            ]
+        );
+        assert_eq!(report.files.len(), 4);
+        assert!(report.overlaps.is_empty());
+        assert_eq!(report.duplicates_removed(), 0);
+        assert!(report.conflicts.is_empty());
+
+        // The order of the sources does not change a clean merge
+        let (swapped, _) = CaptureLoader::new(vec![vps, home]).load().unwrap();
+        assert_eq!(summary(&swapped), summary(&events));
+    }
+
+    #[test]
+    fn test_exact_duplicates_in_overlapping_hours_are_dropped() {
+        // Both machines saw seconds 2-4; their receive times differ
+        let (_home, home) = capture(
+            &ticks(&[0, 1, 2, 3, 4], 5),
+            &[book(1, dec!(0.50)), book(3, dec!(0.52))],
+        );
+        let (_vps, vps) = capture(
+            &ticks(&[2, 3, 4, 5], 40),
+            &[book(3, dec!(0.52)), book(5, dec!(0.53))],
+        );
+
+        let (events, report) = CaptureLoader::new(vec![home.clone(), vps.clone()])
+            .load()
+            .unwrap();
+        let merged = summary(&events);
+        assert_eq!(merged.len(), 9);
+        assert_eq!(
+            merged.iter().filter(|e| e.contains("tick")).count(),
+            6,
+            "{:?}",
+            merged
+        );
+        assert_eq!(report.overlaps.len(), 2);
+        assert!(report
+            .overlaps
+            .iter()
+            .any(|o| o.prefix == "price_ticks" && o.from == at(2) && o.to == at(4)));
+        assert_eq!(report.duplicates_removed(), 4);
+        // Dropped from the lower-priority machine's files: 1 book, 3 ticks
+        assert!(report.duplicates.keys().all(|path| path.starts_with(&vps)));
+        assert_eq!(
+            report.duplicates.values().copied().collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert!(report.conflicts.is_empty());
+        // Shared ticks keep the higher-priority file's receive time
+        for (ts, event) in &events {
+            if let BacktestEvent::PriceTick(tick) = event {
+                if *ts <= at(4) {
+                    assert_eq!(tick.timestamp, *ts + Duration::milliseconds(5));
+                }
+            }
+        }
+
+        // Deterministic run to run
+        let (again, _) = CaptureLoader::new(vec![home, vps]).load().unwrap();
+        assert_eq!(summary(&again), merged);
+    }
+
+    #[test]
+    fn test_conflicting_rows_resolve_by_source_priority() {
+        let mut vps_ticks = ticks(&[1, 2], 40);
+        vps_ticks[1].price = dec!(99_999);
+        let (_home, home) = capture(&ticks(&[1, 2], 5), &[book(1, dec!(0.50))]);
+        let (_vps, vps) = capture(&vps_ticks, &[book(1, dec!(0.49))]);
+
+        let (events, report) = CaptureLoader::new(vec![home.clone(), vps.clone()])
+            .load()
+            .unwrap();
+        assert_eq!(
+            summary(&events),
+            vec![
+                "1700000001 tick 100001",
+                "1700000001 book 0.50",
+                "1700000002 tick 100002",
+            ]
+        );
+        assert_eq!(report.duplicates_removed(), 1);
+        assert_eq!(report.conflicts.len(), 2);
+        let tick = report
+            .conflicts
+            .iter()
+            .find(|c| c.prefix == "price_ticks")
+            .unwrap();
+        assert_eq!((tick.timestamp, tick.key.as_str()), (at(2), "BTCUSDT"));
+        assert!(tick.kept.starts_with(&home));
+        assert!(tick.dropped.starts_with(&vps));
+
+        // Swapping the priority keeps the other machine's rows
+        let (events, _) = CaptureLoader::new(vec![vps, home]).load().unwrap();
+        assert_eq!(
+            summary(&events),
+            vec![
+                "1700000001 tick 100001",
+                "1700000001 book 0.49",
+                "1700000002 tick 99999",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_price_history_stands_in_for_missing_books_only() {
+        let (_dir, dir) = capture(&ticks(&[0], 5), &[book(1, dec!(0.50))]);
+        let points: Vec<_> = [("yes", 2, dec!(0.55)), ("no", 2, dec!(0.45))]
+            .iter()
+            .map(|&(token, secs, price)| PricePointRecord {
+                token_id: Arc::from(token),
+                ts: at(secs),
+                price,
+            })
+            .collect();
+        let writer = ParquetWriter::new(dir.clone(), 3600);
+        let path = writer.file_path(PRICE_HISTORY_PREFIX, at(2));
+        writer.write_price_history(&path, &points).unwrap();
+
+        // Off by default
+        let (events, report) = CaptureLoader::new(vec![dir.clone()]).load().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(!report.low_fidelity());
+
+        let (events, report) = CaptureLoader::new(vec![dir])
+            .with_price_history(true)
+            .load()
+            .unwrap();
+        assert_eq!(
+            summary(&events),
+            vec![
+                "1700000000 tick 100000",
+                "1700000001 book 0.50",
+                "1700000002 book 0.44",
+            ]
+        );
+        let BacktestEvent::OrderBookUpdate(approximated) = &events[2].1 else {
+            panic!("expected a book");
+        };
+        assert_eq!(approximated.token_id, "no");
+        assert_eq!(approximated.asks[0].price, dec!(0.45));
+        assert_eq!(approximated.asks[0].size, PRICE_HISTORY_DEPTH);
+        assert_eq!(report.approximated_books, 1);
+        assert!(report.low_fidelity());
+        assert!(report
+            .to_string()
+            .ends_with("1 books approximated from price history"));
+    }
+
+    #[test]
+    fn test_journaled_markets_open_and_close_around_rows() {
+        let (_dir, dir) = capture(&ticks(&[0, 5, 10, 15], 0), &[]);
+        let journal = Journal::open(dir.join(TRADE_JOURNAL_FILE)).unwrap();
+        for (id, open, close) in [("early", -5, 5), ("cut", 5, 20), ("gone", -20, -10)] {
+            let opened = serde_json::json!({
+                "market_id": id,
+                "asset": "BTC",
+                "yes_token_id": format!("{}-yes", id),
+                "no_token_id": format!("{}-no", id),
+                "open_price": "100000",
+                "open_time": at(open),
+                "close_time": at(close),
+            });
+            journal.append("market_opened", &opened).unwrap();
+        }
+
+        let (events, _) = CaptureLoader::new(vec![dir])
+            .with_range(Some(at(0)), Some(at(15)))
+            .load()
+            .unwrap();
+        let markets: Vec<_> = events
+            .iter()
+            .filter_map(|(ts, event)| match event {
+                BacktestEvent::MarketOpen(m) => Some(format!(
+                    "{} open {}",
+                    ts.timestamp() - 1_700_000_000,
+                    m.condition_id
+                )),
+                BacktestEvent::MarketClose(m) => Some(format!(
+                    "{} close {}",
+                    ts.timestamp() - 1_700_000_000,
+                    m.condition_id
+                )),
+                _ => None,
+            })
+            .collect();
+        // An earlier open replays at the range start; a close past the end
+        // is left out
+        assert_eq!(markets, vec!["0 open early", "5 open cut", "5 close early"]);
+        assert!(matches!(events[0].1, BacktestEvent::MarketOpen(_)));
+        assert!(matches!(events[1].1, BacktestEvent::PriceTick(_)));
+        // The close follows the tick at its timestamp, the open precedes it
+        assert!(matches!(events[2].1, BacktestEvent::MarketOpen(_)));
+        assert!(matches!(events[3].1, BacktestEvent::PriceTick(_)));
+        assert!(matches!(events[4].1, BacktestEvent::MarketClose(_)));
+    }
+
+    #[test]
+    fn test_deduplicated_books_forward_fill_between_rows() {
+        use crate::data::{BookDedup, BookDedupConfig};
+        // A book frame a second, changing at 40s and 45s
+        let frames: Vec<OrderBookRecord> = (0..80)
+            .map(|s| match s {
+                ..40 => book(s, dec!(0.50)),
+                40..45 => book(s, dec!(0.51)),
+                _ => book(s, dec!(0.52)),
+            })
+            .collect();
+        let mut dedup = BookDedup::new(BookDedupConfig::default());
+        let rows: Vec<_> = frames
+            .iter()
+            .filter_map(|f| dedup.filter(f.clone()))
+            .collect();
+        let (_dir, dir) = capture(&[], &rows);
+
+        let (events, _) = CaptureLoader::new(vec![dir]).load().unwrap();
+        assert_eq!(
+            summary(&events),
+            vec![
+                "1700000000 book 0.50",
+                "1700000030 book 0.50",
+                "1700000040 book 0.51",
+                "1700000045 book 0.52",
+                "1700000075 book 0.52",
+            ]
+        );
+        // Holding each book until the next rebuilds every captured frame
+        for frame in &frames {
+            let (_, held) = events
+                .iter()
+                .rev()
+                .find(|(ts, _)| *ts <= frame.timestamp)
+                .unwrap();
+            let BacktestEvent::OrderBookUpdate(held) = held else {
+                panic!("{:?}", held);
+            };
+            let levels: Vec<_> = held.bids.iter().map(|l| (l.price, l.size)).collect();
+            assert_eq!(levels, frame.bids, "at {}", frame.timestamp);
+        }
+    }
+
+    /// Ticks every ten minutes over three hourly files, the middle one cut
+    /// short as by a crash mid-write
+    fn truncated_capture() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let writer = ParquetWriter::new(dir.path().to_path_buf(), 3600);
+        for hour in 0..3 {
+            let secs: Vec<i64> = (0..6).map(|i| hour * 3600 + i * 600).collect();
+            let path = writer.file_path("price_ticks", at(hour * 3600));
+            writer.write_price_ticks(&path, &ticks(&secs, 5)).unwrap();
+        }
+        let middle = writer.file_path("price_ticks", at(3600));
+        let len = std::fs::metadata(&middle).unwrap().len();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&middle)
+            .unwrap()
+            .set_len(len / 2)
+            .unwrap();
+        let path = dir.path().to_path_buf();
+        (dir, path, middle)
+    }
+
+    #[test]
+    fn test_truncated_file_is_skipped_as_a_gap() {
+        let (_dir, path, middle) = truncated_capture();
+        let (events, report) = CaptureLoader::new(vec![path.clone()]).load().unwrap();
+
+        let ticks: Vec<_> = events
+            .iter()
+            .filter(|(_, e)| matches!(e, BacktestEvent::PriceTick(_)))
+            .collect();
+        assert_eq!(ticks.len(), 12);
+        assert!(ticks
+            .iter()
+            .all(|(ts, _)| *ts < at(3600) || *ts >= at(7200)));
+        let gaps: Vec<_> = events
+            .iter()
+            .filter_map(|(ts, e)| match e {
+                BacktestEvent::DataGap { until } => Some((*ts, *until)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(gaps, vec![(at(3600), at(7200))]);
+
+        assert_eq!(report.skipped.len(), 1);
+        let skipped = &report.skipped[0];
+        assert_eq!(skipped.path, middle);
+        assert_eq!((skipped.from, skipped.to), (Some(at(3600)), Some(at(7200))));
+        assert_eq!(report.excluded(), Duration::hours(1));
+        assert!(report
+            .to_string()
+            .ends_with("1 corrupt files skipped (3600s excluded)"));
+
+        // A range starting inside the bad hour only excludes what it covers
+        let (_, report) = CaptureLoader::new(vec![path])
+            .with_range(Some(at(5400)), None)
+            .load()
+            .unwrap();
+        assert_eq!(report.skipped[0].from, Some(at(5400)));
+        assert_eq!(report.excluded(), Duration::minutes(30));
+    }
+
+    #[test]
+    fn test_strict_refuses_a_truncated_file_before_replay() {
+        let (_dir, path, middle) = truncated_capture();
+        let err = CaptureLoader::new(vec![path])
+            .with_corrupt_files(CorruptFiles::Strict)
+            .load()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(&middle.display().to_string()), "{}", err);
+        assert!(err.contains("--corrupt-files skip"), "{}", err);
+    }
+}
@@ -2,16 +2,43 @@
 //!
 //! Replays historical data with realistic execution simulation
 
+mod align;
 mod analytics;
 mod execution_model;
+mod latency;
+mod loader;
+mod progress;
 mod replay;
+mod scenario;
 mod simulator;
+mod timeline;
 
-pub use analytics::{BacktestResult, BacktestSummary};
+pub use align::{align_to_markets, AlignedRange, Alignment};
+pub use analytics::{
+    read_trade_tape, trade_tape_batch, trade_tape_schema, write_trade_tape, BacktestResult,
+    BacktestSummary, BlockBootstrap, BlockBootstrapConfig, EntryFeatures, Percentiles, Rejection,
+    TapeRow, DEFAULT_BACKTEST_SEED, DEFAULT_BOOTSTRAP_BLOCK_SECS, DEFAULT_BOOTSTRAP_RESAMPLES,
+    TRADE_TAPE_FILE, TRADE_TAPE_VERSION, TRADE_TAPE_VERSION_KEY,
+};
 pub use execution_model::QueueSimulator;
+pub use latency::{
+    format_exit_comparison, format_sweep_table, write_sweep_csv, ExitPolicyComparison,
+    LatencyPointResult, LatencySweep,
+};
+pub use loader::{
+    CaptureLoader, Conflict, CorruptFiles, FileRange, MergeReport, Overlap, SkippedFile,
+    PRICE_HISTORY_DEPTH,
+};
+pub use progress::{BacktestProgress, ProgressSink, ProgressTracker, DEFAULT_PROGRESS_INTERVAL};
 pub use replay::{BacktestEvent, EventStream};
+pub use scenario::{
+    Perturbation, Scenario, ScenarioEvent, ScenarioSummary, ScenarioTracker, ScenarioWindow,
+    WindowRisk,
+};
 pub use simulator::BacktestSimulator;
+pub use timeline::BookTimeline;
 
+use crate::data::IncludeCurrent;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use std::path::PathBuf;
@@ -21,6 +48,18 @@ use std::path::PathBuf;
 pub struct BacktestConfig {
     /// Directory containing Parquet data files
     pub data_dir: PathBuf,
+    /// More capture directories merged in, lower priority than `data_dir`
+    pub merge_dirs: Vec<PathBuf>,
+    /// Approximate missing books from backfilled price history
+    pub price_history: bool,
+    /// Synthetic perturbations applied on top of the loaded data
+    pub scenario: Option<Scenario>,
+    /// Whether to trade only markets wholly inside the range
+    pub align: Alignment,
+    /// Whether unreadable capture files are skipped as gaps or fail the run
+    pub corrupt_files: CorruptFiles,
+    /// Whether capture files still being written are left out or waited for
+    pub include_current: IncludeCurrent,
     /// Start time filter
     pub start_time: Option<DateTime<Utc>>,
     /// End time filter
@@ -29,6 +68,10 @@ pub struct BacktestConfig {
     pub initial_capital: Decimal,
     /// Simulated order latency in ms
     pub latency_ms: u64,
+    /// Delay before book updates are visible to the strategy, in ms
+    pub book_staleness_ms: u64,
     /// Fee rate
     pub fee_rate: Decimal,
+    /// Block bootstrap of the trades, seeded by the backtest seed
+    pub bootstrap: BlockBootstrapConfig,
 }
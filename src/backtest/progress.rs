@@ -0,0 +1,185 @@
+//! Backtest progress reporting
+//!
+//! The simulator reports progress through the [`ProgressSink`] trait so the
+//! CLI, tests, and any future daemon/RPC path can all consume the same stream.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Default minimum interval between progress updates
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Point-in-time progress of a running backtest
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestProgress {
+    /// Events processed so far
+    pub events_processed: u64,
+    /// Timestamp of the most recently processed event
+    pub sim_time: Option<DateTime<Utc>>,
+    /// Simulated time covered so far in seconds
+    pub sim_elapsed_secs: i64,
+    /// Wall-clock time spent so far in seconds
+    pub wall_elapsed_secs: f64,
+    /// Trades executed so far
+    pub trades: usize,
+    /// Event throughput (events per wall-clock second)
+    pub events_per_sec: f64,
+    /// Whether this is the final update of the run
+    pub finished: bool,
+}
+
+/// Consumer of backtest progress updates
+pub trait ProgressSink: Send + Sync {
+    /// Called with the latest progress snapshot
+    fn on_progress(&self, progress: &BacktestProgress);
+}
+
+impl<F> ProgressSink for F
+where
+    F: Fn(&BacktestProgress) + Send + Sync,
+{
+    fn on_progress(&self, progress: &BacktestProgress) {
+        self(progress)
+    }
+}
+
+/// Forwards progress over a channel, dropping updates if the receiver lags
+impl ProgressSink for mpsc::Sender<BacktestProgress> {
+    fn on_progress(&self, progress: &BacktestProgress) {
+        let _ = self.try_send(progress.clone());
+    }
+}
+
+/// Accumulates progress counters and rate-limits reporting
+pub struct ProgressTracker {
+    min_interval: Duration,
+    started: Instant,
+    last_report: Option<Instant>,
+    first_sim_time: Option<DateTime<Utc>>,
+    progress: BacktestProgress,
+}
+
+impl ProgressTracker {
+    /// Create a tracker that reports at most once per `min_interval`
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            started: Instant::now(),
+            last_report: None,
+            first_sim_time: None,
+            progress: BacktestProgress {
+                events_processed: 0,
+                sim_time: None,
+                sim_elapsed_secs: 0,
+                wall_elapsed_secs: 0.0,
+                trades: 0,
+                events_per_sec: 0.0,
+                finished: false,
+            },
+        }
+    }
+
+    /// Record one processed event
+    pub fn record_event(&mut self, timestamp: DateTime<Utc>) {
+        self.progress.events_processed += 1;
+        self.progress.sim_time = Some(timestamp);
+        let first = *self.first_sim_time.get_or_insert(timestamp);
+        self.progress.sim_elapsed_secs = (timestamp - first).num_seconds();
+    }
+
+    /// Update the trade count
+    pub fn set_trades(&mut self, trades: usize) {
+        self.progress.trades = trades;
+    }
+
+    /// Current progress snapshot
+    pub fn snapshot(&self) -> BacktestProgress {
+        let mut progress = self.progress.clone();
+        let wall = self.started.elapsed().as_secs_f64();
+        progress.wall_elapsed_secs = wall;
+        progress.events_per_sec = if wall > 0.0 {
+            progress.events_processed as f64 / wall
+        } else {
+            0.0
+        };
+        progress
+    }
+
+    /// Report to the sink if the minimum interval has passed
+    pub fn maybe_report(&mut self, sink: &dyn ProgressSink) {
+        let now = Instant::now();
+        let due = match self.last_report {
+            None => true,
+            Some(last) => now.duration_since(last) >= self.min_interval,
+        };
+        if due {
+            self.last_report = Some(now);
+            sink.on_progress(&self.snapshot());
+        }
+    }
+
+    /// Report the final snapshot unconditionally
+    pub fn finish(&mut self, sink: &dyn ProgressSink) {
+        self.progress.finished = true;
+        sink.on_progress(&self.snapshot());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as ChronoDuration;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_tracker_counts_events() {
+        let mut tracker = ProgressTracker::new(Duration::ZERO);
+        let start = Utc::now();
+        tracker.record_event(start);
+        tracker.record_event(start + ChronoDuration::seconds(30));
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.events_processed, 2);
+        assert_eq!(snapshot.sim_elapsed_secs, 30);
+        assert!(!snapshot.finished);
+    }
+
+    #[test]
+    fn test_tracker_throttles_reports() {
+        let mut tracker = ProgressTracker::new(Duration::from_secs(3600));
+        let calls = Mutex::new(0);
+        let sink = |_: &BacktestProgress| *calls.lock().unwrap() += 1;
+
+        for _ in 0..100 {
+            tracker.record_event(Utc::now());
+            tracker.maybe_report(&sink);
+        }
+        // Only the first report gets through within the interval
+        assert_eq!(*calls.lock().unwrap(), 1);
+
+        tracker.finish(&sink);
+        assert_eq!(*calls.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_channel_sink() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let mut tracker = ProgressTracker::new(Duration::ZERO);
+        tracker.record_event(Utc::now());
+        tracker.finish(&tx);
+
+        let progress = rx.recv().await.unwrap();
+        assert_eq!(progress.events_processed, 1);
+        assert!(progress.finished);
+    }
+
+    #[test]
+    fn test_progress_serializes_to_json() {
+        let tracker = ProgressTracker::new(Duration::ZERO);
+        let json = serde_json::to_string(&tracker.snapshot()).unwrap();
+        assert!(json.contains("events_processed"));
+        assert!(json.contains("events_per_sec"));
+    }
+}
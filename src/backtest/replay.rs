@@ -1,9 +1,14 @@
 //! Event-driven replay from Parquet files
 
+use super::align::{align_to_markets, AlignedRange, Alignment};
+use super::loader::{CaptureLoader, CorruptFiles, MergeReport};
+use super::scenario::{Scenario, ScenarioEvent, ScenarioWindow};
+use crate::data::IncludeCurrent;
 use crate::feed::PriceTick;
 use crate::market::Market;
-use crate::orderbook::OrderBook;
+use crate::orderbook::{OrderBook, PriceLevel};
 use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
 use std::path::PathBuf;
 
 /// Backtest event types
@@ -17,14 +22,58 @@ pub enum BacktestEvent {
     MarketOpen(Market),
     /// Market closed/settled
     MarketClose(Market),
+    /// No capture data from this event until `until`, e.g. a corrupt file
+    /// was skipped; detectors start over and nothing is traded across it
+    DataGap { until: DateTime<Utc> },
+}
+
+impl BacktestEvent {
+    /// Approximate bytes this event holds, inline and on the heap
+    pub fn estimated_bytes(&self) -> usize {
+        let heap = match self {
+            BacktestEvent::PriceTick(tick) => tick.symbol.capacity() + tick.asset.capacity(),
+            BacktestEvent::OrderBookUpdate(book) => {
+                book.token_id.capacity()
+                    + (book.bids.capacity() + book.asks.capacity())
+                        * std::mem::size_of::<PriceLevel>()
+            }
+            BacktestEvent::MarketOpen(market) | BacktestEvent::MarketClose(market) => {
+                market.condition_id.capacity()
+                    + market.asset.capacity()
+                    + market.yes_token_id.capacity()
+                    + market.no_token_id.capacity()
+                    + market.group_id.as_ref().map_or(0, String::capacity)
+            }
+            BacktestEvent::DataGap { .. } => 0,
+        };
+        std::mem::size_of::<Self>() + heap
+    }
 }
 
 /// Merges multiple data sources and yields events in timestamp order
-#[allow(dead_code)]
 pub struct EventStream {
     data_dir: PathBuf,
+    /// Lower-priority capture directories merged into `data_dir`'s
+    merge_dirs: Vec<PathBuf>,
+    /// Approximate missing books from price history files
+    price_history: bool,
+    /// Perturbations applied once loaded
+    scenario: Option<Scenario>,
+    /// Market boundaries trading is snapped to
+    align: Alignment,
+    /// What to do with capture files that cannot be read
+    corrupt_files: CorruptFiles,
+    /// What to do with capture files still being written
+    include_current: IncludeCurrent,
     start_time: Option<DateTime<Utc>>,
     end_time: Option<DateTime<Utc>>,
+    /// Loaded on the first call to `next`
+    events: Option<VecDeque<ScenarioEvent>>,
+    report: Option<MergeReport>,
+    windows: Vec<ScenarioWindow>,
+    aligned: Option<AlignedRange>,
+    /// Estimated size of the replay queue when loading completed
+    loaded_bytes: Option<u64>,
 }
 
 impl EventStream {
@@ -36,15 +85,155 @@ impl EventStream {
     ) -> Self {
         Self {
             data_dir,
+            merge_dirs: vec![],
+            price_history: false,
+            scenario: None,
+            align: Alignment::None,
+            corrupt_files: CorruptFiles::Skip,
+            include_current: IncludeCurrent::Skip,
             start_time,
             end_time,
+            events: None,
+            report: None,
+            windows: vec![],
+            aligned: None,
+            loaded_bytes: None,
         }
     }
 
-    /// Get next event in timestamp order
-    fn next_event(&mut self) -> Option<(DateTime<Utc>, BacktestEvent)> {
-        // TODO: Implement Parquet reading and event merging
-        None
+    /// Also replay `dirs`, dropping rows `data_dir` or an earlier dir
+    /// already holds; see [`CaptureLoader`]
+    pub fn with_merge_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.merge_dirs = dirs;
+        self
+    }
+
+    /// Approximate books from price history for tokens with no captured
+    /// books; see [`CaptureLoader::with_price_history`]
+    pub fn with_price_history(mut self, enabled: bool) -> Self {
+        self.price_history = enabled;
+        self
+    }
+
+    /// Apply `scenario` on top of the loaded events; see [`Scenario`]
+    pub fn with_scenario(mut self, scenario: Option<Scenario>) -> Self {
+        self.scenario = scenario;
+        self
+    }
+
+    /// Trade only markets wholly inside the time range; see
+    /// [`align_to_markets`]
+    pub fn with_alignment(mut self, align: Alignment) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Skip unreadable capture files as data gaps, or refuse to load;
+    /// see [`CorruptFiles`]
+    pub fn with_corrupt_files(mut self, corrupt_files: CorruptFiles) -> Self {
+        self.corrupt_files = corrupt_files;
+        self
+    }
+
+    /// Leave out capture files still being written, or wait for them;
+    /// see [`IncludeCurrent`]
+    pub fn with_include_current(mut self, include_current: IncludeCurrent) -> Self {
+        self.include_current = include_current;
+        self
+    }
+
+    /// The range trading was aligned to, once loading has started
+    pub fn aligned_range(&self) -> Option<&AlignedRange> {
+        self.aligned.as_ref()
+    }
+
+    /// Periods the scenario perturbed, once loading has started
+    pub fn scenario_windows(&self) -> &[ScenarioWindow] {
+        &self.windows
+    }
+
+    /// Overlaps, duplicates and conflicts found, once loading has started
+    pub fn report(&self) -> Option<&MergeReport> {
+        self.report.as_ref()
+    }
+
+    /// Estimated memory held by the loaded events in bytes, once loading
+    /// has completed
+    pub fn loaded_bytes(&self) -> Option<u64> {
+        self.loaded_bytes
+    }
+
+    /// Load the captures now rather than on the first event, failing on
+    /// what iteration would only log, such as corrupt files in strict mode
+    pub fn load(&mut self) -> anyhow::Result<()> {
+        if self.events.is_some() {
+            return Ok(());
+        }
+        let mut sources = vec![self.data_dir.clone()];
+        sources.extend(self.merge_dirs.iter().cloned());
+        let loader = CaptureLoader::new(sources)
+            .with_range(self.start_time, self.end_time)
+            .with_price_history(self.price_history)
+            .with_corrupt_files(self.corrupt_files)
+            .with_include_current(self.include_current);
+        let (events, report) = loader.load()?;
+        report.log();
+        self.report = Some(report);
+        self.prepare(events);
+        Ok(())
+    }
+
+    /// [`Self::load`] on a blocking thread, for async callers: waiting for
+    /// files to seal under [`IncludeCurrent::Wait`] sleeps
+    pub async fn load_blocking(mut self) -> anyhow::Result<Self> {
+        tokio::task::spawn_blocking(move || self.load().map(|()| self))
+            .await
+            .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
+    }
+
+    /// Next event in timestamp order, tagged when a scenario injected it
+    pub fn next_tagged(&mut self) -> Option<ScenarioEvent> {
+        if self.events.is_none() {
+            if let Err(e) = self.load() {
+                tracing::warn!(error = %e, data_dir = ?self.data_dir, "Failed to load captures");
+                self.prepare(vec![]);
+            }
+        }
+        self.events.as_mut()?.pop_front()
+    }
+
+    /// Align and perturb loaded `events` into the replay queue
+    fn prepare(&mut self, events: Vec<(DateTime<Utc>, BacktestEvent)>) {
+        let events = match self.align {
+            Alignment::Market => {
+                let (events, range) = align_to_markets(events, self.start_time, self.end_time);
+                self.aligned = Some(range);
+                events
+            }
+            Alignment::None => events,
+        };
+        let events = match &self.scenario {
+            Some(scenario) => {
+                let (events, windows) = scenario.apply(events);
+                self.windows = windows;
+                events
+            }
+            None => events
+                .into_iter()
+                .map(|(at, event)| ScenarioEvent {
+                    at,
+                    event,
+                    injected: false,
+                })
+                .collect(),
+        };
+        let queued = std::mem::size_of::<ScenarioEvent>() - std::mem::size_of::<BacktestEvent>();
+        let bytes: usize = events
+            .iter()
+            .map(|e| queued + e.event.estimated_bytes())
+            .sum();
+        self.loaded_bytes = Some(bytes as u64);
+        self.events = Some(events.into());
     }
 }
 
@@ -52,13 +241,14 @@ impl Iterator for EventStream {
     type Item = (DateTime<Utc>, BacktestEvent);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.next_event()
+        self.next_tagged().map(|e| (e.at, e.event))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::feed::TickSource;
     use rust_decimal_macros::dec;
     use std::path::PathBuf;
 
@@ -86,13 +276,75 @@ mod tests {
         assert!(stream.next().is_none());
     }
 
+    #[test]
+    fn test_loaded_bytes_grow_with_book_depth() {
+        use crate::orderbook::PriceLevel;
+
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let level = PriceLevel {
+            price: dec!(0.5),
+            size: dec!(10),
+        };
+        let mut shallow = OrderBook::new("yes");
+        shallow.asks = vec![level.clone()];
+        let mut deep = OrderBook::new("yes");
+        deep.asks = vec![level; 100];
+
+        let loaded = |book: OrderBook| {
+            let mut stream = EventStream::new(PathBuf::from("./nonexistent"), None, None);
+            assert!(stream.loaded_bytes().is_none());
+            stream.prepare(vec![(at, BacktestEvent::OrderBookUpdate(book))]);
+            stream.loaded_bytes().unwrap()
+        };
+        let (shallow, deep) = (loaded(shallow), loaded(deep));
+        assert!(shallow >= std::mem::size_of::<BacktestEvent>() as u64);
+        assert_eq!(
+            deep - shallow,
+            99 * std::mem::size_of::<PriceLevel>() as u64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_waiting_for_a_seal_leaves_the_runtime_free() {
+        use crate::data::{price_tick_batch, sink_for, DataFormat, ParquetTuning, PriceTickRecord};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().to_path_buf();
+        let at = DateTime::from_timestamp(1_735_689_600, 0).unwrap();
+        let ticks = vec![PriceTickRecord::new(at, "BTCUSDT".into(), dec!(100000), at)];
+        let batch = price_tick_batch(&ticks).unwrap();
+        let mut sink = sink_for(DataFormat::Parquet, dir.clone(), &ParquetTuning::default());
+        sink.open("price_ticks", at, batch.schema()).unwrap();
+        sink.write_batch(&batch).unwrap();
+
+        // Sealed by a task on the same single-threaded runtime, which a
+        // load sleeping on it would starve
+        let sealer = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+            sink.close().unwrap();
+        });
+        let started = std::time::Instant::now();
+        let stream = EventStream::new(dir, None, None)
+            .with_include_current(IncludeCurrent::Wait)
+            .load_blocking()
+            .await
+            .unwrap();
+        sealer.await.unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        let snapshot = &stream.report().unwrap().snapshots[0];
+        assert!(snapshot.in_progress.is_empty());
+        assert_eq!(snapshot.files.len(), 1);
+    }
+
     #[test]
     fn test_backtest_event_price_tick() {
         let tick = PriceTick {
             symbol: "BTCUSDT".to_string(),
+            asset: "BTC".to_string(),
             price: dec!(42000),
             timestamp: Utc::now(),
             exchange_ts: Utc::now(),
+            source: TickSource::Trade,
         };
 
         let event = BacktestEvent::PriceTick(tick.clone());
@@ -124,11 +376,14 @@ mod tests {
     fn test_backtest_event_market_open() {
         let market = Market {
             condition_id: "cond".to_string(),
+            asset: "BTC".to_string(),
             yes_token_id: "yes".to_string(),
             no_token_id: "no".to_string(),
             open_price: dec!(100000),
             open_time: Utc::now(),
             close_time: Utc::now(),
+            group_id: None,
+            orientation: Default::default(),
         };
 
         let event = BacktestEvent::MarketOpen(market);
@@ -139,11 +394,14 @@ mod tests {
     fn test_backtest_event_market_close() {
         let market = Market {
             condition_id: "cond".to_string(),
+            asset: "BTC".to_string(),
             yes_token_id: "yes".to_string(),
             no_token_id: "no".to_string(),
             open_price: dec!(100000),
             open_time: Utc::now(),
             close_time: Utc::now(),
+            group_id: None,
+            orientation: Default::default(),
         };
 
         let event = BacktestEvent::MarketClose(market);
@@ -154,9 +412,11 @@ mod tests {
     fn test_backtest_event_clone() {
         let tick = PriceTick {
             symbol: "BTCUSDT".to_string(),
+            asset: "BTC".to_string(),
             price: dec!(42000),
             timestamp: Utc::now(),
             exchange_ts: Utc::now(),
+            source: TickSource::Trade,
         };
 
         let event = BacktestEvent::PriceTick(tick);
@@ -0,0 +1,733 @@
+//! Synthetic adverse scenarios injected into replayed data
+//!
+//! A scenario file lists perturbations applied on top of the loaded events
+//! before replay: a price gap, a feed outage, a book wiped of its asks, or
+//! book updates arriving late. Every event a perturbation adds or changes is
+//! tagged as injected, and each perturbation spans a window, so a report can
+//! tell injected periods from organic ones. [`ScenarioTracker`] attributes
+//! what the risk layer did, halts, stale books and withheld entries, to the
+//! window it happened in.
+//!
+//! ```toml
+//! [[perturbation]]
+//! kind = "gap"
+//! at = "2026-01-05T12:00:00Z"
+//! percent = -2.0
+//!
+//! [[perturbation]]
+//! kind = "book_wipe"
+//! at = "2026-01-05T12:03:00Z"
+//! duration_secs = 20
+//! ```
+
+use super::BacktestEvent;
+use crate::engine::EngineStats;
+use crate::orderbook::OrderBook;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// An event and the time it is replayed at
+type TimedEvent = (DateTime<Utc>, BacktestEvent);
+
+/// One perturbation, starting at `at`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Perturbation {
+    /// Move every later tick by `percent`, for `duration_secs` or for good
+    Gap {
+        at: DateTime<Utc>,
+        percent: Decimal,
+        #[serde(default)]
+        duration_secs: Option<u64>,
+        /// Only this symbol's ticks; every symbol when unset
+        #[serde(default)]
+        symbol: Option<String>,
+    },
+    /// Drop every tick for `duration_secs`
+    Outage {
+        at: DateTime<Utc>,
+        duration_secs: u64,
+        #[serde(default)]
+        symbol: Option<String>,
+    },
+    /// Remove every ask for `duration_secs`, then restore the book
+    BookWipe {
+        at: DateTime<Utc>,
+        duration_secs: u64,
+        /// Only this token's book; every token when unset
+        #[serde(default)]
+        token: Option<String>,
+    },
+    /// Replay book updates `delay_ms` late for `duration_secs`
+    BookDelay {
+        at: DateTime<Utc>,
+        duration_secs: u64,
+        delay_ms: u64,
+        #[serde(default)]
+        token: Option<String>,
+    },
+}
+
+impl Perturbation {
+    /// Name used in reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Perturbation::Gap { .. } => "gap",
+            Perturbation::Outage { .. } => "outage",
+            Perturbation::BookWipe { .. } => "book_wipe",
+            Perturbation::BookDelay { .. } => "book_delay",
+        }
+    }
+
+    /// Start and end of the perturbed period; an open-ended gap runs just
+    /// past `last`
+    fn window(&self, last: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let span = |at: DateTime<Utc>, secs: u64| (at, at + Duration::seconds(secs as i64));
+        match self {
+            Perturbation::Gap {
+                at,
+                duration_secs: Some(secs),
+                ..
+            } => span(*at, *secs),
+            Perturbation::Gap { at, .. } => (*at, (last + Duration::milliseconds(1)).max(*at)),
+            Perturbation::Outage {
+                at, duration_secs, ..
+            }
+            | Perturbation::BookWipe {
+                at, duration_secs, ..
+            }
+            | Perturbation::BookDelay {
+                at, duration_secs, ..
+            } => span(*at, *duration_secs),
+        }
+    }
+}
+
+/// A replayed event, tagged when a perturbation added or changed it
+#[derive(Debug, Clone)]
+pub struct ScenarioEvent {
+    pub at: DateTime<Utc>,
+    pub event: BacktestEvent,
+    pub injected: bool,
+}
+
+/// The period one perturbation covered and what it did to the stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScenarioWindow {
+    pub kind: String,
+    pub from: DateTime<Utc>,
+    /// Exclusive end
+    pub to: DateTime<Utc>,
+    /// Events added or changed
+    pub injected: usize,
+    /// Events removed
+    pub dropped: usize,
+}
+
+impl ScenarioWindow {
+    /// Whether `at` falls inside the window
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        at >= self.from && at < self.to
+    }
+}
+
+/// Perturbations read from a scenario file, applied in order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    #[serde(default, rename = "perturbation")]
+    pub perturbations: Vec<Perturbation>,
+}
+
+impl Scenario {
+    /// Read a TOML scenario file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&text)?)
+    }
+
+    /// Apply every perturbation to `events`, oldest first, returning the
+    /// tagged events in replay order and one window per perturbation
+    pub fn apply(&self, events: Vec<TimedEvent>) -> (Vec<ScenarioEvent>, Vec<ScenarioWindow>) {
+        let mut events: Vec<ScenarioEvent> = events
+            .into_iter()
+            .map(|(at, event)| ScenarioEvent {
+                at,
+                event,
+                injected: false,
+            })
+            .collect();
+        let last = events.iter().map(|e| e.at).max().unwrap_or_default();
+        let mut windows = vec![];
+        for perturbation in &self.perturbations {
+            let (from, to) = perturbation.window(last);
+            let (injected, dropped) = match perturbation {
+                Perturbation::Gap {
+                    percent, symbol, ..
+                } => (gap(&mut events, from, to, *percent, symbol.as_deref()), 0),
+                Perturbation::Outage { symbol, .. } => {
+                    (0, outage(&mut events, from, to, symbol.as_deref()))
+                }
+                Perturbation::BookWipe { token, .. } => {
+                    (book_wipe(&mut events, from, to, token.as_deref()), 0)
+                }
+                Perturbation::BookDelay {
+                    delay_ms, token, ..
+                } => (
+                    book_delay(&mut events, from, to, *delay_ms, token.as_deref()),
+                    0,
+                ),
+            };
+            // Stable, so simultaneous events keep their order
+            events.sort_by_key(|e| e.at);
+            windows.push(ScenarioWindow {
+                kind: perturbation.name().to_string(),
+                from,
+                to,
+                injected,
+                dropped,
+            });
+        }
+        (events, windows)
+    }
+}
+
+fn in_window(at: DateTime<Utc>, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+    at >= from && at < to
+}
+
+fn gap(
+    events: &mut [ScenarioEvent],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    percent: Decimal,
+    symbol: Option<&str>,
+) -> usize {
+    let factor = Decimal::ONE + percent / Decimal::ONE_HUNDRED;
+    let mut changed = 0;
+    for e in events.iter_mut() {
+        if !in_window(e.at, from, to) {
+            continue;
+        }
+        if let BacktestEvent::PriceTick(tick) = &mut e.event {
+            if symbol.is_none_or(|s| tick.symbol == s) {
+                tick.price *= factor;
+                e.injected = true;
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
+
+fn outage(
+    events: &mut Vec<ScenarioEvent>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    symbol: Option<&str>,
+) -> usize {
+    let before = events.len();
+    events.retain(|e| match &e.event {
+        BacktestEvent::PriceTick(tick) => {
+            !in_window(e.at, from, to) || symbol.is_some_and(|s| tick.symbol != s)
+        }
+        _ => true,
+    });
+    before - events.len()
+}
+
+fn book_wipe(
+    events: &mut Vec<ScenarioEvent>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    token: Option<&str>,
+) -> usize {
+    let matches = |book: &OrderBook| token.is_none_or(|t| book.token_id == t);
+    let wiped = |book: &OrderBook, at| {
+        let mut book = book.clone();
+        book.asks.clear();
+        book.updated_at = at;
+        book
+    };
+    // The book each token had going in, and the last one seen inside
+    let mut before: HashMap<String, OrderBook> = HashMap::new();
+    let mut inside: HashMap<String, OrderBook> = HashMap::new();
+    let mut changed = 0;
+    for e in events.iter_mut() {
+        let BacktestEvent::OrderBookUpdate(book) = &mut e.event else {
+            continue;
+        };
+        if !matches(book) || e.at >= to {
+            continue;
+        }
+        if e.at < from {
+            before.insert(book.token_id.clone(), book.clone());
+            continue;
+        }
+        inside.insert(book.token_id.clone(), book.clone());
+        book.asks.clear();
+        e.injected = true;
+        changed += 1;
+    }
+    let mut added = vec![];
+    for (token, book) in &before {
+        added.push(ScenarioEvent {
+            at: from,
+            event: BacktestEvent::OrderBookUpdate(wiped(book, from)),
+            injected: true,
+        });
+        // A token with no update inside gets its old book back
+        if !inside.contains_key(token) {
+            let mut restored = book.clone();
+            restored.updated_at = to;
+            added.push(ScenarioEvent {
+                at: to,
+                event: BacktestEvent::OrderBookUpdate(restored),
+                injected: true,
+            });
+        }
+    }
+    for book in inside.into_values() {
+        let mut restored = book;
+        restored.updated_at = to;
+        added.push(ScenarioEvent {
+            at: to,
+            event: BacktestEvent::OrderBookUpdate(restored),
+            injected: true,
+        });
+    }
+    // Deterministic across runs
+    added.sort_by(|a, b| (a.at, book_token(a)).cmp(&(b.at, book_token(b))));
+    changed += added.len();
+    events.extend(added);
+    changed
+}
+
+fn book_token(e: &ScenarioEvent) -> &str {
+    match &e.event {
+        BacktestEvent::OrderBookUpdate(book) => &book.token_id,
+        _ => "",
+    }
+}
+
+fn book_delay(
+    events: &mut [ScenarioEvent],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    delay_ms: u64,
+    token: Option<&str>,
+) -> usize {
+    let delay = Duration::milliseconds(delay_ms as i64);
+    let mut changed = 0;
+    for e in events.iter_mut() {
+        let BacktestEvent::OrderBookUpdate(book) = &e.event else {
+            continue;
+        };
+        if in_window(e.at, from, to) && token.is_none_or(|t| book.token_id == t) {
+            // The book keeps its own timestamp, so it arrives stale
+            e.at += delay;
+            e.injected = true;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// What the risk layer did over one period
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WindowRisk {
+    /// Events replayed
+    pub events: u64,
+    /// Events injected by a perturbation
+    pub injected: u64,
+    pub fills: u64,
+    pub rejected: u64,
+    pub stale_books: u64,
+    /// Entries withheld by a loss cooldown or loss halt
+    pub cooled_down: u64,
+    pub rate_capped: u64,
+    /// Orders suppressed while the execution circuit was open
+    pub suppressed: u64,
+    /// Hard halts raised
+    pub halts: u64,
+}
+
+impl WindowRisk {
+    /// Add the change from `before` to `after`
+    fn add_delta(&mut self, before: &EngineStats, after: &EngineStats) {
+        self.fills += after.fills - before.fills;
+        self.rejected += after.rejected - before.rejected;
+        self.stale_books += after.stale_books - before.stale_books;
+        self.cooled_down += after.cooled_down - before.cooled_down;
+        self.rate_capped += after.rate_capped - before.rate_capped;
+        self.suppressed += after.suppressed - before.suppressed;
+        if before.halt.is_none() && after.halt.is_some() {
+            self.halts += 1;
+        }
+    }
+}
+
+/// Attributes engine activity to the scenario windows it happened in
+#[derive(Debug, Clone)]
+pub struct ScenarioTracker {
+    windows: Vec<ScenarioWindow>,
+    risk: Vec<WindowRisk>,
+    organic: WindowRisk,
+    last: EngineStats,
+}
+
+impl ScenarioTracker {
+    /// Track `windows`, starting from the engine's current `stats`
+    pub fn new(windows: Vec<ScenarioWindow>, stats: &EngineStats) -> Self {
+        Self {
+            risk: vec![WindowRisk::default(); windows.len()],
+            windows,
+            organic: WindowRisk::default(),
+            last: stats.clone(),
+        }
+    }
+
+    /// Record the engine's `stats` after processing the event at `at`
+    pub fn observe(&mut self, at: DateTime<Utc>, injected: bool, stats: &EngineStats) {
+        let mut organic = true;
+        for (window, risk) in self.windows.iter().zip(&mut self.risk) {
+            if window.contains(at) {
+                organic = false;
+                risk.events += 1;
+                risk.injected += injected as u64;
+                risk.add_delta(&self.last, stats);
+            }
+        }
+        if organic {
+            self.organic.events += 1;
+            self.organic.add_delta(&self.last, stats);
+        }
+        self.last = stats.clone();
+    }
+
+    /// Per-window and organic activity
+    pub fn summary(&self) -> ScenarioSummary {
+        ScenarioSummary {
+            windows: self
+                .windows
+                .iter()
+                .cloned()
+                .zip(self.risk.iter().cloned())
+                .collect(),
+            organic: self.organic.clone(),
+        }
+    }
+}
+
+/// Risk activity inside each scenario window and outside all of them
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioSummary {
+    pub windows: Vec<(ScenarioWindow, WindowRisk)>,
+    pub organic: WindowRisk,
+}
+
+impl fmt::Display for ScenarioSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "  {:<12} {:<20} {:>6} {:>8} {:>6} {:>8} {:>6} {:>8} {:>6}",
+            "Window", "From", "Secs", "Events", "Fills", "Rejected", "Stale", "Withheld", "Halts"
+        )?;
+        let row =
+            |f: &mut fmt::Formatter<'_>, name: &str, from: String, secs: String, r: &WindowRisk| {
+                writeln!(
+                    f,
+                    "  {:<12} {:<20} {:>6} {:>8} {:>6} {:>8} {:>6} {:>8} {:>6}",
+                    name,
+                    from,
+                    secs,
+                    r.events,
+                    r.fills,
+                    r.rejected,
+                    r.stale_books,
+                    r.cooled_down + r.rate_capped + r.suppressed,
+                    r.halts
+                )
+            };
+        for (window, risk) in &self.windows {
+            row(
+                f,
+                &window.kind,
+                window.from.format("%Y-%m-%d %H:%M:%S").to_string(),
+                (window.to - window.from).num_seconds().to_string(),
+                risk,
+            )?;
+        }
+        row(
+            f,
+            "organic",
+            "-".to_string(),
+            "-".to_string(),
+            &self.organic,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::{PriceTick, TickSource};
+    use crate::orderbook::PriceLevel;
+    use rust_decimal_macros::dec;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn tick(secs: i64, price: Decimal) -> TimedEvent {
+        (
+            at(secs),
+            BacktestEvent::PriceTick(PriceTick {
+                symbol: "BTCUSDT".to_string(),
+                asset: "BTC".to_string(),
+                price,
+                timestamp: at(secs),
+                exchange_ts: at(secs),
+                source: TickSource::Trade,
+            }),
+        )
+    }
+
+    fn book(secs: i64, token: &str, ask: Decimal) -> TimedEvent {
+        let level = |price| PriceLevel {
+            price,
+            size: dec!(10),
+        };
+        (
+            at(secs),
+            BacktestEvent::OrderBookUpdate(OrderBook {
+                token_id: token.to_string(),
+                bids: vec![level(ask - dec!(0.02))],
+                asks: vec![level(ask)],
+                updated_at: at(secs),
+            }),
+        )
+    }
+
+    /// Ticks each second from 0 to 9, a `yes` book at 1, 4 and 8 and a
+    /// `no` book at 2
+    fn fixture() -> Vec<TimedEvent> {
+        let mut events: Vec<_> = (0..10).map(|s| tick(s, dec!(100000))).collect();
+        events.extend([
+            book(1, "yes", dec!(0.60)),
+            book(2, "no", dec!(0.42)),
+            book(4, "yes", dec!(0.61)),
+            book(8, "yes", dec!(0.62)),
+        ]);
+        events.sort_by_key(|(ts, _)| *ts);
+        events
+    }
+
+    fn scenario(toml: &str) -> Scenario {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn summary(events: &[ScenarioEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|e| {
+                let mark = if e.injected { "*" } else { "" };
+                match &e.event {
+                    BacktestEvent::PriceTick(t) => {
+                        let secs = e.at.timestamp() - 1_700_000_000;
+                        format!("{}{} tick {}", mark, secs, t.price.normalize())
+                    }
+                    BacktestEvent::OrderBookUpdate(b) => format!(
+                        "{}{} {} asks {}",
+                        mark,
+                        (e.at - at(0)).num_milliseconds(),
+                        b.token_id,
+                        b.asks.len()
+                    ),
+                    other => format!("{:?}", other),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_gap_moves_later_ticks_only() {
+        let (events, windows) = scenario(
+            r#"
+            [[perturbation]]
+            kind = "gap"
+            at = "2023-11-14T22:13:27Z"
+            percent = -2.0
+            duration_secs = 3
+            "#,
+        )
+        .apply(fixture());
+        let ticks: Vec<_> = summary(&events)
+            .into_iter()
+            .filter(|e| e.contains("tick"))
+            .collect();
+        assert_eq!(ticks[6], "6 tick 100000");
+        assert_eq!(ticks[7], "*7 tick 98000");
+        assert_eq!(ticks[9], "*9 tick 98000");
+        assert_eq!(windows[0].kind, "gap");
+        assert_eq!((windows[0].from, windows[0].to), (at(7), at(10)));
+        assert_eq!((windows[0].injected, windows[0].dropped), (3, 0));
+
+        // Without a duration the gap holds to the end
+        let (events, windows) = scenario(
+            r#"
+            [[perturbation]]
+            kind = "gap"
+            at = "2023-11-14T22:13:25Z"
+            percent = 1.5
+            symbol = "BTCUSDT"
+            "#,
+        )
+        .apply(fixture());
+        assert_eq!(events.iter().filter(|e| e.injected).count(), 5);
+        assert_eq!(windows[0].to, at(9) + Duration::milliseconds(1));
+    }
+
+    #[test]
+    fn test_outage_drops_ticks_in_the_window() {
+        let (events, windows) = scenario(
+            r#"
+            [[perturbation]]
+            kind = "outage"
+            at = "2023-11-14T22:13:23Z"
+            duration_secs = 4
+            "#,
+        )
+        .apply(fixture());
+        let seconds: Vec<i64> = events
+            .iter()
+            .filter(|e| matches!(e.event, BacktestEvent::PriceTick(_)))
+            .map(|e| e.at.timestamp() - 1_700_000_000)
+            .collect();
+        assert_eq!(seconds, vec![0, 1, 2, 7, 8, 9]);
+        // Books keep flowing
+        assert_eq!(events.len(), 10);
+        assert!(events.iter().all(|e| !e.injected));
+        assert_eq!((windows[0].injected, windows[0].dropped), (0, 4));
+    }
+
+    #[test]
+    fn test_book_wipe_empties_asks_then_restores() {
+        let (events, windows) = scenario(
+            r#"
+            [[perturbation]]
+            kind = "book_wipe"
+            at = "2023-11-14T22:13:23Z"
+            duration_secs = 3
+            token = "yes"
+            "#,
+        )
+        .apply(fixture());
+        let books: Vec<_> = summary(&events)
+            .into_iter()
+            .filter(|e| e.contains("asks"))
+            .collect();
+        assert_eq!(
+            books,
+            vec![
+                "1000 yes asks 1",
+                "2000 no asks 1",
+                "*3000 yes asks 0",
+                "*4000 yes asks 0",
+                "*6000 yes asks 1",
+                "8000 yes asks 1",
+            ]
+        );
+        // Restored to the last book seen inside the window
+        let restored = events
+            .iter()
+            .find_map(|e| match &e.event {
+                BacktestEvent::OrderBookUpdate(b) if e.at == at(6) => Some(b),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(restored.asks[0].price, dec!(0.61));
+        assert_eq!((windows[0].injected, windows[0].dropped), (3, 0));
+    }
+
+    #[test]
+    fn test_book_delay_replays_books_late_and_stale() {
+        let (events, windows) = scenario(
+            r#"
+            [[perturbation]]
+            kind = "book_delay"
+            at = "2023-11-14T22:13:21Z"
+            duration_secs = 5
+            delay_ms = 1500
+            "#,
+        )
+        .apply(fixture());
+        let books: Vec<_> = summary(&events)
+            .into_iter()
+            .filter(|e| e.contains("asks"))
+            .collect();
+        assert_eq!(
+            books,
+            vec![
+                "*2500 yes asks 1",
+                "*3500 no asks 1",
+                "*5500 yes asks 1",
+                "8000 yes asks 1"
+            ]
+        );
+        // Ordered by replay time, the book keeps its original timestamp
+        assert!(events.windows(2).all(|w| w[0].at <= w[1].at));
+        let late = events
+            .iter()
+            .find(|e| e.at == at(2) + Duration::milliseconds(500));
+        let Some(BacktestEvent::OrderBookUpdate(book)) = late.map(|e| &e.event) else {
+            panic!("expected the delayed book");
+        };
+        assert_eq!(book.updated_at, at(1));
+        assert_eq!(windows[0].injected, 3);
+    }
+
+    #[test]
+    fn test_tracker_separates_windows_from_organic_periods() {
+        let (events, windows) = scenario(
+            r#"
+            [[perturbation]]
+            kind = "outage"
+            at = "2023-11-14T22:13:24Z"
+            duration_secs = 2
+
+            [[perturbation]]
+            kind = "book_wipe"
+            at = "2023-11-14T22:13:27Z"
+            duration_secs = 2
+            "#,
+        )
+        .apply(fixture());
+        let mut stats = EngineStats::default();
+        let mut tracker = ScenarioTracker::new(windows, &stats);
+        for event in &events {
+            // Pretend the engine only finds books stale while they are wiped
+            if event.injected {
+                stats.stale_books += 1;
+            }
+            tracker.observe(event.at, event.injected, &stats);
+        }
+        let summary = tracker.summary();
+        let (outage, outage_risk) = &summary.windows[0];
+        assert_eq!((outage.kind.as_str(), outage_risk.events), ("outage", 1));
+        // Both books wiped at the start and the update inside; the books
+        // restored at the end fall just outside
+        let (_, wipe_risk) = &summary.windows[1];
+        assert_eq!(wipe_risk.injected, 3);
+        assert_eq!(wipe_risk.stale_books, 3);
+        assert_eq!(summary.organic.stale_books, 2);
+        assert_eq!(
+            summary.organic.events + outage_risk.events + wipe_risk.events,
+            events.len() as u64
+        );
+        assert!(summary.to_string().contains("book_wipe"));
+    }
+}
@@ -1,32 +1,289 @@
 //! Backtest simulator engine
+//!
+//! Trades the events the way [`LatencySweep`] replays them, at the
+//! configured latency alone, reporting progress as the replay goes.
 
-use super::{BacktestConfig, BacktestResult, EventStream};
+use super::progress::{ProgressSink, ProgressTracker};
+use super::{
+    BacktestConfig, BacktestEvent, BacktestResult, BlockBootstrap, EventStream, LatencySweep,
+};
+use crate::model::GbmModel;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
 
 /// Runs backtest simulation
 pub struct BacktestSimulator {
     config: BacktestConfig,
+    progress_interval: Duration,
 }
 
 impl BacktestSimulator {
     /// Create a new simulator
     pub fn new(config: BacktestConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            progress_interval: super::progress::DEFAULT_PROGRESS_INTERVAL,
+        }
+    }
+
+    /// Set the minimum interval between progress updates
+    pub fn with_progress_interval(mut self, interval: Duration) -> Self {
+        self.progress_interval = interval;
+        self
     }
 
     /// Run the backtest
     pub async fn run(&self) -> anyhow::Result<BacktestResult> {
-        let mut events = EventStream::new(
+        self.run_with_progress(None).await
+    }
+
+    /// Run the backtest, reporting progress to the given sink
+    pub async fn run_with_progress(
+        &self,
+        sink: Option<&dyn ProgressSink>,
+    ) -> anyhow::Result<BacktestResult> {
+        let events = EventStream::new(
             self.config.data_dir.clone(),
             self.config.start_time,
             self.config.end_time,
-        );
+        )
+        .with_merge_dirs(self.config.merge_dirs.clone())
+        .with_price_history(self.config.price_history)
+        .with_scenario(self.config.scenario.clone())
+        .with_alignment(self.config.align)
+        .with_corrupt_files(self.config.corrupt_files)
+        .with_include_current(self.config.include_current);
+        // Unreadable files fail here, before anything is replayed
+        let mut events = events.load_blocking().await?;
+        let mut result = self.run_events(&mut events, sink)?;
+        if let Some(report) = events.report() {
+            result.summary.duplicates_removed = report.duplicates_removed() as u64;
+            result.summary.conflicts_resolved = report.conflicts.len() as u64;
+            result.summary.approximated_books = report.approximated_books as u64;
+            result.summary.low_fidelity = report.low_fidelity();
+            result.summary.skipped_files = report.skipped.clone();
+            result.summary.excluded_secs = report.excluded().num_seconds() as u64;
+            result.summary.snapshots = report.snapshots.clone();
+        }
+        result.summary.scenario = events.scenario_windows().to_vec();
+        result.summary.aligned = events.aligned_range().cloned();
+        result.summary.loaded_bytes = events.loaded_bytes();
+        Ok(result)
+    }
+
+    /// Run the backtest over an explicit event sequence
+    pub fn run_events<I>(
+        &self,
+        events: I,
+        sink: Option<&dyn ProgressSink>,
+    ) -> anyhow::Result<BacktestResult>
+    where
+        I: IntoIterator<Item = (DateTime<Utc>, BacktestEvent)>,
+    {
+        let events: Vec<_> = events.into_iter().collect();
+        let sweep = LatencySweep::new(GbmModel::new(), self.config.clone(), events);
+        let mut tracker = ProgressTracker::new(self.progress_interval);
 
-        // TODO: Process events through strategy
-        for (_timestamp, _event) in &mut events {
-            // Process event
+        let point = sweep.run_point_with_progress(self.config.latency_ms, |timestamp, fills| {
+            tracker.record_event(timestamp);
+            tracker.set_trades(fills);
+            if let Some(sink) = sink {
+                tracker.maybe_report(sink);
+            }
+        });
+        tracker.set_trades(point.fills);
+
+        if let Some(sink) = sink {
+            tracker.finish(sink);
         }
 
-        // TODO: Return actual results
-        Ok(BacktestResult::default())
+        let mut result = BacktestResult {
+            trades: point.trades,
+            ..Default::default()
+        };
+        result.summary.events_processed = tracker.snapshot().events_processed;
+        result.summary.total_trades = point.fills;
+        result.summary.net_pnl = point.net_pnl;
+        result.summary.avg_edge = point.avg_realized_edge;
+        result.summary.bootstrap = BlockBootstrap::new(&result.trades, &self.config.bootstrap);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::{Alignment, BacktestProgress, CorruptFiles};
+    use crate::data::IncludeCurrent;
+    use crate::feed::{PriceTick, TickSource};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    fn test_config() -> BacktestConfig {
+        BacktestConfig {
+            data_dir: PathBuf::from("./nonexistent"),
+            merge_dirs: vec![],
+            price_history: false,
+            scenario: None,
+            align: Alignment::Market,
+            corrupt_files: CorruptFiles::Skip,
+            include_current: IncludeCurrent::Skip,
+            start_time: None,
+            end_time: None,
+            initial_capital: dec!(500),
+            latency_ms: 50,
+            book_staleness_ms: 0,
+            fee_rate: dec!(0),
+            bootstrap: Default::default(),
+        }
+    }
+
+    fn tick_events(n: usize) -> Vec<(DateTime<Utc>, BacktestEvent)> {
+        let start = Utc::now();
+        (0..n)
+            .map(|i| {
+                let ts = start + chrono::Duration::seconds(i as i64);
+                let tick = PriceTick {
+                    symbol: "BTCUSDT".to_string(),
+                    asset: "BTC".to_string(),
+                    price: dec!(100000),
+                    timestamp: ts,
+                    exchange_ts: ts,
+                    source: TickSource::Trade,
+                };
+                (ts, BacktestEvent::PriceTick(tick))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_progress_counts_are_monotonic() {
+        let sim = BacktestSimulator::new(test_config()).with_progress_interval(Duration::ZERO);
+        let seen = Mutex::new(Vec::new());
+        let sink = |p: &BacktestProgress| seen.lock().unwrap().push(p.events_processed);
+
+        let result = sim.run_events(tick_events(50), Some(&sink)).unwrap();
+
+        let seen = seen.into_inner().unwrap();
+        assert!(seen.len() > 1);
+        assert!(seen.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(*seen.last().unwrap(), 50);
+        assert_eq!(result.summary.events_processed, 50);
+    }
+
+    #[test]
+    fn test_progress_counts_the_trades_made() {
+        use crate::market::Market;
+        use crate::orderbook::{OrderBook, PriceLevel};
+
+        // Spot rallies well above the open while the YES ask sits at 0.40
+        let open_time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let market = Market {
+            condition_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time,
+            close_time: open_time + chrono::Duration::minutes(15),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        let mut events = vec![(open_time, BacktestEvent::MarketOpen(market.clone()))];
+        for i in 0..60 {
+            let ts = open_time + chrono::Duration::seconds(i);
+            let tick = PriceTick {
+                symbol: "BTCUSDT".to_string(),
+                asset: "BTC".to_string(),
+                price: dec!(100000) + Decimal::from(i * 50 + i % 2 * 20),
+                timestamp: ts,
+                exchange_ts: ts,
+                source: TickSource::Trade,
+            };
+            events.push((ts, BacktestEvent::PriceTick(tick)));
+        }
+        let mut book = OrderBook::new("yes");
+        book.bids = vec![PriceLevel {
+            price: dec!(0.39),
+            size: dec!(100),
+        }];
+        book.asks = vec![PriceLevel {
+            price: dec!(0.40),
+            size: dec!(100),
+        }];
+        book.updated_at = open_time + chrono::Duration::seconds(61);
+        events.push((book.updated_at, BacktestEvent::OrderBookUpdate(book)));
+        events.push((market.close_time, BacktestEvent::MarketClose(market)));
+
+        let sim = BacktestSimulator::new(test_config()).with_progress_interval(Duration::ZERO);
+        let last = Mutex::new(None);
+        let sink = |p: &BacktestProgress| *last.lock().unwrap() = Some(p.clone());
+        let result = sim.run_events(events, Some(&sink)).unwrap();
+
+        assert_eq!(result.summary.total_trades, 1);
+        assert_eq!(last.into_inner().unwrap().unwrap().trades, 1);
+    }
+
+    #[test]
+    fn test_final_progress_is_marked_finished() {
+        let sim = BacktestSimulator::new(test_config());
+        let last = Mutex::new(None);
+        let sink = |p: &BacktestProgress| *last.lock().unwrap() = Some(p.clone());
+
+        sim.run_events(tick_events(10), Some(&sink)).unwrap();
+
+        let last = last.into_inner().unwrap().unwrap();
+        assert!(last.finished);
+        assert_eq!(last.events_processed, 10);
+        assert_eq!(last.sim_elapsed_secs, 9);
+    }
+
+    #[tokio::test]
+    async fn test_approximated_books_flag_the_summary() {
+        use crate::data::{ParquetWriter, PricePointRecord, PRICE_HISTORY_PREFIX};
+
+        let dir = tempfile::tempdir().unwrap();
+        let ts = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let writer = ParquetWriter::new(dir.path().to_path_buf(), 3600);
+        let point = PricePointRecord {
+            token_id: "yes".into(),
+            ts,
+            price: dec!(0.55),
+        };
+        writer
+            .write_price_history(&writer.file_path(PRICE_HISTORY_PREFIX, ts), &[point])
+            .unwrap();
+        let config = BacktestConfig {
+            data_dir: dir.path().to_path_buf(),
+            ..test_config()
+        };
+
+        let captured_only = BacktestSimulator::new(config.clone()).run().await.unwrap();
+        assert_eq!(captured_only.summary.events_processed, 0);
+        assert!(!captured_only.summary.low_fidelity);
+
+        let result = BacktestSimulator::new(BacktestConfig {
+            price_history: true,
+            ..config
+        })
+        .run()
+        .await
+        .unwrap();
+        assert_eq!(result.summary.events_processed, 1);
+        assert_eq!(result.summary.approximated_books, 1);
+        assert!(result.summary.low_fidelity);
+        assert!(result
+            .summary
+            .format_table()
+            .contains("Fidelity:         LOW"));
+    }
+
+    #[tokio::test]
+    async fn test_run_without_sink() {
+        let sim = BacktestSimulator::new(test_config());
+        let result = sim.run().await.unwrap();
+        assert_eq!(result.summary.events_processed, 0);
     }
 }
@@ -0,0 +1,115 @@
+//! Point-in-time order book lookup for replayed data
+
+use super::BacktestEvent;
+use crate::orderbook::OrderBook;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// Every book update per token, in time order
+#[derive(Debug, Default)]
+pub struct BookTimeline {
+    books: HashMap<String, Vec<(DateTime<Utc>, OrderBook)>>,
+}
+
+impl BookTimeline {
+    /// Build a timeline from replayed events (assumed in timestamp order)
+    pub fn from_events<'a, I>(events: I) -> Self
+    where
+        I: IntoIterator<Item = &'a (DateTime<Utc>, BacktestEvent)>,
+    {
+        let mut timeline = Self::default();
+        for (timestamp, event) in events {
+            if let BacktestEvent::OrderBookUpdate(book) = event {
+                timeline.push(*timestamp, book.clone());
+            }
+        }
+        timeline
+    }
+
+    /// Append a book update
+    pub fn push(&mut self, timestamp: DateTime<Utc>, book: OrderBook) {
+        let history = self.books.entry(book.token_id.clone()).or_default();
+        // Keep history sorted even if an update arrives out of order
+        let idx = history.partition_point(|(ts, _)| *ts <= timestamp);
+        history.insert(idx, (timestamp, book));
+    }
+
+    /// Latest book for `token_id` at or before `at`
+    pub fn book_at(&self, token_id: &str, at: DateTime<Utc>) -> Option<&OrderBook> {
+        let history = self.books.get(token_id)?;
+        let idx = history.partition_point(|(ts, _)| *ts <= at);
+        idx.checked_sub(1).map(|i| &history[i].1)
+    }
+
+    /// Number of tokens with at least one update
+    pub fn token_count(&self) -> usize {
+        self.books.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::PriceLevel;
+    use chrono::Duration;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn book(ask: Decimal) -> OrderBook {
+        let mut book = OrderBook::new("yes");
+        book.asks = vec![PriceLevel {
+            price: ask,
+            size: dec!(10),
+        }];
+        book
+    }
+
+    #[test]
+    fn test_book_at_returns_latest_at_or_before() {
+        let t0 = Utc::now();
+        let events = vec![
+            (t0, BacktestEvent::OrderBookUpdate(book(dec!(0.40)))),
+            (
+                t0 + Duration::milliseconds(200),
+                BacktestEvent::OrderBookUpdate(book(dec!(0.60))),
+            ),
+        ];
+        let timeline = BookTimeline::from_events(&events);
+
+        assert_eq!(timeline.token_count(), 1);
+        assert!(timeline
+            .book_at("yes", t0 - Duration::milliseconds(1))
+            .is_none());
+        assert_eq!(
+            timeline.book_at("yes", t0).unwrap().best_ask(),
+            Some(dec!(0.40))
+        );
+        assert_eq!(
+            timeline
+                .book_at("yes", t0 + Duration::milliseconds(199))
+                .unwrap()
+                .best_ask(),
+            Some(dec!(0.40))
+        );
+        assert_eq!(
+            timeline
+                .book_at("yes", t0 + Duration::milliseconds(200))
+                .unwrap()
+                .best_ask(),
+            Some(dec!(0.60))
+        );
+        assert!(timeline.book_at("no", t0).is_none());
+    }
+
+    #[test]
+    fn test_out_of_order_push() {
+        let t0 = Utc::now();
+        let mut timeline = BookTimeline::default();
+        timeline.push(t0 + Duration::seconds(1), book(dec!(0.60)));
+        timeline.push(t0, book(dec!(0.40)));
+        assert_eq!(
+            timeline.book_at("yes", t0).unwrap().best_ask(),
+            Some(dec!(0.40))
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Circuit breaker around a failing dependency
+//!
+//! After enough consecutive failures the circuit opens and calls are
+//! refused for a cooldown. When the cooldown ends a single probe call is let
+//! through: success closes the circuit, failure opens it for another
+//! cooldown. Failures that cannot fix themselves, such as rejected
+//! credentials, open the circuit at once.
+
+use crate::duration::DurationConfig;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Default consecutive failures that open the circuit
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// Default seconds the circuit stays open before a probe
+pub const DEFAULT_COOLDOWN_SECS: u64 = 60;
+
+/// When a circuit opens and how long it stays open
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakerConfig {
+    /// Consecutive failures that open the circuit
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a probe call
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: DurationConfig,
+}
+
+fn default_failure_threshold() -> u32 {
+    DEFAULT_FAILURE_THRESHOLD
+}
+
+fn default_cooldown_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_COOLDOWN_SECS)
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown_secs: default_cooldown_secs(),
+        }
+    }
+}
+
+/// How an error counts towards opening a circuit
+pub trait Failure: fmt::Display {
+    /// Whether the error opens the circuit on its own
+    fn is_fatal(&self) -> bool;
+}
+
+impl Failure for anyhow::Error {
+    /// Rejected credentials: retrying cannot succeed until an operator acts
+    fn is_fatal(&self) -> bool {
+        self.chain().any(|cause| {
+            cause
+                .downcast_ref::<reqwest::Error>()
+                .and_then(|e| e.status())
+                .is_some_and(|status| {
+                    status == reqwest::StatusCode::UNAUTHORIZED
+                        || status == reqwest::StatusCode::FORBIDDEN
+                })
+        })
+    }
+}
+
+/// State of a circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Calls flow normally
+    Closed,
+    /// Calls are refused until `until`
+    Open { until: DateTime<Utc> },
+    /// The cooldown ended; one probe call decides the next state
+    HalfOpen,
+}
+
+impl BreakerState {
+    /// Name used in logs, journals and health reasons
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open { .. } => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+
+    /// 0 closed, 1 half-open, 2 open, for gauges
+    pub fn severity(&self) -> u8 {
+        match self {
+            BreakerState::Closed => 0,
+            BreakerState::HalfOpen => 1,
+            BreakerState::Open { .. } => 2,
+        }
+    }
+}
+
+impl fmt::Display for BreakerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakerState::Open { until } => write!(f, "open until {}", until.format("%H:%M:%S")),
+            state => f.write_str(state.as_str()),
+        }
+    }
+}
+
+/// Circuit breaker over calls failing with `E`
+#[derive(Debug)]
+pub struct CircuitBreaker<E> {
+    config: BreakerConfig,
+    state: BreakerState,
+    failures: u32,
+    probing: bool,
+    last_error: Option<String>,
+    _error: PhantomData<fn(&E)>,
+}
+
+impl<E: Failure> CircuitBreaker<E> {
+    /// Create a closed circuit
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            state: BreakerState::Closed,
+            failures: 0,
+            probing: false,
+            last_error: None,
+            _error: PhantomData,
+        }
+    }
+
+    /// Current state, as of the last call to [`Self::allow`]
+    pub fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    /// Consecutive failures since the last success
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Message of the most recent failure
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Whether a call may be made at `now`
+    ///
+    /// An open circuit whose cooldown has ended turns half-open and allows
+    /// one probe; further calls are refused until the probe is recorded.
+    pub fn allow(&mut self, now: DateTime<Utc>) -> bool {
+        match self.state {
+            BreakerState::Closed => true,
+            BreakerState::Open { until } if now < until => false,
+            BreakerState::Open { .. } => {
+                self.state = BreakerState::HalfOpen;
+                self.probing = true;
+                true
+            }
+            BreakerState::HalfOpen if self.probing => false,
+            BreakerState::HalfOpen => {
+                self.probing = true;
+                true
+            }
+        }
+    }
+
+    /// Record a successful call; returns true if this closed the circuit
+    pub fn record_success(&mut self) -> bool {
+        let reopened = self.state != BreakerState::Closed;
+        self.state = BreakerState::Closed;
+        self.failures = 0;
+        self.probing = false;
+        reopened
+    }
+
+    /// Record a failed call; returns true if this opened the circuit
+    pub fn record_failure(&mut self, error: &E, now: DateTime<Utc>) -> bool {
+        self.failures += 1;
+        self.probing = false;
+        self.last_error = Some(error.to_string());
+        let trips = match self.state {
+            BreakerState::Closed => {
+                error.is_fatal() || self.failures >= self.config.failure_threshold
+            }
+            BreakerState::HalfOpen => true,
+            BreakerState::Open { .. } => false,
+        };
+        if trips {
+            self.state = BreakerState::Open {
+                until: now + self.config.cooldown_secs.to_chrono(),
+            };
+        }
+        trips
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    enum TestError {
+        Timeout,
+        Auth,
+    }
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
+    impl Failure for TestError {
+        fn is_fatal(&self) -> bool {
+            matches!(self, TestError::Auth)
+        }
+    }
+
+    fn ts(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    /// A breaker opened at t=0 by three timeouts, cooling down for 60s
+    fn tripped() -> CircuitBreaker<TestError> {
+        let mut breaker = CircuitBreaker::new(BreakerConfig::default());
+        assert!(!breaker.record_failure(&TestError::Timeout, ts(0)));
+        assert!(!breaker.record_failure(&TestError::Timeout, ts(0)));
+        assert!(breaker.record_failure(&TestError::Timeout, ts(0)));
+        breaker
+    }
+
+    #[test]
+    fn test_consecutive_failures_trip() {
+        let mut breaker = tripped();
+        assert_eq!(breaker.state(), BreakerState::Open { until: ts(60) });
+        assert_eq!(breaker.last_error(), Some("Timeout"));
+        assert!(!breaker.allow(ts(30)));
+
+        // A success in between resets the count
+        let mut breaker = CircuitBreaker::new(BreakerConfig::default());
+        breaker.record_failure(&TestError::Timeout, ts(0));
+        breaker.record_failure(&TestError::Timeout, ts(0));
+        assert!(!breaker.record_success());
+        assert!(!breaker.record_failure(&TestError::Timeout, ts(0)));
+        assert!(breaker.allow(ts(0)));
+    }
+
+    #[test]
+    fn test_auth_failure_trips_immediately() {
+        let mut breaker = CircuitBreaker::new(BreakerConfig::default());
+        assert!(breaker.record_failure(&TestError::Auth, ts(0)));
+        assert_eq!(breaker.state().as_str(), "open");
+    }
+
+    #[test]
+    fn test_half_open_probe_success_closes() {
+        let mut breaker = tripped();
+        assert!(!breaker.allow(ts(59)));
+
+        // Only one probe goes out when the cooldown ends
+        assert!(breaker.allow(ts(60)));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(!breaker.allow(ts(61)));
+
+        assert!(breaker.record_success());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(breaker.failures(), 0);
+        assert!(breaker.allow(ts(62)));
+    }
+
+    #[test]
+    fn test_half_open_probe_failure_reopens() {
+        let mut breaker = tripped();
+        assert!(breaker.allow(ts(70)));
+        assert!(breaker.record_failure(&TestError::Timeout, ts(70)));
+        assert_eq!(breaker.state(), BreakerState::Open { until: ts(130) });
+        assert!(!breaker.allow(ts(100)));
+        assert!(breaker.allow(ts(130)));
+    }
+}
@@ -0,0 +1,367 @@
+//! Bounded, non-blocking fanout of market data
+//!
+//! Every subscriber gets its own ring buffer with its own capacity. The
+//! producer never waits: when a subscriber's buffer is full its oldest event
+//! is dropped and counted, so one slow consumer cannot stall the hot path or
+//! the other consumers. The recorder subscribes with a large buffer;
+//! analytics consumers that only care about recent state use small ones.
+
+use crate::feed::{PriceTick, TickStream};
+use crate::orderbook::OrderBook;
+use crate::telemetry::record_bus_dropped;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Default buffer of the recorder's subscription
+pub const DEFAULT_RECORDER_CAPACITY: usize = 65_536;
+
+/// Bus settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusConfig {
+    /// Events buffered for the recorder before the oldest are dropped
+    #[serde(default = "default_recorder_capacity")]
+    pub recorder_capacity: usize,
+}
+
+fn default_recorder_capacity() -> usize {
+    DEFAULT_RECORDER_CAPACITY
+}
+
+impl Default for BusConfig {
+    fn default() -> Self {
+        Self {
+            recorder_capacity: DEFAULT_RECORDER_CAPACITY,
+        }
+    }
+}
+
+/// A public trade print on a market token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradePrint {
+    /// Token traded
+    pub token_id: String,
+    /// Trade price
+    pub price: Decimal,
+    /// Shares traded
+    pub size: Decimal,
+    /// Exchange timestamp
+    pub timestamp: DateTime<Utc>,
+}
+
+/// One market data update carried by the bus
+#[derive(Debug, Clone)]
+pub enum MarketDataEvent {
+    /// Order book snapshot
+    Book(OrderBook),
+    /// Market trade
+    Trade(TradePrint),
+    /// Spot price tick
+    Tick(PriceTick),
+}
+
+/// Counters for one subscriber
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubscriberStats {
+    /// Subscriber name
+    pub name: String,
+    /// Events handed to the subscriber
+    pub delivered: u64,
+    /// Events dropped because the subscriber fell behind
+    pub dropped: u64,
+    /// Events waiting in the subscriber's buffer
+    pub queued: usize,
+    /// Size of the subscriber's buffer
+    pub capacity: usize,
+}
+
+/// Which events a subscriber wants
+type Filter = fn(&MarketDataEvent) -> bool;
+
+struct Slot {
+    name: String,
+    capacity: usize,
+    filter: Filter,
+    queue: Mutex<VecDeque<MarketDataEvent>>,
+    notify: Notify,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl Slot {
+    fn push(&self, event: MarketDataEvent) {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        if queue.len() >= self.capacity {
+            queue.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            record_bus_dropped(&self.name, 1);
+        }
+        queue.push_back(event);
+        drop(queue);
+        self.notify.notify_one();
+    }
+
+    fn pop(&self) -> Option<MarketDataEvent> {
+        let event = self
+            .queue
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()?;
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+        Some(event)
+    }
+
+    fn stats(&self) -> SubscriberStats {
+        SubscriberStats {
+            name: self.name.clone(),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            queued: self.queue.lock().map(|q| q.len()).unwrap_or_default(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+/// Producer side of the market data bus
+///
+/// Dropping the bus closes it; subscribers drain what is buffered and then
+/// see `None`.
+#[derive(Default)]
+pub struct MarketDataBus {
+    slots: Mutex<Vec<Arc<Slot>>>,
+    published: AtomicU64,
+}
+
+impl MarketDataBus {
+    /// Create a bus with no subscribers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to every event, buffering at most `capacity`
+    pub fn subscribe(&self, name: impl Into<String>, capacity: usize) -> BusReceiver {
+        self.subscribe_filtered(name, capacity, |_| true)
+    }
+
+    /// Subscribe to spot price ticks only
+    pub fn subscribe_ticks(&self, name: impl Into<String>, capacity: usize) -> BusReceiver {
+        self.subscribe_filtered(name, capacity, |e| matches!(e, MarketDataEvent::Tick(_)))
+    }
+
+    /// Subscribe to the events `filter` accepts, buffering at most `capacity`
+    pub fn subscribe_filtered(
+        &self,
+        name: impl Into<String>,
+        capacity: usize,
+        filter: Filter,
+    ) -> BusReceiver {
+        let slot = Arc::new(Slot {
+            name: name.into(),
+            capacity: capacity.max(1),
+            filter,
+            queue: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        });
+        self.slots
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(slot.clone());
+        BusReceiver { slot }
+    }
+
+    /// Hand `event` to every interested subscriber without waiting
+    ///
+    /// Returns the number of subscribers it reached. Subscribers whose
+    /// receiver has been dropped are removed.
+    pub fn publish(&self, event: MarketDataEvent) -> usize {
+        self.published.fetch_add(1, Ordering::Relaxed);
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.retain(|slot| Arc::strong_count(slot) > 1);
+        let interested: Vec<&Arc<Slot>> = slots.iter().filter(|s| (s.filter)(&event)).collect();
+        if let Some((last, rest)) = interested.split_last() {
+            for slot in rest {
+                slot.push(event.clone());
+            }
+            last.push(event);
+        }
+        interested.len()
+    }
+
+    /// Events published so far
+    pub fn published(&self) -> u64 {
+        self.published.load(Ordering::Relaxed)
+    }
+
+    /// Counters of every live subscriber, in subscription order
+    pub fn stats(&self) -> Vec<SubscriberStats> {
+        self.slots
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|s| s.stats())
+            .collect()
+    }
+}
+
+impl Drop for MarketDataBus {
+    fn drop(&mut self) {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        for slot in slots.iter() {
+            slot.closed.store(true, Ordering::Release);
+            slot.notify.notify_one();
+        }
+    }
+}
+
+/// Consumer side of one bus subscription
+pub struct BusReceiver {
+    slot: Arc<Slot>,
+}
+
+impl BusReceiver {
+    /// Next event; `None` once the bus is closed and the buffer drained
+    pub async fn recv(&mut self) -> Option<MarketDataEvent> {
+        loop {
+            if let Some(event) = self.slot.pop() {
+                return Some(event);
+            }
+            if self.slot.closed.load(Ordering::Acquire) {
+                // An event may have landed between the pop and the close
+                return self.slot.pop();
+            }
+            self.slot.notify.notified().await;
+        }
+    }
+
+    /// Next buffered event, without waiting
+    pub fn try_recv(&mut self) -> Option<MarketDataEvent> {
+        self.slot.pop()
+    }
+
+    /// This subscription's counters
+    pub fn stats(&self) -> SubscriberStats {
+        self.slot.stats()
+    }
+}
+
+/// Ticks from the subscription; other events are skipped
+#[async_trait]
+impl TickStream for BusReceiver {
+    async fn next_tick(&mut self) -> Option<PriceTick> {
+        loop {
+            if let MarketDataEvent::Tick(tick) = self.recv().await? {
+                return Some(tick);
+            }
+        }
+    }
+
+    fn try_next_tick(&mut self) -> Option<PriceTick> {
+        loop {
+            if let MarketDataEvent::Tick(tick) = self.try_recv()? {
+                return Some(tick);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::TickSource;
+    use std::time::Duration;
+
+    fn tick(n: i64) -> MarketDataEvent {
+        let ts = DateTime::from_timestamp(1_735_689_600 + n, 0).unwrap();
+        MarketDataEvent::Tick(PriceTick {
+            symbol: "BTCUSDT".to_string(),
+            asset: "BTC".to_string(),
+            price: Decimal::from(n),
+            timestamp: ts,
+            exchange_ts: ts,
+            source: TickSource::Trade,
+        })
+    }
+
+    fn price(event: &MarketDataEvent) -> Decimal {
+        match event {
+            MarketDataEvent::Tick(tick) => tick.price,
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_drops_oldest_without_blocking() {
+        const EVENTS: i64 = 10_000;
+        let bus = MarketDataBus::new();
+        let mut fast = bus.subscribe("fast", EVENTS as usize);
+        let mut slow = bus.subscribe("slow", 8);
+
+        let consumer = tokio::spawn(async move {
+            let mut seen = vec![];
+            while let Some(event) = fast.recv().await {
+                seen.push(price(&event));
+            }
+            (seen, fast.stats())
+        });
+
+        // The slow subscriber never reads while the producer runs
+        let started = std::time::Instant::now();
+        for n in 0..EVENTS {
+            assert_eq!(bus.publish(tick(n)), 2);
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(bus.published(), EVENTS as u64);
+        drop(bus);
+
+        let (seen, stats) = consumer.await.unwrap();
+        assert_eq!(seen.len(), EVENTS as usize);
+        assert!(seen.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(stats.dropped, 0);
+
+        let stats = slow.stats();
+        assert_eq!(stats.dropped, EVENTS as u64 - 8);
+        assert_eq!(stats.queued, 8);
+        // The newest events survive
+        let first = slow.recv().await.unwrap();
+        assert_eq!(price(&first), Decimal::from(EVENTS - 8));
+        let mut rest = 0;
+        while slow.recv().await.is_some() {
+            rest += 1;
+        }
+        assert_eq!(rest, 7);
+    }
+
+    #[tokio::test]
+    async fn test_filtered_and_dropped_subscribers() {
+        let bus = MarketDataBus::new();
+        let mut ticks = bus.subscribe_ticks("detection", 4);
+        let mut all = bus.subscribe("recorder", 4);
+        let gone = bus.subscribe("gone", 4);
+        drop(gone);
+
+        let trade = MarketDataEvent::Trade(TradePrint {
+            token_id: "yes".to_string(),
+            price: Decimal::new(55, 2),
+            size: Decimal::from(10),
+            timestamp: Utc::now(),
+        });
+        assert_eq!(bus.publish(trade), 1);
+        assert_eq!(bus.publish(tick(1)), 2);
+        assert_eq!(bus.stats().len(), 2);
+
+        assert!(matches!(all.try_recv(), Some(MarketDataEvent::Trade(_))));
+        assert!(matches!(all.try_recv(), Some(MarketDataEvent::Tick(_))));
+        assert_eq!(price(&ticks.recv().await.unwrap()), Decimal::from(1));
+        assert!(ticks.try_recv().is_none());
+        assert_eq!(ticks.stats().delivered, 1);
+    }
+}
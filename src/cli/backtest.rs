@@ -1,8 +1,24 @@
 //! Backtest command implementation
 
+use crate::backtest::{
+    format_exit_comparison, format_sweep_table, write_sweep_csv, write_trade_tape, Alignment,
+    BacktestConfig, BacktestProgress, BacktestSimulator, BlockBootstrapConfig, CorruptFiles,
+    LatencySweep, ProgressSink, Scenario, DEFAULT_BACKTEST_SEED, DEFAULT_BOOTSTRAP_RESAMPLES,
+};
+use crate::config::Config;
+use crate::data::IncludeCurrent;
+use crate::duration::DurationConfig;
+use crate::fingerprint;
+use crate::model::GbmModel;
+use crate::report::{CohortReport, ExpectedValueConfig, ExpectedValueReport};
+use crate::signal::{BookShockConfig, DEFAULT_MOMENTUM_WINDOW_SECS};
+use chrono::{DateTime, Utc};
 use clap::Args;
+use indicatif::{ProgressBar, ProgressStyle};
 use rust_decimal::Decimal;
-use std::path::PathBuf;
+use rust_decimal_macros::dec;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Args, Debug)]
 pub struct BacktestArgs {
@@ -10,6 +26,40 @@ pub struct BacktestArgs {
     #[arg(long, default_value = "./data")]
     pub data_dir: PathBuf,
 
+    /// Also replay captures from this directory, e.g. another machine's;
+    /// rows already in --data-dir or an earlier --merge-dir are dropped,
+    /// and on conflicting rows the earlier directory wins
+    #[arg(long)]
+    pub merge_dir: Vec<PathBuf>,
+
+    /// Approximate books from backfilled price history for tokens with no
+    /// captured books; results are flagged as low fidelity
+    #[arg(long)]
+    pub price_history: bool,
+
+    /// Apply the perturbations in this TOML scenario file on top of the
+    /// loaded data: price gaps, feed outages, book wipes, delayed books
+    #[arg(long)]
+    pub scenario: Option<PathBuf>,
+
+    /// Market boundaries to trade within: `market` trades only markets
+    /// wholly inside --start/--end, warming up on the data before the
+    /// first open; `none` also trades the windows they cut
+    #[arg(long, value_enum, default_value_t = Alignment::Market)]
+    pub align: Alignment,
+
+    /// Capture files that cannot be read: `skip` leaves them out and
+    /// reports the time they covered as a data gap, traded across by
+    /// nothing; `strict` refuses to start, listing every bad file
+    #[arg(long, value_enum, default_value_t = CorruptFiles::Skip)]
+    pub corrupt_files: CorruptFiles,
+
+    /// Capture files still being written, e.g. by a capture running on the
+    /// same directory: `skip` leaves them out; `wait` waits for them to be
+    /// sealed, up to 30s. Rows still buffered in memory are never read
+    #[arg(long, value_enum, default_value_t = IncludeCurrent::Skip)]
+    pub include_current: IncludeCurrent,
+
     /// Start time filter (ISO 8601)
     #[arg(long)]
     pub start: Option<String>,
@@ -26,19 +76,388 @@ pub struct BacktestArgs {
     #[arg(long, default_value = "50")]
     pub latency: u64,
 
+    /// Rerun the same data at each of these latencies (ms, comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub latency_sweep: Option<Vec<u64>>,
+
+    /// Delay before book updates reach the strategy, simulating WS lag (ms)
+    #[arg(long, default_value = "0")]
+    pub book_staleness: u64,
+
+    /// Seed of the run's randomness, including the bootstrap resampling
+    #[arg(long, default_value_t = DEFAULT_BACKTEST_SEED)]
+    pub seed: u64,
+
+    /// Length of the blocks of market windows the bootstrap resamples,
+    /// e.g. `1d` or `6h`
+    #[arg(long, default_value = "1d")]
+    pub bootstrap_block: DurationConfig,
+
+    /// Resamples behind the bootstrap percentiles; 0 skips them
+    #[arg(long, default_value_t = DEFAULT_BOOTSTRAP_RESAMPLES)]
+    pub bootstrap_resamples: usize,
+
+    /// Retrace fraction above which the sweep tags fills as reverting
+    #[arg(long, default_value = "0.30")]
+    pub max_retrace: Decimal,
+
+    /// Have the sweep sell fills on book shocks, outside the last minute
+    #[arg(long)]
+    pub book_shock: bool,
+
+    /// Have the sweep sell fills in parts near the close, on the rungs of
+    /// `[risk.exit_ladder]` (on anyway when that is enabled); without
+    /// --latency-sweep, sweeps --latency alone
+    #[arg(long)]
+    pub exit_ladder: bool,
+
+    /// Print holding to resolution and the exit ladder side by side
+    #[arg(long)]
+    pub compare_exits: bool,
+
     /// Output directory for results
     #[arg(long, default_value = "./output")]
     pub output: PathBuf,
 
+    /// Write closed trades as a Parquet trade tape to this path; a sweep
+    /// of several latencies writes one per latency, suffixed `_<ms>ms`
+    #[arg(long)]
+    pub trades_out: Option<PathBuf>,
+
     /// Output format: json or table
     #[arg(long, default_value = "table")]
     pub format: String,
+
+    /// Suppress progress output
+    #[arg(long, conflicts_with = "progress_json")]
+    pub quiet: bool,
+
+    /// Emit progress as JSON lines on stderr instead of a progress bar
+    #[arg(long)]
+    pub progress_json: bool,
 }
 
 impl BacktestArgs {
-    pub async fn execute(&self) -> anyhow::Result<()> {
-        // TODO: Implement backtesting
+    pub async fn execute(&self, app_config: &Config) -> anyhow::Result<()> {
         tracing::info!("Running backtest on {:?}...", self.data_dir);
+
+        let config = self.config()?;
+        if let Some(latencies) = &self.latency_sweep {
+            return self.run_latency_sweep(config, app_config, latencies).await;
+        }
+        // Exits are replayed by the sweep; the simulator holds to settlement
+        if self.exit_ladder || self.compare_exits {
+            return self
+                .run_latency_sweep(config, app_config, &[self.latency])
+                .await;
+        }
+
+        let simulator = BacktestSimulator::new(config);
+
+        let mut result = if self.quiet {
+            simulator.run().await?
+        } else if self.progress_json {
+            simulator.run_with_progress(Some(&JsonProgress)).await?
+        } else {
+            let bar = BarProgress::new();
+            let result = simulator.run_with_progress(Some(&bar)).await?;
+            bar.bar.finish_and_clear();
+            result
+        };
+        result.summary.config_hash = fingerprint::active().map(|fp| fp.hash.clone());
+        if let Some(path) = &self.trades_out {
+            write_trade_tape(path, &result.trades)?;
+            tracing::info!(path = ?path, trades = result.trades.len(), "Wrote trade tape");
+        }
+
+        match self.format.as_str() {
+            "json" => println!("{}", serde_json::to_string_pretty(&result.summary)?),
+            _ => println!("{}", result.summary.format_table()),
+        }
+        if !result.trades.is_empty() && self.format != "json" {
+            let config = ExpectedValueConfig::default();
+            let report = ExpectedValueReport::new(&result.trades, config.bootstrap_resamples);
+            print!("{}", report);
+            report.warn_on_shortfall(&config);
+            let cohorts = CohortReport::new(&result.trades);
+            if !cohorts.is_empty() {
+                print!("{}", cohorts);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl BacktestArgs {
+    /// Replay configuration of these arguments
+    fn config(&self) -> anyhow::Result<BacktestConfig> {
+        Ok(BacktestConfig {
+            data_dir: self.data_dir.clone(),
+            merge_dirs: self.merge_dir.clone(),
+            price_history: self.price_history,
+            scenario: self.scenario.as_deref().map(Scenario::load).transpose()?,
+            align: self.align,
+            corrupt_files: self.corrupt_files,
+            include_current: self.include_current,
+            start_time: parse_time(self.start.as_deref())?,
+            end_time: parse_time(self.end.as_deref())?,
+            initial_capital: self.capital.unwrap_or(dec!(500)),
+            latency_ms: self.latency,
+            book_staleness_ms: self.book_staleness,
+            fee_rate: Decimal::ZERO,
+            bootstrap: BlockBootstrapConfig {
+                block: self.bootstrap_block.to_chrono(),
+                resamples: self.bootstrap_resamples,
+                seed: self.seed,
+            },
+        })
+    }
+
+    async fn run_latency_sweep(
+        &self,
+        config: BacktestConfig,
+        app_config: &Config,
+        latencies: &[u64],
+    ) -> anyhow::Result<()> {
+        // Loading may wait for capture files to seal, off the runtime
+        let sweep =
+            tokio::task::spawn_blocking(move || LatencySweep::load(GbmModel::new(), config))
+                .await
+                .map_err(|e| anyhow::anyhow!("Task join error: {}", e))??;
+        let mut sweep = sweep.with_momentum(
+            chrono::Duration::seconds(DEFAULT_MOMENTUM_WINDOW_SECS as i64),
+            self.max_retrace,
+        );
+        if self.book_shock {
+            let shock = BookShockConfig {
+                enabled: true,
+                ..Default::default()
+            };
+            sweep = sweep.with_book_shock(shock, chrono::Duration::minutes(1));
+        }
+        let mut ladder = app_config.risk.exit_ladder.clone();
+        ladder.enabled |= self.exit_ladder;
+        sweep = sweep
+            .with_exit_ladder(ladder)
+            .with_allocation(
+                app_config.risk.allocation.clone(),
+                app_config.risk.max_concurrent_positions,
+            )
+            .with_camouflage(app_config.execution.camouflage.clone(), self.seed);
+        tracing::info!(
+            events = sweep.event_count(),
+            points = latencies.len(),
+            "Running latency sweep"
+        );
+        let results = sweep.run(latencies);
+
+        let csv_path = self.output.join("latency_sweep.csv");
+        write_sweep_csv(&csv_path, &results)?;
+        match self.format.as_str() {
+            "json" => println!("{}", serde_json::to_string_pretty(&results)?),
+            _ => print!("{}", format_sweep_table(&results)),
+        }
+        tracing::info!(path = ?csv_path, "Wrote latency sweep results");
+        if let Some(path) = &self.trades_out {
+            for point in &results {
+                let path = tape_path(path, point.latency_ms, results.len());
+                write_trade_tape(&path, &point.trades)?;
+                tracing::info!(
+                    path = ?path,
+                    latency_ms = point.latency_ms,
+                    trades = point.trades.len(),
+                    "Wrote trade tape"
+                );
+            }
+        }
+
+        if self.compare_exits {
+            let comparison = sweep.compare_exits(latencies);
+            match self.format.as_str() {
+                "json" => println!("{}", serde_json::to_string_pretty(&comparison)?),
+                _ => print!("\nExit policies\n{}", format_exit_comparison(&comparison)),
+            }
+        }
         Ok(())
     }
 }
+
+/// Trade tape of the sweep point at `latency_ms`, one of `points`
+fn tape_path(path: &Path, latency_ms: u64, points: usize) -> PathBuf {
+    if points <= 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{}ms.{}", stem, latency_ms, ext.to_string_lossy()),
+        None => format!("{}_{}ms", stem, latency_ms),
+    };
+    path.with_file_name(name)
+}
+
+/// Parse an optional ISO 8601 timestamp argument
+fn parse_time(value: Option<&str>) -> anyhow::Result<Option<DateTime<Utc>>> {
+    value
+        .map(|s| {
+            DateTime::parse_from_rfc3339(s)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| anyhow::anyhow!("Invalid timestamp '{}': {}", s, e))
+        })
+        .transpose()
+}
+
+/// Machine-readable progress lines for CI
+struct JsonProgress;
+
+impl ProgressSink for JsonProgress {
+    fn on_progress(&self, progress: &BacktestProgress) {
+        if let Ok(line) = serde_json::to_string(progress) {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+/// Interactive progress bar
+struct BarProgress {
+    bar: ProgressBar,
+}
+
+impl BarProgress {
+    fn new() -> Self {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(
+            ProgressStyle::with_template("{spinner} [{elapsed_precise}] {msg}")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        );
+        bar.enable_steady_tick(Duration::from_millis(200));
+        Self { bar }
+    }
+}
+
+impl ProgressSink for BarProgress {
+    fn on_progress(&self, progress: &BacktestProgress) {
+        let date = progress
+            .sim_time
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let speedup = if progress.wall_elapsed_secs > 0.0 {
+            progress.sim_elapsed_secs as f64 / progress.wall_elapsed_secs
+        } else {
+            0.0
+        };
+        self.bar.set_message(format!(
+            "{} events | {} | {:.0}x realtime | {} trades | {:.0} ev/s",
+            progress.events_processed, date, speedup, progress.trades, progress.events_per_sec
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{OrderBookRecord, ParquetWriter, PriceTickRecord};
+    use crate::journal::Journal;
+    use crate::orderbook::BookUpdateKind;
+    use chrono::Duration as ChronoDuration;
+    use clap::Parser;
+    use std::sync::Arc;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        backtest: BacktestArgs,
+    }
+
+    /// A capture of two back-to-back windows, each rallying above its
+    /// strike for a minute while its YES ask sits at 0.40
+    fn capture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let writer = ParquetWriter::new(dir.path().to_path_buf(), 3600);
+        let journal = Journal::open(dir.path().join("trade_journal.jsonl")).unwrap();
+        let start = DateTime::from_timestamp(1_700_000_100, 0).unwrap();
+        let (mut ticks, mut books) = (vec![], vec![]);
+        for window in 0..2 {
+            let open = start + ChronoDuration::minutes(15 * window);
+            let strike = dec!(100000) + Decimal::from(window * 3000);
+            let id = format!("window-{}", window);
+            let opened = serde_json::json!({
+                "market_id": id,
+                "asset": "BTC",
+                "yes_token_id": format!("{}-yes", id),
+                "no_token_id": format!("{}-no", id),
+                "open_price": strike.to_string(),
+                "open_time": open,
+                "close_time": open + ChronoDuration::minutes(15),
+            });
+            journal.append("market_opened", &opened).unwrap();
+            for i in 0..60 {
+                let at = open + ChronoDuration::seconds(i);
+                let price = strike + Decimal::from(i * 50 + i % 2 * 20);
+                ticks.push(PriceTickRecord::new(at, Arc::from("BTCUSDT"), price, at));
+            }
+            books.push(OrderBookRecord {
+                timestamp: open + ChronoDuration::seconds(61),
+                token_id: Arc::from(format!("{}-yes", id)),
+                bids: vec![(dec!(0.39), dec!(100))],
+                asks: vec![(dec!(0.40), dec!(100))],
+                crossed: false,
+                kind: BookUpdateKind::Snapshot,
+            });
+        }
+        let end = start + ChronoDuration::minutes(30);
+        ticks.push(PriceTickRecord::new(
+            end,
+            Arc::from("BTCUSDT"),
+            dec!(106000),
+            end,
+        ));
+        let path = writer.file_path("price_ticks", start);
+        writer.write_price_ticks(&path, &ticks).unwrap();
+        let path = writer.file_path("orderbook", start);
+        writer.write_orderbook_snapshots(&path, &books).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_resamples_the_captured_trades() {
+        let dir = capture();
+        let cli = Cli::try_parse_from([
+            "backtest",
+            "--data-dir",
+            dir.path().to_str().unwrap(),
+            "--bootstrap-block",
+            "15m",
+            "--quiet",
+        ])
+        .unwrap();
+
+        let result = BacktestSimulator::new(cli.backtest.config().unwrap())
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(result.trades.len(), 2);
+        let bootstrap = result.summary.bootstrap.expect("no bootstrap");
+        assert_eq!((bootstrap.blocks, bootstrap.windows), (2, 2));
+        assert_eq!(bootstrap.resamples, DEFAULT_BOOTSTRAP_RESAMPLES);
+        assert_eq!(bootstrap.net_pnl.point, result.summary.net_pnl);
+        assert!(bootstrap.net_pnl.p5 <= bootstrap.net_pnl.p50);
+        assert!(bootstrap.net_pnl.p50 <= bootstrap.net_pnl.p95);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let parsed = parse_time(Some("2025-01-04T12:30:00Z")).unwrap().unwrap();
+        assert_eq!(parsed.to_rfc3339(), "2025-01-04T12:30:00+00:00");
+        assert!(parse_time(None).unwrap().is_none());
+        assert!(parse_time(Some("yesterday")).is_err());
+    }
+
+    #[test]
+    fn test_tape_path_per_sweep_point() {
+        let path = Path::new("out/trades.parquet");
+        assert_eq!(tape_path(path, 50, 1), path);
+        assert_eq!(tape_path(path, 50, 3), Path::new("out/trades_50ms.parquet"));
+        assert_eq!(tape_path(Path::new("tape"), 0, 2), Path::new("tape_0ms"));
+    }
+}
@@ -1,8 +1,12 @@
 //! Capture command implementation
 
-use crate::data::{DataRecorder, RecorderConfig};
-use crate::feed::{BinanceFeed, PriceFeed};
-use crate::telemetry::{record_latency, record_price_tick, LatencyMetric};
+use crate::config::DataConfig;
+use crate::data::{DataDirLock, DataRecorder, DiskManager, RecorderConfig};
+use crate::duration::DurationConfig;
+use crate::feed::{BinanceFeed, KlineClient, PriceFeed};
+use crate::fingerprint;
+use crate::journal::Journal;
+use crate::telemetry::{record_latency, record_price_tick, HealthRegistry, LatencyMetric};
 use chrono::Utc;
 use clap::Args;
 use std::path::PathBuf;
@@ -23,13 +27,21 @@ pub struct CaptureArgs {
     #[arg(long, default_value = "1000")]
     pub buffer_size: usize,
 
-    /// Flush interval in seconds
+    /// Longest time between flushes, e.g. 60s or 2m [default: data.flush_interval]
+    #[arg(long)]
+    pub flush_interval: Option<DurationConfig>,
+
+    /// File rotation interval, e.g. 1h or 3600 [default: data.rotation_interval]
+    #[arg(long)]
+    pub rotation_interval: Option<DurationConfig>,
+
+    /// Minutes of 1m klines to backfill at startup and after feed gaps (0 disables)
     #[arg(long, default_value = "60")]
-    pub flush_interval: u64,
+    pub kline_backfill: usize,
 
-    /// File rotation interval in seconds (default: 1 hour)
-    #[arg(long, default_value = "3600")]
-    pub rotation_interval: u64,
+    /// If another process holds the output directory, write to a subdirectory of it
+    #[arg(long)]
+    pub share_data_dir: bool,
 
     /// Enable verbose output
     #[arg(short, long)]
@@ -37,21 +49,59 @@ pub struct CaptureArgs {
 }
 
 impl CaptureArgs {
-    pub async fn execute(&self) -> anyhow::Result<()> {
+    pub async fn execute(&self, data_config: &DataConfig) -> anyhow::Result<()> {
+        let lock = DataDirLock::acquire_or_share(&self.output, "capture", self.share_data_dir)?;
+        let output = lock.dir().to_path_buf();
         tracing::info!(
-            output = ?self.output,
+            output = ?output,
             symbol = %self.symbol,
             "Starting data capture..."
         );
 
         // Create data recorder
         let recorder_config = RecorderConfig {
-            output_dir: self.output.clone(),
-            rotation_interval_secs: self.rotation_interval,
+            output_dir: output.clone(),
+            rotation_interval_secs: self
+                .rotation_interval
+                .unwrap_or(data_config.rotation_interval)
+                .as_secs(),
             buffer_size: self.buffer_size,
-            flush_interval_secs: self.flush_interval,
-        };
-        let recorder = DataRecorder::new(recorder_config);
+            flush_interval_secs: self
+                .flush_interval
+                .unwrap_or(data_config.flush_interval)
+                .as_secs(),
+            ..Default::default()
+        }
+        .with_formats(data_config.format, data_config.prefix_formats.clone())
+        .with_parquet(data_config.parquet.clone())
+        .with_durable_queue(data_config.durable_queue_config());
+        let recorder = DataRecorder::try_new(recorder_config)?;
+
+        // Enforce retention and pause recording if the disk fills up
+        let mut disk_manager = DiskManager::new(
+            output.clone(),
+            data_config.retention.clone(),
+            data_config.disk.clone(),
+            recorder.pause_flag(),
+            HealthRegistry::new(),
+        );
+        match Journal::open(output.join("retention_journal.jsonl")) {
+            Ok(journal) => {
+                if let Some(fingerprint) = fingerprint::active() {
+                    if let Err(e) = journal.write_header(fingerprint) {
+                        tracing::warn!(error = %e, "Failed to write journal header");
+                    }
+                }
+                disk_manager = disk_manager.with_journal(journal);
+            }
+            Err(e) => tracing::warn!(error = %e, "Failed to open retention journal"),
+        }
+        let disk_task = tokio::spawn(disk_manager.run());
+
+        // Backfill recent klines so the output has history from the start
+        let klines = KlineClient::new();
+        self.backfill_klines(&klines, &recorder, self.kline_backfill)
+            .await;
 
         // Create Binance feed
         let feed = BinanceFeed::new(&self.symbol);
@@ -61,11 +111,12 @@ impl CaptureArgs {
         println!(
             "Capturing {} data to {:?}",
             self.symbol.to_uppercase(),
-            self.output
+            output
         );
         println!("Press Ctrl+C to stop");
 
         let mut tick_count: u64 = 0;
+        let mut last_tick_ts = None;
         let start_time = Utc::now();
 
         loop {
@@ -82,6 +133,16 @@ impl CaptureArgs {
                             // Record to metrics
                             record_price_tick();
 
+                            // Fill any hole left by a disconnect
+                            if let Some(last) = last_tick_ts.replace(tick.exchange_ts) {
+                                let gap = tick.exchange_ts - last;
+                                if gap > chrono::Duration::minutes(1) {
+                                    tracing::warn!(gap_secs = gap.num_seconds(), "Price feed gap, backfilling klines");
+                                    let minutes = (gap.num_minutes() as usize + 1).min(self.kline_backfill);
+                                    self.backfill_klines(&klines, &recorder, minutes).await;
+                                }
+                            }
+
                             // Record to Parquet - non-blocking!
                             if let Err(e) = recorder.record_price(tick.clone()) {
                                 tracing::warn!(error = %e, "Failed to record price tick");
@@ -116,6 +177,8 @@ impl CaptureArgs {
             }
         }
 
+        disk_task.abort();
+
         // Print final stats
         let stats = recorder.stats();
         let elapsed = (Utc::now() - start_time).num_seconds();
@@ -126,8 +189,29 @@ impl CaptureArgs {
         println!("  Price ticks written: {}", stats.price_ticks_written);
         println!("  Files written: {}", stats.files_written);
         println!("  Channel drops: {}", stats.channel_drops);
-        println!("  Output directory: {:?}", self.output);
+        println!("  Skipped (low disk): {}", stats.records_skipped_low_disk);
+        println!("  Output directory: {:?}", output);
+        if let Some(fingerprint) = fingerprint::active() {
+            println!("  Config hash: {}", fingerprint.hash);
+        }
 
         Ok(())
     }
+
+    /// Fetch and record the last `minutes` of klines; failures are logged
+    async fn backfill_klines(&self, client: &KlineClient, recorder: &DataRecorder, minutes: usize) {
+        if minutes == 0 {
+            return;
+        }
+        let result = async {
+            let ticks = client.backfill_ticks(&self.symbol, minutes).await?;
+            recorder.record_klines(&ticks).await
+        }
+        .await;
+        match result {
+            Ok(Some(path)) => tracing::info!(minutes, path = ?path, "Recorded kline backfill"),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, "Kline backfill failed"),
+        }
+    }
 }
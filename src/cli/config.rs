@@ -0,0 +1,72 @@
+//! Config command implementation
+
+use crate::config::Config;
+use crate::fingerprint::ConfigFingerprint;
+use clap::{Args, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub action: Option<ConfigAction>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the fingerprint hash of a config file
+    Fingerprint(FingerprintArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct FingerprintArgs {
+    /// Config file to fingerprint (default: the global --config)
+    pub file: Option<PathBuf>,
+
+    /// Also print the serialized effective config
+    #[arg(long)]
+    pub full: bool,
+}
+
+impl ConfigArgs {
+    pub fn execute(&self, config: &Config, config_path: &str) -> anyhow::Result<()> {
+        match &self.action {
+            None => {
+                show(config);
+                Ok(())
+            }
+            Some(ConfigAction::Fingerprint(args)) => args.execute(config_path),
+        }
+    }
+}
+
+impl FingerprintArgs {
+    pub fn execute(&self, config_path: &str) -> anyhow::Result<()> {
+        let path = self
+            .file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(config_path));
+        let config = Config::load(&path)
+            .map_err(|e| anyhow::anyhow!("Could not load config from {:?}: {}", path, e))?;
+        let fingerprint = ConfigFingerprint::new(&config)?;
+        println!("{}", fingerprint.hash);
+        if self.full {
+            println!("{}", fingerprint.config);
+        }
+        Ok(())
+    }
+}
+
+fn show(config: &Config) {
+    println!("Current configuration:");
+    println!("  Feed: {} {}", config.feed.exchange, config.feed.symbol);
+    println!(
+        "  Market: {} {}",
+        config.market.asset, config.market.interval
+    );
+    println!("  Execution: {:?}", config.execution.mode);
+    println!(
+        "  Risk: Kelly={}, MaxPos={}%",
+        config.risk.kelly_fraction,
+        config.risk.max_position_pct * rust_decimal_macros::dec!(100)
+    );
+}
@@ -0,0 +1,204 @@
+//! Ctl command implementation
+
+use crate::config::{Config, ExecutionMode};
+use crate::engine::request_handoff;
+use crate::flags::{request_flag, FlagState};
+use crate::journal::Journal;
+use crate::risk::{
+    HaltStore, Ledger, LossCooldown, StrategyReview, HALT_JOURNAL_FILE, LEDGER_FILE,
+    LOSS_COOLDOWN_FILE, STRATEGY_REVIEW_FILE,
+};
+use crate::telemetry::{request_log_directives, EventCode};
+use chrono::Utc;
+use clap::{Args, Subcommand};
+use rust_decimal::Decimal;
+
+#[derive(Args, Debug)]
+pub struct CtlArgs {
+    #[command(subcommand)]
+    pub action: CtlAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CtlAction {
+    /// Acknowledge a hard halt so the next run may place orders again
+    AckHalt {
+        /// Halt id, as shown by `status`
+        id: String,
+    },
+    /// Lift a loss halt so the asset may be entered again
+    AckCooldown {
+        /// Asset, as shown by `status`
+        asset: String,
+    },
+    /// Clear a strategy review flag, restoring full sizing; a running
+    /// session applies it within a second
+    AckReview {
+        /// Asset, as shown by `status`
+        asset: String,
+    },
+    /// Add paper funds to the bankroll; a running session sizes against
+    /// them within a second
+    Deposit {
+        /// Amount in USD
+        amount: Decimal,
+        /// Note kept with the ledger entry
+        #[arg(long, default_value = "ctl deposit")]
+        reference: String,
+    },
+    /// Take paper funds out of the bankroll
+    Withdraw {
+        /// Amount in USD
+        amount: Decimal,
+        /// Note kept with the ledger entry
+        #[arg(long, default_value = "ctl withdraw")]
+        reference: String,
+    },
+    /// Ask the running session to hand over to a new process started
+    /// with `run --takeover`
+    Handoff,
+    /// Set a feature flag in the running session, which applies it
+    /// within a second
+    Flag {
+        /// Flag name, as under `[flags]`
+        name: String,
+        /// `off`, `on` or a percentage of signals, e.g. `25%`
+        state: FlagState,
+    },
+    /// Set the log filter of the running session, which applies it
+    /// within a second
+    LogLevel {
+        /// `EnvFilter` directives, e.g. `info,poly_hft::ws=debug`
+        directives: String,
+    },
+}
+
+impl CtlArgs {
+    pub fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        match &self.action {
+            CtlAction::AckHalt { id } => {
+                let data_dir = &config.data.output_dir;
+                let store = HaltStore::new(data_dir)
+                    .with_journal(Journal::open(data_dir.join(HALT_JOURNAL_FILE))?);
+                let record = store.acknowledge(id, Utc::now())?;
+                tracing::warn!(
+                    event_code = %EventCode::HaltAcknowledged,
+                    halt = %record.id,
+                    reason = ?record.reason,
+                    "Hard halt acknowledged"
+                );
+                println!("Acknowledged halt {}", record);
+                let remaining = store.pending()?;
+                if !remaining.is_empty() {
+                    println!("{} halt(s) still pending acknowledgement", remaining.len());
+                }
+                Ok(())
+            }
+            CtlAction::AckCooldown { asset } => {
+                let data_dir = &config.data.output_dir;
+                let mut cooldown = LossCooldown::new(config.risk.loss_cooldown.clone())
+                    .with_state_file(data_dir.join(LOSS_COOLDOWN_FILE))?
+                    .with_journal(Journal::open(data_dir.join(HALT_JOURNAL_FILE))?);
+                cooldown.acknowledge(asset, Utc::now())?;
+                tracing::warn!(
+                    event_code = %EventCode::HaltAcknowledged,
+                    asset = %asset,
+                    "Loss halt acknowledged"
+                );
+                println!("Acknowledged loss halt on {}", asset);
+                Ok(())
+            }
+            CtlAction::AckReview { asset } => {
+                let data_dir = &config.data.output_dir;
+                StrategyReview::new(config.risk.review.clone())
+                    .with_state_file(data_dir.join(STRATEGY_REVIEW_FILE))?
+                    .request_clear(asset)?;
+                tracing::warn!(
+                    event_code = %EventCode::HaltAcknowledged,
+                    asset = %asset,
+                    "Strategy review cleared"
+                );
+                println!("Cleared the strategy review flag on {}", asset);
+                Ok(())
+            }
+            CtlAction::Deposit { amount, reference } => {
+                let mut ledger = paper_ledger(config)?;
+                ledger.deposit(*amount, reference, Utc::now())?;
+                println!("Deposited {}, balance {}", amount, ledger.balance());
+                Ok(())
+            }
+            CtlAction::Withdraw { amount, reference } => {
+                let mut ledger = paper_ledger(config)?;
+                ledger.withdraw(*amount, reference, Utc::now())?;
+                println!("Withdrew {}, balance {}", amount, ledger.balance());
+                Ok(())
+            }
+            CtlAction::Handoff => {
+                let path = request_handoff(&config.data.output_dir)?;
+                println!("Handoff requested; the running session drains and writes its state");
+                println!(
+                    "Start the successor with `poly-hft run --takeover {}`",
+                    path.display()
+                );
+                Ok(())
+            }
+            CtlAction::Flag { name, state } => {
+                request_flag(&config.data.output_dir, name, *state)?;
+                println!("Requested flag {} = {}", name, state);
+                println!("A running session applies it within a second");
+                Ok(())
+            }
+            CtlAction::LogLevel { directives } => {
+                request_log_directives(&config.data.output_dir, directives)?;
+                println!("Requested log filter {}", directives);
+                println!("A running session applies it within a second");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The bankroll ledger, opened with the configured bankroll if new
+///
+/// Live funds move on chain, where reconciliation picks them up, so the
+/// ledger is only written by hand in paper mode.
+fn paper_ledger(config: &Config) -> anyhow::Result<Ledger> {
+    if config.execution.mode == ExecutionMode::Live {
+        anyhow::bail!(
+            "Deposits and withdrawals are for paper mode; live balances are reconciled with the exchange"
+        );
+    }
+    std::fs::create_dir_all(&config.data.output_dir)?;
+    Ledger::open(config.data.output_dir.join(LEDGER_FILE))?
+        .with_opening_deposit(config.risk.initial_bankroll, Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::{take_log_directives_request, LOG_LEVEL_REQUEST_FILE};
+
+    #[test]
+    fn test_log_level_is_left_for_the_running_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.data.output_dir = dir.path().to_path_buf();
+        let ctl = |directives: &str| {
+            CtlArgs {
+                action: CtlAction::LogLevel {
+                    directives: directives.to_string(),
+                },
+            }
+            .execute(&config)
+        };
+
+        assert!(ctl("debug,poly_hft::ws=loud").is_err());
+        assert!(!dir.path().join(LOG_LEVEL_REQUEST_FILE).exists());
+
+        ctl("info,poly_hft::orderbook=debug").unwrap();
+        assert_eq!(
+            take_log_directives_request(dir.path()).as_deref(),
+            Some("info,poly_hft::orderbook=debug")
+        );
+    }
+}
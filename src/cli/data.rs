@@ -0,0 +1,247 @@
+//! Data command implementation
+
+use crate::data::{
+    audit_book, benchmark_encoding, load_book_records, microstructure_stats, BookAudit,
+    ClobHistoryClient, PriceBackfill, DEFAULT_BACKFILL_CHUNK_HOURS, DEFAULT_BACKFILL_FIDELITY_MINS,
+};
+use crate::journal::Journal;
+use crate::report::markets_from_journal;
+use chrono::{DateTime, Duration, Utc};
+use clap::{Args, Subcommand};
+use rust_decimal::Decimal;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct DataArgs {
+    #[command(subcommand)]
+    pub action: DataAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DataAction {
+    /// Rewrite a Parquet capture with each encoding preset and compare them
+    BenchmarkEncoding {
+        /// Captured Parquet file to re-encode
+        sample_file: PathBuf,
+    },
+    /// Replay a token's captured order book through the merge logic and
+    /// diff it against every snapshot
+    AuditBook {
+        /// Capture directory
+        dir: PathBuf,
+        /// Token whose book to audit
+        #[arg(long)]
+        token: String,
+        /// Size difference still counted as a match
+        #[arg(long, default_value = "0")]
+        tolerance: Decimal,
+        /// Fail if more than this fraction of snapshots diverged
+        #[arg(long, default_value = "0")]
+        max_divergence: Decimal,
+        /// Divergences to detail, worst first
+        #[arg(long, default_value = "10")]
+        top: usize,
+    },
+    /// Fetch tokens' price history from the CLOB into `price_history`
+    /// files; a rerun of the same job resumes after the last chunk written
+    Backfill {
+        /// Token IDs to fetch (comma-separated)
+        #[arg(long, value_delimiter = ',', required_unless_present = "from_markets")]
+        tokens: Vec<String>,
+        /// Fetch both tokens of every market this session's journal opened
+        #[arg(long)]
+        from_markets: Option<PathBuf>,
+        /// Start of the history (ISO 8601)
+        #[arg(long)]
+        from: String,
+        /// End of the history (ISO 8601)
+        #[arg(long)]
+        to: String,
+        /// Output directory
+        #[arg(long, default_value = "./data")]
+        output: PathBuf,
+        /// Hours of history per request
+        #[arg(long, default_value_t = DEFAULT_BACKFILL_CHUNK_HOURS)]
+        chunk_hours: u64,
+        /// Minutes between price points
+        #[arg(long, default_value_t = DEFAULT_BACKFILL_FIDELITY_MINS)]
+        fidelity: u32,
+    },
+    /// Spread, depth, update rate and one-sided books of the YES book by
+    /// minute of the market window, over every captured window
+    Stats {
+        /// Capture directory
+        dir: PathBuf,
+        /// Session whose journal's markets window the tokens; the capture
+        /// directory's own journal when it has one
+        #[arg(long)]
+        from_markets: Option<PathBuf>,
+        /// Also write the table, as Parquet or CSV by extension
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+impl DataArgs {
+    pub async fn execute(&self) -> anyhow::Result<()> {
+        match &self.action {
+            DataAction::BenchmarkEncoding { sample_file } => {
+                let work_dir =
+                    std::env::temp_dir().join(format!("poly-hft-encoding-{}", std::process::id()));
+                let results = benchmark_encoding(sample_file, &work_dir);
+                let _ = std::fs::remove_dir_all(&work_dir);
+                let results = results?;
+                let original = std::fs::metadata(sample_file)?.len();
+
+                println!(
+                    "Encoding benchmark for {:?} ({} bytes)",
+                    sample_file, original
+                );
+                println!(
+                    "  {:<14} {:>12} {:>8} {:>10} {:>10}",
+                    "preset", "bytes", "ratio", "write ms", "read ms"
+                );
+                for result in results {
+                    println!(
+                        "  {:<14} {:>12} {:>8.3} {:>10.1} {:>10.1}",
+                        result.preset,
+                        result.bytes,
+                        result.bytes as f64 / original.max(1) as f64,
+                        result.write_time.as_secs_f64() * 1e3,
+                        result.read_time.as_secs_f64() * 1e3
+                    );
+                }
+                Ok(())
+            }
+            DataAction::AuditBook {
+                dir,
+                token,
+                tolerance,
+                max_divergence,
+                top,
+            } => {
+                let records = load_book_records(dir, token)?;
+                if records.is_empty() {
+                    anyhow::bail!("no order book rows for token {} under {:?}", token, dir);
+                }
+                let audit = audit_book(&records, *tolerance);
+                print_audit(token, &audit, *top);
+                if audit.divergence_rate() > *max_divergence {
+                    anyhow::bail!(
+                        "{} of {} snapshots diverged from the merged book",
+                        audit.divergences.len(),
+                        audit.compared
+                    );
+                }
+                Ok(())
+            }
+            DataAction::Backfill {
+                tokens,
+                from_markets,
+                from,
+                to,
+                output,
+                chunk_hours,
+                fidelity,
+            } => {
+                let mut tokens = tokens.clone();
+                if let Some(session) = from_markets {
+                    let journal = Journal::read_all(session.join("trade_journal.jsonl"))?;
+                    for market in markets_from_journal(&journal) {
+                        tokens.extend([market.yes_token_id, market.no_token_id]);
+                    }
+                }
+                let mut seen = std::collections::HashSet::new();
+                tokens.retain(|t| seen.insert(t.clone()));
+                if tokens.is_empty() {
+                    anyhow::bail!("no tokens to backfill");
+                }
+                let (from, to) = (parse_time(from)?, parse_time(to)?);
+                if from >= to {
+                    anyhow::bail!("--from must be before --to");
+                }
+
+                let job = PriceBackfill::new(tokens.clone(), from, to, output.clone())
+                    .with_chunk(Duration::hours(*chunk_hours as i64));
+                let mut client = ClobHistoryClient::new().with_fidelity(*fidelity);
+                let report = job.run(&mut client).await?;
+                println!(
+                    "Backfilled {} tokens over {} chunks ({} fetched, {} already done): {} points",
+                    tokens.len(),
+                    report.chunks,
+                    report.fetched,
+                    report.skipped,
+                    report.points
+                );
+                Ok(())
+            }
+            DataAction::Stats {
+                dir,
+                from_markets,
+                output,
+            } => {
+                let journal = from_markets
+                    .as_ref()
+                    .unwrap_or(dir)
+                    .join("trade_journal.jsonl");
+                let markets = match from_markets.is_some() || journal.exists() {
+                    true => markets_from_journal(&Journal::read_all(&journal)?),
+                    false => vec![],
+                };
+                let report = microstructure_stats(dir, &markets)?;
+                print!("{}", report);
+                if let Some(output) = output {
+                    report.write(output)?;
+                    println!("Wrote {:?}", output);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn parse_time(s: &str) -> anyhow::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| anyhow::anyhow!("Invalid timestamp '{}': {}", s, e))
+}
+
+fn print_audit(token: &str, audit: &BookAudit, top: usize) {
+    println!(
+        "Book audit for {}: {} rows ({} snapshots, {} deltas), {} of {} snapshots diverged ({:.2}%)",
+        token,
+        audit.rows,
+        audit.snapshots,
+        audit.deltas,
+        audit.divergences.len(),
+        audit.compared,
+        audit.divergence_rate() * Decimal::ONE_HUNDRED
+    );
+    if audit.keep_alives > 0 {
+        println!(
+            "  {} keep-alive rows of unchanged books, longest gap between rows {}s",
+            audit.keep_alives,
+            audit.longest_gap.num_seconds()
+        );
+    }
+    if audit.duplicates + audit.out_of_order > 0 {
+        println!(
+            "  Dropped {} duplicate and {} out-of-order rows",
+            audit.duplicates, audit.out_of_order
+        );
+    }
+    if audit.divergences.is_empty() {
+        return;
+    }
+    println!("\nTimeline:");
+    for diff in &audit.divergences {
+        println!("  {}", diff);
+    }
+    println!("\nWorst offenders:");
+    for diff in audit.worst(top) {
+        println!("  {}", diff);
+        for level in diff.levels() {
+            println!("    {}", level);
+        }
+    }
+}
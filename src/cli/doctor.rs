@@ -0,0 +1,35 @@
+//! Doctor command implementation
+
+use crate::config::Config;
+use crate::doctor::{Doctor, DEFAULT_CHECK_TIMEOUT_SECS};
+use clap::Args;
+use std::time::Duration;
+
+#[derive(Args, Debug)]
+pub struct DoctorArgs {
+    /// Seconds each check may take before it fails
+    #[arg(long, default_value_t = DEFAULT_CHECK_TIMEOUT_SECS)]
+    pub timeout_secs: u64,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl DoctorArgs {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        let report = Doctor::new()
+            .with_timeout(Duration::from_secs(self.timeout_secs))
+            .run(config)
+            .await;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            println!("{}", report);
+        }
+        if !report.passed() {
+            anyhow::bail!("{} doctor check(s) failed", report.failures().count());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+//! Eval command implementation
+
+use crate::config::Config;
+use crate::engine::{evaluate, Snapshot};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct EvalArgs {
+    /// Market as JSON, inline or a file path
+    #[arg(long)]
+    pub market: String,
+
+    /// YES token order book as JSON, inline or a file path
+    #[arg(long)]
+    pub book: String,
+
+    /// NO token order book as JSON, inline or a file path; a NO book that
+    /// has already repriced marks the YES book stale
+    #[arg(long)]
+    pub no_book: Option<String>,
+
+    /// Spot price at evaluation time
+    #[arg(long)]
+    pub spot: Decimal,
+
+    /// Evaluation time, ISO 8601 (default: now)
+    #[arg(long)]
+    pub at: Option<String>,
+
+    /// CSV of recent spot ticks (timestamp,price) for the volatility estimate
+    #[arg(long)]
+    pub ticks: Option<PathBuf>,
+
+    /// Print the explanation as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl EvalArgs {
+    pub fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        let at = match &self.at {
+            Some(s) => parse_time(s)?,
+            None => Utc::now(),
+        };
+        let snapshot = Snapshot {
+            market: load_json(&self.market)?,
+            book: load_json(&self.book)?,
+            no_book: self.no_book.as_deref().map(load_json).transpose()?,
+            spot: self.spot,
+            at,
+            ticks: match &self.ticks {
+                Some(path) => load_ticks(path)?,
+                None => Vec::new(),
+            },
+        };
+
+        let explanation = evaluate(config, &snapshot);
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&explanation)?);
+        } else {
+            println!("{}", explanation);
+        }
+        Ok(())
+    }
+}
+
+fn parse_time(s: &str) -> anyhow::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| anyhow::anyhow!("Invalid timestamp '{}': {}", s, e))
+}
+
+/// Parse `value` as JSON, or read it from a file when it is not an object
+fn load_json<T: DeserializeOwned>(value: &str) -> anyhow::Result<T> {
+    let text = if value.trim_start().starts_with('{') {
+        value.to_string()
+    } else {
+        std::fs::read_to_string(value)
+            .map_err(|e| anyhow::anyhow!("Could not read {}: {}", value, e))?
+    };
+    serde_json::from_str(&text).map_err(|e| anyhow::anyhow!("Invalid JSON in {}: {}", value, e))
+}
+
+/// Read `timestamp,price` rows; a header line is skipped
+fn load_ticks(path: &Path) -> anyhow::Result<Vec<(DateTime<Utc>, Decimal)>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Could not read {:?}: {}", path, e))?;
+    let mut ticks = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (i == 0 && line.starts_with("timestamp")) {
+            continue;
+        }
+        let (ts, price) = line.split_once(',').ok_or_else(|| {
+            anyhow::anyhow!("{:?} line {}: expected timestamp,price", path, i + 1)
+        })?;
+        let price = price
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("{:?} line {}: invalid price: {}", path, i + 1, e))?;
+        ticks.push((parse_time(ts.trim())?, price));
+    }
+    Ok(ticks)
+}
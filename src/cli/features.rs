@@ -0,0 +1,95 @@
+//! Features command implementation
+
+use crate::backtest::EventStream;
+use crate::data::features::{write_feature_rows, Feature, FeatureConfig, FeatureExtractor};
+use clap::{Args, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct FeaturesArgs {
+    #[command(subcommand)]
+    pub action: FeaturesAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FeaturesAction {
+    /// Extract a labelled training dataset from captured data
+    Extract(ExtractArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ExtractArgs {
+    /// Directory containing captured Parquet files
+    #[arg(long, default_value = "./data")]
+    pub data_dir: PathBuf,
+
+    /// Output Parquet file
+    #[arg(long, default_value = "./output/features.parquet")]
+    pub output: PathBuf,
+
+    /// Comma-separated feature columns to emit (default: all)
+    #[arg(long, value_delimiter = ',')]
+    pub features: Vec<String>,
+}
+
+impl FeaturesArgs {
+    pub async fn execute(&self) -> anyhow::Result<()> {
+        match &self.action {
+            FeaturesAction::Extract(args) => args.execute().await,
+        }
+    }
+}
+
+impl ExtractArgs {
+    pub async fn execute(&self) -> anyhow::Result<()> {
+        let features = parse_features(&self.features)?;
+        let config = FeatureConfig {
+            features: features.clone(),
+            ..Default::default()
+        };
+
+        tracing::info!(data_dir = ?self.data_dir, "Extracting features...");
+        let events = EventStream::new(self.data_dir.clone(), None, None);
+        let rows = FeatureExtractor::new(config).extract(events);
+        let labelled = rows.iter().filter(|r| r.label_win.is_some()).count();
+
+        write_feature_rows(&self.output, &rows, &features)?;
+
+        println!(
+            "Wrote {} rows ({} labelled) to {:?}",
+            rows.len(),
+            labelled,
+            self.output
+        );
+        Ok(())
+    }
+}
+
+/// Resolve feature names, defaulting to all features
+fn parse_features(names: &[String]) -> anyhow::Result<Vec<Feature>> {
+    if names.is_empty() {
+        return Ok(Feature::ALL.to_vec());
+    }
+    names
+        .iter()
+        .map(|name| {
+            Feature::from_name(name.trim())
+                .ok_or_else(|| anyhow::anyhow!("Unknown feature '{}'", name))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_features() {
+        assert_eq!(parse_features(&[]).unwrap().len(), Feature::ALL.len());
+        assert_eq!(
+            parse_features(&["spread".to_string(), " slope".to_string()]).unwrap(),
+            vec![Feature::Spread, Feature::Slope]
+        );
+        assert!(parse_features(&["bogus".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! Gamma command implementation
+
+use crate::config::Config;
+use crate::market::{GammaClient, GammaSnapshot, DEFAULT_SNAPSHOT_MAX_ITEMS, GAMMA_FIXTURE_DIR};
+use chrono::Utc;
+use clap::{Args, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct GammaArgs {
+    #[command(subcommand)]
+    pub action: GammaAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GammaAction {
+    /// Record current Gamma responses as scrubbed fixtures and check them
+    /// against the strict schema mirrors
+    Snapshot {
+        /// Directory the fixtures are written to
+        #[arg(long, default_value = GAMMA_FIXTURE_DIR)]
+        output: PathBuf,
+        /// Items kept of each array
+        #[arg(long, default_value_t = DEFAULT_SNAPSHOT_MAX_ITEMS)]
+        max_items: usize,
+    },
+}
+
+impl GammaArgs {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        match &self.action {
+            GammaAction::Snapshot { output, max_items } => {
+                let client = GammaClient::from_config(&config.market);
+                let snapshot = GammaSnapshot::fetch(&client, Utc::now())
+                    .await?
+                    .scrubbed(*max_items);
+                snapshot.save(output)?;
+                println!("Wrote Gamma fixtures to {}", output.display());
+
+                let divergences = snapshot.divergences();
+                if divergences.is_empty() {
+                    println!("Responses match the strict schema mirrors");
+                    return Ok(());
+                }
+                println!("Responses diverge from the strict schema mirrors:");
+                for divergence in &divergences {
+                    println!("  {divergence}");
+                }
+                anyhow::bail!(
+                    "{} divergence(s); update src/market/schema.rs and the Gamma structs",
+                    divergences.len()
+                )
+            }
+        }
+    }
+}
@@ -4,15 +4,38 @@
 //! - `run`: Start paper trading
 //! - `capture`: Data capture only (no trading)
 //! - `backtest`: Run backtest on captured data
+//! - `eval`: Explain the trade decision for one market snapshot
+//! - `data`: Maintenance helpers for captured data
+//! - `features`: Extract ML training datasets from captured data
+//! - `report`: Per-market session timelines for charting
+//! - `gamma`: Record Gamma responses as contract fixtures
 //! - `status`: Show current state
-//! - `config`: Show/edit configuration
+//! - `doctor`: Check the environment before a session
+//! - `ctl`: Operator actions such as acknowledging a hard halt
+//! - `config`: Show configuration or print its fingerprint
 
 mod backtest;
 mod capture;
+mod config;
+mod ctl;
+mod data;
+mod doctor;
+mod eval;
+mod features;
+mod gamma;
+mod report;
 mod run;
 
 pub use backtest::BacktestArgs;
 pub use capture::CaptureArgs;
+pub use config::{ConfigAction, ConfigArgs, FingerprintArgs};
+pub use ctl::{CtlAction, CtlArgs};
+pub use data::{DataAction, DataArgs};
+pub use doctor::DoctorArgs;
+pub use eval::EvalArgs;
+pub use features::{ExtractArgs, FeaturesAction, FeaturesArgs};
+pub use gamma::{GammaAction, GammaArgs};
+pub use report::{ReportAction, ReportArgs};
 pub use run::RunArgs;
 
 use clap::{Parser, Subcommand};
@@ -38,8 +61,31 @@ pub enum Commands {
     Capture(CaptureArgs),
     /// Run backtest on captured data
     Backtest(BacktestArgs),
+    /// Explain what the bot would do with one market snapshot
+    Eval(EvalArgs),
+    /// Captured data helpers
+    Data(DataArgs),
+    /// Offline feature extraction
+    Features(FeaturesArgs),
+    /// Session reports
+    Report(ReportArgs),
+    /// Gamma API contract fixtures
+    Gamma(GammaArgs),
     /// Show current state
-    Status,
-    /// Show/edit configuration
-    Config,
+    Status {
+        /// Also show internal structure sizes and channel depths from the
+        /// running bot's latest snapshot
+        #[arg(long)]
+        internals: bool,
+        /// Also show every parameter the running bot has in force, where
+        /// it came from and what changed it
+        #[arg(long)]
+        effective_config: bool,
+    },
+    /// Check clock, connectivity, disk, ports, config and credentials
+    Doctor(DoctorArgs),
+    /// Operator actions on a stopped or running bot
+    Ctl(CtlArgs),
+    /// Show configuration or print its fingerprint
+    Config(ConfigArgs),
 }
@@ -0,0 +1,343 @@
+//! Report command implementation
+
+use crate::backtest::{write_trade_tape, TapeRow};
+use crate::config::Config;
+use crate::execution::{read_trades, SlippageCalibration};
+use crate::journal::Journal;
+use crate::model::VolatilityEstimator;
+use crate::report::{
+    closed_trades, load_timeline, parse_display_offset, read_sheet, write_sheet, CohortReport,
+    CostReport, ExpectedValueReport, JournalLedger, PnlReconciler, ShadowReport, SheetLayout,
+    DEFAULT_TIMELINE_RESOLUTION_MS,
+};
+use crate::risk::{Ledger, StrategyReview, LEDGER_FILE, STRATEGY_REVIEW_FILE};
+use chrono::{Duration, NaiveDate};
+use clap::{Args, Subcommand};
+use rust_decimal::Decimal;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    #[command(subcommand)]
+    pub action: ReportAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReportAction {
+    /// Export one market's spot, YES ask, expected price and trade markers
+    /// as timeline JSON
+    Timeline {
+        /// Market condition id
+        #[arg(long)]
+        market: String,
+        /// Session directory holding the trade journal and captured data
+        #[arg(long, default_value = "./data")]
+        session: PathBuf,
+        /// Downsampling bucket width in milliseconds
+        #[arg(long, default_value_t = DEFAULT_TIMELINE_RESOLUTION_MS)]
+        resolution_ms: i64,
+        /// Write the JSON here instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Rebuild a session's positions from its exported fills and compare
+    /// them with its trade journal
+    Reconcile {
+        /// Session directory holding the trade journal
+        #[arg(long, default_value = "./data")]
+        session: PathBuf,
+        /// Fills written by `run --export-trades`
+        #[arg(long)]
+        trades: PathBuf,
+        /// Largest tolerated P&L or fee difference in USD, instead of
+        /// [reconcile] tolerance
+        #[arg(long)]
+        tolerance: Option<Decimal>,
+    },
+    /// Measure realized spread, fees and slippage of exported fills against
+    /// the mid and fair value journaled when each order was submitted
+    Costs {
+        /// Session directory holding the trade journal
+        #[arg(long, default_value = "./data")]
+        session: PathBuf,
+        /// Fills written by `run --export-trades`
+        #[arg(long)]
+        trades: PathBuf,
+        /// Write measured slippage per size bucket here, for
+        /// [execution.costs] calibration
+        #[arg(long)]
+        calibrate: Option<PathBuf>,
+        /// Also write the report as JSON here
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Summarize how many paper entries the market's trade prints could
+    /// have filled, over every session's shadow fills
+    Shadow {
+        /// Data directory holding the shadow fills
+        #[arg(long, default_value = "./data")]
+        session: PathBuf,
+        /// Write the slippage per size bucket the prints needed here, for
+        /// [execution.costs] calibration
+        #[arg(long)]
+        calibrate: Option<PathBuf>,
+    },
+    /// List the bankroll ledger's deposits, withdrawals, settlements, fees
+    /// and adjustments with the running and ending balance
+    Ledger {
+        /// Data directory holding the ledger
+        #[arg(long, default_value = "./data")]
+        session: PathBuf,
+        /// Only entries booked on or after this UTC date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+    },
+    /// Adverse excursion percentiles per asset, their history and any
+    /// strategy review flag
+    Review {
+        /// Data directory holding the review state
+        #[arg(long, default_value = "./data")]
+        session: PathBuf,
+    },
+    /// Compare the expected value each signal claimed at entry with what
+    /// its trade realized
+    ExpectedValue {
+        /// Directory searched for trade tapes and position archives
+        #[arg(long, default_value = "./data")]
+        session: PathBuf,
+        /// Also write the report as JSON here
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Compare P&L and win rate between the treated and untreated
+    /// cohorts of each feature flag rolled out to a percentage of signals
+    Cohorts {
+        /// Directory searched for trade tapes and position archives
+        #[arg(long, default_value = "./data")]
+        session: PathBuf,
+        /// Also write the report as JSON here
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Export closed trades in the P&L spreadsheet's CSV layout
+    ExportCsv {
+        /// Directory searched for trade tapes and position archives
+        #[arg(long, default_value = "./data")]
+        session: PathBuf,
+        /// Columns to write: simple or detailed
+        #[arg(long, default_value = "simple")]
+        format: String,
+        /// Only trades entered on or after this UTC date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Offset dates are shown in: UTC or ±HH:MM
+        #[arg(long, default_value = "UTC")]
+        tz: String,
+        /// Write the CSV here instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Import spreadsheet CSV rows as a trade tape
+    ImportCsv {
+        /// CSV in the spreadsheet's layout
+        #[arg(long)]
+        input: PathBuf,
+        /// Trade tape Parquet to write
+        #[arg(long)]
+        output: PathBuf,
+    },
+}
+
+impl ReportArgs {
+    pub fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        match &self.action {
+            ReportAction::Timeline {
+                market,
+                session,
+                resolution_ms,
+                output,
+            } => {
+                let volatility =
+                    VolatilityEstimator::new(config.model.volatility_window_minutes.to_chrono())
+                        .with_max_samples(config.model.volatility_max_samples);
+                let timeline = load_timeline(
+                    session,
+                    market,
+                    Duration::milliseconds(*resolution_ms),
+                    volatility,
+                )?;
+                let json = serde_json::to_string(&timeline)?;
+                match output {
+                    Some(path) => {
+                        std::fs::write(path, json)?;
+                        println!(
+                            "Wrote {} spot, {} ask and {} expected points, {} events to {:?}",
+                            timeline.spot.len(),
+                            timeline.yes_ask.len(),
+                            timeline.expected_price.len(),
+                            timeline.events.len(),
+                            path
+                        );
+                    }
+                    None => println!("{}", json),
+                }
+                Ok(())
+            }
+            ReportAction::Reconcile {
+                session,
+                trades,
+                tolerance,
+            } => {
+                let fills = read_trades(trades)?;
+                let journal = Journal::read_all(session.join("trade_journal.jsonl"))?;
+                let ledger = JournalLedger::from_entries(&journal);
+                let reconciliation =
+                    PnlReconciler::new(&fills, &ledger.markets, &ledger.settlements)
+                        .with_journal(&ledger)
+                        .run(tolerance.unwrap_or(config.reconcile.tolerance))?;
+                print!("{}", reconciliation);
+                if !reconciliation.passed {
+                    anyhow::bail!(
+                        "{} discrepancies between fills and journal",
+                        reconciliation.discrepancies.len()
+                    );
+                }
+                Ok(())
+            }
+            ReportAction::Costs {
+                session,
+                trades,
+                calibrate,
+                output,
+            } => {
+                let fills = read_trades(trades)?;
+                let journal = Journal::read_all(session.join("trade_journal.jsonl"))?;
+                let report = CostReport::from_fills(&fills, &journal);
+                print!("{}", report);
+                if let Some(path) = output {
+                    report.write(path)?;
+                    println!("Wrote cost report to {:?}", path);
+                }
+                if let Some(path) = calibrate {
+                    write_calibration(config, &report.calibration(), path)?;
+                }
+                Ok(())
+            }
+            ReportAction::Shadow { session, calibrate } => {
+                let report = ShadowReport::load(session)?;
+                print!("{}", report);
+                if let Some(path) = calibrate {
+                    write_calibration(config, &report.calibration(), path)?;
+                }
+                Ok(())
+            }
+            ReportAction::Ledger { session, since } => {
+                let path = session.join(LEDGER_FILE);
+                if !path.exists() {
+                    anyhow::bail!("No ledger at {:?}", path);
+                }
+                let since = since.map(|d| d.and_hms_opt(0, 0, 0).expect("midnight").and_utc());
+                print!("{}", Ledger::open(path)?.report(since));
+                Ok(())
+            }
+            ReportAction::Review { session } => {
+                let path = session.join(STRATEGY_REVIEW_FILE);
+                if !path.exists() {
+                    anyhow::bail!("No strategy review at {:?}", path);
+                }
+                let review =
+                    StrategyReview::new(config.risk.review.clone()).with_state_file(path)?;
+                print!("{}", review);
+                Ok(())
+            }
+            ReportAction::ExpectedValue { session, output } => {
+                let rows = closed_trades(session)?;
+                let report =
+                    ExpectedValueReport::new(&rows, config.expected_value.bootstrap_resamples);
+                print!("{}", report);
+                if report.warn_on_shortfall(&config.expected_value) {
+                    println!("!! Realized P&L well below expected; re-tune the entry thresholds");
+                }
+                if let Some(path) = output {
+                    report.write(path)?;
+                    println!("Wrote expected value report to {:?}", path);
+                }
+                Ok(())
+            }
+            ReportAction::Cohorts { session, output } => {
+                let report = CohortReport::new(&closed_trades(session)?);
+                if report.is_empty() {
+                    println!("No feature flag was rolling out for these trades");
+                    return Ok(());
+                }
+                print!("{}", report);
+                if let Some(path) = output {
+                    report.write(path)?;
+                    println!("Wrote flag cohort report to {:?}", path);
+                }
+                Ok(())
+            }
+            ReportAction::ExportCsv {
+                session,
+                format,
+                since,
+                tz,
+                output,
+            } => {
+                let layout: SheetLayout = format.parse()?;
+                let offset = parse_display_offset(tz)?;
+                let mut rows = closed_trades(session)?;
+                if let Some(since) = since {
+                    let since = since.and_hms_opt(0, 0, 0).expect("midnight").and_utc();
+                    rows.retain(|r| r.entry_time >= since);
+                }
+                match output {
+                    Some(path) => {
+                        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                        write_sheet(&mut file, &rows, layout, offset)?;
+                        println!("Wrote {} trades to {:?}", rows.len(), path);
+                    }
+                    None => write_sheet(&mut std::io::stdout().lock(), &rows, layout, offset)?,
+                }
+                Ok(())
+            }
+            ReportAction::ImportCsv { input, output } => {
+                let sheet = read_sheet(BufReader::new(std::fs::File::open(input)?))?;
+                let rows: Vec<TapeRow> = sheet
+                    .iter()
+                    .enumerate()
+                    .map(|(i, row)| row.to_tape(i + 1))
+                    .collect();
+                write_trade_tape(output, &rows)?;
+                println!("Imported {} trades to {:?}", rows.len(), output);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Write `calibration` to `path` and print its buckets
+fn write_calibration(
+    config: &Config,
+    calibration: &SlippageCalibration,
+    path: &Path,
+) -> anyhow::Result<()> {
+    calibration.write(path)?;
+    let min_samples = config.execution.costs.min_calibration_samples;
+    println!("Wrote slippage calibration to {:?}", path);
+    for bucket in &calibration.buckets {
+        println!(
+            "  up to {:>6} shares: {:>4} samples, mean slippage {}{}",
+            bucket.max_size.map_or("any".to_string(), |s| s.to_string()),
+            bucket.samples,
+            bucket.mean_slippage.round_dp(4),
+            if bucket.samples >= min_samples {
+                ""
+            } else {
+                " (too few samples, constants used)"
+            }
+        );
+    }
+    Ok(())
+}
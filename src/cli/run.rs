@@ -1,18 +1,1280 @@
 //! Run command implementation
 
+use crate::backtest::{
+    write_trade_tape, BacktestEvent, Scenario, ScenarioTracker, TRADE_TAPE_FILE,
+};
+use crate::bus::{BusReceiver, MarketDataBus, MarketDataEvent};
+use crate::clock::ClockSync;
+use crate::config::{Config, DataConfig, ExecutionMode};
+use crate::data::{DataDirLock, DataRecorder, HistoryArchive, LockError, RecorderConfig};
+use crate::doctor::Doctor;
+use crate::duration::DurationConfig;
+use crate::effective::EFFECTIVE_CONFIG_FILE;
+use crate::engine::{
+    ready_path, take_handoff_request, HandoffState, TradingEngine, WarmState, HANDOFF_FILE,
+    WARM_STATE_FILE,
+};
+use crate::execution::{
+    write_trades, ClobClient, CollateralBalance, ExecutionEngine, Fill, IntentLog, NoopEngine,
+    PaperEngine, INTENT_LOG_FILE,
+};
+use crate::feed::{BinanceFeed, KlineClient, LagAwareReceiver, PriceFeed};
+use crate::fingerprint;
+use crate::flags::{self, FlagWatcher};
+use crate::journal::Journal;
+use crate::leader::Leadership;
+use crate::market::{forward_books, GammaClient, Market, PreOpenPreparer, PreOpenTask};
+use crate::orderbook::{BookCheckpoint, PolymarketClient, BOOK_CHECKPOINT_FILE};
+use crate::report::{
+    shadow_file, AttributionReport, CanaryMetrics, CanaryReport, CohortReport, CostReport,
+    ExpectedValueReport, JournalLedger, PnlReconciler, PnlReconciliation, ShadowReport,
+    CANARY_REPORT_FILE, COST_REPORT_FILE, EXPECTED_VALUE_FILE, FLAG_COHORT_FILE,
+    PNL_ATTRIBUTION_FILE, PNL_RECONCILIATION_FILE,
+};
+use crate::risk::{
+    HaltStore, Ledger, LossCooldown, RateLimiter, ResolutionBook, StrategyReview, TradingSchedule,
+    HALT_JOURNAL_FILE, LEDGER_FILE, LOSS_COOLDOWN_FILE, PENDING_RESOLUTIONS_FILE, RATE_CAPS_FILE,
+    STRATEGY_REVIEW_FILE,
+};
+use crate::signal::Side;
+use crate::sim::{Breakpoint, MarketEvent, Pacer, Simulation, Speed, Until};
+use crate::stream;
+use crate::supervisor::{RestartPolicy, Supervisor, TaskState, TASK_JOURNAL_FILE};
+use crate::symbols::SymbolMap;
+use crate::telemetry::{
+    apply_log_directives_request, set_warm_start, ChannelDepth, EventCode, HealthRegistry,
+    HealthState, INTERNALS_FILE,
+};
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
 use clap::Args;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
 
 #[derive(Args, Debug)]
 pub struct RunArgs {
     /// Enable verbose output
     #[arg(short, long)]
     pub verbose: bool,
+
+    /// Write all paper fills to this file on shutdown (.csv or .parquet)
+    #[arg(long)]
+    pub export_trades: Option<PathBuf>,
+
+    /// Trade synthetic data from the [sim] config section instead of live feeds
+    #[arg(long)]
+    pub sim: bool,
+
+    /// Override the simulated session length
+    #[arg(long, requires = "sim")]
+    pub sim_minutes: Option<u64>,
+
+    /// Inject the perturbations in this TOML scenario file into the
+    /// simulated stream and summarize the risk layer per perturbed window
+    #[arg(long, requires = "sim")]
+    pub scenario: Option<PathBuf>,
+
+    /// Pace the simulated stream: max, realtime, or a factor such as 10x
+    #[arg(long, requires = "sim", default_value = "max")]
+    pub speed: Speed,
+
+    /// Wait for Enter at each breakpoint, by default every signal
+    #[arg(long, requires = "sim")]
+    pub step: bool,
+
+    /// Stop at: signal, open, close, gap, or an RFC 3339 time; repeatable
+    #[arg(long, requires = "sim")]
+    pub break_on: Vec<Breakpoint>,
+
+    /// Fast-forward to the first signal, or the first rejection of a code
+    /// (e.g. rejection:edge_too_small), then stop
+    #[arg(long, requires = "sim")]
+    pub until: Option<Until>,
+
+    /// If another process holds the data directory, write to a subdirectory of it
+    #[arg(long)]
+    pub share_data_dir: bool,
+
+    /// Run sizing, risk, and position tracking against instant simulated
+    /// fills without submitting anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Trade paper through the live path for this long (e.g. 30m, 6h, 2d),
+    /// then judge the session against [canary] and exit non-zero on a no-go
+    #[arg(long, conflicts_with_all = ["sim", "dry_run"])]
+    pub canary: Option<String>,
+
+    /// Run the doctor checks first and refuse to start if any fails
+    #[arg(long, conflicts_with = "sim")]
+    pub preflight: bool,
+
+    /// Take over the session a draining process handed over in this file,
+    /// written after `poly-hft ctl handoff`
+    #[arg(long, conflicts_with_all = ["sim", "canary"])]
+    pub takeover: Option<PathBuf>,
 }
 
 impl RunArgs {
-    pub async fn execute(&self) -> anyhow::Result<()> {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        match (self.sim, self.dry_run) {
+            (true, true) => self.execute_sim(config, NoopEngine::new()).await,
+            (true, false) => self.execute_sim(config, paper_engine(config)?).await,
+            (false, true) => self.execute_live(config, NoopEngine::new()).await,
+            (false, false) => self.execute_live(config, paper_engine(config)?).await,
+        }
+    }
+
+    async fn execute_live<E: ExecutionEngine>(
+        &self,
+        config: &Config,
+        mut execution: E,
+    ) -> anyhow::Result<()> {
+        // Fail before anything starts if the feed does not price the markets
+        let symbols = SymbolMap::from_config(config)?;
+
+        if self.preflight {
+            let report = Doctor::new().serving_metrics().run(config).await;
+            println!("{}", report);
+            if !report.passed() {
+                anyhow::bail!(
+                    "Preflight failed: {}",
+                    report
+                        .failures()
+                        .map(|c| c.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+        }
+
+        // A canary proves the live credentials before trading paper
+        let canary = self.canary.as_deref().map(parse_duration).transpose()?;
+        if canary.is_some() {
+            let live = config.execution.live.clone().ok_or_else(|| {
+                anyhow::anyhow!("--canary needs [execution.live] credentials to validate")
+            })?;
+            ClobClient::new(live)
+                .check_auth()
+                .await
+                .context("CLOB rejected the [execution.live] credentials")?;
+            tracing::info!("Live credentials accepted");
+        }
+
+        // Journals and captured data both land in the data directory
+        // A successor starts while the process handing over still holds it
+        let share = self.share_data_dir || self.takeover.is_some();
+        let lock = DataDirLock::acquire_or_share(&config.data.output_dir, "run", share)?;
+        let output_dir = lock.dir().to_path_buf();
+
+        // Older fills and closed positions move to disk in long sessions
+        let history = &config.data.history;
+        let archive = HistoryArchive::for_session(&output_dir, Utc::now());
+        execution.retain_fills(archive.clone(), history.max_fills);
+
+        // Journals are written in batches, and journaled entries also go
+        // out to event stream clients
+        let events = stream::start(&config.stream).await?;
+        let open_journal = |path: PathBuf| -> anyhow::Result<Journal> {
+            let journal = Journal::open(path)?.batched(&config.data.journal)?;
+            Ok(match &events {
+                Some(events) => journal.with_stream(events.clone()),
+                None => journal,
+            })
+        };
+
+        let trade_journal = open_journal(output_dir.join("trade_journal.jsonl"))?;
+        if let Some(fingerprint) = fingerprint::active() {
+            trade_journal.write_header(fingerprint)?;
+        }
+        let health = HealthRegistry::new();
+        let mut engine = TradingEngine::new(config, execution)
+            .with_outcome_journal(open_journal(output_dir.join("outcome_journal.jsonl"))?)
+            .with_trade_journal(trade_journal)
+            .with_health(health.clone())
+            .with_position_archive(archive.clone(), history.max_closed_positions)
+            .with_intent_log(IntentLog::open(output_dir.join(INTENT_LOG_FILE))?)
+            .with_shadow_fills(config.execution.shadow.clone())
+            .with_effective_config_file(output_dir.join(EFFECTIVE_CONFIG_FILE))
+            .with_flag_watcher(FlagWatcher::new(
+                flags::config_path(),
+                &output_dir,
+                &config.flags,
+            ));
+        if let Some(events) = &events {
+            engine = engine.with_state_stream(events.clone());
+        }
+        let repaired = engine.recover_intents(Utc::now()).await?;
+        if !repaired.is_empty() {
+            tracing::warn!(
+                orders = repaired.len(),
+                "Reconciled orders left in flight by the previous session"
+            );
+        }
+
+        // Hard halts live in the shared data directory, not an instance
+        // subdirectory, so every later run finds them
+        let data_dir = &config.data.output_dir;
+        let halts =
+            HaltStore::new(data_dir).with_journal(open_journal(data_dir.join(HALT_JOURNAL_FILE))?);
+        let pending = halts.pending()?;
+        engine = engine.with_halt_store(halts);
+        if let Some(halt) = pending.into_iter().next() {
+            tracing::warn!(
+                event_code = %EventCode::HaltPending,
+                halt = %halt,
+                "Unacknowledged hard halt, running observe-only; acknowledge with `poly-hft ctl ack-halt {}`",
+                halt.id
+            );
+            engine = engine.with_pending_halt(halt);
+        }
+        let cooldown = LossCooldown::new(config.risk.loss_cooldown.clone())
+            .with_state_file(data_dir.join(LOSS_COOLDOWN_FILE))?
+            .with_journal(open_journal(data_dir.join(HALT_JOURNAL_FILE))?);
+        for (asset, state) in cooldown.assets() {
+            if state.halted_at.is_some() {
+                tracing::warn!(
+                    event_code = %EventCode::HaltPending,
+                    asset = %asset,
+                    losses = state.consecutive_losses,
+                    "Asset halted on losses, entries withheld; acknowledge with `poly-hft ctl ack-cooldown {}`",
+                    asset
+                );
+            }
+        }
+        engine = engine.with_loss_cooldown(cooldown);
+        let review = StrategyReview::new(config.risk.review.clone())
+            .with_state_file(data_dir.join(STRATEGY_REVIEW_FILE))?
+            .with_journal(open_journal(data_dir.join(HALT_JOURNAL_FILE))?);
+        for (asset, state) in review.assets() {
+            let Some(flag) = &state.flag else {
+                continue;
+            };
+            tracing::warn!(
+                event_code = %EventCode::StrategyReview,
+                asset = %asset,
+                flag = %flag,
+                "Strategy flagged for review, entries sized at {} of the stake; clear with `poly-hft ctl ack-review {}`",
+                config.risk.review.size_scale,
+                asset
+            );
+        }
+        engine = engine.with_strategy_review(review);
+        let rate = RateLimiter::new(config.risk.rate_caps.clone())
+            .with_state_file(data_dir.join(RATE_CAPS_FILE))?;
+        engine = engine.with_rate_limiter(rate);
+        // The bankroll is the balance of the shared ledger, which `poly-hft
+        // ctl deposit`/`withdraw` append to while the session runs
+        let ledger = Ledger::open(data_dir.join(LEDGER_FILE))?
+            .with_opening_deposit(config.risk.initial_bankroll, Utc::now())?;
+        tracing::info!(balance = %ledger.balance(), entries = ledger.entries().len(), "Ledger opened");
+        engine = engine.with_ledger(ledger);
+        engine = engine.with_resolution_book(
+            ResolutionBook::new(config.risk.resolution.clone())
+                .with_state_file(data_dir.join(PENDING_RESOLUTIONS_FILE)),
+        );
+        let warm_path = data_dir.join(WARM_STATE_FILE);
+        // A takeover brings fresher spot windows of its own
+        if self.takeover.is_none() {
+            restore_warm_state(config, &mut engine, &warm_path).await;
+        }
+        let book_path = data_dir.join(BOOK_CHECKPOINT_FILE);
+        restore_books(config, &mut engine, &book_path);
+        if config.leader.enabled {
+            let leader = &config.leader;
+            let instance = leader.instance_id();
+            tracing::info!(
+                %instance,
+                lease = ?leader.lease_path(data_dir),
+                "Leader election enabled, observing until the lease is won"
+            );
+            let leadership = Leadership::new(leader, leader.elector(data_dir), instance)?;
+            engine = engine.with_leadership(leadership);
+            engine.check_leadership(Utc::now()).await;
+        }
+        let journal = Journal::open(output_dir.join("schedule_journal.jsonl"))?;
+        if let Some(fingerprint) = fingerprint::active() {
+            journal.write_header(fingerprint)?;
+        }
+        let schedule = TradingSchedule::new(config.schedule.clone()).with_journal(journal);
+        engine = engine.with_schedule(schedule);
+        engine.sync_schedule(Utc::now()).await?;
+
+        // Background tasks are supervised: capture restarts on its own, a
+        // dead feed ends the session
+        let supervisor = Supervisor::new()
+            .with_health(health.clone())
+            .with_journal(Arc::new(Journal::open(output_dir.join(TASK_JOURNAL_FILE))?));
+
+        // Market data fans out over the bus: the recorder gets a deep buffer
+        // so a slow detection loop cannot cost capture completeness, and
+        // neither consumer can stall the feed
+        let bus = Arc::new(MarketDataBus::new());
+        let internals_bus = bus.clone();
+        let book_bus = bus.clone();
+        let detection_rx = bus.subscribe_ticks("detection", config.feed.lag.channel_capacity);
+        // Paper entries are checked against the trade prints that follow
+        let mut shadow_rx = config.execution.shadow.enabled.then(|| {
+            bus.subscribe_filtered("shadow", config.feed.lag.channel_capacity, |e| {
+                matches!(e, MarketDataEvent::Trade(_))
+            })
+        });
+        if config.data.capture_enabled {
+            let recorder = Arc::new(DataRecorder::try_with_supervisor(
+                recorder_config(&config.data, &lock),
+                &supervisor,
+            )?);
+            let recorder_rx = Arc::new(Mutex::new(
+                bus.subscribe("recorder", config.feed.bus.recorder_capacity),
+            ));
+            supervisor.spawn("recorder", RestartPolicy::backoff(), move || {
+                let recorder = recorder.clone();
+                let recorder_rx = recorder_rx.clone();
+                async move {
+                    let mut recorder_rx = recorder_rx.lock().await;
+                    while let Some(event) = recorder_rx.recv().await {
+                        let result = match event {
+                            MarketDataEvent::Tick(tick) => recorder.record_price(tick),
+                            MarketDataEvent::Book(book) => recorder.record_orderbook(book),
+                            MarketDataEvent::Trade(_) => Ok(()),
+                        };
+                        if let Err(e) = result {
+                            tracing::warn!(error = %e, "Failed to record market data");
+                        }
+                    }
+                    let stats = recorder_rx.stats();
+                    tracing::info!(
+                        delivered = stats.delivered,
+                        dropped = stats.dropped,
+                        "Recorder bus summary"
+                    );
+                    Ok(())
+                }
+            });
+        }
+        let feed = BinanceFeed::new(&config.feed.symbol);
+        let feed_rx = Arc::new(Mutex::new(feed.subscribe().await?));
+        let symbols = Arc::new(symbols);
+        let mut feed_task = supervisor.spawn("feed", RestartPolicy::Never, move || {
+            let feed_rx = feed_rx.clone();
+            let symbols = symbols.clone();
+            let bus = bus.clone();
+            async move {
+                let mut feed_rx = feed_rx.lock().await;
+                while let Some(mut tick) = feed_rx.recv().await {
+                    if !symbols.tag_tick(&mut tick) {
+                        tracing::warn!(symbol = %tick.symbol, "Dropped tick of an unmapped symbol");
+                        continue;
+                    }
+                    bus.publish(MarketDataEvent::Tick(tick));
+                }
+                Ok(())
+            }
+        });
+        let mut feed_finished = false;
+        let feed_journal = Journal::open(output_dir.join("feed_journal.jsonl"))?;
+        let mut prices =
+            LagAwareReceiver::new(detection_rx, config.feed.lag.clone()).with_journal(feed_journal);
+
         // TODO: Implement paper trading loop
-        tracing::info!("Starting paper trading...");
+        tracing::info!(
+            engine = engine.execution().name(),
+            "Starting paper trading..."
+        );
+        let started = Utc::now();
+        let canary_end = match canary {
+            Some(duration) => {
+                tracing::info!(until = %(started + duration), "Canary session started");
+                Some(tokio::time::Instant::now() + duration.to_std()?)
+            }
+            None => None,
+        };
+        let mut canary_completed = false;
+        // Each next window is looked up and its books subscribed before it
+        // opens, off the loop; books reach the engine over the bus
+        let gamma = Arc::new(GammaClient::from_config(&config.market));
+        let books = Arc::new(PolymarketClient::new());
+        let mut book_rx = Some(book_bus.subscribe_filtered(
+            "engine",
+            config.feed.lag.channel_capacity,
+            |e| matches!(e, MarketDataEvent::Book(_)),
+        ));
+        let mut preopen = PreOpenTask::spawn(
+            PreOpenPreparer::new(
+                vec![config.market.asset.to_uppercase()],
+                config.market.preopen_lead_secs.as_secs(),
+            ),
+            gamma.clone(),
+            books.clone(),
+            book_bus.clone(),
+        );
+        // Connected now, so the process handing over may let go. A
+        // successor in an instance subdirectory takes the data directory's
+        // own lock once that process has exited; its session files stay
+        // where they are, under the instance lock
+        let mut data_dir_lock = None;
+        let mut awaiting_lock = false;
+        if let Some(path) = &self.takeover {
+            let shared = lock.dir() != data_dir.as_path();
+            let (tokens, taken) = take_over(config, &mut engine, path, data_dir, shared).await?;
+            for token in tokens {
+                forward_books(books.as_ref(), &token, book_bus.clone()).await?;
+            }
+            awaiting_lock = shared;
+            data_dir_lock = taken;
+        }
+        let mut handoff = None;
+        let mut schedule_timer = tokio::time::interval(std::time::Duration::from_secs(1));
+        let warm_interval = config.signal.warm_state.interval_secs.to_chrono();
+        let mut warm_saved_at = Utc::now();
+        let book_interval = config.signal.book_checkpoint.interval_secs.to_chrono();
+        let mut books_saved_at = Utc::now();
+        let internals_interval = config.telemetry.internals_interval_secs.to_chrono();
+        let internals_path = output_dir.join(INTERNALS_FILE);
+        let mut internals_at = Utc::now();
+        let resolution_interval = config.risk.resolution.poll_interval_secs.to_chrono();
+        let mut resolutions_at = Utc::now();
+        let (resolved_tx, mut resolved_rx) = tokio::sync::mpsc::channel::<(String, Side)>(16);
+        let mut resolution_lookup: Option<tokio::task::JoinHandle<()>> = None;
+        // Live mode books drift of the exchange's collateral balance
+        let balance_source = match (&config.execution.mode, &config.execution.live) {
+            (ExecutionMode::Live, Some(live)) => Some(ClobClient::new(live.clone())),
+            _ => None,
+        };
+        let balance_interval = config.risk.ledger.check_interval_secs.to_chrono();
+        // A takeover checks the balance it was handed at once
+        let mut balance_at = match self.takeover {
+            Some(_) => Utc::now() - balance_interval,
+            None => Utc::now(),
+        };
+        // Window and timer math runs on the corrected clock, which a
+        // backward wall-clock step cannot send back
+        let mut clock = ClockSync::new(config.clock.clone());
+        loop {
+            tokio::select! {
+                ticks = prices.recv_batch() => {
+                    let Some(ticks) = ticks else {
+                        tracing::warn!(event_code = %EventCode::FeedClosed, "Price feed closed");
+                        break;
+                    };
+                    tracing::trace!(ticks = ticks.len(), "Price ticks");
+                    engine.on_ticks(ticks).await?;
+                }
+                event = next_event(&mut book_rx) => match event {
+                    Some(MarketDataEvent::Book(book)) => {
+                        engine.on_event(clock.now(), BacktestEvent::OrderBookUpdate(book)).await?;
+                    }
+                    Some(_) => {}
+                    None => book_rx = None,
+                },
+                Some(market) = preopen.recv() => {
+                    engine.on_event(clock.now(), BacktestEvent::MarketOpen(market)).await?;
+                }
+                Some((market_id, official)) = resolved_rx.recv() => {
+                    engine.resolve(Utc::now(), &market_id, official);
+                }
+                event = next_event(&mut shadow_rx) => match event {
+                    Some(MarketDataEvent::Trade(print)) => engine.on_trade(&print),
+                    Some(_) => {}
+                    None => shadow_rx = None,
+                },
+                state = feed_task.stopped(), if !feed_finished => {
+                    if state == TaskState::Failed {
+                        tracing::error!(
+                            event_code = %EventCode::TaskFailed,
+                            component = feed_task.name(),
+                            "Trading-critical task died, halting the session"
+                        );
+                        break;
+                    }
+                    // A clean end closes the bus; buffered ticks drain first
+                    feed_finished = true;
+                }
+                _ = schedule_timer.tick() => {
+                    if let Some(event) = clock.check() {
+                        engine.on_clock_event(&event);
+                    }
+                    let now = clock.now();
+                    engine.check_leadership(now).await;
+                    engine.sync_ledger(now);
+                    engine.sync_strategy_review();
+                    engine.sync_flags();
+                    apply_log_directives_request(data_dir);
+                    if awaiting_lock && data_dir_lock.is_none() {
+                        if let Some(taken) = try_lock(data_dir)? {
+                            tracing::info!(dir = ?data_dir, "Old process exited, data directory locked");
+                            data_dir_lock = Some(taken);
+                        }
+                    }
+                    if handoff.is_none() && take_handoff_request(data_dir) {
+                        engine.start_draining(now);
+                        handoff = Some(data_dir.join(HANDOFF_FILE));
+                    }
+                    // Written every second while draining, as exits change
+                    // it; released once the successor is connected
+                    if let Some(path) = &handoff {
+                        let mut state = engine.handoff_state(now);
+                        state.released = ready_path(path).exists();
+                        if let Err(e) = state.save(path) {
+                            tracing::warn!(path = ?path, error = %e, "Failed to write handoff state");
+                        } else if state.released {
+                            tracing::info!(
+                                event_code = %EventCode::Shutdown,
+                                positions = state.positions.len(),
+                                "Successor connected, session handed over"
+                            );
+                            break;
+                        }
+                    }
+                    if let Some(client) = &balance_source {
+                        if !balance_interval.is_zero() && now - balance_at >= balance_interval {
+                            balance_at = now;
+                            check_balance(&mut engine, client, config, now).await;
+                        }
+                    }
+                    if !warm_interval.is_zero() && now - warm_saved_at >= warm_interval {
+                        warm_saved_at = now;
+                        save_warm_state(&engine, &warm_path);
+                    }
+                    if !book_interval.is_zero() && now - books_saved_at >= book_interval {
+                        books_saved_at = now;
+                        save_books(&mut engine, &book_path, false);
+                    }
+                    if !internals_interval.is_zero() && now - internals_at >= internals_interval {
+                        internals_at = now;
+                        save_internals(&engine, &internals_bus, &internals_path);
+                    }
+                    if now - resolutions_at >= resolution_interval {
+                        resolutions_at = now;
+                        // A lookup still running keeps the pending list it took
+                        if resolution_lookup.as_ref().is_none_or(|task| task.is_finished()) {
+                            resolution_lookup = Some(spawn_resolution_lookup(
+                                gamma.clone(),
+                                engine.pending_resolutions(),
+                                resolved_tx.clone(),
+                            ));
+                        }
+                        engine.expire_resolutions(Utc::now());
+                    }
+                    preopen.poll(clock.now());
+                    engine.sync_schedule(clock.now()).await?;
+                }
+                _ = async {
+                    match canary_end {
+                        Some(end) => tokio::time::sleep_until(end).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    canary_completed = true;
+                    break;
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    tracing::info!(event_code = %EventCode::Shutdown, "Received shutdown signal");
+                    break;
+                }
+            }
+        }
+        // Hand over at once rather than after the lease lapses
+        engine.release_leadership().await;
+        save_warm_state(&engine, &warm_path);
+        save_books(&mut engine, &book_path, true);
+
+        let lag = prices.stats();
+        tracing::info!(
+            delivered = lag.ticks_delivered,
+            skipped = lag.ticks_skipped,
+            catch_ups = lag.catch_ups,
+            batches = lag.batches,
+            "Price feed summary"
+        );
+        tracing::info!(
+            engine = engine.stats().engine,
+            outcomes = %engine.stats().outcomes,
+            "Signal outcome summary"
+        );
+        for component in health.snapshot() {
+            if component.state != HealthState::Healthy {
+                tracing::warn!(
+                    component = %component.component,
+                    state = ?component.state,
+                    reason = ?component.reason,
+                    "Component unhealthy at shutdown"
+                );
+            }
+        }
+        self.export_trades(&engine, Some(&archive)).await?;
+        let reconciliation =
+            reconcile_pnl(config, &engine, Some(&archive), Some(&output_dir)).await?;
+        report_costs(&engine, Some(&archive), &output_dir).await?;
+        write_tape(&engine, &output_dir);
+        report_attribution(&engine, &output_dir);
+        report_expected_value(config, &engine, &output_dir);
+        report_flag_cohorts(&engine, &output_dir);
+        report_shadow_fills(&mut engine, &output_dir, started);
+        print!("{}", engine.ledger().report(Some(started)));
+        print!("{}", engine.strategy_review());
+        print!("{}", engine.effective_config().timeline());
+
+        if canary.is_some() {
+            let metrics =
+                CanaryMetrics::from_engine(&engine, prices.session_lag(), canary_completed)?;
+            let report = CanaryReport::evaluate(&config.canary, metrics, started, Utc::now())
+                .with_health(&health)
+                .with_reconciliation(&reconciliation);
+            let path = output_dir.join(CANARY_REPORT_FILE);
+            report.write(&path)?;
+            println!("{}", engine.stats());
+            println!("{}", report);
+            println!("  Report: {}", path.display());
+            if !report.passed {
+                let failed: Vec<&str> = report.failures().map(|c| c.name.as_str()).collect();
+                tracing::error!(
+                    event_code = %EventCode::CanaryFailed,
+                    failed = ?failed,
+                    "Canary no-go"
+                );
+                anyhow::bail!("canary failed: {}", failed.join(", "));
+            }
+            tracing::info!(event_code = %EventCode::CanaryPassed, "Canary go");
+        }
+        if !reconciliation.passed {
+            anyhow::bail!("P&L reconciliation failed; see {}", PNL_RECONCILIATION_FILE);
+        }
+        Ok(())
+    }
+
+    async fn execute_sim<E: ExecutionEngine>(
+        &self,
+        config: &Config,
+        execution: E,
+    ) -> anyhow::Result<()> {
+        SymbolMap::from_config(config)?;
+        let mut sim = config.sim.clone();
+        if let Some(minutes) = self.sim_minutes {
+            sim.duration_mins = DurationConfig::from_mins(minutes);
+        }
+        tracing::info!(
+            seed = sim.seed,
+            minutes = %sim.duration_mins,
+            engine = execution.name(),
+            "Starting simulated paper trading..."
+        );
+
+        let mut engine = TradingEngine::new(config, execution);
+        let mut output_dir = config.data.output_dir.join("sim");
+        let _lock = if config.data.capture_enabled {
+            let lock = DataDirLock::acquire_or_share(&output_dir, "sim", self.share_data_dir)?;
+            output_dir = lock.dir().to_path_buf();
+            let trade_journal = Journal::open(output_dir.join("trade_journal.jsonl"))?;
+            if let Some(fingerprint) = fingerprint::active() {
+                trade_journal.write_header(fingerprint)?;
+            }
+            engine = engine
+                .with_recorder(DataRecorder::try_new(recorder_config(&config.data, &lock))?)
+                .with_outcome_journal(Journal::open(output_dir.join("outcome_journal.jsonl"))?)
+                .with_trade_journal(trade_journal);
+            Some(lock)
+        } else {
+            None
+        };
+        let scenario = self.scenario.as_deref().map(Scenario::load).transpose()?;
+        let events = Simulation::new(&config.feed.symbol, &config.market.asset, &sim).collect();
+        let (events, windows) = scenario.clone().unwrap_or_default().apply(events);
+        let mut tracker = ScenarioTracker::new(windows, engine.stats());
+        let mut pacer = self.pacer();
+        if pacer.is_interactive() {
+            engine = engine.with_explanations();
+        }
+        for event in events {
+            let (at, injected) = (event.at, event.injected);
+            pacer.before(at).await;
+            let market = MarketEvent::of(&event.event);
+            engine.on_event(at, event.event).await?;
+            tracker.observe(at, injected, engine.stats());
+            if let Some(pause) = pacer.after(at, market, engine.take_explanation()) {
+                print!("{}", pause);
+                if self.step {
+                    println!("  Press Enter to continue");
+                }
+                pacer.hold(at, &mut std::io::stdin().lock())?;
+            }
+        }
+
+        if self.dry_run {
+            println!("Simulation complete (seed {}, dry run):", sim.seed);
+        } else {
+            println!("Simulation complete (seed {}):", sim.seed);
+        }
+        println!("{}", engine.stats());
+        print!("{}", engine.effective_config().timeline());
+        if scenario.is_some() {
+            println!("  Scenario:");
+            print!("{}", tracker.summary());
+        }
+        if config.data.capture_enabled {
+            println!("  Data: {}", output_dir.display());
+        }
+        self.export_trades(&engine, None).await?;
+
+        let data_dir = config.data.capture_enabled.then_some(output_dir.as_path());
+        if let Some(dir) = data_dir {
+            report_costs(&engine, None, dir).await?;
+            write_tape(&engine, dir);
+            report_attribution(&engine, dir);
+            report_expected_value(config, &engine, dir);
+            report_flag_cohorts(&engine, dir);
+        }
+        if !reconcile_pnl(config, &engine, None, data_dir).await?.passed {
+            anyhow::bail!("P&L reconciliation failed");
+        }
+        Ok(())
+    }
+
+    /// Pacing and breakpoints of the simulated stream
+    fn pacer(&self) -> Pacer {
+        let mut breakpoints = self.break_on.clone();
+        if self.step && breakpoints.is_empty() {
+            breakpoints.push(Breakpoint::Signal);
+        }
+        let mut pacer = Pacer::new(self.speed).with_breakpoints(breakpoints);
+        if let Some(until) = &self.until {
+            pacer = pacer.with_until(until.clone());
+        }
+        if self.step {
+            pacer = pacer.stepping();
+        }
+        pacer
+    }
+
+    /// Write the session's fills, archived ones first, to `--export-trades`,
+    /// if given
+    async fn export_trades<E: ExecutionEngine>(
+        &self,
+        engine: &TradingEngine<E>,
+        archive: Option<&HistoryArchive>,
+    ) -> anyhow::Result<()> {
+        let Some(path) = &self.export_trades else {
+            return Ok(());
+        };
+        let fills = session_fills(engine, archive).await?;
+        write_trades(path, &fills)?;
+        tracing::info!(
+            path = ?path,
+            trades = fills.len(),
+            engine = engine.execution().name(),
+            "Exported trades"
+        );
         Ok(())
     }
 }
+
+/// The session's fills, archived ones first
+async fn session_fills<E: ExecutionEngine>(
+    engine: &TradingEngine<E>,
+    archive: Option<&HistoryArchive>,
+) -> anyhow::Result<Vec<Fill>> {
+    let mut fills = match archive {
+        Some(archive) => archive.read_fills()?,
+        None => vec![],
+    };
+    fills.extend(engine.inherited_fills().iter().cloned());
+    fills.extend(engine.execution().get_fills().await?);
+    Ok(fills)
+}
+
+/// Restore the spot windows saved by the previous session, if recent
+/// enough, and backfill the gap since from klines
+async fn restore_warm_state<E: ExecutionEngine>(
+    config: &Config,
+    engine: &mut TradingEngine<E>,
+    path: &Path,
+) {
+    let max_age = config.signal.warm_state.max_age_secs;
+    let now = Utc::now();
+    let Some(state) = (!max_age.is_zero())
+        .then(|| WarmState::load(path, now, max_age.to_chrono()))
+        .flatten()
+    else {
+        set_warm_start(false);
+        return;
+    };
+    let taken_at = state.taken_at;
+    engine.restore_warm_state(state);
+    let minutes = (now - taken_at).num_minutes() as usize + 2;
+    let backfilled = match KlineClient::new()
+        .backfill_ticks(&config.feed.symbol, minutes)
+        .await
+    {
+        Ok(ticks) => engine.backfill_spot(&ticks, taken_at),
+        Err(e) => {
+            tracing::warn!(error = %e, "Kline backfill after warm state failed");
+            0
+        }
+    };
+    tracing::info!(
+        age_secs = (now - taken_at).num_seconds(),
+        backfilled,
+        "Restored spot windows from warm state"
+    );
+    set_warm_start(true);
+}
+
+/// Restore the order books saved by the previous session, if recent
+/// enough, to be confirmed by live updates
+fn restore_books<E: ExecutionEngine>(config: &Config, engine: &mut TradingEngine<E>, path: &Path) {
+    let max_age = config.signal.book_checkpoint.max_age_secs;
+    let now = Utc::now();
+    let Some(checkpoint) = (!max_age.is_zero())
+        .then(|| BookCheckpoint::load(path, now, max_age.to_chrono()))
+        .flatten()
+    else {
+        return;
+    };
+    let restored = engine.restore_books(&checkpoint);
+    tracing::info!(
+        age_secs = (now - checkpoint.taken_at).num_seconds(),
+        restored,
+        saved = checkpoint.books.len(),
+        "Restored order books from checkpoint, unconfirmed until live updates arrive"
+    );
+}
+
+/// Tell the process handing over at `path` that this one is connected,
+/// wait for it to release its state, and take the session over; returns
+/// the book tokens to subscribe, and the lock on `data_dir` if this
+/// process, `shared` in an instance subdirectory, already took it
+///
+/// Past `[handoff] release_timeout_secs`, as when the old process died,
+/// nothing is traded until that process has let go of `data_dir`; its
+/// latest state is then taken as it is. A state released by an earlier
+/// handoff is refused.
+async fn take_over<E: ExecutionEngine>(
+    config: &Config,
+    engine: &mut TradingEngine<E>,
+    path: &Path,
+    data_dir: &Path,
+    shared: bool,
+) -> anyhow::Result<(Vec<String>, Option<DataDirLock>)> {
+    // Refuse an unreadable or foreign state before signalling anything
+    HandoffState::load(path)?;
+    let ready = ready_path(path);
+    let ready_at = Utc::now();
+    std::fs::write(&ready, ready_at.to_rfc3339())?;
+    tracing::info!(path = ?path, "Connected, waiting for the old process to release the session");
+    let timeout = std::time::Duration::from_secs(config.handoff.release_timeout_secs.as_secs());
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut taken = None;
+    let state = loop {
+        let state = HandoffState::load(path)?;
+        if state.released_since(ready_at) {
+            break state;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            if shared {
+                taken = Some(wait_for_lock(data_dir, timeout).await?);
+            }
+            let state = HandoffState::load(path)?;
+            if state.released && !state.released_since(ready_at) {
+                anyhow::bail!(
+                    "handoff state {} was released at {}, before this process was ready; \
+                     it is left from an earlier handoff",
+                    path.display(),
+                    state.taken_at
+                );
+            }
+            tracing::warn!(
+                taken_at = %state.taken_at,
+                "Old process did not release in time and has exited, taking its latest state"
+            );
+            break state;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    };
+    for file in [&ready, path] {
+        if let Err(e) = std::fs::remove_file(file) {
+            tracing::debug!(path = ?file, error = %e, "Could not remove the handoff file");
+        }
+    }
+    let tokens = state.tokens.clone();
+    engine.take_over(state, Utc::now());
+    Ok((tokens, taken))
+}
+
+/// Lock `data_dir` once the process holding it lets go, warning every
+/// `interval` while it does not
+async fn wait_for_lock(
+    data_dir: &Path,
+    interval: std::time::Duration,
+) -> anyhow::Result<DataDirLock> {
+    let mut warn_at = tokio::time::Instant::now();
+    loop {
+        if let Some(lock) = try_lock(data_dir)? {
+            return Ok(lock);
+        }
+        if tokio::time::Instant::now() >= warn_at {
+            tracing::warn!(
+                dir = ?data_dir,
+                "Old process still holds the data directory, not trading until it exits"
+            );
+            warn_at += interval.max(std::time::Duration::from_secs(1));
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+}
+
+/// The lock on `dir`, or `None` while another process holds it
+fn try_lock(dir: &Path) -> anyhow::Result<Option<DataDirLock>> {
+    match DataDirLock::acquire(dir, "run") {
+        Ok(lock) => Ok(Some(lock)),
+        Err(LockError::Held { .. } | LockError::HeldUnknown(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Save the spot windows for the next session; failures are logged
+fn save_warm_state<E: ExecutionEngine>(engine: &TradingEngine<E>, path: &Path) {
+    if let Err(e) = engine.warm_state(Utc::now()).save(path) {
+        tracing::warn!(path = ?path, error = %e, "Failed to save warm state");
+    }
+}
+
+/// Save the order books for the next session if any changed, or whatever
+/// they are with `force`; failures are logged
+fn save_books<E: ExecutionEngine>(engine: &mut TradingEngine<E>, path: &Path, force: bool) {
+    let Some(checkpoint) = engine.book_checkpoint(Utc::now(), force) else {
+        return;
+    };
+    if let Err(e) = checkpoint.save(path) {
+        tracing::warn!(path = ?path, error = %e, "Failed to save book checkpoint");
+    }
+}
+
+/// Look up provisional settlements off the trading loop, sending back the
+/// official outcomes Gamma has published
+fn spawn_resolution_lookup(
+    gamma: Arc<GammaClient>,
+    markets: Vec<Market>,
+    resolved: tokio::sync::mpsc::Sender<(String, Side)>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        for market in markets {
+            match gamma.fetch_resolution(&market).await {
+                Ok(Some(official)) => {
+                    if resolved
+                        .send((market.condition_id, official))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::debug!(market_id = %market.condition_id, error = %e, "Resolution lookup failed")
+                }
+            }
+        }
+    })
+}
+
+/// Reconcile the ledger with the exchange's collateral balance
+async fn check_balance<E: ExecutionEngine>(
+    engine: &mut TradingEngine<E>,
+    source: &impl CollateralBalance,
+    config: &Config,
+    now: DateTime<Utc>,
+) {
+    match source.collateral_balance().await {
+        Ok(actual) => {
+            engine.reconcile_balance(actual, config.risk.ledger.drift_tolerance, now);
+        }
+        Err(e) => tracing::warn!(error = %e, "Collateral balance lookup failed"),
+    }
+}
+
+/// Publish the engine's structure sizes, with the bus backlogs, and save
+/// them for `status --internals`; failures are logged
+fn save_internals<E: ExecutionEngine>(engine: &TradingEngine<E>, bus: &MarketDataBus, path: &Path) {
+    let mut internals = engine.internals(Utc::now());
+    internals
+        .channels
+        .extend(bus.stats().into_iter().map(|s| ChannelDepth {
+            name: format!("bus:{}", s.name),
+            depth: s.queued,
+            capacity: s.capacity,
+        }));
+    internals.publish();
+    tracing::debug!(
+        order_books = internals.order_books,
+        detectors = internals.detectors,
+        session_records = internals.session_records,
+        journal_queue = internals.journal_queue,
+        "Internals snapshot"
+    );
+    if let Err(e) = internals.save(path) {
+        tracing::warn!(path = ?path, error = %e, "Failed to save internals snapshot");
+    }
+}
+
+/// Reconcile the session's P&L at shutdown
+///
+/// Positions rebuilt from the fills are compared with the tracker and, when
+/// the session has a data directory, with the current session of its trade
+/// journal; the result is written there too.
+async fn reconcile_pnl<E: ExecutionEngine>(
+    config: &Config,
+    engine: &TradingEngine<E>,
+    archive: Option<&HistoryArchive>,
+    output_dir: Option<&Path>,
+) -> anyhow::Result<PnlReconciliation> {
+    let fills = session_fills(engine, archive).await?;
+    let ledger = match output_dir.map(|dir| dir.join("trade_journal.jsonl")) {
+        Some(path) if path.exists() => Some(JournalLedger::from_entries(&Journal::read_all(path)?)),
+        _ => None,
+    };
+    let mut reconciler = PnlReconciler::new(&fills, engine.markets_seen(), engine.settlements())
+        .with_tracker(engine.positions());
+    if let Some(ledger) = &ledger {
+        reconciler = reconciler.with_journal(ledger);
+    }
+    let reconciliation = reconciler.run(config.reconcile.tolerance)?;
+
+    let path = output_dir.map(|dir| dir.join(PNL_RECONCILIATION_FILE));
+    if let Some(path) = &path {
+        if let Err(e) = reconciliation.write(path) {
+            tracing::warn!(path = ?path, error = %e, "Could not write P&L reconciliation");
+        }
+    }
+    if reconciliation.passed {
+        tracing::info!(
+            fills = fills.len(),
+            realized_pnl = %reconciliation.fills.realized_pnl,
+            "P&L reconciled"
+        );
+    } else {
+        tracing::error!(
+            event_code = %EventCode::PnlMismatch,
+            discrepancies = reconciliation.discrepancies.len(),
+            path = ?path,
+            "P&L reconciliation failed"
+        );
+        print!("{}", reconciliation);
+    }
+    Ok(reconciliation)
+}
+
+/// Measure the session's execution costs against its trade journal
+///
+/// Writes and prints the report when any fill could be joined to the mid
+/// journaled at decision time.
+async fn report_costs<E: ExecutionEngine>(
+    engine: &TradingEngine<E>,
+    archive: Option<&HistoryArchive>,
+    output_dir: &Path,
+) -> anyhow::Result<()> {
+    let journal_path = output_dir.join("trade_journal.jsonl");
+    if !journal_path.exists() {
+        return Ok(());
+    }
+    let fills = session_fills(engine, archive).await?;
+    let report = CostReport::from_fills(&fills, &Journal::read_all(journal_path)?);
+    if report.fills.is_empty() {
+        return Ok(());
+    }
+    let path = output_dir.join(COST_REPORT_FILE);
+    if let Err(e) = report.write(&path) {
+        tracing::warn!(path = ?path, error = %e, "Could not write cost report");
+    }
+    print!("{}", report);
+    Ok(())
+}
+
+/// Write the session's closed trades as a trade tape in `output_dir`
+fn write_tape<E: ExecutionEngine>(engine: &TradingEngine<E>, output_dir: &Path) {
+    let trades = engine.trade_tape();
+    if trades.is_empty() {
+        return;
+    }
+    let path = output_dir.join(TRADE_TAPE_FILE);
+    match write_trade_tape(&path, trades) {
+        Ok(()) => println!("  Trade tape: {} ({} trades)", path.display(), trades.len()),
+        Err(e) => tracing::warn!(path = ?path, error = %e, "Could not write trade tape"),
+    }
+}
+
+/// Write and print the attribution of the session's settled positions
+fn report_attribution<E: ExecutionEngine>(engine: &TradingEngine<E>, output_dir: &Path) {
+    let positions = engine.pnl_attribution();
+    if positions.is_empty() {
+        return;
+    }
+    let report = AttributionReport::new(positions.to_vec());
+    let path = output_dir.join(PNL_ATTRIBUTION_FILE);
+    if let Err(e) = report.write(&path) {
+        tracing::warn!(path = ?path, error = %e, "Could not write P&L attribution");
+    }
+    print!("{}", report);
+}
+
+/// Write and print the session's expected against realized P&L, warning
+/// when realized falls well short
+fn report_expected_value<E: ExecutionEngine>(
+    config: &Config,
+    engine: &TradingEngine<E>,
+    output_dir: &Path,
+) {
+    let trades = engine.trade_tape();
+    if trades.is_empty() {
+        return;
+    }
+    let report = ExpectedValueReport::new(trades, config.expected_value.bootstrap_resamples);
+    let path = output_dir.join(EXPECTED_VALUE_FILE);
+    if let Err(e) = report.write(&path) {
+        tracing::warn!(path = ?path, error = %e, "Could not write expected value report");
+    }
+    print!("{}", report);
+    if report.warn_on_shortfall(&config.expected_value) {
+        println!("  !! Realized P&L well below expected; re-tune the entry thresholds");
+    }
+}
+
+/// Write and print the session's trades split by the cohorts of each
+/// feature flag rolling out, if any was
+fn report_flag_cohorts<E: ExecutionEngine>(engine: &TradingEngine<E>, output_dir: &Path) {
+    let report = CohortReport::new(engine.trade_tape());
+    if report.is_empty() {
+        return;
+    }
+    let path = output_dir.join(FLAG_COHORT_FILE);
+    if let Err(e) = report.write(&path) {
+        tracing::warn!(path = ?path, error = %e, "Could not write flag cohort report");
+    }
+    print!("{}", report);
+}
+
+/// Write and print the attainability of the session's shadowed paper
+/// entries
+fn report_shadow_fills<E: ExecutionEngine>(
+    engine: &mut TradingEngine<E>,
+    output_dir: &Path,
+    started: DateTime<Utc>,
+) {
+    let fills = engine.finish_shadow_fills();
+    if fills.is_empty() {
+        return;
+    }
+    let report = ShadowReport::new(fills.to_vec());
+    let path = shadow_file(output_dir, started);
+    if let Err(e) = report.write(&path) {
+        tracing::warn!(path = ?path, error = %e, "Could not write shadow fills");
+    }
+    print!("{}", report);
+}
+
+/// Next event of an optional bus subscription; pending without one
+async fn next_event(rx: &mut Option<BusReceiver>) -> Option<MarketDataEvent> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Duration such as `90s`, `30m`, `6h` or `2d`
+fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let s = s.trim();
+    let (count, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let count: i64 = count
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration '{}', expected e.g. 30m or 6h", s))?;
+    let duration = match unit {
+        "s" => Duration::seconds(count),
+        "m" => Duration::minutes(count),
+        "h" => Duration::hours(count),
+        "d" => Duration::days(count),
+        _ => anyhow::bail!("Invalid duration '{}', expected a unit of s, m, h or d", s),
+    };
+    if duration <= Duration::zero() {
+        anyhow::bail!("Duration '{}' must be positive", s);
+    }
+    Ok(duration)
+}
+
+/// Paper engine costing fills with the configured, possibly calibrated,
+/// cost model
+fn paper_engine(config: &Config) -> anyhow::Result<PaperEngine> {
+    let costs = config.execution.costs.clone().load_calibration()?;
+    if let Some(calibration) = &costs.calibrated {
+        let calibrated = calibration
+            .buckets
+            .iter()
+            .filter(|b| b.samples >= costs.min_calibration_samples)
+            .count();
+        tracing::info!(
+            path = ?costs.calibration,
+            calibrated,
+            buckets = calibration.buckets.len(),
+            "Slippage calibrated from measured fills"
+        );
+    }
+    Ok(PaperEngine::with_cost_model(costs))
+}
+
+/// Recorder settings for `output_dir` with the configured encodings
+fn recorder_config(data: &DataConfig, lock: &DataDirLock) -> RecorderConfig {
+    // A second instance sharing the directory captures to its own
+    RecorderConfig {
+        output_dir: lock.dir().to_path_buf(),
+        rotation_interval_secs: data.rotation_interval.as_secs(),
+        flush_interval_secs: data.flush_interval.as_secs(),
+        ..Default::default()
+    }
+    .with_formats(data.format, data.prefix_formats.clone())
+    .with_parquet(data.parquet.clone())
+    .with_durable_queue(data.durable_queue_config())
+    .with_dedup(data.dedup.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::seconds(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::minutes(30));
+        assert_eq!(parse_duration(" 6h").unwrap(), Duration::hours(6));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::days(2));
+        assert!(parse_duration("6").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("1w").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shared_instance_captures_to_its_subdirectory() {
+        use crate::feed::{PriceTick, TickSource};
+        use rust_decimal_macros::dec;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.data.output_dir = dir.path().to_path_buf();
+        let _first = DataDirLock::acquire_or_share(dir.path(), "run", true).unwrap();
+        let second = DataDirLock::acquire_or_share(dir.path(), "run", true).unwrap();
+        assert_ne!(second.dir(), dir.path());
+
+        let recorder = DataRecorder::try_new(recorder_config(&config.data, &second)).unwrap();
+        let now = Utc::now();
+        let tick = PriceTick {
+            symbol: "BTCUSDT".to_string(),
+            asset: "BTC".to_string(),
+            price: dec!(100000),
+            timestamp: now,
+            exchange_ts: now,
+            source: TickSource::Trade,
+        };
+        let path = recorder.record_klines(&[tick]).await.unwrap().unwrap();
+        assert!(path.starts_with(second.dir()), "{:?}", path);
+        assert!(path.exists());
+    }
+}
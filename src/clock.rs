@@ -0,0 +1,337 @@
+//! Wall-clock steps and process pauses
+//!
+//! Market windows, pre-open lookups, cooldowns and timers are wall-clock
+//! arithmetic, so an NTP step or a paused VM moves `now` by seconds at
+//! once. [`ClockSync`] compares the wall clock against a monotonic
+//! baseline at every check:
+//!
+//! - a *step* is the wall clock moving further or less than the monotonic
+//!   clock since the last check, by `step_threshold_ms` or more. Entries
+//!   are frozen for `settle_secs` after one.
+//! - a *pause* is both clocks agreeing but the monotonic clock itself
+//!   jumping by `pause_threshold_secs` or more between checks: the process
+//!   did not run, as when its VM was paused, and what it holds is stale.
+//!
+//! Both are logged (`CLOCK_STEP`, `CLOCK_PAUSE`) and counted in
+//! `polyhft_clock_events_total{kind}`; the engine journals them.
+//!
+//! [`ClockSync::now`] is the corrected clock: the wall clock held where it
+//! was until it catches up with a backward step, so durations measured
+//! against it never run backwards.
+
+use crate::duration::{DurationConfig, Millis};
+use crate::telemetry::{record_clock_event, EventCode};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Default smallest wall-clock step reported, in milliseconds
+pub const DEFAULT_STEP_THRESHOLD_MS: u64 = 500;
+
+/// Default smallest gap between checks reported as a pause, in seconds
+pub const DEFAULT_PAUSE_THRESHOLD_SECS: u64 = 5;
+
+/// Default seconds entries stay frozen after a step
+pub const DEFAULT_SETTLE_SECS: u64 = 5;
+
+/// Clock step and pause detection, under `[clock]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ClockConfig {
+    /// Smallest disagreement between the wall and monotonic clocks
+    /// reported as a step
+    #[serde(default = "default_step_threshold_ms")]
+    pub step_threshold_ms: DurationConfig<Millis>,
+    /// Smallest gap between checks reported as a pause
+    #[serde(default = "default_pause_threshold_secs")]
+    pub pause_threshold_secs: DurationConfig,
+    /// How long entries stay frozen after a step; 0 only reports it
+    #[serde(default = "default_settle_secs")]
+    pub settle_secs: DurationConfig,
+}
+
+fn default_step_threshold_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_STEP_THRESHOLD_MS)
+}
+
+fn default_pause_threshold_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_PAUSE_THRESHOLD_SECS)
+}
+
+fn default_settle_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_SETTLE_SECS)
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            step_threshold_ms: default_step_threshold_ms(),
+            pause_threshold_secs: default_pause_threshold_secs(),
+            settle_secs: default_settle_secs(),
+        }
+    }
+}
+
+/// A wall-clock step or process pause found by [`ClockSync`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEvent {
+    /// The wall clock moved `skew_ms` more than the monotonic clock,
+    /// negative when it stepped back; entries are frozen until
+    /// `settle_until` on the corrected clock
+    Step {
+        at: DateTime<Utc>,
+        skew_ms: i64,
+        settle_until: DateTime<Utc>,
+    },
+    /// Neither clock advanced for `gap_ms` as far as the process saw
+    Pause { at: DateTime<Utc>, gap_ms: i64 },
+}
+
+impl ClockEvent {
+    /// `step` or `pause`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClockEvent::Step { .. } => "step",
+            ClockEvent::Pause { .. } => "pause",
+        }
+    }
+
+    /// Corrected time the event was found at
+    pub fn at(&self) -> DateTime<Utc> {
+        match self {
+            ClockEvent::Step { at, .. } | ClockEvent::Pause { at, .. } => *at,
+        }
+    }
+}
+
+impl fmt::Display for ClockEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClockEvent::Step { skew_ms, .. } => write!(f, "clock step of {:+}ms", skew_ms),
+            ClockEvent::Pause { gap_ms, .. } => write!(f, "pause of {}ms", gap_ms),
+        }
+    }
+}
+
+/// Watches the wall clock against a monotonic baseline
+#[derive(Debug, Clone)]
+pub struct ClockSync {
+    config: ClockConfig,
+    origin: Instant,
+    /// Monotonic time since `origin` and wall time at the last check
+    last: Option<(Duration, DateTime<Utc>)>,
+    /// Latest corrected time handed out
+    corrected: DateTime<Utc>,
+    settle_until: Option<DateTime<Utc>>,
+    steps: u64,
+    pauses: u64,
+}
+
+impl ClockSync {
+    /// Watch the clocks from now on
+    pub fn new(config: ClockConfig) -> Self {
+        Self {
+            config,
+            origin: Instant::now(),
+            last: None,
+            corrected: DateTime::<Utc>::MIN_UTC,
+            settle_until: None,
+            steps: 0,
+            pauses: 0,
+        }
+    }
+
+    /// Compare the clocks now, reporting a step or pause since the last
+    /// check
+    pub fn check(&mut self) -> Option<ClockEvent> {
+        let event = self.observe(self.origin.elapsed(), Utc::now())?;
+        record_clock_event(event.kind());
+        match event {
+            ClockEvent::Step {
+                skew_ms,
+                settle_until,
+                ..
+            } => tracing::warn!(
+                event_code = %EventCode::ClockStep,
+                skew_ms,
+                settle_until = %settle_until,
+                "Wall clock stepped, entries frozen while it settles"
+            ),
+            ClockEvent::Pause { gap_ms, .. } => tracing::warn!(
+                event_code = %EventCode::ClockPause,
+                gap_ms,
+                "Process paused, held state may be stale"
+            ),
+        }
+        Some(event)
+    }
+
+    /// Compare `wall` with `monotonic`, the monotonic time since this
+    /// clock was made, against the previous observation
+    pub fn observe(&mut self, monotonic: Duration, wall: DateTime<Utc>) -> Option<ClockEvent> {
+        let previous = self.last.replace((monotonic, wall));
+        let at = self.correct(wall);
+        let (last_monotonic, last_wall) = previous?;
+        let elapsed = chrono::Duration::from_std(monotonic.saturating_sub(last_monotonic))
+            .unwrap_or(chrono::Duration::MAX);
+        let skew = (wall - last_wall) - elapsed;
+        if skew.abs() >= self.config.step_threshold_ms.to_chrono() {
+            self.steps += 1;
+            let settle_until = at + self.config.settle_secs.to_chrono();
+            self.settle_until = Some(settle_until);
+            return Some(ClockEvent::Step {
+                at,
+                skew_ms: skew.num_milliseconds(),
+                settle_until,
+            });
+        }
+        if elapsed >= self.config.pause_threshold_secs.to_chrono() {
+            self.pauses += 1;
+            return Some(ClockEvent::Pause {
+                at,
+                gap_ms: elapsed.num_milliseconds(),
+            });
+        }
+        None
+    }
+
+    /// The corrected time now
+    pub fn now(&mut self) -> DateTime<Utc> {
+        self.correct(Utc::now())
+    }
+
+    /// `wall` on the corrected clock, which never runs backwards
+    pub fn correct(&mut self, wall: DateTime<Utc>) -> DateTime<Utc> {
+        self.corrected = self.corrected.max(wall);
+        self.corrected
+    }
+
+    /// Whether entries are still frozen by a step at corrected time `now`
+    pub fn is_settling(&self, now: DateTime<Utc>) -> bool {
+        self.settle_until.is_some_and(|until| now < until)
+    }
+
+    /// Steps found so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Pauses found so far
+    pub fn pauses(&self) -> u64 {
+        self.pauses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_735_689_600, 0).unwrap()
+    }
+
+    /// Checks every second for `secs` seconds, the wall clock keeping time
+    fn tick(clock: &mut ClockSync, from: u64, secs: u64) -> Vec<ClockEvent> {
+        (from..from + secs)
+            .filter_map(|s| {
+                clock.observe(
+                    Duration::from_secs(s),
+                    t0() + chrono::Duration::seconds(s as i64),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_steady_clocks_report_nothing() {
+        let mut clock = ClockSync::new(ClockConfig::default());
+        assert!(tick(&mut clock, 0, 60).is_empty());
+        assert!(!clock.is_settling(t0() + chrono::Duration::seconds(59)));
+    }
+
+    #[test]
+    fn test_backward_step_freezes_and_holds_the_clock() {
+        let mut clock = ClockSync::new(ClockConfig::default());
+        tick(&mut clock, 0, 11);
+        // NTP steps the wall clock back 2s between the 10s and 11s checks
+        let stepped = t0() + chrono::Duration::seconds(9);
+        let event = clock.observe(Duration::from_secs(11), stepped).unwrap();
+        let ClockEvent::Step {
+            at,
+            skew_ms,
+            settle_until,
+        } = event
+        else {
+            panic!("expected a step, got {:?}", event);
+        };
+        assert_eq!(skew_ms, -2000);
+        // The corrected clock stays at the last time it handed out
+        assert_eq!(at, t0() + chrono::Duration::seconds(10));
+        assert_eq!(settle_until, at + chrono::Duration::seconds(5));
+        assert!(clock.is_settling(at + chrono::Duration::seconds(4)));
+        assert!(!clock.is_settling(settle_until));
+
+        // It stands still until the wall clock catches up, then follows it
+        let held = clock.correct(t0() + chrono::Duration::seconds(9));
+        assert_eq!(held, at);
+        assert_eq!(
+            clock.correct(t0() + chrono::Duration::seconds(12)),
+            t0() + chrono::Duration::seconds(12)
+        );
+
+        // Once stepped, the clocks agree again
+        let later = (12..20)
+            .filter_map(|s| {
+                clock.observe(
+                    Duration::from_secs(s),
+                    t0() + chrono::Duration::seconds(s as i64 - 2),
+                )
+            })
+            .count();
+        assert_eq!(later, 0);
+        assert_eq!((clock.steps(), clock.pauses()), (1, 0));
+    }
+
+    #[test]
+    fn test_forward_step_is_reported() {
+        let mut clock = ClockSync::new(ClockConfig::default());
+        tick(&mut clock, 0, 5);
+        let event = clock
+            .observe(Duration::from_secs(5), t0() + chrono::Duration::seconds(8))
+            .unwrap();
+        assert!(matches!(event, ClockEvent::Step { skew_ms: 3000, .. }));
+        assert_eq!(event.at(), t0() + chrono::Duration::seconds(8));
+    }
+
+    #[test]
+    fn test_pause_is_reported_apart_from_a_step() {
+        let mut clock = ClockSync::new(ClockConfig::default());
+        tick(&mut clock, 0, 5);
+        // Neither clock saw the process for 30s, and they still agree
+        let event = clock
+            .observe(
+                Duration::from_secs(34),
+                t0() + chrono::Duration::seconds(34),
+            )
+            .unwrap();
+        assert_eq!(
+            event,
+            ClockEvent::Pause {
+                at: t0() + chrono::Duration::seconds(34),
+                gap_ms: 30_000
+            }
+        );
+        assert_eq!(event.kind(), "pause");
+        assert!(!clock.is_settling(t0() + chrono::Duration::seconds(35)));
+        assert_eq!((clock.steps(), clock.pauses()), (0, 1));
+    }
+
+    #[test]
+    fn test_small_drift_is_not_a_step() {
+        let mut clock = ClockSync::new(ClockConfig::default());
+        tick(&mut clock, 0, 3);
+        let slewed = t0() + chrono::Duration::milliseconds(3_200);
+        assert!(clock.observe(Duration::from_secs(3), slewed).is_none());
+    }
+}
@@ -1,89 +1,463 @@
 //! Configuration types for poly-hft
 
+use crate::breaker::BreakerConfig;
+use crate::bus::BusConfig;
+use crate::clock::ClockConfig;
+use crate::data::{
+    BookDedupConfig, DataFormat, DiskConfig, HistoryConfig, MicrostructureConfig, ParquetTuning,
+    QueueConfig, RetentionPolicy,
+};
+use crate::duration::{DurationConfig, Millis, Minutes};
+use crate::engine::{ExitLadderConfig, HandoffConfig, WarmStateConfig};
+use crate::execution::{CamouflageConfig, CostModel, LiveConfig, ShadowConfig};
+use crate::feed::TickLagConfig;
+use crate::flags::FeatureFlags;
+use crate::ids::IdConfig;
+use crate::journal::JournalConfig;
+use crate::leader::LeaderConfig;
+use crate::orderbook::{BookCheckpointConfig, FreshnessConfig};
+use crate::report::{CanaryConfig, ExpectedValueConfig, ReconcileConfig};
+use crate::retry::RetryConfig;
+use crate::risk::{
+    AllocationConfig, DampingConfig, LedgerConfig, LossCooldownConfig, MarketLimits, RateCapConfig,
+    ResolutionConfig, ScheduleConfig, StrategyReviewConfig,
+};
+use crate::signal::{BookShockConfig, ConsistencyConfig, ModelSanityConfig};
+use crate::sim::SimConfig;
+use crate::stream::StreamConfig;
+use crate::symbols::SymbolTable;
+use crate::telemetry::{
+    LabelConfig, LogFormat, LogRotation, DEFAULT_INTERNALS_INTERVAL_SECS, DEFAULT_MAX_SERIES,
+};
 use rust_decimal::Decimal;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 /// Root configuration structure
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Config {
     pub feed: FeedConfig,
     pub market: MarketConfig,
     pub model: ModelConfig,
     pub signal: SignalConfig,
     pub risk: RiskConfig,
+    /// Allowed trading windows
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
     pub execution: ExecutionConfig,
+    /// Acceptance criteria of `run --canary`
+    #[serde(default)]
+    pub canary: CanaryConfig,
+    /// Shutdown P&L reconciliation
+    #[serde(default)]
+    pub reconcile: ReconcileConfig,
+    /// Expected against realized P&L in session and backtest reports
+    #[serde(default)]
+    pub expected_value: ExpectedValueConfig,
+    /// Leader election between redundant instances
+    #[serde(default)]
+    pub leader: LeaderConfig,
+    /// Wall-clock step and pause detection
+    #[serde(default)]
+    pub clock: ClockConfig,
+    /// Handing a running session over to a new process
+    #[serde(default)]
+    pub handoff: HandoffConfig,
+    /// How signal and order IDs are assigned
+    #[serde(default)]
+    pub ids: IdConfig,
+    /// Feature flags gating new logic per signal
+    #[serde(default)]
+    pub flags: FeatureFlags,
+    /// Live event stream for external consumers
+    #[serde(default)]
+    pub stream: StreamConfig,
     pub data: DataConfig,
     pub telemetry: TelemetryConfig,
+    /// Synthetic data for `run --sim`
+    #[serde(default)]
+    pub sim: SimConfig,
+    /// Feed symbol of each market asset per exchange
+    #[serde(default)]
+    pub symbols: SymbolTable,
 }
 
 /// Price feed configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct FeedConfig {
     pub exchange: String,
     pub symbol: String,
+    /// Detection loop lag thresholds
+    #[serde(default)]
+    pub lag: TickLagConfig,
+    /// Market data fanout buffers
+    #[serde(default)]
+    pub bus: BusConfig,
 }
 
 /// Market discovery configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct MarketConfig {
     pub asset: String,
-    pub interval: String,
-    pub refresh_interval_secs: u64,
+    /// Length of a market window
+    pub interval: DurationConfig,
+    pub refresh_interval_secs: DurationConfig,
+    /// Page size for paginated Gamma listings
+    #[serde(default = "default_gamma_page_size")]
+    pub page_size: usize,
+    /// Maximum pages fetched per Gamma listing
+    #[serde(default = "default_gamma_max_pages")]
+    pub max_pages: usize,
+    /// Event slug lookups in flight at once
+    #[serde(default = "default_lookup_concurrency")]
+    pub lookup_concurrency: usize,
+    /// Event slugs sent in one request
+    #[serde(default = "default_slug_batch_size")]
+    pub slug_batch_size: usize,
+    /// Time budget for one cycle's slug lookups
+    #[serde(default = "default_discovery_deadline_ms")]
+    pub discovery_deadline_ms: DurationConfig<Millis>,
+    /// How long before a window opens to look up its market and subscribe
+    #[serde(default = "default_preopen_lead_secs")]
+    pub preopen_lead_secs: DurationConfig,
+    /// Retries of each Gamma request; by default none
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+fn default_gamma_page_size() -> usize {
+    crate::market::DEFAULT_PAGE_SIZE
+}
+
+fn default_gamma_max_pages() -> usize {
+    crate::market::DEFAULT_MAX_PAGES
+}
+
+fn default_lookup_concurrency() -> usize {
+    crate::market::DEFAULT_LOOKUP_CONCURRENCY
+}
+
+fn default_slug_batch_size() -> usize {
+    crate::market::DEFAULT_SLUG_BATCH_SIZE
+}
+
+fn default_discovery_deadline_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(crate::market::DEFAULT_DISCOVERY_DEADLINE_MS)
+}
+
+fn default_preopen_lead_secs() -> DurationConfig {
+    DurationConfig::from_secs(crate::market::DEFAULT_PREOPEN_LEAD_SECS)
 }
 
 /// Fair value model configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ModelConfig {
-    pub volatility_window_minutes: u64,
-    pub min_time_to_expiry_secs: u64,
+    pub volatility_window_minutes: DurationConfig<Minutes>,
+    /// No-trade margin before close
+    pub min_time_to_expiry_secs: DurationConfig,
+    /// Price samples kept per volatility window before thinning
+    #[serde(default = "default_volatility_max_samples")]
+    pub volatility_max_samples: usize,
+}
+
+fn default_volatility_max_samples() -> usize {
+    crate::model::DEFAULT_MAX_SAMPLES
 }
 
 /// Signal generation configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct SignalConfig {
     pub min_edge_threshold: Decimal,
     pub max_edge_threshold: Decimal,
+    /// Widest bid-ask spread on the traded book worth entering against
+    #[serde(default = "default_max_entry_spread")]
+    pub max_entry_spread: Decimal,
+    /// Apply `min_edge_threshold` to the edge left after paying the spread
+    /// back on exit
+    #[serde(default)]
+    pub use_round_trip_edge: bool,
+    /// Lookback over which the spot move behind a signal is measured
+    #[serde(default = "default_momentum_window_secs")]
+    pub momentum_window_secs: DurationConfig,
+    /// Spot prices held per momentum window before thinning
+    #[serde(default = "default_momentum_max_samples")]
+    pub momentum_max_samples: usize,
+    /// Largest fraction of that move spot may have given back at entry
+    #[serde(default = "default_max_momentum_retrace")]
+    pub max_momentum_retrace: Decimal,
+    /// Spot venues that must each be on the signal's side of the strike;
+    /// 1 trades on the primary feed alone
+    #[serde(default = "default_momentum_require_venues")]
+    pub momentum_require_venues: usize,
+    /// Widest spread between venue spot prices, in percent, for those
+    /// venues to count as agreeing
+    #[serde(default = "default_max_venue_divergence_pct")]
+    pub max_venue_divergence_pct: Decimal,
+    /// How old a book may be before it is too stale to trade on
+    #[serde(default)]
+    pub book_freshness: FreshnessConfig,
+    /// Tick size of the books; prices are quotable one tick in from 0 and 1
+    #[serde(default = "default_tick_size")]
+    pub tick_size: Decimal,
+    /// Smallest raw edge against the bound for a side whose expected price
+    /// was clamped to it
+    #[serde(default = "default_near_bound_min_edge")]
+    pub near_bound_min_edge: Decimal,
+    /// How far the YES price the NO book implies may sit past the YES ask,
+    /// in the lag's direction, before the YES book is taken as stale
+    #[serde(default = "default_complement_tolerance")]
+    pub complement_tolerance: Decimal,
+    /// Saving the spot windows for a warm restart
+    #[serde(default)]
+    pub warm_state: WarmStateConfig,
+    /// Saving the order books for a restart
+    #[serde(default)]
+    pub book_checkpoint: BookCheckpointConfig,
+    /// Cross-checking the GBM fair value against the linear lag model
+    #[serde(default)]
+    pub model_sanity: ModelSanityConfig,
+    /// Exiting held positions when the book under them evaporates
+    #[serde(default)]
+    pub book_shock: BookShockConfig,
+    /// Checking the YES and NO books of a market against each other
+    #[serde(default)]
+    pub consistency: ConsistencyConfig,
+}
+
+fn default_max_entry_spread() -> Decimal {
+    crate::signal::DEFAULT_MAX_ENTRY_SPREAD
+}
+
+fn default_momentum_window_secs() -> DurationConfig {
+    DurationConfig::from_secs(crate::signal::DEFAULT_MOMENTUM_WINDOW_SECS)
+}
+
+fn default_momentum_max_samples() -> usize {
+    crate::signal::DEFAULT_MOMENTUM_MAX_SAMPLES
+}
+
+fn default_max_momentum_retrace() -> Decimal {
+    crate::signal::DEFAULT_MAX_MOMENTUM_RETRACE
+}
+
+fn default_momentum_require_venues() -> usize {
+    crate::signal::DEFAULT_REQUIRE_VENUES
+}
+
+fn default_max_venue_divergence_pct() -> Decimal {
+    crate::signal::DEFAULT_MAX_VENUE_DIVERGENCE_PCT
+}
+
+fn default_tick_size() -> Decimal {
+    crate::market::DEFAULT_TICK_SIZE
+}
+
+fn default_near_bound_min_edge() -> Decimal {
+    crate::signal::DEFAULT_NEAR_BOUND_MIN_EDGE
+}
+
+fn default_complement_tolerance() -> Decimal {
+    crate::signal::DEFAULT_COMPLEMENT_TOLERANCE
+}
+
+fn default_max_depth_multiple() -> Decimal {
+    crate::risk::DEFAULT_MAX_DEPTH_MULTIPLE
 }
 
 /// Risk management configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct RiskConfig {
     pub kelly_fraction: Decimal,
     pub max_position_pct: Decimal,
     pub max_concurrent_positions: usize,
     pub initial_bankroll: Decimal,
+    /// Largest order as a multiple of the size within 2 cents of the touch
+    #[serde(default = "default_max_depth_multiple")]
+    pub max_depth_multiple: Decimal,
+    /// Per-market exposure caps across strategies
+    #[serde(default)]
+    pub market: MarketLimits,
+    /// Pause an asset's entries after a losing settlement
+    #[serde(default)]
+    pub loss_cooldown: LossCooldownConfig,
+    /// Hard caps on entries per window, asset hour and day
+    #[serde(default)]
+    pub rate_caps: RateCapConfig,
+    /// Provisional settlement until the official resolution
+    #[serde(default)]
+    pub resolution: ResolutionConfig,
+    /// Profit taken in parts near the close
+    #[serde(default)]
+    pub exit_ladder: ExitLadderConfig,
+    /// Bankroll ledger and its reconciliation with the exchange
+    #[serde(default)]
+    pub ledger: LedgerConfig,
+    /// Review flag on drifting adverse excursions
+    #[serde(default)]
+    pub review: StrategyReviewConfig,
+    /// Ranking of signals competing for limited capital
+    #[serde(default)]
+    pub allocation: AllocationConfig,
+    /// Entries sized down by the exposure already held in their direction
+    #[serde(default)]
+    pub damping: DampingConfig,
 }
 
 /// Execution engine configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ExecutionConfig {
     pub mode: ExecutionMode,
     pub slippage_estimate: Decimal,
+    /// Fee and slippage model for paper fills
+    #[serde(default)]
+    pub costs: CostModel,
+    /// CLOB credentials and endpoints for live mode
+    #[serde(default)]
+    pub live: Option<LiveConfig>,
+    /// Suppress orders after repeated submission failures
+    #[serde(default)]
+    pub breaker: BreakerConfig,
+    /// Paper fills checked against the market's trade prints
+    #[serde(default)]
+    pub shadow: ShadowConfig,
+    /// Random delay, size jitter and splitting of entries
+    #[serde(default)]
+    pub camouflage: CamouflageConfig,
 }
 
 /// Execution mode: paper trading or live
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
+#[non_exhaustive]
 pub enum ExecutionMode {
     Paper,
     Live,
 }
 
 /// Data capture configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct DataConfig {
     pub capture_enabled: bool,
     pub output_dir: PathBuf,
-    pub rotation_interval: String,
+    /// How long a capture file is written before the next is started
+    pub rotation_interval: DurationConfig,
+    /// Longest capture buffers are held before being written
+    #[serde(default = "default_flush_interval")]
+    pub flush_interval: DurationConfig,
+    /// Capture file encoding
+    #[serde(default)]
+    pub format: DataFormat,
+    /// Encoding overrides keyed by file prefix, e.g. `orderbook = "arrow"`
+    #[serde(default)]
+    pub prefix_formats: HashMap<String, DataFormat>,
+    /// Parquet compression, dictionary and row group settings
+    #[serde(default)]
+    pub parquet: ParquetTuning,
+    /// Retention policy for captured files
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    /// Free-space thresholds for the data directory
+    #[serde(default)]
+    pub disk: DiskConfig,
+    /// Closed positions and fills kept in memory before archiving
+    #[serde(default)]
+    pub history: HistoryConfig,
+    /// Put captured ticks and books through a disk-backed queue instead of
+    /// a bounded channel, so none are dropped while the writers fall behind
+    #[serde(default)]
+    pub durable_queue: bool,
+    /// Segment and fsync settings of the durable queue
+    #[serde(default)]
+    pub queue: QueueConfig,
+    /// Batching of the session journals' writes
+    #[serde(default)]
+    pub journal: JournalConfig,
+    /// Skipping of captured order books identical to the token's last row
+    #[serde(default)]
+    pub dedup: BookDedupConfig,
+    /// Live spread percentile of the current window minute
+    #[serde(default)]
+    pub stats: MicrostructureConfig,
+}
+
+impl DataConfig {
+    /// Durable queue settings if the queue is enabled
+    pub fn durable_queue_config(&self) -> Option<QueueConfig> {
+        self.durable_queue.then(|| self.queue.clone())
+    }
 }
 
 /// Telemetry configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct TelemetryConfig {
     pub metrics_port: u16,
+    /// EnvFilter directives, e.g. `info,poly_hft::orderbook=debug`
     pub log_level: String,
+    /// Console log format
+    #[serde(default)]
+    pub log_format: LogFormat,
+    /// Optional rolling JSON log file
+    #[serde(default)]
+    pub log_file: Option<LogFileConfig>,
     pub otlp_endpoint: Option<String>,
+    /// Most label combinations across all metrics; new ones beyond are
+    /// logged and dropped
+    #[serde(default = "default_max_series")]
+    pub max_series: usize,
+    /// How market and token ids become metric labels
+    #[serde(default)]
+    pub labels: LabelConfig,
+    /// Time between snapshots of the internal structure sizes; 0 never
+    /// takes one
+    #[serde(default = "default_internals_interval_secs")]
+    pub internals_interval_secs: DurationConfig,
+}
+
+fn default_max_series() -> usize {
+    DEFAULT_MAX_SERIES
+}
+
+fn default_internals_interval_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_INTERNALS_INTERVAL_SECS)
+}
+
+/// Rolling log file configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LogFileConfig {
+    /// Directory the log files are written to
+    pub directory: PathBuf,
+    /// File name prefix
+    #[serde(default = "default_log_file_prefix")]
+    pub prefix: String,
+    /// Rotation period
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Number of rotated files to retain
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+fn default_log_file_prefix() -> String {
+    "poly-hft".to_string()
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
+fn default_flush_interval() -> DurationConfig {
+    DurationConfig::from_secs(crate::data::DEFAULT_FLUSH_INTERVAL_SECS)
 }
 
 impl Config {
@@ -91,8 +465,85 @@ impl Config {
     pub fn load(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
         let content = std::fs::read_to_string(path)?;
         let config: Config = toml::from_str(&content)?;
+        config.validate()?;
         Ok(config)
     }
+
+    /// Check durations that only make sense against one another
+    ///
+    /// Each parses on its own; this catches combinations that would
+    /// silently never trade, never poll or never renew.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let interval = self.market.interval;
+        if interval.is_zero() {
+            anyhow::bail!("[market] interval must be longer than zero");
+        }
+        let within_window = [
+            (
+                "[model] min_time_to_expiry_secs",
+                self.model.min_time_to_expiry_secs,
+            ),
+            ("[market] preopen_lead_secs", self.market.preopen_lead_secs),
+        ];
+        for (key, value) in within_window {
+            if value >= interval {
+                anyhow::bail!(
+                    "{} ({}) must be shorter than [market] interval ({})",
+                    key,
+                    value,
+                    interval
+                );
+            }
+        }
+        let ladder = &self.risk.exit_ladder;
+        if ladder.enabled {
+            if let Some(rung) = ladder
+                .rungs
+                .iter()
+                .find(|r| r.secs_before_close >= interval)
+            {
+                anyhow::bail!(
+                    "[risk.exit_ladder] rung secs_before_close ({}) must be shorter than [market] interval ({})",
+                    rung.secs_before_close,
+                    interval
+                );
+            }
+        }
+        let resolution = &self.risk.resolution;
+        if resolution.enabled && resolution.poll_interval_secs > resolution.confirmation_window_secs
+        {
+            anyhow::bail!(
+                "[risk.resolution] poll_interval_secs ({}) must not exceed confirmation_window_secs ({})",
+                resolution.poll_interval_secs,
+                resolution.confirmation_window_secs
+            );
+        }
+        if self.leader.enabled && self.leader.renew_interval_secs >= self.leader.ttl_secs {
+            anyhow::bail!(
+                "[leader] renew_interval_secs ({}) must be shorter than ttl_secs ({})",
+                self.leader.renew_interval_secs,
+                self.leader.ttl_secs
+            );
+        }
+        let freshness = &self.signal.book_freshness;
+        if freshness.min_age_ms > freshness.max_age_ms {
+            anyhow::bail!(
+                "[signal.book_freshness] min_age_ms ({}) must not exceed max_age_ms ({})",
+                freshness.min_age_ms,
+                freshness.max_age_ms
+            );
+        }
+        let whole_seconds = [
+            ("rotation_interval", self.data.rotation_interval),
+            ("flush_interval", self.data.flush_interval),
+        ];
+        for (key, value) in whole_seconds {
+            if value.as_secs() == 0 {
+                anyhow::bail!("[data] {} ({}) must be at least 1s", key, value);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -145,6 +596,9 @@ mod tests {
         assert_eq!(config.feed.exchange, "binance");
         assert_eq!(config.risk.max_concurrent_positions, 3);
         assert_eq!(config.execution.mode, ExecutionMode::Paper);
+        assert_eq!(config.market.page_size, crate::market::DEFAULT_PAGE_SIZE);
+        assert!(config.data.retention.max_total_bytes.is_none());
+        assert!(!config.data.retention.include_compacted);
     }
 
     #[test]
@@ -190,6 +644,8 @@ mod tests {
         let config: Config = toml::from_str(toml).unwrap();
         assert_eq!(config.execution.mode, ExecutionMode::Live);
         assert!(config.telemetry.otlp_endpoint.is_none());
+        assert_eq!(config.telemetry.log_format, LogFormat::Pretty);
+        assert!(config.telemetry.log_file.is_none());
     }
 
     #[test]
@@ -197,6 +653,8 @@ mod tests {
         let config = FeedConfig {
             exchange: "binance".to_string(),
             symbol: "BTCUSDT".to_string(),
+            lag: TickLagConfig::default(),
+            bus: BusConfig::default(),
         };
         assert_eq!(config.exchange, "binance");
         assert_eq!(config.symbol, "BTCUSDT");
@@ -206,11 +664,18 @@ mod tests {
     fn test_market_config() {
         let config = MarketConfig {
             asset: "BTC".to_string(),
-            interval: "15m".to_string(),
-            refresh_interval_secs: 30,
+            interval: DurationConfig::from_mins(15),
+            refresh_interval_secs: DurationConfig::from_secs(30),
+            page_size: 100,
+            max_pages: 20,
+            lookup_concurrency: 4,
+            slug_batch_size: 10,
+            discovery_deadline_ms: DurationConfig::from_millis(5000),
+            preopen_lead_secs: DurationConfig::from_secs(60),
+            retry: RetryConfig::default(),
         };
         assert_eq!(config.asset, "BTC");
-        assert_eq!(config.refresh_interval_secs, 30);
+        assert_eq!(config.refresh_interval_secs.as_secs(), 30);
     }
 
     #[test]
@@ -218,6 +683,22 @@ mod tests {
         let config = SignalConfig {
             min_edge_threshold: dec!(0.005),
             max_edge_threshold: dec!(0.10),
+            max_entry_spread: dec!(0.05),
+            use_round_trip_edge: false,
+            momentum_window_secs: DurationConfig::from_secs(60),
+            momentum_max_samples: 1024,
+            max_momentum_retrace: dec!(0.30),
+            momentum_require_venues: 1,
+            max_venue_divergence_pct: dec!(0.1),
+            book_freshness: FreshnessConfig::default(),
+            tick_size: dec!(0.01),
+            near_bound_min_edge: dec!(0.05),
+            complement_tolerance: dec!(0.03),
+            warm_state: WarmStateConfig::default(),
+            book_checkpoint: BookCheckpointConfig::default(),
+            model_sanity: ModelSanityConfig::default(),
+            book_shock: BookShockConfig::default(),
+            consistency: ConsistencyConfig::default(),
         };
         assert_eq!(config.min_edge_threshold, dec!(0.005));
     }
@@ -229,6 +710,16 @@ mod tests {
             max_position_pct: dec!(0.01),
             max_concurrent_positions: 3,
             initial_bankroll: dec!(500),
+            max_depth_multiple: dec!(0.5),
+            market: MarketLimits::default(),
+            loss_cooldown: LossCooldownConfig::default(),
+            rate_caps: RateCapConfig::default(),
+            resolution: ResolutionConfig::default(),
+            exit_ladder: ExitLadderConfig::default(),
+            ledger: LedgerConfig::default(),
+            review: StrategyReviewConfig::default(),
+            allocation: AllocationConfig::default(),
+            damping: DampingConfig::default(),
         };
         assert_eq!(config.kelly_fraction, dec!(0.25));
     }
@@ -239,6 +730,125 @@ mod tests {
         assert!(result.is_err());
     }
 
+    /// Example config with `key` under `table` set to `value`
+    fn example_with(table: &str, key: &str, value: toml::Value) -> String {
+        let mut root: toml::Value = toml::from_str(include_str!("../config.toml.example")).unwrap();
+        let mut node = &mut root;
+        for part in table.split('.') {
+            node = node
+                .as_table_mut()
+                .unwrap()
+                .entry(part)
+                .or_insert_with(|| toml::Value::Table(Default::default()));
+        }
+        node.as_table_mut().unwrap().insert(key.to_string(), value);
+        toml::to_string(&root).unwrap()
+    }
+
+    #[test]
+    fn test_durations_accept_strings_and_integers() {
+        // Each field as a string, and what it serializes back to in the
+        // unit a bare integer counts
+        let fields = [
+            ("feed.lag", "p95_threshold_ms", "2s", 2000),
+            ("feed.lag", "window_ms", "2s", 2000),
+            ("market", "refresh_interval_secs", "2m", 120),
+            ("market", "discovery_deadline_ms", "2s", 2000),
+            ("market", "preopen_lead_secs", "2m", 120),
+            ("market.retry", "base_delay_ms", "2s", 2000),
+            ("market.retry", "max_delay_ms", "2s", 2000),
+            ("market.retry", "deadline_ms", "2s", 2000),
+            ("model", "volatility_window_minutes", "2h", 120),
+            ("model", "min_time_to_expiry_secs", "2m", 120),
+            ("signal", "momentum_window_secs", "2m", 120),
+            ("signal.book_freshness", "max_book_age_ms", "2s", 2000),
+            ("signal.book_freshness", "min_age_ms", "2s", 2000),
+            ("signal.book_freshness", "max_age_ms", "2s", 2000),
+            ("signal.warm_state", "interval_secs", "2m", 120),
+            ("signal.warm_state", "max_age_secs", "2m", 120),
+            ("signal.book_checkpoint", "interval_secs", "2m", 120),
+            ("signal.book_checkpoint", "max_age_secs", "2m", 120),
+            ("signal.book_shock", "window_ms", "2s", 2000),
+            ("signal.book_shock", "cooldown_secs", "2m", 120),
+            ("risk.loss_cooldown", "minutes", "2h", 120),
+            ("risk.resolution", "confirmation_window_secs", "2h", 7200),
+            ("risk.resolution", "poll_interval_secs", "2m", 120),
+            ("execution.breaker", "cooldown_secs", "2m", 120),
+            ("execution.camouflage", "max_delay_ms", "2s", 2000),
+            ("canary", "max_tick_lag_p95_ms", "2s", 2000),
+            ("leader", "ttl_secs", "2m", 120),
+            ("leader", "renew_interval_secs", "2s", 2),
+            ("data", "rotation_interval", "2h", 7200),
+            ("data", "flush_interval", "2m", 120),
+            ("data.retention", "protected_window_secs", "2h", 7200),
+            ("data.retention", "cleanup_interval_secs", "2m", 120),
+            ("data.retention.max_age_hours", "orderbook", "2d", 48),
+            ("data.disk", "check_interval_secs", "2m", 120),
+            ("data.journal", "batch_interval_ms", "2s", 2000),
+            ("data.dedup", "keepalive_secs", "2m", 120),
+            ("telemetry", "internals_interval_secs", "2m", 120),
+            ("telemetry.labels", "expiry_mins", "2h", 120),
+            ("sim", "duration_mins", "2h", 120),
+            ("sim", "tick_interval_ms", "2s", 2000),
+            ("sim", "lag_delay_ms", "2s", 2000),
+        ];
+        let read_back = |toml: &str, table: &str, key: &str| {
+            let config: Config = toml::from_str(toml).unwrap();
+            let value = toml::Value::try_from(&config).unwrap();
+            let node = table.split('.').fold(&value, |node, part| &node[part]);
+            node[key].as_integer().unwrap()
+        };
+        for (table, key, string, units) in fields {
+            let toml = example_with(table, key, string.into());
+            assert_eq!(read_back(&toml, table, key), units, "{}.{}", table, key);
+
+            let toml = example_with(table, key, toml::Value::Integer(7));
+            assert_eq!(read_back(&toml, table, key), 7, "{}.{}", table, key);
+
+            let toml = example_with(table, key, "ninety".into());
+            let error = toml::from_str::<Config>(&toml).unwrap_err().to_string();
+            assert!(error.contains(&format!("{} = ", key)), "{}", error);
+            assert!(error.contains("invalid duration"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_durations_that_do_not_fit() {
+        let cases = [
+            (
+                "model",
+                "min_time_to_expiry_secs",
+                "15m",
+                "min_time_to_expiry_secs",
+            ),
+            ("market", "preopen_lead_secs", "1h", "preopen_lead_secs"),
+            (
+                "risk.resolution",
+                "poll_interval_secs",
+                "3h",
+                "poll_interval_secs",
+            ),
+            ("signal.book_freshness", "min_age_ms", "1m", "min_age_ms"),
+            ("data", "rotation_interval", "500ms", "rotation_interval"),
+        ];
+        for (table, key, value, named) in cases {
+            let config: Config = toml::from_str(&example_with(table, key, value.into())).unwrap();
+            let error = config.validate().unwrap_err().to_string();
+            assert!(error.contains(named), "{}", error);
+        }
+
+        let mut config: Config = toml::from_str(include_str!("../config.toml.example")).unwrap();
+        config.validate().unwrap();
+        config.risk.exit_ladder.enabled = true;
+        config.risk.exit_ladder.rungs[0].secs_before_close = DurationConfig::from_mins(20);
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("secs_before_close (20m)"), "{}", error);
+        config.risk.exit_ladder.enabled = false;
+        config.leader.enabled = true;
+        config.leader.renew_interval_secs = config.leader.ttl_secs;
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_execution_mode_equality() {
         assert_eq!(ExecutionMode::Paper, ExecutionMode::Paper);
@@ -251,6 +861,8 @@ mod tests {
         let config = FeedConfig {
             exchange: "binance".to_string(),
             symbol: "BTCUSDT".to_string(),
+            lag: TickLagConfig::default(),
+            bus: BusConfig::default(),
         };
         let cloned = config.clone();
         assert_eq!(config.exchange, cloned.exchange);
@@ -0,0 +1,481 @@
+//! Order book merge audit against captured snapshots
+//!
+//! Replays a token's captured rows through [`OrderBookManager::apply`] in
+//! timestamp order, under the same duplicate and ordering rules as the live
+//! books. Each full snapshot is first compared with the book the preceding
+//! rows built, so a dropped or misapplied delta shows up as a divergence at
+//! the next snapshot.
+//!
+//! Keep-alive rows, written by capture deduplication when a book went
+//! unchanged, restate the book and are compared like snapshots. Between
+//! rows a book is held as it was, so a gap in a deduplicated capture is
+//! not a loss; a gap longer than the keep-alive interval is.
+
+use super::parquet::{orderbooks_from_batch, OrderBookRecord, CAPTURED_BOOK_LEVELS};
+use super::{read_batches, scan_data_files};
+use crate::orderbook::{
+    BookUpdate, BookUpdateKind, MergeOutcome, OrderBook, OrderBookManager, PriceLevel,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::path::Path;
+
+/// One level the merged book and the snapshot disagree on
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelDiff {
+    /// `bid` or `ask`
+    pub side: &'static str,
+    /// Level price
+    pub price: Decimal,
+    /// Size in the snapshot, if the level is there
+    pub snapshot: Option<Decimal>,
+    /// Size in the merged book, if the level is there
+    pub merged: Option<Decimal>,
+}
+
+impl fmt::Display for LevelDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = |s: Option<Decimal>| s.map_or("-".to_string(), |s| s.to_string());
+        write!(
+            f,
+            "{} {}: snapshot {} vs merged {}",
+            self.side,
+            self.price,
+            size(self.snapshot),
+            size(self.merged)
+        )
+    }
+}
+
+/// Differences found when one snapshot arrived
+#[derive(Debug, Clone)]
+pub struct SnapshotDiff {
+    /// Snapshot timestamp
+    pub timestamp: DateTime<Utc>,
+    /// Deltas merged since the previous snapshot
+    pub deltas_since: usize,
+    /// Levels in the snapshot the merged book lacks
+    pub missing: Vec<LevelDiff>,
+    /// Levels in the merged book the snapshot lacks
+    pub extra: Vec<LevelDiff>,
+    /// Levels whose sizes differ by more than the tolerance
+    pub size_mismatches: Vec<LevelDiff>,
+    /// `crossed` or `locked` if the merged book was
+    pub crossed: Option<&'static str>,
+}
+
+impl SnapshotDiff {
+    /// Whether the merged book differed from the snapshot at all
+    pub fn is_divergent(&self) -> bool {
+        self.score() > 0
+    }
+
+    /// Number of problems found
+    pub fn score(&self) -> usize {
+        self.missing.len()
+            + self.extra.len()
+            + self.size_mismatches.len()
+            + usize::from(self.crossed.is_some())
+    }
+
+    /// Every level difference
+    pub fn levels(&self) -> impl Iterator<Item = &LevelDiff> {
+        self.missing
+            .iter()
+            .chain(&self.extra)
+            .chain(&self.size_mismatches)
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} after {} deltas: {} missing, {} extra, {} size mismatches",
+            self.timestamp.to_rfc3339(),
+            self.deltas_since,
+            self.missing.len(),
+            self.extra.len(),
+            self.size_mismatches.len()
+        )?;
+        if let Some(fault) = self.crossed {
+            write!(f, ", merged book {}", fault)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of replaying one token's capture
+#[derive(Debug, Clone, Default)]
+pub struct BookAudit {
+    /// Rows replayed
+    pub rows: usize,
+    /// Snapshot rows, including the first one the replay starts from
+    pub snapshots: usize,
+    /// Delta rows
+    pub deltas: usize,
+    /// Keep-alive rows restating an unchanged book
+    pub keep_alives: usize,
+    /// Rows dropped as exact duplicates of an applied row
+    pub duplicates: usize,
+    /// Rows dropped as older than the book
+    pub out_of_order: usize,
+    /// Snapshots compared against a merged book
+    pub compared: usize,
+    /// Divergent snapshots, in timestamp order
+    pub divergences: Vec<SnapshotDiff>,
+    /// Longest time between consecutive rows
+    pub longest_gap: chrono::Duration,
+}
+
+impl BookAudit {
+    /// Fraction of compared snapshots that diverged
+    pub fn divergence_rate(&self) -> Decimal {
+        if self.compared == 0 {
+            return Decimal::ZERO;
+        }
+        Decimal::from(self.divergences.len()) / Decimal::from(self.compared)
+    }
+
+    /// The `n` divergences with the most problems, worst first
+    pub fn worst(&self, n: usize) -> Vec<&SnapshotDiff> {
+        let mut worst: Vec<_> = self.divergences.iter().collect();
+        worst.sort_by_key(|d| std::cmp::Reverse(d.score()));
+        worst.truncate(n);
+        worst
+    }
+}
+
+/// Captured order book rows of `token_id` under `dir`, oldest first
+pub fn load_book_records(dir: &Path, token_id: &str) -> anyhow::Result<Vec<OrderBookRecord>> {
+    let mut records = vec![];
+    for file in scan_data_files(dir, true)? {
+        if file.prefix != "orderbook" {
+            continue;
+        }
+        for batch in read_batches(&file.path)? {
+            records.extend(
+                orderbooks_from_batch(&batch)?
+                    .into_iter()
+                    .filter(|r| &*r.token_id == token_id),
+            );
+        }
+    }
+    records.sort_by_key(|r| r.timestamp);
+    Ok(records)
+}
+
+/// Replay `records` of one token, comparing each snapshot with the book
+/// merged so far; sizes within `size_tolerance` count as equal
+pub fn audit_book(records: &[OrderBookRecord], size_tolerance: Decimal) -> BookAudit {
+    let mut manager = OrderBookManager::new();
+    let mut audit = BookAudit::default();
+    let mut deltas_since = 0;
+    let mut previous: Option<DateTime<Utc>> = None;
+
+    for record in records {
+        if let Some(previous) = previous {
+            audit.longest_gap = audit.longest_gap.max(record.timestamp - previous);
+        }
+        previous = Some(record.timestamp);
+        let bids = levels(&record.bids);
+        let asks = levels(&record.asks);
+        let update = BookUpdate {
+            token_id: &record.token_id,
+            kind: record.kind,
+            bids: &bids,
+            asks: &asks,
+            timestamp: record.timestamp,
+            hash: None,
+        };
+        audit.rows += 1;
+        match manager.classify(&update) {
+            MergeOutcome::Applied => {}
+            MergeOutcome::Duplicate => {
+                audit.duplicates += 1;
+                continue;
+            }
+            MergeOutcome::OutOfOrder => {
+                audit.out_of_order += 1;
+                continue;
+            }
+        }
+        match record.kind {
+            BookUpdateKind::Delta => {
+                audit.deltas += 1;
+                deltas_since += 1;
+            }
+            BookUpdateKind::Snapshot | BookUpdateKind::KeepAlive => {
+                match record.kind {
+                    BookUpdateKind::KeepAlive => audit.keep_alives += 1,
+                    _ => audit.snapshots += 1,
+                }
+                if let Some(merged) = manager.book(&record.token_id) {
+                    audit.compared += 1;
+                    let diff = compare(merged, &bids, &asks, size_tolerance, record, deltas_since);
+                    if diff.is_divergent() {
+                        audit.divergences.push(diff);
+                    }
+                }
+                deltas_since = 0;
+            }
+        }
+        manager.apply(&update);
+    }
+    audit
+}
+
+fn levels(levels: &[(Decimal, Decimal)]) -> Vec<PriceLevel> {
+    levels
+        .iter()
+        .map(|(price, size)| PriceLevel {
+            price: *price,
+            size: *size,
+        })
+        .collect()
+}
+
+fn compare(
+    merged: &OrderBook,
+    bids: &[PriceLevel],
+    asks: &[PriceLevel],
+    size_tolerance: Decimal,
+    record: &OrderBookRecord,
+    deltas_since: usize,
+) -> SnapshotDiff {
+    let mut diff = SnapshotDiff {
+        timestamp: record.timestamp,
+        deltas_since,
+        missing: vec![],
+        extra: vec![],
+        size_mismatches: vec![],
+        crossed: merged.top_of_book_fault(),
+    };
+    for (side, snapshot, merged, better) in [
+        ("bid", bids, &merged.bids, Decimal::max as fn(_, _) -> _),
+        ("ask", asks, &merged.asks, Decimal::min as fn(_, _) -> _),
+    ] {
+        // A full capture row is cut at its last level; deeper merged levels
+        // cannot be checked against it
+        let floor = snapshot
+            .last()
+            .filter(|_| snapshot.len() >= CAPTURED_BOOK_LEVELS)
+            .map(|l| l.price);
+        let in_range = |price: Decimal| floor.is_none_or(|f| better(price, f) == price);
+
+        for level in snapshot {
+            match merged.iter().find(|m| m.price == level.price) {
+                None => diff.missing.push(LevelDiff {
+                    side,
+                    price: level.price,
+                    snapshot: Some(level.size),
+                    merged: None,
+                }),
+                Some(m) if (m.size - level.size).abs() > size_tolerance => {
+                    diff.size_mismatches.push(LevelDiff {
+                        side,
+                        price: level.price,
+                        snapshot: Some(level.size),
+                        merged: Some(m.size),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for level in merged.iter().filter(|m| in_range(m.price)) {
+            if !snapshot.iter().any(|s| s.price == level.price) {
+                diff.extra.push(LevelDiff {
+                    side,
+                    price: level.price,
+                    snapshot: None,
+                    merged: Some(level.size),
+                });
+            }
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::ParquetWriter;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn row(
+        at: DateTime<Utc>,
+        kind: BookUpdateKind,
+        bids: &[(Decimal, Decimal)],
+        asks: &[(Decimal, Decimal)],
+    ) -> OrderBookRecord {
+        OrderBookRecord {
+            timestamp: at,
+            token_id: Arc::from("yes"),
+            bids: bids.to_vec(),
+            asks: asks.to_vec(),
+            crossed: false,
+            kind,
+        }
+    }
+
+    /// Snapshot, three deltas, snapshot of the result, then the same again
+    /// with the second delta dropped from the capture
+    fn capture(drop_delta: bool) -> Vec<OrderBookRecord> {
+        use BookUpdateKind::{Delta, Snapshot};
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let at = |s: i64| t0 + Duration::seconds(s);
+        let start = [(dec!(0.50), dec!(100)), (dec!(0.49), dec!(50))];
+        let asks = [(dec!(0.52), dec!(80)), (dec!(0.53), dec!(40))];
+
+        let mut rows = vec![
+            row(at(0), Snapshot, &start, &asks),
+            row(at(1), Delta, &[(dec!(0.51), dec!(30))], &[]),
+            row(at(2), Delta, &[(dec!(0.50), dec!(0))], &[]),
+            row(at(3), Delta, &[], &[(dec!(0.52), dec!(60))]),
+        ];
+        let after = row(
+            at(4),
+            Snapshot,
+            &[(dec!(0.51), dec!(30)), (dec!(0.49), dec!(50))],
+            &[(dec!(0.52), dec!(60)), (dec!(0.53), dec!(40))],
+        );
+        rows.push(after);
+        if drop_delta {
+            rows.remove(2);
+        }
+        rows
+    }
+
+    #[test]
+    fn test_clean_capture_has_no_divergence() {
+        let audit = audit_book(&capture(false), Decimal::ZERO);
+        assert_eq!((audit.rows, audit.snapshots, audit.deltas), (5, 2, 3));
+        assert_eq!(audit.compared, 1);
+        assert!(audit.divergences.is_empty());
+        assert_eq!(audit.divergence_rate(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_keep_alives_are_compared_like_snapshots() {
+        use BookUpdateKind::{Delta, KeepAlive};
+        let mut rows = capture(false);
+        let held = rows[4].clone();
+        let restated = |secs: i64| OrderBookRecord {
+            timestamp: held.timestamp + Duration::seconds(secs),
+            kind: KeepAlive,
+            ..held.clone()
+        };
+        rows.push(restated(30));
+        rows.push(row(
+            held.timestamp + Duration::seconds(45),
+            Delta,
+            &[(dec!(0.49), dec!(0))],
+            &[],
+        ));
+        // Restates the book as it was before the delta
+        rows.push(restated(75));
+
+        let audit = audit_book(&rows, Decimal::ZERO);
+        assert_eq!(
+            (audit.rows, audit.snapshots, audit.deltas, audit.keep_alives),
+            (8, 2, 4, 2)
+        );
+        assert_eq!(audit.compared, 3);
+        assert_eq!(audit.divergences.len(), 1);
+        assert_eq!(audit.divergences[0].deltas_since, 1);
+        assert_eq!(audit.divergences[0].missing[0].price, dec!(0.49));
+        assert_eq!(audit.longest_gap, Duration::seconds(30));
+    }
+
+    #[test]
+    fn test_redelivered_rows_are_dropped() {
+        let mut rows = capture(false);
+        rows.insert(2, rows[1].clone());
+        // An older snapshot than the book, not seen before
+        let mut stale = rows[0].clone();
+        stale.bids.truncate(1);
+        rows.push(stale);
+        let audit = audit_book(&rows, Decimal::ZERO);
+        assert_eq!(
+            (audit.rows, audit.duplicates, audit.out_of_order),
+            (7, 1, 1)
+        );
+        assert_eq!(audit.compared, 1);
+        assert!(audit.divergences.is_empty());
+    }
+
+    #[test]
+    fn test_dropped_delta_shows_at_next_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let writer = ParquetWriter::new(dir.path().to_path_buf(), 3600);
+        let rows = capture(true);
+        // Split across two files, written newest first
+        let (first, second) = rows.split_at(2);
+        writer
+            .write_orderbook_snapshots(&writer.file_path("orderbook", second[0].timestamp), second)
+            .unwrap();
+        writer
+            .write_orderbook_snapshots(&writer.file_path("orderbook", first[0].timestamp), first)
+            .unwrap();
+
+        let records = load_book_records(dir.path(), "yes").unwrap();
+        assert_eq!(records.len(), 4);
+        assert!(load_book_records(dir.path(), "no").unwrap().is_empty());
+
+        let audit = audit_book(&records, Decimal::ZERO);
+        assert_eq!(audit.divergence_rate(), Decimal::ONE);
+        let diff = &audit.divergences[0];
+        assert_eq!(diff.deltas_since, 2);
+        assert!(diff.missing.is_empty());
+        assert_eq!(
+            diff.extra,
+            vec![LevelDiff {
+                side: "bid",
+                price: dec!(0.50),
+                snapshot: None,
+                merged: Some(dec!(100)),
+            }]
+        );
+        assert_eq!(diff.crossed, None);
+        assert_eq!(
+            diff.extra[0].to_string(),
+            "bid 0.50: snapshot - vs merged 100"
+        );
+        assert_eq!(audit.worst(5).len(), 1);
+    }
+
+    #[test]
+    fn test_size_tolerance_and_crossed_merge() {
+        use BookUpdateKind::{Delta, Snapshot};
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let rows = vec![
+            row(
+                t0,
+                Snapshot,
+                &[(dec!(0.50), dec!(100))],
+                &[(dec!(0.52), dec!(80))],
+            ),
+            // Bid through the ask that the removal of 0.52 never cleared
+            row(t0, Delta, &[(dec!(0.53), dec!(5))], &[]),
+            row(
+                t0,
+                Snapshot,
+                &[(dec!(0.53), dec!(5)), (dec!(0.50), dec!(100.4))],
+                &[(dec!(0.54), dec!(80))],
+            ),
+        ];
+        let audit = audit_book(&rows, dec!(0.5));
+        let diff = &audit.divergences[0];
+        assert_eq!(diff.crossed, Some("crossed"));
+        assert!(diff.size_mismatches.is_empty());
+        assert_eq!(diff.missing[0].price, dec!(0.54));
+        assert_eq!(diff.extra[0].price, dec!(0.52));
+        assert_eq!(diff.score(), 3);
+
+        let strict = audit_book(&rows, Decimal::ZERO);
+        assert_eq!(strict.divergences[0].size_mismatches.len(), 1);
+    }
+}
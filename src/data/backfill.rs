@@ -0,0 +1,439 @@
+//! Price history backfill
+//!
+//! Polymarket's CLOB serves a token's past prices, without books, over
+//! REST. A backfill fetches them for a set of tokens over a time range in
+//! fixed chunks, pausing between requests, and writes each chunk to its own
+//! `price_history` Parquet file. A manifest in the output directory records
+//! the last chunk written, so a rerun of the same job after a failure
+//! resumes where it stopped instead of fetching everything again.
+
+use super::parquet::{ParquetWriter, PricePointRecord, PRICE_HISTORY_PREFIX};
+use super::sink::{capture_path, DataFormat};
+use crate::execution::CLOB_URL;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Default hours of history fetched per request
+pub const DEFAULT_BACKFILL_CHUNK_HOURS: u64 = 24;
+
+/// Default pause between requests, in milliseconds
+pub const DEFAULT_BACKFILL_REQUEST_INTERVAL_MS: u64 = 250;
+
+/// Default minutes between returned price points
+pub const DEFAULT_BACKFILL_FIDELITY_MINS: u32 = 1;
+
+/// File in the output directory recording a backfill's progress
+pub const BACKFILL_MANIFEST_FILE: &str = "price_history_manifest.json";
+
+/// One point of the `/prices-history` response
+#[derive(Debug, Deserialize)]
+struct HistoryPoint {
+    t: i64,
+    p: serde_json::Number,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryResponse {
+    history: Vec<HistoryPoint>,
+}
+
+/// Client for the CLOB's price history endpoint, at most one request per
+/// interval
+pub struct ClobHistoryClient {
+    base_url: String,
+    http: reqwest::Client,
+    fidelity_mins: u32,
+    request_interval: std::time::Duration,
+    last_request: Option<Instant>,
+}
+
+impl ClobHistoryClient {
+    /// Create a client against the public CLOB API
+    pub fn new() -> Self {
+        Self::with_base_url(CLOB_URL)
+    }
+
+    /// Create a client against a custom base URL
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            fidelity_mins: DEFAULT_BACKFILL_FIDELITY_MINS,
+            request_interval: std::time::Duration::from_millis(
+                DEFAULT_BACKFILL_REQUEST_INTERVAL_MS,
+            ),
+            last_request: None,
+        }
+    }
+
+    /// Ask for a point every `minutes`
+    pub fn with_fidelity(mut self, minutes: u32) -> Self {
+        self.fidelity_mins = minutes.max(1);
+        self
+    }
+
+    /// Wait at least `interval` between requests
+    pub fn with_request_interval(mut self, interval: std::time::Duration) -> Self {
+        self.request_interval = interval;
+        self
+    }
+
+    /// Prices of `token_id` from `from` up to but excluding `to`
+    pub async fn fetch(
+        &mut self,
+        token_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<PricePointRecord>> {
+        if let Some(last) = self.last_request {
+            let wait = self.request_interval.saturating_sub(last.elapsed());
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+        }
+        self.last_request = Some(Instant::now());
+
+        let response: HistoryResponse = self
+            .http
+            .get(format!("{}/prices-history", self.base_url))
+            .query(&[
+                ("market", token_id.to_string()),
+                ("startTs", from.timestamp().to_string()),
+                ("endTs", to.timestamp().to_string()),
+                ("fidelity", self.fidelity_mins.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let token_id: Arc<str> = Arc::from(token_id);
+        let mut points = vec![];
+        for point in response.history {
+            let Some(ts) = DateTime::from_timestamp(point.t, 0) else {
+                continue;
+            };
+            if ts < from || ts >= to {
+                continue;
+            }
+            points.push(PricePointRecord {
+                token_id: token_id.clone(),
+                ts,
+                price: Decimal::from_str(&point.p.to_string())?,
+            });
+        }
+        Ok(points)
+    }
+}
+
+impl Default for ClobHistoryClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Progress of one backfill job, saved after every chunk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackfillManifest {
+    pub tokens: Vec<String>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub chunk_secs: i64,
+    /// End of the last chunk written
+    pub done_through: Option<DateTime<Utc>>,
+    /// Points written so far
+    pub points: usize,
+}
+
+impl BackfillManifest {
+    /// The manifest at `path`, if one was saved
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the manifest to `path`, replacing any earlier one whole
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn same_job(&self, other: &Self) -> bool {
+        (&self.tokens, self.from, self.to, self.chunk_secs)
+            == (&other.tokens, other.from, other.to, other.chunk_secs)
+    }
+}
+
+/// What a backfill run did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackfillReport {
+    /// Chunks in the job
+    pub chunks: usize,
+    /// Chunks an earlier run had already written
+    pub skipped: usize,
+    /// Chunks fetched by this run
+    pub fetched: usize,
+    /// Points written by this run
+    pub points: usize,
+}
+
+/// Fetches price history for `tokens` over `[from, to)` in chunks
+#[derive(Debug, Clone)]
+pub struct PriceBackfill {
+    tokens: Vec<String>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    chunk: Duration,
+    output_dir: PathBuf,
+}
+
+impl PriceBackfill {
+    pub fn new(
+        tokens: Vec<String>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        output_dir: PathBuf,
+    ) -> Self {
+        Self {
+            tokens,
+            from,
+            to,
+            chunk: Duration::hours(DEFAULT_BACKFILL_CHUNK_HOURS as i64),
+            output_dir,
+        }
+    }
+
+    /// Fetch `chunk` of history per request
+    pub fn with_chunk(mut self, chunk: Duration) -> Self {
+        self.chunk = chunk.max(Duration::seconds(1));
+        self
+    }
+
+    /// Path of the job's manifest
+    pub fn manifest_path(&self) -> PathBuf {
+        self.output_dir.join(BACKFILL_MANIFEST_FILE)
+    }
+
+    /// Start and end of each chunk; the last is cut short at `to`
+    pub fn chunks(&self) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let mut chunks = vec![];
+        let mut start = self.from;
+        while start < self.to {
+            let end = (start + self.chunk).min(self.to);
+            chunks.push((start, end));
+            start = end;
+        }
+        chunks
+    }
+
+    /// Fetch every chunk not already written, saving the manifest after
+    /// each
+    ///
+    /// Fails if the output directory holds the manifest of another job.
+    pub async fn run(&self, client: &mut ClobHistoryClient) -> anyhow::Result<BackfillReport> {
+        std::fs::create_dir_all(&self.output_dir)?;
+        let path = self.manifest_path();
+        let job = BackfillManifest {
+            tokens: self.tokens.clone(),
+            from: self.from,
+            to: self.to,
+            chunk_secs: self.chunk.num_seconds(),
+            done_through: None,
+            points: 0,
+        };
+        let mut manifest = match BackfillManifest::load(&path)? {
+            Some(saved) if saved.same_job(&job) => saved,
+            Some(_) => anyhow::bail!(
+                "{:?} belongs to another backfill; rerun it with the same arguments or remove it",
+                path
+            ),
+            None => job,
+        };
+
+        let writer = ParquetWriter::new(self.output_dir.clone(), self.chunk.num_seconds() as u64);
+        let chunks = self.chunks();
+        let mut report = BackfillReport {
+            chunks: chunks.len(),
+            ..Default::default()
+        };
+        for (start, end) in chunks {
+            if manifest.done_through.is_some_and(|done| end <= done) {
+                report.skipped += 1;
+                continue;
+            }
+            let mut points = vec![];
+            for token in &self.tokens {
+                points.extend(client.fetch(token, start, end).await?);
+            }
+            let file = capture_path(
+                &self.output_dir,
+                PRICE_HISTORY_PREFIX,
+                start,
+                DataFormat::Parquet,
+            );
+            writer.write_price_history(&file, &points)?;
+            tracing::info!(from = %start, to = %end, points = points.len(), "Backfilled price history");
+
+            manifest.done_through = Some(end);
+            manifest.points += points.len();
+            manifest.save(&path)?;
+            report.fetched += 1;
+            report.points += points.len();
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::price_history_from_batch;
+    use crate::data::{read_batches, scan_data_files};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const FROM: i64 = 1_767_225_600; // 2026-01-01 00:00 UTC
+    const HOUR: i64 = 3600;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(secs, 0).unwrap()
+    }
+
+    /// A point every 20 minutes of the hour from `start`, and one past it
+    fn history(start: i64, price: &str) -> serde_json::Value {
+        let points: Vec<_> = [0, 1200, 2400, HOUR]
+            .iter()
+            .map(|offset| serde_json::json!({"t": start + offset, "p": price.parse::<f64>().unwrap()}))
+            .collect();
+        serde_json::json!({ "history": points })
+    }
+
+    async fn serve(server: &MockServer, token: &str, start: i64, status: u16, expect: u64) {
+        let response = match status {
+            200 => ResponseTemplate::new(200).set_body_json(history(start, "0.515")),
+            _ => ResponseTemplate::new(status),
+        };
+        Mock::given(method("GET"))
+            .and(path("/prices-history"))
+            .and(query_param("market", token))
+            .and(query_param("startTs", start.to_string()))
+            .respond_with(response)
+            .expect(expect)
+            .mount(server)
+            .await;
+    }
+
+    fn client(server: &MockServer) -> ClobHistoryClient {
+        ClobHistoryClient::with_base_url(server.uri())
+            .with_request_interval(std::time::Duration::ZERO)
+    }
+
+    fn backfill(dir: &Path) -> PriceBackfill {
+        PriceBackfill::new(
+            vec!["yes".to_string(), "no".to_string()],
+            at(FROM),
+            at(FROM + 3 * HOUR),
+            dir.to_path_buf(),
+        )
+        .with_chunk(Duration::hours(1))
+    }
+
+    fn written(dir: &Path) -> Vec<PricePointRecord> {
+        let mut points = vec![];
+        let mut files = scan_data_files(dir, false).unwrap();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        for file in files {
+            assert_eq!(file.prefix, PRICE_HISTORY_PREFIX);
+            for batch in read_batches(&file.path).unwrap() {
+                points.extend(price_history_from_batch(&batch).unwrap());
+            }
+        }
+        points
+    }
+
+    #[tokio::test]
+    async fn test_backfill_fetches_each_chunk_once_per_token() {
+        let server = MockServer::start().await;
+        for chunk in 0..3 {
+            for token in ["yes", "no"] {
+                serve(&server, token, FROM + chunk * HOUR, 200, 1).await;
+            }
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let job = backfill(dir.path());
+        assert_eq!(job.chunks().len(), 3);
+
+        let report = job.run(&mut client(&server)).await.unwrap();
+        assert_eq!(
+            report,
+            BackfillReport {
+                chunks: 3,
+                skipped: 0,
+                fetched: 3,
+                points: 18,
+            }
+        );
+        // The point past each chunk's end is left to the next chunk
+        let points = written(dir.path());
+        assert_eq!(points.len(), 18);
+        assert_eq!(points[0].price, rust_decimal_macros::dec!(0.515));
+        assert!(points.iter().all(|p| p.ts < at(FROM + 3 * HOUR)));
+        let manifest = BackfillManifest::load(&job.manifest_path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(manifest.done_through, Some(at(FROM + 3 * HOUR)));
+        assert_eq!(manifest.points, 18);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_resumes_after_a_failed_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let job = backfill(dir.path());
+
+        // The second chunk fails for one token
+        let flaky = MockServer::start().await;
+        serve(&flaky, "yes", FROM, 200, 1).await;
+        serve(&flaky, "no", FROM, 200, 1).await;
+        serve(&flaky, "yes", FROM + HOUR, 200, 1).await;
+        serve(&flaky, "no", FROM + HOUR, 500, 1).await;
+        assert!(job.run(&mut client(&flaky)).await.is_err());
+        let manifest = BackfillManifest::load(&job.manifest_path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(manifest.done_through, Some(at(FROM + HOUR)));
+        assert_eq!(written(dir.path()).len(), 6);
+
+        // The rerun never asks for the first chunk again
+        let healthy = MockServer::start().await;
+        serve(&healthy, "yes", FROM, 200, 0).await;
+        serve(&healthy, "no", FROM, 200, 0).await;
+        for chunk in 1..3 {
+            for token in ["yes", "no"] {
+                serve(&healthy, token, FROM + chunk * HOUR, 200, 1).await;
+            }
+        }
+        let report = job.run(&mut client(&healthy)).await.unwrap();
+        assert_eq!((report.skipped, report.fetched), (1, 2));
+        assert_eq!(written(dir.path()).len(), 18);
+
+        // Another job in the same directory is refused
+        let other = PriceBackfill::new(
+            vec!["yes".to_string()],
+            at(FROM),
+            at(FROM + HOUR),
+            dir.path().to_path_buf(),
+        );
+        assert!(other.run(&mut client(&healthy)).await.is_err());
+    }
+}
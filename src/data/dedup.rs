@@ -0,0 +1,226 @@
+//! Capture-side deduplication of order book snapshots
+//!
+//! Polymarket re-sends unchanged books, and the merged books we record are
+//! often unchanged from one message to the next, so many captured rows
+//! repeat the token's row before them. With deduplication on, the recorder
+//! keeps a fingerprint of the captured levels last written for each token
+//! and skips a book that matches it.
+//!
+//! So that a quiet book can be told from a missing feed, the first
+//! unchanged book once `keepalive_secs` have passed since the token's last
+//! row is written anyway, as a `keepalive` row restating the book. Readers
+//! apply a keep-alive as a snapshot and hold each book until the token's
+//! next row; a token with no row for longer than the keep-alive interval
+//! had no data, not an unchanged book.
+
+use super::parquet::{OrderBookRecord, CAPTURED_BOOK_LEVELS};
+use crate::duration::DurationConfig;
+use crate::orderbook::BookUpdateKind;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Default longest an unchanged book goes without a captured row
+pub const DEFAULT_BOOK_KEEPALIVE_SECS: u64 = 30;
+
+/// Deduplication of captured order books, under `[data.dedup]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookDedupConfig {
+    /// Skip books identical to the token's last captured row; off captures
+    /// every frame
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Longest an unchanged book goes without a row
+    #[serde(default = "default_keepalive_secs")]
+    pub keepalive_secs: DurationConfig,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_keepalive_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_BOOK_KEEPALIVE_SECS)
+}
+
+impl Default for BookDedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            keepalive_secs: default_keepalive_secs(),
+        }
+    }
+}
+
+/// The row last written for one token
+#[derive(Debug, Clone, Copy)]
+struct Written {
+    fingerprint: u64,
+    at: DateTime<Utc>,
+}
+
+/// Change detection in front of the order book writer
+#[derive(Debug)]
+pub struct BookDedup {
+    config: BookDedupConfig,
+    written: HashMap<Arc<str>, Written>,
+    pruned_at: Option<DateTime<Utc>>,
+}
+
+impl BookDedup {
+    /// Deduplicate as `config` directs
+    pub fn new(config: BookDedupConfig) -> Self {
+        Self {
+            config,
+            written: HashMap::new(),
+            pruned_at: None,
+        }
+    }
+
+    /// The row to write for `record`: as it is when the book changed, as a
+    /// keep-alive when it went unchanged for the keep-alive interval, and
+    /// `None` when the token's last row, written more recently, holds it
+    pub fn filter(&mut self, mut record: OrderBookRecord) -> Option<OrderBookRecord> {
+        if !self.config.enabled {
+            return Some(record);
+        }
+        let keepalive = self.config.keepalive_secs.to_chrono();
+        let now = record.timestamp;
+        self.prune(now, keepalive);
+
+        let fingerprint = fingerprint(&record);
+        match self.written.get_mut(&record.token_id) {
+            Some(last) if last.fingerprint == fingerprint => {
+                if now - last.at < keepalive {
+                    return None;
+                }
+                last.at = now;
+                record.kind = BookUpdateKind::KeepAlive;
+            }
+            Some(last) => {
+                *last = Written {
+                    fingerprint,
+                    at: now,
+                }
+            }
+            None => {
+                self.written.insert(
+                    record.token_id.clone(),
+                    Written {
+                        fingerprint,
+                        at: now,
+                    },
+                );
+            }
+        }
+        Some(record)
+    }
+
+    /// Forget tokens without a book for twice the keep-alive interval, as
+    /// those of settled markets, checking at most once per interval; the
+    /// next book of one is written as a snapshot
+    fn prune(&mut self, now: DateTime<Utc>, keepalive: chrono::Duration) {
+        if self.pruned_at.is_some_and(|at| now - at < keepalive) {
+            return;
+        }
+        self.pruned_at = Some(now);
+        self.written.retain(|_, last| now - last.at < keepalive * 2);
+    }
+}
+
+/// Hash of what a captured row holds: its top levels and crossed flag
+fn fingerprint(record: &OrderBookRecord) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for side in [&record.bids, &record.asks] {
+        let top = &side[..side.len().min(CAPTURED_BOOK_LEVELS)];
+        top.len().hash(&mut hasher);
+        for (price, size) in top {
+            price.hash(&mut hasher);
+            size.hash(&mut hasher);
+        }
+    }
+    record.crossed.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+
+    fn book(token: &str, secs: i64, bid: rust_decimal::Decimal) -> OrderBookRecord {
+        OrderBookRecord {
+            timestamp: DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+                + Duration::seconds(secs),
+            token_id: Arc::from(token),
+            bids: vec![(bid, dec!(100))],
+            asks: vec![(dec!(0.56), dec!(80))],
+            crossed: false,
+            kind: BookUpdateKind::Snapshot,
+        }
+    }
+
+    #[test]
+    fn test_repeated_books_are_suppressed_between_keep_alives() {
+        let mut dedup = BookDedup::new(BookDedupConfig::default());
+        let written: Vec<(i64, BookUpdateKind)> = (0..100)
+            .filter_map(|s| dedup.filter(book("yes", s, dec!(0.55))))
+            .map(|r| (r.timestamp.timestamp() - 1_700_000_000, r.kind))
+            .collect();
+        use BookUpdateKind::{KeepAlive, Snapshot};
+        assert_eq!(
+            written,
+            [
+                (0, Snapshot),
+                (30, KeepAlive),
+                (60, KeepAlive),
+                (90, KeepAlive)
+            ]
+        );
+
+        // A change is written at once and restarts the interval; tokens
+        // are tracked apart
+        assert!(dedup.filter(book("no", 100, dec!(0.55))).is_some());
+        assert_eq!(
+            dedup.filter(book("yes", 101, dec!(0.54))).unwrap().kind,
+            Snapshot
+        );
+        assert!(dedup.filter(book("yes", 125, dec!(0.54))).is_none());
+        assert_eq!(
+            dedup.filter(book("yes", 131, dec!(0.54))).unwrap().kind,
+            KeepAlive
+        );
+        // A level past the captured depth is not captured, so changes nothing
+        let mut deep = book("yes", 132, dec!(0.54));
+        deep.bids.extend((1..=5).map(|i| {
+            (
+                dec!(0.54) - dec!(0.01) * rust_decimal::Decimal::from(i),
+                dec!(1),
+            )
+        }));
+        let mut deeper = deep.clone();
+        deeper.timestamp += Duration::seconds(1);
+        deeper.bids.push((dec!(0.10), dec!(1)));
+        assert!(dedup.filter(deep).is_some());
+        assert!(dedup.filter(deeper).is_none());
+        // Forgotten after twice the interval without a book
+        assert_eq!(
+            dedup.filter(book("no", 200, dec!(0.55))).unwrap().kind,
+            Snapshot
+        );
+
+        let mut every_frame = BookDedup::new(BookDedupConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        let written = (0..10)
+            .filter_map(|s| every_frame.filter(book("yes", s, dec!(0.55))))
+            .filter(|r| r.kind == Snapshot)
+            .count();
+        assert_eq!(written, 10);
+    }
+}
@@ -0,0 +1,248 @@
+//! Disk space monitoring and retention enforcement for captured data
+//!
+//! Below the soft free-space threshold the `disk` health component goes
+//! Degraded; below the hard threshold recording is paused (buffers are
+//! dropped and counted) instead of failing on every flush.
+
+use super::retention::{data_dir_bytes, enforce_retention, RetentionPolicy};
+use crate::duration::DurationConfig;
+use crate::journal::Journal;
+use crate::telemetry::EventCode;
+use crate::telemetry::{set_data_dir_bytes, HealthRegistry, HealthState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Health component name used for disk state
+pub const DISK_HEALTH_COMPONENT: &str = "disk";
+
+/// Free-space thresholds for the data directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskConfig {
+    /// Below this many free bytes the disk is reported Degraded
+    #[serde(default = "default_soft_min_free_bytes")]
+    pub soft_min_free_bytes: u64,
+    /// Below this many free bytes recording is paused
+    #[serde(default = "default_hard_min_free_bytes")]
+    pub hard_min_free_bytes: u64,
+    /// Interval between free-space checks
+    #[serde(default = "default_check_interval_secs")]
+    pub check_interval_secs: DurationConfig,
+}
+
+fn default_soft_min_free_bytes() -> u64 {
+    5 * 1024 * 1024 * 1024
+}
+
+fn default_hard_min_free_bytes() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_check_interval_secs() -> DurationConfig {
+    DurationConfig::from_secs(30)
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            soft_min_free_bytes: default_soft_min_free_bytes(),
+            hard_min_free_bytes: default_hard_min_free_bytes(),
+            check_interval_secs: default_check_interval_secs(),
+        }
+    }
+}
+
+/// Free-space classification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskState {
+    /// Enough free space
+    Ok,
+    /// Below the soft threshold
+    Low,
+    /// Below the hard threshold; recording paused
+    Critical,
+}
+
+impl DiskState {
+    /// Classify free bytes against the configured thresholds
+    pub fn classify(free_bytes: u64, config: &DiskConfig) -> Self {
+        if free_bytes < config.hard_min_free_bytes {
+            DiskState::Critical
+        } else if free_bytes < config.soft_min_free_bytes {
+            DiskState::Low
+        } else {
+            DiskState::Ok
+        }
+    }
+}
+
+/// Bytes available to this process on the filesystem holding `path`
+pub fn available_space(path: &Path) -> std::io::Result<u64> {
+    fs4::available_space(path)
+}
+
+/// Periodically enforces retention and watches free space
+pub struct DiskManager {
+    dir: PathBuf,
+    retention: RetentionPolicy,
+    disk: DiskConfig,
+    paused: Arc<AtomicBool>,
+    health: HealthRegistry,
+    journal: Option<Journal>,
+}
+
+impl DiskManager {
+    /// Create a manager for `dir`, toggling `paused` when space is critical
+    pub fn new(
+        dir: PathBuf,
+        retention: RetentionPolicy,
+        disk: DiskConfig,
+        paused: Arc<AtomicBool>,
+        health: HealthRegistry,
+    ) -> Self {
+        Self {
+            dir,
+            retention,
+            disk,
+            paused,
+            health,
+            journal: None,
+        }
+    }
+
+    /// Journal retention deletions to the given journal
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Run cleanup and free-space checks until the task is aborted
+    pub async fn run(self) {
+        let min = std::time::Duration::from_secs(1);
+        let check = self.disk.check_interval_secs.get().max(min);
+        let cleanup = self.retention.cleanup_interval_secs.get().max(min);
+        let mut check_timer = tokio::time::interval(check);
+        let mut cleanup_timer = tokio::time::interval(cleanup);
+
+        loop {
+            tokio::select! {
+                _ = cleanup_timer.tick() => self.cleanup(Utc::now()),
+                _ = check_timer.tick() => match available_space(&self.dir) {
+                    Ok(free) => {
+                        self.apply_free_space(free);
+                    }
+                    Err(e) => tracing::warn!(error = %e, dir = ?self.dir, "Failed to read free disk space"),
+                },
+            }
+        }
+    }
+
+    /// Run one retention pass
+    pub fn cleanup(&self, now: DateTime<Utc>) {
+        match enforce_retention(&self.dir, &self.retention, self.journal.as_ref(), now) {
+            Ok(report) => {
+                if !report.removed.is_empty() {
+                    tracing::info!(
+                        files = report.removed.len(),
+                        bytes = report.bytes_freed,
+                        "Retention cleanup removed data files"
+                    );
+                }
+                set_data_dir_bytes(report.remaining_bytes as f64);
+            }
+            Err(e) => tracing::warn!(error = %e, "Retention cleanup failed"),
+        }
+    }
+
+    /// Update health and the pause flag for the given free space
+    pub fn apply_free_space(&self, free_bytes: u64) -> DiskState {
+        let state = DiskState::classify(free_bytes, &self.disk);
+        let was_paused = self
+            .paused
+            .swap(state == DiskState::Critical, Ordering::Relaxed);
+
+        let reason = Some(format!("{} bytes free", free_bytes));
+        match state {
+            DiskState::Ok => self
+                .health
+                .set(DISK_HEALTH_COMPONENT, HealthState::Healthy, None),
+            DiskState::Low => self
+                .health
+                .set(DISK_HEALTH_COMPONENT, HealthState::Degraded, reason),
+            DiskState::Critical => {
+                self.health
+                    .set(DISK_HEALTH_COMPONENT, HealthState::Unhealthy, reason)
+            }
+        }
+
+        match (was_paused, state == DiskState::Critical) {
+            (false, true) => {
+                tracing::error!(event_code = %EventCode::DiskCritical, free_bytes, "Disk space critical, recording paused")
+            }
+            (true, false) => {
+                tracing::info!(event_code = %EventCode::DiskRecovered, free_bytes, "Disk space recovered, recording resumed")
+            }
+            _ => {}
+        }
+
+        if let Ok(bytes) = data_dir_bytes(&self.dir) {
+            set_data_dir_bytes(bytes as f64);
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn config() -> DiskConfig {
+        DiskConfig {
+            soft_min_free_bytes: 1000,
+            hard_min_free_bytes: 100,
+            check_interval_secs: DurationConfig::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(DiskState::classify(5000, &config()), DiskState::Ok);
+        assert_eq!(DiskState::classify(500, &config()), DiskState::Low);
+        assert_eq!(DiskState::classify(50, &config()), DiskState::Critical);
+    }
+
+    #[test]
+    fn test_free_space_drives_health_and_pause() {
+        let dir = TempDir::new().unwrap();
+        let paused = Arc::new(AtomicBool::new(false));
+        let health = HealthRegistry::new();
+        let manager = DiskManager::new(
+            dir.path().to_path_buf(),
+            RetentionPolicy::default(),
+            config(),
+            paused.clone(),
+            health.clone(),
+        );
+
+        manager.apply_free_space(500);
+        assert_eq!(health.overall(), HealthState::Degraded);
+        assert!(!paused.load(Ordering::Relaxed));
+
+        manager.apply_free_space(50);
+        assert_eq!(health.overall(), HealthState::Unhealthy);
+        assert!(paused.load(Ordering::Relaxed));
+
+        manager.apply_free_space(5000);
+        assert_eq!(health.overall(), HealthState::Healthy);
+        assert!(!paused.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_available_space_reads_filesystem() {
+        let dir = TempDir::new().unwrap();
+        assert!(available_space(dir.path()).unwrap() > 0);
+    }
+}
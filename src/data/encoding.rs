@@ -0,0 +1,373 @@
+//! Parquet writer tuning
+//!
+//! Capture files are dominated by a handful of repeated identifiers
+//! (token ids, symbols, sides) next to decimal strings that rarely repeat
+//! exactly, so the defaults dictionary-encode only the identifier columns
+//! and lean on zstd for the rest.
+
+use super::parquet::{CAPTURE_SCHEMA_VERSION, SCHEMA_VERSION_KEY};
+use crate::fingerprint::ConfigFingerprint;
+use arrow::compute::concat_batches;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use parquet::schema::types::ColumnPath;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Default zstd level
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+/// Default maximum rows per row group
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 1024 * 1024;
+
+/// Default maximum rows per row group for order book files
+pub const DEFAULT_ORDERBOOK_ROW_GROUP_SIZE: usize = 4 * 1024 * 1024;
+
+/// Default target bytes per data page
+pub const DEFAULT_DATA_PAGE_SIZE: usize = 1024 * 1024;
+
+/// Compression codec for Parquet column chunks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParquetCodec {
+    #[default]
+    Zstd,
+    Snappy,
+    Lz4,
+    None,
+}
+
+impl ParquetCodec {
+    /// Every codec, for benchmarks and tests
+    pub const ALL: [ParquetCodec; 4] = [Self::Zstd, Self::Snappy, Self::Lz4, Self::None];
+
+    fn compression(self, level: i32) -> anyhow::Result<Compression> {
+        Ok(match self {
+            Self::Zstd => Compression::ZSTD(ZstdLevel::try_new(level)?),
+            Self::Snappy => Compression::SNAPPY,
+            Self::Lz4 => Compression::LZ4_RAW,
+            Self::None => Compression::UNCOMPRESSED,
+        })
+    }
+}
+
+/// Parquet writer settings, under `[data.parquet]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParquetTuning {
+    /// Compression codec
+    #[serde(default)]
+    pub codec: ParquetCodec,
+    /// Compression level; only zstd (1-22) uses it
+    #[serde(default = "default_compression_level")]
+    pub compression_level: i32,
+    /// Dictionary-encode columns not listed in `dictionary_columns`
+    #[serde(default)]
+    pub dictionary_default: bool,
+    /// Columns always dictionary-encoded
+    #[serde(default = "default_dictionary_columns")]
+    pub dictionary_columns: Vec<String>,
+    /// Maximum rows per row group
+    #[serde(default = "default_row_group_size")]
+    pub row_group_size: usize,
+    /// Row group size overrides keyed by file prefix
+    #[serde(default = "default_prefix_row_group_sizes")]
+    pub prefix_row_group_sizes: HashMap<String, usize>,
+    /// Target bytes per data page
+    #[serde(default = "default_data_page_size")]
+    pub data_page_size: usize,
+}
+
+fn default_compression_level() -> i32 {
+    DEFAULT_COMPRESSION_LEVEL
+}
+
+fn default_dictionary_columns() -> Vec<String> {
+    ["token_id", "symbol", "side"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+fn default_row_group_size() -> usize {
+    DEFAULT_ROW_GROUP_SIZE
+}
+
+fn default_prefix_row_group_sizes() -> HashMap<String, usize> {
+    HashMap::from([("orderbook".to_string(), DEFAULT_ORDERBOOK_ROW_GROUP_SIZE)])
+}
+
+fn default_data_page_size() -> usize {
+    DEFAULT_DATA_PAGE_SIZE
+}
+
+impl Default for ParquetTuning {
+    fn default() -> Self {
+        Self {
+            codec: ParquetCodec::default(),
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            dictionary_default: false,
+            dictionary_columns: default_dictionary_columns(),
+            row_group_size: DEFAULT_ROW_GROUP_SIZE,
+            prefix_row_group_sizes: default_prefix_row_group_sizes(),
+            data_page_size: DEFAULT_DATA_PAGE_SIZE,
+        }
+    }
+}
+
+impl ParquetTuning {
+    /// Settings files were written with before tuning was configurable:
+    /// snappy with dictionary encoding on every column
+    pub fn legacy() -> Self {
+        Self {
+            codec: ParquetCodec::Snappy,
+            dictionary_default: true,
+            dictionary_columns: vec![],
+            prefix_row_group_sizes: HashMap::new(),
+            ..Self::default()
+        }
+    }
+
+    /// The defaults with another codec
+    pub fn with_codec(mut self, codec: ParquetCodec, level: i32) -> Self {
+        self.codec = codec;
+        self.compression_level = level;
+        self
+    }
+
+    /// Named settings compared by `data benchmark-encoding`
+    pub fn presets() -> Vec<(&'static str, Self)> {
+        vec![
+            ("legacy-snappy", Self::legacy()),
+            ("zstd-3", Self::default()),
+            ("zstd-9", Self::default().with_codec(ParquetCodec::Zstd, 9)),
+            (
+                "snappy",
+                Self::default().with_codec(ParquetCodec::Snappy, DEFAULT_COMPRESSION_LEVEL),
+            ),
+            (
+                "lz4",
+                Self::default().with_codec(ParquetCodec::Lz4, DEFAULT_COMPRESSION_LEVEL),
+            ),
+            (
+                "none",
+                Self::default().with_codec(ParquetCodec::None, DEFAULT_COMPRESSION_LEVEL),
+            ),
+        ]
+    }
+
+    /// Maximum rows per row group for files with `prefix`
+    pub fn row_group_size_for(&self, prefix: &str) -> usize {
+        self.prefix_row_group_sizes
+            .get(prefix)
+            .copied()
+            .unwrap_or(self.row_group_size)
+    }
+
+    /// Writer properties for a `prefix` file stamped with the schema
+    /// version and `fingerprint`, if any
+    pub fn properties(
+        &self,
+        prefix: &str,
+        fingerprint: Option<&ConfigFingerprint>,
+    ) -> anyhow::Result<WriterProperties> {
+        let mut metadata = vec![KeyValue::new(
+            SCHEMA_VERSION_KEY.to_string(),
+            CAPTURE_SCHEMA_VERSION.to_string(),
+        )];
+        if let Some(fp) = fingerprint {
+            metadata.extend(
+                fp.metadata()
+                    .into_iter()
+                    .map(|(key, value)| KeyValue::new(key.to_string(), value)),
+            );
+        }
+        let mut builder = WriterProperties::builder()
+            .set_compression(self.codec.compression(self.compression_level)?)
+            .set_dictionary_enabled(self.dictionary_default)
+            .set_max_row_group_size(self.row_group_size_for(prefix).max(1))
+            .set_data_page_size_limit(self.data_page_size.max(1))
+            .set_key_value_metadata(Some(metadata));
+        for column in &self.dictionary_columns {
+            builder =
+                builder.set_column_dictionary_enabled(ColumnPath::from(column.as_str()), true);
+        }
+        Ok(builder.build())
+    }
+}
+
+/// One preset's result from [`benchmark_encoding`]
+#[derive(Debug, Clone)]
+pub struct EncodingBenchmark {
+    /// Preset name
+    pub preset: &'static str,
+    /// Bytes on disk
+    pub bytes: u64,
+    /// Time to encode and write the file
+    pub write_time: Duration,
+    /// Time to read and decode it back
+    pub read_time: Duration,
+}
+
+/// Rewrite the Parquet file at `sample` with every preset under `work_dir`
+/// and measure size, write time and read time
+pub fn benchmark_encoding(
+    sample: &Path,
+    work_dir: &Path,
+) -> anyhow::Result<Vec<EncodingBenchmark>> {
+    let batches = ParquetRecordBatchReaderBuilder::try_new(File::open(sample)?)?
+        .build()?
+        .collect::<Result<Vec<_>, _>>()?;
+    let schema = batches
+        .first()
+        .map(|b| b.schema())
+        .ok_or_else(|| anyhow::anyhow!("{} has no rows", sample.display()))?;
+    let prefix = super::file_prefix(sample).unwrap_or_default();
+
+    std::fs::create_dir_all(work_dir)?;
+    let mut results = vec![];
+    for (preset, tuning) in ParquetTuning::presets() {
+        let path = work_dir.join(format!("{preset}.parquet"));
+
+        let started = Instant::now();
+        let props = tuning.properties(&prefix, None)?;
+        let mut writer = ArrowWriter::try_new(File::create(&path)?, schema.clone(), Some(props))?;
+        for batch in &batches {
+            writer.write(batch)?;
+        }
+        writer.close()?;
+        let write_time = started.elapsed();
+
+        let started = Instant::now();
+        let read: Vec<RecordBatch> = ParquetRecordBatchReaderBuilder::try_new(File::open(&path)?)?
+            .build()?
+            .collect::<Result<_, _>>()?;
+        let read_time = started.elapsed();
+        anyhow::ensure!(
+            concat_batches(&schema, &read)? == concat_batches(&schema, &batches)?,
+            "{preset} did not round-trip the sample"
+        );
+
+        results.push(EncodingBenchmark {
+            preset,
+            bytes: std::fs::metadata(&path)?.len(),
+            write_time,
+            read_time,
+        });
+        std::fs::remove_file(&path)?;
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::parquet::{orderbook_batch, OrderBookRecord};
+    use crate::orderbook::BookUpdateKind;
+    use chrono::{DateTime, Duration as ChronoDuration};
+    use rust_decimal::Decimal;
+    use std::sync::Arc;
+
+    /// A few minutes of five-level books on two tokens
+    fn orderbook_fixture() -> RecordBatch {
+        let start = DateTime::from_timestamp(1_735_689_600, 0).unwrap();
+        let tokens: [Arc<str>; 2] = [
+            Arc::from(
+                "21742633143463906290569050155826241533067272736897614950488156847949938836455",
+            ),
+            Arc::from(
+                "48331043336612883890938759509493159234755048973500640148014422747788308965732",
+            ),
+        ];
+        let records: Vec<OrderBookRecord> = (0..4_000i64)
+            .map(|n| {
+                let mid = 5_000 + (n * 7919 % 61) - 30;
+                let level = |offset: i64, size: i64| {
+                    (
+                        Decimal::new(mid + offset, 4),
+                        Decimal::new(10_000 + (n * 31 + size) % 9_000, 2),
+                    )
+                };
+                OrderBookRecord {
+                    timestamp: start + ChronoDuration::milliseconds(n * 250),
+                    token_id: tokens[(n % 2) as usize].clone(),
+                    bids: (1..=5).map(|i| level(-10 * i, i)).collect(),
+                    asks: (1..=5).map(|i| level(10 * i, i * 3)).collect(),
+                    crossed: false,
+                    kind: BookUpdateKind::Snapshot,
+                }
+            })
+            .collect();
+        orderbook_batch(&records).unwrap()
+    }
+
+    fn write(dir: &Path, name: &str, batch: &RecordBatch, tuning: &ParquetTuning) -> u64 {
+        let path = dir.join(format!("{name}.parquet"));
+        let props = tuning.properties("orderbook", None).unwrap();
+        let mut writer =
+            ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), Some(props))
+                .unwrap();
+        writer.write(batch).unwrap();
+        writer.close().unwrap();
+
+        let read: Vec<RecordBatch> =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+                .unwrap()
+                .build()
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        let read = concat_batches(&batch.schema(), &read).unwrap();
+        assert_eq!(&read, batch, "{name} did not round-trip");
+        std::fs::metadata(&path).unwrap().len()
+    }
+
+    #[test]
+    fn test_every_codec_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let batch = orderbook_fixture();
+        for codec in ParquetCodec::ALL {
+            let tuning = ParquetTuning::default().with_codec(codec, DEFAULT_COMPRESSION_LEVEL);
+            write(dir.path(), &format!("{codec:?}"), &batch, &tuning);
+        }
+        assert!(ParquetTuning::default()
+            .with_codec(ParquetCodec::Zstd, 99)
+            .properties("orderbook", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_defaults_beat_legacy_snappy() {
+        let dir = tempfile::tempdir().unwrap();
+        let batch = orderbook_fixture();
+        let legacy = write(dir.path(), "legacy", &batch, &ParquetTuning::legacy());
+        let tuned = write(dir.path(), "tuned", &batch, &ParquetTuning::default());
+        assert!(tuned < legacy, "default {tuned} bytes vs legacy {legacy}");
+    }
+
+    #[test]
+    fn test_tuning_from_toml() {
+        let tuning: ParquetTuning = toml::from_str(
+            r#"
+            codec = "lz4"
+            dictionary_columns = ["token_id"]
+            [prefix_row_group_sizes]
+            price = 1000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(tuning.codec, ParquetCodec::Lz4);
+        assert_eq!(tuning.compression_level, DEFAULT_COMPRESSION_LEVEL);
+        assert_eq!(tuning.row_group_size_for("price"), 1000);
+        assert_eq!(
+            tuning.row_group_size_for("orderbook"),
+            DEFAULT_ROW_GROUP_SIZE
+        );
+    }
+}
@@ -0,0 +1,742 @@
+//! Offline feature extraction for model training
+//!
+//! Replays captured events through the momentum detector and emits one row
+//! per momentum confirmation with the features observed at that instant,
+//! labelled with the market resolution once the market closes.
+//!
+//! # Output schema (version [`FEATURE_SCHEMA_VERSION`])
+//!
+//! | column           | type                   | notes                                   |
+//! |------------------|------------------------|-----------------------------------------|
+//! | `timestamp`      | timestamp[us, UTC]     | time the momentum confirmed             |
+//! | `schema_version` | uint32                 | bumped on any column change             |
+//! | `market_id`      | utf8                   | condition id                            |
+//! | `side`           | utf8                   | `yes` or `no`                           |
+//! | `entry_price`    | float64                | price we would have paid                |
+//! | one per feature  | float64, nullable      | see [`Feature`] for names               |
+//! | `label_win`      | bool, nullable         | traded side won; null if never resolved |
+//! | `label_pnl`      | float64, nullable      | payout minus entry price per share      |
+//!
+//! The features are computed with the code the live path uses: the momentum
+//! detector's velocity, [`OrderBook::depth_within`], and the free functions
+//! below, which the fair value and book shock code call too, so training
+//! data and live inputs cannot drift apart.
+
+use super::parquet::writer_properties;
+use crate::backtest::BacktestEvent;
+use crate::fingerprint;
+use crate::market::Market;
+use crate::model::VolatilityEstimator;
+use crate::orderbook::{BookSide, OrderBook};
+use crate::signal::{MomentumDetector, MomentumSignal, Side, DEFAULT_MAX_MOMENTUM_RETRACE};
+use arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, StringArray, TimestampMicrosecondArray, UInt32Array,
+};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Duration, Utc};
+use parquet::arrow::ArrowWriter;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Version of the feature dataset schema
+pub const FEATURE_SCHEMA_VERSION: u32 = 1;
+
+/// A single model input feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// Spot move since market open, in percent
+    MovePct,
+    /// Momentum velocity over the slope window, in price units per second
+    Slope,
+    /// (bid size - ask size) / (bid size + ask size) over the top levels
+    BookImbalance,
+    /// Best ask minus best bid
+    Spread,
+    /// Bid size within N cents of the best bid
+    DepthBid,
+    /// Ask size within N cents of the best ask
+    DepthAsk,
+    /// Seconds until market close
+    SecondsToClose,
+    /// Annualized realized volatility
+    RealizedVol,
+    /// Previous market resolved in the direction of the traded side (0/1)
+    Carryover,
+}
+
+impl Feature {
+    /// All features in output column order
+    pub const ALL: [Feature; 9] = [
+        Feature::MovePct,
+        Feature::Slope,
+        Feature::BookImbalance,
+        Feature::Spread,
+        Feature::DepthBid,
+        Feature::DepthAsk,
+        Feature::SecondsToClose,
+        Feature::RealizedVol,
+        Feature::Carryover,
+    ];
+
+    /// Column name in the output dataset
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::MovePct => "move_pct",
+            Feature::Slope => "slope",
+            Feature::BookImbalance => "book_imbalance",
+            Feature::Spread => "spread",
+            Feature::DepthBid => "depth_bid",
+            Feature::DepthAsk => "depth_ask",
+            Feature::SecondsToClose => "seconds_to_close",
+            Feature::RealizedVol => "realized_vol",
+            Feature::Carryover => "carryover",
+        }
+    }
+
+    /// Look up a feature by column name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+}
+
+/// Feature extraction configuration
+#[derive(Debug, Clone)]
+pub struct FeatureConfig {
+    /// Features to emit, in column order
+    pub features: Vec<Feature>,
+    /// Momentum window behind the slope and the confirmations
+    pub slope_window: Duration,
+    /// Lookback for realized volatility
+    pub vol_window: Duration,
+    /// Number of top levels used for book imbalance
+    pub imbalance_levels: usize,
+    /// Cents from the touch counted as depth
+    pub depth_cents: u32,
+    /// Minimum time between rows for the same market
+    pub cooldown: Duration,
+    /// Retrace fraction above which a move does not confirm
+    pub max_retrace: Decimal,
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self {
+            features: Feature::ALL.to_vec(),
+            slope_window: Duration::seconds(60),
+            vol_window: Duration::minutes(30),
+            imbalance_levels: 5,
+            depth_cents: 2,
+            cooldown: Duration::seconds(30),
+            max_retrace: DEFAULT_MAX_MOMENTUM_RETRACE,
+        }
+    }
+}
+
+/// Spot move since open, in percent
+pub fn move_pct(open_price: Decimal, current_price: Decimal) -> Option<Decimal> {
+    if open_price <= Decimal::ZERO {
+        return None;
+    }
+    Some((current_price - open_price) / open_price * dec!(100))
+}
+
+/// Size imbalance over the top `levels` of the book, in [-1, 1]
+pub fn book_imbalance(book: &OrderBook, levels: usize) -> Option<Decimal> {
+    let bid: Decimal = book.bids.iter().take(levels).map(|l| l.size).sum();
+    let ask: Decimal = book.asks.iter().take(levels).map(|l| l.size).sum();
+    let total = bid + ask;
+    if total.is_zero() {
+        return None;
+    }
+    Some((bid - ask) / total)
+}
+
+/// Seconds until the market closes (negative once closed)
+pub fn seconds_to_close(market: &Market, now: DateTime<Utc>) -> i64 {
+    (market.close_time - now).num_seconds()
+}
+
+/// Winning side given the spot price at close
+pub fn resolution(market: &Market, close_price: Decimal) -> Side {
+    if close_price >= market.open_price {
+        Side::Yes
+    } else {
+        Side::No
+    }
+}
+
+/// Features observed at one instant
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeatureVector {
+    pub move_pct: Option<Decimal>,
+    pub slope: Option<Decimal>,
+    pub book_imbalance: Option<Decimal>,
+    pub spread: Option<Decimal>,
+    pub depth_bid: Option<Decimal>,
+    pub depth_ask: Option<Decimal>,
+    pub seconds_to_close: i64,
+    pub realized_vol: Option<Decimal>,
+    pub carryover: bool,
+}
+
+impl FeatureVector {
+    /// Value of a single feature as a float column value
+    pub fn get(&self, feature: Feature) -> Option<f64> {
+        let d = |v: Option<Decimal>| v.and_then(|v| v.to_f64());
+        match feature {
+            Feature::MovePct => d(self.move_pct),
+            Feature::Slope => d(self.slope),
+            Feature::BookImbalance => d(self.book_imbalance),
+            Feature::Spread => d(self.spread),
+            Feature::DepthBid => d(self.depth_bid),
+            Feature::DepthAsk => d(self.depth_ask),
+            Feature::SecondsToClose => Some(self.seconds_to_close as f64),
+            Feature::RealizedVol => d(self.realized_vol),
+            Feature::Carryover => Some(if self.carryover { 1.0 } else { 0.0 }),
+        }
+    }
+}
+
+/// Rolling spot state needed to compute features
+pub struct FeatureState {
+    config: FeatureConfig,
+    spot: Option<Decimal>,
+    momentum: MomentumDetector,
+    volatility: VolatilityEstimator,
+    last_resolution: Option<Side>,
+}
+
+impl FeatureState {
+    /// Create empty feature state
+    pub fn new(config: FeatureConfig) -> Self {
+        let volatility = VolatilityEstimator::new(config.vol_window);
+        let momentum = MomentumDetector::new(config.slope_window);
+        Self {
+            config,
+            spot: None,
+            momentum,
+            volatility,
+            last_resolution: None,
+        }
+    }
+
+    /// Record a spot price observation
+    pub fn update_spot(&mut self, timestamp: DateTime<Utc>, price: Decimal) {
+        self.spot = Some(price);
+        self.momentum.update(timestamp, price);
+        self.volatility.update(timestamp, price);
+    }
+
+    /// Forget the spot history, e.g. across a gap in the data
+    pub fn clear_spot(&mut self) {
+        self.spot = None;
+        self.momentum.clear();
+        self.volatility.clear();
+    }
+
+    /// Record the winning side of the most recently closed market
+    pub fn record_resolution(&mut self, winner: Side) {
+        self.last_resolution = Some(winner);
+    }
+
+    /// Latest spot price
+    pub fn spot_price(&self) -> Option<Decimal> {
+        self.spot
+    }
+
+    /// The move in the momentum window, if it has gone one way and given
+    /// back no more than `max_retrace` of it
+    pub fn confirmation(&self, max_retrace: Decimal) -> Option<MomentumSignal> {
+        let window = self.momentum.signal(Side::Yes)?;
+        let side = if window.current_price > window.start_price {
+            Side::Yes
+        } else if window.current_price < window.start_price {
+            Side::No
+        } else {
+            return None;
+        };
+        self.momentum
+            .signal(side)
+            .filter(|m| !m.is_reverting(max_retrace))
+    }
+
+    /// Realized volatility estimate
+    pub fn volatility(&self) -> Option<Decimal> {
+        self.volatility.estimate()
+    }
+
+    /// Compute all features for a market/book/side at `now`
+    pub fn compute(
+        &self,
+        market: &Market,
+        book: &OrderBook,
+        side: Side,
+        now: DateTime<Utc>,
+    ) -> FeatureVector {
+        FeatureVector {
+            move_pct: self
+                .spot_price()
+                .and_then(|p| move_pct(market.open_price, p)),
+            slope: self.momentum.signal(side).map(|m| m.velocity),
+            book_imbalance: book_imbalance(book, self.config.imbalance_levels),
+            spread: book.spread(),
+            depth_bid: Some(book.depth_within(BookSide::Bid, self.config.depth_cents)),
+            depth_ask: Some(book.depth_within(BookSide::Ask, self.config.depth_cents)),
+            seconds_to_close: seconds_to_close(market, now),
+            realized_vol: self.volatility(),
+            carryover: self.last_resolution == Some(side),
+        }
+    }
+}
+
+/// One row of the training dataset
+#[derive(Debug, Clone)]
+pub struct FeatureRow {
+    pub timestamp: DateTime<Utc>,
+    pub market_id: String,
+    pub side: Side,
+    pub entry_price: Decimal,
+    pub features: FeatureVector,
+    pub label_win: Option<bool>,
+    pub label_pnl: Option<Decimal>,
+}
+
+/// Replays events and collects labelled feature rows
+pub struct FeatureExtractor {
+    state: FeatureState,
+    cooldown: Duration,
+    max_retrace: Decimal,
+    markets: HashMap<String, Market>,
+    last_row: HashMap<String, DateTime<Utc>>,
+    pending: HashMap<String, Vec<FeatureRow>>,
+    rows: Vec<FeatureRow>,
+}
+
+impl FeatureExtractor {
+    /// Create a new extractor
+    pub fn new(config: FeatureConfig) -> Self {
+        Self {
+            cooldown: config.cooldown,
+            max_retrace: config.max_retrace,
+            state: FeatureState::new(config),
+            markets: HashMap::new(),
+            last_row: HashMap::new(),
+            pending: HashMap::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Process one replayed event
+    pub fn process(&mut self, timestamp: DateTime<Utc>, event: &BacktestEvent) {
+        match event {
+            BacktestEvent::PriceTick(tick) => self.state.update_spot(timestamp, tick.price),
+            BacktestEvent::MarketOpen(market) => {
+                self.markets
+                    .insert(market.yes_token_id.clone(), market.clone());
+            }
+            BacktestEvent::OrderBookUpdate(book) => self.on_book(timestamp, book),
+            BacktestEvent::MarketClose(market) => self.on_close(market),
+            BacktestEvent::DataGap { .. } => self.state.clear_spot(),
+        }
+    }
+
+    /// Finish extraction; rows for markets that never closed are unlabelled
+    pub fn finish(mut self) -> Vec<FeatureRow> {
+        let mut rows = self.rows;
+        rows.extend(self.pending.drain().flat_map(|(_, rows)| rows));
+        rows.sort_by_key(|r| r.timestamp);
+        rows
+    }
+
+    /// Run the extractor over an event sequence
+    pub fn extract<I>(mut self, events: I) -> Vec<FeatureRow>
+    where
+        I: IntoIterator<Item = (DateTime<Utc>, BacktestEvent)>,
+    {
+        for (timestamp, event) in events {
+            self.process(timestamp, &event);
+        }
+        self.finish()
+    }
+
+    fn on_book(&mut self, timestamp: DateTime<Utc>, book: &OrderBook) {
+        let Some(market) = self.markets.get(&book.token_id) else {
+            return;
+        };
+        if self
+            .last_row
+            .get(&market.condition_id)
+            .is_some_and(|last| timestamp - *last < self.cooldown)
+        {
+            return;
+        }
+        if timestamp >= market.close_time {
+            return;
+        }
+        let Some(momentum) = self.state.confirmation(self.max_retrace) else {
+            return;
+        };
+        // Entered at the ask; NO is bought against the YES bid
+        let entry_price = match momentum.side {
+            Side::Yes => book.best_ask(),
+            Side::No => book.best_bid().map(|bid| Decimal::ONE - bid),
+        };
+        let Some(entry_price) = entry_price.filter(|_| book.top_of_book_fault().is_none()) else {
+            return;
+        };
+
+        let row = FeatureRow {
+            timestamp,
+            market_id: market.condition_id.clone(),
+            side: momentum.side,
+            entry_price,
+            features: self.state.compute(market, book, momentum.side, timestamp),
+            label_win: None,
+            label_pnl: None,
+        };
+        self.last_row.insert(market.condition_id.clone(), timestamp);
+        self.pending
+            .entry(market.condition_id.clone())
+            .or_default()
+            .push(row);
+    }
+
+    fn on_close(&mut self, market: &Market) {
+        self.markets.remove(&market.yes_token_id);
+        self.last_row.remove(&market.condition_id);
+        let Some(close_price) = self.state.spot_price() else {
+            return;
+        };
+        let winner = resolution(market, close_price);
+        self.state.record_resolution(winner);
+
+        for mut row in self
+            .pending
+            .remove(&market.condition_id)
+            .unwrap_or_default()
+        {
+            let won = row.side == winner;
+            let payout = if won { Decimal::ONE } else { Decimal::ZERO };
+            row.label_win = Some(won);
+            row.label_pnl = Some(payout - row.entry_price);
+            self.rows.push(row);
+        }
+    }
+}
+
+/// Arrow schema for a feature dataset with the given feature columns
+pub fn feature_schema(features: &[Feature]) -> Schema {
+    let mut fields = vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("schema_version", DataType::UInt32, false),
+        Field::new("market_id", DataType::Utf8, false),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("entry_price", DataType::Float64, false),
+    ];
+    for feature in features {
+        fields.push(Field::new(feature.name(), DataType::Float64, true));
+    }
+    fields.push(Field::new("label_win", DataType::Boolean, true));
+    fields.push(Field::new("label_pnl", DataType::Float64, true));
+    Schema::new(fields)
+}
+
+/// Write feature rows to a Parquet file
+pub fn write_feature_rows(
+    path: &Path,
+    rows: &[FeatureRow],
+    features: &[Feature],
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let schema = Arc::new(feature_schema(features));
+    let props = writer_properties(fingerprint::active());
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(props))?;
+
+    let timestamps: Vec<i64> = rows
+        .iter()
+        .map(|r| r.timestamp.timestamp_micros())
+        .collect();
+    let sides: Vec<&str> = rows
+        .iter()
+        .map(|r| match r.side {
+            Side::Yes => "yes",
+            Side::No => "no",
+        })
+        .collect();
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(TimestampMicrosecondArray::from(timestamps).with_timezone("UTC")),
+        Arc::new(UInt32Array::from(vec![FEATURE_SCHEMA_VERSION; rows.len()])),
+        Arc::new(StringArray::from(
+            rows.iter()
+                .map(|r| r.market_id.as_str())
+                .collect::<Vec<_>>(),
+        )),
+        Arc::new(StringArray::from(sides)),
+        Arc::new(Float64Array::from(
+            rows.iter()
+                .map(|r| r.entry_price.to_f64().unwrap_or(0.0))
+                .collect::<Vec<_>>(),
+        )),
+    ];
+    for feature in features {
+        columns.push(Arc::new(Float64Array::from(
+            rows.iter()
+                .map(|r| r.features.get(*feature))
+                .collect::<Vec<_>>(),
+        )));
+    }
+    columns.push(Arc::new(BooleanArray::from(
+        rows.iter().map(|r| r.label_win).collect::<Vec<_>>(),
+    )));
+    columns.push(Arc::new(Float64Array::from(
+        rows.iter()
+            .map(|r| r.label_pnl.and_then(|p| p.to_f64()))
+            .collect::<Vec<_>>(),
+    )));
+
+    let batch = RecordBatch::try_new(schema, columns)?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    tracing::debug!(path = ?path, count = rows.len(), "Wrote feature rows to Parquet");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::{PriceTick, TickSource};
+    use crate::orderbook::PriceLevel;
+    use arrow::array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use tempfile::TempDir;
+
+    fn ts(secs: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-01-04T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + Duration::seconds(secs)
+    }
+
+    fn market(id: &str, open: i64) -> Market {
+        Market {
+            condition_id: id.to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: format!("{}-yes", id),
+            no_token_id: format!("{}-no", id),
+            open_price: dec!(100000),
+            open_time: ts(open),
+            close_time: ts(open + 900),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    fn level(price: Decimal, size: Decimal) -> PriceLevel {
+        PriceLevel { price, size }
+    }
+
+    fn book(token: &str, at: i64) -> OrderBook {
+        OrderBook {
+            token_id: token.to_string(),
+            bids: vec![
+                level(dec!(0.49), dec!(300)),
+                level(dec!(0.48), dec!(100)),
+                level(dec!(0.40), dec!(1000)),
+            ],
+            asks: vec![level(dec!(0.51), dec!(100)), level(dec!(0.60), dec!(500))],
+            updated_at: ts(at),
+        }
+    }
+
+    fn tick(at: i64, price: Decimal) -> (DateTime<Utc>, BacktestEvent) {
+        (
+            ts(at),
+            BacktestEvent::PriceTick(PriceTick {
+                symbol: "BTCUSDT".to_string(),
+                asset: "BTC".to_string(),
+                price,
+                timestamp: ts(at),
+                exchange_ts: ts(at),
+                source: TickSource::Trade,
+            }),
+        )
+    }
+
+    /// Two consecutive markets: spot rallies in the first, resolves up, and
+    /// the second opens with the same setup (so carryover is set).
+    fn fixture() -> Vec<(DateTime<Utc>, BacktestEvent)> {
+        let first = market("m1", 0);
+        let second = market("m2", 900);
+        let mut events = vec![(ts(0), BacktestEvent::MarketOpen(first.clone()))];
+        events.push(tick(0, dec!(100000)));
+        events.push(tick(300, dec!(100100)));
+        events.push(tick(310, dec!(100200)));
+        events.push(tick(320, dec!(100300)));
+        events.push((ts(320), BacktestEvent::OrderBookUpdate(book("m1-yes", 320))));
+        // Within the cooldown, no second row
+        events.push((ts(330), BacktestEvent::OrderBookUpdate(book("m1-yes", 330))));
+        // Unknown token is ignored
+        events.push((ts(331), BacktestEvent::OrderBookUpdate(book("other", 331))));
+        events.push(tick(900, dec!(100300)));
+        events.push((ts(900), BacktestEvent::MarketClose(first)));
+
+        events.push((ts(900), BacktestEvent::MarketOpen(second.clone())));
+        events.push(tick(1190, dec!(100300)));
+        events.push(tick(1200, dec!(100400)));
+        events.push((
+            ts(1200),
+            BacktestEvent::OrderBookUpdate(book("m2-yes", 1200)),
+        ));
+        events
+    }
+
+    #[test]
+    fn test_feature_functions() {
+        assert_eq!(move_pct(dec!(100000), dec!(100300)), Some(dec!(0.3)));
+        assert_eq!(move_pct(Decimal::ZERO, dec!(1)), None);
+
+        let b = book("t", 0);
+        assert_eq!(book_imbalance(&b, 2), Some(dec!(-0.2)));
+
+        let m = market("m", 0);
+        assert_eq!(seconds_to_close(&m, ts(600)), 300);
+        assert_eq!(resolution(&m, dec!(100000)), Side::Yes);
+        assert_eq!(resolution(&m, dec!(99999)), Side::No);
+    }
+
+    #[test]
+    fn test_golden_extraction() {
+        let extractor = FeatureExtractor::new(FeatureConfig::default());
+        let rows = extractor.extract(fixture());
+
+        assert_eq!(rows.len(), 2);
+
+        let first = &rows[0];
+        assert_eq!(first.market_id, "m1");
+        assert_eq!(first.timestamp, ts(320));
+        assert_eq!(first.side, Side::Yes);
+        assert_eq!(first.entry_price, dec!(0.51));
+        assert_eq!(first.features.move_pct, Some(dec!(0.3)));
+        assert_eq!(first.features.slope, Some(dec!(10)));
+        assert_eq!(first.features.book_imbalance, Some(dec!(0.4)));
+        assert_eq!(first.features.spread, Some(dec!(0.02)));
+        assert_eq!(first.features.depth_bid, Some(dec!(400)));
+        assert_eq!(first.features.depth_ask, Some(dec!(100)));
+        assert_eq!(first.features.seconds_to_close, 580);
+        assert!(first.features.realized_vol.is_some());
+        assert!(!first.features.carryover);
+        assert_eq!(first.label_win, Some(true));
+        assert_eq!(first.label_pnl, Some(dec!(0.49)));
+
+        let second = &rows[1];
+        assert_eq!(second.market_id, "m2");
+        assert!(second.features.carryover);
+        assert_eq!(second.label_win, None);
+        assert_eq!(second.label_pnl, None);
+    }
+
+    #[test]
+    fn test_rows_follow_momentum_confirmations() {
+        let m = market("m1", 0);
+        let rows = |ticks: &[(i64, Decimal)]| {
+            let mut events = vec![(ts(0), BacktestEvent::MarketOpen(m.clone()))];
+            events.extend(ticks.iter().map(|(at, price)| tick(*at, *price)));
+            events.push((ts(330), BacktestEvent::OrderBookUpdate(book("m1-yes", 330))));
+            FeatureExtractor::new(FeatureConfig::default()).extract(events)
+        };
+
+        // A fall confirms NO, bought against the YES bid
+        let falling = rows(&[(300, dec!(100000)), (320, dec!(99800))]);
+        assert_eq!(falling.len(), 1);
+        assert_eq!(falling[0].side, Side::No);
+        assert_eq!(falling[0].entry_price, dec!(0.51));
+        assert_eq!(falling[0].features.slope, Some(dec!(-10)));
+
+        // Half the rise given back, flat, or a single price: nothing confirms
+        let reverting = [
+            (300, dec!(100000)),
+            (310, dec!(100200)),
+            (320, dec!(100100)),
+        ];
+        assert!(rows(&reverting).is_empty());
+        assert!(rows(&[(300, dec!(100000)), (320, dec!(100000))]).is_empty());
+        assert!(rows(&[(320, dec!(100200))]).is_empty());
+    }
+
+    #[test]
+    fn test_feature_names_round_trip() {
+        for feature in Feature::ALL {
+            assert_eq!(Feature::from_name(feature.name()), Some(feature));
+        }
+        assert_eq!(Feature::from_name("bogus"), None);
+    }
+
+    #[test]
+    fn test_write_feature_parquet() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("features.parquet");
+        let features = vec![Feature::MovePct, Feature::Carryover];
+        let rows = FeatureExtractor::new(FeatureConfig::default()).extract(fixture());
+
+        write_feature_rows(&path, &rows, &features).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.into_iter().next().unwrap().unwrap();
+        let schema = batch.schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "timestamp",
+                "schema_version",
+                "market_id",
+                "side",
+                "entry_price",
+                "move_pct",
+                "carryover",
+                "label_win",
+                "label_pnl"
+            ]
+        );
+        assert_eq!(batch.num_rows(), 2);
+
+        let version = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<UInt32Array>()
+            .unwrap();
+        assert_eq!(version.value(0), FEATURE_SCHEMA_VERSION);
+        let move_pct = batch
+            .column(5)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert!((move_pct.value(0) - 0.3).abs() < 1e-9);
+        let label = batch
+            .column(7)
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        assert!(label.value(0));
+        assert!(label.is_null(1));
+    }
+}
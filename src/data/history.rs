@@ -0,0 +1,186 @@
+//! Archive of closed positions and fills evicted from memory
+//!
+//! A long session keeps only its most recent closed positions and fills in
+//! memory. Once a history reaches twice its limit the older half is written
+//! to `<data dir>/history/<session>/` as Parquet and dropped, so memory
+//! stays bounded and every record can still be read back for reports. The
+//! directory is outside capture retention, which never deletes from it.
+
+use super::ParquetWriter;
+use crate::execution::{read_trades, write_trades, Fill};
+use crate::risk::ClosedPosition;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Subdirectory of the data directory holding archived history
+pub const HISTORY_DIR: &str = "history";
+
+/// Default closed positions kept in memory
+pub const DEFAULT_MAX_CLOSED_POSITIONS: usize = 1000;
+
+/// Default paper fills kept in memory
+pub const DEFAULT_MAX_FILLS: usize = 1000;
+
+const CLOSED_POSITIONS_PREFIX: &str = "closed_positions";
+const FILLS_PREFIX: &str = "fills";
+
+/// How much history stays in memory; 0 keeps everything
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryConfig {
+    /// Closed positions kept in memory
+    #[serde(default = "default_max_closed_positions")]
+    pub max_closed_positions: usize,
+    /// Paper fills kept in memory
+    #[serde(default = "default_max_fills")]
+    pub max_fills: usize,
+}
+
+fn default_max_closed_positions() -> usize {
+    DEFAULT_MAX_CLOSED_POSITIONS
+}
+
+fn default_max_fills() -> usize {
+    DEFAULT_MAX_FILLS
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_closed_positions: DEFAULT_MAX_CLOSED_POSITIONS,
+            max_fills: DEFAULT_MAX_FILLS,
+        }
+    }
+}
+
+/// Remove and return the oldest records once `records` reaches twice `keep`
+///
+/// Evicting down to `keep` rather than one record at a time keeps archive
+/// files large. `keep` of 0 never evicts.
+pub fn evict_oldest<T>(records: &mut Vec<T>, keep: usize) -> Option<Vec<T>> {
+    if keep == 0 || records.len() < keep * 2 {
+        return None;
+    }
+    let excess = records.len() - keep;
+    Some(records.drain(..excess).collect())
+}
+
+/// Parquet archive of history under a data directory
+#[derive(Clone)]
+pub struct HistoryArchive {
+    dir: PathBuf,
+    writer: ParquetWriter,
+}
+
+impl HistoryArchive {
+    /// Archive in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        Self {
+            writer: ParquetWriter::new(dir.clone(), 0),
+            dir,
+        }
+    }
+
+    /// Archive of the session started at `started`, under `data_dir`
+    ///
+    /// Each session gets its own directory so its reads never pick up an
+    /// earlier session's records.
+    pub fn for_session(data_dir: &Path, started: DateTime<Utc>) -> Self {
+        Self::new(
+            data_dir
+                .join(HISTORY_DIR)
+                .join(started.format("%Y%m%d_%H%M%S").to_string()),
+        )
+    }
+
+    /// Directory holding the archive files
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write closed positions to a new archive file
+    pub fn archive_closed(&self, closed: &[ClosedPosition]) -> anyhow::Result<PathBuf> {
+        let first = closed.first().map(|c| c.exit_time).unwrap_or_else(Utc::now);
+        let path = self.path(CLOSED_POSITIONS_PREFIX, first);
+        self.writer.write_closed_positions(&path, closed)?;
+        Ok(path)
+    }
+
+    /// Every archived closed position, oldest exit first
+    pub fn read_closed(&self) -> anyhow::Result<Vec<ClosedPosition>> {
+        let mut closed = vec![];
+        for path in self.files(CLOSED_POSITIONS_PREFIX)? {
+            for batch in super::read_batches(&path)? {
+                closed.extend(super::closed_positions_from_batch(&batch)?);
+            }
+        }
+        closed.sort_by_key(|c| c.exit_time);
+        Ok(closed)
+    }
+
+    /// Write fills to a new archive file
+    pub fn archive_fills(&self, fills: &[Fill]) -> anyhow::Result<PathBuf> {
+        let first = fills.first().map(|f| f.timestamp).unwrap_or_else(Utc::now);
+        let path = self.path(FILLS_PREFIX, first);
+        write_trades(&path, fills)?;
+        Ok(path)
+    }
+
+    /// Every archived fill, oldest first
+    pub fn read_fills(&self) -> anyhow::Result<Vec<Fill>> {
+        let mut fills = vec![];
+        for path in self.files(FILLS_PREFIX)? {
+            fills.extend(read_trades(&path)?);
+        }
+        fills.sort_by_key(|f| f.timestamp);
+        Ok(fills)
+    }
+
+    /// A fresh file named by its first record's time
+    fn path(&self, prefix: &str, first: DateTime<Utc>) -> PathBuf {
+        let suffix = uuid::Uuid::new_v4().simple().to_string();
+        self.dir.join(format!(
+            "{}_{}_{}.parquet",
+            prefix,
+            first.format("%Y%m%d_%H%M%S"),
+            &suffix[..8]
+        ))
+    }
+
+    fn files(&self, prefix: &str) -> anyhow::Result<Vec<PathBuf>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut files = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            if name.starts_with(&format!("{}_", prefix)) && name.ends_with(".parquet") {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eviction_halves_at_twice_the_limit() {
+        let mut records: Vec<u32> = (0..7).collect();
+        assert_eq!(evict_oldest(&mut records, 4), None);
+        records.push(7);
+        assert_eq!(evict_oldest(&mut records, 4), Some(vec![0, 1, 2, 3]));
+        assert_eq!(records, vec![4, 5, 6, 7]);
+        assert_eq!(evict_oldest(&mut records, 0), None);
+    }
+}
@@ -0,0 +1,409 @@
+//! Data directory instance lock
+//!
+//! Every process that writes captured data holds an advisory lock on
+//! `<dir>/.poly-hft.lock` (flock on Unix, LockFileEx on Windows) for as long
+//! as it runs, and records who it is in the file. A second writer either
+//! refuses to start, naming the holder, or moves into its own subdirectory
+//! under `instances/`. The OS drops the lock when a process dies, so a lock
+//! file that can be locked again is stale; reclaiming one is journaled.
+
+use crate::fingerprint;
+use crate::journal::Journal;
+use crate::telemetry::EventCode;
+use chrono::{DateTime, Utc};
+use fs4::fs_std::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Lock file name inside the data directory
+pub const LOCK_FILE: &str = ".poly-hft.lock";
+
+/// Journal recording lock reclamation, inside the data directory
+pub const INSTANCE_JOURNAL_FILE: &str = "instance_journal.jsonl";
+
+/// Parent of per-instance subdirectories when a data directory is shared
+pub const INSTANCES_DIR: &str = "instances";
+
+/// Journal entry kind for a reclaimed stale lock
+const STALE_LOCK_KIND: &str = "stale_lock_reclaimed";
+
+/// Process recorded in a lock file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockHolder {
+    /// Process id
+    pub pid: u32,
+    /// When the process took the lock
+    pub started_at: DateTime<Utc>,
+    /// Command that took it, e.g. `capture`
+    pub mode: String,
+    /// Config fingerprint hash, if one was installed
+    pub config_hash: Option<String>,
+}
+
+impl LockHolder {
+    /// The current process in `mode`
+    pub fn current(mode: &str) -> Self {
+        Self {
+            pid: std::process::id(),
+            started_at: Utc::now(),
+            mode: mode.to_string(),
+            config_hash: fingerprint::active().map(|fp| fp.hash.clone()),
+        }
+    }
+
+    /// Whether the recorded process still exists
+    pub fn is_alive(&self) -> bool {
+        pid_alive(self.pid)
+    }
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pid {} ({}, since {}",
+            self.pid,
+            self.mode,
+            self.started_at.to_rfc3339()
+        )?;
+        if let Some(hash) = &self.config_hash {
+            write!(f, ", config {}", &hash[..12.min(hash.len())])?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Data directory lock errors
+#[derive(Debug, Error)]
+pub enum LockError {
+    /// Another process holds the lock
+    #[error("Data directory {dir:?} is in use by {holder}; stop it or pass --share-data-dir")]
+    Held { dir: PathBuf, holder: LockHolder },
+    /// The lock is held but its holder could not be read
+    #[error("Data directory {0:?} is locked by another process")]
+    HeldUnknown(PathBuf),
+    /// Lock file could not be created or written
+    #[error("Could not lock data directory: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Who, if anyone, holds a data directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockStatus {
+    /// No lock file
+    Unlocked,
+    /// Locked by a running process
+    Held(LockHolder),
+    /// Lock file left behind by a process that no longer holds it
+    Stale(LockHolder),
+}
+
+impl fmt::Display for LockStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockStatus::Unlocked => write!(f, "not locked"),
+            LockStatus::Held(holder) => write!(f, "locked by {}", holder),
+            LockStatus::Stale(holder) if holder.is_alive() => {
+                write!(f, "stale lock from {}", holder)
+            }
+            LockStatus::Stale(holder) => {
+                write!(f, "stale lock from {}, process gone", holder)
+            }
+        }
+    }
+}
+
+/// Exclusive hold on a data directory, released on drop
+#[derive(Debug)]
+pub struct DataDirLock {
+    dir: PathBuf,
+    file: File,
+    holder: LockHolder,
+    reclaimed: Option<LockHolder>,
+}
+
+impl DataDirLock {
+    /// Lock `dir` for this process, or fail naming the current holder
+    pub fn acquire(dir: &Path, mode: &str) -> Result<Self, LockError> {
+        fs::create_dir_all(dir)?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(LOCK_FILE))?;
+
+        if !file.try_lock_exclusive()? {
+            return Err(match read_holder(&mut file) {
+                Some(holder) => LockError::Held {
+                    dir: dir.to_path_buf(),
+                    holder,
+                },
+                None => LockError::HeldUnknown(dir.to_path_buf()),
+            });
+        }
+
+        // Whoever wrote the file before us no longer holds the lock
+        let reclaimed = read_holder(&mut file);
+        if let Some(stale) = &reclaimed {
+            record_reclaim(dir, stale);
+        }
+
+        let holder = LockHolder::current(mode);
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(
+            serde_json::to_string(&holder)
+                .map_err(std::io::Error::other)?
+                .as_bytes(),
+        )?;
+        file.sync_all()?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            holder,
+            reclaimed,
+        })
+    }
+
+    /// Lock `dir`, or with `share` fall back to a subdirectory of it
+    /// reserved for this process when another process holds `dir`
+    pub fn acquire_or_share(dir: &Path, mode: &str, share: bool) -> Result<Self, LockError> {
+        match Self::acquire(dir, mode) {
+            Err(LockError::Held { holder, .. }) if share => {
+                let instance = instance_dir(dir, mode);
+                tracing::info!(
+                    dir = ?dir,
+                    %holder,
+                    instance = ?instance,
+                    "Data directory in use, writing to instance subdirectory"
+                );
+                Self::acquire(&instance, mode)
+            }
+            Err(LockError::HeldUnknown(_)) if share => {
+                Self::acquire(&instance_dir(dir, mode), mode)
+            }
+            result => result,
+        }
+    }
+
+    /// Who holds `dir`, without taking the lock
+    pub fn status(dir: &Path) -> anyhow::Result<LockStatus> {
+        let path = dir.join(LOCK_FILE);
+        if !path.exists() {
+            return Ok(LockStatus::Unlocked);
+        }
+        let mut file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let Some(holder) = read_holder(&mut file) else {
+            return Ok(LockStatus::Unlocked);
+        };
+        if FileExt::try_lock_shared(&file)? {
+            FileExt::unlock(&file)?;
+            Ok(LockStatus::Stale(holder))
+        } else {
+            Ok(LockStatus::Held(holder))
+        }
+    }
+
+    /// Status of every per-instance subdirectory of a shared `dir`
+    pub fn instances(dir: &Path) -> anyhow::Result<Vec<(PathBuf, LockStatus)>> {
+        let parent = dir.join(INSTANCES_DIR);
+        if !parent.exists() {
+            return Ok(Vec::new());
+        }
+        let mut instances = Vec::new();
+        for entry in fs::read_dir(parent)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                let status = Self::status(&path)?;
+                instances.push((path, status));
+            }
+        }
+        instances.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(instances)
+    }
+
+    /// Directory this process owns; a subdirectory when sharing
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// This process as recorded in the lock file
+    pub fn holder(&self) -> &LockHolder {
+        &self.holder
+    }
+
+    /// Holder of the stale lock this one replaced
+    pub fn reclaimed(&self) -> Option<&LockHolder> {
+        self.reclaimed.as_ref()
+    }
+}
+
+impl Drop for DataDirLock {
+    fn drop(&mut self) {
+        // A clean exit leaves an empty file, so only a crash leaves a
+        // holder behind to reclaim
+        let _ = self.file.set_len(0);
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Subdirectory reserved for this process under a shared `dir`
+pub fn instance_dir(dir: &Path, mode: &str) -> PathBuf {
+    dir.join(INSTANCES_DIR)
+        .join(format!("{}-{}", mode, std::process::id()))
+}
+
+fn read_holder(file: &mut File) -> Option<LockHolder> {
+    let mut text = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut text).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn record_reclaim(dir: &Path, stale: &LockHolder) {
+    let alive = stale.is_alive();
+    tracing::warn!(
+        event_code = %EventCode::StaleLockReclaimed,
+        dir = ?dir,
+        holder = %stale,
+        alive,
+        "Reclaimed stale data directory lock"
+    );
+    let note = serde_json::json!({ "holder": stale, "process_alive": alive });
+    if let Err(e) = Journal::open(dir.join(INSTANCE_JOURNAL_FILE))
+        .and_then(|journal| journal.append(STALE_LOCK_KIND, &note))
+    {
+        tracing::warn!(error = %e, "Failed to journal stale lock");
+    }
+}
+
+#[cfg(unix)]
+fn pid_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks the process exists; EPERM means it does but
+    // belongs to someone else
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn pid_alive(_pid: u32) -> bool {
+    // No cheap check; assume the worst
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::Journal;
+    use tempfile::TempDir;
+
+    /// A pid no live process has
+    const DEAD_PID: u32 = i32::MAX as u32;
+
+    fn stale_holder() -> LockHolder {
+        LockHolder {
+            pid: DEAD_PID,
+            started_at: Utc::now(),
+            mode: "capture".to_string(),
+            config_hash: Some("abc123".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_second_writer_is_refused_with_holder() {
+        let dir = TempDir::new().unwrap();
+        let first = DataDirLock::acquire(dir.path(), "capture").unwrap();
+        assert_eq!(first.holder().pid, std::process::id());
+        assert_eq!(
+            DataDirLock::status(dir.path()).unwrap(),
+            LockStatus::Held(first.holder().clone())
+        );
+
+        let err = DataDirLock::acquire(dir.path(), "run").unwrap_err();
+        match &err {
+            LockError::Held { holder, .. } => assert_eq!(holder.mode, "capture"),
+            other => panic!("unexpected error: {}", other),
+        }
+        assert!(err.to_string().contains("--share-data-dir"));
+
+        // Released on drop, and a clean release is not stale
+        drop(first);
+        assert_eq!(
+            DataDirLock::status(dir.path()).unwrap(),
+            LockStatus::Unlocked
+        );
+        let second = DataDirLock::acquire(dir.path(), "run").unwrap();
+        assert_eq!(second.holder().mode, "run");
+        assert!(second.reclaimed().is_none());
+    }
+
+    #[test]
+    fn test_stale_lock_is_reclaimed_and_journaled() {
+        let dir = TempDir::new().unwrap();
+        let stale = stale_holder();
+        fs::write(
+            dir.path().join(LOCK_FILE),
+            serde_json::to_string(&stale).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            DataDirLock::status(dir.path()).unwrap(),
+            LockStatus::Stale(stale.clone())
+        );
+        assert!(!stale.is_alive());
+
+        let lock = DataDirLock::acquire(dir.path(), "run").unwrap();
+        assert_eq!(lock.reclaimed().map(|h| h.pid), Some(DEAD_PID));
+
+        let entries = Journal::read_all(dir.path().join(INSTANCE_JOURNAL_FILE)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, STALE_LOCK_KIND);
+        assert_eq!(entries[0].data["holder"]["pid"], DEAD_PID);
+        assert_eq!(entries[0].data["process_alive"], false);
+    }
+
+    #[test]
+    fn test_shared_data_dir_moves_to_instance_subdirectory() {
+        let dir = TempDir::new().unwrap();
+        let first = DataDirLock::acquire_or_share(dir.path(), "capture", true).unwrap();
+        assert_eq!(first.dir(), dir.path());
+
+        // Without sharing the second writer is still refused
+        assert!(DataDirLock::acquire_or_share(dir.path(), "run", false).is_err());
+
+        let second = DataDirLock::acquire_or_share(dir.path(), "run", true).unwrap();
+        assert_eq!(second.dir(), instance_dir(dir.path(), "run"));
+        assert!(second.dir().starts_with(dir.path().join(INSTANCES_DIR)));
+        assert!(matches!(
+            DataDirLock::status(second.dir()).unwrap(),
+            LockStatus::Held(h) if h.mode == "run"
+        ));
+        assert!(matches!(
+            DataDirLock::status(dir.path()).unwrap(),
+            LockStatus::Held(h) if h.mode == "capture"
+        ));
+
+        let instances = DataDirLock::instances(dir.path()).unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].0, second.dir());
+    }
+
+    #[test]
+    fn test_unlocked_directory_status() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            DataDirLock::status(dir.path()).unwrap(),
+            LockStatus::Unlocked
+        );
+        assert!(LockHolder::current("capture").is_alive());
+    }
+}
@@ -0,0 +1,373 @@
+//! Sealed capture files and consistent snapshots of a data directory
+//!
+//! A capture, a backtest and a live session may share one data directory.
+//! Every capture file is written under a `.tmp` name; before it is renamed
+//! into place its size and SHA-256 are appended to the directory's
+//! [`CAPTURE_MANIFEST_FILE`], sealing it. A [`CaptureSnapshot`] lists the
+//! directory first and reads the manifest second, so every file it sees
+//! already has its seal: the snapshot holds exactly the sealed files
+//! present when it was taken, with the checksums a later run can be
+//! checked against.
+//!
+//! Files still being written at snapshot time are left out, or waited for
+//! with [`IncludeCurrent::Wait`]. Files written before manifests existed
+//! are taken whole, as they were renamed into place too, and checksummed
+//! when the snapshot is taken.
+
+use super::retention::{file_prefix, scan_data_files, COMPACTED_DIR};
+use super::sink::{DataFormat, PARTIAL_SUFFIX};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Seals of the capture files in a directory, one JSON line each
+pub const CAPTURE_MANIFEST_FILE: &str = "capture_manifest.jsonl";
+
+/// Default longest wait for files being written to seal, in seconds
+pub const DEFAULT_SEAL_WAIT_SECS: u64 = 30;
+
+/// Interval between looks at files being written
+const SEAL_POLL: Duration = Duration::from_millis(100);
+
+/// What a snapshot does with capture files still being written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum IncludeCurrent {
+    /// Leave them out
+    #[default]
+    Skip,
+    /// Wait for them to seal, then take them in
+    Wait,
+}
+
+/// A manifest line: one capture file sealed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// File name, relative to the manifest's directory
+    pub file: String,
+    /// Size in bytes
+    pub bytes: u64,
+    /// SHA-256 of the contents, hex
+    pub sha256: String,
+    /// When the file was sealed
+    pub sealed_at: DateTime<Utc>,
+}
+
+/// Seal the partial file `tmp`, to be renamed to `path`, in the manifest of
+/// `path`'s directory
+pub(super) fn seal(tmp: &Path, path: &Path) -> anyhow::Result<ManifestEntry> {
+    let file = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow::anyhow!("capture path {} has no file name", path.display()))?;
+    let entry = ManifestEntry {
+        file: file.to_string(),
+        bytes: fs::metadata(tmp)?.len(),
+        sha256: sha256(tmp)?,
+        sealed_at: Utc::now(),
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut line = serde_json::to_vec(&entry)?;
+    line.push(b'\n');
+    // One append of the whole line, so readers never see half of one
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(CAPTURE_MANIFEST_FILE))?
+        .write_all(&line)?;
+    Ok(entry)
+}
+
+/// SHA-256 of the file at `path`, hex
+pub fn sha256(path: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Seals in the manifest of `dir`, by file name; a later seal of one name
+/// replaces an earlier one, and a last line without its newline is still
+/// being appended
+pub fn read_manifest(dir: &Path) -> anyhow::Result<HashMap<String, ManifestEntry>> {
+    let text = match fs::read_to_string(dir.join(CAPTURE_MANIFEST_FILE)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let complete = text.rfind('\n').map_or("", |end| &text[..end]);
+    let mut entries = HashMap::new();
+    for line in complete.lines().filter(|l| !l.trim().is_empty()) {
+        let entry: ManifestEntry = serde_json::from_str(line).map_err(|e| {
+            anyhow::anyhow!("unreadable capture manifest in {}: {}", dir.display(), e)
+        })?;
+        entries.insert(entry.file.clone(), entry);
+    }
+    Ok(entries)
+}
+
+/// A capture file taken into a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotFile {
+    pub path: PathBuf,
+    /// File prefix, e.g. `price_ticks`
+    pub prefix: String,
+    /// Size in bytes
+    pub bytes: u64,
+    /// SHA-256 of the contents, hex
+    pub sha256: String,
+    /// When it was sealed; none for a file written before manifests,
+    /// checksummed when the snapshot was taken
+    pub sealed_at: Option<DateTime<Utc>>,
+}
+
+/// The sealed capture files of a directory at one moment
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CaptureSnapshot {
+    /// Directory snapshotted
+    pub dir: PathBuf,
+    /// When the snapshot was taken
+    pub taken_at: Option<DateTime<Utc>>,
+    /// Files taken in, by path
+    pub files: Vec<SnapshotFile>,
+    /// Files still being written, left out
+    pub in_progress: Vec<PathBuf>,
+}
+
+impl CaptureSnapshot {
+    /// Snapshot `dir` and its `compacted/` directory, waiting up to `wait`
+    /// for files being written under [`IncludeCurrent::Wait`]
+    ///
+    /// The wait sleeps the calling thread; async callers load through
+    /// [`crate::backtest::EventStream::load_blocking`].
+    pub fn take(
+        dir: &Path,
+        include_current: IncludeCurrent,
+        wait: Duration,
+    ) -> anyhow::Result<Self> {
+        let deadline = Instant::now() + wait;
+        loop {
+            let snapshot = Self::scan(dir)?;
+            let waiting = include_current == IncludeCurrent::Wait
+                && !snapshot.in_progress.is_empty()
+                && Instant::now() < deadline;
+            if !waiting {
+                return Ok(snapshot);
+            }
+            std::thread::sleep(SEAL_POLL);
+        }
+    }
+
+    /// Sealed and in-progress files of `dir` now
+    fn scan(dir: &Path) -> anyhow::Result<Self> {
+        let taken_at = Utc::now();
+        // Partial files first, so one renamed while the rest is listed is
+        // either taken in or still counted as in progress
+        let mut in_progress = vec![];
+        for partial_dir in [dir.to_path_buf(), dir.join(COMPACTED_DIR)] {
+            if partial_dir.is_dir() {
+                in_progress.extend(partial_files(&partial_dir)?);
+            }
+        }
+        // Listed before the manifests are read: a file renamed into place
+        // was sealed before, so its seal is read below
+        let mut listed = scan_data_files(dir, true)?;
+        listed.sort_by(|a, b| a.path.cmp(&b.path));
+        in_progress.retain(|partial| {
+            let path = partial.with_extension("");
+            !listed.iter().any(|file| file.path == path)
+        });
+        in_progress.sort();
+        let mut manifests: HashMap<PathBuf, HashMap<String, ManifestEntry>> = HashMap::new();
+        for file in &listed {
+            let parent = file.path.parent().unwrap_or(dir).to_path_buf();
+            if !manifests.contains_key(&parent) {
+                manifests.insert(parent.clone(), read_manifest(&parent)?);
+            }
+        }
+        let mut files = vec![];
+        for file in listed {
+            let parent = file.path.parent().unwrap_or(dir);
+            let name = file
+                .path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            let snapshotted = match manifests.get(parent).and_then(|m| m.get(name)) {
+                Some(entry) => SnapshotFile {
+                    path: file.path,
+                    prefix: file.prefix,
+                    bytes: entry.bytes,
+                    sha256: entry.sha256.clone(),
+                    sealed_at: Some(entry.sealed_at),
+                },
+                None => SnapshotFile {
+                    sha256: sha256(&file.path)?,
+                    path: file.path,
+                    prefix: file.prefix,
+                    bytes: file.bytes,
+                    sealed_at: None,
+                },
+            };
+            files.push(snapshotted);
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            taken_at: Some(taken_at),
+            files,
+            in_progress,
+        })
+    }
+
+    /// Files taken in that were written before manifests existed
+    pub fn unsealed(&self) -> usize {
+        self.files.iter().filter(|f| f.sealed_at.is_none()).count()
+    }
+}
+
+/// Capture files of `dir` still being written
+fn partial_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut partial = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_partial = path.extension().is_some_and(|e| e == PARTIAL_SUFFIX)
+            && DataFormat::from_path(&path.with_extension("")).is_some()
+            && file_prefix(&path.with_extension("")).is_some();
+        if is_partial {
+            partial.push(path);
+        }
+    }
+    Ok(partial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::CaptureLoader;
+    use crate::data::{price_tick_batch, sink_for, ParquetTuning, PriceTickRecord};
+    use rust_decimal::Decimal;
+    use std::collections::HashSet;
+    use tempfile::TempDir;
+
+    fn ticks(minute: i64) -> Vec<PriceTickRecord> {
+        (0..50)
+            .map(|i| {
+                let at = DateTime::from_timestamp(1_735_689_600 + minute * 60 + i, 0).unwrap();
+                PriceTickRecord::new(at, "BTCUSDT".into(), Decimal::from(100_000 + i), at)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_snapshots_taken_while_recording_hold_only_sealed_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_path_buf();
+
+        // Written as the recorder writes a flush: one file per batch
+        let writer = {
+            let dir = dir.clone();
+            std::thread::spawn(move || {
+                for minute in 0..40 {
+                    let batch = price_tick_batch(&ticks(minute)).unwrap();
+                    let mut sink =
+                        sink_for(DataFormat::Parquet, dir.clone(), &ParquetTuning::default());
+                    let at = DateTime::from_timestamp(1_735_689_600 + minute * 60, 0).unwrap();
+                    sink.open("price_ticks", at, batch.schema()).unwrap();
+                    sink.write_batch(&batch).unwrap();
+                    sink.close().unwrap();
+                }
+            })
+        };
+
+        let mut snapshots = 0;
+        while !writer.is_finished() || snapshots == 0 {
+            let before = read_manifest(&dir).unwrap();
+            let snapshot =
+                CaptureSnapshot::take(&dir, IncludeCurrent::Skip, Duration::ZERO).unwrap();
+            let after = read_manifest(&dir).unwrap();
+            let taken: HashSet<String> = snapshot
+                .files
+                .iter()
+                .map(|f| f.path.file_name().unwrap().to_str().unwrap().to_string())
+                .collect();
+
+            // Every file sealed and in place before is in; every file in
+            // is sealed, with the contents its seal vouches for
+            assert!(before
+                .keys()
+                .filter(|name| dir.join(name).exists())
+                .all(|name| taken.contains(name)));
+            for file in &snapshot.files {
+                let name = file.path.file_name().unwrap().to_str().unwrap();
+                assert_eq!(Some(&file.sha256), after.get(name).map(|e| &e.sha256));
+                assert_eq!(file.sha256, sha256(&file.path).unwrap());
+            }
+
+            // The loader reads its own snapshot and never a part-written file
+            let (events, report) = CaptureLoader::new(vec![dir.clone()]).load().unwrap();
+            assert!(report.skipped.is_empty(), "{:?}", report.skipped);
+            let loaded = &report.snapshots[0];
+            assert!(loaded.files.len() >= snapshot.files.len());
+            assert_eq!(events.len(), loaded.files.len() * 50);
+            snapshots += 1;
+        }
+        writer.join().unwrap();
+
+        let snapshot =
+            CaptureSnapshot::take(&dir, IncludeCurrent::Wait, Duration::from_secs(5)).unwrap();
+        assert!(snapshot.in_progress.is_empty());
+        assert_eq!(snapshot.files.len(), 40);
+        assert_eq!(read_manifest(&dir).unwrap().len(), 40);
+    }
+
+    #[test]
+    fn test_files_being_written_are_waited_for_or_left_out() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let legacy = dir.join("price_ticks_20250101_000000.csv");
+        fs::write(&legacy, "timestamp,symbol,price\n").unwrap();
+        let partial = dir.join("price_ticks_20250101_010000.csv.tmp");
+        fs::write(&partial, "timestamp,sym").unwrap();
+
+        // A file from before manifests is checksummed at snapshot time
+        let snapshot = CaptureSnapshot::take(dir, IncludeCurrent::Skip, Duration::ZERO).unwrap();
+        assert_eq!(snapshot.files.len(), 1);
+        assert_eq!(snapshot.unsealed(), 1);
+        assert_eq!(snapshot.files[0].sha256, sha256(&legacy).unwrap());
+        assert_eq!(snapshot.in_progress, vec![partial.clone()]);
+
+        // Sealed and renamed into place while a snapshot waits
+        let sealer = {
+            let partial = partial.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(250));
+                fs::write(&partial, "timestamp,symbol,price\n").unwrap();
+                let path = partial.with_extension("");
+                seal(&partial, &path).unwrap();
+                fs::rename(&partial, &path).unwrap();
+            })
+        };
+        let snapshot =
+            CaptureSnapshot::take(dir, IncludeCurrent::Wait, Duration::from_secs(5)).unwrap();
+        sealer.join().unwrap();
+        assert!(snapshot.in_progress.is_empty());
+        assert_eq!(snapshot.files.len(), 2);
+        assert_eq!(snapshot.unsealed(), 1);
+
+        // A torn last line is an append still under way
+        let mut manifest = OpenOptions::new()
+            .append(true)
+            .open(dir.join(CAPTURE_MANIFEST_FILE))
+            .unwrap();
+        manifest.write_all(b"{\"file\":\"price_ti").unwrap();
+        assert_eq!(read_manifest(dir).unwrap().len(), 1);
+    }
+}
@@ -1,12 +1,76 @@
 //! Data capture module
 //!
-//! Stores tick data to Parquet for backtesting
+//! Stores tick data to Parquet (or CSV / Arrow IPC) for backtesting
 
+mod audit;
+mod backfill;
+mod dedup;
+mod disk;
+mod encoding;
+pub mod features;
+mod history;
+mod lock;
+mod manifest;
 mod parquet;
+mod queue;
 mod recorder;
+mod retention;
+mod sink;
+mod stats;
 
+pub use audit::{audit_book, load_book_records, BookAudit, LevelDiff, SnapshotDiff};
+pub use backfill::{
+    BackfillManifest, BackfillReport, ClobHistoryClient, PriceBackfill, BACKFILL_MANIFEST_FILE,
+    DEFAULT_BACKFILL_CHUNK_HOURS, DEFAULT_BACKFILL_FIDELITY_MINS,
+    DEFAULT_BACKFILL_REQUEST_INTERVAL_MS,
+};
+pub use dedup::{BookDedup, BookDedupConfig, DEFAULT_BOOK_KEEPALIVE_SECS};
+pub use disk::{available_space, DiskConfig, DiskManager, DiskState, DISK_HEALTH_COMPONENT};
+pub use encoding::{
+    benchmark_encoding, EncodingBenchmark, ParquetCodec, ParquetTuning, DEFAULT_COMPRESSION_LEVEL,
+    DEFAULT_DATA_PAGE_SIZE, DEFAULT_ORDERBOOK_ROW_GROUP_SIZE, DEFAULT_ROW_GROUP_SIZE,
+};
+pub use history::{
+    evict_oldest, HistoryArchive, HistoryConfig, DEFAULT_MAX_CLOSED_POSITIONS, DEFAULT_MAX_FILLS,
+    HISTORY_DIR,
+};
+pub use lock::{
+    instance_dir, DataDirLock, LockError, LockHolder, LockStatus, INSTANCES_DIR,
+    INSTANCE_JOURNAL_FILE, LOCK_FILE,
+};
+pub use manifest::{
+    read_manifest, sha256, CaptureSnapshot, IncludeCurrent, ManifestEntry, SnapshotFile,
+    CAPTURE_MANIFEST_FILE, DEFAULT_SEAL_WAIT_SECS,
+};
 pub use parquet::{
-    orderbook_schema, price_tick_schema, signal_schema, OrderBookRecord, ParquetReader,
-    ParquetWriter, PriceTickRecord, SignalRecord,
+    closed_position_batch, closed_position_schema, closed_positions_from_batch, orderbook_batch,
+    orderbook_schema, orderbooks_from_batch, price_history_batch, price_history_from_batch,
+    price_history_schema, price_tick_batch, price_tick_schema, price_ticks_from_batch,
+    read_config_fingerprint, read_parquet_batches, schema_version, signal_batch,
+    signal_outcome_batch, signal_outcome_schema, signal_schema, signals_from_batch,
+    writer_properties, BatchColumns, OrderBookRecord, ParquetReader, ParquetWriter,
+    PricePointRecord, PriceTickRecord, SignalRecord, CAPTURED_BOOK_LEVELS, CAPTURE_SCHEMA_VERSION,
+    PRICE_HISTORY_PREFIX, SCHEMA_VERSION_KEY,
+};
+pub(crate) use parquet::{decimal_column, str_column, timestamp_column};
+pub use queue::{
+    DurableQueue, QueueConfig, QueueReader, DEFAULT_SEGMENT_BYTES, DEFAULT_SYNC_EVERY,
+    DEFAULT_SYNC_INTERVAL_MS, QUEUE_DIR,
+};
+pub use recorder::{
+    AtomicRecorderStats, DataRecorder, RecordError, RecorderConfig, RecorderStats,
+    DEFAULT_FLUSH_INTERVAL_SECS, DEFAULT_ROTATION_INTERVAL_SECS,
+};
+pub use retention::{
+    data_dir_bytes, enforce_retention, file_prefix, plan_cleanup, scan_data_files, CleanupReport,
+    DataFile, RemovalReason, RetentionPolicy, COMPACTED_DIR,
+};
+pub use sink::{
+    capture_path, read_batches, schema_for_prefix, sink_for, validate_capture, CsvSink, DataFormat,
+    IpcSink, ParquetSink, RecordSink,
+};
+pub use stats::{
+    microstructure_batch, microstructure_schema, microstructure_stats, window_minute, BookShape,
+    LiveMicrostructure, MicrostructureConfig, MicrostructureReport, MicrostructureStats,
+    MinuteStats, DEFAULT_LIVE_SPREAD_SAMPLES, INFERRED_ASSET, WINDOW_MINUTES,
 };
-pub use recorder::{AtomicRecorderStats, DataRecorder, RecordError, RecorderConfig, RecorderStats};
@@ -1,17 +1,213 @@
 //! Parquet file writer with rotation
-
-use arrow::array::{ArrayRef, StringArray, TimestampMicrosecondArray};
+//!
+//! Readers look columns up by name through [`BatchColumns`], so captures
+//! keep reading as the schemas grow: columns a reader does not know are
+//! ignored, and columns added since a file was written read as their
+//! defaults. Files are stamped with [`CAPTURE_SCHEMA_VERSION`] under
+//! [`SCHEMA_VERSION_KEY`]; one without the key is version 1 and may lack
+//! any column added since the first capture, while a stamped file must
+//! hold every column of its version. A file missing a column it needs is
+//! refused up front, naming the columns missing and the schema it has.
+
+use super::encoding::ParquetTuning;
+use super::sink::{capture_path, DataFormat, PartialFile};
+use crate::fingerprint::{self, ConfigFingerprint, CONFIG_HASH_KEY, CONFIG_JSON_KEY};
+use crate::market::Market;
+use crate::orderbook::{BookUpdateKind, DepthProfile};
+use crate::precision::{round_pct, round_price, round_size};
+use crate::risk::{ClosedPosition, Position};
+use crate::signal::{Side, Signal, SignalOutcome, CHECKPOINTS_SECS};
+use arrow::array::{
+    Array, ArrayRef, BooleanArray, Int64Array, StringArray, StringBuilder,
+    TimestampMicrosecondArray,
+};
 use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
 use arrow::record_batch::RecordBatch;
 use chrono::{DateTime, Duration, Utc};
 use parquet::arrow::ArrowWriter;
-use parquet::basic::Compression;
 use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
 use std::fs::{self, File};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// Default-tuned writer properties stamped with `fingerprint`, if any
+pub fn writer_properties(fingerprint: Option<&ConfigFingerprint>) -> WriterProperties {
+    ParquetTuning::default()
+        .properties("", fingerprint)
+        .expect("default Parquet tuning is valid")
+}
+
+/// Read the config fingerprint stamped into a Parquet file
+///
+/// Returns `None` for files written without one (older captures).
+pub fn read_config_fingerprint(path: &Path) -> anyhow::Result<Option<ConfigFingerprint>> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    let Some(metadata) = reader.metadata().file_metadata().key_value_metadata() else {
+        return Ok(None);
+    };
+    let value = |key: &str| {
+        metadata
+            .iter()
+            .find(|kv| kv.key == key)
+            .and_then(|kv| kv.value.clone())
+    };
+    Ok(value(CONFIG_HASH_KEY).map(|hash| ConfigFingerprint {
+        hash,
+        config: value(CONFIG_JSON_KEY).unwrap_or_default(),
+    }))
+}
+
+/// File metadata key holding the schema version a file was written with
+pub const SCHEMA_VERSION_KEY: &str = "poly_hft.schema_version";
+
+/// Schema version written into every file from now on
+///
+/// Version 1 is every file written before the stamp, whichever columns it
+/// has. Version 2 files hold every column of their schema as it is now.
+pub const CAPTURE_SCHEMA_VERSION: u32 = 2;
+
+/// Columns of the order book schema added after the first captures, with
+/// the version every file holds them from
+const ORDERBOOK_ADDED: &[(&str, u32)] = &[("crossed", 2), ("kind", 2)];
+
+/// Columns of the signal schema added after the first captures
+const SIGNAL_ADDED: &[(&str, u32)] = &[
+    ("raw_edge", 2),
+    ("spread", 2),
+    ("round_trip_edge", 2),
+    ("depth_1c", 2),
+    ("depth_2c", 2),
+    ("depth_3c", 2),
+    ("signal_id", 2),
+];
+
+/// Every record batch of a Parquet `file`, each carrying the file's
+/// metadata, and so its schema version, in its schema
+pub fn read_parquet_batches(file: File) -> anyhow::Result<Vec<RecordBatch>> {
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let schema = builder.schema().clone();
+    builder
+        .build()?
+        .map(|batch| Ok(batch?.with_schema(schema.clone())?))
+        .collect()
+}
+
+/// Schema version stamped in `metadata`, 1 when there is none
+pub fn schema_version(metadata: &HashMap<String, String>) -> anyhow::Result<u32> {
+    match metadata.get(SCHEMA_VERSION_KEY) {
+        None => Ok(1),
+        Some(version) => version
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid {} '{}'", SCHEMA_VERSION_KEY, version)),
+    }
+}
+
+/// Columns of a batch looked up by name against the schema they are read
+/// as
+///
+/// Every column of `schema` a file of its version must hold is checked
+/// for when the batch is opened; columns added since are optional, and
+/// columns the schema does not name are never looked at.
+pub struct BatchColumns<'a> {
+    batch: &'a RecordBatch,
+    /// What the batch holds, for errors, e.g. `price_ticks`
+    what: &'static str,
+    schema: Schema,
+    version: u32,
+}
+
+impl<'a> BatchColumns<'a> {
+    /// Open `batch` as `what` data in `schema`, whose `added` columns are
+    /// only required from the version given with each
+    pub fn new(
+        batch: &'a RecordBatch,
+        what: &'static str,
+        schema: Schema,
+        added: &[(&str, u32)],
+    ) -> anyhow::Result<Self> {
+        let version = schema_version(batch.schema_ref().metadata())?;
+        let missing: Vec<&str> = schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .filter(|name| {
+                let since = added.iter().find(|(n, _)| n == name).map_or(1, |(_, v)| *v);
+                since <= version && batch.column_by_name(name).is_none()
+            })
+            .collect();
+        if !missing.is_empty() {
+            let has: Vec<String> = batch
+                .schema_ref()
+                .fields()
+                .iter()
+                .map(|f| format!("{}: {}", f.name(), f.data_type()))
+                .collect();
+            anyhow::bail!(
+                "{} data of schema version {} is missing column(s) {}; it has {}",
+                what,
+                version,
+                missing.join(", "),
+                if has.is_empty() {
+                    "none".to_string()
+                } else {
+                    has.join(", ")
+                }
+            );
+        }
+        Ok(Self {
+            batch,
+            what,
+            schema,
+            version,
+        })
+    }
+
+    /// Schema version of the file the batch was read from
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Rows in the batch
+    pub fn num_rows(&self) -> usize {
+        self.batch.num_rows()
+    }
+
+    /// Column `name`, which every file holds
+    pub fn required<T: Array + 'static>(&self, name: &str) -> anyhow::Result<&'a T> {
+        self.optional(name)?
+            .ok_or_else(|| anyhow::anyhow!("{} data is missing column {}", self.what, name))
+    }
+
+    /// Column `name` if the file has it; one of another type is an error
+    pub fn optional<T: Array + 'static>(&self, name: &str) -> anyhow::Result<Option<&'a T>> {
+        let Some(column) = self.batch.column_by_name(name) else {
+            return Ok(None);
+        };
+        let column = column.as_any().downcast_ref::<T>().ok_or_else(|| {
+            let expected = self
+                .schema
+                .field_with_name(name)
+                .map(|f| f.data_type().to_string())
+                .unwrap_or_else(|_| "another type".to_string());
+            anyhow::anyhow!(
+                "{} column {} is {}, expected {}",
+                self.what,
+                name,
+                column.data_type(),
+                expected
+            )
+        })?;
+        Ok(Some(column))
+    }
+}
+
 /// Price tick schema fields
 pub fn price_tick_schema() -> Schema {
     Schema::new(vec![
@@ -30,6 +226,25 @@ pub fn price_tick_schema() -> Schema {
     ])
 }
 
+/// Prefix of price history files backfilled over REST
+pub const PRICE_HISTORY_PREFIX: &str = "price_history";
+
+/// Price history schema fields: one price per token and time, no book
+pub fn price_history_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("token_id", DataType::Utf8, false),
+        Field::new(
+            "ts",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("price", DataType::Utf8, false),
+    ])
+}
+
+/// Levels per side stored in a captured order book row
+pub const CAPTURED_BOOK_LEVELS: usize = 5;
+
 /// Order book schema fields (top 5 levels)
 pub fn orderbook_schema() -> Schema {
     let mut fields = vec![
@@ -49,15 +264,487 @@ pub fn orderbook_schema() -> Schema {
         fields.push(Field::new(format!("ask_size_{}", i), DataType::Utf8, true));
     }
 
+    // Best bid >= best ask at capture time
+    fields.push(Field::new("crossed", DataType::Boolean, false));
+
+    // `snapshot`, `delta` or `keepalive`; absent in older captures
+    fields.push(Field::new("kind", DataType::Utf8, true));
+
     Schema::new(fields)
 }
 
+/// Bytes reserved per decimal value when building a text column
+const DECIMAL_TEXT_BYTES: usize = 8;
+
+/// Append `value` as its `Display` text, formatting from the mantissa on
+/// the stack rather than through `fmt`
+fn append_decimal(builder: &mut StringBuilder, value: Decimal) {
+    let Ok(mut mantissa) = u64::try_from(value.mantissa().unsigned_abs()) else {
+        write!(builder, "{}", value).expect("formatting into a StringBuilder cannot fail");
+        builder.append_value("");
+        return;
+    };
+    let scale = value.scale() as usize;
+    // 20 digits of u64, up to 28 leading zeros of the scale, point and sign
+    let mut buf = [0u8; 52];
+    let mut pos = buf.len();
+    let mut digits = 0;
+    while mantissa > 0 || digits <= scale {
+        if digits == scale && scale > 0 {
+            pos -= 1;
+            buf[pos] = b'.';
+        }
+        pos -= 1;
+        buf[pos] = b'0' + (mantissa % 10) as u8;
+        mantissa /= 10;
+        digits += 1;
+    }
+    if value.is_sign_negative() {
+        pos -= 1;
+        buf[pos] = b'-';
+    }
+    builder.append_value(std::str::from_utf8(&buf[pos..]).expect("decimal text is ASCII"));
+}
+
+/// Decimal text column of `records`, null where `value` is `None`
+///
+/// Values are written straight into the column's buffer, with no `String`
+/// per value.
+pub(crate) fn decimal_column<R>(records: &[R], value: impl Fn(&R) -> Option<Decimal>) -> ArrayRef {
+    let mut builder =
+        StringBuilder::with_capacity(records.len(), records.len() * DECIMAL_TEXT_BYTES);
+    for record in records {
+        match value(record) {
+            Some(v) => append_decimal(&mut builder, v),
+            None => builder.append_null(),
+        }
+    }
+    Arc::new(builder.finish())
+}
+
+/// Text column of borrowed strings
+pub(crate) fn str_column<'a, R: 'a>(
+    records: &'a [R],
+    value: impl Fn(&'a R) -> &'a str,
+) -> ArrayRef {
+    Arc::new(StringArray::from_iter_values(records.iter().map(value)))
+}
+
+/// UTC microsecond timestamp column
+pub(crate) fn timestamp_column<R>(records: &[R], value: impl Fn(&R) -> DateTime<Utc>) -> ArrayRef {
+    Arc::new(
+        TimestampMicrosecondArray::from_iter_values(
+            records.iter().map(|r| value(r).timestamp_micros()),
+        )
+        .with_timezone("UTC"),
+    )
+}
+
+/// Price ticks as a batch in [`price_tick_schema`]
+pub fn price_tick_batch(ticks: &[PriceTickRecord]) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_new(
+        Arc::new(price_tick_schema()),
+        vec![
+            timestamp_column(ticks, |t| t.timestamp),
+            str_column(ticks, |t| &t.symbol),
+            decimal_column(ticks, |t| Some(round_price(t.price))),
+            timestamp_column(ticks, |t| t.exchange_ts),
+        ],
+    )?)
+}
+
+/// Order book snapshots as a batch in [`orderbook_schema`]
+pub fn orderbook_batch(snapshots: &[OrderBookRecord]) -> anyhow::Result<RecordBatch> {
+    let mut columns: Vec<ArrayRef> = vec![
+        timestamp_column(snapshots, |s| s.timestamp),
+        str_column(snapshots, |s| &s.token_id),
+    ];
+
+    // Add bid/ask levels
+    for i in 0..5 {
+        columns.push(decimal_column(snapshots, |s| {
+            s.bids.get(i).map(|(p, _)| round_price(*p))
+        }));
+        columns.push(decimal_column(snapshots, |s| {
+            s.bids.get(i).map(|(_, s)| round_size(*s))
+        }));
+        columns.push(decimal_column(snapshots, |s| {
+            s.asks.get(i).map(|(p, _)| round_price(*p))
+        }));
+        columns.push(decimal_column(snapshots, |s| {
+            s.asks.get(i).map(|(_, s)| round_size(*s))
+        }));
+    }
+
+    columns.push(Arc::new(BooleanArray::from_iter(
+        snapshots.iter().map(|s| Some(s.crossed)),
+    )));
+    columns.push(str_column(snapshots, |s| s.kind.as_str()));
+
+    Ok(RecordBatch::try_new(Arc::new(orderbook_schema()), columns)?)
+}
+
+/// Price history points as a batch in [`price_history_schema`]
+pub fn price_history_batch(points: &[PricePointRecord]) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_new(
+        Arc::new(price_history_schema()),
+        vec![
+            str_column(points, |p| &p.token_id),
+            timestamp_column(points, |p| p.ts),
+            decimal_column(points, |p| Some(p.price)),
+        ],
+    )?)
+}
+
+/// Signals as a batch in [`signal_schema`]
+pub fn signal_batch(signals: &[SignalRecord]) -> anyhow::Result<RecordBatch> {
+    let depth = |within: fn(&DepthProfile) -> Decimal| {
+        decimal_column(signals, move |s| Some(round_size(within(&s.depth))))
+    };
+
+    Ok(RecordBatch::try_new(
+        Arc::new(signal_schema()),
+        vec![
+            timestamp_column(signals, |s| s.timestamp),
+            str_column(signals, |s| &s.market_id),
+            str_column(signals, |s| &s.side),
+            decimal_column(signals, |s| Some(round_pct(s.fair_value))),
+            decimal_column(signals, |s| Some(round_price(s.market_price))),
+            decimal_column(signals, |s| Some(round_pct(s.edge))),
+            str_column(signals, |s| &s.action),
+            decimal_column(signals, |s| Some(round_pct(s.raw_edge))),
+            decimal_column(signals, |s| Some(round_price(s.spread))),
+            decimal_column(signals, |s| Some(round_pct(s.round_trip_edge))),
+            depth(|d| d.within_1c),
+            depth(|d| d.within_2c),
+            depth(|d| d.within_3c),
+            str_column(signals, |s| &s.signal_id),
+        ],
+    )?)
+}
+
+/// Signal outcome schema; one `price_<n>s` column per checkpoint
+pub fn signal_outcome_schema() -> Schema {
+    let mut fields = vec![
+        Field::new("signal_id", DataType::Utf8, false),
+        Field::new("market_id", DataType::Utf8, false),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("action", DataType::Utf8, false),
+        Field::new(
+            "emitted_at",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("entry_price", DataType::Utf8, false),
+        Field::new("expected_price", DataType::Utf8, false),
+    ];
+    for secs in CHECKPOINTS_SECS {
+        fields.push(Field::new(format!("price_{}s", secs), DataType::Utf8, true));
+    }
+    fields.extend([
+        Field::new("close_price", DataType::Utf8, true),
+        Field::new("max_favorable", DataType::Utf8, false),
+        Field::new("max_adverse", DataType::Utf8, false),
+        Field::new("time_to_converge_secs", DataType::Int64, true),
+    ]);
+    Schema::new(fields)
+}
+
+/// Signal outcomes as a batch in [`signal_outcome_schema`]
+pub fn signal_outcome_batch(outcomes: &[SignalOutcome]) -> anyhow::Result<RecordBatch> {
+    let strings = |f: &dyn Fn(&SignalOutcome) -> String| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(outcomes.iter().map(f)))
+    };
+    let price = |f: fn(&SignalOutcome) -> Option<Decimal>| {
+        decimal_column(outcomes, move |o| f(o).map(round_price))
+    };
+
+    let mut columns = vec![
+        strings(&|o| o.signal_id.to_string()),
+        str_column(outcomes, |o| &o.market_id),
+        strings(&|o| format!("{:?}", o.side).to_uppercase()),
+        str_column(outcomes, |o| &o.action),
+        timestamp_column(outcomes, |o| o.emitted_at),
+        price(|o| Some(o.entry_price)),
+        price(|o| Some(o.expected_price)),
+    ];
+    for i in 0..CHECKPOINTS_SECS.len() {
+        columns.push(decimal_column(outcomes, |o| {
+            o.checkpoints[i].map(round_price)
+        }));
+    }
+    columns.extend([
+        price(|o| o.close_price),
+        price(|o| Some(o.max_favorable)),
+        price(|o| Some(o.max_adverse)),
+        Arc::new(Int64Array::from_iter(
+            outcomes.iter().map(|o| o.time_to_converge_secs),
+        )) as ArrayRef,
+    ]);
+
+    Ok(RecordBatch::try_new(
+        Arc::new(signal_outcome_schema()),
+        columns,
+    )?)
+}
+
+/// Closed position schema; decimals are stored unrounded as strings
+pub fn closed_position_schema() -> Schema {
+    let timestamp = |name: &str| {
+        Field::new(
+            name,
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        )
+    };
+    Schema::new(vec![
+        Field::new("position_id", DataType::Utf8, false),
+        Field::new("strategy", DataType::Utf8, false),
+        Field::new("market_id", DataType::Utf8, false),
+        Field::new("asset", DataType::Utf8, false),
+        Field::new("yes_token_id", DataType::Utf8, false),
+        Field::new("no_token_id", DataType::Utf8, false),
+        Field::new("group_id", DataType::Utf8, true),
+        Field::new("open_price", DataType::Utf8, false),
+        timestamp("open_time"),
+        timestamp("close_time"),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("entry_price", DataType::Utf8, false),
+        Field::new("size", DataType::Utf8, false),
+        timestamp("entry_time"),
+        Field::new("exit_price", DataType::Utf8, false),
+        timestamp("exit_time"),
+        Field::new("realized_pnl", DataType::Utf8, false),
+        Field::new("fees", DataType::Utf8, false),
+    ])
+}
+
+/// Closed positions as a batch in [`closed_position_schema`]
+pub fn closed_position_batch(closed: &[ClosedPosition]) -> anyhow::Result<RecordBatch> {
+    let decimal = |f: fn(&ClosedPosition) -> Decimal| decimal_column(closed, move |c| Some(f(c)));
+    Ok(RecordBatch::try_new(
+        Arc::new(closed_position_schema()),
+        vec![
+            Arc::new(StringArray::from_iter_values(
+                closed.iter().map(|c| c.position.id.to_string()),
+            )) as ArrayRef,
+            str_column(closed, |c| &c.position.strategy),
+            str_column(closed, |c| &c.position.market.condition_id),
+            str_column(closed, |c| &c.position.market.asset),
+            str_column(closed, |c| &c.position.market.yes_token_id),
+            str_column(closed, |c| &c.position.market.no_token_id),
+            Arc::new(
+                closed
+                    .iter()
+                    .map(|c| c.position.market.group_id.as_deref())
+                    .collect::<StringArray>(),
+            ) as ArrayRef,
+            decimal(|c| c.position.market.open_price),
+            timestamp_column(closed, |c| c.position.market.open_time),
+            timestamp_column(closed, |c| c.position.market.close_time),
+            str_column(closed, |c| match c.position.side {
+                Side::Yes => "yes",
+                Side::No => "no",
+            }),
+            decimal(|c| c.position.entry_price),
+            decimal(|c| c.position.size),
+            timestamp_column(closed, |c| c.position.entry_time),
+            decimal(|c| c.exit_price),
+            timestamp_column(closed, |c| c.exit_time),
+            decimal(|c| c.realized_pnl),
+            decimal(|c| c.fees),
+        ],
+    )?)
+}
+
+/// Decode a batch in [`closed_position_schema`]
+pub fn closed_positions_from_batch(batch: &RecordBatch) -> anyhow::Result<Vec<ClosedPosition>> {
+    use std::str::FromStr;
+
+    let columns = BatchColumns::new(batch, "closed_positions", closed_position_schema(), &[])?;
+    let strings = |name: &str| columns.required::<StringArray>(name);
+    let timestamps = |name: &str| columns.required::<TimestampMicrosecondArray>(name);
+    let decimal = |column: &StringArray, row: usize| Decimal::from_str(column.value(row));
+    let time = |column: &TimestampMicrosecondArray, row: usize| {
+        DateTime::from_timestamp_micros(column.value(row))
+            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))
+    };
+    let ids = strings("position_id")?;
+    let strategies = strings("strategy")?;
+    let market_ids = strings("market_id")?;
+    let assets = strings("asset")?;
+    let yes_tokens = strings("yes_token_id")?;
+    let no_tokens = strings("no_token_id")?;
+    let groups = strings("group_id")?;
+    let open_prices = strings("open_price")?;
+    let open_times = timestamps("open_time")?;
+    let close_times = timestamps("close_time")?;
+    let sides = strings("side")?;
+    let entry_prices = strings("entry_price")?;
+    let sizes = strings("size")?;
+    let entry_times = timestamps("entry_time")?;
+    let exit_prices = strings("exit_price")?;
+    let exit_times = timestamps("exit_time")?;
+    let pnls = strings("realized_pnl")?;
+    let fees = strings("fees")?;
+
+    let mut closed = Vec::with_capacity(columns.num_rows());
+    for row in 0..columns.num_rows() {
+        let side = match sides.value(row) {
+            "yes" => Side::Yes,
+            "no" => Side::No,
+            other => anyhow::bail!("unknown side: {}", other),
+        };
+        let market = Market {
+            condition_id: market_ids.value(row).to_string(),
+            asset: assets.value(row).to_string(),
+            yes_token_id: yes_tokens.value(row).to_string(),
+            no_token_id: no_tokens.value(row).to_string(),
+            open_price: decimal(open_prices, row)?,
+            open_time: time(open_times, row)?,
+            close_time: time(close_times, row)?,
+            group_id: (!groups.is_null(row)).then(|| groups.value(row).to_string()),
+            orientation: Default::default(),
+        };
+        closed.push(ClosedPosition {
+            position: Position {
+                id: ids.value(row).parse()?,
+                market,
+                side,
+                entry_price: decimal(entry_prices, row)?,
+                size: decimal(sizes, row)?,
+                entry_time: time(entry_times, row)?,
+                unrealized_pnl: Decimal::ZERO,
+                // Part of `fees`, which is all the archive keeps
+                entry_fee: Decimal::ZERO,
+                strategy: strategies.value(row).to_string(),
+                max_adverse: Decimal::ZERO,
+            },
+            exit_price: decimal(exit_prices, row)?,
+            exit_time: time(exit_times, row)?,
+            realized_pnl: decimal(pnls, row)?,
+            fees: decimal(fees, row)?,
+        });
+    }
+    Ok(closed)
+}
+
+/// Decode a batch in [`price_tick_schema`]
+pub fn price_ticks_from_batch(batch: &RecordBatch) -> anyhow::Result<Vec<PriceTickRecord>> {
+    use std::str::FromStr;
+
+    let columns = BatchColumns::new(batch, "price_ticks", price_tick_schema(), &[])?;
+    let timestamps = columns.required::<TimestampMicrosecondArray>("timestamp")?;
+    let symbols = columns.required::<StringArray>("symbol")?;
+    let prices = columns.required::<StringArray>("price")?;
+    let exchange_timestamps = columns.required::<TimestampMicrosecondArray>("exchange_ts")?;
+
+    let mut ticks = Vec::with_capacity(columns.num_rows());
+    for i in 0..columns.num_rows() {
+        let timestamp = DateTime::from_timestamp_micros(timestamps.value(i))
+            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?;
+        let exchange_ts = DateTime::from_timestamp_micros(exchange_timestamps.value(i))
+            .ok_or_else(|| anyhow::anyhow!("Invalid exchange_ts"))?;
+
+        ticks.push(PriceTickRecord {
+            timestamp,
+            symbol: Arc::from(symbols.value(i)),
+            price: Decimal::from_str(prices.value(i))?,
+            exchange_ts,
+        });
+    }
+    Ok(ticks)
+}
+
+/// Decode a batch in [`price_history_schema`]
+pub fn price_history_from_batch(batch: &RecordBatch) -> anyhow::Result<Vec<PricePointRecord>> {
+    use std::str::FromStr;
+
+    let columns = BatchColumns::new(batch, "price_history", price_history_schema(), &[])?;
+    let tokens = columns.required::<StringArray>("token_id")?;
+    let prices = columns.required::<StringArray>("price")?;
+    let timestamps = columns.required::<TimestampMicrosecondArray>("ts")?;
+
+    let mut points = Vec::with_capacity(columns.num_rows());
+    for row in 0..columns.num_rows() {
+        points.push(PricePointRecord {
+            token_id: Arc::from(tokens.value(row)),
+            ts: DateTime::from_timestamp_micros(timestamps.value(row))
+                .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?,
+            price: Decimal::from_str(prices.value(row))?,
+        });
+    }
+    Ok(points)
+}
+
+/// Read order book records back from a batch in [`orderbook_schema`]
+///
+/// Captures from before the `kind` column count as snapshots when either
+/// side fills every captured level, and as deltas otherwise; those from
+/// before the `crossed` column as not crossed.
+pub fn orderbooks_from_batch(batch: &RecordBatch) -> anyhow::Result<Vec<OrderBookRecord>> {
+    use std::str::FromStr;
+
+    let columns = BatchColumns::new(batch, "orderbook", orderbook_schema(), ORDERBOOK_ADDED)?;
+    let strings = |name: &str| columns.required::<StringArray>(name);
+    let timestamps = columns.required::<TimestampMicrosecondArray>("timestamp")?;
+    let token_ids = strings("token_id")?;
+    let crossed = columns.optional::<BooleanArray>("crossed")?;
+    let kinds = columns.optional::<StringArray>("kind")?;
+    let mut levels = Vec::with_capacity(CAPTURED_BOOK_LEVELS);
+    for i in 0..CAPTURED_BOOK_LEVELS {
+        levels.push([
+            strings(&format!("bid_price_{}", i))?,
+            strings(&format!("bid_size_{}", i))?,
+            strings(&format!("ask_price_{}", i))?,
+            strings(&format!("ask_size_{}", i))?,
+        ]);
+    }
+
+    let level = |price: &StringArray, size: &StringArray, row: usize| {
+        if price.is_null(row) || size.is_null(row) {
+            return Ok(None);
+        }
+        Ok::<_, anyhow::Error>(Some((
+            Decimal::from_str(price.value(row))?,
+            Decimal::from_str(size.value(row))?,
+        )))
+    };
+    let mut records = Vec::with_capacity(columns.num_rows());
+    for row in 0..columns.num_rows() {
+        let timestamp = DateTime::from_timestamp_micros(timestamps.value(row))
+            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?;
+        let mut bids = vec![];
+        let mut asks = vec![];
+        for [bid_price, bid_size, ask_price, ask_size] in &levels {
+            bids.extend(level(bid_price, bid_size, row)?);
+            asks.extend(level(ask_price, ask_size, row)?);
+        }
+        let kind = match kinds.filter(|k| !k.is_null(row)) {
+            Some(k) => BookUpdateKind::from_str(k.value(row))?,
+            None if bids.len().max(asks.len()) >= CAPTURED_BOOK_LEVELS => BookUpdateKind::Snapshot,
+            None => BookUpdateKind::Delta,
+        };
+        records.push(OrderBookRecord {
+            timestamp,
+            token_id: Arc::from(token_ids.value(row)),
+            bids,
+            asks,
+            crossed: crossed.is_some_and(|c| c.value(row)),
+            kind,
+        });
+    }
+    Ok(records)
+}
+
 /// Parquet file writer with time-based rotation
 #[derive(Clone)]
 pub struct ParquetWriter {
     output_dir: PathBuf,
     rotation_interval: Duration,
     current_file_start: Option<DateTime<Utc>>,
+    fingerprint: Option<ConfigFingerprint>,
+    tuning: ParquetTuning,
 }
 
 impl ParquetWriter {
@@ -67,9 +754,23 @@ impl ParquetWriter {
             output_dir,
             rotation_interval: Duration::seconds(rotation_interval_secs as i64),
             current_file_start: None,
+            fingerprint: fingerprint::active().cloned(),
+            tuning: ParquetTuning::default(),
         }
     }
 
+    /// Encode files with `tuning` instead of the defaults
+    pub fn with_tuning(mut self, tuning: ParquetTuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
+
+    /// Stamp files with this fingerprint instead of the process-wide one
+    pub fn with_fingerprint(mut self, fingerprint: ConfigFingerprint) -> Self {
+        self.fingerprint = Some(fingerprint);
+        self
+    }
+
     /// Ensure output directory exists
     fn ensure_dir(&self) -> anyhow::Result<()> {
         fs::create_dir_all(&self.output_dir)?;
@@ -86,8 +787,7 @@ impl ParquetWriter {
 
     /// Generate file path for a given timestamp and prefix
     pub fn file_path(&self, prefix: &str, timestamp: DateTime<Utc>) -> PathBuf {
-        let filename = format!("{}_{}.parquet", prefix, timestamp.format("%Y%m%d_%H%M%S"));
-        self.output_dir.join(filename)
+        capture_path(&self.output_dir, prefix, timestamp, DataFormat::Parquet)
     }
 
     /// Get current output file path (for compatibility)
@@ -111,47 +811,39 @@ impl ParquetWriter {
             return Ok(());
         }
 
-        self.ensure_dir()?;
+        self.write_batch(path, &price_tick_batch(ticks)?)?;
 
-        let schema = Arc::new(price_tick_schema());
-        let file = File::create(path)?;
+        tracing::debug!(path = ?path, count = ticks.len(), "Wrote price ticks to Parquet");
 
-        let props = WriterProperties::builder()
-            .set_compression(Compression::SNAPPY)
-            .build();
+        Ok(())
+    }
 
-        let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(props))?;
+    /// Write one batch to `path`, renaming it into place when complete
+    fn write_batch(&self, path: &Path, batch: &RecordBatch) -> anyhow::Result<()> {
+        self.ensure_dir()?;
 
-        // Build arrays
-        let timestamps: Vec<i64> = ticks
-            .iter()
-            .map(|t| t.timestamp.timestamp_micros())
-            .collect();
-        let symbols: Vec<&str> = ticks.iter().map(|t| t.symbol.as_ref()).collect();
-        let prices: Vec<String> = ticks.iter().map(|t| t.price.to_string()).collect();
-        let exchange_ts: Vec<i64> = ticks
-            .iter()
-            .map(|t| t.exchange_ts.timestamp_micros())
-            .collect();
+        let (partial, file) = PartialFile::create(path.to_path_buf())?;
+        let prefix = super::file_prefix(path).unwrap_or_default();
+        let props = self.tuning.properties(&prefix, self.fingerprint.as_ref())?;
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))?;
+        writer.write(batch)?;
+        partial.commit(writer.into_inner()?)?;
+        Ok(())
+    }
 
-        let batch = RecordBatch::try_new(
-            schema,
-            vec![
-                Arc::new(TimestampMicrosecondArray::from(timestamps).with_timezone("UTC"))
-                    as ArrayRef,
-                Arc::new(StringArray::from(symbols)) as ArrayRef,
-                Arc::new(StringArray::from(
-                    prices.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
-                )) as ArrayRef,
-                Arc::new(TimestampMicrosecondArray::from(exchange_ts).with_timezone("UTC"))
-                    as ArrayRef,
-            ],
-        )?;
+    /// Write closed positions to a Parquet file
+    pub fn write_closed_positions(
+        &self,
+        path: &Path,
+        closed: &[ClosedPosition],
+    ) -> anyhow::Result<()> {
+        if closed.is_empty() {
+            return Ok(());
+        }
 
-        writer.write(&batch)?;
-        writer.close()?;
+        self.write_batch(path, &closed_position_batch(closed)?)?;
 
-        tracing::debug!(path = ?path, count = ticks.len(), "Wrote price ticks to Parquet");
+        tracing::debug!(path = ?path, count = closed.len(), "Wrote closed positions to Parquet");
 
         Ok(())
     }
@@ -172,68 +864,34 @@ impl ParquetWriter {
             .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
     }
 
-    /// Write order book snapshots to a Parquet file (blocking)
-    pub fn write_orderbook_snapshots(
+    /// Write backfilled price history to a Parquet file
+    pub fn write_price_history(
         &self,
-        path: &PathBuf,
-        snapshots: &[OrderBookRecord],
+        path: &Path,
+        points: &[PricePointRecord],
     ) -> anyhow::Result<()> {
-        if snapshots.is_empty() {
+        if points.is_empty() {
             return Ok(());
         }
 
-        self.ensure_dir()?;
-
-        let schema = Arc::new(orderbook_schema());
-        let file = File::create(path)?;
-
-        let props = WriterProperties::builder()
-            .set_compression(Compression::SNAPPY)
-            .build();
-
-        let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(props))?;
+        self.write_batch(path, &price_history_batch(points)?)?;
 
-        // Build arrays
-        let timestamps: Vec<i64> = snapshots
-            .iter()
-            .map(|s| s.timestamp.timestamp_micros())
-            .collect();
-        let token_ids: Vec<&str> = snapshots.iter().map(|s| s.token_id.as_ref()).collect();
-
-        let mut columns: Vec<ArrayRef> = vec![
-            Arc::new(TimestampMicrosecondArray::from(timestamps).with_timezone("UTC")),
-            Arc::new(StringArray::from(token_ids)),
-        ];
+        tracing::debug!(path = ?path, count = points.len(), "Wrote price history to Parquet");
 
-        // Add bid/ask levels
-        for i in 0..5 {
-            let bid_prices: Vec<Option<String>> = snapshots
-                .iter()
-                .map(|s| s.bids.get(i).map(|(p, _)| p.to_string()))
-                .collect();
-            let bid_sizes: Vec<Option<String>> = snapshots
-                .iter()
-                .map(|s| s.bids.get(i).map(|(_, s)| s.to_string()))
-                .collect();
-            let ask_prices: Vec<Option<String>> = snapshots
-                .iter()
-                .map(|s| s.asks.get(i).map(|(p, _)| p.to_string()))
-                .collect();
-            let ask_sizes: Vec<Option<String>> = snapshots
-                .iter()
-                .map(|s| s.asks.get(i).map(|(_, s)| s.to_string()))
-                .collect();
+        Ok(())
+    }
 
-            columns.push(Arc::new(StringArray::from(bid_prices)));
-            columns.push(Arc::new(StringArray::from(bid_sizes)));
-            columns.push(Arc::new(StringArray::from(ask_prices)));
-            columns.push(Arc::new(StringArray::from(ask_sizes)));
+    /// Write order book snapshots to a Parquet file (blocking)
+    pub fn write_orderbook_snapshots(
+        &self,
+        path: &PathBuf,
+        snapshots: &[OrderBookRecord],
+    ) -> anyhow::Result<()> {
+        if snapshots.is_empty() {
+            return Ok(());
         }
 
-        let batch = RecordBatch::try_new(schema, columns)?;
-
-        writer.write(&batch)?;
-        writer.close()?;
+        self.write_batch(path, &orderbook_batch(snapshots)?)?;
 
         tracing::debug!(path = ?path, count = snapshots.len(), "Wrote orderbook snapshots to Parquet");
 
@@ -259,7 +917,7 @@ impl ParquetWriter {
 
 /// Record type for price ticks (for writing)
 /// Uses Arc<str> for symbol to reduce allocations on hot path
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PriceTickRecord {
     pub timestamp: DateTime<Utc>,
     pub symbol: Arc<str>,
@@ -286,12 +944,24 @@ impl PriceTickRecord {
 
 /// Record type for order book snapshots (for writing)
 /// Uses Arc<str> for token_id to reduce allocations
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderBookRecord {
     pub timestamp: DateTime<Utc>,
     pub token_id: Arc<str>,
     pub bids: Vec<(Decimal, Decimal)>, // (price, size)
     pub asks: Vec<(Decimal, Decimal)>,
+    /// Top of book was crossed or locked
+    pub crossed: bool,
+    /// Whether the row is a full book or changed levels only
+    pub kind: BookUpdateKind,
+}
+
+/// One point of a token's price history, with no book behind it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricePointRecord {
+    pub token_id: Arc<str>,
+    pub ts: DateTime<Utc>,
+    pub price: Decimal,
 }
 
 /// Reader for Parquet files
@@ -307,58 +977,32 @@ impl ParquetReader {
 
     /// Read price ticks from a Parquet file
     pub fn read_price_ticks(&self) -> anyhow::Result<Vec<PriceTickRecord>> {
-        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
-        use std::str::FromStr;
-
-        let file = File::open(&self.path)?;
-        let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
-        let reader = builder.build()?;
-
-        let mut ticks = Vec::new();
-
-        for batch_result in reader {
-            let batch = batch_result?;
+        self.read(price_ticks_from_batch)
+    }
 
-            let timestamps = batch
-                .column(0)
-                .as_any()
-                .downcast_ref::<TimestampMicrosecondArray>()
-                .ok_or_else(|| anyhow::anyhow!("Invalid timestamp column"))?;
+    /// Read order book records from a Parquet file
+    pub fn read_orderbooks(&self) -> anyhow::Result<Vec<OrderBookRecord>> {
+        self.read(orderbooks_from_batch)
+    }
 
-            let symbols = batch
-                .column(1)
-                .as_any()
-                .downcast_ref::<StringArray>()
-                .ok_or_else(|| anyhow::anyhow!("Invalid symbol column"))?;
+    /// Read signal records from a Parquet file
+    pub fn read_signals(&self) -> anyhow::Result<Vec<SignalRecord>> {
+        self.read(signals_from_batch)
+    }
 
-            let prices = batch
-                .column(2)
-                .as_any()
-                .downcast_ref::<StringArray>()
-                .ok_or_else(|| anyhow::anyhow!("Invalid price column"))?;
+    /// Every row of the file decoded by `decode`, batch by batch
+    fn read<T>(
+        &self,
+        decode: fn(&RecordBatch) -> anyhow::Result<Vec<T>>,
+    ) -> anyhow::Result<Vec<T>> {
+        use anyhow::Context;
 
-            let exchange_timestamps = batch
-                .column(3)
-                .as_any()
-                .downcast_ref::<TimestampMicrosecondArray>()
-                .ok_or_else(|| anyhow::anyhow!("Invalid exchange_ts column"))?;
-
-            for i in 0..batch.num_rows() {
-                let timestamp = DateTime::from_timestamp_micros(timestamps.value(i))
-                    .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?;
-                let exchange_ts = DateTime::from_timestamp_micros(exchange_timestamps.value(i))
-                    .ok_or_else(|| anyhow::anyhow!("Invalid exchange_ts"))?;
-
-                ticks.push(PriceTickRecord {
-                    timestamp,
-                    symbol: Arc::from(symbols.value(i)),
-                    price: Decimal::from_str(prices.value(i))?,
-                    exchange_ts,
-                });
-            }
+        let mut rows = Vec::new();
+        for batch in read_parquet_batches(File::open(&self.path)?)? {
+            rows.extend(decode(&batch).with_context(|| self.path.display().to_string())?);
         }
 
-        Ok(ticks)
+        Ok(rows)
     }
 
     /// Read price ticks asynchronously
@@ -372,6 +1016,11 @@ impl ParquetReader {
         .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
     }
 
+    /// Read the config fingerprint the file was written under
+    pub fn config_fingerprint(&self) -> anyhow::Result<Option<ConfigFingerprint>> {
+        read_config_fingerprint(&self.path)
+    }
+
     /// Get the file path
     pub fn path(&self) -> &PathBuf {
         &self.path
@@ -387,7 +1036,37 @@ pub struct SignalRecord {
     pub fair_value: Decimal,
     pub market_price: Decimal,
     pub edge: Decimal,
+    /// Edge before fees and slippage
+    pub raw_edge: Decimal,
+    /// Bid-ask spread of the traded book
+    pub spread: Decimal,
+    /// Edge after paying the spread back on exit
+    pub round_trip_edge: Decimal,
     pub action: Arc<str>,
+    /// Size near the touch of the traded book
+    pub depth: DepthProfile,
+    /// Signal identifier, joinable across runs of the same data
+    pub signal_id: Arc<str>,
+}
+
+impl SignalRecord {
+    /// Record of `signal` and what was done with it
+    pub fn from_signal(signal: &Signal, action: &str) -> Self {
+        Self {
+            timestamp: signal.timestamp,
+            market_id: Arc::from(signal.market.condition_id.as_str()),
+            side: Arc::from(format!("{:?}", signal.side).to_uppercase()),
+            fair_value: signal.fair_value,
+            market_price: signal.market_price,
+            edge: signal.adjusted_edge,
+            raw_edge: signal.raw_edge,
+            spread: signal.spread,
+            round_trip_edge: signal.round_trip_edge,
+            action: Arc::from(action),
+            depth: signal.depth,
+            signal_id: Arc::from(signal.id.to_string()),
+        }
+    }
 }
 
 /// Signal schema
@@ -404,9 +1083,70 @@ pub fn signal_schema() -> Schema {
         Field::new("market_price", DataType::Utf8, false),
         Field::new("edge", DataType::Utf8, false),
         Field::new("action", DataType::Utf8, false),
+        Field::new("raw_edge", DataType::Utf8, false),
+        Field::new("spread", DataType::Utf8, false),
+        Field::new("round_trip_edge", DataType::Utf8, false),
+        Field::new("depth_1c", DataType::Utf8, false),
+        Field::new("depth_2c", DataType::Utf8, false),
+        Field::new("depth_3c", DataType::Utf8, false),
+        Field::new("signal_id", DataType::Utf8, false),
     ])
 }
 
+/// Read signal records back from a batch in [`signal_schema`]
+///
+/// Signals from before the cost columns read their raw and round-trip
+/// edges as the edge and their spread as zero; those from before depth
+/// and ids, zero depth and an empty id.
+pub fn signals_from_batch(batch: &RecordBatch) -> anyhow::Result<Vec<SignalRecord>> {
+    use std::str::FromStr;
+
+    let columns = BatchColumns::new(batch, "signals", signal_schema(), SIGNAL_ADDED)?;
+    let strings = |name: &str| columns.required::<StringArray>(name);
+    let added = |name: &str| columns.optional::<StringArray>(name);
+    let timestamps = columns.required::<TimestampMicrosecondArray>("timestamp")?;
+    let (market_ids, sides, actions) =
+        (strings("market_id")?, strings("side")?, strings("action")?);
+    let fair_values = strings("fair_value")?;
+    let market_prices = strings("market_price")?;
+    let edges = strings("edge")?;
+    let (raw_edges, spreads) = (added("raw_edge")?, added("spread")?);
+    let round_trip_edges = added("round_trip_edge")?;
+    let depths = [added("depth_1c")?, added("depth_2c")?, added("depth_3c")?];
+    let signal_ids = added("signal_id")?;
+
+    let decimal = |column: &StringArray, row: usize| Decimal::from_str(column.value(row));
+    let or = |column: Option<&StringArray>, row: usize, default: Decimal| match column {
+        Some(column) if !column.is_null(row) => decimal(column, row),
+        _ => Ok(default),
+    };
+    let mut signals = Vec::with_capacity(columns.num_rows());
+    for row in 0..columns.num_rows() {
+        let edge = decimal(edges, row)?;
+        let [depth_1c, depth_2c, depth_3c] = depths;
+        signals.push(SignalRecord {
+            timestamp: DateTime::from_timestamp_micros(timestamps.value(row))
+                .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?,
+            market_id: Arc::from(market_ids.value(row)),
+            side: Arc::from(sides.value(row)),
+            fair_value: decimal(fair_values, row)?,
+            market_price: decimal(market_prices, row)?,
+            edge,
+            raw_edge: or(raw_edges, row, edge)?,
+            spread: or(spreads, row, Decimal::ZERO)?,
+            round_trip_edge: or(round_trip_edges, row, edge)?,
+            action: Arc::from(actions.value(row)),
+            depth: DepthProfile {
+                within_1c: or(depth_1c, row, Decimal::ZERO)?,
+                within_2c: or(depth_2c, row, Decimal::ZERO)?,
+                within_3c: or(depth_3c, row, Decimal::ZERO)?,
+            },
+            signal_id: Arc::from(signal_ids.map_or("", |ids| ids.value(row))),
+        });
+    }
+    Ok(signals)
+}
+
 impl ParquetWriter {
     /// Write signal records to a Parquet file
     pub fn write_signals(&self, path: &PathBuf, signals: &[SignalRecord]) -> anyhow::Result<()> {
@@ -414,51 +1154,7 @@ impl ParquetWriter {
             return Ok(());
         }
 
-        self.ensure_dir()?;
-
-        let schema = Arc::new(signal_schema());
-        let file = File::create(path)?;
-
-        let props = WriterProperties::builder()
-            .set_compression(Compression::SNAPPY)
-            .build();
-
-        let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(props))?;
-
-        let timestamps: Vec<i64> = signals
-            .iter()
-            .map(|s| s.timestamp.timestamp_micros())
-            .collect();
-        let market_ids: Vec<&str> = signals.iter().map(|s| s.market_id.as_ref()).collect();
-        let sides: Vec<&str> = signals.iter().map(|s| s.side.as_ref()).collect();
-        let fair_values: Vec<String> = signals.iter().map(|s| s.fair_value.to_string()).collect();
-        let market_prices: Vec<String> =
-            signals.iter().map(|s| s.market_price.to_string()).collect();
-        let edges: Vec<String> = signals.iter().map(|s| s.edge.to_string()).collect();
-        let actions: Vec<&str> = signals.iter().map(|s| s.action.as_ref()).collect();
-
-        let batch = RecordBatch::try_new(
-            schema,
-            vec![
-                Arc::new(TimestampMicrosecondArray::from(timestamps).with_timezone("UTC"))
-                    as ArrayRef,
-                Arc::new(StringArray::from(market_ids)) as ArrayRef,
-                Arc::new(StringArray::from(sides)) as ArrayRef,
-                Arc::new(StringArray::from(
-                    fair_values.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
-                )) as ArrayRef,
-                Arc::new(StringArray::from(
-                    market_prices.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
-                )) as ArrayRef,
-                Arc::new(StringArray::from(
-                    edges.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
-                )) as ArrayRef,
-                Arc::new(StringArray::from(actions)) as ArrayRef,
-            ],
-        )?;
-
-        writer.write(&batch)?;
-        writer.close()?;
+        self.write_batch(path, &signal_batch(signals)?)?;
 
         tracing::debug!(path = ?path, count = signals.len(), "Wrote signals to Parquet");
 
@@ -485,9 +1181,29 @@ impl ParquetWriter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
     use rust_decimal_macros::dec;
     use tempfile::TempDir;
 
+    proptest! {
+        #[test]
+        fn test_decimal_column_matches_display(
+            mantissa in any::<i128>().prop_map(|m| m >> 32),
+            scale in 0u32..=28,
+        ) {
+            let values = [
+                Decimal::from_i128_with_scale(mantissa, scale),
+                Decimal::from_i128_with_scale(mantissa % 1_000_000, scale),
+                -Decimal::from_i128_with_scale(0, scale),
+            ];
+            let column = decimal_column(&values, |v| Some(*v));
+            let column = column.as_any().downcast_ref::<StringArray>().unwrap();
+            for (i, value) in values.iter().enumerate() {
+                prop_assert_eq!(column.value(i), value.to_string());
+            }
+        }
+    }
+
     #[test]
     fn test_price_tick_schema() {
         let schema = price_tick_schema();
@@ -501,8 +1217,8 @@ mod tests {
     #[test]
     fn test_orderbook_schema() {
         let schema = orderbook_schema();
-        // 2 base fields + 5 levels * 4 fields each = 22 fields
-        assert_eq!(schema.fields().len(), 22);
+        // 2 base fields + 5 levels * 4 fields each + crossed flag + kind = 24 fields
+        assert_eq!(schema.fields().len(), 24);
     }
 
     #[test]
@@ -570,6 +1286,49 @@ mod tests {
         assert_eq!(read_ticks[1].price, dec!(42501.25));
     }
 
+    fn one_tick() -> Vec<PriceTickRecord> {
+        let now = Utc::now();
+        vec![PriceTickRecord {
+            timestamp: now,
+            symbol: Arc::from("BTCUSDT"),
+            price: dec!(42500.50),
+            exchange_ts: now,
+        }]
+    }
+
+    #[test]
+    fn test_config_fingerprint_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let fingerprint = ConfigFingerprint {
+            hash: "ab".repeat(32),
+            config: r#"{"feed":{"symbol":"BTCUSDT"}}"#.to_string(),
+        };
+        let writer = ParquetWriter::new(temp_dir.path().to_path_buf(), 3600)
+            .with_fingerprint(fingerprint.clone());
+
+        let path = temp_dir.path().join("price_ticks.parquet");
+        writer.write_price_ticks(&path, &one_tick()).unwrap();
+
+        let reader = ParquetReader::new(path.clone());
+        assert_eq!(reader.config_fingerprint().unwrap(), Some(fingerprint));
+        assert_eq!(reader.read_price_ticks().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_config_fingerprint_missing_in_older_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("old.parquet");
+        let schema = Arc::new(price_tick_schema());
+        let props = WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::SNAPPY)
+            .build();
+        let writer = ArrowWriter::try_new(File::create(&path).unwrap(), schema, Some(props));
+        writer.unwrap().close().unwrap();
+
+        assert_eq!(read_config_fingerprint(&path).unwrap(), None);
+        assert!(read_config_fingerprint(&temp_dir.path().join("missing.parquet")).is_err());
+    }
+
     #[test]
     fn test_write_empty_ticks() {
         let temp_dir = TempDir::new().unwrap();
@@ -630,20 +1389,204 @@ mod tests {
                 token_id: Arc::from("yes-token"),
                 bids: vec![(dec!(0.55), dec!(100)), (dec!(0.54), dec!(200))],
                 asks: vec![(dec!(0.56), dec!(150)), (dec!(0.57), dec!(250))],
+                crossed: false,
+                kind: BookUpdateKind::Snapshot,
             },
             OrderBookRecord {
                 timestamp: now,
                 token_id: Arc::from("no-token"),
                 bids: vec![(dec!(0.45), dec!(50))],
                 asks: vec![(dec!(0.46), dec!(75))],
+                crossed: false,
+                kind: BookUpdateKind::Delta,
             },
         ];
 
         let path = writer.file_path("orderbook", now);
         writer.write_orderbook_snapshots(&path, &snapshots).unwrap();
 
-        // Verify file was created
-        assert!(path.exists());
+        let read = ParquetReader::new(path).read_orderbooks().unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].bids, snapshots[0].bids);
+        assert_eq!(read[0].asks, snapshots[0].asks);
+        assert_eq!(read[1].kind, BookUpdateKind::Delta);
+        assert_eq!(&*read[1].token_id, "no-token");
+    }
+
+    #[test]
+    fn test_orderbook_kind_inferred_for_old_captures() {
+        let full: Vec<_> = (1..=5)
+            .map(|i| (Decimal::from(i) / dec!(100), dec!(10)))
+            .collect();
+        let records = vec![
+            OrderBookRecord {
+                timestamp: Utc::now(),
+                token_id: Arc::from("yes"),
+                bids: full,
+                asks: vec![],
+                crossed: false,
+                kind: BookUpdateKind::Delta,
+            },
+            OrderBookRecord {
+                timestamp: Utc::now(),
+                token_id: Arc::from("yes"),
+                bids: vec![(dec!(0.05), dec!(3))],
+                asks: vec![],
+                crossed: false,
+                kind: BookUpdateKind::Snapshot,
+            },
+        ];
+        let batch = orderbook_batch(&records).unwrap();
+        let without_kind = batch
+            .project(&(0..batch.num_columns() - 1).collect::<Vec<_>>())
+            .unwrap();
+
+        let kinds: Vec<_> = orderbooks_from_batch(&without_kind)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.kind)
+            .collect();
+        assert_eq!(kinds, vec![BookUpdateKind::Snapshot, BookUpdateKind::Delta]);
+
+        // Stamped with the current version, the column must be there
+        let metadata = HashMap::from([(
+            SCHEMA_VERSION_KEY.to_string(),
+            CAPTURE_SCHEMA_VERSION.to_string(),
+        )]);
+        let schema = without_kind.schema_ref().as_ref().clone();
+        let stamped = without_kind
+            .with_schema(Arc::new(schema.with_metadata(metadata)))
+            .unwrap();
+        let error = orderbooks_from_batch(&stamped).unwrap_err().to_string();
+        assert!(
+            error.contains("schema version 2 is missing column(s) kind"),
+            "{}",
+            error
+        );
+    }
+
+    fn fixture(name: &str) -> ParquetReader {
+        ParquetReader::new(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures/parquet")
+                .join(name),
+        )
+    }
+
+    #[test]
+    fn test_captures_of_each_schema_version_read() {
+        // Version 1 predates the stamp; version 2 has its columns in
+        // another order and some this build does not know
+        let ticks = |name| fixture(name).read_price_ticks().unwrap();
+        let (v1, v2) = (
+            ticks("price_ticks_v1.parquet"),
+            ticks("price_ticks_v2.parquet"),
+        );
+        assert_eq!(v1.len(), 2);
+        for (old, new) in v1.iter().zip(&v2) {
+            assert_eq!(
+                (old.timestamp, &old.symbol, old.price, old.exchange_ts),
+                (new.timestamp, &new.symbol, new.price, new.exchange_ts)
+            );
+        }
+        assert_eq!(v2[1].price, dec!(97001.25));
+
+        // Without `crossed` and `kind`: not crossed, and a partial book is
+        // a delta
+        let v1 = fixture("orderbook_v1.parquet").read_orderbooks().unwrap();
+        let v2 = fixture("orderbook_v2.parquet").read_orderbooks().unwrap();
+        assert_eq!((v1[0].crossed, v1[0].kind), (false, BookUpdateKind::Delta));
+        assert_eq!(v2[0].kind, BookUpdateKind::Snapshot);
+        assert_eq!((&v1[0].bids, &v1[0].asks), (&v2[0].bids, &v2[0].asks));
+        assert_eq!(
+            v2[0].asks,
+            vec![(dec!(0.52), dec!(80)), (dec!(0.53), dec!(200))]
+        );
+
+        // Without the cost, depth and id columns: edges as the edge,
+        // nothing else
+        let v1 = &fixture("signals_v1.parquet").read_signals().unwrap()[0];
+        let v2 = &fixture("signals_v2.parquet").read_signals().unwrap()[0];
+        assert_eq!(
+            (v1.edge, v1.raw_edge, v1.round_trip_edge),
+            (dec!(0.045), dec!(0.045), dec!(0.045))
+        );
+        assert_eq!(
+            (v1.spread, v1.depth, &*v1.signal_id),
+            (dec!(0), DepthProfile::default(), "")
+        );
+        assert_eq!(
+            (v2.raw_edge, v2.spread, v2.round_trip_edge),
+            (dec!(0.06), dec!(0.04), dec!(0.005))
+        );
+        assert_eq!((v2.depth.within_2c, &*v2.signal_id), (dec!(280), "sig-1"));
+        assert_eq!(
+            (&v1.market_id, v1.fair_value),
+            (&v2.market_id, v2.fair_value)
+        );
+    }
+
+    #[test]
+    fn test_file_missing_columns_is_refused_with_its_schema() {
+        let error = fixture("price_ticks_broken.parquet")
+            .read_price_ticks()
+            .unwrap_err();
+        let message = format!("{:#}", error);
+        assert!(
+            message.contains("price_ticks_broken.parquet"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains(
+                "price_ticks data of schema version 2 is missing column(s) price, exchange_ts"
+            ),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("it has timestamp: Timestamp("),
+            "{}",
+            message
+        );
+        assert!(message.ends_with("symbol: Utf8"), "{}", message);
+
+        // A known column of another type is refused too
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("token_id", DataType::Utf8, false),
+                Field::new("ts", DataType::Int64, false),
+                Field::new("price", DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(StringArray::from(vec!["yes"])),
+                Arc::new(Int64Array::from(vec![0])),
+                Arc::new(StringArray::from(vec!["0.5"])),
+            ],
+        )
+        .unwrap();
+        let error = price_history_from_batch(&batch).unwrap_err().to_string();
+        assert!(
+            error.starts_with("price_history column ts is Int64, expected Timestamp("),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_files_are_stamped_with_the_schema_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = ParquetWriter::new(temp_dir.path().to_path_buf(), 3600);
+        let path = writer.file_path("price_ticks", Utc::now());
+        writer.write_price_ticks(&path, &one_tick()).unwrap();
+
+        let batches = crate::data::read_batches(&path).unwrap();
+        let version = schema_version(batches[0].schema_ref().metadata()).unwrap();
+        assert_eq!(version, CAPTURE_SCHEMA_VERSION);
+        assert_eq!(
+            ParquetReader::new(path).read_price_ticks().unwrap().len(),
+            1
+        );
     }
 
     #[test]
@@ -667,6 +1610,8 @@ mod tests {
             token_id: Arc::from("test-token"),
             bids: vec![(dec!(0.50), dec!(100))],
             asks: vec![(dec!(0.52), dec!(100))],
+            crossed: false,
+            kind: BookUpdateKind::Snapshot,
         }];
 
         let path = writer.file_path("orderbook", now);
@@ -725,7 +1670,9 @@ mod tests {
     #[test]
     fn test_signal_schema() {
         let schema = signal_schema();
-        assert_eq!(schema.fields().len(), 7);
+        assert_eq!(schema.fields().len(), 14);
+        assert_eq!(schema.field(13).name(), "signal_id");
+        assert_eq!(schema.field(11).name(), "depth_2c");
         assert_eq!(schema.field(0).name(), "timestamp");
         assert_eq!(schema.field(1).name(), "market_id");
         assert_eq!(schema.field(2).name(), "side");
@@ -733,6 +1680,7 @@ mod tests {
         assert_eq!(schema.field(4).name(), "market_price");
         assert_eq!(schema.field(5).name(), "edge");
         assert_eq!(schema.field(6).name(), "action");
+        assert_eq!(schema.field(9).name(), "round_trip_edge");
     }
 
     #[test]
@@ -749,7 +1697,12 @@ mod tests {
                 fair_value: dec!(0.55),
                 market_price: dec!(0.50),
                 edge: dec!(0.05),
+                raw_edge: dec!(0.05),
+                spread: dec!(0.02),
+                round_trip_edge: dec!(0.05),
                 action: Arc::from("BUY"),
+                depth: DepthProfile::default(),
+                signal_id: Arc::from("signal-1"),
             },
             SignalRecord {
                 timestamp: now,
@@ -758,7 +1711,12 @@ mod tests {
                 fair_value: dec!(0.45),
                 market_price: dec!(0.50),
                 edge: dec!(-0.05),
+                raw_edge: dec!(-0.05),
+                spread: dec!(0.02),
+                round_trip_edge: dec!(-0.05),
                 action: Arc::from("HOLD"),
+                depth: DepthProfile::default(),
+                signal_id: Arc::from("signal-1"),
             },
         ];
 
@@ -791,7 +1749,12 @@ mod tests {
             fair_value: dec!(0.55),
             market_price: dec!(0.50),
             edge: dec!(0.05),
+            raw_edge: dec!(0.05),
+            spread: dec!(0.02),
+            round_trip_edge: dec!(0.05),
             action: Arc::from("BUY"),
+            depth: DepthProfile::default(),
+            signal_id: Arc::from("signal-1"),
         }];
 
         let path = writer.file_path("signals", now);
@@ -839,6 +1802,8 @@ mod tests {
             token_id: Arc::from("test"),
             bids: vec![(dec!(0.50), dec!(100))],
             asks: vec![(dec!(0.52), dec!(100))],
+            crossed: false,
+            kind: BookUpdateKind::Snapshot,
         };
         let cloned = record.clone();
         assert_eq!(record.token_id, cloned.token_id);
@@ -854,10 +1819,91 @@ mod tests {
             fair_value: dec!(0.55),
             market_price: dec!(0.50),
             edge: dec!(0.05),
+            raw_edge: dec!(0.05),
+            spread: dec!(0.02),
+            round_trip_edge: dec!(0.05),
             action: Arc::from("BUY"),
+            depth: DepthProfile::default(),
+            signal_id: Arc::from("signal-1"),
         };
         let cloned = record.clone();
         assert_eq!(record.market_id, cloned.market_id);
         assert_eq!(record.fair_value, cloned.fair_value);
     }
+
+    #[test]
+    fn test_signal_record_from_signal_keeps_both_edges() {
+        let market = crate::market::Market {
+            condition_id: "market-123".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time: Utc::now(),
+            close_time: Utc::now() + Duration::minutes(15),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        let signal = Signal::new(
+            market,
+            crate::signal::Side::Yes,
+            dec!(0.60),
+            dec!(0.50),
+            dec!(0.09),
+            dec!(0.8),
+            crate::signal::SignalReason::SpotDivergence,
+        )
+        .with_spread(dec!(0.04));
+
+        let record = SignalRecord::from_signal(&signal, "traded");
+        assert_eq!(record.side.as_ref(), "YES");
+        assert_eq!(record.raw_edge, dec!(0.10));
+        assert_eq!(record.edge, dec!(0.09));
+        assert_eq!(record.spread, dec!(0.04));
+        assert_eq!(record.round_trip_edge, dec!(0.07));
+
+        let batch = signal_batch(&[record]).unwrap();
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .value(0)
+                .to_string()
+        };
+        assert_eq!(column("raw_edge"), "0.10");
+        assert_eq!(column("round_trip_edge"), "0.07");
+    }
+
+    #[test]
+    fn test_signal_outcome_batch_keeps_missing_checkpoints_null() {
+        let outcome = SignalOutcome {
+            signal_id: uuid::Uuid::new_v4(),
+            market_id: "market-123".to_string(),
+            side: crate::signal::Side::No,
+            action: "blocked".to_string(),
+            emitted_at: Utc::now(),
+            entry_price: dec!(0.50),
+            expected_price: dec!(0.40),
+            checkpoints: [Some(dec!(0.45)), None, None],
+            close_price: Some(dec!(0.42)),
+            max_favorable: dec!(0.08),
+            max_adverse: dec!(0.01),
+            time_to_converge_secs: None,
+        };
+        let batch = signal_outcome_batch(&[outcome]).unwrap();
+
+        assert_eq!(batch.schema().as_ref(), &signal_outcome_schema());
+        assert_eq!(batch.column_by_name("price_30s").unwrap().null_count(), 0);
+        assert_eq!(batch.column_by_name("price_180s").unwrap().null_count(), 1);
+        assert_eq!(
+            batch
+                .column_by_name("time_to_converge_secs")
+                .unwrap()
+                .null_count(),
+            1
+        );
+    }
 }
@@ -0,0 +1,624 @@
+//! Disk-backed capture queue
+//!
+//! With `data.durable_queue` set, the recorder appends every price tick and
+//! book snapshot to a segmented log under `<output_dir>/queue/<prefix>/`
+//! instead of a bounded channel, so a slow disk or a stalled writer backs
+//! records up on disk rather than dropping them. Each record is encoded as
+//! MessagePack and framed with its sequence number and a checksum, appends are fsynced `sync_every` at
+//! a time, and only fsynced records are handed to the writer.
+//!
+//! The writer commits the last sequence number it has written to a capture
+//! file. Before writing a file it stages the file's path with that number,
+//! so a writer killed between the file landing and the commit is found to
+//! have delivered it when the queue is next opened. Records past the commit
+//! are replayed on restart and records up to it are skipped, so each lands
+//! in exactly one capture file. Segments wholly committed are removed.
+
+use crate::duration::{DurationConfig, Millis};
+use crate::telemetry::{set_capture_queue_depth, set_capture_queue_segments};
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// Directory under the capture output directory holding the queues
+pub const QUEUE_DIR: &str = "queue";
+
+/// Default bytes a segment grows to before the next is started
+pub const DEFAULT_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Default appends between fsyncs
+pub const DEFAULT_SYNC_EVERY: usize = 256;
+
+/// Default longest wait before appends short of a batch are fsynced, in
+/// milliseconds
+pub const DEFAULT_SYNC_INTERVAL_MS: u64 = 200;
+
+const SEGMENT_EXTENSION: &str = "seg";
+const CURSOR_FILE: &str = "cursor";
+const STAGED_FILE: &str = "staged.json";
+
+/// Length, checksum and sequence number ahead of each record
+const FRAME_HEADER_BYTES: usize = 16;
+
+/// Segment and fsync settings of the durable capture queue, under
+/// `[data.queue]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct QueueConfig {
+    /// Bytes a segment grows to before the next is started
+    #[serde(default = "default_segment_bytes")]
+    pub segment_bytes: u64,
+    /// Appends between fsyncs
+    #[serde(default = "default_sync_every")]
+    pub sync_every: usize,
+    /// Longest appends short of a batch wait to be fsynced and written
+    #[serde(default = "default_sync_interval_ms")]
+    pub sync_interval_ms: DurationConfig<Millis>,
+}
+
+fn default_segment_bytes() -> u64 {
+    DEFAULT_SEGMENT_BYTES
+}
+
+fn default_sync_every() -> usize {
+    DEFAULT_SYNC_EVERY
+}
+
+fn default_sync_interval_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_SYNC_INTERVAL_MS)
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            segment_bytes: default_segment_bytes(),
+            sync_every: default_sync_every(),
+            sync_interval_ms: default_sync_interval_ms(),
+        }
+    }
+}
+
+impl QueueConfig {
+    /// Start a segment every `segment_bytes` and fsync every `sync_every`
+    /// appends
+    pub fn with_segments(mut self, segment_bytes: u64, sync_every: usize) -> Self {
+        self.segment_bytes = segment_bytes;
+        self.sync_every = sync_every.max(1);
+        self
+    }
+}
+
+/// A capture file that, once in place, delivered records up to `seq`
+#[derive(Debug, Serialize, Deserialize)]
+struct Staged {
+    seq: u64,
+    path: PathBuf,
+}
+
+/// The segment being appended to
+struct Appender {
+    file: BufWriter<File>,
+    bytes: u64,
+    unsynced: usize,
+}
+
+/// Segmented on-disk log of capture records, appended by the recorder and
+/// drained by its writer
+pub struct DurableQueue<T> {
+    dir: PathBuf,
+    prefix: &'static str,
+    config: QueueConfig,
+    appender: Mutex<Appender>,
+    /// Last sequence number appended
+    appended: AtomicU64,
+    /// Last sequence number fsynced, which the writer may read up to
+    synced: AtomicU64,
+    /// Last sequence number delivered to a capture file
+    committed: AtomicU64,
+    segments: AtomicUsize,
+    closed: AtomicBool,
+    notify: Notify,
+    records: PhantomData<fn(T) -> T>,
+}
+
+impl<T> DurableQueue<T> {
+    /// Open the `prefix` queue under `output_dir`, recovering a staged
+    /// file and cutting off a record torn by a crash mid-append
+    pub fn open(
+        output_dir: &Path,
+        prefix: &'static str,
+        config: QueueConfig,
+    ) -> anyhow::Result<Self> {
+        let dir = output_dir.join(QUEUE_DIR).join(prefix);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("cannot create capture queue {}", dir.display()))?;
+
+        let mut committed = read_cursor(&dir)?;
+        if let Some(staged) = read_staged(&dir)? {
+            if staged.seq > committed && staged.path.exists() {
+                committed = staged.seq;
+                write_cursor(&dir, committed)?;
+            }
+            fs::remove_file(dir.join(STAGED_FILE))?;
+        }
+
+        let segments = list_segments(&dir)?;
+        let (active, appended, bytes) = match segments.last() {
+            Some((first, path)) => {
+                let (last, valid) = scan_segment(path, *first)?;
+                let file = OpenOptions::new().write(true).open(path)?;
+                if file.metadata()?.len() > valid {
+                    tracing::warn!(
+                        segment = %path.display(),
+                        bytes = file.metadata()?.len() - valid,
+                        "Cutting off a torn capture queue record"
+                    );
+                    file.set_len(valid)?;
+                    file.sync_all()?;
+                }
+                drop(file);
+                let file = OpenOptions::new().append(true).open(path)?;
+                (file, last.max(committed), valid)
+            }
+            None => {
+                let file = File::create(segment_path(&dir, committed + 1))?;
+                (file, committed, 0)
+            }
+        };
+
+        let queue = Self {
+            dir,
+            prefix,
+            config,
+            appender: Mutex::new(Appender {
+                file: BufWriter::new(active),
+                bytes,
+                unsynced: 0,
+            }),
+            appended: AtomicU64::new(appended),
+            synced: AtomicU64::new(appended),
+            committed: AtomicU64::new(committed),
+            segments: AtomicUsize::new(segments.len().max(1)),
+            closed: AtomicBool::new(false),
+            notify: Notify::new(),
+            records: PhantomData,
+        };
+        queue.remove_committed_segments()?;
+        queue.report();
+        Ok(queue)
+    }
+
+    /// Fsync appends short of a batch, handing them to the writer
+    pub fn sync(&self) -> anyhow::Result<()> {
+        let mut appender = self.appender.lock().unwrap_or_else(|e| e.into_inner());
+        self.sync_locked(&mut appender)
+    }
+
+    /// Fsync what is appended and stop the writer once it has drained it
+    pub fn close(&self) -> anyhow::Result<()> {
+        let synced = self.sync();
+        self.closed.store(true, Ordering::Release);
+        self.notify.notify_one();
+        synced
+    }
+
+    /// Whether the queue has been closed
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Last sequence number the writer may read up to
+    pub fn synced(&self) -> u64 {
+        self.synced.load(Ordering::Acquire)
+    }
+
+    /// Last sequence number delivered to a capture file
+    pub fn committed(&self) -> u64 {
+        self.committed.load(Ordering::Acquire)
+    }
+
+    /// Records appended and not yet delivered
+    pub fn depth(&self) -> u64 {
+        self.appended
+            .load(Ordering::Acquire)
+            .saturating_sub(self.committed())
+    }
+
+    /// Segment files on disk
+    pub fn segment_count(&self) -> usize {
+        self.segments.load(Ordering::Relaxed)
+    }
+
+    /// Wait until more records are fsynced or the queue is closed
+    pub async fn notified(&self) {
+        self.notify.notified().await
+    }
+
+    /// Read records from just past the commit
+    pub fn reader(&self) -> QueueReader<T> {
+        QueueReader {
+            dir: self.dir.clone(),
+            next: self.committed() + 1,
+            segment: None,
+            records: PhantomData,
+        }
+    }
+
+    /// Record that the capture file about to be written at `path` delivers
+    /// records up to `seq`
+    pub fn stage(&self, seq: u64, path: &Path) -> anyhow::Result<()> {
+        let staged = Staged {
+            seq,
+            path: path.to_path_buf(),
+        };
+        write_atomic(&self.dir.join(STAGED_FILE), &serde_json::to_vec(&staged)?)
+    }
+
+    /// Record that records up to `seq` are delivered, removing the
+    /// segments holding only those
+    pub fn commit(&self, seq: u64) -> anyhow::Result<()> {
+        write_cursor(&self.dir, seq)?;
+        self.committed.store(seq, Ordering::Release);
+        if let Err(e) = fs::remove_file(self.dir.join(STAGED_FILE)) {
+            if e.kind() != ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        self.remove_committed_segments()?;
+        self.report();
+        Ok(())
+    }
+
+    fn sync_locked(&self, appender: &mut Appender) -> anyhow::Result<()> {
+        if appender.unsynced == 0 {
+            return Ok(());
+        }
+        appender.file.flush()?;
+        appender.file.get_ref().sync_data()?;
+        appender.unsynced = 0;
+        self.synced
+            .store(self.appended.load(Ordering::Acquire), Ordering::Release);
+        self.notify.notify_one();
+        self.report();
+        Ok(())
+    }
+
+    /// Seal the active segment and start one whose first record is `seq`
+    fn roll(&self, appender: &mut Appender, seq: u64) -> anyhow::Result<()> {
+        self.sync_locked(appender)?;
+        let file = File::create(segment_path(&self.dir, seq))?;
+        appender.file = BufWriter::new(file);
+        appender.bytes = 0;
+        self.segments.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Remove every segment but the last whose records are all committed
+    fn remove_committed_segments(&self) -> anyhow::Result<()> {
+        let segments = list_segments(&self.dir)?;
+        let committed = self.committed();
+        let mut kept = segments.len();
+        for pair in segments.windows(2) {
+            let ((_, path), (next_first, _)) = (&pair[0], &pair[1]);
+            if *next_first <= committed + 1 {
+                fs::remove_file(path)?;
+                kept -= 1;
+            }
+        }
+        self.segments.store(kept.max(1), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn report(&self) {
+        set_capture_queue_depth(self.prefix, self.depth());
+        set_capture_queue_segments(self.prefix, self.segment_count());
+    }
+}
+
+impl<T: Serialize> DurableQueue<T> {
+    /// Append `record`, returning its sequence number; it reaches the
+    /// writer once fsynced
+    pub fn append(&self, record: &T) -> anyhow::Result<u64> {
+        let payload = rmp_serde::to_vec(record)?;
+        let mut appender = self.appender.lock().unwrap_or_else(|e| e.into_inner());
+        let seq = self.appended.load(Ordering::Acquire) + 1;
+        if appender.bytes >= self.config.segment_bytes {
+            self.roll(&mut appender, seq)?;
+        }
+
+        let mut frame = Vec::with_capacity(FRAME_HEADER_BYTES + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&checksum(seq, &payload).to_le_bytes());
+        frame.extend_from_slice(&seq.to_le_bytes());
+        frame.extend_from_slice(&payload);
+        appender.file.write_all(&frame)?;
+        appender.bytes += frame.len() as u64;
+        appender.unsynced += 1;
+        self.appended.store(seq, Ordering::Release);
+
+        if appender.unsynced >= self.config.sync_every {
+            self.sync_locked(&mut appender)?;
+        }
+        Ok(seq)
+    }
+}
+
+/// Reads a queue's records in sequence order
+pub struct QueueReader<T> {
+    dir: PathBuf,
+    next: u64,
+    segment: Option<(u64, BufReader<File>)>,
+    records: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> QueueReader<T> {
+    /// Sequence number of the next record read
+    pub fn next_seq(&self) -> u64 {
+        self.next
+    }
+
+    /// Up to `max` records, through at most sequence number `upto`
+    pub fn read(&mut self, upto: u64, max: usize) -> anyhow::Result<Vec<(u64, T)>> {
+        let mut records = Vec::new();
+        while records.len() < max && self.next <= upto {
+            let (first, reader) = match self.segment.as_mut() {
+                Some((first, reader)) => (*first, reader),
+                None => {
+                    let (first, path) = list_segments(&self.dir)?
+                        .into_iter()
+                        .rev()
+                        .find(|(first, _)| *first <= self.next)
+                        .with_context(|| {
+                            format!("capture queue has no segment holding {}", self.next)
+                        })?;
+                    let (_, reader) = self
+                        .segment
+                        .insert((first, BufReader::new(File::open(path)?)));
+                    (first, reader)
+                }
+            };
+
+            match read_frame(reader)? {
+                Some((seq, payload)) => {
+                    if seq >= self.next {
+                        records.push((seq, rmp_serde::from_slice(&payload)?));
+                        self.next = seq + 1;
+                    }
+                }
+                None => {
+                    // The end of a sealed segment; go on to the next one
+                    let next = list_segments(&self.dir)?
+                        .into_iter()
+                        .find(|(f, _)| *f > first)
+                        .with_context(|| {
+                            format!("capture queue ends before record {}", self.next)
+                        })?;
+                    self.segment = Some((next.0, BufReader::new(File::open(next.1)?)));
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// The next frame's sequence number and payload, or `None` at the end of
+/// the segment; a torn or corrupt frame is an error
+fn read_frame(reader: &mut impl Read) -> anyhow::Result<Option<(u64, Vec<u8>)>> {
+    let mut header = [0u8; FRAME_HEADER_BYTES];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let (len, crc, seq) = parse_header(&header);
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    if checksum(seq, &payload) != crc {
+        anyhow::bail!("capture queue record {} fails its checksum", seq);
+    }
+    Ok(Some((seq, payload)))
+}
+
+/// The last sequence number in a segment and the length of its valid
+/// frames; an empty segment ends just before `first`
+fn scan_segment(path: &Path, first: u64) -> anyhow::Result<(u64, u64)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let (mut last, mut valid) = (first - 1, 0u64);
+    loop {
+        let mut header = [0u8; FRAME_HEADER_BYTES];
+        if reader.read_exact(&mut header).is_err() {
+            break;
+        }
+        let (len, crc, seq) = parse_header(&header);
+        let mut payload = vec![0u8; len];
+        if reader.read_exact(&mut payload).is_err() || checksum(seq, &payload) != crc {
+            break;
+        }
+        last = seq;
+        valid += (FRAME_HEADER_BYTES + len) as u64;
+    }
+    Ok((last, valid))
+}
+
+fn parse_header(header: &[u8; FRAME_HEADER_BYTES]) -> (usize, u32, u64) {
+    let len = u32::from_le_bytes(header[0..4].try_into().unwrap_or_default()) as usize;
+    let crc = u32::from_le_bytes(header[4..8].try_into().unwrap_or_default());
+    let seq = u64::from_le_bytes(header[8..16].try_into().unwrap_or_default());
+    (len, crc, seq)
+}
+
+fn checksum(seq: u64, payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&seq.to_le_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+fn segment_path(dir: &Path, first: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", first, SEGMENT_EXTENSION))
+}
+
+/// Segments in the queue with their first sequence numbers, in order
+fn list_segments(dir: &Path) -> anyhow::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+        if let Some(first) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse().ok())
+        {
+            segments.push((first, path));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+fn read_cursor(dir: &Path) -> anyhow::Result<u64> {
+    match fs::read_to_string(dir.join(CURSOR_FILE)) {
+        Ok(text) => text
+            .trim()
+            .parse()
+            .with_context(|| format!("unreadable capture queue cursor in {}", dir.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_cursor(dir: &Path, seq: u64) -> anyhow::Result<()> {
+    write_atomic(&dir.join(CURSOR_FILE), seq.to_string().as_bytes())
+}
+
+fn read_staged(dir: &Path) -> anyhow::Result<Option<Staged>> {
+    match fs::read(dir.join(STAGED_FILE)) {
+        // A staged file torn by a crash was never written past
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes).ok()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace `path` with `bytes` whole
+fn write_atomic(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn open(dir: &Path, config: QueueConfig) -> DurableQueue<u64> {
+        DurableQueue::open(dir, "test", config).unwrap()
+    }
+
+    #[test]
+    fn test_records_read_back_in_order_across_segments() {
+        let dir = TempDir::new().unwrap();
+        let config = QueueConfig::default().with_segments(64, 10);
+        let queue = open(dir.path(), config.clone());
+        for value in 1..=100u64 {
+            assert_eq!(queue.append(&(value * 10)).unwrap(), value);
+        }
+        // Only fsynced records are handed on
+        assert!(queue.synced() < 100);
+        queue.sync().unwrap();
+        assert_eq!(queue.synced(), 100);
+        assert!(queue.segment_count() > 5);
+
+        let mut reader = queue.reader();
+        let first: Vec<_> = reader.read(100, 60).unwrap();
+        assert_eq!(first.len(), 60);
+        assert_eq!(first[59], (60, 600));
+        queue.commit(60).unwrap();
+        assert_eq!(queue.depth(), 40);
+        let remaining = list_segments(&queue.dir).unwrap();
+        assert!(remaining[0].0 <= 61 && remaining[1].0 > 61);
+        drop(queue);
+
+        // Reopened, reading resumes past the commit and numbering goes on
+        let queue = open(dir.path(), config);
+        let rest = queue.reader().read(queue.synced(), usize::MAX).unwrap();
+        let seqs: Vec<u64> = rest.iter().map(|(seq, _)| *seq).collect();
+        assert_eq!(seqs, (61..=100).collect::<Vec<_>>());
+        assert_eq!(queue.append(&0).unwrap(), 101);
+    }
+
+    #[test]
+    fn test_torn_tail_is_cut_off_on_open() {
+        let dir = TempDir::new().unwrap();
+        let queue = open(dir.path(), QueueConfig::default());
+        for value in 1..=10u64 {
+            queue.append(&value).unwrap();
+        }
+        queue.close().unwrap();
+        let (_, segment) = list_segments(&queue.dir).unwrap().pop().unwrap();
+        drop(queue);
+
+        // A record half written when the process died
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[8, 0, 0, 0, 1, 2, 3]).unwrap();
+        drop(file);
+
+        let queue = open(dir.path(), QueueConfig::default());
+        assert_eq!(queue.append(&11).unwrap(), 11);
+        queue.sync().unwrap();
+        let values: Vec<u64> = queue
+            .reader()
+            .read(queue.synced(), usize::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        assert_eq!(values, (1..=11).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_staged_file_in_place_counts_as_delivered() {
+        let dir = TempDir::new().unwrap();
+        let queue = open(dir.path(), QueueConfig::default());
+        for value in 1..=5u64 {
+            queue.append(&value).unwrap();
+        }
+        queue.sync().unwrap();
+
+        // Killed before the file landed: nothing is delivered
+        let capture = dir.path().join("price_ticks_20240101_000000.parquet");
+        queue.stage(3, &capture).unwrap();
+        drop(queue);
+        let queue = open(dir.path(), QueueConfig::default());
+        assert_eq!(queue.committed(), 0);
+
+        // Killed after it landed but before the commit
+        queue.stage(3, &capture).unwrap();
+        fs::write(&capture, b"written").unwrap();
+        drop(queue);
+        let queue = open(dir.path(), QueueConfig::default());
+        assert_eq!(queue.committed(), 3);
+        assert_eq!(queue.reader().next_seq(), 4);
+        assert!(!dir
+            .path()
+            .join(QUEUE_DIR)
+            .join("test")
+            .join(STAGED_FILE)
+            .exists());
+    }
+}
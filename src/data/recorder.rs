@@ -1,13 +1,41 @@
 //! Data recorder for tick capture
 
-use super::parquet::{OrderBookRecord, ParquetWriter, PriceTickRecord};
+use super::dedup::{BookDedup, BookDedupConfig};
+use super::encoding::ParquetTuning;
+use super::parquet::{
+    orderbook_batch, price_tick_batch, signal_outcome_batch, OrderBookRecord, PriceTickRecord,
+};
+use super::queue::{DurableQueue, QueueConfig};
+use super::sink::{capture_path, sink_for, DataFormat};
 use crate::feed::PriceTick;
-use crate::orderbook::OrderBook;
-use chrono::{Duration, Utc};
+use crate::orderbook::{BookUpdateKind, OrderBook};
+use crate::signal::SignalOutcome;
+use crate::supervisor::{RestartPolicy, Supervisor};
+use crate::telemetry::{instrumented_channel, EventCode, InstrumentedSender, RecorderBuffers};
+use crate::telemetry::{
+    record_capture_book_suppressed, record_capture_queue_replayed, record_data_bytes_written,
+};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Mutex};
+
+/// Supervised component writing price ticks
+pub const PRICE_WRITER_COMPONENT: &str = "recorder.prices";
+
+/// Supervised component writing order books
+pub const ORDERBOOK_WRITER_COMPONENT: &str = "recorder.orderbooks";
+
+/// Default seconds a capture file is written before rotating
+pub const DEFAULT_ROTATION_INTERVAL_SECS: u64 = 3600;
+
+/// Default longest seconds between flushes
+pub const DEFAULT_FLUSH_INTERVAL_SECS: u64 = 60;
 
 /// Configuration for data recording
 #[derive(Debug, Clone)]
@@ -20,15 +48,70 @@ pub struct RecorderConfig {
     pub buffer_size: usize,
     /// Maximum time between flushes
     pub flush_interval_secs: u64,
+    /// File encoding
+    pub format: DataFormat,
+    /// Encoding overrides keyed by file prefix
+    pub prefix_formats: HashMap<String, DataFormat>,
+    /// Parquet writer settings
+    pub parquet: ParquetTuning,
+    /// Disk-backed queue between recording and the writers; `None` keeps
+    /// the bounded in-memory channels, which drop records while full
+    pub durable_queue: Option<QueueConfig>,
+    /// Skipping of order books unchanged since the token's last row
+    pub dedup: BookDedupConfig,
+}
+
+impl RecorderConfig {
+    /// Set the default encoding and per-prefix overrides
+    pub fn with_formats(
+        mut self,
+        format: DataFormat,
+        prefix_formats: HashMap<String, DataFormat>,
+    ) -> Self {
+        self.format = format;
+        self.prefix_formats = prefix_formats;
+        self
+    }
+
+    /// Set the Parquet writer settings
+    pub fn with_parquet(mut self, parquet: ParquetTuning) -> Self {
+        self.parquet = parquet;
+        self
+    }
+
+    /// Put records through a disk-backed queue with `queue` settings
+    pub fn with_durable_queue(mut self, queue: Option<QueueConfig>) -> Self {
+        self.durable_queue = queue;
+        self
+    }
+
+    /// Skip unchanged order books as `dedup` directs
+    pub fn with_dedup(mut self, dedup: BookDedupConfig) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Encoding for files with `prefix`
+    pub fn format_for(&self, prefix: &str) -> DataFormat {
+        self.prefix_formats
+            .get(prefix)
+            .copied()
+            .unwrap_or(self.format)
+    }
 }
 
 impl Default for RecorderConfig {
     fn default() -> Self {
         Self {
             output_dir: PathBuf::from("./data"),
-            rotation_interval_secs: 3600, // 1 hour
+            rotation_interval_secs: DEFAULT_ROTATION_INTERVAL_SECS,
             buffer_size: 1000,
-            flush_interval_secs: 60,
+            flush_interval_secs: DEFAULT_FLUSH_INTERVAL_SECS,
+            format: DataFormat::default(),
+            prefix_formats: HashMap::new(),
+            parquet: ParquetTuning::default(),
+            durable_queue: None,
+            dedup: BookDedupConfig::default(),
         }
     }
 }
@@ -42,6 +125,15 @@ pub struct AtomicRecorderStats {
     pub orderbook_updates_written: AtomicU64,
     pub files_written: AtomicU64,
     pub channel_drops: AtomicU64,
+    pub records_skipped_low_disk: AtomicU64,
+    /// Records in the price writer's buffer
+    pub price_buffered: AtomicUsize,
+    /// Records in the orderbook writer's buffer
+    pub orderbook_buffered: AtomicUsize,
+    /// Records a writer read again from the durable queue after a restart
+    pub records_replayed: AtomicU64,
+    /// Order books not written for repeating the token's last row
+    pub orderbook_duplicates_suppressed: AtomicU64,
 }
 
 impl AtomicRecorderStats {
@@ -54,6 +146,27 @@ impl AtomicRecorderStats {
             orderbook_updates_written: self.orderbook_updates_written.load(Ordering::Relaxed),
             files_written: self.files_written.load(Ordering::Relaxed),
             channel_drops: self.channel_drops.load(Ordering::Relaxed),
+            records_skipped_low_disk: self.records_skipped_low_disk.load(Ordering::Relaxed),
+            records_replayed: self.records_replayed.load(Ordering::Relaxed),
+            orderbook_duplicates_suppressed: self
+                .orderbook_duplicates_suppressed
+                .load(Ordering::Relaxed),
+        }
+    }
+
+    /// Received, written and buffered counters of the writer for `prefix`
+    fn writer_counters(&self, prefix: &str) -> (&AtomicU64, &AtomicU64, &AtomicUsize) {
+        match prefix {
+            "orderbook" => (
+                &self.orderbook_updates_received,
+                &self.orderbook_updates_written,
+                &self.orderbook_buffered,
+            ),
+            _ => (
+                &self.price_ticks_received,
+                &self.price_ticks_written,
+                &self.price_buffered,
+            ),
         }
     }
 }
@@ -67,53 +180,188 @@ pub struct RecorderStats {
     pub orderbook_updates_written: u64,
     pub files_written: u64,
     pub channel_drops: u64,
+    /// Records dropped while recording was paused for low disk space
+    pub records_skipped_low_disk: u64,
+    /// Records a writer read again from the durable queue after a restart
+    pub records_replayed: u64,
+    /// Order books not written for repeating the token's last row
+    pub orderbook_duplicates_suppressed: u64,
 }
 
-/// Records market data to Parquet files
+/// Records market data to capture files
 pub struct DataRecorder {
     config: RecorderConfig,
-    price_tx: mpsc::Sender<PriceTickRecord>,
-    orderbook_tx: mpsc::Sender<OrderBookRecord>,
+    price_tx: Intake<PriceTickRecord>,
+    orderbook_tx: Intake<OrderBookRecord>,
     stats: Arc<AtomicRecorderStats>,
+    paused: Arc<AtomicBool>,
+    dedup: std::sync::Mutex<BookDedup>,
 }
 
 impl DataRecorder {
     /// Create a new data recorder
+    ///
+    /// # Panics
+    ///
+    /// If the config asks for a durable queue that cannot be opened; use
+    /// [`DataRecorder::try_new`] to handle that.
     pub fn new(config: RecorderConfig) -> Self {
-        let (price_tx, price_rx) = mpsc::channel(10_000);
-        let (orderbook_tx, orderbook_rx) = mpsc::channel(10_000);
+        Self::with_supervisor(config, &Supervisor::new())
+    }
+
+    /// Create a new data recorder, opening its durable queue if configured
+    pub fn try_new(config: RecorderConfig) -> anyhow::Result<Self> {
+        Self::try_with_supervisor(config, &Supervisor::new())
+    }
+
+    /// Create a new data recorder whose writers report to `supervisor`
+    ///
+    /// A writer that panics is restarted with backoff on the same channel;
+    /// only its unflushed buffer is lost.
+    ///
+    /// # Panics
+    ///
+    /// If the config asks for a durable queue that cannot be opened; use
+    /// [`DataRecorder::try_with_supervisor`] to handle that.
+    pub fn with_supervisor(config: RecorderConfig, supervisor: &Supervisor) -> Self {
+        Self::try_with_supervisor(config, supervisor)
+            .unwrap_or_else(|e| panic!("cannot open the durable capture queue: {:#}", e))
+    }
+
+    /// Create a new data recorder whose writers report to `supervisor`,
+    /// opening its durable queue if configured
+    ///
+    /// A restarted writer on a durable queue loses nothing: it replays the
+    /// records its predecessor had not written to a file.
+    pub fn try_with_supervisor(
+        config: RecorderConfig,
+        supervisor: &Supervisor,
+    ) -> anyhow::Result<Self> {
+        if config.durable_queue.is_some() {
+            return Self::with_queues(config, supervisor);
+        }
+        let (price_tx, price_rx) = instrumented_channel("recorder_price", 10_000);
+        let (orderbook_tx, orderbook_rx) = instrumented_channel("recorder_orderbook", 10_000);
         let stats = Arc::new(AtomicRecorderStats::default());
+        let paused = Arc::new(AtomicBool::new(false));
 
         // Spawn price tick writer
-        let price_writer =
-            ParquetWriter::new(config.output_dir.clone(), config.rotation_interval_secs);
+        let price_rx = Arc::new(Mutex::new(price_rx));
         let price_stats = stats.clone();
         let price_config = config.clone();
-        tokio::spawn(async move {
-            Self::run_price_writer(price_rx, price_writer, price_config, price_stats).await;
-        });
+        let price_paused = paused.clone();
+        supervisor.spawn(
+            PRICE_WRITER_COMPONENT,
+            RestartPolicy::backoff(),
+            move || {
+                let rx = price_rx.clone();
+                let config = price_config.clone();
+                let stats = price_stats.clone();
+                let paused = price_paused.clone();
+                async move {
+                    let mut rx = rx.lock().await;
+                    Self::run_price_writer(&mut rx, config, stats, paused).await;
+                    Ok(())
+                }
+            },
+        );
 
         // Spawn orderbook writer
-        let orderbook_writer =
-            ParquetWriter::new(config.output_dir.clone(), config.rotation_interval_secs);
+        let orderbook_rx = Arc::new(Mutex::new(orderbook_rx));
         let orderbook_stats = stats.clone();
         let orderbook_config = config.clone();
-        tokio::spawn(async move {
-            Self::run_orderbook_writer(
-                orderbook_rx,
-                orderbook_writer,
-                orderbook_config,
-                orderbook_stats,
-            )
-            .await;
-        });
+        let orderbook_paused = paused.clone();
+        supervisor.spawn(
+            ORDERBOOK_WRITER_COMPONENT,
+            RestartPolicy::backoff(),
+            move || {
+                let rx = orderbook_rx.clone();
+                let config = orderbook_config.clone();
+                let stats = orderbook_stats.clone();
+                let paused = orderbook_paused.clone();
+                async move {
+                    let mut rx = rx.lock().await;
+                    Self::run_orderbook_writer(&mut rx, config, stats, paused).await;
+                    Ok(())
+                }
+            },
+        );
 
-        Self {
+        Ok(Self {
+            price_tx: Intake::Channel(price_tx),
+            orderbook_tx: Intake::Channel(orderbook_tx),
+            dedup: std::sync::Mutex::new(BookDedup::new(config.dedup.clone())),
             config,
+            stats,
+            paused,
+        })
+    }
+
+    /// Create a recorder appending to durable queues under its output
+    /// directory, each drained by a supervised writer
+    fn with_queues(config: RecorderConfig, supervisor: &Supervisor) -> anyhow::Result<Self> {
+        let stats = Arc::new(AtomicRecorderStats::default());
+        let paused = Arc::new(AtomicBool::new(false));
+        let price_tx = Self::spawn_queue_writer(
+            supervisor,
+            PRICE_WRITER_COMPONENT,
+            "price_ticks",
+            price_tick_batch,
+            &config,
+            &stats,
+            &paused,
+        )?;
+        let orderbook_tx = Self::spawn_queue_writer(
+            supervisor,
+            ORDERBOOK_WRITER_COMPONENT,
+            "orderbook",
+            orderbook_batch,
+            &config,
+            &stats,
+            &paused,
+        )?;
+        Ok(Self {
             price_tx,
             orderbook_tx,
+            dedup: std::sync::Mutex::new(BookDedup::new(config.dedup.clone())),
+            config,
             stats,
-        }
+            paused,
+        })
+    }
+
+    /// Open the `prefix` queue and supervise a writer draining it
+    fn spawn_queue_writer<R>(
+        supervisor: &Supervisor,
+        component: &str,
+        prefix: &'static str,
+        build: fn(&[R]) -> anyhow::Result<RecordBatch>,
+        config: &RecorderConfig,
+        stats: &Arc<AtomicRecorderStats>,
+        paused: &Arc<AtomicBool>,
+    ) -> anyhow::Result<Intake<R>>
+    where
+        R: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let queue_config = config.durable_queue.clone().unwrap_or_default();
+        let queue = Arc::new(DurableQueue::open(
+            &config.output_dir,
+            prefix,
+            queue_config,
+        )?);
+        let (writer_queue, config, stats, paused) =
+            (queue.clone(), config.clone(), stats.clone(), paused.clone());
+        supervisor.spawn(component, RestartPolicy::backoff(), move || {
+            Self::run_queue_writer(
+                writer_queue.clone(),
+                prefix,
+                config.clone(),
+                stats.clone(),
+                paused.clone(),
+                build,
+            )
+        });
+        Ok(Intake::Queue(queue))
     }
 
     /// Create a new recorder with default config
@@ -127,10 +375,10 @@ impl DataRecorder {
 
     /// Run the price tick writer task
     async fn run_price_writer(
-        mut rx: mpsc::Receiver<PriceTickRecord>,
-        mut writer: ParquetWriter,
+        rx: &mut mpsc::Receiver<PriceTickRecord>,
         config: RecorderConfig,
         stats: Arc<AtomicRecorderStats>,
+        paused: Arc<AtomicBool>,
     ) {
         let mut buffer: Vec<PriceTickRecord> = Vec::with_capacity(config.buffer_size);
         let mut last_flush = Utc::now();
@@ -151,14 +399,14 @@ impl DataRecorder {
 
                             // Flush if buffer is full
                             if buffer.len() >= config.buffer_size {
-                                Self::flush_price_buffer(&mut buffer, &mut writer, &stats).await;
+                                Self::flush_price_buffer(&mut buffer, &config, &stats, &paused).await;
                                 last_flush = Utc::now();
                             }
                         }
                         None => {
                             // Channel closed, flush remaining and exit
                             if !buffer.is_empty() {
-                                Self::flush_price_buffer(&mut buffer, &mut writer, &stats).await;
+                                Self::flush_price_buffer(&mut buffer, &config, &stats, &paused).await;
                             }
                             tracing::info!("Price writer shutting down");
                             break;
@@ -170,58 +418,58 @@ impl DataRecorder {
                     // Periodic flush
                     let now = Utc::now();
                     if now - last_flush >= flush_interval && !buffer.is_empty() {
-                        Self::flush_price_buffer(&mut buffer, &mut writer, &stats).await;
+                        Self::flush_price_buffer(&mut buffer, &config, &stats, &paused).await;
                         last_flush = now;
                     }
                 }
             }
+            stats.price_buffered.store(buffer.len(), Ordering::Relaxed);
         }
     }
 
     /// Flush price tick buffer to disk using async spawn_blocking
     async fn flush_price_buffer(
         buffer: &mut Vec<PriceTickRecord>,
-        writer: &mut ParquetWriter,
+        config: &RecorderConfig,
         stats: &Arc<AtomicRecorderStats>,
+        paused: &AtomicBool,
     ) {
         if buffer.is_empty() {
             return;
         }
 
-        let now = Utc::now();
-
-        // Check for rotation
-        if writer.needs_rotation(now) {
-            writer.mark_rotation(now);
+        if paused.load(Ordering::Relaxed) {
+            Self::skip_buffer(buffer, stats);
+            return;
         }
 
-        let path = writer.file_path("price_ticks", now);
         let count = buffer.len();
 
         // Take ownership of buffer data for async write
         let ticks = std::mem::take(buffer);
 
-        // Use async write with spawn_blocking
-        match writer.write_price_ticks_async(path.clone(), ticks).await {
-            Ok(()) => {
+        match Self::write_records(config, "price_ticks", Utc::now(), ticks, price_tick_batch).await
+        {
+            Ok(path) => {
                 stats
                     .price_ticks_written
                     .fetch_add(count as u64, Ordering::Relaxed);
                 stats.files_written.fetch_add(1, Ordering::Relaxed);
+                Self::record_file_bytes("price_ticks", &path);
                 tracing::debug!(count, path = ?path, "Flushed price ticks");
             }
             Err(e) => {
-                tracing::error!(error = %e, "Failed to write price ticks");
+                tracing::error!(event_code = %EventCode::FlushFailed, error = %e, "Failed to write price ticks");
             }
         }
     }
 
     /// Run the orderbook writer task
     async fn run_orderbook_writer(
-        mut rx: mpsc::Receiver<OrderBookRecord>,
-        mut writer: ParquetWriter,
+        rx: &mut mpsc::Receiver<OrderBookRecord>,
         config: RecorderConfig,
         stats: Arc<AtomicRecorderStats>,
+        paused: Arc<AtomicBool>,
     ) {
         let mut buffer: Vec<OrderBookRecord> = Vec::with_capacity(config.buffer_size);
         let mut last_flush = Utc::now();
@@ -238,13 +486,13 @@ impl DataRecorder {
                             buffer.push(book);
 
                             if buffer.len() >= config.buffer_size {
-                                Self::flush_orderbook_buffer(&mut buffer, &mut writer, &stats).await;
+                                Self::flush_orderbook_buffer(&mut buffer, &config, &stats, &paused).await;
                                 last_flush = Utc::now();
                             }
                         }
                         None => {
                             if !buffer.is_empty() {
-                                Self::flush_orderbook_buffer(&mut buffer, &mut writer, &stats).await;
+                                Self::flush_orderbook_buffer(&mut buffer, &config, &stats, &paused).await;
                             }
                             tracing::info!("Orderbook writer shutting down");
                             break;
@@ -255,53 +503,301 @@ impl DataRecorder {
                 _ = tokio::time::sleep(timeout) => {
                     let now = Utc::now();
                     if now - last_flush >= flush_interval && !buffer.is_empty() {
-                        Self::flush_orderbook_buffer(&mut buffer, &mut writer, &stats).await;
+                        Self::flush_orderbook_buffer(&mut buffer, &config, &stats, &paused).await;
                         last_flush = now;
                     }
                 }
             }
+            stats
+                .orderbook_buffered
+                .store(buffer.len(), Ordering::Relaxed);
         }
     }
 
     /// Flush orderbook buffer to disk using async spawn_blocking
     async fn flush_orderbook_buffer(
         buffer: &mut Vec<OrderBookRecord>,
-        writer: &mut ParquetWriter,
+        config: &RecorderConfig,
         stats: &Arc<AtomicRecorderStats>,
+        paused: &AtomicBool,
     ) {
         if buffer.is_empty() {
             return;
         }
 
-        let now = Utc::now();
-
-        if writer.needs_rotation(now) {
-            writer.mark_rotation(now);
+        if paused.load(Ordering::Relaxed) {
+            Self::skip_buffer(buffer, stats);
+            return;
         }
 
-        let path = writer.file_path("orderbook", now);
         let count = buffer.len();
 
         // Take ownership for async write
         let snapshots = std::mem::take(buffer);
 
-        match writer
-            .write_orderbook_snapshots_async(path.clone(), snapshots)
-            .await
+        match Self::write_records(config, "orderbook", Utc::now(), snapshots, orderbook_batch).await
         {
-            Ok(()) => {
+            Ok(path) => {
                 stats
                     .orderbook_updates_written
                     .fetch_add(count as u64, Ordering::Relaxed);
                 stats.files_written.fetch_add(1, Ordering::Relaxed);
+                Self::record_file_bytes("orderbook", &path);
                 tracing::debug!(count, path = ?path, "Flushed orderbook snapshots");
             }
             Err(e) => {
-                tracing::error!(error = %e, "Failed to write orderbook snapshots");
+                tracing::error!(event_code = %EventCode::FlushFailed, error = %e, "Failed to write orderbook snapshots");
+            }
+        }
+    }
+
+    /// Run a writer draining a durable queue
+    ///
+    /// Records are read from just past the queue's commit, so a restarted
+    /// writer first replays what its predecessor had not written to a file.
+    /// Returns once the queue is closed and drained; a failed write is
+    /// returned so the supervisor restarts the writer, which replays it.
+    async fn run_queue_writer<R>(
+        queue: Arc<DurableQueue<R>>,
+        prefix: &'static str,
+        config: RecorderConfig,
+        stats: Arc<AtomicRecorderStats>,
+        paused: Arc<AtomicBool>,
+        build: fn(&[R]) -> anyhow::Result<RecordBatch>,
+    ) -> anyhow::Result<()>
+    where
+        R: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let (received, _, buffered) = stats.writer_counters(prefix);
+        let sync_interval = config
+            .durable_queue
+            .clone()
+            .unwrap_or_default()
+            .sync_interval_ms
+            .get();
+        let flush_interval = Duration::seconds(config.flush_interval_secs as i64);
+        let mut reader = queue.reader();
+        let mut buffer: Vec<R> = Vec::with_capacity(config.buffer_size);
+        let mut last_seq = queue.committed();
+        let mut last_flush = Utc::now();
+
+        let mut replaying = queue.synced().saturating_sub(queue.committed());
+        if replaying > 0 {
+            tracing::info!(
+                prefix,
+                records = replaying,
+                "Replaying queued capture records"
+            );
+        }
+
+        loop {
+            // Read before checking for more, so a closed queue is drained
+            let closed = queue.is_closed();
+            let records = reader.read(queue.synced(), config.buffer_size - buffer.len())?;
+            let caught_up = reader.next_seq() > queue.synced();
+
+            let count = records.len() as u64;
+            for (seq, record) in records {
+                buffer.push(record);
+                last_seq = seq;
+            }
+            received.fetch_add(count, Ordering::Relaxed);
+            if replaying > 0 && count > 0 {
+                let replayed = count.min(replaying);
+                replaying -= replayed;
+                stats
+                    .records_replayed
+                    .fetch_add(replayed, Ordering::Relaxed);
+                record_capture_queue_replayed(prefix, replayed);
+                if replaying == 0 {
+                    tracing::info!(prefix, "Capture queue replay complete");
+                }
+            }
+
+            let due = Utc::now() - last_flush >= flush_interval;
+            if buffer.len() >= config.buffer_size
+                || (!buffer.is_empty() && (due || (closed && caught_up)))
+            {
+                if paused.load(Ordering::Relaxed) {
+                    Self::skip_buffer(&mut buffer, &stats);
+                    queue.commit(last_seq)?;
+                } else {
+                    Self::flush_queued(
+                        &mut buffer,
+                        last_seq,
+                        &queue,
+                        prefix,
+                        &config,
+                        &stats,
+                        build,
+                    )
+                    .await?;
+                }
+                last_flush = Utc::now();
+            }
+            buffered.store(buffer.len(), Ordering::Relaxed);
+
+            if closed && caught_up && buffer.is_empty() {
+                tracing::info!(prefix, "Capture queue writer shutting down");
+                return Ok(());
+            }
+            if caught_up {
+                tokio::select! {
+                    _ = queue.notified() => {}
+                    _ = tokio::time::sleep(sync_interval) => queue.sync()?,
+                }
             }
         }
     }
 
+    /// Write `buffer` to a new file and commit the queue through `last_seq`
+    ///
+    /// The file is staged in the queue first, so a writer killed before
+    /// the commit is found to have written it rather than writing it twice.
+    async fn flush_queued<R: Send + 'static>(
+        buffer: &mut Vec<R>,
+        last_seq: u64,
+        queue: &DurableQueue<R>,
+        prefix: &'static str,
+        config: &RecorderConfig,
+        stats: &AtomicRecorderStats,
+        build: fn(&[R]) -> anyhow::Result<RecordBatch>,
+    ) -> anyhow::Result<()> {
+        // File names have whole seconds; never replace one already written
+        let format = config.format_for(prefix);
+        let mut timestamp = Utc::now();
+        while capture_path(&config.output_dir, prefix, timestamp, format).exists() {
+            timestamp += Duration::seconds(1);
+        }
+        queue.stage(
+            last_seq,
+            &capture_path(&config.output_dir, prefix, timestamp, format),
+        )?;
+
+        let count = buffer.len();
+        let records = std::mem::take(buffer);
+        let path = Self::write_records(config, prefix, timestamp, records, build)
+            .await
+            .inspect_err(|e| {
+                tracing::error!(event_code = %EventCode::FlushFailed, error = %e, prefix, "Failed to write queued capture records");
+            })?;
+        queue.commit(last_seq)?;
+
+        let (_, written, _) = stats.writer_counters(prefix);
+        written.fetch_add(count as u64, Ordering::Relaxed);
+        stats.files_written.fetch_add(1, Ordering::Relaxed);
+        Self::record_file_bytes(prefix, &path);
+        tracing::debug!(count, last_seq, path = ?path, "Flushed queued capture records");
+        Ok(())
+    }
+
+    /// Build one batch from `records` and write it to a new `prefix` file
+    /// in the prefix's format, off the async runtime
+    async fn write_records<R: Send + 'static>(
+        config: &RecorderConfig,
+        prefix: &'static str,
+        timestamp: DateTime<Utc>,
+        records: Vec<R>,
+        build: fn(&[R]) -> anyhow::Result<RecordBatch>,
+    ) -> anyhow::Result<PathBuf> {
+        let mut sink = sink_for(
+            config.format_for(prefix),
+            config.output_dir.clone(),
+            &config.parquet,
+        );
+        tokio::task::spawn_blocking(move || {
+            let batch = build(&records)?;
+            sink.open(prefix, timestamp, batch.schema())?;
+            sink.write_batch(&batch)?;
+            sink.close()?
+                .ok_or_else(|| anyhow::anyhow!("No {} file was open", prefix))
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
+    }
+
+    /// Drop a buffer while recording is paused, counting the skipped records
+    fn skip_buffer<T>(buffer: &mut Vec<T>, stats: &AtomicRecorderStats) {
+        let count = buffer.len() as u64;
+        buffer.clear();
+        stats
+            .records_skipped_low_disk
+            .fetch_add(count, Ordering::Relaxed);
+        tracing::debug!(count, "Recording paused, dropped buffered records");
+    }
+
+    /// Report the size of a freshly written file
+    fn record_file_bytes(prefix: &str, path: &std::path::Path) {
+        if let Ok(metadata) = std::fs::metadata(path) {
+            record_data_bytes_written(prefix, metadata.len());
+        }
+    }
+
+    /// Write kline-derived ticks straight to a `klines` file, named after
+    /// the first tick so separate backfills do not overwrite each other
+    pub async fn record_klines(&self, ticks: &[PriceTick]) -> anyhow::Result<Option<PathBuf>> {
+        let Some(first) = ticks.first() else {
+            return Ok(None);
+        };
+        if self.is_paused() {
+            self.stats
+                .records_skipped_low_disk
+                .fetch_add(ticks.len() as u64, Ordering::Relaxed);
+            return Ok(None);
+        }
+
+        let records = ticks
+            .iter()
+            .map(|t| PriceTickRecord {
+                timestamp: t.timestamp,
+                symbol: Arc::from(t.symbol.as_str()),
+                price: t.price,
+                exchange_ts: t.exchange_ts,
+            })
+            .collect();
+        let path = Self::write_records(
+            &self.config,
+            "klines",
+            first.exchange_ts,
+            records,
+            price_tick_batch,
+        )
+        .await?;
+        self.stats.files_written.fetch_add(1, Ordering::Relaxed);
+        Self::record_file_bytes("klines", &path);
+        Ok(Some(path))
+    }
+
+    /// Write finished signal outcomes to a `signal_outcomes` file, named
+    /// after the first signal
+    pub async fn record_signal_outcomes(
+        &self,
+        outcomes: Vec<SignalOutcome>,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let Some(first) = outcomes.first() else {
+            return Ok(None);
+        };
+        if self.is_paused() {
+            self.stats
+                .records_skipped_low_disk
+                .fetch_add(outcomes.len() as u64, Ordering::Relaxed);
+            return Ok(None);
+        }
+
+        let timestamp = first.emitted_at;
+        let path = Self::write_records(
+            &self.config,
+            "signal_outcomes",
+            timestamp,
+            outcomes,
+            signal_outcome_batch,
+        )
+        .await?;
+        self.stats.files_written.fetch_add(1, Ordering::Relaxed);
+        Self::record_file_bytes("signal_outcomes", &path);
+        Ok(Some(path))
+    }
+
     /// Record a price tick - non-blocking using try_send
     pub fn record_price(&self, tick: PriceTick) -> Result<(), RecordError> {
         let record = PriceTickRecord {
@@ -311,14 +807,7 @@ impl DataRecorder {
             exchange_ts: tick.exchange_ts,
         };
 
-        match self.price_tx.try_send(record) {
-            Ok(()) => Ok(()),
-            Err(mpsc::error::TrySendError::Full(_)) => {
-                self.stats.channel_drops.fetch_add(1, Ordering::Relaxed);
-                Err(RecordError::ChannelFull)
-            }
-            Err(mpsc::error::TrySendError::Closed(_)) => Err(RecordError::ChannelClosed),
-        }
+        self.price_tx.try_record(record, &self.stats)
     }
 
     /// Record a price tick - async version that waits if channel is full
@@ -330,44 +819,65 @@ impl DataRecorder {
             exchange_ts: tick.exchange_ts,
         };
 
-        self.price_tx
-            .send(record)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to send price tick: {}", e))
+        match &self.price_tx {
+            Intake::Channel(tx) => tx
+                .send(record)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to send price tick: {}", e)),
+            Intake::Queue(queue) => queue.append(&record).map(|_| ()),
+        }
     }
 
     /// Record an order book snapshot - non-blocking using try_send
+    ///
+    /// A book repeating the token's last row is skipped when deduplication
+    /// is on.
     pub fn record_orderbook(&self, book: OrderBook) -> Result<(), RecordError> {
-        let record = OrderBookRecord {
-            timestamp: book.updated_at,
-            token_id: Arc::from(book.token_id.as_str()),
-            bids: book.bids.iter().map(|l| (l.price, l.size)).collect(),
-            asks: book.asks.iter().map(|l| (l.price, l.size)).collect(),
+        let Some(record) = self.dedup_orderbook(&book) else {
+            return Ok(());
         };
 
-        match self.orderbook_tx.try_send(record) {
-            Ok(()) => Ok(()),
-            Err(mpsc::error::TrySendError::Full(_)) => {
-                self.stats.channel_drops.fetch_add(1, Ordering::Relaxed);
-                Err(RecordError::ChannelFull)
-            }
-            Err(mpsc::error::TrySendError::Closed(_)) => Err(RecordError::ChannelClosed),
-        }
+        self.orderbook_tx.try_record(record, &self.stats)
     }
 
     /// Record an order book snapshot - async version
     pub async fn record_orderbook_async(&self, book: OrderBook) -> anyhow::Result<()> {
+        let Some(record) = self.dedup_orderbook(&book) else {
+            return Ok(());
+        };
+
+        match &self.orderbook_tx {
+            Intake::Channel(tx) => tx
+                .send(record)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to send orderbook: {}", e)),
+            Intake::Queue(queue) => queue.append(&record).map(|_| ()),
+        }
+    }
+
+    /// The row to write for `book`; `None` when it repeats the token's
+    /// last row
+    fn dedup_orderbook(&self, book: &OrderBook) -> Option<OrderBookRecord> {
         let record = OrderBookRecord {
             timestamp: book.updated_at,
             token_id: Arc::from(book.token_id.as_str()),
             bids: book.bids.iter().map(|l| (l.price, l.size)).collect(),
             asks: book.asks.iter().map(|l| (l.price, l.size)).collect(),
+            crossed: book.top_of_book_fault().is_some(),
+            kind: BookUpdateKind::Snapshot,
         };
-
-        self.orderbook_tx
-            .send(record)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to send orderbook: {}", e))
+        let written = self
+            .dedup
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .filter(record);
+        if written.is_none() {
+            self.stats
+                .orderbook_duplicates_suppressed
+                .fetch_add(1, Ordering::Relaxed);
+            record_capture_book_suppressed();
+        }
+        written
     }
 
     /// Get output directory
@@ -375,10 +885,69 @@ impl DataRecorder {
         &self.config.output_dir
     }
 
+    /// Flag that pauses writing when set (shared with the disk manager)
+    pub fn pause_flag(&self) -> Arc<AtomicBool> {
+        self.paused.clone()
+    }
+
+    /// Whether recording is currently paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
     /// Get current statistics (lock-free snapshot)
     pub fn stats(&self) -> RecorderStats {
         self.stats.snapshot()
     }
+
+    /// Records received by the writers and not yet flushed
+    pub fn buffers(&self) -> RecorderBuffers {
+        RecorderBuffers {
+            price_ticks: self.stats.price_buffered.load(Ordering::Relaxed),
+            orderbooks: self.stats.orderbook_buffered.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Where recorded records go on their way to a writer
+enum Intake<R> {
+    /// Bounded channel; records are dropped while it is full
+    Channel(InstrumentedSender<R>),
+    /// Disk-backed queue, closed when the recorder is dropped
+    Queue(Arc<DurableQueue<R>>),
+}
+
+impl<R: Serialize> Intake<R> {
+    /// Hand `record` on without waiting
+    fn try_record(&self, record: R, stats: &AtomicRecorderStats) -> Result<(), RecordError> {
+        match self {
+            Intake::Channel(tx) => match tx.try_send(record) {
+                Ok(()) => Ok(()),
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    stats.channel_drops.fetch_add(1, Ordering::Relaxed);
+                    Err(RecordError::ChannelFull)
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => Err(RecordError::ChannelClosed),
+            },
+            Intake::Queue(queue) => match queue.append(&record) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    tracing::error!(event_code = %EventCode::FlushFailed, error = %e, "Failed to append to the capture queue");
+                    Err(RecordError::QueueFailed)
+                }
+            },
+        }
+    }
+}
+
+impl<R> Drop for Intake<R> {
+    fn drop(&mut self) {
+        if let Intake::Queue(queue) = self {
+            if let Err(e) = queue.close() {
+                tracing::error!(event_code = %EventCode::FlushFailed, error = %e, "Failed to sync the capture queue");
+            }
+        }
+    }
 }
 
 /// Error type for recording operations
@@ -388,6 +957,8 @@ pub enum RecordError {
     ChannelFull,
     /// Channel is closed - recorder is shutting down
     ChannelClosed,
+    /// The durable queue could not be appended to - data was dropped
+    QueueFailed,
 }
 
 impl std::fmt::Display for RecordError {
@@ -395,6 +966,7 @@ impl std::fmt::Display for RecordError {
         match self {
             RecordError::ChannelFull => write!(f, "Channel full, data dropped"),
             RecordError::ChannelClosed => write!(f, "Channel closed"),
+            RecordError::QueueFailed => write!(f, "Capture queue append failed, data dropped"),
         }
     }
 }
@@ -404,7 +976,10 @@ impl std::error::Error for RecordError {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::feed::TickSource;
     use crate::orderbook::PriceLevel;
+    use chrono::DateTime;
+    use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
     use tempfile::TempDir;
 
@@ -416,6 +991,7 @@ mod tests {
             rotation_interval_secs: 3600,
             buffer_size: 10,
             flush_interval_secs: 1,
+            ..Default::default()
         };
 
         let recorder = DataRecorder::new(config);
@@ -430,15 +1006,18 @@ mod tests {
             rotation_interval_secs: 3600,
             buffer_size: 1, // Flush immediately
             flush_interval_secs: 1,
+            ..Default::default()
         };
 
         let recorder = DataRecorder::new(config);
 
         let tick = PriceTick {
             symbol: "BTCUSDT".to_string(),
+            asset: "BTC".to_string(),
             price: dec!(42500.00),
             timestamp: Utc::now(),
             exchange_ts: Utc::now(),
+            source: TickSource::Trade,
         };
 
         // Use non-blocking record
@@ -451,6 +1030,86 @@ mod tests {
         assert_eq!(stats.price_ticks_received, 1);
     }
 
+    #[tokio::test]
+    async fn test_buffers_report_unflushed_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = RecorderConfig {
+            output_dir: temp_dir.path().to_path_buf(),
+            rotation_interval_secs: 3600,
+            buffer_size: 4,
+            flush_interval_secs: 60,
+            ..Default::default()
+        };
+        let recorder = DataRecorder::new(config);
+        let record = |n: usize| {
+            for _ in 0..n {
+                let tick = PriceTick {
+                    symbol: "BTCUSDT".to_string(),
+                    asset: "BTC".to_string(),
+                    price: dec!(42500.00),
+                    timestamp: Utc::now(),
+                    exchange_ts: Utc::now(),
+                    source: TickSource::Trade,
+                };
+                recorder.record_price(tick).unwrap();
+            }
+        };
+
+        record(3);
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        assert_eq!(
+            recorder.buffers(),
+            RecorderBuffers {
+                price_ticks: 3,
+                orderbooks: 0,
+            }
+        );
+
+        // The fourth fills the buffer and flushes it
+        record(1);
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        assert_eq!(recorder.buffers().price_ticks, 0);
+        assert_eq!(recorder.stats().price_ticks_written, 4);
+    }
+
+    #[tokio::test]
+    async fn test_record_klines_writes_separate_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let recorder = DataRecorder::new(RecorderConfig {
+            output_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        });
+        assert!(recorder.record_klines(&[]).await.unwrap().is_none());
+
+        let start = DateTime::from_timestamp(1_700_000_040, 0).unwrap();
+        let ticks: Vec<_> = (0..3)
+            .map(|i| PriceTick {
+                symbol: "BTCUSDT".to_string(),
+                asset: "BTC".to_string(),
+                price: dec!(37000) + Decimal::from(i),
+                timestamp: start + Duration::minutes(i),
+                exchange_ts: start + Duration::minutes(i),
+                source: TickSource::Kline,
+            })
+            .collect();
+
+        let path = recorder.record_klines(&ticks).await.unwrap().unwrap();
+        assert_eq!(
+            path.file_name().unwrap().to_str().unwrap(),
+            "klines_20231114_221400.parquet"
+        );
+        let records = crate::data::ParquetReader::new(path)
+            .read_price_ticks()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].price, dec!(37002));
+
+        // Paused recording drops the backfill
+        recorder.pause_flag().store(true, Ordering::Relaxed);
+        assert!(recorder.record_klines(&ticks).await.unwrap().is_none());
+        assert_eq!(recorder.stats().records_skipped_low_disk, 3);
+    }
+
     #[tokio::test]
     async fn test_record_orderbook() {
         let temp_dir = TempDir::new().unwrap();
@@ -459,6 +1118,7 @@ mod tests {
             rotation_interval_secs: 3600,
             buffer_size: 1,
             flush_interval_secs: 1,
+            ..Default::default()
         };
 
         let recorder = DataRecorder::new(config);
@@ -553,15 +1213,18 @@ mod tests {
             rotation_interval_secs: 3600,
             buffer_size: 1,
             flush_interval_secs: 1,
+            ..Default::default()
         };
 
         let recorder = DataRecorder::new(config);
 
         let tick = PriceTick {
             symbol: "BTCUSDT".to_string(),
+            asset: "BTC".to_string(),
             price: dec!(42500.00),
             timestamp: Utc::now(),
             exchange_ts: Utc::now(),
+            source: TickSource::Trade,
         };
 
         recorder.record_price_async(tick).await.unwrap();
@@ -580,6 +1243,7 @@ mod tests {
             rotation_interval_secs: 3600,
             buffer_size: 1,
             flush_interval_secs: 1,
+            ..Default::default()
         };
 
         let recorder = DataRecorder::new(config);
@@ -622,12 +1286,141 @@ mod tests {
             orderbook_updates_written: 45,
             files_written: 5,
             channel_drops: 2,
+            records_skipped_low_disk: 0,
+            records_replayed: 0,
+            orderbook_duplicates_suppressed: 0,
         };
         let cloned = stats.clone();
         assert_eq!(stats.price_ticks_received, cloned.price_ticks_received);
         assert_eq!(stats.channel_drops, cloned.channel_drops);
     }
 
+    #[tokio::test]
+    async fn test_paused_recorder_skips_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = RecorderConfig {
+            output_dir: temp_dir.path().to_path_buf(),
+            rotation_interval_secs: 3600,
+            buffer_size: 2,
+            flush_interval_secs: 60,
+            ..Default::default()
+        };
+
+        let recorder = DataRecorder::new(config);
+        recorder.pause_flag().store(true, Ordering::Relaxed);
+        assert!(recorder.is_paused());
+
+        for _ in 0..2 {
+            let tick = PriceTick {
+                symbol: "BTCUSDT".to_string(),
+                asset: "BTC".to_string(),
+                price: dec!(42500.00),
+                timestamp: Utc::now(),
+                exchange_ts: Utc::now(),
+                source: TickSource::Trade,
+            };
+            recorder.record_price(tick).unwrap();
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let stats = recorder.stats();
+        assert_eq!(stats.records_skipped_low_disk, 2);
+        assert_eq!(stats.price_ticks_written, 0);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_books_are_suppressed() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = RecorderConfig {
+            output_dir: temp_dir.path().to_path_buf(),
+            rotation_interval_secs: 3600,
+            buffer_size: 100,
+            flush_interval_secs: 60,
+            ..Default::default()
+        };
+        let recorder = DataRecorder::new(config.clone());
+        let every_frame = DataRecorder::new(config.with_dedup(BookDedupConfig {
+            enabled: false,
+            ..Default::default()
+        }));
+
+        let mut book = OrderBook::new("yes-token");
+        book.bids = vec![crate::orderbook::PriceLevel {
+            price: dec!(0.50),
+            size: dec!(10),
+        }];
+        for _ in 0..3 {
+            recorder.record_orderbook(book.clone()).unwrap();
+            every_frame.record_orderbook(book.clone()).unwrap();
+        }
+        book.bids[0].size = dec!(12);
+        recorder.record_orderbook_async(book.clone()).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let stats = recorder.stats();
+        assert_eq!(stats.orderbook_duplicates_suppressed, 2);
+        assert_eq!(stats.orderbook_updates_received, 2);
+        let stats = every_frame.stats();
+        assert_eq!(stats.orderbook_duplicates_suppressed, 0);
+        assert_eq!(stats.orderbook_updates_received, 3);
+    }
+
+    #[tokio::test]
+    async fn test_crossed_book_persisted_and_tagged() {
+        use crate::orderbook::PriceLevel;
+        use arrow::array::{Array, BooleanArray};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = RecorderConfig {
+            output_dir: temp_dir.path().to_path_buf(),
+            rotation_interval_secs: 3600,
+            buffer_size: 2,
+            flush_interval_secs: 60,
+            ..Default::default()
+        };
+        let recorder = DataRecorder::new(config);
+
+        let level = |price| PriceLevel {
+            price,
+            size: dec!(10),
+        };
+        let mut healthy = OrderBook::new("yes-token");
+        healthy.bids = vec![level(dec!(0.50))];
+        healthy.asks = vec![level(dec!(0.52))];
+        let mut crossed = healthy.clone();
+        crossed.bids = vec![level(dec!(0.53))];
+
+        recorder.record_orderbook(healthy).unwrap();
+        recorder.record_orderbook(crossed).unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        assert_eq!(recorder.stats().orderbook_updates_written, 2);
+
+        let path = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.extension().is_some_and(|e| e == "parquet"))
+            .unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let flags = batch
+            .column_by_name("crossed")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        assert_eq!(flags.len(), 2);
+        assert!(!flags.value(0));
+        assert!(flags.value(1));
+    }
+
     #[tokio::test]
     async fn test_multiple_price_ticks() {
         let temp_dir = TempDir::new().unwrap();
@@ -636,6 +1429,7 @@ mod tests {
             rotation_interval_secs: 3600,
             buffer_size: 10,
             flush_interval_secs: 1,
+            ..Default::default()
         };
 
         let recorder = DataRecorder::new(config);
@@ -644,9 +1438,11 @@ mod tests {
         for i in 0..5 {
             let tick = PriceTick {
                 symbol: "BTCUSDT".to_string(),
+                asset: "BTC".to_string(),
                 price: dec!(42500.00) + rust_decimal::Decimal::from(i),
                 timestamp: Utc::now(),
                 exchange_ts: Utc::now(),
+                source: TickSource::Trade,
             };
             recorder.record_price(tick).unwrap();
         }
@@ -680,4 +1476,122 @@ mod tests {
         assert_eq!(snapshot.files_written, 2);
         assert_eq!(snapshot.channel_drops, 1);
     }
+
+    fn queued_tick(i: u64) -> PriceTickRecord {
+        let ts = DateTime::from_timestamp(1_700_000_000 + i as i64, 0).unwrap();
+        PriceTickRecord::new(ts, Arc::from("BTCUSDT"), Decimal::from(i), ts)
+    }
+
+    /// Prices of every price tick written under `dir`, sorted
+    fn written_prices(dir: &std::path::Path) -> Vec<Decimal> {
+        let mut prices: Vec<Decimal> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| DataFormat::from_path(p) == Some(DataFormat::Parquet))
+            .flat_map(|p| {
+                super::super::ParquetReader::new(p)
+                    .read_price_ticks()
+                    .unwrap()
+            })
+            .map(|t| t.price)
+            .collect();
+        prices.sort();
+        prices
+    }
+
+    #[tokio::test]
+    async fn test_durable_queue_records_every_tick() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = RecorderConfig {
+            output_dir: temp_dir.path().to_path_buf(),
+            buffer_size: 25,
+            ..Default::default()
+        }
+        .with_durable_queue(Some(QueueConfig::default().with_segments(4096, 16)));
+        let recorder = DataRecorder::try_new(config).unwrap();
+
+        // Far more than a channel would hold while the writer is busy
+        for i in 1..=200 {
+            let tick = PriceTick {
+                symbol: "BTCUSDT".to_string(),
+                asset: "BTC".to_string(),
+                price: Decimal::from(i),
+                timestamp: Utc::now(),
+                exchange_ts: Utc::now(),
+                source: TickSource::Trade,
+            };
+            recorder.record_price(tick).unwrap();
+        }
+        assert_eq!(recorder.stats().channel_drops, 0);
+        drop(recorder);
+
+        let expected: Vec<Decimal> = (1..=200).map(Decimal::from).collect();
+        for _ in 0..500 {
+            if written_prices(temp_dir.path()).len() == expected.len() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(written_prices(temp_dir.path()), expected);
+    }
+
+    #[tokio::test]
+    async fn test_killed_queue_writer_replays_without_loss_or_duplicates() {
+        let temp_dir = TempDir::new().unwrap();
+        let queue_config = QueueConfig::default().with_segments(512, 1000);
+        let config = RecorderConfig {
+            output_dir: temp_dir.path().to_path_buf(),
+            buffer_size: 10,
+            ..Default::default()
+        }
+        .with_durable_queue(Some(queue_config.clone()));
+        let paused = Arc::new(AtomicBool::new(false));
+        let spawn_writer = |queue: &Arc<DurableQueue<PriceTickRecord>>| {
+            let stats = Arc::new(AtomicRecorderStats::default());
+            let writer = tokio::spawn(DataRecorder::run_queue_writer(
+                queue.clone(),
+                "price_ticks",
+                config.clone(),
+                stats.clone(),
+                paused.clone(),
+                price_tick_batch,
+            ));
+            (writer, stats)
+        };
+
+        let queue = Arc::new(
+            DurableQueue::open(temp_dir.path(), "price_ticks", queue_config.clone()).unwrap(),
+        );
+        let (writer, stats) = spawn_writer(&queue);
+        for i in 1..=150 {
+            queue.append(&queued_tick(i)).unwrap();
+        }
+        queue.sync().unwrap();
+
+        // Kill the writer mid-stream, as a crashed process would be
+        while stats.files_written.load(Ordering::Relaxed) < 6 {
+            tokio::task::yield_now().await;
+        }
+        writer.abort();
+        let _ = writer.await;
+        // Let a write already handed off land, as it would have before a kill
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        assert!(queue.committed() < 150);
+        drop(queue);
+
+        // Restarted, the writer replays what was not written and goes on
+        let queue =
+            Arc::new(DurableQueue::open(temp_dir.path(), "price_ticks", queue_config).unwrap());
+        let (writer, stats) = spawn_writer(&queue);
+        for i in 151..=300 {
+            queue.append(&queued_tick(i)).unwrap();
+        }
+        queue.close().unwrap();
+        writer.await.unwrap().unwrap();
+
+        assert!(stats.records_replayed.load(Ordering::Relaxed) > 0);
+        assert_eq!(queue.depth(), 0);
+        let expected: Vec<Decimal> = (1..=300).map(Decimal::from).collect();
+        assert_eq!(written_prices(temp_dir.path()), expected);
+    }
 }
@@ -0,0 +1,448 @@
+//! Retention policy for captured data files
+//!
+//! Deletes the oldest capture files first until per-prefix age limits and the
+//! total size budget are met. Files modified within the protected window are
+//! never touched, and `compacted/` is skipped unless explicitly included.
+
+use super::sink::DataFormat;
+use crate::duration::{DurationConfig, Hours};
+use crate::journal::Journal;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the directory holding compacted files
+pub const COMPACTED_DIR: &str = "compacted";
+
+/// Retention policy for the data directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Maximum total size of data files in bytes
+    #[serde(default)]
+    pub max_total_bytes: Option<u64>,
+    /// Maximum file age, keyed by file prefix (e.g. `orderbook`)
+    #[serde(default)]
+    pub max_age_hours: HashMap<String, DurationConfig<Hours>>,
+    /// Files modified more recently than this are never deleted
+    #[serde(default = "default_protected_window_secs")]
+    pub protected_window_secs: DurationConfig,
+    /// Also apply the policy to `compacted/`
+    #[serde(default)]
+    pub include_compacted: bool,
+    /// Interval between cleanup runs
+    #[serde(default = "default_cleanup_interval_secs")]
+    pub cleanup_interval_secs: DurationConfig,
+}
+
+fn default_protected_window_secs() -> DurationConfig {
+    DurationConfig::from_secs(3600)
+}
+
+fn default_cleanup_interval_secs() -> DurationConfig {
+    DurationConfig::from_secs(300)
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_total_bytes: None,
+            max_age_hours: HashMap::new(),
+            protected_window_secs: default_protected_window_secs(),
+            include_compacted: false,
+            cleanup_interval_secs: default_cleanup_interval_secs(),
+        }
+    }
+}
+
+/// A data file considered by the retention policy
+#[derive(Debug, Clone)]
+pub struct DataFile {
+    /// File path
+    pub path: PathBuf,
+    /// File prefix, e.g. `price_ticks` for `price_ticks_20250104_123000.csv`
+    pub prefix: String,
+    /// Size in bytes
+    pub bytes: u64,
+    /// Last modification time
+    pub modified: DateTime<Utc>,
+}
+
+/// Why a file was removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalReason {
+    /// Older than the prefix's maximum age
+    MaxAge,
+    /// Removed to bring the directory under its size budget
+    SizeBudget,
+}
+
+/// Outcome of one cleanup run
+#[derive(Debug, Clone, Default)]
+pub struct CleanupReport {
+    /// Files removed, oldest first
+    pub removed: Vec<PathBuf>,
+    /// Bytes freed
+    pub bytes_freed: u64,
+    /// Total size of data files after cleanup
+    pub remaining_bytes: u64,
+}
+
+#[derive(Serialize)]
+struct RemovalRecord<'a> {
+    path: &'a Path,
+    bytes: u64,
+    modified: DateTime<Utc>,
+    reason: RemovalReason,
+}
+
+/// File prefix from a `<prefix>_<YYYYMMDD>_<HHMMSS>.<ext>` name
+pub fn file_prefix(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let mut parts = stem.rsplitn(3, '_');
+    let (_time, _date, prefix) = (parts.next()?, parts.next()?, parts.next()?);
+    Some(prefix.to_string())
+}
+
+/// List capture files of any format under `dir`, ignoring partial writes
+pub fn scan_data_files(dir: &Path, include_compacted: bool) -> anyhow::Result<Vec<DataFile>> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    collect_files(dir, include_compacted, &mut files)?;
+    Ok(files)
+}
+
+fn collect_files(
+    dir: &Path,
+    include_compacted: bool,
+    out: &mut Vec<DataFile>,
+) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            // Renamed away since listed, as a partial file is once sealed
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        if metadata.is_dir() {
+            if entry.file_name() == COMPACTED_DIR && include_compacted {
+                collect_files(&path, include_compacted, out)?;
+            }
+            continue;
+        }
+        if DataFormat::from_path(&path).is_none() {
+            continue;
+        }
+
+        out.push(DataFile {
+            prefix: file_prefix(&path).unwrap_or_default(),
+            bytes: metadata.len(),
+            modified: metadata.modified()?.into(),
+            path,
+        });
+    }
+    Ok(())
+}
+
+/// Total size of data files under `dir`, including `compacted/`
+pub fn data_dir_bytes(dir: &Path) -> anyhow::Result<u64> {
+    Ok(scan_data_files(dir, true)?.iter().map(|f| f.bytes).sum())
+}
+
+/// Decide which files to remove, oldest first
+pub fn plan_cleanup(
+    files: &[DataFile],
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+) -> Vec<(DataFile, RemovalReason)> {
+    let protected_after = now - policy.protected_window_secs.to_chrono();
+    let mut candidates: Vec<&DataFile> = files
+        .iter()
+        .filter(|f| f.modified < protected_after)
+        .collect();
+    candidates.sort_by_key(|f| f.modified);
+
+    let mut total: u64 = files.iter().map(|f| f.bytes).sum();
+    let mut removals = Vec::new();
+
+    for file in candidates {
+        let expired = policy
+            .max_age_hours
+            .get(&file.prefix)
+            .is_some_and(|max_age| file.modified < now - max_age.to_chrono());
+        let over_budget = policy.max_total_bytes.is_some_and(|max| total > max);
+
+        let reason = if expired {
+            RemovalReason::MaxAge
+        } else if over_budget {
+            RemovalReason::SizeBudget
+        } else {
+            continue;
+        };
+        total = total.saturating_sub(file.bytes);
+        removals.push((file.clone(), reason));
+    }
+
+    removals
+}
+
+/// Apply the retention policy to `dir`, journaling every removal
+pub fn enforce_retention(
+    dir: &Path,
+    policy: &RetentionPolicy,
+    journal: Option<&Journal>,
+    now: DateTime<Utc>,
+) -> anyhow::Result<CleanupReport> {
+    let files = scan_data_files(dir, policy.include_compacted)?;
+    let mut report = CleanupReport {
+        remaining_bytes: files.iter().map(|f| f.bytes).sum(),
+        ..Default::default()
+    };
+
+    for (file, reason) in plan_cleanup(&files, policy, now) {
+        if let Err(e) = fs::remove_file(&file.path) {
+            tracing::warn!(path = ?file.path, error = %e, "Failed to remove data file");
+            continue;
+        }
+        tracing::info!(path = ?file.path, bytes = file.bytes, ?reason, "Removed data file");
+        if let Some(journal) = journal {
+            let record = RemovalRecord {
+                path: &file.path,
+                bytes: file.bytes,
+                modified: file.modified,
+                reason,
+            };
+            if let Err(e) = journal.append("retention_delete", &record) {
+                tracing::warn!(error = %e, "Failed to journal data file removal");
+            }
+        }
+        report.bytes_freed += file.bytes;
+        report.remaining_bytes = report.remaining_bytes.saturating_sub(file.bytes);
+        report.removed.push(file.path);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use std::fs::File;
+    use std::time::SystemTime;
+    use tempfile::TempDir;
+
+    fn write_file(dir: &Path, name: &str, bytes: usize, age: Duration) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, vec![0u8; bytes]).unwrap();
+        let modified: SystemTime = (Utc::now() - age).into();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        path
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_file_prefix() {
+        assert_eq!(
+            file_prefix(Path::new("/d/price_ticks_20250104_123000.parquet")).as_deref(),
+            Some("price_ticks")
+        );
+        assert_eq!(
+            file_prefix(Path::new("orderbook_20250104_123000.parquet")).as_deref(),
+            Some("orderbook")
+        );
+        assert_eq!(file_prefix(Path::new("x.parquet")), None);
+    }
+
+    #[test]
+    fn test_size_budget_removes_oldest_first() {
+        let dir = TempDir::new().unwrap();
+        write_file(
+            dir.path(),
+            "orderbook_20250101_000000.parquet",
+            100,
+            Duration::hours(5),
+        );
+        write_file(
+            dir.path(),
+            "price_ticks_20250101_010000.parquet",
+            100,
+            Duration::hours(4),
+        );
+        write_file(
+            dir.path(),
+            "orderbook_20250101_020000.parquet",
+            100,
+            Duration::hours(3),
+        );
+        write_file(
+            dir.path(),
+            "price_ticks_20250101_030000.parquet",
+            100,
+            Duration::hours(2),
+        );
+
+        let policy = RetentionPolicy {
+            max_total_bytes: Some(250),
+            ..Default::default()
+        };
+        let report = enforce_retention(dir.path(), &policy, None, Utc::now()).unwrap();
+
+        assert_eq!(
+            names(&report.removed),
+            vec![
+                "orderbook_20250101_000000.parquet",
+                "price_ticks_20250101_010000.parquet"
+            ]
+        );
+        assert_eq!(report.bytes_freed, 200);
+        assert_eq!(report.remaining_bytes, 200);
+    }
+
+    #[test]
+    fn test_protected_window_is_never_deleted() {
+        let dir = TempDir::new().unwrap();
+        write_file(
+            dir.path(),
+            "orderbook_20250101_000000.parquet",
+            100,
+            Duration::hours(2),
+        );
+        let recent = write_file(
+            dir.path(),
+            "orderbook_20250101_010000.parquet",
+            100,
+            Duration::minutes(10),
+        );
+
+        let policy = RetentionPolicy {
+            max_total_bytes: Some(0),
+            max_age_hours: HashMap::from([("orderbook".to_string(), DurationConfig::default())]),
+            protected_window_secs: DurationConfig::from_secs(3600),
+            ..Default::default()
+        };
+        let report = enforce_retention(dir.path(), &policy, None, Utc::now()).unwrap();
+
+        assert_eq!(report.removed.len(), 1);
+        assert!(recent.exists());
+    }
+
+    #[test]
+    fn test_max_age_per_prefix() {
+        let dir = TempDir::new().unwrap();
+        write_file(
+            dir.path(),
+            "orderbook_20250101_000000.parquet",
+            10,
+            Duration::hours(30),
+        );
+        write_file(
+            dir.path(),
+            "price_ticks_20250101_000000.parquet",
+            10,
+            Duration::hours(30),
+        );
+        write_file(
+            dir.path(),
+            "orderbook_20250102_000000.parquet",
+            10,
+            Duration::hours(5),
+        );
+
+        let policy = RetentionPolicy {
+            max_age_hours: HashMap::from([(
+                "orderbook".to_string(),
+                DurationConfig::from_units(24),
+            )]),
+            ..Default::default()
+        };
+        let report = enforce_retention(dir.path(), &policy, None, Utc::now()).unwrap();
+
+        assert_eq!(
+            names(&report.removed),
+            vec!["orderbook_20250101_000000.parquet"]
+        );
+    }
+
+    #[test]
+    fn test_compacted_skipped_unless_configured() {
+        let dir = TempDir::new().unwrap();
+        let compacted = dir.path().join(COMPACTED_DIR);
+        fs::create_dir(&compacted).unwrap();
+        let old = write_file(
+            &compacted,
+            "orderbook_20240101_000000.parquet",
+            100,
+            Duration::days(30),
+        );
+        write_file(
+            dir.path(),
+            "orderbook_20250101_000000.parquet",
+            100,
+            Duration::hours(2),
+        );
+
+        let mut policy = RetentionPolicy {
+            max_total_bytes: Some(0),
+            ..Default::default()
+        };
+        let report = enforce_retention(dir.path(), &policy, None, Utc::now()).unwrap();
+        assert_eq!(
+            names(&report.removed),
+            vec!["orderbook_20250101_000000.parquet"]
+        );
+        assert!(old.exists());
+
+        policy.include_compacted = true;
+        let report = enforce_retention(dir.path(), &policy, None, Utc::now()).unwrap();
+        assert_eq!(
+            names(&report.removed),
+            vec!["orderbook_20240101_000000.parquet"]
+        );
+    }
+
+    #[test]
+    fn test_removals_are_journaled() {
+        let dir = TempDir::new().unwrap();
+        write_file(
+            dir.path(),
+            "orderbook_20250101_000000.parquet",
+            42,
+            Duration::hours(2),
+        );
+        write_file(dir.path(), "notes.txt", 42, Duration::hours(2));
+
+        let journal_path = dir.path().join("journal.jsonl");
+        let journal = Journal::open(&journal_path).unwrap();
+        let policy = RetentionPolicy {
+            max_total_bytes: Some(0),
+            ..Default::default()
+        };
+        enforce_retention(dir.path(), &policy, Some(&journal), Utc::now()).unwrap();
+
+        let entries = Journal::read_all(&journal_path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, "retention_delete");
+        assert_eq!(entries[0].data["bytes"], 42);
+        assert_eq!(entries[0].data["reason"], "size_budget");
+        assert!(dir.path().join("notes.txt").exists());
+    }
+}
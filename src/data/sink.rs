@@ -0,0 +1,659 @@
+//! Capture file encodings
+//!
+//! The recorder builds Arrow [`RecordBatch`]es once and hands them to a
+//! [`RecordSink`], which encodes them as Parquet, CSV or an Arrow IPC
+//! stream. Every sink writes to a `.tmp` file, seals it in the directory's
+//! capture manifest, and renames it into place on close, so readers and
+//! retention never see a partial file whatever the format.
+
+use super::encoding::ParquetTuning;
+use super::manifest::seal;
+use super::parquet::{
+    orderbook_schema, price_tick_schema, read_parquet_batches, signal_outcome_schema, signal_schema,
+};
+use crate::fingerprint::{self, ConfigFingerprint};
+use arrow::compute::cast;
+use arrow::csv;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Utc};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Suffix of files still being written
+pub(super) const PARTIAL_SUFFIX: &str = "tmp";
+
+/// RFC 3339 timestamps in CSV output
+const CSV_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6f%:z";
+
+/// Fixed-offset spelling of UTC; arrow's CSV codec cannot resolve named
+/// zones without chrono-tz
+const CSV_TIMEZONE: &str = "+00:00";
+
+/// Encoding of captured data files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    /// Parquet, encoded per `[data.parquet]`
+    #[default]
+    Parquet,
+    /// CSV with a header row
+    Csv,
+    /// Arrow IPC stream
+    Arrow,
+}
+
+impl DataFormat {
+    /// Every format
+    pub const ALL: [DataFormat; 3] = [DataFormat::Parquet, DataFormat::Csv, DataFormat::Arrow];
+
+    /// File extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DataFormat::Parquet => "parquet",
+            DataFormat::Csv => "csv",
+            DataFormat::Arrow => "arrows",
+        }
+    }
+
+    /// Format of a captured file, from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::ALL.into_iter().find(|f| f.extension() == extension)
+    }
+}
+
+/// Path of a capture file: `<dir>/<prefix>_<YYYYMMDD>_<HHMMSS>.<ext>`
+pub fn capture_path(
+    output_dir: &Path,
+    prefix: &str,
+    timestamp: DateTime<Utc>,
+    format: DataFormat,
+) -> PathBuf {
+    output_dir.join(format!(
+        "{}_{}.{}",
+        prefix,
+        timestamp.format("%Y%m%d_%H%M%S"),
+        format.extension()
+    ))
+}
+
+/// Schema of the records stored under a file prefix
+pub fn schema_for_prefix(prefix: &str) -> Option<Schema> {
+    match prefix {
+        "price_ticks" | "klines" => Some(price_tick_schema()),
+        "orderbook" => Some(orderbook_schema()),
+        "signals" => Some(signal_schema()),
+        "signal_outcomes" => Some(signal_outcome_schema()),
+        _ => None,
+    }
+}
+
+/// Writes record batches to one capture file at a time
+pub trait RecordSink: Send {
+    /// Format this sink writes
+    fn format(&self) -> DataFormat;
+
+    /// Start a file for `prefix` stamped `timestamp`, closing any open one
+    fn open(
+        &mut self,
+        prefix: &str,
+        timestamp: DateTime<Utc>,
+        schema: SchemaRef,
+    ) -> anyhow::Result<()>;
+
+    /// Append a batch to the open file
+    fn write_batch(&mut self, batch: &RecordBatch) -> anyhow::Result<()>;
+
+    /// Finish the open file and move it into place, returning its path
+    fn close(&mut self) -> anyhow::Result<Option<PathBuf>>;
+
+    /// Close the current file and open the next one
+    fn rotate(
+        &mut self,
+        prefix: &str,
+        timestamp: DateTime<Utc>,
+        schema: SchemaRef,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let closed = self.close()?;
+        self.open(prefix, timestamp, schema)?;
+        Ok(closed)
+    }
+}
+
+/// Sink for `format` writing under `output_dir`, stamped with the active
+/// config fingerprint where the format has room for it
+pub fn sink_for(
+    format: DataFormat,
+    output_dir: PathBuf,
+    parquet: &ParquetTuning,
+) -> Box<dyn RecordSink> {
+    let fingerprint = fingerprint::active().cloned();
+    match format {
+        DataFormat::Parquet => {
+            Box::new(ParquetSink::new(output_dir, fingerprint).with_tuning(parquet.clone()))
+        }
+        DataFormat::Csv => Box::new(CsvSink::new(output_dir)),
+        DataFormat::Arrow => Box::new(IpcSink::new(output_dir, fingerprint)),
+    }
+}
+
+/// A file written under a temporary name until committed
+pub(super) struct PartialFile {
+    path: PathBuf,
+    tmp: PathBuf,
+}
+
+impl PartialFile {
+    pub(super) fn create(path: PathBuf) -> anyhow::Result<(Self, File)> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".");
+        tmp.push(PARTIAL_SUFFIX);
+        let tmp = PathBuf::from(tmp);
+        let file = File::create(&tmp)?;
+        Ok((Self { path, tmp }, file))
+    }
+
+    pub(super) fn commit(self, file: File) -> anyhow::Result<PathBuf> {
+        file.sync_all()?;
+        drop(file);
+        // Sealed before it is renamed, so a reader that sees the file also
+        // sees its seal
+        seal(&self.tmp, &self.path)?;
+        fs::rename(&self.tmp, &self.path)?;
+        Ok(self.path.clone())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        // No-op once committed; otherwise discard the partial file
+        let _ = fs::remove_file(&self.tmp);
+    }
+}
+
+/// Parquet files stamped with the config fingerprint
+pub struct ParquetSink {
+    output_dir: PathBuf,
+    fingerprint: Option<ConfigFingerprint>,
+    tuning: ParquetTuning,
+    open: Option<(PartialFile, ArrowWriter<File>)>,
+}
+
+impl ParquetSink {
+    /// Create a sink writing under `output_dir`
+    pub fn new(output_dir: PathBuf, fingerprint: Option<ConfigFingerprint>) -> Self {
+        Self {
+            output_dir,
+            fingerprint,
+            tuning: ParquetTuning::default(),
+            open: None,
+        }
+    }
+
+    /// Encode files with `tuning` instead of the defaults
+    pub fn with_tuning(mut self, tuning: ParquetTuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
+}
+
+impl RecordSink for ParquetSink {
+    fn format(&self) -> DataFormat {
+        DataFormat::Parquet
+    }
+
+    fn open(
+        &mut self,
+        prefix: &str,
+        timestamp: DateTime<Utc>,
+        schema: SchemaRef,
+    ) -> anyhow::Result<()> {
+        self.close()?;
+        let path = capture_path(&self.output_dir, prefix, timestamp, self.format());
+        let (partial, file) = PartialFile::create(path)?;
+        let props = self.tuning.properties(prefix, self.fingerprint.as_ref())?;
+        let writer = ArrowWriter::try_new(file, schema, Some(props))?;
+        self.open = Some((partial, writer));
+        Ok(())
+    }
+
+    fn write_batch(&mut self, batch: &RecordBatch) -> anyhow::Result<()> {
+        let (_, writer) = self
+            .open
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("No open Parquet file"))?;
+        writer.write(batch)?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> anyhow::Result<Option<PathBuf>> {
+        let Some((partial, writer)) = self.open.take() else {
+            return Ok(None);
+        };
+        let file = writer.into_inner()?;
+        partial.commit(file).map(Some)
+    }
+}
+
+/// CSV files with a header row and RFC 3339 timestamps
+pub struct CsvSink {
+    output_dir: PathBuf,
+    open: Option<(PartialFile, csv::Writer<File>)>,
+}
+
+impl CsvSink {
+    /// Create a sink writing under `output_dir`
+    pub fn new(output_dir: PathBuf) -> Self {
+        Self {
+            output_dir,
+            open: None,
+        }
+    }
+}
+
+impl RecordSink for CsvSink {
+    fn format(&self) -> DataFormat {
+        DataFormat::Csv
+    }
+
+    fn open(
+        &mut self,
+        prefix: &str,
+        timestamp: DateTime<Utc>,
+        _schema: SchemaRef,
+    ) -> anyhow::Result<()> {
+        self.close()?;
+        let path = capture_path(&self.output_dir, prefix, timestamp, self.format());
+        let (partial, file) = PartialFile::create(path)?;
+        let writer = csv::WriterBuilder::new()
+            .with_header(true)
+            .with_timestamp_tz_format(CSV_TIMESTAMP_FORMAT.to_string())
+            .build(file);
+        self.open = Some((partial, writer));
+        Ok(())
+    }
+
+    fn write_batch(&mut self, batch: &RecordBatch) -> anyhow::Result<()> {
+        let (_, writer) = self
+            .open
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("No open CSV file"))?;
+        writer.write(&cast_batch(batch, &Arc::new(csv_schema(&batch.schema())))?)?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> anyhow::Result<Option<PathBuf>> {
+        let Some((partial, writer)) = self.open.take() else {
+            return Ok(None);
+        };
+        partial.commit(writer.into_inner()).map(Some)
+    }
+}
+
+/// Arrow IPC stream files; the fingerprint goes in the schema metadata
+pub struct IpcSink {
+    output_dir: PathBuf,
+    fingerprint: Option<ConfigFingerprint>,
+    open: Option<(PartialFile, StreamWriter<File>, SchemaRef)>,
+}
+
+impl IpcSink {
+    /// Create a sink writing under `output_dir`
+    pub fn new(output_dir: PathBuf, fingerprint: Option<ConfigFingerprint>) -> Self {
+        Self {
+            output_dir,
+            fingerprint,
+            open: None,
+        }
+    }
+}
+
+impl RecordSink for IpcSink {
+    fn format(&self) -> DataFormat {
+        DataFormat::Arrow
+    }
+
+    fn open(
+        &mut self,
+        prefix: &str,
+        timestamp: DateTime<Utc>,
+        schema: SchemaRef,
+    ) -> anyhow::Result<()> {
+        self.close()?;
+        let path = capture_path(&self.output_dir, prefix, timestamp, self.format());
+        let (partial, file) = PartialFile::create(path)?;
+        let mut metadata = schema.metadata().clone();
+        if let Some(fp) = &self.fingerprint {
+            metadata.extend(
+                fp.metadata()
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value)),
+            );
+        }
+        let schema = Arc::new(schema.as_ref().clone().with_metadata(metadata));
+        let writer = StreamWriter::try_new(file, &schema)?;
+        self.open = Some((partial, writer, schema));
+        Ok(())
+    }
+
+    fn write_batch(&mut self, batch: &RecordBatch) -> anyhow::Result<()> {
+        let (_, writer, schema) = self
+            .open
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("No open Arrow IPC file"))?;
+        writer.write(&batch.clone().with_schema(schema.clone())?)?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> anyhow::Result<Option<PathBuf>> {
+        let Some((partial, mut writer, _)) = self.open.take() else {
+            return Ok(None);
+        };
+        writer.finish()?;
+        partial.commit(writer.into_inner()?).map(Some)
+    }
+}
+
+/// `schema` with zoned timestamps spelled as a fixed UTC offset
+fn csv_schema(schema: &Schema) -> Schema {
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|field| match field.data_type() {
+            DataType::Timestamp(unit, Some(_)) => field
+                .as_ref()
+                .clone()
+                .with_data_type(DataType::Timestamp(*unit, Some(CSV_TIMEZONE.into()))),
+            _ => field.as_ref().clone(),
+        })
+        .collect();
+    Schema::new_with_metadata(fields, schema.metadata().clone())
+}
+
+/// Cast every column of `batch` to the matching type in `schema`
+fn cast_batch(batch: &RecordBatch, schema: &SchemaRef) -> anyhow::Result<RecordBatch> {
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| cast(column, field.data_type()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Check that a capture file can be opened for reading without reading
+/// its rows: a Parquet footer, an Arrow stream's schema, a CSV prefix's
+/// schema
+///
+/// A file that passes can still hold a corrupt page, but a truncated or
+/// half-written file fails here.
+pub fn validate_capture(path: &Path) -> anyhow::Result<()> {
+    let format = DataFormat::from_path(path)
+        .ok_or_else(|| anyhow::anyhow!("Unknown capture format: {:?}", path))?;
+    let file = File::open(path)?;
+    match format {
+        DataFormat::Parquet => {
+            ParquetRecordBatchReaderBuilder::try_new(file)?;
+        }
+        DataFormat::Csv => {
+            let prefix = super::file_prefix(path).unwrap_or_default();
+            schema_for_prefix(&prefix)
+                .ok_or_else(|| anyhow::anyhow!("No schema for CSV prefix '{}'", prefix))?;
+        }
+        DataFormat::Arrow => {
+            StreamReader::try_new(BufReader::new(file), None)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read every batch from a capture file in any format
+///
+/// CSV carries no schema, so it is looked up from the file prefix.
+pub fn read_batches(path: &Path) -> anyhow::Result<Vec<RecordBatch>> {
+    let format = DataFormat::from_path(path)
+        .ok_or_else(|| anyhow::anyhow!("Unknown capture format: {:?}", path))?;
+    let file = File::open(path)?;
+    let batches = match format {
+        DataFormat::Parquet => read_parquet_batches(file)?,
+        DataFormat::Csv => {
+            let prefix = super::file_prefix(path).unwrap_or_default();
+            let schema = schema_for_prefix(&prefix)
+                .ok_or_else(|| anyhow::anyhow!("No schema for CSV prefix '{}'", prefix))?;
+            let schema = Arc::new(schema);
+            csv::ReaderBuilder::new(Arc::new(csv_schema(&schema)))
+                .with_header(true)
+                .build(BufReader::new(file))?
+                .map(|batch| cast_batch(&batch?, &schema))
+                .collect::<anyhow::Result<Vec<_>>>()?
+        }
+        DataFormat::Arrow => {
+            StreamReader::try_new(BufReader::new(file), None)?.collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::parquet::{orderbook_batch, price_tick_batch, price_ticks_from_batch};
+    use crate::data::{scan_data_files, OrderBookRecord, PriceTickRecord};
+    use crate::fingerprint::CONFIG_HASH_KEY;
+    use crate::orderbook::BookUpdateKind;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn ts(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn ticks() -> Vec<PriceTickRecord> {
+        vec![
+            PriceTickRecord::new(
+                ts("2025-01-04T12:30:00.123456Z"),
+                Arc::from("BTCUSDT"),
+                dec!(97000.1234),
+                ts("2025-01-04T12:30:00.100Z"),
+            ),
+            PriceTickRecord::new(
+                ts("2025-01-04T12:30:01Z"),
+                Arc::from("BTCUSDT"),
+                dec!(97001.5),
+                ts("2025-01-04T12:30:00.900Z"),
+            ),
+        ]
+    }
+
+    fn fingerprint() -> ConfigFingerprint {
+        ConfigFingerprint {
+            hash: "abc123".to_string(),
+            config: "{}".to_string(),
+        }
+    }
+
+    fn sinks(dir: &Path) -> Vec<Box<dyn RecordSink>> {
+        vec![
+            Box::new(ParquetSink::new(dir.to_path_buf(), Some(fingerprint()))),
+            Box::new(CsvSink::new(dir.to_path_buf())),
+            Box::new(IpcSink::new(dir.to_path_buf(), Some(fingerprint()))),
+        ]
+    }
+
+    fn write(sink: &mut dyn RecordSink, prefix: &str, batch: &RecordBatch) -> PathBuf {
+        sink.open(prefix, ts("2025-01-04T12:30:00Z"), batch.schema())
+            .unwrap();
+        sink.write_batch(batch).unwrap();
+        sink.close().unwrap().unwrap()
+    }
+
+    fn no_partial_files(dir: &Path) -> bool {
+        fs::read_dir(dir)
+            .unwrap()
+            .all(|e| e.unwrap().path().extension().unwrap() != PARTIAL_SUFFIX)
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        for format in DataFormat::ALL {
+            let path = capture_path(Path::new("/d"), "orderbook", Utc::now(), format);
+            assert_eq!(DataFormat::from_path(&path), Some(format));
+        }
+        assert_eq!(DataFormat::from_path(Path::new("x.parquet.tmp")), None);
+        assert_eq!(DataFormat::from_path(Path::new("x")), None);
+    }
+
+    #[test]
+    fn test_price_ticks_round_trip_every_format() {
+        let temp_dir = TempDir::new().unwrap();
+        let batch = price_tick_batch(&ticks()).unwrap();
+
+        for mut sink in sinks(temp_dir.path()) {
+            let path = write(sink.as_mut(), "price_ticks", &batch);
+            assert_eq!(DataFormat::from_path(&path), Some(sink.format()));
+
+            let read = read_batches(&path).unwrap();
+            assert_eq!(read.len(), 1, "{:?}", sink.format());
+            let decoded = price_ticks_from_batch(&read[0]).unwrap();
+            let expected = ticks();
+            assert_eq!(decoded.len(), expected.len());
+            for (got, want) in decoded.iter().zip(&expected) {
+                assert_eq!(got.timestamp, want.timestamp, "{:?}", sink.format());
+                assert_eq!(got.symbol, want.symbol);
+                assert_eq!(got.price, want.price);
+                assert_eq!(got.exchange_ts, want.exchange_ts);
+            }
+        }
+        assert!(no_partial_files(temp_dir.path()));
+    }
+
+    #[test]
+    fn test_orderbook_round_trip_every_format() {
+        let temp_dir = TempDir::new().unwrap();
+        let snapshots = vec![
+            OrderBookRecord {
+                timestamp: ts("2025-01-04T12:30:00Z"),
+                token_id: Arc::from("yes-token"),
+                bids: vec![(dec!(0.55), dec!(100)), (dec!(0.54), dec!(200))],
+                asks: vec![(dec!(0.56), dec!(150))],
+                crossed: false,
+                kind: BookUpdateKind::Snapshot,
+            },
+            OrderBookRecord {
+                timestamp: ts("2025-01-04T12:30:01Z"),
+                token_id: Arc::from("no-token"),
+                bids: vec![],
+                asks: vec![(dec!(0.46), dec!(75))],
+                crossed: true,
+                kind: BookUpdateKind::Snapshot,
+            },
+        ];
+        let batch = orderbook_batch(&snapshots).unwrap();
+
+        for mut sink in sinks(temp_dir.path()) {
+            let path = write(sink.as_mut(), "orderbook", &batch);
+            let read = read_batches(&path).unwrap();
+            assert_eq!(read.len(), 1);
+            // Metadata differs by format; the columns must not
+            assert_eq!(read[0].columns(), batch.columns(), "{:?}", sink.format());
+        }
+    }
+
+    #[test]
+    fn test_ipc_schema_carries_fingerprint() {
+        let temp_dir = TempDir::new().unwrap();
+        let batch = price_tick_batch(&ticks()).unwrap();
+        let mut sink = IpcSink::new(temp_dir.path().to_path_buf(), Some(fingerprint()));
+        let path = write(&mut sink, "price_ticks", &batch);
+
+        let reader = StreamReader::try_new(File::open(path).unwrap(), None).unwrap();
+        assert_eq!(
+            reader
+                .schema()
+                .metadata()
+                .get(CONFIG_HASH_KEY)
+                .map(String::as_str),
+            Some("abc123")
+        );
+    }
+
+    #[test]
+    fn test_rotate_closes_previous_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let batch = price_tick_batch(&ticks()).unwrap();
+        let mut sink = CsvSink::new(temp_dir.path().to_path_buf());
+
+        sink.open("price_ticks", ts("2025-01-04T12:00:00Z"), batch.schema())
+            .unwrap();
+        sink.write_batch(&batch).unwrap();
+        let first = sink
+            .rotate("price_ticks", ts("2025-01-04T13:00:00Z"), batch.schema())
+            .unwrap()
+            .unwrap();
+        assert!(first.exists());
+        assert!(!no_partial_files(temp_dir.path()));
+
+        sink.write_batch(&batch).unwrap();
+        let second = sink.close().unwrap().unwrap();
+        assert_ne!(first, second);
+        assert!(no_partial_files(temp_dir.path()));
+        assert_eq!(sink.close().unwrap(), None);
+    }
+
+    #[test]
+    fn test_dropped_sink_leaves_no_partial_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let batch = price_tick_batch(&ticks()).unwrap();
+        {
+            let mut sink = ParquetSink::new(temp_dir.path().to_path_buf(), None);
+            sink.open("price_ticks", Utc::now(), batch.schema())
+                .unwrap();
+            sink.write_batch(&batch).unwrap();
+        }
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_switching_formats_between_runs() {
+        // A restart with a different format leaves earlier files in place;
+        // retention and readers must see both generations
+        let temp_dir = TempDir::new().unwrap();
+        let batch = price_tick_batch(&ticks()).unwrap();
+        let starts = [
+            ("2025-01-04T12:00:00Z", DataFormat::Parquet),
+            ("2025-01-04T13:00:00Z", DataFormat::Csv),
+            ("2025-01-04T14:00:00Z", DataFormat::Arrow),
+        ];
+        for (start, format) in starts {
+            let mut sink = sink_for(
+                format,
+                temp_dir.path().to_path_buf(),
+                &ParquetTuning::default(),
+            );
+            sink.open("price_ticks", ts(start), batch.schema()).unwrap();
+            sink.write_batch(&batch).unwrap();
+            sink.close().unwrap();
+        }
+
+        let mut files = scan_data_files(temp_dir.path(), false).unwrap();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(files.len(), 3);
+        for (file, (_, format)) in files.iter().zip(starts) {
+            assert_eq!(file.prefix, "price_ticks");
+            assert_eq!(DataFormat::from_path(&file.path), Some(format));
+            let read = read_batches(&file.path).unwrap();
+            assert_eq!(price_ticks_from_batch(&read[0]).unwrap().len(), 2);
+        }
+    }
+}
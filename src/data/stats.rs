@@ -0,0 +1,776 @@
+//! Book microstructure by minute of the market window
+//!
+//! How wide and deep the YES book is, how often it changes, and how often
+//! it has only one side or none, for each minute 0 to 14 of the 15-minute
+//! window, over every captured window of each asset. Each captured row
+//! holds the token's whole book, so rows are classified one by one and
+//! captures are read a batch at a time.
+//!
+//! Windows come from market metadata, the `market_opened` entries of the
+//! session's trade journal: a market's YES rows fall in the minute since
+//! its open, and its NO rows are left out. A token with no metadata is
+//! windowed by its activity instead, on the 15-minute grid window in which
+//! it has the most rows, and reported under the asset [`INFERRED_ASSET`];
+//! both tokens of such a market are counted.
+//!
+//! Spread percentiles cover two-sided books only. Depth is the size at the
+//! best bid plus the best ask, in whole shares. Updates count rows that
+//! changed the book, leaving out the `keepalive` rows deduplication writes
+//! for unchanged books, and are averaged over the windows of the asset,
+//! including windows without an update in that minute. One-sided and empty
+//! shares are of every row in the minute.
+//!
+//! With `[data.stats] live` on, the engine also keeps recent spreads of
+//! each asset and window minute and publishes where the current spread
+//! ranks among them.
+
+use super::parquet::{decimal_column, str_column, writer_properties, OrderBookRecord};
+use super::retention::scan_data_files;
+use super::sink::{read_batches, DataFormat};
+use crate::fingerprint;
+use crate::market::{window_start, Market};
+use crate::orderbook::{BookUpdateKind, OrderBook};
+use anyhow::Context;
+use arrow::array::{ArrayRef, UInt64Array};
+use arrow::csv;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Utc};
+use parquet::arrow::ArrowWriter;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Minutes in a market window
+pub const WINDOW_MINUTES: usize = 15;
+
+/// Asset reported for tokens without market metadata
+pub const INFERRED_ASSET: &str = "unknown";
+
+/// Default spreads kept per asset and window minute for the live percentile
+pub const DEFAULT_LIVE_SPREAD_SAMPLES: usize = 500;
+
+/// Microstructure statistics settings, under `[data.stats]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MicrostructureConfig {
+    /// Publish the live spread percentile of the current window minute
+    #[serde(default)]
+    pub live: bool,
+    /// Recent spreads kept per asset and window minute to rank against
+    #[serde(default = "default_live_samples")]
+    pub live_samples: usize,
+}
+
+fn default_live_samples() -> usize {
+    DEFAULT_LIVE_SPREAD_SAMPLES
+}
+
+impl Default for MicrostructureConfig {
+    fn default() -> Self {
+        Self {
+            live: false,
+            live_samples: default_live_samples(),
+        }
+    }
+}
+
+/// Minute of the window opened at `open` that `at` falls in, if any
+pub fn window_minute(open: DateTime<Utc>, at: DateTime<Utc>) -> Option<usize> {
+    let minute = usize::try_from((at - open).num_minutes()).ok()?;
+    (at >= open && minute < WINDOW_MINUTES).then_some(minute)
+}
+
+/// Shape of one captured book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookShape {
+    /// Bids and asks
+    TwoSided,
+    /// Bids or asks only
+    OneSided,
+    /// No levels at all
+    Empty,
+}
+
+impl BookShape {
+    /// Shape of a book with `bids` and `asks` levels
+    pub fn of(bids: usize, asks: usize) -> Self {
+        match (bids > 0, asks > 0) {
+            (true, true) => BookShape::TwoSided,
+            (false, false) => BookShape::Empty,
+            _ => BookShape::OneSided,
+        }
+    }
+}
+
+/// Counts and distributions of one token's rows in one window minute
+#[derive(Debug, Clone, Default)]
+struct MinuteAccum {
+    rows: u64,
+    updates: u64,
+    one_sided: u64,
+    empty: u64,
+    /// Spread of each two-sided row, by value
+    spreads: BTreeMap<Decimal, u64>,
+    /// Top-level depth of each row, in whole shares
+    depths: BTreeMap<Decimal, u64>,
+}
+
+impl MinuteAccum {
+    fn observe(&mut self, record: &OrderBookRecord) {
+        self.rows += 1;
+        if record.kind != BookUpdateKind::KeepAlive {
+            self.updates += 1;
+        }
+        let (bid, ask) = (record.bids.first(), record.asks.first());
+        match BookShape::of(record.bids.len(), record.asks.len()) {
+            BookShape::Empty => self.empty += 1,
+            BookShape::OneSided => self.one_sided += 1,
+            BookShape::TwoSided => {}
+        }
+        if let (Some((bid, _)), Some((ask, _))) = (bid, ask) {
+            *self.spreads.entry(ask - bid).or_default() += 1;
+        }
+        let depth = bid.map_or(Decimal::ZERO, |(_, size)| *size)
+            + ask.map_or(Decimal::ZERO, |(_, size)| *size);
+        *self.depths.entry(depth.round()).or_default() += 1;
+    }
+
+    fn merge(&mut self, other: &MinuteAccum) {
+        self.rows += other.rows;
+        self.updates += other.updates;
+        self.one_sided += other.one_sided;
+        self.empty += other.empty;
+        for (into, from) in [
+            (&mut self.spreads, &other.spreads),
+            (&mut self.depths, &other.depths),
+        ] {
+            for (value, count) in from {
+                *into.entry(*value).or_default() += count;
+            }
+        }
+    }
+}
+
+/// Where a token's rows are windowed
+#[derive(Debug, Clone)]
+enum Windowing {
+    /// YES token of a known market
+    Known { asset: String, open: DateTime<Utc> },
+    /// NO token of a known market, left out
+    Skipped,
+}
+
+/// Accumulates book microstructure statistics over captured rows
+#[derive(Debug, Default)]
+pub struct MicrostructureStats {
+    tokens: HashMap<String, Windowing>,
+    /// Per token and window open, one accumulator per minute
+    windows: HashMap<(Arc<str>, DateTime<Utc>), Vec<MinuteAccum>>,
+    /// Rows of known markets' YES tokens outside their window
+    outside: u64,
+}
+
+impl MicrostructureStats {
+    /// Statistics windowing the YES tokens of `markets` by their open time
+    pub fn new(markets: &[Market]) -> Self {
+        let mut tokens = HashMap::new();
+        for market in markets {
+            let asset = match market.asset.is_empty() {
+                true => INFERRED_ASSET.to_string(),
+                false => market.asset.to_uppercase(),
+            };
+            tokens.insert(
+                market.yes_token_id.clone(),
+                Windowing::Known {
+                    asset,
+                    open: market.open_time,
+                },
+            );
+            tokens.insert(market.no_token_id.clone(), Windowing::Skipped);
+        }
+        Self {
+            tokens,
+            ..Default::default()
+        }
+    }
+
+    /// Count one captured row
+    pub fn observe(&mut self, record: &OrderBookRecord) {
+        let open = match self.tokens.get(&*record.token_id) {
+            Some(Windowing::Skipped) => return,
+            Some(Windowing::Known { open, .. }) => *open,
+            None => window_start(record.timestamp),
+        };
+        let Some(minute) = window_minute(open, record.timestamp) else {
+            self.outside += 1;
+            return;
+        };
+        self.windows
+            .entry((record.token_id.clone(), open))
+            .or_insert_with(|| vec![MinuteAccum::default(); WINDOW_MINUTES])[minute]
+            .observe(record);
+    }
+
+    /// Statistics per asset and window minute over every window seen
+    pub fn report(&self) -> MicrostructureReport {
+        // A token without metadata keeps only its busiest window
+        let mut inferred: HashMap<&Arc<str>, (u64, DateTime<Utc>)> = HashMap::new();
+        for ((token, open), minutes) in &self.windows {
+            if self.tokens.contains_key(&**token) {
+                continue;
+            }
+            let rows = minutes.iter().map(|m| m.rows).sum::<u64>();
+            let best = inferred.entry(token).or_insert((rows, *open));
+            if (rows, std::cmp::Reverse(*open)) > (best.0, std::cmp::Reverse(best.1)) {
+                *best = (rows, *open);
+            }
+        }
+
+        let mut by_asset: BTreeMap<&str, Vec<&Vec<MinuteAccum>>> = BTreeMap::new();
+        for ((token, open), minutes) in &self.windows {
+            let asset = match self.tokens.get(&**token) {
+                Some(Windowing::Known { asset, .. }) => asset.as_str(),
+                _ if inferred.get(token).is_some_and(|(_, best)| best == open) => INFERRED_ASSET,
+                _ => continue,
+            };
+            by_asset.entry(asset).or_default().push(minutes);
+        }
+
+        let mut rows = vec![];
+        for (asset, windows) in &by_asset {
+            for minute in 0..WINDOW_MINUTES {
+                let mut total = MinuteAccum::default();
+                let mut updates: Vec<u64> = vec![];
+                for window in windows {
+                    total.merge(&window[minute]);
+                    updates.push(window[minute].updates);
+                }
+                rows.push(MinuteStats::new(asset, minute, &total, updates));
+            }
+        }
+        MicrostructureReport {
+            rows,
+            windows: by_asset.values().map(Vec::len).sum(),
+            inferred: inferred.len(),
+            outside: self.outside,
+        }
+    }
+}
+
+/// Book microstructure of one asset in one window minute
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinuteStats {
+    pub asset: String,
+    /// Minutes since the window opened, 0 to 14
+    pub minute: usize,
+    /// Windows of the asset counted
+    pub windows: u64,
+    /// Captured rows in the minute over every window
+    pub rows: u64,
+    /// Spread percentiles and mean of two-sided books
+    pub spread_p10: Option<Decimal>,
+    pub spread_p50: Option<Decimal>,
+    pub spread_p90: Option<Decimal>,
+    pub spread_mean: Option<Decimal>,
+    /// Best bid plus best ask size, in shares
+    pub depth_p50: Option<Decimal>,
+    pub depth_p90: Option<Decimal>,
+    /// Book changes in the minute per window
+    pub updates_mean: Decimal,
+    pub updates_p50: Decimal,
+    /// Share of rows with one side only
+    pub one_sided_share: Decimal,
+    /// Share of rows with no levels
+    pub empty_share: Decimal,
+}
+
+impl MinuteStats {
+    fn new(asset: &str, minute: usize, total: &MinuteAccum, mut updates: Vec<u64>) -> Self {
+        updates.sort_unstable();
+        let windows = updates.len() as u64;
+        let share = |n: u64| match total.rows {
+            0 => Decimal::ZERO,
+            rows => Decimal::from(n) / Decimal::from(rows),
+        };
+        Self {
+            asset: asset.to_string(),
+            minute,
+            windows,
+            rows: total.rows,
+            spread_p10: percentile(&total.spreads, dec!(0.1)),
+            spread_p50: percentile(&total.spreads, dec!(0.5)),
+            spread_p90: percentile(&total.spreads, dec!(0.9)),
+            spread_mean: mean(&total.spreads),
+            depth_p50: percentile(&total.depths, dec!(0.5)),
+            depth_p90: percentile(&total.depths, dec!(0.9)),
+            updates_mean: match windows {
+                0 => Decimal::ZERO,
+                n => Decimal::from(updates.iter().sum::<u64>()) / Decimal::from(n),
+            },
+            updates_p50: Decimal::from(updates.get(rank(windows, dec!(0.5))).copied().unwrap_or(0)),
+            one_sided_share: share(total.one_sided),
+            empty_share: share(total.empty),
+        }
+    }
+}
+
+/// Index of the `q` quantile among `n` sorted values, by nearest rank
+fn rank(n: u64, q: Decimal) -> usize {
+    let rank = (Decimal::from(n) * q).ceil().to_u64().unwrap_or(1);
+    rank.clamp(1, n.max(1)) as usize - 1
+}
+
+/// The `q` quantile of a histogram, by nearest rank
+fn percentile(histogram: &BTreeMap<Decimal, u64>, q: Decimal) -> Option<Decimal> {
+    let total: u64 = histogram.values().sum();
+    if total == 0 {
+        return None;
+    }
+    let target = rank(total, q) as u64;
+    let mut seen = 0;
+    histogram.iter().find_map(|(value, count)| {
+        seen += count;
+        (seen > target).then_some(*value)
+    })
+}
+
+fn mean(histogram: &BTreeMap<Decimal, u64>) -> Option<Decimal> {
+    let total: u64 = histogram.values().sum();
+    let sum: Decimal = histogram
+        .iter()
+        .map(|(value, count)| value * Decimal::from(*count))
+        .sum();
+    (total > 0).then(|| (sum / Decimal::from(total)).round_dp(4))
+}
+
+/// Book microstructure per asset and window minute
+#[derive(Debug, Clone)]
+pub struct MicrostructureReport {
+    /// One row per asset and minute, by asset then minute
+    pub rows: Vec<MinuteStats>,
+    /// Token windows counted
+    pub windows: usize,
+    /// Tokens windowed by their activity, for want of metadata
+    pub inferred: usize,
+    /// Rows of known markets outside their window, left out
+    pub outside: u64,
+}
+
+impl MicrostructureReport {
+    /// The row of `asset` in `minute`
+    pub fn minute(&self, asset: &str, minute: usize) -> Option<&MinuteStats> {
+        self.rows
+            .iter()
+            .find(|r| r.asset == asset && r.minute == minute)
+    }
+
+    /// Write the rows to `path`, as Parquet or CSV by its extension
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let batch = microstructure_batch(&self.rows)?;
+        match DataFormat::from_path(path) {
+            Some(DataFormat::Parquet) => {
+                let props = writer_properties(fingerprint::active());
+                let mut writer =
+                    ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(props))?;
+                writer.write(&batch)?;
+                writer.close()?;
+            }
+            Some(DataFormat::Csv) => {
+                let mut writer = csv::WriterBuilder::new()
+                    .with_header(true)
+                    .build(File::create(path)?);
+                writer.write(&batch)?;
+            }
+            _ => anyhow::bail!("Statistics are written as .parquet or .csv, not {:?}", path),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MicrostructureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Book microstructure: {} windows ({} tokens windowed by activity), {} rows outside their window",
+            self.windows, self.inferred, self.outside
+        )?;
+        writeln!(
+            f,
+            "  {:<8} {:>3} {:>7} {:>8} {:>8} {:>8} {:>8} {:>9} {:>9} {:>8} {:>9} {:>6}",
+            "asset",
+            "min",
+            "windows",
+            "rows",
+            "sprd p10",
+            "sprd p50",
+            "sprd p90",
+            "depth p50",
+            "depth p90",
+            "upd/win",
+            "one-sided",
+            "empty"
+        )?;
+        let opt = |v: Option<Decimal>| v.map_or("-".to_string(), |v| v.normalize().to_string());
+        let pct = |v: Decimal| format!("{:.1}%", v * Decimal::ONE_HUNDRED);
+        for r in &self.rows {
+            writeln!(
+                f,
+                "  {:<8} {:>3} {:>7} {:>8} {:>8} {:>8} {:>8} {:>9} {:>9} {:>8} {:>9} {:>6}",
+                r.asset,
+                r.minute,
+                r.windows,
+                r.rows,
+                opt(r.spread_p10),
+                opt(r.spread_p50),
+                opt(r.spread_p90),
+                opt(r.depth_p50),
+                opt(r.depth_p90),
+                r.updates_mean.round_dp(1),
+                pct(r.one_sided_share),
+                pct(r.empty_share)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Schema of written microstructure statistics
+pub fn microstructure_schema() -> Schema {
+    let text = |name: &str, nullable: bool| Field::new(name, DataType::Utf8, nullable);
+    Schema::new(vec![
+        text("asset", false),
+        Field::new("minute", DataType::UInt64, false),
+        Field::new("windows", DataType::UInt64, false),
+        Field::new("rows", DataType::UInt64, false),
+        text("spread_p10", true),
+        text("spread_p50", true),
+        text("spread_p90", true),
+        text("spread_mean", true),
+        text("depth_p50", true),
+        text("depth_p90", true),
+        text("updates_mean", false),
+        text("updates_p50", false),
+        text("one_sided_share", false),
+        text("empty_share", false),
+    ])
+}
+
+/// Microstructure statistics as a batch in [`microstructure_schema`]
+pub fn microstructure_batch(rows: &[MinuteStats]) -> anyhow::Result<RecordBatch> {
+    let count = |value: fn(&MinuteStats) -> u64| -> ArrayRef {
+        Arc::new(UInt64Array::from_iter_values(rows.iter().map(value)))
+    };
+    let columns: Vec<ArrayRef> = vec![
+        str_column(rows, |r| &r.asset),
+        count(|r| r.minute as u64),
+        count(|r| r.windows),
+        count(|r| r.rows),
+        decimal_column(rows, |r| r.spread_p10),
+        decimal_column(rows, |r| r.spread_p50),
+        decimal_column(rows, |r| r.spread_p90),
+        decimal_column(rows, |r| r.spread_mean),
+        decimal_column(rows, |r| r.depth_p50),
+        decimal_column(rows, |r| r.depth_p90),
+        decimal_column(rows, |r| Some(r.updates_mean.round_dp(4))),
+        decimal_column(rows, |r| Some(r.updates_p50)),
+        decimal_column(rows, |r| Some(r.one_sided_share.round_dp(4))),
+        decimal_column(rows, |r| Some(r.empty_share.round_dp(4))),
+    ];
+    Ok(RecordBatch::try_new(
+        Arc::new(microstructure_schema()),
+        columns,
+    )?)
+}
+
+/// Microstructure statistics over every order book capture in `dir`,
+/// windowed by `markets`
+pub fn microstructure_stats(
+    dir: &Path,
+    markets: &[Market],
+) -> anyhow::Result<MicrostructureReport> {
+    let mut stats = MicrostructureStats::new(markets);
+    for file in scan_data_files(dir, true)? {
+        if file.prefix != "orderbook" {
+            continue;
+        }
+        for batch in read_batches(&file.path).with_context(|| format!("{:?}", file.path))? {
+            for record in super::parquet::orderbooks_from_batch(&batch)? {
+                stats.observe(&record);
+            }
+        }
+    }
+    Ok(stats.report())
+}
+
+/// Recent spreads of each asset and window minute, for ranking the spread
+/// the engine sees now
+#[derive(Debug)]
+pub struct LiveMicrostructure {
+    samples: usize,
+    spreads: HashMap<(String, usize), VecDeque<Decimal>>,
+}
+
+impl LiveMicrostructure {
+    /// Keep the last `samples` spreads per asset and minute
+    pub fn new(samples: usize) -> Self {
+        Self {
+            samples: samples.max(1),
+            spreads: HashMap::new(),
+        }
+    }
+
+    /// Record the spread of `market`'s YES `book` at `now`, returning the
+    /// share, 0 to 1, of recent spreads in the same window minute below it,
+    /// counting equal spreads as half; `None` with nothing to rank against
+    /// or no spread to rank
+    pub fn observe(
+        &mut self,
+        market: &Market,
+        book: &OrderBook,
+        now: DateTime<Utc>,
+    ) -> Option<Decimal> {
+        let minute = window_minute(market.open_time, now)?;
+        let spread = book.spread()?;
+        let samples = self
+            .spreads
+            .entry((market.asset.to_uppercase(), minute))
+            .or_default();
+        let rank = (!samples.is_empty()).then(|| {
+            let below = samples.iter().filter(|s| **s < spread).count();
+            let equal = samples.iter().filter(|s| **s == spread).count();
+            (Decimal::from(below) + Decimal::from(equal) / dec!(2)) / Decimal::from(samples.len())
+        });
+        if samples.len() == self.samples {
+            samples.pop_front();
+        }
+        samples.push_back(spread);
+        rank
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{read_parquet_batches, ParquetWriter};
+    use crate::orderbook::PriceLevel;
+    use chrono::Duration;
+
+    fn open() -> DateTime<Utc> {
+        // On the 15-minute grid
+        DateTime::from_timestamp(1_700_000_100, 0).unwrap()
+    }
+
+    fn row(token: &str, at: DateTime<Utc>, bid: bool, ask: Option<Decimal>) -> OrderBookRecord {
+        OrderBookRecord {
+            timestamp: at,
+            token_id: Arc::from(token),
+            bids: match bid {
+                true => vec![(dec!(0.50), dec!(100))],
+                false => vec![],
+            },
+            asks: ask
+                .map(|spread| vec![(dec!(0.50) + spread, dec!(40.4))])
+                .unwrap_or_default(),
+            crossed: false,
+            kind: BookUpdateKind::Snapshot,
+        }
+    }
+
+    /// Three rows a minute over the window: an empty book in minute 0, bids
+    /// only in minute 1, then a spread of one cent per minute since open,
+    /// plus one keep-alive in minute 5
+    fn window(token: &str, open: DateTime<Utc>) -> Vec<OrderBookRecord> {
+        let mut rows = vec![];
+        for minute in 0..WINDOW_MINUTES as i64 {
+            for secs in [0, 20, 40] {
+                let at = open + Duration::seconds(minute * 60 + secs);
+                let spread = Decimal::new(minute, 2);
+                rows.push(match minute {
+                    0 => row(token, at, false, None),
+                    1 => row(token, at, true, None),
+                    _ => row(token, at, true, Some(spread)),
+                });
+            }
+        }
+        let mut keepalive = rows[15].clone();
+        keepalive.timestamp += Duration::seconds(50);
+        keepalive.kind = BookUpdateKind::KeepAlive;
+        rows.push(keepalive);
+        rows
+    }
+
+    fn markets() -> Vec<Market> {
+        let next = open() + Duration::minutes(15);
+        let mut a = Market::new("a", "btc", dec!(100000), open(), next);
+        a.yes_token_id = "yes-a".into();
+        a.no_token_id = "no-a".into();
+        let mut b = Market::new("b", "btc", dec!(100000), next, next + Duration::minutes(15));
+        b.yes_token_id = "yes-b".into();
+        b.no_token_id = "no-b".into();
+        vec![a, b]
+    }
+
+    fn capture() -> Vec<OrderBookRecord> {
+        let next = open() + Duration::minutes(15);
+        let mut rows = window("yes-a", open());
+        rows.extend(window("no-a", open()));
+        rows.extend(window("yes-b", next));
+        // Before its open, and after its close
+        rows.push(row("yes-b", next - Duration::seconds(5), true, None));
+        rows.push(row("yes-a", next, true, Some(dec!(0.01))));
+        // A token without metadata, seen once before its busiest window
+        rows.push(row("orphan", open() + Duration::seconds(30), true, None));
+        for secs in 0..5 {
+            let at = next + Duration::seconds(3 * 60 + secs);
+            rows.push(row("orphan", at, true, Some(dec!(0.10))));
+        }
+        rows
+    }
+
+    #[test]
+    fn test_rows_bucket_by_window_minute() {
+        let mut stats = MicrostructureStats::new(&markets());
+        for record in capture() {
+            stats.observe(&record);
+        }
+        let report = stats.report();
+        assert_eq!((report.windows, report.inferred, report.outside), (3, 1, 2));
+        assert_eq!(report.rows.len(), 2 * WINDOW_MINUTES);
+
+        // NO rows are left out: two windows of three rows a minute
+        let empty = report.minute("BTC", 0).unwrap();
+        assert_eq!((empty.windows, empty.rows), (2, 6));
+        assert_eq!(empty.empty_share, Decimal::ONE);
+        assert_eq!(empty.one_sided_share, Decimal::ZERO);
+        assert_eq!((empty.spread_p50, empty.depth_p50), (None, Some(dec!(0))));
+
+        let one_sided = report.minute("BTC", 1).unwrap();
+        assert_eq!(one_sided.one_sided_share, Decimal::ONE);
+        assert_eq!(one_sided.empty_share, Decimal::ZERO);
+        assert_eq!(one_sided.spread_p50, None);
+        assert_eq!(one_sided.depth_p50, Some(dec!(100)));
+
+        let seventh = report.minute("BTC", 7).unwrap();
+        assert_eq!(seventh.spread_p10, Some(dec!(0.07)));
+        assert_eq!(seventh.spread_p90, Some(dec!(0.07)));
+        assert_eq!(seventh.spread_mean, Some(dec!(0.07)));
+        assert_eq!(seventh.depth_p50, Some(dec!(140)));
+        assert_eq!(seventh.one_sided_share + seventh.empty_share, Decimal::ZERO);
+        assert_eq!(
+            (seventh.updates_mean, seventh.updates_p50),
+            (dec!(3), dec!(3))
+        );
+
+        // Each keep-alive is a row, not an update
+        let fifth = report.minute("BTC", 5).unwrap();
+        assert_eq!(fifth.rows, 8);
+        assert_eq!(fifth.updates_mean, dec!(3));
+
+        // Windowed on the grid where it was busiest
+        let orphan = report.minute(INFERRED_ASSET, 3).unwrap();
+        assert_eq!((orphan.windows, orphan.rows), (1, 5));
+        assert_eq!(orphan.spread_p50, Some(dec!(0.10)));
+        assert_eq!(report.minute(INFERRED_ASSET, 0).unwrap().rows, 0);
+    }
+
+    #[test]
+    fn test_percentiles_and_update_spread_over_windows() {
+        let mut stats = MicrostructureStats::new(&[]);
+        let spreads = [dec!(0.01), dec!(0.02), dec!(0.03), dec!(0.04), dec!(0.10)];
+        for (i, spread) in spreads.into_iter().enumerate() {
+            stats.observe(&row(
+                "t",
+                open() + Duration::seconds(i as i64),
+                true,
+                Some(spread),
+            ));
+        }
+        // A quiet window of the same asset
+        let later = open() + Duration::hours(1);
+        stats.observe(&row("u", later + Duration::minutes(1), true, None));
+        let report = stats.report();
+        let first = report.minute(INFERRED_ASSET, 0).unwrap();
+        assert_eq!(first.spread_p10, Some(dec!(0.01)));
+        assert_eq!(first.spread_p50, Some(dec!(0.03)));
+        assert_eq!(first.spread_p90, Some(dec!(0.10)));
+        assert_eq!(first.spread_mean, Some(dec!(0.04)));
+        assert_eq!(first.updates_mean, dec!(2.5));
+        assert_eq!(first.updates_p50, dec!(0));
+    }
+
+    #[test]
+    fn test_stats_stream_captures_and_write_both_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let writer = ParquetWriter::new(dir.path().to_path_buf(), 3600);
+        let rows = capture();
+        let (first, second) = rows.split_at(40);
+        for part in [first, second] {
+            writer
+                .write_orderbook_snapshots(&writer.file_path("orderbook", part[0].timestamp), part)
+                .unwrap();
+        }
+        let report = microstructure_stats(dir.path(), &markets()).unwrap();
+        assert_eq!(report.minute("BTC", 7).unwrap().rows, 6);
+        assert!(report.to_string().contains("BTC"));
+
+        let parquet = dir.path().join("stats/microstructure.parquet");
+        report.write(&parquet).unwrap();
+        let batches = read_parquet_batches(File::open(&parquet).unwrap()).unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 30);
+
+        let csv = dir.path().join("microstructure.csv");
+        report.write(&csv).unwrap();
+        let text = std::fs::read_to_string(&csv).unwrap();
+        assert!(text.starts_with("asset,minute,windows,rows,spread_p10"));
+        assert_eq!(text.lines().count(), 31);
+
+        assert!(report.write(&dir.path().join("stats.json")).is_err());
+    }
+
+    #[test]
+    fn test_live_percentile_ranks_within_the_minute() {
+        let mut live = LiveMicrostructure::new(3);
+        let market = &markets()[0];
+        let book = |spread: Decimal| {
+            let mut book = OrderBook::new("yes-a");
+            book.bids = vec![PriceLevel {
+                price: dec!(0.50),
+                size: dec!(10),
+            }];
+            book.asks = vec![PriceLevel {
+                price: dec!(0.50) + spread,
+                size: dec!(10),
+            }];
+            book
+        };
+        let at = open() + Duration::seconds(90);
+        assert_eq!(live.observe(market, &book(dec!(0.02)), at), None);
+        assert_eq!(live.observe(market, &book(dec!(0.04)), at), Some(dec!(1)));
+        assert_eq!(
+            live.observe(market, &book(dec!(0.02)), at),
+            Some(dec!(0.25))
+        );
+        // Another minute ranks against its own spreads; outside the window
+        // nothing is ranked
+        assert_eq!(live.observe(market, &book(dec!(0.01)), open()), None);
+        assert_eq!(
+            live.observe(market, &book(dec!(0.01)), open() - Duration::seconds(1)),
+            None
+        );
+        // Only the last three are kept
+        assert_eq!(
+            live.observe(market, &book(dec!(0.03)), at),
+            Some(dec!(2) / dec!(3))
+        );
+        assert_eq!(live.observe(market, &book(dec!(0.03)), at), Some(dec!(0.5)));
+    }
+}
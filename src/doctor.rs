@@ -0,0 +1,644 @@
+//! Environment self-test
+//!
+//! Most failed deployments are environmental: a skewed clock, an unwritable
+//! data directory, blocked websocket egress, wrong credentials. `poly-hft
+//! doctor`, and `run --preflight` before a session, run one async check per
+//! dependency, each under a timeout, and print a pass/warn/fail table. Any
+//! failure exits non-zero, so provisioning scripts can gate on it.
+
+use crate::config::{Config, ExecutionMode};
+use crate::data::{available_space, DiskState};
+use crate::execution::ClobClient;
+use crate::feed::{BINANCE_REST_URL, BINANCE_WS_URL};
+use crate::market::GAMMA_URL;
+use crate::orderbook::MARKET_WS_URL;
+use crate::symbols::SymbolMap;
+use chrono::{DateTime, Utc};
+use futures_util::SinkExt;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Default time each check may take
+pub const DEFAULT_CHECK_TIMEOUT_SECS: u64 = 5;
+
+/// Clock offset from Binance server time above which the clock check warns
+pub const CLOCK_WARN_OFFSET_MS: i64 = 250;
+
+/// Clock offset from Binance server time above which the clock check fails
+pub const CLOCK_FAIL_OFFSET_MS: i64 = 1000;
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    /// Usable, but worth fixing
+    Warn,
+    Fail,
+    /// Not applicable to this config
+    Skip,
+}
+
+impl CheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skip => "SKIP",
+        }
+    }
+}
+
+/// One check's status and what it found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckResult {
+    /// Check name
+    pub name: String,
+    pub status: CheckStatus,
+    /// What was measured, or why it failed
+    pub detail: String,
+    /// Time the check took
+    pub elapsed_ms: u64,
+}
+
+/// Every check of one doctor run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DoctorReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    /// Whether no check failed; warnings pass
+    pub fn passed(&self) -> bool {
+        !self.checks.iter().any(|c| c.status == CheckStatus::Fail)
+    }
+
+    /// The checks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|c| c.status == CheckStatus::Fail)
+    }
+
+    /// Status of the check `name`, if it ran
+    pub fn status(&self, name: &str) -> Option<CheckStatus> {
+        self.checks
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.status)
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<16} {:<6} {:>8}  Detail", "Check", "Status", "Time")?;
+        for check in &self.checks {
+            writeln!(
+                f,
+                "{:<16} {:<6} {:>6}ms  {}",
+                check.name,
+                check.status.as_str(),
+                check.elapsed_ms,
+                check.detail
+            )?;
+        }
+        let failed = self.failures().count();
+        if failed == 0 {
+            write!(f, "All checks passed")
+        } else {
+            write!(f, "{} check(s) failed", failed)
+        }
+    }
+}
+
+/// Endpoints the connectivity checks reach
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    /// Binance REST base, for server time
+    pub binance_rest: String,
+    /// Binance websocket base; the feed symbol's trade stream is opened
+    pub binance_ws: String,
+    /// Polymarket market channel
+    pub polymarket_ws: String,
+    /// Gamma REST base
+    pub gamma: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            binance_rest: BINANCE_REST_URL.to_string(),
+            binance_ws: BINANCE_WS_URL.to_string(),
+            polymarket_ws: MARKET_WS_URL.to_string(),
+            gamma: GAMMA_URL.to_string(),
+        }
+    }
+}
+
+/// Runs the checks against a config
+#[derive(Debug, Clone)]
+pub struct Doctor {
+    endpoints: Endpoints,
+    timeout: Duration,
+    serving_metrics: bool,
+}
+
+impl Default for Doctor {
+    fn default() -> Self {
+        Self {
+            endpoints: Endpoints::default(),
+            timeout: Duration::from_secs(DEFAULT_CHECK_TIMEOUT_SECS),
+            serving_metrics: false,
+        }
+    }
+}
+
+impl Doctor {
+    /// Checks against the public endpoints
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reach `endpoints` instead of the public ones
+    pub fn with_endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    /// Fail a check still running after `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// This process already serves metrics on the configured port, so a
+    /// bound port answering `/metrics` is expected
+    pub fn serving_metrics(mut self) -> Self {
+        self.serving_metrics = true;
+        self
+    }
+
+    /// Run every check, in order
+    pub async fn run(&self, config: &Config) -> DoctorReport {
+        let http = reqwest::Client::new();
+        let stream = format!(
+            "{}/{}@trade",
+            self.endpoints.binance_ws.trim_end_matches('/'),
+            config.feed.symbol.to_lowercase()
+        );
+        let port = config.telemetry.metrics_port;
+        let checks = vec![
+            self.timed("config", async { check_config(config) }).await,
+            self.timed("clock", check_clock(&http, &self.endpoints.binance_rest))
+                .await,
+            self.timed("binance_ws", check_ws(&stream)).await,
+            self.timed("polymarket_ws", check_ws(&self.endpoints.polymarket_ws))
+                .await,
+            self.timed("gamma", check_gamma(&http, &self.endpoints.gamma))
+                .await,
+            self.timed("data_dir", async { check_data_dir(config) })
+                .await,
+            self.timed(
+                "metrics_port",
+                check_metrics_port(port, self.serving_metrics),
+            )
+            .await,
+            self.timed("live_auth", check_live_auth(config)).await,
+        ];
+        for check in &checks {
+            tracing::debug!(
+                check = %check.name,
+                status = check.status.as_str(),
+                detail = %check.detail,
+                "Doctor check"
+            );
+        }
+        DoctorReport { checks }
+    }
+
+    async fn timed(
+        &self,
+        name: &str,
+        check: impl Future<Output = (CheckStatus, String)>,
+    ) -> CheckResult {
+        let started = Instant::now();
+        let (status, detail) = match tokio::time::timeout(self.timeout, check).await {
+            Ok(outcome) => outcome,
+            Err(_) => (
+                CheckStatus::Fail,
+                format!("timed out after {}ms", self.timeout.as_millis()),
+            ),
+        };
+        CheckResult {
+            name: name.to_string(),
+            status,
+            detail,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+/// The config is internally consistent: its feed prices the market asset
+/// and its thresholds are ordered
+pub fn check_config(config: &Config) -> (CheckStatus, String) {
+    let mut problems = vec![];
+    if let Err(e) = SymbolMap::from_config(config) {
+        problems.push(e.to_string());
+    }
+    let signal = &config.signal;
+    if signal.min_edge_threshold >= signal.max_edge_threshold {
+        problems.push(format!(
+            "signal.min_edge_threshold {} is not below max_edge_threshold {}",
+            signal.min_edge_threshold, signal.max_edge_threshold
+        ));
+    }
+    let kelly = config.risk.kelly_fraction;
+    if kelly <= rust_decimal::Decimal::ZERO || kelly > rust_decimal::Decimal::ONE {
+        problems.push(format!("risk.kelly_fraction {} is outside (0, 1]", kelly));
+    }
+    let disk = &config.data.disk;
+    if disk.hard_min_free_bytes > disk.soft_min_free_bytes {
+        problems.push(format!(
+            "data.disk.hard_min_free_bytes {} is above soft_min_free_bytes {}",
+            disk.hard_min_free_bytes, disk.soft_min_free_bytes
+        ));
+    }
+    if problems.is_empty() {
+        (CheckStatus::Pass, "valid".to_string())
+    } else {
+        (CheckStatus::Fail, problems.join("; "))
+    }
+}
+
+#[derive(Deserialize)]
+struct ServerTime {
+    #[serde(rename = "serverTime")]
+    server_time: i64,
+}
+
+/// Local clock offset from Binance server time, taken at the midpoint of
+/// the request's round trip
+pub async fn check_clock(http: &reqwest::Client, rest_url: &str) -> (CheckStatus, String) {
+    let sent = Utc::now();
+    let response = match http
+        .get(format!("{}/api/v3/time", rest_url.trim_end_matches('/')))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+    {
+        Ok(response) => response,
+        Err(e) => return (CheckStatus::Fail, format!("server time unavailable: {}", e)),
+    };
+    let received = Utc::now();
+    let time: ServerTime = match response.json().await {
+        Ok(time) => time,
+        Err(e) => return (CheckStatus::Fail, format!("unreadable server time: {}", e)),
+    };
+    let Some(server) = DateTime::<Utc>::from_timestamp_millis(time.server_time) else {
+        return (CheckStatus::Fail, "server time out of range".to_string());
+    };
+    let rtt = received - sent;
+    let local = sent + rtt / 2;
+    let offset_ms = (local - server).num_milliseconds();
+    let detail = format!(
+        "offset {:+}ms from Binance (rtt {}ms)",
+        offset_ms,
+        rtt.num_milliseconds()
+    );
+    let status = match offset_ms.abs() {
+        o if o > CLOCK_FAIL_OFFSET_MS => CheckStatus::Fail,
+        o if o > CLOCK_WARN_OFFSET_MS => CheckStatus::Warn,
+        _ => CheckStatus::Pass,
+    };
+    (status, detail)
+}
+
+/// A websocket handshake with `url` completes
+pub async fn check_ws(url: &str) -> (CheckStatus, String) {
+    match tokio_tungstenite::connect_async(url).await {
+        Ok((mut ws, response)) => {
+            let _ = ws.close(None).await;
+            let _ = ws.flush().await;
+            (
+                CheckStatus::Pass,
+                format!("handshake ok ({})", response.status()),
+            )
+        }
+        Err(e) => (CheckStatus::Fail, format!("{}: {}", url, e)),
+    }
+}
+
+/// Gamma answers a one-event listing
+pub async fn check_gamma(http: &reqwest::Client, base_url: &str) -> (CheckStatus, String) {
+    match http
+        .get(format!("{}/events", base_url.trim_end_matches('/')))
+        .query(&[("limit", "1")])
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+    {
+        Ok(response) => (
+            CheckStatus::Pass,
+            format!("reachable ({})", response.status()),
+        ),
+        Err(e) => (CheckStatus::Fail, e.to_string()),
+    }
+}
+
+/// The data directory can be created and written, with free space above
+/// the disk thresholds
+pub fn check_data_dir(config: &Config) -> (CheckStatus, String) {
+    let dir = &config.data.output_dir;
+    if let Err(e) = probe_write(dir) {
+        return (
+            CheckStatus::Fail,
+            format!("{} not writable: {}", dir.display(), e),
+        );
+    }
+    let free = match available_space(dir) {
+        Ok(free) => free,
+        Err(e) => {
+            return (
+                CheckStatus::Warn,
+                format!("{} writable, free space unknown: {}", dir.display(), e),
+            )
+        }
+    };
+    let detail = format!(
+        "{} writable, {} MiB free",
+        dir.display(),
+        free / (1024 * 1024)
+    );
+    match DiskState::classify(free, &config.data.disk) {
+        DiskState::Ok => (CheckStatus::Pass, detail),
+        DiskState::Low => (
+            CheckStatus::Warn,
+            format!("{}, below the soft minimum", detail),
+        ),
+        DiskState::Critical => (
+            CheckStatus::Fail,
+            format!("{}, below the hard minimum; recording would pause", detail),
+        ),
+    }
+}
+
+fn probe_write(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    std::fs::write(&probe, b"ok")?;
+    std::fs::remove_file(&probe)
+}
+
+/// The metrics port can be bound, or is already served by this process
+pub async fn check_metrics_port(port: u16, serving: bool) -> (CheckStatus, String) {
+    match tokio::net::TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(_) => (CheckStatus::Pass, format!("port {} free", port)),
+        Err(e) if serving && e.kind() == std::io::ErrorKind::AddrInUse => {
+            let url = format!("http://127.0.0.1:{}/metrics", port);
+            match reqwest::get(&url).await.and_then(|r| r.error_for_status()) {
+                Ok(_) => (CheckStatus::Pass, format!("serving on port {}", port)),
+                Err(e) => (
+                    CheckStatus::Fail,
+                    format!("port {} held, but not by our exporter: {}", port, e),
+                ),
+            }
+        }
+        Err(e) => (CheckStatus::Fail, format!("port {}: {}", port, e)),
+    }
+}
+
+/// The CLOB accepts the `[execution.live]` credentials; skipped without
+/// them unless live mode needs them
+pub async fn check_live_auth(config: &Config) -> (CheckStatus, String) {
+    let Some(live) = config.execution.live.clone() else {
+        return match config.execution.mode {
+            ExecutionMode::Live => (
+                CheckStatus::Fail,
+                "live mode without [execution.live] credentials".to_string(),
+            ),
+            ExecutionMode::Paper => (
+                CheckStatus::Skip,
+                "no [execution.live] credentials".to_string(),
+            ),
+        };
+    };
+    match ClobClient::new(live).check_auth().await {
+        Ok(()) => (CheckStatus::Pass, "credentials accepted".to_string()),
+        Err(e) => (
+            CheckStatus::Fail,
+            format!("CLOB rejected credentials: {}", e),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::{LiveConfig, USER_WS_URL};
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config_in(dir: &Path) -> Config {
+        let mut config: Config = toml::from_str(include_str!("../config.toml.example")).unwrap();
+        config.data.output_dir = dir.to_path_buf();
+        config.data.disk.soft_min_free_bytes = 0;
+        config.data.disk.hard_min_free_bytes = 0;
+        config
+    }
+
+    async fn time_server(offset_ms: i64) -> MockServer {
+        let server = MockServer::start().await;
+        let now = Utc::now().timestamp_millis() + offset_ms;
+        Mock::given(method("GET"))
+            .and(path("/api/v3/time"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"serverTime": now})),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    /// A websocket server accepting every handshake
+    async fn ws_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let _ = tokio_tungstenite::accept_async(stream).await;
+                });
+            }
+        });
+        url
+    }
+
+    /// An address nothing listens on
+    async fn closed_url(scheme: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        format!("{}://{}", scheme, addr)
+    }
+
+    #[tokio::test]
+    async fn test_clock_offset_passes_warns_and_fails() {
+        let http = reqwest::Client::new();
+        let (status, detail) = check_clock(&http, &time_server(0).await.uri()).await;
+        assert_eq!(status, CheckStatus::Pass, "{}", detail);
+        // Server 600ms ahead: our clock is behind
+        let (status, detail) = check_clock(&http, &time_server(600).await.uri()).await;
+        assert_eq!(status, CheckStatus::Warn, "{}", detail);
+        assert!(detail.starts_with("offset -"), "{}", detail);
+        let (status, _) = check_clock(&http, &time_server(-3000).await.uri()).await;
+        assert_eq!(status, CheckStatus::Fail);
+        let (status, _) = check_clock(&http, &closed_url("http").await).await;
+        assert_eq!(status, CheckStatus::Fail);
+    }
+
+    #[tokio::test]
+    async fn test_ws_handshake_and_gamma_reachability() {
+        assert_eq!(check_ws(&ws_server().await).await.0, CheckStatus::Pass);
+        assert_eq!(check_ws(&closed_url("ws").await).await.0, CheckStatus::Fail);
+
+        let http = reqwest::Client::new();
+        let gamma = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("limit", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .mount(&gamma)
+            .await;
+        assert_eq!(check_gamma(&http, &gamma.uri()).await.0, CheckStatus::Pass);
+        let down = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&down)
+            .await;
+        let (status, detail) = check_gamma(&http, &down.uri()).await;
+        assert_eq!(status, CheckStatus::Fail);
+        assert!(detail.contains("503"), "{}", detail);
+    }
+
+    #[tokio::test]
+    async fn test_data_dir_and_metrics_port() {
+        let dir = TempDir::new().unwrap();
+        let mut config = config_in(&dir.path().join("data"));
+        assert_eq!(check_data_dir(&config).0, CheckStatus::Pass);
+        config.data.disk.soft_min_free_bytes = u64::MAX;
+        assert_eq!(check_data_dir(&config).0, CheckStatus::Warn);
+        config.data.disk.hard_min_free_bytes = u64::MAX;
+        assert_eq!(check_data_dir(&config).0, CheckStatus::Fail);
+
+        // A file where the directory should be
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        let (status, detail) = check_data_dir(&config_in(&file));
+        assert_eq!(status, CheckStatus::Fail);
+        assert!(detail.contains("not writable"), "{}", detail);
+
+        let held = TcpListener::bind("0.0.0.0:0").await.unwrap();
+        let port = held.local_addr().unwrap().port();
+        assert_eq!(check_metrics_port(port, false).await.0, CheckStatus::Fail);
+        // Held by something that hangs up instead of answering /metrics
+        let holder = tokio::spawn(async move {
+            while let Ok((stream, _)) = held.accept().await {
+                drop(stream);
+            }
+        });
+        assert_eq!(check_metrics_port(port, true).await.0, CheckStatus::Fail);
+        holder.abort();
+        let _ = holder.await;
+        assert_eq!(check_metrics_port(port, false).await.0, CheckStatus::Pass);
+    }
+
+    #[tokio::test]
+    async fn test_config_and_live_auth() {
+        let dir = TempDir::new().unwrap();
+        let mut config = config_in(dir.path());
+        assert_eq!(check_config(&config).0, CheckStatus::Pass);
+        assert_eq!(check_live_auth(&config).await.0, CheckStatus::Skip);
+
+        config.execution.mode = ExecutionMode::Live;
+        assert_eq!(check_live_auth(&config).await.0, CheckStatus::Fail);
+
+        let clob = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&clob)
+            .await;
+        config.execution.live = Some(LiveConfig {
+            clob_url: clob.uri(),
+            user_ws_url: USER_WS_URL.to_string(),
+            address: "0xabc".to_string(),
+            api_key: "key-1".to_string(),
+            secret: "c2VjcmV0".to_string(),
+            passphrase: "hunter2".to_string(),
+            reconcile_interval_secs: crate::duration::DurationConfig::from_secs(60),
+        });
+        let (status, detail) = check_live_auth(&config).await;
+        assert_eq!(status, CheckStatus::Fail);
+        assert!(detail.contains("401"), "{}", detail);
+
+        config.signal.min_edge_threshold = config.signal.max_edge_threshold;
+        config.feed.symbol = "ETHUSDT".to_string();
+        let (status, detail) = check_config(&config);
+        assert_eq!(status, CheckStatus::Fail);
+        assert!(detail.contains("min_edge_threshold") && detail.contains("ETHUSDT"));
+    }
+
+    #[tokio::test]
+    async fn test_report_fails_on_any_failure_and_times_out() {
+        let dir = TempDir::new().unwrap();
+        let config = config_in(dir.path());
+        let rest = time_server(0).await;
+        let gamma = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .mount(&gamma)
+            .await;
+        let ws = ws_server().await;
+        let endpoints = Endpoints {
+            binance_rest: rest.uri(),
+            binance_ws: ws.clone(),
+            polymarket_ws: ws,
+            gamma: gamma.uri(),
+        };
+        let mut config = config;
+        let free = TcpListener::bind("0.0.0.0:0").await.unwrap();
+        config.telemetry.metrics_port = free.local_addr().unwrap().port();
+        drop(free);
+
+        let report = Doctor::new()
+            .with_endpoints(endpoints.clone())
+            .run(&config)
+            .await;
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.checks.len(), 8);
+        assert_eq!(report.status("live_auth"), Some(CheckStatus::Skip));
+        assert!(report.to_string().ends_with("All checks passed"));
+
+        // A Gamma that never answers is cut off by the timeout
+        let slow = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&slow)
+            .await;
+        let report = Doctor::new()
+            .with_endpoints(Endpoints {
+                gamma: slow.uri(),
+                ..endpoints
+            })
+            .with_timeout(Duration::from_millis(300))
+            .run(&config)
+            .await;
+        assert!(!report.passed());
+        let failed: Vec<_> = report.failures().map(|c| c.name.as_str()).collect();
+        assert_eq!(failed, ["gamma"]);
+        assert!(report.to_string().contains("timed out after 300ms"));
+    }
+}
@@ -0,0 +1,271 @@
+//! Durations in the config
+//!
+//! Every duration-like setting is a [`DurationConfig`], which reads a
+//! humantime string ("90s", "2m", "1h 30m", "250ms") or a bare integer. A
+//! bare integer counts the field's unit, the one its key was named for
+//! (`window_ms` counts milliseconds, `volatility_window_minutes` minutes)
+//! and seconds otherwise, so configs written before strings were accepted
+//! read the same. Durations serialize back as that integer when whole, so
+//! the config fingerprint of such a config is unchanged.
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Unit a bare integer counts in a [`DurationConfig`]
+pub trait DurationUnit {
+    /// Length of one unit
+    const UNIT: Duration;
+    /// Plural name, for errors
+    const NAME: &'static str;
+}
+
+/// Bare integers are seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Secs;
+
+/// Bare integers are milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Millis;
+
+/// Bare integers are minutes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Minutes;
+
+/// Bare integers are hours
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hours;
+
+impl DurationUnit for Secs {
+    const UNIT: Duration = Duration::from_secs(1);
+    const NAME: &'static str = "seconds";
+}
+
+impl DurationUnit for Millis {
+    const UNIT: Duration = Duration::from_millis(1);
+    const NAME: &'static str = "milliseconds";
+}
+
+impl DurationUnit for Minutes {
+    const UNIT: Duration = Duration::from_secs(60);
+    const NAME: &'static str = "minutes";
+}
+
+impl DurationUnit for Hours {
+    const UNIT: Duration = Duration::from_secs(3600);
+    const NAME: &'static str = "hours";
+}
+
+/// A configured duration; bare integers count `U`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DurationConfig<U = Secs> {
+    duration: Duration,
+    unit: PhantomData<U>,
+}
+
+impl<U> DurationConfig<U> {
+    /// Wrap `duration`
+    pub const fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            unit: PhantomData,
+        }
+    }
+
+    /// `secs` seconds
+    pub const fn from_secs(secs: u64) -> Self {
+        Self::new(Duration::from_secs(secs))
+    }
+
+    /// `millis` milliseconds
+    pub const fn from_millis(millis: u64) -> Self {
+        Self::new(Duration::from_millis(millis))
+    }
+
+    /// `mins` minutes
+    pub const fn from_mins(mins: u64) -> Self {
+        Self::new(Duration::from_secs(mins * 60))
+    }
+
+    /// The duration
+    pub const fn get(&self) -> Duration {
+        self.duration
+    }
+
+    /// Whole seconds
+    pub const fn as_secs(&self) -> u64 {
+        self.duration.as_secs()
+    }
+
+    /// Whole milliseconds
+    pub fn as_millis(&self) -> u64 {
+        self.duration.as_millis() as u64
+    }
+
+    /// Whether the duration is zero, which disables most settings
+    pub const fn is_zero(&self) -> bool {
+        self.duration.is_zero()
+    }
+
+    /// As a chrono duration, for arithmetic on timestamps
+    pub fn to_chrono(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.duration).unwrap_or(chrono::Duration::MAX)
+    }
+}
+
+impl<U: DurationUnit> DurationConfig<U> {
+    /// `count` of the field's unit
+    pub fn from_units(count: u64) -> Self {
+        let nanos = U::UNIT.as_nanos() * count as u128;
+        let secs = u64::try_from(nanos / 1_000_000_000).unwrap_or(u64::MAX);
+        Self::new(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+    }
+
+    /// Whole units, if the duration is a whole number of them
+    fn as_units(&self) -> Option<u64> {
+        let unit = U::UNIT.as_nanos();
+        let nanos = self.duration.as_nanos();
+        nanos.is_multiple_of(unit).then(|| (nanos / unit) as u64)
+    }
+}
+
+impl<U> From<Duration> for DurationConfig<U> {
+    fn from(duration: Duration) -> Self {
+        Self::new(duration)
+    }
+}
+
+impl<U> fmt::Display for DurationConfig<U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", humantime::format_duration(self.duration))
+    }
+}
+
+impl<U: DurationUnit> FromStr for DurationConfig<U> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(count) = s.parse::<u64>() {
+            return Ok(Self::from_units(count));
+        }
+        humantime::parse_duration(s).map(Self::new).map_err(|e| {
+            format!(
+                "invalid duration {:?} ({}); expected e.g. \"90s\", \"2m\" or \"1h\", or a whole number of {}",
+                s,
+                e,
+                U::NAME
+            )
+        })
+    }
+}
+
+impl<U: DurationUnit> Serialize for DurationConfig<U> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.as_units() {
+            Some(count) => serializer.serialize_u64(count),
+            None => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de, U: DurationUnit> Deserialize<'de> for DurationConfig<U> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DurationVisitor<U>(PhantomData<U>);
+
+        impl<U: DurationUnit> Visitor<'_> for DurationVisitor<U> {
+            type Value = DurationConfig<U>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(
+                    f,
+                    "a duration such as \"90s\", \"2m\" or \"1h\", or a whole number of {}",
+                    U::NAME
+                )
+            }
+
+            fn visit_u64<E: de::Error>(self, count: u64) -> Result<Self::Value, E> {
+                Ok(DurationConfig::from_units(count))
+            }
+
+            fn visit_i64<E: de::Error>(self, count: i64) -> Result<Self::Value, E> {
+                match u64::try_from(count) {
+                    Ok(count) => self.visit_u64(count),
+                    Err(_) => Err(E::invalid_value(de::Unexpected::Signed(count), &self)),
+                }
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(DurationVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Fields {
+        secs: DurationConfig,
+        millis: DurationConfig<Millis>,
+    }
+
+    fn parse(toml: &str) -> Result<Fields, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    #[test]
+    fn test_strings_and_bare_integers() {
+        let fields = parse("secs = 90\nmillis = 250").unwrap();
+        assert_eq!(fields.secs.get(), Duration::from_secs(90));
+        assert_eq!(fields.millis.get(), Duration::from_millis(250));
+
+        let fields = parse("secs = \"1m 30s\"\nmillis = \"2s\"").unwrap();
+        assert_eq!(fields.secs.get(), Duration::from_secs(90));
+        assert_eq!(fields.millis.as_millis(), 2000);
+        assert_eq!(fields.secs.to_chrono(), chrono::Duration::seconds(90));
+
+        let hours: DurationConfig<Hours> = "2".parse().unwrap();
+        assert_eq!(hours.as_secs(), 7200);
+        let minutes: DurationConfig<Minutes> = "1h".parse().unwrap();
+        assert_eq!(minutes, DurationConfig::from_mins(60));
+    }
+
+    #[test]
+    fn test_serializes_whole_units_as_integers() {
+        let fields = parse("secs = \"2m\"\nmillis = \"1s\"").unwrap();
+        assert_eq!(
+            toml::to_string(&fields).unwrap(),
+            "secs = 120\nmillis = 1000\n"
+        );
+        let fields = parse("secs = \"1500ms\"\nmillis = 3").unwrap();
+        assert_eq!(
+            toml::to_string(&fields).unwrap(),
+            "secs = \"1s 500ms\"\nmillis = 3\n"
+        );
+    }
+
+    #[test]
+    fn test_bad_values_name_the_key() {
+        for (toml, key) in [
+            ("secs = \"ninety\"\nmillis = 1", "secs"),
+            ("secs = 1\nmillis = -5", "millis"),
+            ("secs = 1.5\nmillis = 1", "secs"),
+        ] {
+            let error = parse(toml).unwrap_err().to_string();
+            assert!(error.contains(&format!("{} = ", key)), "{}", error);
+            assert!(error.contains("duration"), "{}", error);
+        }
+        let error = parse("secs = \"5 parsecs\"\nmillis = 1")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("whole number of seconds"), "{}", error);
+    }
+}
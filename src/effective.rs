@@ -0,0 +1,421 @@
+//! Effective runtime parameters and where each came from
+//!
+//! The config file is only where parameters start: a strategy review
+//! scales sizing down on its own and `ctl ack-review` restores it while
+//! the session runs. [`EffectiveConfig`] holds every parameter in force,
+//! seeded from the loaded config with each value marked as set in the
+//! file or left at its default, and takes every later change with its
+//! [`Provenance`]. Each change bumps the revision, which the engine stamps
+//! on everything it journals, so any signal or trade joins back to the
+//! exact parameters it was decided under.
+//!
+//! The running session writes the registry to [`EFFECTIVE_CONFIG_FILE`]
+//! for `status --effective-config`, serves it at the event stream's
+//! `/state`, and journals each change as `parameter_changed`.
+
+use crate::config::Config;
+use crate::stream::EventStream;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// File in the data directory holding the running session's registry
+pub const EFFECTIVE_CONFIG_FILE: &str = "effective_config.json";
+
+/// Effective size multiple of entries: the review scale while flagged
+pub const SIZE_SCALE: &str = "engine.size_scale";
+
+static SOURCE: OnceLock<Option<toml::Table>> = OnceLock::new();
+
+/// Keep the config file's text, so parameters it sets are told from
+/// defaults; unparseable text counts as setting nothing
+pub fn install_source(text: Option<&str>) {
+    let _ = SOURCE.set(text.and_then(|text| text.parse().ok()));
+}
+
+/// The config file installed at startup, if any
+pub fn source() -> Option<&'static toml::Table> {
+    SOURCE.get().and_then(Option::as_ref)
+}
+
+/// Where a parameter's value came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Provenance {
+    /// Set in the config file
+    File,
+    /// Left at its default
+    Default,
+    /// Changed by an operator through `poly-hft ctl`
+    Ctl,
+    /// Changed by the bot reacting to its own results
+    Adaptive,
+}
+
+impl Provenance {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Provenance::File => "file",
+            Provenance::Default => "default",
+            Provenance::Ctl => "ctl",
+            Provenance::Adaptive => "adaptive",
+        }
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One parameter's value in force
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub value: Value,
+    pub provenance: Provenance,
+    /// Revision that set it; 0 for values in force from the start
+    pub revision: u64,
+    /// When it was last changed; none for values in force from the start
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// A change to one parameter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterChange {
+    /// Revision the change started
+    pub revision: u64,
+    pub at: DateTime<Utc>,
+    pub name: String,
+    /// Value before; none for a parameter first set by the change
+    pub old: Option<Value>,
+    pub new: Value,
+    pub provenance: Provenance,
+}
+
+impl fmt::Display for ParameterChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let old = self
+            .old
+            .as_ref()
+            .map_or("unset".to_string(), Value::to_string);
+        write!(
+            f,
+            "r{} {} {}: {} -> {} ({})",
+            self.revision,
+            self.at.format("%Y-%m-%d %H:%M:%S"),
+            self.name,
+            old,
+            self.new,
+            self.provenance
+        )
+    }
+}
+
+/// Every parameter in force and how it got there
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct EffectiveConfig {
+    /// Changes since the config was loaded
+    pub revision: u64,
+    /// By dotted name, e.g. `risk.kelly_fraction`
+    pub parameters: BTreeMap<String, Parameter>,
+    /// Every change, oldest first
+    pub changes: Vec<ParameterChange>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    stream: Option<EventStream>,
+}
+
+impl EffectiveConfig {
+    /// Every leaf of `config` at revision 0, marked as set in `source`,
+    /// the parsed config file, or left at its default
+    pub fn new(config: &Config, source: Option<&toml::Table>) -> Self {
+        let mut leaves = BTreeMap::new();
+        match serde_json::to_value(config) {
+            Ok(value) => flatten("", &value, &mut leaves),
+            Err(e) => tracing::warn!(error = %e, "Failed to register the effective config"),
+        }
+        let parameters = leaves
+            .into_iter()
+            .map(|(name, value)| {
+                let provenance = match source {
+                    Some(table) if in_file(table, &name) => Provenance::File,
+                    _ => Provenance::Default,
+                };
+                let parameter = Parameter {
+                    value,
+                    provenance,
+                    revision: 0,
+                    since: None,
+                };
+                (name, parameter)
+            })
+            .collect();
+        Self {
+            parameters,
+            ..Default::default()
+        }
+    }
+
+    /// Write the registry to `path` on every change
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self.save();
+        self
+    }
+
+    /// Serve the registry at the stream's `/state`
+    pub fn with_stream(mut self, stream: EventStream) -> Self {
+        self.stream = Some(stream);
+        self.save();
+        self
+    }
+
+    /// Register a parameter derived at runtime, in force from the start
+    pub fn register(&mut self, name: &str, value: impl Serialize, provenance: Provenance) {
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        self.parameters.insert(
+            name.to_string(),
+            Parameter {
+                value,
+                provenance,
+                revision: 0,
+                since: None,
+            },
+        );
+    }
+
+    /// Set `name` to `value`, starting a new revision unless it already
+    /// holds it; returns the change
+    pub fn set(
+        &mut self,
+        name: &str,
+        value: impl Serialize,
+        provenance: Provenance,
+        at: DateTime<Utc>,
+    ) -> Option<ParameterChange> {
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        let old = self.parameters.get(name).map(|p| p.value.clone());
+        if old.as_ref() == Some(&value) {
+            return None;
+        }
+        self.revision += 1;
+        self.parameters.insert(
+            name.to_string(),
+            Parameter {
+                value: value.clone(),
+                provenance,
+                revision: self.revision,
+                since: Some(at),
+            },
+        );
+        let change = ParameterChange {
+            revision: self.revision,
+            at,
+            name: name.to_string(),
+            old,
+            new: value,
+            provenance,
+        };
+        self.changes.push(change.clone());
+        self.save();
+        Some(change)
+    }
+
+    /// The parameter `name` in force
+    pub fn get(&self, name: &str) -> Option<&Parameter> {
+        self.parameters.get(name)
+    }
+
+    /// The changes, for the session summary
+    pub fn timeline(&self) -> Timeline<'_> {
+        Timeline(&self.changes)
+    }
+
+    /// The registry the running session wrote to `path`, if any
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self) {
+        if self.path.is_none() && self.stream.is_none() {
+            return;
+        }
+        let json = match serde_json::to_string_pretty(self) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to serialize the effective config");
+                return;
+            }
+        };
+        if let Some(path) = &self.path {
+            let tmp = path.with_extension("json.tmp");
+            let written = std::fs::write(&tmp, &json).and_then(|_| std::fs::rename(&tmp, path));
+            if let Err(e) = written {
+                tracing::warn!(path = ?path, error = %e, "Failed to save the effective config");
+            }
+        }
+        if let Some(stream) = &self.stream {
+            stream.set_state(json);
+        }
+    }
+}
+
+impl fmt::Display for EffectiveConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "    revision {}", self.revision)?;
+        for (name, parameter) in &self.parameters {
+            write!(
+                f,
+                "    {} = {} ({}",
+                name, parameter.value, parameter.provenance
+            )?;
+            match parameter.since {
+                Some(since) => writeln!(
+                    f,
+                    ", r{} at {})",
+                    parameter.revision,
+                    since.format("%Y-%m-%d %H:%M:%S")
+                )?,
+                None => writeln!(f, ")")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parameter changes of a session, oldest first
+pub struct Timeline<'a>(&'a [ParameterChange]);
+
+impl fmt::Display for Timeline<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return writeln!(f, "  Parameter changes: none");
+        }
+        writeln!(f, "  Parameter changes:")?;
+        for change in self.0 {
+            writeln!(f, "    {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every leaf of `value` by dotted path; arrays and empty tables are
+/// leaves
+fn flatten(prefix: &str, value: &Value, leaves: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let name = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&name, value, leaves);
+            }
+        }
+        _ => {
+            leaves.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+/// Whether the config file sets the dotted `name`
+fn in_file(table: &toml::Table, name: &str) -> bool {
+    let mut table = table;
+    let mut keys = name.split('.').peekable();
+    while let Some(key) = keys.next() {
+        let Some(value) = table.get(key) else {
+            return false;
+        };
+        if keys.peek().is_none() {
+            return true;
+        }
+        match value.as_table() {
+            Some(inner) => table = inner,
+            None => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    const EXAMPLE: &str = include_str!("../config.toml.example");
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_767_600_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_file_and_default_values_are_told_apart() {
+        let config: Config = toml::from_str(EXAMPLE).unwrap();
+        // A file setting one value of a table and nothing of another
+        let source: toml::Table = "[risk]\nkelly_fraction = 0.25\n".parse().unwrap();
+        let registry = EffectiveConfig::new(&config, Some(&source));
+        assert_eq!(registry.revision, 0);
+
+        let kelly = registry.get("risk.kelly_fraction").unwrap();
+        assert_eq!(kelly.provenance, Provenance::File);
+        assert_eq!(kelly.value, serde_json::json!("0.25"));
+        for name in ["risk.max_position_pct", "risk.review.percentile"] {
+            let parameter = registry.get(name).unwrap();
+            assert_eq!(parameter.provenance, Provenance::Default, "{}", name);
+        }
+        // Secrets are never serialized, so never registered
+        assert!(registry.get("stream.token").is_none());
+
+        let without_file = EffectiveConfig::new(&config, None);
+        assert_eq!(
+            without_file.get("risk.kelly_fraction").unwrap().provenance,
+            Provenance::Default
+        );
+    }
+
+    #[test]
+    fn test_changes_bump_the_revision_and_are_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(EFFECTIVE_CONFIG_FILE);
+        let mut registry = EffectiveConfig::default().with_file(&path);
+        registry.register(SIZE_SCALE, dec!(1), Provenance::Default);
+
+        // Holding the value already is no change
+        assert!(registry
+            .set(SIZE_SCALE, dec!(1), Provenance::Adaptive, at(0))
+            .is_none());
+        let flagged = registry
+            .set(SIZE_SCALE, dec!(0.5), Provenance::Adaptive, at(10))
+            .unwrap();
+        assert_eq!(flagged.revision, 1);
+        assert_eq!(flagged.old, Some(serde_json::json!("1")));
+        let cleared = registry
+            .set(SIZE_SCALE, dec!(1), Provenance::Ctl, at(20))
+            .unwrap();
+        assert_eq!(cleared.revision, 2);
+
+        let saved = EffectiveConfig::load(&path).unwrap().unwrap();
+        assert_eq!(saved.revision, 2);
+        let scale = saved.get(SIZE_SCALE).unwrap();
+        assert_eq!(scale.provenance, Provenance::Ctl);
+        assert_eq!((scale.revision, scale.since), (2, Some(at(20))));
+        assert_eq!(saved.changes, vec![flagged, cleared]);
+        assert_eq!(
+            saved.timeline().to_string(),
+            "  Parameter changes:\n    \
+             r1 2026-01-05 08:00:10 engine.size_scale: \"1\" -> \"0.5\" (adaptive)\n    \
+             r2 2026-01-05 08:00:20 engine.size_scale: \"0.5\" -> \"1\" (ctl)\n"
+        );
+    }
+}
@@ -0,0 +1,856 @@
+//! Pre-trade decision stack
+//!
+//! Detection, filtering, sizing and limit checks for one market at one
+//! moment, with every intermediate number kept. The trading engine acts on
+//! the resulting [`Explanation`]; `poly-hft eval` prints it, so a what-if
+//! answer always matches what the engine would have done.
+
+use crate::config::Config;
+use crate::execution::{Order, OrderAction, OrderType};
+use crate::flags::{FeatureFlags, FlagState};
+use crate::ids::{self, IdMode};
+use crate::market::{Market, PriceBounds};
+use crate::model::{
+    FairValue, FairValueModel, FairValueParams, GbmModel, LinearLagModel, VolatilityEstimator,
+};
+use crate::orderbook::{MarketBooks, OrderBook};
+use crate::precision::{round_pct, round_price, round_size, round_usd};
+use crate::risk::{
+    Damping, DampingConfig, KellyCalculator, PositionLimits, PositionTracker, DEFAULT_STRATEGY,
+};
+use crate::signal::{
+    DisagreementMode, FilterConfig, ModelEstimates, MomentumDetector, NoLagReason, RejectReason,
+    Side, Signal, SignalDetector, SignalFilter,
+};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Longest market the filter accepts
+const MAX_TIME_TO_EXPIRY_MINS: i64 = 15;
+
+/// Smallest top-of-book size worth trading against
+const MIN_LIQUIDITY: Decimal = dec!(1);
+
+/// Annualized volatility outside this range is treated as a bad estimate
+const VOLATILITY_RANGE: (Decimal, Decimal) = (dec!(0.05), dec!(5));
+
+/// Market data at one moment, as loaded by `poly-hft eval`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Market being evaluated
+    pub market: Market,
+    /// YES token order book
+    pub book: OrderBook,
+    /// NO token order book, to vouch that the YES book is current
+    #[serde(default)]
+    pub no_book: Option<OrderBook>,
+    /// Spot price at `at`
+    pub spot: Decimal,
+    /// Evaluation time
+    pub at: DateTime<Utc>,
+    /// Recent spot ticks for the volatility estimate, oldest first
+    #[serde(default)]
+    pub ticks: Vec<(DateTime<Utc>, Decimal)>,
+}
+
+/// What the decision stack concluded
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "reason", rename_all = "snake_case")]
+pub enum Verdict {
+    /// Volatility or book missing or unusable
+    NoData(String),
+    /// Neither side has an edge after costs
+    NoEdge,
+    /// The expected price was clamped to the venue's bound and the edge
+    /// against it is too small to trust
+    NearBound,
+    /// The NO book has already repriced in the lag's direction, so the
+    /// YES book is stale
+    ComplementRepriced,
+    /// A filter rejected the signal
+    Filtered(RejectReason),
+    /// Sizing came out at zero shares
+    Unsized,
+    /// A risk limit blocked the order
+    Blocked(String),
+    /// The order would be submitted
+    Trade,
+}
+
+impl Verdict {
+    /// Action label used by the signal metrics
+    pub fn label(&self) -> &'static str {
+        match self {
+            Verdict::NoData(_) => "no_data",
+            Verdict::NoEdge => "no_edge",
+            Verdict::NearBound => "near_bound",
+            Verdict::ComplementRepriced => "complement_repriced",
+            Verdict::Filtered(_) => "rejected",
+            Verdict::Unsized => "unsized",
+            Verdict::Blocked(_) => "blocked",
+            Verdict::Trade => "traded",
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::NoData(why) => write!(f, "no trade, {}", why),
+            Verdict::NoEdge => write!(f, "no trade, no edge after costs"),
+            Verdict::NearBound => write!(f, "no trade, expected price at the price bound"),
+            Verdict::ComplementRepriced => {
+                write!(f, "no trade, NO book already repriced, YES book stale")
+            }
+            Verdict::Filtered(why) => write!(f, "no trade, filtered ({})", why),
+            Verdict::Unsized => write!(f, "no trade, size rounds to zero"),
+            Verdict::Blocked(why) => write!(f, "no trade, blocked ({})", why),
+            Verdict::Trade => write!(f, "trade"),
+        }
+    }
+}
+
+/// One filter or risk check
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// `filter` or `risk`
+    pub stage: &'static str,
+    /// Check name
+    pub name: &'static str,
+    /// Whether the check passed
+    pub passed: bool,
+    /// The numbers involved
+    pub detail: String,
+}
+
+/// Every step of one pre-trade decision
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    /// Evaluation time
+    pub at: DateTime<Utc>,
+    /// Market condition id
+    pub market_id: String,
+    /// Spot price
+    pub spot: Decimal,
+    /// Spot at market open
+    pub open_price: Decimal,
+    /// Seconds until the market closes
+    pub time_to_expiry_secs: i64,
+    /// Annualized volatility estimate
+    pub volatility: Option<Decimal>,
+    /// Age of the book at evaluation time
+    pub book_age_ms: i64,
+    /// Best YES ask
+    pub yes_ask: Option<Decimal>,
+    /// Size at the best YES ask
+    pub available: Decimal,
+    /// Model fair value
+    pub fair_value: Option<FairValue>,
+    /// GBM and linear YES estimates and the book mid
+    pub models: Option<ModelEstimates>,
+    /// YES fair value minus the YES ask
+    pub yes_edge: Option<Decimal>,
+    /// NO fair value minus the implied NO price
+    pub no_edge: Option<Decimal>,
+    /// Fee plus slippage deducted from the raw edge
+    pub costs: Decimal,
+    /// Detected signal
+    pub signal: Option<Signal>,
+    /// Filter and risk checks, in evaluation order
+    pub checks: Vec<Check>,
+    /// Directional exposure already held and the share of the size it
+    /// leaves, when damping is enabled
+    pub damping: Option<Damping>,
+    /// Kelly stake in dollars, scaled down under a strategy review and by
+    /// damping
+    pub stake: Option<Decimal>,
+    /// Most shares the depth near the touch allows
+    pub depth_cap: Option<Decimal>,
+    /// Shares after capping at the top of the book and the depth cap
+    pub size: Option<Decimal>,
+    /// Order that would be submitted
+    pub order: Option<Order>,
+    /// Outcome
+    pub verdict: Verdict,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Market {} at {}", self.market_id, self.at.to_rfc3339())?;
+        let moved = if self.open_price.is_zero() {
+            Decimal::ZERO
+        } else {
+            (self.spot - self.open_price) / self.open_price * dec!(100)
+        };
+        writeln!(
+            f,
+            "  Spot: {:.2} (open {:.2}, {:+.4}%)",
+            self.spot,
+            self.open_price,
+            round_pct(moved)
+        )?;
+        writeln!(f, "  Time to expiry: {}s", self.time_to_expiry_secs)?;
+        match self.volatility {
+            Some(vol) => writeln!(f, "  Volatility: {:.4}", round_pct(vol))?,
+            None => writeln!(f, "  Volatility: unavailable")?,
+        }
+        match self.yes_ask {
+            Some(ask) => writeln!(
+                f,
+                "  Book: yes ask {:.4} x {:.2}, implied no {:.4}, age {}ms",
+                round_price(ask),
+                round_size(self.available),
+                round_price(Decimal::ONE - ask),
+                self.book_age_ms
+            )?,
+            None => writeln!(f, "  Book: no asks, age {}ms", self.book_age_ms)?,
+        }
+        if let Some(fv) = &self.fair_value {
+            writeln!(
+                f,
+                "  Fair value: yes {:.6}, no {:.6}",
+                round_pct(fv.yes_prob),
+                round_pct(fv.no_prob)
+            )?;
+        }
+        if let Some(models) = &self.models {
+            writeln!(
+                f,
+                "  Cross-check: gbm yes {:.4}, linear yes {:.4}, mid {}",
+                round_pct(models.gbm),
+                round_pct(models.linear),
+                models
+                    .mid
+                    .map_or("none".to_string(), |m| format!("{:.4}", round_price(m)))
+            )?;
+        }
+        if let (Some(yes), Some(no)) = (self.yes_edge, self.no_edge) {
+            writeln!(
+                f,
+                "  Edge: yes {:+.6}, no {:+.6}, costs {:.4}",
+                round_pct(yes),
+                round_pct(no),
+                round_pct(self.costs)
+            )?;
+        }
+        if let Some(signal) = &self.signal {
+            writeln!(f, "  Signal: {}", signal)?;
+            writeln!(
+                f,
+                "  Depth: {:.2} within 1c, {:.2} within 2c, {:.2} within 3c",
+                round_size(signal.depth.within_1c),
+                round_size(signal.depth.within_2c),
+                round_size(signal.depth.within_3c)
+            )?;
+        }
+        if !self.checks.is_empty() {
+            writeln!(f, "  Checks:")?;
+            for check in &self.checks {
+                writeln!(
+                    f,
+                    "    [{}] {}.{}: {}",
+                    if check.passed { "pass" } else { "FAIL" },
+                    check.stage,
+                    check.name,
+                    check.detail
+                )?;
+            }
+        }
+        if let (Some(stake), Some(size)) = (self.stake, self.size) {
+            writeln!(
+                f,
+                "  Size: stake {:.4} -> {:.2} shares (depth cap {:.2})",
+                round_usd(stake),
+                round_size(size),
+                round_size(self.depth_cap.unwrap_or_default())
+            )?;
+        }
+        if let Some(order) = &self.order {
+            writeln!(
+                f,
+                "  Order: {:?} {:?} {:.2} @ {:.4} {:?} on {}",
+                order.action,
+                order.side,
+                round_size(order.size),
+                round_price(order.price),
+                order.order_type,
+                order.token_id
+            )?;
+        }
+        write!(f, "  Verdict: {}", self.verdict)
+    }
+}
+
+/// Detector, filters, sizer and limits built from one config
+pub struct DecisionStack {
+    model: GbmModel,
+    linear: LinearLagModel,
+    detector: SignalDetector<GbmModel>,
+    bounds: PriceBounds,
+    costs: Decimal,
+    filter: SignalFilter,
+    kelly: KellyCalculator,
+    limits: PositionLimits,
+    max_positions: usize,
+    max_depth_multiple: Decimal,
+    size_scale: Decimal,
+    damping: DampingConfig,
+    ids: IdMode,
+    flags: FeatureFlags,
+}
+
+impl DecisionStack {
+    /// Build the stack from the bot config
+    pub fn new(config: &Config) -> Self {
+        let filter = SignalFilter::new(FilterConfig {
+            min_edge: config.signal.min_edge_threshold,
+            use_round_trip_edge: config.signal.use_round_trip_edge,
+            max_edge: config.signal.max_edge_threshold,
+            min_time_to_expiry: config.model.min_time_to_expiry_secs.to_chrono(),
+            max_time_to_expiry: Duration::minutes(MAX_TIME_TO_EXPIRY_MINS),
+            min_liquidity: MIN_LIQUIDITY,
+            max_spread: config.signal.max_entry_spread,
+            min_volatility: VOLATILITY_RANGE.0,
+            max_volatility: VOLATILITY_RANGE.1,
+            max_retrace: config.signal.max_momentum_retrace,
+            require_venues: config.signal.momentum_require_venues,
+            max_venue_divergence_pct: config.signal.max_venue_divergence_pct,
+            max_model_disagreement: config.signal.model_sanity.max_disagreement,
+            suppress_model_disagreement: config.signal.model_sanity.mode
+                == DisagreementMode::Suppress,
+        });
+        let costs = config.execution.costs.taker_fee_rate + config.execution.slippage_estimate;
+        let bounds = PriceBounds::new(config.signal.tick_size);
+        Self {
+            model: GbmModel::new().with_bounds(bounds),
+            linear: LinearLagModel::new(config.signal.model_sanity.lag_sensitivity),
+            detector: SignalDetector::new(
+                GbmModel::new(),
+                config.execution.costs.taker_fee_rate,
+                config.execution.slippage_estimate,
+            )
+            .with_bounds(bounds, config.signal.near_bound_min_edge)
+            .with_complement_tolerance(config.signal.complement_tolerance),
+            bounds,
+            costs,
+            filter,
+            kelly: KellyCalculator::new(config.risk.kelly_fraction, config.risk.max_position_pct),
+            limits: PositionLimits {
+                market: config.risk.market.clone(),
+                ..Default::default()
+            },
+            max_positions: config.risk.max_concurrent_positions,
+            max_depth_multiple: config.risk.max_depth_multiple,
+            size_scale: Decimal::ONE,
+            damping: config.risk.damping.clone(),
+            ids: config.ids.mode,
+            flags: config.flags.clone(),
+        }
+    }
+
+    /// Model probability that `market` settles Up
+    pub fn up_probability(
+        &self,
+        market: &Market,
+        spot: Decimal,
+        volatility: Decimal,
+        now: DateTime<Utc>,
+    ) -> Decimal {
+        self.model
+            .calculate(FairValueParams {
+                current_price: spot,
+                open_price: market.open_price,
+                time_to_expiry: market.close_time - now,
+                volatility,
+            })
+            .yes_prob
+    }
+
+    /// Enter at `scale` of the Kelly stake, as while the strategy is
+    /// flagged for review
+    pub fn set_size_scale(&mut self, scale: Decimal) {
+        self.size_scale = scale;
+    }
+
+    /// Evaluate flag `name` in `state` for signals from now on
+    pub fn set_flag(&mut self, name: &str, state: FlagState) {
+        self.flags.set(name, state);
+    }
+
+    /// Most positions held at once
+    pub fn max_positions(&self) -> usize {
+        self.max_positions
+    }
+
+    /// Position limits, including the drawdown halt thresholds
+    pub fn limits(&self) -> &PositionLimits {
+        &self.limits
+    }
+
+    /// Run the full decision for `market` at `now`
+    #[allow(clippy::too_many_arguments)]
+    pub fn explain(
+        &self,
+        market: &Market,
+        books: MarketBooks<'_>,
+        spot: Decimal,
+        volatility: Option<Decimal>,
+        momentum: &MomentumDetector,
+        now: DateTime<Utc>,
+        bankroll: Decimal,
+        positions: &PositionTracker,
+    ) -> Explanation {
+        let time_to_expiry = market.close_time - now;
+        let book = books.yes;
+        let yes_ask = book.best_ask();
+        let available = book.asks.first().map(|l| l.size).unwrap_or_default();
+        let mut x = Explanation {
+            at: now,
+            market_id: market.condition_id.clone(),
+            spot,
+            open_price: market.open_price,
+            time_to_expiry_secs: time_to_expiry.num_seconds(),
+            volatility,
+            book_age_ms: (now - book.updated_at).num_milliseconds(),
+            yes_ask,
+            available,
+            fair_value: None,
+            models: None,
+            yes_edge: None,
+            no_edge: None,
+            costs: self.costs,
+            signal: None,
+            checks: Vec::new(),
+            damping: None,
+            stake: None,
+            depth_cap: None,
+            size: None,
+            order: None,
+            verdict: Verdict::NoEdge,
+        };
+
+        let Some(vol) = volatility else {
+            x.verdict = Verdict::NoData("no volatility estimate".to_string());
+            return x;
+        };
+        if time_to_expiry <= Duration::zero() {
+            x.verdict = Verdict::NoData("market has closed".to_string());
+            return x;
+        }
+        let Some(ask) = yes_ask else {
+            x.verdict = Verdict::NoData("book has no asks".to_string());
+            return x;
+        };
+        let params = FairValueParams {
+            current_price: spot,
+            open_price: market.open_price,
+            time_to_expiry,
+            volatility: vol,
+        };
+        let fair_value = self.model.calculate(params.clone());
+        let models = ModelEstimates {
+            gbm: fair_value.yes_prob,
+            linear: self.linear.calculate(params).yes_prob,
+            mid: book.mid_price(),
+        };
+        x.models = Some(models);
+        x.yes_edge = Some(fair_value.yes_prob - ask);
+        x.no_edge = Some(fair_value.no_prob - (Decimal::ONE - ask));
+        x.fair_value = Some(fair_value);
+
+        let mut signal = match self.detector.evaluate_at(market, spot, vol, books, now) {
+            Ok(signal) => signal,
+            Err(why) => {
+                match why {
+                    NoLagReason::BookFault => {
+                        if let Some(fault) = book.top_of_book_fault() {
+                            x.verdict = Verdict::NoData(format!("book is {}", fault));
+                        }
+                    }
+                    NoLagReason::NearBound { .. } => x.verdict = Verdict::NearBound,
+                    NoLagReason::ComplementRepriced { .. } => {
+                        x.verdict = Verdict::ComplementRepriced
+                    }
+                    NoLagReason::Expired | NoLagReason::NoAsk | NoLagReason::NoEdge { .. } => {}
+                }
+                return x;
+            }
+        };
+        signal.id = self
+            .ids
+            .signal_id(&market.condition_id, DEFAULT_STRATEGY, now, signal.side);
+        signal.flags = self.flags.evaluate(signal.id);
+        signal = signal.with_models(models);
+        let signal = match momentum.signal(signal.side) {
+            Some(m) => {
+                signal.with_momentum(&m.with_venues(momentum.venue_moves(market.open_price)))
+            }
+            None => signal,
+        };
+
+        let checks = self.filter.checks(
+            &signal,
+            positions.open_count(),
+            self.max_positions,
+            available,
+            vol,
+            time_to_expiry,
+        );
+        let rejected = checks.iter().find_map(|c| c.reject.clone());
+        x.checks.extend(checks.into_iter().map(|c| Check {
+            stage: "filter",
+            name: c.name,
+            passed: c.passed(),
+            detail: c.detail,
+        }));
+        if let Some(why) = rejected {
+            x.signal = Some(signal);
+            x.verdict = Verdict::Filtered(why);
+            return x;
+        }
+
+        let damping = self.damping.damp(signal.side, positions, bankroll);
+        if let Some(damping) = damping {
+            x.damping = Some(damping);
+            x.checks.push(Check {
+                stage: "risk",
+                name: "directional_damping",
+                passed: damping.factor > Decimal::ZERO,
+                detail: damping.to_string(),
+            });
+            if damping.factor.is_zero() {
+                x.signal = Some(signal);
+                x.verdict = Verdict::Blocked(format!("directional exposure {}", damping));
+                return x;
+            }
+        }
+        let damped = damping.map_or(Decimal::ONE, |d| d.factor);
+        let stake = self.kelly.calculate(&signal, bankroll) * self.size_scale * damped;
+        let depth_cap = round_size(signal.depth.within_2c * self.max_depth_multiple);
+        let size = round_size(stake / signal.market_price)
+            .min(available)
+            .min(depth_cap);
+        x.stake = Some(stake);
+        x.depth_cap = Some(depth_cap);
+        x.size = Some(size);
+        if size <= Decimal::ZERO {
+            x.signal = Some(signal);
+            x.verdict = Verdict::Unsized;
+            return x;
+        }
+
+        let order = Order {
+            token_id: match signal.side {
+                Side::Yes => market.yes_token_id.clone(),
+                Side::No => market.no_token_id.clone(),
+            },
+            side: signal.side,
+            price: signal.market_price,
+            size,
+            order_type: OrderType::Limit,
+            action: OrderAction::Buy,
+            client_order_id: Some(ids::client_order_id(signal.id, 1)),
+        };
+        if let Err(e) = self.bounds.check(order.price) {
+            x.checks.push(Check {
+                stage: "risk",
+                name: "price_bounds",
+                passed: false,
+                detail: e.to_string(),
+            });
+            x.signal = Some(signal);
+            x.verdict = Verdict::Blocked(e.to_string());
+            return x;
+        }
+        let exposure = self.limits.market_exposure_after(&order, positions);
+        let mut check = Check {
+            stage: "risk",
+            name: "market_limits",
+            passed: true,
+            detail: format!(
+                "worst-case loss {:.4}, net shares {:.2}, gross notional {:.4}",
+                round_usd(exposure.worst_case_loss()),
+                round_size(exposure.net_shares()),
+                round_usd(exposure.gross_notional())
+            ),
+        };
+        if let Err(e) = self.limits.check_limits(&order, positions) {
+            check.passed = false;
+            x.checks.push(check);
+            x.signal = Some(signal);
+            x.verdict = Verdict::Blocked(e.to_string());
+            return x;
+        }
+        x.checks.push(check);
+        if let Some(group) = self.limits.group_exposure_after(&order, market, positions) {
+            let mut check = Check {
+                stage: "risk",
+                name: "group_limits",
+                passed: true,
+                detail: format!(
+                    "group {} gross notional {:.4} across {} markets, worst-case loss {:.4}",
+                    group.group_id,
+                    round_usd(group.gross_notional()),
+                    group.markets.len(),
+                    round_usd(group.worst_case_loss())
+                ),
+            };
+            if let Err(e) = self.limits.check_group_limits(&order, market, positions) {
+                check.passed = false;
+                x.checks.push(check);
+                x.signal = Some(signal);
+                x.verdict = Verdict::Blocked(e.to_string());
+                return x;
+            }
+            x.checks.push(check);
+        }
+        x.signal = Some(signal);
+        x.order = Some(order);
+        x.verdict = Verdict::Trade;
+        x
+    }
+}
+
+/// Momentum window sized from the signal config
+pub fn momentum_detector(config: &Config) -> MomentumDetector {
+    MomentumDetector::new(config.signal.momentum_window_secs.to_chrono())
+        .with_max_samples(config.signal.momentum_max_samples)
+}
+
+/// Explain what the engine would do with `snapshot`, holding no positions
+/// and the configured starting bankroll
+pub fn evaluate(config: &Config, snapshot: &Snapshot) -> Explanation {
+    explain_snapshot(
+        config,
+        &DecisionStack::new(config),
+        snapshot,
+        &PositionTracker::new(),
+    )
+}
+
+/// Explain `snapshot` through `stack` with `positions` held
+fn explain_snapshot(
+    config: &Config,
+    stack: &DecisionStack,
+    snapshot: &Snapshot,
+    positions: &PositionTracker,
+) -> Explanation {
+    let mut volatility =
+        VolatilityEstimator::new(config.model.volatility_window_minutes.to_chrono())
+            .with_max_samples(config.model.volatility_max_samples);
+    for (ts, price) in snapshot.ticks.iter().filter(|(ts, _)| *ts < snapshot.at) {
+        volatility.update(*ts, *price);
+    }
+    volatility.update(snapshot.at, snapshot.spot);
+    let mut momentum = momentum_detector(config);
+    for (ts, price) in snapshot.ticks.iter().filter(|(ts, _)| *ts < snapshot.at) {
+        momentum.update(*ts, *price);
+    }
+    momentum.update(snapshot.at, snapshot.spot);
+
+    stack.explain(
+        &snapshot.market,
+        MarketBooks::new(&snapshot.book).with_no(snapshot.no_book.as_ref()),
+        snapshot.spot,
+        volatility.estimate(),
+        &momentum,
+        snapshot.at,
+        config.risk.initial_bankroll,
+        positions,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/eval");
+
+    fn config() -> Config {
+        toml::from_str(include_str!("../../config.toml.example")).unwrap()
+    }
+
+    fn snapshot(name: &str) -> Snapshot {
+        let path = format!("{}/{}.json", GOLDEN_DIR, name);
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_golden_explanations() {
+        for (name, verdict) in [
+            ("trade", "traded"),
+            ("stale_book", "rejected"),
+            ("no_edge", "no_edge"),
+        ] {
+            let explanation = evaluate(&config(), &snapshot(name));
+            assert_eq!(explanation.verdict.label(), verdict, "{}", name);
+
+            let path = format!("{}/{}.txt", GOLDEN_DIR, name);
+            let rendered = format!("{}\n", explanation);
+            if std::env::var_os("BLESS").is_some() {
+                std::fs::write(&path, &rendered).unwrap();
+            }
+            assert_eq!(
+                std::fs::read_to_string(&path).unwrap(),
+                rendered,
+                "{}.txt is stale; rerun this test with BLESS=1",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_trade_carries_order_and_all_checks() {
+        let explanation = evaluate(&config(), &snapshot("trade"));
+        let order = explanation.order.unwrap();
+        assert_eq!(order.token_id, "yes-0000");
+        assert_eq!(Some(order.size), explanation.size);
+        assert_eq!(explanation.checks.len(), 11);
+        assert!(explanation.checks.iter().all(|c| c.passed));
+    }
+
+    #[test]
+    fn test_size_capped_by_depth_near_touch() {
+        use crate::orderbook::PriceLevel;
+
+        // 8 shares at the touch, then nothing until 5 cents away
+        let mut snapshot = snapshot("trade");
+        snapshot.book.asks = vec![
+            PriceLevel {
+                price: dec!(0.70),
+                size: dec!(8),
+            },
+            PriceLevel {
+                price: dec!(0.75),
+                size: dec!(300),
+            },
+        ];
+        let explanation = evaluate(&config(), &snapshot);
+        let depth = explanation.signal.as_ref().unwrap().depth;
+        assert_eq!(depth.within_2c, dec!(8));
+        assert_eq!(depth.within_3c, dec!(8));
+        // Kelly wants 7.14 shares; half of the 2c depth is 4
+        assert_eq!(explanation.depth_cap, Some(dec!(4)));
+        assert_eq!(explanation.order.unwrap().size, dec!(4));
+    }
+
+    /// 90 seconds from close, 0.1% over the strike after a quiet window:
+    /// the low volatility has GBM above 0.95 while the linear model sits
+    /// at 0.60
+    fn near_expiry() -> Snapshot {
+        use crate::orderbook::PriceLevel;
+
+        let mut snapshot = snapshot("trade");
+        snapshot.at = snapshot.market.close_time - Duration::seconds(90);
+        snapshot.spot = dec!(100100);
+        let start = snapshot.at - Duration::minutes(30);
+        snapshot.ticks = (0..30)
+            .map(|i| {
+                let wobble = if i % 2 == 0 { dec!(20) } else { dec!(-20) };
+                (start + Duration::minutes(i), dec!(100000) + wobble)
+            })
+            .collect();
+        let level = |price, size| PriceLevel { price, size };
+        snapshot.book.bids = vec![level(dec!(0.91), dec!(150))];
+        snapshot.book.asks = vec![level(dec!(0.93), dec!(150)), level(dec!(0.94), dec!(300))];
+        snapshot.book.updated_at = snapshot.at - Duration::seconds(1);
+        snapshot
+    }
+
+    #[test]
+    fn test_model_disagreement_suppresses_signal() {
+        let explanation = evaluate(&config(), &near_expiry());
+        let models = explanation.models.unwrap();
+        assert!(models.gbm > dec!(0.95));
+        assert_eq!(models.linear, dec!(0.60));
+        assert_eq!(models.mid, Some(dec!(0.92)));
+        assert!(matches!(
+            explanation.verdict,
+            Verdict::Filtered(RejectReason::ModelDisagreement { gap, .. }) if gap == models.disagreement()
+        ));
+        assert_eq!(explanation.signal.unwrap().models, Some(models));
+        let check = explanation.checks.last().unwrap();
+        assert_eq!(check.name, "model_agreement");
+        assert!(!check.passed);
+    }
+
+    #[test]
+    fn test_model_disagreement_only_annotates_when_configured() {
+        let mut config = config();
+        config.signal.model_sanity.mode = DisagreementMode::Annotate;
+        let explanation = evaluate(&config, &near_expiry());
+        assert!(matches!(explanation.verdict, Verdict::Trade));
+        let signal = explanation.signal.unwrap();
+        assert!(signal.models.unwrap().disagreement() > dec!(0.35));
+        let check = explanation
+            .checks
+            .iter()
+            .find(|c| c.name == "model_agreement");
+        assert!(check.unwrap().passed);
+        assert!(check.unwrap().detail.ends_with("(annotate only)"));
+    }
+
+    #[test]
+    fn test_same_direction_entries_are_damped_progressively() {
+        use crate::execution::{Fill, LiquidityFlag};
+
+        let mut config = config();
+        config.risk.damping.enabled = true;
+        let stack = DecisionStack::new(&config);
+        let mut positions = PositionTracker::new();
+        let mut sizes = vec![];
+        // Three markets signalling YES one after another, each filled
+        for i in 0..3 {
+            let mut snapshot = snapshot("trade");
+            snapshot.market.condition_id = format!("market-{}", i);
+            snapshot.market.yes_token_id = format!("yes-{}", i);
+            snapshot.market.no_token_id = format!("no-{}", i);
+            let explanation = explain_snapshot(&config, &stack, &snapshot, &positions);
+            let damping = explanation.damping.unwrap();
+            let order = explanation.order.unwrap();
+            sizes.push((damping.factor, order.size));
+            positions.open(
+                &explanation.signal.unwrap(),
+                &Fill {
+                    order_id: uuid::Uuid::new_v4(),
+                    token_id: order.token_id,
+                    side: order.side,
+                    price: order.price,
+                    size: order.size,
+                    timestamp: snapshot.at,
+                    fee: Decimal::ZERO,
+                    estimated_slippage: Decimal::ZERO,
+                    liquidity: LiquidityFlag::Taker,
+                    action: OrderAction::Buy,
+                    client_order_id: String::new(),
+                    simulated: true,
+                },
+            );
+        }
+        // Nothing held yet, so the first enters at full size
+        assert_eq!(sizes[0].0, Decimal::ONE);
+        assert!(
+            sizes[1].0 < sizes[0].0 && sizes[2].0 < sizes[1].0,
+            "{:?}",
+            sizes
+        );
+        assert!(
+            sizes[1].1 < sizes[0].1 && sizes[2].1 < sizes[1].1,
+            "{:?}",
+            sizes
+        );
+        assert!(positions.directional_exposure(Side::No).is_zero());
+    }
+
+    #[test]
+    fn test_without_ticks_there_is_no_volatility() {
+        let mut snapshot = snapshot("trade");
+        snapshot.ticks.clear();
+        let explanation = evaluate(&config(), &snapshot);
+        assert!(matches!(explanation.verdict, Verdict::NoData(_)));
+        assert!(explanation.signal.is_none());
+    }
+}
@@ -0,0 +1,278 @@
+//! Early exits
+//!
+//! Positions are held to settlement unless a trigger asks for them to be
+//! closed sooner. A trigger files an [`ExitRequest`] with its
+//! [`ExitReason`]; the [`ExitManager`] keeps one request per position until
+//! the engine sells the position's token at the touch.
+//!
+//! The [`ExitLadder`] sells in parts: at each rung's time before close, a
+//! position whose bid shows at least the rung's profit per share sells the
+//! rung's fraction of what remains. What is left after the last rung rides
+//! to resolution.
+
+use crate::duration::DurationConfig;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+/// Why a position is closed before settlement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitReason {
+    /// The book supporting the position evaporated; see
+    /// [`crate::signal::BookShockDetector`]
+    BookShock,
+    /// A rung of the exit ladder took profit
+    Ladder,
+    /// The trading schedule closed with `flatten_on_close` set
+    Schedule,
+}
+
+impl ExitReason {
+    /// Metric and journal label
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExitReason::BookShock => "book_shock",
+            ExitReason::Ladder => "ladder",
+            ExitReason::Schedule => "schedule",
+        }
+    }
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// A request to close one position now
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExitRequest {
+    /// Position to close
+    pub position_id: Uuid,
+    /// What triggered the exit
+    pub reason: ExitReason,
+    /// When it was requested
+    pub at: DateTime<Utc>,
+    /// Share of the remaining size to sell; 1 closes the position
+    pub fraction: Decimal,
+    /// What the trigger saw, for the log and journal
+    pub detail: String,
+}
+
+/// Pending exit requests, one per position
+#[derive(Debug, Default)]
+pub struct ExitManager {
+    pending: HashMap<Uuid, ExitRequest>,
+}
+
+impl ExitManager {
+    /// No requests pending
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// File `request`; false if its position already has one pending
+    pub fn request(&mut self, request: ExitRequest) -> bool {
+        if self.pending.contains_key(&request.position_id) {
+            return false;
+        }
+        self.pending.insert(request.position_id, request);
+        true
+    }
+
+    /// Take every pending request, oldest first
+    pub fn drain(&mut self) -> Vec<ExitRequest> {
+        let mut requests: Vec<_> = self.pending.drain().map(|(_, r)| r).collect();
+        requests.sort_by_key(|r| (r.at, r.position_id));
+        requests
+    }
+
+    /// Whether any request is pending
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// One rung of the exit ladder
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LadderRung {
+    /// Time before close the rung comes due
+    pub secs_before_close: DurationConfig,
+    /// Unrealized profit per share, at the bid, the rung needs
+    pub min_profit: Decimal,
+    /// Share of the remaining size it sells
+    pub fraction: Decimal,
+}
+
+/// Exit ladder settings, under `[risk.exit_ladder]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ExitLadderConfig {
+    /// Sell profitable positions in parts as the close approaches
+    #[serde(default)]
+    pub enabled: bool,
+    /// Rungs, in any order
+    #[serde(default = "default_rungs")]
+    pub rungs: Vec<LadderRung>,
+}
+
+fn default_rungs() -> Vec<LadderRung> {
+    let rung = |secs_before_close, min_profit, fraction| LadderRung {
+        secs_before_close: DurationConfig::from_secs(secs_before_close),
+        min_profit,
+        fraction,
+    };
+    vec![
+        rung(180, dec!(0.10), dec!(0.25)),
+        rung(90, dec!(0.10), dec!(0.33)),
+        rung(45, dec!(0.05), dec!(0.5)),
+    ]
+}
+
+impl Default for ExitLadderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rungs: default_rungs(),
+        }
+    }
+}
+
+/// Rungs each position has passed
+#[derive(Debug, Default)]
+pub struct ExitLadder {
+    enabled: bool,
+    /// Latest first
+    rungs: Vec<LadderRung>,
+    /// Index of the next rung each position may take
+    next: HashMap<Uuid, usize>,
+}
+
+impl ExitLadder {
+    /// Ladder of `config`'s rungs
+    ///
+    /// A rung selling nothing or everything is dropped with a warning:
+    /// full exits are the other triggers' job, and the last part rides to
+    /// resolution.
+    pub fn new(config: &ExitLadderConfig) -> Self {
+        let mut rungs = config.rungs.clone();
+        rungs.retain(|rung| {
+            let sells_part = rung.fraction > Decimal::ZERO && rung.fraction < Decimal::ONE;
+            if !sells_part {
+                tracing::warn!(
+                    secs_before_close = %rung.secs_before_close,
+                    fraction = %rung.fraction,
+                    "Exit ladder rung must sell between 0 and 1 of the position, ignoring it"
+                );
+            }
+            sells_part
+        });
+        rungs.sort_by_key(|r| std::cmp::Reverse(r.secs_before_close));
+        Self {
+            enabled: config.enabled,
+            rungs,
+            next: HashMap::new(),
+        }
+    }
+
+    /// Whether the ladder sells anything
+    pub fn is_enabled(&self) -> bool {
+        self.enabled && !self.rungs.is_empty()
+    }
+
+    /// The rung position `position_id`, bought at `entry_price`, takes at
+    /// `now` with its token bid at `bid`, if one is due and shows its profit
+    ///
+    /// A rung is live from its time until the next one's. One passed
+    /// without its profit is skipped, as is every rung after one taken
+    /// until the next comes due.
+    pub fn check(
+        &mut self,
+        position_id: Uuid,
+        entry_price: Decimal,
+        bid: Decimal,
+        close_time: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Option<&LadderRung> {
+        if !self.is_enabled() || now >= close_time {
+            return None;
+        }
+        let left = close_time - now;
+        let live = self
+            .rungs
+            .iter()
+            .rposition(|r| left <= r.secs_before_close.to_chrono())?;
+        let next = self.next.entry(position_id).or_default();
+        if live < *next || bid - entry_price < self.rungs[live].min_profit {
+            return None;
+        }
+        *next = live + 1;
+        Some(&self.rungs[live])
+    }
+
+    /// Stop tracking `position_id`, once closed
+    pub fn forget(&mut self, position_id: &Uuid) {
+        self.next.remove(position_id);
+    }
+
+    /// Index of the next rung each position may take
+    pub fn progress(&self) -> HashMap<Uuid, usize> {
+        self.next.clone()
+    }
+
+    /// Carry on from another ladder's [`Self::progress`], so no rung is
+    /// sold twice
+    pub fn resume(&mut self, progress: HashMap<Uuid, usize>) {
+        self.next.extend(progress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_each_rung_fires_once_when_in_profit() {
+        let close = Utc::now();
+        let before = |secs| close - Duration::seconds(secs);
+        let mut ladder = ExitLadder::new(&ExitLadderConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        let id = Uuid::new_v4();
+        let mut check = |bid, now| ladder.check(id, dec!(0.50), bid, close, now).cloned();
+
+        // Not due yet, then due but short of the profit
+        assert!(check(dec!(0.90), before(200)).is_none());
+        assert!(check(dec!(0.55), before(170)).is_none());
+        let first = check(dec!(0.65), before(150)).unwrap();
+        assert_eq!(first.fraction, dec!(0.25));
+        assert!(check(dec!(0.70), before(120)).is_none());
+
+        // The 90s rung passes unprofitable; the 45s one takes its place
+        assert!(check(dec!(0.52), before(80)).is_none());
+        let last = check(dec!(0.56), before(40)).unwrap();
+        assert_eq!(last.secs_before_close.as_secs(), 45);
+        assert!(check(dec!(0.90), before(10)).is_none());
+        assert!(check(dec!(0.90), close).is_none());
+    }
+
+    #[test]
+    fn test_rungs_selling_everything_are_dropped() {
+        let mut config = ExitLadderConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        config.rungs[0].fraction = Decimal::ONE;
+        config.rungs[1].fraction = Decimal::ZERO;
+        let ladder = ExitLadder::new(&config);
+        assert_eq!(ladder.rungs.len(), 1);
+        assert_eq!(ladder.rungs[0].secs_before_close.as_secs(), 45);
+    }
+}
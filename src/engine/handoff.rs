@@ -0,0 +1,331 @@
+//! Handing a running session over to a new process
+//!
+//! A config change or a new binary need not cost a market window. `poly-hft
+//! ctl handoff` leaves [`HANDOFF_REQUEST_FILE`] in the data directory; the
+//! running process picks it up within a second, stops entering, and keeps
+//! managing exits while it writes its transferable state to
+//! [`HANDOFF_FILE`] each second. The successor, started with `run
+//! --takeover <file>`, connects its feeds and leaves a `.ready` marker next
+//! to the file. The old process then writes the state one last time marked
+//! `released`, gives up its lease, and exits; the successor restores that
+//! state, removes the file, and trades on. Only a state taken after the
+//! `.ready` marker counts as released for it, so the file of an earlier
+//! handoff is never restored. Past the release timeout the successor waits
+//! for the old process to let go of the data directory before trading on
+//! its latest state, and once it has, takes the data directory's lock.
+//!
+//! The state carries what lives only in memory: open positions and their
+//! entry fills, ladder progress and signal features, active markets and the
+//! markets entered, the spot windows, the last spot price, and a pending
+//! hard halt. Loss cooldowns, rate caps and the ledger already live in the
+//! shared data directory; the ledger balance is carried only to check the
+//! successor reads the same one. A spot feed silent for longer than
+//! `gap_tolerance_ms` across the handoff is logged as `HANDOFF_GAP`.
+
+use super::warm::WarmState;
+use crate::backtest::EntryFeatures;
+use crate::duration::{DurationConfig, Millis};
+use crate::execution::{Fill, LiquidityFlag, OrderAction};
+use crate::market::Market;
+use crate::risk::{HaltRecord, Position};
+use crate::signal::Side;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// File in the data directory the draining process writes its state to
+pub const HANDOFF_FILE: &str = "handoff.json";
+
+/// File in the data directory asking the running process to hand over
+pub const HANDOFF_REQUEST_FILE: &str = "handoff.request";
+
+/// Schema version of [`HandoffState`]; a state of any other is refused
+pub const HANDOFF_VERSION: u32 = 1;
+
+/// Default longest wait for the old process to release, in seconds
+pub const DEFAULT_RELEASE_TIMEOUT_SECS: u64 = 30;
+
+/// Default longest spot feed silence across a handoff not reported, in
+/// milliseconds
+pub const DEFAULT_GAP_TOLERANCE_MS: u64 = 2000;
+
+/// Handing a session over between processes, under `[handoff]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct HandoffConfig {
+    /// How long a successor waits for the old process to release its
+    /// state before waiting for it to exit and taking the latest one it
+    /// wrote
+    #[serde(default = "default_release_timeout_secs")]
+    pub release_timeout_secs: DurationConfig,
+    /// Longest the spot feed may go silent across the handoff unreported
+    #[serde(default = "default_gap_tolerance_ms")]
+    pub gap_tolerance_ms: DurationConfig<Millis>,
+}
+
+fn default_release_timeout_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_RELEASE_TIMEOUT_SECS)
+}
+
+fn default_gap_tolerance_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_GAP_TOLERANCE_MS)
+}
+
+impl Default for HandoffConfig {
+    fn default() -> Self {
+        Self {
+            release_timeout_secs: default_release_timeout_secs(),
+            gap_tolerance_ms: default_gap_tolerance_ms(),
+        }
+    }
+}
+
+/// What a draining engine hands its successor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandoffState {
+    /// Schema version, [`HANDOFF_VERSION`] when written
+    pub version: u32,
+    /// When the state was taken
+    pub taken_at: DateTime<Utc>,
+    /// Set on the last state the old process writes before exiting
+    pub released: bool,
+    /// Open positions, under their ids
+    pub positions: Vec<Position>,
+    /// Entry fill of each open position, as it is held now, so the
+    /// successor's fills account for it
+    pub fills: Vec<Fill>,
+    /// Signal features of the open positions, for the trade tape
+    pub features: HashMap<Uuid, EntryFeatures>,
+    /// Next exit ladder rung of each open position
+    pub ladder: HashMap<Uuid, usize>,
+    /// Active markets
+    pub markets: Vec<Market>,
+    /// Markets already traded this window
+    pub entered: Vec<String>,
+    /// Book tokens of the active markets, to subscribe
+    pub tokens: Vec<String>,
+    /// Spot windows
+    pub warm: WarmState,
+    /// Last spot price
+    pub spot: Option<Decimal>,
+    /// When the last spot tick was seen
+    pub last_tick_at: Option<DateTime<Utc>>,
+    /// Ledger balance, checked against the successor's
+    pub balance: Decimal,
+    /// Unacknowledged hard halt
+    pub halt: Option<HaltRecord>,
+    /// P&L realized by the old process this session
+    pub realized_pnl: Decimal,
+}
+
+impl HandoffState {
+    /// Whether this is the release of a successor that left its `.ready`
+    /// marker at `ready_at`, rather than one left from an earlier handoff
+    pub fn released_since(&self, ready_at: DateTime<Utc>) -> bool {
+        self.released && self.taken_at > ready_at
+    }
+
+    /// Write the state to `path`, replacing any earlier one whole
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// The state at `path`; one of another schema version is an error, as
+    /// it cannot be taken over
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("cannot read handoff state {}", path.display()))?;
+        let version: Versioned = serde_json::from_slice(&bytes)
+            .with_context(|| format!("unreadable handoff state {}", path.display()))?;
+        if version.version != HANDOFF_VERSION {
+            anyhow::bail!(
+                "handoff state {} is version {}, this build takes over version {}",
+                path.display(),
+                version.version,
+                HANDOFF_VERSION
+            );
+        }
+        serde_json::from_slice(&bytes)
+            .with_context(|| format!("unreadable handoff state {}", path.display()))
+    }
+}
+
+/// Only the version is read first, so a state of another schema is
+/// refused as such rather than failing to parse
+#[derive(Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+/// The fill that would have opened `position` as it is held now
+pub(super) fn entry_fill(position: &Position) -> Fill {
+    let token_id = match position.side {
+        Side::Yes => position.market.yes_token_id.clone(),
+        Side::No => position.market.no_token_id.clone(),
+    };
+    Fill {
+        order_id: position.id,
+        token_id,
+        side: position.side,
+        price: position.entry_price,
+        size: position.size,
+        timestamp: position.entry_time,
+        fee: position.entry_fee,
+        estimated_slippage: Decimal::ZERO,
+        liquidity: LiquidityFlag::default(),
+        action: OrderAction::Buy,
+        client_order_id: format!("handoff-{}", position.id),
+        simulated: false,
+    }
+}
+
+/// Ask the process running on `data_dir` to hand over; returns where it
+/// will write its state
+pub fn request_handoff(data_dir: &Path) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(data_dir)?;
+    std::fs::write(data_dir.join(HANDOFF_REQUEST_FILE), Utc::now().to_rfc3339())?;
+    Ok(data_dir.join(HANDOFF_FILE))
+}
+
+/// Whether a handoff was requested on `data_dir`, clearing the request
+pub fn take_handoff_request(data_dir: &Path) -> bool {
+    std::fs::remove_file(data_dir.join(HANDOFF_REQUEST_FILE)).is_ok()
+}
+
+/// Marker the successor leaves next to the handoff state at `path` once
+/// its feeds are connected
+pub fn ready_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".ready");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::TradingEngine;
+    use crate::execution::{ExecutionEngine, PaperEngine};
+    use crate::report::PnlReconciler;
+    use crate::sim::Simulation;
+
+    fn config() -> Config {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(45);
+        config
+    }
+
+    fn engine(config: &Config) -> TradingEngine<PaperEngine> {
+        TradingEngine::new(
+            config,
+            PaperEngine::with_cost_model(config.execution.costs.clone()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_handoff_mid_session_continues_the_session() {
+        let config = config();
+        let events: Vec<_> = Simulation::new("BTCUSDT", "BTC", &config.sim).collect();
+
+        let mut uninterrupted = engine(&config);
+        for (ts, event) in events.clone() {
+            uninterrupted.on_event(ts, event).await.unwrap();
+        }
+
+        // Hand over at the first moment past halfway with a position open
+        let mut old = engine(&config);
+        let mut events = events.into_iter();
+        let mut at = None;
+        for (i, (ts, event)) in events.by_ref().enumerate() {
+            old.on_event(ts, event).await.unwrap();
+            if i >= 2000 && old.positions().open_count() > 0 {
+                at = Some(ts);
+                break;
+            }
+        }
+        let at = at.expect("a position open mid-session");
+        old.start_draining(at);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HANDOFF_FILE);
+        let mut state = old.handoff_state(at);
+        state.released = true;
+        state.save(&path).unwrap();
+        let handed: Vec<Uuid> = old.positions().open_positions.keys().copied().collect();
+
+        // The ledger file is shared, so the successor reads the same one
+        let mut new = engine(&config).with_ledger(old.ledger().clone());
+        new.take_over(HandoffState::load(&path).unwrap(), at);
+        for id in &handed {
+            assert!(new.positions().open_positions.contains_key(id));
+        }
+        for (ts, event) in events {
+            new.on_event(ts, event).await.unwrap();
+        }
+
+        // Every order of the uninterrupted run went out once, from one side
+        let (old_stats, new_stats, whole) = (old.stats(), new.stats(), uninterrupted.stats());
+        assert_eq!(old_stats.orders + new_stats.orders, whole.orders);
+        assert_eq!(old_stats.fills + new_stats.fills, whole.fills);
+        assert_eq!(new_stats.drained, 0);
+        assert_eq!(new_stats.handoff_gap_ms, None);
+        assert_eq!(
+            old_stats.realized_pnl + new_stats.realized_pnl,
+            whole.realized_pnl
+        );
+        assert_eq!(new.bankroll(), uninterrupted.bankroll());
+        assert_eq!(new.positions().open_count(), 0);
+
+        // The successor's fills, inherited ones included, reconcile
+        let mut fills = new.inherited_fills().to_vec();
+        fills.extend(new.execution().get_fills().await.unwrap());
+        let reconciliation = PnlReconciler::new(&fills, new.markets_seen(), new.settlements())
+            .with_tracker(new.positions())
+            .run(config.reconcile.tolerance)
+            .unwrap();
+        assert!(reconciliation.passed, "{}", reconciliation);
+    }
+
+    #[test]
+    fn test_state_of_another_version_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HANDOFF_FILE);
+        assert!(HandoffState::load(&path).is_err());
+
+        let config = config();
+        let mut state = engine(&config).handoff_state(Utc::now());
+        state.save(&path).unwrap();
+        let loaded = HandoffState::load(&path).unwrap();
+        assert_eq!(loaded.warm, state.warm);
+        assert!(!loaded.released);
+
+        state.version = HANDOFF_VERSION + 1;
+        state.save(&path).unwrap();
+        let error = HandoffState::load(&path).unwrap_err().to_string();
+        assert!(error.contains("version 2"), "{}", error);
+
+        // A release counts only once taken after the successor was ready
+        let ready_at = state.taken_at;
+        state.released = true;
+        assert!(!state.released_since(ready_at));
+        state.taken_at = ready_at + chrono::Duration::milliseconds(1);
+        assert!(state.released_since(ready_at));
+        state.released = false;
+        assert!(!state.released_since(ready_at));
+
+        assert!(!take_handoff_request(dir.path()));
+        assert_eq!(request_handoff(dir.path()).unwrap(), path);
+        assert!(take_handoff_request(dir.path()));
+        assert!(!take_handoff_request(dir.path()));
+        assert_eq!(ready_path(&path), dir.path().join("handoff.json.ready"));
+    }
+}
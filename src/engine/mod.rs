@@ -0,0 +1,3578 @@
+//! Trading engine
+//!
+//! Turns market data events into trades: the [`DecisionStack`] decides,
+//! then the engine executes and tracks positions. The live run loop and the
+//! offline simulation both drive it with the same [`BacktestEvent`]s, so a
+//! simulated session exercises the real path.
+
+mod decision;
+mod exit;
+mod handoff;
+mod warm;
+
+pub use decision::{
+    evaluate, momentum_detector, Check, DecisionStack, Explanation, Snapshot, Verdict,
+};
+pub use exit::{ExitLadder, ExitLadderConfig, ExitManager, ExitReason, ExitRequest, LadderRung};
+pub use handoff::{
+    ready_path, request_handoff, take_handoff_request, HandoffConfig, HandoffState,
+    DEFAULT_GAP_TOLERANCE_MS, DEFAULT_RELEASE_TIMEOUT_SECS, HANDOFF_FILE, HANDOFF_REQUEST_FILE,
+    HANDOFF_VERSION,
+};
+pub use warm::{
+    WarmState, WarmStateConfig, DEFAULT_WARM_STATE_INTERVAL_SECS, DEFAULT_WARM_STATE_MAX_AGE_SECS,
+    WARM_STATE_FILE, WARM_STATE_VERSION,
+};
+
+use crate::backtest::{BacktestEvent, EntryFeatures, Rejection, TapeRow};
+use crate::breaker::{BreakerState, CircuitBreaker, Failure};
+use crate::bus::TradePrint;
+use crate::clock::ClockEvent;
+use crate::config::Config;
+use crate::data::features::resolution;
+use crate::data::{DataRecorder, HistoryArchive, LiveMicrostructure};
+use crate::effective::{self, EffectiveConfig, Provenance, SIZE_SCALE};
+use crate::execution::{
+    Camouflage, CamouflagePlan, ExecutionEngine, Fill, IntentLog, IntentOutcome, IntentStatus,
+    Order, OrderAction, OrderId, OrderIntent, OrderType, ShadowConfig, ShadowFill,
+    ShadowFillValidator,
+};
+use crate::feed::PriceTick;
+use crate::flags::{self, FlagWatcher};
+use crate::ids;
+use crate::journal::{pending_appends, Journal};
+use crate::leader::{Leadership, Role, LEADERSHIP_HEALTH_COMPONENT};
+use crate::market::{tokens_reversed, Market, TokenOrientation};
+use crate::model::VolatilityEstimator;
+use crate::orderbook::{BookCheckpoint, MarketBooks, MergeOutcome, OrderBook, OrderBookManager};
+use crate::precision::round_size;
+use crate::report::{AttributionBucket, PositionAttribution};
+use crate::risk::{
+    Allocator, Candidate, ClosedPosition, CooldownTrigger, Damping, DrawdownMonitor, HaltRecord,
+    HaltStore, Ledger, LedgerEntry, LedgerEntryKind, LossCooldown, PendingResolution, Position,
+    PositionTracker, RateLimiter, ResolutionBook, ResolutionStatus, RiskError, Settlement,
+    StrategyReview, TradingSchedule, DEFAULT_STRATEGY,
+};
+use crate::signal::{
+    BookShockDetector, ConsistencyMonitor, DisagreementMode, ModelSanityMonitor, MomentumDetector,
+    OutcomeSummary, SellSpreadSignal, Side, Signal, SignalOutcome, SignalOutcomeTracker,
+};
+use crate::stream::EventStream;
+use crate::telemetry::{
+    channel_depths, label_policy, record_asset_mismatch, record_exit, record_fill,
+    record_flag_evaluation, record_model_disagreement, record_open_to_first_book, record_order,
+    record_rate_cap_hit, record_resolution, record_signal, record_signal_rejected,
+    record_unmapped_book, set_balance_drift, set_circuit_state, set_leader_state,
+    set_loss_cooldown, set_provisional_pnl, set_signal_convergence_rate, set_strategy_review,
+    set_window_spread_percentile, EventCode, HealthRegistry, HealthState, InternalsSnapshot,
+};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Health component reporting the execution circuit
+pub const EXECUTION_HEALTH_COMPONENT: &str = "execution";
+
+/// Counters for one engine session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineStats {
+    /// Tag of the execution engine, e.g. `paper` or `dry_run`
+    pub engine: &'static str,
+    /// Price ticks processed
+    pub ticks: u64,
+    /// Order book updates processed
+    pub book_updates: u64,
+    /// Markets discovered
+    pub markets_opened: u64,
+    /// Markets settled
+    pub markets_settled: u64,
+    /// Signals from the detector
+    pub signals: u64,
+    /// Signals rejected by filters, sizing, or limits
+    pub rejected: u64,
+    /// Orders submitted
+    pub orders: u64,
+    /// Orders filled
+    pub fills: u64,
+    /// Positions closed before settlement by an exit trigger
+    pub exits: u64,
+    /// Order submissions that failed
+    pub submit_failures: u64,
+    /// Orders suppressed while the execution circuit was open
+    pub suppressed: u64,
+    /// Times the execution circuit opened
+    pub circuit_trips: u64,
+    /// Entries withheld by a loss cooldown or loss halt
+    pub cooled_down: u64,
+    /// Entries withheld by a rate cap
+    pub rate_capped: u64,
+    /// Times signals competing for capital were ranked
+    pub allocations: u64,
+    /// Signals that would have traded but were outranked for capital
+    pub outranked: u64,
+    /// Times drifting adverse excursions flagged the strategy for review
+    pub review_flags: u64,
+    /// Ticks and markets of another asset dropped before detection
+    pub asset_mismatches: u64,
+    /// Book updates for tokens of no market seen this session
+    pub unmapped_books: u64,
+    /// Books too old to trade on when processed
+    pub stale_books: u64,
+    /// Books restored from a checkpoint and unconfirmed when processed
+    pub unconfirmed_books: u64,
+    /// Book updates dropped as redeliveries or older than the book held
+    pub dropped_books: u64,
+    /// Lags rejected because the NO book had already repriced
+    pub complement_repriced: u64,
+    /// Books held stale because the YES and NO mids disagreed
+    pub inconsistent_books: u64,
+    /// Markets sold on both sides with YES and NO bids summing above 1
+    pub sell_spreads: u64,
+    /// Gaps in replayed data, across which nothing was entered
+    pub data_gaps: u64,
+    /// Detections whose fair value models disagreed past the limit
+    pub model_disagreements: u64,
+    /// Markets opened before their window, strike pending
+    pub preopened: u64,
+    /// Longest wait from a market's open to its first book
+    pub slowest_first_book_secs: Option<i64>,
+    /// Role under leader election; `None` when not electing
+    pub role: Option<Role>,
+    /// Seconds the current holder has held the leader lease
+    pub lease_age_secs: Option<i64>,
+    /// Orders withheld while on standby
+    pub standby_withheld: u64,
+    /// Times this instance took the leader lease
+    pub promotions: u64,
+    /// Times this instance lost it
+    pub demotions: u64,
+    /// P&L of settled positions, provisional settlements included
+    pub realized_pnl: Decimal,
+    /// Part of `realized_pnl` booked on outcomes not yet official
+    pub provisional_pnl: Decimal,
+    /// Settlements awaiting their official resolution
+    pub resolutions_pending: usize,
+    /// Provisional settlements the official resolution agreed with
+    pub resolutions_confirmed: u64,
+    /// Provisional settlements the official resolution reversed
+    pub resolutions_corrected: u64,
+    /// Provisional settlements left standing with no official answer
+    pub resolutions_unconfirmed: u64,
+    /// Where the P&L of settled positions came from
+    pub attribution: AttributionBucket,
+    /// Whether followed signals reached their expected price
+    pub outcomes: OutcomeSummary,
+    /// Hard halt withholding orders, if any
+    pub halt: Option<HaltRecord>,
+    /// Entries withheld while draining for a handoff
+    pub drained: u64,
+    /// Entries withheld outside the trading schedule
+    pub off_schedule: u64,
+    /// Spot feed silence across a handoff taken over, if past tolerance
+    pub handoff_gap_ms: Option<i64>,
+}
+
+impl fmt::Display for EngineStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(halt) = &self.halt {
+            writeln!(f, "  HALTED: {}", halt)?;
+            writeln!(
+                f,
+                "  Orders withheld until `poly-hft ctl ack-halt {}`",
+                halt.id
+            )?;
+        }
+        writeln!(f, "  Engine: {}", self.engine)?;
+        if let Some(role) = self.role {
+            let age = self
+                .lease_age_secs
+                .map(|secs| format!(", lease age {}s", secs))
+                .unwrap_or_default();
+            writeln!(
+                f,
+                "  Role: {}{} ({} promotions, {} demotions, {} orders withheld on standby)",
+                role, age, self.promotions, self.demotions, self.standby_withheld
+            )?;
+        }
+        writeln!(f, "  Price ticks: {}", self.ticks)?;
+        writeln!(f, "  Book updates: {}", self.book_updates)?;
+        writeln!(
+            f,
+            "  Markets: {} opened, {} settled",
+            self.markets_opened, self.markets_settled
+        )?;
+        writeln!(
+            f,
+            "  Signals: {} ({} rejected)",
+            self.signals, self.rejected
+        )?;
+        writeln!(f, "  Orders: {} ({} filled)", self.orders, self.fills)?;
+        if self.exits > 0 {
+            writeln!(f, "  Early exits: {}", self.exits)?;
+        }
+        if self.drained > 0 {
+            writeln!(
+                f,
+                "  Entries withheld draining for a handoff: {}",
+                self.drained
+            )?;
+        }
+        if self.off_schedule > 0 {
+            writeln!(
+                f,
+                "  Entries withheld outside the trading schedule: {}",
+                self.off_schedule
+            )?;
+        }
+        if let Some(gap) = self.handoff_gap_ms {
+            writeln!(f, "  Spot feed gap across the handoff: {}ms", gap)?;
+        }
+        if let Some(secs) = self.slowest_first_book_secs {
+            writeln!(
+                f,
+                "  First books: at most {}s after open ({} markets prepared before open)",
+                secs, self.preopened
+            )?;
+        }
+        if self.submit_failures > 0 {
+            writeln!(
+                f,
+                "  Submission failures: {} (circuit opened {}x, {} orders suppressed)",
+                self.submit_failures, self.circuit_trips, self.suppressed
+            )?;
+        }
+        if self.asset_mismatches > 0 {
+            writeln!(
+                f,
+                "  ASSET MISMATCHES: {} ticks or markets of another asset dropped",
+                self.asset_mismatches
+            )?;
+        }
+        if self.unmapped_books > 0 {
+            writeln!(
+                f,
+                "  UNMAPPED BOOKS: {} updates for tokens of no tracked market",
+                self.unmapped_books
+            )?;
+        }
+        if self.stale_books > 0 {
+            writeln!(f, "  Stale books skipped: {}", self.stale_books)?;
+        }
+        if self.unconfirmed_books > 0 {
+            writeln!(
+                f,
+                "  Restored books skipped: {} (awaiting a live update)",
+                self.unconfirmed_books
+            )?;
+        }
+        if self.dropped_books > 0 {
+            writeln!(
+                f,
+                "  Book updates dropped: {} (redelivered or out of order)",
+                self.dropped_books
+            )?;
+        }
+        if self.complement_repriced > 0 {
+            writeln!(
+                f,
+                "  Lags already priced into the NO book: {} (YES book held stale until its next update)",
+                self.complement_repriced
+            )?;
+        }
+        if self.inconsistent_books > 0 {
+            writeln!(
+                f,
+                "  Books inconsistent with their complement: {} (held stale until their next update)",
+                self.inconsistent_books
+            )?;
+        }
+        if self.sell_spreads > 0 {
+            writeln!(f, "  Sell spreads: {}", self.sell_spreads)?;
+        }
+        if self.data_gaps > 0 {
+            writeln!(
+                f,
+                "  Data gaps: {} (spot windows restarted after each)",
+                self.data_gaps
+            )?;
+        }
+        if self.model_disagreements > 0 {
+            writeln!(f, "  Model disagreements: {}", self.model_disagreements)?;
+        }
+        if self.cooled_down > 0 {
+            writeln!(
+                f,
+                "  Entries withheld by loss cooldown: {}",
+                self.cooled_down
+            )?;
+        }
+        if self.rate_capped > 0 {
+            writeln!(f, "  Entries withheld by rate caps: {}", self.rate_capped)?;
+        }
+        if self.allocations > 0 {
+            writeln!(
+                f,
+                "  Capital allocation: {} rankings, {} signals outranked",
+                self.allocations, self.outranked
+            )?;
+        }
+        if self.review_flags > 0 {
+            writeln!(f, "  Strategy review flags raised: {}", self.review_flags)?;
+        }
+        writeln!(f, "  Signal outcomes: {}", self.outcomes)?;
+        if self.attribution.positions > 0 {
+            writeln!(f, "  P&L attribution: {}", self.attribution)?;
+        }
+        let resolved =
+            self.resolutions_confirmed + self.resolutions_corrected + self.resolutions_unconfirmed;
+        if resolved > 0 || self.resolutions_pending > 0 {
+            writeln!(
+                f,
+                "  Resolutions: {} confirmed, {} corrected, {} unconfirmed, {} pending",
+                self.resolutions_confirmed,
+                self.resolutions_corrected,
+                self.resolutions_unconfirmed,
+                self.resolutions_pending
+            )?;
+        }
+        write!(f, "  Realized P&L: {:+.2}", self.realized_pnl)?;
+        if self.resolutions_pending > 0 {
+            write!(
+                f,
+                " ({:+.2} PROVISIONAL, awaiting official resolution)",
+                self.provisional_pnl
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A signal held to compete for capital, as it would be entered
+#[derive(Debug, Clone)]
+struct HeldEntry {
+    signal: Signal,
+    order: Order,
+    /// Mid of the traded side when it was decided
+    mid: Option<Decimal>,
+    /// Directional damping applied to its size
+    damping: Option<Damping>,
+}
+
+/// An entry waiting out its camouflage delay before it is submitted
+#[derive(Debug, Clone)]
+struct DelayedEntry {
+    due: DateTime<Utc>,
+    entry: HeldEntry,
+    plan: CamouflagePlan,
+}
+
+/// Event-driven trading pipeline over an execution engine
+pub struct TradingEngine<E: ExecutionEngine> {
+    execution: E,
+    stack: DecisionStack,
+    /// Balance movements; its balance is the bankroll sized against
+    ledger: Ledger,
+    drawdown: DrawdownMonitor,
+    /// Day the drawdown monitor's daily P&L started
+    day: Option<chrono::NaiveDate>,
+    halts: Option<HaltStore>,
+    breaker: CircuitBreaker<anyhow::Error>,
+    health: Option<HealthRegistry>,
+    intents: Option<IntentLog>,
+    /// Asset of every market traded, the key of its loss cooldown
+    asset: String,
+    cooldown: LossCooldown,
+    review: StrategyReview,
+    rate: RateLimiter,
+    /// Parameters in force and their changes; its revision stamps every
+    /// journaled entry
+    effective: EffectiveConfig,
+    /// Flag changes from the config file and `poly-hft ctl flag`
+    flag_watcher: Option<FlagWatcher>,
+    /// Signals competing for the position slots and cash left
+    allocator: Allocator<HeldEntry>,
+    /// Random delay, size jitter and splitting of entries
+    camouflage: Camouflage,
+    /// Entries waiting out their camouflage delay, their market taken
+    delayed: Vec<DelayedEntry>,
+    /// Recent YES spreads per window minute, when `[data.stats] live` is on
+    microstructure: Option<LiveMicrostructure>,
+    volatility: VolatilityEstimator,
+    momentum: MomentumDetector,
+    positions: PositionTracker,
+    /// Active markets keyed by YES token
+    markets: HashMap<String, Market>,
+    /// Every market opened and settled this session, for reconciliation
+    seen_markets: Vec<Market>,
+    settlements: Vec<Settlement>,
+    /// Markets already traded this window
+    entered: HashSet<String>,
+    /// Markets whose assumed token orientation no book has settled yet
+    unoriented: HashSet<String>,
+    /// Unmapped tokens already logged
+    unmapped: HashSet<String>,
+    /// Last rejection journaled per market, so a repeat is not journaled again
+    rejections: HashMap<String, &'static str>,
+    /// Markets with an entry withheld by a rate cap, reported once each
+    rate_capped: HashSet<String>,
+    /// Tokens whose held book is crossed or locked, reported once each
+    faulted_books: HashSet<String>,
+    /// Every rejection per market this window, for the trade tape
+    trail: HashMap<String, Vec<Rejection>>,
+    /// Signal features of each open position, for the trade tape
+    entries: HashMap<Uuid, EntryFeatures>,
+    /// Closed trades this session
+    tape: Vec<TapeRow>,
+    /// Settled positions attributed this session
+    attribution: Vec<PositionAttribution>,
+    /// Leader election; without it the engine always trades
+    leadership: Option<Leadership>,
+    /// Submitted orders not filled at submission, by market
+    resting: HashMap<OrderId, String>,
+    /// Orders submitted per market and signal, numbering client order IDs
+    attempts: HashMap<(String, Uuid), u32>,
+    /// Markets that have had a book, for open-to-first-book latency
+    booked: HashSet<String>,
+    /// Update cadence per token, judging book staleness
+    books: OrderBookManager,
+    /// YES and NO books of each market checked against each other
+    consistency: ConsistencyMonitor,
+    /// Disagreement streaks of the fair value models per market
+    sanity: ModelSanityMonitor,
+    /// Depth evaporation under held positions
+    shocks: BookShockDetector,
+    /// Settlements awaiting the official resolution
+    resolutions: ResolutionBook,
+    exits: ExitManager,
+    /// Profit taken in parts near the close
+    ladder: ExitLadder,
+    spot: Option<Decimal>,
+    /// End of a gap in the data; nothing is entered before it
+    gap_until: Option<DateTime<Utc>>,
+    /// End of the settle period after a wall-clock step
+    clock_settle_until: Option<DateTime<Utc>>,
+    /// Withholding entries ahead of a handoff
+    draining: bool,
+    /// Allowed trading windows; nothing is entered outside them
+    schedule: TradingSchedule,
+    /// Last tick of the process handed over from, until the first here
+    handoff_from: Option<DateTime<Utc>>,
+    handoff_gap_tolerance: Duration,
+    /// Entry fills of positions handed over by another process
+    inherited: Vec<Fill>,
+    last_tick_at: Option<DateTime<Utc>>,
+    recorder: Option<DataRecorder>,
+    outcomes: SignalOutcomeTracker,
+    outcome_journal: Option<Journal>,
+    trade_journal: Option<Journal>,
+    /// Paper entries checked against the market's trade prints
+    shadow: ShadowFillValidator,
+    /// Keep the latest evaluation for a stepped replay to show
+    keep_explanations: bool,
+    explanation: Option<Explanation>,
+    stats: EngineStats,
+}
+
+impl<E: ExecutionEngine> TradingEngine<E> {
+    /// Create an engine from the bot config
+    pub fn new(config: &Config, execution: E) -> Self {
+        let stats = EngineStats {
+            engine: execution.name(),
+            ..Default::default()
+        };
+        Self {
+            execution,
+            stack: DecisionStack::new(config),
+            ledger: Ledger::new()
+                .with_opening_deposit(config.risk.initial_bankroll, Utc::now())
+                .unwrap_or_default(),
+            drawdown: DrawdownMonitor::new(config.risk.initial_bankroll),
+            day: None,
+            halts: None,
+            breaker: CircuitBreaker::new(config.execution.breaker.clone()),
+            health: None,
+            intents: None,
+            asset: config.market.asset.to_uppercase(),
+            cooldown: LossCooldown::new(config.risk.loss_cooldown.clone()),
+            review: StrategyReview::new(config.risk.review.clone()),
+            rate: RateLimiter::new(config.risk.rate_caps.clone()),
+            effective: {
+                let mut registry = EffectiveConfig::new(config, effective::source());
+                registry.register(SIZE_SCALE, Decimal::ONE, Provenance::Default);
+                registry
+            },
+            flag_watcher: None,
+            allocator: Allocator::new(config.risk.allocation.clone()),
+            camouflage: Camouflage::new(&config.execution.camouflage),
+            delayed: vec![],
+            microstructure: config
+                .data
+                .stats
+                .live
+                .then(|| LiveMicrostructure::new(config.data.stats.live_samples)),
+            volatility: VolatilityEstimator::new(
+                config.model.volatility_window_minutes.to_chrono(),
+            )
+            .with_max_samples(config.model.volatility_max_samples),
+            momentum: momentum_detector(config),
+            positions: PositionTracker::new(),
+            markets: HashMap::new(),
+            seen_markets: vec![],
+            settlements: vec![],
+            entered: HashSet::new(),
+            unoriented: HashSet::new(),
+            unmapped: HashSet::new(),
+            rejections: HashMap::new(),
+            rate_capped: HashSet::new(),
+            faulted_books: HashSet::new(),
+            trail: HashMap::new(),
+            entries: HashMap::new(),
+            tape: vec![],
+            attribution: vec![],
+            leadership: None,
+            resting: HashMap::new(),
+            attempts: HashMap::new(),
+            booked: HashSet::new(),
+            books: OrderBookManager::new()
+                .with_freshness(config.signal.book_freshness.clone())
+                .with_tick_size(config.signal.tick_size)
+                .with_restored_signals(config.signal.book_checkpoint.allow_signals),
+            consistency: ConsistencyMonitor::new(config.signal.consistency.clone())
+                .with_fee_rate(config.execution.costs.taker_fee_rate),
+            sanity: ModelSanityMonitor::new(config.signal.model_sanity.clone()),
+            shocks: BookShockDetector::new(
+                config.signal.book_shock.clone(),
+                config.model.min_time_to_expiry_secs.to_chrono(),
+            ),
+            exits: ExitManager::new(),
+            ladder: ExitLadder::new(&config.risk.exit_ladder),
+            resolutions: ResolutionBook::new(config.risk.resolution.clone()),
+            spot: None,
+            gap_until: None,
+            clock_settle_until: None,
+            draining: false,
+            schedule: TradingSchedule::new(config.schedule.clone()),
+            handoff_from: None,
+            handoff_gap_tolerance: config.handoff.gap_tolerance_ms.to_chrono(),
+            inherited: vec![],
+            last_tick_at: None,
+            recorder: None,
+            outcomes: SignalOutcomeTracker::new(),
+            outcome_journal: None,
+            trade_journal: None,
+            shadow: ShadowFillValidator::default(),
+            keep_explanations: false,
+            explanation: None,
+            stats,
+        }
+    }
+
+    /// Record ticks and books as they are processed
+    pub fn with_recorder(mut self, recorder: DataRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Journal every finished signal outcome
+    pub fn with_outcome_journal(mut self, journal: Journal) -> Self {
+        self.outcome_journal = Some(journal);
+        self
+    }
+
+    /// Trade only while holding the leader lease, observing otherwise
+    pub fn with_leadership(mut self, leadership: Leadership) -> Self {
+        self.stats.role = Some(leadership.role());
+        self.leadership = Some(leadership);
+        self
+    }
+
+    /// Persist hard halts so they survive a restart
+    pub fn with_halt_store(mut self, store: HaltStore) -> Self {
+        self.halts = Some(store);
+        self
+    }
+
+    /// Start halted by an unacknowledged hard halt: signals are still
+    /// evaluated and followed, but no order is submitted
+    pub fn with_pending_halt(mut self, halt: HaltRecord) -> Self {
+        self.stats.halt = Some(halt);
+        self
+    }
+
+    /// Keep loss cooldowns in `cooldown`, usually one loaded from disk so
+    /// a cooldown or loss halt outlives the process
+    pub fn with_loss_cooldown(mut self, cooldown: LossCooldown) -> Self {
+        set_loss_cooldown(&self.asset, cooldown.severity(&self.asset, Utc::now()));
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Keep adverse excursion statistics in `review`, usually one loaded
+    /// from disk so a review flag and its reduced sizing outlive the process
+    pub fn with_strategy_review(mut self, review: StrategyReview) -> Self {
+        self.review = review;
+        self.apply_review(Utc::now(), Provenance::Adaptive);
+        self
+    }
+
+    /// Write the effective config registry to `path` on every change, for
+    /// `status --effective-config`
+    pub fn with_effective_config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.effective = std::mem::take(&mut self.effective).with_file(path);
+        self
+    }
+
+    /// Draw the camouflage of entries from `camouflage` rather than the
+    /// configured, unseeded one
+    pub fn with_camouflage(mut self, camouflage: Camouflage) -> Self {
+        self.camouflage = camouflage;
+        self
+    }
+
+    /// Gate entries on `schedule`, e.g. one journaling its transitions,
+    /// rather than the configured one
+    pub fn with_schedule(mut self, schedule: TradingSchedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Take flag changes from `watcher` on every [`Self::sync_flags`]
+    pub fn with_flag_watcher(mut self, watcher: FlagWatcher) -> Self {
+        self.flag_watcher = Some(watcher);
+        self
+    }
+
+    /// Serve the effective config registry at the event stream's `/state`
+    pub fn with_state_stream(mut self, stream: EventStream) -> Self {
+        self.effective = std::mem::take(&mut self.effective).with_stream(stream);
+        self
+    }
+
+    /// Parameters in force and every change to them this session
+    pub fn effective_config(&self) -> &EffectiveConfig {
+        &self.effective
+    }
+
+    /// Hold settlements provisional in `book`, e.g. one writing its state
+    /// file
+    pub fn with_resolution_book(mut self, book: ResolutionBook) -> Self {
+        self.resolutions = book;
+        self
+    }
+
+    /// Count entries against `limiter`, e.g. one with persisted counters
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate = limiter;
+        self
+    }
+
+    /// Keep about `max_closed` closed positions in memory, archiving older
+    /// ones to `archive`
+    pub fn with_position_archive(mut self, archive: HistoryArchive, max_closed: usize) -> Self {
+        self.positions = self.positions.with_archive(archive, max_closed);
+        self
+    }
+
+    /// Report the execution circuit as the `execution` component
+    pub fn with_health(mut self, health: HealthRegistry) -> Self {
+        health.set(EXECUTION_HEALTH_COMPONENT, HealthState::Healthy, None);
+        self.health = Some(health);
+        self
+    }
+
+    /// Log every order durably before submitting it, see
+    /// [`Self::recover_intents`]
+    pub fn with_intent_log(mut self, intents: IntentLog) -> Self {
+        self.intents = Some(intents);
+        self
+    }
+
+    /// Journal orders, fills, and settlements tagged with the engine name
+    pub fn with_trade_journal(mut self, journal: Journal) -> Self {
+        self.trade_journal = Some(journal);
+        self
+    }
+
+    /// Keep the bankroll in `ledger`, usually the session's ledger file,
+    /// measuring drawdown from its balance
+    pub fn with_ledger(mut self, ledger: Ledger) -> Self {
+        self.drawdown = DrawdownMonitor::new(ledger.balance());
+        self.ledger = ledger;
+        self
+    }
+
+    /// Check each paper entry against the trade prints passed to
+    /// [`Self::on_trade`]
+    pub fn with_shadow_fills(mut self, config: ShadowConfig) -> Self {
+        self.shadow = ShadowFillValidator::new(config);
+        self
+    }
+
+    /// Keep each pre-trade evaluation for [`Self::take_explanation`], as a
+    /// stepped replay shows them
+    pub fn with_explanations(mut self) -> Self {
+        self.keep_explanations = true;
+        self
+    }
+
+    /// Process one event
+    pub async fn on_event(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        event: BacktestEvent,
+    ) -> anyhow::Result<()> {
+        self.check_leadership(timestamp).await;
+        self.expire_resolutions(timestamp);
+        self.allocate(timestamp).await?;
+        self.release_delayed(timestamp).await?;
+        match event {
+            BacktestEvent::PriceTick(tick) => self.apply_ticks(vec![tick]).await,
+            BacktestEvent::MarketOpen(market) => {
+                if !self.routed("market", &market.asset) {
+                    return Ok(());
+                }
+                // Prepared before open, then found again by discovery
+                if self
+                    .markets
+                    .values()
+                    .any(|m| m.condition_id == market.condition_id)
+                {
+                    return Ok(());
+                }
+                self.stats.markets_opened += 1;
+                if timestamp < market.open_time {
+                    self.stats.preopened += 1;
+                }
+                tracing::debug!(market = %market.condition_id, "Market opened");
+                self.journal(
+                    "market_opened",
+                    serde_json::json!({
+                        "market_id": market.condition_id,
+                        "asset": market.asset,
+                        "yes_token_id": market.yes_token_id,
+                        "no_token_id": market.no_token_id,
+                        "open_price": market.open_price,
+                        "open_time": market.open_time,
+                        "close_time": market.close_time,
+                        "orientation": market.orientation,
+                    }),
+                );
+                if market.orientation == TokenOrientation::Assumed {
+                    tracing::info!(
+                        market_id = %market.condition_id,
+                        "No outcome labels, inferring YES/NO tokens from the order book"
+                    );
+                    self.unoriented.insert(market.condition_id.clone());
+                }
+                self.cooldown.on_market_open(&self.asset, &market);
+                label_policy().register_market(&market);
+                self.seen_markets.push(market.clone());
+                self.markets.insert(market.yes_token_id.clone(), market);
+            }
+            BacktestEvent::OrderBookUpdate(book) => {
+                self.stats.book_updates += 1;
+                self.check_book_token(timestamp, &book);
+                self.check_first_book(timestamp, &book);
+                // A late or repeated book would roll the held one back
+                if self.books.insert(&book) == MergeOutcome::Applied {
+                    self.check_book_fault(&book);
+                    self.outcomes.on_book(timestamp, &book);
+                    self.mark_positions(&book);
+                    self.check_book_shock(timestamp, &book);
+                    self.check_exit_ladder(timestamp, &book);
+                    self.process_exits(timestamp, &book).await?;
+                    self.on_book(timestamp, &book).await?;
+                    self.check_consistency(timestamp, &book).await?;
+                } else {
+                    self.stats.dropped_books += 1;
+                }
+                if let Some(recorder) = &self.recorder {
+                    if let Err(e) = recorder.record_orderbook(book) {
+                        tracing::debug!(error = %e, "Failed to record order book");
+                    }
+                }
+            }
+            BacktestEvent::MarketClose(market) => {
+                // Tokens may have been swapped since the market opened
+                let market = self
+                    .seen_markets
+                    .iter()
+                    .rev()
+                    .find(|m| m.condition_id == market.condition_id)
+                    .cloned()
+                    .unwrap_or(market);
+                // Closed first so its marks are there to attribute against
+                let outcome = self.outcomes.close(&market);
+                self.finish_outcomes(outcome.into_iter().collect()).await;
+                self.consistency.remove(&market.condition_id);
+                self.settle(timestamp, &market);
+                label_policy().close_market(&market.condition_id, timestamp);
+            }
+            BacktestEvent::DataGap { until } => {
+                // As after a feed outage: momentum and volatility start over
+                // once data resumes, and nothing is entered until then
+                self.stats.data_gaps += 1;
+                tracing::warn!(from = %timestamp, until = %until, "Gap in the data, entries withheld");
+                self.momentum.clear();
+                self.volatility.clear();
+                self.gap_until = Some(until);
+            }
+        }
+        Ok(())
+    }
+
+    /// Process the ticks drained from the feed in one pass, oldest first
+    ///
+    /// Ends in the state `on_event` would reach tick by tick: each tick is
+    /// counted, recorded, and sampled for volatility and strikes, while the
+    /// momentum window takes the batch in one update and expiry checks run
+    /// once, at the latest tick.
+    pub async fn on_ticks(&mut self, ticks: Vec<PriceTick>) -> anyhow::Result<()> {
+        let Some(timestamp) = ticks.last().map(|t| t.exchange_ts) else {
+            return Ok(());
+        };
+        self.check_leadership(timestamp).await;
+        self.expire_resolutions(timestamp);
+        self.allocate(timestamp).await?;
+        self.release_delayed(timestamp).await?;
+        self.apply_ticks(ticks).await;
+        Ok(())
+    }
+
+    async fn apply_ticks(&mut self, ticks: Vec<PriceTick>) {
+        let mut prices = Vec::with_capacity(ticks.len());
+        for tick in ticks {
+            if !self.routed("tick", &tick.asset) {
+                continue;
+            }
+            self.stats.ticks += 1;
+            self.set_strikes(tick.exchange_ts, tick.price);
+            self.volatility.update(tick.exchange_ts, tick.price);
+            prices.push((tick.exchange_ts, tick.price));
+            if let Some(recorder) = &self.recorder {
+                if let Err(e) = recorder.record_price(tick) {
+                    tracing::debug!(error = %e, "Failed to record price tick");
+                }
+            }
+        }
+        if let (Some(from), Some(&(first, _))) = (self.handoff_from, prices.first()) {
+            self.handoff_from = None;
+            self.check_handoff_gap(from, first);
+        }
+        let Some(&(now, spot)) = prices.last() else {
+            return;
+        };
+        self.spot = Some(spot);
+        self.last_tick_at = Some(now);
+        self.momentum.update_prices(&prices);
+        let expired = self.outcomes.expire(now);
+        self.finish_outcomes(expired).await;
+    }
+
+    /// Whether a tick or market of `asset` belongs to this engine
+    ///
+    /// Routing tags everything with its asset before it gets here, so a
+    /// mismatch is a wiring bug: it is counted, logged, and dropped, and
+    /// fails debug builds outright.
+    fn routed(&mut self, kind: &str, asset: &str) -> bool {
+        if asset == self.asset {
+            return true;
+        }
+        self.stats.asset_mismatches += 1;
+        record_asset_mismatch(kind, &self.asset, asset);
+        tracing::error!(
+            expected = %self.asset,
+            actual = %asset,
+            kind,
+            "Dropped {} of another asset before detection",
+            kind
+        );
+        debug_assert!(
+            false,
+            "{} for asset '{}' reached the {} engine",
+            kind, asset, self.asset
+        );
+        false
+    }
+
+    /// Settle an assumed token orientation from a book of either token, and
+    /// count books whose token belongs to no market seen this session
+    ///
+    /// The book is compared with the model's fair value of Up. A market with
+    /// an unsettled orientation is not traded: near 0.5 both tokens trade
+    /// alike, so the first books can leave it undecided.
+    fn check_book_token(&mut self, now: DateTime<Utc>, book: &OrderBook) {
+        if self.unoriented.is_empty() && self.markets.contains_key(&book.token_id) {
+            return;
+        }
+        let Some(market) = self
+            .markets
+            .values()
+            .find(|m| m.yes_token_id == book.token_id || m.no_token_id == book.token_id)
+        else {
+            if self
+                .seen_markets
+                .iter()
+                .any(|m| m.yes_token_id == book.token_id || m.no_token_id == book.token_id)
+            {
+                return;
+            }
+            self.stats.unmapped_books += 1;
+            record_unmapped_book();
+            if self.unmapped.insert(book.token_id.clone()) {
+                tracing::warn!(
+                    event_code = %EventCode::BookUnmapped,
+                    token_id = %book.token_id,
+                    "Order book for a token of no tracked market"
+                );
+            }
+            return;
+        };
+        if !self.unoriented.contains(&market.condition_id) || market.open_price.is_zero() {
+            return;
+        }
+        let (Some(spot), Some(volatility), Some(mid)) =
+            (self.spot, self.volatility.estimate(), book.mid_price())
+        else {
+            return;
+        };
+        let up_fair = self.stack.up_probability(market, spot, volatility, now);
+        let yes_mid = if book.token_id == market.yes_token_id {
+            mid
+        } else {
+            Decimal::ONE - mid
+        };
+        let Some(reversed) = tokens_reversed(yes_mid, up_fair) else {
+            return;
+        };
+
+        let key = market.yes_token_id.clone();
+        let Some(mut market) = self.markets.remove(&key) else {
+            return;
+        };
+        self.unoriented.remove(&market.condition_id);
+        let mut yes_mid = yes_mid;
+        if reversed {
+            market.swap_tokens();
+            // Books held under the assumed orientation
+            self.consistency.remove(&market.condition_id);
+            yes_mid = Decimal::ONE - yes_mid;
+            if let Some(seen) = self
+                .seen_markets
+                .iter_mut()
+                .rev()
+                .find(|m| m.condition_id == market.condition_id)
+            {
+                *seen = market.clone();
+            }
+        }
+        tracing::warn!(
+            event_code = %EventCode::TokensInferred,
+            market_id = %market.condition_id,
+            reversed,
+            yes_token_id = %market.yes_token_id,
+            yes_mid = %yes_mid,
+            up_fair = %up_fair,
+            "Inferred YES/NO tokens of an unlabeled market from its order book"
+        );
+        self.journal(
+            "tokens_inferred",
+            serde_json::json!({
+                "market_id": market.condition_id,
+                "reversed": reversed,
+                "yes_token_id": market.yes_token_id,
+                "no_token_id": market.no_token_id,
+                "yes_mid": yes_mid,
+                "up_fair": up_fair,
+            }),
+        );
+        self.markets.insert(market.yes_token_id.clone(), market);
+    }
+
+    /// Fill in the strike of markets prepared before open from the first
+    /// tick at or after their open
+    fn set_strikes(&mut self, now: DateTime<Utc>, price: Decimal) {
+        let mut set = vec![];
+        for market in self.markets.values_mut() {
+            if market.open_price.is_zero() && market.open_time <= now {
+                market.open_price = price;
+                set.push(market.clone());
+            }
+        }
+        for market in set {
+            if let Some(seen) = self
+                .seen_markets
+                .iter_mut()
+                .rev()
+                .find(|m| m.condition_id == market.condition_id)
+            {
+                seen.open_price = price;
+            }
+            tracing::info!(
+                market_id = %market.condition_id,
+                open_price = %price,
+                delay_ms = (now - market.open_time).num_milliseconds(),
+                "Strike set from the feed at open"
+            );
+            self.journal(
+                "strike_set",
+                serde_json::json!({
+                    "market_id": market.condition_id,
+                    "open_price": price,
+                    "open_time": market.open_time,
+                }),
+            );
+        }
+    }
+
+    /// Take the next snapshot of a token whose book came in crossed or
+    /// locked, whatever its time, rather than wait for the book to go stale
+    fn check_book_fault(&mut self, book: &OrderBook) {
+        let Some(fault) = book.top_of_book_fault() else {
+            self.faulted_books.remove(&book.token_id);
+            return;
+        };
+        self.books.resync(&book.token_id);
+        // Once per fault, not on every book update
+        if self.faulted_books.insert(book.token_id.clone()) {
+            tracing::warn!(
+                event_code = %EventCode::BookCrossed,
+                token_id = %book.token_id,
+                asset = %self.asset,
+                fault,
+                "Order book top is inconsistent, resyncing"
+            );
+        }
+    }
+
+    /// Record how long after its open a market's first book arrived, zero
+    /// for a book subscribed before open
+    fn check_first_book(&mut self, now: DateTime<Utc>, book: &OrderBook) {
+        let Some(market) = self
+            .markets
+            .values()
+            .find(|m| m.yes_token_id == book.token_id || m.no_token_id == book.token_id)
+        else {
+            return;
+        };
+        if !self.booked.insert(market.condition_id.clone()) {
+            return;
+        }
+        let wait = (now - market.open_time).max(Duration::zero());
+        record_open_to_first_book(wait.num_milliseconds() as f64 / 1000.0);
+        let secs = wait.num_seconds();
+        self.stats.slowest_first_book_secs = Some(
+            self.stats
+                .slowest_first_book_secs
+                .map_or(secs, |s| s.max(secs)),
+        );
+    }
+
+    async fn on_book(&mut self, now: DateTime<Utc>, book: &OrderBook) -> anyhow::Result<()> {
+        let Some(market) = self.markets.get(&book.token_id) else {
+            return Ok(());
+        };
+        if let Some(live) = &mut self.microstructure {
+            if let Some(percentile) = live.observe(market, book, now) {
+                set_window_spread_percentile(
+                    &market.asset.to_uppercase(),
+                    percentile.to_f64().unwrap_or_default(),
+                );
+            }
+        }
+        // Prepared before open; nothing to price against until the strike is set
+        if market.open_price.is_zero() {
+            return Ok(());
+        }
+        if self.entered.contains(&market.condition_id)
+            || self.unoriented.contains(&market.condition_id)
+            || self.gap_until.is_some_and(|until| now < until)
+            || self.clock_settle_until.is_some_and(|until| now < until)
+        {
+            return Ok(());
+        }
+        let Some(spot) = self.spot else {
+            return Ok(());
+        };
+        if !self.books.is_fresh(&book.token_id, now) {
+            self.stats.stale_books += 1;
+            return Ok(());
+        }
+        if !self.books.may_signal(&book.token_id) {
+            self.stats.unconfirmed_books += 1;
+            return Ok(());
+        }
+        // An old or unconfirmed NO book vouches for nothing
+        let no = self.books.book(&market.no_token_id).filter(|_| {
+            self.books.is_fresh(&market.no_token_id, now)
+                && self.books.may_signal(&market.no_token_id)
+        });
+        let explanation = self.stack.explain(
+            market,
+            MarketBooks::new(book).with_no(no),
+            spot,
+            self.volatility.estimate(),
+            &self.momentum,
+            now,
+            self.ledger.balance(),
+            &self.positions,
+        );
+        if self.keep_explanations {
+            self.explanation = Some(explanation.clone());
+        }
+        if let Some(models) = &explanation.models {
+            if self.sanity.disagrees(models) {
+                self.stats.model_disagreements += 1;
+                record_model_disagreement(match self.sanity.mode() {
+                    DisagreementMode::Suppress => "suppressed",
+                    DisagreementMode::Annotate => "annotated",
+                });
+            }
+            if let Some(alert) = self.sanity.observe(&market.condition_id, models, now) {
+                tracing::warn!(
+                    event_code = %EventCode::ModelDisagreement,
+                    market_id = %market.condition_id,
+                    streak = alert.streak,
+                    since = %alert.since,
+                    gbm = %alert.latest.gbm,
+                    linear = %alert.latest.linear,
+                    mid = ?alert.latest.mid,
+                    open_price = %market.open_price,
+                    volatility = ?self.volatility.estimate(),
+                    "Fair value models keep disagreeing; check the strike and volatility inputs"
+                );
+                self.journal(
+                    "model_disagreement",
+                    serde_json::json!({
+                        "market_id": market.condition_id,
+                        "streak": alert.streak,
+                        "since": alert.since,
+                        "gbm": alert.latest.gbm,
+                        "linear": alert.latest.linear,
+                        "mid": alert.latest.mid,
+                    }),
+                );
+            }
+        }
+        if matches!(explanation.verdict, Verdict::ComplementRepriced) {
+            self.stats.complement_repriced += 1;
+            tracing::debug!(
+                market_id = %market.condition_id,
+                token_id = %book.token_id,
+                "NO book already repriced, YES book held stale until its next update"
+            );
+            self.books.mark_suspect(&book.token_id);
+            return Ok(());
+        }
+        let Some(signal) = explanation.signal else {
+            return Ok(());
+        };
+        self.stats.signals += 1;
+        let side = format!("{:?}", signal.side).to_lowercase();
+        let reason = format!("{:?}", signal.reason);
+        record_signal(&side, &reason, explanation.verdict.label());
+        for (flag, decision) in &signal.flags {
+            record_flag_evaluation(flag, decision.on);
+        }
+        if let Verdict::Filtered(why) = &explanation.verdict {
+            record_signal_rejected(why.code());
+            self.trail
+                .entry(market.condition_id.clone())
+                .or_default()
+                .push(Rejection {
+                    at: now,
+                    reason: why.code().to_string(),
+                    context: why.context(),
+                });
+            let previous = self
+                .rejections
+                .insert(market.condition_id.clone(), why.code());
+            if previous != Some(why.code()) {
+                self.journal(
+                    "signal_rejected",
+                    serde_json::json!({
+                        "market_id": market.condition_id,
+                        "signal_id": signal.id,
+                        "side": side,
+                        "reason": why.code(),
+                        "context": why.context(),
+                        "edge": signal.adjusted_edge,
+                    }),
+                );
+            }
+        }
+        // Follow traded and risk-blocked signals alike
+        if matches!(explanation.verdict, Verdict::Trade | Verdict::Blocked(_)) {
+            self.outcomes
+                .watch(&signal, book, explanation.verdict.label());
+        }
+
+        let cooldown = match explanation.verdict {
+            Verdict::Trade => self.cooldown.block(&self.asset, market, now),
+            _ => None,
+        };
+        let capped = match explanation.verdict {
+            Verdict::Trade => match self.rate.check(&self.asset, market, now) {
+                Err(error @ RiskError::RateCapExceeded { cap, .. }) => Some((cap, error)),
+                _ => None,
+            },
+            _ => None,
+        };
+        let order = match explanation.verdict {
+            Verdict::Trade if self.stats.halt.is_some() => {
+                tracing::info!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %market.condition_id,
+                    halt = %self.stats.halt.as_ref().map(|h| h.id.as_str()).unwrap_or_default(),
+                    "Order withheld, trading halted"
+                );
+                self.stats.rejected += 1;
+                return Ok(());
+            }
+            Verdict::Trade if self.draining => {
+                tracing::info!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %market.condition_id,
+                    "Order withheld, draining for a handoff"
+                );
+                self.stats.rejected += 1;
+                self.stats.drained += 1;
+                return Ok(());
+            }
+            Verdict::Trade if !self.schedule.allows_entry(DEFAULT_STRATEGY, now) => {
+                tracing::info!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %market.condition_id,
+                    "Order withheld, outside the trading schedule"
+                );
+                self.stats.rejected += 1;
+                self.stats.off_schedule += 1;
+                return Ok(());
+            }
+            Verdict::Trade if self.is_standby(now) => {
+                tracing::info!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %market.condition_id,
+                    "Order withheld, standby instance"
+                );
+                self.stats.rejected += 1;
+                self.stats.standby_withheld += 1;
+                record_order(&side, "standby");
+                self.journal(
+                    "order_withheld",
+                    serde_json::json!({
+                        "market_id": market.condition_id,
+                        "signal_id": signal.id,
+                        "side": side,
+                        "edge": signal.adjusted_edge,
+                    }),
+                );
+                return Ok(());
+            }
+            Verdict::Trade if cooldown.is_some() => {
+                let block = cooldown.expect("checked above");
+                tracing::info!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %market.condition_id,
+                    asset = %self.asset,
+                    %block,
+                    "Order withheld, asset cooling down after a loss"
+                );
+                self.stats.rejected += 1;
+                self.stats.cooled_down += 1;
+                record_signal_rejected(block.label());
+                self.trail
+                    .entry(market.condition_id.clone())
+                    .or_default()
+                    .push(Rejection {
+                        at: now,
+                        reason: block.label().to_string(),
+                        context: serde_json::Value::Null,
+                    });
+                let previous = self
+                    .rejections
+                    .insert(market.condition_id.clone(), block.label());
+                if previous != Some(block.label()) {
+                    self.journal(
+                        "signal_rejected",
+                        serde_json::json!({
+                            "market_id": market.condition_id,
+                            "signal_id": signal.id,
+                            "side": side,
+                            "reason": block.label(),
+                            "edge": signal.adjusted_edge,
+                        }),
+                    );
+                }
+                return Ok(());
+            }
+            Verdict::Trade if capped.is_some() => {
+                let (cap, error) = capped.expect("checked above");
+                self.stats.rejected += 1;
+                self.stats.rate_capped += 1;
+                record_rate_cap_hit(cap.label());
+                self.trail
+                    .entry(market.condition_id.clone())
+                    .or_default()
+                    .push(Rejection {
+                        at: now,
+                        reason: "rate_cap".to_string(),
+                        context: serde_json::json!({ "cap": cap }),
+                    });
+                // Once per market, not on every book update
+                if self.rate_capped.insert(market.condition_id.clone()) {
+                    tracing::warn!(
+                        event_code = %EventCode::RateCapHit,
+                        market_id = %market.condition_id,
+                        asset = %self.asset,
+                        cap = cap.label(),
+                        %error,
+                        "Order withheld by rate cap"
+                    );
+                    self.journal(
+                        "rate_cap_exceeded",
+                        serde_json::json!({
+                            "market_id": market.condition_id,
+                            "signal_id": signal.id,
+                            "asset": self.asset,
+                            "cap": cap,
+                            "error": error.to_string(),
+                        }),
+                    );
+                }
+                if cap.is_global() && self.rate.notify(now) {
+                    tracing::error!(
+                        event_code = %EventCode::DailyCapReached,
+                        asset = %self.asset,
+                        %error,
+                        "Global daily entry cap reached, entries stopped until UTC midnight; check for a feed or signal fault"
+                    );
+                }
+                return Ok(());
+            }
+            Verdict::Trade => explanation.order.expect("trade verdict carries an order"),
+            Verdict::Blocked(error) => {
+                tracing::info!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %market.condition_id,
+                    %error,
+                    "Order blocked by limits"
+                );
+                self.stats.rejected += 1;
+                return Ok(());
+            }
+            verdict => {
+                tracing::debug!(
+                    event_code = %EventCode::SignalRejected,
+                    market_id = %market.condition_id,
+                    ?verdict,
+                    "Signal filtered"
+                );
+                self.stats.rejected += 1;
+                return Ok(());
+            }
+        };
+        // Mid of the traded side at decision time, for realized spread
+        let mid = book.mid_price().map(|yes| match signal.side {
+            Side::Yes => yes,
+            Side::No => Decimal::ONE - yes,
+        });
+        let damping = explanation.damping;
+        if self.compete(now, &signal, &order, mid, damping) {
+            return Ok(());
+        }
+        self.enter(now, signal, order, mid, damping).await
+    }
+
+    /// Check `book` against its complement, once the update has been acted
+    /// on: the older book of a market whose mids disagree is held stale
+    /// until its next update, and a sell spread is traded as an entry
+    async fn check_consistency(
+        &mut self,
+        now: DateTime<Utc>,
+        book: &OrderBook,
+    ) -> anyhow::Result<()> {
+        let market = match self.markets.get(&book.token_id) {
+            Some(market) => market,
+            None => match self
+                .markets
+                .values()
+                .find(|m| m.no_token_id == book.token_id)
+            {
+                Some(market) => market,
+                None => return Ok(()),
+            },
+        };
+        // Which book is which is not known yet
+        if self.unoriented.contains(&market.condition_id) {
+            return Ok(());
+        }
+        let Some(check) = self.consistency.update(market, book) else {
+            return Ok(());
+        };
+        if let Some(token) = &check.suspect_token {
+            self.stats.inconsistent_books += 1;
+            self.books.mark_suspect(token);
+        }
+        match check.sell_signal {
+            Some(signal) => self.sell_spread(now, signal).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Sell both sides of `signal`'s market, withheld as an entry would be
+    ///
+    /// The pair is taken as split from collateral at 1 a share, so the
+    /// proceeds over 1 a share, less fees, are booked once both legs fill.
+    async fn sell_spread(
+        &mut self,
+        now: DateTime<Utc>,
+        mut signal: SellSpreadSignal,
+    ) -> anyhow::Result<()> {
+        let market_id = signal.market.condition_id.clone();
+        if self.entered.contains(&market_id)
+            || self.gap_until.is_some_and(|until| now < until)
+            || self.clock_settle_until.is_some_and(|until| now < until)
+        {
+            return Ok(());
+        }
+        self.stats.signals += 1;
+        record_signal("both", "sell_spread", "traded");
+        let withheld = if self.stats.halt.is_some() {
+            Some("trading halted")
+        } else if self.draining {
+            Some("draining for a handoff")
+        } else if !self.schedule.allows_entry(DEFAULT_STRATEGY, now) {
+            Some("outside the trading schedule")
+        } else if self.is_standby(now) {
+            Some("standby instance")
+        } else {
+            None
+        };
+        if let Some(why) = withheld {
+            tracing::info!(
+                event_code = %EventCode::OrderRejected,
+                market_id = %market_id,
+                "Sell spread withheld, {}",
+                why
+            );
+            self.stats.rejected += 1;
+            if self.draining {
+                self.stats.drained += 1;
+            } else if !self.schedule.allows_entry(DEFAULT_STRATEGY, now) {
+                self.stats.off_schedule += 1;
+            }
+            return Ok(());
+        }
+        // Each pair ties up a share of collateral until it is sold
+        let cap = round_size(self.ledger.balance() * self.stack.limits().max_position_pct);
+        signal.size = signal.size.min(cap);
+        if signal.size <= Decimal::ZERO {
+            self.stats.rejected += 1;
+            return Ok(());
+        }
+        self.entered.insert(market_id.clone());
+
+        let mut fills = vec![];
+        for order in signal.orders() {
+            let side = format!("{:?}", order.side).to_lowercase();
+            let order_id = match self.execution.submit_order(order).await {
+                Ok(order_id) => order_id,
+                Err(error) => {
+                    self.stats.submit_failures += 1;
+                    record_order(&side, "failed");
+                    tracing::warn!(
+                        event_code = %EventCode::OrderRejected,
+                        market_id = %market_id,
+                        signal_id = %signal.id,
+                        %error,
+                        "Sell spread leg failed"
+                    );
+                    return Ok(());
+                }
+            };
+            self.stats.orders += 1;
+            record_order(&side, "sell_spread");
+            let filled = self.execution.get_fills().await?;
+            match filled.into_iter().find(|f| f.order_id == order_id) {
+                Some(fill) => fills.push(fill),
+                None => {
+                    self.resting.insert(order_id, market_id.clone());
+                }
+            }
+        }
+        self.stats.fills += fills.len() as u64;
+        if fills.len() < 2 {
+            tracing::warn!(
+                market_id = %market_id,
+                signal_id = %signal.id,
+                filled = fills.len(),
+                "Sell spread leg did not fill at once, left resting"
+            );
+            return Ok(());
+        }
+        let pairs = fills.iter().map(|f| f.size).min().unwrap_or_default();
+        let proceeds: Decimal = fills.iter().map(|f| f.price * f.size).sum();
+        let fees: Decimal = fills.iter().map(|f| f.fee).sum();
+        let reference = signal.id.to_string();
+        self.book(
+            LedgerEntryKind::TradeSettlement,
+            proceeds - pairs,
+            &reference,
+            now,
+        );
+        self.book(LedgerEntryKind::Fee, -fees, &reference, now);
+        self.stats.sell_spreads += 1;
+        self.stats.realized_pnl += proceeds - pairs - fees;
+        self.update_drawdown(now);
+        tracing::info!(
+            event_code = %EventCode::SignalEmitted,
+            market_id = %market_id,
+            yes_bid = %signal.yes_bid,
+            no_bid = %signal.no_bid,
+            size = %pairs,
+            net_edge = %signal.net_edge,
+            "Sold both sides of the market"
+        );
+        self.journal(
+            "sell_spread",
+            serde_json::json!({
+                "market_id": market_id,
+                "signal_id": signal.id,
+                "yes_bid": signal.yes_bid,
+                "no_bid": signal.no_bid,
+                "size": pairs,
+                "proceeds": proceeds,
+                "fee": fees,
+                "net_edge": signal.net_edge,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Submit the entry `order` for `signal` and open its position on a
+    /// fill, once any camouflage delay is up
+    async fn enter(
+        &mut self,
+        now: DateTime<Utc>,
+        signal: Signal,
+        order: Order,
+        mid: Option<Decimal>,
+        damping: Option<Damping>,
+    ) -> anyhow::Result<()> {
+        let market = signal.market.clone();
+        tracing::info!(
+            event_code = %EventCode::SignalEmitted,
+            market_id = %market.condition_id,
+            signal = %signal,
+            raw_edge = %signal.raw_edge,
+            spread = %signal.spread,
+            round_trip_edge = %signal.round_trip_edge,
+            size = %order.size,
+            "Trading signal"
+        );
+        self.journal(
+            "signal_emitted",
+            serde_json::json!({
+                "market_id": market.condition_id,
+                "signal": signal,
+                "size": order.size,
+                "damping": damping,
+            }),
+        );
+
+        let plan = self.camouflage.plan(order.size);
+        if let Some(plan) = plan.clone().filter(|p| p.delay_ms > 0) {
+            // Taken now, so nothing else enters the market while it waits
+            self.entered.insert(market.condition_id.clone());
+            self.delayed.push(DelayedEntry {
+                due: now + Duration::milliseconds(plan.delay_ms as i64),
+                entry: HeldEntry {
+                    signal,
+                    order,
+                    mid,
+                    damping,
+                },
+                plan,
+            });
+            return Ok(());
+        }
+        self.submit_entry(now, signal, order, mid, damping, plan)
+            .await
+    }
+
+    /// Submit the entries whose camouflage delay is up, unless their market
+    /// can no longer be entered
+    async fn release_delayed(&mut self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        if !self.delayed.iter().any(|d| d.due <= now) {
+            return Ok(());
+        }
+        let (due, waiting) = std::mem::take(&mut self.delayed)
+            .into_iter()
+            .partition(|d| d.due <= now);
+        self.delayed = waiting;
+        for DelayedEntry { entry, plan, .. } in due {
+            let HeldEntry {
+                signal,
+                order,
+                mid,
+                damping,
+            } = entry;
+            let market_id = signal.market.condition_id.clone();
+            self.entered.remove(&market_id);
+            let active = self.markets.values().any(|m| m.condition_id == market_id);
+            if !active
+                || self.stats.halt.is_some()
+                || self.draining
+                || !self.schedule.allows_entry(DEFAULT_STRATEGY, now)
+                || self.is_standby(now)
+            {
+                tracing::info!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %market_id,
+                    "Delayed entry withheld, no longer enterable"
+                );
+                self.stats.rejected += 1;
+                self.journal_camouflage(&signal, &plan, &[], &[]);
+                continue;
+            }
+            self.submit_entry(now, signal, order, mid, damping, Some(plan))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Submit `order`, or the clips camouflage cut it into, one after the
+    /// other, stopping at the first not submitted
+    async fn submit_entry(
+        &mut self,
+        now: DateTime<Utc>,
+        signal: Signal,
+        mut order: Order,
+        mid: Option<Decimal>,
+        damping: Option<Damping>,
+        plan: Option<CamouflagePlan>,
+    ) -> anyhow::Result<()> {
+        let mut clips = match &plan {
+            Some(plan) => plan.clips.clone(),
+            None => vec![order.size],
+        };
+        // Each clip opens a position; without a slot for each it goes whole
+        if clips.len() > 1 && self.free_slots() < clips.len() {
+            clips = vec![clips.iter().sum()];
+        }
+        let mut client_order_ids = vec![];
+        for (i, clip) in clips.iter().enumerate() {
+            order.size = *clip;
+            let submitted = self
+                .submit_clip(now, &signal, order.clone(), mid, damping, i == 0)
+                .await?;
+            match submitted {
+                Some(client_order_id) => client_order_ids.push(client_order_id),
+                None => break,
+            }
+        }
+        if let Some(plan) = &plan {
+            self.journal_camouflage(&signal, plan, &clips, &client_order_ids);
+        }
+        Ok(())
+    }
+
+    /// Journal what camouflage drew for `signal`'s entry, the `clips` it
+    /// went out as and the client order IDs of those submitted
+    fn journal_camouflage(
+        &mut self,
+        signal: &Signal,
+        plan: &CamouflagePlan,
+        clips: &[Decimal],
+        client_order_ids: &[String],
+    ) {
+        self.journal(
+            "camouflage_applied",
+            serde_json::json!({
+                "market_id": signal.market.condition_id,
+                "signal_id": signal.id,
+                "delay_ms": plan.delay_ms,
+                "size": plan.size,
+                "size_factor": plan.size_factor,
+                "jittered_size": plan.jittered_size(),
+                "drawn_clips": plan.clips,
+                "clips": clips,
+                "client_order_ids": client_order_ids,
+            }),
+        );
+    }
+
+    /// Submit one order of `signal`'s entry and open its position on a
+    /// fill; its client order ID if it was submitted. The `first` order
+    /// takes the market and counts against the rate caps.
+    async fn submit_clip(
+        &mut self,
+        now: DateTime<Utc>,
+        signal: &Signal,
+        mut order: Order,
+        mid: Option<Decimal>,
+        damping: Option<Damping>,
+        first: bool,
+    ) -> anyhow::Result<Option<String>> {
+        let market = signal.market.clone();
+        let side = format!("{:?}", signal.side).to_lowercase();
+        if !self.breaker.allow(now) {
+            tracing::info!(
+                event_code = %EventCode::OrderRejected,
+                market_id = %market.condition_id,
+                circuit = %self.breaker.state(),
+                "Order suppressed, execution circuit open"
+            );
+            self.stats.suppressed += 1;
+            record_order(&side, "suppressed");
+            self.journal(
+                "order_suppressed",
+                serde_json::json!({
+                    "market_id": market.condition_id,
+                    "signal_id": signal.id,
+                    "side": side,
+                    "price": order.price,
+                    "size": order.size,
+                    "circuit": self.breaker.state().as_str(),
+                }),
+            );
+            self.report_circuit();
+            return Ok(None);
+        }
+        let attempt = self
+            .attempts
+            .entry((market.condition_id.clone(), signal.id))
+            .or_default();
+        *attempt += 1;
+        order.client_order_id = Some(ids::client_order_id(signal.id, *attempt));
+        if let Some(intents) = &self.intents {
+            let logged = OrderIntent::new(&market, &order, now)
+                .and_then(|intent| intents.record_intent(&intent));
+            if let Err(error) = logged {
+                // Never submit what a crash could leave untracked
+                tracing::error!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %market.condition_id,
+                    %error,
+                    "Order not submitted, intent log write failed"
+                );
+                self.stats.rejected += 1;
+                return Ok(None);
+            }
+        }
+        self.entered.insert(market.condition_id.clone());
+        if first {
+            self.rate.record(&self.asset, &market, now);
+        }
+        self.journal(
+            "order_submitted",
+            serde_json::json!({
+                "market_id": market.condition_id,
+                "signal_id": signal.id,
+                "client_order_id": order.client_order_id,
+                "strategy": DEFAULT_STRATEGY,
+                "side": side,
+                "price": order.price,
+                "size": order.size,
+                "mid": mid,
+                "fair_value": signal.fair_value,
+                "damping": damping.map(|d| d.factor),
+                "flags": signal.flags,
+            }),
+        );
+        let client_id = order.client_order_id.clone().unwrap_or_default();
+        let order_price = order.price;
+        let order_id = match self.execution.submit_order(order).await {
+            Ok(order_id) => {
+                if self.breaker.record_success() {
+                    tracing::info!(
+                        event_code = %EventCode::CircuitClosed,
+                        circuit = EXECUTION_HEALTH_COMPONENT,
+                        "Probe order accepted, execution circuit closed"
+                    );
+                    self.journal("circuit_closed", serde_json::json!({}));
+                    self.report_circuit();
+                }
+                order_id
+            }
+            Err(error) => {
+                let mut outcome = IntentOutcome::new(&client_id, IntentStatus::Rejected, now);
+                outcome.error = Some(error.to_string());
+                self.record_outcome(&outcome);
+                self.on_submit_failure(now, signal, &side, error);
+                return Ok(None);
+            }
+        };
+        self.stats.orders += 1;
+        record_order(&side, "submitted");
+
+        let fills = self.execution.get_fills().await?;
+        let fill = fills.iter().find(|f| f.order_id == order_id);
+        let mut outcome = IntentOutcome::new(&client_id, IntentStatus::Unfilled, now);
+        outcome.order_id = Some(order_id);
+        if let Some(fill) = fill {
+            outcome.status = IntentStatus::Filled;
+            outcome.filled = fill.size;
+        } else {
+            self.resting
+                .insert(order_id, signal.market.condition_id.clone());
+        }
+        self.record_outcome(&outcome);
+        if let Some(fill) = fill {
+            self.stats.fills += 1;
+            record_fill(&side);
+            let position = self.positions.open(signal, fill);
+            let rejections = self
+                .trail
+                .get(&signal.market.condition_id)
+                .cloned()
+                .unwrap_or_default();
+            let expected_value = self
+                .entries
+                .entry(position.id)
+                .or_insert_with(|| EntryFeatures::new(signal, now, rejections))
+                .expected_value(&position);
+            tracing::info!(
+                event_code = %EventCode::PositionOpened,
+                market_id = %signal.market.condition_id,
+                order_id = %order_id,
+                side = %side,
+                price = %fill.price,
+                size = %fill.size,
+                edge = %signal.adjusted_edge,
+                "Opened position"
+            );
+            if let Some(group_id) = &signal.market.group_id {
+                if let Some(group) = self.positions.exposure_for_group(group_id) {
+                    tracing::info!(group = %group, "Group exposure");
+                }
+            }
+            self.journal(
+                "position_opened",
+                serde_json::json!({
+                    "market_id": signal.market.condition_id,
+                    "order_id": order_id,
+                    "side": side,
+                    "price": fill.price,
+                    "size": fill.size,
+                    "fee": fill.fee,
+                    "simulated": fill.simulated,
+                    "expected_value_usd": expected_value,
+                }),
+            );
+            self.shadow.watch(signal, order_price, fill);
+        }
+        Ok(Some(client_id))
+    }
+
+    /// Position slots not taken by open positions, resting entries or
+    /// entries waiting out their camouflage delay
+    fn free_slots(&self) -> usize {
+        self.stack
+            .max_positions()
+            .saturating_sub(self.positions.open_count() + self.resting.len() + self.delayed.len())
+    }
+
+    /// What open positions cost, fees included
+    fn held(&self) -> Decimal {
+        self.positions
+            .open_positions
+            .values()
+            .map(|p| p.entry_price * p.size + p.entry_fee)
+            .sum()
+    }
+
+    /// Hold `signal` to compete for capital when more markets could fire
+    /// than there are free slots; returns whether it was held
+    fn compete(
+        &mut self,
+        now: DateTime<Utc>,
+        signal: &Signal,
+        order: &Order,
+        mid: Option<Decimal>,
+        damping: Option<Damping>,
+    ) -> bool {
+        let competitors = self
+            .markets
+            .values()
+            .filter(|m| m.close_time > now && !self.entered.contains(&m.condition_id))
+            .count();
+        if !self.allocator.is_contended(self.free_slots(), competitors) {
+            return false;
+        }
+        let (signals, wins) = self.outcomes.bucket_record(&signal.market);
+        let score = self.allocator.score(signal, order.size, now, signals, wins);
+        tracing::debug!(
+            market_id = %signal.market.condition_id,
+            score = %score.total,
+            competitors,
+            "Signal held to compete for capital"
+        );
+        self.allocator.offer(Candidate {
+            market_id: signal.market.condition_id.clone(),
+            signal_id: signal.id,
+            score,
+            cost: order.price * order.size,
+            offered_at: now,
+            entry: HeldEntry {
+                signal: signal.clone(),
+                order: order.clone(),
+                mid,
+                damping,
+            },
+        });
+        true
+    }
+
+    /// Once the held signals' window closes, rank them and enter the best
+    /// the free slots and cash allow
+    async fn allocate(&mut self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        if !self.allocator.is_due(now) {
+            return Ok(());
+        }
+        let cash = self.ledger.balance() - self.held();
+        let allocation = self.allocator.allocate(self.free_slots(), cash);
+        self.stats.allocations += 1;
+        self.stats.outranked += allocation.skipped.len() as u64;
+        tracing::info!(
+            candidates = allocation.ranking.len(),
+            selected = allocation.selected.len(),
+            best = %allocation.ranking[0].market_id,
+            "Signals ranked for capital"
+        );
+        self.journal(
+            "allocation_ranked",
+            serde_json::json!({ "ranking": allocation.ranking }),
+        );
+        for (candidate, reason) in &allocation.skipped {
+            record_signal_rejected("outranked");
+            self.trail
+                .entry(candidate.market_id.clone())
+                .or_default()
+                .push(Rejection {
+                    at: now,
+                    reason: "outranked".to_string(),
+                    context: serde_json::json!({
+                        "score": candidate.score.total,
+                        "skipped": reason.as_str(),
+                    }),
+                });
+        }
+        for candidate in allocation.selected {
+            // Held for a moment; the market may have closed or been entered
+            let active = self
+                .markets
+                .values()
+                .any(|m| m.condition_id == candidate.market_id);
+            if !active
+                || self.entered.contains(&candidate.market_id)
+                || self.stats.halt.is_some()
+                || self.draining
+                || !self.schedule.allows_entry(DEFAULT_STRATEGY, now)
+                || self.is_standby(now)
+            {
+                tracing::info!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %candidate.market_id,
+                    "Ranked entry withheld, no longer enterable"
+                );
+                self.stats.rejected += 1;
+                continue;
+            }
+            let HeldEntry {
+                signal,
+                order,
+                mid,
+                damping,
+            } = candidate.entry;
+            self.enter(now, signal, order, mid, damping).await?;
+        }
+        Ok(())
+    }
+
+    /// Request an exit for each position held in the market of `book`
+    /// whose supporting depth just evaporated
+    ///
+    /// This runs on every book update, ahead of any spot-based check, so
+    /// the exit goes out before spot prints the move.
+    fn check_book_shock(&mut self, now: DateTime<Utc>, book: &OrderBook) {
+        if !self.shocks.is_enabled() {
+            return;
+        }
+        // Both sides are read off the YES book
+        let Some(market) = self
+            .markets
+            .get(&book.token_id)
+            .filter(|m| m.yes_token_id == book.token_id)
+        else {
+            return;
+        };
+        let held: Vec<Position> = self
+            .positions
+            .open_positions
+            .values()
+            .filter(|p| p.market.condition_id == market.condition_id)
+            .cloned()
+            .collect();
+        let close_time = market.close_time;
+        for position in held {
+            if !self.flag_on(position.id, flags::BOOK_SHOCK_EXIT) {
+                continue;
+            }
+            let key = position.id.to_string();
+            let Some(shock) = self
+                .shocks
+                .observe(&key, book, position.side, close_time, now)
+            else {
+                continue;
+            };
+            tracing::warn!(
+                event_code = %EventCode::BookShock,
+                market_id = %position.market.condition_id,
+                position_id = %position.id,
+                %shock,
+                "Book supporting a held position evaporated, exiting"
+            );
+            self.journal(
+                "book_shock",
+                serde_json::json!({
+                    "market_id": position.market.condition_id,
+                    "position_id": position.id,
+                    "side": shock.side,
+                    "peak_depth": shock.peak_depth,
+                    "depth": shock.depth,
+                    "depth_drop": shock.depth_drop,
+                    "depth_rate": shock.depth_rate,
+                    "imbalance_swing": shock.imbalance_swing,
+                }),
+            );
+            self.exits.request(ExitRequest {
+                position_id: position.id,
+                reason: ExitReason::BookShock,
+                at: now,
+                fraction: Decimal::ONE,
+                detail: shock.to_string(),
+            });
+        }
+    }
+
+    /// Mark each position held in the market of `book`, its YES book, to
+    /// the bid of its token
+    fn mark_positions(&mut self, book: &OrderBook) {
+        let held: Vec<(Uuid, Side)> = self
+            .positions
+            .open_positions
+            .values()
+            .filter(|p| p.market.yes_token_id == book.token_id)
+            .map(|p| (p.id, p.side))
+            .collect();
+        for (id, side) in held {
+            let bid = match side {
+                Side::Yes => book.best_bid(),
+                Side::No => book.best_ask().map(|ask| Decimal::ONE - ask),
+            };
+            if let Some(bid) = bid {
+                self.positions.mark(id, bid);
+            }
+        }
+    }
+
+    /// Request the due rung of the exit ladder for each position held in
+    /// the market of `book` whose bid shows the rung's profit
+    ///
+    /// Runs after the shock check, so a full exit requested on the same
+    /// book takes precedence.
+    fn check_exit_ladder(&mut self, now: DateTime<Utc>, book: &OrderBook) {
+        if !self.ladder.is_enabled() {
+            return;
+        }
+        let Some(market) = self
+            .markets
+            .get(&book.token_id)
+            .filter(|m| m.yes_token_id == book.token_id)
+        else {
+            return;
+        };
+        let held: Vec<Position> = self
+            .positions
+            .open_positions
+            .values()
+            .filter(|p| p.market.condition_id == market.condition_id)
+            .cloned()
+            .collect();
+        let close_time = market.close_time;
+        for position in held {
+            if !self.flag_on(position.id, flags::EXIT_LADDER) {
+                continue;
+            }
+            let bid = match position.side {
+                Side::Yes => book.best_bid(),
+                Side::No => book.best_ask().map(|ask| Decimal::ONE - ask),
+            };
+            let Some(bid) = bid else {
+                continue;
+            };
+            let Some(rung) =
+                self.ladder
+                    .check(position.id, position.entry_price, bid, close_time, now)
+            else {
+                continue;
+            };
+            let detail = format!(
+                "T-{}, bid {} against entry {}, selling {}",
+                rung.secs_before_close, bid, position.entry_price, rung.fraction
+            );
+            self.exits.request(ExitRequest {
+                position_id: position.id,
+                reason: ExitReason::Ladder,
+                at: now,
+                fraction: rung.fraction,
+                detail,
+            });
+        }
+    }
+
+    /// Sell every position with an exit requested into `book`, the YES book
+    /// of its market
+    async fn process_exits(&mut self, now: DateTime<Utc>, book: &OrderBook) -> anyhow::Result<()> {
+        if self.exits.is_empty() {
+            return Ok(());
+        }
+        for request in self.exits.drain() {
+            let Some(position) = self
+                .positions
+                .open_positions
+                .get(&request.position_id)
+                .cloned()
+            else {
+                continue;
+            };
+            if position.market.yes_token_id != book.token_id {
+                // Another market's; wait for its own book
+                self.exits.request(request);
+                continue;
+            }
+            self.exit_position(now, position, request, book).await?;
+        }
+        Ok(())
+    }
+
+    /// Close `position`, or the requested fraction of it, at the touch of
+    /// `book`
+    ///
+    /// A YES holder sells into the YES bid; a NO holder into the NO bid,
+    /// one minus the YES ask. Exits reduce risk, so they are not held back
+    /// by halts or the execution circuit, only by standby.
+    async fn exit_position(
+        &mut self,
+        now: DateTime<Utc>,
+        position: Position,
+        request: ExitRequest,
+        book: &OrderBook,
+    ) -> anyhow::Result<()> {
+        let market_id = position.market.condition_id.clone();
+        if self.is_standby(now) {
+            tracing::info!(
+                event_code = %EventCode::OrderRejected,
+                market_id = %market_id,
+                reason = %request.reason,
+                "Exit withheld, standby instance"
+            );
+            return Ok(());
+        }
+        let side = format!("{:?}", position.side).to_lowercase();
+        let (token_id, price) = match position.side {
+            Side::Yes => (position.market.yes_token_id.clone(), book.best_bid()),
+            Side::No => (
+                position.market.no_token_id.clone(),
+                book.best_ask().map(|ask| Decimal::ONE - ask),
+            ),
+        };
+        let Some(price) = price else {
+            tracing::warn!(
+                market_id = %market_id,
+                position_id = %position.id,
+                reason = %request.reason,
+                "No bid to exit into, holding the position"
+            );
+            return Ok(());
+        };
+        let (size, client_order_id) = if request.fraction < Decimal::ONE {
+            (
+                round_size(position.size * request.fraction),
+                ids::ladder_order_id(position.id, request.at),
+            )
+        } else {
+            (position.size, ids::exit_order_id(position.id))
+        };
+        if size <= Decimal::ZERO {
+            return Ok(());
+        }
+        let order = Order {
+            token_id,
+            side: position.side,
+            price,
+            size,
+            order_type: OrderType::Market,
+            action: OrderAction::Sell,
+            client_order_id: Some(client_order_id),
+        };
+        let order_id = match self.execution.submit_order(order).await {
+            Ok(order_id) => order_id,
+            Err(error) => {
+                tracing::warn!(
+                    event_code = %EventCode::OrderRejected,
+                    market_id = %market_id,
+                    position_id = %position.id,
+                    reason = %request.reason,
+                    %error,
+                    "Exit order failed, holding the position"
+                );
+                return Ok(());
+            }
+        };
+        self.stats.orders += 1;
+        record_order(&side, "exit");
+
+        let fills = self.execution.get_fills().await?;
+        let Some(fill) = fills.iter().find(|f| f.order_id == order_id) else {
+            self.resting.insert(order_id, market_id);
+            return Ok(());
+        };
+        let Some(closed) = self.positions.close(position.id, fill) else {
+            return Ok(());
+        };
+        let remaining = self
+            .positions
+            .open_positions
+            .get(&position.id)
+            .map_or(Decimal::ZERO, |p| p.size);
+        // The rest of a partial exit keeps its shock watch and features
+        let entry = if remaining.is_zero() {
+            self.shocks.forget(&position.id.to_string());
+            self.ladder.forget(&position.id);
+            self.entries.remove(&position.id)
+        } else {
+            self.entries.get(&position.id).cloned()
+        };
+        self.stats.fills += 1;
+        self.stats.exits += 1;
+        record_fill(&side);
+        record_exit(request.reason.label());
+        let pnl = closed.realized_pnl;
+        self.tape.push(TapeRow::new(&closed, entry));
+        self.book_closed(&closed);
+        self.stats.realized_pnl += pnl;
+        self.update_drawdown(now);
+        tracing::info!(
+            event_code = %EventCode::PositionExited,
+            market_id = %market_id,
+            order_id = %order_id,
+            side = %side,
+            reason = %request.reason,
+            price = %fill.price,
+            size = %fill.size,
+            remaining = %remaining,
+            pnl = %pnl,
+            detail = %request.detail,
+            "Closed position before settlement"
+        );
+        self.journal(
+            "position_exited",
+            serde_json::json!({
+                "market_id": market_id,
+                "position_id": position.id,
+                "order_id": order_id,
+                "side": side,
+                "reason": request.reason,
+                "price": fill.price,
+                "size": fill.size,
+                "fee": fill.fee,
+                "pnl": pnl,
+                "remaining": remaining,
+                "requested_at": request.at,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Append a submission outcome to the intent log, if one is kept
+    fn record_outcome(&self, outcome: &IntentOutcome) {
+        let Some(intents) = &self.intents else {
+            return;
+        };
+        if let Err(e) = intents.record_outcome(outcome) {
+            tracing::error!(
+                error = %e,
+                client_order_id = %outcome.client_order_id,
+                "Failed to log order outcome; it will be reconciled on restart"
+            );
+        }
+    }
+
+    /// Reconcile intents a previous session logged without an outcome
+    ///
+    /// Each is looked up on the execution engine by client order id. A fill
+    /// reopens its position and marks the market entered; an order the
+    /// engine does not know is closed out as not found. Every repair is
+    /// journaled. Intents whose lookup fails stay pending for next time.
+    pub async fn recover_intents(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<IntentOutcome>> {
+        let Some(intents) = &self.intents else {
+            return Ok(vec![]);
+        };
+        let pending = intents.pending()?;
+        let mut repaired = vec![];
+        for intent in pending {
+            let found = match self.execution.find_order(&intent.client_order_id).await {
+                Ok(found) => found,
+                Err(error) => {
+                    tracing::warn!(
+                        client_order_id = %intent.client_order_id,
+                        %error,
+                        "Order lookup failed, intent left pending"
+                    );
+                    continue;
+                }
+            };
+            let mut outcome =
+                IntentOutcome::new(&intent.client_order_id, IntentStatus::NotFound, now);
+            outcome.recovered = true;
+            if let Some(fill) = &found {
+                outcome.status = IntentStatus::Filled;
+                outcome.order_id = Some(fill.order_id);
+                outcome.filled = fill.size;
+                self.entered.insert(intent.market.condition_id.clone());
+                self.positions.apply_fill(&intent.market, fill);
+                self.stats.fills += 1;
+            }
+            self.record_outcome(&outcome);
+            tracing::warn!(
+                event_code = %EventCode::IntentRepaired,
+                market_id = %intent.market.condition_id,
+                client_order_id = %intent.client_order_id,
+                status = ?outcome.status,
+                filled = %outcome.filled,
+                "Repaired order left in flight by the previous session"
+            );
+            self.journal(
+                "intent_repaired",
+                serde_json::json!({
+                    "market_id": intent.market.condition_id,
+                    "client_order_id": intent.client_order_id,
+                    "status": outcome.status,
+                    "order_id": outcome.order_id,
+                    "side": intent.order.side,
+                    "price": found.as_ref().map(|f| f.price),
+                    "size": outcome.filled,
+                    "fee": found.as_ref().map(|f| f.fee),
+                }),
+            );
+            repaired.push(outcome);
+        }
+        Ok(repaired)
+    }
+
+    /// Count a failed submission against the execution circuit
+    ///
+    /// The market is left open for another entry; while the circuit is
+    /// open that entry is suppressed rather than submitted.
+    fn on_submit_failure(
+        &mut self,
+        now: DateTime<Utc>,
+        signal: &Signal,
+        side: &str,
+        error: anyhow::Error,
+    ) {
+        let market_id = &signal.market.condition_id;
+        self.entered.remove(market_id);
+        self.stats.submit_failures += 1;
+        record_order(side, "failed");
+        tracing::warn!(
+            event_code = %EventCode::OrderRejected,
+            market_id = %market_id,
+            %error,
+            "Order submission failed"
+        );
+        self.journal(
+            "order_failed",
+            serde_json::json!({
+                "market_id": market_id,
+                "signal_id": signal.id,
+                "error": error.to_string(),
+            }),
+        );
+        if !self.breaker.record_failure(&error, now) {
+            return;
+        }
+        self.stats.circuit_trips += 1;
+        tracing::error!(
+            event_code = %EventCode::CircuitOpened,
+            circuit = EXECUTION_HEALTH_COMPONENT,
+            state = %self.breaker.state(),
+            failures = self.breaker.failures(),
+            fatal = error.is_fatal(),
+            %error,
+            "Execution circuit opened, orders suppressed"
+        );
+        self.journal(
+            "circuit_opened",
+            serde_json::json!({
+                "failures": self.breaker.failures(),
+                "error": error.to_string(),
+            }),
+        );
+        self.report_circuit();
+    }
+
+    /// Whether orders are withheld at `now` because another instance leads
+    fn is_standby(&self, now: DateTime<Utc>) -> bool {
+        self.leadership
+            .as_ref()
+            .is_some_and(|leadership| !leadership.may_submit(now))
+    }
+
+    /// Renew or contend for the leader lease when due
+    ///
+    /// Losing the lease withholds orders at once and cancels those still
+    /// resting; both transitions are logged and journaled.
+    pub async fn check_leadership(&mut self, now: DateTime<Utc>) {
+        let Some(leadership) = &mut self.leadership else {
+            return;
+        };
+        if !leadership.due(now) {
+            return;
+        }
+        let change = leadership.poll(now).await;
+        let role = leadership.role();
+        let instance = leadership.instance().to_string();
+        let lease = leadership.lease().cloned();
+        let age = lease.as_ref().map(|l| l.age(now).num_seconds());
+        let holder = lease.as_ref().map_or("nobody", |l| l.holder.as_str());
+        self.stats.role = Some(role);
+        self.stats.lease_age_secs = age;
+        set_leader_state(role == Role::Leader, age.unwrap_or_default() as f64);
+        if let Some(health) = &self.health {
+            let age = age.unwrap_or_default();
+            let (state, reason) = match role {
+                Role::Leader => (HealthState::Healthy, format!("leader, lease age {}s", age)),
+                Role::Standby => (
+                    HealthState::Degraded,
+                    format!("standby, lease held by {} for {}s", holder, age),
+                ),
+            };
+            health.set(LEADERSHIP_HEALTH_COMPONENT, state, Some(reason));
+        }
+        match change {
+            Some(Role::Leader) => {
+                self.stats.promotions += 1;
+                tracing::warn!(
+                    event_code = %EventCode::LeaderPromoted,
+                    %instance,
+                    "Took the leader lease, submitting orders"
+                );
+                self.journal(
+                    "leader_promoted",
+                    serde_json::json!({
+                        "instance": instance,
+                        "expires_at": lease.as_ref().map(|l| l.expires_at),
+                    }),
+                );
+            }
+            Some(Role::Standby) => {
+                self.stats.demotions += 1;
+                tracing::error!(
+                    event_code = %EventCode::LeaderDemoted,
+                    %instance,
+                    %holder,
+                    resting = self.resting.len(),
+                    "Lost the leader lease, withholding orders"
+                );
+                let holder = holder.to_string();
+                let cancelled = self.cancel_resting().await;
+                self.journal(
+                    "leader_demoted",
+                    serde_json::json!({
+                        "instance": instance,
+                        "holder": holder,
+                        "cancelled": cancelled,
+                    }),
+                );
+            }
+            None => {}
+        }
+    }
+
+    /// Cancel every order still resting; returns how many were cancelled
+    async fn cancel_resting(&mut self) -> usize {
+        let mut resting: Vec<(OrderId, String)> = self.resting.drain().collect();
+        resting.sort_by(|a, b| (&a.1, a.0).cmp(&(&b.1, b.0)));
+        let mut cancelled = 0;
+        for (order_id, market_id) in resting {
+            match self.execution.cancel_order(order_id).await {
+                Ok(()) => {
+                    cancelled += 1;
+                    tracing::info!(
+                        event_code = %EventCode::OrderCancelled,
+                        %market_id,
+                        %order_id,
+                        "Cancelled resting order"
+                    );
+                    self.journal(
+                        "order_cancelled",
+                        serde_json::json!({
+                            "market_id": market_id,
+                            "order_id": order_id,
+                            "reason": "demoted",
+                        }),
+                    );
+                }
+                Err(error) => {
+                    tracing::error!(
+                        %market_id,
+                        %order_id,
+                        %error,
+                        "Could not cancel resting order"
+                    );
+                }
+            }
+        }
+        cancelled
+    }
+
+    /// Give up the leader lease so a standby takes over without waiting
+    /// for it to lapse
+    pub async fn release_leadership(&mut self) {
+        if let Some(leadership) = &mut self.leadership {
+            if let Err(error) = leadership.release().await {
+                tracing::warn!(%error, "Could not release the leader lease");
+            }
+            self.stats.role = Some(Role::Standby);
+        }
+    }
+
+    /// Leader election state, if this engine takes part in one
+    pub fn leadership(&self) -> Option<&Leadership> {
+        self.leadership.as_ref()
+    }
+
+    /// Publish the execution circuit state to metrics and health
+    fn report_circuit(&self) {
+        let state = self.breaker.state();
+        set_circuit_state(EXECUTION_HEALTH_COMPONENT, state.severity());
+        if let Some(health) = &self.health {
+            let health_state = match state {
+                BreakerState::Closed => HealthState::Healthy,
+                BreakerState::HalfOpen => HealthState::Degraded,
+                BreakerState::Open { .. } => HealthState::Unhealthy,
+            };
+            let reason = self
+                .breaker
+                .last_error()
+                .filter(|_| state != BreakerState::Closed)
+                .map(|error| format!("circuit {}: {}", state, error));
+            health.set(EXECUTION_HEALTH_COMPONENT, health_state, reason);
+        }
+    }
+
+    fn settle(&mut self, now: DateTime<Utc>, market: &Market) {
+        self.markets.remove(&market.yes_token_id);
+        self.allocator.withdraw(&market.condition_id);
+        self.entered.remove(&market.condition_id);
+        self.unoriented.remove(&market.condition_id);
+        self.rejections.remove(&market.condition_id);
+        self.rate_capped.remove(&market.condition_id);
+        self.faulted_books.remove(&market.yes_token_id);
+        self.faulted_books.remove(&market.no_token_id);
+        self.sanity.forget(&market.condition_id);
+        self.trail.remove(&market.condition_id);
+        self.resting.retain(|_, id| *id != market.condition_id);
+        self.attempts
+            .retain(|(id, _), _| *id != market.condition_id);
+        self.booked.remove(&market.condition_id);
+        self.books.forget(&market.yes_token_id);
+        self.books.forget(&market.no_token_id);
+        let marks = self.outcomes.take_marks(&market.condition_id);
+        let Some(spot) = self.spot else {
+            return;
+        };
+        let winner = resolution(market, spot);
+        let settled = self.positions.settle(&market.condition_id, winner, now);
+        for closed in &settled {
+            self.shocks.forget(&closed.position.id.to_string());
+            self.ladder.forget(&closed.position.id);
+            let entry = self.entries.remove(&closed.position.id);
+            let row = TapeRow::new(closed, entry);
+            // Entry on the event clock; paper fills carry the wall clock
+            let held_from = row.entry.as_ref().map_or(closed.position.entry_time, |e| {
+                market.close_time - Duration::seconds(e.secs_to_close)
+            });
+            if let Some(attribution) = PositionAttribution::new(&row, held_from, &marks) {
+                self.stats.attribution.add(&attribution);
+                self.attribution.push(attribution);
+            }
+            self.tape.push(row);
+        }
+        // Only positions have anything to correct
+        let held = !settled.is_empty()
+            && self.resolutions.hold(PendingResolution {
+                market: market.clone(),
+                winner,
+                settled_at: now,
+                positions: settled.clone(),
+            });
+        let status = if held {
+            ResolutionStatus::Provisional
+        } else {
+            ResolutionStatus::Unconfirmed
+        };
+        self.settlements.push(Settlement {
+            market_id: market.condition_id.clone(),
+            winner,
+            at: now,
+            status,
+        });
+        self.publish_resolutions();
+        let pnl: Decimal = settled.iter().map(|c| c.realized_pnl).sum();
+        for closed in &settled {
+            self.book_closed(closed);
+        }
+        self.stats.realized_pnl += pnl;
+        self.update_drawdown(now);
+        self.stats.markets_settled += 1;
+        tracing::info!(
+            event_code = %EventCode::MarketSettled,
+            market_id = %market.condition_id,
+            ?winner,
+            %status,
+            positions = settled.len(),
+            pnl = %pnl,
+            "Market settled"
+        );
+        self.journal(
+            "market_settled",
+            serde_json::json!({
+                "market_id": market.condition_id,
+                "winner": winner,
+                "status": status,
+                "positions": settled.len(),
+                "pnl": pnl,
+            }),
+        );
+        if !settled.is_empty() {
+            self.update_cooldown(now, market, pnl);
+        }
+    }
+
+    /// Markets settled provisionally, awaiting their official resolution
+    pub fn pending_resolutions(&self) -> Vec<Market> {
+        self.resolutions
+            .pending()
+            .map(|p| p.market.clone())
+            .collect()
+    }
+
+    /// Finalize the provisional settlement of `market_id` on its official
+    /// outcome
+    ///
+    /// An outcome that differs from the one settled on rebooks every
+    /// position of the settlement at the official payout, in the tracker,
+    /// bankroll, trade tape and attribution. The loss cooldown keeps
+    /// whatever the provisional outcome started.
+    pub fn resolve(&mut self, now: DateTime<Utc>, market_id: &str, official: Side) {
+        let Some(pending) = self.resolutions.take(market_id) else {
+            return;
+        };
+        if official == pending.winner {
+            self.stats.resolutions_confirmed += 1;
+            self.finish_resolution(
+                &pending,
+                ResolutionStatus::Confirmed,
+                Some(official),
+                Decimal::ZERO,
+            );
+            return;
+        }
+        let mut delta = Decimal::ZERO;
+        for settled in &pending.positions {
+            let corrected = self.positions.resettle(settled, official);
+            delta += corrected.realized_pnl - settled.realized_pnl;
+            let id = settled.position.id.to_string();
+            let Some(row) = self.tape.iter_mut().find(|r| r.position_id == id) else {
+                continue;
+            };
+            *row = TapeRow::new(&corrected, row.entry.take());
+            if let Some(attribution) = self.attribution.iter_mut().find(|a| a.position_id == id) {
+                self.stats.attribution.remove(attribution);
+                attribution.resettle(row);
+                self.stats.attribution.add(attribution);
+            }
+        }
+        if !delta.is_zero() {
+            let reference = format!("resettled {}", market_id);
+            self.book(LedgerEntryKind::TradeSettlement, delta, &reference, now);
+        }
+        self.stats.realized_pnl += delta;
+        self.update_drawdown(now);
+        self.stats.resolutions_corrected += 1;
+        tracing::error!(
+            event_code = %EventCode::ResolutionCorrected,
+            market_id,
+            provisional = ?pending.winner,
+            ?official,
+            positions = pending.positions.len(),
+            pnl_delta = %delta,
+            "Official resolution contradicts the provisional settlement, P&L rebooked"
+        );
+        self.finish_resolution(&pending, ResolutionStatus::Corrected, Some(official), delta);
+    }
+
+    /// Let provisional settlements whose confirmation window has passed at
+    /// `now` stand as settled
+    pub fn expire_resolutions(&mut self, now: DateTime<Utc>) {
+        for pending in self.resolutions.expire(now) {
+            self.stats.resolutions_unconfirmed += 1;
+            tracing::warn!(
+                event_code = %EventCode::ResolutionUnconfirmed,
+                market_id = %pending.market.condition_id,
+                winner = ?pending.winner,
+                pnl = %pending.pnl(),
+                "No official resolution within the confirmation window, provisional outcome stands"
+            );
+            self.finish_resolution(&pending, ResolutionStatus::Unconfirmed, None, Decimal::ZERO);
+        }
+    }
+
+    fn finish_resolution(
+        &mut self,
+        pending: &PendingResolution,
+        status: ResolutionStatus,
+        official: Option<Side>,
+        pnl_delta: Decimal,
+    ) {
+        let market_id = &pending.market.condition_id;
+        if let Some(settlement) = self
+            .settlements
+            .iter_mut()
+            .rev()
+            .find(|s| s.market_id == *market_id)
+        {
+            settlement.status = status;
+            settlement.winner = official.unwrap_or(settlement.winner);
+        }
+        record_resolution(status.label());
+        self.publish_resolutions();
+        self.journal(
+            &format!("resolution_{}", status.label()),
+            serde_json::json!({
+                "market_id": market_id,
+                "status": status,
+                "provisional": pending.winner,
+                "official": official,
+                "positions": pending.positions.len(),
+                "pnl": pending.pnl(),
+                "pnl_delta": pnl_delta,
+            }),
+        );
+    }
+
+    fn publish_resolutions(&mut self) {
+        self.stats.provisional_pnl = self.resolutions.provisional_pnl();
+        self.stats.resolutions_pending = self.resolutions.len();
+        set_provisional_pnl(self.stats.provisional_pnl.to_f64().unwrap_or(0.0));
+    }
+
+    /// Start a loss cooldown, or halt the asset, on a losing settlement
+    fn update_cooldown(&mut self, now: DateTime<Utc>, market: &Market, pnl: Decimal) {
+        let trigger = self.cooldown.on_settled(&self.asset, market, pnl, now);
+        if trigger.is_some() {
+            // Markets already open for a later window are skipped too
+            for open in self.markets.values() {
+                self.cooldown.on_market_open(&self.asset, open);
+            }
+        }
+        set_loss_cooldown(&self.asset, self.cooldown.severity(&self.asset, now));
+        let losses = self
+            .cooldown
+            .assets()
+            .get(&self.asset)
+            .map_or(0, |state| state.consecutive_losses);
+        match trigger {
+            Some(CooldownTrigger::Cooling) => {
+                tracing::warn!(
+                    event_code = %EventCode::LossCooldown,
+                    market_id = %market.condition_id,
+                    asset = %self.asset,
+                    pnl = %pnl,
+                    losses,
+                    windows = self.cooldown.config().windows,
+                    minutes = %self.cooldown.config().minutes,
+                    "Loss cooldown started"
+                );
+            }
+            Some(CooldownTrigger::Halted) => {
+                tracing::error!(
+                    event_code = %EventCode::AssetHalted,
+                    market_id = %market.condition_id,
+                    asset = %self.asset,
+                    pnl = %pnl,
+                    losses,
+                    "Asset halted on consecutive losses; acknowledge with `poly-hft ctl ack-cooldown {}`",
+                    self.asset
+                );
+            }
+            None => return,
+        }
+        self.journal(
+            "loss_cooldown",
+            serde_json::json!({
+                "market_id": market.condition_id,
+                "asset": self.asset,
+                "pnl": pnl,
+                "consecutive_losses": losses,
+                "halted": trigger == Some(CooldownTrigger::Halted),
+            }),
+        );
+    }
+
+    /// Track equity and halt on a breached drawdown limit
+    fn update_drawdown(&mut self, now: DateTime<Utc>) {
+        let today = now.date_naive();
+        if self.day.replace(today).is_some_and(|day| day != today) {
+            self.drawdown.reset_daily();
+        }
+        self.drawdown.update(self.ledger.balance());
+        if self.stats.halt.is_some() {
+            return;
+        }
+        let Some(reason) = self.drawdown.should_halt(self.stack.limits()) else {
+            return;
+        };
+        tracing::error!(
+            event_code = %EventCode::Halt,
+            reason = ?reason,
+            hard = reason.is_hard(),
+            equity = %self.drawdown.current_equity,
+            "Trading halted"
+        );
+        if !reason.is_hard() {
+            return;
+        }
+        let record = HaltRecord::new(reason, &self.drawdown, now);
+        if let Some(store) = &self.halts {
+            match store.record(&record) {
+                Ok(path) => tracing::error!(
+                    event_code = %EventCode::Halt,
+                    halt = %record.id,
+                    path = ?path,
+                    "Hard halt persisted; acknowledge with `poly-hft ctl ack-halt {}`",
+                    record.id
+                ),
+                Err(e) => tracing::error!(error = %e, "Failed to persist hard halt"),
+            }
+        }
+        self.stats.halt = Some(record);
+    }
+
+    /// Append a trade journal entry carrying the engine tag
+    fn journal(&self, kind: &str, mut data: serde_json::Value) {
+        let Some(journal) = &self.trade_journal else {
+            return;
+        };
+        data["engine"] = self.execution.name().into();
+        data["config_revision"] = self.effective.revision.into();
+        if self.stats.role == Some(Role::Standby) {
+            data["standby"] = true.into();
+        }
+        if let Err(e) = journal.append(kind, &data) {
+            tracing::warn!(error = %e, kind, "Failed to journal trade event");
+        }
+    }
+
+    async fn finish_outcomes(&mut self, outcomes: Vec<SignalOutcome>) {
+        if outcomes.is_empty() {
+            return;
+        }
+        if let Some(journal) = &self.outcome_journal {
+            for outcome in &outcomes {
+                if let Err(e) = journal.append("signal_outcome", outcome) {
+                    tracing::warn!(error = %e, "Failed to journal signal outcome");
+                }
+            }
+        }
+        for outcome in &outcomes {
+            tracing::debug!(
+                market_id = %outcome.market_id,
+                signal_id = %outcome.signal_id,
+                converged = outcome.converged(),
+                favorable = %outcome.max_favorable,
+                adverse = %outcome.max_adverse,
+                "Signal outcome"
+            );
+        }
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.record_signal_outcomes(outcomes).await {
+                tracing::error!(
+                    event_code = %EventCode::FlushFailed,
+                    error = %e,
+                    "Failed to write signal outcomes"
+                );
+            }
+        }
+        self.stats.outcomes = self.outcomes.summary();
+        if let Some(rate) = self.stats.outcomes.convergence_rate() {
+            set_signal_convergence_rate(rate);
+        }
+    }
+
+    /// Spot windows of the momentum detector and volatility estimator,
+    /// taken at `now`
+    pub fn warm_state(&self, now: DateTime<Utc>) -> WarmState {
+        WarmState {
+            version: WARM_STATE_VERSION,
+            taken_at: now,
+            momentum: self.momentum.state(),
+            volatility: self.volatility.state(),
+        }
+    }
+
+    /// Carry on from spot windows saved by an earlier session
+    pub fn restore_warm_state(&mut self, state: WarmState) {
+        self.momentum.restore(state.momentum);
+        self.volatility.restore(state.volatility);
+    }
+
+    /// Every order book held, taken at `now`, or `None` when none has
+    /// changed since the last checkpoint unless `force`
+    pub fn book_checkpoint(&mut self, now: DateTime<Utc>, force: bool) -> Option<BookCheckpoint> {
+        (force || self.books.changed()).then(|| self.books.checkpoint(now))
+    }
+
+    /// Hold the books of an earlier session's checkpoint until live
+    /// updates confirm them; returns how many were restored
+    pub fn restore_books(&mut self, checkpoint: &BookCheckpoint) -> usize {
+        self.books.restore(checkpoint)
+    }
+
+    /// Stop entering ahead of a handoff; exits and settlements go on
+    pub fn start_draining(&mut self, now: DateTime<Utc>) {
+        if self.draining {
+            return;
+        }
+        self.draining = true;
+        tracing::warn!(
+            event_code = %EventCode::HandoffDraining,
+            open_positions = self.positions.open_count(),
+            markets = self.markets.len(),
+            "Draining for a handoff, entries withheld"
+        );
+        self.journal(
+            "handoff_draining",
+            serde_json::json!({
+                "at": now,
+                "open_positions": self.positions.open_count(),
+            }),
+        );
+    }
+
+    /// Whether entries are withheld ahead of a handoff
+    pub fn is_draining(&self) -> bool {
+        self.draining
+    }
+
+    /// What a successor needs to carry on the session, taken at `now`
+    pub fn handoff_state(&self, now: DateTime<Utc>) -> HandoffState {
+        let mut positions: Vec<Position> =
+            self.positions.open_positions.values().cloned().collect();
+        positions.sort_by_key(|p| (p.entry_time, p.id));
+        let held = |id: &Uuid| self.positions.open_positions.contains_key(id);
+        let mut markets: Vec<Market> = self.markets.values().cloned().collect();
+        markets.sort_by(|a, b| (a.open_time, &a.condition_id).cmp(&(b.open_time, &b.condition_id)));
+        let mut entered: Vec<String> = self.entered.iter().cloned().collect();
+        entered.sort();
+        HandoffState {
+            version: HANDOFF_VERSION,
+            taken_at: now,
+            released: false,
+            fills: positions.iter().map(handoff::entry_fill).collect(),
+            features: self
+                .entries
+                .iter()
+                .filter(|(id, _)| held(id))
+                .map(|(id, features)| (*id, features.clone()))
+                .collect(),
+            ladder: self
+                .ladder
+                .progress()
+                .into_iter()
+                .filter(|(id, _)| held(id))
+                .collect(),
+            tokens: markets
+                .iter()
+                .flat_map(|m| [m.yes_token_id.clone(), m.no_token_id.clone()])
+                .collect(),
+            positions,
+            markets,
+            entered,
+            warm: self.warm_state(now),
+            spot: self.spot,
+            last_tick_at: self.last_tick_at,
+            balance: self.ledger.balance(),
+            halt: self.stats.halt.clone(),
+            realized_pnl: self.stats.realized_pnl,
+        }
+    }
+
+    /// Carry on the session a draining process handed over
+    ///
+    /// Its positions are held under their ids, their entry fills counted
+    /// with this session's, and its markets, entries, spot windows and halt
+    /// restored. The ledger, the shared file, is synced and checked against
+    /// the balance handed over. The first tick is checked for a gap since
+    /// the old process's last.
+    pub fn take_over(&mut self, state: HandoffState, now: DateTime<Utc>) {
+        for market in &state.markets {
+            label_policy().register_market(market);
+            self.journal(
+                "market_opened",
+                serde_json::json!({
+                    "market_id": market.condition_id,
+                    "asset": market.asset,
+                    "yes_token_id": market.yes_token_id,
+                    "no_token_id": market.no_token_id,
+                    "open_price": market.open_price,
+                    "open_time": market.open_time,
+                    "close_time": market.close_time,
+                    "orientation": market.orientation,
+                    "taken_over": true,
+                }),
+            );
+            self.seen_markets.push(market.clone());
+            self.markets
+                .insert(market.yes_token_id.clone(), market.clone());
+        }
+        for position in &state.positions {
+            self.journal(
+                "position_taken_over",
+                serde_json::json!({
+                    "market_id": position.market.condition_id,
+                    "position_id": position.id,
+                    "order_id": position.id,
+                    "side": position.side,
+                    "price": position.entry_price,
+                    "size": position.size,
+                    "fee": position.entry_fee,
+                }),
+            );
+            self.positions.restore(position.clone());
+        }
+        self.entered.extend(state.entered);
+        self.inherited.extend(state.fills);
+        self.entries.extend(state.features);
+        self.ladder.resume(state.ladder);
+        self.restore_warm_state(state.warm);
+        self.spot = self.spot.or(state.spot);
+        self.handoff_from = state.last_tick_at;
+        if self.stats.halt.is_none() {
+            self.stats.halt = state.halt;
+        }
+        self.sync_ledger(now);
+        let drift = self.ledger.balance() - state.balance;
+        tracing::warn!(
+            event_code = %EventCode::HandoffTakenOver,
+            positions = state.positions.len(),
+            markets = state.markets.len(),
+            released = state.released,
+            balance = %self.ledger.balance(),
+            %drift,
+            "Took over a handed-over session"
+        );
+        if !drift.is_zero() {
+            tracing::error!(
+                handed_over = %state.balance,
+                balance = %self.ledger.balance(),
+                "Ledger balance differs from the one handed over"
+            );
+        }
+        self.journal(
+            "handoff_taken_over",
+            serde_json::json!({
+                "taken_at": state.taken_at,
+                "released": state.released,
+                "positions": state.positions.len(),
+                "markets": state.markets.len(),
+                "balance": self.ledger.balance(),
+                "drift": drift,
+                "predecessor_pnl": state.realized_pnl,
+            }),
+        );
+    }
+
+    /// Log and journal a spot feed silence from the handed-over process's
+    /// last tick at `from` to this one's first at `to`, if past tolerance
+    fn check_handoff_gap(&mut self, from: DateTime<Utc>, to: DateTime<Utc>) {
+        let gap = to - from;
+        if gap <= self.handoff_gap_tolerance {
+            return;
+        }
+        self.stats.handoff_gap_ms = Some(gap.num_milliseconds());
+        tracing::warn!(
+            event_code = %EventCode::HandoffGap,
+            from = %from,
+            until = %to,
+            gap_ms = gap.num_milliseconds(),
+            "Spot feed gap across the handoff"
+        );
+        self.journal(
+            "handoff_gap",
+            serde_json::json!({
+                "from": from,
+                "until": to,
+                "gap_ms": gap.num_milliseconds(),
+            }),
+        );
+    }
+
+    /// The evaluation made since the last call, if any; kept only
+    /// [`Self::with_explanations`]
+    pub fn take_explanation(&mut self) -> Option<Explanation> {
+        self.explanation.take()
+    }
+
+    /// Entry fills of the positions handed over by another process, which
+    /// this session's execution engine never saw
+    pub fn inherited_fills(&self) -> &[Fill] {
+        &self.inherited
+    }
+
+    /// Feed backfilled spot prices after `after`, e.g. klines covering the
+    /// gap since a restored state, to the spot windows; returns how many
+    pub fn backfill_spot(&mut self, ticks: &[PriceTick], after: DateTime<Utc>) -> usize {
+        let mut applied = 0;
+        for tick in ticks.iter().filter(|t| t.exchange_ts > after) {
+            self.volatility.update(tick.exchange_ts, tick.price);
+            self.momentum.update(tick.exchange_ts, tick.price);
+            applied += 1;
+        }
+        applied
+    }
+
+    /// Session counters
+    pub fn stats(&self) -> &EngineStats {
+        &self.stats
+    }
+
+    /// Open and closed positions
+    pub fn positions(&self) -> &PositionTracker {
+        &self.positions
+    }
+
+    /// Books held, as the engine trades on them
+    pub fn order_books(&self) -> &OrderBookManager {
+        &self.books
+    }
+
+    /// Markets opened this session, settled or not
+    pub fn markets_seen(&self) -> &[Market] {
+        &self.seen_markets
+    }
+
+    /// Entry counts of the engine's structures at `now`, with the depth of
+    /// the instrumented channels, journal writers and recorder buffers
+    pub fn internals(&self, now: DateTime<Utc>) -> InternalsSnapshot {
+        InternalsSnapshot {
+            taken_at: now,
+            order_books: self.books.tracked(),
+            book_levels: self.books.levels(),
+            markets: self.markets.len(),
+            detectors: self.outcomes.active() + self.sanity.tracked() + self.shocks.watching(),
+            price_samples: self.momentum.sample_count() + self.volatility.sample_count(),
+            open_positions: self.positions.open_positions.len(),
+            closed_positions: self.positions.closed_positions.len(),
+            session_records: self.seen_markets.len()
+                + self.settlements.len()
+                + self.tape.len()
+                + self.attribution.len(),
+            channels: channel_depths(),
+            recorder: self.recorder.as_ref().map(DataRecorder::buffers),
+            journal_queue: pending_appends(),
+        }
+    }
+
+    /// Markets settled this session, in order
+    pub fn settlements(&self) -> &[Settlement] {
+        &self.settlements
+    }
+
+    /// Trades closed this session, in settlement order
+    pub fn trade_tape(&self) -> &[TapeRow] {
+        &self.tape
+    }
+
+    /// Journal a wall-clock step or pause, freezing entries until a step
+    /// has settled
+    pub fn on_clock_event(&mut self, event: &ClockEvent) {
+        match *event {
+            ClockEvent::Step {
+                at,
+                skew_ms,
+                settle_until,
+            } => {
+                self.clock_settle_until = Some(settle_until);
+                self.journal(
+                    "clock_step",
+                    serde_json::json!({
+                        "at": at,
+                        "skew_ms": skew_ms,
+                        "settle_until": settle_until,
+                    }),
+                );
+            }
+            ClockEvent::Pause { at, gap_ms } => self.journal(
+                "clock_pause",
+                serde_json::json!({ "at": at, "gap_ms": gap_ms }),
+            ),
+        }
+    }
+
+    /// Pick up ledger entries booked by another process, such as
+    /// `poly-hft ctl deposit`, so sizing follows the new balance
+    ///
+    /// Deposits and withdrawals move the drawdown baseline with the
+    /// equity rather than reading as profit or loss.
+    pub fn sync_ledger(&mut self, now: DateTime<Utc>) -> Vec<LedgerEntry> {
+        let added = match self.ledger.sync() {
+            Ok(added) => added,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to read the ledger");
+                return vec![];
+            }
+        };
+        for entry in &added {
+            if entry.kind.is_transfer() {
+                self.drawdown.transfer(entry.amount);
+            }
+            tracing::info!(
+                kind = %entry.kind,
+                amount = %entry.amount,
+                reference = %entry.reference,
+                balance = %self.ledger.balance(),
+                "Ledger entry booked outside the engine"
+            );
+            self.journal("ledger_entry", ledger_json(entry));
+        }
+        if !added.is_empty() {
+            self.update_drawdown(now);
+        }
+        added
+    }
+
+    /// Compare the exchange's collateral balance with the ledger's cash,
+    /// booking an adjustment and alerting when it drifts beyond
+    /// `tolerance`
+    pub fn reconcile_balance(
+        &mut self,
+        actual: Decimal,
+        tolerance: Decimal,
+        now: DateTime<Utc>,
+    ) -> Option<LedgerEntry> {
+        self.sync_ledger(now);
+        let held = self.held();
+        let expected = self.ledger.balance() - held;
+        set_balance_drift((actual - expected).to_f64().unwrap_or_default());
+        let adjustment = match self.ledger.reconcile(held, actual, tolerance, now) {
+            Ok(adjustment) => adjustment?,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to book a balance adjustment");
+                return None;
+            }
+        };
+        tracing::error!(
+            event_code = %EventCode::BalanceDrift,
+            actual = %actual,
+            expected = %expected,
+            adjustment = %adjustment.amount,
+            balance = %self.ledger.balance(),
+            "Exchange balance drifted from the ledger, adjustment booked"
+        );
+        self.journal("ledger_entry", ledger_json(&adjustment));
+        self.update_drawdown(now);
+        Some(adjustment)
+    }
+
+    /// Book a closed position's gross P&L and fees, and its adverse
+    /// excursion
+    fn book_closed(&mut self, closed: &ClosedPosition) {
+        self.review_closed(closed);
+        let reference = closed.position.id.to_string();
+        let gross = closed.realized_pnl + closed.fees;
+        self.book(
+            LedgerEntryKind::TradeSettlement,
+            gross,
+            &reference,
+            closed.exit_time,
+        );
+        if !closed.fees.is_zero() {
+            self.book(
+                LedgerEntryKind::Fee,
+                -closed.fees,
+                &reference,
+                closed.exit_time,
+            );
+        }
+    }
+
+    /// Count a closed position's adverse excursion, flagging the strategy
+    /// for review when the recent ones drifted past the baseline
+    fn review_closed(&mut self, closed: &ClosedPosition) {
+        let now = closed.exit_time;
+        let Some(flag) = self.review.record(&self.asset, closed, now) else {
+            return;
+        };
+        self.stats.review_flags += 1;
+        self.apply_review(now, Provenance::Adaptive);
+        tracing::error!(
+            event_code = %EventCode::StrategyReview,
+            asset = %self.asset,
+            percentile = self.review.config().percentile,
+            recent = %flag.recent,
+            baseline = %flag.baseline,
+            ratio = %flag.ratio(),
+            size_scale = %self.review.config().size_scale,
+            "Adverse excursions drifted past the baseline, strategy flagged for review; clear with `poly-hft ctl ack-review {}`",
+            self.asset
+        );
+        self.journal(
+            "strategy_review",
+            serde_json::json!({
+                "asset": self.asset,
+                "position_id": closed.position.id,
+                "recent": flag.recent,
+                "baseline": flag.baseline,
+                "ratio": flag.ratio(),
+                "size_scale": self.review.config().size_scale,
+            }),
+        );
+    }
+
+    /// Apply the review flags cleared with `poly-hft ctl ack-review`
+    pub fn sync_strategy_review(&mut self) {
+        for (asset, flag) in self.review.apply_clears() {
+            tracing::warn!(
+                asset = %asset,
+                raised_at = %flag.raised_at,
+                "Strategy review cleared, full sizing restored"
+            );
+        }
+        self.apply_review(Utc::now(), Provenance::Ctl);
+    }
+
+    /// Apply the flags changed in the config file or set with
+    /// `poly-hft ctl flag` since the last call
+    pub fn sync_flags(&mut self) {
+        let Some(watcher) = &mut self.flag_watcher else {
+            return;
+        };
+        let now = Utc::now();
+        for change in watcher.poll() {
+            tracing::warn!(
+                flag = %change.name,
+                state = %change.state,
+                provenance = %change.provenance,
+                "Feature flag changed"
+            );
+            self.stack.set_flag(&change.name, change.state);
+            self.set_parameter(
+                &format!("flags.{}", change.name),
+                change.state,
+                change.provenance,
+                now,
+            );
+        }
+    }
+
+    /// Re-evaluate the trading schedule at `now`, closing every open
+    /// position when the engine's window ends with `flatten_on_close` set
+    ///
+    /// A position whose book is not held yet is closed on its next book.
+    pub async fn sync_schedule(&mut self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let flatten = self
+            .schedule
+            .update(now)
+            .into_iter()
+            .any(|t| t.flatten && t.strategy == DEFAULT_STRATEGY);
+        if !flatten {
+            return Ok(());
+        }
+        let positions: Vec<Position> = self.positions.open_positions.values().cloned().collect();
+        tracing::warn!(
+            positions = positions.len(),
+            "Trading schedule closed, flattening positions"
+        );
+        for position in positions {
+            let request = ExitRequest {
+                position_id: position.id,
+                reason: ExitReason::Schedule,
+                at: now,
+                fraction: Decimal::ONE,
+                detail: "trading schedule closed".to_string(),
+            };
+            match self.books.book(&position.market.yes_token_id).cloned() {
+                Some(book) => self.exit_position(now, position, request, &book).await?,
+                None => {
+                    self.exits.request(request);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether flag `name` was on for the signal that opened `position_id`;
+    /// a position whose signal did not evaluate it counts as on
+    fn flag_on(&self, position_id: Uuid, name: &str) -> bool {
+        self.entries
+            .get(&position_id)
+            .and_then(|entry| entry.flags.get(name))
+            .is_none_or(|decision| decision.on)
+    }
+
+    /// Size entries, and set the gauge, by the review flag of the asset;
+    /// a new scale is registered as changed by `provenance`
+    fn apply_review(&mut self, now: DateTime<Utc>, provenance: Provenance) {
+        set_strategy_review(&self.asset, self.review.flag(&self.asset).is_some());
+        let scale = self.review.size_scale(&self.asset);
+        self.stack.set_size_scale(scale);
+        self.set_parameter(SIZE_SCALE, scale.normalize(), provenance, now);
+    }
+
+    /// Register a runtime change to a parameter, journaling it
+    fn set_parameter(
+        &mut self,
+        name: &str,
+        value: impl serde::Serialize,
+        provenance: Provenance,
+        now: DateTime<Utc>,
+    ) {
+        let Some(change) = self.effective.set(name, value, provenance, now) else {
+            return;
+        };
+        tracing::info!(
+            parameter = %change.name,
+            old = ?change.old,
+            new = %change.new,
+            provenance = %change.provenance,
+            revision = change.revision,
+            "Parameter changed"
+        );
+        self.journal(
+            "parameter_changed",
+            serde_json::to_value(&change).unwrap_or_default(),
+        );
+    }
+
+    /// Adverse excursion statistics and review flags
+    pub fn strategy_review(&self) -> &StrategyReview {
+        &self.review
+    }
+
+    fn book(&mut self, kind: LedgerEntryKind, amount: Decimal, reference: &str, at: DateTime<Utc>) {
+        if let Err(e) = self.ledger.record(kind, amount, reference, at) {
+            tracing::warn!(error = %e, %kind, %amount, reference, "Failed to write ledger entry");
+        }
+    }
+
+    /// Count a trade print toward the paper entries being shadowed,
+    /// journaling those whose window it closes
+    pub fn on_trade(&mut self, print: &TradePrint) {
+        if !self.shadow.is_enabled() {
+            return;
+        }
+        self.shadow.observe(print);
+        for result in self.shadow.expire(print.timestamp) {
+            self.journal_shadow_fill(&result);
+        }
+    }
+
+    /// Close every shadow window still open, e.g. at shutdown, and return
+    /// all shadowed entries of the session
+    pub fn finish_shadow_fills(&mut self) -> &[ShadowFill] {
+        for result in self.shadow.finish() {
+            self.journal_shadow_fill(&result);
+        }
+        self.shadow.results()
+    }
+
+    fn journal_shadow_fill(&self, result: &ShadowFill) {
+        tracing::debug!(
+            market_id = %result.market_id,
+            attainability = %result.attainability,
+            prints = result.prints,
+            available = %result.available_size,
+            "Shadow fill closed"
+        );
+        self.journal(
+            "shadow_fill",
+            serde_json::json!({
+                "market_id": result.market_id,
+                "signal_id": result.signal_id,
+                "price": result.fill_price,
+                "size": result.size,
+                "attainability": result.attainability,
+                "available_size": result.available_size,
+                "best_price": result.best_price,
+            }),
+        );
+    }
+
+    /// Settled positions attributed this session, in settlement order
+    pub fn pnl_attribution(&self) -> &[PositionAttribution] {
+        &self.attribution
+    }
+
+    /// Balance of the ledger: deposits less withdrawals plus the P&L of
+    /// every settled position
+    pub fn bankroll(&self) -> Decimal {
+        self.ledger.balance()
+    }
+
+    /// Balance movements behind [`Self::bankroll`]
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Equity peak and drawdowns
+    pub fn drawdown(&self) -> &DrawdownMonitor {
+        &self.drawdown
+    }
+
+    /// Orders logged in the intent log with no outcome yet; 0 without a log
+    pub fn unreconciled_orders(&self) -> anyhow::Result<usize> {
+        match &self.intents {
+            Some(intents) => Ok(intents.pending()?.len()),
+            None => Ok(0),
+        }
+    }
+
+    /// Underlying execution engine
+    pub fn execution(&self) -> &E {
+        &self.execution
+    }
+}
+
+/// Journal payload of a ledger entry
+fn ledger_json(entry: &LedgerEntry) -> serde_json::Value {
+    serde_json::json!({
+        "seq": entry.seq,
+        "at": entry.at,
+        "kind": entry.kind,
+        "amount": entry.amount,
+        "reference": entry.reference,
+    })
+}
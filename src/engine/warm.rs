@@ -0,0 +1,232 @@
+//! Warm restart of the spot windows
+//!
+//! The momentum detector needs a full window of fresh ticks, and the
+//! volatility estimator longer, before either says anything, so a restart
+//! at the wrong moment costs a whole market window. Their prices are saved
+//! to [`WARM_STATE_FILE`] periodically and at shutdown. On startup a state
+//! younger than `max_age_secs` is restored and the gap since it was taken
+//! backfilled from klines; an older one, or one of another schema version,
+//! is ignored and the engine starts cold.
+
+use crate::duration::DurationConfig;
+use crate::model::VolatilityState;
+use crate::signal::MomentumState;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// File in the data directory holding the saved state
+pub const WARM_STATE_FILE: &str = "warm_state.json";
+
+/// Schema version of [`WarmState`]; a saved state of any other is ignored
+pub const WARM_STATE_VERSION: u32 = 1;
+
+/// Default seconds between saves
+pub const DEFAULT_WARM_STATE_INTERVAL_SECS: u64 = 30;
+
+/// Default oldest state restored at startup, in seconds
+pub const DEFAULT_WARM_STATE_MAX_AGE_SECS: u64 = 300;
+
+/// Saving and restoring the spot windows, under `[signal.warm_state]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmStateConfig {
+    /// Time between saves; 0 saves at shutdown only
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: DurationConfig,
+    /// Oldest state restored at startup; 0 always starts cold
+    #[serde(default = "default_max_age_secs")]
+    pub max_age_secs: DurationConfig,
+}
+
+fn default_interval_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_WARM_STATE_INTERVAL_SECS)
+}
+
+fn default_max_age_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_WARM_STATE_MAX_AGE_SECS)
+}
+
+impl Default for WarmStateConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_interval_secs(),
+            max_age_secs: default_max_age_secs(),
+        }
+    }
+}
+
+/// Prices of the momentum detector and volatility estimator at one moment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WarmState {
+    /// Schema version, [`WARM_STATE_VERSION`] when written
+    pub version: u32,
+    /// When the state was taken
+    pub taken_at: DateTime<Utc>,
+    pub momentum: MomentumState,
+    pub volatility: VolatilityState,
+}
+
+/// Only the version is read first, so a state of another schema is
+/// recognised as such rather than failing to parse
+#[derive(Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+impl WarmState {
+    /// Write the state to `path`, replacing any earlier one whole
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// The state at `path` if it is of this schema version and at most
+    /// `max_age` old at `now`
+    pub fn load(path: &Path, now: DateTime<Utc>, max_age: Duration) -> Option<Self> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                tracing::warn!(path = ?path, error = %e, "Failed to read warm state, starting cold");
+                return None;
+            }
+        };
+        match serde_json::from_slice::<Versioned>(&bytes) {
+            Ok(v) if v.version == WARM_STATE_VERSION => {}
+            Ok(v) => {
+                tracing::info!(
+                    version = v.version,
+                    expected = WARM_STATE_VERSION,
+                    "Ignoring warm state of another schema version"
+                );
+                return None;
+            }
+            Err(e) => {
+                tracing::warn!(path = ?path, error = %e, "Ignoring unreadable warm state");
+                return None;
+            }
+        }
+        let state: Self = match serde_json::from_slice(&bytes) {
+            Ok(state) => state,
+            Err(e) => {
+                tracing::warn!(path = ?path, error = %e, "Ignoring unreadable warm state");
+                return None;
+            }
+        };
+        let age = now - state.taken_at;
+        if age > max_age {
+            tracing::info!(
+                age_secs = age.num_seconds(),
+                max_age_secs = max_age.num_seconds(),
+                "Discarding stale warm state, starting cold"
+            );
+            return None;
+        }
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::VolatilityEstimator;
+    use crate::signal::{MomentumDetector, Side};
+    use rust_decimal::Decimal;
+
+    /// A wandering spot price, one tick a second
+    fn ticks(from: i64, to: i64) -> Vec<(DateTime<Utc>, Decimal)> {
+        let start = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+        (from..to)
+            .map(|i| {
+                let cents = 10_000_000 + (i * 7919 % 501) - 250 + i * 3;
+                (start + Duration::seconds(i), Decimal::new(cents, 2))
+            })
+            .collect()
+    }
+
+    fn models() -> (MomentumDetector, VolatilityEstimator) {
+        (
+            MomentumDetector::default(),
+            VolatilityEstimator::new(Duration::minutes(5)).with_max_samples(64),
+        )
+    }
+
+    fn feed(
+        (momentum, volatility): &mut (MomentumDetector, VolatilityEstimator),
+        ticks: &[(DateTime<Utc>, Decimal)],
+    ) {
+        for (at, price) in ticks {
+            momentum.update(*at, *price);
+            volatility.update(*at, *price);
+        }
+    }
+
+    #[test]
+    fn test_restored_state_detects_like_an_uninterrupted_run() {
+        let mut uninterrupted = models();
+        feed(&mut uninterrupted, &ticks(0, 600));
+
+        let mut before = models();
+        feed(&mut before, &ticks(0, 300));
+        let taken_at = ticks(299, 300)[0].0;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(WARM_STATE_FILE);
+        WarmState {
+            version: WARM_STATE_VERSION,
+            taken_at,
+            momentum: before.0.state(),
+            volatility: before.1.state(),
+        }
+        .save(&path)
+        .unwrap();
+
+        let state = WarmState::load(&path, taken_at + Duration::seconds(5), Duration::minutes(5))
+            .expect("fresh state restored");
+        let mut after = models();
+        after.0.restore(state.momentum);
+        after.1.restore(state.volatility);
+        feed(&mut after, &ticks(300, 600));
+
+        for side in [Side::Yes, Side::No] {
+            assert_eq!(after.0.signal(side), uninterrupted.0.signal(side));
+        }
+        assert_eq!(after.0.state(), uninterrupted.0.state());
+        assert_eq!(after.1.state(), uninterrupted.1.state());
+        assert_eq!(after.1.estimate(), uninterrupted.1.estimate());
+    }
+
+    #[test]
+    fn test_stale_or_foreign_state_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(WARM_STATE_FILE);
+        let taken_at = ticks(0, 1)[0].0;
+        let max_age = Duration::seconds(300);
+        assert!(WarmState::load(&path, taken_at, max_age).is_none());
+
+        let mut state = WarmState {
+            version: WARM_STATE_VERSION,
+            taken_at,
+            momentum: MomentumState::default(),
+            volatility: VolatilityState::default(),
+        };
+        state.save(&path).unwrap();
+        assert!(WarmState::load(&path, taken_at + max_age, max_age).is_some());
+        assert!(
+            WarmState::load(&path, taken_at + max_age + Duration::seconds(1), max_age).is_none()
+        );
+
+        // Another version is ignored even when its fields no longer parse
+        state.version = WARM_STATE_VERSION + 1;
+        state.save(&path).unwrap();
+        assert!(WarmState::load(&path, taken_at, max_age).is_none());
+        std::fs::write(&path, r#"{"version": 99, "windows": []}"#).unwrap();
+        assert!(WarmState::load(&path, taken_at, max_age).is_none());
+        std::fs::write(&path, "not json").unwrap();
+        assert!(WarmState::load(&path, taken_at, max_age).is_none());
+    }
+}
@@ -0,0 +1,296 @@
+//! Execution camouflage: jittered timing and size of live entries
+//!
+//! An entry submitted the instant a lag is detected, at exactly the size
+//! the sizing formula gives, is easy to spot and to trade against. With
+//! camouflage on, each entry waits a random delay of up to `max_delay_ms`,
+//! its size is cut by a random factor of up to `size_jitter_pct` percent
+//! and rounded to the share increment, and with `split_probability` it is
+//! sent as two unequal clips instead of one order. The size is only ever
+//! jittered down: it was already held to the Kelly, position and exposure
+//! caps, so the clips never add up to more than was approved.
+//!
+//! The engine draws from its own generator, seeded from the OS and never
+//! logged, so the draws cannot be predicted from anything we publish. What
+//! was drawn is journaled after the fact for each entry. Backtests seed the
+//! same model from `--seed` so runs stay reproducible. Off by default, in
+//! which case nothing is drawn and entries go out as decided.
+
+use crate::duration::{DurationConfig, Millis};
+use crate::precision::{round_size, SIZE_DP};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rust_decimal::{Decimal, RoundingStrategy};
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+
+/// Default longest random delay before an entry is submitted
+pub const DEFAULT_CAMOUFLAGE_MAX_DELAY_MS: u64 = 400;
+
+/// Default largest cut to an entry's size, in percent
+pub const DEFAULT_SIZE_JITTER_PCT: Decimal = dec!(10);
+
+/// Default chance an entry is sent as two clips
+pub const DEFAULT_SPLIT_PROBABILITY: Decimal = dec!(0.2);
+
+/// Default smallest share of a split entry the smaller clip takes
+pub const DEFAULT_MIN_CLIP_FRACTION: Decimal = dec!(0.25);
+
+/// Basis points in one
+const BPS: i64 = 10_000;
+
+/// Execution camouflage settings, under `[execution.camouflage]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CamouflageConfig {
+    /// Jitter the timing and size of entries
+    #[serde(default)]
+    pub enabled: bool,
+    /// Longest random delay before an entry is submitted
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: DurationConfig<Millis>,
+    /// Largest cut to an entry's size, in percent
+    #[serde(default = "default_size_jitter_pct")]
+    pub size_jitter_pct: Decimal,
+    /// Chance, 0 to 1, that an entry is sent as two clips
+    #[serde(default = "default_split_probability")]
+    pub split_probability: Decimal,
+    /// Smallest share, below 0.5, of a split entry the smaller clip takes
+    #[serde(default = "default_min_clip_fraction")]
+    pub min_clip_fraction: Decimal,
+}
+
+fn default_max_delay_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_CAMOUFLAGE_MAX_DELAY_MS)
+}
+
+fn default_size_jitter_pct() -> Decimal {
+    DEFAULT_SIZE_JITTER_PCT
+}
+
+fn default_split_probability() -> Decimal {
+    DEFAULT_SPLIT_PROBABILITY
+}
+
+fn default_min_clip_fraction() -> Decimal {
+    DEFAULT_MIN_CLIP_FRACTION
+}
+
+impl Default for CamouflageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_delay_ms: default_max_delay_ms(),
+            size_jitter_pct: default_size_jitter_pct(),
+            split_probability: default_split_probability(),
+            min_clip_fraction: default_min_clip_fraction(),
+        }
+    }
+}
+
+/// What was drawn for one entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CamouflagePlan {
+    /// Wait before the entry is submitted
+    pub delay_ms: u64,
+    /// Size the entry was decided at
+    pub size: Decimal,
+    /// Factor the decided size was scaled by, e.g. 0.97; never above 1
+    pub size_factor: Decimal,
+    /// Sizes submitted, in order: one, or two unequal clips when split.
+    /// Each is a whole number of share increments.
+    pub clips: Vec<Decimal>,
+}
+
+impl CamouflagePlan {
+    /// Size submitted over every clip
+    pub fn jittered_size(&self) -> Decimal {
+        self.clips.iter().sum()
+    }
+
+    /// Whether the entry is sent as more than one order
+    pub fn is_split(&self) -> bool {
+        self.clips.len() > 1
+    }
+}
+
+/// Draws the camouflage of each entry
+#[derive(Debug)]
+pub struct Camouflage {
+    config: CamouflageConfig,
+    /// `None` when disabled
+    rng: Option<ChaCha8Rng>,
+}
+
+impl Camouflage {
+    /// Camouflage drawing from a seed out of the OS, which is never logged
+    pub fn new(config: &CamouflageConfig) -> Self {
+        Self {
+            config: config.clone(),
+            rng: config.enabled.then(ChaCha8Rng::from_entropy),
+        }
+    }
+
+    /// Camouflage whose draws are reproduced by `seed`, for backtests
+    pub fn seeded(config: &CamouflageConfig, seed: u64) -> Self {
+        Self {
+            config: config.clone(),
+            rng: config.enabled.then(|| ChaCha8Rng::seed_from_u64(seed)),
+        }
+    }
+
+    /// Whether entries are camouflaged
+    pub fn is_enabled(&self) -> bool {
+        self.rng.is_some()
+    }
+
+    /// Draw the camouflage of an entry of `size` shares; `None` when
+    /// disabled. Every entry draws the same number of values, so a seeded
+    /// sequence does not depend on the sizes it was asked about.
+    pub fn plan(&mut self, size: Decimal) -> Option<CamouflagePlan> {
+        let rng = self.rng.as_mut()?;
+        let increment = Decimal::new(1, SIZE_DP);
+
+        let delay_ms = rng.gen_range(0..=self.config.max_delay_ms.as_millis());
+
+        let jitter_bps = to_bps(self.config.size_jitter_pct / Decimal::ONE_HUNDRED).min(BPS - 1);
+        let size_factor = Decimal::ONE + bps(rng.gen_range(-jitter_bps..=0));
+        // Rounded down, so it never passes the size risk approved
+        let jittered = (size * size_factor)
+            .round_dp_with_strategy(SIZE_DP, RoundingStrategy::ToZero)
+            .max(increment);
+
+        let split = rng.gen_bool(to_bps(self.config.split_probability) as f64 / BPS as f64);
+        let min_bps = to_bps(self.config.min_clip_fraction).min(BPS / 2);
+        let fraction = bps(rng.gen_range(min_bps..=BPS - min_bps));
+
+        let first = round_size(jittered * fraction)
+            .max(increment)
+            .min(jittered - increment);
+        // Fewer than three increments cannot be cut into unequal clips
+        let clips = if split && jittered >= increment * dec!(3) {
+            // Equal halves would read as one order cut in two
+            let first = match first * dec!(2) == jittered {
+                true => first + increment,
+                false => first,
+            };
+            vec![first, jittered - first]
+        } else {
+            vec![jittered]
+        };
+
+        Some(CamouflagePlan {
+            delay_ms,
+            size,
+            size_factor,
+            clips,
+        })
+    }
+}
+
+/// `value`, between 0 and 1, in whole basis points
+fn to_bps(value: Decimal) -> i64 {
+    (value.max(Decimal::ZERO).min(Decimal::ONE) * Decimal::from(BPS))
+        .trunc()
+        .try_into()
+        .unwrap_or(0)
+}
+
+/// `n` basis points as a fraction
+fn bps(n: i64) -> Decimal {
+    Decimal::from(n) / Decimal::from(BPS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> CamouflageConfig {
+        CamouflageConfig {
+            enabled: true,
+            split_probability: dec!(0.5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_draws_stay_within_bounds_on_the_share_increment() {
+        let config = enabled();
+        let mut camouflage = Camouflage::seeded(&config, 7);
+        let increment = Decimal::new(1, SIZE_DP);
+        let mut splits = 0;
+        for i in 0..2_000 {
+            let size = Decimal::from(1 + i % 50) + dec!(0.37);
+            let plan = camouflage.plan(size).unwrap();
+
+            assert!(plan.delay_ms <= DEFAULT_CAMOUFLAGE_MAX_DELAY_MS);
+            assert!(plan.size_factor >= dec!(0.9) && plan.size_factor <= Decimal::ONE);
+            let jittered = plan.jittered_size();
+            assert!(jittered >= round_size(size * dec!(0.9)) && jittered <= size);
+            for clip in &plan.clips {
+                assert!(*clip >= increment, "{:?}", plan);
+                assert_eq!(clip % increment, Decimal::ZERO, "{:?}", plan);
+            }
+            if plan.is_split() {
+                splits += 1;
+                assert_eq!(plan.clips.len(), 2);
+                assert_ne!(plan.clips[0], plan.clips[1], "{:?}", plan);
+                let smaller = plan.clips[0].min(plan.clips[1]);
+                assert!(smaller >= round_size(jittered * DEFAULT_MIN_CLIP_FRACTION) - increment);
+            }
+        }
+        assert!((800..1200).contains(&splits), "{} splits", splits);
+
+        // Reproduced by the seed
+        let mut again = Camouflage::seeded(&config, 7);
+        let mut camouflage = Camouflage::seeded(&config, 7);
+        assert_eq!(again.plan(dec!(10)), camouflage.plan(dec!(10)));
+    }
+
+    #[test]
+    fn test_jittered_total_never_exceeds_the_approved_size() {
+        // The widest jitter allowed, always split, over sizes on and off
+        // the share increment
+        let config = CamouflageConfig {
+            size_jitter_pct: dec!(100),
+            split_probability: Decimal::ONE,
+            ..enabled()
+        };
+        let mut camouflage = Camouflage::seeded(&config, 11);
+        let mut cut = 0;
+        for i in 1..5_000i64 {
+            let approved = Decimal::new(i * 7, 2) + Decimal::new(i % 3, 3);
+            let plan = camouflage.plan(approved).unwrap();
+            assert!(plan.jittered_size() <= approved, "{} {:?}", approved, plan);
+            assert!(plan.clips.iter().all(|clip| *clip <= approved));
+            let increment = Decimal::new(1, SIZE_DP);
+            assert!(plan
+                .clips
+                .iter()
+                .all(|clip| *clip % increment == Decimal::ZERO));
+            if plan.jittered_size() < round_size(approved) {
+                cut += 1;
+            }
+        }
+        assert!(cut > 4_000, "{} cut", cut);
+    }
+
+    #[test]
+    fn test_disabled_draws_nothing() {
+        let mut camouflage = Camouflage::new(&CamouflageConfig::default());
+        assert!(!camouflage.is_enabled());
+        assert_eq!(camouflage.plan(dec!(10)), None);
+
+        // Too small to split unequally or to shrink below one increment
+        let mut camouflage = Camouflage::seeded(
+            &CamouflageConfig {
+                split_probability: Decimal::ONE,
+                ..enabled()
+            },
+            1,
+        );
+        let plan = camouflage.plan(dec!(0.01)).unwrap();
+        assert_eq!(plan.clips, [dec!(0.01)]);
+        let plan = camouflage.plan(dec!(0.02)).unwrap();
+        assert_eq!(plan.clips.len(), 1, "{:?}", plan);
+    }
+}
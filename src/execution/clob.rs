@@ -0,0 +1,392 @@
+//! Polymarket CLOB credentials and authenticated REST access
+//!
+//! Account endpoints use "L2" auth: every request carries the API key and
+//! passphrase plus an HMAC-SHA256 over `timestamp + method + path + body`,
+//! keyed with the base64url-decoded API secret.
+
+use super::user_channel::{RawTrade, TradeEvent};
+use crate::duration::DurationConfig;
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+
+/// Polymarket CLOB REST base URL
+pub const CLOB_URL: &str = "https://clob.polymarket.com";
+
+/// Polymarket CLOB user channel WebSocket URL
+pub const USER_WS_URL: &str = "wss://ws-subscriptions-clob.polymarket.com/ws/user";
+
+/// Default seconds between REST reconciliations
+pub const DEFAULT_RECONCILE_INTERVAL_SECS: u64 = 60;
+
+/// Trades endpoint, also the signed request path
+const TRADES_PATH: &str = "/data/trades";
+
+/// Balance and allowance endpoint, also the signed request path
+const BALANCE_PATH: &str = "/balance-allowance";
+
+/// Decimals of USDC, the collateral; balances come in its base units
+const COLLATERAL_DECIMALS: u32 = 6;
+
+/// Pagination cursors of the CLOB API
+const FIRST_CURSOR: &str = "MA==";
+const END_CURSOR: &str = "LTE=";
+
+/// Live trading settings, under `[execution.live]`
+///
+/// The API credentials are read from the config file but never serialized,
+/// so they stay out of the config fingerprint stamped into every output.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LiveConfig {
+    /// CLOB REST base URL
+    #[serde(default = "default_clob_url")]
+    pub clob_url: String,
+    /// User channel WebSocket URL
+    #[serde(default = "default_user_ws_url")]
+    pub user_ws_url: String,
+    /// Funder wallet address
+    pub address: String,
+    /// CLOB API key
+    #[serde(skip_serializing)]
+    pub api_key: String,
+    /// CLOB API secret (base64url)
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// CLOB API passphrase
+    #[serde(skip_serializing)]
+    pub passphrase: String,
+    /// Time between REST reconciliations of user channel fills
+    #[serde(default = "default_reconcile_interval_secs")]
+    pub reconcile_interval_secs: DurationConfig,
+}
+
+fn default_clob_url() -> String {
+    CLOB_URL.to_string()
+}
+
+fn default_user_ws_url() -> String {
+    USER_WS_URL.to_string()
+}
+
+fn default_reconcile_interval_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_RECONCILE_INTERVAL_SECS)
+}
+
+impl fmt::Debug for LiveConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiveConfig")
+            .field("clob_url", &self.clob_url)
+            .field("user_ws_url", &self.user_ws_url)
+            .field("address", &self.address)
+            .field("api_key", &"<redacted>")
+            .field("secret", &"<redacted>")
+            .field("passphrase", &"<redacted>")
+            .field("reconcile_interval_secs", &self.reconcile_interval_secs)
+            .finish()
+    }
+}
+
+impl LiveConfig {
+    /// L2 signature of a request, base64url-encoded
+    pub fn sign(
+        &self,
+        timestamp: i64,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> anyhow::Result<String> {
+        let key = URL_SAFE
+            .decode(self.secret.trim())
+            .map_err(|e| anyhow::anyhow!("CLOB secret is not base64url: {}", e))?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&key)?;
+        mac.update(format!("{timestamp}{method}{path}{body}").as_bytes());
+        Ok(URL_SAFE.encode(mac.finalize().into_bytes()))
+    }
+
+    /// L2 auth headers for a request at `now`
+    pub fn l2_headers(
+        &self,
+        method: &str,
+        path: &str,
+        body: &str,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<(&'static str, String)>> {
+        let timestamp = now.timestamp();
+        Ok(vec![
+            ("POLY_ADDRESS", self.address.clone()),
+            ("POLY_SIGNATURE", self.sign(timestamp, method, path, body)?),
+            ("POLY_TIMESTAMP", timestamp.to_string()),
+            ("POLY_API_KEY", self.api_key.clone()),
+            ("POLY_PASSPHRASE", self.passphrase.clone()),
+        ])
+    }
+}
+
+/// Source of our account's trades for replay and reconciliation
+#[async_trait]
+pub trait TradeHistory: Send + Sync {
+    /// Trades matched at or after `since`
+    async fn trades_since(&self, since: DateTime<Utc>) -> anyhow::Result<Vec<TradeEvent>>;
+}
+
+/// Source of the account's collateral balance for ledger reconciliation
+#[async_trait]
+pub trait CollateralBalance: Send + Sync {
+    /// USDC held at the exchange, outside open positions
+    async fn collateral_balance(&self) -> anyhow::Result<Decimal>;
+}
+
+#[derive(Deserialize)]
+struct BalanceAllowance {
+    /// Base units, as a decimal string
+    balance: String,
+}
+
+#[derive(Deserialize)]
+struct TradesPage {
+    #[serde(default)]
+    data: Vec<RawTrade>,
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+/// Authenticated client for the CLOB REST API
+pub struct ClobClient {
+    config: LiveConfig,
+    http: reqwest::Client,
+}
+
+impl ClobClient {
+    /// Create a client from the live settings
+    pub fn new(config: LiveConfig) -> Self {
+        Self {
+            config: LiveConfig {
+                clob_url: config.clob_url.trim_end_matches('/').to_string(),
+                ..config
+            },
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Fail unless the CLOB accepts our credentials
+    ///
+    /// Signs one request for the first page of trades from now, so it
+    /// fetches next to nothing.
+    pub async fn check_auth(&self) -> anyhow::Result<()> {
+        let mut request = self
+            .http
+            .get(format!("{}{}", self.config.clob_url, TRADES_PATH))
+            .query(&[
+                ("after", Utc::now().timestamp().to_string()),
+                ("next_cursor", FIRST_CURSOR.to_string()),
+            ]);
+        for (name, value) in self.config.l2_headers("GET", TRADES_PATH, "", Utc::now())? {
+            request = request.header(name, value);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TradeHistory for ClobClient {
+    async fn trades_since(&self, since: DateTime<Utc>) -> anyhow::Result<Vec<TradeEvent>> {
+        let mut trades = vec![];
+        let mut cursor = FIRST_CURSOR.to_string();
+        while cursor != END_CURSOR {
+            let mut request = self
+                .http
+                .get(format!("{}{}", self.config.clob_url, TRADES_PATH))
+                .query(&[
+                    ("after", since.timestamp().to_string()),
+                    ("next_cursor", cursor.clone()),
+                ]);
+            for (name, value) in self.config.l2_headers("GET", TRADES_PATH, "", Utc::now())? {
+                request = request.header(name, value);
+            }
+            let page: TradesPage = request.send().await?.error_for_status()?.json().await?;
+
+            let count = page.data.len();
+            trades.extend(
+                page.data
+                    .into_iter()
+                    .filter_map(|raw| raw.into_event(&self.config.api_key)),
+            );
+            match page.next_cursor {
+                Some(next) if count > 0 && next != cursor => cursor = next,
+                _ => break,
+            }
+        }
+        Ok(trades)
+    }
+}
+
+#[async_trait]
+impl CollateralBalance for ClobClient {
+    async fn collateral_balance(&self) -> anyhow::Result<Decimal> {
+        let mut request = self
+            .http
+            .get(format!("{}{}", self.config.clob_url, BALANCE_PATH))
+            .query(&[("asset_type", "COLLATERAL")]);
+        for (name, value) in self
+            .config
+            .l2_headers("GET", BALANCE_PATH, "", Utc::now())?
+        {
+            request = request.header(name, value);
+        }
+        let body: BalanceAllowance = request.send().await?.error_for_status()?.json().await?;
+        let units: Decimal = body.balance.parse()?;
+        Ok(units / Decimal::from(10u64.pow(COLLATERAL_DECIMALS)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn live_config(clob_url: &str) -> LiveConfig {
+        LiveConfig {
+            clob_url: clob_url.to_string(),
+            user_ws_url: USER_WS_URL.to_string(),
+            address: "0xabc".to_string(),
+            api_key: "key-1".to_string(),
+            secret: "cG9seS1oZnQtdGVzdC1zZWNyZXQtMDEyMzQ1Njc4OQ==".to_string(),
+            passphrase: "hunter2".to_string(),
+            reconcile_interval_secs: default_reconcile_interval_secs(),
+        }
+    }
+
+    #[test]
+    fn test_l2_signature_and_redaction() {
+        let config = live_config(CLOB_URL);
+        assert_eq!(
+            config.sign(1_700_000_000, "GET", TRADES_PATH, "").unwrap(),
+            "qfZXh3bKi4iBVPkRIpho4NKqWnB7nTp_fuATii8VwTA="
+        );
+
+        let shown = format!("{:?}", config);
+        assert!(!shown.contains("hunter2") && !shown.contains("key-1"));
+        let serialized = serde_json::to_string(&config).unwrap();
+        assert!(!serialized.contains(&config.secret));
+        assert!(!serialized.contains("key-1"));
+    }
+
+    #[tokio::test]
+    async fn test_check_auth() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(TRADES_PATH))
+            .and(header("POLY_API_KEY", "key-1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"data": [], "next_cursor": END_CURSOR})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(TRADES_PATH))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        let client = ClobClient::new(live_config(&server.uri()));
+        client.check_auth().await.unwrap();
+
+        let mut config = live_config(&server.uri());
+        config.api_key = "revoked".to_string();
+        let err = ClobClient::new(config).check_auth().await.unwrap_err();
+        assert!(crate::breaker::Failure::is_fatal(&err));
+    }
+
+    #[tokio::test]
+    async fn test_trades_since_signs_and_pages() {
+        let server = MockServer::start().await;
+        let trade = |id: &str| {
+            serde_json::json!({
+                "id": id,
+                "taker_order_id": "0xorder",
+                "market": "0xmarket",
+                "asset_id": "yes-token",
+                "side": "BUY",
+                "size": "10",
+                "price": "0.55",
+                "status": "CONFIRMED",
+                "match_time": "1700000000",
+                "maker_orders": [],
+            })
+        };
+        Mock::given(method("GET"))
+            .and(path(TRADES_PATH))
+            .and(query_param("next_cursor", FIRST_CURSOR))
+            .and(header("POLY_API_KEY", "key-1"))
+            .and(header("POLY_PASSPHRASE", "hunter2"))
+            .and(header("POLY_ADDRESS", "0xabc"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({"data": [trade("t1")], "next_cursor": "MQ=="}),
+                ),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(TRADES_PATH))
+            .and(query_param("next_cursor", "MQ=="))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"data": [trade("t2")], "next_cursor": END_CURSOR}),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = ClobClient::new(live_config(&server.uri()));
+        let since = DateTime::from_timestamp(1_699_999_000, 0).unwrap();
+        let trades = client.trades_since(since).await.unwrap();
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].order_id, "0xorder");
+        assert_eq!(trades[1].size, dec!(10));
+
+        let requests = server.received_requests().await.unwrap();
+        let request = &requests[0];
+        assert!(request.url.query().unwrap().contains("after=1699999000"));
+        let stamp: i64 = request.headers["POLY_TIMESTAMP"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let expected = live_config("").sign(stamp, "GET", TRADES_PATH, "").unwrap();
+        assert_eq!(
+            request.headers["POLY_SIGNATURE"].to_str().unwrap(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collateral_balance_in_usdc() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(BALANCE_PATH))
+            .and(query_param("asset_type", "COLLATERAL"))
+            .and(header("POLY_API_KEY", "key-1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"balance": "1234567890", "allowance": "0"})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = ClobClient::new(live_config(&server.uri()));
+        assert_eq!(client.collateral_balance().await.unwrap(), dec!(1234.56789));
+    }
+}
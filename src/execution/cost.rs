@@ -0,0 +1,388 @@
+//! Execution cost model
+//!
+//! Splits the cost of a fill into exchange fee and estimated slippage so
+//! paper fills carry the same itemization a live fill would. Slippage comes
+//! from configured constants or, in calibrated mode, from the slippage
+//! measured on earlier fills of a similar size.
+
+use super::{Order, OrderAction, OrderType};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Largest order size of each slippage calibration bucket; larger orders
+/// share a final open-ended bucket
+pub const SLIPPAGE_SIZE_BUCKETS: [Decimal; 4] = [dec!(10), dec!(50), dec!(100), dec!(500)];
+
+/// Default fills a calibration bucket needs before its measurement is used
+pub const DEFAULT_MIN_CALIBRATION_SAMPLES: usize = 30;
+
+/// Whether a fill added or removed liquidity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LiquidityFlag {
+    /// Resting order filled by someone else
+    Maker,
+    /// Order crossed the spread
+    #[default]
+    Taker,
+}
+
+impl LiquidityFlag {
+    /// Lowercase name used in exports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LiquidityFlag::Maker => "maker",
+            LiquidityFlag::Taker => "taker",
+        }
+    }
+
+    /// Parse an export name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "maker" => Some(LiquidityFlag::Maker),
+            "taker" => Some(LiquidityFlag::Taker),
+            _ => None,
+        }
+    }
+}
+
+/// Fee and slippage parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostModel {
+    /// Fee rate on notional for maker fills
+    #[serde(default)]
+    pub maker_fee_rate: Decimal,
+    /// Fee rate on notional for taker fills
+    #[serde(default = "default_taker_fee_rate")]
+    pub taker_fee_rate: Decimal,
+    /// Fixed price slippage for taker fills
+    #[serde(default)]
+    pub base_slippage: Decimal,
+    /// Additional price slippage per share of order size
+    #[serde(default)]
+    pub slippage_per_share: Decimal,
+    /// Cap on price slippage
+    #[serde(default = "default_max_slippage")]
+    pub max_slippage: Decimal,
+    /// Measured slippage written by `report costs --calibrate`; enables
+    /// calibrated mode
+    #[serde(default)]
+    pub calibration: Option<PathBuf>,
+    /// Fills a calibration bucket needs before it replaces the constants
+    #[serde(default = "default_min_calibration_samples")]
+    pub min_calibration_samples: usize,
+    /// Calibration read from `calibration` by [`CostModel::load_calibration`]
+    #[serde(skip)]
+    pub calibrated: Option<SlippageCalibration>,
+}
+
+fn default_taker_fee_rate() -> Decimal {
+    dec!(0.005)
+}
+
+fn default_max_slippage() -> Decimal {
+    dec!(0.05)
+}
+
+fn default_min_calibration_samples() -> usize {
+    DEFAULT_MIN_CALIBRATION_SAMPLES
+}
+
+/// Slippage measured on fills up to one order size
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlippageBucket {
+    /// Largest order size in the bucket; `None` for the open-ended last one
+    pub max_size: Option<Decimal>,
+    /// Fills measured
+    pub samples: usize,
+    /// Mean price slippage against the order price
+    pub mean_slippage: Decimal,
+}
+
+/// Measured slippage per order size bucket
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlippageCalibration {
+    /// One bucket per [`SLIPPAGE_SIZE_BUCKETS`] bound, then the open one
+    pub buckets: Vec<SlippageBucket>,
+}
+
+impl SlippageCalibration {
+    /// Calibration from `(size, slippage)` pairs of measured fills
+    pub fn from_samples(samples: impl IntoIterator<Item = (Decimal, Decimal)>) -> Self {
+        let bounds = SLIPPAGE_SIZE_BUCKETS.iter().map(|b| Some(*b)).chain([None]);
+        let mut buckets: Vec<(SlippageBucket, Decimal)> = bounds
+            .map(|max_size| {
+                let bucket = SlippageBucket {
+                    max_size,
+                    samples: 0,
+                    mean_slippage: Decimal::ZERO,
+                };
+                (bucket, Decimal::ZERO)
+            })
+            .collect();
+        for (size, slippage) in samples {
+            let (bucket, sum) = buckets
+                .iter_mut()
+                .find(|(b, _)| b.max_size.is_none_or(|max| size <= max))
+                .expect("the last bucket is open-ended");
+            bucket.samples += 1;
+            *sum += slippage;
+        }
+        Self {
+            buckets: buckets
+                .into_iter()
+                .map(|(mut bucket, sum)| {
+                    if bucket.samples > 0 {
+                        bucket.mean_slippage = sum / Decimal::from(bucket.samples);
+                    }
+                    bucket
+                })
+                .collect(),
+        }
+    }
+
+    /// Bucket an order of `size` shares falls in
+    pub fn bucket(&self, size: Decimal) -> Option<&SlippageBucket> {
+        self.buckets
+            .iter()
+            .find(|b| b.max_size.is_none_or(|max| size <= max))
+    }
+
+    /// Read a calibration written by [`Self::write`]
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Write the calibration as JSON
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            maker_fee_rate: Decimal::ZERO,
+            taker_fee_rate: default_taker_fee_rate(),
+            base_slippage: Decimal::ZERO,
+            slippage_per_share: Decimal::ZERO,
+            max_slippage: default_max_slippage(),
+            calibration: None,
+            min_calibration_samples: DEFAULT_MIN_CALIBRATION_SAMPLES,
+            calibrated: None,
+        }
+    }
+}
+
+/// Itemized costs for one fill
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillCosts {
+    /// Execution price after slippage
+    pub price: Decimal,
+    /// Exchange fee
+    pub fee: Decimal,
+    /// Slippage cost versus the order price
+    pub estimated_slippage: Decimal,
+    /// Maker or taker
+    pub liquidity: LiquidityFlag,
+}
+
+impl CostModel {
+    /// Same fee for maker and taker, no slippage
+    pub fn flat(fee_rate: Decimal) -> Self {
+        Self {
+            maker_fee_rate: fee_rate,
+            taker_fee_rate: fee_rate,
+            base_slippage: Decimal::ZERO,
+            slippage_per_share: Decimal::ZERO,
+            max_slippage: Decimal::ZERO,
+            ..Default::default()
+        }
+    }
+
+    /// Read the `calibration` file, if set, to switch to calibrated mode
+    pub fn load_calibration(mut self) -> anyhow::Result<Self> {
+        if let Some(path) = &self.calibration {
+            let calibration = SlippageCalibration::load(path).map_err(|e| {
+                anyhow::anyhow!("Failed to read slippage calibration {:?}: {}", path, e)
+            })?;
+            self.calibrated = Some(calibration);
+        }
+        Ok(self)
+    }
+
+    /// Market orders take liquidity; limit orders are assumed to rest
+    pub fn liquidity(&self, order: &Order) -> LiquidityFlag {
+        match order.order_type {
+            OrderType::Market => LiquidityFlag::Taker,
+            OrderType::Limit => LiquidityFlag::Maker,
+        }
+    }
+
+    /// Price slippage for a taker order of `size` shares
+    ///
+    /// In calibrated mode a bucket with enough samples supplies its measured
+    /// mean instead of the constants; price improvement counts as none.
+    pub fn slippage(&self, size: Decimal) -> Decimal {
+        let measured = self
+            .calibrated
+            .as_ref()
+            .and_then(|c| c.bucket(size))
+            .filter(|b| b.samples >= self.min_calibration_samples);
+        match measured {
+            Some(bucket) => bucket.mean_slippage.max(Decimal::ZERO),
+            None => self.base_slippage + self.slippage_per_share * size,
+        }
+        .min(self.max_slippage)
+    }
+
+    /// Cost an order filled in full
+    pub fn apply(&self, order: &Order) -> FillCosts {
+        let liquidity = self.liquidity(order);
+        let (fee_rate, slippage) = match liquidity {
+            LiquidityFlag::Maker => (self.maker_fee_rate, Decimal::ZERO),
+            LiquidityFlag::Taker => (self.taker_fee_rate, self.slippage(order.size)),
+        };
+
+        // Slippage always moves the price against us, within [0, 1]
+        let price = match order.action {
+            OrderAction::Buy => (order.price + slippage).min(Decimal::ONE),
+            OrderAction::Sell => (order.price - slippage).max(Decimal::ZERO),
+        };
+
+        FillCosts {
+            price,
+            fee: price * order.size * fee_rate,
+            estimated_slippage: (price - order.price).abs() * order.size,
+            liquidity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::Side;
+
+    fn order(order_type: OrderType, action: OrderAction, size: Decimal) -> Order {
+        Order {
+            token_id: "yes-token".to_string(),
+            side: Side::Yes,
+            price: dec!(0.50),
+            size,
+            order_type,
+            action,
+            client_order_id: None,
+        }
+    }
+
+    fn model() -> CostModel {
+        CostModel {
+            maker_fee_rate: dec!(0),
+            taker_fee_rate: dec!(0.01),
+            base_slippage: dec!(0.005),
+            slippage_per_share: dec!(0.0001),
+            max_slippage: dec!(0.02),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_taker_buy_pays_fee_and_slippage() {
+        let costs = model().apply(&order(OrderType::Market, OrderAction::Buy, dec!(100)));
+        // slippage = 0.005 + 0.0001 * 100 = 0.015
+        assert_eq!(costs.liquidity, LiquidityFlag::Taker);
+        assert_eq!(costs.price, dec!(0.515));
+        assert_eq!(costs.estimated_slippage, dec!(1.5));
+        assert_eq!(costs.fee, dec!(0.515));
+    }
+
+    #[test]
+    fn test_taker_sell_slips_down_and_caps() {
+        let costs = model().apply(&order(OrderType::Market, OrderAction::Sell, dec!(1000)));
+        assert_eq!(costs.price, dec!(0.48));
+        assert_eq!(costs.estimated_slippage, dec!(20));
+    }
+
+    #[test]
+    fn test_maker_has_no_slippage() {
+        let costs = model().apply(&order(OrderType::Limit, OrderAction::Buy, dec!(100)));
+        assert_eq!(costs.liquidity, LiquidityFlag::Maker);
+        assert_eq!(costs.price, dec!(0.50));
+        assert_eq!(costs.fee, dec!(0));
+        assert_eq!(costs.estimated_slippage, dec!(0));
+    }
+
+    #[test]
+    fn test_flat_model() {
+        let costs = CostModel::flat(dec!(0.001)).apply(&order(
+            OrderType::Market,
+            OrderAction::Buy,
+            dec!(100),
+        ));
+        assert_eq!(costs.price, dec!(0.50));
+        assert_eq!(costs.fee, dec!(0.05));
+    }
+
+    #[test]
+    fn test_calibrated_slippage_needs_min_samples() {
+        // 3 small fills slipping 0.01, 2 mid-size fills slipping 0.03
+        let samples = [
+            (dec!(5), dec!(0.008)),
+            (dec!(10), dec!(0.012)),
+            (dec!(8), dec!(0.010)),
+            (dec!(40), dec!(0.03)),
+            (dec!(20), dec!(0.03)),
+        ];
+        let calibration = SlippageCalibration::from_samples(samples);
+        assert_eq!(calibration.buckets.len(), SLIPPAGE_SIZE_BUCKETS.len() + 1);
+        assert_eq!(calibration.buckets[0].samples, 3);
+        assert_eq!(calibration.buckets[0].mean_slippage, dec!(0.01));
+        assert_eq!(calibration.bucket(dec!(1000)).unwrap().max_size, None);
+
+        let mut model = CostModel {
+            max_slippage: dec!(0.05),
+            min_calibration_samples: 3,
+            calibrated: Some(calibration),
+            ..model()
+        };
+        // The small bucket has enough samples; the 50-share one falls back
+        assert_eq!(model.slippage(dec!(10)), dec!(0.01));
+        assert_eq!(model.slippage(dec!(50)), dec!(0.01));
+        model.min_calibration_samples = 2;
+        assert_eq!(model.slippage(dec!(50)), dec!(0.03));
+        // Empty buckets, and the cap, still apply
+        assert_eq!(model.slippage(dec!(100)), dec!(0.015));
+        model.max_slippage = dec!(0.02);
+        model.min_calibration_samples = 1;
+        assert_eq!(model.slippage(dec!(50)), dec!(0.02));
+
+        let costs = model.apply(&order(OrderType::Market, OrderAction::Buy, dec!(10)));
+        assert_eq!(costs.price, dec!(0.51));
+    }
+
+    #[test]
+    fn test_calibration_file_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("slippage.json");
+        let calibration = SlippageCalibration::from_samples([(dec!(5), dec!(0.01))]);
+        calibration.write(&path).unwrap();
+
+        let model = CostModel {
+            calibration: Some(path),
+            ..CostModel::default()
+        }
+        .load_calibration()
+        .unwrap();
+        assert_eq!(model.calibrated, Some(calibration));
+
+        let missing = CostModel {
+            calibration: Some(dir.path().join("missing.json")),
+            ..CostModel::default()
+        };
+        assert!(missing.load_calibration().is_err());
+    }
+}
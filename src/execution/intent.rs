@@ -0,0 +1,209 @@
+//! Write-ahead log of order submissions
+//!
+//! Each order is logged, fsync'd, before it is submitted, and its outcome
+//! once the submission returns. An intent left without an outcome means
+//! the process died in between: the order may be live on the venue while
+//! the position tracker never heard of it. On startup those intents are
+//! looked up on the execution engine and the state repaired.
+
+use super::{Order, OrderId};
+use crate::journal::Journal;
+use crate::market::Market;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Intent log file name in the data directory
+pub const INTENT_LOG_FILE: &str = "order_intents.jsonl";
+
+const INTENT_KIND: &str = "order_intent";
+const OUTCOME_KIND: &str = "order_outcome";
+
+/// An order about to be submitted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderIntent {
+    /// Identifier the order is submitted under
+    pub client_order_id: String,
+    /// Market the order trades, enough to reopen the position
+    pub market: Market,
+    pub order: Order,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrderIntent {
+    /// Intent for `order`, which must carry a client order id
+    pub fn new(market: &Market, order: &Order, now: DateTime<Utc>) -> anyhow::Result<Self> {
+        let client_order_id = order
+            .client_order_id
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Order intent needs a client order id"))?;
+        Ok(Self {
+            client_order_id,
+            market: market.clone(),
+            order: order.clone(),
+            created_at: now,
+        })
+    }
+}
+
+/// How a submission ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntentStatus {
+    /// The order filled
+    Filled,
+    /// The order was accepted without filling
+    Unfilled,
+    /// The submission failed
+    Rejected,
+    /// After a crash, the engine had no fill for the order
+    NotFound,
+}
+
+/// Outcome appended once the submission returned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntentOutcome {
+    pub client_order_id: String,
+    pub status: IntentStatus,
+    #[serde(default)]
+    pub order_id: Option<OrderId>,
+    /// Filled size, zero unless filled
+    #[serde(default)]
+    pub filled: Decimal,
+    /// Error of a rejected submission
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Whether the outcome was found by startup recovery
+    #[serde(default)]
+    pub recovered: bool,
+    pub at: DateTime<Utc>,
+}
+
+impl IntentOutcome {
+    /// Outcome of `client_order_id` with nothing filled
+    pub fn new(client_order_id: &str, status: IntentStatus, at: DateTime<Utc>) -> Self {
+        Self {
+            client_order_id: client_order_id.to_string(),
+            status,
+            order_id: None,
+            filled: Decimal::ZERO,
+            error: None,
+            recovered: false,
+            at,
+        }
+    }
+}
+
+/// Append-only, fsync'd log of order intents and their outcomes
+pub struct IntentLog {
+    journal: Journal,
+}
+
+impl IntentLog {
+    /// Open (or create) the log at `path`
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        Ok(Self {
+            journal: Journal::open(path)?.durable(),
+        })
+    }
+
+    /// Log file path
+    pub fn path(&self) -> &Path {
+        self.journal.path()
+    }
+
+    /// Durably record an order before it is submitted
+    pub fn record_intent(&self, intent: &OrderIntent) -> anyhow::Result<()> {
+        self.journal.append(INTENT_KIND, intent)
+    }
+
+    /// Durably record how a submission ended
+    pub fn record_outcome(&self, outcome: &IntentOutcome) -> anyhow::Result<()> {
+        self.journal.append(OUTCOME_KIND, outcome)
+    }
+
+    /// Intents with no outcome, oldest first
+    pub fn pending(&self) -> anyhow::Result<Vec<OrderIntent>> {
+        let mut intents: HashMap<String, OrderIntent> = HashMap::new();
+        for entry in Journal::read_all(self.path())? {
+            match entry.kind.as_str() {
+                INTENT_KIND => {
+                    let intent: OrderIntent = serde_json::from_value(entry.data)?;
+                    intents.insert(intent.client_order_id.clone(), intent);
+                }
+                OUTCOME_KIND => {
+                    let outcome: IntentOutcome = serde_json::from_value(entry.data)?;
+                    intents.remove(&outcome.client_order_id);
+                }
+                _ => {}
+            }
+        }
+        let mut pending: Vec<_> = intents.into_values().collect();
+        pending.sort_by_key(|i| i.created_at);
+        Ok(pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::{OrderAction, OrderType};
+    use crate::signal::Side;
+    use rust_decimal_macros::dec;
+
+    fn intent(id: &str, secs: i64) -> OrderIntent {
+        let at = DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        let market = Market {
+            condition_id: "m1".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes-1".to_string(),
+            no_token_id: "no-1".to_string(),
+            open_price: dec!(100000),
+            open_time: at,
+            close_time: at + chrono::Duration::minutes(15),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        let order = Order {
+            token_id: "yes-1".to_string(),
+            side: Side::Yes,
+            price: dec!(0.55),
+            size: dec!(10),
+            order_type: OrderType::Limit,
+            action: OrderAction::Buy,
+            client_order_id: Some(id.to_string()),
+        };
+        OrderIntent::new(&market, &order, at).unwrap()
+    }
+
+    #[test]
+    fn test_pending_are_intents_without_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = IntentLog::open(dir.path().join(INTENT_LOG_FILE)).unwrap();
+        for (id, secs) in [("a", 0), ("b", 1), ("c", 2)] {
+            log.record_intent(&intent(id, secs)).unwrap();
+        }
+        log.record_outcome(&IntentOutcome::new("b", IntentStatus::Filled, Utc::now()))
+            .unwrap();
+
+        // A reopened log sees the same state
+        let reopened = IntentLog::open(log.path()).unwrap();
+        let pending: Vec<_> = reopened
+            .pending()
+            .unwrap()
+            .into_iter()
+            .map(|i| i.client_order_id)
+            .collect();
+        assert_eq!(pending, vec!["a", "c"]);
+        assert_eq!(reopened.pending().unwrap()[0].order.size, dec!(10));
+    }
+
+    #[test]
+    fn test_intent_needs_client_order_id() {
+        let mut order = intent("a", 0).order;
+        order.client_order_id = None;
+        assert!(OrderIntent::new(&intent("a", 0).market, &order, Utc::now()).is_err());
+    }
+}
@@ -1,22 +1,70 @@
 //! Execution engine module
 //!
-//! Handles order submission (paper and live modes)
+//! Handles order submission (paper, dry-run, and live modes)
 
+mod camouflage;
+mod clob;
+mod cost;
+mod intent;
+mod noop;
 mod paper;
+mod reconcile;
+mod shadow;
+mod trade_log;
 mod types;
+mod user_channel;
 
-pub use paper::PaperEngine;
-pub use types::{Fill, Order, OrderId, OrderType};
+pub use camouflage::{
+    Camouflage, CamouflageConfig, CamouflagePlan, DEFAULT_CAMOUFLAGE_MAX_DELAY_MS,
+    DEFAULT_MIN_CLIP_FRACTION, DEFAULT_SIZE_JITTER_PCT, DEFAULT_SPLIT_PROBABILITY,
+};
+pub use clob::{
+    ClobClient, CollateralBalance, LiveConfig, TradeHistory, CLOB_URL,
+    DEFAULT_RECONCILE_INTERVAL_SECS, USER_WS_URL,
+};
+pub use cost::{
+    CostModel, FillCosts, LiquidityFlag, SlippageBucket, SlippageCalibration,
+    DEFAULT_MIN_CALIBRATION_SAMPLES, SLIPPAGE_SIZE_BUCKETS,
+};
+pub use intent::{IntentLog, IntentOutcome, IntentStatus, OrderIntent, INTENT_LOG_FILE};
+pub use noop::{NoopEngine, DRY_RUN_ENGINE};
+pub use paper::{PaperEngine, PAPER_ENGINE};
+pub use reconcile::{
+    reconcile, Discrepancy, FillReconciler, OrderTracker, DEFAULT_RECONCILE_GRACE_SECS,
+};
+pub use shadow::{
+    Attainability, ShadowConfig, ShadowFill, ShadowFillValidator, DEFAULT_SHADOW_WINDOW_SECS,
+};
+pub(crate) use trade_log::{csv_field, split_csv_line};
+pub use trade_log::{read_trades, write_trades};
+pub use types::{Fill, Order, OrderAction, OrderId, OrderType};
+pub use user_channel::{
+    parse_user_message, subscription_message, ClobSide, OrderEvent, OrderEventKind, TradeEvent,
+    TradeStatus, UserChannel, UserEvent, REPLAY_MARGIN_SECS,
+};
 
+use crate::data::HistoryArchive;
 use async_trait::async_trait;
 
 /// Trait for execution engine implementations
 #[async_trait]
 pub trait ExecutionEngine: Send + Sync {
+    /// Engine tag recorded in journals and reports, e.g. `paper`
+    fn name(&self) -> &'static str;
     /// Submit an order
     async fn submit_order(&self, order: Order) -> anyhow::Result<OrderId>;
     /// Cancel an order
     async fn cancel_order(&self, id: OrderId) -> anyhow::Result<()>;
-    /// Get all fills
+    /// Get all fills still in memory
     async fn get_fills(&self) -> anyhow::Result<Vec<Fill>>;
+    /// Keep about `max_fills` fills in memory, archiving older ones to
+    /// `archive`; 0 keeps them all. Engines that hold no fills ignore it.
+    fn retain_fills(&mut self, _archive: HistoryArchive, _max_fills: usize) {}
+    /// Fill of the order submitted with `client_order_id`, if it filled
+    async fn find_order(&self, client_order_id: &str) -> anyhow::Result<Option<Fill>> {
+        let fills = self.get_fills().await?;
+        Ok(fills
+            .into_iter()
+            .find(|f| f.client_order_id == client_order_id))
+    }
 }
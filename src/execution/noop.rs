@@ -0,0 +1,110 @@
+//! Dry-run execution engine
+
+use super::{ExecutionEngine, Fill, LiquidityFlag, Order, OrderId};
+use crate::telemetry::EventCode;
+use async_trait::async_trait;
+use chrono::Utc;
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Engine tag of dry-run sessions
+pub const DRY_RUN_ENGINE: &str = "dry_run";
+
+/// Execution engine that never leaves the process
+///
+/// Orders get ids and an instant, cost-free fill at the order price flagged
+/// `simulated`, so sizing, risk, and position tracking above it run exactly
+/// as they would against a real engine.
+#[derive(Default)]
+pub struct NoopEngine {
+    fills: Arc<RwLock<Vec<Fill>>>,
+}
+
+impl NoopEngine {
+    /// Create a dry-run engine
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ExecutionEngine for NoopEngine {
+    fn name(&self) -> &'static str {
+        DRY_RUN_ENGINE
+    }
+
+    async fn submit_order(&self, order: Order) -> anyhow::Result<OrderId> {
+        let order_id = OrderId::new_v4();
+        let fill = Fill {
+            order_id,
+            token_id: order.token_id,
+            side: order.side,
+            price: order.price,
+            size: order.size,
+            timestamp: Utc::now(),
+            fee: Decimal::ZERO,
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::default(),
+            action: order.action,
+            client_order_id: order
+                .client_order_id
+                .unwrap_or_else(|| order_id.to_string()),
+            simulated: true,
+        }
+        .rounded();
+
+        tracing::info!(
+            event_code = %EventCode::OrderFilled,
+            %order_id,
+            token_id = %fill.token_id,
+            action = ?fill.action,
+            price = %fill.price,
+            "Dry-run order filled"
+        );
+        self.fills.write().await.push(fill);
+        Ok(order_id)
+    }
+
+    async fn cancel_order(&self, id: OrderId) -> anyhow::Result<()> {
+        tracing::info!(event_code = %EventCode::OrderCancelled, order_id = %id, "Dry-run order cancelled");
+        Ok(())
+    }
+
+    async fn get_fills(&self) -> anyhow::Result<Vec<Fill>> {
+        Ok(self.fills.read().await.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::{OrderAction, OrderType};
+    use crate::signal::Side;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn test_noop_engine_fills_at_order_price() {
+        let engine = NoopEngine::new();
+        let order = Order {
+            token_id: "yes-token".to_string(),
+            side: Side::Yes,
+            price: dec!(0.55),
+            size: dec!(20),
+            order_type: OrderType::Market,
+            action: OrderAction::Buy,
+            client_order_id: Some("signal-1".to_string()),
+        };
+
+        let order_id = engine.submit_order(order).await.unwrap();
+        let fills = engine.get_fills().await.unwrap();
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].order_id, order_id);
+        assert_eq!(fills[0].price, dec!(0.55));
+        assert_eq!(fills[0].total_cost(), dec!(0));
+        assert_eq!(fills[0].client_order_id, "signal-1");
+        assert!(fills[0].simulated);
+        assert_eq!(engine.name(), DRY_RUN_ENGINE);
+    }
+}
@@ -1,54 +1,112 @@
 //! Paper trading execution engine
 
-use super::{ExecutionEngine, Fill, Order, OrderId};
+use super::trade_log::write_trades;
+use super::{CostModel, ExecutionEngine, Fill, Order, OrderId};
+use crate::data::{evict_oldest, HistoryArchive};
+use crate::telemetry::EventCode;
 use async_trait::async_trait;
 use chrono::Utc;
 use rust_decimal::Decimal;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Engine tag of paper sessions
+pub const PAPER_ENGINE: &str = "paper";
+
 /// Paper trading execution engine with simulated fills
 pub struct PaperEngine {
-    fee_rate: Decimal,
+    cost_model: CostModel,
     fills: Arc<RwLock<Vec<Fill>>>,
+    archive: Option<HistoryArchive>,
+    max_fills: usize,
 }
 
 impl PaperEngine {
-    /// Create a new paper trading engine
+    /// Create a new paper trading engine with a flat fee and no slippage
     pub fn new(fee_rate: Decimal) -> Self {
+        Self::with_cost_model(CostModel::flat(fee_rate))
+    }
+
+    /// Create a paper engine that costs fills with the given model
+    pub fn with_cost_model(cost_model: CostModel) -> Self {
         Self {
-            fee_rate,
+            cost_model,
             fills: Arc::new(RwLock::new(vec![])),
+            archive: None,
+            max_fills: 0,
         }
     }
+
+    /// Write all fills so far, archived ones included, to `path` (`.csv` or
+    /// `.parquet`)
+    pub async fn export_trades(&self, path: &Path) -> anyhow::Result<usize> {
+        let mut fills = match &self.archive {
+            Some(archive) => archive.read_fills()?,
+            None => vec![],
+        };
+        fills.extend(self.fills.read().await.iter().cloned());
+        write_trades(path, &fills)?;
+        tracing::info!(path = ?path, trades = fills.len(), "Exported paper trades");
+        Ok(fills.len())
+    }
 }
 
 #[async_trait]
 impl ExecutionEngine for PaperEngine {
+    fn name(&self) -> &'static str {
+        PAPER_ENGINE
+    }
+
     async fn submit_order(&self, order: Order) -> anyhow::Result<OrderId> {
         let order_id = OrderId::new_v4();
 
-        // Simulate immediate fill at order price
-        let fees = order.size * order.price * self.fee_rate;
+        // Simulate an immediate full fill, costed by the model
+        let costs = self.cost_model.apply(&order);
         let fill = Fill {
             order_id,
             token_id: order.token_id,
             side: order.side,
-            price: order.price,
+            price: costs.price,
             size: order.size,
             timestamp: Utc::now(),
-            fees,
-        };
+            fee: costs.fee,
+            estimated_slippage: costs.estimated_slippage,
+            liquidity: costs.liquidity,
+            action: order.action,
+            client_order_id: order
+                .client_order_id
+                .unwrap_or_else(|| order_id.to_string()),
+            simulated: false,
+        }
+        .rounded();
 
+        tracing::info!(
+            event_code = %EventCode::OrderFilled,
+            %order_id,
+            token_id = %fill.token_id,
+            action = ?fill.action,
+            liquidity = ?fill.liquidity,
+            fee = %fill.fee,
+            slippage = %fill.estimated_slippage,
+            "Paper order filled"
+        );
         let mut fills = self.fills.write().await;
         fills.push(fill);
-
-        tracing::info!(?order_id, "Paper order filled");
+        if let Some(archive) = &self.archive {
+            if let Some(evicted) = evict_oldest(&mut fills, self.max_fills) {
+                // A failed write keeps the fills in memory for the next attempt
+                if let Err(e) = archive.archive_fills(&evicted) {
+                    tracing::error!(error = %e, "Failed to archive paper fills");
+                    fills.splice(0..0, evicted);
+                }
+            }
+        }
         Ok(order_id)
     }
 
     async fn cancel_order(&self, id: OrderId) -> anyhow::Result<()> {
-        tracing::info!(?id, "Paper order cancelled");
+        tracing::info!(event_code = %EventCode::OrderCancelled, order_id = %id, "Paper order cancelled");
         Ok(())
     }
 
@@ -56,12 +114,17 @@ impl ExecutionEngine for PaperEngine {
         let fills = self.fills.read().await;
         Ok(fills.clone())
     }
+
+    fn retain_fills(&mut self, archive: HistoryArchive, max_fills: usize) {
+        self.archive = Some(archive);
+        self.max_fills = max_fills;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::execution::OrderType;
+    use crate::execution::{OrderAction, OrderType};
     use crate::signal::Side;
     use rust_decimal_macros::dec;
 
@@ -75,6 +138,8 @@ mod tests {
             price: dec!(0.50),
             size: dec!(100),
             order_type: OrderType::Limit,
+            action: OrderAction::Buy,
+            client_order_id: None,
         };
 
         let order_id = engine.submit_order(order).await.unwrap();
@@ -82,7 +147,7 @@ mod tests {
 
         assert_eq!(fills.len(), 1);
         assert_eq!(fills[0].order_id, order_id);
-        assert_eq!(fills[0].fees, dec!(0.05)); // 100 * 0.50 * 0.001
+        assert_eq!(fills[0].fee, dec!(0.05)); // 100 * 0.50 * 0.001
     }
 
     #[tokio::test]
@@ -105,6 +170,8 @@ mod tests {
             price: dec!(0.55),
             size: dec!(50),
             order_type: OrderType::Market,
+            action: OrderAction::Buy,
+            client_order_id: None,
         };
 
         let order2 = Order {
@@ -113,6 +180,8 @@ mod tests {
             price: dec!(0.45),
             size: dec!(75),
             order_type: OrderType::Limit,
+            action: OrderAction::Buy,
+            client_order_id: None,
         };
 
         engine.submit_order(order1).await.unwrap();
@@ -134,11 +203,108 @@ mod tests {
             price: dec!(0.50),
             size: dec!(100),
             order_type: OrderType::Limit,
+            action: OrderAction::Buy,
+            client_order_id: None,
         };
 
         engine.submit_order(order).await.unwrap();
         let fills = engine.get_fills().await.unwrap();
 
-        assert_eq!(fills[0].fees, dec!(0));
+        assert_eq!(fills[0].fee, dec!(0));
+    }
+
+    #[tokio::test]
+    async fn test_paper_engine_sell_fill() {
+        let engine = PaperEngine::new(dec!(0.001));
+
+        let order = Order {
+            token_id: "yes-token".to_string(),
+            side: Side::Yes,
+            price: dec!(0.60),
+            size: dec!(10),
+            order_type: OrderType::Limit,
+            action: OrderAction::Sell,
+            client_order_id: None,
+        };
+
+        engine.submit_order(order).await.unwrap();
+        let fills = engine.get_fills().await.unwrap();
+
+        assert_eq!(fills[0].action, OrderAction::Sell);
+        assert_eq!(fills[0].fee, dec!(0.006));
+    }
+
+    #[tokio::test]
+    async fn test_export_fees_match_position_tracker() {
+        use crate::execution::{read_trades, CostModel, LiquidityFlag};
+        use crate::market::Market;
+        use crate::risk::PositionTracker;
+        use crate::signal::{Signal, SignalReason};
+
+        let engine = PaperEngine::with_cost_model(CostModel {
+            maker_fee_rate: dec!(0),
+            taker_fee_rate: dec!(0.01),
+            base_slippage: dec!(0.01),
+            slippage_per_share: dec!(0),
+            max_slippage: dec!(0.05),
+            ..Default::default()
+        });
+        let order = |action, price| Order {
+            token_id: "yes-token".to_string(),
+            side: Side::Yes,
+            price,
+            size: dec!(100),
+            order_type: OrderType::Market,
+            action,
+            client_order_id: Some(format!("{:?}", action)),
+        };
+        engine
+            .submit_order(order(OrderAction::Buy, dec!(0.50)))
+            .await
+            .unwrap();
+        engine
+            .submit_order(order(OrderAction::Sell, dec!(0.60)))
+            .await
+            .unwrap();
+
+        let fills = engine.get_fills().await.unwrap();
+        assert_eq!(fills[0].price, dec!(0.51));
+        assert_eq!(fills[0].liquidity, LiquidityFlag::Taker);
+        assert_eq!(fills[0].estimated_slippage, dec!(1));
+        assert_eq!(fills[1].price, dec!(0.59));
+
+        let market = Market {
+            condition_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes-token".to_string(),
+            no_token_id: "no-token".to_string(),
+            open_price: dec!(100000),
+            open_time: Utc::now(),
+            close_time: Utc::now(),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        let signal = Signal::new(
+            market,
+            Side::Yes,
+            dec!(0.55),
+            dec!(0.50),
+            dec!(0.02),
+            dec!(0.8),
+            SignalReason::SpotDivergence,
+        );
+        let mut tracker = PositionTracker::new();
+        let position = tracker.open(&signal, &fills[0]);
+        tracker.close(position.id, &fills[1]).unwrap();
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("trades.csv");
+        assert_eq!(engine.export_trades(&path).await.unwrap(), 2);
+
+        let exported = read_trades(&path).unwrap();
+        let exported_fees: Decimal = exported.iter().map(|f| f.fee).sum();
+        assert_eq!(exported_fees, tracker.total_fees);
+        assert_eq!(exported_fees, dec!(0.51) + dec!(0.59));
+        assert_eq!(exported[0].client_order_id, "Buy");
     }
 }
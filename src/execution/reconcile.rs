@@ -0,0 +1,270 @@
+//! Order state from the user channel, checked against REST
+//!
+//! The user channel is the primary source of fills. REST is only polled
+//! periodically to catch what the socket missed or misreported.
+
+use super::clob::TradeHistory;
+use super::user_channel::{OrderEvent, TradeEvent, TradeStatus, UserEvent};
+use crate::telemetry::EventCode;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Seconds a trade may take to show up on REST before it is flagged
+pub const DEFAULT_RECONCILE_GRACE_SECS: i64 = 10;
+
+/// Latest state of our orders and fills
+#[derive(Debug, Default)]
+pub struct OrderTracker {
+    orders: HashMap<String, OrderEvent>,
+    trades: HashMap<String, TradeEvent>,
+}
+
+impl OrderTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply an event; returns true for a trade not seen before
+    ///
+    /// Later status updates of a known trade replace it without counting
+    /// as a new fill.
+    pub fn apply(&mut self, event: UserEvent) -> bool {
+        match event {
+            UserEvent::Order(order) => {
+                self.orders.insert(order.id.clone(), order);
+                false
+            }
+            UserEvent::Trade(trade) => self.trades.insert(trade.id.clone(), trade).is_none(),
+        }
+    }
+
+    /// Latest event for order `id`
+    pub fn order(&self, id: &str) -> Option<&OrderEvent> {
+        self.orders.get(id)
+    }
+
+    /// Shares filled on order `id`, excluding failed trades
+    pub fn filled(&self, order_id: &str) -> Decimal {
+        self.trades
+            .values()
+            .filter(|t| t.order_id == order_id && t.status != TradeStatus::Failed)
+            .map(|t| t.size)
+            .sum()
+    }
+
+    /// Every fill seen, in no particular order
+    pub fn trades(&self) -> impl Iterator<Item = &TradeEvent> {
+        self.trades.values()
+    }
+}
+
+/// A fill the user channel and REST disagree on
+#[derive(Debug, Clone, PartialEq)]
+pub enum Discrepancy {
+    /// REST has a trade the user channel never delivered
+    MissingOnWs(TradeEvent),
+    /// The user channel delivered a trade REST does not know
+    MissingOnRest(TradeEvent),
+    /// Both have the trade with different sizes
+    SizeMismatch {
+        trade_id: String,
+        ws: Decimal,
+        rest: Decimal,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingOnWs(t) => write!(f, "trade {} on REST but not WS ({})", t.id, t.size),
+            Self::MissingOnRest(t) => write!(f, "trade {} on WS but not REST ({})", t.id, t.size),
+            Self::SizeMismatch { trade_id, ws, rest } => {
+                write!(
+                    f,
+                    "trade {} size {} on WS vs {} on REST",
+                    trade_id, ws, rest
+                )
+            }
+        }
+    }
+}
+
+/// Compare fills matched before `cutoff` from both sources
+///
+/// Later trades are skipped on both sides, since REST may not list them yet.
+pub fn reconcile(
+    ws: &[TradeEvent],
+    rest: &[TradeEvent],
+    cutoff: DateTime<Utc>,
+) -> Vec<Discrepancy> {
+    let settled = |t: &&TradeEvent| t.timestamp < cutoff;
+    let ws: HashMap<&str, &TradeEvent> = ws
+        .iter()
+        .filter(settled)
+        .map(|t| (t.id.as_str(), t))
+        .collect();
+    let rest: HashMap<&str, &TradeEvent> = rest
+        .iter()
+        .filter(settled)
+        .map(|t| (t.id.as_str(), t))
+        .collect();
+
+    let mut found = vec![];
+    for (id, trade) in &rest {
+        match ws.get(id) {
+            None => found.push(Discrepancy::MissingOnWs((*trade).clone())),
+            Some(seen) if seen.size != trade.size => found.push(Discrepancy::SizeMismatch {
+                trade_id: id.to_string(),
+                ws: seen.size,
+                rest: trade.size,
+            }),
+            Some(_) => {}
+        }
+    }
+    for (id, trade) in &ws {
+        if !rest.contains_key(id) {
+            found.push(Discrepancy::MissingOnRest((*trade).clone()));
+        }
+    }
+    found.sort_by_key(|d| match d {
+        Discrepancy::MissingOnWs(t) | Discrepancy::MissingOnRest(t) => t.id.clone(),
+        Discrepancy::SizeMismatch { trade_id, .. } => trade_id.clone(),
+    });
+    found
+}
+
+/// Periodic REST check of the fills the user channel delivered
+pub struct FillReconciler<H> {
+    history: H,
+    grace: Duration,
+}
+
+impl<H: TradeHistory> FillReconciler<H> {
+    /// Check against `history`
+    pub fn new(history: H) -> Self {
+        Self {
+            history,
+            grace: Duration::seconds(DEFAULT_RECONCILE_GRACE_SECS),
+        }
+    }
+
+    /// Compare the tracker's fills since `since` with REST, logging each
+    /// discrepancy
+    pub async fn check(
+        &self,
+        tracker: &OrderTracker,
+        since: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Discrepancy>> {
+        let rest = self.history.trades_since(since).await?;
+        let ws: Vec<TradeEvent> = tracker
+            .trades()
+            .filter(|t| t.timestamp >= since)
+            .cloned()
+            .collect();
+        let found = reconcile(&ws, &rest, now - self.grace);
+        for discrepancy in &found {
+            tracing::warn!(
+                event_code = %EventCode::FillDiscrepancy,
+                %discrepancy,
+                "User channel and REST fills disagree"
+            );
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::user_channel::{parse_user_message, ClobSide};
+    use async_trait::async_trait;
+    use rust_decimal_macros::dec;
+
+    fn trade(id: &str, size: Decimal, at: i64) -> TradeEvent {
+        TradeEvent {
+            id: id.to_string(),
+            order_id: "0xorder".to_string(),
+            asset_id: "yes-token".to_string(),
+            market: "0xmarket".to_string(),
+            side: ClobSide::Buy,
+            price: dec!(0.57),
+            size,
+            status: TradeStatus::Matched,
+            timestamp: DateTime::from_timestamp(at, 0).unwrap(),
+        }
+    }
+
+    struct Rest(Vec<TradeEvent>);
+
+    #[async_trait]
+    impl TradeHistory for Rest {
+        async fn trades_since(&self, _since: DateTime<Utc>) -> anyhow::Result<Vec<TradeEvent>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_tracker_counts_each_trade_once() {
+        let mut tracker = OrderTracker::new();
+        assert!(tracker.apply(UserEvent::Trade(trade("t1", dec!(4), 100))));
+        let mut confirmed = trade("t1", dec!(4), 100);
+        confirmed.status = TradeStatus::Confirmed;
+        assert!(!tracker.apply(UserEvent::Trade(confirmed)));
+        assert!(tracker.apply(UserEvent::Trade(trade("t2", dec!(6), 101))));
+
+        let order = r#"{"event_type":"order","id":"0xorder","asset_id":"yes-token","side":"BUY",
+            "price":"0.57","original_size":"10","size_matched":"10","type":"UPDATE","timestamp":"101"}"#;
+        for event in parse_user_message(order, "key-1") {
+            assert!(!tracker.apply(event));
+        }
+        assert_eq!(tracker.filled("0xorder"), dec!(10));
+        assert_eq!(tracker.order("0xorder").unwrap().size_matched, dec!(10));
+    }
+
+    #[tokio::test]
+    async fn test_reconciliation_flags_mismatches() {
+        let mut tracker = OrderTracker::new();
+        for t in [
+            trade("agree", dec!(10), 100),
+            trade("short", dec!(5), 100),
+            trade("ws-only", dec!(3), 100),
+            // Too recent for REST; not flagged
+            trade("fresh", dec!(1), 195),
+        ] {
+            tracker.apply(UserEvent::Trade(t));
+        }
+        let rest = Rest(vec![
+            trade("agree", dec!(10), 100),
+            trade("short", dec!(4), 100),
+            trade("rest-only", dec!(2), 100),
+        ]);
+
+        let since = DateTime::from_timestamp(50, 0).unwrap();
+        let now = DateTime::from_timestamp(200, 0).unwrap();
+        let found = FillReconciler::new(rest)
+            .check(&tracker, since, now)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            found,
+            vec![
+                Discrepancy::MissingOnWs(trade("rest-only", dec!(2), 100)),
+                Discrepancy::SizeMismatch {
+                    trade_id: "short".to_string(),
+                    ws: dec!(5),
+                    rest: dec!(4),
+                },
+                Discrepancy::MissingOnRest(trade("ws-only", dec!(3), 100)),
+            ]
+        );
+        assert_eq!(
+            found[1].to_string(),
+            "trade short size 5 on WS vs 4 on REST"
+        );
+    }
+}
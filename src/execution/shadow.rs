@@ -0,0 +1,423 @@
+//! Shadow fills: would a paper fill have happened?
+//!
+//! A paper fill is assumed at the touch plus modeled slippage. To check
+//! that price was there to be had, each filled entry is shadowed: the
+//! public trade prints (`last_trade_price`) on its token over the next
+//! `window_secs` are watched, and the size that traded at or better than
+//! the assumed fill price is summed. A fill whose whole size printed is
+//! attainable, one with some of it partially attainable, one with none
+//! unattainable.
+//!
+//! The walk up the printed prices needed to cover the size also gives the
+//! slippage a live order of that size would have paid, which calibrates
+//! the [`CostModel`](super::CostModel) with evidence from the market rather
+//! than from our own paper fills.
+
+use super::Fill;
+use crate::bus::TradePrint;
+use crate::duration::DurationConfig;
+use crate::signal::{Side, Signal};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Default time each fill is shadowed for
+pub const DEFAULT_SHADOW_WINDOW_SECS: u64 = 30;
+
+/// Shadow fill settings, under `[execution.shadow]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ShadowConfig {
+    /// Shadow paper entries against the market's trade prints
+    #[serde(default)]
+    pub enabled: bool,
+    /// How long after a fill its prints are watched
+    #[serde(default = "default_window_secs")]
+    pub window_secs: DurationConfig,
+}
+
+fn default_window_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_SHADOW_WINDOW_SECS)
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: default_window_secs(),
+        }
+    }
+}
+
+/// Whether the market traded enough at the assumed fill price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Attainability {
+    /// The whole size printed at or better than the fill price
+    Attainable,
+    /// Some of it did
+    Partial,
+    /// None of it did
+    Unattainable,
+}
+
+impl Attainability {
+    /// Lowercase name used in exports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Attainability::Attainable => "attainable",
+            Attainability::Partial => "partial",
+            Attainability::Unattainable => "unattainable",
+        }
+    }
+
+    /// Parse an export name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "attainable" => Some(Attainability::Attainable),
+            "partial" => Some(Attainability::Partial),
+            "unattainable" => Some(Attainability::Unattainable),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Attainability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the prints after one paper entry showed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShadowFill {
+    /// Signal behind the entry
+    pub signal_id: String,
+    /// Market condition ID
+    pub market_id: String,
+    /// Token bought
+    pub token_id: String,
+    /// Side bought
+    pub side: Side,
+    /// Paper fill time, the start of the window
+    pub filled_at: DateTime<Utc>,
+    /// End of the window
+    pub window_end: DateTime<Utc>,
+    /// Limit price of the order
+    pub order_price: Decimal,
+    /// Price the paper engine assumed, after slippage
+    pub fill_price: Decimal,
+    /// Shares filled
+    pub size: Decimal,
+    /// Fair value less book price of the signal
+    pub lag: Decimal,
+    /// Bid/ask spread of the signal's book
+    pub spread: Decimal,
+    /// Prints seen on the token in the window
+    pub prints: u64,
+    /// Shares printed at or below the fill price
+    pub available_size: Decimal,
+    /// Lowest price printed; `None` without prints
+    pub best_price: Option<Decimal>,
+    /// Highest price needed to buy the whole size from the prints, cheapest
+    /// first; `None` if less than the size printed in all
+    pub fill_level: Option<Decimal>,
+    /// Verdict on the fill price
+    pub attainability: Attainability,
+}
+
+impl ShadowFill {
+    /// Slippage over the order price a live order would have paid for the
+    /// whole size, if the prints covered it
+    pub fn required_slippage(&self) -> Option<Decimal> {
+        self.fill_level.map(|level| level - self.order_price)
+    }
+}
+
+/// A fill still being shadowed
+#[derive(Debug)]
+struct Watch {
+    fill: ShadowFill,
+    /// (price, size) of each print in the window
+    prints: Vec<(Decimal, Decimal)>,
+}
+
+impl Watch {
+    /// The fill with its verdict on the prints seen
+    fn finish(mut self) -> ShadowFill {
+        let fill = &mut self.fill;
+        fill.prints = self.prints.len() as u64;
+        fill.available_size = self
+            .prints
+            .iter()
+            .filter(|(price, _)| *price <= fill.fill_price)
+            .map(|(_, size)| size)
+            .sum();
+        self.prints.sort_by_key(|(price, _)| *price);
+        fill.best_price = self.prints.first().map(|(price, _)| *price);
+        let mut covered = Decimal::ZERO;
+        fill.fill_level = self.prints.iter().find_map(|(price, size)| {
+            covered += size;
+            (covered >= fill.size).then_some(*price)
+        });
+        fill.attainability = if fill.available_size >= fill.size {
+            Attainability::Attainable
+        } else if fill.available_size > Decimal::ZERO {
+            Attainability::Partial
+        } else {
+            Attainability::Unattainable
+        };
+        self.fill
+    }
+}
+
+/// Shadows paper entries against the trade prints that follow them
+#[derive(Debug, Default)]
+pub struct ShadowFillValidator {
+    config: ShadowConfig,
+    /// Fills whose window is still open, oldest first
+    pending: Vec<Watch>,
+    /// Fills whose window has closed
+    results: Vec<ShadowFill>,
+}
+
+impl ShadowFillValidator {
+    /// Validator with `config`; disabled, it watches nothing
+    pub fn new(config: ShadowConfig) -> Self {
+        Self {
+            config,
+            pending: vec![],
+            results: vec![],
+        }
+    }
+
+    /// Whether fills are shadowed
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Start shadowing `fill` of an order at `order_price` for `signal`
+    pub fn watch(&mut self, signal: &Signal, order_price: Decimal, fill: &Fill) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.pending.push(Watch {
+            fill: ShadowFill {
+                signal_id: signal.id.to_string(),
+                market_id: signal.market.condition_id.clone(),
+                token_id: fill.token_id.clone(),
+                side: fill.side,
+                filled_at: fill.timestamp,
+                window_end: fill.timestamp + self.config.window_secs.to_chrono(),
+                order_price,
+                fill_price: fill.price,
+                size: fill.size,
+                lag: signal.raw_edge,
+                spread: signal.spread,
+                prints: 0,
+                available_size: Decimal::ZERO,
+                best_price: None,
+                fill_level: None,
+                attainability: Attainability::Unattainable,
+            },
+            prints: vec![],
+        });
+    }
+
+    /// Count `print` towards every open window of its token
+    pub fn observe(&mut self, print: &TradePrint) {
+        for watch in &mut self.pending {
+            let fill = &watch.fill;
+            if fill.token_id == print.token_id
+                && print.timestamp > fill.filled_at
+                && print.timestamp <= fill.window_end
+            {
+                watch.prints.push((print.price, print.size));
+            }
+        }
+    }
+
+    /// Close the windows ended by `now`, returning their results
+    pub fn expire(&mut self, now: DateTime<Utc>) -> Vec<ShadowFill> {
+        let (ended, open) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|w| w.fill.window_end <= now);
+        self.pending = open;
+        self.close(ended)
+    }
+
+    /// Close every window, e.g. at shutdown, on the prints seen so far
+    pub fn finish(&mut self) -> Vec<ShadowFill> {
+        let pending = std::mem::take(&mut self.pending);
+        self.close(pending)
+    }
+
+    fn close(&mut self, watches: Vec<Watch>) -> Vec<ShadowFill> {
+        let finished: Vec<ShadowFill> = watches.into_iter().map(Watch::finish).collect();
+        self.results.extend(finished.iter().cloned());
+        finished
+    }
+
+    /// Fills shadowed to the end of their window, in closing order
+    pub fn results(&self) -> &[ShadowFill] {
+        &self.results
+    }
+
+    /// Fills still being watched
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::{LiquidityFlag, OrderAction};
+    use crate::market::Market;
+    use crate::signal::SignalReason;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+    use uuid::Uuid;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_735_689_600, 0).unwrap()
+    }
+
+    fn signal() -> Signal {
+        let market = Market {
+            condition_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes-token".to_string(),
+            no_token_id: "no-token".to_string(),
+            open_price: dec!(100000),
+            open_time: t0() - Duration::minutes(5),
+            close_time: t0() + Duration::minutes(10),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        Signal::new(
+            market,
+            Side::Yes,
+            dec!(0.62),
+            dec!(0.55),
+            dec!(0.05),
+            dec!(0.8),
+            SignalReason::SpotDivergence,
+        )
+        .with_spread(dec!(0.02))
+    }
+
+    /// 100 shares of YES ordered at 0.55 and filled at 0.56
+    fn fill() -> Fill {
+        Fill {
+            order_id: Uuid::new_v4(),
+            token_id: "yes-token".to_string(),
+            side: Side::Yes,
+            price: dec!(0.56),
+            size: dec!(100),
+            timestamp: t0(),
+            fee: Decimal::ZERO,
+            estimated_slippage: dec!(1),
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: "lag-1".to_string(),
+            simulated: true,
+        }
+    }
+
+    fn print(token: &str, secs: i64, price: Decimal, size: Decimal) -> TradePrint {
+        TradePrint {
+            token_id: token.to_string(),
+            price,
+            size,
+            timestamp: t0() + Duration::seconds(secs),
+        }
+    }
+
+    /// The result of shadowing `fill()` through `prints`
+    fn shadow(prints: &[TradePrint]) -> ShadowFill {
+        let mut validator = ShadowFillValidator::new(ShadowConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        validator.watch(&signal(), dec!(0.55), &fill());
+        for print in prints {
+            validator.observe(print);
+        }
+        assert!(validator.expire(t0() + Duration::seconds(29)).is_empty());
+        let mut results = validator.expire(t0() + Duration::seconds(30));
+        assert_eq!(validator.results().len(), 1);
+        results.remove(0)
+    }
+
+    #[test]
+    fn test_attainable_when_the_whole_size_prints_at_the_fill_price() {
+        let result = shadow(&[
+            print("yes-token", 2, dec!(0.57), dec!(50)),
+            print("yes-token", 5, dec!(0.56), dec!(60)),
+            print("yes-token", 9, dec!(0.55), dec!(40)),
+        ]);
+        assert_eq!(result.attainability, Attainability::Attainable);
+        assert_eq!(result.prints, 3);
+        assert_eq!(result.available_size, dec!(100));
+        assert_eq!(result.best_price, Some(dec!(0.55)));
+        // 40 at 0.55, then 60 at 0.56 covers the 100 shares
+        assert_eq!(result.fill_level, Some(dec!(0.56)));
+        assert_eq!(result.required_slippage(), Some(dec!(0.01)));
+        assert_eq!(result.lag, dec!(0.07));
+        assert_eq!(result.spread, dec!(0.02));
+    }
+
+    #[test]
+    fn test_partial_when_only_some_prints_at_the_fill_price() {
+        let result = shadow(&[
+            print("yes-token", 3, dec!(0.56), dec!(30)),
+            print("yes-token", 4, dec!(0.58), dec!(200)),
+        ]);
+        assert_eq!(result.attainability, Attainability::Partial);
+        assert_eq!(result.available_size, dec!(30));
+        assert_eq!(result.best_price, Some(dec!(0.56)));
+        assert_eq!(result.fill_level, Some(dec!(0.58)));
+        assert_eq!(result.required_slippage(), Some(dec!(0.03)));
+    }
+
+    #[test]
+    fn test_unattainable_without_prints_at_the_fill_price() {
+        // Above the price, on the other token, before the fill, after the window
+        let result = shadow(&[
+            print("yes-token", 1, dec!(0.60), dec!(20)),
+            print("no-token", 2, dec!(0.40), dec!(500)),
+            print("yes-token", 0, dec!(0.50), dec!(500)),
+            print("yes-token", 31, dec!(0.50), dec!(500)),
+        ]);
+        assert_eq!(result.attainability, Attainability::Unattainable);
+        assert_eq!(result.prints, 1);
+        assert_eq!(result.available_size, dec!(0));
+        assert_eq!(result.best_price, Some(dec!(0.60)));
+        assert_eq!(result.fill_level, None);
+        assert_eq!(result.required_slippage(), None);
+
+        let silent = shadow(&[]);
+        assert_eq!(silent.attainability, Attainability::Unattainable);
+        assert_eq!(silent.best_price, None);
+    }
+
+    #[test]
+    fn test_disabled_watches_nothing_and_finish_closes_all() {
+        let mut validator = ShadowFillValidator::default();
+        validator.watch(&signal(), dec!(0.55), &fill());
+        assert_eq!(validator.pending(), 0);
+
+        let mut validator = ShadowFillValidator::new(ShadowConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        validator.watch(&signal(), dec!(0.55), &fill());
+        validator.observe(&print("yes-token", 1, dec!(0.56), dec!(100)));
+        let finished = validator.finish();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].attainability, Attainability::Attainable);
+        assert_eq!(validator.pending(), 0);
+    }
+}
@@ -0,0 +1,345 @@
+//! Trade log export
+//!
+//! Writes fills to CSV or Parquet (chosen by file extension) for offline
+//! analysis. Decimals are stored as strings at their canonical scales.
+
+use super::{Fill, LiquidityFlag, OrderAction};
+use crate::data::writer_properties;
+use crate::fingerprint;
+use crate::precision::{round_price, round_size, round_usd};
+use crate::signal::Side;
+use anyhow::{anyhow, bail, Context};
+use arrow::array::{ArrayRef, StringArray, TimestampMicrosecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Utc};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use rust_decimal::Decimal;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Column order shared by the CSV and Parquet exports
+const COLUMNS: [&str; 11] = [
+    "timestamp",
+    "order_id",
+    "client_order_id",
+    "token_id",
+    "side",
+    "action",
+    "price",
+    "size",
+    "fee",
+    "estimated_slippage",
+    "liquidity",
+];
+
+/// Trade log Parquet schema
+pub fn trade_schema() -> Schema {
+    let mut fields = vec![Field::new(
+        "timestamp",
+        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        false,
+    )];
+    fields.extend(
+        COLUMNS[1..]
+            .iter()
+            .map(|name| Field::new(*name, DataType::Utf8, false)),
+    );
+    Schema::new(fields)
+}
+
+/// Write fills to `path`; `.parquet` writes Parquet, anything else CSV
+pub fn write_trades(path: &Path, fills: &[Fill]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if is_parquet(path) {
+        write_parquet(path, fills)
+    } else {
+        write_csv(path, fills)
+    }
+}
+
+/// Read fills back from a CSV or Parquet export
+pub fn read_trades(path: &Path) -> anyhow::Result<Vec<Fill>> {
+    if is_parquet(path) {
+        read_parquet(path)
+    } else {
+        read_csv(path)
+    }
+}
+
+fn is_parquet(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("parquet")
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Yes => "yes",
+        Side::No => "no",
+    }
+}
+
+fn action_name(action: OrderAction) -> &'static str {
+    match action {
+        OrderAction::Buy => "buy",
+        OrderAction::Sell => "sell",
+    }
+}
+
+/// String columns after the timestamp, in `COLUMNS` order
+fn string_fields(fill: &Fill) -> [String; 10] {
+    [
+        fill.order_id.to_string(),
+        fill.client_order_id.clone(),
+        fill.token_id.clone(),
+        side_name(fill.side).to_string(),
+        action_name(fill.action).to_string(),
+        round_price(fill.price).to_string(),
+        round_size(fill.size).to_string(),
+        round_usd(fill.fee).to_string(),
+        round_usd(fill.estimated_slippage).to_string(),
+        fill.liquidity.as_str().to_string(),
+    ]
+}
+
+fn parse_fill(timestamp: DateTime<Utc>, fields: &[&str]) -> anyhow::Result<Fill> {
+    if fields.len() != COLUMNS.len() - 1 {
+        bail!(
+            "expected {} columns, got {}",
+            COLUMNS.len(),
+            fields.len() + 1
+        );
+    }
+    let side = match fields[3] {
+        "yes" => Side::Yes,
+        "no" => Side::No,
+        other => bail!("unknown side: {}", other),
+    };
+    let action = match fields[4] {
+        "buy" => OrderAction::Buy,
+        "sell" => OrderAction::Sell,
+        other => bail!("unknown action: {}", other),
+    };
+    Ok(Fill {
+        order_id: fields[0].parse()?,
+        client_order_id: fields[1].to_string(),
+        token_id: fields[2].to_string(),
+        side,
+        action,
+        price: Decimal::from_str(fields[5])?,
+        size: Decimal::from_str(fields[6])?,
+        fee: Decimal::from_str(fields[7])?,
+        estimated_slippage: Decimal::from_str(fields[8])?,
+        liquidity: LiquidityFlag::from_name(fields[9])
+            .ok_or_else(|| anyhow!("unknown liquidity flag: {}", fields[9]))?,
+        timestamp,
+        simulated: false,
+    })
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or newline
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Split one CSV line, honouring double-quoted fields
+pub(crate) fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+fn write_csv(path: &Path, fills: &[Fill]) -> anyhow::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "{}", COLUMNS.join(","))?;
+    for fill in fills {
+        let mut row = vec![fill.timestamp.to_rfc3339()];
+        row.extend(string_fields(fill).iter().map(|f| csv_field(f)));
+        writeln!(out, "{}", row.join(","))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn read_csv(path: &Path) -> anyhow::Result<Vec<Fill>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut fills = vec![];
+    for (n, line) in reader.lines().enumerate().skip(1) {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(&line);
+        let timestamp = DateTime::parse_from_rfc3339(&fields[0])
+            .with_context(|| format!("line {}: bad timestamp", n + 1))?
+            .with_timezone(&Utc);
+        let rest: Vec<&str> = fields[1..].iter().map(String::as_str).collect();
+        fills.push(parse_fill(timestamp, &rest).with_context(|| format!("line {}", n + 1))?);
+    }
+    Ok(fills)
+}
+
+fn write_parquet(path: &Path, fills: &[Fill]) -> anyhow::Result<()> {
+    let schema = Arc::new(trade_schema());
+    let props = writer_properties(fingerprint::active());
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(props))?;
+
+    let timestamps: Vec<i64> = fills
+        .iter()
+        .map(|f| f.timestamp.timestamp_micros())
+        .collect();
+    let rows: Vec<[String; 10]> = fills.iter().map(string_fields).collect();
+
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(
+        TimestampMicrosecondArray::from(timestamps).with_timezone("UTC"),
+    )];
+    for i in 0..COLUMNS.len() - 1 {
+        columns.push(Arc::new(StringArray::from(
+            rows.iter().map(|r| r[i].as_str()).collect::<Vec<_>>(),
+        )));
+    }
+
+    writer.write(&RecordBatch::try_new(schema, columns)?)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn read_parquet(path: &Path) -> anyhow::Result<Vec<Fill>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    let mut fills = vec![];
+    for batch in reader {
+        let batch = batch?;
+        let timestamps = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .ok_or_else(|| anyhow!("timestamp column has wrong type"))?;
+        let strings = (1..COLUMNS.len())
+            .map(|i| {
+                batch
+                    .column(i)
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .ok_or_else(|| anyhow!("column {} has wrong type", COLUMNS[i]))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        for row in 0..batch.num_rows() {
+            let timestamp = DateTime::from_timestamp_micros(timestamps.value(row))
+                .ok_or_else(|| anyhow!("timestamp out of range"))?;
+            let fields: Vec<&str> = strings.iter().map(|c| c.value(row)).collect();
+            fills.push(parse_fill(timestamp, &fields)?);
+        }
+    }
+    Ok(fills)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    fn fills() -> Vec<Fill> {
+        vec![
+            Fill {
+                order_id: Uuid::new_v4(),
+                token_id: "yes-token".to_string(),
+                side: Side::Yes,
+                price: dec!(0.515),
+                size: dec!(100),
+                timestamp: DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap(),
+                fee: dec!(0.515),
+                estimated_slippage: dec!(1.5),
+                liquidity: LiquidityFlag::Taker,
+                action: OrderAction::Buy,
+                client_order_id: "lag-1".to_string(),
+                simulated: false,
+            },
+            Fill {
+                order_id: Uuid::new_v4(),
+                token_id: "no-token".to_string(),
+                side: Side::No,
+                price: dec!(0.45),
+                size: dec!(20),
+                timestamp: DateTime::from_timestamp_micros(1_700_000_060_000_000).unwrap(),
+                fee: dec!(0),
+                estimated_slippage: dec!(0),
+                liquidity: LiquidityFlag::Maker,
+                action: OrderAction::Sell,
+                client_order_id: "exit, \"quoted\"".to_string(),
+                simulated: false,
+            },
+        ]
+    }
+
+    fn assert_same(a: &[Fill], b: &[Fill]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert_eq!(x.order_id, y.order_id);
+            assert_eq!(x.client_order_id, y.client_order_id);
+            assert_eq!(x.token_id, y.token_id);
+            assert_eq!(x.side, y.side);
+            assert_eq!(x.action, y.action);
+            assert_eq!(x.price, y.price);
+            assert_eq!(x.size, y.size);
+            assert_eq!(x.fee, y.fee);
+            assert_eq!(x.estimated_slippage, y.estimated_slippage);
+            assert_eq!(x.liquidity, y.liquidity);
+            assert_eq!(x.timestamp, y.timestamp);
+        }
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("trades.csv");
+        let original = fills();
+        write_trades(&path, &original).unwrap();
+
+        let header = std::fs::read_to_string(&path).unwrap();
+        assert!(header.starts_with("timestamp,order_id,client_order_id,token_id"));
+        assert_same(&original, &read_trades(&path).unwrap());
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out").join("trades.parquet");
+        let original = fills();
+        write_trades(&path, &original).unwrap();
+        assert_same(&original, &read_trades(&path).unwrap());
+    }
+
+    #[test]
+    fn test_empty_export() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("trades.csv");
+        write_trades(&path, &[]).unwrap();
+        assert!(read_trades(&path).unwrap().is_empty());
+    }
+}
@@ -1,9 +1,12 @@
 //! Execution types
 
+use super::LiquidityFlag;
+use crate::precision::{round_price, round_size, round_usd};
 use crate::signal::Side;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use uuid::Uuid;
 
 /// Order identifier
@@ -18,6 +21,17 @@ pub enum OrderType {
     Limit,
 }
 
+/// Order direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderAction {
+    /// Buy tokens
+    #[default]
+    Buy,
+    /// Sell tokens
+    Sell,
+}
+
 /// An order to be submitted
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Order {
@@ -31,6 +45,12 @@ pub struct Order {
     pub size: Decimal,
     /// Order type
     pub order_type: OrderType,
+    /// Buy or sell
+    #[serde(default)]
+    pub action: OrderAction,
+    /// Caller-assigned identifier carried through to fills
+    #[serde(default)]
+    pub client_order_id: Option<String>,
 }
 
 /// A fill (executed trade)
@@ -48,8 +68,55 @@ pub struct Fill {
     pub size: Decimal,
     /// Fill timestamp
     pub timestamp: DateTime<Utc>,
-    /// Fees paid
-    pub fees: Decimal,
+    /// Exchange fee paid
+    #[serde(alias = "fees")]
+    pub fee: Decimal,
+    /// Estimated slippage cost versus the order price
+    #[serde(default)]
+    pub estimated_slippage: Decimal,
+    /// Maker or taker
+    #[serde(default)]
+    pub liquidity: LiquidityFlag,
+    /// Buy or sell
+    #[serde(default)]
+    pub action: OrderAction,
+    /// Client order ID of the originating order
+    #[serde(default)]
+    pub client_order_id: String,
+    /// Synthesized by a dry-run engine rather than any venue
+    #[serde(default)]
+    pub simulated: bool,
+}
+
+impl Fill {
+    /// Fee plus estimated slippage
+    pub fn total_cost(&self) -> Decimal {
+        self.fee + self.estimated_slippage
+    }
+
+    /// Round price, size and costs to the canonical scales
+    pub fn rounded(mut self) -> Self {
+        self.price = round_price(self.price);
+        self.size = round_size(self.size);
+        self.fee = round_usd(self.fee);
+        self.estimated_slippage = round_usd(self.estimated_slippage);
+        self
+    }
+}
+
+impl fmt::Display for Fill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:?} {:.2} @ {:.4} fee {:.4} ({})",
+            self.action,
+            self.side,
+            round_size(self.size),
+            round_price(self.price),
+            round_usd(self.fee),
+            self.liquidity.as_str()
+        )
+    }
 }
 
 #[cfg(test)]
@@ -84,6 +151,8 @@ mod tests {
             price: dec!(0.55),
             size: dec!(100),
             order_type: OrderType::Limit,
+            action: OrderAction::Buy,
+            client_order_id: None,
         };
 
         assert_eq!(order.token_id, "yes-token");
@@ -101,6 +170,8 @@ mod tests {
             price: dec!(0.55),
             size: dec!(100),
             order_type: OrderType::Limit,
+            action: OrderAction::Buy,
+            client_order_id: None,
         };
 
         let cloned = order.clone();
@@ -117,13 +188,18 @@ mod tests {
             price: dec!(0.55),
             size: dec!(100),
             timestamp: Utc::now(),
-            fees: dec!(0.5),
+            fee: dec!(0.5),
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: String::new(),
+            simulated: false,
         };
 
         assert_eq!(fill.token_id, "yes-token");
         assert_eq!(fill.side, Side::Yes);
         assert_eq!(fill.price, dec!(0.55));
-        assert_eq!(fill.fees, dec!(0.5));
+        assert_eq!(fill.fee, dec!(0.5));
     }
 
     #[test]
@@ -135,7 +211,12 @@ mod tests {
             price: dec!(0.55),
             size: dec!(100),
             timestamp: Utc::now(),
-            fees: dec!(0.5),
+            fee: dec!(0.5),
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: String::new(),
+            simulated: false,
         };
 
         let cloned = fill.clone();
@@ -143,6 +224,33 @@ mod tests {
         assert_eq!(fill.price, cloned.price);
     }
 
+    #[test]
+    fn test_fill_rounded_and_display() {
+        let fill = Fill {
+            order_id: Uuid::new_v4(),
+            token_id: "yes-token".to_string(),
+            side: Side::Yes,
+            price: dec!(0.113000000000000000001),
+            size: dec!(10.005),
+            timestamp: Utc::now(),
+            fee: dec!(0.0011300000001),
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: String::new(),
+            simulated: false,
+        }
+        .rounded();
+
+        assert_eq!(fill.price, dec!(0.113));
+        assert_eq!(fill.size, dec!(10.00));
+        assert_eq!(fill.fee, dec!(0.0011));
+        assert_eq!(
+            fill.to_string(),
+            "Buy Yes 10.00 @ 0.1130 fee 0.0011 (taker)"
+        );
+    }
+
     #[test]
     fn test_order_type_debug() {
         let order_type = OrderType::Market;
@@ -158,6 +266,8 @@ mod tests {
             price: dec!(0.50),
             size: dec!(10),
             order_type: OrderType::Market,
+            action: OrderAction::Buy,
+            client_order_id: None,
         };
         let debug_str = format!("{:?}", order);
         assert!(debug_str.contains("test"));
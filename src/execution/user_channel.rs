@@ -0,0 +1,544 @@
+//! Polymarket CLOB user channel
+//!
+//! An authenticated WebSocket subscription that streams order and trade
+//! events for our account. The subscription is re-sent on every reconnect,
+//! and trades matched while the socket was down are replayed from the REST
+//! trades endpoint so consumers see each fill once, whichever path it took.
+
+use super::clob::{LiveConfig, TradeHistory};
+use crate::telemetry::EventCode;
+use crate::ws::{WsClient, WsConfig, WsMessage};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// How far before the last event seen a reconnect replay starts
+pub const REPLAY_MARGIN_SECS: i64 = 30;
+
+/// Side of a CLOB order or trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ClobSide {
+    Buy,
+    Sell,
+}
+
+impl ClobSide {
+    fn opposite(self) -> Self {
+        match self {
+            Self::Buy => Self::Sell,
+            Self::Sell => Self::Buy,
+        }
+    }
+}
+
+/// Settlement progress of a trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TradeStatus {
+    Matched,
+    Mined,
+    Confirmed,
+    Retrying,
+    Failed,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Lifecycle step of an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OrderEventKind {
+    Placement,
+    Update,
+    Cancellation,
+    #[serde(other)]
+    Unknown,
+}
+
+/// One of our fills, from the user channel or the REST trades endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeEvent {
+    /// Trade id
+    pub id: String,
+    /// Our order that traded
+    pub order_id: String,
+    /// Token traded
+    pub asset_id: String,
+    /// Market condition id
+    pub market: String,
+    /// Our side of the trade
+    pub side: ClobSide,
+    /// Our fill price
+    pub price: Decimal,
+    /// Our filled shares
+    pub size: Decimal,
+    /// Settlement progress
+    pub status: TradeStatus,
+    /// Match time
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Placement, partial fill or cancellation of one of our orders
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderEvent {
+    /// Order id
+    pub id: String,
+    /// Token the order is for
+    pub asset_id: String,
+    /// Market condition id
+    pub market: String,
+    /// Order side
+    pub side: ClobSide,
+    /// Limit price
+    pub price: Decimal,
+    /// Shares ordered
+    pub original_size: Decimal,
+    /// Shares filled so far
+    pub size_matched: Decimal,
+    /// Lifecycle step
+    pub kind: OrderEventKind,
+    /// Event time
+    pub timestamp: DateTime<Utc>,
+}
+
+/// An event on the user channel
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserEvent {
+    Order(OrderEvent),
+    Trade(TradeEvent),
+}
+
+impl UserEvent {
+    /// When the event happened
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            Self::Order(order) => order.timestamp,
+            Self::Trade(trade) => trade.timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMakerOrder {
+    order_id: String,
+    #[serde(default)]
+    owner: String,
+    matched_amount: Decimal,
+    price: Decimal,
+}
+
+/// Trade as sent by the user channel and the REST trades endpoint
+#[derive(Debug, Deserialize)]
+pub(super) struct RawTrade {
+    id: String,
+    #[serde(default)]
+    taker_order_id: String,
+    #[serde(default)]
+    market: String,
+    asset_id: String,
+    side: ClobSide,
+    size: Decimal,
+    price: Decimal,
+    status: TradeStatus,
+    #[serde(default, alias = "matchtime")]
+    match_time: Option<String>,
+    #[serde(default)]
+    timestamp: Option<String>,
+    #[serde(default)]
+    maker_orders: Vec<RawMakerOrder>,
+}
+
+impl RawTrade {
+    /// Our side of the trade; `api_key` picks out our maker order, if any
+    pub(super) fn into_event(self, api_key: &str) -> Option<TradeEvent> {
+        let timestamp = self
+            .match_time
+            .as_deref()
+            .or(self.timestamp.as_deref())
+            .and_then(parse_unix)?;
+        let (order_id, side, price, size) =
+            match self.maker_orders.iter().find(|m| m.owner == api_key) {
+                Some(maker) => (
+                    maker.order_id.clone(),
+                    self.side.opposite(),
+                    maker.price,
+                    maker.matched_amount,
+                ),
+                None => (self.taker_order_id, self.side, self.price, self.size),
+            };
+        Some(TradeEvent {
+            id: self.id,
+            order_id,
+            asset_id: self.asset_id,
+            market: self.market,
+            side,
+            price,
+            size,
+            status: self.status,
+            timestamp,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawOrder {
+    id: String,
+    asset_id: String,
+    #[serde(default)]
+    market: String,
+    side: ClobSide,
+    price: Decimal,
+    original_size: Decimal,
+    size_matched: Decimal,
+    #[serde(rename = "type")]
+    kind: OrderEventKind,
+    timestamp: String,
+}
+
+/// Unix seconds or milliseconds, as a string
+fn parse_unix(value: &str) -> Option<DateTime<Utc>> {
+    let n: i64 = value.trim().parse().ok()?;
+    if n > 100_000_000_000 {
+        DateTime::from_timestamp_millis(n)
+    } else {
+        DateTime::from_timestamp(n, 0)
+    }
+}
+
+fn parse_event(value: serde_json::Value, api_key: &str) -> Option<UserEvent> {
+    match value.get("event_type")?.as_str()? {
+        "trade" => serde_json::from_value::<RawTrade>(value)
+            .ok()?
+            .into_event(api_key)
+            .map(UserEvent::Trade),
+        "order" => {
+            let raw: RawOrder = serde_json::from_value(value).ok()?;
+            Some(UserEvent::Order(OrderEvent {
+                timestamp: parse_unix(&raw.timestamp)?,
+                id: raw.id,
+                asset_id: raw.asset_id,
+                market: raw.market,
+                side: raw.side,
+                price: raw.price,
+                original_size: raw.original_size,
+                size_matched: raw.size_matched,
+                kind: raw.kind,
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Parse one user channel frame, an event or an array of events
+pub fn parse_user_message(text: &str, api_key: &str) -> Vec<UserEvent> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(text) else {
+        tracing::debug!(text, "Ignoring non-JSON user channel frame");
+        return vec![];
+    };
+    let values = match value {
+        serde_json::Value::Array(values) => values,
+        value => vec![value],
+    };
+    values
+        .into_iter()
+        .filter_map(|v| parse_event(v, api_key))
+        .collect()
+}
+
+/// Subscription frame authenticating the user channel for `markets`
+pub fn subscription_message(config: &LiveConfig, markets: &[String]) -> String {
+    serde_json::json!({
+        "auth": {
+            "apiKey": config.api_key,
+            "secret": config.secret,
+            "passphrase": config.passphrase,
+        },
+        "markets": markets,
+        "type": "user",
+    })
+    .to_string()
+}
+
+/// Client for the user channel
+pub struct UserChannel {
+    ws: WsConfig,
+    subscription: String,
+    api_key: String,
+}
+
+impl UserChannel {
+    /// Channel for our orders on `markets`, reconnecting indefinitely
+    pub fn new(config: &LiveConfig, markets: &[String]) -> Self {
+        Self {
+            ws: WsConfig::new(&config.user_ws_url)
+                .max_reconnects(0)
+                .initial_delay(std::time::Duration::from_secs(1))
+                .max_delay(std::time::Duration::from_secs(30))
+                .ping_interval(std::time::Duration::from_secs(10)),
+            subscription: subscription_message(config, markets),
+            api_key: config.api_key.clone(),
+        }
+    }
+
+    /// Use `ws` for the connection instead of the defaults
+    pub fn with_ws_config(mut self, ws: WsConfig) -> Self {
+        self.ws = ws;
+        self
+    }
+
+    /// Connect and stream our order and trade events
+    ///
+    /// After a reconnect, trades since shortly before the last event seen
+    /// are fetched from `history` and the ones the socket missed are
+    /// emitted before any new live events.
+    pub fn connect(self, history: Arc<dyn TradeHistory>) -> mpsc::Receiver<UserEvent> {
+        let (tx, rx) = mpsc::channel(1024);
+        let (ws_rx, send_tx) = WsClient::new(self.ws.clone()).connect_bidirectional();
+        tokio::spawn(async move {
+            self.run(ws_rx, send_tx.inner().clone(), history, tx).await;
+        });
+        rx
+    }
+
+    async fn run(
+        self,
+        mut ws_rx: mpsc::Receiver<WsMessage>,
+        send_tx: mpsc::Sender<String>,
+        history: Arc<dyn TradeHistory>,
+        tx: mpsc::Sender<UserEvent>,
+    ) {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut last_event: Option<DateTime<Utc>> = None;
+        let mut connected_before = false;
+
+        while let Some(msg) = ws_rx.recv().await {
+            match msg {
+                WsMessage::Connected => {
+                    if send_tx.send(self.subscription.clone()).await.is_err() {
+                        break;
+                    }
+                    tracing::info!("User channel subscribed");
+                    if connected_before {
+                        let since = last_event.unwrap_or_else(Utc::now)
+                            - Duration::seconds(REPLAY_MARGIN_SECS);
+                        match history.trades_since(since).await {
+                            Ok(trades) => {
+                                let missed: Vec<TradeEvent> = trades
+                                    .into_iter()
+                                    .filter(|t| seen.insert(t.id.clone()))
+                                    .collect();
+                                tracing::info!(
+                                    since = %since,
+                                    missed = missed.len(),
+                                    "Replayed user channel trades after reconnect"
+                                );
+                                for trade in missed {
+                                    if tx.send(UserEvent::Trade(trade)).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                            Err(e) => tracing::warn!(
+                                error = %e,
+                                "Trade replay failed; reconciliation will catch missed fills"
+                            ),
+                        }
+                    }
+                    connected_before = true;
+                }
+                WsMessage::Text(text) => {
+                    for event in parse_user_message(&text, &self.api_key) {
+                        if let UserEvent::Trade(trade) = &event {
+                            seen.insert(trade.id.clone());
+                        }
+                        last_event = last_event.max(Some(event.timestamp()));
+                        if tx.send(event).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                WsMessage::Reconnecting { attempt } => {
+                    tracing::warn!(attempt, "User channel reconnecting...");
+                }
+                WsMessage::Disconnected => {
+                    tracing::warn!(event_code = %EventCode::WsDisconnected, feed = "user", "User channel disconnected");
+                    break;
+                }
+                WsMessage::Binary(_) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures_util::{SinkExt, StreamExt};
+    use rust_decimal_macros::dec;
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message;
+
+    const API_KEY: &str = "key-1";
+
+    fn live_config(ws_url: &str) -> LiveConfig {
+        toml::from_str(&format!(
+            r#"
+            user_ws_url = "{ws_url}"
+            address = "0xabc"
+            api_key = "{API_KEY}"
+            secret = "c2VjcmV0"
+            passphrase = "hunter2"
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn trade_json(id: &str, size: &str, at: i64) -> String {
+        serde_json::json!({
+            "event_type": "trade",
+            "id": id,
+            "taker_order_id": "0xtaker",
+            "market": "0xmarket",
+            "asset_id": "yes-token",
+            "side": "BUY",
+            "size": size,
+            "price": "0.57",
+            "status": "MATCHED",
+            "matchtime": at.to_string(),
+            "maker_orders": [],
+        })
+        .to_string()
+    }
+
+    fn trade(id: &str, size: Decimal, at: i64) -> TradeEvent {
+        let raw: RawTrade = serde_json::from_str(&trade_json(id, &size.to_string(), at)).unwrap();
+        raw.into_event(API_KEY).unwrap()
+    }
+
+    struct FakeHistory {
+        trades: Vec<TradeEvent>,
+        calls: Mutex<Vec<DateTime<Utc>>>,
+    }
+
+    #[async_trait]
+    impl TradeHistory for FakeHistory {
+        async fn trades_since(&self, since: DateTime<Utc>) -> anyhow::Result<Vec<TradeEvent>> {
+            self.calls.lock().unwrap().push(since);
+            Ok(self.trades.clone())
+        }
+    }
+
+    #[test]
+    fn test_parse_order_and_maker_trade() {
+        let order = r#"{"asset_id":"yes-token","associate_trades":null,"event_type":"order",
+            "id":"0xff35","market":"0xbd31","order_owner":"key-1","original_size":"10",
+            "outcome":"YES","owner":"key-1","price":"0.57","side":"SELL","size_matched":"0",
+            "timestamp":"1672290687","type":"PLACEMENT"}"#;
+        let maker_trade = r#"[{"asset_id":"yes-token","event_type":"trade","id":"28c4",
+            "maker_orders":[{"asset_id":"yes-token","matched_amount":"4","order_id":"0xff35",
+            "outcome":"YES","owner":"key-1","price":"0.57"}],"market":"0xbd31",
+            "matchtime":"1672290701","owner":"other","price":"0.57","side":"BUY","size":"10",
+            "status":"MATCHED","taker_order_id":"0x06bc","timestamp":"1672290701123","type":"TRADE"}]"#;
+
+        let events = parse_user_message(order, API_KEY);
+        let UserEvent::Order(order) = &events[0] else {
+            panic!("expected an order event, got {events:?}");
+        };
+        assert_eq!(order.kind, OrderEventKind::Placement);
+        assert_eq!(order.original_size, dec!(10));
+        assert_eq!(order.side, ClobSide::Sell);
+
+        let events = parse_user_message(maker_trade, API_KEY);
+        let UserEvent::Trade(trade) = &events[0] else {
+            panic!("expected a trade event, got {events:?}");
+        };
+        // We were the resting sell the taker bought from
+        assert_eq!(trade.order_id, "0xff35");
+        assert_eq!(trade.side, ClobSide::Sell);
+        assert_eq!(trade.size, dec!(4));
+        assert_eq!(trade.timestamp.timestamp(), 1_672_290_701);
+
+        assert!(parse_user_message("PONG", API_KEY).is_empty());
+        assert!(parse_user_message(r#"{"event_type":"book"}"#, API_KEY).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_auth_resubscribe_and_replay_against_mock_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (sub_tx, mut sub_rx) = mpsc::unbounded_channel();
+
+        // First connection sends two events then drops without a close
+        // frame; the second sends one more after resubscription
+        let frames = vec![
+            vec![
+                r#"{"event_type":"order","id":"0xorder","asset_id":"yes-token","side":"BUY","price":"0.57","original_size":"10","size_matched":"0","type":"PLACEMENT","timestamp":"1700000000"}"#.to_string(),
+                trade_json("t1", "4", 1_700_000_001),
+            ],
+            vec![trade_json("t3", "2", 1_700_000_010)],
+        ];
+        tokio::spawn(async move {
+            for frames in frames {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                let Some(Ok(Message::Text(auth))) = ws.next().await else {
+                    panic!("client did not subscribe");
+                };
+                sub_tx.send(auth).unwrap();
+                for frame in frames {
+                    ws.send(Message::Text(frame)).await.unwrap();
+                }
+                drop(ws);
+            }
+            // Keep the listener open so the client idles rather than spins
+            std::future::pending::<()>().await;
+        });
+
+        let history = Arc::new(FakeHistory {
+            trades: vec![
+                trade("t1", dec!(4), 1_700_000_001),
+                trade("t2", dec!(6), 1_700_000_005),
+            ],
+            calls: Mutex::new(vec![]),
+        });
+        let ws = WsConfig::new(&url).initial_delay(std::time::Duration::from_millis(10));
+        let mut events = UserChannel::new(&live_config(&url), &["0xmarket".to_string()])
+            .with_ws_config(ws)
+            .connect(history.clone());
+
+        let mut ids = vec![];
+        for _ in 0..4 {
+            let event = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            ids.push(match event {
+                UserEvent::Order(order) => order.id,
+                UserEvent::Trade(trade) => trade.id,
+            });
+        }
+        assert_eq!(ids, vec!["0xorder", "t1", "t2", "t3"]);
+
+        for _ in 0..2 {
+            let auth: serde_json::Value =
+                serde_json::from_str(&sub_rx.recv().await.unwrap()).unwrap();
+            assert_eq!(auth["type"], "user");
+            assert_eq!(auth["auth"]["apiKey"], API_KEY);
+            assert_eq!(auth["auth"]["secret"], "c2VjcmV0");
+            assert_eq!(auth["auth"]["passphrase"], "hunter2");
+            assert_eq!(auth["markets"][0], "0xmarket");
+        }
+
+        let calls = history.calls.lock().unwrap().clone();
+        assert_eq!(
+            calls,
+            vec![DateTime::from_timestamp(1_700_000_001 - REPLAY_MARGIN_SECS, 0).unwrap()]
+        );
+    }
+}
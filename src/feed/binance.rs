@@ -1,6 +1,7 @@
 //! Binance WebSocket price feed implementation
 
-use super::{PriceFeed, PriceTick};
+use super::{PriceFeed, PriceTick, TickSource};
+use crate::telemetry::{instrumented_channel, EventCode};
 use crate::ws::{WsClient, WsConfig, WsMessage};
 use async_trait::async_trait;
 use chrono::{TimeZone, Utc};
@@ -11,7 +12,7 @@ use std::time::Duration;
 use tokio::sync::mpsc;
 
 /// Binance WebSocket base URL
-const BINANCE_WS_URL: &str = "wss://stream.binance.com:9443/ws";
+pub const BINANCE_WS_URL: &str = "wss://stream.binance.com:9443/ws";
 
 /// Binance trade message structure
 #[derive(Debug, Deserialize)]
@@ -61,7 +62,7 @@ impl BinanceFeed {
     }
 
     /// Parse a Binance trade message into a PriceTick
-    fn parse_message(msg: &str) -> Option<PriceTick> {
+    pub(crate) fn parse_message(msg: &str) -> Option<PriceTick> {
         let trade: BinanceTradeMessage = serde_json::from_str(msg).ok()?;
 
         if trade.event_type != "trade" {
@@ -74,9 +75,11 @@ impl BinanceFeed {
 
         Some(PriceTick {
             symbol: trade.symbol,
+            asset: String::new(),
             price,
             timestamp,
             exchange_ts,
+            source: TickSource::Trade,
         })
     }
 
@@ -99,7 +102,7 @@ impl BinanceFeed {
                     tracing::info!("Binance feed connected");
                 }
                 WsMessage::Disconnected => {
-                    tracing::warn!("Binance feed disconnected");
+                    tracing::warn!(event_code = %EventCode::WsDisconnected, feed = "binance", "Binance feed disconnected");
                     break;
                 }
                 WsMessage::Reconnecting { attempt } => {
@@ -116,7 +119,7 @@ impl BinanceFeed {
 #[async_trait]
 impl PriceFeed for BinanceFeed {
     async fn subscribe(&self) -> anyhow::Result<mpsc::Receiver<PriceTick>> {
-        let (tick_tx, tick_rx) = mpsc::channel(1024);
+        let (tick_tx, tick_rx) = instrumented_channel("feed_ticks", 1024);
         let url = self.build_ws_url();
 
         tracing::info!(symbol = %self.symbol, "Subscribing to Binance feed");
@@ -133,7 +136,7 @@ impl PriceFeed for BinanceFeed {
 
         // Spawn message processing task
         tokio::spawn(async move {
-            Self::run_message_loop(ws_rx, tick_tx).await;
+            Self::run_message_loop(ws_rx, tick_tx.inner().clone()).await;
         });
 
         Ok(tick_rx)
@@ -0,0 +1,144 @@
+//! Recent price history with point-in-time lookup
+//!
+//! Used to look up the spot price at a market's window start (the strike)
+//! and to detect feed gaps that need a kline backfill.
+
+use super::PriceTick;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+
+/// Time-ordered ring of recent ticks
+pub struct PriceHistory {
+    max_age: Duration,
+    ticks: VecDeque<PriceTick>,
+}
+
+impl PriceHistory {
+    /// Keep ticks for `max_age` behind the newest one
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            ticks: VecDeque::new(),
+        }
+    }
+
+    /// Add a tick, keeping time order and evicting old ticks
+    pub fn push(&mut self, tick: PriceTick) {
+        let ts = tick.exchange_ts;
+        if self.ticks.back().is_none_or(|last| last.exchange_ts <= ts) {
+            self.ticks.push_back(tick);
+        } else {
+            // Backfilled ticks land behind live ones
+            let idx = self.ticks.partition_point(|t| t.exchange_ts <= ts);
+            self.ticks.insert(idx, tick);
+        }
+
+        if let Some(newest) = self.ticks.back().map(|t| t.exchange_ts) {
+            let cutoff = newest - self.max_age;
+            while self.ticks.front().is_some_and(|t| t.exchange_ts < cutoff) {
+                self.ticks.pop_front();
+            }
+        }
+    }
+
+    /// Add many ticks
+    pub fn extend(&mut self, ticks: impl IntoIterator<Item = PriceTick>) {
+        for tick in ticks {
+            self.push(tick);
+        }
+    }
+
+    /// Price of the latest tick at or before `at`
+    pub fn price_at(&self, at: DateTime<Utc>) -> Option<Decimal> {
+        let idx = self.ticks.partition_point(|t| t.exchange_ts <= at);
+        idx.checked_sub(1).map(|i| self.ticks[i].price)
+    }
+
+    /// Most recent tick
+    pub fn latest(&self) -> Option<&PriceTick> {
+        self.ticks.back()
+    }
+
+    /// Most recent live (non-kline) tick
+    pub fn latest_trade(&self) -> Option<&PriceTick> {
+        self.ticks.iter().rev().find(|t| !t.is_kline())
+    }
+
+    /// True if there is no tick within `max_gap` of `now`
+    pub fn has_gap(&self, now: DateTime<Utc>, max_gap: Duration) -> bool {
+        self.latest().is_none_or(|t| now - t.exchange_ts > max_gap)
+    }
+
+    /// Number of ticks held
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+
+    /// Whether no ticks are held
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::TickSource;
+    use rust_decimal_macros::dec;
+
+    fn tick(secs: i64, price: Decimal, source: TickSource) -> PriceTick {
+        let ts = DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        PriceTick {
+            symbol: "BTCUSDT".to_string(),
+            asset: "BTC".to_string(),
+            price,
+            timestamp: ts,
+            exchange_ts: ts,
+            source,
+        }
+    }
+
+    #[test]
+    fn test_price_at_and_eviction() {
+        let mut history = PriceHistory::new(Duration::seconds(60));
+        history.push(tick(0, dec!(100), TickSource::Trade));
+        history.push(tick(30, dec!(101), TickSource::Trade));
+        history.push(tick(60, dec!(102), TickSource::Trade));
+
+        let at = |secs: i64| DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        assert_eq!(history.price_at(at(-1)), None);
+        assert_eq!(history.price_at(at(0)), Some(dec!(100)));
+        assert_eq!(history.price_at(at(45)), Some(dec!(101)));
+
+        history.push(tick(90, dec!(103), TickSource::Trade));
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.price_at(at(10)), None);
+    }
+
+    #[test]
+    fn test_backfill_inserts_in_order() {
+        let mut history = PriceHistory::new(Duration::hours(1));
+        history.push(tick(120, dec!(105), TickSource::Trade));
+        history.extend([
+            tick(0, dec!(100), TickSource::Kline),
+            tick(60, dec!(101), TickSource::Kline),
+        ]);
+
+        let at = |secs: i64| DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        assert_eq!(history.price_at(at(90)), Some(dec!(101)));
+        assert_eq!(history.latest().unwrap().price, dec!(105));
+        assert_eq!(history.latest_trade().unwrap().price, dec!(105));
+    }
+
+    #[test]
+    fn test_gap_detection() {
+        let mut history = PriceHistory::new(Duration::hours(1));
+        let now = DateTime::from_timestamp(1_700_000_030, 0).unwrap();
+        assert!(history.has_gap(now, Duration::seconds(10)));
+
+        history.push(tick(25, dec!(100), TickSource::Trade));
+        assert!(!history.has_gap(now, Duration::seconds(10)));
+        assert!(history.has_gap(now + Duration::seconds(20), Duration::seconds(10)));
+    }
+}
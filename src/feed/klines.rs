@@ -0,0 +1,241 @@
+//! Binance REST klines for history backfill
+//!
+//! At startup (and after a feed gap) the live stream has no history, so
+//! recent 1m klines are fetched and turned into synthetic ticks flagged
+//! `TickSource::Kline` to seed the volatility estimator and strike lookup.
+
+use super::{PriceHistory, PriceTick, TickSource};
+use crate::model::VolatilityEstimator;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// Binance spot REST base URL
+pub const BINANCE_REST_URL: &str = "https://api.binance.com";
+
+/// One OHLC candle
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kline {
+    /// Candle open time
+    pub open_time: DateTime<Utc>,
+    /// Candle close time
+    pub close_time: DateTime<Utc>,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    /// Base asset volume
+    pub volume: Decimal,
+}
+
+impl Kline {
+    /// Parse one row of the `/api/v3/klines` array response
+    fn from_row(row: &[serde_json::Value]) -> Option<Self> {
+        let time = |i: usize| DateTime::from_timestamp_millis(row.get(i)?.as_i64()?);
+        let dec = |i: usize| Decimal::from_str(row.get(i)?.as_str()?).ok();
+        Some(Self {
+            open_time: time(0)?,
+            open: dec(1)?,
+            high: dec(2)?,
+            low: dec(3)?,
+            close: dec(4)?,
+            volume: dec(5)?,
+            close_time: time(6)?,
+        })
+    }
+}
+
+/// Synthetic ticks for a run of klines: each candle's open at its open time,
+/// plus the last candle's close at its close time
+pub fn klines_to_ticks(symbol: &str, klines: &[Kline]) -> Vec<PriceTick> {
+    let tick = |price, ts| PriceTick {
+        symbol: symbol.to_uppercase(),
+        asset: String::new(),
+        price,
+        timestamp: ts,
+        exchange_ts: ts,
+        source: TickSource::Kline,
+    };
+    let mut ticks: Vec<_> = klines.iter().map(|k| tick(k.open, k.open_time)).collect();
+    if let Some(last) = klines.last() {
+        ticks.push(tick(last.close, last.close_time));
+    }
+    ticks
+}
+
+/// Seed the strike lookup and volatility estimator with backfilled ticks
+pub fn seed_history(
+    ticks: &[PriceTick],
+    history: &mut PriceHistory,
+    volatility: &mut VolatilityEstimator,
+) {
+    for tick in ticks {
+        volatility.update(tick.exchange_ts, tick.price);
+    }
+    history.extend(ticks.iter().cloned());
+}
+
+/// Client for Binance's klines endpoint
+pub struct KlineClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl KlineClient {
+    /// Create a client against the public Binance API
+    pub fn new() -> Self {
+        Self::with_base_url(BINANCE_REST_URL)
+    }
+
+    /// Create a client against a custom base URL
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Fetch the most recent `limit` klines (Binance caps this at 1000)
+    pub async fn fetch_klines(
+        &self,
+        symbol: &str,
+        interval: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Kline>> {
+        let rows: Vec<Vec<serde_json::Value>> = self
+            .http
+            .get(format!("{}/api/v3/klines", self.base_url))
+            .query(&[
+                ("symbol", symbol.to_uppercase()),
+                ("interval", interval.to_string()),
+                ("limit", limit.clamp(1, 1000).to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let klines: Vec<Kline> = rows.iter().filter_map(|r| Kline::from_row(r)).collect();
+        if klines.len() < rows.len() {
+            tracing::warn!(
+                skipped = rows.len() - klines.len(),
+                "Skipped malformed kline rows"
+            );
+        }
+        Ok(klines)
+    }
+
+    /// Fetch recent 1m klines as synthetic ticks
+    pub async fn backfill_ticks(
+        &self,
+        symbol: &str,
+        minutes: usize,
+    ) -> anyhow::Result<Vec<PriceTick>> {
+        let klines = self.fetch_klines(symbol, "1m", minutes).await?;
+        tracing::info!(symbol, klines = klines.len(), "Fetched kline backfill");
+        Ok(klines_to_ticks(symbol, &klines))
+    }
+}
+
+impl Default for KlineClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const START_MS: i64 = 1_700_000_040_000; // a minute boundary
+
+    fn rows(n: i64) -> serde_json::Value {
+        let rows: Vec<_> = (0..n)
+            .map(|i| {
+                let open = 37_000 + i * 10 + (i % 2) * 5;
+                serde_json::json!([
+                    START_MS + i * 60_000,
+                    format!("{}.00", open),
+                    format!("{}.00", open + 8),
+                    format!("{}.00", open - 3),
+                    format!("{}.00", open + 10),
+                    "12.5",
+                    START_MS + i * 60_000 + 59_999,
+                    "0",
+                    100,
+                    "0",
+                    "0",
+                    "0"
+                ])
+            })
+            .collect();
+        serde_json::Value::Array(rows)
+    }
+
+    async fn server(n: i64) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v3/klines"))
+            .and(query_param("symbol", "BTCUSDT"))
+            .and(query_param("interval", "1m"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(rows(n)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_fetch_klines_parses_rows() {
+        let server = server(3).await;
+        let client = KlineClient::with_base_url(server.uri());
+        let klines = client.fetch_klines("btcusdt", "1m", 3).await.unwrap();
+
+        assert_eq!(klines.len(), 3);
+        assert_eq!(klines[0].open, dec!(37000.00));
+        assert_eq!(klines[1].high, dec!(37023.00));
+        assert_eq!(
+            klines[2].close_time - klines[2].open_time,
+            Duration::milliseconds(59_999)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backfill_seeds_vol_and_strike_lookup() {
+        let server = server(30).await;
+        let client = KlineClient::with_base_url(server.uri());
+        let ticks = client.backfill_ticks("btcusdt", 30).await.unwrap();
+
+        assert_eq!(ticks.len(), 31);
+        assert!(ticks.iter().all(|t| t.is_kline()));
+
+        let mut history = PriceHistory::new(Duration::hours(2));
+        let mut volatility = VolatilityEstimator::new(Duration::hours(1));
+        assert!(volatility.estimate().is_none());
+        seed_history(&ticks, &mut history, &mut volatility);
+
+        // Usable immediately: vol estimate exists and a window start resolves
+        assert!(volatility.estimate().unwrap() > Decimal::ZERO);
+        let window_start = DateTime::from_timestamp_millis(START_MS + 15 * 60_000).unwrap();
+        assert_eq!(history.price_at(window_start), Some(dec!(37155.00)));
+
+        // Nothing live yet: consumers that need real trades stay cold
+        assert!(history.latest_trade().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_klines_http_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&server)
+            .await;
+        let client = KlineClient::with_base_url(server.uri());
+        assert!(client.fetch_klines("btcusdt", "1m", 10).await.is_err());
+    }
+}
@@ -0,0 +1,629 @@
+//! Tick lag monitoring and catch-up for the detection loop
+//!
+//! Lag is measured when the consumer dequeues a tick, not when the feed
+//! produces it, so a stalled consumer shows up as lag even though the feed
+//! itself is healthy. When the p95 lag over a short window exceeds the
+//! threshold the consumer drains its channel, keeping only the newest tick
+//! per symbol, instead of acting on a backlog of stale prices.
+
+use super::PriceTick;
+use crate::duration::{DurationConfig, Millis};
+use crate::journal::Journal;
+use crate::telemetry::{instrumented_channel, EventCode};
+use crate::telemetry::{record_latency, record_tick_batch, record_ticks_skipped, LatencyMetric};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::mpsc;
+
+/// Journal kind written when the consumer enters catch-up mode
+pub const LAG_DEGRADED_KIND: &str = "tick_lag_degraded";
+
+/// Journal kind written when lag falls back under the threshold
+pub const LAG_RECOVERED_KIND: &str = "tick_lag_recovered";
+
+/// Default most ticks drained into one detection pass
+pub const DEFAULT_MAX_TICK_BATCH: usize = 256;
+
+/// Tick lag thresholds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickLagConfig {
+    /// p95 lag above this switches to catch-up mode
+    #[serde(default = "default_p95_threshold_ms")]
+    pub p95_threshold_ms: DurationConfig<Millis>,
+    /// Window the p95 is computed over
+    #[serde(default = "default_window_ms")]
+    pub window_ms: DurationConfig<Millis>,
+    /// Samples needed in the window before acting on the p95
+    #[serde(default = "default_min_samples")]
+    pub min_samples: usize,
+    /// Local clock minus exchange clock, subtracted from every measurement
+    #[serde(default)]
+    pub clock_offset_ms: i64,
+    /// Buffer of the detection loop's feed subscription
+    #[serde(default = "default_channel_capacity")]
+    pub channel_capacity: usize,
+    /// Most ticks the detection loop takes off the queue per pass; 1
+    /// processes every tick on its own
+    #[serde(default = "default_max_batch")]
+    pub max_batch: usize,
+}
+
+fn default_p95_threshold_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(500)
+}
+
+fn default_window_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(5_000)
+}
+
+fn default_min_samples() -> usize {
+    10
+}
+
+fn default_channel_capacity() -> usize {
+    1024
+}
+
+fn default_max_batch() -> usize {
+    DEFAULT_MAX_TICK_BATCH
+}
+
+impl Default for TickLagConfig {
+    fn default() -> Self {
+        Self {
+            p95_threshold_ms: default_p95_threshold_ms(),
+            window_ms: default_window_ms(),
+            min_samples: default_min_samples(),
+            clock_offset_ms: 0,
+            channel_capacity: default_channel_capacity(),
+            max_batch: default_max_batch(),
+        }
+    }
+}
+
+/// Rolling window of dequeue lag samples
+pub struct TickLagMonitor {
+    config: TickLagConfig,
+    samples: VecDeque<(DateTime<Utc>, i64)>,
+}
+
+impl TickLagMonitor {
+    /// Create a monitor
+    pub fn new(config: TickLagConfig) -> Self {
+        Self {
+            config,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record the lag of a tick dequeued at `now` and return it in ms
+    pub fn observe(&mut self, tick: &PriceTick, now: DateTime<Utc>) -> i64 {
+        let lag_ms = (now - tick.exchange_ts).num_milliseconds() - self.config.clock_offset_ms;
+        if let Ok(lag) = Duration::milliseconds(lag_ms).to_std() {
+            record_latency(LatencyMetric::TickLag, lag);
+        }
+
+        self.samples.push_back((now, lag_ms));
+        let cutoff = now - self.config.window_ms.to_chrono();
+        while self.samples.front().is_some_and(|(t, _)| *t < cutoff) {
+            self.samples.pop_front();
+        }
+        lag_ms
+    }
+
+    /// 95th percentile lag over the window, if enough samples exist
+    pub fn p95(&self) -> Option<i64> {
+        if self.samples.is_empty() || self.samples.len() < self.config.min_samples {
+            return None;
+        }
+        let mut lags: Vec<i64> = self.samples.iter().map(|(_, lag)| *lag).collect();
+        lags.sort_unstable();
+        let idx = ((lags.len() as f64 * 0.95).ceil() as usize).saturating_sub(1);
+        Some(lags[idx])
+    }
+
+    /// Whether the p95 is over the threshold
+    pub fn is_lagging(&self) -> bool {
+        self.p95().is_some_and(|p95| p95 > self.threshold_ms())
+    }
+
+    /// Forget all samples, e.g. after the backlog they measured is dropped
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Configured threshold
+    pub fn threshold_ms(&self) -> i64 {
+        self.config.p95_threshold_ms.as_millis() as i64
+    }
+}
+
+/// Highest lag a [`LagHistogram`] tells apart; slower ticks count as this
+pub const LAG_HISTOGRAM_MAX_MS: i64 = 10_000;
+
+/// Lag distribution over a whole session at 1ms resolution
+///
+/// Unlike [`TickLagMonitor`] it never forgets a sample, so its percentiles
+/// describe the session rather than the last few seconds.
+#[derive(Debug, Clone)]
+pub struct LagHistogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl LagHistogram {
+    /// Create an empty histogram
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; LAG_HISTOGRAM_MAX_MS as usize + 1],
+            total: 0,
+        }
+    }
+
+    /// Count one lag sample, clamped to `0..=LAG_HISTOGRAM_MAX_MS`
+    pub fn record(&mut self, lag_ms: i64) {
+        self.counts[lag_ms.clamp(0, LAG_HISTOGRAM_MAX_MS) as usize] += 1;
+        self.total += 1;
+    }
+
+    /// Samples recorded
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    /// Lag at quantile `q` (0 to 1), if any samples exist
+    pub fn percentile(&self, q: f64) -> Option<i64> {
+        if self.total == 0 {
+            return None;
+        }
+        let rank = ((self.total as f64 * q).ceil() as u64).max(1);
+        let mut seen = 0;
+        self.counts
+            .iter()
+            .position(|count| {
+                seen += count;
+                seen >= rank
+            })
+            .map(|ms| ms as i64)
+    }
+}
+
+impl Default for LagHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Catch-up counters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LagStats {
+    /// Ticks handed to the consumer
+    pub ticks_delivered: u64,
+    /// Stale ticks discarded in catch-up mode
+    pub ticks_skipped: u64,
+    /// Number of catch-up drains
+    pub catch_ups: u64,
+    /// Batches handed out by [`LagAwareReceiver::recv_batch`]
+    pub batches: u64,
+}
+
+/// Queue of price ticks a [`LagAwareReceiver`] reads from
+#[async_trait]
+pub trait TickStream: Send {
+    /// Next tick; `None` once the source is closed and drained
+    async fn next_tick(&mut self) -> Option<PriceTick>;
+    /// Next already-queued tick, without waiting
+    fn try_next_tick(&mut self) -> Option<PriceTick>;
+}
+
+#[async_trait]
+impl TickStream for mpsc::Receiver<PriceTick> {
+    async fn next_tick(&mut self) -> Option<PriceTick> {
+        self.recv().await
+    }
+
+    fn try_next_tick(&mut self) -> Option<PriceTick> {
+        self.try_recv().ok()
+    }
+}
+
+/// Price tick receiver that measures dequeue lag and catches up when behind
+pub struct LagAwareReceiver<S = mpsc::Receiver<PriceTick>> {
+    rx: S,
+    monitor: TickLagMonitor,
+    session: LagHistogram,
+    pending: VecDeque<PriceTick>,
+    degraded: bool,
+    stats: LagStats,
+    journal: Option<Journal>,
+}
+
+impl<S: TickStream> LagAwareReceiver<S> {
+    /// Wrap a tick receiver
+    pub fn new(rx: S, config: TickLagConfig) -> Self {
+        Self {
+            rx,
+            monitor: TickLagMonitor::new(config),
+            session: LagHistogram::new(),
+            pending: VecDeque::new(),
+            degraded: false,
+            stats: LagStats::default(),
+            journal: None,
+        }
+    }
+
+    /// Record degradation and recovery events in a journal
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Next tick to act on; `None` once the feed is closed and drained
+    pub async fn recv(&mut self) -> Option<PriceTick> {
+        if let Some(tick) = self.pending.pop_front() {
+            return Some(self.deliver(tick));
+        }
+
+        let tick = self.rx.next_tick().await?;
+        self.accept(tick)
+    }
+
+    /// Next tick and every tick already queued behind it, up to
+    /// `max_batch`; `None` once the feed is closed and drained
+    ///
+    /// Waits for the first tick only, so a quiet feed yields batches of
+    /// one and a busy one is taken in a single pass.
+    pub async fn recv_batch(&mut self) -> Option<Vec<PriceTick>> {
+        let first = self.recv().await?;
+        let mut batch = vec![first];
+        while batch.len() < self.monitor.config.max_batch {
+            let next = match self.pending.pop_front() {
+                Some(tick) => Some(self.deliver(tick)),
+                None => match self.rx.try_next_tick() {
+                    Some(tick) => self.accept(tick),
+                    None => None,
+                },
+            };
+            match next {
+                Some(tick) => batch.push(tick),
+                None => break,
+            }
+        }
+        self.stats.batches += 1;
+        record_tick_batch(batch.len());
+        Some(batch)
+    }
+
+    /// Measure a dequeued tick's lag and deliver it, or catch up first
+    fn accept(&mut self, tick: PriceTick) -> Option<PriceTick> {
+        let now = Utc::now();
+        let lag_ms = self.monitor.observe(&tick, now);
+        self.session.record(lag_ms);
+
+        if self.monitor.is_lagging() {
+            let p95 = self.monitor.p95().unwrap_or_default();
+            let skipped = self.catch_up(tick);
+            self.stats.catch_ups += 1;
+            self.stats.ticks_skipped += skipped;
+            record_ticks_skipped("catch_up", skipped);
+            // The samples described the backlog that was just dropped
+            self.monitor.reset();
+            tracing::warn!(
+                event_code = %EventCode::TickLagDegraded,
+                p95_ms = p95,
+                threshold_ms = self.monitor.threshold_ms(),
+                skipped,
+                "Detection loop behind the price feed, catching up"
+            );
+            if !self.degraded {
+                self.degraded = true;
+                self.journal(
+                    LAG_DEGRADED_KIND,
+                    serde_json::json!({
+                        "p95_ms": p95,
+                        "threshold_ms": self.monitor.threshold_ms(),
+                        "skipped": skipped,
+                    }),
+                );
+            }
+            return self.pending.pop_front().map(|t| self.deliver(t));
+        }
+
+        if self.degraded && self.monitor.p95().is_some() {
+            self.degraded = false;
+            self.journal(
+                LAG_RECOVERED_KIND,
+                serde_json::json!({ "p95_ms": self.monitor.p95() }),
+            );
+        }
+        Some(self.deliver(tick))
+    }
+
+    /// Drain everything queued, keeping the newest tick per symbol
+    ///
+    /// Returns the number of ticks discarded.
+    fn catch_up(&mut self, first: PriceTick) -> u64 {
+        let mut newest: HashMap<String, PriceTick> = HashMap::new();
+        let mut seen = 0u64;
+        let mut keep = |tick: PriceTick| {
+            seen += 1;
+            match newest.get(&tick.symbol) {
+                Some(current) if current.exchange_ts > tick.exchange_ts => {}
+                _ => {
+                    newest.insert(tick.symbol.clone(), tick);
+                }
+            }
+        };
+        keep(first);
+        while let Some(tick) = self.rx.try_next_tick() {
+            keep(tick);
+        }
+
+        let mut kept: Vec<PriceTick> = newest.into_values().collect();
+        kept.sort_by_key(|t| t.exchange_ts);
+        let skipped = seen - kept.len() as u64;
+        self.pending.extend(kept);
+        skipped
+    }
+
+    fn deliver(&mut self, tick: PriceTick) -> PriceTick {
+        self.stats.ticks_delivered += 1;
+        tick
+    }
+
+    fn journal(&self, kind: &str, data: serde_json::Value) {
+        if let Some(journal) = &self.journal {
+            if let Err(e) = journal.append(kind, &data) {
+                tracing::warn!(error = %e, "Failed to journal tick lag event");
+            }
+        }
+    }
+
+    /// Whether the receiver is currently in catch-up mode
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    /// Catch-up counters
+    pub fn stats(&self) -> &LagStats {
+        &self.stats
+    }
+
+    /// Lag of every tick dequeued this session
+    pub fn session_lag(&self) -> &LagHistogram {
+        &self.session
+    }
+}
+
+/// Split a feed into a detection channel and a lossless recorder channel
+///
+/// The recorder channel is awaited for every tick so capture stays complete.
+/// The detection channel never blocks the split: if the consumer has let it
+/// fill up, new ticks are dropped and counted, and the consumer's own
+/// catch-up logic deals with the stale backlog.
+pub fn tee(
+    mut rx: mpsc::Receiver<PriceTick>,
+    detection_capacity: usize,
+) -> (mpsc::Receiver<PriceTick>, mpsc::Receiver<PriceTick>) {
+    let (detection_tx, detection_rx) = instrumented_channel("tee_detection", detection_capacity);
+    let (recorder_tx, recorder_rx) = instrumented_channel("tee_recorder", detection_capacity);
+    tokio::spawn(async move {
+        while let Some(tick) = rx.recv().await {
+            let recorder_open = recorder_tx.send(tick.clone()).await.is_ok();
+            let detection_open = match detection_tx.try_send(tick) {
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    record_ticks_skipped("overflow", 1);
+                    true
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+                Ok(()) => true,
+            };
+            if !recorder_open && !detection_open {
+                break;
+            }
+        }
+    });
+    (detection_rx, recorder_rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::TickSource;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn tick(symbol: &str, price: Decimal, age_ms: i64) -> PriceTick {
+        let ts = Utc::now() - Duration::milliseconds(age_ms);
+        PriceTick {
+            symbol: symbol.to_string(),
+            asset: "BTC".to_string(),
+            price,
+            timestamp: ts,
+            exchange_ts: ts,
+            source: TickSource::Trade,
+        }
+    }
+
+    fn config(min_samples: usize) -> TickLagConfig {
+        TickLagConfig {
+            p95_threshold_ms: DurationConfig::from_millis(500),
+            min_samples,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_p95_and_clock_offset() {
+        let mut monitor = TickLagMonitor::new(TickLagConfig {
+            clock_offset_ms: 100,
+            min_samples: 20,
+            ..Default::default()
+        });
+        let now = Utc::now();
+        for lag in 1..=20 {
+            let mut t = tick("BTCUSDT", dec!(1), 0);
+            t.exchange_ts = now - Duration::milliseconds(lag * 10 + 100);
+            assert_eq!(monitor.observe(&t, now), lag * 10);
+        }
+        assert_eq!(monitor.p95(), Some(190));
+        assert!(!monitor.is_lagging());
+    }
+
+    #[test]
+    fn test_histogram_percentiles() {
+        let mut histogram = LagHistogram::new();
+        assert_eq!(histogram.percentile(0.95), None);
+        for lag in 1..=100 {
+            histogram.record(lag);
+        }
+        histogram.record(-5);
+        histogram.record(60_000);
+        assert_eq!(histogram.count(), 102);
+        assert_eq!(histogram.percentile(0.0), Some(0));
+        assert_eq!(histogram.percentile(0.5), Some(50));
+        assert_eq!(histogram.percentile(0.95), Some(96));
+        assert_eq!(histogram.percentile(1.0), Some(LAG_HISTOGRAM_MAX_MS));
+    }
+
+    #[test]
+    fn test_window_expires_samples() {
+        let mut monitor = TickLagMonitor::new(config(1));
+        let start = Utc::now();
+        let mut stale = tick("BTCUSDT", dec!(1), 0);
+        stale.exchange_ts = start - Duration::seconds(2);
+        monitor.observe(&stale, start);
+        assert!(monitor.is_lagging());
+
+        let later = start + Duration::seconds(10);
+        let mut fresh = tick("BTCUSDT", dec!(1), 0);
+        fresh.exchange_ts = later;
+        monitor.observe(&fresh, later);
+        assert_eq!(monitor.p95(), Some(0));
+        assert!(!monitor.is_lagging());
+    }
+
+    #[tokio::test]
+    async fn test_stalled_consumer_catches_up_to_latest() {
+        let dir = TempDir::new().unwrap();
+        let journal_path = dir.path().join("feed_journal.jsonl");
+        let (tx, rx) = mpsc::channel(100);
+        let mut receiver = LagAwareReceiver::new(rx, config(1))
+            .with_journal(Journal::open(&journal_path).unwrap());
+
+        // The consumer stalled while 50 BTC and 10 ETH ticks queued up
+        for i in 0..50 {
+            tx.send(tick("BTCUSDT", Decimal::from(100 + i), 3_000 - i * 50))
+                .await
+                .unwrap();
+        }
+        for i in 0..10 {
+            tx.send(tick("ETHUSDT", Decimal::from(10 + i), 2_500 - i * 100))
+                .await
+                .unwrap();
+        }
+
+        let first = receiver.recv().await.unwrap();
+        let second = receiver.recv().await.unwrap();
+        assert_eq!(first.symbol, "ETHUSDT");
+        assert_eq!(first.price, dec!(19));
+        assert_eq!(second.symbol, "BTCUSDT");
+        assert_eq!(second.price, dec!(149));
+        assert!(receiver.is_degraded());
+        assert_eq!(
+            receiver.stats(),
+            &LagStats {
+                ticks_delivered: 2,
+                ticks_skipped: 58,
+                catch_ups: 1,
+                batches: 0,
+            }
+        );
+
+        // Fresh ticks flow through untouched and clear the degradation
+        tx.send(tick("BTCUSDT", dec!(150), 0)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().price, dec!(150));
+        assert!(!receiver.is_degraded());
+
+        let entries = Journal::read_all(&journal_path).unwrap();
+        let kinds: Vec<&str> = entries.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(kinds, vec![LAG_DEGRADED_KIND, LAG_RECOVERED_KIND]);
+        assert_eq!(entries[0].data["skipped"], 58);
+    }
+
+    #[tokio::test]
+    async fn test_keeping_up_delivers_every_tick() {
+        let (tx, rx) = mpsc::channel(100);
+        let mut receiver = LagAwareReceiver::new(rx, config(1));
+        for i in 0..5 {
+            tx.send(tick("BTCUSDT", Decimal::from(i), 0)).await.unwrap();
+        }
+        drop(tx);
+
+        let mut prices = vec![];
+        while let Some(t) = receiver.recv().await {
+            prices.push(t.price);
+        }
+        assert_eq!(prices.len(), 5);
+        assert_eq!(receiver.stats().ticks_skipped, 0);
+        assert!(!receiver.is_degraded());
+    }
+
+    #[tokio::test]
+    async fn test_batches_take_what_is_queued() {
+        let (tx, rx) = mpsc::channel(100);
+        let mut receiver = LagAwareReceiver::new(
+            rx,
+            TickLagConfig {
+                max_batch: 4,
+                ..config(1)
+            },
+        );
+        for i in 0..6 {
+            tx.send(tick("BTCUSDT", Decimal::from(i), 0)).await.unwrap();
+        }
+
+        let prices = |batch: Option<Vec<PriceTick>>| {
+            batch.unwrap().iter().map(|t| t.price).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            prices(receiver.recv_batch().await),
+            vec![dec!(0), dec!(1), dec!(2), dec!(3)]
+        );
+        assert_eq!(prices(receiver.recv_batch().await), vec![dec!(4), dec!(5)]);
+
+        // Nothing queued: the next batch waits for its first tick
+        tx.send(tick("BTCUSDT", dec!(6), 0)).await.unwrap();
+        drop(tx);
+        assert_eq!(prices(receiver.recv_batch().await), vec![dec!(6)]);
+        assert!(receiver.recv_batch().await.is_none());
+        assert_eq!(receiver.stats().batches, 3);
+        assert_eq!(receiver.stats().ticks_delivered, 7);
+    }
+
+    #[tokio::test]
+    async fn test_tee_recorder_gets_every_tick_while_detection_stalls() {
+        let (tx, rx) = mpsc::channel(100);
+        let (mut detection, mut recorder) = tee(rx, 4);
+        for i in 0..20 {
+            tx.send(tick("BTCUSDT", Decimal::from(i), 0)).await.unwrap();
+        }
+        drop(tx);
+
+        let mut recorded = 0;
+        while recorder.recv().await.is_some() {
+            recorded += 1;
+        }
+        assert_eq!(recorded, 20);
+
+        // Detection never read, so only its capacity got through
+        let mut detected = 0;
+        while detection.recv().await.is_some() {
+            detected += 1;
+        }
+        assert_eq!(detected, 4);
+    }
+}
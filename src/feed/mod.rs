@@ -3,10 +3,19 @@
 //! Provides real-time BTC price from Binance WebSocket
 
 mod binance;
+mod history;
+mod klines;
+mod lag;
 mod types;
 
-pub use binance::BinanceFeed;
-pub use types::PriceTick;
+pub use binance::{BinanceFeed, BINANCE_WS_URL};
+pub use history::PriceHistory;
+pub use klines::{klines_to_ticks, seed_history, Kline, KlineClient, BINANCE_REST_URL};
+pub use lag::{
+    tee, LagAwareReceiver, LagHistogram, LagStats, TickLagConfig, TickLagMonitor, TickStream,
+    LAG_DEGRADED_KIND, LAG_HISTOGRAM_MAX_MS, LAG_RECOVERED_KIND,
+};
+pub use types::{PriceTick, TickSource};
 
 use async_trait::async_trait;
 use tokio::sync::mpsc;
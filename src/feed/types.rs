@@ -4,15 +4,39 @@ use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
+/// Where a price tick came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TickSource {
+    /// Live trade from the WebSocket stream
+    #[default]
+    Trade,
+    /// Synthesized from a REST kline; too coarse for momentum confirmation
+    Kline,
+}
+
 /// A single price tick from an exchange
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PriceTick {
     /// Trading symbol (e.g., "BTCUSDT")
     pub symbol: String,
+    /// Canonical asset (e.g., "BTC"), tagged by [`crate::symbols::SymbolMap`]
+    #[serde(default)]
+    pub asset: String,
     /// Trade price
     pub price: Decimal,
     /// Local timestamp when tick was received
     pub timestamp: DateTime<Utc>,
     /// Exchange timestamp (e.g., Binance event time)
     pub exchange_ts: DateTime<Utc>,
+    /// Trade or kline-derived
+    #[serde(default)]
+    pub source: TickSource,
+}
+
+impl PriceTick {
+    /// Whether this tick was synthesized from a kline
+    pub fn is_kline(&self) -> bool {
+        self.source == TickSource::Kline
+    }
 }
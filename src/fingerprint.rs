@@ -0,0 +1,206 @@
+//! Config fingerprinting for experiment tracking
+//!
+//! The fingerprint is a SHA-256 over the effective config (defaults filled
+//! in) serialized as canonical JSON with sorted keys, so reordering fields or
+//! editing comments in the TOML leaves it unchanged. It is computed once at
+//! startup, installed process-wide, and stamped into every output: Parquet
+//! key/value metadata, journals, backtest results, and a metrics label.
+
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Parquet metadata key holding the config hash
+pub const CONFIG_HASH_KEY: &str = "poly_hft.config_hash";
+
+/// Parquet metadata key holding the serialized effective config
+pub const CONFIG_JSON_KEY: &str = "poly_hft.config";
+
+/// Stable hash of an effective config plus the config itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigFingerprint {
+    /// Hex-encoded SHA-256 of `config`
+    pub hash: String,
+    /// Canonical JSON of the effective config
+    pub config: String,
+}
+
+impl ConfigFingerprint {
+    /// Fingerprint an effective config
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        // Going through `Value` sorts every map's keys
+        let value = serde_json::to_value(config)?;
+        let config = serde_json::to_string(&value)?;
+        let hash = Sha256::digest(config.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Ok(Self { hash, config })
+    }
+
+    /// First 12 hex digits, for labels and log lines
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..12.min(self.hash.len())]
+    }
+
+    /// Key/value pairs stamped into output file metadata
+    pub fn metadata(&self) -> [(&'static str, String); 2] {
+        [
+            (CONFIG_HASH_KEY, self.hash.clone()),
+            (CONFIG_JSON_KEY, self.config.clone()),
+        ]
+    }
+}
+
+impl fmt::Display for ConfigFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hash)
+    }
+}
+
+static ACTIVE: OnceLock<ConfigFingerprint> = OnceLock::new();
+
+/// Install the process-wide fingerprint; later calls are ignored
+pub fn install(fingerprint: ConfigFingerprint) -> &'static ConfigFingerprint {
+    ACTIVE.get_or_init(|| fingerprint)
+}
+
+/// The fingerprint installed at startup, if any
+pub fn active() -> Option<&'static ConfigFingerprint> {
+    ACTIVE.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+        # Feed settings
+        [feed]
+        exchange = "binance"
+        symbol = "BTCUSDT"
+
+        [market]
+        asset = "BTC"
+        interval = "15m"
+        refresh_interval_secs = 30
+
+        [model]
+        volatility_window_minutes = 30
+        min_time_to_expiry_secs = 60
+
+        [signal]
+        min_edge_threshold = 0.005
+        max_edge_threshold = 0.10
+
+        [risk]
+        kelly_fraction = 0.25
+        max_position_pct = 0.01
+        max_concurrent_positions = 3
+        initial_bankroll = 500.0
+
+        [execution]
+        mode = "paper"
+        slippage_estimate = 0.001
+
+        [data]
+        capture_enabled = true
+        output_dir = "./data"
+        rotation_interval = "1h"
+
+        [data.retention.max_age_hours]
+        orderbook = 24
+        price = 48
+
+        [telemetry]
+        metrics_port = 9090
+        log_level = "info"
+    "#;
+
+    // Same settings, tables and keys reordered, comments changed
+    const REORDERED: &str = r#"
+        [telemetry]
+        log_level = "info"   # quieter in prod
+        metrics_port = 9090
+
+        [data]
+        rotation_interval = "1h"
+        output_dir = "./data"
+        capture_enabled = true
+
+        [data.retention.max_age_hours]
+        price = 48
+        orderbook = 24
+
+        [execution]
+        slippage_estimate = 0.001
+        mode = "paper"
+
+        [risk]
+        initial_bankroll = 500.0
+        max_concurrent_positions = 3
+        max_position_pct = 0.01
+        kelly_fraction = 0.25
+
+        [signal]
+        max_edge_threshold = 0.10
+        min_edge_threshold = 0.005
+
+        [model]
+        min_time_to_expiry_secs = 60
+        volatility_window_minutes = 30
+
+        [market]
+        refresh_interval_secs = 30
+        interval = "15m"
+        asset = "BTC"
+
+        [feed]
+        symbol = "BTCUSDT"
+        exchange = "binance"
+    "#;
+
+    fn fingerprint(toml: &str) -> ConfigFingerprint {
+        let config: Config = toml::from_str(toml).unwrap();
+        ConfigFingerprint::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_hash_ignores_ordering_and_comments() {
+        let base = fingerprint(BASE);
+        assert_eq!(base.hash.len(), 64);
+        assert_eq!(base, fingerprint(REORDERED));
+        assert_eq!(base, fingerprint(BASE));
+    }
+
+    #[test]
+    fn test_hash_tracks_values_and_defaults() {
+        let base = fingerprint(BASE);
+        let changed = fingerprint(&BASE.replace("kelly_fraction = 0.25", "kelly_fraction = 0.5"));
+        assert_ne!(base.hash, changed.hash);
+
+        // Spelling out a default yields the same effective config
+        let explicit = fingerprint(&BASE.replace(
+            "[market]\n",
+            &format!(
+                "[market]\n        page_size = {}\n",
+                crate::market::DEFAULT_PAGE_SIZE
+            ),
+        ));
+        assert_eq!(base.hash, explicit.hash);
+        assert!(base.config.contains("\"page_size\""));
+    }
+
+    #[test]
+    fn test_metadata_and_short_hash() {
+        let fp = fingerprint(BASE);
+        assert_eq!(fp.short_hash(), &fp.hash[..12]);
+        let [(hash_key, hash), (json_key, json)] = fp.metadata();
+        assert_eq!(hash_key, CONFIG_HASH_KEY);
+        assert_eq!(hash, fp.hash);
+        assert_eq!(json_key, CONFIG_JSON_KEY);
+        assert_eq!(json, fp.config);
+    }
+}
@@ -0,0 +1,450 @@
+//! Feature flags with percentage rollout
+//!
+//! New logic goes live behind a flag under `[flags]`, first for a share of
+//! decisions rather than all of them. A flag is `"off"`, `"on"` or a
+//! percentage such as `"25%"`. Each signal is assigned to a flag's treated
+//! or untreated cohort by a hash of the flag name and the signal ID, so a
+//! signal always gets the same treatment, and a replay of the same data
+//! (whose signal IDs are deterministic, see [`crate::ids`]) the same
+//! cohorts. Hashing the name in keeps the cohorts of two flags at the same
+//! percentage independent.
+//!
+//! The evaluation of every flag is recorded on the signal, journaled with
+//! it and its order, and carried onto the trade tape, so a session's
+//! trades split into cohorts afterwards (see
+//! [`CohortReport`](crate::report::CohortReport)).
+//!
+//! A running session picks up flags changed in the config file, and flags
+//! set with `poly-hft ctl flag`, within a second. Each change is a
+//! `flags.<name>` parameter of the [`EffectiveConfig`] registry, marked
+//! `file` or `ctl`. A flag dropped from the file turns off.
+//!
+//! [`EffectiveConfig`]: crate::effective::EffectiveConfig
+
+use crate::effective::Provenance;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Flag gating the exit ladder per position; on while undefined
+pub const EXIT_LADDER: &str = "exit_ladder";
+
+/// Flag gating book-shock exits per position; on while undefined
+pub const BOOK_SHOCK_EXIT: &str = "book_shock_exit";
+
+/// Requests from `poly-hft ctl flag` waiting for the session, in the
+/// data directory
+pub const FLAG_REQUEST_FILE: &str = "flags.request";
+
+/// Buckets a signal hashes into; a percentage is honoured to 0.01%
+const BUCKETS: u64 = 10_000;
+
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keep the path of the config file, so a running session reloads the
+/// flags it sets
+pub fn install_config_path(path: impl Into<PathBuf>) {
+    let _ = CONFIG_PATH.set(path.into());
+}
+
+/// The config file installed at startup, if any
+pub fn config_path() -> Option<&'static Path> {
+    CONFIG_PATH.get().map(PathBuf::as_path)
+}
+
+/// State of one flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "FlagValue", into = "String")]
+pub enum FlagState {
+    Off,
+    On,
+    /// On for this percentage of signals, 0 to 100
+    Percent(Decimal),
+}
+
+/// A flag as written in the config file: a state, or a bool for on/off
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FlagValue {
+    Bool(bool),
+    Text(String),
+}
+
+impl TryFrom<FlagValue> for FlagState {
+    type Error = anyhow::Error;
+
+    fn try_from(value: FlagValue) -> anyhow::Result<Self> {
+        match value {
+            FlagValue::Bool(true) => Ok(FlagState::On),
+            FlagValue::Bool(false) => Ok(FlagState::Off),
+            FlagValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl FromStr for FlagState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
+            "off" => return Ok(FlagState::Off),
+            "on" => return Ok(FlagState::On),
+            _ => {}
+        }
+        let percent = s
+            .strip_suffix('%')
+            .and_then(|p| Decimal::from_str(p.trim()).ok())
+            .ok_or_else(|| anyhow::anyhow!("flag state {:?} is not off, on or a percentage", s))?;
+        if percent < Decimal::ZERO || percent > Decimal::ONE_HUNDRED {
+            anyhow::bail!("flag percentage {} is outside 0-100%", percent);
+        }
+        Ok(FlagState::Percent(percent.normalize()))
+    }
+}
+
+impl fmt::Display for FlagState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlagState::Off => f.write_str("off"),
+            FlagState::On => f.write_str("on"),
+            FlagState::Percent(p) => write!(f, "{}%", p),
+        }
+    }
+}
+
+impl From<FlagState> for String {
+    fn from(state: FlagState) -> Self {
+        state.to_string()
+    }
+}
+
+/// A flag's evaluation for one signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagDecision {
+    /// Whether the signal is in the treated cohort
+    pub on: bool,
+    /// The flag's percentage, when it was rolling out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollout: Option<Decimal>,
+}
+
+/// Flags by name, under `[flags]`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FeatureFlags(BTreeMap<String, FlagState>);
+
+impl FeatureFlags {
+    /// The state of flag `name`, if defined
+    pub fn get(&self, name: &str) -> Option<FlagState> {
+        self.0.get(name).copied()
+    }
+
+    /// Set flag `name` to `state`
+    pub fn set(&mut self, name: &str, state: FlagState) {
+        self.0.insert(name.to_string(), state);
+    }
+
+    /// Every flag and its state, by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, FlagState)> {
+        self.0.iter().map(|(name, state)| (name.as_str(), *state))
+    }
+
+    /// Every flag evaluated for the signal `signal_id`
+    pub fn evaluate(&self, signal_id: Uuid) -> BTreeMap<String, FlagDecision> {
+        self.iter()
+            .map(|(name, state)| (name.to_string(), decide(name, state, signal_id)))
+            .collect()
+    }
+}
+
+/// Flag `name` in `state` evaluated for the signal `signal_id`
+pub fn decide(name: &str, state: FlagState, signal_id: Uuid) -> FlagDecision {
+    match state {
+        FlagState::Off => FlagDecision {
+            on: false,
+            rollout: None,
+        },
+        FlagState::On => FlagDecision {
+            on: true,
+            rollout: None,
+        },
+        FlagState::Percent(percent) => {
+            let threshold = (percent * Decimal::ONE_HUNDRED)
+                .floor()
+                .to_u64()
+                .unwrap_or_default();
+            FlagDecision {
+                on: bucket(name, signal_id) < threshold,
+                rollout: Some(percent),
+            }
+        }
+    }
+}
+
+/// Bucket of the signal `signal_id` for flag `name`, below [`BUCKETS`]
+fn bucket(name: &str, signal_id: Uuid) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update((name.len() as u64).to_le_bytes());
+    hasher.update(name.as_bytes());
+    hasher.update(signal_id.as_bytes());
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(bytes) % BUCKETS
+}
+
+/// Ask the session running in `data_dir` to set flag `name` to `state`
+pub fn request_flag(data_dir: &Path, name: &str, state: FlagState) -> anyhow::Result<()> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        anyhow::bail!("flag name {:?} is empty or has whitespace", name);
+    }
+    std::fs::create_dir_all(data_dir)?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(data_dir.join(FLAG_REQUEST_FILE))?;
+    writeln!(file, "{} {}", name, state)?;
+    Ok(())
+}
+
+/// A flag change picked up by [`FlagWatcher::poll`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagChange {
+    pub name: String,
+    pub state: FlagState,
+    pub provenance: Provenance,
+}
+
+/// Flags changed in the config file or set with `poly-hft ctl flag`
+pub struct FlagWatcher {
+    config_path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    /// The file's flags as last read
+    file: FeatureFlags,
+    request_path: PathBuf,
+}
+
+impl FlagWatcher {
+    /// Watch `config_path`, whose flags at startup are `flags`, and the
+    /// requests left in `data_dir`
+    pub fn new(config_path: Option<&Path>, data_dir: &Path, flags: &FeatureFlags) -> Self {
+        Self {
+            modified: config_path.and_then(modified),
+            config_path: config_path.map(Path::to_path_buf),
+            file: flags.clone(),
+            request_path: data_dir.join(FLAG_REQUEST_FILE),
+        }
+    }
+
+    /// Changes since the last poll: flags whose state in the file changed,
+    /// then each request in the order made
+    pub fn poll(&mut self) -> Vec<FlagChange> {
+        let mut changes = self.reload();
+        let Ok(requests) = std::fs::read_to_string(&self.request_path) else {
+            return changes;
+        };
+        if let Err(e) = std::fs::remove_file(&self.request_path) {
+            tracing::warn!(error = %e, "Failed to remove flag requests");
+        }
+        for line in requests.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let parsed = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow::anyhow!("no state"))
+                .and_then(|(name, state)| Ok((name.to_string(), state.parse::<FlagState>()?)));
+            match parsed {
+                Ok((name, state)) => changes.push(FlagChange {
+                    name,
+                    state,
+                    provenance: Provenance::Ctl,
+                }),
+                Err(e) => tracing::warn!(request = line, error = %e, "Ignoring flag request"),
+            }
+        }
+        changes
+    }
+
+    /// Flags whose state in the config file changed since it was last read
+    fn reload(&mut self) -> Vec<FlagChange> {
+        let Some(path) = &self.config_path else {
+            return vec![];
+        };
+        let current = modified(path);
+        if current.is_none() || current == self.modified {
+            return vec![];
+        }
+        self.modified = current;
+        let flags = match read_flags(path) {
+            Ok(flags) => flags,
+            Err(e) => {
+                tracing::warn!(path = ?path, error = %e, "Flags not reloaded, config unreadable");
+                return vec![];
+            }
+        };
+        let mut changes: Vec<FlagChange> = flags
+            .iter()
+            .filter(|(name, state)| self.file.get(name) != Some(*state))
+            .map(|(name, state)| FlagChange {
+                name: name.to_string(),
+                state,
+                provenance: Provenance::File,
+            })
+            .collect();
+        changes.extend(
+            self.file
+                .iter()
+                .filter(|(name, _)| flags.get(name).is_none())
+                .map(|(name, _)| FlagChange {
+                    name: name.to_string(),
+                    state: FlagState::Off,
+                    provenance: Provenance::File,
+                }),
+        );
+        self.file = flags;
+        changes
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The `[flags]` table of the config file at `path`
+fn read_flags(path: &Path) -> anyhow::Result<FeatureFlags> {
+    let table: toml::Table = std::fs::read_to_string(path)?.parse()?;
+    Ok(match table.get("flags") {
+        Some(flags) => flags.clone().try_into()?,
+        None => FeatureFlags::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::signal_id;
+    use crate::signal::Side;
+    use chrono::{DateTime, Duration};
+    use rust_decimal_macros::dec;
+
+    fn ids(n: i64) -> impl Iterator<Item = Uuid> {
+        let start = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+        (0..n).map(move |i| signal_id("cond", "lag", start + Duration::milliseconds(i), Side::Yes))
+    }
+
+    #[test]
+    fn test_states_parse_and_round_trip() {
+        let flags: FeatureFlags = toml::from_str(
+            r#"
+            exit_ladder = "25%"
+            book_shock_exit = "on"
+            gbm = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(flags.get(EXIT_LADDER), Some(FlagState::Percent(dec!(25))));
+        assert_eq!(flags.get(BOOK_SHOCK_EXIT), Some(FlagState::On));
+        assert_eq!(flags.get("gbm"), Some(FlagState::Off));
+        assert_eq!(
+            serde_json::to_value(&flags).unwrap(),
+            serde_json::json!({"book_shock_exit": "on", "exit_ladder": "25%", "gbm": "off"})
+        );
+        assert_eq!("12.50%".parse::<FlagState>().unwrap().to_string(), "12.5%");
+        assert!("150%".parse::<FlagState>().is_err());
+        assert!("half".parse::<FlagState>().is_err());
+    }
+
+    #[test]
+    fn test_assignment_is_deterministic() {
+        let mut flags = FeatureFlags::default();
+        flags.set(EXIT_LADDER, FlagState::Percent(dec!(50)));
+        flags.set("passive_entry", FlagState::Percent(dec!(50)));
+        flags.set(BOOK_SHOCK_EXIT, FlagState::Off);
+
+        let evaluated: Vec<_> = ids(200).map(|id| flags.evaluate(id)).collect();
+        let again: Vec<_> = ids(200).map(|id| flags.evaluate(id)).collect();
+        assert_eq!(evaluated, again);
+        assert!(evaluated.iter().all(|e| e[BOOK_SHOCK_EXIT]
+            == FlagDecision {
+                on: false,
+                rollout: None
+            }));
+        assert!(evaluated
+            .iter()
+            .all(|e| e[EXIT_LADDER].rollout == Some(dec!(50))));
+        // Two flags at the same percentage split the signals differently
+        let agree = evaluated
+            .iter()
+            .filter(|e| e[EXIT_LADDER].on == e["passive_entry"].on)
+            .count();
+        assert!(agree < 150, "{} of 200 agree", agree);
+    }
+
+    #[test]
+    fn test_percentage_is_honoured_over_many_signals() {
+        for percent in [dec!(5), dec!(25), dec!(80)] {
+            let state = FlagState::Percent(percent);
+            let on = ids(20_000)
+                .filter(|id| decide(EXIT_LADDER, state, *id).on)
+                .count();
+            let share = Decimal::from(on) / dec!(200);
+            assert!(
+                (share - percent).abs() < dec!(1.5),
+                "{}% rolled out to {}%",
+                percent,
+                share
+            );
+        }
+        assert!(ids(100).all(|id| !decide("x", FlagState::Percent(dec!(0)), id).on));
+        assert!(ids(100).all(|id| decide("x", FlagState::Percent(dec!(100)), id).on));
+    }
+
+    #[test]
+    fn test_watcher_picks_up_file_changes_and_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        std::fs::write(&config, "[flags]\nexit_ladder = \"10%\"\ngbm = \"on\"\n").unwrap();
+        let flags = read_flags(&config).unwrap();
+        let mut watcher = FlagWatcher::new(Some(&config), dir.path(), &flags);
+        assert!(watcher.poll().is_empty());
+
+        std::fs::write(&config, "[flags]\nexit_ladder = \"50%\"\n").unwrap();
+        // Coarse filesystem clocks may not see the rewrite as newer
+        watcher.modified = None;
+        let change = |name: &str, state, provenance| FlagChange {
+            name: name.to_string(),
+            state,
+            provenance,
+        };
+        assert_eq!(
+            watcher.poll(),
+            [
+                change(EXIT_LADDER, FlagState::Percent(dec!(50)), Provenance::File),
+                change("gbm", FlagState::Off, Provenance::File),
+            ]
+        );
+
+        request_flag(dir.path(), EXIT_LADDER, FlagState::On).unwrap();
+        std::fs::write(
+            dir.path().join(FLAG_REQUEST_FILE),
+            format!(
+                "{}bogus\n",
+                std::fs::read_to_string(dir.path().join(FLAG_REQUEST_FILE)).unwrap()
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            watcher.poll(),
+            [change(EXIT_LADDER, FlagState::On, Provenance::Ctl)]
+        );
+        assert!(watcher.poll().is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Deterministic identifiers
+//!
+//! A signal's ID is a hash of what makes it the same signal: market,
+//! strategy, detection time to the millisecond, and side. Replaying the
+//! same data therefore reproduces the same IDs, so journals, Parquet
+//! records and fills of a replay join those of the original session. An
+//! order's client order ID is its signal's ID plus an attempt counter.
+//! `[ids] mode = "random"` restores random IDs.
+
+use crate::signal::Side;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// How signal IDs are assigned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdMode {
+    /// Hashed from market, strategy, detection time and side
+    #[default]
+    Deterministic,
+    /// Random UUIDv4, unique across runs of the same data
+    Random,
+}
+
+/// ID settings, under `[ids]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IdConfig {
+    /// How signal IDs are assigned
+    #[serde(default)]
+    pub mode: IdMode,
+}
+
+impl IdMode {
+    /// ID of a signal in this mode
+    pub fn signal_id(
+        &self,
+        market_id: &str,
+        strategy: &str,
+        detected_at: DateTime<Utc>,
+        side: Side,
+    ) -> Uuid {
+        match self {
+            IdMode::Deterministic => signal_id(market_id, strategy, detected_at, side),
+            IdMode::Random => Uuid::new_v4(),
+        }
+    }
+}
+
+/// Deterministic ID of the signal `strategy` detected in `market_id` at
+/// `detected_at` for `side`
+///
+/// The time is truncated to the millisecond. The ID is a version 8 UUID
+/// built from a SHA-256 of the fields.
+pub fn signal_id(market_id: &str, strategy: &str, detected_at: DateTime<Utc>, side: Side) -> Uuid {
+    let side = match side {
+        Side::Yes => "yes",
+        Side::No => "no",
+    };
+    let mut hasher = Sha256::new();
+    // Length-prefixed so no two field lists hash the same input
+    for field in [
+        market_id,
+        strategy,
+        &detected_at.timestamp_millis().to_string(),
+        side,
+    ] {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+/// Client order ID of the `attempt`th order for `signal_id`, counting from 1
+pub fn client_order_id(signal_id: Uuid, attempt: u32) -> String {
+    format!("{}-{}", signal_id, attempt)
+}
+
+/// Client order ID of the order closing `position_id` before settlement
+pub fn exit_order_id(position_id: Uuid) -> String {
+    format!("{}-exit", position_id)
+}
+
+/// Client order ID of the exit ladder's sale of part of `position_id`,
+/// requested at `requested_at`
+pub fn ladder_order_id(position_id: Uuid, requested_at: DateTime<Utc>) -> String {
+    format!("{}-ladder-{}", position_id, requested_at.timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use std::collections::HashSet;
+
+    fn ts(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_735_689_600_000 + millis).unwrap()
+    }
+
+    #[test]
+    fn test_signal_id_is_stable() {
+        let id = signal_id("0xabc", "default", ts(0), Side::Yes);
+        assert_eq!(id, signal_id("0xabc", "default", ts(0), Side::Yes));
+        assert_eq!(id.get_version_num(), 8);
+        // Pinned so a change to the hash shows up as a failing test
+        assert_eq!(id.to_string(), "08ae353c-58eb-8403-8bae-1f741ebe1cfa");
+        // Sub-millisecond differences are the same signal
+        let later = ts(0) + Duration::microseconds(900);
+        assert_eq!(signal_id("0xabc", "default", later, Side::Yes), id);
+    }
+
+    #[test]
+    fn test_signal_ids_do_not_collide() {
+        let mut ids = HashSet::new();
+        for market in ["0xabc", "0xabd", "0xab", "c"] {
+            for strategy in ["default", "lag", "defaultc"] {
+                for millis in 0..500 {
+                    for side in [Side::Yes, Side::No] {
+                        assert!(ids.insert(signal_id(market, strategy, ts(millis), side)));
+                    }
+                }
+            }
+        }
+        // Field boundaries are part of the hash
+        assert_ne!(
+            signal_id("0xab", "cdefault", ts(0), Side::Yes),
+            signal_id("0xabc", "default", ts(0), Side::Yes)
+        );
+    }
+
+    #[test]
+    fn test_random_mode_and_client_order_ids() {
+        let random = IdMode::Random.signal_id("0xabc", "default", ts(0), Side::Yes);
+        assert_ne!(
+            random,
+            IdMode::Random.signal_id("0xabc", "default", ts(0), Side::Yes)
+        );
+        assert_eq!(random.get_version_num(), 4);
+        let id = signal_id("0xabc", "default", ts(0), Side::Yes);
+        assert_eq!(
+            IdMode::Deterministic.signal_id("0xabc", "default", ts(0), Side::Yes),
+            id
+        );
+        assert_eq!(client_order_id(id, 2), format!("{}-2", id));
+    }
+}
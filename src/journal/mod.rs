@@ -0,0 +1,406 @@
+//! Append-only JSONL journal
+//!
+//! Records operational actions (file deletions, state changes, ...) one JSON
+//! object per line so they can be audited after the fact. Every entry is
+//! numbered; entries are on disk in number order. A journal written from
+//! many tasks is made [`Journal::batched`], handing its writes to a writer
+//! thread that batches and fsyncs them.
+
+mod writer;
+
+pub use writer::{JournalConfig, DEFAULT_JOURNAL_BATCH_INTERVAL_MS, DEFAULT_JOURNAL_BATCH_SIZE};
+
+use crate::fingerprint::ConfigFingerprint;
+use crate::stream::EventStream;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use writer::JournalWriter;
+
+/// Entry kind of the header written when a session opens a journal
+pub const SESSION_START_KIND: &str = "session_start";
+
+/// Bytes at the end of a journal searched for its last sequence number
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// Appends under way across every journal
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Journal appends not yet on disk: entries queued for a batched writer,
+/// and direct appends waiting on or holding a journal file
+pub fn pending_appends() -> usize {
+    PENDING.load(Ordering::Relaxed)
+}
+
+/// One journal line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Position in the journal, assigned when the entry is appended; 0 in
+    /// journals written before entries were numbered
+    #[serde(default)]
+    pub seq: u64,
+    /// When the entry was written
+    pub ts: DateTime<Utc>,
+    /// Entry kind, e.g. `retention_delete`
+    pub kind: String,
+    /// Kind-specific payload
+    pub data: serde_json::Value,
+}
+
+/// Entries read from a journal file, with what could not be read
+#[derive(Debug, Default)]
+pub struct JournalRead {
+    pub entries: Vec<JournalEntry>,
+    /// Complete lines that are not entries, such as the sealed remains of
+    /// an earlier torn write
+    pub malformed: usize,
+    /// Whether the file ends in a partial line, as a crash mid-write leaves
+    pub torn_tail: bool,
+}
+
+/// A file appended to by the caller
+struct Direct {
+    file: File,
+    next_seq: u64,
+}
+
+enum Sink {
+    Direct(Mutex<Direct>),
+    Batched(Arc<JournalWriter>),
+}
+
+/// Append-only JSONL journal file
+pub struct Journal {
+    path: PathBuf,
+    sink: Sink,
+    sync: bool,
+    stream: Option<EventStream>,
+}
+
+impl Journal {
+    /// Open (or create) a journal file for appending
+    ///
+    /// A partial final line left by a crash is ended with a newline, so it
+    /// stays a malformed line of its own, and numbering continues after the
+    /// last entry.
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        // A batched writer on the file may be mid-write; its tail is its own
+        let next_seq = match writer::running(&path) {
+            Some(_) => 0,
+            None => prepare(&path, &mut file)?,
+        };
+        Ok(Self {
+            path,
+            sink: Sink::Direct(Mutex::new(Direct { file, next_seq })),
+            sync: false,
+            stream: None,
+        })
+    }
+
+    /// Fsync every append, so an entry that was written survives a crash
+    pub fn durable(mut self) -> Self {
+        self.sync = true;
+        self
+    }
+
+    /// Hand appends to a writer thread that batches and fsyncs them
+    ///
+    /// `append` then returns once the entry is queued; [`Journal::flush`]
+    /// waits for it to be durable, and dropping the journal writes what is
+    /// still queued. Batched journals on one path share their writer.
+    pub fn batched(self, config: &JournalConfig) -> anyhow::Result<Self> {
+        let Sink::Direct(direct) = self.sink else {
+            return Ok(self);
+        };
+        let direct = direct.into_inner().unwrap_or_else(|e| e.into_inner());
+        let writer = JournalWriter::shared(
+            &self.path,
+            direct.file,
+            direct.next_seq,
+            config,
+            self.stream.clone(),
+        )?;
+        Ok(Self {
+            sink: Sink::Batched(writer),
+            ..self
+        })
+    }
+
+    /// Also send every entry appended to the clients of `stream`, as the
+    /// line written
+    pub fn with_stream(mut self, stream: EventStream) -> Self {
+        if let Sink::Batched(writer) = &self.sink {
+            writer.set_stream(stream.clone());
+        }
+        self.stream = Some(stream);
+        self
+    }
+
+    /// Append an entry
+    pub fn append<T: Serialize>(&self, kind: &str, data: &T) -> anyhow::Result<()> {
+        let data = serde_json::to_value(data)?;
+        let direct = match &self.sink {
+            Sink::Batched(writer) => return writer.enqueue(kind, data),
+            Sink::Direct(direct) => direct,
+        };
+
+        PENDING.fetch_add(1, Ordering::Relaxed);
+        let written = self.write_direct(direct, kind, data);
+        PENDING.fetch_sub(1, Ordering::Relaxed);
+        let (entry, line) = written?;
+        if let Some(stream) = &self.stream {
+            stream.publish(&entry, line.trim_end());
+        }
+        Ok(())
+    }
+
+    fn write_direct(
+        &self,
+        direct: &Mutex<Direct>,
+        kind: &str,
+        data: serde_json::Value,
+    ) -> anyhow::Result<(JournalEntry, String)> {
+        let mut direct = direct.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = JournalEntry {
+            seq: direct.next_seq,
+            ts: Utc::now(),
+            kind: kind.to_string(),
+            data,
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        direct.file.write_all(line.as_bytes())?;
+        direct.file.flush()?;
+        if self.sync {
+            direct.file.sync_data()?;
+        }
+        direct.next_seq += 1;
+        Ok((entry, line))
+    }
+
+    /// Wait until every entry appended so far is on disk
+    pub fn flush(&self) -> anyhow::Result<()> {
+        match &self.sink {
+            Sink::Batched(writer) => writer.flush(),
+            Sink::Direct(_) => Ok(()),
+        }
+    }
+
+    /// Append a `session_start` header recording the config fingerprint
+    pub fn write_header(&self, fingerprint: &ConfigFingerprint) -> anyhow::Result<()> {
+        self.append(SESSION_START_KIND, fingerprint)
+    }
+
+    /// Journal file path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read all entries from a journal file, skipping malformed lines
+    pub fn read_all(path: impl AsRef<Path>) -> anyhow::Result<Vec<JournalEntry>> {
+        let path = path.as_ref();
+        let read = Self::read_checked(path)?;
+        if read.malformed > 0 || read.torn_tail {
+            tracing::warn!(
+                path = ?path,
+                malformed = read.malformed,
+                torn_tail = read.torn_tail,
+                "Skipped unreadable journal lines"
+            );
+        }
+        Ok(read.entries)
+    }
+
+    /// Read all entries from a journal file, counting the lines skipped
+    ///
+    /// A final line without its newline is read if it is a whole entry and
+    /// otherwise reported as torn.
+    pub fn read_checked(path: impl AsRef<Path>) -> anyhow::Result<JournalRead> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut read = JournalRead::default();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let complete = line.last() == Some(&b'\n');
+            match serde_json::from_slice::<JournalEntry>(&line) {
+                Ok(entry) => read.entries.push(entry),
+                Err(_) if !complete => read.torn_tail = true,
+                Err(_) if line.iter().all(u8::is_ascii_whitespace) => {}
+                Err(_) => read.malformed += 1,
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// Seal a torn final line of `file` with a newline and find the sequence
+/// number following its last entry
+fn prepare(path: &Path, file: &mut File) -> anyhow::Result<u64> {
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    if tail.last().is_some_and(|b| *b != b'\n') {
+        tracing::warn!(path = ?path, "Sealing a torn final journal line");
+        file.write_all(b"\n")?;
+        file.sync_data()?;
+    }
+    Ok(tail
+        .split(|b| *b == b'\n')
+        .rev()
+        .find_map(|line| serde_json::from_slice::<JournalEntry>(line).ok())
+        .map_or(0, |entry| entry.seq + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duration::DurationConfig;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_and_read() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let journal = Journal::open(&path).unwrap();
+
+        journal
+            .append("test", &serde_json::json!({ "n": 1 }))
+            .unwrap();
+        journal
+            .append("test", &serde_json::json!({ "n": 2 }))
+            .unwrap();
+
+        let entries = Journal::read_all(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].data["n"], 2);
+        assert_eq!(entries[0].kind, "test");
+    }
+
+    #[test]
+    fn test_reopen_appends() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("journal.jsonl");
+        Journal::open(&path).unwrap().append("a", &1).unwrap();
+        Journal::open(&path).unwrap().append("b", &2).unwrap();
+        assert_eq!(Journal::read_all(&path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_write_header() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let fingerprint = ConfigFingerprint {
+            hash: "cafe".to_string(),
+            config: "{}".to_string(),
+        };
+        Journal::open(&path)
+            .unwrap()
+            .write_header(&fingerprint)
+            .unwrap();
+
+        let entries = Journal::read_all(&path).unwrap();
+        assert_eq!(entries[0].kind, SESSION_START_KIND);
+        assert_eq!(entries[0].data["hash"], "cafe");
+    }
+
+    #[test]
+    fn test_concurrent_producers_keep_sequence_order() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let config = JournalConfig {
+            batch_size: 16,
+            batch_interval_ms: DurationConfig::from_millis(1),
+        };
+        // Two handles on one file share the writer and its numbering
+        let journals = [
+            Arc::new(Journal::open(&path).unwrap().batched(&config).unwrap()),
+            Arc::new(Journal::open(&path).unwrap().batched(&config).unwrap()),
+        ];
+        let kinds = ["signal", "order", "lifecycle", "health"];
+        let producers: Vec<_> = (0..8)
+            .map(|producer| {
+                let journal = journals[producer % 2].clone();
+                std::thread::spawn(move || {
+                    for n in 0..250 {
+                        let kind = kinds[(producer + n) % kinds.len()];
+                        journal
+                            .append(kind, &serde_json::json!({ "producer": producer, "n": n }))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        journals[0].flush().unwrap();
+
+        let read = Journal::read_checked(&path).unwrap();
+        assert_eq!((read.malformed, read.torn_tail), (0, false));
+        let seqs: Vec<u64> = read.entries.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, (0..2000).collect::<Vec<_>>());
+        // Each producer's entries are on disk in the order it appended them
+        let mut last = [None; 8];
+        for entry in &read.entries {
+            let producer = entry.data["producer"].as_u64().unwrap() as usize;
+            let n = entry.data["n"].as_u64().unwrap();
+            assert!(last[producer].is_none_or(|prev| prev < n));
+            last[producer] = Some(n);
+        }
+        drop(journals);
+
+        // Dropping the last handle wrote everything; numbering continues
+        let journal = Journal::open(&path).unwrap().batched(&config).unwrap();
+        journal.append("health", &0).unwrap();
+        drop(journal);
+        let entries = Journal::read_all(&path).unwrap();
+        assert_eq!(entries.last().unwrap().seq, 2000);
+    }
+
+    #[test]
+    fn test_torn_final_line_is_reported_and_sealed() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let journal = Journal::open(&path).unwrap();
+        for n in 0..3 {
+            journal.append("order", &n).unwrap();
+        }
+        drop(journal);
+
+        // A crash cut the last line short
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 10]).unwrap();
+        let read = Journal::read_checked(&path).unwrap();
+        assert_eq!(read.entries.len(), 2);
+        assert!(read.torn_tail);
+        assert_eq!(read.malformed, 0);
+
+        // Reopening seals the fragment, so the next entry is a line of its own
+        let journal = Journal::open(&path).unwrap().durable();
+        journal.append("order", &3).unwrap();
+        let read = Journal::read_checked(&path).unwrap();
+        assert!(!read.torn_tail);
+        assert_eq!(read.malformed, 1);
+        let seqs: Vec<u64> = read.entries.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![0, 1, 2]);
+        assert_eq!(read.entries[2].data, 3);
+    }
+}
@@ -0,0 +1,294 @@
+//! Batched journal writer
+//!
+//! A journal appended to from many tasks at once hands its entries to one
+//! writer thread. Sequence numbers are assigned as an entry is enqueued,
+//! under the same lock that sends it, so the channel, and with it the
+//! file, holds entries in sequence order whatever mix of kinds arrives.
+//! The writer takes up to `batch_size` entries, waiting at most
+//! `batch_interval_ms` after the first, writes them as complete lines in
+//! one write and fsyncs before the next batch. A crash loses at most the
+//! batch not yet fsynced; a line it cut short is sealed off by the next
+//! [`Journal::open`](super::Journal::open) and reported by
+//! [`Journal::read_checked`](super::Journal::read_checked).
+//!
+//! Batched journals opened on the same path share one writer, so the
+//! numbering and order hold across every handle on the file.
+
+use super::{JournalEntry, PENDING};
+use crate::duration::{DurationConfig, Millis};
+use crate::stream::EventStream;
+use crate::telemetry::{record_error, record_journal_batch};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Default largest number of entries written in one batch
+pub const DEFAULT_JOURNAL_BATCH_SIZE: usize = 256;
+
+/// Default longest wait after a batch's first entry before it is written
+pub const DEFAULT_JOURNAL_BATCH_INTERVAL_MS: u64 = 20;
+
+/// Batching of journal writes, under `[data.journal]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalConfig {
+    /// Largest number of entries written and fsynced together
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Longest an entry waits for others to share its fsync
+    #[serde(default = "default_batch_interval_ms")]
+    pub batch_interval_ms: DurationConfig<Millis>,
+}
+
+fn default_batch_size() -> usize {
+    DEFAULT_JOURNAL_BATCH_SIZE
+}
+
+fn default_batch_interval_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_JOURNAL_BATCH_INTERVAL_MS)
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: default_batch_size(),
+            batch_interval_ms: default_batch_interval_ms(),
+        }
+    }
+}
+
+/// An entry serialized and waiting to be written
+struct Queued {
+    entry: JournalEntry,
+    line: String,
+    enqueued: Instant,
+}
+
+enum Command {
+    Entry(Queued),
+    Stream(EventStream),
+    /// Reply once every entry enqueued before is durable, with the first
+    /// write error since the last reply
+    Flush(SyncSender<Option<String>>),
+}
+
+/// The next sequence number and the channel, locked together
+struct Queue {
+    next_seq: u64,
+    tx: Option<Sender<Command>>,
+}
+
+/// Writers running, by journal path
+fn writers() -> &'static Mutex<HashMap<PathBuf, Weak<JournalWriter>>> {
+    static WRITERS: OnceLock<Mutex<HashMap<PathBuf, Weak<JournalWriter>>>> = OnceLock::new();
+    WRITERS.get_or_init(Mutex::default)
+}
+
+/// The writer already running for `path`, if any
+pub(super) fn running(path: &Path) -> Option<Arc<JournalWriter>> {
+    writers()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(path)
+        .and_then(Weak::upgrade)
+}
+
+/// Handle on a journal's writer thread; dropping the last one writes what
+/// is queued
+pub(super) struct JournalWriter {
+    queue: Mutex<Queue>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl JournalWriter {
+    /// The writer running for `path`, or a new one writing to `file` and
+    /// numbering entries from `next_seq`
+    pub(super) fn shared(
+        path: &Path,
+        file: File,
+        next_seq: u64,
+        config: &JournalConfig,
+        stream: Option<EventStream>,
+    ) -> anyhow::Result<Arc<Self>> {
+        let mut writers = writers().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(writer) = writers.get(path).and_then(Weak::upgrade) {
+            if let Some(stream) = stream {
+                writer.set_stream(stream);
+            }
+            return Ok(writer);
+        }
+        let writer = Arc::new(Self::spawn(
+            path.to_path_buf(),
+            file,
+            next_seq,
+            config,
+            stream,
+        )?);
+        writers.retain(|_, writer| writer.strong_count() > 0);
+        writers.insert(path.to_path_buf(), Arc::downgrade(&writer));
+        Ok(writer)
+    }
+
+    /// Start writing to `file`, numbering entries from `next_seq`
+    fn spawn(
+        path: PathBuf,
+        file: File,
+        next_seq: u64,
+        config: &JournalConfig,
+        stream: Option<EventStream>,
+    ) -> anyhow::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let batch_size = config.batch_size.max(1);
+        let interval = config.batch_interval_ms.get();
+        let handle = std::thread::Builder::new()
+            .name("journal-writer".to_string())
+            .spawn(move || run(path, file, rx, batch_size, interval, stream))?;
+        Ok(Self {
+            queue: Mutex::new(Queue {
+                next_seq,
+                tx: Some(tx),
+            }),
+            handle: Some(handle),
+        })
+    }
+
+    /// Number `kind`/`data` and queue it behind every entry numbered before
+    pub(super) fn enqueue(&self, kind: &str, data: serde_json::Value) -> anyhow::Result<()> {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = JournalEntry {
+            seq: queue.next_seq,
+            ts: Utc::now(),
+            kind: kind.to_string(),
+            data,
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        let tx = queue
+            .tx
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("journal writer stopped"))?;
+        PENDING.fetch_add(1, Ordering::Relaxed);
+        if tx
+            .send(Command::Entry(Queued {
+                entry,
+                line,
+                enqueued: Instant::now(),
+            }))
+            .is_err()
+        {
+            PENDING.fetch_sub(1, Ordering::Relaxed);
+            anyhow::bail!("journal writer stopped");
+        }
+        queue.next_seq += 1;
+        Ok(())
+    }
+
+    /// Send entries written from now on to the clients of `stream`
+    pub(super) fn set_stream(&self, stream: EventStream) {
+        let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(tx) = &queue.tx {
+            let _ = tx.send(Command::Stream(stream));
+        }
+    }
+
+    /// Wait until every entry enqueued so far is on disk
+    pub(super) fn flush(&self) -> anyhow::Result<()> {
+        let (reply, done) = mpsc::sync_channel(1);
+        {
+            let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+            let tx = queue
+                .tx
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("journal writer stopped"))?;
+            tx.send(Command::Flush(reply))
+                .map_err(|_| anyhow::anyhow!("journal writer stopped"))?;
+        }
+        match done.recv() {
+            Ok(None) => Ok(()),
+            Ok(Some(error)) => anyhow::bail!("journal write failed: {error}"),
+            Err(_) => anyhow::bail!("journal writer stopped"),
+        }
+    }
+}
+
+impl Drop for JournalWriter {
+    fn drop(&mut self) {
+        self.queue
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .tx
+            .take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Write batches until every sender is gone
+fn run(
+    path: PathBuf,
+    mut file: File,
+    rx: Receiver<Command>,
+    batch_size: usize,
+    interval: Duration,
+    mut stream: Option<EventStream>,
+) {
+    let mut batch: Vec<Queued> = Vec::with_capacity(batch_size);
+    let mut flushes = Vec::new();
+    let mut failure: Option<String> = None;
+
+    while let Ok(first) = rx.recv() {
+        let deadline = Instant::now() + interval;
+        let mut next = Some(first);
+        while let Some(command) = next.take() {
+            match command {
+                Command::Entry(queued) => batch.push(queued),
+                Command::Stream(s) => stream = Some(s),
+                Command::Flush(reply) => {
+                    flushes.push(reply);
+                    break;
+                }
+            }
+            if batch.len() >= batch_size {
+                break;
+            }
+            next = rx
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok();
+        }
+
+        if !batch.is_empty() {
+            if let Err(e) = write_batch(&mut file, &batch) {
+                tracing::error!(path = ?path, entries = batch.len(), error = %e, "Journal batch write failed");
+                record_error("journal", "write_failed");
+                failure.get_or_insert_with(|| e.to_string());
+            } else {
+                record_journal_batch(batch.iter().map(|q| q.enqueued.elapsed()));
+                if let Some(stream) = &stream {
+                    for queued in &batch {
+                        stream.publish(&queued.entry, queued.line.trim_end());
+                    }
+                }
+            }
+            PENDING.fetch_sub(batch.len(), Ordering::Relaxed);
+            batch.clear();
+        }
+        for reply in flushes.drain(..) {
+            let _ = reply.send(failure.take());
+        }
+    }
+}
+
+/// Write a batch as complete lines in one write, then fsync
+fn write_batch(file: &mut File, batch: &[Queued]) -> std::io::Result<()> {
+    let bytes: String = batch.iter().map(|q| q.line.as_str()).collect();
+    file.write_all(bytes.as_bytes())?;
+    file.sync_data()
+}
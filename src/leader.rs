@@ -0,0 +1,524 @@
+//! Leader election between redundant instances
+//!
+//! Two instances can run side by side with only one trading: the holder of
+//! a time-limited lease submits orders, the other runs as a warm standby
+//! that captures, detects and journals but withholds every order. The
+//! leader renews its lease every `renew_interval_secs`; a standby takes it
+//! once it has gone `ttl_secs` without renewal. A leader stops submitting
+//! the moment its own lease runs out, renewed or not, so two instances
+//! never hold an unexpired lease at once.
+//!
+//! The lease lives behind [`LeaderElector`]. The file backend suits
+//! instances sharing a filesystem; both must see the same lease file and
+//! roughly the same clock.
+
+use crate::duration::DurationConfig;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use fs4::fs_std::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Default seconds a lease lasts without renewal
+pub const DEFAULT_LEASE_TTL_SECS: u64 = 15;
+
+/// Default seconds between lease renewals
+pub const DEFAULT_RENEW_INTERVAL_SECS: u64 = 5;
+
+/// Lease file inside the data directory, unless configured elsewhere
+pub const LEADER_LEASE_FILE: &str = "leader.lease";
+
+/// Health component reporting the instance's role
+pub const LEADERSHIP_HEALTH_COMPONENT: &str = "leadership";
+
+/// Where the lease is kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaseBackend {
+    /// A JSON file updated under an exclusive file lock
+    #[default]
+    File,
+}
+
+/// Leader election settings, under `[leader]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderConfig {
+    /// Whether this instance competes for the lease; without it, it always
+    /// trades
+    #[serde(default)]
+    pub enabled: bool,
+    /// Lease backend
+    #[serde(default)]
+    pub backend: LeaseBackend,
+    /// Lease file shared by the instances; defaults to [`LEADER_LEASE_FILE`]
+    /// in the data directory
+    #[serde(default)]
+    pub lease_path: Option<PathBuf>,
+    /// How long a lease lasts without renewal
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: DurationConfig,
+    /// Time between renewals; must be shorter than `ttl_secs`
+    #[serde(default = "default_renew_interval_secs")]
+    pub renew_interval_secs: DurationConfig,
+    /// Name of this instance in the lease; defaults to host and pid
+    #[serde(default)]
+    pub instance_id: Option<String>,
+}
+
+fn default_ttl_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_LEASE_TTL_SECS)
+}
+
+fn default_renew_interval_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_RENEW_INTERVAL_SECS)
+}
+
+impl Default for LeaderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: LeaseBackend::File,
+            lease_path: None,
+            ttl_secs: default_ttl_secs(),
+            renew_interval_secs: default_renew_interval_secs(),
+            instance_id: None,
+        }
+    }
+}
+
+impl LeaderConfig {
+    /// This instance's name in the lease
+    pub fn instance_id(&self) -> String {
+        self.instance_id.clone().unwrap_or_else(|| {
+            let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "poly-hft".to_string());
+            format!("{}-{}", host, std::process::id())
+        })
+    }
+
+    /// Lease file for a data directory
+    pub fn lease_path(&self, data_dir: &Path) -> PathBuf {
+        self.lease_path
+            .clone()
+            .unwrap_or_else(|| data_dir.join(LEADER_LEASE_FILE))
+    }
+
+    /// Elector of the configured backend
+    pub fn elector(&self, data_dir: &Path) -> Box<dyn LeaderElector> {
+        match self.backend {
+            LeaseBackend::File => Box::new(FileElector::new(self.lease_path(data_dir))),
+        }
+    }
+}
+
+/// A time-limited claim to lead
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    /// Instance holding the lease
+    pub holder: String,
+    /// When the holder took it
+    pub acquired_at: DateTime<Utc>,
+    /// When the holder last renewed it
+    pub renewed_at: DateTime<Utc>,
+    /// When it lapses without another renewal
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Lease {
+    /// Whether the lease has lapsed at `now`
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// How long the holder has held it
+    pub fn age(&self, now: DateTime<Utc>) -> Duration {
+        now - self.acquired_at
+    }
+
+    /// Lease after `instance` tries to take or renew `current`
+    fn claim(current: Option<Lease>, instance: &str, now: DateTime<Utc>, ttl: Duration) -> Lease {
+        match current {
+            Some(lease) if lease.holder != instance && !lease.is_expired(now) => lease,
+            Some(lease) if lease.holder == instance && !lease.is_expired(now) => Lease {
+                renewed_at: now,
+                expires_at: now + ttl,
+                ..lease
+            },
+            _ => Lease {
+                holder: instance.to_string(),
+                acquired_at: now,
+                renewed_at: now,
+                expires_at: now + ttl,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Lease {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "held by {} since {}, expires {}",
+            self.holder,
+            self.acquired_at.to_rfc3339(),
+            self.expires_at.to_rfc3339()
+        )
+    }
+}
+
+/// Shared store of the leader lease
+#[async_trait]
+pub trait LeaderElector: Send + Sync {
+    /// Take or renew the lease for `instance` unless another instance holds
+    /// an unexpired one; returns the lease as it stands afterwards
+    async fn acquire(
+        &self,
+        instance: &str,
+        now: DateTime<Utc>,
+        ttl: Duration,
+    ) -> anyhow::Result<Lease>;
+    /// Give the lease up if `instance` holds it
+    async fn release(&self, instance: &str) -> anyhow::Result<()>;
+    /// The lease, if any instance has taken one
+    async fn current(&self) -> anyhow::Result<Option<Lease>>;
+}
+
+/// Lease held in memory, shared by the clones of one elector
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryElector {
+    lease: Arc<Mutex<Option<Lease>>>,
+}
+
+impl InMemoryElector {
+    /// Create an elector with no lease taken
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LeaderElector for InMemoryElector {
+    async fn acquire(
+        &self,
+        instance: &str,
+        now: DateTime<Utc>,
+        ttl: Duration,
+    ) -> anyhow::Result<Lease> {
+        let mut lease = self.lease.lock().unwrap_or_else(|e| e.into_inner());
+        let claimed = Lease::claim(lease.take(), instance, now, ttl);
+        *lease = Some(claimed.clone());
+        Ok(claimed)
+    }
+
+    async fn release(&self, instance: &str) -> anyhow::Result<()> {
+        let mut lease = self.lease.lock().unwrap_or_else(|e| e.into_inner());
+        if lease.as_ref().is_some_and(|l| l.holder == instance) {
+            *lease = None;
+        }
+        Ok(())
+    }
+
+    async fn current(&self) -> anyhow::Result<Option<Lease>> {
+        Ok(self.lease.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+/// Lease kept as JSON in a file, read and written under an exclusive lock
+#[derive(Debug, Clone)]
+pub struct FileElector {
+    path: PathBuf,
+}
+
+impl FileElector {
+    /// Elector over the lease file at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Apply `update` to the lease under the file lock
+    fn update<T>(
+        &self,
+        update: impl FnOnce(Option<Lease>) -> (Option<Lease>, T),
+    ) -> anyhow::Result<T> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        file.lock_exclusive()?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let current = parse_lease(&contents)?;
+        let (next, result) = update(current);
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        if let Some(lease) = next {
+            file.write_all(serde_json::to_string(&lease)?.as_bytes())?;
+        }
+        file.sync_data()?;
+        FileExt::unlock(&file)?;
+        Ok(result)
+    }
+}
+
+fn parse_lease(contents: &str) -> anyhow::Result<Option<Lease>> {
+    if contents.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(contents)?))
+}
+
+#[async_trait]
+impl LeaderElector for FileElector {
+    async fn acquire(
+        &self,
+        instance: &str,
+        now: DateTime<Utc>,
+        ttl: Duration,
+    ) -> anyhow::Result<Lease> {
+        self.update(|current| {
+            let lease = Lease::claim(current, instance, now, ttl);
+            (Some(lease.clone()), lease)
+        })
+    }
+
+    async fn release(&self, instance: &str) -> anyhow::Result<()> {
+        self.update(|current| match current {
+            Some(lease) if lease.holder == instance => (None, ()),
+            other => (other, ()),
+        })
+    }
+
+    async fn current(&self) -> anyhow::Result<Option<Lease>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => parse_lease(&contents),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Whether an instance trades
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Holds the lease and submits orders
+    Leader,
+    /// Observes only, ready to take over
+    Standby,
+}
+
+impl Role {
+    /// Lowercase name used in journals and health
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Leader => "leader",
+            Role::Standby => "standby",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// This instance's side of the election
+///
+/// An instance starts as standby and leads from the first poll that wins
+/// the lease.
+pub struct Leadership {
+    elector: Box<dyn LeaderElector>,
+    instance: String,
+    ttl: Duration,
+    renew_interval: Duration,
+    role: Role,
+    /// Lease as last seen, whoever holds it
+    lease: Option<Lease>,
+    last_poll: Option<DateTime<Utc>>,
+}
+
+impl Leadership {
+    /// Compete for the lease of `elector` as `instance`
+    pub fn new(
+        config: &LeaderConfig,
+        elector: Box<dyn LeaderElector>,
+        instance: impl Into<String>,
+    ) -> anyhow::Result<Self> {
+        if config.renew_interval_secs >= config.ttl_secs {
+            anyhow::bail!(
+                "[leader] renew_interval_secs ({}) must be shorter than ttl_secs ({})",
+                config.renew_interval_secs,
+                config.ttl_secs
+            );
+        }
+        Ok(Self {
+            elector,
+            instance: instance.into(),
+            ttl: config.ttl_secs.to_chrono(),
+            renew_interval: config.renew_interval_secs.to_chrono(),
+            role: Role::Standby,
+            lease: None,
+            last_poll: None,
+        })
+    }
+
+    /// This instance's name in the lease
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    /// Role as of the last poll
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Lease as last seen, whoever holds it
+    pub fn lease(&self) -> Option<&Lease> {
+        self.lease.as_ref()
+    }
+
+    /// Whether orders may go out at `now`: this instance leads and its
+    /// lease has not run out
+    pub fn may_submit(&self, now: DateTime<Utc>) -> bool {
+        self.role == Role::Leader
+            && self
+                .lease
+                .as_ref()
+                .is_some_and(|l| l.holder == self.instance && !l.is_expired(now))
+    }
+
+    /// Whether the lease should be polled at `now`
+    pub fn due(&self, now: DateTime<Utc>) -> bool {
+        let waited = self
+            .last_poll
+            .is_none_or(|last| now - last >= self.renew_interval);
+        waited || (self.role == Role::Leader && !self.may_submit(now))
+    }
+
+    /// Renew or try to take the lease; returns the new role if it changed
+    ///
+    /// When the backend cannot be reached a leader keeps leading only
+    /// until its own lease runs out.
+    pub async fn poll(&mut self, now: DateTime<Utc>) -> Option<Role> {
+        self.last_poll = Some(now);
+        let held = match self.elector.acquire(&self.instance, now, self.ttl).await {
+            Ok(lease) => {
+                let held = lease.holder == self.instance;
+                self.lease = Some(lease);
+                held
+            }
+            Err(error) => {
+                tracing::warn!(instance = %self.instance, %error, "Leader lease unreachable");
+                self.may_submit(now)
+            }
+        };
+        let role = if held { Role::Leader } else { Role::Standby };
+        if role == self.role {
+            return None;
+        }
+        self.role = role;
+        Some(role)
+    }
+
+    /// Give the lease up so a standby can take over at once
+    pub async fn release(&mut self) -> anyhow::Result<()> {
+        self.role = Role::Standby;
+        self.elector.release(&self.instance).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_735_689_600 + secs, 0).unwrap()
+    }
+
+    fn leadership(elector: &InMemoryElector, instance: &str) -> Leadership {
+        Leadership::new(
+            &LeaderConfig::default(),
+            Box::new(elector.clone()),
+            instance,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_standby_takes_over_a_lapsed_lease() {
+        let elector = InMemoryElector::new();
+        let mut a = leadership(&elector, "a");
+        let mut b = leadership(&elector, "b");
+
+        assert_eq!(a.poll(ts(0)).await, Some(Role::Leader));
+        assert_eq!(b.poll(ts(0)).await, None);
+        assert_eq!(b.role(), Role::Standby);
+        assert!(a.may_submit(ts(14)));
+
+        // a stops renewing: it stops submitting when its lease lapses,
+        // before b can take it
+        assert!(!a.may_submit(ts(15)));
+        assert!(a.due(ts(15)));
+        assert_eq!(b.poll(ts(10)).await, None);
+        assert_eq!(b.poll(ts(15)).await, Some(Role::Leader));
+        assert_eq!(b.lease().unwrap().age(ts(20)), Duration::seconds(5));
+        assert_eq!(a.poll(ts(16)).await, Some(Role::Standby));
+        assert_eq!(a.lease().unwrap().holder, "b");
+    }
+
+    #[tokio::test]
+    async fn test_renewal_keeps_the_lease_and_release_hands_it_over() {
+        let elector = InMemoryElector::new();
+        let mut a = leadership(&elector, "a");
+        let mut b = leadership(&elector, "b");
+        a.poll(ts(0)).await;
+        assert!(!a.due(ts(4)));
+        assert!(a.due(ts(5)));
+        a.poll(ts(10)).await;
+        assert_eq!(b.poll(ts(20)).await, None);
+        assert_eq!(a.lease().unwrap().acquired_at, ts(0));
+
+        a.release().await.unwrap();
+        assert!(!a.may_submit(ts(21)));
+        assert_eq!(b.poll(ts(21)).await, Some(Role::Leader));
+    }
+
+    #[tokio::test]
+    async fn test_file_lease_is_shared() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LEADER_LEASE_FILE);
+        let a = FileElector::new(&path);
+        let b = FileElector::new(&path);
+        let ttl = Duration::seconds(15);
+        assert_eq!(a.current().await.unwrap(), None);
+
+        assert_eq!(a.acquire("a", ts(0), ttl).await.unwrap().holder, "a");
+        assert_eq!(b.acquire("b", ts(5), ttl).await.unwrap().holder, "a");
+        assert_eq!(b.acquire("b", ts(15), ttl).await.unwrap().holder, "b");
+        assert_eq!(b.current().await.unwrap().unwrap().holder, "b");
+
+        a.release("a").await.unwrap();
+        assert_eq!(a.current().await.unwrap().unwrap().holder, "b");
+        b.release("b").await.unwrap();
+        assert_eq!(a.current().await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_renew_interval_must_be_shorter_than_ttl() {
+        let config = LeaderConfig {
+            renew_interval_secs: DurationConfig::from_secs(15),
+            ..Default::default()
+        };
+        let elector = Box::new(InMemoryElector::new());
+        assert!(Leadership::new(&config, elector, "a").is_err());
+    }
+}
@@ -2,26 +2,132 @@
 //!
 //! This library provides the core components for:
 //! - Real-time price feeds from Binance
+//! - Bounded fanout of market data to consumers
 //! - Market discovery via Gamma API
+//! - Asset to feed symbol routing
 //! - Order book management from Polymarket WebSocket
 //! - Fair value calculation using GBM model
 //! - Signal generation and filtering
+//! - Feature flags with percentage rollout
 //! - Paper/live execution engine
 //! - Risk management with Kelly criterion
 //! - Data capture to Parquet
 //! - Backtesting with queue simulation
+//! - Offline simulation on synthetic data
+//! - Session reports for charting
 //! - Full observability stack
+//! - Effective runtime parameters and their provenance
+//! - Live event stream for external consumers
+//! - Environment self-test
+//!
+//! Library consumers should import from [`prelude`], whose names are kept
+//! stable; module paths behind it may change between releases. Modules
+//! hidden from the docs serve the binary and are not part of the API.
+//!
+//! Load a config and size a signal from a custom sizer or the Kelly one:
+//!
+//! ```
+//! use poly_hft::prelude::*;
+//! use rust_decimal::Decimal;
+//!
+//! struct FlatSizer(Decimal);
+//!
+//! impl PositionSizer for FlatSizer {
+//!     fn size(&self, _signal: &Signal, bankroll: Decimal) -> Decimal {
+//!         self.0.min(bankroll)
+//!     }
+//! }
+//!
+//! fn sizers(config: &Config) -> Vec<Box<dyn PositionSizer>> {
+//!     vec![
+//!         Box::new(KellyCalculator::new(
+//!             config.risk.kelly_fraction,
+//!             config.risk.max_position_pct,
+//!         )),
+//!         Box::new(FlatSizer(Decimal::TEN)),
+//!     ]
+//! }
+//!
+//! let path = concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml.example");
+//! let config = Config::load(path).unwrap();
+//! assert_eq!(sizers(&config).len(), 2);
+//! assert_eq!(config.execution.mode, ExecutionMode::Paper);
+//! ```
+//!
+//! Feed prices from another source by implementing [`prelude::PriceFeed`]:
+//!
+//! ```
+//! use poly_hft::prelude::*;
+//! use tokio::sync::mpsc;
+//!
+//! struct ReplayFeed(Vec<PriceTick>);
+//!
+//! #[async_trait]
+//! impl PriceFeed for ReplayFeed {
+//!     async fn subscribe(&self) -> anyhow::Result<mpsc::Receiver<PriceTick>> {
+//!         let (tx, rx) = mpsc::channel(self.0.len().max(1));
+//!         for tick in &self.0 {
+//!             tx.try_send(tick.clone())?;
+//!         }
+//!         Ok(rx)
+//!     }
+//! }
+//!
+//! # tokio_test::block_on(async {
+//! let mut rx = ReplayFeed(vec![]).subscribe().await.unwrap();
+//! assert!(rx.recv().await.is_none());
+//! # });
+//! ```
+//!
+//! Read a book:
+//!
+//! ```
+//! use poly_hft::prelude::*;
+//! use rust_decimal_macros::dec;
+//!
+//! let mut book = OrderBook::new("yes-token");
+//! book.bids.push(PriceLevel { price: dec!(0.48), size: dec!(100) });
+//! book.asks.push(PriceLevel { price: dec!(0.52), size: dec!(80) });
+//! assert_eq!(book.mid_price(), Some(dec!(0.50)));
+//! assert_eq!(book.depth_within(BookSide::Ask, 2), dec!(80));
+//! ```
 
 pub mod backtest;
+pub mod breaker;
+pub mod bus;
+#[doc(hidden)]
 pub mod cli;
+pub mod clock;
 pub mod config;
 pub mod data;
+pub mod doctor;
+pub mod duration;
+pub mod effective;
+pub mod engine;
 pub mod execution;
 pub mod feed;
+#[doc(hidden)]
+pub mod fingerprint;
+pub mod flags;
+pub mod ids;
+pub mod journal;
+pub mod leader;
 pub mod market;
 pub mod model;
 pub mod orderbook;
+#[doc(hidden)]
+pub mod precision;
+pub mod prelude;
+pub mod report;
+#[doc(hidden)]
+pub mod retry;
 pub mod risk;
 pub mod signal;
+pub mod sim;
+pub mod stream;
+#[doc(hidden)]
+pub mod supervisor;
+pub mod symbols;
 pub mod telemetry;
+#[doc(hidden)]
 pub mod ws;
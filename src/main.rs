@@ -1,12 +1,15 @@
 use clap::Parser;
 use poly_hft::cli::{Cli, Commands};
 use poly_hft::config::Config;
+use poly_hft::data::DataDirLock;
+use poly_hft::fingerprint::ConfigFingerprint;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     // Load configuration
+    let source = std::fs::read_to_string(&cli.config).ok();
     let config = Config::load(&cli.config).unwrap_or_else(|e| {
         eprintln!("Warning: Could not load config from {}: {}", cli.config, e);
         eprintln!("Using default configuration");
@@ -14,40 +17,310 @@ async fn main() -> anyhow::Result<()> {
         toml::from_str(include_str!("../config.toml.example")).expect("Invalid default config")
     });
 
-    // Initialize telemetry
-    poly_hft::telemetry::init_telemetry(&config.telemetry)?;
+    // Initialize telemetry; doctor checks the metrics port, so its own
+    // exporter takes an ephemeral one
+    let mut telemetry = config.telemetry.clone();
+    if matches!(cli.command, Commands::Doctor(_)) {
+        telemetry.metrics_port = 0;
+    }
+    let _telemetry = poly_hft::telemetry::init_telemetry(&telemetry)?;
+
+    // Parameters the file sets are told from defaults in the effective
+    // config registry
+    poly_hft::effective::install_source(source.as_deref());
+    // A running session reloads the flags the file sets
+    poly_hft::flags::install_config_path(&cli.config);
+
+    // Stamp every output with the effective config
+    let fingerprint = poly_hft::fingerprint::install(ConfigFingerprint::new(&config)?);
+    poly_hft::telemetry::set_config_fingerprint(&fingerprint.hash);
+    tracing::info!(config_hash = %fingerprint.short_hash(), "Config fingerprint");
 
     match cli.command {
         Commands::Run(args) => {
             tracing::info!("Starting paper trading mode");
-            args.execute().await?;
+            args.execute(&config).await?;
         }
         Commands::Capture(args) => {
             tracing::info!("Starting data capture mode");
-            args.execute().await?;
+            args.execute(&config.data).await?;
         }
         Commands::Backtest(args) => {
             tracing::info!("Starting backtest");
+            args.execute(&config).await?;
+        }
+        Commands::Eval(args) => {
+            args.execute(&config)?;
+        }
+        Commands::Data(args) => {
+            args.execute().await?;
+        }
+        Commands::Features(args) => {
             args.execute().await?;
         }
-        Commands::Status => {
+        Commands::Report(args) => {
+            args.execute(&config)?;
+        }
+        Commands::Gamma(args) => {
+            args.execute(&config).await?;
+        }
+        Commands::Status {
+            internals,
+            effective_config,
+        } => {
             println!("poly-hft status");
+            match poly_hft::risk::HaltStore::new(&config.data.output_dir).pending() {
+                Ok(pending) => {
+                    for halt in pending {
+                        println!("  !! HALT PENDING ACKNOWLEDGEMENT: {}", halt);
+                        println!(
+                            "  !! Orders are withheld until `poly-hft ctl ack-halt {}`",
+                            halt.id
+                        );
+                    }
+                }
+                Err(e) => println!("  !! Halt records unreadable: {}", e),
+            }
+            let cooldown = poly_hft::risk::LossCooldown::new(config.risk.loss_cooldown.clone())
+                .with_state_file(
+                    config
+                        .data
+                        .output_dir
+                        .join(poly_hft::risk::LOSS_COOLDOWN_FILE),
+                );
+            match cooldown {
+                Ok(cooldown) => {
+                    let now = chrono::Utc::now();
+                    let windows = cooldown.config().windows;
+                    for (asset, state) in cooldown.assets() {
+                        if state.halted_at.is_some() {
+                            println!(
+                                "  !! {} HALTED after {} losses in a row; entries withheld until `poly-hft ctl ack-cooldown {}`",
+                                asset, state.consecutive_losses, asset
+                            );
+                        } else if state.is_cooling(windows, now) {
+                            let until = state
+                                .until
+                                .filter(|until| *until > now)
+                                .map(|until| format!(", until {}", until.to_rfc3339()))
+                                .unwrap_or_default();
+                            println!(
+                                "  Loss cooldown [{}]: {}/{} windows skipped{} (last loss {:.2}, {} in a row)",
+                                asset,
+                                state.skipped.len(),
+                                windows,
+                                until,
+                                state.last_loss,
+                                state.consecutive_losses
+                            );
+                        }
+                    }
+                }
+                Err(e) => println!("  !! Loss cooldown unreadable: {}", e),
+            }
+            let review = poly_hft::risk::StrategyReview::new(config.risk.review.clone())
+                .with_state_file(
+                    config
+                        .data
+                        .output_dir
+                        .join(poly_hft::risk::STRATEGY_REVIEW_FILE),
+                );
+            match review {
+                Ok(review) => {
+                    for (asset, state) in review.assets() {
+                        let Some(flag) = &state.flag else {
+                            continue;
+                        };
+                        let pending = if review.clear_requested(asset) {
+                            " (clear requested)"
+                        } else {
+                            ""
+                        };
+                        println!(
+                            "  !! {} FLAGGED FOR STRATEGY REVIEW{}: {}",
+                            asset, pending, flag
+                        );
+                        println!(
+                            "  !! Entries sized at {} of the stake until `poly-hft ctl ack-review {}`",
+                            review.config().size_scale,
+                            asset
+                        );
+                    }
+                }
+                Err(e) => println!("  !! Strategy review unreadable: {}", e),
+            }
+            let rate = poly_hft::risk::RateLimiter::new(config.risk.rate_caps.clone())
+                .with_state_file(config.data.output_dir.join(poly_hft::risk::RATE_CAPS_FILE));
+            match rate {
+                Ok(rate) => {
+                    let now = chrono::Utc::now();
+                    let window = poly_hft::market::window_start(now);
+                    let asset = config.market.asset.to_uppercase();
+                    let mut assets = rate.assets();
+                    assets.insert(&asset);
+                    let mut shown = false;
+                    for asset in assets {
+                        for budget in rate.budgets(asset, window, now) {
+                            // The global cap is the same for every asset
+                            if budget.cap.is_global() && shown {
+                                continue;
+                            }
+                            shown |= budget.cap.is_global();
+                            let flag = if budget.remaining() == 0 { "!! " } else { "" };
+                            println!("  {}Rate cap {}", flag, budget);
+                        }
+                    }
+                }
+                Err(e) => println!("  !! Rate caps unreadable: {}", e),
+            }
+            let pending_path = config
+                .data
+                .output_dir
+                .join(poly_hft::risk::PENDING_RESOLUTIONS_FILE);
+            match poly_hft::risk::ResolutionBook::load(&pending_path) {
+                Ok(pending) => {
+                    if !pending.is_empty() {
+                        let pnl: rust_decimal::Decimal = pending.iter().map(|p| p.pnl()).sum();
+                        println!(
+                            "  PROVISIONAL: {} settlements awaiting official resolution, P&L {:+.2}",
+                            pending.len(),
+                            pnl
+                        );
+                    }
+                    for settlement in pending {
+                        println!("    {}", settlement);
+                    }
+                }
+                Err(e) => println!("  !! Pending resolutions unreadable: {}", e),
+            }
             println!("  Mode: Paper Trading");
             println!("  Status: Not running");
-        }
-        Commands::Config => {
-            println!("Current configuration:");
-            println!("  Feed: {} {}", config.feed.exchange, config.feed.symbol);
-            println!(
-                "  Market: {} {}",
-                config.market.asset, config.market.interval
-            );
-            println!("  Execution: {:?}", config.execution.mode);
+            if config.leader.enabled {
+                let elector = config.leader.elector(&config.data.output_dir);
+                let now = chrono::Utc::now();
+                match elector.current().await {
+                    Ok(Some(lease)) if !lease.is_expired(now) => println!(
+                        "  Leader: {} (lease age {}s, expires in {}s)",
+                        lease.holder,
+                        lease.age(now).num_seconds(),
+                        (lease.expires_at - now).num_seconds()
+                    ),
+                    Ok(Some(lease)) => println!(
+                        "  Leader: none (lease of {} lapsed at {})",
+                        lease.holder,
+                        lease.expires_at.to_rfc3339()
+                    ),
+                    Ok(None) => println!("  Leader: none"),
+                    Err(e) => println!("  !! Leader lease unreadable: {}", e),
+                }
+            }
+            let data_dir = &config.data.output_dir;
+            match DataDirLock::status(data_dir) {
+                Ok(status) => println!("  Data dir {}: {}", data_dir.display(), status),
+                Err(e) => println!("  Data dir {}: unreadable lock ({})", data_dir.display(), e),
+            }
+            for (dir, status) in DataDirLock::instances(data_dir).unwrap_or_default() {
+                println!("  Instance dir {}: {}", dir.display(), status);
+            }
+            let schedule = poly_hft::risk::TradingSchedule::new(config.schedule.clone());
+            for status in schedule.status(chrono::Utc::now()) {
+                let next = status
+                    .next_transition
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_else(|| "never".to_string());
+                println!(
+                    "  Schedule [{}]: {} (next change: {})",
+                    status.strategy,
+                    if status.open { "open" } else { "closed" },
+                    next
+                );
+            }
+            let caps = &config.risk.market;
+            let cap = |v: Option<rust_decimal::Decimal>| {
+                v.map(|d| d.to_string())
+                    .unwrap_or_else(|| "none".to_string())
+            };
             println!(
-                "  Risk: Kelly={}, MaxPos={}%",
-                config.risk.kelly_fraction,
-                config.risk.max_position_pct * rust_decimal_macros::dec!(100)
+                "  Market caps: net shares {}, gross notional {}, worst-case loss {}, per group {}",
+                cap(caps.max_net_shares),
+                cap(caps.max_gross_notional),
+                cap(caps.max_worst_case_loss),
+                cap(caps.max_exposure_per_group)
             );
+            // Positions live in the running engine; a stopped bot holds none
+            let tracker = poly_hft::risk::PositionTracker::new();
+            let exposures = tracker.market_exposures();
+            if exposures.is_empty() {
+                println!("  Market exposure: no open positions");
+            }
+            for exposure in exposures {
+                println!("  Market exposure {}", exposure);
+            }
+            for exposure in tracker.group_exposures() {
+                println!("  Group exposure {}", exposure);
+            }
+            if internals {
+                let mut dirs = vec![data_dir.clone()];
+                dirs.extend(
+                    DataDirLock::instances(data_dir)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(dir, _)| dir),
+                );
+                let mut found = false;
+                for dir in dirs {
+                    let path = dir.join(poly_hft::telemetry::INTERNALS_FILE);
+                    match poly_hft::telemetry::InternalsSnapshot::load(&path) {
+                        Ok(Some(snapshot)) => {
+                            found = true;
+                            println!("  Internals of {}:", dir.display());
+                            print!("{}", snapshot);
+                        }
+                        Ok(None) => {}
+                        Err(e) => println!("  !! Internals {} unreadable: {}", path.display(), e),
+                    }
+                }
+                if !found {
+                    println!("  Internals: no snapshot; the bot writes one every internals_interval_secs while running");
+                }
+            }
+            if effective_config {
+                let mut dirs = vec![data_dir.clone()];
+                dirs.extend(
+                    DataDirLock::instances(data_dir)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(dir, _)| dir),
+                );
+                let mut found = false;
+                for dir in dirs {
+                    let path = dir.join(poly_hft::effective::EFFECTIVE_CONFIG_FILE);
+                    match poly_hft::effective::EffectiveConfig::load(&path) {
+                        Ok(Some(registry)) => {
+                            found = true;
+                            println!("  Effective config of {}:", dir.display());
+                            print!("{}", registry);
+                            print!("{}", registry.timeline());
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            println!("  !! Effective config {} unreadable: {}", path.display(), e)
+                        }
+                    }
+                }
+                if !found {
+                    println!("  Effective config: none; the bot writes it when a session starts");
+                }
+            }
+        }
+        Commands::Doctor(args) => {
+            args.execute(&config).await?;
+        }
+        Commands::Ctl(args) => {
+            args.execute(&config)?;
+        }
+        Commands::Config(args) => {
+            args.execute(&config, &cli.config)?;
         }
     }
 
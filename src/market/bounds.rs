@@ -0,0 +1,89 @@
+//! Venue price bounds
+//!
+//! Polymarket books quote on a tick grid strictly inside (0, 1): the best a
+//! token can trade at is one tick from either end. A model probability past
+//! those ticks is not a price anyone can quote, and an order limit past them
+//! is meaningless, so both are held to [`PriceBounds`].
+
+use crate::risk::RiskError;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+/// Default tick size of a Polymarket book
+pub const DEFAULT_TICK_SIZE: Decimal = dec!(0.01);
+
+/// Prices a token can be quoted at, one tick in from 0 and 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceBounds {
+    tick_size: Decimal,
+}
+
+impl Default for PriceBounds {
+    fn default() -> Self {
+        Self::new(DEFAULT_TICK_SIZE)
+    }
+}
+
+impl PriceBounds {
+    /// Bounds of a book quoting in `tick_size` steps
+    pub fn new(tick_size: Decimal) -> Self {
+        Self { tick_size }
+    }
+
+    /// Lowest quotable price
+    pub fn min(&self) -> Decimal {
+        self.tick_size
+    }
+
+    /// Highest quotable price
+    pub fn max(&self) -> Decimal {
+        Decimal::ONE - self.tick_size
+    }
+
+    /// `price` held to the quotable range
+    pub fn clamp(&self, price: Decimal) -> Decimal {
+        price.clamp(self.min(), self.max())
+    }
+
+    /// Whether `price` can be quoted
+    pub fn contains(&self, price: Decimal) -> bool {
+        (self.min()..=self.max()).contains(&price)
+    }
+
+    /// Reject an order limit of `price` outside the quotable range
+    pub fn check(&self, price: Decimal) -> Result<(), RiskError> {
+        if self.contains(price) {
+            return Ok(());
+        }
+        Err(RiskError::PriceOutOfBounds {
+            price,
+            min: self.min(),
+            max: self.max(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounds_follow_the_tick_size() {
+        let cents = PriceBounds::default();
+        assert_eq!(cents.clamp(dec!(0.997)), dec!(0.99));
+        assert_eq!(cents.clamp(dec!(0.003)), dec!(0.01));
+        assert_eq!(cents.clamp(dec!(0.42)), dec!(0.42));
+        assert!(cents.check(dec!(0.99)).is_ok());
+        let err = cents.check(dec!(0.995)).unwrap_err();
+        assert!(matches!(err, RiskError::PriceOutOfBounds { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Limit price 0.995 outside quotable range 0.01-0.99"
+        );
+
+        let mils = PriceBounds::new(dec!(0.001));
+        assert_eq!(mils.clamp(dec!(0.997)), dec!(0.997));
+        assert_eq!(mils.clamp(dec!(1)), dec!(0.999));
+        assert!(mils.check(dec!(0.995)).is_ok());
+    }
+}
@@ -1,25 +1,611 @@
 //! Gamma API client for market discovery
 
-use super::Market;
+use super::schema::{
+    optional, record_drift, required, DriftKind, EncodedList, LenientFlag, LenientText, LenientTime,
+};
+use super::{Market, TokenOrientation};
+use crate::config::MarketConfig;
+use crate::retry::{retry, transient_http, RetryError, RetryPolicy};
+use crate::signal::Side;
+use crate::telemetry::{record_latency, LatencyMetric};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Series slug for the 15-minute BTC up/down markets
+pub(super) const BTC_15M_SERIES_SLUG: &str = "btc-up-or-down-15m";
+
+/// Length of one up/down window in seconds
+pub(crate) const WINDOW_SECS: i64 = 900;
+
+/// Default page size for paginated endpoints
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Gamma REST base URL
+pub const GAMMA_URL: &str = "https://gamma-api.polymarket.com";
+
+/// Default maximum number of pages fetched per listing
+pub const DEFAULT_MAX_PAGES: usize = 20;
+
+/// Default number of slug lookups in flight at once
+pub const DEFAULT_LOOKUP_CONCURRENCY: usize = 4;
+
+/// Default number of slugs sent in one `/events` request
+pub const DEFAULT_SLUG_BATCH_SIZE: usize = 10;
+
+/// Default time budget for the slug lookups of one discovery cycle
+pub const DEFAULT_DISCOVERY_DEADLINE_MS: u64 = 5000;
+
+/// Subsystem of Gamma requests in retry metrics and budgets
+pub const GAMMA_RETRY_SUBSYSTEM: &str = "gamma";
+
+/// Market as returned by the Gamma API
+///
+/// Read through [`RawGammaMarket`], so drifted fields are converted or
+/// recorded rather than failing the response.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawGammaMarket")]
+pub struct GammaMarket {
+    /// Condition identifier
+    pub condition_id: String,
+    /// JSON-encoded array of CLOB token ids, ordered like `outcomes`
+    pub clob_token_ids: Option<String>,
+    /// JSON-encoded array of outcome names
+    pub outcomes: Option<String>,
+    /// JSON-encoded array of outcome prices, ordered like `outcomes`; a
+    /// resolved market pays `"1"` and `"0"`
+    pub outcome_prices: Option<String>,
+    /// Start of the trading window
+    pub event_start_time: Option<DateTime<Utc>>,
+    /// Listing start date (fallback when `event_start_time` is absent)
+    pub start_date: Option<DateTime<Utc>>,
+    /// Market end/settlement date
+    pub end_date: Option<DateTime<Utc>>,
+    /// Whether the market is closed
+    pub closed: bool,
+    /// Whether the market belongs to a neg-risk (mutually exclusive) group
+    pub neg_risk: bool,
+    /// Identifier shared by the markets of a neg-risk group
+    pub neg_risk_market_id: Option<String>,
+}
+
+/// [`GammaMarket`] as received, each field in any shape Gamma has sent it
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawGammaMarket {
+    #[serde(default)]
+    condition_id: Option<LenientText>,
+    #[serde(default)]
+    clob_token_ids: Option<EncodedList>,
+    #[serde(default)]
+    outcomes: Option<EncodedList>,
+    #[serde(default)]
+    outcome_prices: Option<EncodedList>,
+    #[serde(default)]
+    event_start_time: Option<LenientTime>,
+    #[serde(default)]
+    start_date: Option<LenientTime>,
+    #[serde(default)]
+    end_date: Option<LenientTime>,
+    #[serde(default)]
+    closed: Option<LenientFlag>,
+    #[serde(default)]
+    neg_risk: Option<LenientFlag>,
+    #[serde(default, rename = "negRiskMarketID")]
+    neg_risk_market_id: Option<LenientText>,
+}
+
+impl TryFrom<RawGammaMarket> for GammaMarket {
+    type Error = String;
+
+    fn try_from(raw: RawGammaMarket) -> Result<Self, String> {
+        const ENTITY: &str = "market";
+        let condition_id = required(raw.condition_id, ENTITY, "conditionId")
+            .ok_or("market without a conditionId")?;
+        let event_start_time = optional(raw.event_start_time, ENTITY, "eventStartTime");
+        let start_date = optional(raw.start_date, ENTITY, "startDate");
+        if event_start_time.is_none() && start_date.is_none() {
+            record_drift(ENTITY, "eventStartTime", DriftKind::Missing);
+        }
+        let closed = optional(raw.closed, ENTITY, "closed").unwrap_or(false);
+        // Only a closed market is read for its payout
+        let outcome_prices = if closed {
+            required(raw.outcome_prices, ENTITY, "outcomePrices")
+        } else {
+            optional(raw.outcome_prices, ENTITY, "outcomePrices")
+        };
+        Ok(Self {
+            condition_id,
+            clob_token_ids: required(raw.clob_token_ids, ENTITY, "clobTokenIds"),
+            outcomes: optional(raw.outcomes, ENTITY, "outcomes"),
+            outcome_prices,
+            event_start_time,
+            start_date,
+            end_date: required(raw.end_date, ENTITY, "endDate"),
+            closed,
+            neg_risk: optional(raw.neg_risk, ENTITY, "negRisk").unwrap_or(false),
+            neg_risk_market_id: optional(raw.neg_risk_market_id, ENTITY, "negRiskMarketID"),
+        })
+    }
+}
+
+impl GammaMarket {
+    /// Convert to a [`Market`], if it carries both tokens and a time window
+    ///
+    /// The YES token is the one labeled `Up` or `Yes`. Without labels the
+    /// first token is assumed to be YES, to be checked against a book.
+    /// `open_price` is left at zero; it comes from the price feed at open.
+    pub fn to_market(&self) -> Option<Market> {
+        let tokens: Vec<String> = serde_json::from_str(self.clob_token_ids.as_deref()?).ok()?;
+        if tokens.len() != 2 {
+            return None;
+        }
+        let (yes_index, orientation) = match self.outcomes.as_deref() {
+            Some(raw) => {
+                let outcomes: Vec<String> = serde_json::from_str(raw).ok()?;
+                if outcomes.len() != 2 {
+                    return None;
+                }
+                let yes_index = outcomes
+                    .iter()
+                    .position(|o| o.eq_ignore_ascii_case("up") || o.eq_ignore_ascii_case("yes"))?;
+                let orientation = TokenOrientation::Labeled {
+                    yes: outcomes[yes_index].clone(),
+                    no: outcomes[1 - yes_index].clone(),
+                };
+                (yes_index, orientation)
+            }
+            None => (0, TokenOrientation::Assumed),
+        };
+        let no_index = 1 - yes_index;
+
+        Some(Market {
+            condition_id: self.condition_id.clone(),
+            asset: String::new(),
+            yes_token_id: tokens[yes_index].clone(),
+            no_token_id: tokens[no_index].clone(),
+            open_price: Decimal::ZERO,
+            open_time: self.event_start_time.or(self.start_date)?,
+            close_time: self.end_date?,
+            group_id: self.group_id(),
+            orientation,
+        })
+    }
+
+    /// Official winner of `market`, once Gamma reports it closed with a
+    /// clean 1/0 payout
+    ///
+    /// The paying token is matched by id, so the answer holds however the
+    /// engine oriented the tokens.
+    pub fn resolution(&self, market: &Market) -> Option<Side> {
+        if !self.closed {
+            return None;
+        }
+        let tokens: Vec<String> = serde_json::from_str(self.clob_token_ids.as_deref()?).ok()?;
+        let prices: Vec<String> = serde_json::from_str(self.outcome_prices.as_deref()?).ok()?;
+        let prices: Vec<Decimal> = prices
+            .iter()
+            .map(|p| p.parse().ok())
+            .collect::<Option<_>>()?;
+        let paid = match (tokens.as_slice(), prices.as_slice()) {
+            ([a, _], [pa, pb]) if *pa == Decimal::ONE && pb.is_zero() => a,
+            ([_, b], [pa, pb]) if pa.is_zero() && *pb == Decimal::ONE => b,
+            _ => return None,
+        };
+        if *paid == market.yes_token_id {
+            Some(Side::Yes)
+        } else if *paid == market.no_token_id {
+            Some(Side::No)
+        } else {
+            None
+        }
+    }
+
+    /// Neg-risk group identifier, if the market is flagged as grouped
+    pub fn group_id(&self) -> Option<String> {
+        self.neg_risk
+            .then(|| self.neg_risk_market_id.clone())
+            .flatten()
+    }
+}
+
+/// Event as returned by the Gamma API, read through [`RawGammaEvent`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawGammaEvent")]
+pub struct GammaEvent {
+    /// Event slug
+    pub slug: String,
+    /// Markets belonging to the event
+    pub markets: Vec<GammaMarket>,
+    /// Whether the event's markets are mutually exclusive
+    pub neg_risk: bool,
+    /// Identifier shared by the event's neg-risk markets
+    pub neg_risk_market_id: Option<String>,
+}
+
+/// [`GammaEvent`] as received, each field in any shape Gamma has sent it
+#[derive(Deserialize)]
+struct RawGammaEvent {
+    #[serde(default)]
+    slug: Option<LenientText>,
+    #[serde(default)]
+    markets: Option<Vec<GammaMarket>>,
+    #[serde(default, rename = "negRisk")]
+    neg_risk: Option<LenientFlag>,
+    #[serde(default, rename = "negRiskMarketID")]
+    neg_risk_market_id: Option<LenientText>,
+}
+
+impl TryFrom<RawGammaEvent> for GammaEvent {
+    type Error = String;
+
+    fn try_from(raw: RawGammaEvent) -> Result<Self, String> {
+        const ENTITY: &str = "event";
+        Ok(Self {
+            slug: required(raw.slug, ENTITY, "slug").ok_or("event without a slug")?,
+            markets: raw.markets.unwrap_or_default(),
+            neg_risk: optional(raw.neg_risk, ENTITY, "negRisk").unwrap_or(false),
+            neg_risk_market_id: optional(raw.neg_risk_market_id, ENTITY, "negRiskMarketID"),
+        })
+    }
+}
+
+impl GammaEvent {
+    /// Open markets of the event, grouped by the event when it is neg-risk
+    /// and a market carries no group of its own, and tagged with the asset
+    /// the event slug starts with
+    pub fn to_markets(&self) -> Vec<Market> {
+        let asset = self.asset();
+        let group = self.neg_risk.then(|| {
+            self.neg_risk_market_id
+                .clone()
+                .unwrap_or_else(|| self.slug.clone())
+        });
+        self.markets
+            .iter()
+            .filter(|m| !m.closed)
+            .filter_map(GammaMarket::to_market)
+            .map(|mut market| {
+                market.group_id = market.group_id.or_else(|| group.clone());
+                market.asset = asset.clone();
+                market
+            })
+            .collect()
+    }
+
+    /// Asset of an up/down event, e.g. `BTC` for `btc-updown-15m-1767638700`
+    pub fn asset(&self) -> String {
+        self.slug
+            .split('-')
+            .next()
+            .unwrap_or_default()
+            .to_uppercase()
+    }
+}
+
+/// Series as returned by the Gamma API
+#[derive(Debug, Clone, Deserialize)]
+pub struct GammaSeries {
+    /// Series slug
+    pub slug: String,
+    /// Embedded events (capped by the API)
+    #[serde(default)]
+    pub events: Vec<GammaEvent>,
+}
+
+/// Result of looking up events by slug
+#[derive(Debug, Default)]
+pub struct SlugLookup {
+    /// Events found, in no particular order
+    pub events: Vec<GammaEvent>,
+    /// Slugs whose request failed or missed the deadline
+    pub failed: Vec<String>,
+}
 
 /// Client for Polymarket's Gamma API
 pub struct GammaClient {
     base_url: String,
+    http: reqwest::Client,
+    page_size: usize,
+    max_pages: usize,
+    concurrency: usize,
+    slug_batch_size: usize,
+    deadline: Duration,
+    /// Retries of each request
+    retry: RetryPolicy<anyhow::Error>,
+    /// Slugs whose lookup failed last cycle
+    retries: Mutex<Vec<String>>,
 }
 
 impl GammaClient {
     /// Create a new Gamma API client
     pub fn new() -> Self {
+        Self::with_base_url(GAMMA_URL)
+    }
+
+    /// Create a client against a custom base URL
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
         Self {
-            base_url: "https://gamma-api.polymarket.com".to_string(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            page_size: DEFAULT_PAGE_SIZE,
+            max_pages: DEFAULT_MAX_PAGES,
+            concurrency: DEFAULT_LOOKUP_CONCURRENCY,
+            slug_batch_size: DEFAULT_SLUG_BATCH_SIZE,
+            deadline: Duration::from_millis(DEFAULT_DISCOVERY_DEADLINE_MS),
+            retry: RetryPolicy::new(GAMMA_RETRY_SUBSYSTEM).retry_if(transient_http),
+            retries: Mutex::new(vec![]),
         }
     }
 
+    /// Create a client using the pagination and lookup settings from config
+    pub fn from_config(config: &MarketConfig) -> Self {
+        Self::new()
+            .with_pagination(config.page_size, config.max_pages)
+            .with_lookups(
+                config.lookup_concurrency,
+                config.slug_batch_size,
+                config.discovery_deadline_ms.get(),
+            )
+            .with_retry(RetryPolicy::from_config(
+                GAMMA_RETRY_SUBSYSTEM,
+                &config.retry,
+            ))
+    }
+
+    /// Set page size and the maximum number of pages per listing
+    pub fn with_pagination(mut self, page_size: usize, max_pages: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self.max_pages = max_pages.max(1);
+        self
+    }
+
+    /// Set slug lookup concurrency, slugs per request, and the per-cycle deadline
+    pub fn with_lookups(
+        mut self,
+        concurrency: usize,
+        batch_size: usize,
+        deadline: Duration,
+    ) -> Self {
+        self.concurrency = concurrency.max(1);
+        self.slug_batch_size = batch_size.max(1);
+        self.deadline = deadline;
+        self
+    }
+
+    /// Retry each request under `policy`; only transient HTTP failures
+    /// are retried
+    pub fn with_retry(mut self, policy: RetryPolicy<anyhow::Error>) -> Self {
+        self.retry = policy.retry_if(transient_http);
+        self
+    }
+
+    /// Slugs that failed and will be retried next cycle
+    pub fn pending_retries(&self) -> Vec<String> {
+        self.retries.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+
     /// Fetch active 15-minute BTC up/down markets
+    ///
+    /// Combines the paginated series listing with direct slug lookups for the
+    /// current and next windows, so an open window is found even if the
+    /// listing is truncated. Listed events without embedded markets and slugs
+    /// that failed last cycle are looked up in the same batch.
     pub async fn fetch_btc_markets(&self) -> anyhow::Result<Vec<Market>> {
-        // TODO: Implement API call to fetch markets
         tracing::debug!("Fetching BTC markets from {}", self.base_url);
-        Ok(vec![])
+        let started = std::time::Instant::now();
+
+        let series = self
+            .fetch_series(&[("slug", BTC_15M_SERIES_SLUG.to_string())])
+            .await?;
+        let mut events: Vec<GammaEvent> = series.into_iter().flat_map(|s| s.events).collect();
+
+        let current = window_start(Utc::now());
+        let mut slugs = self
+            .retries
+            .lock()
+            .map(|mut r| std::mem::take(&mut *r))
+            .unwrap_or_default();
+        slugs.extend(
+            events
+                .iter()
+                .filter(|e| e.markets.is_empty())
+                .map(|e| e.slug.clone()),
+        );
+        slugs.extend(
+            [current, current + chrono::Duration::seconds(WINDOW_SECS)]
+                .into_iter()
+                .map(|start| event_slug("btc", start)),
+        );
+        let mut seen = HashSet::new();
+        slugs.retain(|slug| seen.insert(slug.clone()));
+
+        let lookup = self
+            .fetch_events_by_slugs(&slugs, Instant::now() + self.deadline)
+            .await;
+        if !lookup.failed.is_empty() {
+            tracing::warn!(slugs = ?lookup.failed, "Event lookups failed, retrying next cycle");
+            if let Ok(mut retries) = self.retries.lock() {
+                retries.extend(lookup.failed);
+            }
+        }
+        events.extend(lookup.events);
+
+        let markets = dedup_markets(events.iter().flat_map(GammaEvent::to_markets).collect());
+        let groups: BTreeSet<&str> = markets
+            .iter()
+            .filter_map(|m| m.group_id.as_deref())
+            .collect();
+        if !groups.is_empty() {
+            tracing::info!(
+                markets = markets.iter().filter(|m| m.group_id.is_some()).count(),
+                groups = ?groups,
+                "Discovered grouped neg-risk markets"
+            );
+        }
+        record_latency(LatencyMetric::MarketDiscovery, started.elapsed());
+        Ok(markets)
+    }
+
+    /// Fetch series, following pagination
+    pub async fn fetch_series(&self, query: &[(&str, String)]) -> anyhow::Result<Vec<GammaSeries>> {
+        self.paginate("/series", query).await
+    }
+
+    /// Fetch markets, following pagination
+    pub async fn fetch_markets(
+        &self,
+        query: &[(&str, String)],
+    ) -> anyhow::Result<Vec<GammaMarket>> {
+        self.paginate("/markets", query).await
+    }
+
+    /// One unpaginated response from `path`, as sent
+    pub async fn fetch_raw(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> anyhow::Result<serde_json::Value> {
+        let url = format!("{}{}", self.base_url, path);
+        self.get_json(&self.retry, || self.http.get(&url).query(query))
+            .await
+    }
+
+    /// Official winner of `market`, `None` until Gamma reports it resolved
+    pub async fn fetch_resolution(&self, market: &Market) -> anyhow::Result<Option<Side>> {
+        let markets = self
+            .fetch_markets(&[
+                ("condition_ids", market.condition_id.clone()),
+                ("closed", "true".to_string()),
+            ])
+            .await?;
+        Ok(markets
+            .iter()
+            .find(|m| m.condition_id == market.condition_id)
+            .and_then(|m| m.resolution(market)))
+    }
+
+    /// Fetch the 15-minute event for `asset` starting at `window_start`
+    ///
+    /// The slug is computed directly (e.g. `btc-updown-15m-1767638700`), so
+    /// this does not depend on any listing endpoint.
+    pub async fn fetch_event_by_ts(
+        &self,
+        asset: &str,
+        window_start: DateTime<Utc>,
+    ) -> anyhow::Result<Option<GammaEvent>> {
+        let slug = event_slug(asset, window_start);
+        let url = format!("{}/events", self.base_url);
+        let events: Vec<GammaEvent> = self
+            .get_json(&self.retry, || {
+                self.http.get(&url).query(&[("slug", slug.as_str())])
+            })
+            .await?;
+        Ok(events.into_iter().find(|e| e.slug == slug))
+    }
+
+    /// Look up events by slug, several slugs per request and several
+    /// requests in flight
+    ///
+    /// Requests still pending at `deadline` are abandoned and their slugs
+    /// reported as failed, and no retry is started that could not finish
+    /// by then. Slugs with no matching event are not failures.
+    pub async fn fetch_events_by_slugs(&self, slugs: &[String], deadline: Instant) -> SlugLookup {
+        let chunks: Vec<Vec<String>> = slugs
+            .chunks(self.slug_batch_size)
+            .map(<[String]>::to_vec)
+            .collect();
+        let policy = self.retry.clone().until(deadline);
+        let policy = &policy;
+        let results: Vec<_> = futures_util::stream::iter(chunks)
+            .map(|chunk| async move {
+                let result = self.fetch_events(policy, &chunk).await;
+                (chunk, result)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        let mut lookup = SlugLookup::default();
+        for (chunk, result) in results {
+            match result {
+                Ok(events) => lookup
+                    .events
+                    .extend(events.into_iter().filter(|e| chunk.contains(&e.slug))),
+                Err(e) => {
+                    tracing::debug!(error = %e, slugs = ?chunk, "Event lookup failed");
+                    lookup.failed.extend(chunk);
+                }
+            }
+        }
+        lookup
+    }
+
+    /// One `/events` request with a repeated `slug` parameter
+    async fn fetch_events(
+        &self,
+        policy: &RetryPolicy<anyhow::Error>,
+        slugs: &[String],
+    ) -> anyhow::Result<Vec<GammaEvent>> {
+        let query: Vec<_> = slugs.iter().map(|slug| ("slug", slug.as_str())).collect();
+        let url = format!("{}/events", self.base_url);
+        self.get_json(policy, || self.http.get(&url).query(&query))
+            .await
+    }
+
+    /// Send the request `build` makes, retried under `policy`, and decode
+    /// its JSON body
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        policy: &RetryPolicy<anyhow::Error>,
+        build: impl Fn() -> reqwest::RequestBuilder,
+    ) -> anyhow::Result<T> {
+        retry(policy, |_| {
+            let request = build();
+            async move { Ok(request.send().await?.error_for_status()?.json().await?) }
+        })
+        .await
+        .map_err(RetryError::into_anyhow)
+    }
+
+    /// Follow limit/offset pagination until a short page or the page cap
+    async fn paginate<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> anyhow::Result<Vec<T>> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut items = Vec::new();
+
+        for page in 0..self.max_pages {
+            let batch: Vec<T> = self
+                .get_json(&self.retry, || {
+                    self.http
+                        .get(&url)
+                        .query(query)
+                        .query(&[("limit", self.page_size), ("offset", page * self.page_size)])
+                })
+                .await?;
+            let count = batch.len();
+            items.extend(batch);
+            if count < self.page_size {
+                return Ok(items);
+            }
+        }
+
+        tracing::warn!(
+            path,
+            pages = self.max_pages,
+            items = items.len(),
+            "Gamma page cap reached, results may be truncated"
+        );
+        Ok(items)
     }
 }
 
@@ -28,3 +614,551 @@ impl Default for GammaClient {
         Self::new()
     }
 }
+
+/// Start of the 15-minute window containing `ts`
+pub fn window_start(ts: DateTime<Utc>) -> DateTime<Utc> {
+    let secs = ts.timestamp() - ts.timestamp().rem_euclid(WINDOW_SECS);
+    DateTime::from_timestamp(secs, 0).unwrap_or(ts)
+}
+
+/// Event slug for a 15-minute up/down window, e.g. `btc-updown-15m-1767638700`
+pub fn event_slug(asset: &str, window_start: DateTime<Utc>) -> String {
+    format!(
+        "{}-updown-15m-{}",
+        asset.to_lowercase(),
+        window_start.timestamp()
+    )
+}
+
+/// Remove duplicate markets by condition id, keeping the first occurrence
+fn dedup_markets(markets: Vec<Market>) -> Vec<Market> {
+    let mut seen = HashSet::new();
+    markets
+        .into_iter()
+        .filter(|m| seen.insert(m.condition_id.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::{EVENTS_FIXTURE, GAMMA_FIXTURE_DIR, MARKETS_FIXTURE, SERIES_FIXTURE};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex as StdMutex};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    fn market_json(condition_id: &str, start: i64) -> serde_json::Value {
+        let start = DateTime::from_timestamp(start, 0).unwrap();
+        let end = start + chrono::Duration::seconds(WINDOW_SECS);
+        json!({
+            "conditionId": condition_id,
+            "clobTokenIds": format!("[\"{}-up\", \"{}-down\"]", condition_id, condition_id),
+            "outcomes": "[\"Up\", \"Down\"]",
+            "eventStartTime": start.to_rfc3339(),
+            "endDate": end.to_rfc3339(),
+            "closed": false
+        })
+    }
+
+    fn event_json(slug: &str, markets: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({ "slug": slug, "markets": markets })
+    }
+
+    #[test]
+    fn test_event_slug() {
+        let ts = DateTime::from_timestamp(1767638700, 0).unwrap();
+        assert_eq!(event_slug("BTC", ts), "btc-updown-15m-1767638700");
+    }
+
+    #[test]
+    fn test_window_start() {
+        let ts = DateTime::from_timestamp(1767638700 + 123, 0).unwrap();
+        assert_eq!(window_start(ts).timestamp(), 1767638700);
+        let aligned = DateTime::from_timestamp(1767638700, 0).unwrap();
+        assert_eq!(window_start(aligned), aligned);
+    }
+
+    #[test]
+    fn test_gamma_market_to_market() {
+        let raw: GammaMarket = serde_json::from_value(json!({
+            "conditionId": "0xabc",
+            "clobTokenIds": "[\"111\", \"222\"]",
+            "outcomes": "[\"Down\", \"Up\"]",
+            "eventStartTime": "2026-01-05T18:45:00Z",
+            "endDate": "2026-01-05T19:00:00Z"
+        }))
+        .unwrap();
+        let market = raw.to_market().unwrap();
+        assert_eq!(market.yes_token_id, "222");
+        assert_eq!(market.no_token_id, "111");
+        assert_eq!(market.open_time.timestamp(), 1767638700);
+        assert_eq!(market.group_id, None);
+
+        assert_eq!(
+            market.orientation,
+            TokenOrientation::Labeled {
+                yes: "Up".to_string(),
+                no: "Down".to_string()
+            }
+        );
+
+        let missing: GammaMarket =
+            serde_json::from_value(json!({ "conditionId": "0xdef" })).unwrap();
+        assert!(missing.to_market().is_none());
+    }
+
+    #[test]
+    fn test_unlabeled_tokens_are_assumed_until_a_book_decides() {
+        use crate::market::tokens_reversed;
+        use rust_decimal_macros::dec;
+
+        // NO listed first, and no labels to tell
+        let raw: GammaMarket = serde_json::from_value(json!({
+            "conditionId": "0xabc",
+            "clobTokenIds": "[\"down\", \"up\"]",
+            "eventStartTime": "2026-01-05T18:45:00Z",
+            "endDate": "2026-01-05T19:00:00Z"
+        }))
+        .unwrap();
+        let mut market = raw.to_market().unwrap();
+        assert_eq!(market.yes_token_id, "down");
+        assert_eq!(market.orientation, TokenOrientation::Assumed);
+
+        // The model puts Up at 0.8; the assumed YES token trades at 0.25
+        assert_eq!(tokens_reversed(dec!(0.25), dec!(0.8)), Some(true));
+        market.swap_tokens();
+        assert_eq!(market.yes_token_id, "up");
+        assert_eq!(market.no_token_id, "down");
+
+        assert_eq!(tokens_reversed(dec!(0.7), dec!(0.8)), Some(false));
+        // A lagging book still tells the tokens apart
+        assert_eq!(tokens_reversed(dec!(0.6), dec!(0.8)), Some(false));
+        // Near 0.5 both tokens trade alike
+        assert_eq!(tokens_reversed(dec!(0.52), dec!(0.49)), None);
+    }
+
+    #[test]
+    fn test_neg_risk_market_carries_group() {
+        let raw: GammaMarket = serde_json::from_value(json!({
+            "conditionId": "0xabc",
+            "clobTokenIds": "[\"111\", \"222\"]",
+            "outcomes": "[\"Yes\", \"No\"]",
+            "eventStartTime": "2026-01-05T18:45:00Z",
+            "endDate": "2026-01-05T19:00:00Z",
+            "negRisk": true,
+            "negRiskMarketID": "0xgroup"
+        }))
+        .unwrap();
+        assert_eq!(
+            raw.to_market().unwrap().group_id.as_deref(),
+            Some("0xgroup")
+        );
+
+        // The id alone does not group a market that is not flagged neg-risk
+        let unflagged = GammaMarket {
+            neg_risk: false,
+            ..raw
+        };
+        assert_eq!(unflagged.to_market().unwrap().group_id, None);
+    }
+
+    #[test]
+    fn test_neg_risk_event_groups_its_markets() {
+        let event: GammaEvent = serde_json::from_value(json!({
+            "slug": "btc-price-ranges",
+            "negRisk": true,
+            "markets": [market_json("a", 0), market_json("b", 0)]
+        }))
+        .unwrap();
+        let markets = event.to_markets();
+        assert_eq!(markets.len(), 2);
+        assert!(markets
+            .iter()
+            .all(|m| m.group_id.as_deref() == Some("btc-price-ranges")));
+
+        let plain: GammaEvent =
+            serde_json::from_value(event_json("btc-updown-15m-1", vec![market_json("c", 0)]))
+                .unwrap();
+        assert_eq!(plain.to_markets()[0].group_id, None);
+    }
+
+    #[test]
+    fn test_official_resolution_matches_tokens_by_id() {
+        let raw = |closed: bool, prices: &str| -> GammaMarket {
+            serde_json::from_value(json!({
+                "conditionId": "0xabc",
+                "clobTokenIds": "[\"111\", \"222\"]",
+                "outcomes": "[\"Down\", \"Up\"]",
+                "outcomePrices": prices,
+                "eventStartTime": "2026-01-05T18:45:00Z",
+                "endDate": "2026-01-05T19:00:00Z",
+                "closed": closed
+            }))
+            .unwrap()
+        };
+        let mut market = raw(false, "[]").to_market().unwrap();
+        assert_eq!(
+            raw(true, "[\"0\", \"1\"]").resolution(&market),
+            Some(Side::Yes)
+        );
+        assert_eq!(
+            raw(true, "[\"1\", \"0\"]").resolution(&market),
+            Some(Side::No)
+        );
+
+        // Tokens swapped after the book showed the labels wrong
+        market.swap_tokens();
+        assert_eq!(
+            raw(true, "[\"0\", \"1\"]").resolution(&market),
+            Some(Side::No)
+        );
+
+        // Still open, or not paying out cleanly yet
+        assert_eq!(raw(false, "[\"0\", \"1\"]").resolution(&market), None);
+        assert_eq!(raw(true, "[\"0.5\", \"0.5\"]").resolution(&market), None);
+        assert_eq!(raw(true, "[\"0.02\", \"0.98\"]").resolution(&market), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_markets_follows_pages() {
+        let server = MockServer::start().await;
+        for (offset, ids) in [(0, vec!["a", "b"]), (2, vec!["c", "d"]), (4, vec!["e"])] {
+            let body: Vec<_> = ids.iter().map(|id| market_json(id, 0)).collect();
+            Mock::given(method("GET"))
+                .and(path("/markets"))
+                .and(query_param("offset", offset.to_string()))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let client = GammaClient::with_base_url(server.uri()).with_pagination(2, 10);
+        let markets = client.fetch_markets(&[]).await.unwrap();
+        let ids: Vec<_> = markets.iter().map(|m| m.condition_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c", "d", "e"]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_markets_stops_at_page_cap() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/markets"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(vec![market_json("a", 0), market_json("b", 0)]),
+            )
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let client = GammaClient::with_base_url(server.uri()).with_pagination(2, 3);
+        let markets = client.fetch_markets(&[]).await.unwrap();
+        assert_eq!(markets.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_event_by_ts() {
+        let server = MockServer::start().await;
+        let start = DateTime::from_timestamp(1767638700, 0).unwrap();
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", "btc-updown-15m-1767638700"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec![event_json(
+                "btc-updown-15m-1767638700",
+                vec![market_json("direct", 1767638700)],
+            )]))
+            .mount(&server)
+            .await;
+
+        let client = GammaClient::with_base_url(server.uri());
+        let event = client
+            .fetch_event_by_ts("BTC", start)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.markets[0].condition_id, "direct");
+
+        let missing = client
+            .fetch_event_by_ts("BTC", start + chrono::Duration::seconds(WINDOW_SECS))
+            .await;
+        // Unmatched requests get a 404 from the mock server
+        assert!(missing.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_btc_markets_falls_back_to_slug_and_dedups() {
+        let server = MockServer::start().await;
+        let current = window_start(Utc::now());
+        let current_slug = event_slug("btc", current);
+
+        // Series listing is truncated and misses the current window
+        Mock::given(method("GET"))
+            .and(path("/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec![json!({
+                "slug": BTC_15M_SERIES_SLUG,
+                "events": [
+                    event_json("old", vec![market_json("old", current.timestamp() - 900)]),
+                    event_json("dup", vec![market_json("dup", current.timestamp() + 900)]),
+                ]
+            })]))
+            .mount(&server)
+            .await;
+        // Both window lookups go out in one batched request
+        let next_slug = event_slug("btc", current + chrono::Duration::seconds(WINDOW_SECS));
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", current_slug.as_str()))
+            .and(query_param("slug", next_slug.as_str()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec![
+                event_json(
+                    &current_slug,
+                    vec![market_json("current", current.timestamp())],
+                ),
+                event_json(
+                    &next_slug,
+                    vec![market_json("dup", current.timestamp() + 900)],
+                ),
+            ]))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = GammaClient::with_base_url(server.uri());
+        let markets = client.fetch_btc_markets().await.unwrap();
+        let ids: Vec<_> = markets.iter().map(|m| m.condition_id.as_str()).collect();
+        assert_eq!(ids, vec!["old", "dup", "current"]);
+        // The asset comes from the event slug
+        assert_eq!(markets[2].asset, "BTC");
+        assert!(client.pending_retries().is_empty());
+    }
+
+    /// A response recorded by `gamma snapshot`
+    fn fixture(file: &str) -> serde_json::Value {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(GAMMA_FIXTURE_DIR);
+        serde_json::from_slice(&std::fs::read(dir.join(file)).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_discovery_reads_recorded_responses() {
+        let server = MockServer::start().await;
+        // The recorded listing embeds no markets, so its events are looked up
+        Mock::given(method("GET"))
+            .and(path("/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture(SERIES_FIXTURE)))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", "btc-updown-15m-1767638700"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture(EVENTS_FIXTURE)))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/markets"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture(MARKETS_FIXTURE)))
+            .mount(&server)
+            .await;
+
+        let client = GammaClient::with_base_url(server.uri());
+        let markets = client.fetch_btc_markets().await.unwrap();
+        assert_eq!(markets.len(), 2);
+        assert!(markets.iter().all(|m| m.asset == "BTC"));
+        assert_eq!(
+            markets[0].open_time,
+            DateTime::parse_from_rfc3339("2026-01-05T18:45:00Z").unwrap()
+        );
+        assert!(matches!(
+            markets[0].orientation,
+            TokenOrientation::Labeled { .. }
+        ));
+
+        // The recorded closed market paid its first (Up) token
+        let closed: Vec<GammaMarket> = serde_json::from_value(fixture(MARKETS_FIXTURE)).unwrap();
+        let market = closed[0].to_market().unwrap();
+        assert_eq!(
+            client.fetch_resolution(&market).await.unwrap(),
+            Some(Side::Yes)
+        );
+    }
+
+    /// Records when each request arrived
+    struct Arrivals {
+        times: Arc<StdMutex<Vec<Instant>>>,
+        delay: Duration,
+    }
+
+    impl Respond for Arrivals {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            self.times.lock().unwrap().push(Instant::now());
+            let slug = request
+                .url
+                .query_pairs()
+                .find(|(k, _)| k == "slug")
+                .map(|(_, v)| v.to_string())
+                .unwrap_or_default();
+            ResponseTemplate::new(200)
+                .set_body_json(vec![event_json(&slug, vec![market_json(&slug, 0)])])
+                .set_delay(self.delay)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slug_lookups_overlap() {
+        let server = MockServer::start().await;
+        let times = Arc::new(StdMutex::new(vec![]));
+        let delay = Duration::from_millis(300);
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .respond_with(Arrivals {
+                times: times.clone(),
+                delay,
+            })
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let client =
+            GammaClient::with_base_url(server.uri()).with_lookups(3, 1, Duration::from_secs(5));
+        let slugs: Vec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let started = Instant::now();
+        let lookup = client
+            .fetch_events_by_slugs(&slugs, Instant::now() + Duration::from_secs(5))
+            .await;
+
+        assert_eq!(lookup.events.len(), 3);
+        assert!(lookup.failed.is_empty());
+        // All three were in flight before the first response came back
+        let times = times.lock().unwrap();
+        assert!(times.iter().all(|t| t.duration_since(times[0]) < delay));
+        assert!(started.elapsed() < delay * 2);
+    }
+
+    #[tokio::test]
+    async fn test_deadline_abandons_slow_lookup() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", "fast"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(vec![event_json("fast", vec![market_json("fast", 0)])]),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", "slow"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(vec![event_json("slow", vec![])])
+                    .set_delay(Duration::from_secs(5)),
+            )
+            .mount(&server)
+            .await;
+
+        let client =
+            GammaClient::with_base_url(server.uri()).with_lookups(2, 1, Duration::from_millis(300));
+        let slugs = vec!["slow".to_string(), "fast".to_string()];
+        let started = Instant::now();
+        let lookup = client
+            .fetch_events_by_slugs(&slugs, Instant::now() + Duration::from_millis(300))
+            .await;
+
+        assert_eq!(lookup.events.len(), 1);
+        assert_eq!(lookup.events[0].slug, "fast");
+        assert_eq!(lookup.failed, vec!["slow"]);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried_under_the_policy() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", "flaky"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", "flaky"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(vec![event_json("flaky", vec![])]),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", "gone"))
+            .respond_with(ResponseTemplate::new(404))
+            // Once per client below
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let policy = RetryPolicy::new(GAMMA_RETRY_SUBSYSTEM)
+            .max_attempts(3)
+            .delays(Duration::from_millis(5), Duration::from_millis(20))
+            .with_budget(crate::retry::RetryBudget::new(GAMMA_RETRY_SUBSYSTEM, 4));
+        let client = GammaClient::with_base_url(server.uri())
+            .with_lookups(2, 1, Duration::from_secs(5))
+            .with_retry(policy);
+        let slugs = vec!["flaky".to_string(), "gone".to_string()];
+        let lookup = client
+            .fetch_events_by_slugs(&slugs, Instant::now() + Duration::from_secs(5))
+            .await;
+        assert_eq!(lookup.events.len(), 1);
+        // A 404 is not retried
+        assert_eq!(lookup.failed, vec!["gone"]);
+
+        // By default nothing is retried
+        let client = GammaClient::with_base_url(server.uri());
+        let error = client
+            .fetch_raw("/events", &[("slug", "gone".to_string())])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("404"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_failed_lookup_retried_next_cycle() {
+        let server = MockServer::start().await;
+        let current = window_start(Utc::now());
+        // Listed without its markets, so it needs a slug lookup
+        Mock::given(method("GET"))
+            .and(path("/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec![json!({
+                "slug": BTC_15M_SERIES_SLUG,
+                "events": [event_json("listed", vec![])]
+            })]))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", "listed"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/events"))
+            .and(query_param("slug", "listed"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec![event_json(
+                "listed",
+                vec![market_json("listed", current.timestamp())],
+            )]))
+            .mount(&server)
+            .await;
+
+        let client = GammaClient::with_base_url(server.uri());
+        assert!(client.fetch_btc_markets().await.unwrap().is_empty());
+        assert!(client.pending_retries().contains(&"listed".to_string()));
+
+        let markets = client.fetch_btc_markets().await.unwrap();
+        assert_eq!(markets[0].condition_id, "listed");
+        assert!(client.pending_retries().is_empty());
+    }
+}
@@ -2,22 +2,48 @@
 //!
 //! Finds and tracks active 15-minute BTC up/down markets via Gamma API
 
+mod bounds;
 mod gamma;
+mod preopen;
+mod schema;
 mod tracker;
 
-pub use gamma::GammaClient;
+pub use bounds::{PriceBounds, DEFAULT_TICK_SIZE};
+use gamma::WINDOW_SECS;
+pub use gamma::{
+    event_slug, window_start, GammaClient, GammaEvent, GammaMarket, GammaSeries, SlugLookup,
+    DEFAULT_DISCOVERY_DEADLINE_MS, DEFAULT_LOOKUP_CONCURRENCY, DEFAULT_MAX_PAGES,
+    DEFAULT_PAGE_SIZE, DEFAULT_SLUG_BATCH_SIZE, GAMMA_URL,
+};
+pub use preopen::{
+    forward_books, next_window_open, BookSubscriber, PreOpenPreparer, PreOpenTask, WindowLookup,
+    DEFAULT_PREOPEN_LEAD_SECS,
+};
+pub use schema::{
+    record_drift, scrub, DriftKind, GammaSnapshot, StrictGammaEvent, StrictGammaMarket,
+    StrictGammaSeries, DEFAULT_SNAPSHOT_MAX_ITEMS, EVENTS_FIXTURE, GAMMA_FIXTURE_DIR,
+    MARKETS_FIXTURE, SERIES_FIXTURE,
+};
 pub use tracker::MarketTrackerImpl;
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
 
+/// Margin by which a book must favor one token assignment over the other
+/// before an assumed orientation is settled
+pub const ORIENTATION_MARGIN: Decimal = dec!(0.1);
+
 /// A Polymarket 15-minute binary market
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Market {
     /// Unique condition identifier
     pub condition_id: String,
+    /// Canonical asset the market settles on (e.g., "BTC")
+    #[serde(default)]
+    pub asset: String,
     /// Yes token identifier
     pub yes_token_id: String,
     /// No token identifier
@@ -28,6 +54,91 @@ pub struct Market {
     pub open_time: DateTime<Utc>,
     /// Market close/settlement time
     pub close_time: DateTime<Utc>,
+    /// Neg-risk group shared by mutually exclusive markets of one event
+    #[serde(default)]
+    pub group_id: Option<String>,
+    /// How the YES and NO tokens were told apart
+    #[serde(default)]
+    pub orientation: TokenOrientation,
+}
+
+impl Market {
+    /// A market on `asset` open from `open_time` to `close_time` with its
+    /// strike at `open_price`; its tokens are `<condition_id>-yes` and
+    /// `<condition_id>-no`, as in simulation
+    ///
+    /// ```
+    /// use poly_hft::prelude::*;
+    /// use chrono::{DateTime, Duration};
+    /// use rust_decimal_macros::dec;
+    ///
+    /// let open = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+    /// let market = Market::new("btc-15m", "BTC", dec!(100000), open, open + Duration::minutes(15));
+    /// assert_eq!(market.yes_token_id, "btc-15m-yes");
+    /// assert_eq!(market.group_id, None);
+    /// ```
+    pub fn new(
+        condition_id: impl Into<String>,
+        asset: impl Into<String>,
+        open_price: Decimal,
+        open_time: DateTime<Utc>,
+        close_time: DateTime<Utc>,
+    ) -> Self {
+        let condition_id = condition_id.into();
+        Self {
+            yes_token_id: format!("{}-yes", condition_id),
+            no_token_id: format!("{}-no", condition_id),
+            condition_id,
+            asset: asset.into(),
+            open_price,
+            open_time,
+            close_time,
+            group_id: None,
+            orientation: TokenOrientation::default(),
+        }
+    }
+
+    /// Swap the YES and NO tokens, and their labels if any
+    pub fn swap_tokens(&mut self) {
+        std::mem::swap(&mut self.yes_token_id, &mut self.no_token_id);
+        match &mut self.orientation {
+            TokenOrientation::Labeled { yes, no } => std::mem::swap(yes, no),
+            TokenOrientation::Assumed | TokenOrientation::Unknown => {}
+        }
+    }
+}
+
+/// How a market's YES and NO tokens were told apart
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum TokenOrientation {
+    /// Not recorded, e.g. a simulated market or one read back from capture
+    #[default]
+    Unknown,
+    /// From the outcome labels returned with the tokens, e.g. `Up`/`Down`
+    Labeled { yes: String, no: String },
+    /// No labels: the first token is taken as YES until a book shows
+    /// whether it trades like Up or like Down
+    Assumed,
+}
+
+/// Whether the tokens of an [`TokenOrientation::Assumed`] market are
+/// reversed, given `yes_mid`, the probability a book implies for the
+/// assumed YES token, and `up_fair`, the model's fair value of Up
+///
+/// Gamma's outcome prices follow the token order, so only the model can
+/// tell which token is Up. `None` while neither reading is closer by
+/// [`ORIENTATION_MARGIN`], as near 0.5 where both tokens trade alike.
+pub fn tokens_reversed(yes_mid: Decimal, up_fair: Decimal) -> Option<bool> {
+    let as_assumed = (yes_mid - up_fair).abs();
+    let reversed = (Decimal::ONE - yes_mid - up_fair).abs();
+    if reversed + ORIENTATION_MARGIN <= as_assumed {
+        Some(true)
+    } else if as_assumed + ORIENTATION_MARGIN <= reversed {
+        Some(false)
+    } else {
+        None
+    }
 }
 
 /// Trait for market tracking implementations
@@ -0,0 +1,361 @@
+//! Pre-open market preparation
+//!
+//! Windows open on a fixed 15-minute grid, so the next window's event slug
+//! is known in advance. Looking it up shortly before the open lets its books
+//! be subscribed and its market tracked by the time it opens, rather than
+//! up to a discovery poll later. The strike is left at zero and filled in
+//! by the engine from the first price tick at or after the open.
+//!
+//! Lookups run in a [`PreOpenTask`] off the trading loop, which sends each
+//! market back before subscribing its tokens; their books are forwarded
+//! onto the market data bus.
+
+use super::{event_slug, window_start, GammaClient, Market, WINDOW_SECS};
+use crate::bus::{MarketDataBus, MarketDataEvent};
+use crate::orderbook::{OrderBook, PolymarketClient};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Default lead before a window opens at which its market is looked up
+pub const DEFAULT_PREOPEN_LEAD_SECS: u64 = 60;
+
+/// Open time of the first window starting after `now`
+pub fn next_window_open(now: DateTime<Utc>) -> DateTime<Utc> {
+    window_start(now) + Duration::seconds(WINDOW_SECS)
+}
+
+/// Looks up the market of a window by its open time
+#[async_trait]
+pub trait WindowLookup: Send + Sync {
+    /// Market of `asset`'s window opening at `open`, once it is listed
+    async fn window_market(
+        &self,
+        asset: &str,
+        open: DateTime<Utc>,
+    ) -> anyhow::Result<Option<Market>>;
+}
+
+#[async_trait]
+impl WindowLookup for GammaClient {
+    async fn window_market(
+        &self,
+        asset: &str,
+        open: DateTime<Utc>,
+    ) -> anyhow::Result<Option<Market>> {
+        let event = self.fetch_event_by_ts(asset, open).await?;
+        Ok(event.and_then(|e| e.to_markets().into_iter().next()))
+    }
+}
+
+/// Subscribes to the order books of a token
+#[async_trait]
+pub trait BookSubscriber: Send + Sync {
+    /// Books of `token_id` as they update, until the feed closes
+    async fn subscribe(&self, token_id: &str) -> anyhow::Result<mpsc::Receiver<OrderBook>>;
+}
+
+#[async_trait]
+impl BookSubscriber for PolymarketClient {
+    async fn subscribe(&self, token_id: &str) -> anyhow::Result<mpsc::Receiver<OrderBook>> {
+        PolymarketClient::subscribe(self, token_id).await
+    }
+}
+
+/// Subscribe to `token_id` and publish its books on `bus` until the feed
+/// closes, when the forwarding task ends
+pub async fn forward_books<S: BookSubscriber + ?Sized>(
+    subscriber: &S,
+    token_id: &str,
+    bus: Arc<MarketDataBus>,
+) -> anyhow::Result<()> {
+    let mut books = subscriber.subscribe(token_id).await?;
+    let token_id = token_id.to_string();
+    tokio::spawn(async move {
+        while let Some(book) = books.recv().await {
+            bus.publish(MarketDataEvent::Book(book));
+        }
+        tracing::debug!(%token_id, "Order book feed closed");
+    });
+    Ok(())
+}
+
+/// Looks up each asset's next window before it opens and tracks the
+/// tokens subscribed for it
+pub struct PreOpenPreparer {
+    assets: Vec<String>,
+    lead: Duration,
+    /// Windows already prepared, by asset and open time
+    prepared: HashSet<(String, DateTime<Utc>)>,
+    subscribed: HashSet<String>,
+}
+
+impl PreOpenPreparer {
+    /// Prepare windows of `assets` from `lead_secs` before they open
+    pub fn new(assets: Vec<String>, lead_secs: u64) -> Self {
+        Self {
+            assets,
+            lead: Duration::seconds(lead_secs as i64),
+            prepared: HashSet::new(),
+            subscribed: HashSet::new(),
+        }
+    }
+
+    /// Windows within the lead of opening at `now` and not yet prepared
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<(String, DateTime<Utc>)> {
+        let open = next_window_open(now);
+        if open - now > self.lead {
+            return vec![];
+        }
+        self.assets
+            .iter()
+            .filter(|asset| !self.prepared.contains(&(asset.to_string(), open)))
+            .map(|asset| (asset.clone(), open))
+            .collect()
+    }
+
+    /// Look up the due windows and return the markets found, with both
+    /// tokens marked subscribed
+    ///
+    /// A window not listed yet, or whose lookup fails, is retried on the
+    /// next call until it opens.
+    pub async fn prepare<L: WindowLookup>(
+        &mut self,
+        lookup: &L,
+        now: DateTime<Utc>,
+    ) -> Vec<Market> {
+        let stale = now - Duration::seconds(WINDOW_SECS);
+        self.prepared.retain(|(_, open)| *open > stale);
+
+        let mut markets = vec![];
+        for (asset, open) in self.due(now) {
+            let slug = event_slug(&asset, open);
+            match lookup.window_market(&asset, open).await {
+                Ok(Some(mut market)) => {
+                    market.open_price = Default::default();
+                    tracing::info!(
+                        %slug,
+                        market_id = %market.condition_id,
+                        opens_in_secs = (open - now).num_seconds(),
+                        "Prepared market before its window opens"
+                    );
+                    self.subscribed.insert(market.yes_token_id.clone());
+                    self.subscribed.insert(market.no_token_id.clone());
+                    self.prepared.insert((asset, open));
+                    markets.push(market);
+                }
+                Ok(None) => tracing::debug!(%slug, "Upcoming event not listed yet"),
+                Err(e) => tracing::warn!(%slug, error = %e, "Upcoming event lookup failed"),
+            }
+        }
+        markets
+    }
+
+    /// Whether books of `token_id` were subscribed ahead of its open
+    pub fn is_subscribed(&self, token_id: &str) -> bool {
+        self.subscribed.contains(token_id)
+    }
+}
+
+/// Pre-open lookups run off the trading loop
+///
+/// The loop hands the task its clock's time; each market found is sent
+/// back before its tokens are subscribed, so the engine tracks it by the
+/// time its books arrive on the bus.
+pub struct PreOpenTask {
+    requests: mpsc::Sender<DateTime<Utc>>,
+    markets: mpsc::Receiver<Market>,
+}
+
+impl PreOpenTask {
+    /// Spawn the task, preparing windows with `preparer`
+    pub fn spawn<L, S>(
+        mut preparer: PreOpenPreparer,
+        lookup: Arc<L>,
+        subscriber: Arc<S>,
+        bus: Arc<MarketDataBus>,
+    ) -> Self
+    where
+        L: WindowLookup + 'static,
+        S: BookSubscriber + 'static,
+    {
+        let (requests, mut due) = mpsc::channel(1);
+        let (found, markets) = mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Some(now) = due.recv().await {
+                for market in preparer.prepare(lookup.as_ref(), now).await {
+                    let tokens = [market.yes_token_id.clone(), market.no_token_id.clone()];
+                    if found.send(market).await.is_err() {
+                        return;
+                    }
+                    for token_id in tokens {
+                        if let Err(e) =
+                            forward_books(subscriber.as_ref(), &token_id, bus.clone()).await
+                        {
+                            tracing::warn!(%token_id, error = %e, "Order book subscription failed");
+                        }
+                    }
+                }
+            }
+        });
+        Self { requests, markets }
+    }
+
+    /// Prepare the windows due at `now`; skipped while a lookup is running
+    pub fn poll(&self, now: DateTime<Utc>) {
+        let _ = self.requests.try_send(now);
+    }
+
+    /// Next market prepared
+    pub async fn recv(&mut self) -> Option<Market> {
+        self.markets.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Lists each window's market from `listed_at` before it opens
+    struct Listing {
+        listed_at: Duration,
+        clock: Mutex<DateTime<Utc>>,
+        lookups: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl WindowLookup for Listing {
+        async fn window_market(
+            &self,
+            asset: &str,
+            open: DateTime<Utc>,
+        ) -> anyhow::Result<Option<Market>> {
+            *self.lookups.lock().unwrap() += 1;
+            if open - *self.clock.lock().unwrap() > self.listed_at {
+                return Ok(None);
+            }
+            let slug = event_slug(asset, open);
+            Ok(Some(Market {
+                condition_id: slug.clone(),
+                asset: asset.to_string(),
+                yes_token_id: format!("{}-yes", slug),
+                no_token_id: format!("{}-no", slug),
+                open_price: Default::default(),
+                open_time: open,
+                close_time: open + Duration::seconds(WINDOW_SECS),
+                group_id: None,
+                orientation: Default::default(),
+            }))
+        }
+    }
+
+    #[test]
+    fn test_next_window_open() {
+        let open = DateTime::from_timestamp(1_767_638_700, 0).unwrap();
+        assert_eq!(next_window_open(open - Duration::seconds(1)), open);
+        // At the open instant the next window is the one after it
+        assert_eq!(next_window_open(open), open + Duration::minutes(15));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_subscribed_before_the_window_opens() {
+        let open = DateTime::from_timestamp(1_767_638_700, 0).unwrap();
+        let lookup = Listing {
+            listed_at: Duration::seconds(30),
+            clock: Mutex::new(open),
+            lookups: Mutex::new(0),
+        };
+        let mut preparer = PreOpenPreparer::new(vec!["BTC".to_string()], 60);
+        let token = format!("{}-yes", event_slug("BTC", open));
+
+        // A simulated clock crossing the window boundary
+        let mut subscribed_at = None;
+        let mut now = open - Duration::seconds(120);
+        while now <= open + Duration::seconds(10) {
+            *lookup.clock.lock().unwrap() = now;
+            let markets = preparer.prepare(&lookup, now).await;
+            if preparer.is_subscribed(&token) && subscribed_at.is_none() {
+                subscribed_at = Some(now);
+                assert_eq!(markets.len(), 1);
+                assert!(markets[0].open_price.is_zero());
+            } else {
+                assert!(markets.is_empty());
+            }
+            now += Duration::seconds(5);
+        }
+
+        // Looked up every 5s from the lead on, found once listed, then left alone
+        let subscribed_at = subscribed_at.expect("never subscribed");
+        assert_eq!(subscribed_at, open - Duration::seconds(30));
+        assert_eq!(*lookup.lookups.lock().unwrap(), 7);
+        assert!(preparer.due(open - Duration::seconds(5)).is_empty());
+        assert_eq!(
+            preparer.due(open + Duration::seconds(840)),
+            vec![("BTC".to_string(), open + Duration::minutes(15))]
+        );
+    }
+
+    /// Sends one book for each token subscribed, then closes its feed
+    struct OneBook;
+
+    #[async_trait]
+    impl BookSubscriber for OneBook {
+        async fn subscribe(&self, token_id: &str) -> anyhow::Result<mpsc::Receiver<OrderBook>> {
+            let (tx, rx) = mpsc::channel(1);
+            tx.send(OrderBook::new(token_id)).await?;
+            Ok(rx)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_books_reach_the_bus_before_the_window_opens() {
+        let open = DateTime::from_timestamp(1_767_638_700, 0).unwrap();
+        let lookup = Arc::new(Listing {
+            listed_at: Duration::seconds(30),
+            clock: Mutex::new(open),
+            lookups: Mutex::new(0),
+        });
+        let bus = Arc::new(MarketDataBus::new());
+        let mut books =
+            bus.subscribe_filtered("engine", 16, |e| matches!(e, MarketDataEvent::Book(_)));
+        let mut task = PreOpenTask::spawn(
+            PreOpenPreparer::new(vec!["BTC".to_string()], 60),
+            lookup.clone(),
+            Arc::new(OneBook),
+            bus.clone(),
+        );
+        let slug = event_slug("BTC", open);
+
+        // The loop's clock crossing the window boundary, polling each second
+        let mut delivered = vec![];
+        let mut now = open - Duration::seconds(120);
+        while now <= open + Duration::seconds(10) {
+            *lookup.clock.lock().unwrap() = now;
+            task.poll(now);
+            let wait = std::time::Duration::from_millis(100);
+            if let Ok(Some(market)) = tokio::time::timeout(wait, task.recv()).await {
+                assert_eq!(market.condition_id, slug);
+                for _ in 0..2 {
+                    match tokio::time::timeout(wait, books.recv()).await {
+                        Ok(Some(MarketDataEvent::Book(book))) => {
+                            delivered.push((now, book.token_id))
+                        }
+                        _ => panic!("no book delivered for {}", market.condition_id),
+                    }
+                }
+            }
+            now += Duration::seconds(1);
+        }
+
+        // Both tokens' books arrived once, as soon as the window was listed
+        delivered.sort_by(|a, b| a.1.cmp(&b.1));
+        let at = open - Duration::seconds(30);
+        assert_eq!(
+            delivered,
+            vec![(at, format!("{}-no", slug)), (at, format!("{}-yes", slug))]
+        );
+    }
+}
@@ -0,0 +1,760 @@
+//! Gamma response schema drift
+//!
+//! Gamma has changed its responses without notice: a field renamed, a
+//! JSON-encoded list turned into a plain array, strings turned into
+//! numbers. Each time discovery broke without an error; markets just
+//! stopped appearing. Two defences guard against that:
+//!
+//! - Production parsing is lenient but loud. [`GammaMarket`] and
+//!   [`GammaEvent`] read the fields discovery relies on through untagged
+//!   fallbacks that accept the alternative shapes seen so far. A field
+//!   missing, converted from another shape or of a shape not understood is
+//!   counted in `polyhft_gamma_schema_drift_total` and warned about at
+//!   most once a minute, rather than defaulted silently.
+//! - The strict mirrors [`StrictGammaSeries`], [`StrictGammaEvent`] and
+//!   [`StrictGammaMarket`] deny unknown fields and require the recorded
+//!   types. Tests run them over the responses recorded under
+//!   [`GAMMA_FIXTURE_DIR`] by `gamma snapshot`, so a refresh that no longer
+//!   matches these structs fails CI. Fields Gamma sends that nothing uses
+//!   are listed as [`IgnoredAny`], so a new field is noticed too.
+//!
+//! [`GammaMarket`]: super::GammaMarket
+//! [`GammaEvent`]: super::GammaEvent
+
+use super::gamma::{event_slug, window_start, GammaClient, BTC_15M_SERIES_SLUG, WINDOW_SECS};
+use crate::telemetry::record_gamma_drift;
+use chrono::{DateTime, Duration, Utc};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+/// Directory of the recorded Gamma responses, relative to the crate root
+pub const GAMMA_FIXTURE_DIR: &str = "tests/fixtures/gamma";
+
+/// Recorded `/series` response
+pub const SERIES_FIXTURE: &str = "series.json";
+
+/// Recorded `/events` response
+pub const EVENTS_FIXTURE: &str = "events.json";
+
+/// Recorded `/markets` response for a closed market
+pub const MARKETS_FIXTURE: &str = "markets.json";
+
+/// Default number of items kept of each array in a snapshot
+pub const DEFAULT_SNAPSHOT_MAX_ITEMS: usize = 2;
+
+/// Free-text fields blanked in a snapshot; their content only adds noise
+/// to a fixture diff
+const SCRUBBED_FIELDS: &[&str] = &["description", "resolutionSource", "image", "icon"];
+
+/// Shortest gap between two warnings for the same drifted field
+const DRIFT_WARN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How a field departed from the shape discovery expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DriftKind {
+    /// Absent or null though relied on
+    Missing,
+    /// In a known alternative shape, converted
+    Coerced,
+    /// In a shape not understood; treated as absent
+    Unexpected,
+}
+
+impl DriftKind {
+    /// Metric label of the kind
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Coerced => "coerced",
+            Self::Unexpected => "unexpected",
+        }
+    }
+}
+
+/// Count a drifted field of a Gamma `entity`, warning at most once a
+/// minute for each entity, field and kind
+pub fn record_drift(entity: &'static str, field: &'static str, kind: DriftKind) {
+    type Warned = HashMap<(&'static str, &'static str, DriftKind), Instant>;
+    static LAST_WARNED: OnceLock<Mutex<Warned>> = OnceLock::new();
+
+    record_gamma_drift(entity, field, kind.as_str());
+    let now = Instant::now();
+    let due = LAST_WARNED
+        .get_or_init(Mutex::default)
+        .lock()
+        .map(|mut warned| match warned.get(&(entity, field, kind)) {
+            Some(at) if now.duration_since(*at) < DRIFT_WARN_INTERVAL => false,
+            _ => {
+                warned.insert((entity, field, kind), now);
+                true
+            }
+        })
+        .unwrap_or(false);
+    if due {
+        tracing::warn!(
+            entity,
+            field,
+            kind = kind.as_str(),
+            "Gamma response field drifted from the expected schema"
+        );
+    }
+}
+
+/// Record a field of a shape not understood, logging what was sent
+fn unexpected(entity: &'static str, field: &'static str, value: &Value) {
+    tracing::debug!(entity, field, %value, "Gamma field of an unexpected shape");
+    record_drift(entity, field, DriftKind::Unexpected);
+}
+
+/// A field read through a fallback that records drift
+pub(super) trait Lenient {
+    type Output;
+
+    /// The value in the expected shape, recording any conversion or a
+    /// shape not understood
+    fn read(self, entity: &'static str, field: &'static str) -> Option<Self::Output>;
+}
+
+/// A field discovery cannot do without; its absence is recorded as drift
+pub(super) fn required<L: Lenient>(
+    value: Option<L>,
+    entity: &'static str,
+    field: &'static str,
+) -> Option<L::Output> {
+    match value {
+        Some(value) => value.read(entity, field),
+        None => {
+            record_drift(entity, field, DriftKind::Missing);
+            None
+        }
+    }
+}
+
+/// A field Gamma may legitimately leave out
+pub(super) fn optional<L: Lenient>(
+    value: Option<L>,
+    entity: &'static str,
+    field: &'static str,
+) -> Option<L::Output> {
+    value.and_then(|value| value.read(entity, field))
+}
+
+/// A string, or a number sent in its place
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(super) enum LenientText {
+    Text(String),
+    Number(serde_json::Number),
+    Other(Value),
+}
+
+impl Lenient for LenientText {
+    type Output = String;
+
+    fn read(self, entity: &'static str, field: &'static str) -> Option<String> {
+        match self {
+            Self::Text(text) => Some(text),
+            Self::Number(number) => {
+                record_drift(entity, field, DriftKind::Coerced);
+                Some(number.to_string())
+            }
+            Self::Other(value) => {
+                unexpected(entity, field, &value);
+                None
+            }
+        }
+    }
+}
+
+/// A list of strings JSON-encoded in a string, or sent as a plain array;
+/// numbers among the items are converted to strings
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(super) enum EncodedList {
+    Encoded(String),
+    Plain(Vec<Value>),
+    Other(Value),
+}
+
+impl Lenient for EncodedList {
+    type Output = String;
+
+    fn read(self, entity: &'static str, field: &'static str) -> Option<String> {
+        let items = match self {
+            Self::Encoded(text) => match serde_json::from_str::<Vec<Value>>(&text) {
+                Ok(items) if items.iter().all(Value::is_string) => return Some(text),
+                Ok(items) => items,
+                Err(_) => {
+                    record_drift(entity, field, DriftKind::Unexpected);
+                    return None;
+                }
+            },
+            Self::Plain(items) => items,
+            Self::Other(value) => {
+                unexpected(entity, field, &value);
+                return None;
+            }
+        };
+        let mut strings = Vec::with_capacity(items.len());
+        for item in items {
+            match item {
+                Value::String(text) => strings.push(text),
+                Value::Number(number) => strings.push(number.to_string()),
+                _ => {
+                    record_drift(entity, field, DriftKind::Unexpected);
+                    return None;
+                }
+            }
+        }
+        record_drift(entity, field, DriftKind::Coerced);
+        serde_json::to_string(&strings).ok()
+    }
+}
+
+/// A boolean, or `"true"`/`"false"` sent as text
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(super) enum LenientFlag {
+    Flag(bool),
+    Text(String),
+    Other(Value),
+}
+
+impl Lenient for LenientFlag {
+    type Output = bool;
+
+    fn read(self, entity: &'static str, field: &'static str) -> Option<bool> {
+        let flag = match self {
+            Self::Flag(flag) => return Some(flag),
+            Self::Text(text) if text.eq_ignore_ascii_case("true") => true,
+            Self::Text(text) if text.eq_ignore_ascii_case("false") => false,
+            Self::Text(text) => {
+                unexpected(entity, field, &Value::String(text));
+                return None;
+            }
+            Self::Other(value) => {
+                unexpected(entity, field, &value);
+                return None;
+            }
+        };
+        record_drift(entity, field, DriftKind::Coerced);
+        Some(flag)
+    }
+}
+
+/// An RFC 3339 time, or epoch seconds or milliseconds sent in its place
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(super) enum LenientTime {
+    Text(String),
+    Epoch(i64),
+    Other(Value),
+}
+
+/// Epochs above this are taken as milliseconds; in seconds it is in 5138
+const EPOCH_MILLIS_ABOVE: i64 = 100_000_000_000;
+
+impl Lenient for LenientTime {
+    type Output = DateTime<Utc>;
+
+    fn read(self, entity: &'static str, field: &'static str) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Text(text) => match text.parse() {
+                Ok(time) => Some(time),
+                Err(_) => {
+                    record_drift(entity, field, DriftKind::Unexpected);
+                    None
+                }
+            },
+            Self::Epoch(epoch) => {
+                record_drift(entity, field, DriftKind::Coerced);
+                if epoch > EPOCH_MILLIS_ABOVE {
+                    DateTime::from_timestamp_millis(epoch)
+                } else {
+                    DateTime::from_timestamp(epoch, 0)
+                }
+            }
+            Self::Other(value) => {
+                unexpected(entity, field, &value);
+                None
+            }
+        }
+    }
+}
+
+/// A recorded response checked beyond its types
+trait Contract: DeserializeOwned {
+    fn check(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// A list field JSON-encoded in a string must decode to strings
+fn check_encoded(field: &str, encoded: &str) -> Result<(), String> {
+    serde_json::from_str::<Vec<String>>(encoded)
+        .map(|_| ())
+        .map_err(|e| format!("{field} is not an encoded list of strings: {e}"))
+}
+
+/// `/markets` item as recorded, every field accounted for
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StrictGammaMarket {
+    pub condition_id: String,
+    pub clob_token_ids: String,
+    pub outcomes: String,
+    pub outcome_prices: String,
+    #[serde(default)]
+    pub event_start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: DateTime<Utc>,
+    pub closed: bool,
+    #[serde(default)]
+    pub neg_risk: Option<bool>,
+    #[serde(default, rename = "negRiskMarketID")]
+    pub neg_risk_market_id: Option<String>,
+
+    // Sent but unused
+    #[serde(default)]
+    pub id: IgnoredAny,
+    #[serde(default)]
+    pub question: IgnoredAny,
+    #[serde(default)]
+    pub slug: IgnoredAny,
+    #[serde(default)]
+    pub description: IgnoredAny,
+    #[serde(default)]
+    pub resolution_source: IgnoredAny,
+    #[serde(default)]
+    pub image: IgnoredAny,
+    #[serde(default)]
+    pub icon: IgnoredAny,
+    #[serde(default)]
+    pub active: IgnoredAny,
+    #[serde(default)]
+    pub archived: IgnoredAny,
+    #[serde(default)]
+    pub restricted: IgnoredAny,
+    #[serde(default)]
+    pub created_at: IgnoredAny,
+    #[serde(default)]
+    pub updated_at: IgnoredAny,
+    #[serde(default)]
+    pub closed_time: IgnoredAny,
+    #[serde(default)]
+    pub start_date_iso: IgnoredAny,
+    #[serde(default)]
+    pub end_date_iso: IgnoredAny,
+    #[serde(default)]
+    pub volume: IgnoredAny,
+    #[serde(default)]
+    pub volume_num: IgnoredAny,
+    #[serde(default)]
+    pub volume24hr: IgnoredAny,
+    #[serde(default)]
+    pub liquidity: IgnoredAny,
+    #[serde(default)]
+    pub liquidity_num: IgnoredAny,
+    #[serde(default)]
+    pub enable_order_book: IgnoredAny,
+    #[serde(default)]
+    pub accepting_orders: IgnoredAny,
+    #[serde(default)]
+    pub order_price_min_tick_size: IgnoredAny,
+    #[serde(default)]
+    pub order_min_size: IgnoredAny,
+    #[serde(default)]
+    pub best_bid: IgnoredAny,
+    #[serde(default)]
+    pub best_ask: IgnoredAny,
+    #[serde(default)]
+    pub last_trade_price: IgnoredAny,
+    #[serde(default)]
+    pub spread: IgnoredAny,
+    #[serde(default, rename = "questionID")]
+    pub question_id: IgnoredAny,
+    #[serde(default)]
+    pub uma_resolution_status: IgnoredAny,
+    #[serde(default)]
+    pub fees_enabled: IgnoredAny,
+}
+
+impl Contract for StrictGammaMarket {
+    fn check(&self) -> Result<(), String> {
+        check_encoded("clobTokenIds", &self.clob_token_ids)?;
+        check_encoded("outcomes", &self.outcomes)?;
+        check_encoded("outcomePrices", &self.outcome_prices)?;
+        if self.event_start_time.is_none() && self.start_date.is_none() {
+            return Err("neither eventStartTime nor startDate".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// `/events` item as recorded, every field accounted for
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StrictGammaEvent {
+    pub slug: String,
+    #[serde(default)]
+    pub markets: Vec<StrictGammaMarket>,
+    #[serde(default)]
+    pub neg_risk: Option<bool>,
+    #[serde(default, rename = "negRiskMarketID")]
+    pub neg_risk_market_id: Option<String>,
+
+    // Sent but unused
+    #[serde(default)]
+    pub id: IgnoredAny,
+    #[serde(default)]
+    pub ticker: IgnoredAny,
+    #[serde(default)]
+    pub title: IgnoredAny,
+    #[serde(default)]
+    pub description: IgnoredAny,
+    #[serde(default)]
+    pub resolution_source: IgnoredAny,
+    #[serde(default)]
+    pub image: IgnoredAny,
+    #[serde(default)]
+    pub icon: IgnoredAny,
+    #[serde(default)]
+    pub start_date: IgnoredAny,
+    #[serde(default)]
+    pub end_date: IgnoredAny,
+    #[serde(default)]
+    pub start_time: IgnoredAny,
+    #[serde(default)]
+    pub creation_date: IgnoredAny,
+    #[serde(default)]
+    pub created_at: IgnoredAny,
+    #[serde(default)]
+    pub updated_at: IgnoredAny,
+    #[serde(default)]
+    pub active: IgnoredAny,
+    #[serde(default)]
+    pub closed: IgnoredAny,
+    #[serde(default)]
+    pub archived: IgnoredAny,
+    #[serde(default)]
+    pub restricted: IgnoredAny,
+    #[serde(default)]
+    pub volume: IgnoredAny,
+    #[serde(default)]
+    pub volume24hr: IgnoredAny,
+    #[serde(default)]
+    pub liquidity: IgnoredAny,
+    #[serde(default)]
+    pub open_interest: IgnoredAny,
+    #[serde(default)]
+    pub enable_order_book: IgnoredAny,
+    #[serde(default)]
+    pub series: IgnoredAny,
+    #[serde(default)]
+    pub tags: IgnoredAny,
+}
+
+impl Contract for StrictGammaEvent {
+    fn check(&self) -> Result<(), String> {
+        self.markets
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, market)| market.check().map_err(|e| format!("markets[{i}]: {e}")))
+    }
+}
+
+/// `/series` item as recorded, every field accounted for
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StrictGammaSeries {
+    pub slug: String,
+    #[serde(default)]
+    pub events: Vec<StrictGammaEvent>,
+
+    // Sent but unused
+    #[serde(default)]
+    pub id: IgnoredAny,
+    #[serde(default)]
+    pub ticker: IgnoredAny,
+    #[serde(default)]
+    pub title: IgnoredAny,
+    #[serde(default)]
+    pub series_type: IgnoredAny,
+    #[serde(default)]
+    pub recurrence: IgnoredAny,
+    #[serde(default)]
+    pub image: IgnoredAny,
+    #[serde(default)]
+    pub icon: IgnoredAny,
+    #[serde(default)]
+    pub active: IgnoredAny,
+    #[serde(default)]
+    pub closed: IgnoredAny,
+    #[serde(default)]
+    pub archived: IgnoredAny,
+    #[serde(default)]
+    pub created_at: IgnoredAny,
+    #[serde(default)]
+    pub updated_at: IgnoredAny,
+    #[serde(default)]
+    pub volume: IgnoredAny,
+    #[serde(default)]
+    pub volume24hr: IgnoredAny,
+    #[serde(default)]
+    pub liquidity: IgnoredAny,
+}
+
+impl Contract for StrictGammaSeries {
+    fn check(&self) -> Result<(), String> {
+        self.events
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, event)| event.check().map_err(|e| format!("events[{i}]: {e}")))
+    }
+}
+
+/// Where each item of a recorded array diverges from `T`
+fn diverging<T: Contract>(file: &str, recorded: &Value) -> Vec<String> {
+    let Some(items) = recorded.as_array() else {
+        return vec![format!("{file}: not an array")];
+    };
+    items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| {
+            T::deserialize(item)
+                .map_err(|e| e.to_string())
+                .and_then(|parsed| parsed.check())
+                .err()
+                .map(|e| format!("{file}[{i}]: {e}"))
+        })
+        .collect()
+}
+
+/// Trim a recorded response for a fixture: every array cut to
+/// `max_items` and free-text fields blanked, keys kept
+pub fn scrub(value: &mut Value, max_items: usize) {
+    match value {
+        Value::Array(items) => {
+            items.truncate(max_items);
+            items.iter_mut().for_each(|item| scrub(item, max_items));
+        }
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                if field.is_string() && SCRUBBED_FIELDS.contains(&key.as_str()) {
+                    *field = Value::String(String::new());
+                } else {
+                    scrub(field, max_items);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Raw Gamma responses of the kinds discovery reads
+#[derive(Debug, Clone)]
+pub struct GammaSnapshot {
+    /// `/series` for the BTC 15-minute series
+    pub series: Value,
+    /// `/events` for the previous, current and next windows
+    pub events: Value,
+    /// `/markets` for the closed markets of the previous window
+    pub markets: Value,
+}
+
+impl GammaSnapshot {
+    /// Fetch the responses discovery and resolution read, as of `now`
+    pub async fn fetch(client: &GammaClient, now: DateTime<Utc>) -> anyhow::Result<Self> {
+        let series = client
+            .fetch_raw("/series", &[("slug", BTC_15M_SERIES_SLUG.to_string())])
+            .await?;
+        let current = window_start(now);
+        let previous = event_slug("btc", current - Duration::seconds(WINDOW_SECS));
+        let query: Vec<_> = [
+            previous.clone(),
+            event_slug("btc", current),
+            event_slug("btc", current + Duration::seconds(WINDOW_SECS)),
+        ]
+        .into_iter()
+        .map(|slug| ("slug", slug))
+        .collect();
+        let events = client.fetch_raw("/events", &query).await?;
+
+        let condition_ids: Vec<String> = events
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|event| event["slug"] == previous.as_str())
+            .filter_map(|event| event["markets"].as_array())
+            .flatten()
+            .filter_map(|market| market["conditionId"].as_str().map(str::to_string))
+            .collect();
+        let markets = if condition_ids.is_empty() {
+            tracing::warn!(slug = %previous, "No closed market found for the previous window");
+            Value::Array(vec![])
+        } else {
+            let mut query: Vec<_> = condition_ids
+                .into_iter()
+                .map(|id| ("condition_ids", id))
+                .collect();
+            query.push(("closed", "true".to_string()));
+            client.fetch_raw("/markets", &query).await?
+        };
+        Ok(Self {
+            series,
+            events,
+            markets,
+        })
+    }
+
+    /// Read the snapshot stored in `dir`
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let read = |file: &str| -> anyhow::Result<Value> {
+            let path = dir.join(file);
+            let bytes = std::fs::read(&path)
+                .map_err(|e| anyhow::anyhow!("reading {}: {e}", path.display()))?;
+            Ok(serde_json::from_slice(&bytes)?)
+        };
+        Ok(Self {
+            series: read(SERIES_FIXTURE)?,
+            events: read(EVENTS_FIXTURE)?,
+            markets: read(MARKETS_FIXTURE)?,
+        })
+    }
+
+    /// Scrub every response with [`scrub`]
+    pub fn scrubbed(mut self, max_items: usize) -> Self {
+        for value in [&mut self.series, &mut self.events, &mut self.markets] {
+            scrub(value, max_items);
+        }
+        self
+    }
+
+    /// Write the responses to `dir` as pretty-printed fixtures
+    pub fn save(&self, dir: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for (file, value) in [
+            (SERIES_FIXTURE, &self.series),
+            (EVENTS_FIXTURE, &self.events),
+            (MARKETS_FIXTURE, &self.markets),
+        ] {
+            let mut json = serde_json::to_string_pretty(value)?;
+            json.push('\n');
+            std::fs::write(dir.join(file), json)?;
+        }
+        Ok(())
+    }
+
+    /// Every place the responses diverge from the strict mirrors; empty
+    /// when the structs still describe what Gamma sends
+    pub fn divergences(&self) -> Vec<String> {
+        let mut divergences = diverging::<StrictGammaSeries>(SERIES_FIXTURE, &self.series);
+        divergences.extend(diverging::<StrictGammaEvent>(EVENTS_FIXTURE, &self.events));
+        divergences.extend(diverging::<StrictGammaMarket>(
+            MARKETS_FIXTURE,
+            &self.markets,
+        ));
+        divergences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::{GammaEvent, GammaMarket, GammaSeries};
+    use crate::signal::Side;
+    use serde_json::json;
+
+    fn fixtures() -> GammaSnapshot {
+        GammaSnapshot::load(Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/gamma"
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fixtures_match_the_strict_mirrors() {
+        let fixtures = fixtures();
+        assert_eq!(fixtures.divergences(), Vec::<String>::new());
+
+        // The production structs read the same responses
+        let series: Vec<GammaSeries> = serde_json::from_value(fixtures.series).unwrap();
+        assert!(!series[0].events.is_empty());
+        let events: Vec<GammaEvent> = serde_json::from_value(fixtures.events).unwrap();
+        assert!(events.iter().any(|e| !e.to_markets().is_empty()));
+        let markets: Vec<GammaMarket> = serde_json::from_value(fixtures.markets).unwrap();
+        assert!(markets[0].closed);
+    }
+
+    #[test]
+    fn test_renamed_or_retyped_fields_diverge() {
+        let mut fixtures = fixtures();
+        let market = fixtures.markets[0].as_object_mut().unwrap();
+        let tokens = market.remove("clobTokenIds").unwrap();
+        market.insert("clobTokenIDs".to_string(), tokens);
+        fixtures.events[0]["markets"][0]["outcomePrices"] = json!("[1, 0]");
+        fixtures.series[0]["events"][0]["slug"] = json!(1767638700);
+
+        let divergences = fixtures.divergences();
+        assert_eq!(divergences.len(), 3, "{divergences:?}");
+        assert!(divergences[0].starts_with("series.json[0]"));
+        assert!(divergences[1].contains("outcomePrices"));
+        assert!(divergences[2].contains("clobTokenIDs"));
+    }
+
+    #[test]
+    fn test_lenient_parsing_converts_drifted_shapes() {
+        let market: GammaMarket = serde_json::from_value(json!({
+            "conditionId": "0xabc",
+            "clobTokenIds": ["111", 222],
+            "outcomes": "[\"Up\", \"Down\"]",
+            "outcomePrices": "[1, 0]",
+            "eventStartTime": 1_767_638_700,
+            "endDate": 1_767_639_600_000_i64,
+            "closed": "true",
+            "negRisk": {"unexpected": true}
+        }))
+        .unwrap();
+        assert_eq!(market.clob_token_ids.as_deref(), Some("[\"111\",\"222\"]"));
+        assert_eq!(market.outcome_prices.as_deref(), Some("[\"1\",\"0\"]"));
+        assert_eq!(
+            market.event_start_time,
+            DateTime::from_timestamp(1_767_638_700, 0)
+        );
+        assert_eq!(market.end_date, DateTime::from_timestamp(1_767_639_600, 0));
+        assert!(market.closed);
+        assert!(!market.neg_risk);
+
+        let mut converted = market.to_market().unwrap();
+        converted.yes_token_id = "111".to_string();
+        assert_eq!(market.resolution(&converted), Some(Side::Yes));
+
+        // A market without its id is still rejected
+        assert!(serde_json::from_value::<GammaMarket>(json!({"closed": false})).is_err());
+    }
+
+    #[test]
+    fn test_scrub_trims_arrays_and_blanks_free_text() {
+        let mut value = json!([
+            {"slug": "a", "description": "long text", "markets": [1, 2, 3], "closed": true},
+            {"slug": "b"},
+            {"slug": "c"}
+        ]);
+        scrub(&mut value, 2);
+        assert_eq!(
+            value,
+            json!([
+                {"slug": "a", "description": "", "markets": [1, 2], "closed": true},
+                {"slug": "b"}
+            ])
+        );
+    }
+}
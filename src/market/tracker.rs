@@ -1,6 +1,7 @@
 //! Market tracker implementation
 
 use super::{GammaClient, Market, MarketTracker};
+use crate::symbols::SymbolMap;
 use async_trait::async_trait;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -9,6 +10,7 @@ use tokio::sync::RwLock;
 pub struct MarketTrackerImpl {
     client: GammaClient,
     markets: Arc<RwLock<Vec<Market>>>,
+    symbols: Option<SymbolMap>,
 }
 
 impl MarketTrackerImpl {
@@ -17,8 +19,15 @@ impl MarketTrackerImpl {
         Self {
             client,
             markets: Arc::new(RwLock::new(vec![])),
+            symbols: None,
         }
     }
+
+    /// Refuse discovered markets whose asset has no price feed
+    pub fn with_symbols(mut self, symbols: SymbolMap) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
 }
 
 #[async_trait]
@@ -30,6 +39,9 @@ impl MarketTracker for MarketTrackerImpl {
 
     async fn refresh(&self) -> anyhow::Result<()> {
         let new_markets = self.client.fetch_btc_markets().await?;
+        if let Some(symbols) = &self.symbols {
+            symbols.validate_markets(&new_markets)?;
+        }
         let mut markets = self.markets.write().await;
         *markets = new_markets;
         Ok(())
@@ -2,18 +2,31 @@
 //!
 //! Uses Black-Scholes-style probability calculation:
 //! P(up) = N(d2) where d2 = (ln(S/K) - 0.5*sigma^2*T) / (sigma*sqrt(T))
+//!
+//! With price bounds set, both probabilities are held to the prices a book
+//! can quote, so near expiry the model never prices a token past the
+//! extreme ticks.
 
 use super::{FairValue, FairValueModel, FairValueParams};
+use crate::market::PriceBounds;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
 /// GBM-based fair value model
-pub struct GbmModel;
+pub struct GbmModel {
+    bounds: Option<PriceBounds>,
+}
 
 impl GbmModel {
     /// Create a new GBM model
     pub fn new() -> Self {
-        Self
+        Self { bounds: None }
+    }
+
+    /// Hold fair values to `bounds`
+    pub fn with_bounds(mut self, bounds: PriceBounds) -> Self {
+        self.bounds = Some(bounds);
+        self
     }
 }
 
@@ -25,52 +38,61 @@ impl Default for GbmModel {
 
 impl FairValueModel for GbmModel {
     fn calculate(&self, params: FairValueParams) -> FairValue {
-        // Convert time to expiry to years
-        let t_secs = params.time_to_expiry.num_seconds() as f64;
-        let t_years = t_secs / (365.25 * 24.0 * 60.0 * 60.0);
-
-        if t_years <= 0.0 || params.volatility == dec!(0) {
-            // At expiry or zero vol: deterministic outcome
-            let yes_prob = if params.current_price >= params.open_price {
-                dec!(1)
-            } else {
-                dec!(0)
-            };
-            return FairValue {
-                yes_prob,
-                no_prob: Decimal::ONE - yes_prob,
-                confidence: dec!(1),
-            };
+        let fair_value = probability(params);
+        match &self.bounds {
+            Some(bounds) => fair_value.clamped(bounds),
+            None => fair_value,
         }
+    }
+}
 
-        // Calculate d2 = (ln(S/K) - 0.5*sigma^2*T) / (sigma*sqrt(T))
-        let s: f64 = params.current_price.try_into().unwrap_or(0.0);
-        let k: f64 = params.open_price.try_into().unwrap_or(0.0);
-        let sigma: f64 = params.volatility.try_into().unwrap_or(0.0);
-
-        if k <= 0.0 || s <= 0.0 {
-            return FairValue {
-                yes_prob: dec!(0.5),
-                no_prob: dec!(0.5),
-                confidence: dec!(0),
-            };
-        }
+/// Unbounded probabilities of each outcome
+fn probability(params: FairValueParams) -> FairValue {
+    // Convert time to expiry to years
+    let t_secs = params.time_to_expiry.num_seconds() as f64;
+    let t_years = t_secs / (365.25 * 24.0 * 60.0 * 60.0);
+
+    if t_years <= 0.0 || params.volatility == dec!(0) {
+        // At expiry or zero vol: deterministic outcome
+        let yes_prob = if params.current_price >= params.open_price {
+            dec!(1)
+        } else {
+            dec!(0)
+        };
+        return FairValue {
+            yes_prob,
+            no_prob: Decimal::ONE - yes_prob,
+            confidence: dec!(1),
+        };
+    }
+
+    // Calculate d2 = (ln(S/K) - 0.5*sigma^2*T) / (sigma*sqrt(T))
+    let s: f64 = params.current_price.try_into().unwrap_or(0.0);
+    let k: f64 = params.open_price.try_into().unwrap_or(0.0);
+    let sigma: f64 = params.volatility.try_into().unwrap_or(0.0);
+
+    if k <= 0.0 || s <= 0.0 {
+        return FairValue {
+            yes_prob: dec!(0.5),
+            no_prob: dec!(0.5),
+            confidence: dec!(0),
+        };
+    }
 
-        let d2 = ((s / k).ln() - 0.5 * sigma * sigma * t_years) / (sigma * t_years.sqrt());
+    let d2 = ((s / k).ln() - 0.5 * sigma * sigma * t_years) / (sigma * t_years.sqrt());
 
-        // N(d2) using standard normal CDF approximation
-        let yes_prob_f64 = normal_cdf(d2);
-        let yes_prob = Decimal::try_from(yes_prob_f64).unwrap_or(dec!(0.5));
-        let no_prob = Decimal::ONE - yes_prob;
+    // N(d2) using standard normal CDF approximation
+    let yes_prob_f64 = normal_cdf(d2);
+    let yes_prob = Decimal::try_from(yes_prob_f64).unwrap_or(dec!(0.5));
+    let no_prob = Decimal::ONE - yes_prob;
 
-        // Confidence based on time to expiry (higher confidence closer to expiry)
-        let confidence = Decimal::try_from(1.0 - t_years.min(1.0)).unwrap_or(dec!(0.5));
+    // Confidence based on time to expiry (higher confidence closer to expiry)
+    let confidence = Decimal::try_from(1.0 - t_years.min(1.0)).unwrap_or(dec!(0.5));
 
-        FairValue {
-            yes_prob,
-            no_prob,
-            confidence,
-        }
+    FairValue {
+        yes_prob,
+        no_prob,
+        confidence,
     }
 }
 
@@ -192,6 +214,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::default_constructed_unit_structs)]
     fn test_gbm_default() {
         let model = GbmModel::default();
         let params = FairValueParams {
@@ -234,4 +257,26 @@ mod tests {
         // CDF(-x) = 1 - CDF(x)
         assert!((pos + neg - 1.0).abs() < 0.001);
     }
+
+    #[test]
+    fn test_gbm_with_bounds_stays_quotable() {
+        let params = FairValueParams {
+            current_price: dec!(100130),
+            open_price: dec!(100000),
+            time_to_expiry: Duration::seconds(45),
+            volatility: dec!(0.4),
+        };
+        assert!(GbmModel::new().calculate(params.clone()).yes_prob > dec!(0.99));
+
+        let model = GbmModel::new().with_bounds(PriceBounds::default());
+        let fair_value = model.calculate(params.clone());
+        assert_eq!(fair_value.yes_prob, dec!(0.99));
+        assert_eq!(fair_value.no_prob, dec!(0.01));
+
+        let expired = model.calculate(FairValueParams {
+            time_to_expiry: Duration::zero(),
+            ..params
+        });
+        assert_eq!(expired.yes_prob, dec!(0.99));
+    }
 }
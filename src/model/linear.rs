@@ -0,0 +1,79 @@
+//! Linear lag model
+//!
+//! The expected YES price moves linearly with the spot move since open:
+//! `0.5 + sensitivity * move_pct`, held to [0, 1]. It ignores time to
+//! expiry and volatility entirely, which is what makes it useful as a
+//! cross-check on the GBM model: the two only drift far apart when one of
+//! those inputs, or the strike, is doing something extreme.
+
+use super::{FairValue, FairValueModel, FairValueParams};
+use crate::data::features::move_pct;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+/// Default change in expected price per 1% spot move
+pub const DEFAULT_LAG_SENSITIVITY: Decimal = dec!(1);
+
+/// Expected price linear in the spot move
+pub struct LinearLagModel {
+    sensitivity: Decimal,
+}
+
+impl LinearLagModel {
+    /// Model moving `sensitivity` in price per 1% spot move
+    pub fn new(sensitivity: Decimal) -> Self {
+        Self { sensitivity }
+    }
+}
+
+impl Default for LinearLagModel {
+    fn default() -> Self {
+        Self::new(DEFAULT_LAG_SENSITIVITY)
+    }
+}
+
+impl FairValueModel for LinearLagModel {
+    fn calculate(&self, params: FairValueParams) -> FairValue {
+        let Some(move_pct) = move_pct(params.open_price, params.current_price) else {
+            return FairValue {
+                yes_prob: dec!(0.5),
+                no_prob: dec!(0.5),
+                confidence: dec!(0),
+            };
+        };
+        let yes_prob = (dec!(0.5) + self.sensitivity * move_pct).clamp(Decimal::ZERO, Decimal::ONE);
+        FairValue {
+            yes_prob,
+            no_prob: Decimal::ONE - yes_prob,
+            confidence: dec!(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_linear_ignores_time_and_volatility() {
+        let model = LinearLagModel::default();
+        let params = |minutes, volatility| FairValueParams {
+            current_price: dec!(100100),
+            open_price: dec!(100000),
+            time_to_expiry: Duration::minutes(minutes),
+            volatility,
+        };
+        // 0.1% up is 10 cents over even, however long is left
+        assert_eq!(model.calculate(params(14, dec!(0.3))).yes_prob, dec!(0.6));
+        assert_eq!(model.calculate(params(1, dec!(1.2))).yes_prob, dec!(0.6));
+
+        let crash = FairValueParams {
+            current_price: dec!(98000),
+            ..params(5, dec!(0.5))
+        };
+        let fair_value = model.calculate(crash);
+        assert_eq!(fair_value.yes_prob, Decimal::ZERO);
+        assert_eq!(fair_value.no_prob, Decimal::ONE);
+    }
+}
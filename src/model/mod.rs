@@ -1,13 +1,17 @@
 //! Fair value model module
 //!
-//! Calculates theoretical fair value for Yes/No tokens using GBM
+//! Calculates theoretical fair value for Yes/No tokens using GBM, with a
+//! linear lag model as a cross-check
 
 mod gbm;
+mod linear;
 mod volatility;
 
 pub use gbm::GbmModel;
-pub use volatility::VolatilityEstimator;
+pub use linear::{LinearLagModel, DEFAULT_LAG_SENSITIVITY};
+pub use volatility::{VolatilityEstimator, VolatilityState, DEFAULT_MAX_SAMPLES};
 
+use crate::market::PriceBounds;
 use chrono::Duration;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -36,6 +40,17 @@ pub struct FairValue {
     pub confidence: Decimal,
 }
 
+impl FairValue {
+    /// Expected prices of both tokens, held to what the book can quote
+    pub fn clamped(&self, bounds: &PriceBounds) -> FairValue {
+        FairValue {
+            yes_prob: bounds.clamp(self.yes_prob),
+            no_prob: bounds.clamp(self.no_prob),
+            confidence: self.confidence,
+        }
+    }
+}
+
 /// Trait for fair value model implementations
 pub trait FairValueModel: Send + Sync {
     /// Calculate fair value given parameters
@@ -1,15 +1,34 @@
 //! Volatility estimation module
 //!
-//! Rolling realized volatility from price returns
+//! Rolling realized volatility from price returns. Samples are pruned by
+//! the time window and, during tick storms, thinned to a fixed count so
+//! memory stays bounded whatever the tick rate.
 
 use chrono::{DateTime, Duration, Utc};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
+/// Default cap on retained price samples
+pub const DEFAULT_MAX_SAMPLES: usize = 4096;
+
+/// Samples held by a [`VolatilityEstimator`], for carrying it across a restart
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VolatilityState {
+    /// Minimum gap between samples set by thinning, in milliseconds
+    pub spacing_ms: i64,
+    /// Retained samples, oldest first
+    pub prices: Vec<(DateTime<Utc>, Decimal)>,
+}
+
 /// Rolling volatility estimator from log returns
 pub struct VolatilityEstimator {
     /// Window duration for volatility calculation
     window: Duration,
+    /// Most samples kept before the middle of the window is thinned
+    max_samples: usize,
+    /// Minimum gap between retained samples, set by thinning
+    spacing: Duration,
     /// Price history with timestamps
     prices: VecDeque<(DateTime<Utc>, Decimal)>,
 }
@@ -19,14 +38,52 @@ impl VolatilityEstimator {
     pub fn new(window: Duration) -> Self {
         Self {
             window,
+            max_samples: DEFAULT_MAX_SAMPLES,
+            spacing: Duration::zero(),
             prices: VecDeque::new(),
         }
     }
 
+    /// Cap retained samples (at least 3: both ends plus one to thin)
+    pub fn with_max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = max_samples.max(3);
+        self
+    }
+
+    /// Number of retained price samples
+    pub fn sample_count(&self) -> usize {
+        self.prices.len()
+    }
+
+    /// Samples currently held
+    pub fn state(&self) -> VolatilityState {
+        VolatilityState {
+            spacing_ms: self.spacing.num_milliseconds(),
+            prices: self.prices.iter().copied().collect(),
+        }
+    }
+
+    /// Replace the samples held with `state`, keeping the window and cap
+    pub fn restore(&mut self, state: VolatilityState) {
+        self.spacing = Duration::milliseconds(state.spacing_ms);
+        self.prices = state.prices.into();
+    }
+
+    /// Forget every sample, e.g. across a gap in the data
+    pub fn clear(&mut self) {
+        self.restore(VolatilityState::default());
+    }
+
     /// Add a new price observation
     pub fn update(&mut self, timestamp: DateTime<Utc>, price: Decimal) {
-        // Add new price
-        self.prices.push_back((timestamp, price));
+        // Add new price; inside the thinned spacing it replaces the latest
+        // sample instead, so the newest price is always kept exactly
+        let n = self.prices.len();
+        if n >= 2 && timestamp - self.prices[n - 2].0 < self.spacing {
+            self.prices[n - 1] = (timestamp, price);
+        } else {
+            self.prices.push_back((timestamp, price));
+        }
 
         // Remove old prices outside window
         let cutoff = timestamp - self.window;
@@ -37,6 +94,29 @@ impl VolatilityEstimator {
                 break;
             }
         }
+
+        if self.prices.len() > self.max_samples {
+            self.thin();
+        }
+    }
+
+    /// Drop every other sample between the first and last, then space
+    /// later samples at the resulting average gap so the window stays
+    /// uniformly sampled. Realized variance is additive, so the summed
+    /// squared returns (and hence the estimate) are preserved up to
+    /// sampling noise.
+    fn thin(&mut self) {
+        let last = self.prices.len() - 1;
+        let mut index = 0;
+        self.prices.retain(|_| {
+            let keep = index == 0 || index == last || index % 2 == 0;
+            index += 1;
+            keep
+        });
+        if let (Some((first, _)), Some((last, _))) = (self.prices.front(), self.prices.back()) {
+            let gaps = self.prices.len() as i32 - 1;
+            self.spacing = self.spacing.max((*last - *first) / gaps);
+        }
     }
 
     /// Calculate annualized realized volatility
@@ -235,4 +315,46 @@ mod tests {
         assert!(vol.is_some());
         assert!(vol.unwrap() > dec!(0));
     }
+
+    #[test]
+    fn test_thinning_bounds_memory_and_keeps_estimate() {
+        use crate::duration::DurationConfig;
+        use crate::sim::{SimConfig, SyntheticFeed};
+
+        // 500 ticks/sec for two minutes
+        let config = SimConfig {
+            duration_mins: DurationConfig::from_mins(2),
+            tick_interval_ms: DurationConfig::from_millis(2),
+            jump_probability: 0.0,
+            ..Default::default()
+        };
+        let mut full = VolatilityEstimator::new(Duration::minutes(1)).with_max_samples(usize::MAX);
+        let mut thinned = VolatilityEstimator::new(Duration::minutes(1)).with_max_samples(1024);
+        for tick in SyntheticFeed::new("BTCUSDT", &config) {
+            full.update(tick.exchange_ts, tick.price);
+            thinned.update(tick.exchange_ts, tick.price);
+            assert!(thinned.sample_count() <= 1024);
+        }
+
+        assert!(full.sample_count() > 29_000);
+        let full = f64::try_from(full.estimate().unwrap()).unwrap();
+        let thinned = f64::try_from(thinned.estimate().unwrap()).unwrap();
+        assert!(
+            (thinned - full).abs() / full < 0.1,
+            "thinned {} vs full {}",
+            thinned,
+            full
+        );
+    }
+
+    #[test]
+    fn test_thinning_keeps_both_ends() {
+        let mut estimator = VolatilityEstimator::new(Duration::minutes(5)).with_max_samples(4);
+        let base_time = Utc::now();
+        for i in 0..5 {
+            estimator.update(base_time + Duration::seconds(i), Decimal::from(100 + i));
+        }
+        let kept: Vec<_> = estimator.prices.iter().map(|(_, p)| *p).collect();
+        assert_eq!(kept, vec![dec!(100), dec!(102), dec!(104)]);
+    }
 }
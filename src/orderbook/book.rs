@@ -1,11 +1,35 @@
 //! Order book state management
 
-use super::PriceLevel;
+use super::{BookSide, DepthProfile, PriceLevel};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 /// L2 aggregated order book for a token
+///
+/// Levels are kept best first on each side; [`OrderBook::with_bid`] and
+/// [`OrderBook::with_ask`] put a level in its place:
+///
+/// ```
+/// use poly_hft::prelude::*;
+/// use rust_decimal_macros::dec;
+///
+/// let book = OrderBook::new("btc-15m-yes")
+///     .with_ask(dec!(0.52), dec!(40))
+///     .with_bid(dec!(0.49), dec!(100))
+///     .with_ask(dec!(0.51), dec!(60))
+///     .with_ask(dec!(0.55), dec!(500));
+/// assert_eq!(book.best_ask(), Some(dec!(0.51)));
+/// assert_eq!(book.spread(), Some(dec!(0.02)));
+/// assert_eq!(book.mid_price(), Some(dec!(0.50)));
+/// // 0.55 is four cents from the touch, past the two counted
+/// assert_eq!(book.depth_within(BookSide::Ask, 2), dec!(100));
+/// assert_eq!(book.top_of_book_fault(), None);
+///
+/// // A bid through the ask leaves a book no price is taken from
+/// let crossed = book.with_bid(dec!(0.53), dec!(10));
+/// assert_eq!(crossed.top_of_book_fault(), Some("crossed"));
+/// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderBook {
     /// Token identifier
@@ -29,6 +53,20 @@ impl OrderBook {
         }
     }
 
+    /// Add `size` bid at `price`, in price order, to a level already at
+    /// that price if there is one
+    pub fn with_bid(mut self, price: Decimal, size: Decimal) -> Self {
+        add_level(&mut self.bids, price, size, |a, b| a > b);
+        self
+    }
+
+    /// Add `size` offered at `price`, in price order, to a level already
+    /// at that price if there is one
+    pub fn with_ask(mut self, price: Decimal, size: Decimal) -> Self {
+        add_level(&mut self.asks, price, size, |a, b| a < b);
+        self
+    }
+
     /// Get best bid price
     pub fn best_bid(&self) -> Option<Decimal> {
         self.bids.first().map(|l| l.price)
@@ -54,6 +92,105 @@ impl OrderBook {
             _ => None,
         }
     }
+
+    /// Total size on `side` priced within `cents` cents of its best level
+    ///
+    /// Zero cents is the size at the touch alone. Gaps between levels are
+    /// measured by price, not by level count.
+    pub fn depth_within(&self, side: BookSide, cents: u32) -> Decimal {
+        let levels = match side {
+            BookSide::Bid => &self.bids,
+            BookSide::Ask => &self.asks,
+        };
+        let Some(touch) = levels.first().map(|l| l.price) else {
+            return Decimal::ZERO;
+        };
+        let reach = Decimal::from(cents) / Decimal::ONE_HUNDRED;
+        levels
+            .iter()
+            .take_while(|l| (l.price - touch).abs() <= reach)
+            .map(|l| l.size)
+            .sum()
+    }
+
+    /// Depth within 1, 2 and 3 cents of the touch on `side`
+    pub fn depth_profile(&self, side: BookSide) -> DepthProfile {
+        DepthProfile {
+            within_1c: self.depth_within(side, 1),
+            within_2c: self.depth_within(side, 2),
+            within_3c: self.depth_within(side, 3),
+        }
+    }
+
+    /// Best bid strictly above best ask
+    pub fn is_crossed(&self) -> bool {
+        matches!((self.best_bid(), self.best_ask()), (Some(bid), Some(ask)) if bid > ask)
+    }
+
+    /// Best bid equal to best ask
+    pub fn is_locked(&self) -> bool {
+        matches!((self.best_bid(), self.best_ask()), (Some(bid), Some(ask)) if bid == ask)
+    }
+
+    /// `"crossed"` or `"locked"` if the top of book cannot be traded off
+    pub fn top_of_book_fault(&self) -> Option<&'static str> {
+        if self.is_crossed() {
+            Some("crossed")
+        } else if self.is_locked() {
+            Some("locked")
+        } else {
+            None
+        }
+    }
+}
+
+/// Put `size` at `price` into `levels`, kept best first by `better`
+fn add_level(
+    levels: &mut Vec<PriceLevel>,
+    price: Decimal,
+    size: Decimal,
+    better: fn(Decimal, Decimal) -> bool,
+) {
+    let at = levels.partition_point(|l| better(l.price, price));
+    match levels.get_mut(at) {
+        Some(level) if level.price == price => level.size += size,
+        _ => levels.insert(at, PriceLevel { price, size }),
+    }
+}
+
+/// Both books of one market as the lag decision sees them: the YES book
+/// every price is taken from and, once it has arrived, the NO book
+#[derive(Debug, Clone, Copy)]
+pub struct MarketBooks<'a> {
+    /// YES token book
+    pub yes: &'a OrderBook,
+    /// NO token book, if one is held and usable
+    pub no: Option<&'a OrderBook>,
+}
+
+impl<'a> MarketBooks<'a> {
+    /// The YES book alone
+    pub fn new(yes: &'a OrderBook) -> Self {
+        Self { yes, no: None }
+    }
+
+    /// Check the YES book against `no`
+    pub fn with_no(mut self, no: Option<&'a OrderBook>) -> Self {
+        self.no = no;
+        self
+    }
+
+    /// YES price the NO ask implies: buying NO at its ask is selling YES
+    /// at `1 - no_ask`
+    pub fn implied_yes_bid(&self) -> Option<Decimal> {
+        self.no?.best_ask().map(|ask| Decimal::ONE - ask)
+    }
+
+    /// YES price the NO bid implies: selling NO at its bid is buying YES
+    /// at `1 - no_bid`
+    pub fn implied_yes_ask(&self) -> Option<Decimal> {
+        self.no?.best_bid().map(|bid| Decimal::ONE - bid)
+    }
 }
 
 #[cfg(test)]
@@ -77,6 +214,37 @@ mod tests {
         assert_eq!(book.spread(), Some(dec!(0.02)));
     }
 
+    #[test]
+    fn test_depth_within_across_gaps() {
+        let level = |price, size| PriceLevel { price, size };
+        let mut book = OrderBook::new("test");
+        book.asks = vec![
+            level(dec!(0.48), dec!(10)),
+            level(dec!(0.50), dec!(20)),
+            level(dec!(0.55), dec!(40)),
+        ];
+        book.bids = vec![level(dec!(0.47), dec!(5)), level(dec!(0.44), dec!(7))];
+
+        assert_eq!(book.depth_within(BookSide::Ask, 0), dec!(10));
+        assert_eq!(
+            book.depth_profile(BookSide::Ask),
+            DepthProfile {
+                within_1c: dec!(10),
+                within_2c: dec!(30),
+                // 0.55 is 7 cents away; the gap is not bridged
+                within_3c: dec!(30),
+            }
+        );
+        assert_eq!(book.depth_within(BookSide::Ask, 7), dec!(70));
+
+        assert_eq!(book.depth_within(BookSide::Bid, 2), dec!(5));
+        assert_eq!(book.depth_within(BookSide::Bid, 3), dec!(12));
+        assert_eq!(
+            OrderBook::new("empty").depth_profile(BookSide::Bid),
+            DepthProfile::default()
+        );
+    }
+
     #[test]
     fn test_order_book_new() {
         let book = OrderBook::new("test-token");
@@ -163,4 +331,38 @@ mod tests {
         assert_eq!(book.token_id, cloned.token_id);
         assert_eq!(book.bids.len(), cloned.bids.len());
     }
+
+    fn book(bid: Decimal, ask: Decimal) -> OrderBook {
+        let mut book = OrderBook::new("test");
+        book.bids = vec![PriceLevel {
+            price: bid,
+            size: dec!(100),
+        }];
+        book.asks = vec![PriceLevel {
+            price: ask,
+            size: dec!(100),
+        }];
+        book
+    }
+
+    #[test]
+    fn test_crossed_and_locked() {
+        let normal = book(dec!(0.50), dec!(0.52));
+        assert!(!normal.is_crossed());
+        assert!(!normal.is_locked());
+        assert_eq!(normal.top_of_book_fault(), None);
+
+        let crossed = book(dec!(0.53), dec!(0.52));
+        assert!(crossed.is_crossed());
+        assert!(!crossed.is_locked());
+        assert_eq!(crossed.top_of_book_fault(), Some("crossed"));
+
+        let locked = book(dec!(0.52), dec!(0.52));
+        assert!(!locked.is_crossed());
+        assert!(locked.is_locked());
+        assert_eq!(locked.top_of_book_fault(), Some("locked"));
+
+        // One-sided books are neither
+        assert!(!OrderBook::new("empty").is_crossed());
+    }
 }
@@ -0,0 +1,177 @@
+//! Book update cadence and staleness
+//!
+//! How old a book may be before it is too stale to trade on depends on the
+//! market: a quiet book legitimately goes seconds between updates, while
+//! during a fast move a two-second-old book is far behind. Each token's
+//! intervals between updates are tracked as an EWMA and a p95 over the
+//! recent ones. In adaptive mode the largest age accepted is `k` times that
+//! p95, clamped to absolute bounds; until enough intervals are seen, and in
+//! fixed mode, it is `max_book_age_ms`.
+
+use crate::duration::{DurationConfig, Millis};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Default largest book age in fixed mode, and in adaptive mode until the
+/// cadence is known
+pub const DEFAULT_MAX_BOOK_AGE_MS: u64 = 2_000;
+
+/// Default multiple of the p95 interval accepted in adaptive mode
+pub const DEFAULT_CADENCE_MULTIPLE: Decimal = dec!(3);
+
+/// Default smallest adaptive max age
+pub const DEFAULT_MIN_BOOK_AGE_MS: u64 = 500;
+
+/// Default largest adaptive max age
+pub const DEFAULT_MAX_ADAPTIVE_BOOK_AGE_MS: u64 = 10_000;
+
+/// Intervals kept per token for the p95
+const CADENCE_WINDOW: usize = 64;
+
+/// Intervals needed before the adaptive threshold is used
+const MIN_CADENCE_SAMPLES: usize = 8;
+
+/// Weight of the newest interval in the EWMA
+const EWMA_ALPHA: f64 = 0.2;
+
+/// How the largest accepted book age is chosen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FreshnessMode {
+    /// Always `max_book_age_ms`
+    Fixed,
+    /// `k` times the token's p95 update interval, clamped
+    #[default]
+    Adaptive,
+}
+
+/// Book staleness settings, under `[signal.book_freshness]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FreshnessConfig {
+    /// Fixed or adaptive threshold
+    #[serde(default)]
+    pub mode: FreshnessMode,
+    /// Largest book age in fixed mode, and before the cadence is known
+    #[serde(default = "default_max_book_age_ms")]
+    pub max_book_age_ms: DurationConfig<Millis>,
+    /// Multiple of the p95 update interval accepted in adaptive mode
+    #[serde(default = "default_cadence_multiple")]
+    pub k: Decimal,
+    /// Lower clamp of the adaptive threshold
+    #[serde(default = "default_min_book_age_ms")]
+    pub min_age_ms: DurationConfig<Millis>,
+    /// Upper clamp of the adaptive threshold
+    #[serde(default = "default_max_adaptive_book_age_ms")]
+    pub max_age_ms: DurationConfig<Millis>,
+}
+
+fn default_max_book_age_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_MAX_BOOK_AGE_MS)
+}
+
+fn default_cadence_multiple() -> Decimal {
+    DEFAULT_CADENCE_MULTIPLE
+}
+
+fn default_min_book_age_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_MIN_BOOK_AGE_MS)
+}
+
+fn default_max_adaptive_book_age_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_MAX_ADAPTIVE_BOOK_AGE_MS)
+}
+
+impl Default for FreshnessConfig {
+    fn default() -> Self {
+        Self {
+            mode: FreshnessMode::default(),
+            max_book_age_ms: default_max_book_age_ms(),
+            k: DEFAULT_CADENCE_MULTIPLE,
+            min_age_ms: default_min_book_age_ms(),
+            max_age_ms: default_max_adaptive_book_age_ms(),
+        }
+    }
+}
+
+impl FreshnessConfig {
+    /// Largest book age accepted for a token with `cadence`
+    pub fn max_age(&self, cadence: Option<&CadenceStats>) -> Duration {
+        let adaptive = cadence
+            .filter(|c| self.mode == FreshnessMode::Adaptive && c.samples >= MIN_CADENCE_SAMPLES);
+        match adaptive {
+            Some(cadence) => {
+                let k = self.k.to_f64().unwrap_or_default();
+                let (min, max) = (self.min_age_ms.to_chrono(), self.max_age_ms.to_chrono());
+                Duration::milliseconds((k * cadence.p95_ms as f64).round() as i64)
+                    .clamp(min, min.max(max))
+            }
+            None => self.max_book_age_ms.to_chrono(),
+        }
+    }
+}
+
+/// Update intervals of one token
+#[derive(Debug, Clone, PartialEq)]
+pub struct CadenceStats {
+    /// Intervals seen, up to the window kept
+    pub samples: usize,
+    /// Exponentially weighted mean interval
+    pub ewma_ms: f64,
+    /// 95th percentile of the recent intervals
+    pub p95_ms: i64,
+}
+
+/// Tracks the intervals between one token's updates
+#[derive(Debug, Default)]
+pub(super) struct Cadence {
+    /// Server time of the newest update
+    pub(super) last_at: Option<DateTime<Utc>>,
+    intervals: VecDeque<i64>,
+    ewma_ms: f64,
+}
+
+impl Cadence {
+    /// Note an update at `at`; returns whether an interval was added
+    ///
+    /// Updates older than the newest one seen add no interval.
+    pub(super) fn observe(&mut self, at: DateTime<Utc>) -> bool {
+        let Some(last) = self.last_at else {
+            self.last_at = Some(at);
+            return false;
+        };
+        if at <= last {
+            return false;
+        }
+        self.last_at = Some(at);
+        let interval = (at - last).num_milliseconds();
+        self.ewma_ms = if self.intervals.is_empty() {
+            interval as f64
+        } else {
+            EWMA_ALPHA * interval as f64 + (1.0 - EWMA_ALPHA) * self.ewma_ms
+        };
+        if self.intervals.len() == CADENCE_WINDOW {
+            self.intervals.pop_front();
+        }
+        self.intervals.push_back(interval);
+        true
+    }
+
+    /// EWMA and p95 of the recent intervals, once there are any
+    pub(super) fn stats(&self) -> Option<CadenceStats> {
+        if self.intervals.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<i64> = self.intervals.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = ((sorted.len() as f64 * 0.95).ceil() as usize).saturating_sub(1);
+        Some(CadenceStats {
+            samples: sorted.len(),
+            ewma_ms: self.ewma_ms,
+            p95_ms: sorted[rank],
+        })
+    }
+}
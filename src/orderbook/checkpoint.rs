@@ -0,0 +1,190 @@
+//! Order book checkpoints for a restart
+//!
+//! Polymarket pushes a book only when it changes, so after a restart a
+//! quiet token has no book until its next change. The manager's books are
+//! saved to [`BOOK_CHECKPOINT_FILE`] every `interval_secs` when any has
+//! changed, and at shutdown. On startup a checkpoint at most
+//! `max_age_secs` old is restored with each book flagged restored. A
+//! restored book is the next snapshot's to replace, whatever its time, and
+//! stays unconfirmed until a live snapshot or an update carrying its saved
+//! hash arrives. Until then it serves recording and monitoring, and signals
+//! only with `allow_signals`.
+
+use super::OrderBook;
+use crate::duration::DurationConfig;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// File in the data directory holding the saved books
+pub const BOOK_CHECKPOINT_FILE: &str = "book_checkpoint.json";
+
+/// Schema version of [`BookCheckpoint`]; a checkpoint of any other is ignored
+pub const BOOK_CHECKPOINT_VERSION: u32 = 1;
+
+/// Default seconds between checkpoints of changed books
+pub const DEFAULT_BOOK_CHECKPOINT_INTERVAL_SECS: u64 = 5;
+
+/// Default oldest checkpoint restored at startup, in seconds
+pub const DEFAULT_BOOK_CHECKPOINT_MAX_AGE_SECS: u64 = 120;
+
+/// Saving and restoring order books, under `[signal.book_checkpoint]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookCheckpointConfig {
+    /// Time between checkpoints of changed books; 0 saves at shutdown only
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: DurationConfig,
+    /// Oldest checkpoint restored at startup; 0 always starts with no books
+    #[serde(default = "default_max_age_secs")]
+    pub max_age_secs: DurationConfig,
+    /// Let restored books no live update has confirmed yet feed signals
+    #[serde(default)]
+    pub allow_signals: bool,
+}
+
+fn default_interval_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_BOOK_CHECKPOINT_INTERVAL_SECS)
+}
+
+fn default_max_age_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_BOOK_CHECKPOINT_MAX_AGE_SECS)
+}
+
+impl Default for BookCheckpointConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_interval_secs(),
+            max_age_secs: default_max_age_secs(),
+            allow_signals: false,
+        }
+    }
+}
+
+/// One token's book as saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedBook {
+    /// Both sides, with the server time of the last update
+    pub book: OrderBook,
+    /// Newest server timestamp applied to the book
+    pub applied_at: DateTime<Utc>,
+    /// Tick size the book was quoted in
+    pub tick_size: Decimal,
+    /// Server hash of the book, when the last update carried one
+    pub hash: Option<String>,
+}
+
+/// Every book the manager held at one moment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookCheckpoint {
+    /// Schema version, [`BOOK_CHECKPOINT_VERSION`] when written
+    pub version: u32,
+    /// When the checkpoint was taken
+    pub taken_at: DateTime<Utc>,
+    pub books: Vec<SavedBook>,
+}
+
+/// Only the version is read first, so a checkpoint of another schema is
+/// recognised as such rather than failing to parse
+#[derive(Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+impl BookCheckpoint {
+    /// Write the checkpoint to `path`, replacing any earlier one whole
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// The checkpoint at `path` if it is of this schema version and at
+    /// most `max_age` old at `now`
+    pub fn load(path: &Path, now: DateTime<Utc>, max_age: Duration) -> Option<Self> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                tracing::warn!(path = ?path, error = %e, "Failed to read book checkpoint");
+                return None;
+            }
+        };
+        match serde_json::from_slice::<Versioned>(&bytes) {
+            Ok(v) if v.version == BOOK_CHECKPOINT_VERSION => {}
+            Ok(v) => {
+                tracing::info!(
+                    version = v.version,
+                    expected = BOOK_CHECKPOINT_VERSION,
+                    "Ignoring book checkpoint of another schema version"
+                );
+                return None;
+            }
+            Err(e) => {
+                tracing::warn!(path = ?path, error = %e, "Ignoring unreadable book checkpoint");
+                return None;
+            }
+        }
+        let checkpoint: Self = match serde_json::from_slice(&bytes) {
+            Ok(checkpoint) => checkpoint,
+            Err(e) => {
+                tracing::warn!(path = ?path, error = %e, "Ignoring unreadable book checkpoint");
+                return None;
+            }
+        };
+        let age = now - checkpoint.taken_at;
+        if age > max_age {
+            tracing::info!(
+                age_secs = age.num_seconds(),
+                max_age_secs = max_age.num_seconds(),
+                "Discarding stale book checkpoint"
+            );
+            return None;
+        }
+        Some(checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_stale_or_foreign_checkpoint_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(BOOK_CHECKPOINT_FILE);
+        let taken_at = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+        let max_age = Duration::seconds(120);
+        assert!(BookCheckpoint::load(&path, taken_at, max_age).is_none());
+
+        let mut checkpoint = BookCheckpoint {
+            version: BOOK_CHECKPOINT_VERSION,
+            taken_at,
+            books: vec![SavedBook {
+                book: OrderBook::new("yes").with_bid(dec!(0.48), dec!(10)),
+                applied_at: taken_at,
+                tick_size: dec!(0.01),
+                hash: Some("0xabc".to_string()),
+            }],
+        };
+        checkpoint.save(&path).unwrap();
+        let loaded = BookCheckpoint::load(&path, taken_at + max_age, max_age).unwrap();
+        assert_eq!(loaded.books[0].hash.as_deref(), Some("0xabc"));
+        assert_eq!(loaded.books[0].book.best_bid(), Some(dec!(0.48)));
+        assert!(
+            BookCheckpoint::load(&path, taken_at + max_age + Duration::seconds(1), max_age)
+                .is_none()
+        );
+
+        checkpoint.version = BOOK_CHECKPOINT_VERSION + 1;
+        checkpoint.save(&path).unwrap();
+        assert!(BookCheckpoint::load(&path, taken_at, max_age).is_none());
+        std::fs::write(&path, "not json").unwrap();
+        assert!(BookCheckpoint::load(&path, taken_at, max_age).is_none());
+    }
+}
@@ -1,8 +1,16 @@
 //! Polymarket WebSocket client
 
 use super::OrderBook;
+use crate::bus::TradePrint;
+use crate::telemetry::{instrumented_channel, EventCode};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
 use tokio::sync::mpsc;
 
+/// Polymarket market channel, which streams the order books
+pub const MARKET_WS_URL: &str = "wss://ws-subscriptions-clob.polymarket.com/ws/market";
+
 /// Polymarket WebSocket client for order book updates
 pub struct PolymarketClient {
     // WebSocket connection state
@@ -16,10 +24,10 @@ impl PolymarketClient {
 
     /// Subscribe to order book updates for a token
     pub async fn subscribe(&self, token_id: &str) -> anyhow::Result<mpsc::Receiver<OrderBook>> {
-        let (tx, rx) = mpsc::channel(256);
+        let (tx, rx) = instrumented_channel("book_feed", 256);
 
         // TODO: Implement WebSocket connection to Polymarket
-        tracing::info!("Subscribing to order book for {}", token_id);
+        tracing::info!(event_code = %EventCode::BookSubscribed, token_id, "Subscribing to order book");
 
         let _tx = tx;
         Ok(rx)
@@ -31,3 +39,62 @@ impl Default for PolymarketClient {
         Self::new()
     }
 }
+
+/// A `last_trade_price` event of the market channel
+#[derive(Deserialize)]
+struct RawTradePrint {
+    asset_id: String,
+    price: Decimal,
+    size: Decimal,
+    /// Unix milliseconds, as a string
+    timestamp: String,
+}
+
+/// Trade prints of one market channel frame, an event or an array of
+/// events; other events are skipped
+pub fn parse_trade_prints(text: &str) -> Vec<TradePrint> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(text) else {
+        tracing::debug!(text, "Ignoring non-JSON market channel frame");
+        return vec![];
+    };
+    let values = match value {
+        serde_json::Value::Array(values) => values,
+        value => vec![value],
+    };
+    values
+        .into_iter()
+        .filter(|v| v.get("event_type").and_then(|t| t.as_str()) == Some("last_trade_price"))
+        .filter_map(|v| {
+            let raw: RawTradePrint = serde_json::from_value(v).ok()?;
+            let millis: i64 = raw.timestamp.trim().parse().ok()?;
+            Some(TradePrint {
+                token_id: raw.asset_id,
+                price: raw.price,
+                size: raw.size,
+                timestamp: DateTime::<Utc>::from_timestamp_millis(millis)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_trade_prints() {
+        let frame = r#"[{"asset_id":"yes-token","event_type":"last_trade_price","fee_rate_bps":"0",
+            "market":"0xcond","price":"0.456","side":"BUY","size":"219.217767",
+            "timestamp":"1750428146322"},
+            {"asset_id":"yes-token","event_type":"price_change","market":"0xcond"}]"#;
+        let prints = parse_trade_prints(frame);
+        assert_eq!(prints.len(), 1);
+        assert_eq!(prints[0].token_id, "yes-token");
+        assert_eq!(prints[0].price, dec!(0.456));
+        assert_eq!(prints[0].size, dec!(219.217767));
+        assert_eq!(prints[0].timestamp.timestamp_millis(), 1_750_428_146_322);
+
+        assert!(parse_trade_prints("PONG").is_empty());
+    }
+}
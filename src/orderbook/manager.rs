@@ -0,0 +1,932 @@
+//! Order books kept current from snapshots and incremental updates
+//!
+//! Reconnects can redeliver a message or deliver an old snapshot after a
+//! newer update. Each token's last applied server timestamp and recent
+//! message digests are tracked, so exact duplicates and messages older than
+//! the book are dropped rather than rolling it back.
+//!
+//! Each token's update cadence is tracked too, and [`OrderBookManager::is_fresh`]
+//! is the one staleness check every consumer of a book asks.
+//!
+//! Books restored from a [`BookCheckpoint`] are held unconfirmed until a
+//! live update vouches for them; [`OrderBookManager::may_signal`] says
+//! whether a book may feed signals meanwhile.
+
+use super::cadence::Cadence;
+use super::{
+    BookCheckpoint, CadenceStats, FreshnessConfig, OrderBook, PriceLevel, SavedBook,
+    BOOK_CHECKPOINT_VERSION,
+};
+use crate::market::DEFAULT_TICK_SIZE;
+use crate::telemetry::{record_book_dropped, record_book_freshness, set_book_age_threshold};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// Default slack before a message older than its book is dropped
+pub const DEFAULT_REORDER_TOLERANCE_MS: i64 = 5;
+
+/// Digests of applied messages remembered per token for duplicate checks
+const RECENT_DIGESTS: usize = 32;
+
+/// Whether a book message replaces the book or patches it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookUpdateKind {
+    /// Full book; every level not listed is gone
+    Snapshot,
+    /// Changed levels only; size zero removes a level
+    Delta,
+    /// Full book restated by capture deduplication after the book went
+    /// unchanged for its keep-alive interval; applied as a snapshot
+    KeepAlive,
+}
+
+impl BookUpdateKind {
+    /// Name stored in the `kind` column of captured order books
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BookUpdateKind::Snapshot => "snapshot",
+            BookUpdateKind::Delta => "delta",
+            BookUpdateKind::KeepAlive => "keepalive",
+        }
+    }
+}
+
+impl fmt::Display for BookUpdateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BookUpdateKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "snapshot" => Ok(BookUpdateKind::Snapshot),
+            "delta" => Ok(BookUpdateKind::Delta),
+            "keepalive" => Ok(BookUpdateKind::KeepAlive),
+            other => Err(anyhow::anyhow!("unknown book update kind '{}'", other)),
+        }
+    }
+}
+
+/// One book message as delivered
+#[derive(Debug, Clone, Copy)]
+pub struct BookUpdate<'a> {
+    pub token_id: &'a str,
+    pub kind: BookUpdateKind,
+    pub bids: &'a [PriceLevel],
+    pub asks: &'a [PriceLevel],
+    /// Server timestamp of the message
+    pub timestamp: DateTime<Utc>,
+    /// Server hash of the book, when the feed sends one
+    pub hash: Option<&'a str>,
+}
+
+impl BookUpdate<'_> {
+    /// Identity of the message: the server hash when there is one,
+    /// otherwise its contents
+    fn digest(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.timestamp.hash(&mut hasher);
+        match self.hash {
+            Some(hash) => hash.hash(&mut hasher),
+            None => {
+                self.kind.as_str().hash(&mut hasher);
+                for side in [self.bids, self.asks] {
+                    side.len().hash(&mut hasher);
+                    for level in side {
+                        level.price.hash(&mut hasher);
+                        level.size.hash(&mut hasher);
+                    }
+                }
+            }
+        }
+        hasher.finish()
+    }
+}
+
+/// What became of one book message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeOutcome {
+    /// Merged into the book
+    Applied,
+    /// Dropped: the same message was already applied
+    Duplicate,
+    /// Dropped: older than the book beyond the tolerance
+    OutOfOrder,
+}
+
+impl MergeOutcome {
+    /// Label of a dropped message in metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeOutcome::Applied => "applied",
+            MergeOutcome::Duplicate => "duplicate",
+            MergeOutcome::OutOfOrder => "out_of_order",
+        }
+    }
+}
+
+/// Messages of one token dropped by the ordering rules
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OrderingStats {
+    /// Exact redeliveries of an applied message
+    pub duplicates: u64,
+    /// Messages older than the book they would have patched
+    pub out_of_order: u64,
+}
+
+/// Ordering state of one token
+#[derive(Debug, Default)]
+struct Sequence {
+    /// Newest server timestamp applied
+    applied_at: Option<DateTime<Utc>>,
+    recent: VecDeque<u64>,
+    /// The next snapshot is authoritative, whatever its timestamp
+    resync: bool,
+    /// The book is stale whatever its age until its next update
+    suspect: bool,
+    /// Restored from a checkpoint and not yet confirmed by a live update
+    restored: bool,
+    /// Server hash of the book after the last update applied, if it had one
+    hash: Option<String>,
+    stats: OrderingStats,
+    cadence: Cadence,
+}
+
+/// Current book of every token seen
+#[derive(Debug)]
+pub struct OrderBookManager {
+    books: HashMap<String, OrderBook>,
+    sequences: HashMap<String, Sequence>,
+    tolerance: Duration,
+    freshness: FreshnessConfig,
+    /// Tick size the books are quoted in, saved with each checkpoint
+    tick_size: Decimal,
+    /// Whether restored books may feed signals before a live update
+    restored_signals: bool,
+    /// A book has changed since the last checkpoint
+    changed: bool,
+}
+
+impl Default for OrderBookManager {
+    fn default() -> Self {
+        Self {
+            books: HashMap::new(),
+            sequences: HashMap::new(),
+            tolerance: Duration::milliseconds(DEFAULT_REORDER_TOLERANCE_MS),
+            freshness: FreshnessConfig::default(),
+            tick_size: DEFAULT_TICK_SIZE,
+            restored_signals: false,
+            changed: false,
+        }
+    }
+}
+
+impl OrderBookManager {
+    /// Create a manager with no books
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply messages up to `tolerance` older than the book
+    pub fn with_reorder_tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Judge book staleness by `freshness`
+    pub fn with_freshness(mut self, freshness: FreshnessConfig) -> Self {
+        self.freshness = freshness;
+        self
+    }
+
+    /// Books are quoted in `tick_size` steps; a checkpoint of another
+    /// tick size is not restored
+    pub fn with_tick_size(mut self, tick_size: Decimal) -> Self {
+        self.tick_size = tick_size;
+        self
+    }
+
+    /// Let restored books feed signals before a live update confirms them
+    pub fn with_restored_signals(mut self, allow: bool) -> Self {
+        self.restored_signals = allow;
+        self
+    }
+
+    /// Book of `token_id`, if any update for it has arrived
+    pub fn book(&self, token_id: &str) -> Option<&OrderBook> {
+        self.books.get(token_id)
+    }
+
+    /// Messages of `token_id` dropped so far
+    pub fn ordering(&self, token_id: &str) -> OrderingStats {
+        self.sequences
+            .get(token_id)
+            .map(|s| s.stats)
+            .unwrap_or_default()
+    }
+
+    /// Note an update of `token_id` at server time `at` that was merged
+    /// elsewhere, e.g. a book delivered whole, for its cadence
+    pub fn observe(&mut self, token_id: &str, at: DateTime<Utc>) {
+        let sequence = self.sequences.entry(token_id.to_string()).or_default();
+        if sequence.cadence.last_at.is_none_or(|last| at > last) {
+            sequence.suspect = false;
+        }
+        if sequence.cadence.observe(at) {
+            let max_age = self.freshness.max_age(sequence.cadence.stats().as_ref());
+            set_book_age_threshold(token_id, max_age.num_milliseconds() as f64);
+        }
+    }
+
+    /// Hold `book`, delivered whole, as the book of its token unless it is
+    /// a redelivery or older than the book held
+    pub fn insert(&mut self, book: &OrderBook) -> MergeOutcome {
+        self.apply(&BookUpdate {
+            token_id: &book.token_id,
+            kind: BookUpdateKind::Snapshot,
+            bids: &book.bids,
+            asks: &book.asks,
+            timestamp: book.updated_at,
+            hash: None,
+        })
+    }
+
+    /// Intervals between updates of `token_id`, once it has had two
+    pub fn cadence(&self, token_id: &str) -> Option<CadenceStats> {
+        self.sequences.get(token_id)?.cadence.stats()
+    }
+
+    /// Largest age a book of `token_id` may have to be traded on
+    pub fn max_age(&self, token_id: &str) -> Duration {
+        self.freshness.max_age(self.cadence(token_id).as_ref())
+    }
+
+    /// Whether the newest update of `token_id` is within its max age at
+    /// `now`; a token never updated, or flagged suspect, is not fresh
+    pub fn is_fresh(&self, token_id: &str, now: DateTime<Utc>) -> bool {
+        let fresh = self
+            .sequences
+            .get(token_id)
+            .filter(|s| !s.suspect)
+            .and_then(|s| s.cadence.last_at)
+            .is_some_and(|at| now - at <= self.max_age(token_id));
+        record_book_freshness(fresh);
+        fresh
+    }
+
+    /// Whether the book of `token_id` was restored from a checkpoint and no
+    /// live update has confirmed it yet
+    pub fn is_restored(&self, token_id: &str) -> bool {
+        self.sequences.get(token_id).is_some_and(|s| s.restored)
+    }
+
+    /// Whether the book of `token_id` may feed signals: any book but a
+    /// restored one still unconfirmed, unless those are allowed too
+    pub fn may_signal(&self, token_id: &str) -> bool {
+        self.restored_signals || !self.is_restored(token_id)
+    }
+
+    /// Whether any book has changed since the last checkpoint
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Every book held, taken at `taken_at`
+    pub fn checkpoint(&mut self, taken_at: DateTime<Utc>) -> BookCheckpoint {
+        self.changed = false;
+        let mut books: Vec<SavedBook> = self
+            .books
+            .values()
+            .map(|book| {
+                let sequence = self.sequences.get(&book.token_id);
+                SavedBook {
+                    book: book.clone(),
+                    applied_at: sequence
+                        .and_then(|s| s.applied_at)
+                        .unwrap_or(book.updated_at),
+                    tick_size: self.tick_size,
+                    hash: sequence.and_then(|s| s.hash.clone()),
+                }
+            })
+            .collect();
+        books.sort_by(|a, b| a.book.token_id.cmp(&b.book.token_id));
+        BookCheckpoint {
+            version: BOOK_CHECKPOINT_VERSION,
+            taken_at,
+            books,
+        }
+    }
+
+    /// Hold the books of `checkpoint` as restored, except those of tokens
+    /// already updated live or quoted in another tick size; returns how
+    /// many were restored
+    pub fn restore(&mut self, checkpoint: &BookCheckpoint) -> usize {
+        let mut restored = 0;
+        for saved in &checkpoint.books {
+            let token_id = &saved.book.token_id;
+            if saved.tick_size != self.tick_size || self.sequences.contains_key(token_id) {
+                continue;
+            }
+            self.books.insert(token_id.clone(), saved.book.clone());
+            self.sequences.insert(
+                token_id.clone(),
+                Sequence {
+                    applied_at: Some(saved.applied_at),
+                    resync: true,
+                    restored: true,
+                    hash: saved.hash.clone(),
+                    ..Default::default()
+                },
+            );
+            self.observe(token_id, saved.applied_at);
+            restored += 1;
+        }
+        restored
+    }
+
+    /// Treat the book of `token_id` as stale until its next update, e.g.
+    /// once the other book of its market has repriced and it has not
+    pub fn mark_suspect(&mut self, token_id: &str) {
+        if let Some(sequence) = self.sequences.get_mut(token_id) {
+            sequence.suspect = true;
+        }
+    }
+
+    /// Accept the next snapshot of `token_id` as authoritative, even if it
+    /// is older than the book; the resync path calls this before asking
+    /// for a fresh snapshot
+    pub fn resync(&mut self, token_id: &str) {
+        self.sequences
+            .entry(token_id.to_string())
+            .or_default()
+            .resync = true;
+    }
+
+    /// Drop the book and update history of `token_id`, once its market
+    /// has closed
+    pub fn forget(&mut self, token_id: &str) {
+        self.changed |= self.books.remove(token_id).is_some();
+        self.sequences.remove(token_id);
+    }
+
+    /// Tokens with a book or update history held
+    pub fn tracked(&self) -> usize {
+        self.sequences
+            .keys()
+            .filter(|token| !self.books.contains_key(*token))
+            .count()
+            + self.books.len()
+    }
+
+    /// Price levels across the books held
+    pub fn levels(&self) -> usize {
+        self.books
+            .values()
+            .map(|b| b.bids.len() + b.asks.len())
+            .sum()
+    }
+
+    /// What [`Self::apply`] would do with `update`, without applying it
+    pub fn classify(&self, update: &BookUpdate<'_>) -> MergeOutcome {
+        let Some(sequence) = self.sequences.get(update.token_id) else {
+            return MergeOutcome::Applied;
+        };
+        if sequence.recent.contains(&update.digest()) {
+            return MergeOutcome::Duplicate;
+        }
+        if sequence.resync && update.kind == BookUpdateKind::Snapshot {
+            return MergeOutcome::Applied;
+        }
+        match sequence.applied_at {
+            Some(at) if update.timestamp + self.tolerance < at => MergeOutcome::OutOfOrder,
+            _ => MergeOutcome::Applied,
+        }
+    }
+
+    /// Apply one book message unless it is a duplicate or out of order
+    ///
+    /// A snapshot or keep-alive replaces both sides. A delta sets the size
+    /// of each listed level, inserting it in price order, and drops levels
+    /// set to zero.
+    pub fn apply(&mut self, update: &BookUpdate<'_>) -> MergeOutcome {
+        let outcome = self.classify(update);
+        let sequence = self
+            .sequences
+            .entry(update.token_id.to_string())
+            .or_default();
+        match outcome {
+            MergeOutcome::Applied => {}
+            MergeOutcome::Duplicate => {
+                sequence.stats.duplicates += 1;
+                record_book_dropped(update.token_id, outcome.as_str());
+                return outcome;
+            }
+            MergeOutcome::OutOfOrder => {
+                sequence.stats.out_of_order += 1;
+                record_book_dropped(update.token_id, outcome.as_str());
+                tracing::debug!(
+                    token_id = update.token_id,
+                    kind = %update.kind,
+                    timestamp = %update.timestamp,
+                    applied_at = ?sequence.applied_at,
+                    "Dropped book message older than the book"
+                );
+                return outcome;
+            }
+        }
+        if update.kind == BookUpdateKind::Snapshot {
+            sequence.resync = false;
+        }
+        if sequence.restored {
+            let confirmed = update.kind == BookUpdateKind::Snapshot
+                || (update.hash.is_some() && update.hash == sequence.hash.as_deref());
+            if confirmed {
+                sequence.restored = false;
+                tracing::debug!(
+                    token_id = update.token_id,
+                    "Live update confirmed restored book"
+                );
+            } else if update.hash.is_some() {
+                tracing::debug!(
+                    token_id = update.token_id,
+                    "Live update contradicts restored book, unconfirmed until the next snapshot"
+                );
+            }
+        }
+        sequence.hash = update.hash.map(str::to_string);
+        if sequence.recent.len() == RECENT_DIGESTS {
+            sequence.recent.pop_front();
+        }
+        sequence.recent.push_back(update.digest());
+        sequence.applied_at = sequence.applied_at.max(Some(update.timestamp));
+        self.observe(update.token_id, update.timestamp);
+
+        let book = self
+            .books
+            .entry(update.token_id.to_string())
+            .or_insert_with(|| OrderBook::new(update.token_id));
+        let (bids, asks) = (update.bids, update.asks);
+        match update.kind {
+            BookUpdateKind::Snapshot | BookUpdateKind::KeepAlive => {
+                book.bids = bids.iter().filter(|l| !l.size.is_zero()).cloned().collect();
+                book.asks = asks.iter().filter(|l| !l.size.is_zero()).cloned().collect();
+                book.bids.sort_by_key(|l| std::cmp::Reverse(l.price));
+                book.asks.sort_by_key(|l| l.price);
+            }
+            BookUpdateKind::Delta => {
+                for level in bids {
+                    upsert(&mut book.bids, level, |a, b| b.cmp(&a));
+                }
+                for level in asks {
+                    upsert(&mut book.asks, level, |a, b| a.cmp(&b));
+                }
+            }
+        }
+        book.updated_at = update.timestamp;
+        self.changed = true;
+        outcome
+    }
+
+    /// Apply one book message without a server hash and return the book,
+    /// unchanged if the message was dropped
+    pub fn merge_update(
+        &mut self,
+        token_id: &str,
+        kind: BookUpdateKind,
+        bids: &[PriceLevel],
+        asks: &[PriceLevel],
+        timestamp: DateTime<Utc>,
+    ) -> &OrderBook {
+        self.apply(&BookUpdate {
+            token_id,
+            kind,
+            bids,
+            asks,
+            timestamp,
+            hash: None,
+        });
+        &self.books[token_id]
+    }
+}
+
+/// Set one level of a side kept sorted by `order`
+fn upsert(
+    side: &mut Vec<PriceLevel>,
+    level: &PriceLevel,
+    order: impl Fn(Decimal, Decimal) -> std::cmp::Ordering,
+) {
+    match side.binary_search_by(|l| order(l.price, level.price)) {
+        Ok(i) if level.size.is_zero() => {
+            side.remove(i);
+        }
+        Ok(i) => side[i].size = level.size,
+        Err(_) if level.size.is_zero() => {}
+        Err(i) => side.insert(i, level.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::FreshnessMode;
+    use rust_decimal_macros::dec;
+
+    fn level(price: Decimal, size: Decimal) -> PriceLevel {
+        PriceLevel { price, size }
+    }
+
+    fn prices(levels: &[PriceLevel]) -> Vec<Decimal> {
+        levels.iter().map(|l| l.price).collect()
+    }
+
+    #[test]
+    fn test_deltas_patch_the_snapshot() {
+        let mut manager = OrderBookManager::new();
+        let now = Utc::now();
+        manager.merge_update(
+            "yes",
+            BookUpdateKind::Snapshot,
+            &[level(dec!(0.48), dec!(10)), level(dec!(0.50), dec!(20))],
+            &[level(dec!(0.53), dec!(5)), level(dec!(0.52), dec!(15))],
+            now,
+        );
+        let book = manager.book("yes").unwrap();
+        assert_eq!(prices(&book.bids), vec![dec!(0.50), dec!(0.48)]);
+        assert_eq!(prices(&book.asks), vec![dec!(0.52), dec!(0.53)]);
+
+        let book = manager.merge_update(
+            "yes",
+            BookUpdateKind::Delta,
+            &[level(dec!(0.49), dec!(7)), level(dec!(0.50), dec!(0))],
+            &[level(dec!(0.52), dec!(9)), level(dec!(0.51), dec!(0))],
+            now,
+        );
+        assert_eq!(prices(&book.bids), vec![dec!(0.49), dec!(0.48)]);
+        assert_eq!(book.asks[0].size, dec!(9));
+        assert_eq!(book.asks.len(), 2);
+
+        // A snapshot discards whatever the deltas built
+        let book = manager.merge_update(
+            "yes",
+            BookUpdateKind::Snapshot,
+            &[level(dec!(0.45), dec!(1))],
+            &[],
+            now,
+        );
+        assert_eq!(prices(&book.bids), vec![dec!(0.45)]);
+        assert!(book.asks.is_empty());
+        assert!(manager.book("no").is_none());
+    }
+
+    fn update<'a>(
+        kind: BookUpdateKind,
+        levels: &'a (Vec<PriceLevel>, Vec<PriceLevel>),
+        timestamp: DateTime<Utc>,
+    ) -> BookUpdate<'a> {
+        BookUpdate {
+            token_id: "yes",
+            kind,
+            bids: &levels.0,
+            asks: &levels.1,
+            timestamp,
+            hash: None,
+        }
+    }
+
+    /// Both sides as (price, size) pairs
+    fn sides(book: &OrderBook) -> [Vec<(Decimal, Decimal)>; 2] {
+        [&book.bids, &book.asks].map(|levels| levels.iter().map(|l| (l.price, l.size)).collect())
+    }
+
+    #[test]
+    fn test_duplicate_and_stale_messages_are_dropped() {
+        use BookUpdateKind::{Delta, Snapshot};
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let old = (vec![level(dec!(0.40), dec!(10))], vec![]);
+        let new = (vec![level(dec!(0.50), dec!(10))], vec![]);
+        let patch = (vec![level(dec!(0.49), dec!(5))], vec![]);
+        let mut manager = OrderBookManager::new();
+
+        let snapshot = update(Snapshot, &new, t0 + Duration::seconds(1));
+        assert_eq!(manager.apply(&snapshot), MergeOutcome::Applied);
+        assert_eq!(manager.apply(&snapshot), MergeOutcome::Duplicate);
+        let delta = update(Delta, &patch, t0 + Duration::seconds(2));
+        assert_eq!(manager.apply(&delta), MergeOutcome::Applied);
+        // Same contents at a new time is a fresh message
+        let again = update(Delta, &patch, t0 + Duration::seconds(3));
+        assert_eq!(manager.classify(&again), MergeOutcome::Applied);
+
+        // An older snapshot after a newer delta would roll the book back
+        let stale = update(Snapshot, &old, t0);
+        assert_eq!(manager.apply(&stale), MergeOutcome::OutOfOrder);
+        assert_eq!(
+            prices(&manager.book("yes").unwrap().bids),
+            vec![dec!(0.50), dec!(0.49)]
+        );
+        // ...unless within the tolerance
+        let jitter = update(Delta, &old, t0 + Duration::milliseconds(1_997));
+        assert_eq!(manager.classify(&jitter), MergeOutcome::Applied);
+        assert_eq!(
+            manager.ordering("yes"),
+            OrderingStats {
+                duplicates: 1,
+                out_of_order: 1
+            }
+        );
+
+        // After a resync the next snapshot is taken whatever its time
+        manager.resync("yes");
+        assert_eq!(manager.classify(&delta), MergeOutcome::Duplicate);
+        assert_eq!(manager.apply(&stale), MergeOutcome::Applied);
+        assert_eq!(prices(&manager.book("yes").unwrap().bids), vec![dec!(0.40)]);
+        let older = update(Snapshot, &new, t0 - Duration::seconds(1));
+        assert_eq!(manager.classify(&older), MergeOutcome::OutOfOrder);
+
+        // A server hash identifies the message instead of its contents
+        let hashed = BookUpdate {
+            hash: Some("0xabc"),
+            ..update(Delta, &patch, t0 + Duration::seconds(4))
+        };
+        assert_eq!(manager.apply(&hashed), MergeOutcome::Applied);
+        let same_hash = BookUpdate {
+            hash: Some("0xabc"),
+            ..update(Delta, &new, t0 + Duration::seconds(4))
+        };
+        assert_eq!(manager.classify(&same_hash), MergeOutcome::Duplicate);
+    }
+
+    #[test]
+    fn test_shuffled_and_duplicated_sequences_converge() {
+        use rand::{Rng, SeedableRng};
+        use rand_chacha::ChaCha8Rng;
+        use BookUpdateKind::{Delta, Snapshot};
+
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut messages = vec![];
+        for i in 0..60i64 {
+            let kind = if i % 10 == 0 { Snapshot } else { Delta };
+            let mut side = |n: usize| -> Vec<PriceLevel> {
+                (0..n)
+                    .map(|_| {
+                        level(
+                            Decimal::new(rng.gen_range(40..60), 2),
+                            Decimal::from(rng.gen_range(0..4) * 10),
+                        )
+                    })
+                    .collect()
+            };
+            let levels = (side(3), side(2));
+            messages.push((kind, levels, t0 + Duration::seconds(i)));
+        }
+        let replay = |order: &[usize]| {
+            let mut manager = OrderBookManager::new();
+            for &i in order {
+                let (kind, levels, ts) = &messages[i];
+                manager.apply(&update(*kind, levels, *ts));
+            }
+            sides(manager.book("yes").unwrap())
+        };
+        let in_order: Vec<usize> = (0..messages.len()).collect();
+        let expected = replay(&in_order);
+
+        for _ in 0..50 {
+            // Every message arrives, some more than once and some late again
+            let mut order = vec![];
+            for i in 0..messages.len() {
+                order.push(i);
+                if rng.gen_bool(0.3) {
+                    order.push(rng.gen_range(0..=i));
+                }
+            }
+            assert_eq!(replay(&order), expected);
+        }
+
+        // Snapshots alone converge on the newest in any order
+        let snapshots: Vec<usize> = (0..messages.len()).step_by(10).collect();
+        let newest = replay(&snapshots);
+        for _ in 0..50 {
+            let mut order = snapshots.clone();
+            for i in (1..order.len()).rev() {
+                order.swap(i, rng.gen_range(0..=i));
+            }
+            order.extend(order.clone());
+            assert_eq!(replay(&order), newest);
+        }
+    }
+
+    #[test]
+    fn test_kind_round_trips_through_its_name() {
+        for kind in [
+            BookUpdateKind::Snapshot,
+            BookUpdateKind::Delta,
+            BookUpdateKind::KeepAlive,
+        ] {
+            assert_eq!(kind.as_str().parse::<BookUpdateKind>().unwrap(), kind);
+        }
+        assert!("full".parse::<BookUpdateKind>().is_err());
+    }
+
+    /// Updates of `token_id` every `every_ms` from `start`, returning the last time
+    fn steady(
+        manager: &mut OrderBookManager,
+        start: DateTime<Utc>,
+        every_ms: i64,
+        count: usize,
+    ) -> DateTime<Utc> {
+        let mut at = start;
+        for _ in 0..count {
+            at += Duration::milliseconds(every_ms);
+            manager.observe("yes", at);
+        }
+        at
+    }
+
+    #[test]
+    fn test_max_age_follows_the_update_cadence() {
+        let start = Utc::now();
+        let mut slow = OrderBookManager::new();
+        assert!(!slow.is_fresh("yes", start));
+        // Too few intervals for the cadence to count yet
+        let last = steady(&mut slow, start, 1_500, 5);
+        assert_eq!(slow.max_age("yes"), Duration::milliseconds(2_000));
+        let last = steady(&mut slow, last, 1_500, 20);
+        assert_eq!(slow.cadence("yes").unwrap().p95_ms, 1_500);
+        assert_eq!(slow.max_age("yes"), Duration::milliseconds(4_500));
+        assert!(slow.is_fresh("yes", last + Duration::milliseconds(4_000)));
+        assert!(!slow.is_fresh("yes", last + Duration::milliseconds(5_000)));
+
+        let mut fast = OrderBookManager::new();
+        let last = steady(&mut fast, start, 250, 50);
+        assert_eq!(fast.max_age("yes"), Duration::milliseconds(750));
+        assert!(!fast.is_fresh("yes", last + Duration::milliseconds(1_000)));
+
+        // A stale replayed update neither moves the clock nor adds an interval
+        fast.observe("yes", start);
+        assert_eq!(fast.cadence("yes").unwrap().samples, 49);
+        assert!(fast.is_fresh("yes", last + Duration::milliseconds(500)));
+    }
+
+    #[test]
+    fn test_insert_keeps_the_newest_whole_book() {
+        let mut manager = OrderBookManager::new();
+        let t0 = Utc::now();
+        let book = |bid: Decimal, at: DateTime<Utc>| OrderBook {
+            token_id: "yes".to_string(),
+            bids: vec![level(bid, dec!(100))],
+            asks: vec![level(dec!(0.60), dec!(100))],
+            updated_at: at,
+        };
+        let newer = book(dec!(0.55), t0 + Duration::seconds(1));
+        assert_eq!(manager.insert(&newer), MergeOutcome::Applied);
+        assert_eq!(manager.insert(&newer), MergeOutcome::Duplicate);
+        // Delivered late, after the book it precedes
+        assert_eq!(
+            manager.insert(&book(dec!(0.50), t0)),
+            MergeOutcome::OutOfOrder
+        );
+        let held = manager.book("yes").unwrap();
+        assert_eq!(held.best_bid(), Some(dec!(0.55)));
+        assert_eq!(held.updated_at, newer.updated_at);
+    }
+
+    #[test]
+    fn test_suspect_book_is_stale_until_its_next_update() {
+        let mut manager = OrderBookManager::new();
+        let last = steady(&mut manager, Utc::now(), 200, 10);
+        assert!(manager.is_fresh("yes", last));
+
+        manager.mark_suspect("yes");
+        assert!(!manager.is_fresh("yes", last));
+        // A replay of an update already seen vouches for nothing
+        manager.observe("yes", last);
+        assert!(!manager.is_fresh("yes", last));
+
+        manager.observe("yes", last + Duration::milliseconds(200));
+        assert!(manager.is_fresh("yes", last + Duration::milliseconds(200)));
+        // Unknown tokens are simply not fresh
+        manager.mark_suspect("no");
+        assert!(!manager.is_fresh("no", last));
+    }
+
+    #[test]
+    fn test_max_age_tracks_a_cadence_shift() {
+        let mut manager = OrderBookManager::new();
+        let last = steady(&mut manager, Utc::now(), 200, 64);
+        assert_eq!(manager.max_age("yes"), Duration::milliseconds(600));
+
+        // The book goes quiet; the p95 picks it up once a few slow intervals are in
+        let last = steady(&mut manager, last, 2_000, 2);
+        assert_eq!(manager.max_age("yes"), Duration::milliseconds(600));
+        let last = steady(&mut manager, last, 2_000, 2);
+        assert_eq!(manager.max_age("yes"), Duration::milliseconds(6_000));
+        assert!(manager.is_fresh("yes", last + Duration::milliseconds(5_000)));
+        assert!(manager.cadence("yes").unwrap().ewma_ms > 200.0);
+
+        // And back to fast once the slow intervals leave the window
+        steady(&mut manager, last, 200, 64);
+        assert_eq!(manager.max_age("yes"), Duration::milliseconds(600));
+    }
+
+    #[test]
+    fn test_max_age_is_clamped_or_fixed() {
+        let start = Utc::now();
+        let mut fast = OrderBookManager::new();
+        steady(&mut fast, start, 50, 20);
+        assert_eq!(fast.max_age("yes"), Duration::milliseconds(500));
+
+        let mut slow = OrderBookManager::new();
+        steady(&mut slow, start, 5_000, 20);
+        assert_eq!(slow.max_age("yes"), Duration::milliseconds(10_000));
+
+        let mut fixed = OrderBookManager::new().with_freshness(FreshnessConfig {
+            mode: FreshnessMode::Fixed,
+            ..Default::default()
+        });
+        steady(&mut fixed, start, 5_000, 20);
+        assert_eq!(fixed.max_age("yes"), Duration::milliseconds(2_000));
+    }
+
+    #[test]
+    fn test_restored_books_wait_for_a_live_update() {
+        use crate::orderbook::BOOK_CHECKPOINT_FILE;
+        use BookUpdateKind::{Delta, Snapshot};
+
+        let t0 = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+        let levels = (
+            vec![level(dec!(0.48), dec!(10))],
+            vec![level(dec!(0.52), dec!(20))],
+        );
+        let mut before = OrderBookManager::new();
+        assert!(!before.changed());
+        before.apply(&BookUpdate {
+            hash: Some("0xabc"),
+            ..update(Snapshot, &levels, t0)
+        });
+        assert!(before.changed());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(BOOK_CHECKPOINT_FILE);
+        before.checkpoint(t0).save(&path).unwrap();
+        assert!(!before.changed());
+
+        // Restarted a few seconds later
+        let now = t0 + Duration::seconds(3);
+        let checkpoint = BookCheckpoint::load(&path, now, Duration::seconds(120)).unwrap();
+        let mut after = OrderBookManager::new();
+        assert_eq!(after.restore(&checkpoint), 1);
+        assert_eq!(
+            sides(after.book("yes").unwrap()),
+            sides(before.book("yes").unwrap())
+        );
+        assert!(after.is_restored("yes"));
+        assert!(!after.may_signal("yes"));
+        assert!(after.is_fresh("yes", t0 + Duration::milliseconds(500)));
+        assert!(!after.changed());
+        // Unless allowed, and not into another tick size
+        let mut allowed = OrderBookManager::new().with_restored_signals(true);
+        allowed.restore(&checkpoint);
+        assert!(allowed.is_restored("yes") && allowed.may_signal("yes"));
+        let mut other = OrderBookManager::new().with_tick_size(dec!(0.001));
+        assert_eq!(other.restore(&checkpoint), 0);
+
+        // A live delta carrying another hash leaves the book unconfirmed
+        let patch = (vec![level(dec!(0.49), dec!(5))], vec![]);
+        let contradicting = BookUpdate {
+            hash: Some("0xdef"),
+            ..update(Delta, &patch, t0 + Duration::seconds(4))
+        };
+        assert_eq!(after.apply(&contradicting), MergeOutcome::Applied);
+        assert!(after.is_restored("yes") && !after.may_signal("yes"));
+        // ...until a live snapshot replaces it, even one older than the book
+        let live = (vec![level(dec!(0.45), dec!(30))], vec![]);
+        let snapshot = update(Snapshot, &live, t0 - Duration::seconds(1));
+        assert_eq!(after.apply(&snapshot), MergeOutcome::Applied);
+        assert!(!after.is_restored("yes") && after.may_signal("yes"));
+        assert_eq!(prices(&after.book("yes").unwrap().bids), vec![dec!(0.45)]);
+
+        // A live update carrying the saved hash confirms the book as it is
+        let mut confirmed = OrderBookManager::new();
+        confirmed.restore(&checkpoint);
+        let unchanged = (vec![], vec![]);
+        let confirming = BookUpdate {
+            hash: Some("0xabc"),
+            ..update(Delta, &unchanged, t0 + Duration::seconds(5))
+        };
+        assert_eq!(confirmed.apply(&confirming), MergeOutcome::Applied);
+        assert!(!confirmed.is_restored("yes"));
+        assert_eq!(
+            sides(confirmed.book("yes").unwrap()),
+            sides(before.book("yes").unwrap())
+        );
+    }
+}
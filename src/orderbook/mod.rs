@@ -3,14 +3,50 @@
 //! Real-time order book from Polymarket WebSocket
 
 mod book;
+mod cadence;
+mod checkpoint;
 mod client;
+mod manager;
 
-pub use book::OrderBook;
-pub use client::PolymarketClient;
+pub use book::{MarketBooks, OrderBook};
+pub use cadence::{
+    CadenceStats, FreshnessConfig, FreshnessMode, DEFAULT_CADENCE_MULTIPLE,
+    DEFAULT_MAX_ADAPTIVE_BOOK_AGE_MS, DEFAULT_MAX_BOOK_AGE_MS, DEFAULT_MIN_BOOK_AGE_MS,
+};
+pub use checkpoint::{
+    BookCheckpoint, BookCheckpointConfig, SavedBook, BOOK_CHECKPOINT_FILE, BOOK_CHECKPOINT_VERSION,
+    DEFAULT_BOOK_CHECKPOINT_INTERVAL_SECS, DEFAULT_BOOK_CHECKPOINT_MAX_AGE_SECS,
+};
+pub use client::{parse_trade_prints, PolymarketClient, MARKET_WS_URL};
+pub use manager::{
+    BookUpdate, BookUpdateKind, MergeOutcome, OrderBookManager, OrderingStats,
+    DEFAULT_REORDER_TOLERANCE_MS,
+};
 
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
+/// One side of an order book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookSide {
+    /// Resting buy orders
+    Bid,
+    /// Resting sell orders
+    Ask,
+}
+
+/// Cumulative size near the touch of one side of a book
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthProfile {
+    /// Size at prices within 1 cent of the best level
+    pub within_1c: Decimal,
+    /// Size at prices within 2 cents of the best level
+    pub within_2c: Decimal,
+    /// Size at prices within 3 cents of the best level
+    pub within_3c: Decimal,
+}
+
 /// A price level in the order book
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PriceLevel {
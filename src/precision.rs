@@ -0,0 +1,91 @@
+//! Decimal precision policy
+//!
+//! Canonical scales for each kind of quantity. Records (signals, fills,
+//! positions) are rounded when they are constructed, and values are rounded
+//! again when they are stored or displayed. Intermediate math keeps full
+//! precision. All rounding is banker's rounding, so repeated rounding to the
+//! same scale does not drift.
+
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// Decimal places for prices and probabilities quoted as prices
+pub const PRICE_DP: u32 = 4;
+
+/// Decimal places for share sizes
+pub const SIZE_DP: u32 = 2;
+
+/// Decimal places for percentages, edges and fair probabilities
+pub const PCT_DP: u32 = 6;
+
+/// Decimal places for cash amounts (fees, notional, P&L)
+pub const USD_DP: u32 = 4;
+
+fn round(value: Decimal, dp: u32) -> Decimal {
+    value.round_dp_with_strategy(dp, RoundingStrategy::MidpointNearestEven)
+}
+
+/// Round a price to [`PRICE_DP`]
+pub fn round_price(value: Decimal) -> Decimal {
+    round(value, PRICE_DP)
+}
+
+/// Round a share size to [`SIZE_DP`]
+pub fn round_size(value: Decimal) -> Decimal {
+    round(value, SIZE_DP)
+}
+
+/// Round a percentage or edge to [`PCT_DP`]
+pub fn round_pct(value: Decimal) -> Decimal {
+    round(value, PCT_DP)
+}
+
+/// Round a cash amount to [`USD_DP`]
+pub fn round_usd(value: Decimal) -> Decimal {
+    round(value, USD_DP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rust_decimal_macros::dec;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_bankers_rounding() {
+        assert_eq!(round_size(dec!(0.125)), dec!(0.12));
+        assert_eq!(round_size(dec!(0.135)), dec!(0.14));
+        assert_eq!(round_price(dec!(0.113000000000000000001)), dec!(0.113));
+        assert_eq!(round_pct(dec!(-0.0000005)), dec!(0));
+    }
+
+    fn any_decimal() -> impl Strategy<Value = Decimal> {
+        (any::<i64>(), 0u32..=18).prop_map(|(m, s)| Decimal::new(m, s))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_rounding_is_idempotent(value in any_decimal()) {
+            for round in [round_price, round_size, round_pct, round_usd] {
+                prop_assert_eq!(round(round(value)), round(value));
+            }
+        }
+
+        #[test]
+        fn prop_storage_round_trip_within_half_ulp(value in any_decimal()) {
+            for (round, dp) in [
+                (round_price as fn(Decimal) -> Decimal, PRICE_DP),
+                (round_size, SIZE_DP),
+                (round_pct, PCT_DP),
+                (round_usd, USD_DP),
+            ] {
+                // Stored as a string, read back, rounded again on load
+                let stored = round(value).to_string();
+                let loaded = round(Decimal::from_str(&stored).unwrap());
+                let half_ulp = Decimal::new(5, dp + 1);
+                prop_assert!((loaded - value).abs() <= half_ulp);
+                prop_assert_eq!(loaded, round(value));
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Stable types for library consumers
+//!
+//! `use poly_hft::prelude::*;` brings in the market data, signal and order
+//! types, the traits to implement or drive (feeds, market trackers,
+//! execution, risk and sizing), and the config sections. Everything here
+//! keeps its name and path across releases; the per-module paths it is
+//! re-exported from may move. Config sections and signal types are
+//! `#[non_exhaustive]`: read them from TOML and their fields, and match
+//! their enums with a wildcard arm. `tests/golden/public_api.txt` records
+//! this surface, and a test fails when it changes.
+
+pub use crate::config::{
+    Config, DataConfig, ExecutionConfig, ExecutionMode, FeedConfig, LogFileConfig, MarketConfig,
+    ModelConfig, RiskConfig, SignalConfig, TelemetryConfig,
+};
+pub use crate::execution::{ExecutionEngine, Fill, Order, OrderAction, OrderId, OrderType};
+pub use crate::feed::{PriceFeed, PriceTick, TickSource};
+pub use crate::market::{Market, MarketTracker};
+pub use crate::orderbook::{BookSide, OrderBook, PriceLevel};
+pub use crate::risk::{
+    HaltReason, KellyCalculator, PositionSizer, PositionTracker, RiskError, RiskManager,
+};
+pub use crate::signal::{NoLagReason, Side, Signal, SignalReason};
+
+/// The attribute the async traits are declared with, for implementing them
+pub use async_trait::async_trait;
+
+#[cfg(test)]
+mod tests {
+    const LIB: &str = include_str!("lib.rs");
+    const PRELUDE: &str = include_str!("prelude.rs");
+
+    /// Top-level modules, hidden ones marked, then every prelude name with
+    /// the path it comes from
+    fn surface() -> String {
+        let mut out = String::from("# Modules\n");
+        let mut hidden = false;
+        for line in LIB.lines().map(str::trim) {
+            if line == "#[doc(hidden)]" {
+                hidden = true;
+            } else if let Some(name) = line
+                .strip_prefix("pub mod ")
+                .and_then(|l| l.strip_suffix(';'))
+            {
+                let marker = if hidden { " (hidden)" } else { "" };
+                out.push_str(&format!("{}{}\n", name, marker));
+                hidden = false;
+            }
+        }
+
+        let mut items = vec![];
+        let body = &PRELUDE[..PRELUDE.find("#[cfg(test)]").unwrap()];
+        for statement in body.split(';') {
+            let Some(start) = statement.find("pub use ") else {
+                continue;
+            };
+            let path: String = statement[start + 8..]
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect();
+            match path.split_once("::{") {
+                Some((module, names)) => {
+                    for name in names.trim_end_matches('}').split(',') {
+                        if !name.is_empty() {
+                            items.push(format!("{} = {}::{}", name, module, name));
+                        }
+                    }
+                }
+                None => {
+                    let name = path.rsplit("::").next().unwrap();
+                    items.push(format!("{} = {}", name, path));
+                }
+            }
+        }
+        items.sort();
+        out.push_str("\n# Prelude\n");
+        for item in items {
+            out.push_str(&item);
+            out.push('\n');
+        }
+        out
+    }
+
+    #[test]
+    fn test_public_api_matches_snapshot() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/public_api.txt");
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::write(path, surface()).unwrap();
+        }
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap_or_default(),
+            surface(),
+            "the public API changed; if intended, rerun this test with BLESS=1"
+        );
+    }
+}
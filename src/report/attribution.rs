@@ -0,0 +1,549 @@
+//! P&L attribution of settled positions
+//!
+//! A winning session can come from capturing the lag or from holding the
+//! side that happened to win. Each settled position's P&L is split at the
+//! last book mark before settlement:
+//!
+//! - expected: the lag at entry (fair value less book price) times size,
+//!   what the signal promised
+//! - convergence: the move from the entry price to the last mark while
+//!   held, what the book paid
+//! - timing: convergence less expected, how far the book fell short of or
+//!   overshot the promise
+//! - resolution: the final jump from the last mark to the 0 or 1 payout,
+//!   the luck of the settlement
+//!
+//! so that realized = convergence + resolution - fees. Marks are the YES
+//! mids the [`SignalOutcomeTracker`](crate::signal::SignalOutcomeTracker)
+//! samples, priced for the side held. A position with no mark while held
+//! has no convergence; its whole move is resolution.
+//!
+//! Per-position rows are written as Parquet; decimals are text, times are
+//! UTC microseconds, and `mark_times`/`mark_prices` hold the mark series.
+
+use crate::backtest::TapeRow;
+use crate::data::{decimal_column, str_column, timestamp_column, writer_properties};
+use crate::fingerprint;
+use crate::precision::round_usd;
+use crate::signal::{Mark, Side};
+use arrow::array::{ArrayRef, ListBuilder, StringBuilder, TimestampMicrosecondBuilder};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Utc};
+use parquet::arrow::ArrowWriter;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Attribution written to the data directory at shutdown
+pub const PNL_ATTRIBUTION_FILE: &str = "pnl_attribution.parquet";
+
+/// Where one settled position's P&L came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PositionAttribution {
+    /// Position identifier
+    pub position_id: String,
+    /// Signal that opened it
+    pub signal_id: String,
+    /// Market condition ID
+    pub market_id: String,
+    /// Strategy that traded
+    pub strategy: String,
+    /// Side bought
+    pub side: Side,
+    /// Fill time
+    pub entry_time: DateTime<Utc>,
+    /// Settlement time
+    pub exit_time: DateTime<Utc>,
+    /// Shares
+    pub size: Decimal,
+    /// Fill price
+    pub entry_price: Decimal,
+    /// Model price of the side at entry
+    pub expected_price: Decimal,
+    /// Last mark of the side before settlement, if any while held
+    pub last_mark: Option<Decimal>,
+    /// Settlement payout per share
+    pub exit_price: Decimal,
+    /// Lag at entry times size
+    pub expected_pnl: Decimal,
+    /// Entry price to last mark, times size
+    pub convergence_pnl: Decimal,
+    /// Last mark to payout, times size
+    pub resolution_pnl: Decimal,
+    /// Fees paid
+    pub fees: Decimal,
+    /// P&L after fees
+    pub realized_pnl: Decimal,
+    /// Price of the side held, sampled while held
+    pub marks: Vec<Mark>,
+}
+
+impl PositionAttribution {
+    /// Attribute the closed trade `row`, entered at `held_from`, against
+    /// its market's YES mid `marks`
+    ///
+    /// `held_from` is the decision time on the clock of the marks, which
+    /// a paper fill's timestamp need not be. `None` for a trade with no
+    /// signal behind it, e.g. a recovered order.
+    pub fn new(row: &TapeRow, held_from: DateTime<Utc>, marks: &[Mark]) -> Option<Self> {
+        let entry = row.entry.as_ref()?;
+        let marks: Vec<Mark> = marks
+            .iter()
+            .filter(|(at, _)| *at >= held_from && *at <= row.exit_time)
+            .map(|&(at, mid)| match row.side {
+                Side::Yes => (at, mid),
+                Side::No => (at, Decimal::ONE - mid),
+            })
+            .collect();
+        let last_mark = marks.last().map(|&(_, price)| price);
+        let convergence_pnl =
+            round_usd((last_mark.unwrap_or(row.entry_price) - row.entry_price) * row.size);
+        Some(Self {
+            position_id: row.position_id.clone(),
+            signal_id: entry.signal_id.clone(),
+            market_id: row.market_id.clone(),
+            strategy: row.strategy.clone(),
+            side: row.side,
+            entry_time: row.entry_time,
+            exit_time: row.exit_time,
+            size: row.size,
+            entry_price: row.entry_price,
+            expected_price: entry.fair_value,
+            last_mark,
+            exit_price: row.exit_price,
+            expected_pnl: round_usd(entry.lag * row.size),
+            convergence_pnl,
+            // The rest of the realized P&L, so the parts always add up
+            resolution_pnl: row.realized_pnl + row.fees - convergence_pnl,
+            fees: row.fees,
+            realized_pnl: row.realized_pnl,
+            marks,
+        })
+    }
+
+    /// Convergence less expected P&L
+    pub fn timing_pnl(&self) -> Decimal {
+        self.convergence_pnl - self.expected_pnl
+    }
+
+    /// Take the payout and P&L of `row` after its settlement was
+    /// corrected; the difference is all resolution
+    pub fn resettle(&mut self, row: &TapeRow) {
+        self.exit_price = row.exit_price;
+        self.realized_pnl = row.realized_pnl;
+        self.resolution_pnl = row.realized_pnl + row.fees - self.convergence_pnl;
+    }
+}
+
+/// Attribution of a group of positions
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttributionBucket {
+    /// Group name: a strategy, or `all`
+    pub key: String,
+    /// Positions attributed
+    pub positions: u64,
+    /// Lag at entry times size
+    pub expected: Decimal,
+    /// Entry to last mark
+    pub convergence: Decimal,
+    /// Last mark to payout
+    pub resolution: Decimal,
+    /// Fees paid
+    pub fees: Decimal,
+    /// P&L after fees
+    pub realized: Decimal,
+}
+
+impl AttributionBucket {
+    /// Empty bucket named `key`
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Count `position` in the bucket
+    pub fn add(&mut self, position: &PositionAttribution) {
+        self.positions += 1;
+        self.expected += position.expected_pnl;
+        self.convergence += position.convergence_pnl;
+        self.resolution += position.resolution_pnl;
+        self.fees += position.fees;
+        self.realized += position.realized_pnl;
+    }
+
+    /// Take `position` back out of the bucket
+    pub fn remove(&mut self, position: &PositionAttribution) {
+        self.positions -= 1;
+        self.expected -= position.expected_pnl;
+        self.convergence -= position.convergence_pnl;
+        self.resolution -= position.resolution_pnl;
+        self.fees -= position.fees;
+        self.realized -= position.realized_pnl;
+    }
+
+    /// Convergence less expected P&L
+    pub fn timing(&self) -> Decimal {
+        self.convergence - self.expected
+    }
+
+    /// Share of the P&L explained by convergence rather than resolution
+    ///
+    /// `|convergence| / (|convergence| + |resolution|)`; `None` when both
+    /// are zero.
+    pub fn convergence_share(&self) -> Option<Decimal> {
+        let moved = self.convergence.abs() + self.resolution.abs();
+        (!moved.is_zero()).then(|| self.convergence.abs() / moved)
+    }
+}
+
+impl fmt::Display for AttributionBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {:+.2}, convergence {:+.2}, resolution {:+.2}, fees {:.2}",
+            self.expected, self.convergence, self.resolution, self.fees
+        )?;
+        if let Some(share) = self.convergence_share() {
+            write!(
+                f,
+                " ({:.0}% convergence, {:.0}% resolution)",
+                share * Decimal::ONE_HUNDRED,
+                (Decimal::ONE - share) * Decimal::ONE_HUNDRED
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Attribution of a session's settled positions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AttributionReport {
+    /// Every position attributed, in settlement order
+    pub positions: Vec<PositionAttribution>,
+    /// All positions
+    pub total: AttributionBucket,
+    /// By strategy
+    pub by_strategy: Vec<AttributionBucket>,
+}
+
+impl AttributionReport {
+    /// Report over `positions`
+    pub fn new(positions: Vec<PositionAttribution>) -> Self {
+        let mut total = AttributionBucket::new("all");
+        let mut by_strategy: BTreeMap<&str, AttributionBucket> = BTreeMap::new();
+        for position in &positions {
+            total.add(position);
+            by_strategy
+                .entry(&position.strategy)
+                .or_insert_with(|| AttributionBucket::new(&position.strategy))
+                .add(position);
+        }
+        let by_strategy = by_strategy.into_values().collect();
+        Self {
+            positions,
+            total,
+            by_strategy,
+        }
+    }
+
+    /// Write the per-position rows to `path` as Parquet
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let batch = attribution_batch(&self.positions)?;
+        let props = writer_properties(fingerprint::active());
+        let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(props))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for AttributionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "P&L attribution: {} positions settled",
+            self.positions.len()
+        )?;
+        writeln!(
+            f,
+            "  {:<12} {:>9} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>12}",
+            "strategy",
+            "positions",
+            "expected",
+            "timing",
+            "converge",
+            "resolve",
+            "fees",
+            "realized",
+            "convergence"
+        )?;
+        for b in std::iter::once(&self.total).chain(self.by_strategy.iter()) {
+            writeln!(
+                f,
+                "  {:<12} {:>9} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>12}",
+                b.key,
+                b.positions,
+                b.expected.round_dp(2),
+                b.timing().round_dp(2),
+                b.convergence.round_dp(2),
+                b.resolution.round_dp(2),
+                b.fees.round_dp(2),
+                b.realized.round_dp(2),
+                b.convergence_share().map_or("n/a".to_string(), |s| format!(
+                    "{:.1}%",
+                    s * Decimal::ONE_HUNDRED
+                )),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Attribution Parquet schema
+pub fn attribution_schema() -> Schema {
+    let text = |name: &str, nullable: bool| Field::new(name, DataType::Utf8, nullable);
+    let time = || DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    Schema::new(vec![
+        text("position_id", false),
+        text("signal_id", false),
+        text("market_id", false),
+        text("strategy", false),
+        text("side", false),
+        Field::new("entry_time", time(), false),
+        Field::new("exit_time", time(), false),
+        text("size", false),
+        text("entry_price", false),
+        text("expected_price", false),
+        text("last_mark", true),
+        text("exit_price", false),
+        text("expected_pnl", false),
+        text("convergence_pnl", false),
+        text("timing_pnl", false),
+        text("resolution_pnl", false),
+        text("fees", false),
+        text("realized_pnl", false),
+        Field::new(
+            "mark_times",
+            DataType::List(Arc::new(Field::new_list_field(time(), true))),
+            false,
+        ),
+        Field::new(
+            "mark_prices",
+            DataType::List(Arc::new(Field::new_list_field(DataType::Utf8, true))),
+            false,
+        ),
+    ])
+}
+
+/// Attributed positions as a batch in [`attribution_schema`]
+pub fn attribution_batch(rows: &[PositionAttribution]) -> anyhow::Result<RecordBatch> {
+    let side = |r: &PositionAttribution| match r.side {
+        Side::Yes => "yes",
+        Side::No => "no",
+    };
+    let mut times = ListBuilder::new(TimestampMicrosecondBuilder::new().with_timezone("UTC"));
+    let mut prices = ListBuilder::new(StringBuilder::new());
+    for row in rows {
+        for (at, price) in &row.marks {
+            times.values().append_value(at.timestamp_micros());
+            prices.values().append_value(price.to_string());
+        }
+        times.append(true);
+        prices.append(true);
+    }
+    let columns: Vec<ArrayRef> = vec![
+        str_column(rows, |r| &r.position_id),
+        str_column(rows, |r| &r.signal_id),
+        str_column(rows, |r| &r.market_id),
+        str_column(rows, |r| &r.strategy),
+        str_column(rows, side),
+        timestamp_column(rows, |r| r.entry_time),
+        timestamp_column(rows, |r| r.exit_time),
+        decimal_column(rows, |r| Some(r.size)),
+        decimal_column(rows, |r| Some(r.entry_price)),
+        decimal_column(rows, |r| Some(r.expected_price)),
+        decimal_column(rows, |r| r.last_mark),
+        decimal_column(rows, |r| Some(r.exit_price)),
+        decimal_column(rows, |r| Some(r.expected_pnl)),
+        decimal_column(rows, |r| Some(r.convergence_pnl)),
+        decimal_column(rows, |r| Some(r.timing_pnl())),
+        decimal_column(rows, |r| Some(r.resolution_pnl)),
+        decimal_column(rows, |r| Some(r.fees)),
+        decimal_column(rows, |r| Some(r.realized_pnl)),
+        Arc::new(times.finish()),
+        Arc::new(prices.finish()),
+    ];
+    Ok(RecordBatch::try_new(
+        Arc::new(attribution_schema()),
+        columns,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::EntryFeatures;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_735_689_600, 0).unwrap()
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        t0() + Duration::seconds(secs)
+    }
+
+    /// 100 shares of `side` bought at `entry` on a lag of `lag`, paying `exit`
+    fn row(strategy: &str, side: Side, entry: Decimal, lag: Decimal, exit: Decimal) -> TapeRow {
+        TapeRow {
+            position_id: format!("{}-{:?}", strategy, side),
+            market_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            strategy: strategy.to_string(),
+            side,
+            entry_time: at(0),
+            exit_time: at(900),
+            size: dec!(100),
+            entry_price: entry,
+            exit_price: exit,
+            fees: dec!(0.50),
+            realized_pnl: (exit - entry) * dec!(100) - dec!(0.50),
+            expected_value_usd: Some(lag * dec!(100) - dec!(0.50)),
+            entry: Some(EntryFeatures {
+                signal_id: "signal".to_string(),
+                reason: "SpotDivergence".to_string(),
+                fair_value: entry + lag,
+                market_price: entry,
+                lag,
+                edge: lag,
+                momentum_move: None,
+                retrace: None,
+                confidence: dec!(0.8),
+                secs_to_close: 900,
+                rejections: vec![],
+                flags: Default::default(),
+            }),
+        }
+    }
+
+    fn marks(path: &[(i64, Decimal)]) -> Vec<Mark> {
+        path.iter().map(|&(secs, mid)| (at(secs), mid)).collect()
+    }
+
+    #[test]
+    fn test_converged_position_attributes_to_convergence() {
+        // Bought YES at 0.50 with fair 0.60; the book converged to 0.60,
+        // then the market settled YES
+        let row = row("lag", Side::Yes, dec!(0.50), dec!(0.10), Decimal::ONE);
+        let path = marks(&[
+            (-5, dec!(0.40)),
+            (0, dec!(0.50)),
+            (300, dec!(0.56)),
+            (600, dec!(0.60)),
+            (905, dec!(0.99)),
+        ]);
+        let a = PositionAttribution::new(&row, at(0), &path).unwrap();
+
+        assert_eq!(a.marks.len(), 3);
+        assert_eq!(a.last_mark, Some(dec!(0.60)));
+        assert_eq!(a.expected_pnl, dec!(10));
+        assert_eq!(a.convergence_pnl, dec!(10));
+        assert_eq!(a.timing_pnl(), dec!(0));
+        assert_eq!(a.resolution_pnl, dec!(40));
+        assert_eq!(
+            a.convergence_pnl + a.resolution_pnl - a.fees,
+            a.realized_pnl
+        );
+    }
+
+    #[test]
+    fn test_no_position_uses_no_prices_and_lucky_resolution() {
+        // Bought NO at 0.40 with fair 0.45; YES rose, so NO marked down
+        // to 0.30, yet NO won the settlement
+        let row = row("lag", Side::No, dec!(0.40), dec!(0.05), Decimal::ONE);
+        let path = marks(&[(0, dec!(0.60)), (400, dec!(0.65)), (800, dec!(0.70))]);
+        let a = PositionAttribution::new(&row, at(0), &path).unwrap();
+
+        assert_eq!(a.marks[2], (at(800), dec!(0.30)));
+        assert_eq!(a.expected_pnl, dec!(5));
+        assert_eq!(a.convergence_pnl, dec!(-10));
+        assert_eq!(a.timing_pnl(), dec!(-15));
+        assert_eq!(a.resolution_pnl, dec!(70));
+        assert_eq!(a.realized_pnl, dec!(59.50));
+    }
+
+    #[test]
+    fn test_unmarked_and_recovered_positions() {
+        // No mark while held: the whole move is resolution
+        let row = row("lag", Side::Yes, dec!(0.50), dec!(0.10), Decimal::ZERO);
+        let a = PositionAttribution::new(&row, at(0), &marks(&[(-10, dec!(0.45))])).unwrap();
+        assert_eq!(a.last_mark, None);
+        assert_eq!(a.convergence_pnl, dec!(0));
+        assert_eq!(a.resolution_pnl, dec!(-50));
+
+        let mut recovered = row.clone();
+        recovered.entry = None;
+        assert!(PositionAttribution::new(&recovered, at(0), &[]).is_none());
+    }
+
+    #[test]
+    fn test_report_splits_by_strategy_and_round_trips() {
+        let converged = PositionAttribution::new(
+            &row("lag", Side::Yes, dec!(0.50), dec!(0.10), Decimal::ONE),
+            at(0),
+            &marks(&[(600, dec!(0.60))]),
+        )
+        .unwrap();
+        let lucky = PositionAttribution::new(
+            &row("momentum", Side::Yes, dec!(0.50), dec!(0.10), Decimal::ONE),
+            at(0),
+            &marks(&[(600, dec!(0.50))]),
+        )
+        .unwrap();
+        let report = AttributionReport::new(vec![lucky, converged]);
+
+        assert_eq!(report.total.positions, 2);
+        assert_eq!(report.total.convergence, dec!(10));
+        assert_eq!(report.total.resolution, dec!(90));
+        assert_eq!(report.total.convergence_share(), Some(dec!(0.1)));
+        let keys: Vec<&str> = report.by_strategy.iter().map(|b| b.key.as_str()).collect();
+        assert_eq!(keys, ["lag", "momentum"]);
+        assert_eq!(report.by_strategy[0].convergence_share(), Some(dec!(0.2)));
+        assert_eq!(report.by_strategy[1].convergence_share(), Some(dec!(0)));
+        assert!(report
+            .total
+            .to_string()
+            .contains("(10% convergence, 90% resolution)"));
+        assert!(report.to_string().contains("momentum"));
+
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PNL_ATTRIBUTION_FILE);
+        report.write(&path).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.map(|b| b.unwrap()).next().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.schema().as_ref(), &attribution_schema());
+        let timing = batch
+            .column_by_name("timing_pnl")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow::array::StringArray>()
+            .unwrap();
+        assert_eq!(timing.value(0), "-10.00");
+    }
+}
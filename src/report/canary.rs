@@ -0,0 +1,400 @@
+//! Go/no-go report of a canary session
+//!
+//! A canary trades paper through the live configuration path for a fixed
+//! time before live execution is switched on. When it ends, the session is
+//! judged against the `[canary]` acceptance criteria; `run --canary` writes
+//! the report and exits non-zero on a no-go, so a deployment script can gate
+//! `mode = "live"` on its exit status.
+
+use super::PnlReconciliation;
+use crate::duration::{DurationConfig, Millis};
+use crate::engine::TradingEngine;
+use crate::execution::ExecutionEngine;
+use crate::feed::LagHistogram;
+use crate::fingerprint;
+use crate::telemetry::{HealthRegistry, HealthState};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Report file written to the data directory at the end of a canary
+pub const CANARY_REPORT_FILE: &str = "canary_report.json";
+
+/// Default signals a canary must produce
+pub const DEFAULT_MIN_SIGNALS: u64 = 10;
+
+/// Default share of settled positions that must win
+pub const DEFAULT_MIN_WIN_RATE: Decimal = dec!(0.5);
+
+/// Default deepest drawdown from peak equity allowed
+pub const DEFAULT_MAX_DRAWDOWN: Decimal = dec!(0.10);
+
+/// Default highest session p95 tick lag allowed
+pub const DEFAULT_MAX_TICK_LAG_P95_MS: u64 = 500;
+
+/// Acceptance criteria of a canary, under `[canary]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryConfig {
+    /// Signals the session must produce
+    #[serde(default = "default_min_signals")]
+    pub min_signals: u64,
+    /// Share of settled positions that must win, 0 to 1
+    #[serde(default = "default_min_win_rate")]
+    pub min_win_rate: Decimal,
+    /// Deepest drawdown from peak equity allowed, 0 to 1
+    #[serde(default = "default_max_drawdown")]
+    pub max_drawdown: Decimal,
+    /// Highest p95 tick lag over the whole session
+    #[serde(default = "default_max_tick_lag_p95_ms")]
+    pub max_tick_lag_p95_ms: DurationConfig<Millis>,
+    /// Orders still without an outcome when the session ends
+    #[serde(default)]
+    pub max_unreconciled_orders: u64,
+}
+
+fn default_min_signals() -> u64 {
+    DEFAULT_MIN_SIGNALS
+}
+
+fn default_min_win_rate() -> Decimal {
+    DEFAULT_MIN_WIN_RATE
+}
+
+fn default_max_drawdown() -> Decimal {
+    DEFAULT_MAX_DRAWDOWN
+}
+
+fn default_max_tick_lag_p95_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_MAX_TICK_LAG_P95_MS)
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            min_signals: DEFAULT_MIN_SIGNALS,
+            min_win_rate: DEFAULT_MIN_WIN_RATE,
+            max_drawdown: DEFAULT_MAX_DRAWDOWN,
+            max_tick_lag_p95_ms: default_max_tick_lag_p95_ms(),
+            max_unreconciled_orders: 0,
+        }
+    }
+}
+
+/// What a canary session measured
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CanaryMetrics {
+    /// Whether the session ran its full duration
+    pub completed: bool,
+    /// Signals from the detector
+    pub signals: u64,
+    /// Orders submitted
+    pub orders: u64,
+    /// Positions closed or settled
+    pub settled: u64,
+    /// Settled positions with a positive P&L
+    pub wins: u64,
+    /// P&L of settled positions
+    pub realized_pnl: Decimal,
+    /// Deepest drawdown from peak equity, 0 to 1
+    pub max_drawdown: Decimal,
+    /// p95 tick lag over the session; `None` if no tick arrived
+    pub tick_lag_p95_ms: Option<i64>,
+    /// Orders without an outcome at the end
+    pub unreconciled_orders: u64,
+}
+
+impl CanaryMetrics {
+    /// Measurements of `engine`'s session, whose ticks lagged as `lag`
+    pub fn from_engine<E: ExecutionEngine>(
+        engine: &TradingEngine<E>,
+        lag: &LagHistogram,
+        completed: bool,
+    ) -> anyhow::Result<Self> {
+        let stats = engine.stats();
+        let positions = engine.positions();
+        Ok(Self {
+            completed,
+            signals: stats.signals,
+            orders: stats.orders,
+            settled: positions.closed_count(),
+            wins: positions.wins(),
+            realized_pnl: stats.realized_pnl,
+            max_drawdown: engine.drawdown().max_drawdown,
+            tick_lag_p95_ms: lag.percentile(0.95),
+            unreconciled_orders: engine.unreconciled_orders()? as u64,
+        })
+    }
+
+    /// Share of settled positions that won, if any settled
+    pub fn win_rate(&self) -> Option<Decimal> {
+        (self.settled > 0).then(|| Decimal::from(self.wins) / Decimal::from(self.settled))
+    }
+}
+
+/// One acceptance criterion and how the session measured up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CriterionResult {
+    /// Criterion name, e.g. `min_signals`
+    pub name: String,
+    /// Required value, as displayed
+    pub required: String,
+    /// Measured value, as displayed
+    pub actual: String,
+    /// Whether the session met it
+    pub passed: bool,
+}
+
+impl CriterionResult {
+    fn new(name: &str, required: String, actual: String, passed: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            required,
+            actual,
+            passed,
+        }
+    }
+}
+
+/// Go/no-go verdict of a canary session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryReport {
+    /// Config hash of the session, if stamped
+    pub config_hash: Option<String>,
+    /// When the canary started
+    pub started: DateTime<Utc>,
+    /// When it ended
+    pub finished: DateTime<Utc>,
+    /// What it measured
+    pub metrics: CanaryMetrics,
+    /// Every criterion, in config order
+    pub criteria: Vec<CriterionResult>,
+    /// Components not healthy at the end, for the operator; not a criterion
+    #[serde(default)]
+    pub unhealthy: Vec<String>,
+    /// Whether every criterion passed
+    pub passed: bool,
+}
+
+impl CanaryReport {
+    /// Judge `metrics` against `config`
+    ///
+    /// A session that stopped early fails regardless of its numbers. One
+    /// with no settled positions has a win rate of 0, and one with no ticks
+    /// has no lag to fail on.
+    pub fn evaluate(
+        config: &CanaryConfig,
+        metrics: CanaryMetrics,
+        started: DateTime<Utc>,
+        finished: DateTime<Utc>,
+    ) -> Self {
+        let percent = |d: Decimal| format!("{:.1}%", d * dec!(100));
+        let win_rate = metrics.win_rate();
+        let criteria = vec![
+            CriterionResult::new(
+                "completed",
+                "full duration".to_string(),
+                if metrics.completed {
+                    "full duration"
+                } else {
+                    "stopped early"
+                }
+                .to_string(),
+                metrics.completed,
+            ),
+            CriterionResult::new(
+                "min_signals",
+                format!(">= {}", config.min_signals),
+                metrics.signals.to_string(),
+                metrics.signals >= config.min_signals,
+            ),
+            CriterionResult::new(
+                "min_win_rate",
+                format!(">= {}", percent(config.min_win_rate)),
+                match win_rate {
+                    Some(rate) => format!("{} of {}", percent(rate), metrics.settled),
+                    None => "n/a (nothing settled)".to_string(),
+                },
+                win_rate.unwrap_or(Decimal::ZERO) >= config.min_win_rate,
+            ),
+            CriterionResult::new(
+                "max_drawdown",
+                format!("<= {}", percent(config.max_drawdown)),
+                percent(metrics.max_drawdown),
+                metrics.max_drawdown <= config.max_drawdown,
+            ),
+            CriterionResult::new(
+                "max_tick_lag_p95_ms",
+                format!("<= {}ms", config.max_tick_lag_p95_ms.as_millis()),
+                match metrics.tick_lag_p95_ms {
+                    Some(p95) => format!("{}ms", p95),
+                    None => "n/a (no ticks)".to_string(),
+                },
+                metrics
+                    .tick_lag_p95_ms
+                    .is_none_or(|p95| p95 <= config.max_tick_lag_p95_ms.as_millis() as i64),
+            ),
+            CriterionResult::new(
+                "max_unreconciled_orders",
+                format!("<= {}", config.max_unreconciled_orders),
+                metrics.unreconciled_orders.to_string(),
+                metrics.unreconciled_orders <= config.max_unreconciled_orders,
+            ),
+        ];
+        Self {
+            config_hash: fingerprint::active().map(|f| f.hash.clone()),
+            started,
+            finished,
+            passed: criteria.iter().all(|c| c.passed),
+            metrics,
+            criteria,
+            unhealthy: vec![],
+        }
+    }
+
+    /// Note the components `health` reports as not healthy
+    pub fn with_health(mut self, health: &HealthRegistry) -> Self {
+        self.unhealthy = health
+            .snapshot()
+            .into_iter()
+            .filter(|c| c.state != HealthState::Healthy)
+            .map(|c| match c.reason {
+                Some(reason) => format!("{} ({:?}: {})", c.component, c.state, reason),
+                None => format!("{} ({:?})", c.component, c.state),
+            })
+            .collect();
+        self
+    }
+
+    /// Add the session's P&L reconciliation as a criterion
+    pub fn with_reconciliation(mut self, reconciliation: &PnlReconciliation) -> Self {
+        self.criteria.push(CriterionResult::new(
+            "pnl_reconciled",
+            format!("within {}", reconciliation.tolerance),
+            match reconciliation.discrepancies.len() {
+                0 => "reconciled".to_string(),
+                n => format!("{} discrepancies", n),
+            },
+            reconciliation.passed,
+        ));
+        self.passed = self.passed && reconciliation.passed;
+        self
+    }
+
+    /// Criteria the session missed
+    pub fn failures(&self) -> impl Iterator<Item = &CriterionResult> {
+        self.criteria.iter().filter(|c| !c.passed)
+    }
+
+    /// Write the report as JSON
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for CanaryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Canary {} ({} to {}):",
+            if self.passed { "GO" } else { "NO-GO" },
+            self.started.format("%Y-%m-%d %H:%M:%S"),
+            self.finished.format("%Y-%m-%d %H:%M:%S")
+        )?;
+        for c in &self.criteria {
+            writeln!(
+                f,
+                "  [{}] {}: {} (required {})",
+                if c.passed { "pass" } else { "FAIL" },
+                c.name,
+                c.actual,
+                c.required
+            )?;
+        }
+        for component in &self.unhealthy {
+            writeln!(f, "  Unhealthy at end: {}", component)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passing() -> CanaryMetrics {
+        CanaryMetrics {
+            completed: true,
+            signals: 12,
+            orders: 8,
+            settled: 8,
+            wins: 5,
+            realized_pnl: dec!(4.2),
+            max_drawdown: dec!(0.03),
+            tick_lag_p95_ms: Some(120),
+            unreconciled_orders: 0,
+        }
+    }
+
+    fn evaluate(metrics: CanaryMetrics) -> CanaryReport {
+        let now = Utc::now();
+        CanaryReport::evaluate(&CanaryConfig::default(), metrics, now, now)
+    }
+
+    fn failed(report: &CanaryReport) -> Vec<&str> {
+        report.failures().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_every_criterion_met_is_a_go() {
+        let report = evaluate(passing());
+        assert!(report.passed, "{}", report);
+        assert_eq!(report.criteria.len(), 6);
+        assert!(report.to_string().starts_with("Canary GO"));
+        let win_rate = &report.criteria[2];
+        assert_eq!(win_rate.actual, "62.5% of 8");
+        assert_eq!(win_rate.required, ">= 50.0%");
+    }
+
+    #[test]
+    fn test_any_missed_criterion_is_a_no_go() {
+        let mut metrics = passing();
+        metrics.max_drawdown = dec!(0.15);
+        metrics.unreconciled_orders = 1;
+        let report = evaluate(metrics);
+        assert!(!report.passed);
+        assert_eq!(failed(&report), ["max_drawdown", "max_unreconciled_orders"]);
+        assert!(report.to_string().contains("[FAIL] max_drawdown: 15.0%"));
+
+        // Stopping early fails even with good numbers
+        let mut metrics = passing();
+        metrics.completed = false;
+        assert_eq!(failed(&evaluate(metrics)), ["completed"]);
+
+        // Nothing settled is no evidence of a win rate
+        let mut metrics = passing();
+        metrics.settled = 0;
+        metrics.wins = 0;
+        metrics.tick_lag_p95_ms = None;
+        assert_eq!(failed(&evaluate(metrics)), ["min_win_rate"]);
+    }
+
+    #[test]
+    fn test_report_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CANARY_REPORT_FILE);
+        let health = HealthRegistry::new();
+        health.set("feed", HealthState::Degraded, Some("lagging".to_string()));
+        let report = evaluate(passing()).with_health(&health);
+        report.write(&path).unwrap();
+
+        let read: CanaryReport =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(read.passed);
+        assert_eq!(read.metrics, report.metrics);
+        assert_eq!(read.unhealthy, ["feed (Degraded: lagging)"]);
+    }
+}
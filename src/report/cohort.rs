@@ -0,0 +1,286 @@
+//! Trades split by feature flag cohort
+//!
+//! A flag rolling out to a percentage of signals splits the trades behind
+//! them into a treated and an untreated cohort, assigned by hash rather
+//! than by time, so the two compare directly: same markets, same hours,
+//! one difference. Each flag that was rolling out for any trade gets its
+//! cohorts' P&L and win rates side by side. Flags only ever on or off
+//! have nothing to compare and are left out, as are trades with no signal
+//! behind them or whose signal did not evaluate the flag.
+
+use crate::backtest::TapeRow;
+use crate::precision::round_pct;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Report written to the data directory at shutdown, when a flag was
+/// rolling out
+pub const FLAG_COHORT_FILE: &str = "flag_cohorts.json";
+
+/// P&L of the trades of one cohort
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cohort {
+    pub trades: u64,
+    /// Trades that made money
+    pub wins: u64,
+    /// P&L after fees
+    pub pnl: Decimal,
+}
+
+impl Cohort {
+    fn add(&mut self, pnl: Decimal) {
+        self.trades += 1;
+        if pnl > Decimal::ZERO {
+            self.wins += 1;
+        }
+        self.pnl += pnl;
+    }
+
+    /// Share of trades that made money; `None` without trades
+    pub fn win_rate(&self) -> Option<Decimal> {
+        (self.trades > 0).then(|| round_pct(Decimal::from(self.wins) / Decimal::from(self.trades)))
+    }
+
+    /// P&L per trade; `None` without trades
+    pub fn avg_pnl(&self) -> Option<Decimal> {
+        (self.trades > 0).then(|| self.pnl / Decimal::from(self.trades))
+    }
+}
+
+/// The treated and untreated cohorts of one flag
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagCohorts {
+    pub flag: String,
+    /// Percentages the flag rolled out at over the trades, lowest first
+    pub rollouts: Vec<Decimal>,
+    /// Trades whose signal had the flag on
+    pub treated: Cohort,
+    /// Trades whose signal had it off
+    pub untreated: Cohort,
+}
+
+/// Cohort comparison of every flag rolling out over a set of trades
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CohortReport {
+    /// By flag name
+    pub flags: Vec<FlagCohorts>,
+}
+
+impl CohortReport {
+    /// Report over the closed trades `rows`
+    pub fn new(rows: &[TapeRow]) -> Self {
+        let mut flags: BTreeMap<&str, FlagCohorts> = BTreeMap::new();
+        let rolled_out = rows
+            .iter()
+            .filter_map(|r| r.entry.as_ref())
+            .flat_map(|e| &e.flags)
+            .filter(|(_, d)| d.rollout.is_some())
+            .map(|(name, _)| name.as_str());
+        for name in rolled_out {
+            flags.entry(name).or_insert_with(|| FlagCohorts {
+                flag: name.to_string(),
+                ..Default::default()
+            });
+        }
+        for row in rows {
+            let Some(entry) = &row.entry else {
+                continue;
+            };
+            for (name, decision) in &entry.flags {
+                let Some(cohorts) = flags.get_mut(name.as_str()) else {
+                    continue;
+                };
+                let rollout = decision.rollout.unwrap_or(match decision.on {
+                    true => Decimal::ONE_HUNDRED,
+                    false => Decimal::ZERO,
+                });
+                if !cohorts.rollouts.contains(&rollout) {
+                    cohorts.rollouts.push(rollout);
+                }
+                match decision.on {
+                    true => cohorts.treated.add(row.realized_pnl),
+                    false => cohorts.untreated.add(row.realized_pnl),
+                }
+            }
+        }
+        let mut flags: Vec<FlagCohorts> = flags.into_values().collect();
+        for cohorts in &mut flags {
+            cohorts.rollouts.sort();
+        }
+        Self { flags }
+    }
+
+    /// Whether no flag was rolling out
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+
+    /// Write the report to `path` as JSON
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for CohortReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Flag cohorts: {} flag(s) rolling out", self.flags.len())?;
+        writeln!(
+            f,
+            "  {:<24} {:<10} {:>6} {:>10} {:>9} {:>8}",
+            "flag", "cohort", "trades", "pnl", "avg pnl", "win rate"
+        )?;
+        let pct = |d: Option<Decimal>| {
+            d.map_or("n/a".to_string(), |d| {
+                format!("{}%", (d * Decimal::ONE_HUNDRED).round_dp(1))
+            })
+        };
+        let usd = |d: Option<Decimal>| d.map_or("n/a".to_string(), |d| d.round_dp(2).to_string());
+        for cohorts in &self.flags {
+            let rollouts: Vec<String> =
+                cohorts.rollouts.iter().map(|r| format!("{}%", r)).collect();
+            let label = format!("{} ({})", cohorts.flag, rollouts.join(", "));
+            for (name, cohort) in [
+                ("treated", &cohorts.treated),
+                ("untreated", &cohorts.untreated),
+            ] {
+                writeln!(
+                    f,
+                    "  {:<24} {:<10} {:>6} {:>10} {:>9} {:>8}",
+                    label,
+                    name,
+                    cohort.trades,
+                    cohort.pnl.round_dp(2),
+                    usd(cohort.avg_pnl()),
+                    pct(cohort.win_rate()),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::EntryFeatures;
+    use crate::flags::{FlagDecision, EXIT_LADDER};
+    use crate::signal::Side;
+    use chrono::{DateTime, Duration, Utc};
+    use rust_decimal_macros::dec;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_767_571_200, 0).unwrap() + Duration::seconds(secs)
+    }
+
+    /// A trade realizing `pnl` whose signal evaluated `flags`
+    fn trade(secs: i64, pnl: Decimal, flags: &[(&str, bool, Option<Decimal>)]) -> TapeRow {
+        TapeRow {
+            position_id: format!("p{}", secs),
+            market_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            strategy: "lag".to_string(),
+            side: Side::Yes,
+            entry_time: at(secs),
+            exit_time: at(secs + 600),
+            size: dec!(10),
+            entry_price: dec!(0.50),
+            exit_price: Decimal::ONE,
+            fees: Decimal::ZERO,
+            realized_pnl: pnl,
+            expected_value_usd: Some(dec!(1)),
+            entry: Some(EntryFeatures {
+                signal_id: format!("s{}", secs),
+                reason: "SpotDivergence".to_string(),
+                fair_value: dec!(0.55),
+                market_price: dec!(0.50),
+                lag: dec!(0.05),
+                edge: dec!(0.04),
+                momentum_move: None,
+                retrace: None,
+                confidence: dec!(0.8),
+                secs_to_close: 600,
+                rejections: vec![],
+                flags: flags
+                    .iter()
+                    .map(|(name, on, rollout)| {
+                        let decision = FlagDecision {
+                            on: *on,
+                            rollout: *rollout,
+                        };
+                        (name.to_string(), decision)
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_trades_split_by_cohort_of_flags_rolling_out() {
+        let quarter = Some(dec!(25));
+        let rows = vec![
+            trade(
+                0,
+                dec!(5),
+                &[(EXIT_LADDER, true, quarter), ("gbm", true, None)],
+            ),
+            trade(
+                1,
+                dec!(-2),
+                &[(EXIT_LADDER, true, quarter), ("gbm", true, None)],
+            ),
+            trade(
+                2,
+                dec!(4),
+                &[(EXIT_LADDER, false, quarter), ("gbm", true, None)],
+            ),
+            trade(3, dec!(-3), &[(EXIT_LADDER, false, quarter)]),
+            trade(4, dec!(-1), &[(EXIT_LADDER, false, quarter)]),
+            // Switched fully on later by ctl
+            trade(5, dec!(6), &[(EXIT_LADDER, true, None)]),
+            // Before the flag existed
+            trade(6, dec!(9), &[]),
+            TapeRow {
+                entry: None,
+                ..trade(7, dec!(9), &[])
+            },
+        ];
+        let report = CohortReport::new(&rows);
+
+        // gbm was only ever on, so has no cohorts to compare
+        assert_eq!(report.flags.len(), 1);
+        let ladder = &report.flags[0];
+        assert_eq!(ladder.flag, EXIT_LADDER);
+        assert_eq!(ladder.rollouts, [dec!(25), dec!(100)]);
+        assert_eq!(
+            ladder.treated,
+            Cohort {
+                trades: 3,
+                wins: 2,
+                pnl: dec!(9)
+            }
+        );
+        assert_eq!(
+            ladder.untreated,
+            Cohort {
+                trades: 3,
+                wins: 1,
+                pnl: dec!(0)
+            }
+        );
+        assert_eq!(ladder.treated.win_rate(), Some(dec!(0.666667)));
+        assert_eq!(ladder.untreated.avg_pnl(), Some(dec!(0)));
+        let rendered = report.to_string();
+        assert!(rendered.contains("exit_ladder (25%, 100%)"), "{}", rendered);
+        assert!(rendered.contains("66.7%"), "{}", rendered);
+
+        let settled: Vec<_> = rows.iter().skip(5).cloned().collect();
+        assert!(CohortReport::new(&settled).is_empty());
+    }
+}
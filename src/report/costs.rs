@@ -0,0 +1,446 @@
+//! Execution costs measured on our own fills
+//!
+//! Each fill is joined through its client order ID to the `order_submitted`
+//! journal entry of the signal behind it, which records the mid and fair
+//! value at decision time. From that come the realized spread (fill price
+//! against the mid), the slippage against the order price, and the cost as
+//! a share of the edge the model saw. Measured slippage per size bucket can
+//! be fed back into the [`CostModel`](crate::execution::CostModel) as a
+//! calibration.
+
+use super::markets_from_journal;
+use crate::execution::{Fill, OrderAction, OrderId, SlippageCalibration};
+use crate::journal::JournalEntry;
+use crate::signal::Side;
+use chrono::{DateTime, Timelike, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+/// Report file written to the data directory at shutdown
+pub const COST_REPORT_FILE: &str = "cost_report.json";
+
+/// What the engine knew when it submitted an order
+#[derive(Debug, Clone, Deserialize)]
+struct Decision {
+    signal_id: String,
+    /// Absent from journals older than deterministic IDs, whose client
+    /// order ID was the signal ID
+    #[serde(default)]
+    client_order_id: Option<String>,
+    market_id: String,
+    #[serde(default)]
+    strategy: Option<String>,
+    side: Side,
+    price: Decimal,
+    mid: Option<Decimal>,
+    fair_value: Option<Decimal>,
+}
+
+/// Costs of one fill against its decision
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FillCost {
+    /// Execution engine order ID
+    pub order_id: OrderId,
+    /// Signal behind the order
+    pub signal_id: String,
+    /// Market condition ID
+    pub market_id: String,
+    /// Market asset, if its `market_opened` entry was found
+    pub asset: Option<String>,
+    /// Strategy that traded
+    pub strategy: String,
+    /// Side bought
+    pub side: Side,
+    /// Fill time
+    pub at: DateTime<Utc>,
+    /// Shares filled
+    pub size: Decimal,
+    /// Fill price
+    pub price: Decimal,
+    /// Limit price of the order
+    pub order_price: Decimal,
+    /// Mid of the traded side at decision time
+    pub mid: Decimal,
+    /// Fair price of the traded side at decision time
+    pub fair_value: Decimal,
+    /// Fee paid
+    pub fee: Decimal,
+}
+
+impl FillCost {
+    /// Price paid over the decision-time mid, per share
+    pub fn realized_spread(&self) -> Decimal {
+        self.price - self.mid
+    }
+
+    /// Price paid over the order price, per share
+    pub fn slippage(&self) -> Decimal {
+        self.price - self.order_price
+    }
+
+    /// Edge the model saw against the mid, per share
+    pub fn edge(&self) -> Decimal {
+        self.fair_value - self.mid
+    }
+}
+
+/// Costs of a group of fills
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CostBucket {
+    /// Group name: market, asset, strategy or UTC hour
+    pub key: String,
+    /// Fills in the group
+    pub fills: u64,
+    /// Shares filled
+    pub shares: Decimal,
+    /// Paid over the mid, in USD
+    pub spread_cost: Decimal,
+    /// Paid over the order price, in USD
+    pub slippage_cost: Decimal,
+    /// Fees paid
+    pub fees: Decimal,
+    /// Edge the model saw, in USD
+    pub edge: Decimal,
+}
+
+impl CostBucket {
+    fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, fill: &FillCost) {
+        self.fills += 1;
+        self.shares += fill.size;
+        self.spread_cost += fill.realized_spread() * fill.size;
+        self.slippage_cost += fill.slippage() * fill.size;
+        self.fees += fill.fee;
+        self.edge += fill.edge() * fill.size;
+    }
+
+    /// Size-weighted realized spread per share
+    pub fn realized_spread(&self) -> Option<Decimal> {
+        (!self.shares.is_zero()).then(|| self.spread_cost / self.shares)
+    }
+
+    /// Spread and fees paid
+    pub fn effective_cost(&self) -> Decimal {
+        self.spread_cost + self.fees
+    }
+
+    /// Effective cost as a fraction of the edge; `None` without positive edge
+    pub fn cost_to_edge(&self) -> Option<Decimal> {
+        (self.edge > Decimal::ZERO).then(|| self.effective_cost() / self.edge)
+    }
+}
+
+/// Execution costs of a set of fills, broken down several ways
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CostReport {
+    /// Every fill joined to its decision
+    pub fills: Vec<FillCost>,
+    /// Buy fills with no decision, or one without a mid, to join
+    pub unmatched: u64,
+    /// All joined fills
+    pub total: CostBucket,
+    /// By market
+    pub by_market: Vec<CostBucket>,
+    /// By market asset
+    pub by_asset: Vec<CostBucket>,
+    /// By strategy
+    pub by_strategy: Vec<CostBucket>,
+    /// By UTC hour of the fill
+    pub by_hour: Vec<CostBucket>,
+}
+
+impl CostReport {
+    /// Join buy `fills` to the `order_submitted` entries of `journal`
+    ///
+    /// Client order IDs are unique, so the journal may span many sessions.
+    pub fn from_fills(fills: &[Fill], journal: &[JournalEntry]) -> Self {
+        let decisions: HashMap<String, Decision> = journal
+            .iter()
+            .filter(|e| e.kind == "order_submitted")
+            .filter_map(|e| serde_json::from_value::<Decision>(e.data.clone()).ok())
+            .map(|d| {
+                let key = d.client_order_id.clone().unwrap_or(d.signal_id.clone());
+                (key, d)
+            })
+            .collect();
+        let assets: HashMap<String, String> = markets_from_journal(journal)
+            .into_iter()
+            .filter(|m| !m.asset.is_empty())
+            .map(|m| (m.condition_id, m.asset))
+            .collect();
+
+        let mut report = Self {
+            total: CostBucket::new("total"),
+            ..Default::default()
+        };
+        for fill in fills.iter().filter(|f| f.action == OrderAction::Buy) {
+            let joined = decisions.get(&fill.client_order_id).and_then(|d| {
+                Some(FillCost {
+                    order_id: fill.order_id,
+                    signal_id: d.signal_id.clone(),
+                    market_id: d.market_id.clone(),
+                    asset: assets.get(&d.market_id).cloned(),
+                    strategy: d
+                        .strategy
+                        .clone()
+                        .unwrap_or_else(|| crate::risk::DEFAULT_STRATEGY.to_string()),
+                    side: d.side,
+                    at: fill.timestamp,
+                    size: fill.size,
+                    price: fill.price,
+                    order_price: d.price,
+                    mid: d.mid?,
+                    fair_value: d.fair_value?,
+                    fee: fill.fee,
+                })
+            });
+            match joined {
+                Some(cost) => report.fills.push(cost),
+                None => report.unmatched += 1,
+            }
+        }
+
+        let mut markets = BTreeMap::new();
+        let mut assets = BTreeMap::new();
+        let mut strategies = BTreeMap::new();
+        let mut hours = BTreeMap::new();
+        for fill in &report.fills {
+            report.total.add(fill);
+            let asset = fill.asset.as_deref().unwrap_or("unknown");
+            let hour = format!("{:02}:00", fill.at.hour());
+            for (groups, key) in [
+                (&mut markets, fill.market_id.as_str()),
+                (&mut assets, asset),
+                (&mut strategies, fill.strategy.as_str()),
+                (&mut hours, hour.as_str()),
+            ] {
+                groups
+                    .entry(key.to_string())
+                    .or_insert_with(|| CostBucket::new(key))
+                    .add(fill);
+            }
+        }
+        report.by_market = markets.into_values().collect();
+        report.by_asset = assets.into_values().collect();
+        report.by_strategy = strategies.into_values().collect();
+        report.by_hour = hours.into_values().collect();
+        report
+    }
+
+    /// Slippage per size bucket measured on the joined fills
+    pub fn calibration(&self) -> SlippageCalibration {
+        SlippageCalibration::from_samples(self.fills.iter().map(|f| (f.size, f.slippage())))
+    }
+
+    /// Write the report as JSON
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Execution costs: {} fills joined, {} unmatched",
+            self.fills.len(),
+            self.unmatched
+        )?;
+        let sections = [
+            ("asset", &self.by_asset),
+            ("strategy", &self.by_strategy),
+            ("hour (UTC)", &self.by_hour),
+        ];
+        for (name, buckets) in sections {
+            writeln!(
+                f,
+                "  {:<12} {:>6} {:>10} {:>10} {:>10} {:>10} {:>12}",
+                name, "fills", "shares", "spread", "fees", "edge", "cost/edge"
+            )?;
+            for b in std::iter::once(&self.total).chain(buckets.iter()) {
+                writeln!(
+                    f,
+                    "  {:<12} {:>6} {:>10} {:>10} {:>10} {:>10} {:>12}",
+                    b.key,
+                    b.fills,
+                    b.shares.round_dp(2),
+                    b.realized_spread()
+                        .map_or("n/a".to_string(), |s| s.round_dp(4).to_string()),
+                    b.fees.round_dp(4),
+                    b.edge.round_dp(4),
+                    b.cost_to_edge().map_or("n/a".to_string(), |r| format!(
+                        "{:.1}%",
+                        r * Decimal::ONE_HUNDRED
+                    )),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::{LiquidityFlag, DEFAULT_MIN_CALIBRATION_SAMPLES};
+    use rust_decimal_macros::dec;
+    use uuid::Uuid;
+
+    fn fill(client_order_id: &str, hour: u32, size: Decimal, price: Decimal, fee: Decimal) -> Fill {
+        Fill {
+            order_id: Uuid::new_v4(),
+            token_id: "yes".to_string(),
+            side: Side::Yes,
+            price,
+            size,
+            timestamp: DateTime::from_timestamp(1_700_006_400 + hour as i64 * 3600, 0).unwrap(),
+            fee,
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: client_order_id.to_string(),
+            simulated: false,
+        }
+    }
+
+    fn entry(kind: &str, data: serde_json::Value) -> JournalEntry {
+        JournalEntry {
+            seq: 0,
+            ts: Utc::now(),
+            kind: kind.to_string(),
+            data,
+        }
+    }
+
+    fn opened(market_id: &str, asset: &str) -> JournalEntry {
+        entry(
+            "market_opened",
+            serde_json::json!({
+                "market_id": market_id,
+                "asset": asset,
+                "yes_token_id": format!("{}-yes", market_id),
+                "no_token_id": format!("{}-no", market_id),
+                "open_price": "100",
+                "open_time": Utc::now(),
+                "close_time": Utc::now(),
+            }),
+        )
+    }
+
+    fn submitted(signal: &str, market_id: &str, price: Decimal, mid: Decimal) -> JournalEntry {
+        entry(
+            "order_submitted",
+            serde_json::json!({
+                "market_id": market_id,
+                "signal_id": signal,
+                "client_order_id": format!("{}-1", signal),
+                "strategy": "lag",
+                "side": "yes",
+                "price": price,
+                "size": "10",
+                "mid": mid,
+                "fair_value": mid + dec!(0.10),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_realized_spread_against_decision_mid() {
+        let journal = vec![
+            opened("btc-1", "BTC"),
+            opened("eth-1", "ETH"),
+            submitted("s1", "btc-1", dec!(0.52), dec!(0.50)),
+            submitted("s2", "eth-1", dec!(0.42), dec!(0.40)),
+            // No mid: the book had no bid
+            entry(
+                "order_submitted",
+                serde_json::json!({
+                    "market_id": "btc-1", "signal_id": "s3", "side": "yes",
+                    "price": "0.6", "size": "1", "mid": null, "fair_value": "0.7",
+                }),
+            ),
+        ];
+        let fills = vec![
+            // 10 @ 0.53 against a 0.50 mid: 0.03 spread, 0.01 of it slippage
+            fill("s1-1", 14, dec!(10), dec!(0.53), dec!(0.05)),
+            // 30 @ 0.41 against a 0.40 mid: price improvement on the order
+            fill("s2-1", 2, dec!(30), dec!(0.41), dec!(0.06)),
+            // Journaled before client order IDs: joined on the signal ID
+            fill("s3", 2, dec!(1), dec!(0.60), dec!(0)),
+            fill("unknown", 2, dec!(1), dec!(0.60), dec!(0)),
+        ];
+        let report = CostReport::from_fills(&fills, &journal);
+        assert_eq!(report.fills.len(), 2);
+        assert_eq!(report.unmatched, 2);
+
+        let btc = &report.fills[0];
+        assert_eq!(btc.signal_id, "s1");
+        assert_eq!(btc.asset.as_deref(), Some("BTC"));
+        assert_eq!(btc.strategy, "lag");
+        assert_eq!(btc.realized_spread(), dec!(0.03));
+        assert_eq!(btc.slippage(), dec!(0.01));
+        assert_eq!(btc.edge(), dec!(0.10));
+        assert_eq!(report.fills[1].slippage(), dec!(-0.01));
+
+        // 0.3 + 0.3 paid over the mid on 40 shares; 1 + 3 of edge
+        let total = &report.total;
+        assert_eq!(total.spread_cost, dec!(0.6));
+        assert_eq!(total.realized_spread(), Some(dec!(0.015)));
+        assert_eq!(total.effective_cost(), dec!(0.71));
+        assert_eq!(total.cost_to_edge(), Some(dec!(0.1775)));
+
+        let keys = |buckets: &[CostBucket]| -> Vec<String> {
+            buckets.iter().map(|b| b.key.clone()).collect()
+        };
+        assert_eq!(keys(&report.by_asset), ["BTC", "ETH"]);
+        assert_eq!(keys(&report.by_hour), ["02:00", "14:00"]);
+        assert_eq!(keys(&report.by_market), ["btc-1", "eth-1"]);
+        assert_eq!(report.by_asset[0].cost_to_edge(), Some(dec!(0.35)));
+        assert_eq!(report.by_strategy[0].fills, 2);
+        assert!(report.to_string().contains("hour (UTC)"));
+    }
+
+    #[test]
+    fn test_calibration_from_measured_fills() {
+        let mut journal = vec![];
+        let mut fills = vec![];
+        for i in 0..DEFAULT_MIN_CALIBRATION_SAMPLES {
+            let signal = format!("s{}", i);
+            journal.push(submitted(&signal, "m", dec!(0.50), dec!(0.49)));
+            fills.push(fill(
+                &format!("{}-1", signal),
+                0,
+                dec!(5),
+                dec!(0.52),
+                dec!(0),
+            ));
+        }
+        // One large fill is not enough to calibrate its bucket
+        journal.push(submitted("big", "m", dec!(0.50), dec!(0.49)));
+        fills.push(fill("big-1", 0, dec!(400), dec!(0.55), dec!(0)));
+
+        let calibration = CostReport::from_fills(&fills, &journal).calibration();
+        let small = calibration.bucket(dec!(5)).unwrap();
+        assert_eq!(small.samples, DEFAULT_MIN_CALIBRATION_SAMPLES);
+        assert_eq!(small.mean_slippage, dec!(0.02));
+        assert_eq!(calibration.bucket(dec!(400)).unwrap().samples, 1);
+
+        let model = crate::execution::CostModel {
+            base_slippage: dec!(0.001),
+            calibrated: Some(calibration),
+            ..Default::default()
+        };
+        assert_eq!(model.slippage(dec!(5)), dec!(0.02));
+        assert_eq!(model.slippage(dec!(400)), dec!(0.001));
+    }
+}
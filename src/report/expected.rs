@@ -0,0 +1,438 @@
+//! Expected against realized P&L
+//!
+//! Every traded signal claims an expected value: the model price of the
+//! side bought less the fill price, times the shares, less the entry fee
+//! (`expected_value_usd` on the trade tape). Set against what the trades
+//! realized, it is the plainest check on the model: a realized/expected
+//! ratio that stays well below 1 means the lags it sees do not pay what it
+//! thinks they do, and the entry thresholds want re-tuning.
+//!
+//! Trades are summed overall and bucketed by ISO week of entry, by lag at
+//! entry and by time from entry to close. Each ratio carries a 90%
+//! bootstrap interval: the bucket's trades are resampled with replacement
+//! and the ratio of every resample taken. The resampling is seeded, so a
+//! report over the same trades always comes out the same.
+
+use crate::backtest::TapeRow;
+use crate::precision::round_pct;
+use crate::telemetry::EventCode;
+use chrono::Datelike;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Report written to the data directory at shutdown
+pub const EXPECTED_VALUE_FILE: &str = "expected_value.json";
+
+/// Default trades needed before a shortfall is flagged
+pub const DEFAULT_EV_MIN_TRADES: usize = 30;
+
+/// Default ratio the whole interval must sit below to flag a shortfall
+pub const DEFAULT_EV_WARN_RATIO: Decimal = dec!(0.5);
+
+/// Default resamples behind each interval
+pub const DEFAULT_BOOTSTRAP_RESAMPLES: usize = 1000;
+
+/// Seed of the bootstrap resampling
+const BOOTSTRAP_SEED: u64 = 2443;
+
+/// Share of resampled ratios cut from each tail of the interval
+const INTERVAL_TAIL: f64 = 0.05;
+
+/// Lag buckets by exclusive upper bound; larger lags fall in `>=10c`
+pub(super) const LAG_BUCKETS: [(Decimal, &str); 3] = [
+    (dec!(0.02), "<2c"),
+    (dec!(0.05), "2-5c"),
+    (dec!(0.10), "5-10c"),
+];
+
+/// Time-to-close buckets by exclusive upper bound in seconds; longer
+/// falls in `>=10m`
+const CLOSE_BUCKETS: [(i64, &str); 3] = [(120, "<2m"), (300, "2-5m"), (600, "5-10m")];
+
+/// Expected value settings, under `[expected_value]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ExpectedValueConfig {
+    /// Trades needed before a shortfall is flagged
+    #[serde(default = "default_min_trades")]
+    pub min_trades: usize,
+    /// Flag a shortfall when the whole interval of realized/expected
+    /// sits below this
+    #[serde(default = "default_warn_ratio")]
+    pub warn_ratio: Decimal,
+    /// Resamples behind each interval; 0 skips the intervals
+    #[serde(default = "default_bootstrap_resamples")]
+    pub bootstrap_resamples: usize,
+}
+
+fn default_min_trades() -> usize {
+    DEFAULT_EV_MIN_TRADES
+}
+
+fn default_warn_ratio() -> Decimal {
+    DEFAULT_EV_WARN_RATIO
+}
+
+fn default_bootstrap_resamples() -> usize {
+    DEFAULT_BOOTSTRAP_RESAMPLES
+}
+
+impl Default for ExpectedValueConfig {
+    fn default() -> Self {
+        Self {
+            min_trades: DEFAULT_EV_MIN_TRADES,
+            warn_ratio: DEFAULT_EV_WARN_RATIO,
+            bootstrap_resamples: DEFAULT_BOOTSTRAP_RESAMPLES,
+        }
+    }
+}
+
+/// Expected against realized P&L of a group of trades
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpectedValueBucket {
+    /// Group name: `all`, an ISO week, a lag or a time-to-close range
+    pub key: String,
+    /// Trades in the group
+    pub trades: u64,
+    /// Expected value claimed at entry
+    pub expected: Decimal,
+    /// P&L after fees
+    pub realized: Decimal,
+    /// Realized over expected; `None` unless expected is positive
+    pub ratio: Option<Decimal>,
+    /// 90% bootstrap interval of the ratio; `None` under two trades
+    pub interval: Option<(Decimal, Decimal)>,
+}
+
+impl ExpectedValueBucket {
+    /// Bucket `key` over `trades`, each an (expected, realized) pair
+    fn new(
+        key: impl Into<String>,
+        trades: &[(Decimal, Decimal)],
+        resamples: usize,
+        rng: &mut ChaCha8Rng,
+    ) -> Self {
+        let expected: Decimal = trades.iter().map(|(e, _)| e).sum();
+        let realized: Decimal = trades.iter().map(|(_, r)| r).sum();
+        Self {
+            key: key.into(),
+            trades: trades.len() as u64,
+            expected,
+            realized,
+            ratio: ratio(expected, realized),
+            interval: bootstrap(trades, resamples, rng),
+        }
+    }
+}
+
+fn ratio(expected: Decimal, realized: Decimal) -> Option<Decimal> {
+    (expected > Decimal::ZERO).then(|| round_pct(realized / expected))
+}
+
+/// Central 90% of the ratios of `resamples` resamples of `trades`
+fn bootstrap(
+    trades: &[(Decimal, Decimal)],
+    resamples: usize,
+    rng: &mut ChaCha8Rng,
+) -> Option<(Decimal, Decimal)> {
+    if trades.len() < 2 {
+        return None;
+    }
+    let mut ratios: Vec<Decimal> = (0..resamples)
+        .filter_map(|_| {
+            let (mut expected, mut realized) = (Decimal::ZERO, Decimal::ZERO);
+            for _ in 0..trades.len() {
+                let (e, r) = trades[rng.gen_range(0..trades.len())];
+                expected += e;
+                realized += r;
+            }
+            ratio(expected, realized)
+        })
+        .collect();
+    if ratios.is_empty() {
+        return None;
+    }
+    ratios.sort();
+    let at = |q: f64| ratios[((ratios.len() - 1) as f64 * q).round() as usize];
+    Some((at(INTERVAL_TAIL), at(1.0 - INTERVAL_TAIL)))
+}
+
+/// Expected against realized P&L of a session's or backtest's trades
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExpectedValueReport {
+    /// Every trade with a claim
+    pub total: ExpectedValueBucket,
+    /// By ISO week of entry, oldest first
+    pub by_week: Vec<ExpectedValueBucket>,
+    /// By lag at entry, smallest first
+    pub by_lag: Vec<ExpectedValueBucket>,
+    /// By time from entry to close, shortest first
+    pub by_time_to_close: Vec<ExpectedValueBucket>,
+    /// Trades with no signal behind them, left out
+    pub unclaimed: usize,
+}
+
+impl ExpectedValueReport {
+    /// Report over the closed trades `rows`, with `resamples` resamples
+    /// behind each interval
+    pub fn new(rows: &[TapeRow], resamples: usize) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(BOOTSTRAP_SEED);
+        let mut all = vec![];
+        let mut weeks: BTreeMap<String, Vec<_>> = BTreeMap::new();
+        let mut lags: BTreeMap<usize, Vec<_>> = BTreeMap::new();
+        let mut closes: BTreeMap<usize, Vec<_>> = BTreeMap::new();
+        let mut unclaimed = 0;
+        for row in rows {
+            let (Some(expected), Some(entry)) = (row.expected_value_usd, &row.entry) else {
+                unclaimed += 1;
+                continue;
+            };
+            let trade = (expected, row.realized_pnl);
+            all.push(trade);
+            let week = row.entry_time.iso_week();
+            weeks
+                .entry(format!("{}-W{:02}", week.year(), week.week()))
+                .or_default()
+                .push(trade);
+            let lag = LAG_BUCKETS
+                .iter()
+                .position(|(below, _)| entry.lag < *below)
+                .unwrap_or(LAG_BUCKETS.len());
+            lags.entry(lag).or_default().push(trade);
+            let close = CLOSE_BUCKETS
+                .iter()
+                .position(|(below, _)| entry.secs_to_close < *below)
+                .unwrap_or(CLOSE_BUCKETS.len());
+            closes.entry(close).or_default().push(trade);
+        }
+        let lag_key = |i: usize| LAG_BUCKETS.get(i).map_or(">=10c", |(_, key)| key);
+        let close_key = |i: usize| CLOSE_BUCKETS.get(i).map_or(">=10m", |(_, key)| key);
+        let mut bucket =
+            |key: &str, trades: &[_]| ExpectedValueBucket::new(key, trades, resamples, &mut rng);
+        Self {
+            total: bucket("all", &all),
+            by_week: weeks.iter().map(|(key, t)| bucket(key, t)).collect(),
+            by_lag: lags.iter().map(|(i, t)| bucket(lag_key(*i), t)).collect(),
+            by_time_to_close: closes
+                .iter()
+                .map(|(i, t)| bucket(close_key(*i), t))
+                .collect(),
+            unclaimed,
+        }
+    }
+
+    /// Whether realized P&L sits well below expected: at least
+    /// `min_trades` trades and the whole interval under `warn_ratio`
+    pub fn shortfall(&self, config: &ExpectedValueConfig) -> bool {
+        self.total.trades as usize >= config.min_trades
+            && self
+                .total
+                .interval
+                .is_some_and(|(_, high)| high < config.warn_ratio)
+    }
+
+    /// Warn when [`Self::shortfall`] holds; true if it did
+    pub fn warn_on_shortfall(&self, config: &ExpectedValueConfig) -> bool {
+        if !self.shortfall(config) {
+            return false;
+        }
+        let (low, high) = self.total.interval.unwrap_or_default();
+        tracing::warn!(
+            event_code = %EventCode::ExpectedValueShortfall,
+            trades = self.total.trades,
+            expected = %self.total.expected,
+            realized = %self.total.realized,
+            ratio = ?self.total.ratio,
+            low = %low,
+            high = %high,
+            warn_ratio = %config.warn_ratio,
+            "Realized P&L well below what the signals claimed; re-tune the entry thresholds"
+        );
+        true
+    }
+
+    /// Write the report to `path` as JSON
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ExpectedValueReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Expected value: {} trades", self.total.trades)?;
+        if self.unclaimed > 0 {
+            write!(f, " ({} without a signal left out)", self.unclaimed)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "  {:<16} {:>6} {:>10} {:>10} {:>8} {:>18}",
+            "bucket", "trades", "expected", "realized", "ratio", "90% interval"
+        )?;
+        let groups = [
+            ("", std::slice::from_ref(&self.total)),
+            ("week ", &self.by_week[..]),
+            ("lag ", &self.by_lag[..]),
+            ("close ", &self.by_time_to_close[..]),
+        ];
+        for (prefix, buckets) in groups {
+            for b in buckets {
+                writeln!(
+                    f,
+                    "  {:<16} {:>6} {:>10} {:>10} {:>8} {:>18}",
+                    format!("{}{}", prefix, b.key),
+                    b.trades,
+                    b.expected.round_dp(2),
+                    b.realized.round_dp(2),
+                    b.ratio
+                        .map_or("n/a".to_string(), |r| r.round_dp(2).to_string()),
+                    b.interval.map_or("n/a".to_string(), |(low, high)| format!(
+                        "{} to {}",
+                        low.round_dp(2),
+                        high.round_dp(2)
+                    )),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::EntryFeatures;
+    use crate::signal::Side;
+    use chrono::{DateTime, Duration, Utc};
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        // Monday 2026-01-05, ISO week 2
+        DateTime::from_timestamp(1_767_571_200, 0).unwrap() + Duration::seconds(secs)
+    }
+
+    /// A trade entered `secs` into the tape at `lag`, `secs_to_close`
+    /// before close, expecting `expected` and realizing `realized`
+    fn trade(
+        secs: i64,
+        lag: Decimal,
+        secs_to_close: i64,
+        expected: Decimal,
+        realized: Decimal,
+    ) -> TapeRow {
+        TapeRow {
+            position_id: format!("p{}", secs),
+            market_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            strategy: "lag".to_string(),
+            side: Side::Yes,
+            entry_time: at(secs),
+            exit_time: at(secs + secs_to_close),
+            size: dec!(100),
+            entry_price: dec!(0.50),
+            exit_price: Decimal::ONE,
+            fees: Decimal::ZERO,
+            realized_pnl: realized,
+            expected_value_usd: Some(expected),
+            entry: Some(EntryFeatures {
+                signal_id: "signal".to_string(),
+                reason: "SpotDivergence".to_string(),
+                fair_value: dec!(0.50) + lag,
+                market_price: dec!(0.50),
+                lag,
+                edge: lag,
+                momentum_move: None,
+                retrace: None,
+                confidence: dec!(0.8),
+                secs_to_close,
+                rejections: vec![],
+                flags: Default::default(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_trades_sum_into_their_buckets() {
+        let week = 7 * 24 * 3600;
+        let rows = vec![
+            trade(0, dec!(0.01), 60, dec!(1), dec!(2)),
+            trade(10, dec!(0.03), 200, dec!(3), dec!(-1)),
+            trade(week, dec!(0.07), 400, dec!(7), dec!(7)),
+            trade(week + 10, dec!(0.12), 900, dec!(12), dec!(6)),
+            TapeRow {
+                expected_value_usd: None,
+                ..trade(20, dec!(0.01), 60, dec!(0), dec!(5))
+            },
+        ];
+        let report = ExpectedValueReport::new(&rows, 200);
+
+        assert_eq!(report.unclaimed, 1);
+        assert_eq!(report.total.trades, 4);
+        assert_eq!(report.total.expected, dec!(23));
+        assert_eq!(report.total.realized, dec!(14));
+        let keys = |b: &[ExpectedValueBucket]| b.iter().map(|b| b.key.clone()).collect::<Vec<_>>();
+        assert_eq!(keys(&report.by_week), ["2026-W02", "2026-W03"]);
+        assert_eq!(keys(&report.by_lag), ["<2c", "2-5c", "5-10c", ">=10c"]);
+        assert_eq!(
+            keys(&report.by_time_to_close),
+            ["<2m", "2-5m", "5-10m", ">=10m"]
+        );
+        assert_eq!(report.by_week[0].expected, dec!(4));
+        assert_eq!(report.by_week[0].realized, dec!(1));
+        assert_eq!(report.by_week[0].ratio, Some(dec!(0.25)));
+        assert_eq!(report.by_lag[3].ratio, Some(dec!(0.5)));
+        // A lone trade has no interval
+        assert_eq!(report.by_lag[3].interval, None);
+        // Resampling is seeded
+        let again = ExpectedValueReport::new(&rows, 200);
+        assert_eq!(again.total.interval, report.total.interval);
+    }
+
+    #[test]
+    fn test_constant_ratio_has_a_degenerate_interval() {
+        let rows: Vec<_> = (0..10)
+            .map(|i| {
+                trade(
+                    i,
+                    dec!(0.04),
+                    300,
+                    dec!(2) + Decimal::from(i),
+                    (dec!(2) + Decimal::from(i)) / dec!(2),
+                )
+            })
+            .collect();
+        let report = ExpectedValueReport::new(&rows, 500);
+        assert_eq!(report.total.ratio, Some(dec!(0.5)));
+        assert_eq!(report.total.interval, Some((dec!(0.5), dec!(0.5))));
+    }
+
+    #[test]
+    fn test_shortfall_needs_enough_trades_well_below_expected() {
+        let config = ExpectedValueConfig::default();
+        let trades = |n: i64, ratio: Decimal| -> Vec<TapeRow> {
+            (0..n)
+                .map(|i| trade(i, dec!(0.04), 300, dec!(5), dec!(5) * ratio))
+                .collect()
+        };
+
+        let short = ExpectedValueReport::new(&trades(40, dec!(0.2)), 500);
+        assert!(short.shortfall(&config));
+        assert!(short.warn_on_shortfall(&config));
+
+        let paid = ExpectedValueReport::new(&trades(40, Decimal::ONE), 500);
+        assert!(!paid.shortfall(&config));
+
+        // Too few trades to judge
+        let few = ExpectedValueReport::new(&trades(10, dec!(0.2)), 500);
+        assert!(!few.shortfall(&config));
+    }
+}
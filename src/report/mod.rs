@@ -0,0 +1,53 @@
+//! Post-session reports: canary verdicts, P&L reconciliation and
+//! attribution, execution costs, expected against realized P&L, feature
+//! flag cohorts, shadow fill attainability, timelines built from journals
+//! and captured data, and closed trades as spreadsheet CSV
+
+mod attribution;
+mod canary;
+mod cohort;
+mod costs;
+mod expected;
+mod reconcile;
+mod shadow;
+mod spreadsheet;
+mod timeline;
+
+pub use attribution::{
+    attribution_batch, attribution_schema, AttributionBucket, AttributionReport,
+    PositionAttribution, PNL_ATTRIBUTION_FILE,
+};
+
+pub use canary::{
+    CanaryConfig, CanaryMetrics, CanaryReport, CriterionResult, CANARY_REPORT_FILE,
+    DEFAULT_MAX_DRAWDOWN, DEFAULT_MAX_TICK_LAG_P95_MS, DEFAULT_MIN_SIGNALS, DEFAULT_MIN_WIN_RATE,
+};
+
+pub use cohort::{Cohort, CohortReport, FlagCohorts, FLAG_COHORT_FILE};
+
+pub use costs::{CostBucket, CostReport, FillCost, COST_REPORT_FILE};
+
+pub use expected::{
+    ExpectedValueBucket, ExpectedValueConfig, ExpectedValueReport, DEFAULT_BOOTSTRAP_RESAMPLES,
+    DEFAULT_EV_MIN_TRADES, DEFAULT_EV_WARN_RATIO, EXPECTED_VALUE_FILE,
+};
+
+pub use reconcile::{
+    JournalLedger, JournaledFill, LedgerTotals, PnlDiscrepancy, PnlReconciler, PnlReconciliation,
+    ReconcileConfig, DEFAULT_RECONCILE_TOLERANCE, PNL_RECONCILIATION_FILE,
+};
+
+pub use shadow::{
+    read_shadow_fills, shadow_batch, shadow_file, shadow_schema, ShadowBucket, ShadowReport,
+    SHADOW_DIR,
+};
+
+pub use spreadsheet::{
+    closed_trades, parse_display_offset, read_sheet, write_sheet, SheetLayout, SheetRow,
+    DETAILED_COLUMNS, IMPORTED_STRATEGY, SHEET_COLUMNS,
+};
+
+pub use timeline::{
+    load_timeline, market_from_journal, markets_from_journal, MarketTimeline, TimelineBuilder,
+    TimelineEvent, TimelineEventKind, TimelinePoint, DEFAULT_TIMELINE_RESOLUTION_MS,
+};
@@ -0,0 +1,962 @@
+//! Reconciliation of session P&L across fills, tracker and journal
+//!
+//! Three records of a session should agree: the execution engine's fills,
+//! the position tracker, and the trade journal. The fill stream is taken as
+//! the truth: positions are rebuilt from it with
+//! [`PositionTracker::rebuild_from_fills`], and the tracker and the journal
+//! are each compared against the rebuild. Every discrepancy names the fill
+//! or position behind it.
+
+use super::markets_from_journal;
+use crate::execution::{Fill, OrderAction, OrderId};
+use crate::journal::{JournalEntry, SESSION_START_KIND};
+use crate::market::Market;
+use crate::precision::{round_price, round_size};
+use crate::risk::{PositionTracker, Settlement};
+use crate::signal::Side;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+/// Report file written to the data directory at shutdown
+pub const PNL_RECONCILIATION_FILE: &str = "pnl_reconciliation.json";
+
+/// Default largest P&L or fee difference that still reconciles, in USD
+pub const DEFAULT_RECONCILE_TOLERANCE: Decimal = dec!(0.01);
+
+/// Reconciliation settings, under `[reconcile]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcileConfig {
+    /// Largest P&L or fee difference that still reconciles, in USD
+    #[serde(default = "default_tolerance")]
+    pub tolerance: Decimal,
+}
+
+fn default_tolerance() -> Decimal {
+    DEFAULT_RECONCILE_TOLERANCE
+}
+
+impl Default for ReconcileConfig {
+    fn default() -> Self {
+        Self {
+            tolerance: DEFAULT_RECONCILE_TOLERANCE,
+        }
+    }
+}
+
+/// Totals of one record of the session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerTotals {
+    /// Positions opened
+    pub opened: u64,
+    /// Positions still open
+    pub open: u64,
+    /// P&L of closed positions
+    pub realized_pnl: Decimal,
+    /// Fees paid
+    pub fees: Decimal,
+}
+
+/// A fill the journal recorded as opening a position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournaledFill {
+    /// Execution engine order ID
+    pub order_id: OrderId,
+    /// Market condition ID
+    pub market_id: String,
+    /// Side bought
+    pub side: Side,
+    /// Shares bought
+    pub size: Decimal,
+    /// Fee paid
+    pub fee: Decimal,
+}
+
+/// Trading as one session of the trade journal recorded it
+#[derive(Debug, Clone, Default)]
+pub struct JournalLedger {
+    /// Markets opened
+    pub markets: Vec<Market>,
+    /// Fills that opened positions, repaired and taken-over ones included
+    pub opened: Vec<JournaledFill>,
+    /// Markets settled
+    pub settlements: Vec<Settlement>,
+    /// Positions closed by settlement or an early exit
+    pub settled_positions: u64,
+    /// P&L of the settlements and exits
+    pub realized_pnl: Decimal,
+    /// Fees of the exit fills
+    pub exit_fees: Decimal,
+}
+
+impl JournalLedger {
+    /// Ledger of the last session in `entries`, the entries after the last
+    /// `session_start` header
+    pub fn from_entries(entries: &[JournalEntry]) -> Self {
+        let start = entries
+            .iter()
+            .rposition(|e| e.kind == SESSION_START_KIND)
+            .map_or(0, |i| i + 1);
+        let entries = &entries[start..];
+        let mut ledger = Self {
+            markets: markets_from_journal(entries),
+            ..Default::default()
+        };
+        for entry in entries {
+            let data = &entry.data;
+            let decimal = |key: &str| {
+                serde_json::from_value::<Decimal>(data[key].clone()).unwrap_or_default()
+            };
+            match entry.kind.as_str() {
+                "position_opened" | "position_taken_over" => {
+                    ledger.push_opened(data, decimal("size"), decimal("fee"))
+                }
+                "intent_repaired" if data["status"] == "filled" => {
+                    ledger.push_opened(data, decimal("size"), decimal("fee"))
+                }
+                "market_settled" => {
+                    let (Some(market_id), Ok(winner)) = (
+                        data["market_id"].as_str(),
+                        serde_json::from_value::<Side>(data["winner"].clone()),
+                    ) else {
+                        continue;
+                    };
+                    ledger.settlements.push(Settlement {
+                        market_id: market_id.to_string(),
+                        winner,
+                        at: entry.ts,
+                        status: serde_json::from_value(data["status"].clone()).unwrap_or_default(),
+                    });
+                    ledger.settled_positions += data["positions"].as_u64().unwrap_or(0);
+                    ledger.realized_pnl += decimal("pnl");
+                }
+                "resolution_confirmed" | "resolution_corrected" | "resolution_unconfirmed" => {
+                    let Some(settlement) = ledger
+                        .settlements
+                        .iter_mut()
+                        .rev()
+                        .find(|s| data["market_id"] == s.market_id.as_str())
+                    else {
+                        continue;
+                    };
+                    if let Ok(status) = serde_json::from_value(data["status"].clone()) {
+                        settlement.status = status;
+                    }
+                    if let Ok(official) = serde_json::from_value::<Side>(data["official"].clone()) {
+                        settlement.winner = official;
+                    }
+                    ledger.realized_pnl += decimal("pnl_delta");
+                }
+                "position_exited" => {
+                    // A ladder sale leaves the rest of the position open
+                    if decimal("remaining").is_zero() {
+                        ledger.settled_positions += 1;
+                    }
+                    ledger.realized_pnl += decimal("pnl");
+                    ledger.exit_fees += decimal("fee");
+                }
+                _ => {}
+            }
+        }
+        ledger
+    }
+
+    fn push_opened(&mut self, data: &serde_json::Value, size: Decimal, fee: Decimal) {
+        let (Ok(order_id), Some(market_id), Ok(side)) = (
+            serde_json::from_value::<OrderId>(data["order_id"].clone()),
+            data["market_id"].as_str(),
+            serde_json::from_value::<Side>(data["side"].clone()),
+        ) else {
+            return;
+        };
+        self.opened.push(JournaledFill {
+            order_id,
+            market_id: market_id.to_string(),
+            side,
+            size,
+            fee,
+        });
+    }
+
+    /// Totals as journaled
+    pub fn totals(&self) -> LedgerTotals {
+        let opened = self.opened.len() as u64;
+        LedgerTotals {
+            opened,
+            open: opened.saturating_sub(self.settled_positions),
+            realized_pnl: self.realized_pnl,
+            fees: self.opened.iter().map(|f| f.fee).sum::<Decimal>() + self.exit_fees,
+        }
+    }
+}
+
+/// One way the records of a session disagree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PnlDiscrepancy {
+    /// The fill stream holds the same order more than once
+    DuplicateFill {
+        order_id: OrderId,
+        token_id: String,
+        copies: usize,
+    },
+    /// A fill of a token in no known market, or a sell with nothing open
+    UnappliedFill {
+        order_id: OrderId,
+        token_id: String,
+        action: OrderAction,
+    },
+    /// A fill the tracker has no position for
+    MissingFromTracker {
+        order_id: OrderId,
+        market_id: String,
+        side: Side,
+        size: Decimal,
+    },
+    /// A tracker position no fill accounts for
+    TrackerOnly {
+        market_id: String,
+        side: Side,
+        size: Decimal,
+        entry_time: DateTime<Utc>,
+    },
+    /// A fill the journal never recorded
+    NotJournaled {
+        order_id: OrderId,
+        market_id: String,
+        size: Decimal,
+    },
+    /// A journaled fill missing from the fill stream
+    JournalOnly {
+        order_id: OrderId,
+        market_id: String,
+        size: Decimal,
+    },
+    /// A total differing from the fills' by more than the tolerance
+    Total {
+        source: String,
+        field: String,
+        fills: Decimal,
+        actual: Decimal,
+    },
+}
+
+impl fmt::Display for PnlDiscrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PnlDiscrepancy::DuplicateFill {
+                order_id,
+                token_id,
+                copies,
+            } => write!(
+                f,
+                "duplicate fill: order {} of token {} appears {} times",
+                order_id, token_id, copies
+            ),
+            PnlDiscrepancy::UnappliedFill {
+                order_id,
+                token_id,
+                action,
+            } => write!(
+                f,
+                "unapplied fill: {:?} order {} of token {} matches no market or position",
+                action, order_id, token_id
+            ),
+            PnlDiscrepancy::MissingFromTracker {
+                order_id,
+                market_id,
+                side,
+                size,
+            } => write!(
+                f,
+                "missing from tracker: order {} ({:?} {} in {}) has no position",
+                order_id, side, size, market_id
+            ),
+            PnlDiscrepancy::TrackerOnly {
+                market_id,
+                side,
+                size,
+                entry_time,
+            } => write!(
+                f,
+                "tracker only: {:?} {} in {} entered {} has no fill",
+                side,
+                size,
+                market_id,
+                entry_time.to_rfc3339()
+            ),
+            PnlDiscrepancy::NotJournaled {
+                order_id,
+                market_id,
+                size,
+            } => write!(
+                f,
+                "not journaled: order {} ({} in {}) has no position_opened entry",
+                order_id, size, market_id
+            ),
+            PnlDiscrepancy::JournalOnly {
+                order_id,
+                market_id,
+                size,
+            } => write!(
+                f,
+                "journal only: order {} ({} in {}) is missing from the fills",
+                order_id, size, market_id
+            ),
+            PnlDiscrepancy::Total {
+                source,
+                field,
+                fills,
+                actual,
+            } => write!(
+                f,
+                "{} {}: {} against {} from fills (off by {})",
+                source,
+                field,
+                actual,
+                fills,
+                actual - fills
+            ),
+        }
+    }
+}
+
+/// Where the records of a session agree and where they do not
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PnlReconciliation {
+    /// Largest total difference tolerated
+    pub tolerance: Decimal,
+    /// Totals of positions rebuilt from the fills
+    pub fills: LedgerTotals,
+    /// Totals of the tracker, if compared
+    pub tracker: Option<LedgerTotals>,
+    /// Totals of the journal, if compared
+    pub journal: Option<LedgerTotals>,
+    /// Every disagreement found
+    pub discrepancies: Vec<PnlDiscrepancy>,
+    /// Whether nothing disagreed
+    pub passed: bool,
+}
+
+impl PnlReconciliation {
+    /// Write the reconciliation as JSON
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for PnlReconciliation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "P&L reconciliation: {} (tolerance {})",
+            if self.passed { "OK" } else { "MISMATCH" },
+            self.tolerance
+        )?;
+        writeln!(
+            f,
+            "  {:<8} {:>7} {:>6} {:>14} {:>10}",
+            "source", "opened", "open", "realized_pnl", "fees"
+        )?;
+        let sources = [
+            ("fills", Some(&self.fills)),
+            ("tracker", self.tracker.as_ref()),
+            ("journal", self.journal.as_ref()),
+        ];
+        for (name, totals) in sources {
+            if let Some(t) = totals {
+                writeln!(
+                    f,
+                    "  {:<8} {:>7} {:>6} {:>14} {:>10}",
+                    name,
+                    t.opened,
+                    t.open,
+                    t.realized_pnl.round_dp(4),
+                    t.fees.round_dp(4)
+                )?;
+            }
+        }
+        for discrepancy in &self.discrepancies {
+            writeln!(f, "  ! {}", discrepancy)?;
+        }
+        Ok(())
+    }
+}
+
+/// Identity of a position: the market, side, time, price and size of the
+/// fill that opened it
+///
+/// Time is in microseconds, the precision archived positions keep.
+type PositionKey = (String, Side, i64, Decimal, Decimal);
+
+/// Compares a session's fills with its tracker and journal
+pub struct PnlReconciler<'a> {
+    fills: &'a [Fill],
+    markets: &'a [Market],
+    settlements: &'a [Settlement],
+    tracker: Option<&'a PositionTracker>,
+    journal: Option<&'a JournalLedger>,
+}
+
+impl<'a> PnlReconciler<'a> {
+    /// Reconcile `fills` of `markets`, settled as `settlements`
+    pub fn new(fills: &'a [Fill], markets: &'a [Market], settlements: &'a [Settlement]) -> Self {
+        Self {
+            fills,
+            markets,
+            settlements,
+            tracker: None,
+            journal: None,
+        }
+    }
+
+    /// Compare the tracker's positions and totals
+    pub fn with_tracker(mut self, tracker: &'a PositionTracker) -> Self {
+        self.tracker = Some(tracker);
+        self
+    }
+
+    /// Compare the journal's fills and totals
+    pub fn with_journal(mut self, journal: &'a JournalLedger) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Run the comparison; totals within `tolerance` of the fills' agree
+    pub fn run(&self, tolerance: Decimal) -> anyhow::Result<PnlReconciliation> {
+        let (mut rebuilt, unapplied) =
+            PositionTracker::rebuild_from_fills(self.fills, self.markets);
+        let mut settlements = self.settlements.to_vec();
+        settlements.sort_by_key(|s| s.at);
+        for settlement in &settlements {
+            rebuilt.settle(&settlement.market_id, settlement.winner, settlement.at);
+        }
+        let fills = LedgerTotals {
+            opened: opened_count(&rebuilt)?,
+            open: rebuilt.open_count() as u64,
+            realized_pnl: rebuilt.realized_pnl(),
+            fees: rebuilt.total_fees,
+        };
+
+        let mut discrepancies = vec![];
+        let mut copies: BTreeMap<OrderId, Vec<&Fill>> = BTreeMap::new();
+        for fill in self.fills {
+            copies.entry(fill.order_id).or_default().push(fill);
+        }
+        for (order_id, same) in &copies {
+            if same.len() > 1 {
+                discrepancies.push(PnlDiscrepancy::DuplicateFill {
+                    order_id: *order_id,
+                    token_id: same[0].token_id.clone(),
+                    copies: same.len(),
+                });
+            }
+        }
+        for fill in &unapplied {
+            discrepancies.push(PnlDiscrepancy::UnappliedFill {
+                order_id: fill.order_id,
+                token_id: fill.token_id.clone(),
+                action: fill.action,
+            });
+        }
+
+        // Buy fills that opened a position, keyed like the position
+        let by_token: HashMap<&str, &Market> = self
+            .markets
+            .iter()
+            .flat_map(|m| [(m.yes_token_id.as_str(), m), (m.no_token_id.as_str(), m)])
+            .collect();
+        let opening: Vec<(PositionKey, &Fill)> = self
+            .fills
+            .iter()
+            .filter(|f| f.action == OrderAction::Buy)
+            .filter_map(|f| {
+                let market = by_token.get(f.token_id.as_str())?;
+                let key = (
+                    market.condition_id.clone(),
+                    f.side,
+                    f.timestamp.timestamp_micros(),
+                    round_price(f.price),
+                    round_size(f.size),
+                );
+                Some((key, f))
+            })
+            .collect();
+
+        let mut tracker_totals = None;
+        if let Some(tracker) = self.tracker {
+            let mut positions: HashMap<PositionKey, usize> = HashMap::new();
+            let closed = tracker.history_query(..)?;
+            let held = tracker
+                .open_positions
+                .values()
+                .chain(closed.iter().map(|c| &c.position));
+            // A position sold in parts is one entry fill; add its parts up
+            let mut whole: HashMap<uuid::Uuid, PositionKey> = HashMap::new();
+            for p in held {
+                whole
+                    .entry(p.id)
+                    .and_modify(|key| key.4 += p.size)
+                    .or_insert_with(|| {
+                        (
+                            p.market.condition_id.clone(),
+                            p.side,
+                            p.entry_time.timestamp_micros(),
+                            p.entry_price,
+                            p.size,
+                        )
+                    });
+            }
+            for key in whole.into_values() {
+                *positions.entry(key).or_default() += 1;
+            }
+            for (key, fill) in &opening {
+                match positions.get_mut(key) {
+                    Some(count) if *count > 0 => *count -= 1,
+                    _ => discrepancies.push(PnlDiscrepancy::MissingFromTracker {
+                        order_id: fill.order_id,
+                        market_id: key.0.clone(),
+                        side: key.1,
+                        size: key.4,
+                    }),
+                }
+            }
+            let mut extra: Vec<_> = positions.into_iter().filter(|(_, n)| *n > 0).collect();
+            extra.sort_by_key(|(key, _)| key.2);
+            for ((market_id, side, entry_micros, _, size), count) in extra {
+                for _ in 0..count {
+                    discrepancies.push(PnlDiscrepancy::TrackerOnly {
+                        market_id: market_id.clone(),
+                        side,
+                        size,
+                        entry_time: DateTime::from_timestamp_micros(entry_micros)
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+            let totals = LedgerTotals {
+                opened: opened_count(tracker)?,
+                open: tracker.open_count() as u64,
+                realized_pnl: tracker.realized_pnl(),
+                fees: tracker.total_fees,
+            };
+            push_totals(&mut discrepancies, "tracker", &fills, &totals, tolerance);
+            tracker_totals = Some(totals);
+        }
+
+        let mut journal_totals = None;
+        if let Some(journal) = self.journal {
+            let mut journaled: HashMap<OrderId, usize> = HashMap::new();
+            for fill in &journal.opened {
+                *journaled.entry(fill.order_id).or_default() += 1;
+            }
+            for (key, fill) in &opening {
+                match journaled.get_mut(&fill.order_id) {
+                    Some(count) if *count > 0 => *count -= 1,
+                    _ => discrepancies.push(PnlDiscrepancy::NotJournaled {
+                        order_id: fill.order_id,
+                        market_id: key.0.clone(),
+                        size: key.4,
+                    }),
+                }
+            }
+            for fill in &journal.opened {
+                if let Some(count) = journaled.get_mut(&fill.order_id).filter(|n| **n > 0) {
+                    *count -= 1;
+                    discrepancies.push(PnlDiscrepancy::JournalOnly {
+                        order_id: fill.order_id,
+                        market_id: fill.market_id.clone(),
+                        size: fill.size,
+                    });
+                }
+            }
+            let totals = journal.totals();
+            push_totals(&mut discrepancies, "journal", &fills, &totals, tolerance);
+            journal_totals = Some(totals);
+        }
+
+        Ok(PnlReconciliation {
+            tolerance,
+            fills,
+            tracker: tracker_totals,
+            journal: journal_totals,
+            passed: discrepancies.is_empty(),
+            discrepancies,
+        })
+    }
+}
+
+/// Positions `tracker` ever held, counting one sold in parts once
+fn opened_count(tracker: &PositionTracker) -> anyhow::Result<u64> {
+    let mut ids: HashSet<uuid::Uuid> = tracker.open_positions.keys().copied().collect();
+    ids.extend(tracker.history_query(..)?.iter().map(|c| c.position.id));
+    Ok(ids.len() as u64)
+}
+
+/// Flag realized P&L and fees of `actual` off from the fills' by more than
+/// `tolerance`
+fn push_totals(
+    discrepancies: &mut Vec<PnlDiscrepancy>,
+    source: &str,
+    fills: &LedgerTotals,
+    actual: &LedgerTotals,
+    tolerance: Decimal,
+) {
+    let fields = [
+        ("realized_pnl", fills.realized_pnl, actual.realized_pnl),
+        ("fees", fills.fees, actual.fees),
+    ];
+    for (field, expected, value) in fields {
+        if (value - expected).abs() > tolerance {
+            discrepancies.push(PnlDiscrepancy::Total {
+                source: source.to_string(),
+                field: field.to_string(),
+                fills: expected,
+                actual: value,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::LiquidityFlag;
+    use crate::risk::{FillEffect, ResolutionStatus};
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    fn market() -> Market {
+        let now = Utc::now();
+        Market {
+            condition_id: "m1".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time: now,
+            close_time: now + Duration::minutes(15),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    fn fill(secs: i64, price: Decimal) -> Fill {
+        Fill {
+            order_id: Uuid::new_v4(),
+            token_id: "yes".to_string(),
+            side: Side::Yes,
+            price,
+            size: dec!(10),
+            timestamp: DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap(),
+            fee: dec!(0.05),
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: String::new(),
+            simulated: false,
+        }
+    }
+
+    /// A session of three buys settled YES, as the tracker and journal saw it
+    fn session() -> (
+        Vec<Fill>,
+        Vec<Market>,
+        Vec<Settlement>,
+        PositionTracker,
+        JournalLedger,
+    ) {
+        let market = market();
+        let fills = vec![
+            fill(0, dec!(0.50)),
+            fill(1, dec!(0.55)),
+            fill(2, dec!(0.60)),
+        ];
+        let settlements = vec![Settlement {
+            market_id: market.condition_id.clone(),
+            winner: Side::Yes,
+            at: DateTime::from_timestamp(1_700_000_900, 0).unwrap(),
+            status: ResolutionStatus::Unconfirmed,
+        }];
+        let mut tracker = PositionTracker::new();
+        let mut entries = vec![JournalEntry {
+            seq: 0,
+            ts: Utc::now(),
+            kind: "market_opened".to_string(),
+            data: serde_json::json!({
+                "market_id": market.condition_id,
+                "asset": market.asset,
+                "yes_token_id": market.yes_token_id,
+                "no_token_id": market.no_token_id,
+                "open_price": market.open_price,
+                "open_time": market.open_time,
+                "close_time": market.close_time,
+            }),
+        }];
+        for f in &fills {
+            tracker.apply_fill(&market, f);
+            entries.push(JournalEntry {
+                seq: 0,
+                ts: f.timestamp,
+                kind: "position_opened".to_string(),
+                data: serde_json::json!({
+                    "market_id": market.condition_id,
+                    "order_id": f.order_id,
+                    "side": f.side,
+                    "price": f.price,
+                    "size": f.size,
+                    "fee": f.fee,
+                }),
+            });
+        }
+        let settled = tracker.settle(&market.condition_id, Side::Yes, settlements[0].at);
+        let pnl: Decimal = settled.iter().map(|c| c.realized_pnl).sum();
+        entries.push(JournalEntry {
+            seq: 0,
+            ts: settlements[0].at,
+            kind: "market_settled".to_string(),
+            data: serde_json::json!({
+                "market_id": market.condition_id,
+                "winner": Side::Yes,
+                "positions": settled.len(),
+                "pnl": pnl,
+            }),
+        });
+        let ledger = JournalLedger::from_entries(&entries);
+        (fills, vec![market], settlements, tracker, ledger)
+    }
+
+    fn reconcile(
+        fills: &[Fill],
+        markets: &[Market],
+        settlements: &[Settlement],
+        tracker: &PositionTracker,
+        ledger: &JournalLedger,
+    ) -> PnlReconciliation {
+        PnlReconciler::new(fills, markets, settlements)
+            .with_tracker(tracker)
+            .with_journal(ledger)
+            .run(DEFAULT_RECONCILE_TOLERANCE)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_consistent_session_reconciles() {
+        let (fills, markets, settlements, tracker, ledger) = session();
+        assert_eq!(ledger.markets.len(), 1);
+        assert_eq!(ledger.markets[0].yes_token_id, markets[0].yes_token_id);
+        let result = reconcile(&fills, &markets, &settlements, &tracker, &ledger);
+        assert!(result.passed, "{}", result);
+        assert_eq!(result.fills.opened, 3);
+        assert_eq!(result.fills.open, 0);
+        assert_eq!(result.fills.realized_pnl, dec!(13.35));
+        assert_eq!(result.fills.fees, dec!(0.15));
+        assert_eq!(result.tracker.as_ref(), Some(&result.fills));
+        assert_eq!(result.journal.as_ref(), Some(&result.fills));
+    }
+
+    #[test]
+    fn test_position_sold_in_parts_reconciles() {
+        let market = market();
+        let buy = fill(0, dec!(0.50));
+        let mut sell = fill(60, dec!(0.70));
+        sell.action = OrderAction::Sell;
+        sell.size = dec!(4);
+        let at = DateTime::from_timestamp(1_700_000_900, 0).unwrap();
+        let settlements = vec![Settlement {
+            market_id: market.condition_id.clone(),
+            winner: Side::No,
+            at,
+            status: ResolutionStatus::Unconfirmed,
+        }];
+
+        let mut tracker = PositionTracker::new();
+        tracker.apply_fill(&market, &buy);
+        let FillEffect::Closed(slice) = tracker.apply_fill(&market, &sell) else {
+            panic!("the sell should close a slice");
+        };
+        let settled = tracker.settle(&market.condition_id, Side::No, at);
+        let entry = |kind: &str, data: serde_json::Value| JournalEntry {
+            seq: 0,
+            ts: at,
+            kind: kind.to_string(),
+            data,
+        };
+        let entries = vec![
+            entry(
+                "position_opened",
+                serde_json::json!({
+                    "market_id": market.condition_id,
+                    "order_id": buy.order_id,
+                    "side": buy.side,
+                    "size": buy.size,
+                    "fee": buy.fee,
+                }),
+            ),
+            entry(
+                "position_exited",
+                serde_json::json!({
+                    "pnl": slice.realized_pnl,
+                    "fee": sell.fee,
+                    "remaining": 6,
+                }),
+            ),
+            entry(
+                "market_settled",
+                serde_json::json!({
+                    "market_id": market.condition_id,
+                    "winner": Side::No,
+                    "positions": settled.len(),
+                    "pnl": settled[0].realized_pnl,
+                }),
+            ),
+        ];
+        let ledger = JournalLedger::from_entries(&entries);
+
+        let result = reconcile(&[buy, sell], &[market], &settlements, &tracker, &ledger);
+        assert!(result.passed, "{}", result);
+        assert_eq!(result.fills.opened, 1);
+        assert_eq!(result.fills.open, 0);
+        // 0.20 * 4 against 0.50 * 6 lost, less 0.10 of fees
+        assert_eq!(result.fills.realized_pnl, dec!(-2.30));
+        assert_eq!(result.tracker.as_ref(), Some(&result.fills));
+        assert_eq!(result.journal.as_ref(), Some(&result.fills));
+    }
+
+    #[test]
+    fn test_dropped_fill_is_pinpointed() {
+        let (mut fills, markets, settlements, tracker, ledger) = session();
+        let dropped = fills.remove(1);
+        let result = reconcile(&fills, &markets, &settlements, &tracker, &ledger);
+        assert!(!result.passed);
+        assert!(result.to_string().contains("MISMATCH"));
+
+        let d = &result.discrepancies;
+        assert!(d.contains(&PnlDiscrepancy::TrackerOnly {
+            market_id: "m1".to_string(),
+            side: Side::Yes,
+            size: dec!(10),
+            entry_time: dropped.timestamp,
+        }));
+        assert!(d.contains(&PnlDiscrepancy::JournalOnly {
+            order_id: dropped.order_id,
+            market_id: "m1".to_string(),
+            size: dec!(10),
+        }));
+        // The missing 4.5 of P&L and 0.05 of fees show up in both totals
+        let totals: Vec<(&str, &str)> = d
+            .iter()
+            .filter_map(|d| match d {
+                PnlDiscrepancy::Total { source, field, .. } => {
+                    Some((source.as_str(), field.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            totals,
+            [
+                ("tracker", "realized_pnl"),
+                ("tracker", "fees"),
+                ("journal", "realized_pnl"),
+                ("journal", "fees"),
+            ]
+        );
+        assert_eq!(d.len(), 6);
+    }
+
+    #[test]
+    fn test_duplicated_fill_is_pinpointed() {
+        let (mut fills, markets, settlements, tracker, ledger) = session();
+        let duplicate = fills[2].clone();
+        fills.push(duplicate.clone());
+        let result = reconcile(&fills, &markets, &settlements, &tracker, &ledger);
+        assert!(!result.passed);
+        assert_eq!(result.fills.opened, 4);
+
+        let d = &result.discrepancies;
+        assert_eq!(
+            d[0],
+            PnlDiscrepancy::DuplicateFill {
+                order_id: duplicate.order_id,
+                token_id: "yes".to_string(),
+                copies: 2,
+            }
+        );
+        assert!(d.contains(&PnlDiscrepancy::MissingFromTracker {
+            order_id: duplicate.order_id,
+            market_id: "m1".to_string(),
+            side: Side::Yes,
+            size: dec!(10),
+        }));
+        assert!(d.contains(&PnlDiscrepancy::NotJournaled {
+            order_id: duplicate.order_id,
+            market_id: "m1".to_string(),
+            size: dec!(10),
+        }));
+        assert!(d[0].to_string().contains("appears 2 times"));
+    }
+
+    #[test]
+    fn test_differences_within_tolerance_pass() {
+        let (fills, markets, settlements, mut tracker, ledger) = session();
+        tracker.total_fees += dec!(0.005);
+        assert!(reconcile(&fills, &markets, &settlements, &tracker, &ledger).passed);
+        tracker.total_fees += dec!(0.01);
+        let result = reconcile(&fills, &markets, &settlements, &tracker, &ledger);
+        assert_eq!(
+            result.discrepancies,
+            [PnlDiscrepancy::Total {
+                source: "tracker".to_string(),
+                field: "fees".to_string(),
+                fills: dec!(0.15),
+                actual: dec!(0.165),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_ledger_reads_last_session_only() {
+        let stale = JournalEntry {
+            seq: 0,
+            ts: Utc::now(),
+            kind: "position_opened".to_string(),
+            data: serde_json::json!({
+                "market_id": "old",
+                "order_id": Uuid::new_v4(),
+                "side": "yes",
+                "size": "1",
+                "fee": "0",
+            }),
+        };
+        let header = JournalEntry {
+            seq: 0,
+            ts: Utc::now(),
+            kind: SESSION_START_KIND.to_string(),
+            data: serde_json::json!({}),
+        };
+        let repaired = JournalEntry {
+            seq: 0,
+            ts: Utc::now(),
+            kind: "intent_repaired".to_string(),
+            data: serde_json::json!({
+                "market_id": "m1",
+                "status": "filled",
+                "order_id": Uuid::new_v4(),
+                "side": "no",
+                "size": "2",
+                "fee": "0.01",
+            }),
+        };
+        let ledger = JournalLedger::from_entries(&[stale, header, repaired]);
+        assert_eq!(ledger.opened.len(), 1);
+        assert_eq!(ledger.opened[0].side, Side::No);
+        assert_eq!(ledger.totals().fees, dec!(0.01));
+        assert_eq!(ledger.totals().open, 1);
+    }
+}
@@ -0,0 +1,472 @@
+//! Attainability of paper fills against the market's trade prints
+//!
+//! Each session writes its [`ShadowFill`]s to `shadow/` in the data
+//! directory, one Parquet file per session so they accumulate. The report
+//! gives the share of fills that were attainable overall, by lag at entry
+//! and by the spread of the book traded; a low rate in a bucket means the
+//! paper engine is filling trades there that the market would not have.
+//!
+//! The slippage each covered fill would have needed, sized into
+//! [`SLIPPAGE_SIZE_BUCKETS`](crate::execution::SLIPPAGE_SIZE_BUCKETS),
+//! calibrates the [`CostModel`](crate::execution::CostModel).
+
+use super::expected::LAG_BUCKETS;
+use crate::data::{decimal_column, str_column, timestamp_column, writer_properties};
+use crate::execution::{Attainability, ShadowFill, SlippageCalibration};
+use crate::fingerprint;
+use crate::signal::Side;
+use anyhow::{anyhow, Context};
+use arrow::array::{Array, ArrayRef, StringArray, TimestampMicrosecondArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Utc};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Directory of the data directory holding shadow fill files
+pub const SHADOW_DIR: &str = "shadow";
+
+/// Spread buckets by exclusive upper bound; wider falls in `>=5c`
+const SPREAD_BUCKETS: [(Decimal, &str); 3] = [
+    (Decimal::from_parts(1, 0, 0, false, 2), "<1c"),
+    (Decimal::from_parts(2, 0, 0, false, 2), "1-2c"),
+    (Decimal::from_parts(5, 0, 0, false, 2), "2-5c"),
+];
+
+/// Shadow fill file of a session started at `started`
+pub fn shadow_file(dir: &Path, started: DateTime<Utc>) -> PathBuf {
+    dir.join(SHADOW_DIR).join(format!(
+        "shadow_fills_{}.parquet",
+        started.format("%Y%m%d_%H%M%S")
+    ))
+}
+
+/// Attainability of a group of fills
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShadowBucket {
+    /// Group name: `all`, a lag or a spread range
+    pub key: String,
+    /// Fills shadowed
+    pub fills: u64,
+    /// Whose whole size printed at the fill price
+    pub attainable: u64,
+    /// Whose size printed only in part
+    pub partial: u64,
+    /// With nothing printed at the fill price
+    pub unattainable: u64,
+}
+
+impl ShadowBucket {
+    fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, fill: &ShadowFill) {
+        self.fills += 1;
+        match fill.attainability {
+            Attainability::Attainable => self.attainable += 1,
+            Attainability::Partial => self.partial += 1,
+            Attainability::Unattainable => self.unattainable += 1,
+        }
+    }
+
+    /// Share of fills that were attainable; `None` without fills
+    pub fn rate(&self) -> Option<Decimal> {
+        (self.fills > 0).then(|| Decimal::from(self.attainable) / Decimal::from(self.fills))
+    }
+}
+
+/// Attainability of shadowed paper fills
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShadowReport {
+    /// Every fill, in closing order
+    pub fills: Vec<ShadowFill>,
+    /// All fills
+    pub total: ShadowBucket,
+    /// By lag at entry, smallest first
+    pub by_lag: Vec<ShadowBucket>,
+    /// By spread of the book traded, tightest first
+    pub by_spread: Vec<ShadowBucket>,
+}
+
+impl ShadowReport {
+    /// Report over `fills`
+    pub fn new(fills: Vec<ShadowFill>) -> Self {
+        let mut total = ShadowBucket::new("all");
+        let mut lags: BTreeMap<usize, ShadowBucket> = BTreeMap::new();
+        let mut spreads: BTreeMap<usize, ShadowBucket> = BTreeMap::new();
+        for fill in &fills {
+            total.add(fill);
+            let lag = bucket_of(&LAG_BUCKETS, fill.lag);
+            lags.entry(lag)
+                .or_insert_with(|| ShadowBucket::new(key_of(&LAG_BUCKETS, lag, ">=10c")))
+                .add(fill);
+            let spread = bucket_of(&SPREAD_BUCKETS, fill.spread);
+            spreads
+                .entry(spread)
+                .or_insert_with(|| ShadowBucket::new(key_of(&SPREAD_BUCKETS, spread, ">=5c")))
+                .add(fill);
+        }
+        Self {
+            fills,
+            total,
+            by_lag: lags.into_values().collect(),
+            by_spread: spreads.into_values().collect(),
+        }
+    }
+
+    /// Report over every shadow fill file in `dir`'s [`SHADOW_DIR`]
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let shadow_dir = dir.join(SHADOW_DIR);
+        let mut paths: Vec<PathBuf> = match std::fs::read_dir(&shadow_dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("parquet"))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        paths.sort();
+        let mut fills = vec![];
+        for path in paths {
+            fills.extend(read_shadow_fills(&path).with_context(|| format!("{:?}", path))?);
+        }
+        Ok(Self::new(fills))
+    }
+
+    /// Slippage per size bucket that the fills covered by the prints
+    /// would have needed; uncovered fills say nothing about it
+    pub fn calibration(&self) -> SlippageCalibration {
+        SlippageCalibration::from_samples(
+            self.fills
+                .iter()
+                .filter_map(|f| f.required_slippage().map(|s| (f.size, s))),
+        )
+    }
+
+    /// Write the fills to `path` as Parquet
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let batch = shadow_batch(&self.fills)?;
+        let props = writer_properties(fingerprint::active());
+        let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(props))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+fn bucket_of(bounds: &[(Decimal, &str)], value: Decimal) -> usize {
+    bounds
+        .iter()
+        .position(|(below, _)| value < *below)
+        .unwrap_or(bounds.len())
+}
+
+fn key_of<'a>(bounds: &[(Decimal, &'a str)], i: usize, last: &'a str) -> &'a str {
+    bounds.get(i).map_or(last, |(_, key)| key)
+}
+
+impl fmt::Display for ShadowReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Shadow fills: {} paper entries", self.total.fills)?;
+        writeln!(
+            f,
+            "  {:<12} {:>6} {:>10} {:>8} {:>12} {:>8}",
+            "bucket", "fills", "attainable", "partial", "unattainable", "rate"
+        )?;
+        let groups = [
+            ("", std::slice::from_ref(&self.total)),
+            ("lag ", &self.by_lag[..]),
+            ("spread ", &self.by_spread[..]),
+        ];
+        for (prefix, buckets) in groups {
+            for b in buckets {
+                writeln!(
+                    f,
+                    "  {:<12} {:>6} {:>10} {:>8} {:>12} {:>8}",
+                    format!("{}{}", prefix, b.key),
+                    b.fills,
+                    b.attainable,
+                    b.partial,
+                    b.unattainable,
+                    b.rate().map_or("n/a".to_string(), |r| format!(
+                        "{:.1}%",
+                        r * Decimal::ONE_HUNDRED
+                    )),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shadow fill Parquet schema
+pub fn shadow_schema() -> Schema {
+    let text = |name: &str, nullable: bool| Field::new(name, DataType::Utf8, nullable);
+    let time = || DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    Schema::new(vec![
+        text("signal_id", false),
+        text("market_id", false),
+        text("token_id", false),
+        text("side", false),
+        Field::new("filled_at", time(), false),
+        Field::new("window_end", time(), false),
+        text("order_price", false),
+        text("fill_price", false),
+        text("size", false),
+        text("lag", false),
+        text("spread", false),
+        Field::new("prints", DataType::UInt64, false),
+        text("available_size", false),
+        text("best_price", true),
+        text("fill_level", true),
+        text("attainability", false),
+    ])
+}
+
+/// Shadow fills as a batch in [`shadow_schema`]
+pub fn shadow_batch(rows: &[ShadowFill]) -> anyhow::Result<RecordBatch> {
+    let side = |r: &ShadowFill| match r.side {
+        Side::Yes => "yes",
+        Side::No => "no",
+    };
+    let columns: Vec<ArrayRef> = vec![
+        str_column(rows, |r| &r.signal_id),
+        str_column(rows, |r| &r.market_id),
+        str_column(rows, |r| &r.token_id),
+        str_column(rows, side),
+        timestamp_column(rows, |r| r.filled_at),
+        timestamp_column(rows, |r| r.window_end),
+        decimal_column(rows, |r| Some(r.order_price)),
+        decimal_column(rows, |r| Some(r.fill_price)),
+        decimal_column(rows, |r| Some(r.size)),
+        decimal_column(rows, |r| Some(r.lag)),
+        decimal_column(rows, |r| Some(r.spread)),
+        Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.prints))),
+        decimal_column(rows, |r| Some(r.available_size)),
+        decimal_column(rows, |r| r.best_price),
+        decimal_column(rows, |r| r.fill_level),
+        str_column(rows, |r| r.attainability.as_str()),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(shadow_schema()), columns)?)
+}
+
+/// Read shadow fills written by [`ShadowReport::write`]
+pub fn read_shadow_fills(path: &Path) -> anyhow::Result<Vec<ShadowFill>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    let mut fills = vec![];
+    for batch in reader {
+        let batch = batch?;
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .ok_or_else(|| anyhow!("missing column {}", name))
+        };
+        let text = |name: &str| {
+            column(name)?
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or_else(|| anyhow!("column {} has wrong type", name))
+        };
+        let time = |name: &str| {
+            column(name)?
+                .as_any()
+                .downcast_ref::<TimestampMicrosecondArray>()
+                .ok_or_else(|| anyhow!("column {} has wrong type", name))
+        };
+        let prints = column("prints")?
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .ok_or_else(|| anyhow!("column prints has wrong type"))?;
+        let (filled_at, window_end) = (time("filled_at")?, time("window_end")?);
+        let names = [
+            "signal_id",
+            "market_id",
+            "token_id",
+            "side",
+            "order_price",
+            "fill_price",
+            "size",
+            "lag",
+            "spread",
+            "available_size",
+            "best_price",
+            "fill_level",
+            "attainability",
+        ];
+        let strings = names
+            .iter()
+            .map(|name| text(name))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for row in 0..batch.num_rows() {
+            let value = |i: usize| strings[i].value(row);
+            let decimal = |i: usize| Decimal::from_str(value(i));
+            let optional = |i: usize| -> anyhow::Result<Option<Decimal>> {
+                Ok(if strings[i].is_null(row) {
+                    None
+                } else {
+                    Some(decimal(i)?)
+                })
+            };
+            let at = |column: &TimestampMicrosecondArray| {
+                DateTime::from_timestamp_micros(column.value(row))
+                    .ok_or_else(|| anyhow!("timestamp out of range"))
+            };
+            fills.push(ShadowFill {
+                signal_id: value(0).to_string(),
+                market_id: value(1).to_string(),
+                token_id: value(2).to_string(),
+                side: match value(3) {
+                    "yes" => Side::Yes,
+                    "no" => Side::No,
+                    other => anyhow::bail!("unknown side: {}", other),
+                },
+                filled_at: at(filled_at)?,
+                window_end: at(window_end)?,
+                order_price: decimal(4)?,
+                fill_price: decimal(5)?,
+                size: decimal(6)?,
+                lag: decimal(7)?,
+                spread: decimal(8)?,
+                prints: prints.value(row),
+                available_size: decimal(9)?,
+                best_price: optional(10)?,
+                fill_level: optional(11)?,
+                attainability: Attainability::from_name(value(12))
+                    .ok_or_else(|| anyhow!("unknown attainability: {}", value(12)))?,
+            });
+        }
+    }
+    Ok(fills)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_735_689_600, 0).unwrap()
+    }
+
+    /// `size` shares at a `lag` and `spread`, of which `available` printed
+    /// at the fill price and the whole size by `fill_level`
+    fn fill(
+        lag: Decimal,
+        spread: Decimal,
+        size: Decimal,
+        available: Decimal,
+        fill_level: Option<Decimal>,
+    ) -> ShadowFill {
+        let attainability = if available >= size {
+            Attainability::Attainable
+        } else if available > Decimal::ZERO {
+            Attainability::Partial
+        } else {
+            Attainability::Unattainable
+        };
+        ShadowFill {
+            signal_id: format!("sig-{}-{}", lag, available),
+            market_id: "cond".to_string(),
+            token_id: "yes-token".to_string(),
+            side: Side::Yes,
+            filled_at: t0(),
+            window_end: t0() + Duration::seconds(30),
+            order_price: dec!(0.50),
+            fill_price: dec!(0.51),
+            size,
+            lag,
+            spread,
+            prints: 2,
+            available_size: available,
+            best_price: fill_level.map(|_| dec!(0.50)),
+            fill_level,
+            attainability,
+        }
+    }
+
+    fn fills() -> Vec<ShadowFill> {
+        vec![
+            fill(dec!(0.01), dec!(0.01), dec!(5), dec!(5), Some(dec!(0.51))),
+            fill(dec!(0.03), dec!(0.01), dec!(8), dec!(2), Some(dec!(0.53))),
+            fill(dec!(0.03), dec!(0.04), dec!(40), dec!(0), None),
+            fill(dec!(0.12), dec!(0.06), dec!(20), dec!(20), Some(dec!(0.51))),
+        ]
+    }
+
+    #[test]
+    fn test_attainability_by_lag_and_spread() {
+        let report = ShadowReport::new(fills());
+        assert_eq!(report.total.fills, 4);
+        assert_eq!(report.total.attainable, 2);
+        assert_eq!(report.total.partial, 1);
+        assert_eq!(report.total.unattainable, 1);
+        assert_eq!(report.total.rate(), Some(dec!(0.5)));
+
+        let lags: Vec<(&str, u64, u64)> = report
+            .by_lag
+            .iter()
+            .map(|b| (b.key.as_str(), b.fills, b.attainable))
+            .collect();
+        assert_eq!(lags, [("<2c", 1, 1), ("2-5c", 2, 0), (">=10c", 1, 1)]);
+        let spreads: Vec<(&str, u64)> = report
+            .by_spread
+            .iter()
+            .map(|b| (b.key.as_str(), b.fills))
+            .collect();
+        assert_eq!(spreads, [("1-2c", 2), ("2-5c", 1), (">=5c", 1)]);
+
+        let text = report.to_string();
+        assert!(text.contains("Shadow fills: 4 paper entries"), "{}", text);
+        assert!(text.contains("lag 2-5c"), "{}", text);
+        assert!(text.contains("50.0%"), "{}", text);
+    }
+
+    #[test]
+    fn test_calibration_from_covered_fills() {
+        let calibration = ShadowReport::new(fills()).calibration();
+        // The two small fills needed 0.01 and 0.03; the uncovered one is
+        // left out and the 20-share one needed 0.01
+        let small = calibration.bucket(dec!(5)).unwrap();
+        assert_eq!(small.samples, 2);
+        assert_eq!(small.mean_slippage, dec!(0.02));
+        assert_eq!(calibration.bucket(dec!(20)).unwrap().samples, 1);
+        assert_eq!(calibration.bucket(dec!(400)).unwrap().samples, 0);
+    }
+
+    #[test]
+    fn test_sessions_accumulate_and_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let all = fills();
+        let (first, second) = all.split_at(2);
+        ShadowReport::new(first.to_vec())
+            .write(&shadow_file(dir.path(), t0()))
+            .unwrap();
+        ShadowReport::new(second.to_vec())
+            .write(&shadow_file(dir.path(), t0() + Duration::hours(1)))
+            .unwrap();
+
+        let report = ShadowReport::load(dir.path()).unwrap();
+        assert_eq!(report.fills, fills());
+        assert_eq!(report.total.fills, 4);
+
+        let empty = TempDir::new().unwrap();
+        assert_eq!(ShadowReport::load(empty.path()).unwrap().total.fills, 0);
+    }
+}
@@ -0,0 +1,563 @@
+//! Closed trades as spreadsheet CSV
+//!
+//! A P&L tracking spreadsheet keeps one row per trade in a fixed layout,
+//! [`SHEET_COLUMNS`]. Closed positions from trade tapes and position
+//! archives are exported to it, the strategy, signal, rejection trail and
+//! exit folded into the notes; the detailed layout appends more columns
+//! after them. Rows typed by hand, such as live trades from before the bot,
+//! are imported back as trade tape rows.
+//!
+//! Dates are RFC 3339 with an explicit offset: written in UTC unless a
+//! display offset is asked for, read in any offset and kept as UTC.
+
+use crate::backtest::{read_trade_tape, TapeRow, TRADE_TAPE_FILE};
+use crate::data::{HistoryArchive, HISTORY_DIR};
+use crate::execution::{csv_field, split_csv_line};
+use crate::signal::{reason_code, Side};
+use anyhow::Context;
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Header of the spreadsheet, in order
+pub const SHEET_COLUMNS: [&str; 9] = [
+    "date", "market", "side", "entry", "exit", "size", "fees", "pnl", "notes",
+];
+
+/// Columns the detailed layout appends after [`SHEET_COLUMNS`]
+pub const DETAILED_COLUMNS: [&str; 6] = [
+    "position_id",
+    "asset",
+    "strategy",
+    "exit_time",
+    "fair_value",
+    "edge",
+];
+
+/// Strategy of an imported row whose notes name none
+pub const IMPORTED_STRATEGY: &str = "manual";
+
+/// Which columns an export writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SheetLayout {
+    /// [`SHEET_COLUMNS`] only
+    Simple,
+    /// [`SHEET_COLUMNS`] then [`DETAILED_COLUMNS`]
+    Detailed,
+}
+
+impl FromStr for SheetLayout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple" => Ok(SheetLayout::Simple),
+            "detailed" => Ok(SheetLayout::Detailed),
+            other => anyhow::bail!(
+                "unknown CSV format '{}', expected simple or detailed",
+                other
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SheetLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SheetLayout::Simple => write!(f, "simple"),
+            SheetLayout::Detailed => write!(f, "detailed"),
+        }
+    }
+}
+
+/// Offset dates are displayed in: `UTC` or `±HH:MM`
+pub fn parse_display_offset(tz: &str) -> anyhow::Result<FixedOffset> {
+    if tz.eq_ignore_ascii_case("utc") || tz == "Z" {
+        return Ok(FixedOffset::east_opt(0).expect("zero offset"));
+    }
+    FixedOffset::from_str(tz)
+        .with_context(|| format!("bad offset '{}', expected UTC or ±HH:MM", tz))
+}
+
+/// One spreadsheet row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetRow {
+    /// Entry time
+    pub date: DateTime<Utc>,
+    /// Market condition ID or slug
+    pub market: String,
+    pub side: Side,
+    /// Entry price
+    pub entry: Decimal,
+    /// Exit price, 1 or 0 at settlement
+    pub exit: Decimal,
+    /// Shares
+    pub size: Decimal,
+    pub fees: Decimal,
+    /// P&L after fees
+    pub pnl: Decimal,
+    /// Free text; exports write `key=value` pairs separated by `; `
+    pub notes: String,
+}
+
+impl SheetRow {
+    /// Row of a closed trade
+    pub fn from_tape(row: &TapeRow) -> Self {
+        Self {
+            date: row.entry_time,
+            market: row.market_id.clone(),
+            side: row.side,
+            entry: row.entry_price,
+            exit: row.exit_price,
+            size: row.size,
+            fees: row.fees,
+            pnl: row.realized_pnl,
+            notes: notes(row),
+        }
+    }
+
+    /// Trade tape row of an imported line, numbered `line`
+    ///
+    /// Only the entry time is known, so the exit is taken to be then too.
+    pub fn to_tape(&self, line: usize) -> TapeRow {
+        TapeRow {
+            position_id: format!("sheet-{}-{}", self.date.format("%Y%m%d%H%M%S"), line),
+            market_id: self.market.clone(),
+            asset: String::new(),
+            strategy: note(&self.notes, "strategy")
+                .unwrap_or(IMPORTED_STRATEGY)
+                .to_string(),
+            side: self.side,
+            entry_time: self.date,
+            exit_time: self.date,
+            size: self.size,
+            entry_price: self.entry,
+            exit_price: self.exit,
+            fees: self.fees,
+            realized_pnl: self.pnl,
+            expected_value_usd: None,
+            entry: None,
+        }
+    }
+}
+
+/// Strategy, signal, rejection trail and exit of a trade
+fn notes(row: &TapeRow) -> String {
+    let mut notes = vec![format!("strategy={}", row.strategy)];
+    if let Some(entry) = &row.entry {
+        notes.push(format!("signal={}", entry.reason));
+        if !entry.rejections.is_empty() {
+            let reasons: Vec<&str> = entry
+                .rejections
+                .iter()
+                .map(|r| reason_code(&r.reason).unwrap_or(&r.reason))
+                .collect();
+            notes.push(format!("rejections={}", reasons.join(",")));
+        }
+    }
+    let exit = match row.exit_price {
+        p if p == Decimal::ONE => "settled won",
+        p if p.is_zero() => "settled lost",
+        _ => "settled",
+    };
+    notes.push(format!("exit={}", exit));
+    notes.join("; ")
+}
+
+/// Value of `key` among `key=value` pairs of `notes`
+fn note<'a>(notes: &'a str, key: &str) -> Option<&'a str> {
+    notes
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v.trim())
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Yes => "yes",
+        Side::No => "no",
+    }
+}
+
+/// Write `rows` as spreadsheet CSV, dates shown at `offset`
+pub fn write_sheet(
+    out: &mut impl Write,
+    rows: &[TapeRow],
+    layout: SheetLayout,
+    offset: FixedOffset,
+) -> anyhow::Result<()> {
+    let mut header: Vec<&str> = SHEET_COLUMNS.to_vec();
+    if layout == SheetLayout::Detailed {
+        header.extend(DETAILED_COLUMNS);
+    }
+    writeln!(out, "{}", header.join(","))?;
+    for row in rows {
+        let sheet = SheetRow::from_tape(row);
+        let date = sheet
+            .date
+            .with_timezone(&offset)
+            .to_rfc3339_opts(SecondsFormat::Secs, true);
+        let mut fields = vec![
+            date,
+            sheet.market,
+            side_name(sheet.side).to_string(),
+            sheet.entry.normalize().to_string(),
+            sheet.exit.normalize().to_string(),
+            sheet.size.normalize().to_string(),
+            sheet.fees.normalize().to_string(),
+            sheet.pnl.normalize().to_string(),
+            sheet.notes,
+        ];
+        if layout == SheetLayout::Detailed {
+            let entry = |f: fn(&crate::backtest::EntryFeatures) -> Decimal| {
+                row.entry
+                    .as_ref()
+                    .map(|e| f(e).normalize().to_string())
+                    .unwrap_or_default()
+            };
+            fields.extend([
+                row.position_id.clone(),
+                row.asset.clone(),
+                row.strategy.clone(),
+                row.exit_time
+                    .with_timezone(&offset)
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                entry(|e| e.fair_value),
+                entry(|e| e.edge),
+            ]);
+        }
+        let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Read spreadsheet CSV; columns are found by header name, so extra ones
+/// and any order are accepted
+pub fn read_sheet(input: impl BufRead) -> anyhow::Result<Vec<SheetRow>> {
+    let mut lines = input.lines();
+    let header = split_csv_line(
+        lines
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("empty CSV, expected a header row"))??
+            .trim_start_matches('\u{feff}'),
+    );
+    let index: Vec<usize> = SHEET_COLUMNS
+        .iter()
+        .map(|name| {
+            header
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name))
+                .ok_or_else(|| anyhow::anyhow!("CSV has no '{}' column", name))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    let mut rows = vec![];
+    for (n, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(&line);
+        let field = |column: usize| fields.get(index[column]).map_or("", |f| f.trim());
+        let decimal = |column: usize| {
+            Decimal::from_str(field(column))
+                .with_context(|| format!("line {}: bad {}", n + 2, SHEET_COLUMNS[column]))
+        };
+        let date = DateTime::parse_from_rfc3339(field(0))
+            .with_context(|| {
+                format!(
+                    "line {}: bad date '{}', expected RFC 3339 with an offset",
+                    n + 2,
+                    field(0)
+                )
+            })?
+            .with_timezone(&Utc);
+        let side = match field(2).to_ascii_lowercase().as_str() {
+            "yes" | "up" => Side::Yes,
+            "no" | "down" => Side::No,
+            other => anyhow::bail!("line {}: unknown side '{}'", n + 2, other),
+        };
+        rows.push(SheetRow {
+            date,
+            market: field(1).to_string(),
+            side,
+            entry: decimal(3)?,
+            exit: decimal(4)?,
+            size: decimal(5)?,
+            fees: decimal(6)?,
+            pnl: decimal(7)?,
+            notes: field(8).to_string(),
+        });
+    }
+    Ok(rows)
+}
+
+/// Every closed trade under `dir`: trade tapes, then archived positions
+/// no tape has, oldest entry first
+pub fn closed_trades(dir: &Path) -> anyhow::Result<Vec<TapeRow>> {
+    let mut tapes = vec![];
+    let mut archives = vec![];
+    find_sources(dir, &mut tapes, &mut archives)?;
+    tapes.sort();
+    archives.sort();
+
+    let mut seen = HashSet::new();
+    let mut rows = vec![];
+    for path in &tapes {
+        for row in read_trade_tape(path).with_context(|| format!("reading {:?}", path))? {
+            if seen.insert(row.position_id.clone()) {
+                rows.push(row);
+            }
+        }
+    }
+    for archive in archives {
+        for closed in HistoryArchive::new(archive).read_closed()? {
+            let row = TapeRow::new(&closed, None);
+            if seen.insert(row.position_id.clone()) {
+                rows.push(row);
+            }
+        }
+    }
+    rows.sort_by_key(|r| r.entry_time);
+    Ok(rows)
+}
+
+/// Trade tapes and session archive directories at or below `dir`
+fn find_sources(
+    dir: &Path,
+    tapes: &mut Vec<std::path::PathBuf>,
+    archives: &mut Vec<std::path::PathBuf>,
+) -> anyhow::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if path.is_dir() {
+            if name == HISTORY_DIR {
+                for session in std::fs::read_dir(&path)? {
+                    let session = session?.path();
+                    if session.is_dir() {
+                        archives.push(session);
+                    }
+                }
+            } else {
+                find_sources(&path, tapes, archives)?;
+            }
+        } else if name == TRADE_TAPE_FILE {
+            tapes.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::{write_trade_tape, EntryFeatures, Rejection};
+    use crate::market::Market;
+    use crate::risk::{ClosedPosition, Position};
+    use rust_decimal_macros::dec;
+    use std::io::BufReader;
+
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/spreadsheet/trades.csv"
+    );
+
+    fn ts(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_735_689_600 + secs, 0).unwrap()
+    }
+
+    fn traded() -> TapeRow {
+        TapeRow {
+            position_id: "00000000-0000-0000-0000-000000000001".to_string(),
+            market_id: "btc-updown-15m-1735689600".to_string(),
+            asset: "BTC".to_string(),
+            strategy: "default".to_string(),
+            side: Side::Yes,
+            entry_time: ts(120),
+            exit_time: ts(900),
+            size: dec!(20),
+            entry_price: dec!(0.4500),
+            exit_price: dec!(1),
+            fees: dec!(0.045),
+            realized_pnl: dec!(10.955),
+            expected_value_usd: Some(dec!(2.555)),
+            entry: Some(EntryFeatures {
+                signal_id: "00000000-0000-0000-0000-00000000000a".to_string(),
+                reason: "SpotDivergence".to_string(),
+                fair_value: dec!(0.58),
+                market_price: dec!(0.45),
+                lag: dec!(0.13),
+                edge: dec!(0.11),
+                momentum_move: None,
+                retrace: None,
+                confidence: dec!(0.8),
+                secs_to_close: 780,
+                rejections: vec![
+                    Rejection {
+                        at: ts(60),
+                        reason: "edge_too_small".to_string(),
+                        context: serde_json::json!({"edge": "0.003", "min": "0.005"}),
+                    },
+                    Rejection {
+                        at: ts(90),
+                        reason: "spread_too_wide".to_string(),
+                        context: serde_json::json!({"spread": "0.08", "max": "0.05"}),
+                    },
+                ],
+                flags: Default::default(),
+            }),
+        }
+    }
+
+    fn export(rows: &[TapeRow], layout: SheetLayout, tz: &str) -> String {
+        let mut out = vec![];
+        write_sheet(&mut out, rows, layout, parse_display_offset(tz).unwrap()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_export_folds_reasons_into_notes() {
+        let csv = export(&[traded()], SheetLayout::Simple, "UTC");
+        assert_eq!(
+            csv,
+            "date,market,side,entry,exit,size,fees,pnl,notes\n\
+             2025-01-01T00:02:00Z,btc-updown-15m-1735689600,yes,0.45,1,20,0.045,10.955,\
+             \"strategy=default; signal=SpotDivergence; rejections=edge_too_small,spread_too_wide; exit=settled won\"\n"
+        );
+
+        // Only the display moves with the offset; the instant is the same
+        let shifted = export(&[traded()], SheetLayout::Detailed, "+02:00");
+        let line = shifted.lines().nth(1).unwrap();
+        assert!(line.starts_with("2025-01-01T02:02:00+02:00,"));
+        assert!(line.ends_with(
+            ",00000000-0000-0000-0000-000000000001,BTC,default,2025-01-01T02:15:00+02:00,0.58,0.11"
+        ));
+        let read = read_sheet(BufReader::new(shifted.as_bytes())).unwrap();
+        assert_eq!(read[0].date, ts(120));
+        assert!(parse_display_offset("Europe/London").is_err());
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let mut lost = traded();
+        lost.position_id = "00000000-0000-0000-0000-000000000002".to_string();
+        lost.side = Side::No;
+        lost.exit_price = dec!(0);
+        lost.realized_pnl = dec!(-9.045);
+        lost.entry = None;
+        lost.strategy = "fade".to_string();
+        let rows = vec![traded(), lost];
+
+        for layout in [SheetLayout::Simple, SheetLayout::Detailed] {
+            let csv = export(&rows, layout, "UTC");
+            let imported = read_sheet(BufReader::new(csv.as_bytes())).unwrap();
+            assert_eq!(imported.len(), 2);
+            for (sheet, row) in imported.iter().zip(&rows) {
+                assert_eq!(*sheet, SheetRow::from_tape(row));
+                let tape = sheet.to_tape(1);
+                assert_eq!(tape.strategy, row.strategy);
+                assert_eq!(tape.realized_pnl, row.realized_pnl);
+                assert_eq!(tape.entry_price, row.entry_price);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fixture_matches_the_spreadsheet_layout() {
+        let text = std::fs::read_to_string(FIXTURE).unwrap();
+        assert_eq!(text.lines().next().unwrap(), SHEET_COLUMNS.join(","));
+
+        let sheet = read_sheet(BufReader::new(text.as_bytes())).unwrap();
+        assert_eq!(sheet.len(), 3);
+        // Entered in local time, kept as UTC
+        assert_eq!(
+            sheet[1].date,
+            DateTime::parse_from_rfc3339("2024-11-04T14:32:00Z").unwrap()
+        );
+        assert_eq!(sheet[1].notes, "fat finger, meant 10");
+
+        let tape: Vec<TapeRow> = sheet
+            .iter()
+            .enumerate()
+            .map(|(i, r)| r.to_tape(i))
+            .collect();
+        assert_eq!(tape[0].strategy, IMPORTED_STRATEGY);
+        assert_eq!(tape[2].strategy, "default");
+
+        // Exported again, the hand-typed rows come back as typed, in UTC
+        let exported = export(&tape, SheetLayout::Simple, "UTC");
+        let typed: Vec<&str> = text.lines().collect();
+        let again: Vec<&str> = exported.lines().collect();
+        assert_eq!(again[0], typed[0]);
+        assert_eq!(again[3], typed[3]);
+        let row = read_sheet(BufReader::new(exported.as_bytes())).unwrap();
+        for (a, b) in row.iter().zip(&sheet) {
+            assert_eq!(
+                (a.date, &a.market, a.side, a.pnl),
+                (b.date, &b.market, b.side, b.pnl)
+            );
+        }
+    }
+
+    #[test]
+    fn test_closed_trades_merges_tapes_and_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        write_trade_tape(&dir.path().join(TRADE_TAPE_FILE), &[traded()]).unwrap();
+
+        let market = Market {
+            condition_id: "btc-updown-15m-1735690500".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time: ts(900),
+            close_time: ts(1800),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        let archived = |id: u128, entry: i64| ClosedPosition {
+            position: Position {
+                id: uuid::Uuid::from_u128(id),
+                market: market.clone(),
+                side: Side::No,
+                entry_price: dec!(0.3),
+                size: dec!(10),
+                entry_time: ts(entry),
+                unrealized_pnl: Decimal::ZERO,
+                entry_fee: Decimal::ZERO,
+                strategy: "default".to_string(),
+                max_adverse: Decimal::ZERO,
+            },
+            exit_price: dec!(1),
+            exit_time: ts(1800),
+            realized_pnl: dec!(7),
+            fees: Decimal::ZERO,
+        };
+        // The same position in the tape and an archive is exported once
+        let archive = HistoryArchive::for_session(dir.path(), ts(0));
+        archive
+            .archive_closed(&[archived(1, 120), archived(3, 1000)])
+            .unwrap();
+
+        let rows = closed_trades(dir.path()).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], traded());
+        assert_eq!(rows[1].position_id, uuid::Uuid::from_u128(3).to_string());
+        assert!(rows[1].entry.is_none());
+    }
+}
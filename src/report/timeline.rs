@@ -0,0 +1,482 @@
+//! Per-market session timeline
+//!
+//! Lines up spot, the YES ask and the model's expected YES price with what
+//! the engine did in one market, downsampled into compact `[ts_ms, value]`
+//! arrays a chart can plot directly.
+
+use crate::data::{load_book_records, price_ticks_from_batch, read_batches, scan_data_files};
+use crate::journal::{Journal, JournalEntry};
+use crate::market::{Market, TokenOrientation};
+use crate::model::{FairValueModel, FairValueParams, GbmModel, VolatilityEstimator};
+use crate::orderbook::{OrderBook, OrderBookManager, PriceLevel};
+use crate::signal::reason_code;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Default width of a downsampling bucket, in milliseconds
+pub const DEFAULT_TIMELINE_RESOLUTION_MS: i64 = 1000;
+
+/// `[timestamp_ms, value]`
+pub type TimelinePoint = (i64, f64);
+
+/// What a timeline marker records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineEventKind {
+    /// The market window opened
+    MarketOpen,
+    /// A signal was filtered out; repeats of the same reason are not marked
+    SignalRejected,
+    /// An order was submitted
+    Order,
+    /// An order filled and opened a position
+    Fill,
+    /// The market settled
+    Resolution,
+}
+
+/// One marker on the timeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    /// Milliseconds since the epoch
+    pub ts: i64,
+    pub kind: TimelineEventKind,
+    /// Short human-readable description
+    pub detail: String,
+}
+
+/// Everything needed to chart one market of a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketTimeline {
+    pub market_id: String,
+    pub yes_token_id: String,
+    pub open_price: Decimal,
+    /// Window open, milliseconds since the epoch
+    pub open_time: i64,
+    /// Window close, milliseconds since the epoch
+    pub close_time: i64,
+    /// Downsampling bucket width the series were built with
+    pub resolution_ms: i64,
+    /// Spot price
+    pub spot: Vec<TimelinePoint>,
+    /// Best YES ask
+    pub yes_ask: Vec<TimelinePoint>,
+    /// GBM fair value of YES
+    pub expected_price: Vec<TimelinePoint>,
+    /// Markers in time order
+    pub events: Vec<TimelineEvent>,
+}
+
+/// Payload of a `market_opened` trade journal entry
+#[derive(Deserialize)]
+struct MarketOpened {
+    market_id: String,
+    #[serde(default)]
+    asset: String,
+    yes_token_id: String,
+    no_token_id: String,
+    open_price: Decimal,
+    open_time: DateTime<Utc>,
+    close_time: DateTime<Utc>,
+    #[serde(default)]
+    orientation: TokenOrientation,
+}
+
+/// The market `market_id` as journaled when it opened
+pub fn market_from_journal(entries: &[JournalEntry], market_id: &str) -> Option<Market> {
+    markets_from_journal(entries)
+        .into_iter()
+        .find(|m| m.condition_id == market_id)
+}
+
+/// Every market a journal's `market_opened` entries describe, in order,
+/// with the tokens of any later `tokens_inferred` entry
+pub fn markets_from_journal(entries: &[JournalEntry]) -> Vec<Market> {
+    let mut markets: Vec<Market> = entries
+        .iter()
+        .filter(|e| e.kind == "market_opened")
+        .filter_map(|e| serde_json::from_value::<MarketOpened>(e.data.clone()).ok())
+        .map(|opened| Market {
+            condition_id: opened.market_id,
+            asset: opened.asset,
+            yes_token_id: opened.yes_token_id,
+            no_token_id: opened.no_token_id,
+            open_price: opened.open_price,
+            open_time: opened.open_time,
+            close_time: opened.close_time,
+            group_id: None,
+            orientation: opened.orientation,
+        })
+        .collect();
+    for inferred in entries.iter().filter(|e| e.kind == "tokens_inferred") {
+        let data = &inferred.data;
+        if data["reversed"] != true {
+            continue;
+        }
+        if let Some(market) = markets
+            .iter_mut()
+            .rev()
+            .find(|m| data["market_id"] == m.condition_id.as_str())
+        {
+            if data["yes_token_id"] != market.yes_token_id.as_str() {
+                market.swap_tokens();
+            }
+        }
+    }
+    markets
+}
+
+/// Collects one market's series and markers
+pub struct TimelineBuilder {
+    market: Market,
+    resolution_ms: i64,
+    model: GbmModel,
+    volatility: VolatilityEstimator,
+    spot: Vec<TimelinePoint>,
+    yes_ask: Vec<TimelinePoint>,
+    expected_price: Vec<TimelinePoint>,
+    events: Vec<TimelineEvent>,
+}
+
+impl TimelineBuilder {
+    /// Build the timeline of `market`, bucketing series by `resolution`
+    ///
+    /// `volatility` should be configured like the engine's estimator so the
+    /// expected price matches what the engine saw.
+    pub fn new(market: Market, resolution: Duration, volatility: VolatilityEstimator) -> Self {
+        Self {
+            market,
+            resolution_ms: resolution.num_milliseconds(),
+            model: GbmModel::new(),
+            volatility,
+            spot: Vec::new(),
+            yes_ask: Vec::new(),
+            expected_price: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn in_window(&self, timestamp: DateTime<Utc>) -> bool {
+        timestamp >= self.market.open_time && timestamp <= self.market.close_time
+    }
+
+    /// Add a spot price; ticks before the open only warm up the volatility
+    pub fn on_tick(&mut self, timestamp: DateTime<Utc>, price: Decimal) {
+        self.volatility.update(timestamp, price);
+        if !self.in_window(timestamp) {
+            return;
+        }
+        let ts = timestamp.timestamp_millis();
+        self.spot.push((ts, price.to_f64().unwrap_or_default()));
+        if let Some(volatility) = self.volatility.estimate() {
+            let fair = self.model.calculate(FairValueParams {
+                current_price: price,
+                open_price: self.market.open_price,
+                time_to_expiry: self.market.close_time - timestamp,
+                volatility,
+            });
+            self.expected_price
+                .push((ts, fair.yes_prob.round_dp(4).to_f64().unwrap_or_default()));
+        }
+    }
+
+    /// Add a book update; only the market's YES book is plotted
+    pub fn on_book(&mut self, timestamp: DateTime<Utc>, book: &OrderBook) {
+        if book.token_id != self.market.yes_token_id || !self.in_window(timestamp) {
+            return;
+        }
+        if let Some(ask) = book.best_ask() {
+            self.yes_ask.push((
+                timestamp.timestamp_millis(),
+                ask.to_f64().unwrap_or_default(),
+            ));
+        }
+    }
+
+    /// Add a trade journal entry; entries of other markets are ignored
+    pub fn on_journal(&mut self, entry: &JournalEntry) {
+        let data = &entry.data;
+        if data["market_id"] != self.market.condition_id.as_str() {
+            return;
+        }
+        let (kind, detail) = match entry.kind.as_str() {
+            "signal_rejected" => (TimelineEventKind::SignalRejected, {
+                let reason = text(data, "reason");
+                format!(
+                    "{} {} (edge {})",
+                    text(data, "side"),
+                    reason_code(&reason).unwrap_or(&reason),
+                    text(data, "edge")
+                )
+            }),
+            "order_submitted" => (
+                TimelineEventKind::Order,
+                format!(
+                    "buy {} {} @ {}",
+                    text(data, "size"),
+                    text(data, "side"),
+                    text(data, "price")
+                ),
+            ),
+            "position_opened" => (
+                TimelineEventKind::Fill,
+                format!(
+                    "filled {} {} @ {}",
+                    text(data, "size"),
+                    text(data, "side"),
+                    text(data, "price")
+                ),
+            ),
+            "market_settled" => (
+                TimelineEventKind::Resolution,
+                format!("{} won, pnl {}", text(data, "winner"), text(data, "pnl")),
+            ),
+            _ => return,
+        };
+        self.events.push(TimelineEvent {
+            ts: entry.ts.timestamp_millis(),
+            kind,
+            detail,
+        });
+    }
+
+    /// Downsample the series and order the markers
+    pub fn finish(self) -> MarketTimeline {
+        let mut events = vec![TimelineEvent {
+            ts: self.market.open_time.timestamp_millis(),
+            kind: TimelineEventKind::MarketOpen,
+            detail: format!("open {}", self.market.open_price),
+        }];
+        events.extend(self.events);
+        events.sort_by_key(|e| e.ts);
+
+        MarketTimeline {
+            market_id: self.market.condition_id,
+            yes_token_id: self.market.yes_token_id,
+            open_price: self.market.open_price,
+            open_time: self.market.open_time.timestamp_millis(),
+            close_time: self.market.close_time.timestamp_millis(),
+            resolution_ms: self.resolution_ms,
+            spot: downsample(self.spot, self.resolution_ms),
+            yes_ask: downsample(self.yes_ask, self.resolution_ms),
+            expected_price: downsample(self.expected_price, self.resolution_ms),
+            events,
+        }
+    }
+}
+
+/// Keep the last point of every `resolution_ms` bucket
+fn downsample(points: Vec<TimelinePoint>, resolution_ms: i64) -> Vec<TimelinePoint> {
+    if resolution_ms <= 1 {
+        return points;
+    }
+    let mut kept: Vec<TimelinePoint> = Vec::with_capacity(points.len());
+    for point in points {
+        match kept.last_mut() {
+            Some(last) if last.0.div_euclid(resolution_ms) == point.0.div_euclid(resolution_ms) => {
+                *last = point
+            }
+            _ => kept.push(point),
+        }
+    }
+    kept
+}
+
+/// A journal field as plain text
+fn text(data: &serde_json::Value, key: &str) -> String {
+    match &data[key] {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => "?".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Build the timeline of `market_id` from a session directory
+///
+/// The directory is the one `run` writes to: the trade journal and the
+/// captured price ticks and order books.
+pub fn load_timeline(
+    session: &Path,
+    market_id: &str,
+    resolution: Duration,
+    volatility: VolatilityEstimator,
+) -> anyhow::Result<MarketTimeline> {
+    let entries = Journal::read_all(session.join("trade_journal.jsonl"))?;
+    let market = market_from_journal(&entries, market_id).ok_or_else(|| {
+        anyhow::anyhow!("No market_opened entry for {} in {:?}", market_id, session)
+    })?;
+    let yes_token_id = market.yes_token_id.clone();
+    let mut builder = TimelineBuilder::new(market, resolution, volatility);
+
+    let mut ticks = vec![];
+    for file in scan_data_files(session, true)? {
+        if file.prefix != "price_ticks" {
+            continue;
+        }
+        for batch in read_batches(&file.path)? {
+            ticks.extend(price_ticks_from_batch(&batch)?);
+        }
+    }
+    ticks.sort_by_key(|t| t.exchange_ts);
+    for tick in &ticks {
+        builder.on_tick(tick.exchange_ts, tick.price);
+    }
+
+    let mut books = OrderBookManager::new();
+    for record in load_book_records(session, &yes_token_id)? {
+        let levels = |side: &[(Decimal, Decimal)]| -> Vec<PriceLevel> {
+            side.iter()
+                .map(|&(price, size)| PriceLevel { price, size })
+                .collect()
+        };
+        let book = books.merge_update(
+            &record.token_id,
+            record.kind,
+            &levels(&record.bids),
+            &levels(&record.asks),
+            record.timestamp,
+        );
+        builder.on_book(record.timestamp, book);
+    }
+
+    for entry in &entries {
+        builder.on_journal(entry);
+    }
+    Ok(builder.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    const GOLDEN: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/golden/report/timeline.json"
+    );
+
+    fn ts(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn entry(secs: i64, kind: &str, data: serde_json::Value) -> JournalEntry {
+        JournalEntry {
+            seq: 0,
+            ts: ts(secs),
+            kind: kind.to_string(),
+            data,
+        }
+    }
+
+    /// Journal of a five-minute market entered on a rally, plus a second
+    /// market whose entries must not leak into the first's timeline
+    fn journal() -> Vec<JournalEntry> {
+        let opened = |id: &str, token: &str| {
+            serde_json::json!({
+                "market_id": id,
+                "yes_token_id": token,
+                "no_token_id": format!("{}-no", token),
+                "open_price": "100000",
+                "open_time": ts(0),
+                "close_time": ts(300),
+                "engine": "paper",
+            })
+        };
+        vec![
+            entry(0, "market_opened", opened("m1", "yes-1")),
+            entry(0, "market_opened", opened("m2", "yes-2")),
+            entry(
+                45,
+                "signal_rejected",
+                serde_json::json!({"market_id": "m1", "side": "yes",
+                    "reason": "edge_too_small", "edge": "0.012"}),
+            ),
+            entry(
+                95,
+                "order_submitted",
+                serde_json::json!({"market_id": "m1", "side": "yes",
+                    "price": "0.55", "size": "20"}),
+            ),
+            entry(
+                95,
+                "position_opened",
+                serde_json::json!({"market_id": "m1", "side": "yes",
+                    "price": "0.55", "size": "20", "fee": "0"}),
+            ),
+            entry(
+                120,
+                "order_submitted",
+                serde_json::json!({"market_id": "m2", "side": "no",
+                    "price": "0.40", "size": "5"}),
+            ),
+            entry(
+                300,
+                "market_settled",
+                serde_json::json!({"market_id": "m1", "winner": "yes",
+                    "positions": 1, "pnl": "9"}),
+            ),
+        ]
+    }
+
+    /// Spot drifts up from the open with a small wobble, sampled each
+    /// second from a minute before the open; the YES ask follows every 5s
+    fn synthetic_timeline() -> MarketTimeline {
+        let entries = journal();
+        let market = market_from_journal(&entries, "m1").unwrap();
+        let mut builder = TimelineBuilder::new(
+            market,
+            Duration::seconds(30),
+            VolatilityEstimator::new(Duration::minutes(5)),
+        );
+        for secs in -60i64..=300 {
+            let wobble = Decimal::from((secs * 37).rem_euclid(50) - 25);
+            let price = dec!(100000) + Decimal::from(secs.max(0)) * dec!(0.5) + wobble;
+            builder.on_tick(ts(secs), price);
+            if secs % 5 == 0 {
+                let mut book = OrderBook::new("yes-1");
+                let ask = dec!(0.50) + Decimal::from(secs.max(0) / 30) * dec!(0.01);
+                book.asks = vec![PriceLevel {
+                    price: ask,
+                    size: dec!(100),
+                }];
+                builder.on_book(ts(secs), &book);
+            }
+        }
+        for entry in &entries {
+            builder.on_journal(entry);
+        }
+        builder.finish()
+    }
+
+    #[test]
+    fn test_golden_timeline() {
+        let timeline = synthetic_timeline();
+        assert_eq!(timeline.spot.len(), 11);
+        assert_eq!(timeline.yes_ask.len(), 11);
+        assert!(timeline.events.iter().all(|e| !e.detail.contains("0.40")));
+
+        let rendered = format!("{}\n", serde_json::to_string_pretty(&timeline).unwrap());
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::create_dir_all(Path::new(GOLDEN).parent().unwrap()).unwrap();
+            std::fs::write(GOLDEN, &rendered).unwrap();
+        }
+        assert_eq!(
+            std::fs::read_to_string(GOLDEN).unwrap(),
+            rendered,
+            "timeline.json is stale; rerun this test with BLESS=1"
+        );
+    }
+
+    #[test]
+    fn test_downsample_keeps_last_point_per_bucket() {
+        let points = vec![(0, 1.0), (400, 2.0), (999, 3.0), (1000, 4.0), (2500, 5.0)];
+        assert_eq!(
+            downsample(points.clone(), 1000),
+            vec![(999, 3.0), (1000, 4.0), (2500, 5.0)]
+        );
+        assert_eq!(downsample(points.clone(), 0), points);
+    }
+}
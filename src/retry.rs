@@ -0,0 +1,662 @@
+//! Retries of network calls
+//!
+//! [`retry`] runs an operation until it succeeds, fails with an error its
+//! [`RetryPolicy`] does not retry, runs out of attempts, or passes the
+//! policy's deadline. Between attempts it waits a backoff that doubles from
+//! `base_delay` up to `max_delay`, jittered as the policy says so clients
+//! failing together do not come back together. Each call is counted in
+//! `polyhft_retry_attempts` and `polyhft_retry_seconds` by subsystem.
+//!
+//! A policy may draw on its subsystem's shared [`RetryBudget`], which caps
+//! how many operations retry at once. During an outage every caller fails;
+//! without a cap each of them would keep retrying, and the load on a
+//! struggling service would grow with the number of callers. An operation
+//! takes a slot of the budget at its first failure and holds it until its
+//! outcome. When none is free it fails at once, counted in
+//! `polyhft_retry_budget_exhausted_total`.
+
+use crate::duration::{DurationConfig, Millis};
+use crate::telemetry::{record_retry, record_retry_budget_exhausted};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Default attempts of a configured policy: one, so nothing is retried
+/// unless the config asks for it
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 1;
+
+/// Default wait before the first retry
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 250;
+
+/// Default longest wait between attempts
+pub const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 5_000;
+
+/// Default operations of one subsystem retrying at once
+pub const DEFAULT_RETRY_BUDGET: usize = 8;
+
+/// How a backoff is randomized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Jitter {
+    /// Wait exactly the backoff
+    None,
+    /// Wait anywhere from nothing to the backoff
+    #[default]
+    Full,
+    /// Wait at least half the backoff, the rest drawn at random
+    Equal,
+}
+
+impl Jitter {
+    /// A wait drawn for `backoff`
+    pub fn apply(self, backoff: Duration, rng: &mut impl Rng) -> Duration {
+        let millis = backoff.as_millis() as u64;
+        match self {
+            Jitter::None => backoff,
+            Jitter::Full => Duration::from_millis(rng.gen_range(0..=millis)),
+            Jitter::Equal => {
+                Duration::from_millis(millis / 2 + rng.gen_range(0..=millis - millis / 2))
+            }
+        }
+    }
+}
+
+/// When and how often to retry a failing operation with error `E`
+pub struct RetryPolicy<E> {
+    /// Name of the subsystem, labelling metrics and the budget drawn on
+    pub subsystem: &'static str,
+    /// Attempts in all, the first included; 0 retries until the deadline
+    pub max_attempts: u32,
+    /// Backoff before the first retry
+    pub base_delay: Duration,
+    /// Longest backoff
+    pub max_delay: Duration,
+    /// How each backoff is randomized
+    pub jitter: Jitter,
+    /// Longest a call may take, attempts and waits together
+    pub deadline: Option<Duration>,
+    /// Time by which every call must end, whenever it starts
+    pub until: Option<Instant>,
+    /// Whether an error is worth another attempt
+    pub retryable: fn(&E) -> bool,
+    /// Budget of the subsystem, when its retries are capped
+    pub budget: Option<Arc<RetryBudget>>,
+}
+
+impl<E> RetryPolicy<E> {
+    /// A policy for `subsystem` retrying every error with the config
+    /// defaults, drawing on the subsystem's shared budget
+    pub fn new(subsystem: &'static str) -> Self {
+        Self {
+            subsystem,
+            max_attempts: DEFAULT_RETRY_ATTEMPTS,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS),
+            max_delay: Duration::from_millis(DEFAULT_RETRY_MAX_DELAY_MS),
+            jitter: Jitter::default(),
+            deadline: None,
+            until: None,
+            retryable: |_| true,
+            budget: Some(RetryBudget::shared(subsystem)),
+        }
+    }
+
+    /// A policy for `subsystem` from `config`, which also sets the limit
+    /// of the subsystem's shared budget
+    pub fn from_config(subsystem: &'static str, config: &RetryConfig) -> Self {
+        let policy = Self {
+            max_attempts: config.max_attempts,
+            base_delay: config.base_delay_ms.get(),
+            max_delay: config.max_delay_ms.get(),
+            jitter: config.jitter,
+            deadline: config.deadline_ms.map(|d| d.get()),
+            ..Self::new(subsystem)
+        };
+        if let Some(budget) = &policy.budget {
+            budget.set_limit(config.budget);
+        }
+        policy
+    }
+
+    /// Set the attempts in all; 0 retries until the deadline
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Set the first and the longest backoff
+    pub fn delays(mut self, base: Duration, max: Duration) -> Self {
+        self.base_delay = base;
+        self.max_delay = max;
+        self
+    }
+
+    /// Set how backoffs are randomized
+    pub fn jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Give each call at most `deadline`
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// End every call by `until`, as well as within the deadline
+    pub fn until(mut self, until: Instant) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Retry only errors for which `retryable` holds
+    pub fn retry_if(mut self, retryable: fn(&E) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Draw on `budget` instead of the subsystem's shared one
+    pub fn with_budget(mut self, budget: Arc<RetryBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Retry without a budget, for an operation that is never run more
+    /// than once at a time
+    pub fn unbudgeted(mut self) -> Self {
+        self.budget = None;
+        self
+    }
+
+    /// Backoff before retry number `retry`, counting from 1, before jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let doublings = retry.saturating_sub(1).min(31);
+        self.base_delay
+            .saturating_mul(1 << doublings)
+            .min(self.max_delay)
+    }
+}
+
+// By hand, as derives would ask the error type to be `Clone` and `Debug`
+impl<E> Clone for RetryPolicy<E> {
+    fn clone(&self) -> Self {
+        Self {
+            budget: self.budget.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<E> fmt::Debug for RetryPolicy<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("subsystem", &self.subsystem)
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("jitter", &self.jitter)
+            .field("deadline", &self.deadline)
+            .field("until", &self.until)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+/// Retry settings of a subsystem in the config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Attempts in all, the first included; 0 retries until the deadline
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubling with each one after
+    #[serde(default = "default_base_delay_ms")]
+    pub base_delay_ms: DurationConfig<Millis>,
+    /// Longest backoff
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: DurationConfig<Millis>,
+    /// How backoffs are randomized
+    #[serde(default)]
+    pub jitter: Jitter,
+    /// Longest one call may take, attempts and waits together
+    #[serde(default)]
+    pub deadline_ms: Option<DurationConfig<Millis>>,
+    /// Operations of the subsystem retrying at once; past it, a failure is
+    /// returned at once
+    #[serde(default = "default_budget")]
+    pub budget: usize,
+}
+
+fn default_max_attempts() -> u32 {
+    DEFAULT_RETRY_ATTEMPTS
+}
+
+fn default_base_delay_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_RETRY_BASE_DELAY_MS)
+}
+
+fn default_max_delay_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_RETRY_MAX_DELAY_MS)
+}
+
+fn default_budget() -> usize {
+    DEFAULT_RETRY_BUDGET
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            base_delay_ms: default_base_delay_ms(),
+            max_delay_ms: default_max_delay_ms(),
+            jitter: Jitter::default(),
+            deadline_ms: None,
+            budget: default_budget(),
+        }
+    }
+}
+
+/// Cap on the operations of one subsystem retrying at once
+#[derive(Debug)]
+pub struct RetryBudget {
+    subsystem: &'static str,
+    limit: AtomicUsize,
+    in_use: AtomicUsize,
+    exhausted: AtomicU64,
+}
+
+impl RetryBudget {
+    /// A budget of its own letting `limit` operations retry at once
+    pub fn new(subsystem: &'static str, limit: usize) -> Arc<Self> {
+        Arc::new(Self {
+            subsystem,
+            limit: AtomicUsize::new(limit),
+            in_use: AtomicUsize::new(0),
+            exhausted: AtomicU64::new(0),
+        })
+    }
+
+    /// The budget every policy of `subsystem` shares, created with
+    /// [`DEFAULT_RETRY_BUDGET`] on first use
+    pub fn shared(subsystem: &'static str) -> Arc<Self> {
+        static BUDGETS: OnceLock<Mutex<HashMap<&'static str, Arc<RetryBudget>>>> = OnceLock::new();
+        BUDGETS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(subsystem)
+            .or_insert_with(|| Self::new(subsystem, DEFAULT_RETRY_BUDGET))
+            .clone()
+    }
+
+    /// Let `limit` operations retry at once; ones already retrying keep
+    /// their slots
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Operations retrying now
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::Relaxed)
+    }
+
+    /// Failures returned at once for want of a slot
+    pub fn exhausted(&self) -> u64 {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+
+    /// A slot, held until dropped; `None` when all are taken
+    fn try_acquire(self: &Arc<Self>) -> Option<BudgetSlot> {
+        let limit = self.limit.load(Ordering::Relaxed);
+        let taken = self
+            .in_use
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < limit).then_some(n + 1)
+            })
+            .is_ok();
+        if !taken {
+            self.exhausted.fetch_add(1, Ordering::Relaxed);
+            record_retry_budget_exhausted(self.subsystem);
+        }
+        taken.then(|| BudgetSlot(self.clone()))
+    }
+}
+
+/// A retrying operation's slot of its budget
+struct BudgetSlot(Arc<RetryBudget>);
+
+impl Drop for BudgetSlot {
+    fn drop(&mut self) {
+        self.0.in_use.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Why [`retry`] gave up
+#[derive(Debug)]
+pub enum RetryError<E> {
+    /// The operation failed with an error the policy does not retry
+    Permanent(E),
+    /// Every attempt allowed failed; the last error
+    Exhausted { attempts: u32, error: E },
+    /// The deadline passed; the last error, if an attempt had finished
+    DeadlineExceeded { attempts: u32, error: Option<E> },
+    /// No slot of the subsystem's budget was free to retry the first error
+    BudgetExhausted { subsystem: &'static str, error: E },
+}
+
+impl<E> RetryError<E> {
+    /// The last error the operation returned, if any
+    pub fn into_inner(self) -> Option<E> {
+        match self {
+            RetryError::Permanent(error)
+            | RetryError::Exhausted { error, .. }
+            | RetryError::BudgetExhausted { error, .. } => Some(error),
+            RetryError::DeadlineExceeded { error, .. } => error,
+        }
+    }
+}
+
+impl RetryError<anyhow::Error> {
+    /// The last error with why retrying stopped as context, keeping its
+    /// chain of causes
+    pub fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            RetryError::Permanent(error) | RetryError::Exhausted { attempts: 1, error } => error,
+            RetryError::Exhausted { attempts, error } => {
+                error.context(format!("gave up after {} attempts", attempts))
+            }
+            RetryError::DeadlineExceeded { attempts, error } => {
+                let context = format!("deadline passed after {} attempts", attempts);
+                match error {
+                    Some(error) => error.context(context),
+                    None => anyhow::anyhow!(context),
+                }
+            }
+            RetryError::BudgetExhausted { subsystem, error } => {
+                error.context(format!("retry budget of {} spent", subsystem))
+            }
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryError::Permanent(error) | RetryError::Exhausted { attempts: 1, error } => {
+                write!(f, "{}", error)
+            }
+            RetryError::Exhausted { attempts, error } => {
+                write!(f, "{} (gave up after {} attempts)", error, attempts)
+            }
+            RetryError::DeadlineExceeded { attempts, error } => {
+                write!(f, "deadline passed after {} attempts", attempts)?;
+                match error {
+                    Some(error) => write!(f, ": {}", error),
+                    None => Ok(()),
+                }
+            }
+            RetryError::BudgetExhausted { subsystem, error } => {
+                write!(f, "{} (retry budget of {} spent)", error, subsystem)
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for RetryError<E> {}
+
+/// Run `op` under `policy` until it succeeds or the policy gives up
+///
+/// `op` is passed the attempt number, counting from 1.
+pub async fn retry<T, E, F, Fut>(policy: &RetryPolicy<E>, mut op: F) -> Result<T, RetryError<E>>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let started = Instant::now();
+    let deadline = match (policy.deadline.map(|d| started + d), policy.until) {
+        (Some(deadline), Some(until)) => Some(deadline.min(until)),
+        (deadline, until) => deadline.or(until),
+    };
+    let mut slot = None;
+    let mut attempt = 0;
+    let outcome = loop {
+        attempt += 1;
+        let result = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, op(attempt)).await {
+                Ok(result) => result,
+                Err(_) => {
+                    break Err(RetryError::DeadlineExceeded {
+                        attempts: attempt,
+                        error: None,
+                    })
+                }
+            },
+            None => op(attempt).await,
+        };
+        let error = match result {
+            Ok(value) => break Ok(value),
+            Err(error) => error,
+        };
+        if !(policy.retryable)(&error) {
+            break Err(RetryError::Permanent(error));
+        }
+        if policy.max_attempts > 0 && attempt >= policy.max_attempts {
+            break Err(RetryError::Exhausted {
+                attempts: attempt,
+                error,
+            });
+        }
+        if let (None, Some(budget)) = (&slot, &policy.budget) {
+            slot = budget.try_acquire();
+            if slot.is_none() {
+                break Err(RetryError::BudgetExhausted {
+                    subsystem: policy.subsystem,
+                    error,
+                });
+            }
+        }
+
+        let wait = policy
+            .jitter
+            .apply(policy.backoff(attempt), &mut rand::thread_rng());
+        if deadline.is_some_and(|d| Instant::now() + wait >= d) {
+            break Err(RetryError::DeadlineExceeded {
+                attempts: attempt,
+                error: Some(error),
+            });
+        }
+        tracing::debug!(
+            subsystem = policy.subsystem,
+            attempt,
+            wait_ms = wait.as_millis() as u64,
+            "Retrying after failure"
+        );
+        tokio::time::sleep(wait).await;
+    };
+    record_retry(policy.subsystem, attempt, started.elapsed());
+    outcome
+}
+
+/// Whether an HTTP call failed in a way another attempt could fix: a
+/// timeout, a refused or dropped connection, or a 429 or 5xx response
+pub fn transient_http(error: &anyhow::Error) -> bool {
+    let Some(error) = error.downcast_ref::<reqwest::Error>() else {
+        return false;
+    };
+    match error.status() {
+        Some(status) => status.as_u16() == 429 || status.is_server_error(),
+        None => !error.is_decode() && !error.is_builder(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::sync::atomic::AtomicU32;
+
+    #[derive(Debug, PartialEq)]
+    enum Failure {
+        Transient,
+        Fatal,
+    }
+
+    impl fmt::Display for Failure {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
+    fn policy() -> RetryPolicy<Failure> {
+        RetryPolicy::new("test")
+            .max_attempts(5)
+            .delays(Duration::from_millis(100), Duration::from_secs(1))
+            .jitter(Jitter::None)
+            .retry_if(|e| *e == Failure::Transient)
+            .with_budget(RetryBudget::new("test", 8))
+    }
+
+    /// Fails with `errors` in turn, then succeeds with the attempt number
+    async fn run(
+        policy: &RetryPolicy<Failure>,
+        errors: Vec<Failure>,
+    ) -> (Result<u32, RetryError<Failure>>, Duration) {
+        let started = Instant::now();
+        let errors = Mutex::new(errors.into_iter());
+        let result = retry(policy, |attempt| {
+            let next = errors.lock().unwrap().next();
+            async move { next.map_or(Ok(attempt), Err) }
+        })
+        .await;
+        (result, started.elapsed())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_back_off_until_success_or_attempts_run_out() {
+        use Failure::*;
+        let (result, took) = run(&policy(), vec![Transient, Transient]).await;
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(took, Duration::from_millis(300));
+
+        let longer = policy().max_attempts(6);
+        let (result, took) = run(&longer, (0..9).map(|_| Transient).collect()).await;
+        let error = result.unwrap_err();
+        assert_eq!(error.to_string(), "Transient (gave up after 6 attempts)");
+        assert!(matches!(error, RetryError::Exhausted { attempts: 6, .. }));
+        // 100, 200, 400, 800, then capped at a second
+        assert_eq!(took, Duration::from_millis(2_500));
+        assert_eq!(policy().backoff(30), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_non_retryable_error_aborts_at_once() {
+        use Failure::*;
+        let (result, took) = run(&policy(), vec![Transient, Fatal, Transient]).await;
+        assert!(matches!(result, Err(RetryError::Permanent(Fatal))));
+        assert_eq!(took, Duration::from_millis(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_bounds_attempts_and_waits() {
+        // Never waits past the deadline for an attempt it could not make
+        let forever = policy()
+            .max_attempts(0)
+            .deadline(Duration::from_millis(1_000));
+        let (result, took) = run(&forever, (0..99).map(|_| Failure::Transient).collect()).await;
+        match result.unwrap_err() {
+            RetryError::DeadlineExceeded { attempts, error } => {
+                assert_eq!(attempts, 4);
+                assert_eq!(error, Some(Failure::Transient));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(took, Duration::from_millis(700));
+
+        // A hung attempt is cut off at the deadline
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+        let result: Result<(), _> = retry(&forever, |_| {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            std::future::pending::<Result<(), Failure>>()
+        })
+        .await;
+        assert!(matches!(
+            result,
+            Err(RetryError::DeadlineExceeded {
+                attempts: 1,
+                error: None
+            })
+        ));
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let backoff = Duration::from_millis(1_000);
+        assert_eq!(Jitter::None.apply(backoff, &mut rng), backoff);
+        let full: Vec<_> = (0..1_000)
+            .map(|_| Jitter::Full.apply(backoff, &mut rng))
+            .collect();
+        let equal: Vec<_> = (0..1_000)
+            .map(|_| Jitter::Equal.apply(backoff, &mut rng))
+            .collect();
+        assert!(full.iter().all(|d| *d <= backoff));
+        assert!(full.iter().any(|d| *d < Duration::from_millis(100)));
+        assert!(equal.iter().all(|d| *d >= backoff / 2 && *d <= backoff));
+        assert!(equal.iter().any(|d| *d > Duration::from_millis(900)));
+        assert_eq!(Jitter::Full.apply(Duration::ZERO, &mut rng), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spent_budget_fails_fast_until_a_slot_frees() {
+        let budget = RetryBudget::new("test", 1);
+        let policy = policy().with_budget(budget.clone());
+
+        // One operation retrying holds the only slot
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let rx = Mutex::new(Some(rx));
+        let holder = retry(&policy, |attempt| {
+            let rx = (attempt > 1).then(|| rx.lock().unwrap().take()).flatten();
+            async move {
+                match (attempt, rx) {
+                    (1, _) => Err(Failure::Transient),
+                    (_, Some(rx)) => rx.await.map_err(|_| Failure::Fatal),
+                    _ => Err(Failure::Fatal),
+                }
+            }
+        });
+        let other = async {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            assert_eq!(budget.in_use(), 1);
+            let (result, took) = run(&policy, vec![Failure::Transient]).await;
+            assert!(matches!(
+                result,
+                Err(RetryError::BudgetExhausted {
+                    subsystem: "test",
+                    error: Failure::Transient
+                })
+            ));
+            assert_eq!(took, Duration::ZERO);
+            tx.send(()).unwrap();
+        };
+        let (held, ()) = tokio::join!(holder, other);
+        assert!(held.is_ok());
+        assert_eq!(budget.in_use(), 0);
+        assert_eq!(budget.exhausted(), 1);
+
+        // Freed, the next operation retries
+        let (result, _) = run(&policy, vec![Failure::Transient]).await;
+        assert_eq!(result.unwrap(), 2);
+        // Succeeding first time takes no slot
+        budget.set_limit(0);
+        let (result, _) = run(&policy, vec![]).await;
+        assert_eq!(result.unwrap(), 1);
+    }
+}
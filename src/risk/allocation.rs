@@ -0,0 +1,450 @@
+//! Ranking concurrent signals for limited capital
+//!
+//! Markets of several intervals can be inside their trading window at
+//! once, and the position slots and cash left may not cover a signal in
+//! each. When more markets could fire than there is room for, a signal
+//! that would trade is held as a [`Candidate`] for `window_ms`; signals of
+//! other markets arriving meanwhile join it. Once the window closes the
+//! candidates are ranked by [`Score`] and the best taken, while slots and
+//! cash last, rather than the first to fire. With room for every market,
+//! signals trade at once as before.
+//!
+//! A score is the weighted sum of five parts, each scaled to 0..1: the
+//! adjusted edge against [`EDGE_SCALE`], the model confidence, the depth
+//! within 2 cents as a multiple of the order against [`DEPTH_SCALE`], the
+//! share of the market's window still to run, and the win rate of signals
+//! on markets of the same asset and interval. A bucket with little history
+//! starts from `prior_win_rate`, weighted as `prior_signals` signals, so
+//! the first signals of a new interval are neither favoured nor shut out.
+
+use crate::duration::{DurationConfig, Millis};
+use crate::market::Market;
+use crate::precision::round_pct;
+use crate::signal::Signal;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Default time competing signals are gathered, in milliseconds
+pub const DEFAULT_ALLOCATION_WINDOW_MS: u64 = 500;
+
+/// Default signals of history the prior win rate counts as
+pub const DEFAULT_PRIOR_SIGNALS: u64 = 20;
+
+/// Adjusted edge scoring full marks
+pub const EDGE_SCALE: Decimal = dec!(0.10);
+
+/// Depth within 2 cents, as a multiple of the order, scoring full marks
+pub const DEPTH_SCALE: Decimal = dec!(5);
+
+/// How competing signals are scored, under `[risk.allocation]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct AllocationConfig {
+    /// How long a held signal waits for others to compete with it; zero
+    /// ranks only signals of the same moment
+    #[serde(default = "default_window_ms")]
+    pub window_ms: DurationConfig<Millis>,
+    /// Weight of the adjusted edge
+    #[serde(default = "default_edge_weight")]
+    pub edge_weight: Decimal,
+    /// Weight of the model confidence
+    #[serde(default = "default_confidence_weight")]
+    pub confidence_weight: Decimal,
+    /// Weight of the depth near the touch
+    #[serde(default = "default_depth_weight")]
+    pub depth_weight: Decimal,
+    /// Weight of the share of the window left
+    #[serde(default = "default_time_weight")]
+    pub time_weight: Decimal,
+    /// Weight of the asset and interval's win rate
+    #[serde(default = "default_win_rate_weight")]
+    pub win_rate_weight: Decimal,
+    /// Win rate assumed for an asset and interval with no history
+    #[serde(default = "default_prior_win_rate")]
+    pub prior_win_rate: Decimal,
+    /// Signals of history the prior counts as
+    #[serde(default = "default_prior_signals")]
+    pub prior_signals: u64,
+}
+
+fn default_window_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_ALLOCATION_WINDOW_MS)
+}
+
+fn default_edge_weight() -> Decimal {
+    dec!(0.35)
+}
+
+fn default_confidence_weight() -> Decimal {
+    dec!(0.15)
+}
+
+fn default_depth_weight() -> Decimal {
+    dec!(0.15)
+}
+
+fn default_time_weight() -> Decimal {
+    dec!(0.15)
+}
+
+fn default_win_rate_weight() -> Decimal {
+    dec!(0.20)
+}
+
+fn default_prior_win_rate() -> Decimal {
+    dec!(0.5)
+}
+
+fn default_prior_signals() -> u64 {
+    DEFAULT_PRIOR_SIGNALS
+}
+
+impl Default for AllocationConfig {
+    fn default() -> Self {
+        Self {
+            window_ms: default_window_ms(),
+            edge_weight: default_edge_weight(),
+            confidence_weight: default_confidence_weight(),
+            depth_weight: default_depth_weight(),
+            time_weight: default_time_weight(),
+            win_rate_weight: default_win_rate_weight(),
+            prior_win_rate: default_prior_win_rate(),
+            prior_signals: default_prior_signals(),
+        }
+    }
+}
+
+/// A signal's score and its parts, each 0..1 before weighting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Score {
+    pub edge: Decimal,
+    pub confidence: Decimal,
+    pub depth: Decimal,
+    pub time: Decimal,
+    pub win_rate: Decimal,
+    /// Weighted sum of the parts
+    pub total: Decimal,
+}
+
+/// A signal held to compete for capital, with what entering it takes
+#[derive(Debug, Clone)]
+pub struct Candidate<T> {
+    pub market_id: String,
+    pub signal_id: Uuid,
+    pub score: Score,
+    /// Dollars the entry commits
+    pub cost: Decimal,
+    pub offered_at: DateTime<Utc>,
+    /// What the caller needs to enter it
+    pub entry: T,
+}
+
+/// Why a ranked candidate was not taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Every free position slot went to a better candidate
+    NoSlot,
+    /// The cash left does not cover it
+    NoCapital,
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::NoSlot => "no_slot",
+            SkipReason::NoCapital => "no_capital",
+        }
+    }
+}
+
+/// One line of a ranking, best first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Ranked {
+    pub rank: usize,
+    pub market_id: String,
+    pub signal_id: Uuid,
+    pub score: Score,
+    pub cost: Decimal,
+    /// Why it was not taken; none when it was
+    pub skipped: Option<SkipReason>,
+}
+
+/// Candidates ranked at the close of a window
+#[derive(Debug, Clone)]
+pub struct Allocation<T> {
+    /// Every candidate, best first
+    pub ranking: Vec<Ranked>,
+    /// Candidates taken, best first
+    pub selected: Vec<Candidate<T>>,
+    /// Candidates not taken, best first
+    pub skipped: Vec<(Candidate<T>, SkipReason)>,
+}
+
+/// Holds signals competing for capital and ranks them
+#[derive(Debug, Clone)]
+pub struct Allocator<T> {
+    config: AllocationConfig,
+    /// In the order offered, so equal scores go to the first
+    pending: Vec<Candidate<T>>,
+    /// When the first pending candidate was offered
+    opened_at: Option<DateTime<Utc>>,
+}
+
+impl<T> Allocator<T> {
+    pub fn new(config: AllocationConfig) -> Self {
+        Self {
+            config,
+            pending: vec![],
+            opened_at: None,
+        }
+    }
+
+    /// Win rate of a bucket with `signals` followed and `wins` among them,
+    /// pulled toward the prior while the history is short
+    pub fn win_rate(&self, signals: u64, wins: u64) -> Decimal {
+        let prior = Decimal::from(self.config.prior_signals);
+        let weight = prior + Decimal::from(signals);
+        if weight.is_zero() {
+            return self.config.prior_win_rate;
+        }
+        (self.config.prior_win_rate * prior + Decimal::from(wins)) / weight
+    }
+
+    /// Score `signal` entered with `size` shares at `now`, given the
+    /// `signals` and `wins` of its asset and interval
+    pub fn score(
+        &self,
+        signal: &Signal,
+        size: Decimal,
+        now: DateTime<Utc>,
+        signals: u64,
+        wins: u64,
+    ) -> Score {
+        let unit = |value: Decimal| value.clamp(Decimal::ZERO, Decimal::ONE);
+        let market = &signal.market;
+        let depth = if size.is_zero() {
+            Decimal::ZERO
+        } else {
+            signal.depth.within_2c / size / DEPTH_SCALE
+        };
+        let mut score = Score {
+            edge: unit(signal.adjusted_edge / EDGE_SCALE),
+            confidence: unit(signal.confidence),
+            depth: unit(depth),
+            time: unit(time_left(market, now)),
+            win_rate: unit(self.win_rate(signals, wins)),
+            total: Decimal::ZERO,
+        };
+        let config = &self.config;
+        score.total = round_pct(
+            score.edge * config.edge_weight
+                + score.confidence * config.confidence_weight
+                + score.depth * config.depth_weight
+                + score.time * config.time_weight
+                + score.win_rate * config.win_rate_weight,
+        );
+        score
+    }
+
+    /// Whether a signal that would trade now must compete: a window is
+    /// already open, or more markets could fire than `slots` are free
+    pub fn is_contended(&self, slots: usize, competitors: usize) -> bool {
+        !self.pending.is_empty() || competitors > slots
+    }
+
+    /// Hold `candidate` until the window closes, in place of any earlier
+    /// one of its market; the first opens the window
+    pub fn offer(&mut self, candidate: Candidate<T>) {
+        self.opened_at.get_or_insert(candidate.offered_at);
+        match self
+            .pending
+            .iter_mut()
+            .find(|c| c.market_id == candidate.market_id)
+        {
+            Some(held) => *held = candidate,
+            None => self.pending.push(candidate),
+        }
+    }
+
+    /// Drop the candidate of a market that can no longer be entered
+    pub fn withdraw(&mut self, market_id: &str) -> Option<Candidate<T>> {
+        let at = self.pending.iter().position(|c| c.market_id == market_id)?;
+        let candidate = self.pending.remove(at);
+        if self.pending.is_empty() {
+            self.opened_at = None;
+        }
+        Some(candidate)
+    }
+
+    /// Candidates held
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether the window has closed on held candidates
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.opened_at
+            .is_some_and(|at| now - at >= self.config.window_ms.to_chrono())
+    }
+
+    /// Rank every held candidate and take the best while `slots` and
+    /// `capital` last; a candidate too dear for the cash left is skipped
+    /// and the next tried
+    pub fn allocate(&mut self, mut slots: usize, mut capital: Decimal) -> Allocation<T> {
+        self.opened_at = None;
+        let mut pending = std::mem::take(&mut self.pending);
+        // Stable, so equal scores keep the order they were offered in
+        pending.sort_by_key(|c| std::cmp::Reverse(c.score.total));
+        let mut allocation = Allocation {
+            ranking: Vec::with_capacity(pending.len()),
+            selected: vec![],
+            skipped: vec![],
+        };
+        for (i, candidate) in pending.into_iter().enumerate() {
+            let skipped = if slots == 0 {
+                Some(SkipReason::NoSlot)
+            } else if candidate.cost > capital {
+                Some(SkipReason::NoCapital)
+            } else {
+                None
+            };
+            allocation.ranking.push(Ranked {
+                rank: i + 1,
+                market_id: candidate.market_id.clone(),
+                signal_id: candidate.signal_id,
+                score: candidate.score,
+                cost: candidate.cost,
+                skipped,
+            });
+            match skipped {
+                Some(reason) => allocation.skipped.push((candidate, reason)),
+                None => {
+                    slots -= 1;
+                    capital -= candidate.cost;
+                    allocation.selected.push(candidate);
+                }
+            }
+        }
+        allocation
+    }
+}
+
+/// Share of `market`'s window still to run at `now`
+fn time_left(market: &Market, now: DateTime<Utc>) -> Decimal {
+    let window = (market.close_time - market.open_time).num_milliseconds();
+    if window <= 0 {
+        return Decimal::ZERO;
+    }
+    Decimal::from((market.close_time - now).num_milliseconds()) / Decimal::from(window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::DepthProfile;
+    use crate::signal::{Side, SignalReason};
+    use chrono::Duration;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_767_600_000, 0).unwrap()
+    }
+
+    fn signal(id: &str, minutes: i64, edge: Decimal) -> Signal {
+        let market = Market {
+            condition_id: id.to_string(),
+            asset: "BTC".to_string(),
+            open_time: t0() - Duration::minutes(minutes / 2),
+            close_time: t0() + Duration::minutes(minutes / 2),
+            yes_token_id: format!("{}-yes", id),
+            no_token_id: format!("{}-no", id),
+            open_price: dec!(100000),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        Signal::new(
+            market,
+            Side::Yes,
+            dec!(0.60),
+            dec!(0.50),
+            edge,
+            dec!(0.5),
+            SignalReason::SpotDivergence,
+        )
+        .with_depth(DepthProfile {
+            within_2c: dec!(100),
+            ..Default::default()
+        })
+    }
+
+    fn candidate(allocator: &Allocator<()>, signal: &Signal, cost: Decimal) -> Candidate<()> {
+        Candidate {
+            market_id: signal.market.condition_id.clone(),
+            signal_id: signal.id,
+            score: allocator.score(signal, dec!(10), t0(), 0, 0),
+            cost,
+            offered_at: t0(),
+            entry: (),
+        }
+    }
+
+    #[test]
+    fn test_cold_start_win_rate_leans_on_the_prior() {
+        let allocator = Allocator::<()>::new(AllocationConfig::default());
+        assert_eq!(allocator.win_rate(0, 0), dec!(0.5));
+        // Five straight wins move a 20-signal prior only part of the way
+        assert_eq!(allocator.win_rate(5, 5), dec!(0.6));
+        assert_eq!(allocator.win_rate(180, 36), dec!(0.23));
+
+        let score = allocator.score(&signal("a", 15, dec!(0.05)), dec!(10), t0(), 0, 0);
+        assert_eq!(score.edge, dec!(0.5));
+        assert_eq!(score.depth, dec!(1));
+        assert_eq!(score.time, dec!(0.5));
+        assert_eq!(score.win_rate, dec!(0.5));
+    }
+
+    #[test]
+    fn test_best_scores_take_the_slots_and_cash() {
+        let mut allocator = Allocator::new(AllocationConfig::default());
+        let weak = signal("btc-15m", 15, dec!(0.02));
+        let strong = signal("btc-1h", 60, dec!(0.08));
+        let dear = signal("btc-4h", 240, dec!(0.09));
+        let middling = signal("btc-5m", 5, dec!(0.05));
+        // Offered first-come in the order they fired
+        for (s, cost) in [
+            (&weak, dec!(5)),
+            (&strong, dec!(5)),
+            (&dear, dec!(50)),
+            (&middling, dec!(5)),
+        ] {
+            let c = candidate(&allocator, s, cost);
+            assert!(allocator.is_contended(2, 4));
+            allocator.offer(c);
+        }
+        assert!(!allocator.is_due(t0() + Duration::milliseconds(499)));
+        assert!(allocator.is_due(t0() + Duration::milliseconds(500)));
+
+        let allocation = allocator.allocate(2, dec!(20));
+        let order: Vec<_> = allocation
+            .ranking
+            .iter()
+            .map(|r| (r.market_id.as_str(), r.skipped))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("btc-4h", Some(SkipReason::NoCapital)),
+                ("btc-1h", None),
+                ("btc-5m", None),
+                ("btc-15m", Some(SkipReason::NoSlot)),
+            ]
+        );
+        assert_eq!(allocation.selected[0].market_id, "btc-1h");
+        assert_eq!(allocation.skipped.len(), 2);
+        assert_eq!(allocator.pending(), 0);
+        assert!(!allocator.is_contended(2, 2));
+    }
+}
@@ -0,0 +1,546 @@
+//! Bankroll ledger
+//!
+//! The bankroll is the balance of an append-only log of USDC movements
+//! rather than a number updated in place: deposits and withdrawals, the
+//! gross P&L and fees of each closed position, and adjustments found by
+//! reconciling with the exchange. In a session the log is
+//! `<data dir>/ledger.jsonl`, which `poly-hft ctl deposit`/`withdraw`
+//! append to while the bot runs; [`Ledger::sync`] picks those entries up
+//! so sizing follows the new balance.
+//!
+//! In live mode the collateral balance held at the exchange is compared
+//! with the ledger's cash, its balance less what open positions cost, and
+//! a drift beyond `[risk.ledger] drift_tolerance` is booked as an
+//! [`LedgerEntryKind::Adjustment`].
+
+use crate::duration::DurationConfig;
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Ledger of the bankroll, in the data directory
+pub const LEDGER_FILE: &str = "ledger.jsonl";
+
+/// Reference of the deposit opening a ledger with the configured bankroll
+pub const OPENING_REFERENCE: &str = "initial_bankroll";
+
+/// Default largest difference from the exchange balance left unbooked, USD
+pub const DEFAULT_DRIFT_TOLERANCE: Decimal = dec!(1);
+
+/// Default seconds between exchange balance checks in live mode
+pub const DEFAULT_BALANCE_CHECK_SECS: u64 = 300;
+
+/// Ledger settings, under `[risk.ledger]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LedgerConfig {
+    /// Largest difference between the exchange's collateral balance and
+    /// the ledger's cash that is not booked as an adjustment
+    #[serde(default = "default_drift_tolerance")]
+    pub drift_tolerance: Decimal,
+    /// Time between collateral balance checks in live mode
+    #[serde(default = "default_check_interval_secs")]
+    pub check_interval_secs: DurationConfig,
+}
+
+fn default_drift_tolerance() -> Decimal {
+    DEFAULT_DRIFT_TOLERANCE
+}
+
+fn default_check_interval_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_BALANCE_CHECK_SECS)
+}
+
+impl Default for LedgerConfig {
+    fn default() -> Self {
+        Self {
+            drift_tolerance: DEFAULT_DRIFT_TOLERANCE,
+            check_interval_secs: default_check_interval_secs(),
+        }
+    }
+}
+
+/// What moved the balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerEntryKind {
+    /// Funds added; positive
+    Deposit,
+    /// Funds taken out; negative
+    Withdrawal,
+    /// Gross P&L of a closed position, before fees
+    TradeSettlement,
+    /// Entry and exit fees of a closed position; never positive
+    Fee,
+    /// Correction to the exchange's balance
+    Adjustment,
+}
+
+impl LedgerEntryKind {
+    /// Name as written to the ledger
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerEntryKind::Deposit => "deposit",
+            LedgerEntryKind::Withdrawal => "withdrawal",
+            LedgerEntryKind::TradeSettlement => "trade_settlement",
+            LedgerEntryKind::Fee => "fee",
+            LedgerEntryKind::Adjustment => "adjustment",
+        }
+    }
+
+    /// Whether the entry moves funds in or out rather than making or
+    /// losing money
+    pub fn is_transfer(&self) -> bool {
+        matches!(self, LedgerEntryKind::Deposit | LedgerEntryKind::Withdrawal)
+    }
+}
+
+impl fmt::Display for LedgerEntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One movement of the balance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Position in the ledger, from 0; assigned on reading
+    #[serde(skip)]
+    pub seq: u64,
+    /// When the entry was booked
+    pub at: DateTime<Utc>,
+    pub kind: LedgerEntryKind,
+    /// Signed change to the balance, USD
+    pub amount: Decimal,
+    /// What the entry is for: a position id, a market, an operator note
+    pub reference: String,
+}
+
+/// Append-only log of balance movements, optionally backed by a file
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+    balance: Decimal,
+    path: Option<PathBuf>,
+    /// Bytes of the file read so far
+    read_to: u64,
+}
+
+impl Ledger {
+    /// An empty ledger kept in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The ledger in `path`, created on the first entry if missing
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let mut ledger = Self {
+            path: Some(path.into()),
+            ..Self::default()
+        };
+        ledger.sync()?;
+        Ok(ledger)
+    }
+
+    /// Book `amount` as the opening deposit of an empty ledger
+    pub fn with_opening_deposit(
+        mut self,
+        amount: Decimal,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<Self> {
+        if self.entries.is_empty() && amount > Decimal::ZERO {
+            self.record(LedgerEntryKind::Deposit, amount, OPENING_REFERENCE, at)?;
+        }
+        Ok(self)
+    }
+
+    /// File backing the ledger
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Every entry, in booking order
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    /// Sum of every entry
+    pub fn balance(&self) -> Decimal {
+        self.balance
+    }
+
+    /// Sum of the entries booked at or before `at`
+    pub fn balance_at(&self, at: DateTime<Utc>) -> Decimal {
+        self.entries
+            .iter()
+            .filter(|e| e.at <= at)
+            .map(|e| e.amount)
+            .sum()
+    }
+
+    /// Add `amount` of funds
+    pub fn deposit(
+        &mut self,
+        amount: Decimal,
+        reference: &str,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<LedgerEntry> {
+        self.record(LedgerEntryKind::Deposit, amount, reference, at)
+    }
+
+    /// Take `amount` of funds out; the balance may not go negative
+    pub fn withdraw(
+        &mut self,
+        amount: Decimal,
+        reference: &str,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<LedgerEntry> {
+        self.sync()?;
+        if amount > self.balance {
+            bail!(
+                "Cannot withdraw {} from a balance of {}",
+                amount,
+                self.balance
+            );
+        }
+        self.record(LedgerEntryKind::Withdrawal, -amount, reference, at)
+    }
+
+    /// Book a signed `amount` of `kind`
+    ///
+    /// With a file, the entry is appended and read back with whatever
+    /// others appended since the last [`Self::sync`]. If the file cannot
+    /// be written the entry is still booked in memory, so the balance
+    /// stays right for the session, and the error returned.
+    pub fn record(
+        &mut self,
+        kind: LedgerEntryKind,
+        amount: Decimal,
+        reference: &str,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<LedgerEntry> {
+        let valid = match kind {
+            LedgerEntryKind::Deposit => amount > Decimal::ZERO,
+            LedgerEntryKind::Withdrawal => amount < Decimal::ZERO,
+            LedgerEntryKind::Fee => amount <= Decimal::ZERO,
+            LedgerEntryKind::TradeSettlement | LedgerEntryKind::Adjustment => true,
+        };
+        if !valid {
+            bail!("A {} of {} has the wrong sign", kind, amount);
+        }
+        let entry = LedgerEntry {
+            seq: 0,
+            at,
+            kind,
+            amount,
+            reference: reference.to_string(),
+        };
+        let Some(path) = self.path.clone() else {
+            self.push(entry);
+            return Ok(self.entries[self.entries.len() - 1].clone());
+        };
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| {
+                file.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())
+            });
+        if let Err(e) = written {
+            self.push(entry);
+            return Err(e).with_context(|| format!("Could not write ledger {:?}", path));
+        }
+        self.sync()?;
+        Ok(self
+            .entries
+            .iter()
+            .rev()
+            .find(|e| e.at == entry.at && e.kind == kind && e.amount == amount)
+            .cloned()
+            .unwrap_or(entry))
+    }
+
+    /// Read entries appended to the file since the last read, returning
+    /// them; nothing without a file
+    pub fn sync(&mut self) -> anyhow::Result<Vec<LedgerEntry>> {
+        let Some(path) = self.path.clone() else {
+            return Ok(vec![]);
+        };
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        file.seek(SeekFrom::Start(self.read_to))?;
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        // A line still being written is read next time
+        let complete = text.rfind('\n').map_or(0, |i| i + 1);
+        self.read_to += complete as u64;
+        let mut added = vec![];
+        for line in text[..complete].lines().filter(|l| !l.trim().is_empty()) {
+            match serde_json::from_str::<LedgerEntry>(line) {
+                Ok(entry) => {
+                    self.push(entry);
+                    added.extend(self.entries.last().cloned());
+                }
+                Err(e) => {
+                    tracing::warn!(path = ?path, error = %e, line, "Skipped a bad ledger line")
+                }
+            }
+        }
+        Ok(added)
+    }
+
+    fn push(&mut self, mut entry: LedgerEntry) {
+        entry.seq = self.entries.len() as u64;
+        self.balance += entry.amount;
+        self.entries.push(entry);
+    }
+
+    /// Compare the exchange's collateral balance `actual` with the ledger's
+    /// cash, its balance less `held` in open positions, booking the
+    /// difference as an adjustment when it exceeds `tolerance`
+    pub fn reconcile(
+        &mut self,
+        held: Decimal,
+        actual: Decimal,
+        tolerance: Decimal,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<Option<LedgerEntry>> {
+        self.sync()?;
+        let drift = actual - (self.balance - held);
+        if drift.abs() <= tolerance {
+            return Ok(None);
+        }
+        let reference = format!("collateral balance {}", actual);
+        self.record(LedgerEntryKind::Adjustment, drift, &reference, at)
+            .map(Some)
+    }
+
+    /// Entries booked at or after `since`, with the balances around them
+    pub fn report(&self, since: Option<DateTime<Utc>>) -> LedgerReport {
+        let entries: Vec<LedgerEntry> = self
+            .entries
+            .iter()
+            .filter(|e| since.is_none_or(|since| e.at >= since))
+            .cloned()
+            .collect();
+        let moved: Decimal = entries.iter().map(|e| e.amount).sum();
+        LedgerReport {
+            opening: self.balance - moved,
+            ending: self.balance,
+            entries,
+        }
+    }
+}
+
+/// Ledger entries of a period with its opening and ending balance
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LedgerReport {
+    /// Balance before the first entry
+    pub opening: Decimal,
+    /// Balance after the last entry
+    pub ending: Decimal,
+    pub entries: Vec<LedgerEntry>,
+}
+
+impl LedgerReport {
+    /// Sum of the entries per kind
+    pub fn totals(&self) -> BTreeMap<LedgerEntryKind, Decimal> {
+        let mut totals = BTreeMap::new();
+        for entry in &self.entries {
+            *totals.entry(entry.kind).or_default() += entry.amount;
+        }
+        totals
+    }
+}
+
+impl fmt::Display for LedgerReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Ledger: {} entries", self.entries.len())?;
+        writeln!(f, "  {:<40} {:>12.2}", "opening balance", self.opening)?;
+        let mut balance = self.opening;
+        for entry in &self.entries {
+            balance += entry.amount;
+            writeln!(
+                f,
+                "  {:>5} {} {:<16} {:>+12.2} {:>12.2}  {}",
+                entry.seq,
+                entry.at.format("%Y-%m-%d %H:%M:%S"),
+                entry.kind,
+                entry.amount,
+                balance,
+                entry.reference
+            )?;
+        }
+        for (kind, total) in self.totals() {
+            writeln!(f, "  {:<40} {:>+12.2}", kind, total)?;
+        }
+        writeln!(f, "  {:<40} {:>12.2}", "ending balance", self.ending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::Market;
+    use crate::risk::KellyCalculator;
+    use crate::signal::{Side, Signal, SignalReason};
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_735_689_600, 0).unwrap()
+    }
+
+    #[test]
+    fn test_balance_is_the_sum_of_entries_in_order() {
+        let mut ledger = Ledger::new()
+            .with_opening_deposit(dec!(1000), t0())
+            .unwrap();
+        let at = |mins| t0() + Duration::minutes(mins);
+        ledger
+            .record(
+                LedgerEntryKind::TradeSettlement,
+                dec!(12.50),
+                "pos-1",
+                at(15),
+            )
+            .unwrap();
+        ledger
+            .record(LedgerEntryKind::Fee, dec!(-0.25), "pos-1", at(15))
+            .unwrap();
+        ledger.deposit(dec!(500), "top-up", at(20)).unwrap();
+        ledger.withdraw(dec!(200), "payout", at(30)).unwrap();
+
+        let kinds: Vec<_> = ledger.entries().iter().map(|e| (e.seq, e.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (0, LedgerEntryKind::Deposit),
+                (1, LedgerEntryKind::TradeSettlement),
+                (2, LedgerEntryKind::Fee),
+                (3, LedgerEntryKind::Deposit),
+                (4, LedgerEntryKind::Withdrawal),
+            ]
+        );
+        assert_eq!(ledger.balance(), dec!(1312.25));
+        assert_eq!(ledger.balance_at(at(15)), dec!(1012.25));
+        assert_eq!(ledger.balance_at(at(-1)), Decimal::ZERO);
+
+        // A ledger is opened once; wrong signs and overdrafts are refused
+        let ledger = ledger.with_opening_deposit(dec!(1000), at(31)).unwrap();
+        assert_eq!(ledger.entries().len(), 5);
+        let mut ledger = ledger;
+        assert!(ledger.deposit(dec!(-5), "oops", at(32)).is_err());
+        assert!(ledger
+            .record(LedgerEntryKind::Fee, dec!(0.10), "oops", at(32))
+            .is_err());
+        assert!(ledger.withdraw(dec!(5000), "too much", at(32)).is_err());
+        assert_eq!(ledger.balance(), dec!(1312.25));
+
+        let report = ledger.report(Some(at(20)));
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(report.opening, dec!(1012.25));
+        assert_eq!(report.ending, dec!(1312.25));
+        let text = report.to_string();
+        assert!(text.contains("ending balance"), "{}", text);
+        assert!(text.contains("top-up"), "{}", text);
+    }
+
+    #[test]
+    fn test_deposit_from_another_process_changes_sizing() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(LEDGER_FILE);
+        let mut session = Ledger::open(&path)
+            .unwrap()
+            .with_opening_deposit(dec!(1000), t0())
+            .unwrap();
+
+        let now = Utc::now();
+        let market = Market {
+            condition_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time: now,
+            close_time: now + Duration::minutes(15),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        let signal = Signal::new(
+            market,
+            Side::Yes,
+            dec!(0.55),
+            dec!(0.50),
+            dec!(0.05),
+            dec!(0.8),
+            SignalReason::SpotDivergence,
+        );
+        let kelly = KellyCalculator::new(dec!(0.25), dec!(0.10));
+        assert_eq!(kelly.calculate(&signal, session.balance()), dec!(25));
+
+        // `ctl deposit` opens the same file while the session runs
+        let mut ctl = Ledger::open(&path).unwrap();
+        assert_eq!(ctl.balance(), dec!(1000));
+        ctl.deposit(dec!(1000), "top-up", t0() + Duration::hours(1))
+            .unwrap();
+
+        let added = session.sync().unwrap();
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].seq, 1);
+        assert_eq!(added[0].kind, LedgerEntryKind::Deposit);
+        assert_eq!(session.balance(), dec!(2000));
+        assert_eq!(kelly.calculate(&signal, session.balance()), dec!(50));
+        assert!(session.sync().unwrap().is_empty());
+
+        // The session's own entries land after it in the same file
+        session
+            .record(
+                LedgerEntryKind::Fee,
+                dec!(-1),
+                "pos-1",
+                t0() + Duration::hours(2),
+            )
+            .unwrap();
+        let reread = Ledger::open(&path).unwrap();
+        assert_eq!(reread.entries(), session.entries());
+        assert_eq!(reread.balance(), dec!(1999));
+    }
+
+    #[test]
+    fn test_drift_from_the_exchange_is_booked() {
+        let mut ledger = Ledger::new()
+            .with_opening_deposit(dec!(1000), t0())
+            .unwrap();
+        // 100 is in open positions, so 900 should be at the exchange
+        let held = dec!(100);
+        let within = ledger.reconcile(held, dec!(899.50), dec!(1), t0()).unwrap();
+        assert!(within.is_none());
+        assert_eq!(ledger.balance(), dec!(1000));
+
+        let adjustment = ledger
+            .reconcile(held, dec!(880), dec!(1), t0() + Duration::minutes(5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(adjustment.kind, LedgerEntryKind::Adjustment);
+        assert_eq!(adjustment.amount, dec!(-20));
+        assert_eq!(adjustment.reference, "collateral balance 880");
+        assert_eq!(ledger.balance(), dec!(980));
+
+        // Booked once: the next check agrees
+        assert!(ledger
+            .reconcile(held, dec!(880), dec!(1), t0() + Duration::minutes(10))
+            .unwrap()
+            .is_none());
+    }
+}
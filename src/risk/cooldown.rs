@@ -0,0 +1,477 @@
+//! Loss cooldown per asset
+//!
+//! A settled loss larger than `min_loss` stops new entries on the same
+//! asset for the next `windows` market windows, and for `minutes` after the
+//! loss if that is set. Windows are counted by open time as markets open, so
+//! the count carries across market boundaries and restarts alike. After
+//! `halt_after_losses` such losses in a row the asset halts until an
+//! operator runs `poly-hft ctl ack-cooldown <asset>`.
+//!
+//! State is written to `<data dir>/loss_cooldown.json` on every change.
+
+use crate::duration::{DurationConfig, Minutes};
+use crate::journal::Journal;
+use crate::market::Market;
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Loss cooldown state, in the data directory
+pub const LOSS_COOLDOWN_FILE: &str = "loss_cooldown.json";
+
+/// When a loss cools an asset down
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LossCooldownConfig {
+    /// Settled loss in USD an asset must exceed to cool down
+    #[serde(default)]
+    pub min_loss: Decimal,
+    /// Market windows skipped after a loss; 0 skips none
+    #[serde(default)]
+    pub windows: u32,
+    /// Time after a loss with no entries; 0 disables
+    #[serde(default)]
+    pub minutes: DurationConfig<Minutes>,
+    /// Losses in a row that halt the asset until acknowledged; 0 disables
+    #[serde(default)]
+    pub halt_after_losses: u32,
+}
+
+impl LossCooldownConfig {
+    /// Whether any loss can block an entry
+    pub fn enabled(&self) -> bool {
+        self.windows > 0 || !self.minutes.is_zero() || self.halt_after_losses > 0
+    }
+}
+
+/// Cooldown state of one asset
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetCooldown {
+    /// Counted losses in a row, reset by a profit or an acknowledgement
+    pub consecutive_losses: u32,
+    /// P&L of the last counted loss
+    pub last_loss: Decimal,
+    /// Close of the losing window; windows opening from here on are skipped
+    pub since: Option<DateTime<Utc>>,
+    /// Open times of the windows skipped since the loss
+    #[serde(default)]
+    pub skipped: BTreeSet<DateTime<Utc>>,
+    /// No entries before this time
+    pub until: Option<DateTime<Utc>>,
+    /// When consecutive losses halted the asset
+    pub halted_at: Option<DateTime<Utc>>,
+}
+
+impl AssetCooldown {
+    /// Whether the asset skips windows or waits out `until` at `now`
+    pub fn is_cooling(&self, windows: u32, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|until| now < until)
+            || (self.since.is_some() && self.skipped.len() < windows as usize)
+    }
+
+    /// 0 clear, 1 cooling down, 2 halted, for gauges
+    pub fn severity(&self, windows: u32, now: DateTime<Utc>) -> u8 {
+        if self.halted_at.is_some() {
+            2
+        } else if self.is_cooling(windows, now) {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// Why an entry on a cooled-down asset is withheld
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CooldownBlock {
+    /// The market's window is one of those skipped after a loss
+    Window,
+    /// The timed cooldown runs until the given time
+    Until(DateTime<Utc>),
+    /// Consecutive losses halted the asset
+    Halted(u32),
+}
+
+impl CooldownBlock {
+    /// Reason label for journals and metrics
+    pub fn label(&self) -> &'static str {
+        match self {
+            CooldownBlock::Window | CooldownBlock::Until(_) => "loss_cooldown",
+            CooldownBlock::Halted(_) => "loss_halt",
+        }
+    }
+}
+
+impl fmt::Display for CooldownBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CooldownBlock::Window => f.write_str("window skipped after a loss"),
+            CooldownBlock::Until(until) => {
+                write!(f, "cooling down until {}", until.format("%H:%M:%S"))
+            }
+            CooldownBlock::Halted(losses) => write!(f, "halted after {} losses in a row", losses),
+        }
+    }
+}
+
+/// What a settled loss did to its asset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CooldownTrigger {
+    /// Entries pause for the configured windows or minutes
+    Cooling,
+    /// Entries stop until an operator acknowledges
+    Halted,
+}
+
+/// Loss cooldowns of every asset traded
+pub struct LossCooldown {
+    config: LossCooldownConfig,
+    assets: BTreeMap<String, AssetCooldown>,
+    path: Option<PathBuf>,
+    journal: Option<Journal>,
+}
+
+impl LossCooldown {
+    /// Cooldowns kept in memory only
+    pub fn new(config: LossCooldownConfig) -> Self {
+        Self {
+            config,
+            assets: BTreeMap::new(),
+            path: None,
+            journal: None,
+        }
+    }
+
+    /// Load state from `path`, if it exists, and write every change back
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        self.assets = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow!("unreadable loss cooldown {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Journal every cooldown, halt, and acknowledgement
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Settings in force
+    pub fn config(&self) -> &LossCooldownConfig {
+        &self.config
+    }
+
+    /// State of every asset that has lost
+    pub fn assets(&self) -> &BTreeMap<String, AssetCooldown> {
+        &self.assets
+    }
+
+    /// Count a window of `asset` towards a cooldown in progress
+    ///
+    /// Only windows opening at or after the losing window's close count, so
+    /// a market already open when the loss settles is skipped too.
+    pub fn on_market_open(&mut self, asset: &str, market: &Market) {
+        let windows = self.config.windows as usize;
+        let Some(state) = self.assets.get_mut(asset) else {
+            return;
+        };
+        let counts = state.since.is_some_and(|since| market.open_time >= since)
+            && state.skipped.len() < windows
+            && state.skipped.insert(market.open_time);
+        if counts {
+            self.save();
+        }
+    }
+
+    /// Record the P&L of a settled market of `asset`
+    ///
+    /// A loss beyond `min_loss` starts a cooldown and extends the losing
+    /// streak; a profit ends the streak. Smaller losses leave both alone.
+    pub fn on_settled(
+        &mut self,
+        asset: &str,
+        market: &Market,
+        pnl: Decimal,
+        now: DateTime<Utc>,
+    ) -> Option<CooldownTrigger> {
+        if !self.config.enabled() {
+            return None;
+        }
+        if pnl > Decimal::ZERO {
+            let state = self.assets.get_mut(asset)?;
+            if state.consecutive_losses > 0 {
+                state.consecutive_losses = 0;
+                self.save();
+            }
+            return None;
+        }
+        if -pnl <= self.config.min_loss {
+            return None;
+        }
+        let config = &self.config;
+        let state = self.assets.entry(asset.to_string()).or_default();
+        state.consecutive_losses += 1;
+        state.last_loss = pnl;
+        state.since = Some(market.close_time);
+        state.skipped.clear();
+        state.until = (!config.minutes.is_zero()).then(|| now + config.minutes.to_chrono());
+        let halts = config.halt_after_losses > 0
+            && state.consecutive_losses >= config.halt_after_losses
+            && state.halted_at.is_none();
+        if halts {
+            state.halted_at = Some(now);
+        }
+        let (kind, trigger) = if halts {
+            ("asset_halted", CooldownTrigger::Halted)
+        } else {
+            ("loss_cooldown", CooldownTrigger::Cooling)
+        };
+        self.journal(kind, asset, Some(&market.condition_id));
+        self.save();
+        Some(trigger)
+    }
+
+    /// Why an entry in `market` of `asset` is withheld at `now`, if it is
+    pub fn block(&self, asset: &str, market: &Market, now: DateTime<Utc>) -> Option<CooldownBlock> {
+        let state = self.assets.get(asset)?;
+        if state.halted_at.is_some() {
+            return Some(CooldownBlock::Halted(state.consecutive_losses));
+        }
+        if state.skipped.contains(&market.open_time) {
+            return Some(CooldownBlock::Window);
+        }
+        state
+            .until
+            .filter(|until| now < *until)
+            .map(CooldownBlock::Until)
+    }
+
+    /// 0 clear, 1 cooling down, 2 halted, for gauges
+    pub fn severity(&self, asset: &str, now: DateTime<Utc>) -> u8 {
+        self.assets
+            .get(asset)
+            .map_or(0, |state| state.severity(self.config.windows, now))
+    }
+
+    /// Lift the halt on `asset` and reset its losing streak
+    pub fn acknowledge(
+        &mut self,
+        asset: &str,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<AssetCooldown> {
+        let state = self
+            .assets
+            .get_mut(asset)
+            .filter(|state| state.halted_at.is_some())
+            .ok_or_else(|| anyhow!("{} is not halted on losses", asset))?;
+        state.halted_at = None;
+        state.consecutive_losses = 0;
+        if state.until.is_some_and(|until| until > now) {
+            state.until = Some(now);
+        }
+        let state = state.clone();
+        self.journal("asset_halt_acknowledged", asset, None);
+        if let Err(e) = self.write() {
+            bail!("failed to save loss cooldown: {}", e);
+        }
+        Ok(state)
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.write() {
+            tracing::error!(error = %e, "Failed to save loss cooldown");
+        }
+    }
+
+    fn write(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        write_atomic(path, &serde_json::to_vec_pretty(&self.assets)?)
+    }
+
+    fn journal(&self, kind: &str, asset: &str, market_id: Option<&str>) {
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        let state = &self.assets[asset];
+        let data = serde_json::json!({
+            "asset": asset,
+            "market_id": market_id,
+            "consecutive_losses": state.consecutive_losses,
+            "last_loss": state.last_loss,
+            "windows": self.config.windows,
+            "until": state.until,
+        });
+        if let Err(e) = journal.append(kind, &data) {
+            tracing::warn!(error = %e, "Failed to journal loss cooldown");
+        }
+    }
+}
+
+pub(super) fn write_atomic(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::risk::HALT_JOURNAL_FILE;
+    use rust_decimal_macros::dec;
+
+    fn ts(mins: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + mins * 60, 0).unwrap()
+    }
+
+    /// The 15-minute window opening `index` windows after `ts(0)`
+    fn window(index: i64) -> Market {
+        Market {
+            condition_id: format!("w{}", index),
+            asset: "BTC".to_string(),
+            yes_token_id: format!("yes{}", index),
+            no_token_id: format!("no{}", index),
+            open_price: dec!(100000),
+            open_time: ts(index * 15),
+            close_time: ts(index * 15 + 15),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    fn config(windows: u32, minutes: u64, halt_after_losses: u32) -> LossCooldownConfig {
+        LossCooldownConfig {
+            min_loss: dec!(5),
+            windows,
+            minutes: DurationConfig::from_mins(minutes),
+            halt_after_losses,
+        }
+    }
+
+    #[test]
+    fn test_windows_are_counted_across_market_boundaries() {
+        let mut cooldown = LossCooldown::new(config(2, 0, 0));
+        for i in 0..2 {
+            cooldown.on_market_open("BTC", &window(i));
+        }
+
+        // A loss under the threshold changes nothing
+        assert_eq!(
+            cooldown.on_settled("BTC", &window(0), dec!(-5), ts(15)),
+            None
+        );
+        assert!(cooldown.assets().is_empty());
+
+        // Window 1 opened before window 0 settled; it still counts
+        assert_eq!(
+            cooldown.on_settled("BTC", &window(0), dec!(-12), ts(15)),
+            Some(CooldownTrigger::Cooling)
+        );
+        cooldown.on_market_open("BTC", &window(1));
+        assert_eq!(cooldown.severity("BTC", ts(16)), 1);
+        assert_eq!(
+            cooldown.block("BTC", &window(1), ts(16)),
+            Some(CooldownBlock::Window)
+        );
+        cooldown.on_market_open("BTC", &window(2));
+        cooldown.on_market_open("BTC", &window(3));
+        assert_eq!(
+            cooldown.block("BTC", &window(2), ts(31)),
+            Some(CooldownBlock::Window)
+        );
+        assert_eq!(cooldown.block("BTC", &window(3), ts(46)), None);
+        assert_eq!(cooldown.severity("BTC", ts(46)), 0);
+
+        // Other assets and the losing window itself are untouched
+        assert_eq!(cooldown.block("ETH", &window(1), ts(16)), None);
+        assert_eq!(cooldown.block("BTC", &window(0), ts(14)), None);
+    }
+
+    #[test]
+    fn test_timed_cooldown_expires() {
+        let mut cooldown = LossCooldown::new(config(0, 20, 0));
+        cooldown.on_settled("BTC", &window(0), dec!(-12), ts(15));
+        cooldown.on_market_open("BTC", &window(1));
+        assert_eq!(
+            cooldown.block("BTC", &window(1), ts(30)),
+            Some(CooldownBlock::Until(ts(35)))
+        );
+        assert_eq!(cooldown.block("BTC", &window(1), ts(35)), None);
+    }
+
+    #[test]
+    fn test_cooldown_survives_restart_and_halt_needs_acknowledgement() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOSS_COOLDOWN_FILE);
+        let open = || {
+            LossCooldown::new(config(1, 0, 2))
+                .with_state_file(&path)
+                .unwrap()
+                .with_journal(Journal::open(dir.path().join(HALT_JOURNAL_FILE)).unwrap())
+        };
+
+        let mut cooldown = open();
+        cooldown.on_settled("BTC", &window(0), dec!(-12), ts(15));
+        drop(cooldown);
+
+        // The skipped window opens after the restart
+        let mut cooldown = open();
+        cooldown.on_market_open("BTC", &window(1));
+        assert_eq!(
+            cooldown.block("BTC", &window(1), ts(16)),
+            Some(CooldownBlock::Window)
+        );
+        assert_eq!(
+            cooldown.on_settled("BTC", &window(2), dec!(-8), ts(45)),
+            Some(CooldownTrigger::Halted)
+        );
+        drop(cooldown);
+
+        let mut cooldown = open();
+        assert_eq!(cooldown.severity("BTC", ts(100)), 2);
+        assert_eq!(
+            cooldown.block("BTC", &window(9), ts(150)),
+            Some(CooldownBlock::Halted(2))
+        );
+        assert!(cooldown.acknowledge("ETH", ts(150)).is_err());
+        let acked = cooldown.acknowledge("BTC", ts(150)).unwrap();
+        assert_eq!(acked.consecutive_losses, 0);
+        assert!(cooldown.acknowledge("BTC", ts(150)).is_err());
+        assert_eq!(open().block("BTC", &window(9), ts(150)), None);
+
+        let kinds: Vec<_> = Journal::read_all(dir.path().join(HALT_JOURNAL_FILE))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec!["loss_cooldown", "asset_halted", "asset_halt_acknowledged"]
+        );
+    }
+
+    #[test]
+    fn test_profit_ends_the_losing_streak() {
+        let mut cooldown = LossCooldown::new(config(1, 0, 2));
+        cooldown.on_settled("BTC", &window(0), dec!(-12), ts(15));
+        cooldown.on_settled("BTC", &window(2), dec!(3), ts(45));
+        assert_eq!(
+            cooldown.on_settled("BTC", &window(3), dec!(-12), ts(60)),
+            Some(CooldownTrigger::Cooling)
+        );
+        assert_eq!(cooldown.assets()["BTC"].consecutive_losses, 1);
+    }
+}
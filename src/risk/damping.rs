@@ -0,0 +1,179 @@
+//! Inventory-aware damping of new entries
+//!
+//! Every market here settles Up (YES) or Down (NO) on its asset's move,
+//! and the moves of one asset's intervals, and of the assets themselves,
+//! run together: a YES held in the BTC 15m window and a new YES signal on
+//! BTC 1h or ETH are largely the same bet. Damping sizes a new entry by
+//! how much of the directional cap the positions already held in its
+//! direction use: full size up to `full_below` of the cap, falling
+//! linearly to nothing at `zero_at`. The cap is `max_directional_pct` of
+//! the bankroll.
+
+use super::PositionTracker;
+use crate::precision::round_pct;
+use crate::signal::Side;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Default directional cap as a share of the bankroll
+pub const DEFAULT_MAX_DIRECTIONAL_PCT: Decimal = dec!(0.03);
+
+/// Default share of the cap below which entries are full size
+pub const DEFAULT_FULL_BELOW: Decimal = dec!(0.30);
+
+/// Default share of the cap at which entries are damped to nothing
+pub const DEFAULT_ZERO_AT: Decimal = dec!(0.90);
+
+/// Damping schedule, under `[risk.damping]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct DampingConfig {
+    /// Off sizes every entry as before
+    #[serde(default)]
+    pub enabled: bool,
+    /// Cost of positions held in one direction the cap allows, as a share
+    /// of the bankroll
+    #[serde(default = "default_max_directional_pct")]
+    pub max_directional_pct: Decimal,
+    /// Share of the cap used below which entries are full size
+    #[serde(default = "default_full_below")]
+    pub full_below: Decimal,
+    /// Share of the cap used at which entries are damped to nothing
+    #[serde(default = "default_zero_at")]
+    pub zero_at: Decimal,
+}
+
+fn default_max_directional_pct() -> Decimal {
+    DEFAULT_MAX_DIRECTIONAL_PCT
+}
+
+fn default_full_below() -> Decimal {
+    DEFAULT_FULL_BELOW
+}
+
+fn default_zero_at() -> Decimal {
+    DEFAULT_ZERO_AT
+}
+
+impl Default for DampingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_directional_pct: default_max_directional_pct(),
+            full_below: default_full_below(),
+            zero_at: default_zero_at(),
+        }
+    }
+}
+
+impl DampingConfig {
+    /// Share of the full size an entry gets with `used` of the cap taken
+    pub fn factor(&self, used: Decimal) -> Decimal {
+        if used <= self.full_below {
+            return Decimal::ONE;
+        }
+        if used >= self.zero_at || self.zero_at <= self.full_below {
+            return Decimal::ZERO;
+        }
+        round_pct((self.zero_at - used) / (self.zero_at - self.full_below))
+    }
+
+    /// Damping of a new entry on `side` given the positions held, or
+    /// `None` while disabled
+    pub fn damp(
+        &self,
+        side: Side,
+        positions: &PositionTracker,
+        bankroll: Decimal,
+    ) -> Option<Damping> {
+        if !self.enabled {
+            return None;
+        }
+        let exposure = positions.directional_exposure(side);
+        let cap = bankroll * self.max_directional_pct;
+        let used = if cap > Decimal::ZERO {
+            exposure / cap
+        } else {
+            Decimal::ONE
+        };
+        Some(Damping {
+            side,
+            exposure,
+            cap,
+            used: round_pct(used),
+            factor: self.factor(used),
+        })
+    }
+}
+
+/// How much one entry was damped, and why
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Damping {
+    /// Direction of the entry
+    pub side: Side,
+    /// Cost of the positions already held in that direction
+    pub exposure: Decimal,
+    /// Directional cap in dollars
+    pub cap: Decimal,
+    /// Exposure as a share of the cap
+    pub used: Decimal,
+    /// Share of the full size entered
+    pub factor: Decimal,
+}
+
+impl fmt::Display for Damping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} exposure {:.4} of cap {:.4} ({:.0}%), size x{}",
+            self.side,
+            self.exposure.round_dp(4),
+            self.cap.round_dp(4),
+            (self.used * dec!(100)).round(),
+            self.factor.normalize()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_across_exposure_levels() {
+        let config = DampingConfig::default();
+        for (used, factor) in [
+            (dec!(0), dec!(1)),
+            (dec!(0.15), dec!(1)),
+            (dec!(0.30), dec!(1)),
+            (dec!(0.45), dec!(0.75)),
+            (dec!(0.60), dec!(0.5)),
+            (dec!(0.75), dec!(0.25)),
+            (dec!(0.90), dec!(0)),
+            (dec!(1.50), dec!(0)),
+        ] {
+            assert_eq!(config.factor(used), factor, "{} of the cap", used);
+        }
+    }
+
+    #[test]
+    fn test_disabled_or_without_a_cap() {
+        let positions = PositionTracker::new();
+        assert!(DampingConfig::default()
+            .damp(Side::Yes, &positions, dec!(500))
+            .is_none());
+
+        let config = DampingConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let damping = config.damp(Side::Yes, &positions, dec!(500)).unwrap();
+        assert_eq!(damping.cap, dec!(15));
+        assert_eq!(damping.factor, Decimal::ONE);
+        // No bankroll leaves no room in either direction
+        let broke = config.damp(Side::No, &positions, Decimal::ZERO).unwrap();
+        assert_eq!(broke.factor, Decimal::ZERO);
+    }
+}
@@ -0,0 +1,352 @@
+//! Per-market exposure aggregated across strategies
+//!
+//! Every market settles to exactly one of YES or NO paying 1, so the
+//! outcome P&L of a set of positions is payout minus cost. A spread pair
+//! (YES + NO) pays 1 whichever way it settles, which is why it can reduce
+//! the worst case of a directional position stacked on top of it.
+//!
+//! Markets in a neg-risk group are mutually exclusive: at most one of them
+//! settles YES. Their exposures are bucketed into a [`GroupExposure`] whose
+//! outcomes are "market i wins" (or none does).
+
+use super::Position;
+use crate::signal::Side;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fmt;
+use uuid::Uuid;
+
+/// One position contributing to a market's exposure
+#[derive(Debug, Clone, Serialize)]
+pub struct ExposureLeg {
+    /// Position identifier (nil for a prospective order)
+    pub position_id: Uuid,
+    /// Strategy that owns the position
+    pub strategy: String,
+    /// Token side held
+    pub side: Side,
+    /// Shares held
+    pub size: Decimal,
+    /// Entry price
+    pub entry_price: Decimal,
+}
+
+impl fmt::Display for ExposureLeg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id = self.position_id.to_string();
+        write!(
+            f,
+            "{} [{}] {:?} {} @ {}",
+            &id[..8],
+            self.strategy,
+            self.side,
+            self.size.normalize(),
+            self.entry_price.normalize()
+        )
+    }
+}
+
+/// Aggregate exposure in one market
+#[derive(Debug, Clone, Serialize)]
+pub struct MarketExposure {
+    /// Market condition identifier
+    pub condition_id: String,
+    /// Contributing positions
+    pub legs: Vec<ExposureLeg>,
+}
+
+impl MarketExposure {
+    /// Empty exposure for a market
+    pub fn new(condition_id: impl Into<String>) -> Self {
+        Self {
+            condition_id: condition_id.into(),
+            legs: vec![],
+        }
+    }
+
+    /// Add an open position
+    pub fn add_position(&mut self, position: &Position) {
+        self.legs.push(ExposureLeg {
+            position_id: position.id,
+            strategy: position.strategy.clone(),
+            side: position.side,
+            size: position.size,
+            entry_price: position.entry_price,
+        });
+    }
+
+    /// Total YES shares held
+    pub fn yes_shares(&self) -> Decimal {
+        self.shares(Side::Yes)
+    }
+
+    /// Total NO shares held
+    pub fn no_shares(&self) -> Decimal {
+        self.shares(Side::No)
+    }
+
+    fn shares(&self, side: Side) -> Decimal {
+        self.legs
+            .iter()
+            .filter(|l| l.side == side)
+            .map(|l| l.size)
+            .sum()
+    }
+
+    /// YES minus NO shares; positive means long the YES outcome
+    pub fn net_shares(&self) -> Decimal {
+        self.yes_shares() - self.no_shares()
+    }
+
+    /// Cost of the legs holding `side`
+    pub fn cost(&self, side: Side) -> Decimal {
+        self.legs
+            .iter()
+            .filter(|l| l.side == side)
+            .map(|l| l.size * l.entry_price)
+            .sum()
+    }
+
+    /// Total cost of all legs
+    pub fn gross_notional(&self) -> Decimal {
+        self.legs.iter().map(|l| l.size * l.entry_price).sum()
+    }
+
+    /// P&L if the market settles to `outcome`
+    pub fn pnl_if(&self, outcome: Side) -> Decimal {
+        self.shares(outcome) - self.gross_notional()
+    }
+
+    /// Loss in the worse of the two outcomes (zero if both are profitable)
+    pub fn worst_case_loss(&self) -> Decimal {
+        let worst = self.pnl_if(Side::Yes).min(self.pnl_if(Side::No));
+        (-worst).max(Decimal::ZERO)
+    }
+
+    /// Contributing positions as a comma-separated list
+    pub fn describe_legs(&self) -> String {
+        if self.legs.is_empty() {
+            return "none".to_string();
+        }
+        self.legs
+            .iter()
+            .map(ExposureLeg::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl fmt::Display for MarketExposure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: net {} shares, gross {}, worst-case loss {} ({} positions)",
+            self.condition_id,
+            self.net_shares().normalize(),
+            self.gross_notional().normalize(),
+            self.worst_case_loss().normalize(),
+            self.legs.len()
+        )
+    }
+}
+
+/// Aggregate exposure across the markets of one neg-risk group
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupExposure {
+    /// Group identifier shared by the markets
+    pub group_id: String,
+    /// Per-market exposure, ordered by condition ID
+    pub markets: Vec<MarketExposure>,
+}
+
+impl GroupExposure {
+    /// Empty exposure for a group
+    pub fn new(group_id: impl Into<String>) -> Self {
+        Self {
+            group_id: group_id.into(),
+            markets: vec![],
+        }
+    }
+
+    /// Exposure of one market in the group, created if absent
+    pub fn market_mut(&mut self, condition_id: &str) -> &mut MarketExposure {
+        let index = match self
+            .markets
+            .iter()
+            .position(|m| m.condition_id == condition_id)
+        {
+            Some(index) => index,
+            None => {
+                self.markets.push(MarketExposure::new(condition_id));
+                self.markets.len() - 1
+            }
+        };
+        &mut self.markets[index]
+    }
+
+    /// Total cost of all legs in the group
+    pub fn gross_notional(&self) -> Decimal {
+        self.markets
+            .iter()
+            .map(MarketExposure::gross_notional)
+            .sum()
+    }
+
+    /// P&L if `winner` settles YES and every other market settles NO; `None`
+    /// means no held market wins
+    pub fn pnl_if(&self, winner: Option<&str>) -> Decimal {
+        self.markets
+            .iter()
+            .map(|m| {
+                let outcome = if Some(m.condition_id.as_str()) == winner {
+                    Side::Yes
+                } else {
+                    Side::No
+                };
+                m.pnl_if(outcome)
+            })
+            .sum()
+    }
+
+    /// Loss in the worst mutually exclusive outcome (zero if all are profitable)
+    pub fn worst_case_loss(&self) -> Decimal {
+        let worst = self
+            .markets
+            .iter()
+            .map(|m| self.pnl_if(Some(&m.condition_id)))
+            .fold(self.pnl_if(None), Decimal::min);
+        (-worst).max(Decimal::ZERO)
+    }
+
+    /// Total number of contributing positions
+    pub fn position_count(&self) -> usize {
+        self.markets.iter().map(|m| m.legs.len()).sum()
+    }
+}
+
+impl fmt::Display for GroupExposure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} markets, gross {}, worst-case loss {} ({} positions)",
+            self.group_id,
+            self.markets.len(),
+            self.gross_notional().normalize(),
+            self.worst_case_loss().normalize(),
+            self.position_count()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn leg(strategy: &str, side: Side, size: Decimal, price: Decimal) -> ExposureLeg {
+        ExposureLeg {
+            position_id: Uuid::new_v4(),
+            strategy: strategy.to_string(),
+            side,
+            size,
+            entry_price: price,
+        }
+    }
+
+    fn exposure(legs: Vec<ExposureLeg>) -> MarketExposure {
+        MarketExposure {
+            condition_id: "cond".to_string(),
+            legs,
+        }
+    }
+
+    #[test]
+    fn test_directional_worst_case_is_cost() {
+        let e = exposure(vec![leg("lag", Side::Yes, dec!(10), dec!(0.50))]);
+        assert_eq!(e.net_shares(), dec!(10));
+        assert_eq!(e.gross_notional(), dec!(5.00));
+        assert_eq!(e.pnl_if(Side::Yes), dec!(5.00));
+        assert_eq!(e.worst_case_loss(), dec!(5.00));
+    }
+
+    #[test]
+    fn test_spread_pair_is_riskless_below_one() {
+        let e = exposure(vec![
+            leg("spread", Side::Yes, dec!(10), dec!(0.48)),
+            leg("spread", Side::No, dec!(10), dec!(0.49)),
+        ]);
+        assert_eq!(e.net_shares(), dec!(0));
+        assert_eq!(e.pnl_if(Side::Yes), dec!(0.30));
+        assert_eq!(e.pnl_if(Side::No), dec!(0.30));
+        assert_eq!(e.worst_case_loss(), dec!(0));
+    }
+
+    #[test]
+    fn test_spread_pair_reduces_lag_worst_case() {
+        let lag = leg("lag", Side::Yes, dec!(10), dec!(0.50));
+        let alone = exposure(vec![lag.clone()]).worst_case_loss();
+
+        let combined = exposure(vec![
+            leg("spread", Side::Yes, dec!(10), dec!(0.48)),
+            leg("spread", Side::No, dec!(10), dec!(0.49)),
+            lag,
+        ]);
+        // Cost 14.70; NO pays 10 -> -4.70, YES pays 20 -> +5.30
+        assert_eq!(combined.gross_notional(), dec!(14.70));
+        assert_eq!(combined.net_shares(), dec!(10));
+        assert_eq!(combined.worst_case_loss(), dec!(4.70));
+        assert!(combined.worst_case_loss() < alone);
+    }
+
+    #[test]
+    fn test_group_yes_legs_cannot_all_win() {
+        // Two mutually exclusive YES bets: at most one pays
+        let mut group = GroupExposure::new("group");
+        group
+            .market_mut("a")
+            .legs
+            .push(leg("lag", Side::Yes, dec!(10), dec!(0.40)));
+        group
+            .market_mut("b")
+            .legs
+            .push(leg("lag", Side::Yes, dec!(10), dec!(0.30)));
+
+        assert_eq!(group.gross_notional(), dec!(7.00));
+        assert_eq!(group.pnl_if(Some("a")), dec!(3.00));
+        assert_eq!(group.pnl_if(None), dec!(-7.00));
+        assert_eq!(group.worst_case_loss(), dec!(7.00));
+        assert_eq!(group.position_count(), 2);
+    }
+
+    #[test]
+    fn test_group_no_legs_pay_unless_their_market_wins() {
+        let mut group = GroupExposure::new("group");
+        group
+            .market_mut("a")
+            .legs
+            .push(leg("lag", Side::No, dec!(10), dec!(0.60)));
+        group
+            .market_mut("b")
+            .legs
+            .push(leg("lag", Side::No, dec!(10), dec!(0.70)));
+
+        // Cost 13; one NO at most loses, so the worst case still pays 10
+        assert_eq!(group.pnl_if(None), dec!(7.00));
+        assert_eq!(group.pnl_if(Some("b")), dec!(-3.00));
+        assert_eq!(group.worst_case_loss(), dec!(3.00));
+    }
+
+    #[test]
+    fn test_spread_pair_above_one_adds_loss() {
+        // A pair bought at 1.04 loses 0.04 per share whichever way it settles
+        let combined = exposure(vec![
+            leg("spread", Side::Yes, dec!(10), dec!(0.52)),
+            leg("spread", Side::No, dec!(10), dec!(0.52)),
+            leg("lag", Side::No, dec!(20), dec!(0.30)),
+        ]);
+        assert_eq!(combined.net_shares(), dec!(-20));
+        assert_eq!(combined.pnl_if(Side::No), dec!(13.60));
+        assert_eq!(combined.pnl_if(Side::Yes), dec!(-6.40));
+        assert_eq!(combined.worst_case_loss(), dec!(6.40));
+    }
+}
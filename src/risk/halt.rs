@@ -0,0 +1,229 @@
+//! Persistent hard halts
+//!
+//! A hard halt (daily loss, drawdown) must survive a restart: it is written
+//! to `<data dir>/halts/<id>.json` and, until an operator acknowledges it
+//! with `poly-hft ctl ack-halt <id>` (or deletes the file), the bot starts
+//! observe-only. Soft halts are never written and clear with the process.
+
+use super::{DrawdownMonitor, HaltReason};
+use crate::journal::Journal;
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Subdirectory of the data directory holding halt records
+pub const HALTS_DIR: &str = "halts";
+
+/// Journal of halts and acknowledgements, in the data directory
+pub const HALT_JOURNAL_FILE: &str = "halt_journal.jsonl";
+
+/// A hard halt and the equity that triggered it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HaltRecord {
+    /// Short identifier used to acknowledge the halt
+    pub id: String,
+    /// Why trading stopped
+    pub reason: HaltReason,
+    /// When trading stopped
+    pub halted_at: DateTime<Utc>,
+    /// Equity at the halt
+    pub equity: Decimal,
+    /// Peak equity before the halt
+    pub peak_equity: Decimal,
+    /// Drawdown from peak at the halt
+    pub drawdown: Decimal,
+    /// P&L since the start of the day
+    pub daily_pnl: Decimal,
+    /// When an operator acknowledged the halt
+    #[serde(default)]
+    pub acknowledged_at: Option<DateTime<Utc>>,
+}
+
+impl HaltRecord {
+    /// Snapshot `monitor` for a halt at `now`
+    pub fn new(reason: HaltReason, monitor: &DrawdownMonitor, now: DateTime<Utc>) -> Self {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            id: id[..8].to_string(),
+            reason,
+            halted_at: now,
+            equity: monitor.current_equity,
+            peak_equity: monitor.peak_equity,
+            drawdown: monitor.current_drawdown(),
+            daily_pnl: monitor.daily_pnl,
+            acknowledged_at: None,
+        }
+    }
+}
+
+impl fmt::Display for HaltRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:?} at {} (equity {:.2}, peak {:.2})",
+            self.id,
+            self.reason,
+            self.halted_at.to_rfc3339(),
+            self.equity,
+            self.peak_equity
+        )
+    }
+}
+
+/// Halt records in a data directory
+pub struct HaltStore {
+    dir: PathBuf,
+    journal: Option<Journal>,
+}
+
+impl HaltStore {
+    /// Store under `data_dir`
+    pub fn new(data_dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: data_dir.as_ref().join(HALTS_DIR),
+            journal: None,
+        }
+    }
+
+    /// Journal every halt and acknowledgement
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Directory holding the records
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Persist a new halt
+    pub fn record(&self, record: &HaltRecord) -> anyhow::Result<PathBuf> {
+        let path = self.write(record)?;
+        self.journal("hard_halt", record);
+        Ok(path)
+    }
+
+    /// Unacknowledged halts, oldest first
+    pub fn pending(&self) -> anyhow::Result<Vec<HaltRecord>> {
+        let mut pending: Vec<HaltRecord> = self
+            .all()?
+            .into_iter()
+            .filter(|r| r.acknowledged_at.is_none())
+            .collect();
+        pending.sort_by_key(|r| r.halted_at);
+        Ok(pending)
+    }
+
+    /// Mark halt `id` acknowledged so orders may flow again
+    pub fn acknowledge(&self, id: &str, now: DateTime<Utc>) -> anyhow::Result<HaltRecord> {
+        let mut record = self
+            .all()?
+            .into_iter()
+            .find(|r| r.id == id)
+            .ok_or_else(|| anyhow!("no halt with id {} in {}", id, self.dir.display()))?;
+        if let Some(at) = record.acknowledged_at {
+            bail!(
+                "halt {} was already acknowledged at {}",
+                id,
+                at.to_rfc3339()
+            );
+        }
+        record.acknowledged_at = Some(now);
+        self.write(&record)?;
+        self.journal("halt_acknowledged", &record);
+        Ok(record)
+    }
+
+    fn all(&self) -> anyhow::Result<Vec<HaltRecord>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut records = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let record = serde_json::from_slice(&std::fs::read(&path)?)
+                .map_err(|e| anyhow!("unreadable halt record {}: {}", path.display(), e))?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    fn write(&self, record: &HaltRecord) -> anyhow::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}.json", record.id));
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(record)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    fn journal(&self, kind: &str, record: &HaltRecord) {
+        if let Some(journal) = &self.journal {
+            if let Err(e) = journal.append(kind, record) {
+                tracing::warn!(error = %e, "Failed to journal halt");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn halt() -> HaltRecord {
+        let mut monitor = DrawdownMonitor::new(dec!(500));
+        monitor.update(dec!(440));
+        HaltRecord::new(
+            HaltReason::MaxDrawdownReached(monitor.current_drawdown()),
+            &monitor,
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn test_halt_survives_restart_until_acknowledged() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = halt();
+        assert_eq!(record.drawdown, dec!(0.12));
+        HaltStore::new(dir.path()).record(&record).unwrap();
+
+        // A fresh store over the same directory stands in for a restart
+        let store = HaltStore::new(dir.path())
+            .with_journal(Journal::open(dir.path().join(HALT_JOURNAL_FILE)).unwrap());
+        assert_eq!(store.pending().unwrap(), vec![record.clone()]);
+
+        assert!(store.acknowledge("missing", Utc::now()).is_err());
+        let acked = store.acknowledge(&record.id, Utc::now()).unwrap();
+        assert!(acked.acknowledged_at.is_some());
+        assert!(store.acknowledge(&record.id, Utc::now()).is_err());
+        assert!(HaltStore::new(dir.path()).pending().unwrap().is_empty());
+
+        let kinds: Vec<_> = Journal::read_all(dir.path().join(HALT_JOURNAL_FILE))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(kinds, vec!["halt_acknowledged"]);
+    }
+
+    #[test]
+    fn test_deleting_the_record_clears_the_halt() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HaltStore::new(dir.path());
+        assert!(store.pending().unwrap().is_empty());
+
+        let path = store.record(&halt()).unwrap();
+        assert_eq!(store.pending().unwrap().len(), 1);
+        std::fs::remove_file(path).unwrap();
+        assert!(store.pending().unwrap().is_empty());
+    }
+}
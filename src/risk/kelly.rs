@@ -1,9 +1,13 @@
 //! Kelly criterion position sizing
 
+use super::PositionSizer;
 use crate::signal::Signal;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
+/// Default cap on shares as a multiple of the size within 2 cents of the touch
+pub const DEFAULT_MAX_DEPTH_MULTIPLE: Decimal = dec!(0.5);
+
 /// Kelly criterion calculator for binary outcomes
 pub struct KellyCalculator {
     /// Kelly fraction (e.g., 0.25 for quarter Kelly)
@@ -27,6 +31,38 @@ impl KellyCalculator {
     /// - Shares pay $1 if correct, $0 if wrong
     /// - Odds: b = (1 - market_price) / market_price
     /// - Kelly fraction: f* = (p*b - q) / b = (fair_value - market_price) / (1 - market_price)
+    ///
+    /// The result is in USD, capped at `max_bet_pct` of the bankroll:
+    ///
+    /// ```
+    /// use poly_hft::prelude::*;
+    /// use chrono::{DateTime, Duration};
+    /// use rust_decimal_macros::dec;
+    ///
+    /// let open = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+    /// let market = Market::new("btc-15m", "BTC", dec!(100000), open, open + Duration::minutes(15));
+    /// let signal = |fair_value, market_price| {
+    ///     Signal::new(
+    ///         market.clone(),
+    ///         Side::Yes,
+    ///         fair_value,
+    ///         market_price,
+    ///         fair_value - market_price,
+    ///         dec!(0.8),
+    ///         SignalReason::SpotDivergence,
+    ///     )
+    /// };
+    ///
+    /// // 5 cents of edge at even odds: f* = 0.05 / 0.50 = 10%, a quarter
+    /// // of that is 2.5% of 1000, under the 5% cap
+    /// let kelly = KellyCalculator::new(dec!(0.25), dec!(0.05));
+    /// assert_eq!(kelly.calculate(&signal(dec!(0.55), dec!(0.50)), dec!(1000)), dec!(25));
+    /// // The same edge on a 0.90 favourite is half the bankroll at full
+    /// // Kelly (0.05 / 0.10), an eighth at a quarter: capped at 5%
+    /// assert_eq!(kelly.calculate(&signal(dec!(0.95), dec!(0.90)), dec!(1000)), dec!(50));
+    /// // No edge, no stake
+    /// assert_eq!(kelly.calculate(&signal(dec!(0.50), dec!(0.50)), dec!(1000)), dec!(0));
+    /// ```
     pub fn calculate(&self, signal: &Signal, bankroll: Decimal) -> Decimal {
         let edge = signal.fair_value - signal.market_price;
 
@@ -49,6 +85,12 @@ impl KellyCalculator {
     }
 }
 
+impl PositionSizer for KellyCalculator {
+    fn size(&self, signal: &Signal, bankroll: Decimal) -> Decimal {
+        self.calculate(signal, bankroll)
+    }
+}
+
 impl Default for KellyCalculator {
     fn default() -> Self {
         Self::new(dec!(0.25), dec!(0.01))
@@ -68,11 +110,14 @@ mod tests {
         Signal::new(
             Market {
                 condition_id: "test".to_string(),
+                asset: "BTC".to_string(),
                 yes_token_id: "yes".to_string(),
                 no_token_id: "no".to_string(),
                 open_price: dec!(100000),
                 open_time: now,
                 close_time: now + Duration::minutes(15),
+                group_id: None,
+                orientation: Default::default(),
             },
             Side::Yes,
             fair_value,
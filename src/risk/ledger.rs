@@ -0,0 +1,321 @@
+//! Conservation of money, checked in tests
+//!
+//! [`CashLedger`] keeps its own cash balance and token holdings from fills
+//! and settlement payouts alone, the way a wallet would see them. Whatever
+//! the tracker does, cash plus the open exposure at cost plus the entry
+//! fees of open positions must equal the initial bankroll plus realized
+//! P&L: money only moves between cash and positions, and leaves as fees.
+//! Realized P&L is rounded per position, so each closed position may be
+//! off by half a cent-hundredth.
+
+use super::PositionTracker;
+use crate::execution::{Fill, OrderAction};
+use crate::market::Market;
+use crate::precision::USD_DP;
+use crate::signal::Side;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Cash and holdings as a wallet would see them
+#[derive(Debug, Clone)]
+pub(crate) struct CashLedger {
+    initial: Decimal,
+    cash: Decimal,
+    fees: Decimal,
+    payouts: Decimal,
+    /// Shares held per token
+    holdings: HashMap<String, Decimal>,
+}
+
+impl CashLedger {
+    pub(crate) fn new(initial: Decimal) -> Self {
+        Self {
+            initial,
+            cash: initial,
+            fees: Decimal::ZERO,
+            payouts: Decimal::ZERO,
+            holdings: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn apply_fill(&mut self, fill: &Fill) {
+        let notional = fill.price * fill.size;
+        let held = self.holdings.entry(fill.token_id.clone()).or_default();
+        match fill.action {
+            OrderAction::Buy => {
+                self.cash -= notional + fill.fee;
+                *held += fill.size;
+            }
+            OrderAction::Sell => {
+                self.cash += notional - fill.fee;
+                *held -= fill.size;
+            }
+        }
+        self.fees += fill.fee;
+    }
+
+    /// Pay out the winning token's holdings in `market`, returning the payout
+    pub(crate) fn settle(&mut self, market: &Market, winner: Side) -> Decimal {
+        let (won, lost) = match winner {
+            Side::Yes => (&market.yes_token_id, &market.no_token_id),
+            Side::No => (&market.no_token_id, &market.yes_token_id),
+        };
+        self.holdings.remove(lost);
+        let payout = self.holdings.remove(won).unwrap_or_default();
+        self.cash += payout;
+        self.payouts += payout;
+        payout
+    }
+
+    /// Check the accounting identity against `tracker`
+    pub(crate) fn check(&self, tracker: &PositionTracker) -> Result<(), String> {
+        let open = tracker.open_positions.values();
+        let open_cost: Decimal = open.clone().map(|p| p.size * p.entry_price).sum();
+        let open_fees: Decimal = open.clone().map(|p| p.entry_fee).sum();
+        let open_shares: Decimal = open.map(|p| p.size).sum();
+        let held: Decimal = self.holdings.values().sum();
+        if tracker.total_exposure != open_cost {
+            return Err(format!(
+                "exposure {} is not the open cost {}",
+                tracker.total_exposure, open_cost
+            ));
+        }
+        if held != open_shares {
+            return Err(format!(
+                "{} shares held but {} open in the tracker",
+                held, open_shares
+            ));
+        }
+        if tracker.total_fees != self.fees {
+            return Err(format!(
+                "tracker fees {} but fills paid {}",
+                tracker.total_fees, self.fees
+            ));
+        }
+
+        let assets = self.cash + tracker.total_exposure + open_fees;
+        let expected = self.initial + tracker.realized_pnl();
+        let tolerance = Decimal::new(5, USD_DP + 1) * Decimal::from(tracker.closed_count());
+        if (assets - expected).abs() > tolerance {
+            return Err(format!(
+                "cash {} + exposure {} + open fees {} = {}, but initial {} + realized {} = {} \
+                 (payouts {}, fees {})",
+                self.cash,
+                tracker.total_exposure,
+                open_fees,
+                assets,
+                self.initial,
+                tracker.realized_pnl(),
+                expected,
+                self.payouts,
+                self.fees
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::{CostModel, ExecutionEngine, Order, OrderType, PaperEngine};
+    use chrono::{DateTime, Utc};
+    use proptest::prelude::*;
+    use rust_decimal_macros::dec;
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Buy {
+            market: usize,
+            side: Side,
+            cents: i64,
+            shares: i64,
+            maker: bool,
+        },
+        Sell {
+            market: usize,
+            side: Side,
+            cents: i64,
+        },
+        Settle {
+            market: usize,
+            winner: Side,
+        },
+    }
+
+    fn any_side() -> impl Strategy<Value = Side> {
+        prop_oneof![Just(Side::Yes), Just(Side::No)]
+    }
+
+    fn any_op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            4 => (0..3usize, any_side(), 1..100i64, 1..50_000i64, any::<bool>()).prop_map(
+                |(market, side, cents, shares, maker)| Op::Buy {
+                    market,
+                    side,
+                    cents,
+                    shares,
+                    maker,
+                }
+            ),
+            2 => (0..3usize, any_side(), 1..100i64)
+                .prop_map(|(market, side, cents)| Op::Sell { market, side, cents }),
+            1 => (0..3usize, any_side()).prop_map(|(market, winner)| Op::Settle { market, winner }),
+        ]
+    }
+
+    fn any_costs() -> impl Strategy<Value = CostModel> {
+        (0..200i64, 0..200i64, 0..300i64).prop_map(|(maker, taker, slippage)| CostModel {
+            maker_fee_rate: Decimal::new(maker, 4),
+            taker_fee_rate: Decimal::new(taker, 4),
+            base_slippage: Decimal::new(slippage, 4),
+            ..Default::default()
+        })
+    }
+
+    fn market(i: usize) -> Market {
+        let at = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+        Market {
+            condition_id: format!("cond-{}", i),
+            asset: "BTC".to_string(),
+            yes_token_id: format!("yes-{}", i),
+            no_token_id: format!("no-{}", i),
+            open_price: dec!(100000),
+            open_time: at,
+            close_time: at,
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    async fn execute(engine: &PaperEngine, order: Order) -> Fill {
+        let id = engine.submit_order(order).await.unwrap();
+        let fills = engine.get_fills().await.unwrap();
+        fills.into_iter().find(|f| f.order_id == id).unwrap()
+    }
+
+    async fn run(costs: CostModel, ops: Vec<Op>) -> Result<(), String> {
+        let engine = PaperEngine::with_cost_model(costs);
+        let mut tracker = PositionTracker::new();
+        let mut ledger = CashLedger::new(dec!(1000));
+        let markets: Vec<Market> = (0..3).map(market).collect();
+        let token = |market: &Market, side| match side {
+            Side::Yes => market.yes_token_id.clone(),
+            Side::No => market.no_token_id.clone(),
+        };
+        for (step, op) in ops.into_iter().enumerate() {
+            match op {
+                Op::Buy {
+                    market,
+                    side,
+                    cents,
+                    shares,
+                    maker,
+                } => {
+                    let market = &markets[market];
+                    let order = Order {
+                        token_id: token(market, side),
+                        side,
+                        price: Decimal::new(cents, 2),
+                        size: Decimal::new(shares, 2),
+                        order_type: if maker {
+                            OrderType::Limit
+                        } else {
+                            OrderType::Market
+                        },
+                        action: OrderAction::Buy,
+                        client_order_id: None,
+                    };
+                    let fill = execute(&engine, order).await;
+                    tracker.apply_fill(market, &fill);
+                    ledger.apply_fill(&fill);
+                }
+                Op::Sell {
+                    market,
+                    side,
+                    cents,
+                } => {
+                    let market = &markets[market];
+                    // Sells only close what is held, whole
+                    let Some(position) = tracker
+                        .open_positions
+                        .values()
+                        .filter(|p| p.market.condition_id == market.condition_id && p.side == side)
+                        .min_by_key(|p| (p.entry_time, p.id))
+                        .cloned()
+                    else {
+                        continue;
+                    };
+                    let order = Order {
+                        token_id: token(market, side),
+                        side,
+                        price: Decimal::new(cents, 2),
+                        size: position.size,
+                        order_type: OrderType::Market,
+                        action: OrderAction::Sell,
+                        client_order_id: None,
+                    };
+                    let fill = execute(&engine, order).await;
+                    tracker.close(position.id, &fill).unwrap();
+                    ledger.apply_fill(&fill);
+                }
+                Op::Settle { market, winner } => {
+                    let market = &markets[market];
+                    tracker.settle(&market.condition_id, winner, Utc::now());
+                    ledger.settle(market, winner);
+                }
+            }
+            ledger
+                .check(&tracker)
+                .map_err(|e| format!("after step {}: {}", step, e))?;
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_money_is_conserved_through_the_tracker(
+            costs in any_costs(),
+            ops in prop::collection::vec(any_op(), 1..60),
+        ) {
+            let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+            let result = runtime.block_on(run(costs, ops));
+            prop_assert!(result.is_ok(), "{}", result.unwrap_err());
+        }
+    }
+
+    #[test]
+    fn test_close_releases_cost_and_charges_both_fees() {
+        let market = market(0);
+        let mut tracker = PositionTracker::new();
+        let mut ledger = CashLedger::new(dec!(100));
+        let fill = |action, price, fee| Fill {
+            order_id: uuid::Uuid::new_v4(),
+            token_id: market.no_token_id.clone(),
+            side: Side::No,
+            price,
+            size: dec!(10),
+            timestamp: Utc::now(),
+            fee,
+            estimated_slippage: Decimal::ZERO,
+            liquidity: crate::execution::LiquidityFlag::Taker,
+            action,
+            client_order_id: String::new(),
+            simulated: false,
+        };
+        for f in [
+            fill(OrderAction::Buy, dec!(0.40), dec!(0.04)),
+            fill(OrderAction::Sell, dec!(0.70), dec!(0.07)),
+        ] {
+            tracker.apply_fill(&market, &f);
+            ledger.apply_fill(&f);
+            ledger.check(&tracker).unwrap();
+        }
+        // 100 - 4.04 + 6.93
+        assert_eq!(ledger.cash, dec!(102.89));
+        assert_eq!(tracker.realized_pnl(), dec!(2.89));
+        assert_eq!(tracker.total_exposure, Decimal::ZERO);
+    }
+}
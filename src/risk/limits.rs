@@ -1,8 +1,29 @@
 //! Position limits and drawdown controls
 
+use super::{ExposureLeg, GroupExposure, MarketExposure, PositionTracker, RiskError};
+use crate::execution::{Order, OrderAction};
+use crate::market::Market;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Per-market caps on exposure aggregated across strategies (unset = uncapped)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarketLimits {
+    /// Maximum absolute YES minus NO shares
+    #[serde(default)]
+    pub max_net_shares: Option<Decimal>,
+    /// Maximum total cost of all positions
+    #[serde(default)]
+    pub max_gross_notional: Option<Decimal>,
+    /// Maximum loss in the worse settlement outcome
+    #[serde(default)]
+    pub max_worst_case_loss: Option<Decimal>,
+    /// Maximum total cost across the markets of one neg-risk group
+    #[serde(default)]
+    pub max_exposure_per_group: Option<Decimal>,
+}
 
 /// Position and risk limits
 #[derive(Debug, Clone, Deserialize)]
@@ -17,6 +38,9 @@ pub struct PositionLimits {
     pub max_drawdown_pct: Decimal,
     /// Maximum total exposure percentage
     pub max_exposure_pct: Decimal,
+    /// Per-market caps across strategies
+    #[serde(default)]
+    pub market: MarketLimits,
 }
 
 impl Default for PositionLimits {
@@ -27,12 +51,138 @@ impl Default for PositionLimits {
             max_daily_loss_pct: dec!(0.05),
             max_drawdown_pct: dec!(0.10),
             max_exposure_pct: dec!(0.10),
+            market: MarketLimits::default(),
+        }
+    }
+}
+
+impl PositionLimits {
+    /// Exposure in the order's market as it would be after the order fills
+    pub fn market_exposure_after(
+        &self,
+        order: &Order,
+        tracker: &PositionTracker,
+    ) -> MarketExposure {
+        let mut exposure = tracker
+            .exposure_for_token(&order.token_id)
+            .unwrap_or_else(|| MarketExposure::new(&order.token_id));
+        exposure.legs.push(ExposureLeg {
+            position_id: Uuid::nil(),
+            strategy: "order".to_string(),
+            side: order.side,
+            size: order.size,
+            entry_price: order.price,
+        });
+        exposure
+    }
+
+    /// Exposure in `market`'s neg-risk group as it would be after the order
+    /// fills, or `None` for an ungrouped market
+    pub fn group_exposure_after(
+        &self,
+        order: &Order,
+        market: &Market,
+        tracker: &PositionTracker,
+    ) -> Option<GroupExposure> {
+        let group_id = market.group_id.as_deref()?;
+        let mut exposure = tracker
+            .exposure_for_group(group_id)
+            .unwrap_or_else(|| GroupExposure::new(group_id));
+        exposure
+            .market_mut(&market.condition_id)
+            .legs
+            .push(ExposureLeg {
+                position_id: Uuid::nil(),
+                strategy: "order".to_string(),
+                side: order.side,
+                size: order.size,
+                entry_price: order.price,
+            });
+        Some(exposure)
+    }
+
+    /// Reject buys that would push `market`'s neg-risk group over its cap.
+    /// Ungrouped markets and sells always pass.
+    pub fn check_group_limits(
+        &self,
+        order: &Order,
+        market: &Market,
+        tracker: &PositionTracker,
+    ) -> Result<(), RiskError> {
+        if order.action == OrderAction::Sell {
+            return Ok(());
+        }
+        let Some(cap) = self.market.max_exposure_per_group else {
+            return Ok(());
+        };
+        let Some(after) = self.group_exposure_after(order, market, tracker) else {
+            return Ok(());
+        };
+        let value = after.gross_notional();
+        if value <= cap {
+            return Ok(());
         }
+        let existing = match tracker.exposure_for_group(&after.group_id) {
+            Some(existing) => existing
+                .markets
+                .iter()
+                .map(MarketExposure::to_string)
+                .collect::<Vec<_>>()
+                .join("; "),
+            None => "none".to_string(),
+        };
+        Err(RiskError::GroupLimitBreached {
+            group: after.group_id,
+            value,
+            cap,
+            existing,
+        })
+    }
+
+    /// Reject buys that would breach a per-market cap. Sells only reduce
+    /// exposure and always pass.
+    pub fn check_limits(&self, order: &Order, tracker: &PositionTracker) -> Result<(), RiskError> {
+        if order.action == OrderAction::Sell {
+            return Ok(());
+        }
+
+        let after = self.market_exposure_after(order, tracker);
+        let checks = [
+            (
+                "worst-case loss",
+                after.worst_case_loss(),
+                self.market.max_worst_case_loss,
+            ),
+            (
+                "net shares",
+                after.net_shares().abs(),
+                self.market.max_net_shares,
+            ),
+            (
+                "gross notional",
+                after.gross_notional(),
+                self.market.max_gross_notional,
+            ),
+        ];
+        for (limit, value, cap) in checks {
+            if let Some(cap) = cap.filter(|cap| value > *cap) {
+                let mut existing = after.clone();
+                existing.legs.pop();
+                return Err(RiskError::MarketLimitBreached {
+                    market: after.condition_id,
+                    limit,
+                    value,
+                    cap,
+                    positions: existing.describe_legs(),
+                });
+            }
+        }
+        Ok(())
     }
 }
 
 /// Reason for trading halt
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HaltReason {
     /// Maximum daily loss reached
     MaxDailyLossReached(Decimal),
@@ -42,6 +192,17 @@ pub enum HaltReason {
     MaxExposureReached(Decimal),
 }
 
+impl HaltReason {
+    /// Hard halts persist across restarts until acknowledged; soft halts
+    /// clear on their own
+    pub fn is_hard(&self) -> bool {
+        match self {
+            HaltReason::MaxDailyLossReached(_) | HaltReason::MaxDrawdownReached(_) => true,
+            HaltReason::MaxExposureReached(_) => false,
+        }
+    }
+}
+
 /// Monitors drawdown and triggers halts
 pub struct DrawdownMonitor {
     /// Peak equity value
@@ -52,6 +213,8 @@ pub struct DrawdownMonitor {
     pub daily_start_equity: Decimal,
     /// Today's P&L
     pub daily_pnl: Decimal,
+    /// Deepest drawdown from peak seen so far
+    pub max_drawdown: Decimal,
 }
 
 impl DrawdownMonitor {
@@ -62,6 +225,7 @@ impl DrawdownMonitor {
             current_equity: initial_equity,
             daily_start_equity: initial_equity,
             daily_pnl: dec!(0),
+            max_drawdown: dec!(0),
         }
     }
 
@@ -72,6 +236,7 @@ impl DrawdownMonitor {
             self.peak_equity = new_equity;
         }
         self.daily_pnl = new_equity - self.daily_start_equity;
+        self.max_drawdown = self.max_drawdown.max(self.current_drawdown());
     }
 
     /// Get current drawdown from peak
@@ -105,6 +270,16 @@ impl DrawdownMonitor {
         None
     }
 
+    /// Move equity in or out without counting it as profit or loss
+    ///
+    /// A deposit or withdrawal shifts the peak and the day's start with the
+    /// current equity, so neither reads as a drawdown or a gain.
+    pub fn transfer(&mut self, amount: Decimal) {
+        self.current_equity += amount;
+        self.peak_equity += amount;
+        self.daily_start_equity += amount;
+    }
+
     /// Reset for new trading day
     pub fn reset_daily(&mut self) {
         self.daily_start_equity = self.current_equity;
@@ -115,6 +290,267 @@ impl DrawdownMonitor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::execution::{Fill, LiquidityFlag, OrderType};
+    use crate::market::Market;
+    use crate::signal::{Side, Signal, SignalReason};
+    use chrono::{Duration, Utc};
+
+    fn market() -> Market {
+        Market {
+            condition_id: "cond-1".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes-1".to_string(),
+            no_token_id: "no-1".to_string(),
+            open_price: dec!(100000),
+            open_time: Utc::now() - Duration::minutes(5),
+            close_time: Utc::now() + Duration::minutes(10),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    fn open(
+        tracker: &mut PositionTracker,
+        strategy: &str,
+        side: Side,
+        size: Decimal,
+        price: Decimal,
+    ) {
+        open_in(tracker, market(), strategy, side, size, price);
+    }
+
+    fn open_in(
+        tracker: &mut PositionTracker,
+        market: Market,
+        strategy: &str,
+        side: Side,
+        size: Decimal,
+        price: Decimal,
+    ) {
+        let token_id = match side {
+            Side::Yes => market.yes_token_id.clone(),
+            Side::No => market.no_token_id.clone(),
+        };
+        let signal = Signal::new(
+            market,
+            side,
+            dec!(0.60),
+            price,
+            dec!(0.02),
+            dec!(0.8),
+            SignalReason::SpotDivergence,
+        );
+        let fill = Fill {
+            order_id: Uuid::new_v4(),
+            token_id,
+            side,
+            price,
+            size,
+            timestamp: Utc::now(),
+            fee: Decimal::ZERO,
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: String::new(),
+            simulated: false,
+        };
+        tracker.open_for_strategy(strategy, &signal, &fill);
+    }
+
+    fn buy(token: &str, side: Side, size: Decimal, price: Decimal) -> Order {
+        Order {
+            token_id: token.to_string(),
+            side,
+            price,
+            size,
+            order_type: OrderType::Limit,
+            action: OrderAction::Buy,
+            client_order_id: None,
+        }
+    }
+
+    fn worst_case_capped(cap: Decimal) -> PositionLimits {
+        PositionLimits {
+            market: MarketLimits {
+                max_worst_case_loss: Some(cap),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_spread_pair_makes_room_for_lag_order() {
+        let limits = worst_case_capped(dec!(4.80));
+        let order = buy("yes-1", Side::Yes, dec!(10), dec!(0.50));
+
+        // Alone the lag order risks 5.00
+        assert!(limits
+            .check_limits(&order, &PositionTracker::new())
+            .is_err());
+
+        // On top of a sub-1 spread pair the worst case drops to 4.70
+        let mut tracker = PositionTracker::new();
+        open(&mut tracker, "spread", Side::Yes, dec!(10), dec!(0.48));
+        open(&mut tracker, "spread", Side::No, dec!(10), dec!(0.49));
+        let after = limits.market_exposure_after(&order, &tracker);
+        assert_eq!(after.condition_id, "cond-1");
+        assert_eq!(after.worst_case_loss(), dec!(4.70));
+        assert!(limits.check_limits(&order, &tracker).is_ok());
+    }
+
+    #[test]
+    fn test_breach_names_existing_positions() {
+        let limits = worst_case_capped(dec!(7));
+        let mut tracker = PositionTracker::new();
+        open(&mut tracker, "spread", Side::Yes, dec!(10), dec!(0.52));
+        open(&mut tracker, "spread", Side::No, dec!(10), dec!(0.52));
+        open(&mut tracker, "lag", Side::Yes, dec!(10), dec!(0.40));
+
+        // Worst case (NO settles): cost 10.40 + 4.00 + 3.00 against 10 paid out
+        let order = buy("yes-1", Side::Yes, dec!(5), dec!(0.60));
+        let err = limits.check_limits(&order, &tracker).unwrap_err();
+        match &err {
+            RiskError::MarketLimitBreached {
+                market,
+                limit,
+                value,
+                cap,
+                ..
+            } => {
+                assert_eq!(market, "cond-1");
+                assert_eq!(*limit, "worst-case loss");
+                assert_eq!(*value, dec!(7.40));
+                assert_eq!(*cap, dec!(7));
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+        let message = err.to_string();
+        assert_eq!(message.matches("[spread]").count(), 2);
+        assert!(message.contains("[lag] Yes 10 @ 0.4"));
+        assert!(!message.contains("[order]"));
+
+        // Buying NO instead hedges and passes
+        let hedge = buy("no-1", Side::No, dec!(5), dec!(0.60));
+        assert!(limits.check_limits(&hedge, &tracker).is_ok());
+
+        // Sells are never blocked
+        let mut sell = order.clone();
+        sell.action = OrderAction::Sell;
+        assert!(limits.check_limits(&sell, &tracker).is_ok());
+    }
+
+    #[test]
+    fn test_net_and_gross_caps() {
+        let limits = PositionLimits {
+            market: MarketLimits {
+                max_net_shares: Some(dec!(15)),
+                max_gross_notional: Some(dec!(12)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut tracker = PositionTracker::new();
+        open(&mut tracker, "lag", Side::No, dec!(10), dec!(0.45));
+
+        let err = limits
+            .check_limits(&buy("no-1", Side::No, dec!(6), dec!(0.45)), &tracker)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RiskError::MarketLimitBreached {
+                limit: "net shares",
+                ..
+            }
+        ));
+
+        // Opposite side nets down but still adds gross notional
+        let err = limits
+            .check_limits(&buy("yes-1", Side::Yes, dec!(14), dec!(0.55)), &tracker)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RiskError::MarketLimitBreached {
+                limit: "gross notional",
+                ..
+            }
+        ));
+        assert!(limits
+            .check_limits(&buy("yes-1", Side::Yes, dec!(10), dec!(0.55)), &tracker)
+            .is_ok());
+    }
+
+    fn grouped(condition_id: &str) -> Market {
+        Market {
+            condition_id: condition_id.to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: format!("{condition_id}-yes"),
+            no_token_id: format!("{condition_id}-no"),
+            group_id: Some("group-1".to_string()),
+            orientation: Default::default(),
+            ..market()
+        }
+    }
+
+    #[test]
+    fn test_group_cap_spans_markets() {
+        let limits = PositionLimits {
+            market: MarketLimits {
+                max_exposure_per_group: Some(dec!(10)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut tracker = PositionTracker::new();
+        open_in(
+            &mut tracker,
+            grouped("a"),
+            "lag",
+            Side::Yes,
+            dec!(10),
+            dec!(0.40),
+        );
+        open_in(
+            &mut tracker,
+            grouped("b"),
+            "lag",
+            Side::Yes,
+            dec!(10),
+            dec!(0.30),
+        );
+
+        // A third market in the group would push gross notional to 12
+        let c = grouped("c");
+        let order = buy("c-yes", Side::Yes, dec!(10), dec!(0.50));
+        let after = limits.group_exposure_after(&order, &c, &tracker).unwrap();
+        assert_eq!(after.markets.len(), 3);
+        assert_eq!(after.gross_notional(), dec!(12.00));
+        let err = limits.check_group_limits(&order, &c, &tracker).unwrap_err();
+        match &err {
+            RiskError::GroupLimitBreached { group, value, .. } => {
+                assert_eq!(group, "group-1");
+                assert_eq!(*value, dec!(12.00));
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+        assert!(err.to_string().contains("a: net 10 shares"));
+
+        // The same order in an ungrouped market is not bucketed
+        let ungrouped = Market {
+            group_id: None,
+            orientation: Default::default(),
+            ..c.clone()
+        };
+        assert!(limits
+            .group_exposure_after(&order, &ungrouped, &tracker)
+            .is_none());
+        assert!(limits
+            .check_group_limits(&order, &ungrouped, &tracker)
+            .is_ok());
+
+        let small = buy("c-yes", Side::Yes, dec!(5), dec!(0.50));
+        assert!(limits.check_group_limits(&small, &c, &tracker).is_ok());
+    }
 
     #[test]
     fn test_drawdown_monitor() {
@@ -126,6 +562,29 @@ mod tests {
 
         monitor.update(dec!(990)); // Drawdown
         assert_eq!(monitor.current_drawdown(), dec!(0.10)); // 10%
+
+        // The deepest drawdown outlives a recovery
+        monitor.update(dec!(1100));
+        assert_eq!(monitor.current_drawdown(), dec!(0));
+        assert_eq!(monitor.max_drawdown, dec!(0.10));
+    }
+
+    #[test]
+    fn test_transfers_are_not_drawdown() {
+        let mut monitor = DrawdownMonitor::new(dec!(1000));
+        monitor.update(dec!(950));
+
+        // A deposit is neither a new peak nor a gain: the $50 loss stays
+        monitor.transfer(dec!(1000));
+        monitor.update(dec!(1950));
+        assert_eq!(monitor.peak_equity, dec!(2000));
+        assert_eq!(monitor.current_drawdown(), dec!(0.025));
+        assert_eq!(monitor.daily_drawdown(), dec!(0.025));
+
+        monitor.transfer(dec!(-1000));
+        monitor.update(dec!(950));
+        assert_eq!(monitor.current_drawdown(), dec!(0.05));
+        assert_eq!(monitor.daily_drawdown(), dec!(0.05));
     }
 
     #[test]
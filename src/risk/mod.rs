@@ -1,15 +1,63 @@
 //! Risk management module
 //!
-//! Position sizing, limits, and risk controls
+//! Position sizing, limits, rate caps, and risk controls
 
+mod allocation;
+mod bankroll;
+mod cooldown;
+mod damping;
+mod exposure;
+mod halt;
 mod kelly;
+#[cfg(test)]
+pub(crate) mod ledger;
 mod limits;
 mod position;
+mod rate;
+mod resolution;
+mod review;
+mod schedule;
 mod types;
 
-pub use kelly::KellyCalculator;
-pub use limits::{DrawdownMonitor, HaltReason, PositionLimits};
-pub use position::{ClosedPosition, Position, PositionTracker};
+pub use allocation::{
+    Allocation, AllocationConfig, Allocator, Candidate, Ranked, Score, SkipReason,
+    DEFAULT_ALLOCATION_WINDOW_MS, DEFAULT_PRIOR_SIGNALS, DEPTH_SCALE, EDGE_SCALE,
+};
+pub use bankroll::{
+    Ledger, LedgerConfig, LedgerEntry, LedgerEntryKind, LedgerReport, DEFAULT_BALANCE_CHECK_SECS,
+    DEFAULT_DRIFT_TOLERANCE, LEDGER_FILE, OPENING_REFERENCE,
+};
+pub use cooldown::{
+    AssetCooldown, CooldownBlock, CooldownTrigger, LossCooldown, LossCooldownConfig,
+    LOSS_COOLDOWN_FILE,
+};
+pub use damping::{
+    Damping, DampingConfig, DEFAULT_FULL_BELOW, DEFAULT_MAX_DIRECTIONAL_PCT, DEFAULT_ZERO_AT,
+};
+pub use exposure::{ExposureLeg, GroupExposure, MarketExposure};
+pub use halt::{HaltRecord, HaltStore, HALTS_DIR, HALT_JOURNAL_FILE};
+pub use kelly::{KellyCalculator, DEFAULT_MAX_DEPTH_MULTIPLE};
+pub use limits::{DrawdownMonitor, HaltReason, MarketLimits, PositionLimits};
+pub use position::{
+    ArchivedSummary, ClosedPosition, FillEffect, Position, PositionTracker, Settlement,
+};
+pub use rate::{
+    CapBudget, RateCap, RateCapConfig, RateEntry, RateLimiter, DEFAULT_MAX_ENTRIES_PER_ASSET_HOUR,
+    DEFAULT_MAX_ENTRIES_PER_DAY, DEFAULT_MAX_ENTRIES_PER_WINDOW, RATE_CAPS_FILE,
+};
+pub use resolution::{
+    PendingResolution, ResolutionBook, ResolutionConfig, ResolutionStatus,
+    DEFAULT_CONFIRMATION_WINDOW_SECS, DEFAULT_RESOLUTION_POLL_SECS, PENDING_RESOLUTIONS_FILE,
+};
+pub use review::{
+    AssetExcursions, Excursion, ReviewFlag, StrategyReview, StrategyReviewConfig,
+    DEFAULT_BASELINE_TRADES, DEFAULT_MAX_RATIO, DEFAULT_MIN_BASELINE, DEFAULT_RECENT_TRADES,
+    DEFAULT_REVIEW_PERCENTILE, REVIEW_CLEAR_FILE, SPARK_BLOCK, STRATEGY_REVIEW_FILE,
+};
+pub use schedule::{
+    parse_time, ScheduleConfig, ScheduleStatus, ScheduleTransition, StrategySchedule,
+    TradingSchedule, TradingWindow, DEFAULT_STRATEGY,
+};
 pub use types::RiskError;
 
 use crate::execution::Order;
@@ -25,3 +73,9 @@ pub trait RiskManager: Send + Sync {
     /// Check if trading should be halted
     fn should_halt(&self) -> Option<HaltReason>;
 }
+
+/// Trait for position sizing implementations
+pub trait PositionSizer: Send + Sync {
+    /// Dollar size of a position for a signal
+    fn size(&self, signal: &Signal, bankroll: Decimal) -> Decimal;
+}
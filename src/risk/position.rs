@@ -1,13 +1,18 @@
 //! Position tracking
 
-use crate::execution::Fill;
+use super::{GroupExposure, MarketExposure, ResolutionStatus, DEFAULT_STRATEGY};
+use crate::data::{evict_oldest, HistoryArchive};
+use crate::execution::{Fill, OrderAction};
 use crate::market::Market;
+use crate::precision::{round_price, round_size, round_usd};
 use crate::signal::{Side, Signal};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::RangeBounds;
 use uuid::Uuid;
 
 /// An open position
@@ -27,6 +32,34 @@ pub struct Position {
     pub entry_time: DateTime<Utc>,
     /// Current unrealized P&L
     pub unrealized_pnl: Decimal,
+    /// Fee paid on the entry fill, charged to the position when it closes
+    #[serde(default)]
+    pub entry_fee: Decimal,
+    /// Strategy that opened the position
+    #[serde(default = "default_strategy")]
+    pub strategy: String,
+    /// Furthest the held token's bid fell below the entry price while
+    /// marked, per share
+    #[serde(default)]
+    pub max_adverse: Decimal,
+}
+
+fn default_strategy() -> String {
+    DEFAULT_STRATEGY.to_string()
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:.2} @ {:.4} in {} (unrealized {:+.4})",
+            self.side,
+            round_size(self.size),
+            round_price(self.entry_price),
+            self.market.condition_id,
+            round_usd(self.unrealized_pnl)
+        )
+    }
 }
 
 /// A closed position
@@ -38,20 +71,84 @@ pub struct ClosedPosition {
     pub exit_price: Decimal,
     /// Exit timestamp
     pub exit_time: DateTime<Utc>,
-    /// Realized P&L
+    /// Realized P&L, net of entry and exit fees
+    pub realized_pnl: Decimal,
+    /// Total fees paid, entry and exit
+    pub fees: Decimal,
+}
+
+/// A market's resolution, as the engine settled it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settlement {
+    /// Market condition ID
+    pub market_id: String,
+    /// Side whose tokens paid 1
+    pub winner: Side,
+    /// When the market settled
+    pub at: DateTime<Utc>,
+    /// Whether `winner` is the official outcome yet
+    #[serde(default)]
+    pub status: ResolutionStatus,
+}
+
+/// What applying a fill to a tracker changed
+#[derive(Debug, Clone)]
+pub enum FillEffect {
+    /// A buy opened this position
+    Opened(Position),
+    /// A sell closed this position
+    Closed(ClosedPosition),
+    /// A sell with no open position in its token
+    Unmatched,
+}
+
+/// Running totals of closed positions moved to the archive
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchivedSummary {
+    /// Positions archived
+    pub count: u64,
+    /// Positions archived with a positive P&L
+    pub wins: u64,
+    /// Their realized P&L
     pub realized_pnl: Decimal,
-    /// Total fees paid
+    /// Their fees
     pub fees: Decimal,
 }
 
+impl ArchivedSummary {
+    fn add(&mut self, closed: &ClosedPosition) {
+        self.count += 1;
+        if closed.realized_pnl > Decimal::ZERO {
+            self.wins += 1;
+        }
+        self.realized_pnl += closed.realized_pnl;
+        self.fees += closed.fees;
+    }
+
+    fn remove(&mut self, closed: &ClosedPosition) {
+        self.count -= 1;
+        if closed.realized_pnl > Decimal::ZERO {
+            self.wins -= 1;
+        }
+        self.realized_pnl -= closed.realized_pnl;
+        self.fees -= closed.fees;
+    }
+}
+
 /// Tracks all positions
 pub struct PositionTracker {
     /// Open positions by ID
     pub open_positions: HashMap<Uuid, Position>,
-    /// Closed position history
+    /// Recent closed positions; older ones are archived, see
+    /// [`Self::with_archive`]
     pub closed_positions: Vec<ClosedPosition>,
-    /// Total capital at risk
+    /// Cost of the open positions
     pub total_exposure: Decimal,
+    /// Fees paid across all entry and exit fills
+    pub total_fees: Decimal,
+    archive: Option<HistoryArchive>,
+    max_closed: usize,
+    archived: ArchivedSummary,
 }
 
 impl PositionTracker {
@@ -61,49 +158,256 @@ impl PositionTracker {
             open_positions: HashMap::new(),
             closed_positions: vec![],
             total_exposure: dec!(0),
+            total_fees: dec!(0),
+            archive: None,
+            max_closed: 0,
+            archived: ArchivedSummary::default(),
+        }
+    }
+
+    /// Keep about `max_closed` closed positions in memory, archiving older
+    /// ones to `archive`; 0 keeps them all
+    pub fn with_archive(mut self, archive: HistoryArchive, max_closed: usize) -> Self {
+        self.archive = Some(archive);
+        self.max_closed = max_closed;
+        self
+    }
+
+    /// Totals of the closed positions archived so far
+    pub fn archived(&self) -> &ArchivedSummary {
+        &self.archived
+    }
+
+    /// Closed positions that exited within `range`, archived or not,
+    /// oldest exit first
+    pub fn history_query(
+        &self,
+        range: impl RangeBounds<DateTime<Utc>>,
+    ) -> anyhow::Result<Vec<ClosedPosition>> {
+        let mut history = match &self.archive {
+            Some(archive) => archive.read_closed()?,
+            None => vec![],
+        };
+        history.retain(|c| range.contains(&c.exit_time));
+        history.extend(
+            self.closed_positions
+                .iter()
+                .filter(|c| range.contains(&c.exit_time))
+                .cloned(),
+        );
+        history.sort_by_key(|c| c.exit_time);
+        Ok(history)
+    }
+
+    /// Add to the closed history, archiving the oldest once it is full
+    ///
+    /// A failed write keeps the records in memory for the next attempt.
+    fn push_closed(&mut self, closed: ClosedPosition) {
+        self.closed_positions.push(closed);
+        let Some(archive) = &self.archive else {
+            return;
+        };
+        let Some(evicted) = evict_oldest(&mut self.closed_positions, self.max_closed) else {
+            return;
+        };
+        match archive.archive_closed(&evicted) {
+            Ok(path) => {
+                tracing::debug!(path = ?path, count = evicted.len(), "Archived closed positions");
+                for closed in &evicted {
+                    self.archived.add(closed);
+                }
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to archive closed positions");
+                self.closed_positions.splice(0..0, evicted);
+            }
         }
     }
 
     /// Open a new position from a signal and fill
     pub fn open(&mut self, signal: &Signal, fill: &Fill) -> Position {
+        self.open_for_strategy(DEFAULT_STRATEGY, signal, fill)
+    }
+
+    /// Open a new position attributed to `strategy`
+    pub fn open_for_strategy(&mut self, strategy: &str, signal: &Signal, fill: &Fill) -> Position {
+        self.insert(strategy, signal.market.clone(), signal.side, fill)
+    }
+
+    /// Apply a fill in `market` with no signal behind it, as when restoring
+    /// an order found after a crash or rebuilding from the fill stream
+    ///
+    /// A buy opens a position on the fill's side; a sell closes the oldest
+    /// open position on that side of the market.
+    pub fn apply_fill(&mut self, market: &Market, fill: &Fill) -> FillEffect {
+        match fill.action {
+            OrderAction::Buy => {
+                FillEffect::Opened(self.insert(DEFAULT_STRATEGY, market.clone(), fill.side, fill))
+            }
+            OrderAction::Sell => {
+                let oldest = self
+                    .open_positions
+                    .values()
+                    .filter(|p| p.market.condition_id == market.condition_id && p.side == fill.side)
+                    .min_by_key(|p| p.entry_time)
+                    .map(|p| p.id);
+                match oldest.and_then(|id| self.close(id, fill)) {
+                    Some(closed) => FillEffect::Closed(closed),
+                    None => FillEffect::Unmatched,
+                }
+            }
+        }
+    }
+
+    /// Hold `position` as handed over by another process, under its id,
+    /// as if its entry fill were applied here
+    pub fn restore(&mut self, position: Position) {
+        self.total_exposure += position.size * position.entry_price;
+        self.total_fees += position.entry_fee;
+        self.open_positions.insert(position.id, position);
+    }
+
+    /// Positions implied by `fills` alone, applied oldest first
+    ///
+    /// Fills carry only a token, so `markets` maps each to its market.
+    /// Returns the tracker and the fills it could not apply: those of
+    /// tokens in none of `markets`, and sells with nothing open to close.
+    /// Settlements are not fills; apply them with [`Self::settle`].
+    pub fn rebuild_from_fills(fills: &[Fill], markets: &[Market]) -> (Self, Vec<Fill>) {
+        let by_token: HashMap<&str, &Market> = markets
+            .iter()
+            .flat_map(|m| [(m.yes_token_id.as_str(), m), (m.no_token_id.as_str(), m)])
+            .collect();
+        let mut ordered: Vec<&Fill> = fills.iter().collect();
+        ordered.sort_by_key(|f| f.timestamp);
+
+        let mut tracker = Self::new();
+        let mut unapplied = vec![];
+        for fill in ordered {
+            let applied = match by_token.get(fill.token_id.as_str()) {
+                Some(market) => !matches!(tracker.apply_fill(market, fill), FillEffect::Unmatched),
+                None => false,
+            };
+            if !applied {
+                unapplied.push(fill.clone());
+            }
+        }
+        (tracker, unapplied)
+    }
+
+    fn insert(&mut self, strategy: &str, market: Market, side: Side, fill: &Fill) -> Position {
         let position = Position {
             id: Uuid::new_v4(),
-            market: signal.market.clone(),
-            side: signal.side,
-            entry_price: fill.price,
-            size: fill.size,
+            market,
+            side,
+            entry_price: round_price(fill.price),
+            size: round_size(fill.size),
             entry_time: fill.timestamp,
             unrealized_pnl: dec!(0),
+            entry_fee: fill.fee,
+            strategy: strategy.to_string(),
+            max_adverse: Decimal::ZERO,
         };
 
-        self.total_exposure += fill.size * fill.price;
+        self.total_exposure += position.size * position.entry_price;
+        self.total_fees += fill.fee;
         self.open_positions.insert(position.id, position.clone());
         position
     }
 
-    /// Close a position
+    /// Close a position with a sell of its token
+    ///
+    /// Both fills are of the token held, so either side gains when its
+    /// token's price rises. The exposure released is the position's cost,
+    /// whatever the exit price.
+    ///
+    /// A sell of fewer shares than held closes that slice only: it carries
+    /// its share of the entry fee, and the rest stays open under the same
+    /// id.
     pub fn close(&mut self, position_id: Uuid, fill: &Fill) -> Option<ClosedPosition> {
-        let position = self.open_positions.remove(&position_id)?;
-
-        // Calculate P&L
-        let pnl = match position.side {
-            Side::Yes => (fill.price - position.entry_price) * position.size,
-            Side::No => (position.entry_price - fill.price) * position.size,
+        let held = self.open_positions.get_mut(&position_id)?;
+        let position = if fill.size > Decimal::ZERO && fill.size < held.size {
+            let mut slice = held.clone();
+            slice.size = fill.size;
+            slice.entry_fee = round_usd(held.entry_fee * fill.size / held.size);
+            slice.unrealized_pnl = Decimal::ZERO;
+            held.unrealized_pnl = held.unrealized_pnl * (held.size - fill.size) / held.size;
+            held.size -= fill.size;
+            held.entry_fee -= slice.entry_fee;
+            slice
+        } else {
+            self.open_positions.remove(&position_id)?
         };
 
+        let pnl = (fill.price - position.entry_price) * position.size;
+        self.total_exposure -= position.size * position.entry_price;
+        self.total_fees += fill.fee;
+
         let closed = ClosedPosition {
             exit_price: fill.price,
             exit_time: fill.timestamp,
-            realized_pnl: pnl - fill.fees,
-            fees: fill.fees,
+            realized_pnl: round_usd(pnl - position.entry_fee - fill.fee),
+            fees: position.entry_fee + fill.fee,
             position,
         };
 
-        self.total_exposure -= fill.size * fill.price;
-        self.closed_positions.push(closed.clone());
+        self.push_closed(closed.clone());
         Some(closed)
     }
 
+    /// Settle every open position in a market at expiry
+    ///
+    /// Winning tokens pay 1 and losing tokens 0, whichever side was held.
+    pub fn settle(
+        &mut self,
+        market_id: &str,
+        winner: Side,
+        timestamp: DateTime<Utc>,
+    ) -> Vec<ClosedPosition> {
+        let ids: Vec<Uuid> = self
+            .open_positions
+            .values()
+            .filter(|p| p.market.condition_id == market_id)
+            .map(|p| p.id)
+            .collect();
+        let mut settled = vec![];
+        for id in ids {
+            let Some(position) = self.open_positions.remove(&id) else {
+                continue;
+            };
+            self.total_exposure -= position.size * position.entry_price;
+            let closed = settled_at(position, winner, timestamp);
+            self.push_closed(closed.clone());
+            settled.push(closed);
+        }
+        settled
+    }
+
+    /// Settle a settled position again at `winner`'s payout, as when the
+    /// official resolution corrects the outcome it was settled on
+    ///
+    /// `settled` is the position as first settled. The copy in memory is
+    /// replaced; once archived, only the archived totals are corrected and
+    /// the archive file keeps the first payout.
+    pub fn resettle(&mut self, settled: &ClosedPosition, winner: Side) -> ClosedPosition {
+        let corrected = settled_at(settled.position.clone(), winner, settled.exit_time);
+        let id = settled.position.id;
+        // A position sold in parts has a closed entry per part
+        match self
+            .closed_positions
+            .iter_mut()
+            .find(|c| c.position.id == id && c.exit_time == settled.exit_time)
+        {
+            Some(slot) => *slot = corrected.clone(),
+            None if self.archive.is_some() => {
+                self.archived.remove(settled);
+                self.archived.add(&corrected);
+            }
+            None => tracing::warn!(position_id = %id, "Resettled a position not in the history"),
+        }
+        corrected
+    }
+
     /// Update mark-to-market for open positions
     pub fn update_mark(&mut self, market_id: &str, current_price: Decimal) {
         for position in self.open_positions.values_mut() {
@@ -116,17 +420,107 @@ impl PositionTracker {
         }
     }
 
+    /// Mark a position to the bid of the token it holds, keeping its
+    /// maximum adverse excursion
+    pub fn mark(&mut self, position_id: Uuid, bid: Decimal) {
+        if let Some(position) = self.open_positions.get_mut(&position_id) {
+            position.unrealized_pnl = (bid - position.entry_price) * position.size;
+            position.max_adverse = position.max_adverse.max(position.entry_price - bid);
+        }
+    }
+
+    /// P&L of closed positions, archived ones included
+    pub fn realized_pnl(&self) -> Decimal {
+        self.archived.realized_pnl
+            + self
+                .closed_positions
+                .iter()
+                .map(|p| p.realized_pnl)
+                .sum::<Decimal>()
+    }
+
     /// Get total P&L (realized + unrealized)
     pub fn total_pnl(&self) -> Decimal {
-        let realized: Decimal = self.closed_positions.iter().map(|p| p.realized_pnl).sum();
         let unrealized: Decimal = self.open_positions.values().map(|p| p.unrealized_pnl).sum();
-        realized + unrealized
+        self.realized_pnl() + unrealized
     }
 
     /// Get number of open positions
     pub fn open_count(&self) -> usize {
         self.open_positions.len()
     }
+
+    /// Closed positions, archived ones included
+    pub fn closed_count(&self) -> u64 {
+        self.archived.count + self.closed_positions.len() as u64
+    }
+
+    /// Closed positions with a positive P&L, archived ones included
+    pub fn wins(&self) -> u64 {
+        self.archived.wins
+            + self
+                .closed_positions
+                .iter()
+                .filter(|c| c.realized_pnl > Decimal::ZERO)
+                .count() as u64
+    }
+
+    /// Open positions aggregated per market, ordered by condition ID
+    pub fn market_exposures(&self) -> Vec<MarketExposure> {
+        let mut by_market: BTreeMap<&str, MarketExposure> = BTreeMap::new();
+        let mut positions: Vec<_> = self.open_positions.values().collect();
+        positions.sort_by_key(|p| (p.entry_time, p.id));
+        for position in positions {
+            by_market
+                .entry(&position.market.condition_id)
+                .or_insert_with(|| MarketExposure::new(&position.market.condition_id))
+                .add_position(position);
+        }
+        by_market.into_values().collect()
+    }
+
+    /// Open positions in neg-risk groups aggregated per group, ordered by
+    /// group ID
+    pub fn group_exposures(&self) -> Vec<GroupExposure> {
+        let mut by_group: BTreeMap<&str, GroupExposure> = BTreeMap::new();
+        let mut positions: Vec<_> = self.open_positions.values().collect();
+        positions.sort_by_key(|p| (p.market.condition_id.clone(), p.entry_time, p.id));
+        for position in positions {
+            let Some(group_id) = position.market.group_id.as_deref() else {
+                continue;
+            };
+            by_group
+                .entry(group_id)
+                .or_insert_with(|| GroupExposure::new(group_id))
+                .market_mut(&position.market.condition_id)
+                .add_position(position);
+        }
+        by_group.into_values().collect()
+    }
+
+    /// Aggregate exposure in the neg-risk group `group_id`
+    pub fn exposure_for_group(&self, group_id: &str) -> Option<GroupExposure> {
+        self.group_exposures()
+            .into_iter()
+            .find(|e| e.group_id == group_id)
+    }
+
+    /// Cost of the positions held on `side` across every market: YES bets
+    /// on the asset closing up, NO on it closing down
+    pub fn directional_exposure(&self, side: Side) -> Decimal {
+        self.market_exposures().iter().map(|e| e.cost(side)).sum()
+    }
+
+    /// Aggregate exposure in the market that trades `token_id`
+    pub fn exposure_for_token(&self, token_id: &str) -> Option<MarketExposure> {
+        let market = self.open_positions.values().find_map(|p| {
+            (p.market.yes_token_id == token_id || p.market.no_token_id == token_id)
+                .then_some(&p.market.condition_id)
+        })?;
+        self.market_exposures()
+            .into_iter()
+            .find(|e| &e.condition_id == market)
+    }
 }
 
 impl Default for PositionTracker {
@@ -135,21 +529,42 @@ impl Default for PositionTracker {
     }
 }
 
+/// `position` closed at expiry: winning tokens pay 1 and losing tokens 0
+fn settled_at(position: Position, winner: Side, timestamp: DateTime<Utc>) -> ClosedPosition {
+    let payout = if position.side == winner {
+        Decimal::ONE
+    } else {
+        Decimal::ZERO
+    };
+    ClosedPosition {
+        exit_price: payout,
+        exit_time: timestamp,
+        realized_pnl: round_usd(
+            (payout - position.entry_price) * position.size - position.entry_fee,
+        ),
+        fees: position.entry_fee,
+        position,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::execution::Fill;
+    use crate::execution::{Fill, LiquidityFlag, OrderAction};
     use crate::signal::SignalReason;
     use chrono::Duration;
 
     fn create_test_market() -> Market {
         Market {
             condition_id: "test-cond-123".to_string(),
+            asset: "BTC".to_string(),
             yes_token_id: "yes-token".to_string(),
             no_token_id: "no-token".to_string(),
             open_price: dec!(100000),
             open_time: Utc::now() - Duration::minutes(5),
             close_time: Utc::now() + Duration::minutes(10),
+            group_id: None,
+            orientation: Default::default(),
         }
     }
 
@@ -173,7 +588,12 @@ mod tests {
             price,
             size,
             timestamp: Utc::now(),
-            fees,
+            fee: fees,
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: String::new(),
+            simulated: false,
         }
     }
 
@@ -220,10 +640,13 @@ mod tests {
         let exit_fill = create_test_fill(dec!(0.60), dec!(100), dec!(0.5));
         let closed = tracker.close(position_id, &exit_fill).unwrap();
 
-        // P&L = (0.60 - 0.50) * 100 - fees = 10 - 0.5 = 9.5
+        // P&L = (0.60 - 0.50) * 100 - entry and exit fees = 10 - 1 = 9
         assert_eq!(closed.exit_price, dec!(0.60));
-        assert_eq!(closed.realized_pnl, dec!(9.5));
+        assert_eq!(closed.realized_pnl, dec!(9));
+        assert_eq!(closed.fees, dec!(1.0));
+        assert_eq!(tracker.total_exposure, dec!(0));
         assert_eq!(tracker.open_count(), 0);
+        assert_eq!(tracker.total_fees, dec!(1.0));
     }
 
     #[test]
@@ -239,8 +662,33 @@ mod tests {
         let exit_fill = create_test_fill(dec!(0.40), dec!(100), dec!(0.5));
         let closed = tracker.close(position_id, &exit_fill).unwrap();
 
-        // P&L = (0.40 - 0.50) * 100 - fees = -10 - 0.5 = -10.5
-        assert_eq!(closed.realized_pnl, dec!(-10.5));
+        // P&L = (0.40 - 0.50) * 100 - fees = -10 - 1 = -11
+        assert_eq!(closed.realized_pnl, dec!(-11));
+        // The entry cost is released, not the exit notional
+        assert_eq!(tracker.total_exposure, dec!(0));
+    }
+
+    #[test]
+    fn test_settle_pays_winning_side() {
+        let mut tracker = PositionTracker::new();
+        tracker.open(
+            &create_test_signal(Side::Yes),
+            &create_test_fill(dec!(0.40), dec!(10), dec!(0)),
+        );
+        let mut no_fill = create_test_fill(dec!(0.55), dec!(20), dec!(0));
+        no_fill.side = Side::No;
+        tracker.open(&create_test_signal(Side::No), &no_fill);
+
+        let settled = tracker.settle("test-cond-123", Side::No, Utc::now());
+
+        let pnl: Decimal = settled.iter().map(|c| c.realized_pnl).sum();
+        // YES loses 0.40 * 10, NO wins 0.45 * 20
+        assert_eq!(pnl, dec!(5.00));
+        assert_eq!(tracker.open_count(), 0);
+        assert_eq!(tracker.total_exposure, dec!(0));
+        assert!(tracker
+            .settle("test-cond-123", Side::No, Utc::now())
+            .is_empty());
     }
 
     #[test]
@@ -254,26 +702,68 @@ mod tests {
             price: dec!(0.50),
             size: dec!(100),
             timestamp: Utc::now(),
-            fees: dec!(0.5),
+            fee: dec!(0.5),
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: String::new(),
+            simulated: false,
         };
 
         let position = tracker.open(&signal, &entry_fill);
         let position_id = position.id;
 
-        // Exit at lower price (profit for No side - price went down)
+        // Sell the NO tokens after their price rose
         let exit_fill = Fill {
             order_id: Uuid::new_v4(),
             token_id: "no-token".to_string(),
             side: Side::No,
-            price: dec!(0.40),
+            price: dec!(0.60),
             size: dec!(100),
             timestamp: Utc::now(),
-            fees: dec!(0.5),
+            fee: dec!(0.5),
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Sell,
+            client_order_id: String::new(),
+            simulated: false,
         };
         let closed = tracker.close(position_id, &exit_fill).unwrap();
 
-        // P&L for No = (entry - exit) * size - fees = (0.50 - 0.40) * 100 - 0.5 = 9.5
-        assert_eq!(closed.realized_pnl, dec!(9.5));
+        // Both fills are NO token prices: (0.60 - 0.50) * 100 - 1 = 9
+        assert_eq!(closed.realized_pnl, dec!(9));
+    }
+
+    #[test]
+    fn test_partial_close_leaves_the_rest_open() {
+        let mut tracker = PositionTracker::new();
+        let signal = create_test_signal(Side::Yes);
+        let entry_fill = create_test_fill(dec!(0.50), dec!(100), dec!(1));
+        let position_id = tracker.open(&signal, &entry_fill).id;
+
+        // Sell a quarter at 0.70, then let the rest settle as a loser
+        let exit_fill = create_test_fill(dec!(0.70), dec!(25), dec!(0.1));
+        let slice = tracker.close(position_id, &exit_fill).unwrap();
+        assert_eq!(slice.position.size, dec!(25));
+        // 0.20 * 25 - 0.25 entry fee share - 0.1
+        assert_eq!(slice.realized_pnl, dec!(4.65));
+        let rest = &tracker.open_positions[&position_id];
+        assert_eq!(rest.size, dec!(75));
+        assert_eq!(rest.entry_fee, dec!(0.75));
+        assert_eq!(tracker.total_exposure, dec!(37.5));
+
+        let settled = tracker.settle(
+            "test-cond-123",
+            Side::No,
+            exit_fill.timestamp + chrono::Duration::minutes(1),
+        );
+        assert_eq!(settled[0].realized_pnl, dec!(-38.25));
+        assert_eq!(tracker.total_exposure, dec!(0));
+        assert_eq!(tracker.closed_count(), 2);
+
+        // Rebooking the settlement leaves the sold slice alone
+        tracker.resettle(&settled[0], Side::Yes);
+        assert_eq!(tracker.realized_pnl(), dec!(4.65) + dec!(36.75));
     }
 
     #[test]
@@ -312,7 +802,12 @@ mod tests {
             price: dec!(0.50),
             size: dec!(100),
             timestamp: Utc::now(),
-            fees: dec!(0.5),
+            fee: dec!(0.5),
+            estimated_slippage: Decimal::ZERO,
+            liquidity: LiquidityFlag::Taker,
+            action: OrderAction::Buy,
+            client_order_id: String::new(),
+            simulated: false,
         };
 
         let position = tracker.open(&signal, &fill);
@@ -359,8 +854,95 @@ mod tests {
         tracker.open(&signal, &fill2);
         tracker.update_mark("test-cond-123", dec!(0.55));
 
-        // Total P&L = 9.5 (realized) + 5 (unrealized) = 14.5
-        assert_eq!(tracker.total_pnl(), dec!(14.5));
+        // Total P&L = 9 (realized) + 5 (unrealized) = 14
+        assert_eq!(tracker.total_pnl(), dec!(14));
+    }
+
+    #[test]
+    fn test_rebuild_from_fills() {
+        let market = create_test_market();
+        let t0 = Utc::now();
+        let at = |secs: i64, mut fill: Fill| {
+            fill.timestamp = t0 + Duration::seconds(secs);
+            fill
+        };
+        let yes = at(0, create_test_fill(dec!(0.50), dec!(10), dec!(0.01)));
+        let mut no = at(1, create_test_fill(dec!(0.40), dec!(5), dec!(0.01)));
+        no.token_id = "no-token".to_string();
+        no.side = Side::No;
+        let mut sell = at(3, create_test_fill(dec!(0.60), dec!(10), dec!(0.01)));
+        sell.action = OrderAction::Sell;
+        let mut orphan = at(2, create_test_fill(dec!(0.50), dec!(1), dec!(0)));
+        orphan.token_id = "other-token".to_string();
+        let mut unmatched = at(4, create_test_fill(dec!(0.60), dec!(1), dec!(0)));
+        unmatched.action = OrderAction::Sell;
+
+        // Out of order on input; applied by time
+        let fills = [sell, orphan.clone(), no, unmatched.clone(), yes];
+        let (mut tracker, unapplied) =
+            PositionTracker::rebuild_from_fills(&fills, std::slice::from_ref(&market));
+        let unapplied: Vec<Uuid> = unapplied.iter().map(|f| f.order_id).collect();
+        assert_eq!(unapplied, [orphan.order_id, unmatched.order_id]);
+        assert_eq!(tracker.open_count(), 1);
+        assert_eq!(tracker.realized_pnl(), dec!(0.98));
+        assert_eq!(tracker.total_fees, dec!(0.03));
+
+        let settled = tracker.settle(&market.condition_id, Side::No, t0 + Duration::minutes(15));
+        assert_eq!(settled.len(), 1);
+        assert_eq!(tracker.realized_pnl(), dec!(3.97));
+        assert_eq!(tracker.closed_count(), 2);
+    }
+
+    #[test]
+    fn test_bounded_history_matches_unbounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut bounded = PositionTracker::new().with_archive(HistoryArchive::new(dir.path()), 50);
+        let mut unbounded = PositionTracker::new();
+        let signal = create_test_signal(Side::Yes);
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        for i in 0..10_000i64 {
+            let entry = create_test_fill(dec!(0.50), dec!(10), dec!(0.01));
+            let mut exit =
+                create_test_fill(dec!(0.40) + Decimal::new(i % 21, 2), dec!(10), dec!(0.01));
+            exit.timestamp = start + Duration::seconds(i);
+            for tracker in [&mut bounded, &mut unbounded] {
+                let position = tracker.open(&signal, &entry);
+                tracker.close(position.id, &exit).unwrap();
+            }
+        }
+
+        assert!(bounded.closed_positions.len() < 100);
+        assert_eq!(
+            bounded.archived().count as usize + bounded.closed_positions.len(),
+            10_000
+        );
+        assert_eq!(bounded.total_pnl(), unbounded.total_pnl());
+        assert_eq!(bounded.total_fees, unbounded.total_fees);
+
+        // A range straddling the archive and memory boundary
+        let boundary = bounded.closed_positions[0].exit_time;
+        let range = boundary - Duration::seconds(30)..boundary + Duration::seconds(30);
+        let history = bounded.history_query(range.clone()).unwrap();
+        let expected = unbounded.history_query(range).unwrap();
+        assert_eq!(history.len(), 60);
+        assert_eq!(
+            history
+                .iter()
+                .map(|c| (c.exit_time, c.realized_pnl))
+                .collect::<Vec<_>>(),
+            expected
+                .iter()
+                .map(|c| (c.exit_time, c.realized_pnl))
+                .collect::<Vec<_>>()
+        );
+
+        let all = bounded.history_query(..).unwrap();
+        assert_eq!(all.len(), 10_000);
+        assert_eq!(
+            all.iter().map(|c| c.realized_pnl).sum::<Decimal>(),
+            unbounded.total_pnl()
+        );
     }
 
     #[test]
@@ -373,6 +955,9 @@ mod tests {
             size: dec!(100),
             entry_time: Utc::now(),
             unrealized_pnl: dec!(5),
+            entry_fee: dec!(0),
+            strategy: DEFAULT_STRATEGY.to_string(),
+            max_adverse: Decimal::ZERO,
         };
 
         let cloned = position.clone();
@@ -391,6 +976,9 @@ mod tests {
             size: dec!(100),
             entry_time: Utc::now(),
             unrealized_pnl: dec!(0),
+            entry_fee: dec!(0),
+            strategy: DEFAULT_STRATEGY.to_string(),
+            max_adverse: Decimal::ZERO,
         };
 
         let closed = ClosedPosition {
@@ -0,0 +1,447 @@
+//! Entry rate caps
+//!
+//! Hard caps on entries, however good the signals look: per market window
+//! of an asset, per asset over the last hour, and across all assets per
+//! UTC day. They stop a feed glitch or a detection bug from firing dozens
+//! of orders in minutes. Entries are counted when submitted and written to
+//! `<data dir>/rate_caps.json`, so a restart picks up the day's count
+//! where it left off.
+//!
+//! Hitting the daily cap stops entries until UTC midnight and is reported
+//! once per day as an error, since it most likely means something is wrong.
+
+use super::cooldown::write_atomic;
+use super::RiskError;
+use crate::market::Market;
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Rate cap state, in the data directory
+pub const RATE_CAPS_FILE: &str = "rate_caps.json";
+
+/// Default most entries per market window of an asset
+pub const DEFAULT_MAX_ENTRIES_PER_WINDOW: u32 = 2;
+
+/// Default most entries per asset in any hour
+pub const DEFAULT_MAX_ENTRIES_PER_ASSET_HOUR: u32 = 8;
+
+/// Default most entries across all assets per UTC day
+pub const DEFAULT_MAX_ENTRIES_PER_DAY: u32 = 100;
+
+/// Entry caps, under `[risk.rate_caps]`; 0 disables a cap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateCapConfig {
+    /// Most entries per market window of an asset
+    #[serde(default = "default_per_window")]
+    pub per_window: u32,
+    /// Most entries per asset in any hour
+    #[serde(default = "default_per_asset_hour")]
+    pub per_asset_hour: u32,
+    /// Most entries across all assets per UTC day
+    #[serde(default = "default_per_day")]
+    pub per_day: u32,
+}
+
+fn default_per_window() -> u32 {
+    DEFAULT_MAX_ENTRIES_PER_WINDOW
+}
+
+fn default_per_asset_hour() -> u32 {
+    DEFAULT_MAX_ENTRIES_PER_ASSET_HOUR
+}
+
+fn default_per_day() -> u32 {
+    DEFAULT_MAX_ENTRIES_PER_DAY
+}
+
+impl Default for RateCapConfig {
+    fn default() -> Self {
+        Self {
+            per_window: DEFAULT_MAX_ENTRIES_PER_WINDOW,
+            per_asset_hour: DEFAULT_MAX_ENTRIES_PER_ASSET_HOUR,
+            per_day: DEFAULT_MAX_ENTRIES_PER_DAY,
+        }
+    }
+}
+
+/// One of the entry caps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateCap {
+    /// Entries in one market window of an asset
+    Window,
+    /// Entries of one asset in the last hour
+    AssetHour,
+    /// Entries across all assets in the UTC day
+    Day,
+}
+
+impl RateCap {
+    /// Every cap, most global first
+    pub const ALL: [RateCap; 3] = [RateCap::Day, RateCap::AssetHour, RateCap::Window];
+
+    /// Label for journals and metrics
+    pub fn label(&self) -> &'static str {
+        match self {
+            RateCap::Window => "window",
+            RateCap::AssetHour => "asset_hour",
+            RateCap::Day => "day",
+        }
+    }
+
+    /// Whether the cap spans every asset
+    pub fn is_global(&self) -> bool {
+        *self == RateCap::Day
+    }
+}
+
+impl fmt::Display for RateCap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RateCap::Window => "per-window",
+            RateCap::AssetHour => "per-asset hourly",
+            RateCap::Day => "global daily",
+        })
+    }
+}
+
+/// One counted entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateEntry {
+    /// Asset entered
+    pub asset: String,
+    /// Open time of the market's window
+    pub window: DateTime<Utc>,
+    /// When the entry was submitted
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RateState {
+    /// Entries of the last day, oldest first
+    entries: Vec<RateEntry>,
+    /// UTC day the daily cap was last reported
+    notified: Option<NaiveDate>,
+}
+
+/// What is left of one cap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapBudget {
+    /// Cap counted
+    pub cap: RateCap,
+    /// Asset the count covers, `None` for a global cap
+    pub asset: Option<String>,
+    /// Entries allowed
+    pub limit: u32,
+    /// Entries counted so far
+    pub used: u32,
+}
+
+impl CapBudget {
+    /// Entries still allowed
+    pub fn remaining(&self) -> u32 {
+        self.limit.saturating_sub(self.used)
+    }
+}
+
+impl fmt::Display for CapBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.cap)?;
+        if let Some(asset) = &self.asset {
+            write!(f, " [{}]", asset)?;
+        }
+        write!(
+            f,
+            ": {} of {} left ({} used)",
+            self.remaining(),
+            self.limit,
+            self.used
+        )
+    }
+}
+
+/// Counts entries against the caps
+pub struct RateLimiter {
+    config: RateCapConfig,
+    state: RateState,
+    path: Option<PathBuf>,
+}
+
+impl RateLimiter {
+    /// Counters kept in memory only
+    pub fn new(config: RateCapConfig) -> Self {
+        Self {
+            config,
+            state: RateState::default(),
+            path: None,
+        }
+    }
+
+    /// Load counters from `path`, if it exists, and write every change back
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        self.state = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow!("unreadable rate caps {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => RateState::default(),
+            Err(e) => return Err(e.into()),
+        };
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Settings in force
+    pub fn config(&self) -> &RateCapConfig {
+        &self.config
+    }
+
+    /// Assets with entries counted in the last day
+    pub fn assets(&self) -> BTreeSet<&str> {
+        self.state
+            .entries
+            .iter()
+            .map(|e| e.asset.as_str())
+            .collect()
+    }
+
+    fn limit(&self, cap: RateCap) -> u32 {
+        match cap {
+            RateCap::Window => self.config.per_window,
+            RateCap::AssetHour => self.config.per_asset_hour,
+            RateCap::Day => self.config.per_day,
+        }
+    }
+
+    fn used(&self, cap: RateCap, asset: &str, window: DateTime<Utc>, now: DateTime<Utc>) -> u32 {
+        let hour_ago = now - Duration::hours(1);
+        let today = now.date_naive();
+        self.state
+            .entries
+            .iter()
+            .filter(|e| match cap {
+                RateCap::Window => e.asset == asset && e.window == window,
+                RateCap::AssetHour => e.asset == asset && e.at > hour_ago,
+                RateCap::Day => e.at.date_naive() == today,
+            })
+            .count() as u32
+    }
+
+    /// What is left of each enabled cap for `asset`'s window opening at
+    /// `window`
+    pub fn budgets(
+        &self,
+        asset: &str,
+        window: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Vec<CapBudget> {
+        RateCap::ALL
+            .into_iter()
+            .filter(|cap| self.limit(*cap) > 0)
+            .map(|cap| CapBudget {
+                cap,
+                asset: (!cap.is_global()).then(|| asset.to_string()),
+                limit: self.limit(cap),
+                used: self.used(cap, asset, window, now),
+            })
+            .collect()
+    }
+
+    /// Whether one more entry in `market` of `asset` fits every cap at `now`
+    ///
+    /// Names the most global cap hit.
+    pub fn check(&self, asset: &str, market: &Market, now: DateTime<Utc>) -> Result<(), RiskError> {
+        match self
+            .budgets(asset, market.open_time, now)
+            .into_iter()
+            .find(|b| b.remaining() == 0)
+        {
+            Some(budget) => Err(RiskError::RateCapExceeded {
+                cap: budget.cap,
+                limit: budget.limit,
+                count: budget.used,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Count an entry in `market` of `asset` submitted at `now`
+    pub fn record(&mut self, asset: &str, market: &Market, now: DateTime<Utc>) {
+        let day_ago = now - Duration::days(1);
+        self.state.entries.retain(|e| e.at > day_ago);
+        self.state.entries.push(RateEntry {
+            asset: asset.to_string(),
+            window: market.open_time,
+            at: now,
+        });
+        self.save();
+    }
+
+    /// Whether a hit of the daily cap at `now` is the first of its day,
+    /// and so should be reported
+    pub fn notify(&mut self, now: DateTime<Utc>) -> bool {
+        let today = now.date_naive();
+        if self.state.notified == Some(today) {
+            return false;
+        }
+        self.state.notified = Some(today);
+        self.save();
+        true
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let written = serde_json::to_vec_pretty(&self.state)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| write_atomic(path, &bytes));
+        if let Err(e) = written {
+            tracing::error!(error = %e, "Failed to save rate caps");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-01-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn market(asset: &str, open: DateTime<Utc>) -> Market {
+        Market {
+            condition_id: format!("{}-{}", asset, open.timestamp()),
+            asset: asset.to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time: open,
+            close_time: open + Duration::minutes(15),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    fn caps(per_window: u32, per_asset_hour: u32, per_day: u32) -> RateCapConfig {
+        RateCapConfig {
+            per_window,
+            per_asset_hour,
+            per_day,
+        }
+    }
+
+    fn hit(limiter: &RateLimiter, asset: &str, m: &Market, now: DateTime<Utc>) -> Option<RateCap> {
+        match limiter.check(asset, m, now) {
+            Err(RiskError::RateCapExceeded { cap, .. }) => Some(cap),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_window_cap_resets_per_window_but_day_cap_does_not() {
+        let mut limiter = RateLimiter::new(caps(2, 0, 5));
+        let first = market("BTC", t0());
+        limiter.record("BTC", &first, t0());
+        limiter.record("BTC", &first, t0() + Duration::minutes(1));
+        let error = limiter
+            .check("BTC", &first, t0() + Duration::minutes(2))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            RiskError::RateCapExceeded {
+                cap: RateCap::Window,
+                limit: 2,
+                count: 2
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Rate cap per-window exceeded: 2 of 2 entries"
+        );
+        // Another asset's window is counted apart
+        let eth = market("ETH", t0());
+        assert_eq!(hit(&limiter, "ETH", &eth, t0()), None);
+
+        // The next window starts afresh, the day does not
+        let next = market("BTC", t0() + Duration::minutes(15));
+        let now = next.open_time;
+        assert_eq!(hit(&limiter, "BTC", &next, now), None);
+        limiter.record("BTC", &next, now);
+        limiter.record("ETH", &eth, now);
+        limiter.record("BTC", &next, now);
+        let third = market("BTC", t0() + Duration::minutes(30));
+        assert_eq!(
+            hit(&limiter, "BTC", &third, third.open_time),
+            Some(RateCap::Day)
+        );
+
+        // Until the UTC day rolls over
+        let tomorrow = market("BTC", t0() + Duration::hours(14));
+        assert_eq!(hit(&limiter, "BTC", &tomorrow, tomorrow.open_time), None);
+    }
+
+    #[test]
+    fn test_asset_hour_cap_is_rolling() {
+        let mut limiter = RateLimiter::new(caps(0, 3, 0));
+        for i in 0..3 {
+            let m = market("BTC", t0() + Duration::minutes(15 * i));
+            limiter.record("BTC", &m, m.open_time);
+        }
+        let m = market("BTC", t0() + Duration::minutes(45));
+        assert_eq!(
+            hit(&limiter, "BTC", &m, m.open_time),
+            Some(RateCap::AssetHour)
+        );
+        // The first entry leaves the hour
+        assert_eq!(hit(&limiter, "BTC", &m, t0() + Duration::minutes(61)), None);
+        let budgets = limiter.budgets("BTC", m.open_time, m.open_time);
+        assert_eq!(budgets.len(), 1);
+        assert_eq!(budgets[0].remaining(), 0);
+        assert_eq!(
+            budgets[0].to_string(),
+            "per-asset hourly [BTC]: 0 of 3 left (3 used)"
+        );
+    }
+
+    #[test]
+    fn test_counts_survive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(RATE_CAPS_FILE);
+        let mut limiter = RateLimiter::new(caps(0, 0, 2))
+            .with_state_file(&path)
+            .unwrap();
+        for i in 0..2 {
+            let m = market("BTC", t0() + Duration::minutes(15 * i));
+            limiter.record("BTC", &m, m.open_time);
+        }
+        assert!(limiter.notify(t0() + Duration::minutes(30)));
+
+        let mut restarted = RateLimiter::new(caps(0, 0, 2))
+            .with_state_file(&path)
+            .unwrap();
+        let m = market("BTC", t0() + Duration::hours(3));
+        assert_eq!(hit(&restarted, "BTC", &m, m.open_time), Some(RateCap::Day));
+        assert_eq!(restarted.assets(), BTreeSet::from(["BTC"]));
+        // Already reported today
+        assert!(!restarted.notify(m.open_time));
+    }
+
+    #[test]
+    fn test_daily_cap_is_reported_once_per_day() {
+        let mut limiter = RateLimiter::new(caps(0, 0, 1));
+        let now = t0();
+        assert!(limiter.notify(now));
+        assert!(!limiter.notify(now + Duration::hours(1)));
+        assert!(limiter.notify(now + Duration::days(1)));
+        assert!(RateCap::Day.is_global());
+        assert!(!RateCap::Window.is_global());
+    }
+}
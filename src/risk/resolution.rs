@@ -0,0 +1,348 @@
+//! Two-phase settlement
+//!
+//! The engine settles a market at close on its own verdict, spot against
+//! strike, and books the P&L at once. Polymarket's official resolution
+//! comes later and is occasionally corrected after close, so with
+//! `[risk.resolution] enabled` that settlement is provisional:
+//! [`ResolutionBook`] holds it until Gamma reports the official outcome or
+//! `confirmation_window_secs` pass without one. An official outcome that
+//! contradicts the provisional one has the engine rebook the positions at
+//! the official payout. Drawdown follows the P&L as booked, provisional or
+//! not, which is the conservative reading.
+
+use super::cooldown::write_atomic;
+use super::ClosedPosition;
+use crate::duration::DurationConfig;
+use crate::market::Market;
+use crate::signal::Side;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// File in the data directory listing the settlements still provisional
+pub const PENDING_RESOLUTIONS_FILE: &str = "pending_resolutions.json";
+
+/// Default seconds to wait for an official resolution
+pub const DEFAULT_CONFIRMATION_WINDOW_SECS: u64 = 7200;
+
+/// Default seconds between official resolution lookups
+pub const DEFAULT_RESOLUTION_POLL_SECS: u64 = 60;
+
+/// `[risk.resolution]`: how long settlements stay provisional
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ResolutionConfig {
+    /// Hold settlements provisional until confirmed; off, the engine's
+    /// verdict is final at close
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Time after close after which the provisional outcome stands
+    #[serde(default = "default_confirmation_window_secs")]
+    pub confirmation_window_secs: DurationConfig,
+    /// Time between lookups of the official resolution
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: DurationConfig,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_confirmation_window_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_CONFIRMATION_WINDOW_SECS)
+}
+
+fn default_poll_interval_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_RESOLUTION_POLL_SECS)
+}
+
+impl Default for ResolutionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            confirmation_window_secs: default_confirmation_window_secs(),
+            poll_interval_secs: default_poll_interval_secs(),
+        }
+    }
+}
+
+/// Where a settlement stands against the official resolution
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolutionStatus {
+    /// Booked on the engine's verdict, official outcome awaited
+    Provisional,
+    /// The official outcome agreed
+    Confirmed,
+    /// The official outcome differed and the positions were rebooked
+    Corrected,
+    /// Final on the engine's verdict: no official outcome within the
+    /// window, or confirmation is off
+    #[default]
+    Unconfirmed,
+}
+
+impl ResolutionStatus {
+    /// Metric and journal label
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResolutionStatus::Provisional => "provisional",
+            ResolutionStatus::Confirmed => "confirmed",
+            ResolutionStatus::Corrected => "corrected",
+            ResolutionStatus::Unconfirmed => "unconfirmed",
+        }
+    }
+}
+
+impl fmt::Display for ResolutionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// A market settled on the engine's verdict, awaiting the official one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingResolution {
+    pub market: Market,
+    /// Side the engine settled as the winner
+    pub winner: Side,
+    /// When the engine settled it
+    pub settled_at: DateTime<Utc>,
+    /// Positions the settlement closed, as booked
+    pub positions: Vec<ClosedPosition>,
+}
+
+impl PendingResolution {
+    /// P&L booked on the provisional outcome
+    pub fn pnl(&self) -> Decimal {
+        self.positions.iter().map(|c| c.realized_pnl).sum()
+    }
+}
+
+impl fmt::Display for PendingResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} settled {:?} at {}, {} positions, P&L {:+.2} provisional",
+            self.market.condition_id,
+            self.winner,
+            self.settled_at.to_rfc3339(),
+            self.positions.len(),
+            self.pnl()
+        )
+    }
+}
+
+/// Settlements held provisional until confirmed, corrected or timed out
+#[derive(Debug, Default)]
+pub struct ResolutionBook {
+    config: ResolutionConfig,
+    pending: BTreeMap<String, PendingResolution>,
+    path: Option<PathBuf>,
+}
+
+impl ResolutionBook {
+    /// Nothing pending
+    pub fn new(config: ResolutionConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Write the pending settlements to `path` whenever they change, for
+    /// `status`
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self.save();
+        self
+    }
+
+    /// Settings in force
+    pub fn config(&self) -> &ResolutionConfig {
+        &self.config
+    }
+
+    /// Hold `pending` until resolved; false when confirmation is off and
+    /// the settlement is final as booked
+    pub fn hold(&mut self, pending: PendingResolution) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        self.pending
+            .insert(pending.market.condition_id.clone(), pending);
+        self.save();
+        true
+    }
+
+    /// Stop holding `market_id`, e.g. once its official outcome is known
+    pub fn take(&mut self, market_id: &str) -> Option<PendingResolution> {
+        let taken = self.pending.remove(market_id);
+        if taken.is_some() {
+            self.save();
+        }
+        taken
+    }
+
+    /// Stop holding every settlement whose window has passed at `now`,
+    /// oldest first
+    pub fn expire(&mut self, now: DateTime<Utc>) -> Vec<PendingResolution> {
+        let window = self.config.confirmation_window_secs.to_chrono();
+        let expired: Vec<String> = self
+            .pending
+            .values()
+            .filter(|p| now - p.settled_at >= window)
+            .map(|p| p.market.condition_id.clone())
+            .collect();
+        let mut taken: Vec<_> = expired
+            .iter()
+            .filter_map(|id| self.pending.remove(id))
+            .collect();
+        if !taken.is_empty() {
+            self.save();
+        }
+        taken.sort_by_key(|p| p.settled_at);
+        taken
+    }
+
+    /// Settlements still provisional, by market
+    pub fn pending(&self) -> impl Iterator<Item = &PendingResolution> {
+        self.pending.values()
+    }
+
+    /// Number of settlements still provisional
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether nothing is provisional
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// P&L booked on outcomes not yet confirmed
+    pub fn provisional_pnl(&self) -> Decimal {
+        self.pending.values().map(|p| p.pnl()).sum()
+    }
+
+    /// Pending settlements written to `path` by a running session
+    pub fn load(path: &Path) -> anyhow::Result<Vec<PendingResolution>> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let pending: Vec<_> = self.pending.values().collect();
+        let written = serde_json::to_vec_pretty(&pending)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| write_atomic(path, &bytes));
+        if let Err(e) = written {
+            tracing::error!(error = %e, "Failed to save pending resolutions");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::TokenOrientation;
+    use crate::risk::Position;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_767_600_000, 0).unwrap()
+    }
+
+    fn pending(id: &str, settled_at: DateTime<Utc>, pnl: Decimal) -> PendingResolution {
+        let market = Market {
+            condition_id: id.to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: format!("{}-yes", id),
+            no_token_id: format!("{}-no", id),
+            open_price: dec!(100000),
+            open_time: settled_at - Duration::minutes(15),
+            close_time: settled_at,
+            group_id: None,
+            orientation: TokenOrientation::default(),
+        };
+        let position = Position {
+            id: Uuid::new_v4(),
+            market: market.clone(),
+            side: Side::Yes,
+            entry_price: dec!(0.5),
+            size: dec!(10),
+            entry_time: market.open_time,
+            unrealized_pnl: Decimal::ZERO,
+            entry_fee: Decimal::ZERO,
+            strategy: "lag".to_string(),
+            max_adverse: Decimal::ZERO,
+        };
+        PendingResolution {
+            market,
+            winner: Side::Yes,
+            settled_at,
+            positions: vec![ClosedPosition {
+                position,
+                exit_price: Decimal::ONE,
+                exit_time: settled_at,
+                realized_pnl: pnl,
+                fees: Decimal::ZERO,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_hold_take_and_expire() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(PENDING_RESOLUTIONS_FILE);
+        let mut book = ResolutionBook::new(ResolutionConfig {
+            confirmation_window_secs: DurationConfig::from_secs(600),
+            ..Default::default()
+        })
+        .with_state_file(&path);
+        assert!(ResolutionBook::load(&path).unwrap().is_empty());
+
+        assert!(book.hold(pending("m1", t0(), dec!(5))));
+        assert!(book.hold(pending("m2", t0() + Duration::minutes(15), dec!(-5))));
+        assert!(book.hold(pending("m3", t0() + Duration::minutes(30), dec!(2))));
+        assert_eq!(book.provisional_pnl(), dec!(2));
+        assert_eq!(ResolutionBook::load(&path).unwrap().len(), 3);
+
+        assert_eq!(book.take("m2").unwrap().pnl(), dec!(-5));
+        assert!(book.take("m2").is_none());
+
+        // Ten minutes after m1's close; m3's window runs on
+        let expired = book.expire(t0() + Duration::minutes(10));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].market.condition_id, "m1");
+        assert!(book.expire(t0() + Duration::minutes(10)).is_empty());
+
+        let left = ResolutionBook::load(&path).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].market.condition_id, "m3");
+        assert_eq!(book.provisional_pnl(), dec!(2));
+    }
+
+    #[test]
+    fn test_disabled_holds_nothing() {
+        let mut book = ResolutionBook::new(ResolutionConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        assert!(!book.hold(pending("m1", t0(), dec!(5))));
+        assert!(book.is_empty());
+        assert_eq!(book.provisional_pnl(), Decimal::ZERO);
+    }
+}
@@ -0,0 +1,662 @@
+//! Strategy review on drifting adverse excursions
+//!
+//! Every closed position of an asset leaves its maximum adverse excursion,
+//! the furthest its token's bid fell below the entry price while held, per
+//! share, next to its P&L. Once `recent_trades` trades have closed since
+//! the last review, the `percentile` of their excursions is compared with
+//! the same percentile over the `baseline_trades` before them; a ratio of
+//! `max_ratio` or more flags the asset for review. A flag stays until an
+//! operator runs `poly-hft ctl ack-review <asset>`, and while it stands
+//! entries are sized at `size_scale` of the Kelly stake.
+//!
+//! State is written to `<data dir>/strategy_review.json` on every change.
+//! Clears are requested through [`REVIEW_CLEAR_FILE`], so a running
+//! session picks them up without the two processes writing one file.
+
+use super::cooldown::write_atomic;
+use super::ClosedPosition;
+use crate::journal::Journal;
+use crate::precision::round_price;
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Strategy review state, in the data directory
+pub const STRATEGY_REVIEW_FILE: &str = "strategy_review.json";
+
+/// Assets whose review flag an operator cleared, one per line, in the data
+/// directory until the session applies them
+pub const REVIEW_CLEAR_FILE: &str = "strategy_review.clear";
+
+/// Default trades in the window tested against the baseline
+pub const DEFAULT_RECENT_TRADES: usize = 50;
+
+/// Default trades before the recent window forming the baseline
+pub const DEFAULT_BASELINE_TRADES: usize = 200;
+
+/// Default excursion percentile compared
+pub const DEFAULT_REVIEW_PERCENTILE: u32 = 90;
+
+/// Default recent over baseline percentile that flags an asset
+pub const DEFAULT_MAX_RATIO: Decimal = dec!(2);
+
+/// Default floor of the baseline percentile, per share
+pub const DEFAULT_MIN_BASELINE: Decimal = dec!(0.01);
+
+/// Trades per character of the excursion sparkline
+pub const SPARK_BLOCK: usize = 10;
+
+const SPARK: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// When drifting adverse excursions flag the strategy, under
+/// `[risk.review]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct StrategyReviewConfig {
+    /// Latest trades of an asset tested against its baseline
+    #[serde(default = "default_recent_trades")]
+    pub recent_trades: usize,
+    /// Trades before the recent ones the test compares with; at least
+    /// `recent_trades` of them are needed before any test
+    #[serde(default = "default_baseline_trades")]
+    pub baseline_trades: usize,
+    /// Excursion percentile compared, 1 to 100
+    #[serde(default = "default_percentile")]
+    pub percentile: u32,
+    /// Recent over baseline percentile that flags the asset; 0 disables
+    #[serde(default = "default_max_ratio")]
+    pub max_ratio: Decimal,
+    /// Floor of the baseline percentile, per share, so a strategy that
+    /// hardly ever went against its entries is not flagged on noise
+    #[serde(default = "default_min_baseline")]
+    pub min_baseline: Decimal,
+    /// Multiple of the Kelly stake entered while flagged; 1 leaves sizing
+    /// alone
+    #[serde(default = "default_size_scale")]
+    pub size_scale: Decimal,
+}
+
+fn default_recent_trades() -> usize {
+    DEFAULT_RECENT_TRADES
+}
+
+fn default_baseline_trades() -> usize {
+    DEFAULT_BASELINE_TRADES
+}
+
+fn default_percentile() -> u32 {
+    DEFAULT_REVIEW_PERCENTILE
+}
+
+fn default_max_ratio() -> Decimal {
+    DEFAULT_MAX_RATIO
+}
+
+fn default_min_baseline() -> Decimal {
+    DEFAULT_MIN_BASELINE
+}
+
+fn default_size_scale() -> Decimal {
+    Decimal::ONE
+}
+
+impl Default for StrategyReviewConfig {
+    fn default() -> Self {
+        Self {
+            recent_trades: default_recent_trades(),
+            baseline_trades: default_baseline_trades(),
+            percentile: default_percentile(),
+            max_ratio: default_max_ratio(),
+            min_baseline: default_min_baseline(),
+            size_scale: default_size_scale(),
+        }
+    }
+}
+
+impl StrategyReviewConfig {
+    /// Whether a drift can flag an asset
+    pub fn enabled(&self) -> bool {
+        self.max_ratio > Decimal::ZERO && self.recent_trades > 0
+    }
+}
+
+/// One closed position's excursion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Excursion {
+    /// Position closed
+    pub position_id: Uuid,
+    /// Market it was held in
+    pub market_id: String,
+    /// When it was last closed
+    pub at: DateTime<Utc>,
+    /// Maximum adverse excursion per share
+    pub mae: Decimal,
+    /// Realized P&L, every part sold included
+    pub pnl: Decimal,
+}
+
+/// Why an asset was flagged for review
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReviewFlag {
+    /// When the test failed
+    pub raised_at: DateTime<Utc>,
+    /// Excursion percentile of the recent trades
+    pub recent: Decimal,
+    /// Excursion percentile of the baseline, floored at `min_baseline`
+    pub baseline: Decimal,
+    /// Trades in the baseline
+    pub baseline_trades: usize,
+}
+
+impl ReviewFlag {
+    /// How many times the baseline the recent percentile is
+    pub fn ratio(&self) -> Decimal {
+        if self.baseline.is_zero() {
+            return Decimal::ZERO;
+        }
+        (self.recent / self.baseline).round_dp(2)
+    }
+}
+
+impl fmt::Display for ReviewFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "excursion percentile {:.4} against a baseline of {:.4} over {} trades ({}x), since {}",
+            self.recent,
+            self.baseline,
+            self.baseline_trades,
+            self.ratio(),
+            self.raised_at.format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+}
+
+/// Excursion history and review flag of one asset
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetExcursions {
+    /// Closed trades, oldest first, up to the recent window and baseline
+    pub trades: VecDeque<Excursion>,
+    /// Trades closed since the last test or clear
+    #[serde(default)]
+    pub untested: usize,
+    /// Standing review flag
+    pub flag: Option<ReviewFlag>,
+}
+
+impl AssetExcursions {
+    /// Excursion percentile `p` of the latest `n` trades, skipping the last
+    /// `skip`
+    pub fn percentile(&self, p: u32, skip: usize, n: usize) -> Option<Decimal> {
+        let end = self.trades.len().checked_sub(skip)?;
+        let start = end.saturating_sub(n);
+        percentile(self.trades.range(start..end).map(|t| t.mae).collect(), p)
+    }
+
+    /// Excursion percentile `p` of each block of [`SPARK_BLOCK`] trades,
+    /// oldest first, drawn as a sparkline scaled to the largest
+    pub fn sparkline(&self, p: u32) -> String {
+        let trades: Vec<Decimal> = self.trades.iter().map(|t| t.mae).collect();
+        let blocks: Vec<Decimal> = trades
+            .chunks(SPARK_BLOCK)
+            .filter_map(|block| percentile(block.to_vec(), p))
+            .collect();
+        let top = blocks.iter().copied().max().unwrap_or_default();
+        blocks
+            .iter()
+            .map(|value| {
+                if top.is_zero() {
+                    return SPARK[0];
+                }
+                let last = Decimal::from(SPARK.len() - 1);
+                let index = (*value / top * last).round();
+                SPARK[usize::try_from(index).unwrap_or_default()]
+            })
+            .collect()
+    }
+}
+
+/// Nearest-rank percentile `p` of `values`
+fn percentile(mut values: Vec<Decimal>, p: u32) -> Option<Decimal> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort();
+    let rank = (values.len() * p.clamp(1, 100) as usize).div_ceil(100);
+    Some(values[rank.max(1) - 1])
+}
+
+/// Excursion statistics and review flags of every asset traded
+pub struct StrategyReview {
+    config: StrategyReviewConfig,
+    assets: BTreeMap<String, AssetExcursions>,
+    path: Option<PathBuf>,
+    journal: Option<Journal>,
+}
+
+impl StrategyReview {
+    /// Statistics kept in memory only
+    pub fn new(config: StrategyReviewConfig) -> Self {
+        Self {
+            config,
+            assets: BTreeMap::new(),
+            path: None,
+            journal: None,
+        }
+    }
+
+    /// Load state from `path`, if it exists, and write every change back
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        self.assets = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow!("unreadable strategy review {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Journal every flag and clear
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Settings in force
+    pub fn config(&self) -> &StrategyReviewConfig {
+        &self.config
+    }
+
+    /// Statistics of every asset traded
+    pub fn assets(&self) -> &BTreeMap<String, AssetExcursions> {
+        &self.assets
+    }
+
+    /// Standing review flag of `asset`
+    pub fn flag(&self, asset: &str) -> Option<&ReviewFlag> {
+        self.assets.get(asset)?.flag.as_ref()
+    }
+
+    /// Multiple of the Kelly stake `asset` is entered at
+    pub fn size_scale(&self, asset: &str) -> Decimal {
+        if self.flag(asset).is_some() {
+            self.config.size_scale
+        } else {
+            Decimal::ONE
+        }
+    }
+
+    /// Record a closed position of `asset`, and test its excursions once a
+    /// recent window has closed since the last test
+    ///
+    /// A position sold in parts is one trade: each later part adds its P&L
+    /// to the trade its first part recorded. Returns the flag if this
+    /// trade raised it.
+    pub fn record(
+        &mut self,
+        asset: &str,
+        closed: &ClosedPosition,
+        now: DateTime<Utc>,
+    ) -> Option<ReviewFlag> {
+        let keep = self.config.recent_trades + self.config.baseline_trades;
+        let state = self.assets.entry(asset.to_string()).or_default();
+        let id = closed.position.id;
+        if let Some(trade) = state.trades.iter_mut().find(|t| t.position_id == id) {
+            trade.pnl += closed.realized_pnl;
+            trade.mae = trade.mae.max(closed.position.max_adverse);
+            trade.at = closed.exit_time;
+            self.save();
+            return None;
+        }
+        state.trades.push_back(Excursion {
+            position_id: id,
+            market_id: closed.position.market.condition_id.clone(),
+            at: closed.exit_time,
+            mae: closed.position.max_adverse,
+            pnl: closed.realized_pnl,
+        });
+        while state.trades.len() > keep.max(1) {
+            state.trades.pop_front();
+        }
+        state.untested += 1;
+        let flag = self.test(asset, now);
+        self.save();
+        flag
+    }
+
+    /// Compare the recent window of `asset` with its baseline, if a whole
+    /// window has closed since the last test and none is flagged
+    fn test(&mut self, asset: &str, now: DateTime<Utc>) -> Option<ReviewFlag> {
+        let config = &self.config;
+        let state = self.assets.get_mut(asset)?;
+        if !config.enabled() || state.flag.is_some() || state.untested < config.recent_trades {
+            return None;
+        }
+        let window = config.recent_trades;
+        let baseline_trades = state.trades.len() - window;
+        if baseline_trades < window {
+            return None;
+        }
+        state.untested = 0;
+        let p = config.percentile;
+        let recent = state.percentile(p, 0, window)?;
+        let baseline = state
+            .percentile(p, window, config.baseline_trades)?
+            .max(config.min_baseline);
+        if recent < baseline * config.max_ratio {
+            return None;
+        }
+        let flag = ReviewFlag {
+            raised_at: now,
+            recent: round_price(recent),
+            baseline: round_price(baseline),
+            baseline_trades: baseline_trades.min(config.baseline_trades),
+        };
+        state.flag = Some(flag.clone());
+        self.journal("strategy_review", asset, &flag);
+        Some(flag)
+    }
+
+    /// Clear the review flag of `asset`
+    ///
+    /// The next test waits for a whole recent window closed after the
+    /// clear, so the trades that raised the flag do not raise it again.
+    pub fn clear(&mut self, asset: &str) -> anyhow::Result<ReviewFlag> {
+        let state = self
+            .assets
+            .get_mut(asset)
+            .ok_or_else(|| anyhow!("{} is not flagged for review", asset))?;
+        let flag = state
+            .flag
+            .take()
+            .ok_or_else(|| anyhow!("{} is not flagged for review", asset))?;
+        state.untested = 0;
+        self.journal("strategy_review_cleared", asset, &flag);
+        if let Err(e) = self.write() {
+            bail!("failed to save strategy review: {}", e);
+        }
+        Ok(flag)
+    }
+
+    /// Ask the session to clear the review flag of `asset`
+    pub fn request_clear(&self, asset: &str) -> anyhow::Result<()> {
+        if self.flag(asset).is_none() {
+            bail!("{} is not flagged for review", asset);
+        }
+        let path = self
+            .clear_path()
+            .ok_or_else(|| anyhow!("strategy review has no state file"))?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{}", asset)?;
+        Ok(())
+    }
+
+    /// Whether a clear of `asset` is waiting for the session to apply it
+    pub fn clear_requested(&self, asset: &str) -> bool {
+        self.clear_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .is_some_and(|requests| requests.lines().any(|line| line.trim() == asset))
+    }
+
+    /// Apply every clear requested, returning the flags cleared
+    pub fn apply_clears(&mut self) -> Vec<(String, ReviewFlag)> {
+        let Some(path) = self.clear_path() else {
+            return vec![];
+        };
+        let Ok(requests) = std::fs::read_to_string(&path) else {
+            return vec![];
+        };
+        if let Err(e) = std::fs::remove_file(&path) {
+            tracing::warn!(error = %e, "Failed to remove strategy review clear requests");
+        }
+        let mut cleared = vec![];
+        for asset in requests.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Ok(flag) = self.clear(asset) {
+                cleared.push((asset.to_string(), flag));
+            }
+        }
+        cleared
+    }
+
+    fn clear_path(&self) -> Option<PathBuf> {
+        let dir = self.path.as_deref()?.parent().unwrap_or(Path::new("."));
+        Some(dir.join(REVIEW_CLEAR_FILE))
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.write() {
+            tracing::error!(error = %e, "Failed to save strategy review");
+        }
+    }
+
+    fn write(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        write_atomic(path, &serde_json::to_vec_pretty(&self.assets)?)
+    }
+
+    fn journal(&self, kind: &str, asset: &str, flag: &ReviewFlag) {
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        let data = serde_json::json!({
+            "asset": asset,
+            "recent": flag.recent,
+            "baseline": flag.baseline,
+            "ratio": flag.ratio(),
+            "percentile": self.config.percentile,
+            "size_scale": self.config.size_scale,
+            "raised_at": flag.raised_at,
+        });
+        if let Err(e) = journal.append(kind, &data) {
+            tracing::warn!(error = %e, "Failed to journal strategy review");
+        }
+    }
+}
+
+impl fmt::Display for StrategyReview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let p = self.config.percentile;
+        let recent = self.config.recent_trades;
+        writeln!(
+            f,
+            "Adverse excursions (p{} per share, last {} trades against the {} before)",
+            p, recent, self.config.baseline_trades
+        )?;
+        if self.assets.is_empty() {
+            return writeln!(f, "  No trades closed yet");
+        }
+        for (asset, state) in &self.assets {
+            let show = |value: Option<Decimal>| {
+                value.map_or_else(|| "-".to_string(), |v| format!("{:.4}", v))
+            };
+            let pnl: Decimal = state.trades.iter().rev().take(recent).map(|t| t.pnl).sum();
+            writeln!(
+                f,
+                "  {:<6} {:>4} trades  recent {}  baseline {}  recent P&L {:+.2}",
+                asset,
+                state.trades.len(),
+                show(state.percentile(p, 0, recent)),
+                show(state.percentile(p, recent, self.config.baseline_trades)),
+                pnl
+            )?;
+            writeln!(f, "         {}", state.sparkline(p))?;
+            if let Some(flag) = &state.flag {
+                writeln!(f, "  !! {} FLAGGED FOR REVIEW: {}", asset, flag)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::Market;
+    use crate::risk::{Position, HALT_JOURNAL_FILE};
+    use crate::signal::Side;
+
+    fn ts(mins: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + mins * 60, 0).unwrap()
+    }
+
+    fn closed(i: i64, mae: Decimal) -> ClosedPosition {
+        let market = Market {
+            condition_id: format!("m{}", i),
+            asset: "BTC".to_string(),
+            yes_token_id: format!("yes{}", i),
+            no_token_id: format!("no{}", i),
+            open_price: dec!(100000),
+            open_time: ts(i * 15),
+            close_time: ts(i * 15 + 15),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        ClosedPosition {
+            position: Position {
+                id: Uuid::new_v4(),
+                market,
+                side: Side::Yes,
+                entry_price: dec!(0.5),
+                size: dec!(10),
+                entry_time: ts(i * 15 + 5),
+                unrealized_pnl: Decimal::ZERO,
+                entry_fee: Decimal::ZERO,
+                strategy: "default".to_string(),
+                max_adverse: mae,
+            },
+            exit_price: dec!(1),
+            exit_time: ts(i * 15 + 15),
+            realized_pnl: dec!(5),
+            fees: Decimal::ZERO,
+        }
+    }
+
+    fn config(size_scale: Decimal) -> StrategyReviewConfig {
+        StrategyReviewConfig {
+            recent_trades: 10,
+            baseline_trades: 40,
+            size_scale,
+            ..Default::default()
+        }
+    }
+
+    /// Feed `maes` as closed trades from index `from`, returning the index
+    /// of each trade that raised a flag
+    fn feed(review: &mut StrategyReview, from: i64, maes: &[Decimal]) -> Vec<i64> {
+        let mut raised = vec![];
+        for (i, mae) in maes.iter().enumerate() {
+            let i = from + i as i64;
+            if review
+                .record("BTC", &closed(i, *mae), ts(i * 15 + 15))
+                .is_some()
+            {
+                raised.push(i);
+            }
+        }
+        raised
+    }
+
+    #[test]
+    fn test_doubled_excursions_flag_and_scale_sizing_until_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STRATEGY_REVIEW_FILE);
+        let open = || {
+            StrategyReview::new(config(dec!(0.5)))
+                .with_state_file(&path)
+                .unwrap()
+                .with_journal(Journal::open(dir.path().join(HALT_JOURNAL_FILE)).unwrap())
+        };
+        let mut review = open();
+
+        // A steady 2-4 cent baseline; a recent window no worse never flags
+        let steady: Vec<Decimal> = (0..40)
+            .map(|i| dec!(0.02) + dec!(0.0005) * Decimal::from(i % 5))
+            .collect();
+        assert!(feed(&mut review, 0, &steady).is_empty());
+        assert!(feed(&mut review, 40, &steady[..10]).is_empty());
+        assert_eq!(review.size_scale("BTC"), Decimal::ONE);
+
+        // Nine bad trades of ten leave the test waiting for the tenth
+        let bad = [dec!(0.06); 10];
+        assert!(feed(&mut review, 50, &bad[..9]).is_empty());
+        assert_eq!(feed(&mut review, 59, &bad[9..]), vec![59]);
+        let flag = review.flag("BTC").unwrap().clone();
+        assert_eq!(flag.recent, dec!(0.06));
+        assert_eq!(flag.baseline, dec!(0.022));
+        assert_eq!(flag.ratio(), dec!(2.73));
+        assert_eq!(review.size_scale("BTC"), dec!(0.5));
+        assert_eq!(review.size_scale("ETH"), Decimal::ONE);
+        drop(review);
+
+        // The flag and the reduced sizing outlive a restart
+        let mut review = open();
+        assert_eq!(review.flag("BTC"), Some(&flag));
+        assert_eq!(review.size_scale("BTC"), dec!(0.5));
+        assert!(review.to_string().contains("!! BTC FLAGGED FOR REVIEW"));
+
+        // Cleared by an operator; the trades that raised it are not retested
+        assert!(review.request_clear("ETH").is_err());
+        review.request_clear("BTC").unwrap();
+        assert!(open().clear_requested("BTC"));
+        let cleared = review.apply_clears();
+        assert_eq!(cleared, vec![("BTC".to_string(), flag)]);
+        assert!(!review.clear_requested("BTC"));
+        assert_eq!(review.size_scale("BTC"), Decimal::ONE);
+        assert!(review.clear("BTC").is_err());
+        assert!(feed(&mut review, 60, &bad[..9]).is_empty());
+        assert_eq!(open().flag("BTC"), None);
+
+        let kinds: Vec<_> = Journal::read_all(dir.path().join(HALT_JOURNAL_FILE))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(kinds, vec!["strategy_review", "strategy_review_cleared"]);
+    }
+
+    #[test]
+    fn test_parts_of_a_position_are_one_trade() {
+        let mut review = StrategyReview::new(config(Decimal::ONE));
+        let mut part = closed(0, dec!(0.01));
+        review.record("BTC", &part, ts(10));
+        part.position.max_adverse = dec!(0.03);
+        part.realized_pnl = dec!(-1);
+        review.record("BTC", &part, ts(12));
+        let state = &review.assets()["BTC"];
+        assert_eq!(state.trades.len(), 1);
+        assert_eq!(state.trades[0].mae, dec!(0.03));
+        assert_eq!(state.trades[0].pnl, dec!(4));
+        assert_eq!(state.untested, 1);
+    }
+
+    #[test]
+    fn test_baseline_floor_and_sparkline() {
+        // Excursions of nearly nothing doubling stay under the floor
+        let mut review = StrategyReview::new(config(Decimal::ONE));
+        assert!(feed(&mut review, 0, &[dec!(0.001); 40]).is_empty());
+        assert!(feed(&mut review, 40, &[dec!(0.004); 10]).is_empty());
+
+        let state = &review.assets()["BTC"];
+        assert_eq!(state.sparkline(90), "▃▃▃▃█");
+        assert_eq!(AssetExcursions::default().sparkline(90), "");
+        assert_eq!(
+            percentile(vec![dec!(3), dec!(1), dec!(2)], 50),
+            Some(dec!(2))
+        );
+        assert_eq!(percentile(vec![], 90), None);
+    }
+}
@@ -0,0 +1,430 @@
+//! Trading schedule restrictions
+//!
+//! Allowed trading windows are expressed in UTC per weekday. Outside the
+//! schedule the bot keeps capturing data and marking positions but opens
+//! nothing new. Strategies without their own schedule use the default one;
+//! an empty schedule means always open.
+
+use crate::journal::Journal;
+use crate::telemetry::EventCode;
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Strategy name used when no strategy-specific schedule exists
+pub const DEFAULT_STRATEGY: &str = "default";
+
+/// How far ahead `next_transition` searches
+const LOOKAHEAD_DAYS: i64 = 8;
+
+/// Schedule configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScheduleConfig {
+    /// Schedule applied to strategies without their own
+    #[serde(flatten)]
+    pub default: StrategySchedule,
+    /// Per-strategy overrides keyed by strategy name
+    #[serde(default)]
+    pub strategies: BTreeMap<String, StrategySchedule>,
+}
+
+/// Allowed windows for one strategy
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StrategySchedule {
+    /// Allowed windows; empty means always open
+    #[serde(default)]
+    pub windows: Vec<TradingWindow>,
+    /// Close open positions when a window ends
+    #[serde(default)]
+    pub flatten_on_close: bool,
+}
+
+/// A daily window in UTC
+///
+/// `end` before `start` spans midnight: the window opens on each listed day
+/// and closes on the following day. `start == end` covers the whole day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradingWindow {
+    /// Days the window opens on; empty means every day
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Opening time, `HH:MM` or `HH:MM:SS`
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: NaiveTime,
+    /// Closing time, `HH:MM` or `HH:MM:SS`
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end: NaiveTime,
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_time(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid time: {}", s)))
+}
+
+/// Parse `HH:MM` or `HH:MM:SS`
+pub fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M"))
+        .ok()
+}
+
+impl TradingWindow {
+    fn opens_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    fn length(&self) -> Duration {
+        let length = self.end - self.start;
+        if length <= Duration::zero() {
+            length + Duration::days(1)
+        } else {
+            length
+        }
+    }
+
+    /// Open/close instants of the windows that open in the given UTC days
+    fn occurrences(
+        &self,
+        from: DateTime<Utc>,
+        days: i64,
+    ) -> impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>)> + '_ {
+        let first_day = from.date_naive();
+        (0..days).filter_map(move |offset| {
+            let day = first_day + Duration::days(offset);
+            if !self.opens_on(day.weekday()) {
+                return None;
+            }
+            let open = day.and_time(self.start).and_utc();
+            Some((open, open + self.length()))
+        })
+    }
+}
+
+impl StrategySchedule {
+    /// Whether trading is allowed at `now`
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        if self.windows.is_empty() {
+            return true;
+        }
+        // Start a day early to catch windows spanning midnight
+        let from = now - Duration::days(1);
+        self.windows.iter().any(|w| {
+            w.occurrences(from, 2)
+                .any(|(open, close)| open <= now && now < close)
+        })
+    }
+
+    /// Next instant after `now` at which `is_open` changes
+    pub fn next_transition(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let current = self.is_open(now);
+        let from = now - Duration::days(1);
+        let mut boundaries: Vec<DateTime<Utc>> = self
+            .windows
+            .iter()
+            .flat_map(|w| w.occurrences(from, LOOKAHEAD_DAYS + 1))
+            .flat_map(|(open, close)| [open, close])
+            .filter(|t| *t > now)
+            .collect();
+        boundaries.sort();
+        boundaries.into_iter().find(|t| self.is_open(*t) != current)
+    }
+}
+
+/// A strategy entering or leaving its schedule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScheduleTransition {
+    /// Strategy name
+    pub strategy: String,
+    /// New state
+    pub open: bool,
+    /// When the transition was observed
+    pub at: DateTime<Utc>,
+    /// Open positions should be closed
+    pub flatten: bool,
+}
+
+/// Schedule state of one strategy, for status output
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleStatus {
+    /// Strategy name
+    pub strategy: String,
+    /// Whether trading is currently allowed
+    pub open: bool,
+    /// Next state change, if any within the lookahead
+    pub next_transition: Option<DateTime<Utc>>,
+}
+
+/// Tracks schedule state per strategy and reports transitions
+pub struct TradingSchedule {
+    config: ScheduleConfig,
+    states: HashMap<String, bool>,
+    journal: Option<Journal>,
+}
+
+impl TradingSchedule {
+    /// Create a schedule; states are initialised on the first `update`
+    pub fn new(config: ScheduleConfig) -> Self {
+        Self {
+            config,
+            states: HashMap::new(),
+            journal: None,
+        }
+    }
+
+    /// Journal transitions to the given journal
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Schedule for a strategy, falling back to the default
+    pub fn schedule_for(&self, strategy: &str) -> &StrategySchedule {
+        self.config
+            .strategies
+            .get(strategy)
+            .unwrap_or(&self.config.default)
+    }
+
+    /// Whether `strategy` may open new positions at `now`
+    pub fn allows_entry(&self, strategy: &str, now: DateTime<Utc>) -> bool {
+        self.schedule_for(strategy).is_open(now)
+    }
+
+    /// Default strategy first, then overrides by name
+    fn strategies(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(DEFAULT_STRATEGY).chain(self.config.strategies.keys().map(String::as_str))
+    }
+
+    /// Current state of every strategy
+    pub fn status(&self, now: DateTime<Utc>) -> Vec<ScheduleStatus> {
+        self.strategies()
+            .map(|strategy| {
+                let schedule = self.schedule_for(strategy);
+                ScheduleStatus {
+                    strategy: strategy.to_string(),
+                    open: schedule.is_open(now),
+                    next_transition: schedule.next_transition(now),
+                }
+            })
+            .collect()
+    }
+
+    /// Re-evaluate all strategies at `now`
+    ///
+    /// Returns the strategies whose state changed since the previous call.
+    /// The first call establishes the initial state without reporting it.
+    pub fn update(&mut self, now: DateTime<Utc>) -> Vec<ScheduleTransition> {
+        let mut transitions = vec![];
+        for status in self.status(now) {
+            crate::telemetry::set_schedule_state(
+                &status.strategy,
+                status.open,
+                status.next_transition.map(|t| (t - now).num_seconds()),
+            );
+
+            let previous = self.states.insert(status.strategy.clone(), status.open);
+            if previous.is_none_or(|open| open == status.open) {
+                continue;
+            }
+
+            let flatten = !status.open && self.schedule_for(&status.strategy).flatten_on_close;
+            let transition = ScheduleTransition {
+                strategy: status.strategy,
+                open: status.open,
+                at: now,
+                flatten,
+            };
+            tracing::info!(
+                event_code = %EventCode::ScheduleTransition,
+                strategy = %transition.strategy,
+                open = transition.open,
+                flatten = transition.flatten,
+                next = ?status.next_transition,
+                "Trading schedule transition"
+            );
+            if let Some(journal) = &self.journal {
+                if let Err(e) = journal.append("schedule_transition", &transition) {
+                    tracing::warn!(error = %e, "Failed to journal schedule transition");
+                }
+            }
+            transitions.push(transition);
+        }
+        transitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2024-01-01 is a Monday
+        Utc.with_ymd_and_hms(2024, 1, day, hour, minute, 0).unwrap()
+    }
+
+    fn window(days: Vec<Weekday>, start: &str, end: &str) -> TradingWindow {
+        TradingWindow {
+            days,
+            start: parse_time(start).unwrap(),
+            end: parse_time(end).unwrap(),
+        }
+    }
+
+    fn weekdays() -> Vec<Weekday> {
+        vec![
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ]
+    }
+
+    #[test]
+    fn test_empty_schedule_always_open() {
+        let schedule = StrategySchedule::default();
+        assert!(schedule.is_open(at(6, 3, 0)));
+        assert_eq!(schedule.next_transition(at(6, 3, 0)), None);
+    }
+
+    #[test]
+    fn test_window_boundaries_are_half_open() {
+        let schedule = StrategySchedule {
+            windows: vec![window(weekdays(), "13:30", "20:00")],
+            flatten_on_close: false,
+        };
+        assert!(!schedule.is_open(at(1, 13, 29)));
+        assert!(schedule.is_open(at(1, 13, 30)));
+        assert!(schedule.is_open(at(1, 19, 59)));
+        assert!(!schedule.is_open(at(1, 20, 0)));
+        // Saturday
+        assert!(!schedule.is_open(at(6, 15, 0)));
+
+        assert_eq!(schedule.next_transition(at(1, 10, 0)), Some(at(1, 13, 30)));
+        assert_eq!(schedule.next_transition(at(1, 14, 0)), Some(at(1, 20, 0)));
+        // Friday evening -> Monday open
+        assert_eq!(schedule.next_transition(at(5, 21, 0)), Some(at(8, 13, 30)));
+    }
+
+    #[test]
+    fn test_window_spanning_midnight() {
+        let schedule = StrategySchedule {
+            windows: vec![window(vec![Weekday::Fri], "22:00", "02:00")],
+            flatten_on_close: false,
+        };
+        assert!(!schedule.is_open(at(5, 21, 59)));
+        assert!(schedule.is_open(at(5, 23, 0)));
+        // Still open early Saturday, from Friday's window
+        assert!(schedule.is_open(at(6, 1, 59)));
+        assert!(!schedule.is_open(at(6, 2, 0)));
+        // Saturday's own window does not open
+        assert!(!schedule.is_open(at(6, 23, 0)));
+        assert_eq!(schedule.next_transition(at(5, 23, 0)), Some(at(6, 2, 0)));
+    }
+
+    #[test]
+    fn test_adjacent_windows_merge() {
+        let schedule = StrategySchedule {
+            windows: vec![
+                window(vec![], "00:00", "12:00"),
+                window(vec![], "12:00", "18:00"),
+            ],
+            flatten_on_close: false,
+        };
+        assert!(schedule.is_open(at(2, 12, 0)));
+        assert_eq!(schedule.next_transition(at(2, 11, 0)), Some(at(2, 18, 0)));
+    }
+
+    #[test]
+    fn test_per_strategy_schedules_and_transitions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let mut strategies = BTreeMap::new();
+        strategies.insert(
+            "spread".to_string(),
+            StrategySchedule {
+                windows: vec![window(weekdays(), "13:30", "20:00")],
+                flatten_on_close: true,
+            },
+        );
+        let mut schedule = TradingSchedule::new(ScheduleConfig {
+            default: StrategySchedule::default(),
+            strategies,
+        })
+        .with_journal(Journal::open(&path).unwrap());
+
+        assert!(schedule.update(at(1, 13, 0)).is_empty());
+        assert!(schedule.allows_entry("lag", at(1, 13, 0)));
+        assert!(!schedule.allows_entry("spread", at(1, 13, 0)));
+
+        let opened = schedule.update(at(1, 13, 30));
+        assert_eq!(opened.len(), 1);
+        assert_eq!(opened[0].strategy, "spread");
+        assert!(opened[0].open);
+        assert!(!opened[0].flatten);
+
+        assert!(schedule.update(at(1, 15, 0)).is_empty());
+
+        let closed = schedule.update(at(1, 20, 0));
+        assert_eq!(closed.len(), 1);
+        assert!(!closed[0].open);
+        assert!(closed[0].flatten);
+
+        let status = schedule.status(at(1, 20, 0));
+        assert_eq!(status[0].strategy, DEFAULT_STRATEGY);
+        assert!(status[0].open);
+        assert_eq!(status[1].next_transition, Some(at(2, 13, 30)));
+
+        let entries = Journal::read_all(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].kind, "schedule_transition");
+        assert_eq!(entries[1].data["flatten"], true);
+    }
+
+    #[test]
+    fn test_no_flatten_without_flag() {
+        let mut schedule = TradingSchedule::new(ScheduleConfig {
+            default: StrategySchedule {
+                windows: vec![window(vec![], "08:00", "09:00")],
+                flatten_on_close: false,
+            },
+            strategies: BTreeMap::new(),
+        });
+        schedule.update(at(1, 8, 30));
+        let closed = schedule.update(at(1, 9, 0));
+        assert_eq!(closed.len(), 1);
+        assert!(!closed[0].flatten);
+    }
+
+    #[test]
+    fn test_deserialize_config() {
+        let config: ScheduleConfig = toml::from_str(
+            r#"
+            flatten_on_close = true
+
+            [[windows]]
+            days = ["Mon", "Tue"]
+            start = "13:30"
+            end = "20:00:30"
+
+            [strategies.spread]
+            windows = [{ start = "22:00", end = "02:00" }]
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.default.flatten_on_close);
+        assert_eq!(
+            config.default.windows[0].days,
+            vec![Weekday::Mon, Weekday::Tue]
+        );
+        assert_eq!(
+            config.default.windows[0].end,
+            NaiveTime::from_hms_opt(20, 0, 30).unwrap()
+        );
+        assert!(config.strategies["spread"].windows[0].days.is_empty());
+        assert!(!config.strategies["spread"].flatten_on_close);
+    }
+}
@@ -1,6 +1,6 @@
 //! Risk management types
 
-use super::HaltReason;
+use super::{HaltReason, RateCap};
 use rust_decimal::Decimal;
 use thiserror::Error;
 
@@ -16,6 +16,39 @@ pub enum RiskError {
     /// Maximum exposure reached
     #[error("Maximum exposure reached")]
     MaxExposureReached,
+    /// Order would push a market's aggregate exposure over a cap
+    #[error(
+        "Market {market} {limit} {value} would exceed cap {cap}; existing positions: {positions}"
+    )]
+    MarketLimitBreached {
+        market: String,
+        limit: &'static str,
+        value: Decimal,
+        cap: Decimal,
+        positions: String,
+    },
+    /// Order would push a neg-risk group's gross notional over its cap
+    #[error("Group {group} gross notional {value} would exceed cap {cap}; existing: {existing}")]
+    GroupLimitBreached {
+        group: String,
+        value: Decimal,
+        cap: Decimal,
+        existing: String,
+    },
+    /// An entry cap was reached
+    #[error("Rate cap {cap} exceeded: {count} of {limit} entries")]
+    RateCapExceeded {
+        cap: RateCap,
+        limit: u32,
+        count: u32,
+    },
+    /// An order limit falls outside the prices the book can quote
+    #[error("Limit price {price} outside quotable range {min}-{max}")]
+    PriceOutOfBounds {
+        price: Decimal,
+        min: Decimal,
+        max: Decimal,
+    },
     /// Trading has been halted
     #[error("Trading halted: {0:?}")]
     TradingHalted(HaltReason),
@@ -0,0 +1,211 @@
+//! Stable codes of rejection reasons
+//!
+//! [`NoLagReason`] and [`RejectReason`] are written to journals, trade
+//! tapes and metrics as a snake_case code plus a JSON object of the numbers
+//! behind it. Analytics match on the code, so a code once shipped is never
+//! renamed or reused; the tests below pin every one.
+//!
+//! Data captured before the codes were formalized holds older text:
+//! `Debug` renderings such as `EdgeTooLarge(0.2)` and a few early labels.
+//! [`reason_code`] maps those to today's code.
+
+use super::{NoLagReason, RejectReason};
+use serde::Serialize;
+
+/// Labels written before the current codes, and the code each became
+const LEGACY_LABELS: [(&str, &str); 1] = [("edge_below_threshold", "edge_too_small")];
+
+/// The code of a rejection reason as captured, current or legacy text
+///
+/// `None` when the text names no [`NoLagReason`] or [`RejectReason`], e.g.
+/// a cooldown or rate cap block, which callers keep as written.
+pub fn reason_code(text: &str) -> Option<&'static str> {
+    let text = text.trim();
+    let known = |code: &str| {
+        NoLagReason::CODES
+            .into_iter()
+            .chain(RejectReason::CODES)
+            .find(|c| *c == code)
+    };
+    if let Some(code) = known(text) {
+        return Some(code);
+    }
+    if let Some((_, code)) = LEGACY_LABELS.iter().find(|(label, _)| *label == text) {
+        return Some(code);
+    }
+    // `Debug` text: the variant name, then its fields
+    let name = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .next()
+        .unwrap_or_default();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    known(&snake)
+}
+
+/// Fields of an internally tagged reason without its `code`
+pub(super) fn context_of(reason: &impl Serialize) -> serde_json::Value {
+    let mut value = serde_json::to_value(reason).unwrap_or_default();
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("code");
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use std::collections::HashSet;
+
+    fn no_lag_reasons() -> Vec<NoLagReason> {
+        vec![
+            NoLagReason::Expired,
+            NoLagReason::BookFault,
+            NoLagReason::NoAsk,
+            NoLagReason::NoEdge {
+                edge: dec!(0.01),
+                costs: dec!(0.02),
+            },
+            NoLagReason::NearBound {
+                edge: dec!(0.03),
+                min: dec!(0.05),
+            },
+            NoLagReason::ComplementRepriced {
+                yes_ask: dec!(0.40),
+                implied_yes: dec!(0.90),
+            },
+        ]
+    }
+
+    fn reject_reasons() -> Vec<RejectReason> {
+        vec![
+            RejectReason::EdgeTooSmall {
+                edge: dec!(0.001),
+                min: dec!(0.005),
+            },
+            RejectReason::EdgeTooLarge {
+                edge: dec!(0.2),
+                max: dec!(0.15),
+            },
+            RejectReason::InsufficientLiquidity {
+                available: dec!(50),
+                min: dec!(100),
+            },
+            RejectReason::SpreadTooWide {
+                spread: dec!(0.08),
+                max: dec!(0.05),
+            },
+            RejectReason::TooCloseToExpiry {
+                secs_to_close: 30,
+                min_secs: 60,
+            },
+            RejectReason::VolatilityOutOfRange {
+                volatility: dec!(2),
+                min: dec!(0.1),
+                max: dec!(1.5),
+            },
+            RejectReason::MaxPositionsReached { open: 3, max: 3 },
+            RejectReason::MomentumReverting {
+                retrace: dec!(0.5),
+                max: dec!(0.3),
+            },
+            RejectReason::VenuesUnconfirmed {
+                agreeing: 1,
+                required: 2,
+            },
+            RejectReason::VenuesDiverged {
+                divergence_pct: dec!(0.2),
+                max_pct: dec!(0.1),
+            },
+            RejectReason::ModelDisagreement {
+                gap: dec!(0.3),
+                max: dec!(0.2),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_codes_are_unique_and_stable() {
+        let no_lag: Vec<&str> = no_lag_reasons().iter().map(|r| r.code()).collect();
+        let reject: Vec<&str> = reject_reasons().iter().map(|r| r.code()).collect();
+        assert_eq!(no_lag, NoLagReason::CODES);
+        assert_eq!(reject, RejectReason::CODES);
+        // Shipped codes; changing one breaks every consumer of old data
+        assert_eq!(
+            NoLagReason::CODES,
+            [
+                "expired",
+                "book_fault",
+                "no_ask",
+                "no_edge",
+                "near_bound",
+                "complement_repriced"
+            ]
+        );
+        assert_eq!(
+            RejectReason::CODES,
+            [
+                "edge_too_small",
+                "edge_too_large",
+                "insufficient_liquidity",
+                "spread_too_wide",
+                "too_close_to_expiry",
+                "volatility_out_of_range",
+                "max_positions_reached",
+                "momentum_reverting",
+                "venues_unconfirmed",
+                "venues_diverged",
+                "model_disagreement"
+            ]
+        );
+        let all: HashSet<&str> = no_lag.iter().chain(&reject).copied().collect();
+        assert_eq!(all.len(), no_lag.len() + reject.len());
+    }
+
+    #[test]
+    fn test_serialized_code_matches_and_round_trips() {
+        for reason in no_lag_reasons() {
+            let value = serde_json::to_value(reason).unwrap();
+            assert_eq!(value["code"], reason.code());
+            assert!(reason.context().get("code").is_none());
+            let back: NoLagReason = serde_json::from_value(value).unwrap();
+            assert_eq!(back, reason);
+        }
+        for reason in reject_reasons() {
+            let value = serde_json::to_value(&reason).unwrap();
+            assert_eq!(value["code"], reason.code());
+            assert!(!reason.context().as_object().unwrap().is_empty());
+            assert!(reason.to_string().starts_with(reason.code()));
+            let back: RejectReason = serde_json::from_value(value).unwrap();
+            assert_eq!(back, reason);
+        }
+        assert_eq!(NoLagReason::NoAsk.context(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_legacy_text_maps_to_codes() {
+        for (text, code) in [
+            ("spread_too_wide", Some("spread_too_wide")),
+            ("near_bound", Some("near_bound")),
+            ("edge_below_threshold", Some("edge_too_small")),
+            ("EdgeTooLarge(0.202016)", Some("edge_too_large")),
+            (
+                "TooCloseToExpiry(TimeDelta { secs: 30, nanos: 0 })",
+                Some("too_close_to_expiry"),
+            ),
+            ("MaxPositionsReached", Some("max_positions_reached")),
+            ("ComplementRepriced", Some("complement_repriced")),
+            ("rate_cap", None),
+            ("book_stale", None),
+            ("", None),
+        ] {
+            assert_eq!(reason_code(text), code, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! YES/NO book consistency monitoring
+//!
+//! The YES and NO tokens of a binary market must sum to 1. When the two
+//! books disagree beyond the spread, either one of them is stale or there is
+//! a sell-both-sides arbitrage (`yes_bid + no_bid > 1`).
+
+use super::Side;
+use crate::execution::{Order, OrderAction, OrderType};
+use crate::market::Market;
+use crate::orderbook::{BookSide, OrderBook};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Default maximum |yes_mid + no_mid - 1| before the older book is flagged
+pub const DEFAULT_MAX_MID_DEVIATION: Decimal = dec!(0.05);
+
+/// Default minimum net edge of a sell-spread signal
+pub const DEFAULT_MIN_SELL_EDGE: Decimal = dec!(0.005);
+
+/// Configuration for the consistency monitor, under `[signal.consistency]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ConsistencyConfig {
+    /// Maximum |yes_mid + no_mid - 1| before the older book is flagged
+    #[serde(default = "default_max_mid_deviation")]
+    pub max_mid_deviation: Decimal,
+    /// Fee rate charged on each leg; the engine takes it from
+    /// `[execution.costs]`
+    #[serde(skip, default = "default_fee_rate")]
+    pub fee_rate: Decimal,
+    /// Minimum net edge required to emit a sell-spread signal
+    #[serde(default = "default_min_sell_edge")]
+    pub min_sell_edge: Decimal,
+    /// Whether sell-both-sides signals are emitted
+    #[serde(default)]
+    pub sell_spread_enabled: bool,
+}
+
+fn default_max_mid_deviation() -> Decimal {
+    DEFAULT_MAX_MID_DEVIATION
+}
+
+fn default_fee_rate() -> Decimal {
+    dec!(0.001)
+}
+
+fn default_min_sell_edge() -> Decimal {
+    DEFAULT_MIN_SELL_EDGE
+}
+
+impl Default for ConsistencyConfig {
+    fn default() -> Self {
+        Self {
+            max_mid_deviation: DEFAULT_MAX_MID_DEVIATION,
+            fee_rate: default_fee_rate(),
+            min_sell_edge: DEFAULT_MIN_SELL_EDGE,
+            sell_spread_enabled: false,
+        }
+    }
+}
+
+/// Result of checking a market's YES/NO books against each other
+#[derive(Debug, Clone)]
+pub struct ConsistencyCheck {
+    /// Market condition identifier
+    pub condition_id: String,
+    /// yes_ask + no_ask (below 1 means buying both sides is profitable)
+    pub buy_sum: Option<Decimal>,
+    /// yes_bid + no_bid (above 1 means selling both sides is profitable)
+    pub sell_sum: Option<Decimal>,
+    /// yes_mid + no_mid - 1
+    pub mid_deviation: Option<Decimal>,
+    /// Token id of a book that needs a resync: a crossed or locked book
+    /// immediately, otherwise the older book when the mids disagree beyond
+    /// the threshold
+    pub suspect_token: Option<String>,
+    /// `"crossed"` or `"locked"` if either book's top is inconsistent
+    pub book_fault: Option<&'static str>,
+    /// Sell-both-sides opportunity, if enabled and profitable after fees
+    pub sell_signal: Option<SellSpreadSignal>,
+}
+
+/// Sell both YES and NO when their bids sum above 1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SellSpreadSignal {
+    /// Unique signal identifier
+    pub id: Uuid,
+    /// Market this signal is for
+    pub market: Market,
+    /// Best YES bid
+    pub yes_bid: Decimal,
+    /// Best NO bid
+    pub no_bid: Decimal,
+    /// Size available on both sides
+    pub size: Decimal,
+    /// yes_bid + no_bid - 1
+    pub gross_edge: Decimal,
+    /// Gross edge less fees on both legs
+    pub net_edge: Decimal,
+    /// Signal generation timestamp
+    pub timestamp: DateTime<Utc>,
+}
+
+impl SellSpreadSignal {
+    /// Limit sell orders for both legs
+    pub fn orders(&self) -> [Order; 2] {
+        [
+            Order {
+                token_id: self.market.yes_token_id.clone(),
+                side: Side::Yes,
+                price: self.yes_bid,
+                size: self.size,
+                order_type: OrderType::Limit,
+                action: OrderAction::Sell,
+                client_order_id: None,
+            },
+            Order {
+                token_id: self.market.no_token_id.clone(),
+                side: Side::No,
+                price: self.no_bid,
+                size: self.size,
+                order_type: OrderType::Limit,
+                action: OrderAction::Sell,
+                client_order_id: None,
+            },
+        ]
+    }
+}
+
+#[derive(Default)]
+struct BookPair {
+    yes: Option<OrderBook>,
+    no: Option<OrderBook>,
+}
+
+/// Tracks the latest YES/NO books per market and checks them on every update
+pub struct ConsistencyMonitor {
+    config: ConsistencyConfig,
+    books: HashMap<String, BookPair>,
+}
+
+impl ConsistencyMonitor {
+    /// Create a new consistency monitor
+    pub fn new(config: ConsistencyConfig) -> Self {
+        Self {
+            config,
+            books: HashMap::new(),
+        }
+    }
+
+    /// Apply a book update and re-check the market
+    ///
+    /// Returns `None` until both books of the market have been seen, or if
+    /// the book does not belong to the market.
+    pub fn update(&mut self, market: &Market, book: &OrderBook) -> Option<ConsistencyCheck> {
+        let pair = self.books.entry(market.condition_id.clone()).or_default();
+        if book.token_id == market.yes_token_id {
+            pair.yes = Some(book.clone());
+        } else if book.token_id == market.no_token_id {
+            pair.no = Some(book.clone());
+        } else {
+            return None;
+        }
+
+        let (yes, no) = (pair.yes.as_ref()?, pair.no.as_ref()?);
+        let check = check_books(&self.config, market, yes, no);
+
+        if let Some(deviation) = check.mid_deviation {
+            crate::telemetry::record_book_consistency_deviation(
+                &market.condition_id,
+                decimal_to_f64(deviation),
+            );
+        }
+        if let (Some(fault), Some(token)) = (check.book_fault, &check.suspect_token) {
+            tracing::warn!(
+                market = %market.condition_id,
+                token = %token,
+                fault,
+                "Order book top is inconsistent, flagging book for resync"
+            );
+        } else if let Some(ref token) = check.suspect_token {
+            tracing::warn!(
+                market = %market.condition_id,
+                token = %token,
+                deviation = ?check.mid_deviation,
+                "YES/NO books inconsistent, flagging older book as stale"
+            );
+        }
+
+        Some(check)
+    }
+
+    /// Forget a market's books
+    pub fn remove(&mut self, condition_id: &str) {
+        self.books.remove(condition_id);
+    }
+
+    /// Take leg fees at `fee_rate`
+    pub fn with_fee_rate(mut self, fee_rate: Decimal) -> Self {
+        self.config.fee_rate = fee_rate;
+        self
+    }
+}
+
+/// Compute the YES/NO identities for a pair of books
+fn check_books(
+    config: &ConsistencyConfig,
+    market: &Market,
+    yes: &OrderBook,
+    no: &OrderBook,
+) -> ConsistencyCheck {
+    let buy_sum = yes.best_ask().zip(no.best_ask()).map(|(y, n)| y + n);
+    let sell_sum = yes.best_bid().zip(no.best_bid()).map(|(y, n)| y + n);
+    let mid_deviation = yes
+        .mid_price()
+        .zip(no.mid_price())
+        .map(|(y, n)| y + n - Decimal::ONE);
+
+    // A crossed or locked book is bad data regardless of its age
+    let faulted = [yes, no]
+        .into_iter()
+        .find_map(|book| book.top_of_book_fault().map(|fault| (book, fault)));
+
+    let suspect_token = match faulted {
+        Some((book, _)) => Some(book.token_id.clone()),
+        None => mid_deviation
+            .filter(|d| d.abs() > config.max_mid_deviation)
+            .map(|_| {
+                if yes.updated_at <= no.updated_at {
+                    yes.token_id.clone()
+                } else {
+                    no.token_id.clone()
+                }
+            }),
+    };
+
+    let sell_signal = match faulted {
+        _ if !config.sell_spread_enabled => None,
+        Some((_, fault)) => {
+            crate::telemetry::record_crossed_book(fault, "spread");
+            None
+        }
+        None => sell_signal(config, market, yes, no),
+    };
+
+    ConsistencyCheck {
+        condition_id: market.condition_id.clone(),
+        buy_sum,
+        sell_sum,
+        mid_deviation,
+        suspect_token,
+        book_fault: faulted.map(|(_, fault)| fault),
+        sell_signal,
+    }
+}
+
+fn sell_signal(
+    config: &ConsistencyConfig,
+    market: &Market,
+    yes: &OrderBook,
+    no: &OrderBook,
+) -> Option<SellSpreadSignal> {
+    let yes_top = yes.bids.first()?;
+    let no_top = no.bids.first()?;
+
+    let gross_edge = yes_top.price + no_top.price - Decimal::ONE;
+    let fees = (yes_top.price + no_top.price) * config.fee_rate;
+    let net_edge = gross_edge - fees;
+    if net_edge < config.min_sell_edge {
+        return None;
+    }
+
+    Some(SellSpreadSignal {
+        id: Uuid::new_v4(),
+        market: market.clone(),
+        yes_bid: yes_top.price,
+        no_bid: no_top.price,
+        size: yes
+            .depth_within(BookSide::Bid, 0)
+            .min(no.depth_within(BookSide::Bid, 0)),
+        gross_edge,
+        net_edge,
+        timestamp: Utc::now(),
+    })
+}
+
+fn decimal_to_f64(value: Decimal) -> f64 {
+    use rust_decimal::prelude::ToPrimitive;
+    value.to_f64().unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::PriceLevel;
+    use chrono::Duration;
+
+    fn market() -> Market {
+        Market {
+            condition_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time: Utc::now(),
+            close_time: Utc::now() + Duration::minutes(15),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    fn book(token_id: &str, bid: Decimal, ask: Decimal, age_secs: i64) -> OrderBook {
+        let mut book = OrderBook::new(token_id);
+        book.bids = vec![PriceLevel {
+            price: bid,
+            size: dec!(50),
+        }];
+        book.asks = vec![PriceLevel {
+            price: ask,
+            size: dec!(50),
+        }];
+        book.updated_at = Utc::now() - Duration::seconds(age_secs);
+        book
+    }
+
+    fn enabled() -> ConsistencyConfig {
+        ConsistencyConfig {
+            sell_spread_enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_waits_for_both_books() {
+        let mut monitor = ConsistencyMonitor::new(enabled());
+        let market = market();
+        assert!(monitor
+            .update(&market, &book("yes", dec!(0.50), dec!(0.52), 0))
+            .is_none());
+        assert!(monitor
+            .update(&market, &book("other", dec!(0.50), dec!(0.52), 0))
+            .is_none());
+        assert!(monitor
+            .update(&market, &book("no", dec!(0.47), dec!(0.49), 0))
+            .is_some());
+    }
+
+    #[test]
+    fn test_buy_side_arb_reports_sum_without_sell_signal() {
+        let mut monitor = ConsistencyMonitor::new(enabled());
+        let market = market();
+        monitor.update(&market, &book("yes", dec!(0.46), dec!(0.47), 0));
+        let check = monitor
+            .update(&market, &book("no", dec!(0.49), dec!(0.50), 0))
+            .unwrap();
+
+        assert_eq!(check.buy_sum, Some(dec!(0.97)));
+        assert!(check.sell_signal.is_none());
+        assert!(check.suspect_token.is_none());
+    }
+
+    #[test]
+    fn test_sell_side_arb_emits_signal() {
+        let mut monitor = ConsistencyMonitor::new(enabled());
+        let market = market();
+        monitor.update(&market, &book("yes", dec!(0.53), dec!(0.54), 0));
+        let check = monitor
+            .update(&market, &book("no", dec!(0.50), dec!(0.51), 0))
+            .unwrap();
+
+        assert_eq!(check.sell_sum, Some(dec!(1.03)));
+        let signal = check.sell_signal.unwrap();
+        assert_eq!(signal.gross_edge, dec!(0.03));
+        assert_eq!(signal.net_edge, dec!(0.03) - dec!(1.03) * dec!(0.001));
+
+        let orders = signal.orders();
+        assert!(orders.iter().all(|o| o.action == OrderAction::Sell));
+        assert_eq!(orders[0].token_id, "yes");
+        assert_eq!(orders[1].price, dec!(0.50));
+    }
+
+    #[test]
+    fn test_sell_signal_disabled_by_default() {
+        let mut monitor = ConsistencyMonitor::new(ConsistencyConfig::default());
+        let market = market();
+        monitor.update(&market, &book("yes", dec!(0.53), dec!(0.54), 0));
+        let check = monitor
+            .update(&market, &book("no", dec!(0.50), dec!(0.51), 0))
+            .unwrap();
+        assert!(check.sell_signal.is_none());
+    }
+
+    #[test]
+    fn test_stale_book_flagged_without_signal() {
+        let mut monitor = ConsistencyMonitor::new(enabled());
+        let market = market();
+        // YES book is 30s old; mids sum to 1.10 but bids only to 0.98
+        monitor.update(&market, &book("yes", dec!(0.38), dec!(0.52), 30));
+        let check = monitor
+            .update(&market, &book("no", dec!(0.60), dec!(0.70), 0))
+            .unwrap();
+
+        assert_eq!(check.mid_deviation, Some(dec!(0.10)));
+        assert_eq!(check.suspect_token.as_deref(), Some("yes"));
+        assert!(check.sell_signal.is_none());
+    }
+
+    #[test]
+    fn test_crossed_book_flagged_for_resync_and_blocks_signal() {
+        let mut monitor = ConsistencyMonitor::new(enabled());
+        let market = market();
+        // YES is older, but the fresh NO book is crossed and gets flagged
+        monitor.update(&market, &book("yes", dec!(0.53), dec!(0.54), 30));
+        let check = monitor
+            .update(&market, &book("no", dec!(0.52), dec!(0.50), 0))
+            .unwrap();
+
+        assert_eq!(check.sell_sum, Some(dec!(1.05)));
+        assert_eq!(check.book_fault, Some("crossed"));
+        assert_eq!(check.suspect_token.as_deref(), Some("no"));
+        assert!(check.sell_signal.is_none());
+    }
+
+    #[test]
+    fn test_locked_book_blocks_signal() {
+        let mut monitor = ConsistencyMonitor::new(enabled());
+        let market = market();
+        monitor.update(&market, &book("yes", dec!(0.54), dec!(0.54), 0));
+        let check = monitor
+            .update(&market, &book("no", dec!(0.50), dec!(0.51), 0))
+            .unwrap();
+
+        assert_eq!(check.book_fault, Some("locked"));
+        assert_eq!(check.suspect_token.as_deref(), Some("yes"));
+        assert!(check.sell_signal.is_none());
+    }
+}
@@ -1,19 +1,83 @@
 //! Signal detection
-
-use super::{Side, Signal, SignalReason};
-use crate::market::Market;
+//!
+//! The lag is the gap between the fair value of the spot price now and
+//! the YES book still priced off the spot of a moment ago. Both sides are
+//! priced off the YES ask: YES at the ask, NO at one minus it. After a
+//! down move the YES ask has not yet fallen, so NO is still cheap.
+//!
+//! A 1% move against neutral odds, then a Kelly stake:
+//!
+//! ```
+//! use poly_hft::model::GbmModel;
+//! use poly_hft::orderbook::MarketBooks;
+//! use poly_hft::prelude::*;
+//! use poly_hft::signal::SignalDetector;
+//! use chrono::{DateTime, Duration};
+//! use rust_decimal_macros::dec;
+//!
+//! let open = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+//! let market = Market::new("btc-15m", "BTC", dec!(100000), open, open + Duration::minutes(15));
+//! let book = OrderBook::new("btc-15m-yes")
+//!     .with_bid(dec!(0.49), dec!(200))
+//!     .with_ask(dec!(0.51), dec!(200));
+//! // 1% fee, half a cent of slippage
+//! let detector = SignalDetector::new(GbmModel::new(), dec!(0.01), dec!(0.005));
+//! let now = open + Duration::minutes(5);
+//! let volatility = dec!(0.5);
+//!
+//! // Spot 1% over the strike with ten minutes left: YES is near certain,
+//! // clamped to the 0.99 bound, and the book still asks 0.51
+//! let up = detector
+//!     .detect_at(&market, dec!(101000), volatility, MarketBooks::new(&book), now)
+//!     .unwrap();
+//! assert_eq!(up.side, Side::Yes);
+//! assert_eq!((up.fair_value, up.market_price), (dec!(0.99), dec!(0.51)));
+//! assert_eq!(up.adjusted_edge, dec!(0.99) - dec!(0.51) - dec!(0.015));
+//!
+//! // 1% under: NO, bought at one minus the YES ask
+//! let down = detector
+//!     .detect_at(&market, dec!(99000), volatility, MarketBooks::new(&book), now)
+//!     .unwrap();
+//! assert_eq!((down.side, down.market_price), (Side::No, dec!(0.49)));
+//!
+//! // At the strike the odds are about even, and the cent NO is cheap by
+//! // does not cover the costs
+//! let flat = detector.evaluate_at(&market, dec!(100000), volatility, MarketBooks::new(&book), now);
+//! let Err(NoLagReason::NoEdge { edge, costs }) = flat else { panic!("{:?}", flat) };
+//! assert!(edge < costs);
+//!
+//! // Quarter Kelly stakes far more than 1% of the bankroll, the cap
+//! let kelly = KellyCalculator::new(dec!(0.25), dec!(0.01));
+//! assert_eq!(kelly.size(&up, dec!(1000)), dec!(10));
+//! ```
+
+use super::{NoLagReason, Side, Signal, SignalReason};
+use crate::ids;
+use crate::market::{Market, PriceBounds};
 use crate::model::{FairValueModel, FairValueParams};
-use crate::orderbook::OrderBook;
-use chrono::{Duration, Utc};
+use crate::orderbook::{BookSide, MarketBooks, OrderBook};
+use crate::risk::DEFAULT_STRATEGY;
+use chrono::{DateTime, Duration, Utc};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use std::collections::HashMap;
 
+/// Default smallest raw edge, measured against the bound, for a side whose
+/// expected price was clamped to the venue's bound
+pub const DEFAULT_NEAR_BOUND_MIN_EDGE: Decimal = dec!(0.05);
+
+/// Default distance the NO-implied YES price may sit past the YES ask in
+/// the lag's direction
+pub const DEFAULT_COMPLEMENT_TOLERANCE: Decimal = dec!(0.03);
+
 /// Detects tradeable signals from market data
 pub struct SignalDetector<M: FairValueModel> {
     model: M,
     fee_rate: Decimal,
     slippage_estimate: Decimal,
+    bounds: PriceBounds,
+    near_bound_min_edge: Decimal,
+    complement_tolerance: Decimal,
     /// Track last market close times for reset detection
     #[allow(dead_code)]
     last_market_close: HashMap<String, chrono::DateTime<chrono::Utc>>,
@@ -26,10 +90,28 @@ impl<M: FairValueModel> SignalDetector<M> {
             model,
             fee_rate,
             slippage_estimate,
+            bounds: PriceBounds::default(),
+            near_bound_min_edge: DEFAULT_NEAR_BOUND_MIN_EDGE,
+            complement_tolerance: DEFAULT_COMPLEMENT_TOLERANCE,
             last_market_close: HashMap::new(),
         }
     }
 
+    /// Clamp expected prices to `bounds`, trusting a clamped side only with
+    /// at least `near_bound_min_edge` of raw edge against the bound
+    pub fn with_bounds(mut self, bounds: PriceBounds, near_bound_min_edge: Decimal) -> Self {
+        self.bounds = bounds;
+        self.near_bound_min_edge = near_bound_min_edge;
+        self
+    }
+
+    /// Reject a lag once the NO book implies a YES price more than
+    /// `tolerance` past the YES ask in the lag's direction
+    pub fn with_complement_tolerance(mut self, tolerance: Decimal) -> Self {
+        self.complement_tolerance = tolerance;
+        self
+    }
+
     /// Check if market is in post-reset window
     pub fn is_post_reset(&self, market: &Market, window: Duration) -> bool {
         let now = Utc::now();
@@ -44,9 +126,44 @@ impl<M: FairValueModel> SignalDetector<M> {
         volatility: Decimal,
         orderbook: &OrderBook,
     ) -> Option<Signal> {
-        let time_to_expiry = market.close_time - Utc::now();
+        self.detect_at(
+            market,
+            current_price,
+            volatility,
+            MarketBooks::new(orderbook),
+            Utc::now(),
+        )
+    }
+
+    /// Generate a signal as of `now` (used when replaying captured data)
+    pub fn detect_at(
+        &self,
+        market: &Market,
+        current_price: Decimal,
+        volatility: Decimal,
+        books: MarketBooks<'_>,
+        now: DateTime<Utc>,
+    ) -> Option<Signal> {
+        self.evaluate_at(market, current_price, volatility, books, now)
+            .ok()
+    }
+
+    /// Signal as of `now`, or why there is none
+    ///
+    /// Prices come from the YES book; the NO book, when there is one, only
+    /// vouches that the YES book is current.
+    pub fn evaluate_at(
+        &self,
+        market: &Market,
+        current_price: Decimal,
+        volatility: Decimal,
+        books: MarketBooks<'_>,
+        now: DateTime<Utc>,
+    ) -> Result<Signal, NoLagReason> {
+        let orderbook = books.yes;
+        let time_to_expiry = market.close_time - now;
         if time_to_expiry <= Duration::zero() {
-            return None;
+            return Err(NoLagReason::Expired);
         }
 
         // Calculate fair value
@@ -56,21 +173,46 @@ impl<M: FairValueModel> SignalDetector<M> {
             time_to_expiry,
             volatility,
         };
-        let fair_value = self.model.calculate(params);
+        let raw = self.model.calculate(params);
+        // No token trades past the extreme ticks, so neither can its expected price
+        let fair_value = raw.clamped(&self.bounds);
+
+        // Never price an edge off a crossed or locked book
+        if let Some(fault) = orderbook.top_of_book_fault() {
+            // The engine warns once per fault and resyncs the book
+            crate::telemetry::record_crossed_book(fault, "lag");
+            tracing::debug!(
+                market_id = %market.condition_id,
+                token_id = %orderbook.token_id,
+                fault,
+                "Skipping signal: order book top is inconsistent"
+            );
+            return Err(NoLagReason::BookFault);
+        }
 
         // Get market prices from order book
-        let yes_ask = orderbook.best_ask()?;
+        let yes_ask = orderbook.best_ask().ok_or(NoLagReason::NoAsk)?;
         let no_bid = Decimal::ONE - yes_ask; // Implied no price
 
+        // The NO book mirrors the YES book, so both sides share one spread.
+        // With no bid there is nobody to sell back to, so the exit is worth
+        // nothing.
+        let spread = yes_ask - orderbook.best_bid().unwrap_or(Decimal::ZERO);
+
+        // Both sides are priced off the YES asks, so that is the size we take
+        let depth = orderbook.depth_profile(BookSide::Ask);
+
         // Calculate edge for each side
         let yes_edge = fair_value.yes_prob - yes_ask;
         let no_edge = fair_value.no_prob - no_bid;
 
         // Determine best side and edge
-        let (side, raw_edge, fair_prob, market_price) = if yes_edge > no_edge {
-            (Side::Yes, yes_edge, fair_value.yes_prob, yes_ask)
+        let (side, raw_edge, fair_prob, market_price, clamped) = if yes_edge > no_edge {
+            let clamped = fair_value.yes_prob != raw.yes_prob;
+            (Side::Yes, yes_edge, fair_value.yes_prob, yes_ask, clamped)
         } else {
-            (Side::No, no_edge, fair_value.no_prob, no_bid)
+            let clamped = fair_value.no_prob != raw.no_prob;
+            (Side::No, no_edge, fair_value.no_prob, no_bid, clamped)
         };
 
         // Adjust for fees and slippage
@@ -78,11 +220,56 @@ impl<M: FairValueModel> SignalDetector<M> {
         let adjusted_edge = raw_edge - total_costs;
 
         if adjusted_edge <= dec!(0) {
-            return None;
+            return Err(NoLagReason::NoEdge {
+                edge: raw_edge,
+                costs: total_costs,
+            });
+        }
+
+        // Past the bound the model's certainty buys nothing the book can
+        // show, so only a lag well clear of the bound is worth trading
+        if clamped && raw_edge < self.near_bound_min_edge {
+            tracing::debug!(
+                market_id = %market.condition_id,
+                side = ?side,
+                expected = %fair_prob,
+                market_price = %market_price,
+                raw_edge = %raw_edge,
+                "Skipping signal: expected price clamped to the bound"
+            );
+            return Err(NoLagReason::NearBound {
+                edge: raw_edge,
+                min: self.near_bound_min_edge,
+            });
+        }
+
+        // A lag the NO book has already priced in is a stale YES book: the
+        // market moved and only this side of it has not caught up
+        let books = books.with_no(books.no.filter(|b| b.top_of_book_fault().is_none()));
+        let implied = match side {
+            Side::Yes => books
+                .implied_yes_bid()
+                .filter(|bid| *bid - yes_ask > self.complement_tolerance),
+            Side::No => books
+                .implied_yes_ask()
+                .filter(|ask| yes_ask - *ask > self.complement_tolerance),
+        };
+        if let Some(implied) = implied {
+            tracing::debug!(
+                market_id = %market.condition_id,
+                side = ?side,
+                yes_ask = %yes_ask,
+                implied_yes = %implied,
+                "Skipping signal: NO book has already repriced"
+            );
+            return Err(NoLagReason::ComplementRepriced {
+                yes_ask,
+                implied_yes: implied,
+            });
         }
 
         // Determine signal reason
-        let reason = if self.is_post_reset(market, Duration::minutes(2)) {
+        let reason = if now - market.open_time < Duration::minutes(2) {
             SignalReason::PostResetLag
         } else if raw_edge > dec!(0.02) {
             SignalReason::SpotDivergence
@@ -90,7 +277,7 @@ impl<M: FairValueModel> SignalDetector<M> {
             SignalReason::VolatilitySpike
         };
 
-        Some(Signal::new(
+        let mut signal = Signal::new(
             market.clone(),
             side,
             fair_prob,
@@ -98,7 +285,12 @@ impl<M: FairValueModel> SignalDetector<M> {
             adjusted_edge,
             fair_value.confidence,
             reason,
-        ))
+        )
+        .with_spread(spread)
+        .with_depth(depth);
+        signal.timestamp = now;
+        signal.id = ids::signal_id(&market.condition_id, DEFAULT_STRATEGY, now, side);
+        Ok(signal)
     }
 }
 
@@ -113,11 +305,14 @@ mod tests {
         let now = Utc::now();
         Market {
             condition_id: "test-condition".to_string(),
+            asset: "BTC".to_string(),
             yes_token_id: "yes-token".to_string(),
             no_token_id: "no-token".to_string(),
             open_price: dec!(100000),
             open_time: now - Duration::minutes(open_offset_mins),
             close_time: now + Duration::minutes(close_offset_mins),
+            group_id: None,
+            orientation: Default::default(),
         }
     }
 
@@ -236,4 +431,200 @@ mod tests {
             assert_eq!(s.reason, SignalReason::PostResetLag);
         }
     }
+
+    #[test]
+    fn test_detect_refuses_crossed_and_locked_books() {
+        let model = GbmModel::new();
+        let detector = SignalDetector::new(model, dec!(0.001), dec!(0.001));
+        let market = create_test_market(5, 10);
+
+        // Spot far above open, cheap ask: a healthy book signals
+        let mut orderbook = create_test_orderbook(dec!(0.40));
+        orderbook.bids = vec![PriceLevel {
+            price: dec!(0.39),
+            size: dec!(100),
+        }];
+        assert!(detector
+            .detect(&market, dec!(110000), dec!(0.4), &orderbook)
+            .is_some());
+
+        // Locked
+        orderbook.bids[0].price = dec!(0.40);
+        assert!(detector
+            .detect(&market, dec!(110000), dec!(0.4), &orderbook)
+            .is_none());
+
+        // Crossed
+        orderbook.bids[0].price = dec!(0.45);
+        assert!(detector
+            .detect(&market, dec!(110000), dec!(0.4), &orderbook)
+            .is_none());
+    }
+
+    #[test]
+    fn test_detect_records_spread_and_round_trip_edge() {
+        let model = GbmModel::new();
+        let detector = SignalDetector::new(model, dec!(0.001), dec!(0.001));
+        let market = create_test_market(5, 10);
+        let with_bid = |bid: Decimal| {
+            let mut orderbook = create_test_orderbook(dec!(0.40));
+            orderbook.bids = vec![PriceLevel {
+                price: bid,
+                size: dec!(100),
+            }];
+            orderbook
+        };
+
+        // Tight book: one cent round trip
+        let tight = detector
+            .detect(&market, dec!(110000), dec!(0.4), &with_bid(dec!(0.39)))
+            .unwrap();
+        assert_eq!(tight.spread, dec!(0.01));
+        assert_eq!(tight.round_trip_edge, tight.adjusted_edge - dec!(0.005));
+
+        // Wide book: same entry, much less left after the exit
+        let wide = detector
+            .detect(&market, dec!(110000), dec!(0.4), &with_bid(dec!(0.30)))
+            .unwrap();
+        assert_eq!(wide.adjusted_edge, tight.adjusted_edge);
+        assert_eq!(wide.spread, dec!(0.10));
+        assert_eq!(wide.round_trip_edge, wide.adjusted_edge - dec!(0.05));
+
+        // One-sided: nobody to sell back to
+        let one_sided = detector
+            .detect(
+                &market,
+                dec!(110000),
+                dec!(0.4),
+                &create_test_orderbook(dec!(0.40)),
+            )
+            .unwrap();
+        assert_eq!(one_sided.spread, dec!(0.40));
+        assert_eq!(
+            one_sided.round_trip_edge,
+            one_sided.adjusted_edge - dec!(0.20)
+        );
+    }
+
+    #[test]
+    fn test_last_minute_certainty_is_held_to_the_bound() {
+        let model = GbmModel::new();
+        let detector = SignalDetector::new(GbmModel::new(), dec!(0.001), dec!(0.001));
+        let now = Utc::now();
+        let mut market = create_test_market(14, 1);
+        market.close_time = now + Duration::seconds(45);
+        let spot = dec!(100130);
+        let raw = model.calculate(FairValueParams {
+            current_price: spot,
+            open_price: market.open_price,
+            time_to_expiry: market.close_time - now,
+            volatility: dec!(0.4),
+        });
+        assert!(raw.yes_prob > dec!(0.996) && raw.yes_prob < dec!(0.998));
+
+        // 3.7 cents against the model, but at most 3 against a quotable price
+        let book = create_test_orderbook(dec!(0.96));
+        assert_eq!(
+            detector
+                .evaluate_at(&market, spot, dec!(0.4), MarketBooks::new(&book), now)
+                .unwrap_err()
+                .code(),
+            "near_bound"
+        );
+        assert!(detector
+            .detect_at(&market, spot, dec!(0.4), MarketBooks::new(&book), now)
+            .is_none());
+
+        // A lag well clear of the bound still trades, priced to the bound
+        let book = create_test_orderbook(dec!(0.90));
+        let signal = detector
+            .evaluate_at(&market, spot, dec!(0.4), MarketBooks::new(&book), now)
+            .unwrap();
+        assert_eq!(signal.side, Side::Yes);
+        assert_eq!(signal.fair_value, dec!(0.99));
+        assert_eq!(signal.raw_edge, dec!(0.09));
+
+        // A finer tick lets the same price stand
+        let detector = detector.with_bounds(PriceBounds::new(dec!(0.001)), dec!(0.05));
+        let book = create_test_orderbook(dec!(0.96));
+        let signal = detector
+            .evaluate_at(&market, spot, dec!(0.4), MarketBooks::new(&book), now)
+            .unwrap();
+        assert!(signal.fair_value > dec!(0.996));
+    }
+
+    #[test]
+    fn test_repriced_no_book_marks_the_lag_stale() {
+        let detector = SignalDetector::new(GbmModel::new(), dec!(0.001), dec!(0.001));
+        let market = create_test_market(5, 10);
+        let now = Utc::now();
+        let book = |token: &str, bid: Decimal, ask: Decimal| OrderBook {
+            token_id: token.to_string(),
+            bids: vec![PriceLevel {
+                price: bid,
+                size: dec!(100),
+            }],
+            asks: vec![PriceLevel {
+                price: ask,
+                size: dec!(100),
+            }],
+            updated_at: now,
+        };
+
+        // Spot well up, YES ask still at 0.40
+        let yes = book("yes-token", dec!(0.39), dec!(0.40));
+        let evaluate = |no: &OrderBook| {
+            detector.evaluate_at(
+                &market,
+                dec!(110000),
+                dec!(0.4),
+                MarketBooks::new(&yes).with_no(Some(no)),
+                now,
+            )
+        };
+
+        // The NO book mirrors it: the whole market is lagging
+        let lagging = evaluate(&book("no-token", dec!(0.60), dec!(0.61))).unwrap();
+        assert_eq!(lagging.side, Side::Yes);
+
+        // The NO ask has collapsed: the market moved, the YES book did not
+        let repriced = book("no-token", dec!(0.09), dec!(0.10));
+        assert_eq!(
+            evaluate(&repriced).unwrap_err(),
+            NoLagReason::ComplementRepriced {
+                yes_ask: dec!(0.40),
+                implied_yes: dec!(0.90),
+            }
+        );
+
+        // Within the tolerance the YES book is trusted
+        assert!(evaluate(&book("no-token", dec!(0.56), dec!(0.58))).is_ok());
+
+        // A crossed NO book vouches for nothing either way
+        assert!(evaluate(&book("no-token", dec!(0.20), dec!(0.10))).is_ok());
+
+        // Spot well down: a NO lag, undone by a NO bid already bid up
+        let yes = book("yes-token", dec!(0.59), dec!(0.60));
+        let evaluate = |no: &OrderBook| {
+            detector.evaluate_at(
+                &market,
+                dec!(90000),
+                dec!(0.4),
+                MarketBooks::new(&yes).with_no(Some(no)),
+                now,
+            )
+        };
+        assert_eq!(
+            evaluate(&book("no-token", dec!(0.40), dec!(0.41)))
+                .unwrap()
+                .side,
+            Side::No
+        );
+        assert_eq!(
+            evaluate(&book("no-token", dec!(0.90), dec!(0.91)))
+                .unwrap_err()
+                .code(),
+            "complement_repriced"
+        );
+    }
 }
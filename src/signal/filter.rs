@@ -3,7 +3,12 @@
 use super::Signal;
 use chrono::Duration;
 use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Default widest spread worth entering against
+pub const DEFAULT_MAX_ENTRY_SPREAD: Decimal = dec!(0.05);
 
 /// Result of applying filters to a signal
 #[derive(Debug, Clone)]
@@ -15,27 +20,134 @@ pub enum FilterResult {
 }
 
 /// Reason for signal rejection
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Each variant holds the observed value and the threshold it failed.
+/// Serializes as its [`code`](Self::code) under `code` beside them, e.g.
+/// `{"code":"spread_too_wide","spread":"0.08","max":"0.05"}`. Codes are
+/// stable: a variant is never renamed, only added.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum RejectReason {
-    /// Edge below minimum threshold
-    EdgeTooSmall(Decimal),
-    /// Edge above maximum threshold (likely stale data)
-    EdgeTooLarge(Decimal),
+    /// Entry edge below the minimum
+    EdgeTooSmall { edge: Decimal, min: Decimal },
+    /// Edge above the maximum (likely stale data)
+    EdgeTooLarge { edge: Decimal, max: Decimal },
     /// Insufficient liquidity at target price
-    InsufficientLiquidity(Decimal),
+    InsufficientLiquidity { available: Decimal, min: Decimal },
+    /// Bid-ask spread too wide to get back out
+    SpreadTooWide { spread: Decimal, max: Decimal },
     /// Too close to market expiry
-    TooCloseToExpiry(Duration),
+    TooCloseToExpiry { secs_to_close: i64, min_secs: i64 },
     /// Volatility estimate out of reasonable range
-    VolatilityOutOfRange(Decimal),
+    VolatilityOutOfRange {
+        volatility: Decimal,
+        min: Decimal,
+        max: Decimal,
+    },
     /// Maximum concurrent positions reached
-    MaxPositionsReached,
+    MaxPositionsReached { open: usize, max: usize },
+    /// Spot has given back too much of the move behind the signal
+    MomentumReverting { retrace: Decimal, max: Decimal },
+    /// Too few venues show the move
+    VenuesUnconfirmed { agreeing: usize, required: usize },
+    /// Venue prices disagree by more than the limit, in percent
+    VenuesDiverged {
+        divergence_pct: Decimal,
+        max_pct: Decimal,
+    },
+    /// The GBM and linear models disagree by more than the limit
+    ModelDisagreement { gap: Decimal, max: Decimal },
+}
+
+impl RejectReason {
+    /// Every code, in declaration order
+    pub const CODES: [&'static str; 11] = [
+        "edge_too_small",
+        "edge_too_large",
+        "insufficient_liquidity",
+        "spread_too_wide",
+        "too_close_to_expiry",
+        "volatility_out_of_range",
+        "max_positions_reached",
+        "momentum_reverting",
+        "venues_unconfirmed",
+        "venues_diverged",
+        "model_disagreement",
+    ];
+
+    /// Stable machine-readable code, for journals, tapes and metrics
+    pub fn code(&self) -> &'static str {
+        match self {
+            RejectReason::EdgeTooSmall { .. } => "edge_too_small",
+            RejectReason::EdgeTooLarge { .. } => "edge_too_large",
+            RejectReason::InsufficientLiquidity { .. } => "insufficient_liquidity",
+            RejectReason::SpreadTooWide { .. } => "spread_too_wide",
+            RejectReason::TooCloseToExpiry { .. } => "too_close_to_expiry",
+            RejectReason::VolatilityOutOfRange { .. } => "volatility_out_of_range",
+            RejectReason::MaxPositionsReached { .. } => "max_positions_reached",
+            RejectReason::MomentumReverting { .. } => "momentum_reverting",
+            RejectReason::VenuesUnconfirmed { .. } => "venues_unconfirmed",
+            RejectReason::VenuesDiverged { .. } => "venues_diverged",
+            RejectReason::ModelDisagreement { .. } => "model_disagreement",
+        }
+    }
+
+    /// The observed value and threshold as a JSON object
+    pub fn context(&self) -> serde_json::Value {
+        super::codes::context_of(self)
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            RejectReason::EdgeTooSmall { edge, min } => write!(f, "edge {} < {}", edge, min),
+            RejectReason::EdgeTooLarge { edge, max } => write!(f, "edge {} > {}", edge, max),
+            RejectReason::InsufficientLiquidity { available, min } => {
+                write!(f, "{} available < {}", available, min)
+            }
+            RejectReason::SpreadTooWide { spread, max } => {
+                write!(f, "spread {} > {}", spread, max)
+            }
+            RejectReason::TooCloseToExpiry {
+                secs_to_close,
+                min_secs,
+            } => write!(f, "{}s to close < {}s", secs_to_close, min_secs),
+            RejectReason::VolatilityOutOfRange {
+                volatility,
+                min,
+                max,
+            } => write!(f, "{} outside [{}, {}]", volatility.round_dp(4), min, max),
+            RejectReason::MaxPositionsReached { open, max } => {
+                write!(f, "{} open >= {}", open, max)
+            }
+            RejectReason::MomentumReverting { retrace, max } => {
+                write!(f, "retrace {} > {}", retrace, max)
+            }
+            RejectReason::VenuesUnconfirmed { agreeing, required } => {
+                write!(f, "{} venues agree < {}", agreeing, required)
+            }
+            RejectReason::VenuesDiverged {
+                divergence_pct,
+                max_pct,
+            } => write!(f, "divergence {}% > {}%", divergence_pct, max_pct),
+            RejectReason::ModelDisagreement { gap, max } => {
+                write!(f, "gap {} > {}", gap.round_dp(4), max)
+            }
+        }
+    }
 }
 
 /// Configuration for signal filters
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct FilterConfig {
-    /// Minimum adjusted edge threshold
+    /// Minimum entry edge threshold
     pub min_edge: Decimal,
+    /// Apply `min_edge` to the round-trip edge instead of the adjusted edge
+    pub use_round_trip_edge: bool,
     /// Maximum adjusted edge threshold
     pub max_edge: Decimal,
     /// Minimum time to expiry
@@ -44,10 +156,22 @@ pub struct FilterConfig {
     pub max_time_to_expiry: Duration,
     /// Minimum order book liquidity
     pub min_liquidity: Decimal,
+    /// Maximum bid-ask spread on the traded book
+    pub max_spread: Decimal,
     /// Minimum volatility (annualized)
     pub min_volatility: Decimal,
     /// Maximum volatility (annualized)
     pub max_volatility: Decimal,
+    /// Largest fraction of the spot move that may have reverted
+    pub max_retrace: Decimal,
+    /// Venues that must show the move; 1 or less skips the check
+    pub require_venues: usize,
+    /// Widest spread between venue prices, in percent
+    pub max_venue_divergence_pct: Decimal,
+    /// Widest gap between the GBM and linear YES estimates
+    pub max_model_disagreement: Decimal,
+    /// Reject past that gap; otherwise the estimates are only recorded
+    pub suppress_model_disagreement: bool,
 }
 
 /// Signal filter chain
@@ -71,35 +195,222 @@ impl SignalFilter {
         volatility: Decimal,
         time_to_expiry: Duration,
     ) -> FilterResult {
-        // Check position limits
-        if current_positions >= max_positions {
-            return FilterResult::Reject(RejectReason::MaxPositionsReached);
-        }
+        self.checks(
+            signal,
+            current_positions,
+            max_positions,
+            available_liquidity,
+            volatility,
+            time_to_expiry,
+        )
+        .into_iter()
+        .find_map(|check| check.reject)
+        .map_or(FilterResult::Pass, FilterResult::Reject)
+    }
 
-        // Check edge thresholds
-        if signal.adjusted_edge < self.config.min_edge {
-            return FilterResult::Reject(RejectReason::EdgeTooSmall(signal.adjusted_edge));
-        }
-        if signal.adjusted_edge > self.config.max_edge {
-            return FilterResult::Reject(RejectReason::EdgeTooLarge(signal.adjusted_edge));
-        }
+    /// Evaluate every filter, in the order [`apply`](Self::apply) checks them
+    pub fn checks(
+        &self,
+        signal: &Signal,
+        current_positions: usize,
+        max_positions: usize,
+        available_liquidity: Decimal,
+        volatility: Decimal,
+        time_to_expiry: Duration,
+    ) -> Vec<FilterCheck> {
+        let config = &self.config;
+        let edge = signal.adjusted_edge;
+        let entry_edge = signal.entry_edge(config.use_round_trip_edge);
+        let entry_label = if config.use_round_trip_edge {
+            "round-trip edge"
+        } else {
+            "edge"
+        };
+        vec![
+            FilterCheck {
+                name: "max_positions",
+                detail: format!("{} open < {}", current_positions, max_positions),
+                reject: (current_positions >= max_positions).then_some(
+                    RejectReason::MaxPositionsReached {
+                        open: current_positions,
+                        max: max_positions,
+                    },
+                ),
+            },
+            FilterCheck {
+                name: "min_edge",
+                detail: format!("{} {} >= {}", entry_label, entry_edge, config.min_edge),
+                reject: (entry_edge < config.min_edge).then_some(RejectReason::EdgeTooSmall {
+                    edge: entry_edge,
+                    min: config.min_edge,
+                }),
+            },
+            FilterCheck {
+                name: "max_edge",
+                detail: format!("edge {} <= {}", edge, config.max_edge),
+                reject: (edge > config.max_edge).then_some(RejectReason::EdgeTooLarge {
+                    edge,
+                    max: config.max_edge,
+                }),
+            },
+            FilterCheck {
+                name: "time_to_expiry",
+                detail: format!(
+                    "{}s >= {}s",
+                    time_to_expiry.num_seconds(),
+                    config.min_time_to_expiry.num_seconds()
+                ),
+                reject: (time_to_expiry < config.min_time_to_expiry).then_some(
+                    RejectReason::TooCloseToExpiry {
+                        secs_to_close: time_to_expiry.num_seconds(),
+                        min_secs: config.min_time_to_expiry.num_seconds(),
+                    },
+                ),
+            },
+            FilterCheck {
+                name: "liquidity",
+                detail: format!("{} >= {}", available_liquidity, config.min_liquidity),
+                reject: (available_liquidity < config.min_liquidity).then_some(
+                    RejectReason::InsufficientLiquidity {
+                        available: available_liquidity,
+                        min: config.min_liquidity,
+                    },
+                ),
+            },
+            FilterCheck {
+                name: "max_spread",
+                detail: format!("spread {} <= {}", signal.spread, config.max_spread),
+                reject: (signal.spread > config.max_spread).then_some(
+                    RejectReason::SpreadTooWide {
+                        spread: signal.spread,
+                        max: config.max_spread,
+                    },
+                ),
+            },
+            FilterCheck {
+                name: "volatility",
+                detail: format!(
+                    "{} in [{}, {}]",
+                    volatility.round_dp(4),
+                    config.min_volatility,
+                    config.max_volatility
+                ),
+                reject: (volatility < config.min_volatility || volatility > config.max_volatility)
+                    .then_some(RejectReason::VolatilityOutOfRange {
+                        volatility,
+                        min: config.min_volatility,
+                        max: config.max_volatility,
+                    }),
+            },
+            FilterCheck {
+                name: "momentum_reversion",
+                detail: match signal.retrace {
+                    Some(retrace) => format!("retrace {} <= {}", retrace, config.max_retrace),
+                    None => "no momentum window".to_string(),
+                },
+                reject: signal
+                    .retrace
+                    .filter(|r| *r > config.max_retrace)
+                    .map(|retrace| RejectReason::MomentumReverting {
+                        retrace,
+                        max: config.max_retrace,
+                    }),
+            },
+            venue_check(signal, config),
+            model_check(signal, config),
+        ]
+    }
+}
 
-        // Check time to expiry
-        if time_to_expiry < self.config.min_time_to_expiry {
-            return FilterResult::Reject(RejectReason::TooCloseToExpiry(time_to_expiry));
-        }
+/// Whether enough venues show the move and agree on the price
+fn venue_check(signal: &Signal, config: &FilterConfig) -> FilterCheck {
+    let name = "venue_confirmation";
+    if config.require_venues <= 1 {
+        return FilterCheck {
+            name,
+            detail: "single venue".to_string(),
+            reject: None,
+        };
+    }
+    let agreeing = signal.venues_agreeing.unwrap_or(0);
+    if agreeing < config.require_venues {
+        return FilterCheck {
+            name,
+            detail: format!(
+                "{} venues agree < {} required",
+                agreeing, config.require_venues
+            ),
+            reject: Some(RejectReason::VenuesUnconfirmed {
+                agreeing,
+                required: config.require_venues,
+            }),
+        };
+    }
+    let divergence = signal.venue_divergence_pct.unwrap_or_default();
+    FilterCheck {
+        name,
+        detail: format!(
+            "{} venues agree, divergence {}% <= {}%",
+            agreeing, divergence, config.max_venue_divergence_pct
+        ),
+        reject: (divergence > config.max_venue_divergence_pct).then_some(
+            RejectReason::VenuesDiverged {
+                divergence_pct: divergence,
+                max_pct: config.max_venue_divergence_pct,
+            },
+        ),
+    }
+}
 
-        // Check liquidity
-        if available_liquidity < self.config.min_liquidity {
-            return FilterResult::Reject(RejectReason::InsufficientLiquidity(available_liquidity));
-        }
+/// Whether the two fair value models roughly agree
+fn model_check(signal: &Signal, config: &FilterConfig) -> FilterCheck {
+    let name = "model_agreement";
+    let Some(models) = signal.models else {
+        return FilterCheck {
+            name,
+            detail: "no cross-check".to_string(),
+            reject: None,
+        };
+    };
+    let disagreement = models.disagreement();
+    FilterCheck {
+        name,
+        detail: format!(
+            "gbm {} vs linear {}, gap {} <= {}{}",
+            models.gbm.round_dp(4),
+            models.linear.round_dp(4),
+            disagreement.round_dp(4),
+            config.max_model_disagreement,
+            if config.suppress_model_disagreement {
+                ""
+            } else {
+                " (annotate only)"
+            }
+        ),
+        reject: (config.suppress_model_disagreement
+            && disagreement > config.max_model_disagreement)
+            .then_some(RejectReason::ModelDisagreement {
+                gap: disagreement,
+                max: config.max_model_disagreement,
+            }),
+    }
+}
 
-        // Check volatility
-        if volatility < self.config.min_volatility || volatility > self.config.max_volatility {
-            return FilterResult::Reject(RejectReason::VolatilityOutOfRange(volatility));
-        }
+/// Outcome of one filter
+#[derive(Debug, Clone)]
+pub struct FilterCheck {
+    /// Filter name
+    pub name: &'static str,
+    /// The comparison that was made
+    pub detail: String,
+    /// Why the signal was rejected, if it was
+    pub reject: Option<RejectReason>,
+}
 
-        FilterResult::Pass
+impl FilterCheck {
+    /// Whether the signal passed this filter
+    pub fn passed(&self) -> bool {
+        self.reject.is_none()
     }
 }
 
@@ -109,28 +420,37 @@ mod tests {
     use crate::market::Market;
     use crate::signal::{Side, SignalReason};
     use chrono::Utc;
-    use rust_decimal_macros::dec;
 
     fn default_filter_config() -> FilterConfig {
         FilterConfig {
             min_edge: dec!(0.005),
+            use_round_trip_edge: false,
             max_edge: dec!(0.15),
             min_time_to_expiry: Duration::minutes(1),
             max_time_to_expiry: Duration::minutes(14),
             min_liquidity: dec!(100),
+            max_spread: dec!(0.05),
             min_volatility: dec!(0.1),
             max_volatility: dec!(1.5),
+            max_retrace: dec!(0.3),
+            require_venues: 1,
+            max_venue_divergence_pct: dec!(0.1),
+            max_model_disagreement: dec!(0.2),
+            suppress_model_disagreement: true,
         }
     }
 
     fn create_test_signal(adjusted_edge: Decimal) -> Signal {
         let market = Market {
             condition_id: "test-cond".to_string(),
+            asset: "BTC".to_string(),
             yes_token_id: "yes-token".to_string(),
             no_token_id: "no-token".to_string(),
             open_price: dec!(100000),
             open_time: Utc::now() - Duration::minutes(5),
             close_time: Utc::now() + Duration::minutes(10),
+            group_id: None,
+            orientation: Default::default(),
         };
 
         Signal::new(
@@ -186,7 +506,7 @@ mod tests {
 
         assert!(matches!(
             result,
-            FilterResult::Reject(RejectReason::MaxPositionsReached)
+            FilterResult::Reject(RejectReason::MaxPositionsReached { .. })
         ));
     }
 
@@ -200,7 +520,7 @@ mod tests {
 
         assert!(matches!(
             result,
-            FilterResult::Reject(RejectReason::EdgeTooSmall(_))
+            FilterResult::Reject(RejectReason::EdgeTooSmall { .. })
         ));
     }
 
@@ -214,7 +534,7 @@ mod tests {
 
         assert!(matches!(
             result,
-            FilterResult::Reject(RejectReason::EdgeTooLarge(_))
+            FilterResult::Reject(RejectReason::EdgeTooLarge { .. })
         ));
     }
 
@@ -235,7 +555,7 @@ mod tests {
 
         assert!(matches!(
             result,
-            FilterResult::Reject(RejectReason::TooCloseToExpiry(_))
+            FilterResult::Reject(RejectReason::TooCloseToExpiry { .. })
         ));
     }
 
@@ -256,7 +576,7 @@ mod tests {
 
         assert!(matches!(
             result,
-            FilterResult::Reject(RejectReason::InsufficientLiquidity(_))
+            FilterResult::Reject(RejectReason::InsufficientLiquidity { .. })
         ));
     }
 
@@ -277,7 +597,7 @@ mod tests {
 
         assert!(matches!(
             result,
-            FilterResult::Reject(RejectReason::VolatilityOutOfRange(_))
+            FilterResult::Reject(RejectReason::VolatilityOutOfRange { .. })
         ));
     }
 
@@ -298,15 +618,29 @@ mod tests {
 
         assert!(matches!(
             result,
-            FilterResult::Reject(RejectReason::VolatilityOutOfRange(_))
+            FilterResult::Reject(RejectReason::VolatilityOutOfRange { .. })
         ));
     }
 
     #[test]
-    fn test_reject_reason_display_edge_too_small() {
-        let reason = RejectReason::EdgeTooSmall(dec!(0.001));
-        let serialized = serde_json::to_string(&reason).unwrap();
-        assert!(serialized.contains("EdgeTooSmall"));
+    fn test_reject_reason_serializes_code_and_context() {
+        let reason = RejectReason::EdgeTooSmall {
+            edge: dec!(0.001),
+            min: dec!(0.005),
+        };
+        assert_eq!(
+            serde_json::to_value(&reason).unwrap(),
+            serde_json::json!({"code": "edge_too_small", "edge": "0.001", "min": "0.005"})
+        );
+        assert_eq!(
+            reason.context(),
+            serde_json::json!({"edge": "0.001", "min": "0.005"})
+        );
+        assert_eq!(reason.to_string(), "edge_too_small: edge 0.001 < 0.005");
+        let parsed: RejectReason =
+            serde_json::from_str(r#"{"code":"edge_too_small","edge":"0.001","min":"0.005"}"#)
+                .unwrap();
+        assert_eq!(parsed, reason);
     }
 
     #[test]
@@ -316,4 +650,164 @@ mod tests {
         assert_eq!(config.min_edge, cloned.min_edge);
         assert_eq!(config.max_edge, cloned.max_edge);
     }
+
+    #[test]
+    fn test_filter_reject_spread_too_wide() {
+        let filter = SignalFilter::new(default_filter_config());
+
+        let tight = create_test_signal(dec!(0.02)).with_spread(dec!(0.01));
+        let result = filter.apply(&tight, 0, 5, dec!(500), dec!(0.4), Duration::minutes(10));
+        assert!(matches!(result, FilterResult::Pass));
+
+        let wide = create_test_signal(dec!(0.02)).with_spread(dec!(0.08));
+        let result = filter.apply(&wide, 0, 5, dec!(500), dec!(0.4), Duration::minutes(10));
+        assert!(matches!(
+            result,
+            FilterResult::Reject(RejectReason::SpreadTooWide { spread, .. }) if spread == dec!(0.08)
+        ));
+
+        // No bid: the spread is the whole ask
+        let one_sided = create_test_signal(dec!(0.02)).with_spread(dec!(0.50));
+        let result = filter.apply(
+            &one_sided,
+            0,
+            5,
+            dec!(500),
+            dec!(0.4),
+            Duration::minutes(10),
+        );
+        assert!(matches!(
+            result,
+            FilterResult::Reject(RejectReason::SpreadTooWide { .. })
+        ));
+    }
+
+    #[test]
+    fn test_filter_reject_momentum_reverting() {
+        use crate::signal::MomentumDetector;
+
+        // Spot rallies 100 -> 140 and is back at 120 when the signal fires
+        let start = Utc::now() - Duration::seconds(30);
+        let mut momentum = MomentumDetector::default();
+        for (i, price) in [100, 120, 140, 130, 120].into_iter().enumerate() {
+            momentum.update(
+                start + Duration::seconds(i as i64 * 5),
+                Decimal::from(price),
+            );
+        }
+        let filter = SignalFilter::new(default_filter_config());
+
+        let reverting =
+            create_test_signal(dec!(0.02)).with_momentum(&momentum.signal(Side::Yes).unwrap());
+        assert_eq!(reverting.retrace, Some(dec!(0.5)));
+        let result = filter.apply(
+            &reverting,
+            0,
+            5,
+            dec!(500),
+            dec!(0.4),
+            Duration::minutes(10),
+        );
+        assert!(matches!(
+            result,
+            FilterResult::Reject(RejectReason::MomentumReverting { retrace, .. }) if retrace == dec!(0.5)
+        ));
+
+        // Without a momentum window the check has nothing to go on
+        let unknown = create_test_signal(dec!(0.02));
+        let result = filter.apply(&unknown, 0, 5, dec!(500), dec!(0.4), Duration::minutes(10));
+        assert!(matches!(result, FilterResult::Pass));
+        assert_eq!(
+            RejectReason::MomentumReverting {
+                retrace: dec!(0.5),
+                max: dec!(0.3)
+            }
+            .code(),
+            "momentum_reverting"
+        );
+    }
+
+    #[test]
+    fn test_filter_venue_confirmation() {
+        use crate::signal::{MomentumDetector, VenueMove};
+
+        let mut momentum = MomentumDetector::default();
+        let start = Utc::now() - Duration::seconds(10);
+        momentum.update(start, dec!(100000));
+        momentum.update(start + Duration::seconds(5), dec!(100150));
+        let venues = |coinbase: Decimal| {
+            let binance = VenueMove {
+                venue: "binance".to_string(),
+                price: dec!(100150),
+                move_pct: dec!(0.15),
+            };
+            let coinbase = VenueMove {
+                venue: "coinbase".to_string(),
+                price: coinbase,
+                move_pct: (coinbase - dec!(100000)) / dec!(1000),
+            };
+            momentum
+                .signal(Side::Yes)
+                .unwrap()
+                .with_venues(vec![binance, coinbase])
+        };
+        let apply = |config: FilterConfig, coinbase: Decimal| {
+            let signal = create_test_signal(dec!(0.02)).with_momentum(&venues(coinbase));
+            SignalFilter::new(config).apply(
+                &signal,
+                0,
+                5,
+                dec!(500),
+                dec!(0.4),
+                Duration::minutes(10),
+            )
+        };
+        let two_venues = FilterConfig {
+            require_venues: 2,
+            ..default_filter_config()
+        };
+
+        // Off by default: a Binance-only move still passes
+        assert!(matches!(
+            apply(default_filter_config(), dec!(99990)),
+            FilterResult::Pass
+        ));
+        assert!(matches!(
+            apply(two_venues.clone(), dec!(100120)),
+            FilterResult::Pass
+        ));
+        assert!(matches!(
+            apply(two_venues.clone(), dec!(99990)),
+            FilterResult::Reject(RejectReason::VenuesUnconfirmed { agreeing: 1, .. })
+        ));
+        assert!(matches!(
+            apply(two_venues, dec!(100010)),
+            FilterResult::Reject(RejectReason::VenuesDiverged { .. })
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_edge_gates_entry_when_enabled() {
+        // 2% adjusted edge, 4 cent spread: 0% after the exit
+        let signal = create_test_signal(dec!(0.02)).with_spread(dec!(0.04));
+        assert_eq!(signal.round_trip_edge, dec!(0));
+
+        let raw = SignalFilter::new(default_filter_config());
+        let result = raw.apply(&signal, 0, 5, dec!(500), dec!(0.4), Duration::minutes(10));
+        assert!(matches!(result, FilterResult::Pass));
+
+        let round_trip = SignalFilter::new(FilterConfig {
+            use_round_trip_edge: true,
+            ..default_filter_config()
+        });
+        let result = round_trip.apply(&signal, 0, 5, dec!(500), dec!(0.4), Duration::minutes(10));
+        assert!(matches!(
+            result,
+            FilterResult::Reject(RejectReason::EdgeTooSmall { edge, .. }) if edge == dec!(0)
+        ));
+
+        let tight = create_test_signal(dec!(0.02)).with_spread(dec!(0.01));
+        let result = round_trip.apply(&tight, 0, 5, dec!(500), dec!(0.4), Duration::minutes(10));
+        assert!(matches!(result, FilterResult::Pass));
+    }
 }
@@ -2,10 +2,40 @@
 //!
 //! Detects tradeable pricing discrepancies
 
+mod codes;
+mod consistency;
 mod detector;
 mod filter;
+mod momentum;
+mod outcome;
+mod sanity;
+mod shock;
 mod types;
 
-pub use detector::SignalDetector;
-pub use filter::{FilterResult, RejectReason, SignalFilter};
-pub use types::{Side, Signal, SignalReason};
+pub use codes::reason_code;
+pub use consistency::{
+    ConsistencyCheck, ConsistencyConfig, ConsistencyMonitor, SellSpreadSignal,
+    DEFAULT_MAX_MID_DEVIATION, DEFAULT_MIN_SELL_EDGE,
+};
+pub use detector::{SignalDetector, DEFAULT_COMPLEMENT_TOLERANCE, DEFAULT_NEAR_BOUND_MIN_EDGE};
+pub use filter::{
+    FilterCheck, FilterConfig, FilterResult, RejectReason, SignalFilter, DEFAULT_MAX_ENTRY_SPREAD,
+};
+pub use momentum::{
+    MomentumDetector, MomentumSignal, MomentumState, VenueMove, DEFAULT_MAX_MOMENTUM_RETRACE,
+    DEFAULT_MAX_VENUE_DIVERGENCE_PCT, DEFAULT_MOMENTUM_MAX_SAMPLES, DEFAULT_MOMENTUM_WINDOW_SECS,
+    DEFAULT_REQUIRE_VENUES, PRIMARY_VENUE,
+};
+pub use outcome::{
+    Mark, OutcomeSummary, SignalOutcome, SignalOutcomeTracker, CHECKPOINTS_SECS, MARK_INTERVAL_SECS,
+};
+pub use sanity::{
+    DisagreementMode, ModelEstimates, ModelSanityConfig, ModelSanityMonitor,
+    PersistentDisagreement, DEFAULT_DISAGREEMENT_ALERT_AFTER, DEFAULT_MAX_MODEL_DISAGREEMENT,
+};
+pub use shock::{
+    BookShock, BookShockConfig, BookShockDetector, DEFAULT_SHOCK_CONFIRM_UPDATES,
+    DEFAULT_SHOCK_COOLDOWN_SECS, DEFAULT_SHOCK_DEPTH_DROP, DEFAULT_SHOCK_IMBALANCE_SWING,
+    DEFAULT_SHOCK_WINDOW_MS, SHOCK_DEPTH_LEVELS,
+};
+pub use types::{NoLagReason, Side, Signal, SignalReason};
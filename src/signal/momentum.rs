@@ -0,0 +1,722 @@
+//! Spot momentum and exhaustion
+//!
+//! The lag trade only pays while the spot move that opened the edge is
+//! still in place. Entering after spot has already given back much of its
+//! move buys odds that are about to catch up in the other direction.
+//!
+//! A wick printed by one exchange alone, such as a local liquidation
+//! cascade, is ignored by the crowd. With prices from more venues the move
+//! can be required on several of them, agreeing within a divergence limit.
+//!
+//! A move is read for the side it would favour, from the first price in
+//! the window: up for YES, down for NO. There is nothing to read until a
+//! second price arrives.
+//!
+//! A tick storm can put tens of thousands of prices in one window. Past
+//! `max_samples` the middle of the window is thinned, always keeping the
+//! first and newest price and the window's high and low, so memory and the
+//! cost of a read stay bounded while peak and retrace stay exact.
+//! Velocity is a least-squares slope over the prices held, which thinning
+//! leaves close to the unthinned value.
+//!
+//! ```
+//! use poly_hft::signal::{MomentumDetector, Side, DEFAULT_MAX_MOMENTUM_RETRACE};
+//! use chrono::{DateTime, Duration};
+//! use rust_decimal_macros::dec;
+//!
+//! let t0 = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+//! let mut momentum = MomentumDetector::default();
+//! momentum.update(t0, dec!(100000));
+//! assert!(momentum.signal(Side::Yes).is_none());
+//!
+//! // A 1% rally, then three tenths of it given back
+//! momentum.update(t0 + Duration::seconds(20), dec!(101000));
+//! momentum.update(t0 + Duration::seconds(30), dec!(100700));
+//! let up = momentum.signal(Side::Yes).unwrap();
+//! assert_eq!((up.peak_price, up.max_excursion), (dec!(101000), dec!(1000)));
+//! assert_eq!(up.retrace, dec!(0.3));
+//! assert!(!up.is_reverting(DEFAULT_MAX_MOMENTUM_RETRACE));
+//!
+//! // For NO the same window never moved its way
+//! assert_eq!(momentum.signal(Side::No).unwrap().max_excursion, dec!(0));
+//!
+//! // A little more given back and the move is exhausted
+//! momentum.update(t0 + Duration::seconds(35), dec!(100600));
+//! assert!(momentum.signal(Side::Yes).unwrap().is_reverting(DEFAULT_MAX_MOMENTUM_RETRACE));
+//!
+//! // Another venue above the strike agrees with the trade; only the
+//! // primary feed's prices move the window
+//! momentum.update_venue("coinbase", t0 + Duration::seconds(35), dec!(100650));
+//! let up = momentum
+//!     .signal(Side::Yes)
+//!     .unwrap()
+//!     .with_venues(momentum.venue_moves(dec!(100000)));
+//! assert_eq!(up.agreeing_venues(), 2);
+//! assert_eq!(up.venue_divergence_pct(), Some(dec!(0.0497)));
+//! ```
+
+use super::Side;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Default lookback for the momentum move, in seconds
+pub const DEFAULT_MOMENTUM_WINDOW_SECS: u64 = 60;
+
+/// Default largest fraction of the move that may be given back before entry
+pub const DEFAULT_MAX_MOMENTUM_RETRACE: Decimal = dec!(0.30);
+
+/// Default venues that must agree on a move; 1 keeps single-feed behavior
+pub const DEFAULT_REQUIRE_VENUES: usize = 1;
+
+/// Default widest spread between venue prices, in percent, still agreeing
+pub const DEFAULT_MAX_VENUE_DIVERGENCE_PCT: Decimal = dec!(0.1);
+
+/// Default cap on primary prices held per window
+pub const DEFAULT_MOMENTUM_MAX_SAMPLES: usize = 1024;
+
+/// Venue of the prices passed to [`MomentumDetector::update`]
+pub const PRIMARY_VENUE: &str = "binance";
+
+/// One venue's latest price against the market's strike
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueMove {
+    pub venue: String,
+    pub price: Decimal,
+    /// Move from the strike in percent, negative below it
+    pub move_pct: Decimal,
+}
+
+/// Spot move over the momentum window, in the direction of a trade
+#[derive(Debug, Clone, PartialEq)]
+pub struct MomentumSignal {
+    /// Direction the move has to favour
+    pub side: Side,
+    /// First price in the window
+    pub start_price: Decimal,
+    /// Highest price in the window for YES, lowest for NO
+    pub peak_price: Decimal,
+    /// Latest price
+    pub current_price: Decimal,
+    /// Distance from start to peak in the favoured direction, never negative
+    pub max_excursion: Decimal,
+    /// Fraction of the excursion given back since the peak, 0 with no move
+    pub retrace: Decimal,
+    /// Least-squares slope of the prices in the window, per second
+    pub velocity: Decimal,
+    /// Every venue's move from the strike, empty unless attached
+    pub venues: Vec<VenueMove>,
+}
+
+impl MomentumSignal {
+    /// Whether more than `max_retrace` of the move has been given back
+    pub fn is_reverting(&self, max_retrace: Decimal) -> bool {
+        self.retrace > max_retrace
+    }
+
+    /// Attach per-venue moves, see [`MomentumDetector::venue_moves`]
+    pub fn with_venues(mut self, venues: Vec<VenueMove>) -> Self {
+        self.venues = venues;
+        self
+    }
+
+    /// Venues on the trade's side of the strike
+    pub fn agreeing_venues(&self) -> usize {
+        self.venues
+            .iter()
+            .filter(|v| match self.side {
+                Side::Yes => v.move_pct > Decimal::ZERO,
+                Side::No => v.move_pct < Decimal::ZERO,
+            })
+            .count()
+    }
+
+    /// Spread between the highest and lowest venue price, in percent of
+    /// the lowest; `None` with fewer than two venues
+    pub fn venue_divergence_pct(&self) -> Option<Decimal> {
+        if self.venues.len() < 2 {
+            return None;
+        }
+        let prices = || self.venues.iter().map(|v| v.price);
+        let high = prices().max()?;
+        let low = prices().min()?;
+        if low.is_zero() {
+            return None;
+        }
+        Some(((high - low) / low * dec!(100)).round_dp(4))
+    }
+}
+
+/// Prices held by a [`MomentumDetector`], for carrying it across a restart
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MomentumState {
+    /// Primary prices in the window, oldest first
+    pub ticks: Vec<(DateTime<Utc>, Decimal)>,
+    /// Latest price per venue
+    pub venues: BTreeMap<String, (DateTime<Utc>, Decimal)>,
+}
+
+/// Rolling window of spot prices
+#[derive(Debug, Clone)]
+pub struct MomentumDetector {
+    window: Duration,
+    /// Most prices held before the middle of the window is thinned
+    max_samples: usize,
+    /// Minimum gap between held prices, set by thinning
+    spacing: Duration,
+    /// Highest and lowest price held, meaningless while empty
+    high: Decimal,
+    low: Decimal,
+    ticks: VecDeque<(DateTime<Utc>, Decimal)>,
+    /// Latest price per venue, including the primary
+    venues: BTreeMap<String, (DateTime<Utc>, Decimal)>,
+    /// Prices that arrived behind a newer one and were put in place
+    late: u64,
+}
+
+impl MomentumDetector {
+    /// Track moves over `window`
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            max_samples: DEFAULT_MOMENTUM_MAX_SAMPLES,
+            spacing: Duration::zero(),
+            high: Decimal::ZERO,
+            low: Decimal::ZERO,
+            ticks: VecDeque::new(),
+            venues: BTreeMap::new(),
+            late: 0,
+        }
+    }
+
+    /// Cap prices held (at least 8, so a pass always has prices to drop
+    /// besides both ends, the high and the low)
+    pub fn with_max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = max_samples.max(8);
+        self
+    }
+
+    /// Primary prices in the window
+    pub fn sample_count(&self) -> usize {
+        self.ticks.len()
+    }
+
+    /// Prices that arrived out of order and were inserted by timestamp
+    pub fn late_prices(&self) -> u64 {
+        self.late
+    }
+
+    /// Prices currently held
+    pub fn state(&self) -> MomentumState {
+        MomentumState {
+            ticks: self.ticks.iter().copied().collect(),
+            venues: self.venues.clone(),
+        }
+    }
+
+    /// Replace the prices held with `state`, keeping the window and cap
+    pub fn restore(&mut self, state: MomentumState) {
+        self.ticks = state.ticks.into();
+        self.venues = state.venues;
+        self.spacing = Duration::zero();
+        self.refresh_extremes();
+        while self.ticks.len() > self.max_samples {
+            self.thin();
+        }
+    }
+
+    /// Forget every price, e.g. across a gap in the data
+    pub fn clear(&mut self) {
+        self.restore(MomentumState::default());
+    }
+
+    /// Add a spot price, dropping prices older than the window and
+    /// thinning past the sample cap
+    ///
+    /// A price older than the newest one, as after a clock step, is
+    /// inserted in timestamp order if it still falls inside the window, so
+    /// the window stays sorted and is only ever trimmed from the newest
+    /// price back.
+    pub fn update(&mut self, timestamp: DateTime<Utc>, price: Decimal) {
+        if self.insert(timestamp, price) {
+            self.trim();
+        }
+    }
+
+    /// Add spot prices in arrival order, e.g. a batch drained from the
+    /// feed at once
+    ///
+    /// The window ends where [`Self::update`] one price at a time would
+    /// leave it, but is trimmed once for the batch.
+    pub fn update_prices(&mut self, prices: &[(DateTime<Utc>, Decimal)]) {
+        let mut inserted = false;
+        for &(timestamp, price) in prices {
+            inserted |= self.insert(timestamp, price);
+        }
+        if inserted {
+            self.trim();
+        }
+    }
+
+    /// Put a price in timestamp order; false if it is older than the
+    /// window of the newest price
+    fn insert(&mut self, timestamp: DateTime<Utc>, price: Decimal) -> bool {
+        match self.ticks.back() {
+            Some((newest, _)) if timestamp < *newest => {
+                if timestamp < *newest - self.window {
+                    return false;
+                }
+                let at = self.ticks.partition_point(|(ts, _)| *ts <= timestamp);
+                self.ticks.insert(at, (timestamp, price));
+                self.late += 1;
+                self.note_extremes(price);
+            }
+            // Inside the thinned spacing a price replaces the newest one
+            // instead, so the newest price is always kept exactly, unless
+            // that one is the window's high or low
+            _ => {
+                let n = self.ticks.len();
+                if n >= 2
+                    && timestamp - self.ticks[n - 2].0 < self.spacing
+                    && self.ticks[n - 1].1 != self.high
+                    && self.ticks[n - 1].1 != self.low
+                {
+                    self.ticks[n - 1] = (timestamp, price);
+                } else {
+                    self.ticks.push_back((timestamp, price));
+                }
+                self.note_extremes(price);
+            }
+        }
+        true
+    }
+
+    /// Drop prices older than the window of the newest one, then thin
+    /// down to the sample cap
+    fn trim(&mut self) {
+        let Some(&(timestamp, price)) = self.ticks.back() else {
+            return;
+        };
+        self.update_venue(PRIMARY_VENUE, timestamp, price);
+        let cutoff = timestamp - self.window;
+        let mut stale = false;
+        while self.ticks.front().is_some_and(|(ts, _)| *ts < cutoff) {
+            if let Some((_, price)) = self.ticks.pop_front() {
+                stale |= price == self.high || price == self.low;
+            }
+        }
+        if stale {
+            self.refresh_extremes();
+        }
+        while self.ticks.len() > self.max_samples {
+            self.thin();
+        }
+    }
+
+    /// Drop every other price between the first and the newest, keeping
+    /// the highest and lowest, then space later prices at the resulting
+    /// average gap so the window stays uniformly sampled
+    fn thin(&mut self) {
+        let last = self.ticks.len() - 1;
+        let at = |extreme| self.ticks.iter().position(|(_, p)| *p == extreme);
+        let (high, low) = (at(self.high), at(self.low));
+        let mut index = 0;
+        self.ticks.retain(|_| {
+            let keep = index == 0 || index == last || index % 2 == 0;
+            let keep = keep || Some(index) == high || Some(index) == low;
+            index += 1;
+            keep
+        });
+        if let (Some((first, _)), Some((last, _))) = (self.ticks.front(), self.ticks.back()) {
+            let gaps = self.ticks.len() as i32 - 1;
+            self.spacing = self.spacing.max((*last - *first) / gaps);
+        }
+    }
+
+    /// Widen the high and low to a price just added
+    fn note_extremes(&mut self, price: Decimal) {
+        if self.ticks.len() == 1 {
+            (self.high, self.low) = (price, price);
+        } else {
+            self.high = self.high.max(price);
+            self.low = self.low.min(price);
+        }
+    }
+
+    /// Find the high and low again, after one of them left the window
+    fn refresh_extremes(&mut self) {
+        let prices = || self.ticks.iter().map(|(_, p)| *p);
+        self.high = prices().max().unwrap_or_default();
+        self.low = prices().min().unwrap_or_default();
+    }
+
+    /// Least-squares slope of the held prices against time, per second
+    fn velocity(&self) -> Decimal {
+        let Some(&(origin, _)) = self.ticks.front() else {
+            return Decimal::ZERO;
+        };
+        let n = Decimal::from(self.ticks.len());
+        let points = || {
+            self.ticks.iter().map(move |(ts, price)| {
+                (Decimal::new((*ts - origin).num_milliseconds(), 3), *price)
+            })
+        };
+        let mean_t = points().map(|(t, _)| t).sum::<Decimal>() / n;
+        let mean_p = points().map(|(_, p)| p).sum::<Decimal>() / n;
+        let (covariance, variance) =
+            points().fold((Decimal::ZERO, Decimal::ZERO), |(cov, var), (t, p)| {
+                let dt = t - mean_t;
+                (cov + dt * (p - mean_p), var + dt * dt)
+            });
+        if variance.is_zero() {
+            return Decimal::ZERO;
+        }
+        (covariance / variance).round_dp(6)
+    }
+
+    /// Record the latest price of another venue
+    ///
+    /// Only the primary venue's prices drive the window; other venues are
+    /// consulted for confirmation alone.
+    pub fn update_venue(&mut self, venue: &str, timestamp: DateTime<Utc>, price: Decimal) {
+        match self.venues.get_mut(venue) {
+            Some(latest) if timestamp < latest.0 => {}
+            Some(latest) => *latest = (timestamp, price),
+            None => {
+                self.venues.insert(venue.to_string(), (timestamp, price));
+            }
+        }
+    }
+
+    /// Each venue's move from `strike`, leaving out venues with no price
+    /// within the window of the latest primary tick
+    pub fn venue_moves(&self, strike: Decimal) -> Vec<VenueMove> {
+        let Some((now, _)) = self.ticks.back() else {
+            return vec![];
+        };
+        if strike.is_zero() {
+            return vec![];
+        }
+        let cutoff = *now - self.window;
+        self.venues
+            .iter()
+            .filter(|(_, (ts, _))| *ts >= cutoff)
+            .map(|(venue, (_, price))| VenueMove {
+                venue: venue.clone(),
+                price: *price,
+                move_pct: ((*price - strike) / strike * dec!(100)).round_dp(4),
+            })
+            .collect()
+    }
+
+    /// The window's move as seen by a trade on `side`; needs two prices
+    pub fn signal(&self, side: Side) -> Option<MomentumSignal> {
+        if self.ticks.len() < 2 {
+            return None;
+        }
+        let (_, start_price) = *self.ticks.front()?;
+        let (_, current_price) = *self.ticks.back()?;
+        let peak_price = match side {
+            Side::Yes => self.high,
+            Side::No => self.low,
+        };
+        let (max_excursion, given_back) = match side {
+            Side::Yes => (peak_price - start_price, peak_price - current_price),
+            Side::No => (start_price - peak_price, current_price - peak_price),
+        };
+        let retrace = if max_excursion > Decimal::ZERO {
+            (given_back / max_excursion).round_dp(4)
+        } else {
+            Decimal::ZERO
+        };
+        Some(MomentumSignal {
+            side,
+            start_price,
+            peak_price,
+            current_price,
+            max_excursion,
+            retrace,
+            velocity: self.velocity(),
+            venues: vec![],
+        })
+    }
+}
+
+impl Default for MomentumDetector {
+    fn default() -> Self {
+        Self::new(Duration::seconds(DEFAULT_MOMENTUM_WINDOW_SECS as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100 rallies to 140 over 20s, then falls back to 120
+    fn spike_and_retrace() -> MomentumDetector {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut detector = MomentumDetector::default();
+        for (i, price) in [100, 110, 125, 140, 135, 128, 120].into_iter().enumerate() {
+            detector.update(
+                start + Duration::seconds(i as i64 * 5),
+                Decimal::from(price),
+            );
+        }
+        detector
+    }
+
+    #[test]
+    fn test_spike_and_retrace() {
+        let detector = spike_and_retrace();
+
+        let yes = detector.signal(Side::Yes).unwrap();
+        assert_eq!(yes.peak_price, dec!(140));
+        assert_eq!(yes.max_excursion, dec!(40));
+        assert_eq!(yes.retrace, dec!(0.5));
+        assert!(yes.is_reverting(DEFAULT_MAX_MOMENTUM_RETRACE));
+
+        // Spot never fell below the start, so a NO entry has no move to lose
+        let no = detector.signal(Side::No).unwrap();
+        assert_eq!(no.max_excursion, Decimal::ZERO);
+        assert_eq!(no.retrace, Decimal::ZERO);
+        assert!(!no.is_reverting(DEFAULT_MAX_MOMENTUM_RETRACE));
+    }
+
+    #[test]
+    fn test_move_still_in_place() {
+        let mut detector = spike_and_retrace();
+        let last = detector.ticks.back().unwrap().0;
+        detector.update(last + Duration::seconds(5), dec!(136));
+
+        let yes = detector.signal(Side::Yes).unwrap();
+        assert_eq!(yes.retrace, dec!(0.1));
+        assert!(!yes.is_reverting(DEFAULT_MAX_MOMENTUM_RETRACE));
+    }
+
+    /// Binance at `binance` and Coinbase at `coinbase`, both just quoted,
+    /// against a strike of 100
+    fn two_venues(binance: Decimal, coinbase: Decimal) -> MomentumSignal {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut detector = MomentumDetector::default();
+        detector.update(start, dec!(100));
+        detector.update_venue("coinbase", start + Duration::seconds(4), coinbase);
+        detector.update(start + Duration::seconds(5), binance);
+        detector
+            .signal(Side::Yes)
+            .unwrap()
+            .with_venues(detector.venue_moves(dec!(100)))
+    }
+
+    #[test]
+    fn test_venues_agreeing_on_the_move() {
+        let signal = two_venues(dec!(100.20), dec!(100.18));
+        assert_eq!(signal.venues.len(), 2);
+        assert_eq!(signal.venues[0].venue, PRIMARY_VENUE);
+        assert_eq!(signal.venues[0].move_pct, dec!(0.2));
+        assert_eq!(signal.agreeing_venues(), 2);
+        assert_eq!(signal.venue_divergence_pct(), Some(dec!(0.02)));
+    }
+
+    #[test]
+    fn test_move_on_one_venue_only() {
+        // A Binance-only wick: Coinbase never left the strike
+        let signal = two_venues(dec!(100.30), dec!(99.99));
+        assert_eq!(signal.agreeing_venues(), 1);
+    }
+
+    #[test]
+    fn test_diverging_venue_prices() {
+        let signal = two_venues(dec!(100.50), dec!(100.05));
+        assert_eq!(signal.agreeing_venues(), 2);
+        assert!(signal.venue_divergence_pct().unwrap() > DEFAULT_MAX_VENUE_DIVERGENCE_PCT);
+    }
+
+    #[test]
+    fn test_stale_venue_is_left_out() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut detector = MomentumDetector::default();
+        detector.update_venue("coinbase", start, dec!(101));
+        detector.update(start + Duration::seconds(90), dec!(101));
+        let moves = detector.venue_moves(dec!(100));
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].venue, PRIMARY_VENUE);
+    }
+
+    #[test]
+    fn test_batched_prices_match_one_at_a_time() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        // Ten minutes of a wandering price with a late tick every so often
+        let prices: Vec<_> = (0..600i64)
+            .map(|i| {
+                let ts = match i % 37 {
+                    0 if i > 0 => start + Duration::seconds(i - 3),
+                    _ => start + Duration::seconds(i),
+                };
+                (ts, Decimal::from(100_000 + (i * 7919) % 263 - i / 3))
+            })
+            .collect();
+
+        let mut single = MomentumDetector::default();
+        for &(ts, price) in &prices {
+            single.update(ts, price);
+        }
+        for size in [1, 2, 7, 64, 600] {
+            let mut batched = MomentumDetector::default();
+            for chunk in prices.chunks(size) {
+                batched.update_prices(chunk);
+            }
+            assert_eq!(batched.state(), single.state(), "batches of {}", size);
+            for side in [Side::Yes, Side::No] {
+                assert_eq!(batched.signal(side), single.signal(side));
+            }
+        }
+
+        // Nothing usable leaves the detector as it was
+        let mut batched = single.clone();
+        batched.update_prices(&[]);
+        batched.update_prices(&[(start, dec!(1))]);
+        assert_eq!(batched.state(), single.state());
+    }
+
+    #[test]
+    fn test_late_prices_keep_the_window_in_order() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut detector = MomentumDetector::default();
+        detector.update(start, dec!(100));
+        detector.update(start + Duration::seconds(10), dec!(120));
+        // The clock stepped back two seconds: the next prices land between
+        detector.update(start + Duration::seconds(8), dec!(130));
+        detector.update(start + Duration::seconds(9), dec!(125));
+        detector.update(start + Duration::seconds(11), dec!(126));
+
+        let timestamps: Vec<_> = detector.ticks.iter().map(|(ts, _)| *ts).collect();
+        assert!(
+            timestamps.windows(2).all(|w| w[0] <= w[1]),
+            "{:?}",
+            timestamps
+        );
+        assert_eq!(detector.sample_count(), 5);
+        assert_eq!(detector.late_prices(), 2);
+        let yes = detector.signal(Side::Yes).unwrap();
+        assert_eq!(yes.start_price, dec!(100));
+        assert_eq!(yes.peak_price, dec!(130));
+        assert_eq!(yes.current_price, dec!(126));
+
+        // A price from before the window neither enters nor trims it
+        detector.update(start - Duration::seconds(60), dec!(90));
+        assert_eq!(detector.sample_count(), 5);
+        assert_eq!(detector.signal(Side::Yes).unwrap().start_price, dec!(100));
+    }
+
+    #[test]
+    fn test_velocity_is_the_least_squares_slope() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut detector = MomentumDetector::default();
+        // Two dollars a second with noise the endpoints alone would misread
+        for (i, noise) in [0, 0, 0, 0, -5, 3].into_iter().enumerate() {
+            let price = 100 + i as i64 * 2 + noise;
+            detector.update(start + Duration::seconds(i as i64), Decimal::from(price));
+        }
+        let yes = detector.signal(Side::Yes).unwrap();
+        assert_eq!(yes.velocity, dec!(2));
+        assert_eq!(yes.current_price - yes.start_price, dec!(13));
+    }
+
+    #[test]
+    fn test_thinned_storm_matches_unthinned() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        // A minute at 500 prices a second, drifting up with a wobble
+        let prices: Vec<_> = (0..30_000i64)
+            .map(|i| {
+                let cents = 10_000_000 + i / 10 + (i * i * 7919 + i * 104_729) % 401 - 200;
+                (
+                    start + Duration::milliseconds(i * 2),
+                    Decimal::new(cents, 2),
+                )
+            })
+            .collect();
+
+        let mut full = MomentumDetector::default().with_max_samples(usize::MAX);
+        let mut thinned = MomentumDetector::default().with_max_samples(256);
+        for &(ts, price) in &prices {
+            full.update(ts, price);
+            thinned.update(ts, price);
+        }
+        assert_eq!(full.sample_count(), prices.len());
+        assert!(thinned.sample_count() <= 256);
+
+        let (full, thinned) = (
+            full.signal(Side::Yes).unwrap(),
+            thinned.signal(Side::Yes).unwrap(),
+        );
+        assert_eq!(thinned.start_price, full.start_price);
+        assert_eq!(thinned.current_price, full.current_price);
+        let error = ((thinned.velocity - full.velocity) / full.velocity).abs();
+        assert!(
+            error < dec!(0.05),
+            "{} vs {}",
+            thinned.velocity,
+            full.velocity
+        );
+    }
+
+    #[test]
+    fn test_burst_past_the_cap_keeps_the_peak() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        // 20,000 prices in 40s: a rally to a one-tick spike, then a slide
+        // back that gives up 40% of it, with a late price now and then
+        let prices: Vec<_> = (0..20_000i64)
+            .map(|i| {
+                let cents = match i {
+                    12_345 => 10_070_000,
+                    i if i < 12_345 => 10_000_000 + i * 3 + i * 7919 % 97,
+                    i => 10_060_000 - (i - 12_345) * 3 + i * 7919 % 97,
+                };
+                let ms = if i % 1000 == 999 { i * 2 - 30 } else { i * 2 };
+                (start + Duration::milliseconds(ms), Decimal::new(cents, 2))
+            })
+            .collect();
+
+        let mut full = MomentumDetector::default().with_max_samples(usize::MAX);
+        let mut capped = MomentumDetector::default().with_max_samples(64);
+        for &(ts, price) in &prices {
+            full.update(ts, price);
+            capped.update(ts, price);
+            assert!(capped.sample_count() <= 64);
+        }
+        assert_eq!(full.sample_count(), prices.len());
+
+        let timestamps: Vec<_> = capped.ticks.iter().map(|(ts, _)| *ts).collect();
+        assert!(timestamps.windows(2).all(|w| w[0] <= w[1]));
+        for side in [Side::Yes, Side::No] {
+            let (full, capped) = (full.signal(side).unwrap(), capped.signal(side).unwrap());
+            assert_eq!(capped.start_price, full.start_price);
+            assert_eq!(capped.peak_price, full.peak_price);
+            assert_eq!(capped.current_price, full.current_price);
+            assert_eq!(capped.retrace, full.retrace);
+        }
+        let yes = full.signal(Side::Yes).unwrap();
+        assert_eq!(yes.peak_price, dec!(100700));
+        assert!(yes.is_reverting(DEFAULT_MAX_MOMENTUM_RETRACE));
+    }
+
+    #[test]
+    fn test_window_expires_old_prices() {
+        let mut detector = spike_and_retrace();
+        assert!(MomentumDetector::default().signal(Side::Yes).is_none());
+
+        // Once the peak leaves the window only the slide down remains
+        let last = detector.ticks.back().unwrap().0;
+        detector.update(last + Duration::seconds(50), dec!(118));
+        let yes = detector.signal(Side::Yes).unwrap();
+        assert_eq!(yes.start_price, dec!(135));
+        assert_eq!(yes.peak_price, dec!(135));
+        assert_eq!(yes.retrace, Decimal::ZERO);
+
+        let no = detector.signal(Side::No).unwrap();
+        assert_eq!(no.max_excursion, dec!(17));
+        assert_eq!(no.retrace, Decimal::ZERO);
+    }
+}
@@ -0,0 +1,534 @@
+//! Signal outcome forward-tracking
+//!
+//! Follows the YES book after a signal fires, traded or not, to answer "did
+//! the price go where the model said it would?". Each watcher samples the
+//! YES mid at fixed checkpoints and at market close, tracks the furthest
+//! move toward and away from the expected price, and notes when the mid
+//! first reaches it. Watchers are keyed by market and dropped when the
+//! market closes, so memory is bounded by the number of open markets.
+//!
+//! Finished outcomes are also tallied by asset and market interval, so the
+//! convergence rate of a bucket can weigh its next signals; see
+//! [`SignalOutcomeTracker::bucket_record`].
+//!
+//! Watchers also keep the YES mid every [`MARK_INTERVAL_SECS`] as a mark
+//! series. It outlives the watcher until taken with
+//! [`SignalOutcomeTracker::take_marks`], so positions settled on the
+//! market can be attributed against it.
+
+use super::{Side, Signal};
+use crate::market::Market;
+use crate::orderbook::OrderBook;
+use crate::precision::round_price;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+/// Seconds after the signal at which the YES mid is sampled
+pub const CHECKPOINTS_SECS: [i64; 3] = [30, 60, 180];
+
+/// Least seconds between two marks of a watched market
+pub const MARK_INTERVAL_SECS: i64 = 5;
+
+/// YES mid of a market at a point in time
+pub type Mark = (DateTime<Utc>, Decimal);
+
+/// What happened to the YES price after one signal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignalOutcome {
+    /// Signal being followed
+    pub signal_id: Uuid,
+    /// Market condition id
+    pub market_id: String,
+    /// Signal side
+    pub side: Side,
+    /// What the engine did with the signal, e.g. `traded`
+    pub action: String,
+    /// When the signal fired
+    pub emitted_at: DateTime<Utc>,
+    /// YES mid when the signal fired
+    pub entry_price: Decimal,
+    /// Fair YES price from the model
+    pub expected_price: Decimal,
+    /// YES mid at each of [`CHECKPOINTS_SECS`], from the first book after it
+    pub checkpoints: [Option<Decimal>; 3],
+    /// Last YES mid before the market closed
+    pub close_price: Option<Decimal>,
+    /// Furthest the mid moved from entry toward the expected price
+    pub max_favorable: Decimal,
+    /// Furthest the mid moved from entry away from the expected price
+    pub max_adverse: Decimal,
+    /// Seconds until the mid first reached the expected price
+    pub time_to_converge_secs: Option<i64>,
+}
+
+impl SignalOutcome {
+    /// The mid reached the expected price before close
+    pub fn converged(&self) -> bool {
+        self.time_to_converge_secs.is_some()
+    }
+}
+
+/// Convergence across every finished outcome
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutcomeSummary {
+    /// Outcomes finished
+    pub tracked: u64,
+    /// Outcomes whose mid reached the expected price
+    pub converged: u64,
+    /// Median seconds to converge, over converged outcomes
+    pub median_time_to_converge_secs: Option<i64>,
+}
+
+impl OutcomeSummary {
+    /// Share of tracked signals that converged
+    pub fn convergence_rate(&self) -> Option<f64> {
+        (self.tracked > 0).then(|| self.converged as f64 / self.tracked as f64)
+    }
+}
+
+impl fmt::Display for OutcomeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} tracked, {} converged", self.tracked, self.converged)?;
+        if let Some(rate) = self.convergence_rate() {
+            write!(f, " ({:.1}%)", rate * 100.0)?;
+        }
+        if let Some(secs) = self.median_time_to_converge_secs {
+            write!(f, ", median {}s", secs)?;
+        }
+        Ok(())
+    }
+}
+
+struct Watcher {
+    outcome: SignalOutcome,
+    bucket: Bucket,
+    close_time: DateTime<Utc>,
+    /// +1 when the expected price is above entry, -1 when below
+    direction: Decimal,
+    last_mid: Option<Decimal>,
+    /// Sampled mids, ending with the last one observed
+    marks: Vec<Mark>,
+    last_at: DateTime<Utc>,
+}
+
+impl Watcher {
+    fn observe(&mut self, now: DateTime<Utc>, mid: Decimal) {
+        let outcome = &mut self.outcome;
+        let elapsed = (now - outcome.emitted_at).num_seconds();
+        for (slot, offset) in outcome.checkpoints.iter_mut().zip(CHECKPOINTS_SECS) {
+            if slot.is_none() && elapsed >= offset {
+                *slot = Some(mid);
+            }
+        }
+
+        let moved = (mid - outcome.entry_price) * self.direction;
+        outcome.max_favorable = outcome.max_favorable.max(moved);
+        outcome.max_adverse = outcome.max_adverse.max(-moved);
+        if outcome.time_to_converge_secs.is_none()
+            && (mid - outcome.expected_price) * self.direction >= Decimal::ZERO
+        {
+            outcome.time_to_converge_secs = Some(elapsed);
+        }
+        self.last_mid = Some(mid);
+        self.last_at = now;
+        let due = self
+            .marks
+            .last()
+            .is_none_or(|(at, _)| now - *at >= Duration::seconds(MARK_INTERVAL_SECS));
+        if due {
+            self.marks.push((now, mid));
+        }
+    }
+
+    fn finish(mut self) -> (SignalOutcome, Vec<Mark>) {
+        self.outcome.close_price = self.last_mid;
+        if let Some(mid) = self.last_mid {
+            if self.marks.last().is_none_or(|(at, _)| *at < self.last_at) {
+                self.marks.push((self.last_at, mid));
+            }
+        }
+        (self.outcome, self.marks)
+    }
+}
+
+/// Asset and interval in minutes of a market
+type Bucket = (String, i64);
+
+fn bucket(market: &Market) -> Bucket {
+    (
+        market.asset.clone(),
+        (market.close_time - market.open_time).num_minutes(),
+    )
+}
+
+/// Watches the YES book after each signal until its market closes
+#[derive(Default)]
+pub struct SignalOutcomeTracker {
+    /// Outcomes finished and converged, by bucket
+    buckets: HashMap<Bucket, (u64, u64)>,
+    /// Keyed by YES token
+    watchers: HashMap<String, Watcher>,
+    tracked: u64,
+    /// Sorted
+    converge_secs: Vec<i64>,
+    /// Mark series of finished watchers, keyed by market
+    marks: HashMap<String, Vec<Mark>>,
+}
+
+impl SignalOutcomeTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow `signal` from the YES `book` it fired on
+    ///
+    /// Only the first signal per market is followed; returns whether this
+    /// one was.
+    pub fn watch(&mut self, signal: &Signal, book: &OrderBook, action: &str) -> bool {
+        let market = &signal.market;
+        if self.watchers.contains_key(&market.yes_token_id) {
+            return false;
+        }
+        let (entry_price, expected_price) = match signal.side {
+            Side::Yes => (signal.market_price, signal.fair_value),
+            Side::No => (
+                Decimal::ONE - signal.market_price,
+                Decimal::ONE - signal.fair_value,
+            ),
+        };
+        let entry_price = round_price(book.mid_price().unwrap_or(entry_price));
+        let direction = if expected_price >= entry_price {
+            Decimal::ONE
+        } else {
+            -Decimal::ONE
+        };
+        let outcome = SignalOutcome {
+            signal_id: signal.id,
+            market_id: market.condition_id.clone(),
+            side: signal.side,
+            action: action.to_string(),
+            emitted_at: signal.timestamp,
+            entry_price,
+            expected_price,
+            checkpoints: [None; 3],
+            close_price: None,
+            max_favorable: Decimal::ZERO,
+            max_adverse: Decimal::ZERO,
+            time_to_converge_secs: None,
+        };
+        self.watchers.insert(
+            market.yes_token_id.clone(),
+            Watcher {
+                outcome,
+                bucket: bucket(market),
+                close_time: market.close_time,
+                direction,
+                last_mid: Some(entry_price),
+                marks: vec![(signal.timestamp, entry_price)],
+                last_at: signal.timestamp,
+            },
+        );
+        true
+    }
+
+    /// Sample a YES book update
+    pub fn on_book(&mut self, now: DateTime<Utc>, book: &OrderBook) {
+        let Some(watcher) = self.watchers.get_mut(&book.token_id) else {
+            return;
+        };
+        if let Some(mid) = book.mid_price() {
+            watcher.observe(now, round_price(mid));
+        }
+    }
+
+    /// Finish the watcher for a closing market
+    pub fn close(&mut self, market: &Market) -> Option<SignalOutcome> {
+        let watcher = self.watchers.remove(&market.yes_token_id)?;
+        Some(self.finish(watcher))
+    }
+
+    /// Finish watchers whose market closed without a close event
+    pub fn expire(&mut self, now: DateTime<Utc>) -> Vec<SignalOutcome> {
+        let expired: Vec<String> = self
+            .watchers
+            .iter()
+            .filter(|(_, w)| w.close_time <= now)
+            .map(|(token, _)| token.clone())
+            .collect();
+        let mut outcomes = Vec::with_capacity(expired.len());
+        for token in expired {
+            if let Some(watcher) = self.watchers.remove(&token) {
+                outcomes.push(self.finish(watcher));
+            }
+        }
+        outcomes
+    }
+
+    fn finish(&mut self, watcher: Watcher) -> SignalOutcome {
+        let tally = self.buckets.entry(watcher.bucket.clone()).or_default();
+        tally.0 += 1;
+        if watcher.outcome.converged() {
+            tally.1 += 1;
+        }
+        let (outcome, marks) = watcher.finish();
+        self.marks.insert(outcome.market_id.clone(), marks);
+        self.tracked += 1;
+        if let Some(secs) = outcome.time_to_converge_secs {
+            let at = self.converge_secs.partition_point(|&s| s <= secs);
+            self.converge_secs.insert(at, secs);
+        }
+        outcome
+    }
+
+    /// Take the YES mid marks of a market whose watcher has finished
+    ///
+    /// Empty when the market was never watched or is still open.
+    pub fn take_marks(&mut self, market_id: &str) -> Vec<Mark> {
+        self.marks.remove(market_id).unwrap_or_default()
+    }
+
+    /// Outcomes finished on markets of `market`'s asset and interval, and
+    /// how many of them converged
+    pub fn bucket_record(&self, market: &Market) -> (u64, u64) {
+        self.buckets
+            .get(&bucket(market))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Markets being watched
+    pub fn active(&self) -> usize {
+        self.watchers.len()
+    }
+
+    /// Convergence over finished outcomes
+    pub fn summary(&self) -> OutcomeSummary {
+        OutcomeSummary {
+            tracked: self.tracked,
+            converged: self.converge_secs.len() as u64,
+            median_time_to_converge_secs: self
+                .converge_secs
+                .get(self.converge_secs.len().saturating_sub(1) / 2)
+                .copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::PriceLevel;
+    use crate::signal::SignalReason;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-01-01T00:05:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn market() -> Market {
+        Market {
+            condition_id: "cond".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time: t0() - Duration::minutes(5),
+            close_time: t0() + Duration::minutes(10),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    fn signal(side: Side, fair: Decimal, price: Decimal) -> Signal {
+        let mut signal = Signal::new(
+            market(),
+            side,
+            fair,
+            price,
+            dec!(0.05),
+            dec!(0.8),
+            SignalReason::SpotDivergence,
+        );
+        signal.timestamp = t0();
+        signal
+    }
+
+    /// YES book one cent either side of `mid`
+    fn book(mid: Decimal) -> OrderBook {
+        let level = |price| PriceLevel {
+            price,
+            size: dec!(100),
+        };
+        OrderBook {
+            token_id: "yes".to_string(),
+            bids: vec![level(mid - dec!(0.01))],
+            asks: vec![level(mid + dec!(0.01))],
+            updated_at: t0(),
+        }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        t0() + Duration::seconds(secs)
+    }
+
+    #[test]
+    fn test_yes_signal_excursions_checkpoints_and_convergence() {
+        let mut tracker = SignalOutcomeTracker::new();
+        assert!(tracker.watch(
+            &signal(Side::Yes, dec!(0.60), dec!(0.51)),
+            &book(dec!(0.50)),
+            "traded"
+        ));
+
+        for (secs, mid) in [
+            (10, dec!(0.45)),
+            (35, dec!(0.55)),
+            (70, dec!(0.62)),
+            (90, dec!(0.57)),
+            (200, dec!(0.58)),
+        ] {
+            tracker.on_book(at(secs), &book(mid));
+        }
+        let outcome = tracker.close(&market()).unwrap();
+
+        assert_eq!(outcome.entry_price, dec!(0.50));
+        assert_eq!(outcome.expected_price, dec!(0.60));
+        assert_eq!(
+            outcome.checkpoints,
+            [Some(dec!(0.55)), Some(dec!(0.62)), Some(dec!(0.58))]
+        );
+        assert_eq!(outcome.max_favorable, dec!(0.12));
+        assert_eq!(outcome.max_adverse, dec!(0.05));
+        assert_eq!(outcome.time_to_converge_secs, Some(70));
+        assert_eq!(outcome.close_price, Some(dec!(0.58)));
+        assert_eq!(outcome.action, "traded");
+        assert_eq!(tracker.active(), 0);
+        assert_eq!(tracker.bucket_record(&market()), (1, 1));
+    }
+
+    #[test]
+    fn test_marks_are_sampled_and_kept_past_close() {
+        let mut tracker = SignalOutcomeTracker::new();
+        tracker.watch(
+            &signal(Side::Yes, dec!(0.60), dec!(0.51)),
+            &book(dec!(0.50)),
+            "traded",
+        );
+        for (secs, mid) in [
+            (2, dec!(0.52)),
+            (6, dec!(0.54)),
+            (9, dec!(0.55)),
+            (12, dec!(0.57)),
+            (14, dec!(0.58)),
+        ] {
+            tracker.on_book(at(secs), &book(mid));
+        }
+        assert!(tracker.take_marks("cond").is_empty());
+        tracker.close(&market());
+
+        // Every 5s from the signal, then the last mid seen
+        assert_eq!(
+            tracker.take_marks("cond"),
+            vec![
+                (at(0), dec!(0.50)),
+                (at(6), dec!(0.54)),
+                (at(12), dec!(0.57)),
+                (at(14), dec!(0.58)),
+            ]
+        );
+        assert!(tracker.take_marks("cond").is_empty());
+    }
+
+    #[test]
+    fn test_no_signal_measures_against_falling_yes_price() {
+        let mut tracker = SignalOutcomeTracker::new();
+        // NO fair 0.60 means YES expected at 0.40
+        tracker.watch(
+            &signal(Side::No, dec!(0.60), dec!(0.49)),
+            &book(dec!(0.50)),
+            "blocked",
+        );
+        tracker.on_book(at(20), &book(dec!(0.55)));
+        tracker.on_book(at(40), &book(dec!(0.44)));
+        let outcome = tracker.close(&market()).unwrap();
+
+        assert_eq!(outcome.expected_price, dec!(0.40));
+        assert_eq!(outcome.max_favorable, dec!(0.06));
+        assert_eq!(outcome.max_adverse, dec!(0.05));
+        assert_eq!(outcome.checkpoints, [Some(dec!(0.44)), None, None]);
+        assert!(!outcome.converged());
+        assert_eq!(tracker.bucket_record(&market()), (1, 0));
+    }
+
+    #[test]
+    fn test_one_watcher_per_market_and_other_books_ignored() {
+        let mut tracker = SignalOutcomeTracker::new();
+        let first = signal(Side::Yes, dec!(0.60), dec!(0.51));
+        assert!(tracker.watch(&first, &book(dec!(0.50)), "traded"));
+        assert!(!tracker.watch(
+            &signal(Side::Yes, dec!(0.70), dec!(0.51)),
+            &book(dec!(0.50)),
+            "traded"
+        ));
+
+        let mut other = book(dec!(0.90));
+        other.token_id = "no".to_string();
+        tracker.on_book(at(40), &other);
+        let outcome = tracker.close(&market()).unwrap();
+        assert_eq!(outcome.signal_id, first.id);
+        assert_eq!(outcome.checkpoints, [None; 3]);
+        assert_eq!(outcome.close_price, Some(dec!(0.50)));
+    }
+
+    #[test]
+    fn test_watchers_expire_at_market_close() {
+        let mut tracker = SignalOutcomeTracker::new();
+        tracker.watch(
+            &signal(Side::Yes, dec!(0.60), dec!(0.51)),
+            &book(dec!(0.50)),
+            "traded",
+        );
+        assert!(tracker.expire(at(599)).is_empty());
+        assert_eq!(tracker.active(), 1);
+
+        let expired = tracker.expire(at(600));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(tracker.active(), 0);
+        assert!(tracker.close(&market()).is_none());
+        assert_eq!(tracker.summary().tracked, 1);
+    }
+
+    #[test]
+    fn test_summary_convergence_rate_and_median() {
+        let mut tracker = SignalOutcomeTracker::new();
+        for converge_at in [Some(90), Some(30), None, Some(60)] {
+            tracker.watch(
+                &signal(Side::Yes, dec!(0.60), dec!(0.51)),
+                &book(dec!(0.50)),
+                "traded",
+            );
+            if let Some(secs) = converge_at {
+                tracker.on_book(at(secs), &book(dec!(0.61)));
+            }
+            tracker.close(&market());
+        }
+
+        let summary = tracker.summary();
+        assert_eq!(summary.tracked, 4);
+        assert_eq!(summary.converged, 3);
+        assert_eq!(summary.median_time_to_converge_secs, Some(60));
+        assert_eq!(summary.convergence_rate(), Some(0.75));
+        assert_eq!(
+            summary.to_string(),
+            "4 tracked, 3 converged (75.0%), median 60s"
+        );
+    }
+}
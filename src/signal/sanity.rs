@@ -0,0 +1,204 @@
+//! Cross-model sanity checks
+//!
+//! The GBM model and the linear lag model price the same market from
+//! different inputs. When they disagree widely, whichever is wrong will
+//! produce bad trades, so each detection records both YES estimates and the
+//! book mid, and a disagreement past `max_disagreement` either suppresses
+//! the signal or only annotates it. A market that keeps disagreeing usually
+//! has a bad strike or volatility input and is reported once.
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Default widest gap between the GBM and linear YES estimates
+pub const DEFAULT_MAX_MODEL_DISAGREEMENT: Decimal = dec!(0.2);
+
+/// Default consecutive disagreeing detections before a market is reported
+pub const DEFAULT_DISAGREEMENT_ALERT_AFTER: u32 = 10;
+
+/// What a disagreement past the limit does to the signal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisagreementMode {
+    /// Reject the signal as `model_disagreement`
+    #[default]
+    Suppress,
+    /// Record the estimates and trade anyway
+    Annotate,
+}
+
+/// Cross-model checks, under `[signal.model_sanity]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ModelSanityConfig {
+    /// Suppress or only annotate disagreeing signals
+    #[serde(default)]
+    pub mode: DisagreementMode,
+    /// Widest gap between the two YES estimates
+    #[serde(default = "default_max_disagreement")]
+    pub max_disagreement: Decimal,
+    /// Change in the linear model's price per 1% spot move
+    #[serde(default = "default_lag_sensitivity")]
+    pub lag_sensitivity: Decimal,
+    /// Consecutive disagreeing detections in a market before it is
+    /// reported; 0 never reports
+    #[serde(default = "default_alert_after")]
+    pub alert_after: u32,
+}
+
+fn default_max_disagreement() -> Decimal {
+    DEFAULT_MAX_MODEL_DISAGREEMENT
+}
+
+fn default_lag_sensitivity() -> Decimal {
+    crate::model::DEFAULT_LAG_SENSITIVITY
+}
+
+fn default_alert_after() -> u32 {
+    DEFAULT_DISAGREEMENT_ALERT_AFTER
+}
+
+impl Default for ModelSanityConfig {
+    fn default() -> Self {
+        Self {
+            mode: DisagreementMode::default(),
+            max_disagreement: DEFAULT_MAX_MODEL_DISAGREEMENT,
+            lag_sensitivity: crate::model::DEFAULT_LAG_SENSITIVITY,
+            alert_after: DEFAULT_DISAGREEMENT_ALERT_AFTER,
+        }
+    }
+}
+
+/// YES price estimates at one detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelEstimates {
+    /// GBM fair value
+    pub gbm: Decimal,
+    /// Linear lag-implied expected price
+    pub linear: Decimal,
+    /// Book mid, if both sides are quoted
+    pub mid: Option<Decimal>,
+}
+
+impl ModelEstimates {
+    /// Gap between the two models
+    pub fn disagreement(&self) -> Decimal {
+        (self.gbm - self.linear).abs()
+    }
+}
+
+/// A market whose models have disagreed `alert_after` times running
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistentDisagreement {
+    pub market_id: String,
+    /// Consecutive disagreeing detections
+    pub streak: u32,
+    /// When the streak began
+    pub since: DateTime<Utc>,
+    pub latest: ModelEstimates,
+}
+
+/// Tracks disagreement streaks per market
+#[derive(Debug, Default)]
+pub struct ModelSanityMonitor {
+    config: ModelSanityConfig,
+    /// Streak length and start of each disagreeing market
+    streaks: HashMap<String, (u32, DateTime<Utc>)>,
+}
+
+impl ModelSanityMonitor {
+    pub fn new(config: ModelSanityConfig) -> Self {
+        Self {
+            config,
+            streaks: HashMap::new(),
+        }
+    }
+
+    /// Whether disagreeing signals are suppressed or only annotated
+    pub fn mode(&self) -> DisagreementMode {
+        self.config.mode
+    }
+
+    /// Whether `estimates` disagree past the limit
+    pub fn disagrees(&self, estimates: &ModelEstimates) -> bool {
+        estimates.disagreement() > self.config.max_disagreement
+    }
+
+    /// Note a detection in `market_id` at `now`; returns the streak when it
+    /// first reaches `alert_after`
+    ///
+    /// An agreeing detection ends the market's streak.
+    pub fn observe(
+        &mut self,
+        market_id: &str,
+        estimates: &ModelEstimates,
+        now: DateTime<Utc>,
+    ) -> Option<PersistentDisagreement> {
+        if !self.disagrees(estimates) {
+            self.streaks.remove(market_id);
+            return None;
+        }
+        let (streak, since) = self
+            .streaks
+            .entry(market_id.to_string())
+            .or_insert((0, now));
+        *streak += 1;
+        (*streak == self.config.alert_after).then(|| PersistentDisagreement {
+            market_id: market_id.to_string(),
+            streak: *streak,
+            since: *since,
+            latest: *estimates,
+        })
+    }
+
+    /// Forget a market once it has closed
+    pub fn forget(&mut self, market_id: &str) {
+        self.streaks.remove(market_id);
+    }
+
+    /// Markets with a disagreement streak running
+    pub fn tracked(&self) -> usize {
+        self.streaks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_persistent_disagreement_reported_once() {
+        let mut monitor = ModelSanityMonitor::new(ModelSanityConfig {
+            alert_after: 3,
+            ..Default::default()
+        });
+        let now = DateTime::from_timestamp(1_767_600_000, 0).unwrap();
+        let apart = ModelEstimates {
+            gbm: dec!(0.92),
+            linear: dec!(0.60),
+            mid: Some(dec!(0.70)),
+        };
+        let close = ModelEstimates {
+            linear: dec!(0.80),
+            ..apart
+        };
+        assert_eq!(apart.disagreement(), dec!(0.32));
+        assert!(!monitor.disagrees(&close));
+
+        assert!(monitor.observe("m", &apart, now).is_none());
+        // Agreement resets the streak
+        assert!(monitor.observe("m", &close, now).is_none());
+        let at = |secs| now + Duration::seconds(secs);
+        assert!(monitor.observe("m", &apart, at(1)).is_none());
+        assert!(monitor.observe("other", &apart, at(1)).is_none());
+        assert!(monitor.observe("m", &apart, at(2)).is_none());
+        let alert = monitor.observe("m", &apart, at(3)).unwrap();
+        assert_eq!(alert.streak, 3);
+        assert_eq!(alert.since, at(1));
+        assert!(monitor.observe("m", &apart, at(4)).is_none());
+    }
+}
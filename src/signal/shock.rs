@@ -0,0 +1,419 @@
+//! Order book imbalance shocks
+//!
+//! The first sign of a move reversing is sometimes the Polymarket book
+//! itself: the bids under a held token evaporate within a second while spot
+//! has not printed yet. [`BookShockDetector`] watches the book of each held
+//! position and reports a shock when, over a short window, the depth
+//! supporting the position falls by a large fraction and the book's
+//! imbalance swings against it.
+//!
+//! Books flicker: a level pulled and requoted on the next update is not a
+//! shock, so the condition must hold on several consecutive updates. After
+//! a shock the position's book is left alone for a cooldown, and nothing is
+//! reported inside the pre-close no-trade window, where an exit would cross
+//! a spread that settlement is about to close anyway.
+
+use super::Side;
+use crate::data::features::book_imbalance;
+use crate::duration::{DurationConfig, Millis};
+use crate::orderbook::OrderBook;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// Default window over which depth and imbalance are compared, in ms
+pub const DEFAULT_SHOCK_WINDOW_MS: u64 = 1000;
+
+/// Default fraction of the window's peak supporting depth that must vanish
+pub const DEFAULT_SHOCK_DEPTH_DROP: Decimal = dec!(0.6);
+
+/// Default fall in imbalance from the window's peak, on a -1 to 1 scale
+pub const DEFAULT_SHOCK_IMBALANCE_SWING: Decimal = dec!(0.5);
+
+/// Default consecutive shocked updates before one is reported
+pub const DEFAULT_SHOCK_CONFIRM_UPDATES: usize = 2;
+
+/// Default seconds a position's book is ignored after a shock
+pub const DEFAULT_SHOCK_COOLDOWN_SECS: u64 = 30;
+
+/// Book levels counted on each side
+pub const SHOCK_DEPTH_LEVELS: usize = 5;
+
+/// Book shock exit settings, under `[signal.book_shock]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct BookShockConfig {
+    /// Exit held positions on a book shock
+    #[serde(default)]
+    pub enabled: bool,
+    /// Window over which depth and imbalance are compared
+    #[serde(default = "default_shock_window_ms")]
+    pub window_ms: DurationConfig<Millis>,
+    /// Fraction of the window's peak supporting depth that must vanish
+    #[serde(default = "default_shock_depth_drop")]
+    pub min_depth_drop: Decimal,
+    /// Fall in imbalance from the window's peak
+    #[serde(default = "default_shock_imbalance_swing")]
+    pub min_imbalance_swing: Decimal,
+    /// Consecutive shocked updates before one is reported
+    #[serde(default = "default_shock_confirm_updates")]
+    pub confirm_updates: usize,
+    /// How long a position's book is ignored after a shock
+    #[serde(default = "default_shock_cooldown_secs")]
+    pub cooldown_secs: DurationConfig,
+}
+
+fn default_shock_window_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(DEFAULT_SHOCK_WINDOW_MS)
+}
+
+fn default_shock_depth_drop() -> Decimal {
+    DEFAULT_SHOCK_DEPTH_DROP
+}
+
+fn default_shock_imbalance_swing() -> Decimal {
+    DEFAULT_SHOCK_IMBALANCE_SWING
+}
+
+fn default_shock_confirm_updates() -> usize {
+    DEFAULT_SHOCK_CONFIRM_UPDATES
+}
+
+fn default_shock_cooldown_secs() -> DurationConfig {
+    DurationConfig::from_secs(DEFAULT_SHOCK_COOLDOWN_SECS)
+}
+
+impl Default for BookShockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_ms: default_shock_window_ms(),
+            min_depth_drop: DEFAULT_SHOCK_DEPTH_DROP,
+            min_imbalance_swing: DEFAULT_SHOCK_IMBALANCE_SWING,
+            confirm_updates: DEFAULT_SHOCK_CONFIRM_UPDATES,
+            cooldown_secs: default_shock_cooldown_secs(),
+        }
+    }
+}
+
+/// Supporting depth collapsing under a held position
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookShock {
+    /// Side held
+    pub side: Side,
+    /// Update that confirmed the shock
+    pub at: DateTime<Utc>,
+    /// Highest supporting depth in the window
+    pub peak_depth: Decimal,
+    /// Supporting depth now
+    pub depth: Decimal,
+    /// Fraction of the peak depth gone
+    pub depth_drop: Decimal,
+    /// Depth lost per second since the peak
+    pub depth_rate: Decimal,
+    /// Fall in imbalance from the window's peak
+    pub imbalance_swing: Decimal,
+}
+
+impl fmt::Display for BookShock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} support {} -> {} ({:.0}% gone, {:.0}/s), imbalance down {:.2}",
+            self.side,
+            self.peak_depth,
+            self.depth,
+            self.depth_drop * dec!(100),
+            self.depth_rate,
+            self.imbalance_swing
+        )
+    }
+}
+
+/// One update of a watched book
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: DateTime<Utc>,
+    depth: Decimal,
+    imbalance: Decimal,
+}
+
+#[derive(Debug, Default)]
+struct Watch {
+    samples: VecDeque<Sample>,
+    /// Consecutive updates meeting both thresholds
+    streak: usize,
+    last_shock: Option<DateTime<Utc>>,
+}
+
+/// Watches the books of held positions for depth evaporation
+#[derive(Debug)]
+pub struct BookShockDetector {
+    config: BookShockConfig,
+    /// No-trade window before close, where shocks are not reported
+    quiet_before_close: Duration,
+    watches: HashMap<String, Watch>,
+}
+
+impl BookShockDetector {
+    /// Detector from `config`, silent within `quiet_before_close` of a close
+    pub fn new(config: BookShockConfig, quiet_before_close: Duration) -> Self {
+        Self {
+            config,
+            quiet_before_close,
+            watches: HashMap::new(),
+        }
+    }
+
+    /// Whether shocks are detected at all
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Feed the YES book of a market where `side` is held under `key`,
+    /// returning a confirmed shock
+    ///
+    /// A YES holder is supported by the YES bids and pressed by the asks;
+    /// a NO holder the other way round, as NO bids are YES asks.
+    pub fn observe(
+        &mut self,
+        key: &str,
+        book: &OrderBook,
+        side: Side,
+        close_time: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Option<BookShock> {
+        if !self.config.enabled {
+            return None;
+        }
+        let support = match side {
+            Side::Yes => depth(&book.bids),
+            Side::No => depth(&book.asks),
+        };
+        let imbalance = match (side, book_imbalance(book, SHOCK_DEPTH_LEVELS)) {
+            (_, None) => Decimal::ZERO,
+            (Side::Yes, Some(imbalance)) => imbalance,
+            (Side::No, Some(imbalance)) => -imbalance,
+        };
+
+        let window = self.config.window_ms.to_chrono();
+        let watch = self.watches.entry(key.to_string()).or_default();
+        watch.samples.push_back(Sample {
+            at: now,
+            depth: support,
+            imbalance,
+        });
+        while watch.samples.front().is_some_and(|s| now - s.at > window) {
+            watch.samples.pop_front();
+        }
+        if close_time - now < self.quiet_before_close {
+            watch.streak = 0;
+            return None;
+        }
+
+        let peak = watch
+            .samples
+            .iter()
+            .max_by_key(|s| s.depth)
+            .copied()
+            .expect("just pushed");
+        let top_imbalance = watch
+            .samples
+            .iter()
+            .map(|s| s.imbalance)
+            .max()
+            .unwrap_or(imbalance);
+        let depth_drop = if peak.depth.is_zero() {
+            Decimal::ZERO
+        } else {
+            (peak.depth - support) / peak.depth
+        };
+        let imbalance_swing = top_imbalance - imbalance;
+        if depth_drop < self.config.min_depth_drop
+            || imbalance_swing < self.config.min_imbalance_swing
+        {
+            watch.streak = 0;
+            return None;
+        }
+        watch.streak += 1;
+        if watch.streak < self.config.confirm_updates.max(1) {
+            return None;
+        }
+        let cooldown = self.config.cooldown_secs.to_chrono();
+        if watch.last_shock.is_some_and(|at| now - at < cooldown) {
+            return None;
+        }
+        watch.last_shock = Some(now);
+        watch.streak = 0;
+
+        let elapsed_ms = (now - peak.at).num_milliseconds().max(1);
+        Some(BookShock {
+            side,
+            at: now,
+            peak_depth: peak.depth,
+            depth: support,
+            depth_drop,
+            depth_rate: (peak.depth - support) * Decimal::from(1000) / Decimal::from(elapsed_ms),
+            imbalance_swing,
+        })
+    }
+
+    /// Stop watching `key`, once its position is closed
+    pub fn forget(&mut self, key: &str) {
+        self.watches.remove(key);
+    }
+
+    /// Positions being watched
+    pub fn watching(&self) -> usize {
+        self.watches.len()
+    }
+}
+
+/// Size on the first [`SHOCK_DEPTH_LEVELS`] levels of one side
+fn depth(levels: &[crate::orderbook::PriceLevel]) -> Decimal {
+    levels.iter().take(SHOCK_DEPTH_LEVELS).map(|l| l.size).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::PriceLevel;
+
+    fn at(ms: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_767_600_000, 0).unwrap() + Duration::milliseconds(ms)
+    }
+
+    fn book(bid_size: Decimal, ask_size: Decimal) -> OrderBook {
+        let mut book = OrderBook::new("yes");
+        book.bids = vec![PriceLevel {
+            price: dec!(0.60),
+            size: bid_size,
+        }];
+        book.asks = vec![PriceLevel {
+            price: dec!(0.62),
+            size: ask_size,
+        }];
+        book
+    }
+
+    fn detector() -> BookShockDetector {
+        let config = BookShockConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        BookShockDetector::new(config, Duration::seconds(60))
+    }
+
+    /// Feed `(ms, bid size)` updates with a steady 100 on the asks,
+    /// returning the updates that reported a shock
+    fn run(
+        detector: &mut BookShockDetector,
+        side: Side,
+        updates: &[(i64, Decimal)],
+    ) -> Vec<BookShock> {
+        let close = at(15 * 60 * 1000);
+        updates
+            .iter()
+            .filter_map(|&(ms, bids)| {
+                detector.observe("pos", &book(bids, dec!(100)), side, close, at(ms))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_evaporating_bids_trigger_once() {
+        let mut shocks = detector();
+        let updates = [
+            (0, dec!(400)),
+            (200, dec!(380)),
+            (400, dec!(250)),
+            (600, dec!(120)),
+            (800, dec!(40)),
+            (900, dec!(20)),
+            (1000, dec!(10)),
+        ];
+        let found = run(&mut shocks, Side::Yes, &updates);
+        assert_eq!(found.len(), 1);
+        let shock = &found[0];
+        // 120 is 70% down from 400: the second shocked update confirms
+        assert_eq!(shock.at, at(800));
+        assert_eq!(shock.peak_depth, dec!(400));
+        assert_eq!(shock.depth, dec!(40));
+        assert_eq!(shock.depth_drop, dec!(0.9));
+        assert_eq!(shock.depth_rate, dec!(450));
+        assert!(shock.imbalance_swing >= DEFAULT_SHOCK_IMBALANCE_SWING);
+
+        // Rate limited: the book keeps draining inside the cooldown
+        let later = [(5_000, dec!(400)), (5_400, dec!(50)), (5_600, dec!(10))];
+        assert!(run(&mut shocks, Side::Yes, &later).is_empty());
+        let after = [(40_000, dec!(400)), (40_400, dec!(50)), (40_600, dec!(10))];
+        assert_eq!(run(&mut shocks, Side::Yes, &after).len(), 1);
+    }
+
+    #[test]
+    fn test_flickering_books_do_not_trigger() {
+        let mut shocks = detector();
+        // A level pulled and requoted on alternate updates
+        let flicker: Vec<_> = (0..40)
+            .map(|i| (i * 100, if i % 2 == 0 { dec!(400) } else { dec!(20) }))
+            .collect();
+        assert!(run(&mut shocks, Side::Yes, &flicker).is_empty());
+
+        // A slow drain never falls far enough within one window
+        let drain: Vec<_> = (0..40)
+            .map(|i| (10_000 + i * 500, dec!(400) - Decimal::from(i * 9)))
+            .collect();
+        assert!(run(&mut shocks, Side::Yes, &drain).is_empty());
+
+        // Bids vanishing with the asks leave the imbalance where it was
+        let mut both = detector();
+        let close = at(15 * 60 * 1000);
+        let shocked = [(0, dec!(400)), (300, dec!(40)), (600, dec!(20))]
+            .iter()
+            .filter_map(|&(ms, size)| {
+                both.observe("pos", &book(size, size), Side::Yes, close, at(ms))
+            })
+            .count();
+        assert_eq!(shocked, 0);
+    }
+
+    #[test]
+    fn test_no_holder_watches_the_yes_asks_and_close_is_quiet() {
+        let mut shocks = detector();
+        let close = at(15 * 60 * 1000);
+        let asks = [dec!(400), dec!(100), dec!(20)];
+        let shocks: Vec<_> = asks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &size)| {
+                let ms = i as i64 * 300;
+                shocks.observe("no", &book(dec!(100), size), Side::No, close, at(ms))
+            })
+            .collect();
+        assert_eq!(shocks.len(), 1);
+        assert_eq!(shocks[0].side, Side::No);
+
+        // Inside the last minute the same collapse is not reported
+        let mut quiet = detector();
+        let close = at(30_000);
+        let shocks = [(0, dec!(400)), (300, dec!(100)), (600, dec!(20))]
+            .iter()
+            .filter_map(|&(ms, size)| {
+                quiet.observe("pos", &book(size, dec!(100)), Side::Yes, close, at(ms))
+            })
+            .count();
+        assert_eq!(shocks, 0);
+
+        // Disabled by default
+        let mut off = BookShockDetector::new(BookShockConfig::default(), Duration::zero());
+        assert!(run(
+            &mut off,
+            Side::Yes,
+            &[(0, dec!(400)), (300, dec!(10)), (600, dec!(5))]
+        )
+        .is_empty());
+    }
+}
@@ -1,13 +1,19 @@
 //! Signal types
 
+use super::{ModelEstimates, MomentumSignal};
+use crate::flags::FlagDecision;
 use crate::market::Market;
+use crate::orderbook::DepthProfile;
+use crate::precision::{round_pct, round_price};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use uuid::Uuid;
 
 /// Trading side
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Side {
     /// Buy Yes tokens
@@ -18,6 +24,7 @@ pub enum Side {
 
 /// Reason for signal generation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum SignalReason {
     /// Market just opened, prices lagging
     PostResetLag,
@@ -27,8 +34,68 @@ pub enum SignalReason {
     VolatilitySpike,
 }
 
+/// Why the lag detector emitted no signal
+///
+/// Serializes as its [`code`](Self::code) under `code` beside the numbers
+/// behind it, e.g. `{"code":"near_bound","edge":"0.03","min":"0.05"}`.
+/// Codes are stable: a variant is never renamed, only added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum NoLagReason {
+    /// The market has closed
+    Expired,
+    /// The book top is crossed or locked
+    BookFault,
+    /// The book has no asks to price against
+    NoAsk,
+    /// Neither side has an edge after costs: the better side's raw `edge`
+    /// does not cover `costs`
+    NoEdge { edge: Decimal, costs: Decimal },
+    /// The expected price was clamped to the venue's bound and the raw
+    /// `edge` against the bound is below `min`
+    NearBound { edge: Decimal, min: Decimal },
+    /// The NO book has already repriced in the lag's direction; the lag is
+    /// a stale YES book, not a slow market. `implied_yes` is the YES price
+    /// the NO book implies
+    ComplementRepriced {
+        yes_ask: Decimal,
+        implied_yes: Decimal,
+    },
+}
+
+impl NoLagReason {
+    /// Every code, in declaration order
+    pub const CODES: [&'static str; 6] = [
+        "expired",
+        "book_fault",
+        "no_ask",
+        "no_edge",
+        "near_bound",
+        "complement_repriced",
+    ];
+
+    /// Stable machine-readable code, for journals, tapes and metrics
+    pub fn code(&self) -> &'static str {
+        match self {
+            NoLagReason::Expired => "expired",
+            NoLagReason::BookFault => "book_fault",
+            NoLagReason::NoAsk => "no_ask",
+            NoLagReason::NoEdge { .. } => "no_edge",
+            NoLagReason::NearBound { .. } => "near_bound",
+            NoLagReason::ComplementRepriced { .. } => "complement_repriced",
+        }
+    }
+
+    /// The numbers behind the reason as a JSON object, empty for none
+    pub fn context(&self) -> serde_json::Value {
+        super::codes::context_of(self)
+    }
+}
+
 /// A trading signal
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Signal {
     /// Unique signal identifier
     pub id: Uuid,
@@ -44,6 +111,33 @@ pub struct Signal {
     pub raw_edge: Decimal,
     /// Adjusted edge after fees/slippage
     pub adjusted_edge: Decimal,
+    /// Bid-ask spread of the traded book; a missing bid counts as zero
+    #[serde(default)]
+    pub spread: Decimal,
+    /// Adjusted edge less the half-spread given up again on exit
+    #[serde(default)]
+    pub round_trip_edge: Decimal,
+    /// Fraction of the spot move given back within the momentum window
+    #[serde(default)]
+    pub retrace: Option<Decimal>,
+    /// Spot move over the momentum window in the signal's favour
+    #[serde(default)]
+    pub momentum_move: Option<Decimal>,
+    /// Venues whose spot is on the signal's side of the strike
+    #[serde(default)]
+    pub venues_agreeing: Option<usize>,
+    /// Spread between venue spot prices, in percent
+    #[serde(default)]
+    pub venue_divergence_pct: Option<Decimal>,
+    /// Size near the touch of the book the signal would trade against
+    #[serde(default)]
+    pub depth: DepthProfile,
+    /// GBM and linear YES estimates and the book mid at detection
+    #[serde(default)]
+    pub models: Option<ModelEstimates>,
+    /// Every feature flag evaluated for the signal, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub flags: BTreeMap<String, FlagDecision>,
     /// Confidence score
     pub confidence: Decimal,
     /// Reason for signal
@@ -53,7 +147,7 @@ pub struct Signal {
 }
 
 impl Signal {
-    /// Create a new signal
+    /// Create a new signal, rounded to the canonical scales
     pub fn new(
         market: Market,
         side: Side,
@@ -67,13 +161,90 @@ impl Signal {
             id: Uuid::new_v4(),
             market,
             side,
-            fair_value,
-            market_price,
-            raw_edge: fair_value - market_price,
-            adjusted_edge,
-            confidence,
+            fair_value: round_pct(fair_value),
+            market_price: round_price(market_price),
+            raw_edge: round_pct(fair_value - market_price),
+            adjusted_edge: round_pct(adjusted_edge),
+            spread: Decimal::ZERO,
+            round_trip_edge: round_pct(adjusted_edge),
+            retrace: None,
+            momentum_move: None,
+            venues_agreeing: None,
+            venue_divergence_pct: None,
+            depth: DepthProfile::default(),
+            models: None,
+            flags: BTreeMap::new(),
+            confidence: round_pct(confidence),
             reason,
             timestamp: Utc::now(),
         }
     }
+
+    /// Record the traded book's spread and the round-trip edge it implies
+    ///
+    /// Buying at the ask already pays half the spread against the mid;
+    /// selling back at the bid pays the other half.
+    pub fn with_spread(mut self, spread: Decimal) -> Self {
+        self.spread = round_price(spread);
+        self.round_trip_edge = round_pct(self.adjusted_edge - spread / Decimal::TWO);
+        self
+    }
+
+    /// Record the depth near the touch of the traded book
+    pub fn with_depth(mut self, depth: DepthProfile) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Record the cross-model estimates at detection
+    pub fn with_models(mut self, models: ModelEstimates) -> Self {
+        self.models = Some(models);
+        self
+    }
+
+    /// Whether the feature flag `name` is on for the signal; `None` when
+    /// the flag was not evaluated
+    pub fn flag(&self, name: &str) -> Option<bool> {
+        self.flags.get(name).map(|d| d.on)
+    }
+
+    /// Record how much of the spot move behind the signal has reverted
+    ///
+    /// Venue agreement is only recorded when the momentum carries venues.
+    pub fn with_momentum(mut self, momentum: &MomentumSignal) -> Self {
+        self.retrace = Some(momentum.retrace);
+        self.momentum_move = Some(match momentum.side {
+            Side::Yes => momentum.current_price - momentum.start_price,
+            Side::No => momentum.start_price - momentum.current_price,
+        });
+        if !momentum.venues.is_empty() {
+            self.venues_agreeing = Some(momentum.agreeing_venues());
+            self.venue_divergence_pct = momentum.venue_divergence_pct();
+        }
+        self
+    }
+
+    /// Edge the entry threshold is applied to
+    pub fn entry_edge(&self, round_trip: bool) -> Decimal {
+        if round_trip {
+            self.round_trip_edge
+        } else {
+            self.adjusted_edge
+        }
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {} @ {:.4} fair {:.6} edge {:.6} ({:?})",
+            self.side,
+            self.market.condition_id,
+            round_price(self.market_price),
+            round_pct(self.fair_value),
+            round_pct(self.adjusted_edge),
+            self.reason
+        )
+    }
 }
@@ -0,0 +1,131 @@
+//! Synthetic 15-minute markets with lagging books
+
+use super::SimConfig;
+use crate::backtest::BacktestEvent;
+use crate::feed::PriceTick;
+use crate::market::{event_slug, window_start, Market};
+use crate::model::{FairValueModel, FairValueParams, GbmModel};
+use crate::orderbook::{OrderBook, PriceLevel};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::VecDeque;
+
+/// Window length of the generated markets
+const MARKET_MINUTES: i64 = 15;
+
+/// Price levels quoted on each side of the book
+const BOOK_DEPTH: usize = 3;
+
+/// Polymarket tick size
+const TICK: Decimal = dec!(0.01);
+
+/// Opens a market every 15 minutes and quotes its YES book off a stale spot
+///
+/// The book is priced by the same GBM model the detector uses, but on the
+/// spot from `lag_delay_ms` ago, so fast spot moves leave it mispriced.
+pub struct SyntheticMarketSource {
+    asset: String,
+    model: GbmModel,
+    volatility: Decimal,
+    lag: Duration,
+    half_spread: Decimal,
+    book_size: Decimal,
+    spots: VecDeque<(DateTime<Utc>, Decimal)>,
+    current: Option<Market>,
+}
+
+impl SyntheticMarketSource {
+    /// Create a market source for `asset` from the sim config
+    pub fn new(asset: impl Into<String>, config: &SimConfig) -> Self {
+        Self {
+            asset: asset.into(),
+            model: GbmModel::new(),
+            volatility: Decimal::try_from(config.volatility).unwrap_or_default(),
+            lag: config.lag_delay_ms.to_chrono(),
+            half_spread: config.spread / Decimal::TWO,
+            book_size: config.book_size,
+            spots: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    /// Currently open market, if any
+    pub fn current(&self) -> Option<&Market> {
+        self.current.as_ref()
+    }
+
+    /// Market events and a fresh YES book following a spot tick
+    pub fn on_tick(&mut self, tick: &PriceTick) -> Vec<BacktestEvent> {
+        let now = tick.exchange_ts;
+        self.spots.push_back((now, tick.price));
+        let cutoff = now - self.lag;
+        while self.spots.len() > 1 && self.spots[1].0 <= cutoff {
+            self.spots.pop_front();
+        }
+
+        let mut events = vec![];
+        if self.current.as_ref().is_some_and(|m| now >= m.close_time) {
+            events.extend(self.close());
+        }
+        if self.current.is_none() {
+            let market = self.open(now, tick.price);
+            events.push(BacktestEvent::MarketOpen(market.clone()));
+            self.current = Some(market);
+        }
+        if let Some(book) = self.book(now) {
+            events.push(BacktestEvent::OrderBookUpdate(book));
+        }
+        events
+    }
+
+    /// Close the open market, if any
+    pub fn close(&mut self) -> Option<BacktestEvent> {
+        self.current.take().map(BacktestEvent::MarketClose)
+    }
+
+    fn open(&self, now: DateTime<Utc>, spot: Decimal) -> Market {
+        let open_time = window_start(now);
+        let slug = event_slug(&self.asset, open_time);
+        Market::new(
+            slug,
+            self.asset.to_uppercase(),
+            spot,
+            open_time,
+            open_time + Duration::minutes(MARKET_MINUTES),
+        )
+    }
+
+    fn book(&self, now: DateTime<Utc>) -> Option<OrderBook> {
+        let market = self.current.as_ref()?;
+        let (_, stale_spot) = *self.spots.front()?;
+        let fair = self.model.calculate(FairValueParams {
+            current_price: stale_spot,
+            open_price: market.open_price,
+            time_to_expiry: market.close_time - now,
+            volatility: self.volatility,
+        });
+        let bid = quote(fair.yes_prob - self.half_spread);
+        let ask = quote(fair.yes_prob + self.half_spread).max(bid + TICK);
+        let levels = |best: Decimal, step: Decimal| {
+            (0..BOOK_DEPTH)
+                .map(|i| PriceLevel {
+                    price: best + step * Decimal::from(i),
+                    size: self.book_size,
+                })
+                .filter(|l| l.price > Decimal::ZERO && l.price < Decimal::ONE)
+                .collect()
+        };
+        Some(OrderBook {
+            token_id: market.yes_token_id.clone(),
+            bids: levels(bid, -TICK),
+            asks: levels(ask, TICK),
+            updated_at: now,
+        })
+    }
+}
+
+/// Round to the tick grid inside the tradable range
+fn quote(price: Decimal) -> Decimal {
+    price.round_dp(2).clamp(dec!(0.01), dec!(0.98))
+}
@@ -0,0 +1,1824 @@
+//! Offline simulation
+//!
+//! Synthetic spot and market data for `run --sim`: a seeded GBM price path
+//! and 15-minute markets whose books trail the spot by a fixed delay, which
+//! is the inefficiency the strategy trades. The same seed always produces
+//! the same event stream, so a simulated session is reproducible without
+//! any network access.
+
+#[cfg(test)]
+mod chaos;
+mod feed;
+mod markets;
+mod pace;
+
+pub use feed::SyntheticFeed;
+pub use markets::SyntheticMarketSource;
+pub use pace::{Breakpoint, MarketEvent, Pacer, Pause, SimClock, Speed, Until};
+
+use crate::backtest::BacktestEvent;
+use crate::duration::{DurationConfig, Millis, Minutes};
+use chrono::{DateTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Synthetic data parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimConfig {
+    /// RNG seed for the price path
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+    /// Initial spot price
+    #[serde(default = "default_start_price")]
+    pub start_price: Decimal,
+    /// Simulated clock at the first tick
+    #[serde(default = "default_start_time")]
+    pub start_time: DateTime<Utc>,
+    /// Length of the simulated session
+    #[serde(default = "default_duration_mins")]
+    pub duration_mins: DurationConfig<Minutes>,
+    /// Spacing between spot ticks
+    #[serde(default = "default_tick_interval_ms")]
+    pub tick_interval_ms: DurationConfig<Millis>,
+    /// Annualized spot volatility
+    #[serde(default = "default_volatility")]
+    pub volatility: f64,
+    /// Chance of a jump on each tick
+    #[serde(default = "default_jump_probability")]
+    pub jump_probability: f64,
+    /// Jump size as a log return
+    #[serde(default = "default_jump_size")]
+    pub jump_size: f64,
+    /// How far market books trail the spot
+    #[serde(default = "default_lag_delay_ms")]
+    pub lag_delay_ms: DurationConfig<Millis>,
+    /// YES book bid/ask spread
+    #[serde(default = "default_spread")]
+    pub spread: Decimal,
+    /// Shares quoted at each book level
+    #[serde(default = "default_book_size")]
+    pub book_size: Decimal,
+}
+
+fn default_seed() -> u64 {
+    42
+}
+
+fn default_start_price() -> Decimal {
+    dec!(100000)
+}
+
+fn default_start_time() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+}
+
+fn default_duration_mins() -> DurationConfig<Minutes> {
+    DurationConfig::from_mins(60)
+}
+
+fn default_tick_interval_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(1000)
+}
+
+fn default_volatility() -> f64 {
+    0.6
+}
+
+fn default_jump_probability() -> f64 {
+    0.002
+}
+
+fn default_jump_size() -> f64 {
+    0.003
+}
+
+fn default_lag_delay_ms() -> DurationConfig<Millis> {
+    DurationConfig::from_millis(5000)
+}
+
+fn default_spread() -> Decimal {
+    dec!(0.02)
+}
+
+fn default_book_size() -> Decimal {
+    dec!(200)
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            seed: default_seed(),
+            start_price: default_start_price(),
+            start_time: default_start_time(),
+            duration_mins: default_duration_mins(),
+            tick_interval_ms: default_tick_interval_ms(),
+            volatility: default_volatility(),
+            jump_probability: default_jump_probability(),
+            jump_size: default_jump_size(),
+            lag_delay_ms: default_lag_delay_ms(),
+            spread: default_spread(),
+            book_size: default_book_size(),
+        }
+    }
+}
+
+/// Time-ordered event stream from a synthetic feed and market source
+pub struct Simulation {
+    feed: SyntheticFeed,
+    markets: SyntheticMarketSource,
+    pending: VecDeque<(DateTime<Utc>, BacktestEvent)>,
+    last_time: Option<DateTime<Utc>>,
+}
+
+impl Simulation {
+    /// Build a simulation for `symbol` spot and `asset` markets
+    pub fn new(symbol: &str, asset: &str, config: &SimConfig) -> Self {
+        Self {
+            feed: SyntheticFeed::new(symbol, config).with_asset(asset),
+            markets: SyntheticMarketSource::new(asset, config),
+            pending: VecDeque::new(),
+            last_time: None,
+        }
+    }
+}
+
+impl Iterator for Simulation {
+    type Item = (DateTime<Utc>, BacktestEvent);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        match self.feed.next() {
+            Some(tick) => {
+                let now = tick.exchange_ts;
+                self.last_time = Some(now);
+                let events = self.markets.on_tick(&tick);
+                self.pending.extend(events.into_iter().map(|e| (now, e)));
+                Some((now, BacktestEvent::PriceTick(tick)))
+            }
+            // Settle whatever is still open when the path ends
+            None => Some((self.last_time?, self.markets.close()?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::{EngineStats, TradingEngine};
+    use crate::execution::{ExecutionEngine, Fill, NoopEngine, Order, OrderId, PaperEngine};
+    use crate::journal::{Journal, JournalEntry};
+    use crate::orderbook::OrderBook;
+
+    async fn run(config: &Config) -> EngineStats {
+        let mut engine = TradingEngine::new(
+            config,
+            PaperEngine::with_cost_model(config.execution.costs.clone()),
+        );
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            engine.on_event(ts, event).await.unwrap();
+        }
+        engine.stats().clone()
+    }
+
+    #[tokio::test]
+    async fn test_sim_trades_deterministically() {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+
+        let first = run(&config).await;
+        assert!(first.signals >= 1, "no signals: {:?}", first);
+        assert!(first.fills >= 1, "no fills: {:?}", first);
+        assert_eq!(first.markets_settled, 2);
+        assert!(first.outcomes.tracked >= 1, "no outcomes: {:?}", first);
+        assert_eq!(first.asset_mismatches, 0);
+        assert_eq!(run(&config).await, first);
+    }
+
+    /// `run`, handing the ticks between other events over in batches of
+    /// up to `max_batch`, as the live loop drains them
+    async fn run_batched(config: &Config, max_batch: usize) -> TradingEngine<PaperEngine> {
+        let mut engine = TradingEngine::new(
+            config,
+            PaperEngine::with_cost_model(config.execution.costs.clone()),
+        );
+        let mut batch = vec![];
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            match event {
+                BacktestEvent::PriceTick(tick) if max_batch > 0 => {
+                    batch.push(tick);
+                    if batch.len() == max_batch {
+                        engine.on_ticks(std::mem::take(&mut batch)).await.unwrap();
+                    }
+                }
+                event => {
+                    engine.on_ticks(std::mem::take(&mut batch)).await.unwrap();
+                    engine.on_event(ts, event).await.unwrap();
+                }
+            }
+        }
+        engine.on_ticks(batch).await.unwrap();
+        engine
+    }
+
+    #[tokio::test]
+    async fn test_batched_ticks_detect_like_single_ticks() {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+
+        let single = run_batched(&config, 0).await;
+        assert_eq!(single.stats(), &run(&config).await);
+        assert!(
+            single.stats().signals >= 1,
+            "no signals: {:?}",
+            single.stats()
+        );
+        let end = Utc::now();
+        for max_batch in [1, 3, 50, 1000] {
+            let batched = run_batched(&config, max_batch).await;
+            assert_eq!(batched.stats(), single.stats(), "batches of {}", max_batch);
+            assert_eq!(batched.warm_state(end), single.warm_state(end));
+            assert_eq!(batched.settlements(), single.settlements());
+        }
+    }
+
+    /// `run`, with a NO book quoted by `no` from each YES book just ahead of it
+    async fn run_with_no_books(
+        config: &Config,
+        no: impl Fn(&OrderBook) -> OrderBook,
+    ) -> TradingEngine<PaperEngine> {
+        let mut engine = TradingEngine::new(
+            config,
+            PaperEngine::with_cost_model(config.execution.costs.clone()),
+        );
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            if let BacktestEvent::OrderBookUpdate(yes) = &event {
+                let no = no(yes);
+                engine
+                    .on_event(ts, BacktestEvent::OrderBookUpdate(no))
+                    .await
+                    .unwrap();
+            }
+            engine.on_event(ts, event).await.unwrap();
+        }
+        engine
+    }
+
+    #[tokio::test]
+    async fn test_repriced_no_book_suppresses_yes_lags() {
+        use crate::orderbook::PriceLevel;
+        use crate::signal::Side;
+        use rust_decimal_macros::dec;
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let no_book = |yes: &OrderBook, bid: Decimal, ask: Decimal| OrderBook {
+            token_id: yes.token_id.replace("-yes", "-no"),
+            bids: vec![PriceLevel {
+                price: bid,
+                size: dec!(100),
+            }],
+            asks: vec![PriceLevel {
+                price: ask,
+                size: dec!(100),
+            }],
+            updated_at: yes.updated_at,
+        };
+        let yes_entries = |engine: &TradingEngine<PaperEngine>| {
+            engine
+                .trade_tape()
+                .iter()
+                .filter(|row| row.side == Side::Yes)
+                .count()
+        };
+        let plain = run_batched(&config, 0).await;
+        assert!(yes_entries(&plain) >= 1);
+
+        // A NO book mirroring the YES one: the whole market lags, trade it
+        let mirrored = run_with_no_books(&config, |yes| {
+            let bid = Decimal::ONE - yes.best_ask().unwrap_or(Decimal::ONE);
+            let ask = Decimal::ONE - yes.best_bid().unwrap_or(Decimal::ZERO);
+            no_book(yes, bid, ask)
+        })
+        .await;
+        assert_eq!(mirrored.stats().complement_repriced, 0);
+        assert_eq!(mirrored.stats().orders, plain.stats().orders);
+        assert_eq!(yes_entries(&mirrored), yes_entries(&plain));
+
+        // A NO book already collapsed: every YES lag is a stale YES book
+        let repriced = run_with_no_books(&config, |yes| no_book(yes, dec!(0.01), dec!(0.02))).await;
+        assert!(repriced.stats().complement_repriced >= 1);
+        assert_eq!(yes_entries(&repriced), 0);
+        assert!(repriced
+            .stats()
+            .to_string()
+            .contains("Lags already priced into the NO book"));
+    }
+
+    #[tokio::test]
+    async fn test_inconsistent_books_are_held_stale_or_sold() {
+        use crate::market::Market;
+        use crate::orderbook::PriceLevel;
+        use chrono::Duration;
+        use rust_decimal_macros::dec;
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.signal.consistency.sell_spread_enabled = true;
+        let open = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let market = Market::new(
+            "cond",
+            "BTC",
+            dec!(100000),
+            open,
+            open + Duration::minutes(15),
+        );
+        let book = |token: &str, bid: Decimal, ask: Decimal, at: DateTime<Utc>| OrderBook {
+            token_id: token.to_string(),
+            bids: vec![PriceLevel {
+                price: bid,
+                size: dec!(100),
+            }],
+            asks: vec![PriceLevel {
+                price: ask,
+                size: dec!(100),
+            }],
+            updated_at: at,
+        };
+        let session = |yes: OrderBook, no: OrderBook| {
+            let (config, market) = (config.clone(), market.clone());
+            async move {
+                let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO));
+                engine
+                    .on_event(open, BacktestEvent::MarketOpen(market))
+                    .await
+                    .unwrap();
+                for book in [yes, no] {
+                    let at = book.updated_at;
+                    engine
+                        .on_event(at, BacktestEvent::OrderBookUpdate(book))
+                        .await
+                        .unwrap();
+                }
+                engine
+            }
+        };
+        let (first, second) = (open + Duration::seconds(1), open + Duration::seconds(2));
+
+        // Mids summing to 1.1 with no bid pair over 1: the older YES book
+        // is stale, and nothing is sold
+        let stale = session(
+            book("cond-yes", dec!(0.60), dec!(0.80), first),
+            book("cond-no", dec!(0.39), dec!(0.41), second),
+        )
+        .await;
+        assert_eq!(stale.stats().inconsistent_books, 1);
+        assert_eq!(stale.stats().sell_spreads, 0);
+        assert!(!stale.order_books().is_fresh("cond-yes", second));
+        assert!(stale.order_books().is_fresh("cond-no", second));
+
+        // Bids summing to 1.05: both sides sold, the proceeds over 1 booked
+        let sold = session(
+            book("cond-yes", dec!(0.55), dec!(0.56), first),
+            book("cond-no", dec!(0.50), dec!(0.51), second),
+        )
+        .await;
+        assert_eq!(sold.stats().sell_spreads, 1);
+        assert_eq!(sold.stats().orders, 2);
+        // 1% of the 500 bankroll in pairs, 0.05 over 1 on each
+        assert_eq!(sold.stats().realized_pnl, dec!(0.25));
+        assert_eq!(sold.bankroll(), dec!(500.25));
+    }
+
+    #[tokio::test]
+    async fn test_crossed_book_takes_the_next_snapshot() {
+        use crate::market::Market;
+        use crate::orderbook::PriceLevel;
+        use chrono::Duration;
+        use rust_decimal_macros::dec;
+
+        let config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        let open = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let market = Market::new(
+            "cond",
+            "BTC",
+            dec!(100000),
+            open,
+            open + Duration::minutes(15),
+        );
+        let book = |bid: Decimal, ask: Decimal, at: DateTime<Utc>| OrderBook {
+            token_id: "cond-yes".to_string(),
+            bids: vec![PriceLevel {
+                price: bid,
+                size: dec!(100),
+            }],
+            asks: vec![PriceLevel {
+                price: ask,
+                size: dec!(100),
+            }],
+            updated_at: at,
+        };
+        let session = |first: OrderBook| {
+            let (config, market) = (config.clone(), market.clone());
+            async move {
+                let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO));
+                engine
+                    .on_event(open, BacktestEvent::MarketOpen(market))
+                    .await
+                    .unwrap();
+                let at = first.updated_at;
+                engine
+                    .on_event(at, BacktestEvent::OrderBookUpdate(first))
+                    .await
+                    .unwrap();
+                // A snapshot taken before the held book, arriving late
+                let late = book(dec!(0.49), dec!(0.51), at - Duration::seconds(5));
+                engine
+                    .on_event(at, BacktestEvent::OrderBookUpdate(late))
+                    .await
+                    .unwrap();
+                engine
+            }
+        };
+        let at = open + Duration::seconds(10);
+
+        // Behind a sound book the late snapshot is dropped
+        let sound = session(book(dec!(0.50), dec!(0.52), at)).await;
+        assert_eq!(sound.stats().dropped_books, 1);
+        let held = sound.order_books().book("cond-yes").unwrap();
+        assert_eq!(held.best_ask(), Some(dec!(0.52)));
+
+        // A crossed book is resynced: the next snapshot replaces it
+        let crossed = session(book(dec!(0.55), dec!(0.52), at)).await;
+        assert_eq!(crossed.stats().dropped_books, 0);
+        let held = crossed.order_books().book("cond-yes").unwrap();
+        assert_eq!(held.best_ask(), Some(dec!(0.51)));
+        assert!(!held.is_crossed());
+    }
+
+    #[tokio::test]
+    async fn test_internals_shrink_as_markets_settle() {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO));
+        let mut peak = None;
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            let book = matches!(event, BacktestEvent::OrderBookUpdate(_));
+            engine.on_event(ts, event).await.unwrap();
+            if book && peak.is_none() {
+                peak = Some(engine.internals(ts));
+            }
+        }
+        let peak = peak.unwrap();
+        assert_eq!(peak.markets, 1);
+        assert!(peak.order_books >= 1, "{:?}", peak);
+        assert!(peak.price_samples > 0);
+
+        // Both windows settled: per-market state is gone, the session
+        // records for the reports remain
+        let end = engine.internals(Utc::now());
+        assert_eq!(engine.stats().markets_settled, 2);
+        assert_eq!((end.markets, end.order_books, end.book_levels), (0, 0, 0));
+        assert_eq!((end.open_positions, end.detectors), (0, 0));
+        assert!(end.session_records >= 4, "{:?}", end);
+        assert_eq!(
+            end.closed_positions,
+            engine.positions().closed_positions.len()
+        );
+        assert_eq!(end.recorder, None);
+    }
+
+    #[tokio::test]
+    async fn test_reversed_unlabeled_tokens_are_inferred() {
+        use crate::market::TokenOrientation;
+        use crate::orderbook::OrderBook;
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+
+        // Gamma returned no outcome labels; `reversed` also lists NO first
+        let session = |reversed: bool| {
+            let config = config.clone();
+            async move {
+                let dir = tempfile::tempdir().unwrap();
+                let path = dir.path().join("trade_journal.jsonl");
+                let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+                    .with_trade_journal(Journal::open(&path).unwrap());
+                for (ts, mut event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+                    if let BacktestEvent::MarketOpen(market) = &mut event {
+                        market.orientation = TokenOrientation::Assumed;
+                        if reversed {
+                            market.swap_tokens();
+                        }
+                    }
+                    engine.on_event(ts, event).await.unwrap();
+                }
+                let stray = OrderBook::new("stray-token");
+                engine
+                    .on_event(Utc::now(), BacktestEvent::OrderBookUpdate(stray))
+                    .await
+                    .unwrap();
+                let fills: Vec<_> = engine
+                    .execution()
+                    .get_fills()
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|f| (f.token_id, f.side))
+                    .collect();
+                (
+                    engine.stats().clone(),
+                    Journal::read_all(&path).unwrap(),
+                    fills,
+                )
+            }
+        };
+        let kinds = |journal: &[JournalEntry], kind: &str| -> Vec<serde_json::Value> {
+            journal
+                .iter()
+                .filter(|e| e.kind == kind)
+                .map(|e| {
+                    let mut data = e.data.clone();
+                    for key in ["order_id", "signal_id", "reversed"] {
+                        data.as_object_mut().unwrap().remove(key);
+                    }
+                    data
+                })
+                .collect()
+        };
+
+        let (stats, journal, fills) = session(false).await;
+        let (reversed_stats, reversed_journal, reversed_fills) = session(true).await;
+        assert!(stats.orders >= 1, "no orders: {:?}", stats);
+        assert_eq!(reversed_stats, stats);
+        assert_eq!(stats.unmapped_books, 1);
+
+        // Both settle at the same book; only the reversed one swaps
+        let inferred = kinds(&reversed_journal, "tokens_inferred");
+        assert_eq!(inferred.len(), 2);
+        assert_eq!(inferred, kinds(&journal, "tokens_inferred"));
+        assert!(reversed_journal
+            .iter()
+            .filter(|e| e.kind == "tokens_inferred")
+            .all(|e| e.data["reversed"] == true));
+        for kind in ["order_submitted", "position_opened", "market_settled"] {
+            assert_eq!(
+                kinds(&reversed_journal, kind),
+                kinds(&journal, kind),
+                "{} differs",
+                kind
+            );
+        }
+        // Reports read the corrected tokens back from the journal
+        let markets = crate::report::markets_from_journal(&reversed_journal);
+        assert!(markets.iter().all(|m| m.yes_token_id.ends_with("-yes")));
+        // Orders went to the token of the side they bet on
+        assert_eq!(reversed_fills, fills);
+        for (token, side) in &fills {
+            assert_eq!(token.ends_with("-yes"), *side == crate::signal::Side::Yes);
+        }
+    }
+
+    /// Run a session with a trade journal and return its entries
+    async fn journaled<E: ExecutionEngine>(config: &Config, execution: E) -> Vec<JournalEntry> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trade_journal.jsonl");
+        let mut engine =
+            TradingEngine::new(config, execution).with_trade_journal(Journal::open(&path).unwrap());
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            engine.on_event(ts, event).await.unwrap();
+        }
+        Journal::read_all(&path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_parameter_changes_are_registered_journaled_and_stamped() {
+        use crate::effective::{EffectiveConfig, Provenance, EFFECTIVE_CONFIG_FILE, SIZE_SCALE};
+        use crate::risk::{AssetExcursions, ReviewFlag, StrategyReview, STRATEGY_REVIEW_FILE};
+        use rust_decimal_macros::dec;
+        use std::collections::BTreeMap;
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.risk.review.size_scale = dec!(0.5);
+        let dir = tempfile::tempdir().unwrap();
+        let journal_path = dir.path().join("trade_journal.jsonl");
+        let registry_path = dir.path().join(EFFECTIVE_CONFIG_FILE);
+
+        // A previous session flagged BTC for review
+        let flagged = BTreeMap::from([(
+            "BTC".to_string(),
+            AssetExcursions {
+                flag: Some(ReviewFlag {
+                    raised_at: config.sim.start_time,
+                    recent: dec!(0.06),
+                    baseline: dec!(0.02),
+                    baseline_trades: 40,
+                }),
+                ..Default::default()
+            },
+        )]);
+        let review_path = dir.path().join(STRATEGY_REVIEW_FILE);
+        std::fs::write(&review_path, serde_json::to_vec(&flagged).unwrap()).unwrap();
+        let review = StrategyReview::new(config.risk.review.clone())
+            .with_state_file(&review_path)
+            .unwrap();
+
+        let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+            .with_trade_journal(Journal::open(&journal_path).unwrap())
+            .with_effective_config_file(&registry_path)
+            .with_strategy_review(review);
+        let registry = engine.effective_config();
+        assert_eq!(registry.revision, 1);
+        assert_eq!(
+            registry.get(SIZE_SCALE).unwrap().provenance,
+            Provenance::Adaptive
+        );
+
+        // Half way through, the operator clears the flag
+        let midway = config.sim.start_time + chrono::Duration::minutes(30);
+        let mut cleared = false;
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            if ts >= midway && !cleared {
+                StrategyReview::new(config.risk.review.clone())
+                    .with_state_file(&review_path)
+                    .unwrap()
+                    .request_clear("BTC")
+                    .unwrap();
+                engine.sync_strategy_review();
+                cleared = true;
+            }
+            engine.on_event(ts, event).await.unwrap();
+        }
+
+        let saved = EffectiveConfig::load(&registry_path).unwrap().unwrap();
+        for registry in [engine.effective_config(), &saved] {
+            assert_eq!(registry.revision, 2);
+            let scale = registry.get(SIZE_SCALE).unwrap();
+            assert_eq!(scale.provenance, Provenance::Ctl);
+            assert_eq!(scale.value, serde_json::json!("1"));
+            assert_eq!(
+                registry.get("risk.review.size_scale").unwrap().provenance,
+                Provenance::Default
+            );
+        }
+
+        let entries = Journal::read_all(&journal_path).unwrap();
+        let changes: Vec<_> = entries
+            .iter()
+            .filter(|e| e.kind == "parameter_changed")
+            .map(|e| (e.data["provenance"].clone(), e.data["new"].clone()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (serde_json::json!("adaptive"), serde_json::json!("0.5")),
+                (serde_json::json!("ctl"), serde_json::json!("1")),
+            ]
+        );
+        // Every signal carries the revision it was sized under
+        let clear = entries
+            .iter()
+            .position(|e| e.kind == "parameter_changed" && e.data["provenance"] == "ctl")
+            .unwrap();
+        let signals: Vec<_> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.kind == "signal_emitted")
+            .map(|(i, e)| (i < clear, e.data["config_revision"].as_u64().unwrap()))
+            .collect();
+        assert!(signals.contains(&(true, 1)), "{:?}", signals);
+        assert!(signals.contains(&(false, 2)), "{:?}", signals);
+        assert!(signals
+            .iter()
+            .all(|(before, revision)| *revision == if *before { 1 } else { 2 }));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_journals_like_paper() {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+
+        // A cost-free paper engine fills at the order price, like dry-run
+        let paper = journaled(&config, PaperEngine::new(Decimal::ZERO)).await;
+        let dry_run = journaled(&config, NoopEngine::new()).await;
+
+        assert!(paper.iter().any(|e| e.kind == "position_opened"));
+        assert_eq!(paper.len(), dry_run.len());
+        for (paper, dry_run) in paper.iter().zip(&dry_run) {
+            assert_eq!(paper.kind, dry_run.kind);
+            assert_eq!(paper.data["engine"], "paper");
+            assert_eq!(dry_run.data["engine"], "dry_run");
+
+            // Exchange order ids are random per session; signal and client
+            // order ids replay identically. The simulated flag is the engine's
+            let strip = |entry: &JournalEntry| {
+                let mut data = entry.data.clone();
+                for key in ["engine", "order_id", "simulated"] {
+                    data.as_object_mut().unwrap().remove(key);
+                }
+                data
+            };
+            assert_eq!(strip(paper), strip(dry_run), "{} differs", paper.kind);
+            if paper.kind == "order_submitted" {
+                assert!(paper.data["client_order_id"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with(paper.data["signal_id"].as_str().unwrap()));
+            }
+            if paper.kind == "position_opened" {
+                assert_eq!(dry_run.data["simulated"], true);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_camouflaged_entries_are_journaled() {
+        use crate::execution::Camouflage;
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let strip = |entries: &[JournalEntry]| {
+            entries
+                .iter()
+                .map(|e| {
+                    let mut data = e.data.clone();
+                    data.as_object_mut().unwrap().remove("order_id");
+                    (e.kind.clone(), data)
+                })
+                .collect::<Vec<_>>()
+        };
+        let plain = journaled(&config, PaperEngine::new(Decimal::ZERO)).await;
+
+        // Switched off, the other settings change nothing
+        let camouflage = &mut config.execution.camouflage;
+        camouflage.max_delay_ms = DurationConfig::from_millis(3_000);
+        camouflage.split_probability = Decimal::ONE;
+        let disabled = journaled(&config, PaperEngine::new(Decimal::ZERO)).await;
+        assert_eq!(strip(&disabled), strip(&plain));
+        assert!(!plain.iter().any(|e| e.kind == "camouflage_applied"));
+
+        config.execution.camouflage.enabled = true;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trade_journal.jsonl");
+        let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+            .with_trade_journal(Journal::open(&path).unwrap())
+            .with_camouflage(Camouflage::seeded(&config.execution.camouflage, 7));
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            engine.on_event(ts, event).await.unwrap();
+        }
+        let entries = Journal::read_all(&path).unwrap();
+
+        // Every entry decided is journaled with what was drawn for it, and
+        // every order submitted belongs to one of them
+        let of_kind = |kind: &'static str| entries.iter().filter(move |e| e.kind == kind);
+        let applied: Vec<_> = of_kind("camouflage_applied").collect();
+        assert_eq!(applied.len(), of_kind("signal_emitted").count());
+        let submitted: Vec<_> = of_kind("order_submitted").collect();
+        assert!(!submitted.is_empty());
+        let mut split = false;
+        for entry in &applied {
+            let data = &entry.data;
+            let decimal = |v: &serde_json::Value| v.as_str().unwrap().parse::<Decimal>().unwrap();
+            assert!(data["delay_ms"].as_u64().unwrap() <= 3_000);
+            let clips: Vec<Decimal> = data["clips"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(decimal)
+                .collect();
+            assert_eq!(
+                clips.iter().sum::<Decimal>(),
+                decimal(&data["jittered_size"])
+            );
+            let ids = data["client_order_ids"].as_array().unwrap();
+            split |= ids.len() == 2;
+            for (id, clip) in ids.iter().zip(&clips) {
+                let order = submitted
+                    .iter()
+                    .find(|e| &e.data["client_order_id"] == id)
+                    .unwrap();
+                assert_eq!(order.data["signal_id"], data["signal_id"]);
+                assert_eq!(decimal(&order.data["size"]), *clip);
+            }
+        }
+        assert!(split, "no entry was split");
+        let ids: usize = applied
+            .iter()
+            .map(|e| e.data["client_order_ids"].as_array().unwrap().len())
+            .sum();
+        assert_eq!(ids, submitted.len());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_signals_go_to_the_best_score() {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        config.risk.max_concurrent_positions = 1;
+
+        // Every window runs alongside a twin whose YES book sits a cent
+        // higher, so its NO, the side the first signal buys, is cheaper
+        let twin = |id: &str| format!("{}-twin", id);
+        let mut events = vec![];
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            let copy = match &event {
+                BacktestEvent::MarketOpen(market) | BacktestEvent::MarketClose(market) => {
+                    let mut market = market.clone();
+                    market.condition_id = twin(&market.condition_id);
+                    market.yes_token_id = twin(&market.yes_token_id);
+                    market.no_token_id = twin(&market.no_token_id);
+                    Some(match event {
+                        BacktestEvent::MarketOpen(_) => BacktestEvent::MarketOpen(market),
+                        _ => BacktestEvent::MarketClose(market),
+                    })
+                }
+                BacktestEvent::OrderBookUpdate(book) => {
+                    let mut book = book.clone();
+                    book.token_id = twin(&book.token_id);
+                    for level in book.bids.iter_mut().chain(&mut book.asks) {
+                        level.price += dec!(0.01);
+                    }
+                    Some(BacktestEvent::OrderBookUpdate(book))
+                }
+                _ => None,
+            };
+            events.push((ts, event));
+            events.extend(copy.map(|copy| (ts, copy)));
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trade_journal.jsonl");
+        let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+            .with_trade_journal(Journal::open(&path).unwrap());
+        for (ts, event) in events {
+            engine.on_event(ts, event).await.unwrap();
+        }
+        let journal = Journal::read_all(&path).unwrap();
+
+        // The original fires first, yet the twin's better edge takes the slot
+        let ranked = journal
+            .iter()
+            .find(|e| e.kind == "allocation_ranked")
+            .expect("signals ranked");
+        let ranking = ranked.data["ranking"].as_array().unwrap();
+        assert_eq!(ranking.len(), 2);
+        let best = ranking[0]["market_id"].as_str().unwrap();
+        assert!(best.ends_with("-twin"), "{}", best);
+        assert!(ranking[0]["skipped"].is_null());
+        assert_eq!(ranking[1]["skipped"], "no_slot");
+        let opened = journal
+            .iter()
+            .find(|e| e.kind == "position_opened")
+            .unwrap();
+        assert_eq!(opened.data["market_id"], best);
+        assert!(engine.stats().outranked >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_next_window_is_prepared_before_open() {
+        use crate::market::{event_slug, Market, PreOpenPreparer, WindowLookup};
+
+        /// Lists every window like the synthetic market source opens it
+        struct Listed;
+
+        #[async_trait::async_trait]
+        impl WindowLookup for Listed {
+            async fn window_market(
+                &self,
+                asset: &str,
+                open: DateTime<Utc>,
+            ) -> anyhow::Result<Option<Market>> {
+                let slug = event_slug(asset, open);
+                Ok(Some(Market {
+                    condition_id: slug.clone(),
+                    asset: asset.to_string(),
+                    yes_token_id: format!("{}-yes", slug),
+                    no_token_id: format!("{}-no", slug),
+                    open_price: Decimal::ZERO,
+                    open_time: open,
+                    close_time: open + chrono::Duration::minutes(15),
+                    group_id: None,
+                    orientation: Default::default(),
+                }))
+            }
+        }
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trade_journal.jsonl");
+        let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+            .with_trade_journal(Journal::open(&path).unwrap());
+        let mut preparer = PreOpenPreparer::new(vec!["BTC".to_string()], 60);
+
+        // The simulated clock crosses the 00:15 and 00:30 window boundaries
+        let boundary = config.sim.start_time + chrono::Duration::minutes(15);
+        let token = format!("{}-yes", event_slug("BTC", boundary));
+        let mut strike = None;
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            for market in preparer.prepare(&Listed, ts).await {
+                engine
+                    .on_event(ts, BacktestEvent::MarketOpen(market))
+                    .await
+                    .unwrap();
+            }
+            if ts < boundary {
+                assert_eq!(
+                    preparer.is_subscribed(&token),
+                    ts >= boundary - chrono::Duration::seconds(60)
+                );
+            }
+            if let BacktestEvent::PriceTick(tick) = &event {
+                if ts >= boundary && strike.is_none() {
+                    strike = Some(tick.price);
+                }
+            }
+            engine.on_event(ts, event).await.unwrap();
+        }
+
+        let stats = engine.stats().clone();
+        assert!(stats.orders >= 1, "no orders: {:?}", stats);
+        assert_eq!(stats.preopened, 2);
+        assert_eq!(stats.markets_opened, 3);
+        assert_eq!(stats.markets_settled, 2);
+        assert_eq!(stats.slowest_first_book_secs, Some(0));
+
+        // The strike comes from the first tick of the window, as it does
+        // for a market discovered at open, so the session trades the same
+        let journal = Journal::read_all(&path).unwrap();
+        let set: Vec<_> = journal.iter().filter(|e| e.kind == "strike_set").collect();
+        assert_eq!(set.len(), 1);
+        assert_eq!(set[0].data["market_id"], event_slug("BTC", boundary));
+        assert_eq!(
+            set[0].data["open_price"],
+            serde_json::json!(strike.unwrap())
+        );
+        let discovered = journaled(&config, PaperEngine::new(Decimal::ZERO)).await;
+        for kind in ["order_submitted", "position_opened", "market_settled"] {
+            let data = |journal: &[JournalEntry]| -> Vec<serde_json::Value> {
+                journal
+                    .iter()
+                    .filter(|e| e.kind == kind)
+                    .map(|e| {
+                        let mut data = e.data.clone();
+                        data.as_object_mut().unwrap().remove("order_id");
+                        data
+                    })
+                    .collect()
+            };
+            assert_eq!(data(&journal), data(&discovered), "{} differs", kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_halt_withholds_orders_until_acknowledged() {
+        use crate::risk::{DrawdownMonitor, HaltReason, HaltRecord, HaltStore};
+        use rust_decimal_macros::dec;
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let dir = tempfile::tempdir().unwrap();
+        let monitor = DrawdownMonitor::new(dec!(500));
+        let halt = HaltRecord::new(
+            HaltReason::MaxDrawdownReached(dec!(0.2)),
+            &monitor,
+            Utc::now(),
+        );
+        HaltStore::new(dir.path()).record(&halt).unwrap();
+
+        // Each session starts the way `run` does: from the store on disk
+        let session = |config: Config, path: std::path::PathBuf| async move {
+            let store = HaltStore::new(&path);
+            let pending = store.pending().unwrap();
+            let mut engine =
+                TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO)).with_halt_store(store);
+            if let Some(halt) = pending.into_iter().next() {
+                engine = engine.with_pending_halt(halt);
+            }
+            for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+                engine.on_event(ts, event).await.unwrap();
+            }
+            engine.stats().clone()
+        };
+
+        let halted = session(config.clone(), dir.path().to_path_buf()).await;
+        assert!(halted.signals >= 1);
+        assert_eq!(halted.orders, 0);
+        assert_eq!(
+            halted.halt.as_ref().map(|h| h.id.as_str()),
+            Some(halt.id.as_str())
+        );
+        assert!(halted.to_string().contains("ctl ack-halt"));
+
+        HaltStore::new(dir.path())
+            .acknowledge(&halt.id, Utc::now())
+            .unwrap();
+        let resumed = session(config, dir.path().to_path_buf()).await;
+        assert!(resumed.orders >= 1);
+        assert!(resumed.halt.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_withholds_entries_and_flattens_on_close() {
+        use crate::risk::{StrategySchedule, TradingWindow};
+        use chrono::{NaiveTime, Timelike};
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        // The run loop re-evaluates the schedule every second
+        let session = |window: (u32, u32), flatten_on_close: bool| {
+            let mut config = config.clone();
+            config.schedule.default = StrategySchedule {
+                windows: vec![TradingWindow {
+                    days: vec![],
+                    start: NaiveTime::from_hms_opt(0, window.0, 0).unwrap(),
+                    end: NaiveTime::from_hms_opt(0, window.1, 0).unwrap(),
+                }],
+                flatten_on_close,
+            };
+            async move {
+                let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO));
+                let mut exits_at_close = None;
+                for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+                    engine.sync_schedule(ts).await.unwrap();
+                    if exits_at_close.is_none() && ts.minute() >= window.1 {
+                        exits_at_close = Some(engine.stats().exits);
+                    }
+                    engine.on_event(ts, event).await.unwrap();
+                }
+                (engine.stats().clone(), exits_at_close)
+            }
+        };
+
+        // Windows are UTC times of day; the simulation runs 00:00 to 00:30
+        let always = session((0, 0), false).await.0;
+        assert!(always.orders >= 1, "{:?}", always);
+        assert_eq!(always.off_schedule, 0);
+
+        let (closed, _) = session((40, 50), false).await;
+        assert!(closed.signals >= 1);
+        assert_eq!(closed.orders, 0);
+        assert!(closed.off_schedule >= 1);
+        assert!(closed.to_string().contains("outside the trading schedule"));
+
+        // Positions opened in the window are sold the moment it ends, and
+        // held to settlement without flatten_on_close
+        let (held, _) = session((0, 10), false).await;
+        assert!(held.orders >= 1);
+        assert_eq!(held.exits, 0);
+        let (flat, exits_at_close) = session((0, 10), true).await;
+        assert_eq!(exits_at_close, Some(1), "{:?}", flat);
+        assert_eq!(flat.exits, 1);
+        assert!(flat.off_schedule >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_loss_halt_persists_until_acknowledged() {
+        use crate::market::Market;
+        use crate::risk::{LossCooldown, LOSS_COOLDOWN_FILE};
+        use rust_decimal_macros::dec;
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        config.risk.loss_cooldown.halt_after_losses = 1;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOSS_COOLDOWN_FILE);
+        let open = || {
+            LossCooldown::new(config.risk.loss_cooldown.clone())
+                .with_state_file(&path)
+                .unwrap()
+        };
+
+        // A previous session lost on BTC and halted it
+        let lost = Market {
+            condition_id: "lost".to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100000),
+            open_time: config.sim.start_time - chrono::Duration::minutes(15),
+            close_time: config.sim.start_time,
+            group_id: None,
+            orientation: Default::default(),
+        };
+        open().on_settled("BTC", &lost, dec!(-10), config.sim.start_time);
+
+        let session = |cooldown: LossCooldown| {
+            let config = config.clone();
+            async move {
+                let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+                    .with_loss_cooldown(cooldown);
+                for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+                    engine.on_event(ts, event).await.unwrap();
+                }
+                engine.stats().clone()
+            }
+        };
+
+        let halted = session(open()).await;
+        assert!(halted.signals >= 1);
+        assert_eq!(halted.orders, 0);
+        assert!(halted.cooled_down >= 1);
+
+        open().acknowledge("BTC", Utc::now()).unwrap();
+        let resumed = session(open()).await;
+        assert!(resumed.orders >= 1);
+        assert_eq!(resumed.cooled_down, 0);
+    }
+
+    #[tokio::test]
+    async fn test_daily_rate_cap_survives_a_restart() {
+        use crate::risk::{RateLimiter, RATE_CAPS_FILE};
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        config.risk.rate_caps.per_day = 1;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(RATE_CAPS_FILE);
+        let open = || {
+            RateLimiter::new(config.risk.rate_caps.clone())
+                .with_state_file(&path)
+                .unwrap()
+        };
+        let journal_path = dir.path().join("trade_journal.jsonl");
+
+        let session = |rate: RateLimiter| {
+            let config = config.clone();
+            let journal = Journal::open(&journal_path).unwrap();
+            async move {
+                let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+                    .with_rate_limiter(rate)
+                    .with_trade_journal(journal);
+                for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+                    engine.on_event(ts, event).await.unwrap();
+                }
+                engine.stats().clone()
+            }
+        };
+
+        // The first window trades, the second is capped
+        let first = session(open()).await;
+        assert_eq!(first.orders, 1);
+        assert!(first.rate_capped >= 1);
+        assert!(first.to_string().contains("Entries withheld by rate caps"));
+        let capped: Vec<JournalEntry> = Journal::read_all(&journal_path)
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.kind == "rate_cap_exceeded")
+            .collect();
+        assert_eq!(capped.len(), 1);
+        assert_eq!(capped[0].data["cap"], "day");
+
+        // A restart on the same day can't trade again, and the cap was
+        // already reported
+        let restarted = session(open()).await;
+        assert_eq!(restarted.orders, 0);
+        assert!(restarted.rate_capped >= 1);
+        assert!(!open().notify(config.sim.start_time));
+    }
+
+    /// Execution whose every submission fails
+    struct Down;
+
+    #[async_trait::async_trait]
+    impl ExecutionEngine for Down {
+        fn name(&self) -> &'static str {
+            "down"
+        }
+
+        async fn submit_order(&self, _order: Order) -> anyhow::Result<OrderId> {
+            anyhow::bail!("CLOB unavailable")
+        }
+
+        async fn cancel_order(&self, _id: OrderId) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn get_fills(&self) -> anyhow::Result<Vec<Fill>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failing_execution_opens_circuit() {
+        use crate::engine::EXECUTION_HEALTH_COMPONENT;
+        use crate::telemetry::{HealthRegistry, HealthState};
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trade_journal.jsonl");
+        let health = HealthRegistry::new();
+        let mut engine = TradingEngine::new(&config, Down)
+            .with_trade_journal(Journal::open(&path).unwrap())
+            .with_health(health.clone());
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            engine.on_event(ts, event).await.unwrap();
+        }
+
+        // Three failures open the circuit; every later probe fails and
+        // reopens it, and orders in between are suppressed
+        let stats = engine.stats();
+        assert_eq!(stats.orders, 0);
+        assert!(stats.circuit_trips >= 2, "{:?}", stats);
+        assert_eq!(stats.submit_failures, 3 + stats.circuit_trips - 1);
+        assert!(stats.suppressed >= 1);
+        assert!(stats.to_string().contains("orders suppressed"));
+
+        let kinds: Vec<_> = Journal::read_all(&path)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert!(kinds.iter().any(|k| k == "circuit_opened"));
+        assert!(kinds.iter().any(|k| k == "order_suppressed"));
+        assert!(!kinds.iter().any(|k| k == "circuit_closed"));
+
+        let component = health.get(EXECUTION_HEALTH_COMPONENT).unwrap();
+        assert_ne!(component.state, HealthState::Healthy);
+        assert!(component.reason.unwrap().contains("CLOB unavailable"));
+    }
+
+    #[tokio::test]
+    async fn test_intents_in_flight_at_a_crash_are_repaired() {
+        use crate::execution::{
+            IntentLog, IntentStatus, OrderAction, OrderIntent, OrderType, INTENT_LOG_FILE,
+        };
+        use crate::market::Market;
+        use crate::signal::Side;
+        use rust_decimal_macros::dec;
+
+        let config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join(INTENT_LOG_FILE);
+        let now = Utc::now();
+        let market = |id: &str| Market {
+            condition_id: id.to_string(),
+            asset: "BTC".to_string(),
+            yes_token_id: format!("{}-yes", id),
+            no_token_id: format!("{}-no", id),
+            open_price: dec!(100000),
+            open_time: now,
+            close_time: now + chrono::Duration::minutes(15),
+            group_id: None,
+            orientation: Default::default(),
+        };
+        let order = |id: &str| Order {
+            token_id: format!("{}-yes", id),
+            side: Side::Yes,
+            price: dec!(0.55),
+            size: dec!(10),
+            order_type: OrderType::Limit,
+            action: OrderAction::Buy,
+            client_order_id: Some(format!("{}-client", id)),
+        };
+
+        // The previous session logged two intents and died before either
+        // outcome: m1's order reached the engine and filled, m2's never did
+        let paper = PaperEngine::new(Decimal::ZERO);
+        let log = IntentLog::open(&log_path).unwrap();
+        for id in ["m1", "m2"] {
+            let intent = OrderIntent::new(&market(id), &order(id), now).unwrap();
+            log.record_intent(&intent).unwrap();
+        }
+        paper.submit_order(order("m1")).await.unwrap();
+        drop(log);
+
+        let journal_path = dir.path().join("trade_journal.jsonl");
+        let mut engine = TradingEngine::new(&config, paper)
+            .with_trade_journal(Journal::open(&journal_path).unwrap())
+            .with_intent_log(IntentLog::open(&log_path).unwrap());
+        let repaired = engine.recover_intents(now).await.unwrap();
+
+        let statuses: Vec<_> = repaired.iter().map(|o| o.status).collect();
+        assert!(statuses.contains(&IntentStatus::Filled));
+        assert!(statuses.contains(&IntentStatus::NotFound));
+        assert_eq!(engine.positions().open_count(), 1);
+        assert!(engine.positions().exposure_for_token("m1-yes").is_some());
+        let repairs = Journal::read_all(&journal_path)
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.kind == "intent_repaired")
+            .count();
+        assert_eq!(repairs, 2);
+
+        // Outcomes were logged, so a second restart has nothing to repair
+        assert!(IntentLog::open(&log_path)
+            .unwrap()
+            .pending()
+            .unwrap()
+            .is_empty());
+        assert!(engine.recover_intents(now).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sessions_log_an_outcome_for_every_intent() {
+        use crate::execution::{IntentLog, INTENT_LOG_FILE};
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INTENT_LOG_FILE);
+        let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+            .with_intent_log(IntentLog::open(&path).unwrap());
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            engine.on_event(ts, event).await.unwrap();
+        }
+
+        assert!(engine.stats().orders >= 1);
+        let intents = Journal::read_all(&path)
+            .unwrap()
+            .iter()
+            .filter(|e| e.kind == "order_intent")
+            .count() as u64;
+        assert_eq!(intents, engine.stats().orders);
+        assert!(IntentLog::open(&path)
+            .unwrap()
+            .pending()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_synthetic_canary_go_and_no_go() {
+        use crate::execution::{IntentLog, INTENT_LOG_FILE};
+        use crate::feed::LagHistogram;
+        use crate::report::{CanaryConfig, CanaryMetrics, CanaryReport};
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let dir = tempfile::tempdir().unwrap();
+        let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+            .with_intent_log(IntentLog::open(dir.path().join(INTENT_LOG_FILE)).unwrap());
+        let mut lag = LagHistogram::new();
+        let mut span = None;
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            if let BacktestEvent::PriceTick(tick) = &event {
+                lag.record((ts - tick.exchange_ts).num_milliseconds());
+            }
+            span = Some((span.map_or(ts, |(start, _)| start), ts));
+            engine.on_event(ts, event).await.unwrap();
+        }
+        let (started, finished) = span.unwrap();
+        let metrics = CanaryMetrics::from_engine(&engine, &lag, true).unwrap();
+        assert_eq!(metrics.signals, engine.stats().signals);
+        assert_eq!(metrics.settled, engine.positions().closed_count());
+        assert_eq!(metrics.tick_lag_p95_ms, Some(0));
+
+        let criteria = CanaryConfig::default();
+        let report = CanaryReport::evaluate(&criteria, metrics.clone(), started, finished);
+        assert!(report.passed, "{}", report);
+
+        // Too few signals, and stopped before the period ended
+        let strict = CanaryConfig {
+            min_signals: metrics.signals + 1,
+            ..criteria
+        };
+        let stopped = CanaryMetrics {
+            completed: false,
+            ..metrics
+        };
+        let report = CanaryReport::evaluate(&strict, stopped, started, finished);
+        assert!(!report.passed);
+        let failed: Vec<&str> = report.failures().map(|c| c.name.as_str()).collect();
+        assert_eq!(failed, ["completed", "min_signals"]);
+    }
+
+    /// Execution whose orders rest unfilled until cancelled
+    #[derive(Clone, Default)]
+    struct Resting {
+        cancelled: std::sync::Arc<std::sync::Mutex<Vec<OrderId>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ExecutionEngine for Resting {
+        fn name(&self) -> &'static str {
+            "resting"
+        }
+
+        async fn submit_order(&self, _order: Order) -> anyhow::Result<OrderId> {
+            Ok(OrderId::new_v4())
+        }
+
+        async fn cancel_order(&self, id: OrderId) -> anyhow::Result<()> {
+            self.cancelled.lock().unwrap().push(id);
+            Ok(())
+        }
+
+        async fn get_fills(&self) -> anyhow::Result<Vec<Fill>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover_never_has_two_leaders_submitting() {
+        use crate::leader::{InMemoryElector, Leadership, Role};
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(120);
+        let elector = InMemoryElector::new();
+        let leadership = |instance: &str| {
+            Leadership::new(&config.leader, Box::new(elector.clone()), instance).unwrap()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let (a_path, b_path) = (dir.path().join("a.jsonl"), dir.path().join("b.jsonl"));
+        let resting = Resting::default();
+        let mut a = TradingEngine::new(&config, resting.clone())
+            .with_trade_journal(Journal::open(&a_path).unwrap())
+            .with_leadership(leadership("a"));
+        let mut b = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+            .with_trade_journal(Journal::open(&b_path).unwrap())
+            .with_leadership(leadership("b"));
+
+        // a leads until its first order rests, then stalls for longer than
+        // the lease lasts and comes back
+        let stall = chrono::Duration::seconds(30);
+        let mut stalled: Option<DateTime<Utc>> = None;
+        let mut promoted = None;
+        let mut submitted: Vec<(DateTime<Utc>, &str)> = vec![];
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            if stalled.is_none_or(|from| ts < from || ts >= from + stall) {
+                let orders = a.stats().orders;
+                a.on_event(ts, event.clone()).await.unwrap();
+                if a.stats().orders > orders {
+                    submitted.push((ts, "a"));
+                    stalled.get_or_insert(ts + chrono::Duration::seconds(1));
+                }
+            }
+            let orders = b.stats().orders;
+            b.on_event(ts, event).await.unwrap();
+            if b.stats().orders > orders {
+                submitted.push((ts, "b"));
+            }
+            if b.stats().promotions > 0 {
+                promoted.get_or_insert(ts);
+            }
+        }
+
+        // a submitted only before b took over, b only after
+        let stalled = stalled.expect("a never traded");
+        let promoted = promoted.expect("b never took over");
+        assert!(promoted >= stalled, "{} < {}", promoted, stalled);
+        assert!(
+            submitted.iter().any(|(_, who)| *who == "b"),
+            "{:?}",
+            submitted
+        );
+        for (ts, who) in &submitted {
+            match *who {
+                "a" => assert!(*ts < promoted, "a submitted at {} after b led", ts),
+                _ => assert!(*ts >= promoted, "b submitted at {} while standby", ts),
+            }
+        }
+        assert_eq!((a.stats().promotions, a.stats().demotions), (1, 1));
+        assert_eq!((b.stats().promotions, b.stats().demotions), (1, 0));
+        assert_eq!(a.leadership().unwrap().role(), Role::Standby);
+        assert_eq!(b.stats().role, Some(Role::Leader));
+        assert!(a.stats().to_string().contains("Role: standby"));
+
+        // a cancelled its resting order on demotion; b journaled as standby
+        let a_entries = Journal::read_all(&a_path).unwrap();
+        let demoted = a_entries
+            .iter()
+            .find(|e| e.kind == "leader_demoted")
+            .expect("a journaled no demotion");
+        assert_eq!(demoted.data["holder"], "b");
+        assert_eq!(demoted.data["cancelled"], 1);
+        assert_eq!(resting.cancelled.lock().unwrap().len(), 1);
+        let b_entries = Journal::read_all(&b_path).unwrap();
+        assert!(b_entries
+            .iter()
+            .any(|e| e.kind == "order_withheld" && e.data["standby"] == true));
+        assert!(b_entries.iter().any(|e| e.kind == "leader_promoted"));
+    }
+
+    #[tokio::test]
+    async fn test_clean_session_reconciles() {
+        use crate::report::{JournalLedger, PnlReconciler, DEFAULT_RECONCILE_TOLERANCE};
+
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trade_journal.jsonl");
+        let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+            .with_trade_journal(Journal::open(&path).unwrap());
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            engine.on_event(ts, event).await.unwrap();
+        }
+        let fills = engine.execution().get_fills().await.unwrap();
+        assert!(!fills.is_empty());
+
+        let ledger = JournalLedger::from_entries(&Journal::read_all(&path).unwrap());
+        let reconciliation =
+            PnlReconciler::new(&fills, engine.markets_seen(), engine.settlements())
+                .with_tracker(engine.positions())
+                .with_journal(&ledger)
+                .run(DEFAULT_RECONCILE_TOLERANCE)
+                .unwrap();
+        assert!(reconciliation.passed, "{}", reconciliation);
+        assert_eq!(reconciliation.fills.opened, fills.len() as u64);
+        assert_eq!(reconciliation.tracker, Some(reconciliation.fills.clone()));
+        assert_eq!(
+            reconciliation.fills.realized_pnl,
+            engine.positions().realized_pnl()
+        );
+
+        // Every settled trade is on the tape with the signal that opened it
+        let tape = engine.trade_tape();
+        assert!(!tape.is_empty());
+        assert!(tape.iter().all(|row| row.entry.is_some()));
+        let tape_pnl: Decimal = tape.iter().map(|row| row.realized_pnl).sum();
+        assert_eq!(tape_pnl, engine.positions().realized_pnl());
+
+        // ... and attributed against marks sampled while it was held
+        let attribution = engine.pnl_attribution();
+        assert_eq!(attribution.len(), tape.len());
+        assert!(attribution.iter().any(|a| a.last_mark.is_some()));
+        for a in attribution {
+            assert!(a.marks.iter().all(|(at, _)| *at <= a.exit_time));
+            assert_eq!(
+                a.convergence_pnl + a.resolution_pnl - a.fees,
+                a.realized_pnl
+            );
+        }
+        let stats = engine.stats();
+        assert_eq!(stats.attribution.positions, tape.len() as u64);
+        assert_eq!(stats.attribution.realized, tape_pnl);
+        assert!(stats.to_string().contains("P&L attribution: expected"));
+    }
+
+    /// A journaled 30-minute session, ended with its settlements pending
+    async fn provisional_session(path: &std::path::Path) -> TradingEngine<PaperEngine> {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let mut engine = TradingEngine::new(&config, PaperEngine::new(Decimal::ZERO))
+            .with_trade_journal(Journal::open(path).unwrap());
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            engine.on_event(ts, event).await.unwrap();
+        }
+        engine
+    }
+
+    #[tokio::test]
+    async fn test_contradicted_resolution_is_rebooked() {
+        use crate::report::{JournalLedger, PnlReconciler, DEFAULT_RECONCILE_TOLERANCE};
+        use crate::risk::ResolutionStatus;
+        use crate::signal::Side;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trade_journal.jsonl");
+        let mut engine = provisional_session(&path).await;
+        let pending = engine.pending_resolutions();
+        assert!(!pending.is_empty());
+        let before = engine.stats().clone();
+        assert_eq!(before.resolutions_pending, pending.len());
+        assert!(before.to_string().contains("PROVISIONAL"), "{}", before);
+
+        let market = &pending[0];
+        let settled = engine
+            .settlements()
+            .iter()
+            .find(|s| s.market_id == market.condition_id)
+            .unwrap()
+            .clone();
+        assert_eq!(settled.status, ResolutionStatus::Provisional);
+        let official = match settled.winner {
+            Side::Yes => Side::No,
+            Side::No => Side::Yes,
+        };
+        // Each share of the market flips between paying 1 and 0
+        let shares: Decimal = engine
+            .positions()
+            .closed_positions
+            .iter()
+            .filter(|c| c.position.market.condition_id == market.condition_id)
+            .map(|c| match c.position.side == official {
+                true => c.position.size,
+                false => -c.position.size,
+            })
+            .sum();
+        engine.resolve(Utc::now(), &market.condition_id, official);
+
+        let stats = engine.stats().clone();
+        assert_eq!(stats.resolutions_corrected, 1);
+        assert_eq!(stats.resolutions_pending, pending.len() - 1);
+        assert_eq!(stats.realized_pnl, before.realized_pnl + shares);
+        assert_eq!(stats.realized_pnl, engine.positions().realized_pnl());
+        let settlement = engine
+            .settlements()
+            .iter()
+            .find(|s| s.market_id == market.condition_id)
+            .unwrap();
+        assert_eq!(
+            (settlement.winner, settlement.status),
+            (official, ResolutionStatus::Corrected)
+        );
+
+        // Tape and attribution follow the official payout
+        let tape_pnl: Decimal = engine.trade_tape().iter().map(|r| r.realized_pnl).sum();
+        assert_eq!(tape_pnl, stats.realized_pnl);
+        assert_eq!(stats.attribution.realized, tape_pnl);
+        for a in engine.pnl_attribution() {
+            assert_eq!(
+                a.convergence_pnl + a.resolution_pnl - a.fees,
+                a.realized_pnl
+            );
+        }
+
+        // The journal records both outcomes and still reconciles
+        let entries = Journal::read_all(&path).unwrap();
+        let corrected = entries
+            .iter()
+            .find(|e| e.kind == "resolution_corrected")
+            .unwrap();
+        assert_eq!(
+            corrected.data["provisional"],
+            serde_json::json!(settled.winner)
+        );
+        assert_eq!(corrected.data["official"], serde_json::json!(official));
+        let fills = engine.execution().get_fills().await.unwrap();
+        let reconciliation =
+            PnlReconciler::new(&fills, engine.markets_seen(), engine.settlements())
+                .with_tracker(engine.positions())
+                .with_journal(&JournalLedger::from_entries(&entries))
+                .run(DEFAULT_RECONCILE_TOLERANCE)
+                .unwrap();
+        assert!(reconciliation.passed, "{}", reconciliation);
+
+        // A second answer for the same market changes nothing
+        engine.resolve(Utc::now(), &market.condition_id, settled.winner);
+        assert_eq!(engine.stats().realized_pnl, stats.realized_pnl);
+    }
+
+    #[tokio::test]
+    async fn test_confirmed_and_timed_out_resolutions_keep_pnl() {
+        use crate::risk::{ResolutionStatus, DEFAULT_CONFIRMATION_WINDOW_SECS};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trade_journal.jsonl");
+        let mut engine = provisional_session(&path).await;
+        let pending = engine.pending_resolutions();
+        let before = engine.stats().clone();
+        assert!(!pending.is_empty());
+
+        let first = &pending[0];
+        let winner = engine
+            .settlements()
+            .iter()
+            .find(|s| s.market_id == first.condition_id)
+            .unwrap()
+            .winner;
+        engine.resolve(Utc::now(), &first.condition_id, winner);
+        assert_eq!(engine.stats().resolutions_confirmed, 1);
+
+        // Nothing heard for the rest within the window
+        let last_settled = engine.settlements().iter().map(|s| s.at).max().unwrap();
+        engine.expire_resolutions(last_settled);
+        assert_eq!(engine.stats().resolutions_unconfirmed, 0);
+        engine.expire_resolutions(
+            last_settled + chrono::Duration::seconds(DEFAULT_CONFIRMATION_WINDOW_SECS as i64),
+        );
+
+        let stats = engine.stats();
+        assert_eq!(stats.resolutions_unconfirmed, pending.len() as u64 - 1);
+        assert_eq!(
+            (stats.resolutions_pending, stats.provisional_pnl),
+            (0, Decimal::ZERO)
+        );
+        assert_eq!(stats.realized_pnl, before.realized_pnl);
+        assert!(!stats.to_string().contains("PROVISIONAL"));
+        assert!(engine
+            .settlements()
+            .iter()
+            .all(|s| s.status != ResolutionStatus::Provisional));
+        let kinds: Vec<_> = Journal::read_all(&path)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .filter(|k| k.starts_with("resolution_"))
+            .collect();
+        assert_eq!(kinds.len(), pending.len());
+        assert_eq!(kinds[0], "resolution_confirmed");
+    }
+
+    #[test]
+    fn test_books_lag_spot() {
+        let config = SimConfig {
+            duration_mins: DurationConfig::from_mins(15),
+            ..Default::default()
+        };
+        let events: Vec<_> = Simulation::new("BTCUSDT", "BTC", &config).collect();
+        let opens = events
+            .iter()
+            .filter(|(_, e)| matches!(e, BacktestEvent::MarketOpen(_)))
+            .count();
+        let closes = events
+            .iter()
+            .filter(|(_, e)| matches!(e, BacktestEvent::MarketClose(_)))
+            .count();
+        assert_eq!((opens, closes), (1, 1));
+        assert!(events.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert!(events.iter().any(|(_, e)| matches!(
+            e,
+            BacktestEvent::OrderBookUpdate(book) if book.best_bid() < book.best_ask()
+        )));
+    }
+
+    /// Run a session, checking after every event that no money appeared or
+    /// vanished between the paper fills, the tracker and the bankroll
+    async fn conserves_money(config: &Config) -> Result<EngineStats, String> {
+        use crate::risk::ledger::CashLedger;
+
+        let initial = config.risk.initial_bankroll;
+        let mut engine = TradingEngine::new(
+            config,
+            PaperEngine::with_cost_model(config.execution.costs.clone()),
+        );
+        let mut ledger = CashLedger::new(initial);
+        let (mut fills_seen, mut settlements_seen) = (0, 0);
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            engine.on_event(ts, event).await.unwrap();
+            let fills = engine.execution().get_fills().await.unwrap();
+            for fill in &fills[fills_seen..] {
+                ledger.apply_fill(fill);
+            }
+            fills_seen = fills.len();
+            for settlement in &engine.settlements()[settlements_seen..] {
+                let market = engine
+                    .markets_seen()
+                    .iter()
+                    .find(|m| m.condition_id == settlement.market_id)
+                    .unwrap();
+                ledger.settle(market, settlement.winner);
+            }
+            settlements_seen = engine.settlements().len();
+
+            ledger
+                .check(engine.positions())
+                .map_err(|e| format!("at {}: {}", ts, e))?;
+            let realized = engine.positions().realized_pnl();
+            if engine.bankroll() != initial + realized {
+                return Err(format!(
+                    "at {}: bankroll {} but initial {} + realized {}",
+                    ts,
+                    engine.bankroll(),
+                    initial,
+                    realized
+                ));
+            }
+        }
+        Ok(engine.stats().clone())
+    }
+
+    #[tokio::test]
+    async fn test_default_session_conserves_money() {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let stats = conserves_money(&config).await.unwrap();
+        assert!(stats.fills >= 1, "no fills: {:?}", stats);
+        assert_eq!(stats.markets_settled, 2);
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(8))]
+
+        #[test]
+        fn prop_paper_pipeline_conserves_money(
+            seed in proptest::prelude::any::<u64>(),
+            volatility in 0.2f64..1.5,
+            jump_probability in 0.0f64..0.01,
+            lag_delay_ms in 1_000u64..10_000,
+            spread_cents in 1i64..6,
+            book_size in 20i64..400,
+            maker_bps in 0i64..100,
+            taker_bps in 0i64..200,
+            slippage_bps in 0i64..200,
+        ) {
+            let mut config: Config =
+                toml::from_str(include_str!("../../config.toml.example")).unwrap();
+            config.sim = SimConfig {
+                seed,
+                duration_mins: DurationConfig::from_mins(20),
+                volatility,
+                jump_probability,
+                lag_delay_ms: DurationConfig::from_millis(lag_delay_ms),
+                spread: Decimal::new(spread_cents, 2),
+                book_size: Decimal::from(book_size),
+                ..Default::default()
+            };
+            let costs = &mut config.execution.costs;
+            costs.maker_fee_rate = Decimal::new(maker_bps, 4);
+            costs.taker_fee_rate = Decimal::new(taker_bps, 4);
+            costs.base_slippage = Decimal::new(slippage_bps, 4);
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let result = runtime.block_on(conserves_money(&config));
+            proptest::prop_assert!(result.is_ok(), "{}", result.unwrap_err());
+        }
+    }
+}
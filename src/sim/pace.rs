@@ -0,0 +1,483 @@
+//! Paced and stepped replay of a simulated session
+//!
+//! As fast as possible rushes past the moment worth seeing; in real time a
+//! window takes fifteen minutes. [`SimClock`] sleeps the gaps between
+//! events scaled by a [`Speed`], measured from an anchor rather than event
+//! to event so rounding never accumulates. Only the wall-clock waits
+//! change: the engine runs on event timestamps, so a session ends in the
+//! same state at any speed.
+//!
+//! A [`Pacer`] adds breakpoints on top: on every signal, at a timestamp, or
+//! on a market opening, closing or going silent. At each it prints the
+//! latest evaluation, [`Explanation`] style, and in `--step` mode waits for
+//! Enter. `--until` fast-forwards, unpaced and without breakpoints, to the
+//! first signal or the first rejection of a given code.
+
+use crate::backtest::BacktestEvent;
+use crate::engine::{Explanation, Verdict};
+use crate::signal::RejectReason;
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How fast a replay runs against the wall clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// No waiting at all
+    Max,
+    /// One second of session per second
+    Realtime,
+    /// This many seconds of session per second
+    Scaled(f64),
+}
+
+impl Speed {
+    /// Session seconds per wall second; `None` when nothing is waited for
+    pub fn factor(&self) -> Option<f64> {
+        match self {
+            Speed::Max => None,
+            Speed::Realtime => Some(1.0),
+            Speed::Scaled(factor) => Some(*factor),
+        }
+    }
+}
+
+impl FromStr for Speed {
+    type Err = anyhow::Error;
+
+    /// `max`, `realtime`, or a factor such as `10x` or `0.5x`
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "max" => Ok(Speed::Max),
+            "realtime" | "1x" => Ok(Speed::Realtime),
+            other => {
+                let number = other.strip_suffix('x').unwrap_or(other);
+                let factor: f64 = number.parse().map_err(|_| {
+                    anyhow::anyhow!("speed '{}': expected max, realtime or e.g. 10x", s)
+                })?;
+                if !(factor.is_finite() && factor > 0.0) {
+                    anyhow::bail!("speed '{}' must be a positive factor", s);
+                }
+                Ok(Speed::Scaled(factor))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Speed::Max => write!(f, "max"),
+            Speed::Realtime => write!(f, "realtime"),
+            Speed::Scaled(factor) => write!(f, "{}x", factor),
+        }
+    }
+}
+
+/// Session time played out on the wall clock at a [`Speed`]
+#[derive(Debug, Clone)]
+pub struct SimClock {
+    speed: Speed,
+    /// Wall and session time the waits are measured from
+    anchor: Option<(Instant, DateTime<Utc>)>,
+}
+
+impl SimClock {
+    pub fn new(speed: Speed) -> Self {
+        Self {
+            speed,
+            anchor: None,
+        }
+    }
+
+    /// How long to wait at `wall` before the event at session time `at` is
+    /// due; the first event anchors the clock and is due at once
+    pub fn due_in(&mut self, at: DateTime<Utc>, wall: Instant) -> Duration {
+        let Some(factor) = self.speed.factor() else {
+            return Duration::ZERO;
+        };
+        let (wall_from, at_from) = *self.anchor.get_or_insert((wall, at));
+        let elapsed = (at - at_from).to_std().unwrap_or_default();
+        (wall_from + elapsed.div_f64(factor)).saturating_duration_since(wall)
+    }
+
+    /// Sleep until the event at `at` is due
+    pub async fn wait(&mut self, at: DateTime<Utc>) {
+        let due = self.due_in(at, Instant::now());
+        if !due.is_zero() {
+            tokio::time::sleep(due).await;
+        }
+    }
+
+    /// Measure from `at` afresh, as after a pause, so the time paused is
+    /// not made up by rushing
+    pub fn resume(&mut self, at: DateTime<Utc>, wall: Instant) {
+        self.anchor = Some((wall, at));
+    }
+}
+
+/// Market event a breakpoint can name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketEvent {
+    Open,
+    Close,
+    Gap,
+}
+
+impl MarketEvent {
+    /// The market event `event` is, if any
+    pub fn of(event: &BacktestEvent) -> Option<Self> {
+        match event {
+            BacktestEvent::MarketOpen(_) => Some(MarketEvent::Open),
+            BacktestEvent::MarketClose(_) => Some(MarketEvent::Close),
+            BacktestEvent::DataGap { .. } => Some(MarketEvent::Gap),
+            BacktestEvent::PriceTick(_) | BacktestEvent::OrderBookUpdate(_) => None,
+        }
+    }
+}
+
+/// Where a replay pauses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Every detected signal, traded or not
+    Signal,
+    /// The first event at or after this time
+    At(DateTime<Utc>),
+    /// Every market event of this kind
+    Market(MarketEvent),
+}
+
+impl FromStr for Breakpoint {
+    type Err = anyhow::Error;
+
+    /// `signal`, `open`, `close`, `gap`, or an RFC 3339 timestamp
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim() {
+            "signal" => Ok(Breakpoint::Signal),
+            "open" => Ok(Breakpoint::Market(MarketEvent::Open)),
+            "close" => Ok(Breakpoint::Market(MarketEvent::Close)),
+            "gap" => Ok(Breakpoint::Market(MarketEvent::Gap)),
+            other => DateTime::parse_from_rfc3339(other)
+                .map(|at| Breakpoint::At(at.with_timezone(&Utc)))
+                .map_err(|_| {
+                    anyhow::anyhow!(
+                        "breakpoint '{}': expected signal, open, close, gap or an RFC 3339 time",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+/// What `--until` fast-forwards to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Until {
+    /// The first detected signal
+    Signal,
+    /// The first signal rejected with this code
+    Rejection(String),
+}
+
+impl FromStr for Until {
+    type Err = anyhow::Error;
+
+    /// `signal` or `rejection:<code>`, e.g. `rejection:edge_too_small`
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().split_once(':') {
+            None if s.trim() == "signal" => Ok(Until::Signal),
+            Some(("rejection", code)) if RejectReason::CODES.contains(&code) => {
+                Ok(Until::Rejection(code.to_string()))
+            }
+            Some(("rejection", code)) => anyhow::bail!(
+                "unknown rejection code '{}', expected one of {}",
+                code,
+                RejectReason::CODES.join(", ")
+            ),
+            _ => anyhow::bail!("until '{}': expected signal or rejection:<code>", s),
+        }
+    }
+}
+
+impl Until {
+    fn reached(&self, explanation: &Explanation) -> bool {
+        match self {
+            Until::Signal => explanation.signal.is_some(),
+            Until::Rejection(code) => {
+                matches!(&explanation.verdict, Verdict::Filtered(why) if why.code() == code)
+            }
+        }
+    }
+}
+
+/// A stop in a replay and the latest evaluation before it
+#[derive(Debug, Clone)]
+pub struct Pause {
+    pub at: DateTime<Utc>,
+    pub reason: String,
+    pub explanation: Option<Explanation>,
+}
+
+impl fmt::Display for Pause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "-- paused at {}: {}", self.at.to_rfc3339(), self.reason)?;
+        match &self.explanation {
+            Some(explanation) => write!(f, "{}", explanation),
+            None => writeln!(f, "  (nothing evaluated yet)"),
+        }
+    }
+}
+
+/// Paces a replay and stops it at breakpoints
+pub struct Pacer {
+    clock: SimClock,
+    breakpoints: Vec<Breakpoint>,
+    until: Option<Until>,
+    /// Wait for Enter at each pause rather than only printing it
+    step: bool,
+    latest: Option<Explanation>,
+    pauses: u64,
+}
+
+impl Pacer {
+    pub fn new(speed: Speed) -> Self {
+        Self {
+            clock: SimClock::new(speed),
+            breakpoints: vec![],
+            until: None,
+            step: false,
+            latest: None,
+            pauses: 0,
+        }
+    }
+
+    /// Stop at each of `breakpoints`
+    pub fn with_breakpoints(mut self, breakpoints: Vec<Breakpoint>) -> Self {
+        self.breakpoints = breakpoints;
+        self
+    }
+
+    /// Fast-forward until `until`, then stop
+    pub fn with_until(mut self, until: Until) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Wait for Enter at every stop
+    pub fn stepping(mut self) -> Self {
+        self.step = true;
+        self
+    }
+
+    /// Whether the replay stops at all
+    pub fn is_interactive(&self) -> bool {
+        self.step || self.until.is_some() || !self.breakpoints.is_empty()
+    }
+
+    /// Stops made so far
+    pub fn pauses(&self) -> u64 {
+        self.pauses
+    }
+
+    /// Wait until the event at `at` is due; nothing is waited for while
+    /// fast-forwarding
+    pub async fn before(&mut self, at: DateTime<Utc>) {
+        if self.until.is_none() {
+            self.clock.wait(at).await;
+        }
+    }
+
+    /// Where the replay stops after the event at `at`, of kind `market`,
+    /// left the evaluation `explanation`, if anywhere
+    pub fn after(
+        &mut self,
+        at: DateTime<Utc>,
+        market: Option<MarketEvent>,
+        explanation: Option<Explanation>,
+    ) -> Option<Pause> {
+        let fresh = explanation.is_some();
+        if let Some(explanation) = explanation {
+            self.latest = Some(explanation);
+        }
+        let latest = self.latest.as_ref();
+        let reason = if let Some(until) = &self.until {
+            if !(fresh && latest.is_some_and(|e| until.reached(e))) {
+                return None;
+            }
+            let reason = match until {
+                Until::Signal => "first signal".to_string(),
+                Until::Rejection(code) => format!("first {} rejection", code),
+            };
+            self.until = None;
+            reason
+        } else {
+            let mut reasons = vec![];
+            if fresh && latest.is_some_and(|e| e.signal.is_some()) && self.breaks_on_signal() {
+                reasons.push("signal".to_string());
+            }
+            if let Some(event) =
+                market.filter(|e| self.breakpoints.contains(&Breakpoint::Market(*e)))
+            {
+                reasons.push(format!("market {:?}", event).to_lowercase());
+            }
+            let passed = self
+                .breakpoints
+                .iter()
+                .position(|b| matches!(b, Breakpoint::At(time) if *time <= at));
+            if let Some(i) = passed {
+                self.breakpoints.remove(i);
+                reasons.push("breakpoint time".to_string());
+            }
+            if reasons.is_empty() {
+                return None;
+            }
+            reasons.join(", ")
+        };
+        self.pauses += 1;
+        Some(Pause {
+            at,
+            reason,
+            explanation: self.latest.clone(),
+        })
+    }
+
+    fn breaks_on_signal(&self) -> bool {
+        self.breakpoints.contains(&Breakpoint::Signal)
+    }
+
+    /// In step mode, wait for a line on `input` before going on; at the
+    /// end of the input stepping stops and the replay runs to the end.
+    /// Timing resumes from `at` either way.
+    pub fn hold(&mut self, at: DateTime<Utc>, input: &mut dyn BufRead) -> std::io::Result<()> {
+        if self.step {
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                self.step = false;
+                self.breakpoints.clear();
+            }
+        }
+        self.clock.resume(at, Instant::now());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::duration::DurationConfig;
+    use crate::engine::TradingEngine;
+    use crate::execution::PaperEngine;
+    use crate::sim::Simulation;
+    use chrono::Duration as ChronoDuration;
+    use std::io::Cursor;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_767_600_000, 0).unwrap()
+    }
+
+    #[test]
+    fn test_waits_scale_from_the_anchor() {
+        let wall = Instant::now();
+        let mut clock = SimClock::new("10x".parse().unwrap());
+        assert_eq!(clock.due_in(t0(), wall), Duration::ZERO);
+        // 30s of session is 3s of wall time, however late the check
+        let at = t0() + ChronoDuration::seconds(30);
+        assert_eq!(clock.due_in(at, wall), Duration::from_secs(3));
+        assert_eq!(
+            clock.due_in(at, wall + Duration::from_secs(1)),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            clock.due_in(at, wall + Duration::from_secs(5)),
+            Duration::ZERO
+        );
+
+        // After a pause, timing starts over rather than rushing to catch up
+        let later = wall + Duration::from_secs(60);
+        clock.resume(at, later);
+        let next = at + ChronoDuration::seconds(5);
+        assert_eq!(clock.due_in(next, later), Duration::from_millis(500));
+
+        let mut realtime = SimClock::new("realtime".parse().unwrap());
+        realtime.due_in(t0(), wall);
+        assert_eq!(
+            realtime.due_in(t0() + ChronoDuration::milliseconds(1500), wall),
+            Duration::from_millis(1500)
+        );
+        let mut max = SimClock::new(Speed::Max);
+        assert_eq!(
+            max.due_in(t0() + ChronoDuration::hours(1), wall),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_flags_parse() {
+        assert_eq!("0.5x".parse::<Speed>().unwrap(), Speed::Scaled(0.5));
+        assert_eq!("max".parse::<Speed>().unwrap().to_string(), "max");
+        assert!("0x".parse::<Speed>().is_err());
+        assert!("fast".parse::<Speed>().is_err());
+        assert_eq!(
+            "close".parse::<Breakpoint>().unwrap(),
+            Breakpoint::Market(MarketEvent::Close)
+        );
+        assert_eq!(
+            "2026-01-05T08:00:00Z".parse::<Breakpoint>().unwrap(),
+            Breakpoint::At(t0())
+        );
+        assert_eq!(
+            "rejection:edge_too_small".parse::<Until>().unwrap(),
+            Until::Rejection("edge_too_small".to_string())
+        );
+        assert!("rejection:edge_below_threshold".parse::<Until>().is_err());
+    }
+
+    /// Replay the sim through `pacer`, pressing Enter at every stop
+    async fn replay(config: &Config, mut pacer: Pacer) -> (TradingEngine<PaperEngine>, Pacer) {
+        let mut engine = TradingEngine::new(
+            config,
+            PaperEngine::with_cost_model(config.execution.costs.clone()),
+        );
+        if pacer.is_interactive() {
+            engine = engine.with_explanations();
+        }
+        let mut enter = Cursor::new("\n".repeat(10_000));
+        for (at, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            pacer.before(at).await;
+            let market = MarketEvent::of(&event);
+            engine.on_event(at, event).await.unwrap();
+            if let Some(pause) = pacer.after(at, market, engine.take_explanation()) {
+                assert!(pause.to_string().starts_with("-- paused at"));
+                pacer.hold(at, &mut enter).unwrap();
+            }
+        }
+        (engine, pacer)
+    }
+
+    #[tokio::test]
+    async fn test_stepping_ends_where_a_continuous_replay_does() {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+
+        let (continuous, _) = replay(&config, Pacer::new(Speed::Max)).await;
+        let stepping = Pacer::new(Speed::Max)
+            .with_breakpoints(vec![
+                Breakpoint::Signal,
+                Breakpoint::Market(MarketEvent::Close),
+                Breakpoint::At(config.sim.start_time + ChronoDuration::minutes(7)),
+            ])
+            .stepping();
+        let (stepped, pacer) = replay(&config, stepping).await;
+        let stats = continuous.stats();
+        assert_eq!(stepped.stats(), stats);
+        // Every signal and both closes stopped, and the time once
+        assert_eq!(pacer.pauses(), stats.signals + 2 + 1);
+
+        let (fast_forwarded, pacer) =
+            replay(&config, Pacer::new(Speed::Max).with_until(Until::Signal)).await;
+        assert_eq!(fast_forwarded.stats(), stats);
+        assert_eq!(pacer.pauses(), 1);
+    }
+}
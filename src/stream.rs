@@ -0,0 +1,648 @@
+//! Outbound event stream
+//!
+//! External consumers follow the engine live over Server-Sent Events
+//! instead of tailing its journals. With `[stream] enabled`, `run` binds a
+//! small HTTP server answering `GET /events`; every entry appended to a
+//! journal the stream is attached to (trade, outcome and halt journals) is
+//! sent as `event: <kind>` with the journal's own JSON line as `data`, so
+//! the stream and the journal files never differ.
+//!
+//! Clients filter with `?kinds=order_submitted,position_opened` and
+//! `&market=<condition id>`, and authenticate with the `[stream] token`,
+//! either as `Authorization: Bearer <token>` or as `&token=` for browser
+//! `EventSource`s, which cannot set headers. Each client has a buffer of
+//! `client_buffer` entries; entries arriving while it is full are dropped
+//! and counted, and the client is sent a `dropped` event with its running
+//! total before the next entry it does receive.
+//!
+//! `GET /state`, with the same token, answers with the latest state the
+//! session published, the effective config registry.
+
+use crate::journal::JournalEntry;
+use crate::telemetry::record_stream_dropped;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// Path the event stream is served on
+pub const EVENTS_PATH: &str = "/events";
+
+/// Path the session's latest state is served on
+pub const STATE_PATH: &str = "/state";
+
+/// Default address the stream server binds
+pub const DEFAULT_STREAM_BIND: &str = "127.0.0.1:9101";
+
+/// Default entries buffered per client before dropping
+pub const DEFAULT_CLIENT_BUFFER: usize = 1024;
+
+/// Time between keepalive comments on an idle stream
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Longest request head read before giving up on a client
+const MAX_HEADER_LINES: usize = 64;
+
+/// Outbound event stream server, under `[stream]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct StreamConfig {
+    /// Serve the stream during `run`
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to bind
+    #[serde(default = "default_bind")]
+    pub bind: String,
+    /// Token clients must present; required when enabled, and never
+    /// written to the config fingerprint
+    #[serde(default, skip_serializing)]
+    pub token: Option<String>,
+    /// Entries buffered per client before new ones are dropped
+    #[serde(default = "default_client_buffer")]
+    pub client_buffer: usize,
+}
+
+fn default_bind() -> String {
+    DEFAULT_STREAM_BIND.to_string()
+}
+
+fn default_client_buffer() -> usize {
+    DEFAULT_CLIENT_BUFFER
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_bind(),
+            token: None,
+            client_buffer: default_client_buffer(),
+        }
+    }
+}
+
+/// Which entries a client is sent
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    /// Entry kinds to send; all when `None`
+    pub kinds: Option<HashSet<String>>,
+    /// Only entries of this market
+    pub market: Option<String>,
+}
+
+impl EventFilter {
+    /// The filter a request's query string asks for
+    pub fn from_query(query: &str) -> Self {
+        let mut filter = Self::default();
+        for (key, value) in query_pairs(query) {
+            match key.as_str() {
+                "kinds" | "kind" => filter
+                    .kinds
+                    .get_or_insert_with(HashSet::new)
+                    .extend(value.split(',').filter(|k| !k.is_empty()).map(String::from)),
+                "market" => filter.market = Some(value),
+                _ => {}
+            }
+        }
+        filter
+    }
+
+    /// Whether `entry` passes the filter
+    pub fn matches(&self, entry: &JournalEntry) -> bool {
+        if let Some(kinds) = &self.kinds {
+            if !kinds.contains(&entry.kind) {
+                return false;
+            }
+        }
+        match &self.market {
+            Some(market) => entry.data.get("market_id").and_then(|m| m.as_str()) == Some(market),
+            None => true,
+        }
+    }
+}
+
+struct Client {
+    filter: EventFilter,
+    tx: mpsc::Sender<Arc<str>>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Fans journal entries out to the connected clients
+#[derive(Clone)]
+pub struct EventStream {
+    clients: Arc<Mutex<Vec<Client>>>,
+    client_buffer: usize,
+    state: Arc<Mutex<Option<String>>>,
+}
+
+impl EventStream {
+    /// A stream buffering `client_buffer` entries per client
+    pub fn new(client_buffer: usize) -> Self {
+        Self {
+            clients: Arc::new(Mutex::new(Vec::new())),
+            client_buffer: client_buffer.max(1),
+            state: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Serve `json` at `/state` until the next call
+    pub fn set_state(&self, json: String) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = Some(json);
+    }
+
+    /// The JSON served at `/state`
+    pub fn state(&self) -> Option<String> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Follow the entries passing `filter`
+    pub fn subscribe(&self, filter: EventFilter) -> Subscription {
+        let (tx, rx) = mpsc::channel(self.client_buffer);
+        let dropped = Arc::new(AtomicU64::new(0));
+        self.lock().push(Client {
+            filter,
+            tx,
+            dropped: dropped.clone(),
+        });
+        Subscription { rx, dropped }
+    }
+
+    /// Send `entry`, journaled as `line`, to every client whose filter it
+    /// passes, dropping it for clients whose buffer is full
+    pub fn publish(&self, entry: &JournalEntry, line: &str) {
+        let mut clients = self.lock();
+        let mut frame: Option<Arc<str>> = None;
+        clients.retain(|client| {
+            if client.tx.is_closed() {
+                return false;
+            }
+            if !client.filter.matches(entry) {
+                return true;
+            }
+            let frame = frame
+                .get_or_insert_with(|| sse_frame(&entry.kind, line))
+                .clone();
+            match client.tx.try_send(frame) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    client.dropped.fetch_add(1, Ordering::Relaxed);
+                    record_stream_dropped(&entry.kind);
+                    true
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+
+    /// Clients connected
+    pub fn clients(&self) -> usize {
+        let mut clients = self.lock();
+        clients.retain(|client| !client.tx.is_closed());
+        clients.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Client>> {
+        self.clients.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// One client's entries, as SSE frames
+pub struct Subscription {
+    rx: mpsc::Receiver<Arc<str>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscription {
+    /// The next frame, or `None` once the stream is gone
+    pub async fn recv(&mut self) -> Option<Arc<str>> {
+        self.rx.recv().await
+    }
+
+    /// Entries dropped for this client so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// A journal `line` of `kind` as an SSE frame
+fn sse_frame(kind: &str, line: &str) -> Arc<str> {
+    Arc::from(format!("event: {}\ndata: {}\n\n", kind, line))
+}
+
+/// Bind the server `config` asks for and serve a new stream on it in the
+/// background; `None` when the stream is disabled
+pub async fn start(config: &StreamConfig) -> anyhow::Result<Option<EventStream>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let token = config
+        .token
+        .clone()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("[stream] is enabled without a token"))?;
+    let listener = TcpListener::bind(&config.bind)
+        .await
+        .with_context(|| format!("cannot bind the event stream on {}", config.bind))?;
+    let stream = EventStream::new(config.client_buffer);
+    tracing::info!(bind = %config.bind, path = EVENTS_PATH, "Serving the event stream");
+    let serving = stream.clone();
+    tokio::spawn(async move {
+        if let Err(e) = serve(listener, serving, token).await {
+            tracing::error!(error = %e, "Event stream server stopped");
+        }
+    });
+    Ok(Some(stream))
+}
+
+/// Serve `stream` on `listener` to clients presenting `token`
+pub async fn serve(
+    listener: TcpListener,
+    stream: EventStream,
+    token: String,
+) -> anyhow::Result<()> {
+    let token: Arc<str> = Arc::from(token);
+    loop {
+        let (socket, peer) = listener.accept().await?;
+        let (stream, token) = (stream.clone(), token.clone());
+        tokio::spawn(async move {
+            if let Err(e) = handle(socket, &stream, &token).await {
+                tracing::debug!(%peer, error = %e, "Event stream client gone");
+            }
+        });
+    }
+}
+
+async fn handle(socket: TcpStream, stream: &EventStream, token: &str) -> anyhow::Result<()> {
+    let (read, mut write) = socket.into_split();
+    let mut reader = BufReader::new(read);
+    let mut request = String::new();
+    reader.read_line(&mut request).await?;
+    let mut bearer = None;
+    for _ in 0..MAX_HEADER_LINES {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                bearer = value.trim().strip_prefix("Bearer ").map(str::to_string);
+            }
+        }
+    }
+
+    let mut parts = request.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let presented = bearer.or_else(|| {
+        query_pairs(query)
+            .into_iter()
+            .find_map(|(key, value)| (key == "token").then_some(value))
+    });
+
+    if method != "GET" {
+        return respond(&mut write, "405 Method Not Allowed").await;
+    }
+    if path != EVENTS_PATH && path != STATE_PATH {
+        return respond(&mut write, "404 Not Found").await;
+    }
+    if !presented.is_some_and(|p| token_matches(&p, token)) {
+        return respond(&mut write, "401 Unauthorized").await;
+    }
+    if path == STATE_PATH {
+        let body = stream.state().unwrap_or_else(|| "{}".to_string());
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        write.write_all(response.as_bytes()).await?;
+        return Ok(());
+    }
+
+    let filter = EventFilter::from_query(query);
+    let mut subscription = stream.subscribe(filter);
+    write
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+              Cache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n: connected\n\n",
+        )
+        .await?;
+    let mut reported = 0;
+    loop {
+        tokio::select! {
+            frame = subscription.recv() => {
+                let Some(frame) = frame else {
+                    return Ok(());
+                };
+                let dropped = subscription.dropped();
+                if dropped > reported {
+                    let notice = format!("event: dropped\ndata: {{\"dropped\":{}}}\n\n", dropped);
+                    write.write_all(notice.as_bytes()).await?;
+                    reported = dropped;
+                }
+                write.write_all(frame.as_bytes()).await?;
+            }
+            _ = tokio::time::sleep(KEEPALIVE) => {
+                write.write_all(b": keepalive\n\n").await?;
+            }
+        }
+    }
+}
+
+async fn respond(write: &mut (impl AsyncWrite + Unpin), status: &str) -> anyhow::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    write.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// Compare tokens in time independent of where they differ
+fn token_matches(presented: &str, token: &str) -> bool {
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Percent-decoded `key=value` pairs of a query string
+fn query_pairs(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%' && i + 2 < bytes.len())
+            .then(|| std::str::from_utf8(&bytes[i + 1..i + 3]).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            out.push(byte);
+            i += 3;
+            continue;
+        }
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::duration::DurationConfig;
+    use crate::engine::TradingEngine;
+    use crate::execution::PaperEngine;
+    use crate::journal::Journal;
+    use crate::sim::Simulation;
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+
+    const TOKEN: &str = "s3cret/token";
+
+    async fn server(stream: &EventStream) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, stream.clone(), TOKEN.to_string()));
+        addr
+    }
+
+    /// Send a request for `target` and read the response status line
+    async fn request(
+        addr: SocketAddr,
+        target: &str,
+        bearer: Option<&str>,
+    ) -> (String, BufReader<TcpStream>) {
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        let auth = bearer
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        let head = format!("GET {} HTTP/1.1\r\nHost: test\r\n{}\r\n", target, auth);
+        socket.write_all(head.as_bytes()).await.unwrap();
+        let mut reader = BufReader::new(socket);
+        let mut status = String::new();
+        reader.read_line(&mut status).await.unwrap();
+        (status.trim_end().to_string(), reader)
+    }
+
+    /// Follow `target`, reading past the response head
+    async fn follow(addr: SocketAddr, target: &str) -> BufReader<TcpStream> {
+        let (status, mut reader) = request(addr, target, Some(TOKEN)).await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            if line.trim().is_empty() {
+                return reader;
+            }
+        }
+    }
+
+    /// The next event's name and data, skipping comments
+    async fn next_event(reader: &mut BufReader<TcpStream>) -> (String, String) {
+        let (mut event, mut data) = (String::new(), String::new());
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            let line = line.trim_end_matches('\n');
+            if let Some(name) = line.strip_prefix("event: ") {
+                event = name.to_string();
+            } else if let Some(payload) = line.strip_prefix("data: ") {
+                data = payload.to_string();
+            } else if line.is_empty() && !event.is_empty() {
+                return (event, data);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clients_receive_journal_lines_they_filter_for() {
+        let dir = TempDir::new().unwrap();
+        let stream = EventStream::new(16);
+        let addr = server(&stream).await;
+        let journal = Journal::open(dir.path().join("trade_journal.jsonl"))
+            .unwrap()
+            .with_stream(stream.clone());
+
+        let mut all = follow(addr, "/events").await;
+        let mut filtered = follow(addr, "/events?kinds=order_submitted,halt&market=m%2D1").await;
+        assert_eq!(stream.clients(), 2);
+
+        journal
+            .append(
+                "order_submitted",
+                &serde_json::json!({ "market_id": "m-1", "n": 1 }),
+            )
+            .unwrap();
+        journal
+            .append(
+                "order_submitted",
+                &serde_json::json!({ "market_id": "m-2", "n": 2 }),
+            )
+            .unwrap();
+        journal
+            .append(
+                "position_opened",
+                &serde_json::json!({ "market_id": "m-1", "n": 3 }),
+            )
+            .unwrap();
+        journal
+            .append(
+                "order_submitted",
+                &serde_json::json!({ "market_id": "m-1", "n": 4 }),
+            )
+            .unwrap();
+
+        // Every entry is sent as the very line journaled
+        let lines: Vec<String> = std::fs::read_to_string(journal.path())
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        for line in &lines {
+            let (_, data) = next_event(&mut all).await;
+            assert_eq!(&data, line);
+        }
+        let (event, data) = next_event(&mut filtered).await;
+        assert_eq!((event.as_str(), &data), ("order_submitted", &lines[0]));
+        let (_, data) = next_event(&mut filtered).await;
+        assert_eq!(data, lines[3]);
+    }
+
+    #[tokio::test]
+    async fn test_clients_without_the_token_are_refused() {
+        let stream = EventStream::new(16);
+        let addr = server(&stream).await;
+
+        let (status, _) = request(addr, "/events", None).await;
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        let (status, _) = request(addr, "/events", Some("wrong")).await;
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        let (status, _) = request(addr, "/metrics", Some(TOKEN)).await;
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        // Browser EventSources pass the token in the query
+        let (status, _) = request(addr, "/events?token=s3cret%2Ftoken", None).await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+
+        // The state is behind the same token
+        stream.set_state(r#"{"revision":3}"#.to_string());
+        let (status, _) = request(addr, "/state", None).await;
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        let (status, mut reader) = request(addr, "/state", Some(TOKEN)).await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let mut response = String::new();
+        reader.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.ends_with("\r\n\r\n{\"revision\":3}"),
+            "{}",
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_clients_drop_and_are_told_how_many() {
+        let stream = EventStream::new(2);
+        let addr = server(&stream).await;
+        let mut client = follow(addr, "/events").await;
+        let mut idle = stream.subscribe(EventFilter::default());
+
+        // Published faster than either client reads
+        for n in 0..5 {
+            let entry = JournalEntry {
+                seq: 0,
+                ts: chrono::Utc::now(),
+                kind: "order_submitted".to_string(),
+                data: serde_json::json!({ "n": n }),
+            };
+            stream.publish(&entry, &serde_json::to_string(&entry).unwrap());
+        }
+        assert_eq!(idle.dropped(), 3);
+        assert!(idle.recv().await.unwrap().contains(r#""n":0"#));
+
+        let (event, data) = next_event(&mut client).await;
+        assert_eq!(
+            (event.as_str(), data.as_str()),
+            ("dropped", r#"{"dropped":3}"#)
+        );
+        for n in 0..2 {
+            let (_, data) = next_event(&mut client).await;
+            let entry: JournalEntry = serde_json::from_str(&data).unwrap();
+            assert_eq!(entry.data["n"], n);
+        }
+
+        // A client that hangs up is forgotten once a write to it fails
+        drop(idle);
+        drop(client);
+        let entry = JournalEntry {
+            seq: 0,
+            ts: chrono::Utc::now(),
+            kind: "halt".to_string(),
+            data: serde_json::json!({}),
+        };
+        for _ in 0..3 {
+            stream.publish(&entry, "{}");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(stream.clients(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_engine_decisions_stream_as_journaled() {
+        let dir = TempDir::new().unwrap();
+        let mut config: Config = toml::from_str(include_str!("../config.toml.example")).unwrap();
+        config.sim.duration_mins = DurationConfig::from_mins(30);
+        let stream = EventStream::new(100_000);
+        let mut subscription = stream.subscribe(EventFilter::default());
+        let journal = Journal::open(dir.path().join("trade_journal.jsonl"))
+            .unwrap()
+            .with_stream(stream.clone());
+        let mut engine = TradingEngine::new(
+            &config,
+            PaperEngine::with_cost_model(config.execution.costs.clone()),
+        )
+        .with_trade_journal(journal);
+        for (ts, event) in Simulation::new("BTCUSDT", "BTC", &config.sim) {
+            engine.on_event(ts, event).await.unwrap();
+        }
+
+        let journaled = std::fs::read_to_string(dir.path().join("trade_journal.jsonl")).unwrap();
+        let mut kinds = HashSet::new();
+        for line in journaled.lines() {
+            let frame = subscription.recv().await.unwrap();
+            let entry: JournalEntry = serde_json::from_str(line).unwrap();
+            assert_eq!(
+                &*frame,
+                format!("event: {}\ndata: {}\n\n", entry.kind, line)
+            );
+            kinds.insert(entry.kind);
+        }
+        assert_eq!(subscription.dropped(), 0);
+        for kind in ["signal_emitted", "order_submitted", "position_opened"] {
+            assert!(kinds.contains(kind), "no {} in {:?}", kind, kinds);
+        }
+    }
+}
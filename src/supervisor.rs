@@ -0,0 +1,415 @@
+//! Supervision of long-lived background tasks
+//!
+//! A bare `tokio::spawn` that panics or returns early takes its work with
+//! it in silence: the recorder stops writing, the feed stops forwarding, and
+//! nothing says so. [`Supervised::spawn`] runs a task built by a factory and
+//! watches it. A clean return ends supervision; a panic or an error is
+//! logged and journaled with the component name, counted in
+//! `polyhft_task_restarts_total` when the task is restarted, and otherwise
+//! handled by the task's [`RestartPolicy`].
+//!
+//! Liveness is reported into the [`HealthRegistry`] under the component
+//! name: healthy while running, degraded while waiting to restart, and
+//! unhealthy once given up. Trading-critical tasks use
+//! [`RestartPolicy::Never`]; their owner halts on [`Supervised::stopped`]
+//! returning [`TaskState::Failed`].
+
+use crate::journal::Journal;
+use crate::telemetry::{record_task_restart, EventCode, HealthRegistry, HealthState};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::{JoinError, JoinHandle};
+use tokio::time::Instant;
+
+/// Journal of task deaths, in the session directory
+pub const TASK_JOURNAL_FILE: &str = "task_journal.jsonl";
+
+/// Default first restart delay of [`RestartPolicy::backoff`]
+pub const DEFAULT_RESTART_INITIAL_DELAY: Duration = Duration::from_millis(500);
+
+/// Default longest restart delay of [`RestartPolicy::backoff`]
+pub const DEFAULT_RESTART_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// What to do when a supervised task panics or fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Restart at once, every time
+    Always,
+    /// Restart after a delay that doubles with each consecutive failure, up
+    /// to `max`; an attempt that ran for `max` resets it
+    Backoff { initial: Duration, max: Duration },
+    /// Give up; for trading-critical tasks, whose owner halts instead
+    Never,
+}
+
+impl RestartPolicy {
+    /// Backoff with the default delays
+    pub fn backoff() -> Self {
+        RestartPolicy::Backoff {
+            initial: DEFAULT_RESTART_INITIAL_DELAY,
+            max: DEFAULT_RESTART_MAX_DELAY,
+        }
+    }
+}
+
+/// Lifecycle of a supervised task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// The task is running
+    Running,
+    /// The task died and is waiting to be restarted
+    Restarting,
+    /// The task returned cleanly; it is not restarted
+    Finished,
+    /// The task died and its policy gave up on it
+    Failed,
+}
+
+impl TaskState {
+    /// Whether supervision has ended
+    pub fn is_terminal(self) -> bool {
+        matches!(self, TaskState::Finished | TaskState::Failed)
+    }
+}
+
+/// Journal payload of a task death
+#[derive(Debug, Serialize)]
+struct TaskDeath<'a> {
+    component: &'a str,
+    error: &'a str,
+    restarts: u64,
+}
+
+/// Where supervised tasks report: health registry and journal
+#[derive(Clone, Default)]
+pub struct Supervisor {
+    health: Option<HealthRegistry>,
+    journal: Option<Arc<Journal>>,
+}
+
+impl Supervisor {
+    /// A supervisor that only logs and counts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report task liveness into `health`
+    pub fn with_health(mut self, health: HealthRegistry) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Journal each task death
+    pub fn with_journal(mut self, journal: Arc<Journal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Run the task built by `factory` under `policy`
+    ///
+    /// `factory` is called again for every restart, so whatever the task
+    /// consumes must outlive one attempt, e.g. a receiver behind a mutex.
+    pub fn spawn<F, Fut>(&self, name: &str, policy: RestartPolicy, factory: F) -> Supervised
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let restarts = Arc::new(AtomicU64::new(0));
+        let (state_tx, state_rx) = watch::channel(TaskState::Running);
+        let watcher = Watcher {
+            name: name.to_string(),
+            policy,
+            context: self.clone(),
+            restarts: restarts.clone(),
+            state: state_tx,
+        };
+        let handle = tokio::spawn(watcher.run(factory));
+        Supervised {
+            name: name.to_string(),
+            restarts,
+            state: state_rx,
+            handle,
+        }
+    }
+}
+
+/// Handle to a supervised task
+pub struct Supervised {
+    name: String,
+    restarts: Arc<AtomicU64>,
+    state: watch::Receiver<TaskState>,
+    handle: JoinHandle<()>,
+}
+
+impl Supervised {
+    /// Run the task built by `factory` under `policy`, logging and counting
+    /// only; see [`Supervisor::spawn`]
+    pub fn spawn<F, Fut>(name: &str, policy: RestartPolicy, factory: F) -> Self
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        Supervisor::new().spawn(name, policy, factory)
+    }
+
+    /// Component name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Restarts so far
+    pub fn restarts(&self) -> u64 {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    /// Current state
+    pub fn state(&self) -> TaskState {
+        *self.state.borrow()
+    }
+
+    /// Wait until supervision ends, returning the final state
+    pub async fn stopped(&mut self) -> TaskState {
+        match self.state.wait_for(|s| s.is_terminal()).await {
+            Ok(state) => *state,
+            // The watcher is gone without a final state: it was aborted
+            Err(_) => TaskState::Failed,
+        }
+    }
+
+    /// Stop the task and its supervision
+    pub fn abort(&self) {
+        self.handle.abort();
+    }
+}
+
+/// Aborts the running attempt if its watcher is dropped mid-await
+struct AbortOnDrop(JoinHandle<anyhow::Result<()>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// The supervising side of one task
+struct Watcher {
+    name: String,
+    policy: RestartPolicy,
+    context: Supervisor,
+    restarts: Arc<AtomicU64>,
+    state: watch::Sender<TaskState>,
+}
+
+impl Watcher {
+    async fn run<F, Fut>(self, mut factory: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let mut delay = match self.policy {
+            RestartPolicy::Backoff { initial, .. } => initial,
+            _ => Duration::ZERO,
+        };
+        loop {
+            self.report(TaskState::Running, None);
+            let started = Instant::now();
+            // A nested spawn turns a panic into a JoinError instead of
+            // unwinding through the watcher
+            let mut attempt = AbortOnDrop(tokio::spawn(factory()));
+            let error = match (&mut attempt.0).await {
+                Ok(Ok(())) => {
+                    tracing::info!(component = %self.name, "Supervised task finished");
+                    self.report(TaskState::Finished, None);
+                    return;
+                }
+                Ok(Err(e)) => format!("{:#}", e),
+                Err(e) => panic_message(e),
+            };
+
+            let restarts = self.restarts.load(Ordering::Relaxed);
+            if self.policy == RestartPolicy::Never {
+                tracing::error!(
+                    event_code = %EventCode::TaskFailed,
+                    component = %self.name,
+                    error = %error,
+                    restarts,
+                    "Supervised task died and is not restarted"
+                );
+                self.journal("task_failed", &error, restarts);
+                self.report(TaskState::Failed, Some(error));
+                return;
+            }
+
+            let restarts = self.restarts.fetch_add(1, Ordering::Relaxed) + 1;
+            record_task_restart(&self.name);
+            if let RestartPolicy::Backoff { initial, max } = self.policy {
+                if started.elapsed() >= max {
+                    delay = initial;
+                }
+            }
+            tracing::warn!(
+                event_code = %EventCode::TaskRestarted,
+                component = %self.name,
+                error = %error,
+                restarts,
+                delay_ms = delay.as_millis() as u64,
+                "Supervised task died, restarting"
+            );
+            self.journal("task_restart", &error, restarts);
+            self.report(TaskState::Restarting, Some(error));
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            if let RestartPolicy::Backoff { max, .. } = self.policy {
+                delay = (delay * 2).min(max);
+            }
+        }
+    }
+
+    fn report(&self, state: TaskState, reason: Option<String>) {
+        if let Some(health) = &self.context.health {
+            let health_state = match state {
+                TaskState::Running | TaskState::Finished => HealthState::Healthy,
+                TaskState::Restarting => HealthState::Degraded,
+                TaskState::Failed => HealthState::Unhealthy,
+            };
+            health.set(&self.name, health_state, reason);
+        }
+        self.state.send_replace(state);
+    }
+
+    fn journal(&self, kind: &str, error: &str, restarts: u64) {
+        let Some(journal) = &self.context.journal else {
+            return;
+        };
+        let entry = TaskDeath {
+            component: &self.name,
+            error,
+            restarts,
+        };
+        if let Err(e) = journal.append(kind, &entry) {
+            tracing::warn!(component = %self.name, error = %e, "Failed to journal task death");
+        }
+    }
+}
+
+/// Readable reason of a failed attempt
+fn panic_message(error: JoinError) -> String {
+    if !error.is_panic() {
+        return error.to_string();
+    }
+    let payload = error.into_panic();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string());
+    format!("panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    fn counter() -> Arc<AtomicU32> {
+        Arc::new(AtomicU32::new(0))
+    }
+
+    #[tokio::test]
+    async fn test_panicking_task_is_restarted_until_it_finishes() {
+        let health = HealthRegistry::new();
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Arc::new(Journal::open(dir.path().join("tasks.jsonl")).unwrap());
+        let supervisor = Supervisor::new()
+            .with_health(health.clone())
+            .with_journal(journal);
+        let attempts = counter();
+        let seen = attempts.clone();
+        let mut task = supervisor.spawn("writer", RestartPolicy::Always, move || {
+            let attempt = seen.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt < 2 {
+                    panic!("disk on fire");
+                }
+                Ok(())
+            }
+        });
+
+        assert_eq!(task.stopped().await, TaskState::Finished);
+        assert_eq!(task.restarts(), 2);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(health.get("writer").unwrap().state, HealthState::Healthy);
+
+        let entries = Journal::read_all(dir.path().join("tasks.jsonl")).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].kind, "task_restart");
+        assert_eq!(entries[0].data["component"], "writer");
+        assert_eq!(entries[0].data["error"], "panicked: disk on fire");
+        assert_eq!(entries[1].data["restarts"], 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_backoff_reports_degraded_while_waiting() {
+        let health = HealthRegistry::new();
+        let supervisor = Supervisor::new().with_health(health.clone());
+        let attempts = counter();
+        let seen = attempts.clone();
+        let policy = RestartPolicy::Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(4),
+        };
+        let mut task = supervisor.spawn("recorder", policy, move || {
+            let attempt = seen.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt == 0 {
+                    anyhow::bail!("writer lost its file");
+                }
+                std::future::pending().await
+            }
+        });
+
+        let mut state = task.state.clone();
+        state
+            .wait_for(|s| *s == TaskState::Restarting)
+            .await
+            .unwrap();
+        let component = health.get("recorder").unwrap();
+        assert_eq!(component.state, HealthState::Degraded);
+        assert_eq!(component.reason.as_deref(), Some("writer lost its file"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        // Restarted once the delay is up, and healthy again
+        state.wait_for(|s| *s == TaskState::Running).await.unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(health.get("recorder").unwrap().state, HealthState::Healthy);
+        assert_eq!(task.restarts(), 1);
+
+        task.abort();
+        assert_eq!(task.stopped().await, TaskState::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_critical_task_is_not_restarted() {
+        let health = HealthRegistry::new();
+        let supervisor = Supervisor::new().with_health(health.clone());
+        let attempts = counter();
+        let seen = attempts.clone();
+        let mut task = supervisor.spawn("feed", RestartPolicy::Never, move || {
+            seen.fetch_add(1, Ordering::SeqCst);
+            async { panic!("socket gone") }
+        });
+
+        assert_eq!(task.stopped().await, TaskState::Failed);
+        assert_eq!(task.restarts(), 0);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        let component = health.get("feed").unwrap();
+        assert_eq!(component.state, HealthState::Unhealthy);
+        assert_eq!(component.reason.as_deref(), Some("panicked: socket gone"));
+        assert_eq!(health.overall(), HealthState::Unhealthy);
+    }
+}
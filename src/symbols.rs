@@ -0,0 +1,255 @@
+//! Asset to feed symbol mapping
+//!
+//! Markets are keyed by asset (`BTC`), price feeds by exchange symbol
+//! (`BTCUSDT`). The `[symbols]` table maps one to the other per exchange;
+//! without it the single pair `feed.symbol` / `market.asset` is checked
+//! against each other. Ticks and markets are tagged with their canonical
+//! asset here, before they reach the detection layer, so a detector never
+//! sees a price from another asset.
+
+use crate::config::Config;
+use crate::feed::PriceTick;
+use crate::market::Market;
+use anyhow::{anyhow, bail};
+use std::collections::{BTreeMap, HashMap};
+
+/// Feed symbol of each asset per exchange: `asset -> exchange -> symbol`
+pub type SymbolTable = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Quote currencies stripped from a symbol to find its base asset
+const QUOTE_CURRENCIES: [&str; 5] = ["USDT", "USDC", "FDUSD", "BUSD", "USD"];
+
+/// Base asset of an exchange symbol, e.g. `BTC` for `btcusdt`
+pub fn base_asset(symbol: &str) -> Option<String> {
+    let symbol = symbol.to_uppercase();
+    QUOTE_CURRENCIES
+        .iter()
+        .find_map(|quote| symbol.strip_suffix(quote))
+        .filter(|base| !base.is_empty())
+        .map(str::to_string)
+}
+
+/// Asset and symbol routing for one exchange
+#[derive(Debug, Clone)]
+pub struct SymbolMap {
+    exchange: String,
+    /// Feed symbol keyed by asset
+    symbols: BTreeMap<String, String>,
+    /// Asset keyed by upper-cased feed symbol
+    assets: HashMap<String, String>,
+}
+
+impl SymbolMap {
+    /// Mapping of `table` for `exchange`
+    ///
+    /// Fails if two assets claim the same symbol.
+    pub fn new(exchange: &str, table: &SymbolTable) -> anyhow::Result<Self> {
+        let mut map = Self {
+            exchange: exchange.to_string(),
+            symbols: BTreeMap::new(),
+            assets: HashMap::new(),
+        };
+        for (asset, feeds) in table {
+            let Some(symbol) = feeds.get(exchange) else {
+                continue;
+            };
+            let asset = asset.to_uppercase();
+            if let Some(other) = map.assets.insert(symbol.to_uppercase(), asset.clone()) {
+                bail!(
+                    "symbol {} on {} is mapped to both {} and {}",
+                    symbol,
+                    exchange,
+                    other,
+                    asset
+                );
+            }
+            map.symbols.insert(asset, symbol.clone());
+        }
+        Ok(map)
+    }
+
+    /// Mapping for the configured feed, checked against the market asset
+    ///
+    /// An empty `[symbols]` table maps `market.asset` to `feed.symbol`, but
+    /// only if the symbol's base asset is that asset.
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let exchange = &config.feed.exchange;
+        let asset = config.market.asset.to_uppercase();
+        let map = if config.symbols.is_empty() {
+            let base = base_asset(&config.feed.symbol);
+            if base.as_deref() != Some(asset.as_str()) {
+                bail!(
+                    "feed.symbol {} does not quote market.asset {}; add a [symbols] table to map it explicitly",
+                    config.feed.symbol,
+                    asset
+                );
+            }
+            let feeds = BTreeMap::from([(exchange.clone(), config.feed.symbol.clone())]);
+            Self::new(exchange, &SymbolTable::from([(asset.clone(), feeds)]))?
+        } else {
+            Self::new(exchange, &config.symbols)?
+        };
+        let symbol = map.symbol(&asset).ok_or_else(|| {
+            anyhow!(
+                "market.asset {} has no {} feed in [symbols]",
+                asset,
+                exchange
+            )
+        })?;
+        if !symbol.eq_ignore_ascii_case(&config.feed.symbol) {
+            bail!(
+                "feed.symbol {} is not the {} feed for {}, which is {}",
+                config.feed.symbol,
+                exchange,
+                asset,
+                symbol
+            );
+        }
+        Ok(map)
+    }
+
+    /// Exchange the symbols belong to
+    pub fn exchange(&self) -> &str {
+        &self.exchange
+    }
+
+    /// Feed symbol of `asset`
+    pub fn symbol(&self, asset: &str) -> Option<&str> {
+        self.symbols.get(&asset.to_uppercase()).map(String::as_str)
+    }
+
+    /// Canonical asset of a feed `symbol`
+    pub fn asset(&self, symbol: &str) -> Option<&str> {
+        self.assets.get(&symbol.to_uppercase()).map(String::as_str)
+    }
+
+    /// Tag `tick` with the asset of its symbol; false if it has none
+    pub fn tag_tick(&self, tick: &mut PriceTick) -> bool {
+        match self.asset(&tick.symbol) {
+            Some(asset) => {
+                tick.asset = asset.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fail unless every market's asset has a feed
+    pub fn validate_markets(&self, markets: &[Market]) -> anyhow::Result<()> {
+        for market in markets {
+            if self.symbol(&market.asset).is_none() {
+                bail!(
+                    "market {} is for asset '{}', which has no {} feed in [symbols]",
+                    market.condition_id,
+                    market.asset,
+                    self.exchange
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use rust_decimal_macros::dec;
+
+    fn config() -> Config {
+        toml::from_str(include_str!("../config.toml.example")).unwrap()
+    }
+
+    fn tick(symbol: &str) -> PriceTick {
+        PriceTick {
+            symbol: symbol.to_string(),
+            asset: String::new(),
+            price: dec!(100),
+            timestamp: Utc::now(),
+            exchange_ts: Utc::now(),
+            source: Default::default(),
+        }
+    }
+
+    fn market(asset: &str) -> Market {
+        Market {
+            condition_id: format!("{}-m", asset),
+            asset: asset.to_string(),
+            yes_token_id: "yes".to_string(),
+            no_token_id: "no".to_string(),
+            open_price: dec!(100),
+            open_time: Utc::now(),
+            close_time: Utc::now(),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_base_asset() {
+        assert_eq!(base_asset("BTCUSDT").as_deref(), Some("BTC"));
+        assert_eq!(base_asset("ethusdc").as_deref(), Some("ETH"));
+        assert_eq!(base_asset("USDT"), None);
+        assert_eq!(base_asset("BTCEUR"), None);
+    }
+
+    #[test]
+    fn test_missing_mapping_fails_at_startup() {
+        // Without a table BTC maps to BTCUSDT implicitly
+        let mut config = config();
+        config.symbols.clear();
+        let map = SymbolMap::from_config(&config).unwrap();
+        assert_eq!(map.symbol("btc"), Some("BTCUSDT"));
+
+        // ETH markets fed from the BTC symbol
+        config.market.asset = "ETH".to_string();
+        let err = SymbolMap::from_config(&config).unwrap_err().to_string();
+        assert!(err.contains("does not quote market.asset ETH"), "{}", err);
+
+        // A table without the market's asset
+        config.symbols = toml::from_str("[BTC]\nbinance = \"BTCUSDT\"").unwrap();
+        let err = SymbolMap::from_config(&config).unwrap_err().to_string();
+        assert!(err.contains("ETH has no binance feed"), "{}", err);
+
+        // A table whose ETH feed is not the one subscribed
+        config.symbols = toml::from_str("[ETH]\nbinance = \"ETHUSDT\"").unwrap();
+        let err = SymbolMap::from_config(&config).unwrap_err().to_string();
+        assert!(err.contains("not the binance feed for ETH"), "{}", err);
+        config.feed.symbol = "ETHUSDT".to_string();
+        assert!(SymbolMap::from_config(&config).is_ok());
+
+        let table: SymbolTable =
+            toml::from_str("[BTC]\nbinance = \"BTCUSDT\"\n[XBT]\nbinance = \"btcusdt\"").unwrap();
+        assert!(SymbolMap::new("binance", &table).is_err());
+    }
+
+    #[test]
+    fn test_two_assets_route_to_their_own_asset() {
+        let mut config = config();
+        config.symbols = toml::from_str(
+            "[BTC]\nbinance = \"BTCUSDT\"\n[ETH]\nbinance = \"ETHUSDT\"\ncoinbase = \"ETH-USD\"",
+        )
+        .unwrap();
+        let map = SymbolMap::from_config(&config).unwrap();
+        assert_eq!(map.exchange(), "binance");
+
+        let mut btc = tick("BTCUSDT");
+        let mut eth = tick("ethusdt");
+        assert!(map.tag_tick(&mut btc));
+        assert!(map.tag_tick(&mut eth));
+        assert_eq!((btc.asset.as_str(), eth.asset.as_str()), ("BTC", "ETH"));
+
+        // Another exchange's symbol is not routed here
+        let mut other = tick("ETH-USD");
+        assert!(!map.tag_tick(&mut other));
+        assert!(other.asset.is_empty());
+
+        assert!(map
+            .validate_markets(&[market("BTC"), market("ETH")])
+            .is_ok());
+        let err = map
+            .validate_markets(&[market("BTC"), market("SOL")])
+            .unwrap_err();
+        assert!(err.to_string().contains("SOL-m"));
+    }
+}
@@ -0,0 +1,173 @@
+//! Channel depth instrumentation
+//!
+//! An [`InstrumentedSender`] is an mpsc sender registered under a name, so
+//! the depth of every channel created through [`instrumented_channel`] can
+//! be read without touching the receiving task. The registry holds weak
+//! senders only: once a channel's last sender is dropped it leaves the
+//! registry at the next read.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::mpsc;
+
+/// Messages queued in the channels of one name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelDepth {
+    /// Channel name; channels sharing one are summed
+    pub name: String,
+    /// Messages sent and not yet received
+    pub depth: usize,
+    /// Total buffer of the channels
+    pub capacity: usize,
+}
+
+impl fmt::Display for ChannelDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}/{}", self.name, self.depth, self.capacity)
+    }
+}
+
+/// Reads one channel's depth and capacity; `None` once it has closed
+type Probe = Box<dyn Fn() -> Option<(usize, usize)> + Send + Sync>;
+
+fn registry() -> &'static Mutex<Vec<(&'static str, Probe)>> {
+    static REGISTRY: OnceLock<Mutex<Vec<(&'static str, Probe)>>> = OnceLock::new();
+    REGISTRY.get_or_init(Mutex::default)
+}
+
+/// An mpsc sender whose channel depth is reported under its name
+#[derive(Debug)]
+pub struct InstrumentedSender<T> {
+    name: &'static str,
+    inner: mpsc::Sender<T>,
+}
+
+impl<T> Clone for InstrumentedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> InstrumentedSender<T> {
+    /// Name the channel is reported under
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Messages sent and not yet received
+    pub fn depth(&self) -> usize {
+        self.inner.max_capacity() - self.inner.capacity()
+    }
+
+    /// The plain sender, for code that takes one
+    pub fn inner(&self) -> &mpsc::Sender<T> {
+        &self.inner
+    }
+}
+
+impl<T> Deref for InstrumentedSender<T> {
+    type Target = mpsc::Sender<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+/// A bounded mpsc channel whose depth is reported as `name`
+pub fn instrumented_channel<T: Send + 'static>(
+    name: &'static str,
+    capacity: usize,
+) -> (InstrumentedSender<T>, mpsc::Receiver<T>) {
+    let (tx, rx) = mpsc::channel(capacity);
+    let weak = tx.downgrade();
+    let probe: Probe = Box::new(move || {
+        let tx = weak.upgrade()?;
+        Some((tx.max_capacity() - tx.capacity(), tx.max_capacity()))
+    });
+    registry()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((name, probe));
+    (InstrumentedSender { name, inner: tx }, rx)
+}
+
+/// Depth of every open instrumented channel, summed by name and sorted;
+/// closed channels are dropped from the registry
+pub fn channel_depths() -> Vec<ChannelDepth> {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let mut depths: Vec<ChannelDepth> = vec![];
+    registry.retain(|(name, probe)| {
+        let Some((depth, capacity)) = probe() else {
+            return false;
+        };
+        match depths.iter_mut().find(|d| d.name == *name) {
+            Some(d) => {
+                d.depth += depth;
+                d.capacity += capacity;
+            }
+            None => depths.push(ChannelDepth {
+                name: name.to_string(),
+                depth,
+                capacity,
+            }),
+        }
+        true
+    });
+    depths.sort_by(|a, b| a.name.cmp(&b.name));
+    depths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth_of(name: &str) -> Option<ChannelDepth> {
+        channel_depths().into_iter().find(|d| d.name == name)
+    }
+
+    #[tokio::test]
+    async fn test_depth_follows_sends_and_receives() {
+        let (tx, mut rx) = instrumented_channel::<u32>("test_depth", 8);
+        assert_eq!((tx.depth(), tx.name()), (0, "test_depth"));
+        for i in 0..5 {
+            tx.send(i).await.unwrap();
+        }
+        assert!(tx.try_send(5).is_ok());
+        assert_eq!(tx.depth(), 6);
+        assert_eq!(
+            depth_of("test_depth"),
+            Some(ChannelDepth {
+                name: "test_depth".to_string(),
+                depth: 6,
+                capacity: 8,
+            })
+        );
+
+        rx.recv().await.unwrap();
+        rx.recv().await.unwrap();
+        assert_eq!(tx.clone().depth(), 4);
+        assert_eq!(depth_of("test_depth").unwrap().depth, 4);
+    }
+
+    #[tokio::test]
+    async fn test_same_name_sums_and_closed_channels_leave() {
+        let (a, _rx_a) = instrumented_channel::<u32>("test_shared", 4);
+        let (b, _rx_b) = instrumented_channel::<u32>("test_shared", 4);
+        a.send(1).await.unwrap();
+        b.send(2).await.unwrap();
+        b.send(3).await.unwrap();
+        let shared = depth_of("test_shared").unwrap();
+        assert_eq!((shared.depth, shared.capacity), (3, 8));
+
+        drop(b);
+        let shared = depth_of("test_shared").unwrap();
+        assert_eq!((shared.depth, shared.capacity), (1, 4));
+        drop(a);
+        assert_eq!(depth_of("test_shared"), None);
+    }
+}
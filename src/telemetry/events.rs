@@ -0,0 +1,460 @@
+//! Log event catalogue
+//!
+//! Significant log lines carry a stable `event_code` field so they can be
+//! grepped and alerted on without matching free-text messages. Ids use the
+//! field names in [`fields`]. `docs/log-events.md` is generated from this
+//! enum by [`catalogue_markdown`]; a test keeps the two in sync.
+
+use std::fmt;
+use tracing::Level;
+
+/// Field names shared by all log sites
+pub mod fields {
+    /// Event code field
+    pub const EVENT_CODE: &str = "event_code";
+    /// Market condition id
+    pub const MARKET_ID: &str = "market_id";
+    /// CLOB token id
+    pub const TOKEN_ID: &str = "token_id";
+    /// Order id
+    pub const ORDER_ID: &str = "order_id";
+}
+
+/// Stable codes for significant log events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventCode {
+    /// WebSocket connection established
+    WsConnected,
+    /// WebSocket connection lost
+    WsDisconnected,
+    /// WebSocket reconnect attempt scheduled
+    WsReconnect,
+    /// WebSocket reconnect attempts exhausted
+    WsGaveUp,
+    /// Price feed channel closed
+    FeedClosed,
+    /// Detection loop fell behind the price feed
+    TickLagDegraded,
+    /// Wall clock stepped against the monotonic clock, entries frozen
+    ClockStep,
+    /// Process did not run for a while, as in a VM pause
+    ClockPause,
+    /// Order book subscription requested
+    BookSubscribed,
+    /// Crossed or locked order book ignored and resynced
+    BookCrossed,
+    /// Order book update for a token of no tracked market
+    BookUnmapped,
+    /// YES/NO tokens of an unlabeled market inferred from its books
+    TokensInferred,
+    /// Signal passed filters and will be traded
+    SignalEmitted,
+    /// Signal rejected by filters or sizing
+    SignalRejected,
+    /// Order blocked before submission
+    OrderRejected,
+    /// Order filled
+    OrderFilled,
+    /// Order cancelled
+    OrderCancelled,
+    /// User channel and REST disagree about a fill
+    FillDiscrepancy,
+    /// Repeated failures opened a circuit breaker
+    CircuitOpened,
+    /// A probe succeeded and closed a circuit breaker
+    CircuitClosed,
+    /// Position opened from a fill
+    PositionOpened,
+    /// The book supporting a held position evaporated
+    BookShock,
+    /// Position closed before settlement by an exit trigger
+    PositionExited,
+    /// Order left in flight by a crash reconciled at startup
+    IntentRepaired,
+    /// Market settled and positions closed
+    MarketSettled,
+    /// The official resolution contradicted a provisional settlement
+    ResolutionCorrected,
+    /// No official resolution within the confirmation window
+    ResolutionUnconfirmed,
+    /// Trading halted by a risk limit
+    Halt,
+    /// Unacknowledged hard halt found at startup, orders withheld
+    HaltPending,
+    /// Hard halt acknowledged by an operator
+    HaltAcknowledged,
+    /// A settled loss paused entries on its asset
+    LossCooldown,
+    /// Consecutive losses halted an asset until acknowledged
+    AssetHalted,
+    /// Adverse excursions drifted past the baseline; strategy flagged for
+    /// review
+    StrategyReview,
+    /// The fair value models kept disagreeing in one market
+    ModelDisagreement,
+    /// Realized P&L stayed well below what the signals claimed
+    ExpectedValueShortfall,
+    /// An entry was withheld by a rate cap
+    RateCapHit,
+    /// The global daily entry cap was reached
+    DailyCapReached,
+    /// Captured data could not be written
+    FlushFailed,
+    /// Free disk space below the hard threshold, recording paused
+    DiskCritical,
+    /// Free disk space recovered, recording resumed
+    DiskRecovered,
+    /// A supervised task panicked or failed and was restarted
+    TaskRestarted,
+    /// A supervised task died and is not restarted
+    TaskFailed,
+    /// Data directory lock left by a dead process reclaimed
+    StaleLockReclaimed,
+    /// Instance took the leader lease and submits orders
+    LeaderPromoted,
+    /// Instance lost the leader lease and withholds orders
+    LeaderDemoted,
+    /// Trading schedule opened or closed
+    ScheduleTransition,
+    /// Canary session met every acceptance criterion
+    CanaryPassed,
+    /// Canary session missed an acceptance criterion
+    CanaryFailed,
+    /// Fills, positions and journal disagreed on the session's P&L
+    PnlMismatch,
+    /// Exchange collateral balance drifted from the ledger, adjustment booked
+    BalanceDrift,
+    /// Entries withheld ahead of a handoff to a new process
+    HandoffDraining,
+    /// Session taken over from a draining process
+    HandoffTakenOver,
+    /// Spot feed went silent across a handoff
+    HandoffGap,
+    /// Shutdown requested
+    Shutdown,
+}
+
+impl EventCode {
+    /// Every code, in catalogue order
+    pub const ALL: [EventCode; 54] = [
+        EventCode::WsConnected,
+        EventCode::WsDisconnected,
+        EventCode::WsReconnect,
+        EventCode::WsGaveUp,
+        EventCode::FeedClosed,
+        EventCode::TickLagDegraded,
+        EventCode::ClockStep,
+        EventCode::ClockPause,
+        EventCode::BookSubscribed,
+        EventCode::BookCrossed,
+        EventCode::BookUnmapped,
+        EventCode::TokensInferred,
+        EventCode::SignalEmitted,
+        EventCode::SignalRejected,
+        EventCode::OrderRejected,
+        EventCode::OrderFilled,
+        EventCode::OrderCancelled,
+        EventCode::FillDiscrepancy,
+        EventCode::CircuitOpened,
+        EventCode::CircuitClosed,
+        EventCode::PositionOpened,
+        EventCode::BookShock,
+        EventCode::PositionExited,
+        EventCode::IntentRepaired,
+        EventCode::MarketSettled,
+        EventCode::ResolutionCorrected,
+        EventCode::ResolutionUnconfirmed,
+        EventCode::Halt,
+        EventCode::HaltPending,
+        EventCode::HaltAcknowledged,
+        EventCode::LossCooldown,
+        EventCode::AssetHalted,
+        EventCode::StrategyReview,
+        EventCode::ModelDisagreement,
+        EventCode::ExpectedValueShortfall,
+        EventCode::RateCapHit,
+        EventCode::DailyCapReached,
+        EventCode::FlushFailed,
+        EventCode::DiskCritical,
+        EventCode::DiskRecovered,
+        EventCode::TaskRestarted,
+        EventCode::TaskFailed,
+        EventCode::StaleLockReclaimed,
+        EventCode::LeaderPromoted,
+        EventCode::LeaderDemoted,
+        EventCode::ScheduleTransition,
+        EventCode::CanaryPassed,
+        EventCode::CanaryFailed,
+        EventCode::PnlMismatch,
+        EventCode::BalanceDrift,
+        EventCode::HandoffDraining,
+        EventCode::HandoffTakenOver,
+        EventCode::HandoffGap,
+        EventCode::Shutdown,
+    ];
+
+    /// Code as written to the `event_code` field
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventCode::WsConnected => "WS_CONNECTED",
+            EventCode::WsDisconnected => "WS_DISCONNECTED",
+            EventCode::WsReconnect => "WS_RECONNECT",
+            EventCode::WsGaveUp => "WS_GAVE_UP",
+            EventCode::FeedClosed => "FEED_CLOSED",
+            EventCode::TickLagDegraded => "TICK_LAG_DEGRADED",
+            EventCode::ClockStep => "CLOCK_STEP",
+            EventCode::ClockPause => "CLOCK_PAUSE",
+            EventCode::BookSubscribed => "BOOK_SUBSCRIBED",
+            EventCode::BookCrossed => "BOOK_CROSSED",
+            EventCode::BookUnmapped => "BOOK_UNMAPPED",
+            EventCode::TokensInferred => "TOKENS_INFERRED",
+            EventCode::SignalEmitted => "SIGNAL_EMITTED",
+            EventCode::SignalRejected => "SIGNAL_REJECTED",
+            EventCode::OrderRejected => "ORDER_REJECTED",
+            EventCode::OrderFilled => "ORDER_FILLED",
+            EventCode::OrderCancelled => "ORDER_CANCELLED",
+            EventCode::FillDiscrepancy => "FILL_DISCREPANCY",
+            EventCode::CircuitOpened => "CIRCUIT_OPENED",
+            EventCode::CircuitClosed => "CIRCUIT_CLOSED",
+            EventCode::PositionOpened => "POSITION_OPENED",
+            EventCode::BookShock => "BOOK_SHOCK",
+            EventCode::PositionExited => "POSITION_EXITED",
+            EventCode::IntentRepaired => "INTENT_REPAIRED",
+            EventCode::MarketSettled => "MARKET_SETTLED",
+            EventCode::ResolutionCorrected => "RESOLUTION_CORRECTED",
+            EventCode::ResolutionUnconfirmed => "RESOLUTION_UNCONFIRMED",
+            EventCode::Halt => "HALT",
+            EventCode::HaltPending => "HALT_PENDING",
+            EventCode::HaltAcknowledged => "HALT_ACKNOWLEDGED",
+            EventCode::LossCooldown => "LOSS_COOLDOWN",
+            EventCode::AssetHalted => "ASSET_HALTED",
+            EventCode::StrategyReview => "STRATEGY_REVIEW",
+            EventCode::ModelDisagreement => "MODEL_DISAGREEMENT",
+            EventCode::ExpectedValueShortfall => "EV_SHORTFALL",
+            EventCode::RateCapHit => "RATE_CAP_HIT",
+            EventCode::DailyCapReached => "DAILY_CAP_REACHED",
+            EventCode::FlushFailed => "FLUSH_FAILED",
+            EventCode::DiskCritical => "DISK_CRITICAL",
+            EventCode::DiskRecovered => "DISK_RECOVERED",
+            EventCode::TaskRestarted => "TASK_RESTARTED",
+            EventCode::TaskFailed => "TASK_FAILED",
+            EventCode::StaleLockReclaimed => "STALE_LOCK_RECLAIMED",
+            EventCode::LeaderPromoted => "LEADER_PROMOTED",
+            EventCode::LeaderDemoted => "LEADER_DEMOTED",
+            EventCode::ScheduleTransition => "SCHEDULE_TRANSITION",
+            EventCode::CanaryPassed => "CANARY_PASSED",
+            EventCode::CanaryFailed => "CANARY_FAILED",
+            EventCode::PnlMismatch => "PNL_MISMATCH",
+            EventCode::BalanceDrift => "BALANCE_DRIFT",
+            EventCode::HandoffDraining => "HANDOFF_DRAINING",
+            EventCode::HandoffTakenOver => "HANDOFF_TAKEN_OVER",
+            EventCode::HandoffGap => "HANDOFF_GAP",
+            EventCode::Shutdown => "SHUTDOWN",
+        }
+    }
+
+    /// Level the event is logged at
+    pub fn level(&self) -> Level {
+        match self {
+            EventCode::WsGaveUp
+            | EventCode::Halt
+            | EventCode::CircuitOpened
+            | EventCode::AssetHalted
+            | EventCode::StrategyReview
+            | EventCode::DailyCapReached
+            | EventCode::CanaryFailed
+            | EventCode::PnlMismatch
+            | EventCode::BalanceDrift
+            | EventCode::ResolutionCorrected
+            | EventCode::LeaderDemoted
+            | EventCode::FlushFailed
+            | EventCode::DiskCritical
+            | EventCode::TaskFailed => Level::ERROR,
+            EventCode::WsDisconnected
+            | EventCode::WsReconnect
+            | EventCode::FeedClosed
+            | EventCode::TickLagDegraded
+            | EventCode::ClockStep
+            | EventCode::ClockPause
+            | EventCode::BookCrossed
+            | EventCode::BookUnmapped
+            | EventCode::TokensInferred
+            | EventCode::FillDiscrepancy
+            | EventCode::IntentRepaired
+            | EventCode::ResolutionUnconfirmed
+            | EventCode::BookShock
+            | EventCode::HaltPending
+            | EventCode::HaltAcknowledged
+            | EventCode::LossCooldown
+            | EventCode::ModelDisagreement
+            | EventCode::ExpectedValueShortfall
+            | EventCode::RateCapHit
+            | EventCode::StaleLockReclaimed
+            | EventCode::TaskRestarted
+            | EventCode::LeaderPromoted
+            | EventCode::HandoffDraining
+            | EventCode::HandoffTakenOver
+            | EventCode::HandoffGap => Level::WARN,
+            EventCode::SignalRejected => Level::DEBUG,
+            _ => Level::INFO,
+        }
+    }
+
+    /// One-line description for the catalogue
+    pub fn description(&self) -> &'static str {
+        match self {
+            EventCode::WsConnected => "WebSocket connection established",
+            EventCode::WsDisconnected => "WebSocket connection lost",
+            EventCode::WsReconnect => "WebSocket reconnect attempt scheduled",
+            EventCode::WsGaveUp => "WebSocket reconnect attempts exhausted",
+            EventCode::FeedClosed => "Price feed channel closed",
+            EventCode::TickLagDegraded => "Detection loop fell behind the price feed",
+            EventCode::ClockStep => {
+                "Wall clock stepped against the monotonic clock (e.g. NTP); entries frozen while it settles"
+            }
+            EventCode::ClockPause => {
+                "Process did not run for a while, as in a VM pause; held state may be stale"
+            }
+            EventCode::BookSubscribed => "Order book subscription requested",
+            EventCode::BookCrossed => "Crossed or locked order book ignored and resynced",
+            EventCode::BookUnmapped => "Order book update for a token of no tracked market",
+            EventCode::TokensInferred => {
+                "YES/NO tokens of an unlabeled market inferred from its order book"
+            }
+            EventCode::SignalEmitted => "Signal passed filters and will be traded",
+            EventCode::SignalRejected => "Signal rejected by filters or sizing",
+            EventCode::OrderRejected => "Order blocked before submission",
+            EventCode::OrderFilled => "Order filled",
+            EventCode::OrderCancelled => "Order cancelled",
+            EventCode::FillDiscrepancy => "User channel and REST disagree about a fill",
+            EventCode::CircuitOpened => "Repeated failures opened a circuit breaker",
+            EventCode::CircuitClosed => "A probe succeeded and closed a circuit breaker",
+            EventCode::PositionOpened => "Position opened from a fill",
+            EventCode::BookShock => {
+                "Depth supporting a held position evaporated; an exit was requested"
+            }
+            EventCode::PositionExited => "Position closed before settlement by an exit trigger",
+            EventCode::IntentRepaired => "Order left in flight by a crash reconciled at startup",
+            EventCode::MarketSettled => "Market settled and positions closed",
+            EventCode::ResolutionCorrected => {
+                "The official resolution contradicted a provisional settlement; its P&L was rebooked"
+            }
+            EventCode::ResolutionUnconfirmed => {
+                "No official resolution within the confirmation window; the provisional outcome stands"
+            }
+            EventCode::Halt => "Trading halted by a risk limit",
+            EventCode::HaltPending => "Unacknowledged hard halt found at startup, orders withheld",
+            EventCode::HaltAcknowledged => "Hard halt acknowledged by an operator",
+            EventCode::LossCooldown => "A settled loss paused entries on its asset",
+            EventCode::AssetHalted => "Consecutive losses halted an asset until acknowledged",
+            EventCode::StrategyReview => {
+                "Recent adverse excursions drifted well past the baseline; the strategy is flagged for review until cleared"
+            }
+            EventCode::ModelDisagreement => {
+                "GBM and linear models kept disagreeing in a market; check its strike and volatility"
+            }
+            EventCode::ExpectedValueShortfall => {
+                "Realized P&L stayed well below the expected value the signals claimed; re-tune the entry thresholds"
+            }
+            EventCode::RateCapHit => "An entry was withheld by a per-window, hourly or daily cap",
+            EventCode::DailyCapReached => {
+                "The global daily entry cap was reached; entries stop until UTC midnight"
+            }
+            EventCode::FlushFailed => "Captured data could not be written",
+            EventCode::DiskCritical => "Free disk space below the hard threshold, recording paused",
+            EventCode::DiskRecovered => "Free disk space recovered, recording resumed",
+            EventCode::TaskRestarted => "A supervised task panicked or failed and was restarted",
+            EventCode::TaskFailed => {
+                "A supervised task died and is not restarted; a trading-critical one ends the session"
+            }
+            EventCode::StaleLockReclaimed => "Data directory lock left by a dead process reclaimed",
+            EventCode::LeaderPromoted => "Instance took the leader lease and submits orders",
+            EventCode::LeaderDemoted => "Instance lost the leader lease and withholds orders",
+            EventCode::ScheduleTransition => "Trading schedule opened or closed",
+            EventCode::CanaryPassed => "Canary session met every acceptance criterion",
+            EventCode::CanaryFailed => "Canary session missed an acceptance criterion",
+            EventCode::PnlMismatch => {
+                "Fills, position tracker and trade journal disagreed on session P&L"
+            }
+            EventCode::BalanceDrift => {
+                "Exchange collateral balance drifted from the ledger beyond tolerance; adjustment booked"
+            }
+            EventCode::HandoffDraining => {
+                "Entries withheld while a new process takes over; exits still managed"
+            }
+            EventCode::HandoffTakenOver => {
+                "Positions, markets and spot windows taken over from a draining process"
+            }
+            EventCode::HandoffGap => "Spot feed silent across a handoff beyond tolerance",
+            EventCode::Shutdown => "Shutdown requested",
+        }
+    }
+}
+
+impl fmt::Display for EventCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// journald/syslog priority for a tracing level
+pub fn journald_priority(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+/// Markdown catalogue of every event code (the source of `docs/log-events.md`)
+pub fn catalogue_markdown() -> String {
+    let mut out = String::from(
+        "# Log event codes\n\n\
+         <!-- Generated from `EventCode` in src/telemetry/events.rs; do not edit by hand. -->\n\n\
+         With `log_format = \"journald\"` every console line is one JSON object,\n\
+         prefixed with its syslog priority (`<3>` error, `<4>` warn, `<6>` info,\n\
+         `<7>` debug). Ids always use the field names `market_id`, `token_id` and\n\
+         `order_id`.\n\n\
+         | Code | Level | Priority | Description |\n\
+         |------|-------|----------|-------------|\n",
+    );
+    for code in EventCode::ALL {
+        out.push_str(&format!(
+            "| `{}` | {} | {} | {} |\n",
+            code,
+            code.level(),
+            journald_priority(&code.level()),
+            code.description()
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_codes_are_unique_screaming_snake() {
+        let codes: HashSet<_> = EventCode::ALL.iter().map(|c| c.as_str()).collect();
+        assert_eq!(codes.len(), EventCode::ALL.len());
+        for code in codes {
+            assert!(code.chars().all(|c| c.is_ascii_uppercase() || c == '_'));
+        }
+    }
+
+    #[test]
+    fn test_docs_match_catalogue() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/log-events.md");
+        if std::env::var_os("BLESS").is_some() {
+            std::fs::write(path, catalogue_markdown()).unwrap();
+        }
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            catalogue_markdown(),
+            "docs/log-events.md is stale; rerun this test with BLESS=1"
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Component health registry
+//!
+//! Subsystems report their own state; the overall state is the worst of all
+//! components.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Health of a component, ordered from best to worst
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    /// Operating normally
+    #[default]
+    Healthy,
+    /// Operating with reduced capability
+    Degraded,
+    /// Not operating
+    Unhealthy,
+}
+
+/// Latest reported health of one component
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentHealth {
+    /// Component name
+    pub component: String,
+    /// Reported state
+    pub state: HealthState,
+    /// Why the component is not healthy
+    pub reason: Option<String>,
+    /// When the state was last reported
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Shared registry of component health
+#[derive(Debug, Clone, Default)]
+pub struct HealthRegistry {
+    components: Arc<RwLock<HashMap<String, ComponentHealth>>>,
+}
+
+impl HealthRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the state of a component
+    pub fn set(&self, component: &str, state: HealthState, reason: Option<String>) {
+        let mut components = self.components.write().unwrap_or_else(|e| e.into_inner());
+        let previous = components.get(component).map(|c| c.state);
+        if previous != Some(state) {
+            tracing::info!(component, ?state, reason = ?reason, "Component health changed");
+        }
+        components.insert(
+            component.to_string(),
+            ComponentHealth {
+                component: component.to_string(),
+                state,
+                reason,
+                updated_at: Utc::now(),
+            },
+        );
+    }
+
+    /// Latest health of a component
+    pub fn get(&self, component: &str) -> Option<ComponentHealth> {
+        let components = self.components.read().unwrap_or_else(|e| e.into_inner());
+        components.get(component).cloned()
+    }
+
+    /// Worst state across all components
+    pub fn overall(&self) -> HealthState {
+        let components = self.components.read().unwrap_or_else(|e| e.into_inner());
+        components
+            .values()
+            .map(|c| c.state)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// All component states, sorted by name
+    pub fn snapshot(&self) -> Vec<ComponentHealth> {
+        let components = self.components.read().unwrap_or_else(|e| e.into_inner());
+        let mut all: Vec<_> = components.values().cloned().collect();
+        all.sort_by(|a, b| a.component.cmp(&b.component));
+        all
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overall_is_worst_component() {
+        let registry = HealthRegistry::new();
+        assert_eq!(registry.overall(), HealthState::Healthy);
+
+        registry.set("feed", HealthState::Healthy, None);
+        registry.set("disk", HealthState::Degraded, Some("low space".to_string()));
+        assert_eq!(registry.overall(), HealthState::Degraded);
+
+        registry.set("disk", HealthState::Healthy, None);
+        assert_eq!(registry.overall(), HealthState::Healthy);
+        assert_eq!(registry.snapshot().len(), 2);
+    }
+
+    #[test]
+    fn test_clones_share_state() {
+        let registry = HealthRegistry::new();
+        let clone = registry.clone();
+        clone.set("ws", HealthState::Unhealthy, None);
+        assert_eq!(registry.get("ws").unwrap().state, HealthState::Unhealthy);
+    }
+}
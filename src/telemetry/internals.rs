@@ -0,0 +1,168 @@
+//! Sizes of long-lived internal structures
+//!
+//! A multi-day session whose memory creeps up needs to show which
+//! structure is growing. The run loop takes an [`InternalsSnapshot`] every
+//! `internals_interval_secs`, publishes it as gauges and writes it to
+//! [`INTERNALS_FILE`] for `status --internals`. Per-market state is dropped
+//! when the market settles; the session records (trade tape, attribution,
+//! settlements, markets seen) are kept for the end-of-session reports and
+//! grow with the markets traded by design.
+
+use super::channels::ChannelDepth;
+use super::metrics::{set_channel_depth, set_internal_size};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// File in the data directory holding the latest snapshot
+pub const INTERNALS_FILE: &str = "internals.json";
+
+/// Default seconds between snapshots
+pub const DEFAULT_INTERNALS_INTERVAL_SECS: u64 = 30;
+
+/// Records buffered by the capture writers, not yet flushed to disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecorderBuffers {
+    pub price_ticks: usize,
+    pub orderbooks: usize,
+}
+
+/// Entry counts of the engine's structures and the depth of its queues
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InternalsSnapshot {
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+    /// Tokens with book state held
+    pub order_books: usize,
+    /// Price levels across the books held
+    pub book_levels: usize,
+    /// Active markets
+    pub markets: usize,
+    /// Per-market and per-position detector state: outcome watchers,
+    /// disagreement streaks and book shock watches
+    pub detectors: usize,
+    /// Spot prices held by the momentum and volatility windows
+    pub price_samples: usize,
+    pub open_positions: usize,
+    /// Closed positions in memory; older ones are archived
+    pub closed_positions: usize,
+    /// Rows kept for the end-of-session reports
+    pub session_records: usize,
+    /// Instrumented channels and bus subscriptions
+    pub channels: Vec<ChannelDepth>,
+    /// Capture writer buffers, when capture is on
+    pub recorder: Option<RecorderBuffers>,
+    /// Journal appends waiting on a writer
+    pub journal_queue: usize,
+}
+
+impl InternalsSnapshot {
+    /// Sizes by gauge label, in display order
+    pub fn sizes(&self) -> Vec<(&'static str, usize)> {
+        let mut sizes = vec![
+            ("order_books", self.order_books),
+            ("book_levels", self.book_levels),
+            ("markets", self.markets),
+            ("detectors", self.detectors),
+            ("price_samples", self.price_samples),
+            ("open_positions", self.open_positions),
+            ("closed_positions", self.closed_positions),
+            ("session_records", self.session_records),
+            ("journal_queue", self.journal_queue),
+        ];
+        if let Some(recorder) = self.recorder {
+            sizes.push(("recorder_price_buffer", recorder.price_ticks));
+            sizes.push(("recorder_orderbook_buffer", recorder.orderbooks));
+        }
+        sizes
+    }
+
+    /// Set the internals gauges from the snapshot
+    pub fn publish(&self) {
+        for (component, count) in self.sizes() {
+            set_internal_size(component, count as f64);
+        }
+        for channel in &self.channels {
+            set_channel_depth(&channel.name, channel.depth as f64);
+        }
+    }
+
+    /// Write the snapshot to `path`, replacing any earlier one whole
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// The snapshot at `path`, if one was written
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl fmt::Display for InternalsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "    {:<26} {}", "taken_at", self.taken_at.to_rfc3339())?;
+        for (component, count) in self.sizes() {
+            writeln!(f, "    {:<26} {}", component, count)?;
+        }
+        for channel in &self.channels {
+            writeln!(
+                f,
+                "    {:<26} {}/{}",
+                format!("channel {}", channel.name),
+                channel.depth,
+                channel.capacity
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_load_and_display() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(INTERNALS_FILE);
+        assert_eq!(InternalsSnapshot::load(&path).unwrap(), None);
+
+        let snapshot = InternalsSnapshot {
+            taken_at: DateTime::from_timestamp(1_767_600_000, 0).unwrap(),
+            order_books: 4,
+            book_levels: 40,
+            channels: vec![ChannelDepth {
+                name: "bus:recorder".to_string(),
+                depth: 12,
+                capacity: 65_536,
+            }],
+            recorder: Some(RecorderBuffers {
+                price_ticks: 7,
+                orderbooks: 3,
+            }),
+            ..Default::default()
+        };
+        snapshot.save(&path).unwrap();
+        assert_eq!(
+            InternalsSnapshot::load(&path).unwrap(),
+            Some(snapshot.clone())
+        );
+
+        let shown = snapshot.to_string();
+        assert!(shown.contains("book_levels                40"));
+        assert!(shown.contains("recorder_price_buffer      7"));
+        assert!(shown.contains("channel bus:recorder       12/65536"));
+    }
+}
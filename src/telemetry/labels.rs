@@ -0,0 +1,353 @@
+//! Metric label cardinality controls
+//!
+//! Hundreds of 15-minute markets open each day, so a metric labelled by
+//! condition or token id grows a new series per market and the scrape
+//! balloons. [`LabelPolicy`] maps those ids to a bounded group label,
+//! asset and interval such as `BTC-15m`, unless the market is on the
+//! `individual` allowlist for debugging. Series labelled with one market's
+//! id are forgotten `expiry_mins` after it closes, and the exporter drops
+//! series idle that long from the scrape. Past `max_series` label
+//! combinations, new ones are logged and dropped.
+
+use crate::duration::{DurationConfig, Minutes};
+use crate::market::Market;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+
+/// Default minutes a closed market's series outlive it
+pub const DEFAULT_SERIES_EXPIRY_MINS: u64 = 30;
+
+/// Default cap on label combinations across all metrics
+pub const DEFAULT_MAX_SERIES: usize = 2000;
+
+/// Label of a market id no registered market has
+pub const UNKNOWN_MARKET_LABEL: &str = "unknown";
+
+/// Per-market label settings, under `[telemetry.labels]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelConfig {
+    /// Condition or token ids labelled by id rather than by group, for
+    /// debugging one market
+    #[serde(default)]
+    pub individual: Vec<String>,
+    /// Time after a market closes before its series are expired
+    #[serde(default = "default_series_expiry_mins")]
+    pub expiry_mins: DurationConfig<Minutes>,
+}
+
+fn default_series_expiry_mins() -> DurationConfig<Minutes> {
+    DurationConfig::from_mins(DEFAULT_SERIES_EXPIRY_MINS)
+}
+
+impl Default for LabelConfig {
+    fn default() -> Self {
+        Self {
+            individual: vec![],
+            expiry_mins: default_series_expiry_mins(),
+        }
+    }
+}
+
+/// A registered market: its group label and when it closed
+#[derive(Debug, Clone)]
+struct MarketLabel {
+    condition_id: String,
+    group: String,
+    closed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+struct LabelState {
+    /// Keyed by condition id and by each token id
+    markets: HashMap<String, MarketLabel>,
+    /// Admitted series by metric name and rendered labels, with the
+    /// condition id of the market labelled individually, if any
+    series: HashMap<(&'static str, String), Option<String>>,
+    /// Metrics already warned about hitting the cap
+    capped: HashSet<&'static str>,
+    dropped: u64,
+}
+
+/// Maps market ids to bounded labels and caps the series count
+#[derive(Debug)]
+pub struct LabelPolicy {
+    individual: HashSet<String>,
+    expiry: Duration,
+    max_series: usize,
+    state: Mutex<LabelState>,
+}
+
+impl Default for LabelPolicy {
+    fn default() -> Self {
+        Self::new(&LabelConfig::default(), DEFAULT_MAX_SERIES)
+    }
+}
+
+impl LabelPolicy {
+    /// Policy from `config`, admitting at most `max_series` combinations
+    pub fn new(config: &LabelConfig, max_series: usize) -> Self {
+        Self {
+            individual: config.individual.iter().cloned().collect(),
+            expiry: config.expiry_mins.to_chrono(),
+            max_series,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Learn `market`'s group, for labelling its condition and token ids
+    pub fn register_market(&self, market: &Market) {
+        let mins = (market.close_time - market.open_time).num_minutes();
+        let label = MarketLabel {
+            condition_id: market.condition_id.clone(),
+            group: format!("{}-{}m", market.asset, mins),
+            closed_at: None,
+        };
+        let mut state = self.lock();
+        for id in [
+            &market.condition_id,
+            &market.yes_token_id,
+            &market.no_token_id,
+        ] {
+            state.markets.insert(id.clone(), label.clone());
+        }
+    }
+
+    /// Mark `condition_id` closed at `at` and expire series of markets
+    /// closed longer than the expiry window
+    pub fn close_market(&self, condition_id: &str, at: DateTime<Utc>) {
+        {
+            let mut state = self.lock();
+            for label in state.markets.values_mut() {
+                if label.condition_id == condition_id {
+                    label.closed_at.get_or_insert(at);
+                }
+            }
+        }
+        self.expire(at);
+    }
+
+    /// Label for a condition or token id
+    pub fn market_label(&self, id: &str) -> String {
+        if self.individual.contains(id) {
+            return id.to_string();
+        }
+        match self.lock().markets.get(id) {
+            Some(label) => label.group.clone(),
+            None => UNKNOWN_MARKET_LABEL.to_string(),
+        }
+    }
+
+    /// Whether `metric` may be emitted with `labels`, built from the market
+    /// id `market`; a new combination past the cap is logged and dropped
+    pub fn admit(&self, metric: &'static str, labels: &[(&str, &str)], market: &str) -> bool {
+        let rendered = labels
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(",");
+        let key = (metric, rendered);
+        let mut state = self.lock();
+        if state.series.contains_key(&key) {
+            return true;
+        }
+        if state.series.len() >= self.max_series {
+            state.dropped += 1;
+            if state.capped.insert(metric) {
+                tracing::warn!(
+                    metric,
+                    labels = %key.1,
+                    max_series = self.max_series,
+                    "Metric series cap reached, dropping new label combinations"
+                );
+            }
+            return false;
+        }
+        // Only series labelled by id expire; group labels are bounded
+        let individual = self.individual.contains(market).then(|| {
+            state
+                .markets
+                .get(market)
+                .map_or_else(|| market.to_string(), |m| m.condition_id.clone())
+        });
+        state.series.insert(key, individual);
+        true
+    }
+
+    /// Forget series of markets closed more than the expiry window before
+    /// `now`, and the markets themselves; returns the series removed
+    pub fn expire(&self, now: DateTime<Utc>) -> usize {
+        let mut state = self.lock();
+        let expired: HashSet<String> = state
+            .markets
+            .values()
+            .filter(|m| m.closed_at.is_some_and(|at| now - at >= self.expiry))
+            .map(|m| m.condition_id.clone())
+            .collect();
+        if expired.is_empty() {
+            return 0;
+        }
+        state
+            .markets
+            .retain(|_, m| !expired.contains(&m.condition_id));
+        let before = state.series.len();
+        state
+            .series
+            .retain(|_, market| market.as_ref().is_none_or(|m| !expired.contains(m)));
+        let removed = before - state.series.len();
+        if removed > 0 {
+            // Room under the cap again
+            state.capped.clear();
+            tracing::debug!(
+                markets = expired.len(),
+                series = removed,
+                "Expired metric series"
+            );
+        }
+        removed
+    }
+
+    /// Label combinations admitted and not expired
+    pub fn series_count(&self) -> usize {
+        self.lock().series.len()
+    }
+
+    /// New label combinations dropped at the cap
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    /// Minutes a closed market's series outlive it
+    pub fn expiry(&self) -> Duration {
+        self.expiry
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LabelState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+static POLICY: OnceLock<LabelPolicy> = OnceLock::new();
+
+/// Install the process-wide label policy; later calls are ignored
+pub fn init_label_policy(config: &LabelConfig, max_series: usize) {
+    let _ = POLICY.set(LabelPolicy::new(config, max_series));
+}
+
+/// The process-wide label policy, defaults until initialized
+pub fn label_policy() -> &'static LabelPolicy {
+    POLICY.get_or_init(LabelPolicy::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn at(mins: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_767_600_000, 0).unwrap() + Duration::minutes(mins)
+    }
+
+    /// The `i`-th 15-minute BTC market, opening `i * 15` minutes in
+    fn market(i: i64) -> Market {
+        Market {
+            condition_id: format!("cond-{}", i),
+            asset: "BTC".to_string(),
+            yes_token_id: format!("yes-{}", i),
+            no_token_id: format!("no-{}", i),
+            open_price: dec!(100000),
+            open_time: at(i * 15),
+            close_time: at(i * 15 + 15),
+            group_id: None,
+            orientation: Default::default(),
+        }
+    }
+
+    /// Emit a per-market gauge and a per-token counter for `market`
+    fn emit(policy: &LabelPolicy, market: &Market) {
+        let label = policy.market_label(&market.condition_id);
+        policy.admit("deviation", &[("market", &label)], &market.condition_id);
+        for token in [&market.yes_token_id, &market.no_token_id] {
+            let label = policy.market_label(token);
+            policy.admit("dropped", &[("token", &label), ("reason", "stale")], token);
+        }
+    }
+
+    #[test]
+    fn test_grouped_labels_stay_bounded_across_500_markets() {
+        let policy = LabelPolicy::new(&LabelConfig::default(), 100);
+        for i in 0..500 {
+            let market = market(i);
+            policy.register_market(&market);
+            emit(&policy, &market);
+            policy.close_market(&market.condition_id, market.close_time);
+        }
+        assert_eq!(policy.market_label("cond-499"), "BTC-15m");
+        assert_eq!(policy.market_label("yes-499"), "BTC-15m");
+        assert_eq!(policy.market_label("never-seen"), UNKNOWN_MARKET_LABEL);
+        // One series per metric, whatever the market count
+        assert_eq!(policy.series_count(), 2);
+        assert_eq!(policy.dropped(), 0);
+    }
+
+    #[test]
+    fn test_allowlisted_markets_expire_after_the_window() {
+        let config = LabelConfig {
+            individual: (0..500).map(|i| format!("cond-{}", i)).collect(),
+            expiry_mins: DurationConfig::from_mins(30),
+        };
+        let policy = LabelPolicy::new(&config, 10_000);
+        let mut peak = 0;
+        for i in 0..500 {
+            let market = market(i);
+            policy.register_market(&market);
+            emit(&policy, &market);
+            assert_eq!(
+                policy.market_label(&market.condition_id),
+                market.condition_id
+            );
+            policy.close_market(&market.condition_id, market.close_time);
+            peak = peak.max(policy.series_count());
+        }
+        // Per-market series for the markets closed inside the last 30
+        // minutes, plus the grouped token series
+        assert!(peak <= 4, "peak {}", peak);
+        assert_eq!(policy.series_count(), 3);
+        let last = market(499);
+        assert_eq!(policy.expire(last.close_time + Duration::minutes(29)), 1);
+        assert_eq!(policy.market_label("yes-498"), UNKNOWN_MARKET_LABEL);
+        assert_eq!(policy.market_label("yes-499"), "BTC-15m");
+        assert_eq!(policy.expire(last.close_time + Duration::minutes(30)), 1);
+        assert_eq!(policy.series_count(), 1);
+        // A market is forgotten with its series
+        assert_eq!(policy.market_label("yes-499"), UNKNOWN_MARKET_LABEL);
+    }
+
+    #[test]
+    fn test_new_combinations_past_the_cap_are_dropped() {
+        let config = LabelConfig {
+            individual: (0..500).map(|i| format!("cond-{}", i)).collect(),
+            expiry_mins: DurationConfig::from_mins(30),
+        };
+        let policy = LabelPolicy::new(&config, 50);
+        for i in 0..500 {
+            let market = market(i);
+            policy.register_market(&market);
+            emit(&policy, &market);
+        }
+        assert_eq!(policy.series_count(), 50);
+        // Grouped token series plus 49 individual markets fit
+        assert_eq!(policy.dropped(), 451);
+        // Known combinations are still admitted at the cap
+        assert!(policy.admit("deviation", &[("market", "cond-0")], "cond-0"));
+        assert!(!policy.admit("deviation", &[("market", "cond-499")], "cond-499"));
+
+        // Expiry frees room under the cap
+        for i in 0..10 {
+            policy.close_market(&format!("cond-{}", i), at(0));
+        }
+        assert_eq!(policy.expire(at(10_000)), 10);
+        assert!(policy.admit("deviation", &[("market", "cond-499")], "cond-499"));
+    }
+}
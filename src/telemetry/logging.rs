@@ -1,25 +1,489 @@
 //! Structured logging setup
+//!
+//! The active filter accepts `EnvFilter` directives (e.g.
+//! `info,poly_hft::orderbook=debug,poly_hft::ws=trace`) and can be swapped at
+//! runtime through [`LogReloadHandle`], or in a running session with
+//! `poly-hft ctl log-level`. Console output uses [`LogFormat`]; the
+//! optional rolling log file is always JSON lines.
+//!
+//! [`LogFormat::Journald`] is for running under systemd: one JSON object per
+//! line with no span nesting, prefixed with the syslog priority so journald
+//! records the right severity, and the `event_code` field lifted to the top.
 
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use super::events::{fields, journald_priority};
+use crate::config::LogFileConfig;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
 
 /// Log output format
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogFormat {
     /// Human-readable format
+    #[default]
     Pretty,
     /// JSON format for log aggregation
     Json,
+    /// Single-line JSON with a syslog priority prefix, for journald
+    Journald,
 }
 
-/// Initialize logging with the given level
-pub fn init_logging(level: &str) -> anyhow::Result<()> {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+/// Formats each event as `<priority>{json}` on a single line
+pub struct JournaldFormat;
 
-    tracing_subscriber::registry()
-        .with(filter)
-        .with(fmt::layer())
+impl<S, N> FormatEvent<S, N> for JournaldFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let meta = event.metadata();
+        let mut visitor = JsonFields::default();
+        event.record(&mut visitor);
+
+        let mut line = Map::new();
+        line.insert(
+            "ts".into(),
+            Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Micros, true)
+                .into(),
+        );
+        line.insert("level".into(), meta.level().as_str().into());
+        line.insert("target".into(), meta.target().into());
+        if let Some(code) = visitor.0.remove(fields::EVENT_CODE) {
+            line.insert(fields::EVENT_CODE.into(), code);
+        }
+        line.append(&mut visitor.0);
+        writeln!(
+            writer,
+            "<{}>{}",
+            journald_priority(meta.level()),
+            Value::Object(line)
+        )
+    }
+}
+
+/// Collects event fields as JSON values
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
+
+/// Log file rotation period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// New file every hour (`prefix.YYYY-MM-DD-HH.log`)
+    Hourly,
+    /// New file every day (`prefix.YYYY-MM-DD.log`)
+    #[default]
+    Daily,
+    /// Single file, never rotated (`prefix.log`)
+    Never,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+/// Handle for changing the active log filter at runtime
+#[derive(Clone)]
+pub struct LogReloadHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogReloadHandle {
+    /// Replace the active filter with the given directives
+    pub fn reload(&self, directives: &str) -> anyhow::Result<()> {
+        let filter = parse_directives(directives)?;
+        self.handle
+            .reload(filter)
+            .map_err(|e| anyhow::anyhow!("Failed to reload log filter: {}", e))?;
+        tracing::info!(directives, "Log filter reloaded");
+        Ok(())
+    }
+
+    /// Currently active filter directives
+    pub fn current(&self) -> Option<String> {
+        self.handle.with_current(|filter| filter.to_string()).ok()
+    }
+}
+
+static RELOAD_HANDLE: OnceLock<LogReloadHandle> = OnceLock::new();
+
+/// Replace the process-wide log filter
+///
+/// Fails if logging has not been initialized via [`init_logging`].
+pub fn set_log_directives(directives: &str) -> anyhow::Result<()> {
+    RELOAD_HANDLE
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("Logging not initialized"))?
+        .reload(directives)
+}
+
+/// File in the data directory `poly-hft ctl log-level` leaves directives in
+/// for the running session
+pub const LOG_LEVEL_REQUEST_FILE: &str = "log-level.request";
+
+/// Ask the session running on `data_dir` to switch its log filter to
+/// `directives`, rejecting invalid directives up front
+pub fn request_log_directives(data_dir: &Path, directives: &str) -> anyhow::Result<()> {
+    parse_directives(directives)?;
+    std::fs::create_dir_all(data_dir)?;
+    std::fs::write(data_dir.join(LOG_LEVEL_REQUEST_FILE), directives)?;
+    Ok(())
+}
+
+/// Directives requested for the session running on `data_dir`, clearing
+/// the request
+pub fn take_log_directives_request(data_dir: &Path) -> Option<String> {
+    let path = data_dir.join(LOG_LEVEL_REQUEST_FILE);
+    let directives = std::fs::read_to_string(&path).ok()?;
+    if let Err(e) = std::fs::remove_file(&path) {
+        tracing::warn!(error = %e, "Failed to remove log level request");
+    }
+    Some(directives.trim().to_string())
+}
+
+/// Apply the directives requested for the session running on `data_dir`,
+/// if any, with [`set_log_directives`]
+pub fn apply_log_directives_request(data_dir: &Path) {
+    apply_request(data_dir, set_log_directives);
+}
+
+fn apply_request(data_dir: &Path, apply: impl FnOnce(&str) -> anyhow::Result<()>) {
+    let Some(directives) = take_log_directives_request(data_dir) else {
+        return;
+    };
+    if let Err(e) = apply(&directives) {
+        tracing::warn!(directives, error = %e, "Ignoring log level request");
+    }
+}
+
+/// Parse an `EnvFilter` directive string, rejecting invalid directives
+pub fn parse_directives(directives: &str) -> anyhow::Result<EnvFilter> {
+    EnvFilter::builder()
+        .parse(directives)
+        .map_err(|e| anyhow::anyhow!("Invalid log directives '{}': {}", directives, e))
+}
+
+/// Create the rolling file appender for the given configuration
+pub fn file_appender(config: &LogFileConfig) -> anyhow::Result<RollingFileAppender> {
+    RollingFileAppender::builder()
+        .rotation(config.rotation.into())
+        .filename_prefix(&config.prefix)
+        .filename_suffix("log")
+        .max_log_files(config.max_files.max(1))
+        .build(&config.directory)
+        .map_err(|e| anyhow::anyhow!("Failed to create log file appender: {}", e))
+}
+
+/// Keeps the file writer alive; dropping it flushes buffered log lines
+pub struct LoggingGuard {
+    reload: LogReloadHandle,
+    _file_guard: Option<WorkerGuard>,
+}
+
+impl LoggingGuard {
+    /// Handle for changing the log filter at runtime
+    pub fn reload_handle(&self) -> &LogReloadHandle {
+        &self.reload
+    }
+}
+
+/// Initialize logging
+///
+/// `RUST_LOG`, when set, takes precedence over `directives`.
+pub fn init_logging(
+    directives: &str,
+    format: LogFormat,
+    file: Option<&LogFileConfig>,
+) -> anyhow::Result<LoggingGuard> {
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => parse_directives(directives)?,
+    };
+
+    let (subscriber, reload, file_guard) = build_subscriber(filter, format, file)?;
+    subscriber
         .try_init()
         .map_err(|e| anyhow::anyhow!("Failed to init logging: {}", e))?;
 
-    Ok(())
+    let _ = RELOAD_HANDLE.set(reload.clone());
+
+    Ok(LoggingGuard {
+        reload,
+        _file_guard: file_guard,
+    })
+}
+
+fn build_subscriber(
+    filter: EnvFilter,
+    format: LogFormat,
+    file: Option<&LogFileConfig>,
+) -> anyhow::Result<(
+    impl Subscriber + Send + Sync + 'static,
+    LogReloadHandle,
+    Option<WorkerGuard>,
+)> {
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let console = match format {
+        LogFormat::Pretty => fmt::layer().boxed(),
+        LogFormat::Json => fmt::layer().json().boxed(),
+        LogFormat::Journald => fmt::layer()
+            .with_ansi(false)
+            .event_format(JournaldFormat)
+            .boxed(),
+    };
+
+    let (file_layer, file_guard) = match file {
+        Some(config) => {
+            let (writer, guard) = tracing_appender::non_blocking(file_appender(config)?);
+            let layer = fmt::layer()
+                .json()
+                .with_ansi(false)
+                .with_writer(writer)
+                .boxed();
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(file_layer);
+
+    Ok((subscriber, LogReloadHandle { handle }, file_guard))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use tracing::Level;
+
+    fn file_config(dir: &std::path::Path, rotation: LogRotation) -> LogFileConfig {
+        LogFileConfig {
+            directory: dir.to_path_buf(),
+            prefix: "poly-hft".to_string(),
+            rotation,
+            max_files: 3,
+        }
+    }
+
+    fn file_names(dir: &std::path::Path) -> Vec<String> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        let filter = parse_directives("info,poly_hft::orderbook=debug,poly_hft::ws=trace").unwrap();
+        let rendered = filter.to_string();
+        assert!(rendered.contains("poly_hft::orderbook=debug"));
+        assert!(rendered.contains("poly_hft::ws=trace"));
+
+        assert!(parse_directives("info,poly_hft::ws=loud").is_err());
+    }
+
+    #[test]
+    fn test_log_format_deserialize() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            format: LogFormat,
+            rotation: LogRotation,
+        }
+        let w: Wrapper = toml::from_str("format = \"json\"\nrotation = \"hourly\"").unwrap();
+        assert_eq!(w.format, LogFormat::Json);
+        assert_eq!(w.rotation, LogRotation::Hourly);
+        let w: Wrapper = toml::from_str("format = \"journald\"\nrotation = \"daily\"").unwrap();
+        assert_eq!(w.format, LogFormat::Journald);
+    }
+
+    #[test]
+    fn test_journald_format_is_single_line_json() {
+        use crate::telemetry::EventCode;
+        use std::sync::{Arc, Mutex};
+
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let writer = {
+            let buffer = buffer.clone();
+            move || BufferWriter(buffer.clone())
+        };
+        let subscriber = tracing_subscriber::registry().with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer)
+                .event_format(JournaldFormat),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(
+                event_code = %EventCode::WsReconnect,
+                attempt = 3,
+                "Reconnecting\nafter error"
+            );
+            tracing::error!(event_code = %EventCode::FlushFailed, market_id = "0xabc", "Write failed");
+        });
+
+        let output = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("<4>{"));
+        assert!(lines[1].starts_with("<3>{"));
+        let first: serde_json::Value = serde_json::from_str(&lines[0][3..]).unwrap();
+        assert_eq!(first["event_code"], "WS_RECONNECT");
+        assert_eq!(first["attempt"], 3);
+        assert_eq!(first["message"], "Reconnecting\nafter error");
+        let second: serde_json::Value = serde_json::from_str(&lines[1][3..]).unwrap();
+        assert_eq!(second["market_id"], "0xabc");
+        assert_eq!(second["level"], "ERROR");
+    }
+
+    struct BufferWriter(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for BufferWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_daily_file_naming() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut appender = file_appender(&file_config(dir.path(), LogRotation::Daily)).unwrap();
+        std::io::Write::write_all(&mut appender, b"{}\n").unwrap();
+
+        let expected = format!("poly-hft.{}.log", Utc::now().format("%Y-%m-%d"));
+        assert_eq!(file_names(dir.path()), vec![expected]);
+    }
+
+    #[test]
+    fn test_hourly_and_never_file_naming() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut appender = file_appender(&file_config(dir.path(), LogRotation::Hourly)).unwrap();
+        std::io::Write::write_all(&mut appender, b"{}\n").unwrap();
+        let expected = format!("poly-hft.{}.log", Utc::now().format("%Y-%m-%d-%H"));
+        assert_eq!(file_names(dir.path()), vec![expected]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut appender = file_appender(&file_config(dir.path(), LogRotation::Never)).unwrap();
+        std::io::Write::write_all(&mut appender, b"{}\n").unwrap();
+        assert_eq!(file_names(dir.path()), vec!["poly-hft.log".to_string()]);
+    }
+
+    #[test]
+    fn test_runtime_reload() {
+        let filter = parse_directives("info").unwrap();
+        let (subscriber, handle, _) = build_subscriber(filter, LogFormat::Pretty, None).unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(Level::DEBUG));
+            handle.reload("debug").unwrap();
+            assert!(tracing::enabled!(Level::DEBUG));
+            assert!(handle.reload("info,poly_hft::ws=loud").is_err());
+            assert_eq!(handle.current().as_deref(), Some("debug"));
+        });
+    }
+
+    #[test]
+    fn test_requested_directives_reload_the_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let filter = parse_directives("info").unwrap();
+        let (subscriber, handle, _) = build_subscriber(filter, LogFormat::Pretty, None).unwrap();
+
+        assert!(request_log_directives(dir.path(), "info,poly_hft::ws=loud").is_err());
+        assert!(!dir.path().join(LOG_LEVEL_REQUEST_FILE).exists());
+        request_log_directives(dir.path(), "info,poly_hft::ws=debug").unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            apply_request(dir.path(), |d| handle.reload(d));
+            assert_eq!(handle.current().as_deref(), Some("poly_hft::ws=debug,info"));
+            // Taken once
+            assert!(take_log_directives_request(dir.path()).is_none());
+
+            // A request spoiled on disk is dropped, the filter kept
+            std::fs::write(dir.path().join(LOG_LEVEL_REQUEST_FILE), "=bogus=").unwrap();
+            apply_request(dir.path(), |d| handle.reload(d));
+            assert!(!dir.path().join(LOG_LEVEL_REQUEST_FILE).exists());
+            assert_eq!(handle.current().as_deref(), Some("poly_hft::ws=debug,info"));
+        });
+    }
+
+    #[test]
+    fn test_file_layer_writes_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = file_config(dir.path(), LogRotation::Never);
+        let filter = parse_directives("info").unwrap();
+        let (subscriber, _, guard) =
+            build_subscriber(filter, LogFormat::Pretty, Some(&config)).unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(market = "abc", "hello file");
+        });
+        drop(guard);
+
+        let content = std::fs::read_to_string(dir.path().join("poly-hft.log")).unwrap();
+        let line: serde_json::Value =
+            serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(line["fields"]["message"], "hello file");
+    }
 }
@@ -1,16 +1,23 @@
 //! Prometheus metrics implementation
 
+use super::labels::label_policy;
 use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
 use metrics_exporter_prometheus::PrometheusBuilder;
+use metrics_util::MetricKindMask;
 use std::net::SocketAddr;
 use std::time::Duration;
 
 /// Initialize the Prometheus metrics exporter
+///
+/// Series not updated for the label policy's expiry window drop out of
+/// the scrape until updated again, so a closed market's series go.
 pub fn init_metrics_server(port: u16) -> anyhow::Result<()> {
     let addr: SocketAddr = ([0, 0, 0, 0], port).into();
+    let idle = label_policy().expiry().to_std().ok();
 
     PrometheusBuilder::new()
         .with_http_listener(addr)
+        .idle_timeout(MetricKindMask::ALL, idle)
         .install()
         .map_err(|e| anyhow::anyhow!("Failed to install metrics exporter: {}", e))?;
 
@@ -41,6 +48,30 @@ fn register_metrics() {
         "polyhft_order_submission_latency_ms",
         "Order submission latency in milliseconds"
     );
+    describe_histogram!(
+        "polyhft_tick_lag_ms",
+        "Exchange time to detection loop dequeue in milliseconds"
+    );
+    describe_histogram!(
+        "polyhft_market_discovery_ms",
+        "Duration of one Gamma market discovery cycle in milliseconds"
+    );
+    describe_histogram!(
+        "polyhft_open_to_first_book_seconds",
+        "Seconds from a market's open to its first order book, zero if subscribed before open"
+    );
+    describe_histogram!(
+        "polyhft_tick_batch_size",
+        "Price ticks drained into one detection pass"
+    );
+    describe_histogram!(
+        "polyhft_journal_batch_size",
+        "Journal entries written and fsynced together by the journal writer"
+    );
+    describe_histogram!(
+        "polyhft_journal_durable_latency_ms",
+        "Journal entry enqueue to fsync latency in milliseconds"
+    );
 
     // Counters
     describe_counter!("polyhft_price_ticks_total", "Total price updates received");
@@ -52,6 +83,42 @@ fn register_metrics() {
         "polyhft_signals_total",
         "Total signals generated by side, reason, and action"
     );
+    describe_counter!(
+        "polyhft_signals_rejected_total",
+        "Signals rejected by a filter, by reason"
+    );
+    describe_counter!(
+        "polyhft_exits_total",
+        "Positions closed before settlement, by exit reason"
+    );
+    describe_counter!(
+        "polyhft_resolutions_total",
+        "Provisional settlements finalized, by status: confirmed, corrected or unconfirmed"
+    );
+    describe_gauge!(
+        "polyhft_provisional_pnl",
+        "Booked P&L of settlements awaiting their official resolution"
+    );
+    describe_counter!(
+        "polyhft_task_restarts_total",
+        "Supervised task restarts after a panic or failure, by component"
+    );
+    describe_counter!(
+        "polyhft_model_disagreements_total",
+        "Detections whose GBM and linear estimates disagreed past the limit, by action"
+    );
+    describe_counter!(
+        "polyhft_rate_cap_hits_total",
+        "Entries withheld by a rate cap, by cap"
+    );
+    describe_counter!(
+        "polyhft_book_messages_dropped_total",
+        "Book messages dropped per token as duplicates or out of order"
+    );
+    describe_counter!(
+        "polyhft_book_freshness_checks_total",
+        "Book staleness checks by verdict"
+    );
     describe_counter!("polyhft_orders_total", "Total orders by side and status");
     describe_counter!("polyhft_fills_total", "Total executed fills by side");
     describe_counter!(
@@ -59,9 +126,77 @@ fn register_metrics() {
         "WebSocket reconnection count by feed"
     );
     describe_counter!("polyhft_errors_total", "Errors by component and type");
+    describe_counter!(
+        "polyhft_data_bytes_written_total",
+        "Bytes written to captured data files by prefix"
+    );
+    describe_gauge!(
+        "polyhft_capture_queue_depth",
+        "Records in a durable capture queue not yet written, by prefix"
+    );
+    describe_gauge!(
+        "polyhft_capture_queue_segments",
+        "Segment files of a durable capture queue by prefix"
+    );
+    describe_counter!(
+        "polyhft_capture_queue_replayed_total",
+        "Capture queue records replayed after a restart by prefix"
+    );
+    describe_histogram!(
+        "polyhft_retry_attempts",
+        "Attempts made by one retried call, by subsystem"
+    );
+    describe_histogram!(
+        "polyhft_retry_seconds",
+        "Time one retried call took, attempts and waits together, by subsystem"
+    );
+    describe_counter!(
+        "polyhft_retry_budget_exhausted_total",
+        "Failures returned without retrying because a subsystem's retry budget was spent"
+    );
+    describe_gauge!(
+        "polyhft_window_spread_percentile",
+        "Rank, 0 to 1, of the latest YES spread among recent spreads in the same window minute, by asset"
+    );
+    describe_counter!(
+        "polyhft_capture_books_suppressed_total",
+        "Order books not captured for repeating the token's last row"
+    );
+    describe_counter!(
+        "polyhft_stream_dropped_total",
+        "Event stream entries dropped for slow clients by kind"
+    );
+    describe_counter!(
+        "polyhft_ticks_skipped_total",
+        "Price ticks the detection loop skipped by reason"
+    );
+    describe_counter!(
+        "polyhft_bus_dropped_total",
+        "Market data events dropped for slow bus subscribers"
+    );
+    describe_counter!(
+        "polyhft_crossed_books_total",
+        "Crossed or locked books seen by state and consumer"
+    );
+    describe_counter!(
+        "polyhft_unmapped_books_total",
+        "Order book updates for tokens of no tracked market"
+    );
+    describe_counter!(
+        "polyhft_gamma_schema_drift_total",
+        "Gamma response fields missing or of an unexpected type, by entity, field and kind"
+    );
+    describe_counter!(
+        "polyhft_flag_evaluations_total",
+        "Feature flags evaluated for signals, by flag and cohort"
+    );
 
     // Gauges
     describe_gauge!("polyhft_equity_usd", "Current equity value in USD");
+    describe_gauge!(
+        "polyhft_book_age_threshold_ms",
+        "Largest book age accepted per token, in milliseconds"
+    );
     describe_gauge!("polyhft_unrealized_pnl_usd", "Open position P&L in USD");
     describe_gauge!("polyhft_realized_pnl_usd", "Closed position P&L in USD");
     describe_gauge!("polyhft_open_positions", "Number of open positions");
@@ -73,6 +208,46 @@ fn register_metrics() {
     describe_gauge!("polyhft_daily_pnl_usd", "Today's P&L in USD");
     describe_gauge!("polyhft_current_volatility", "Estimated BTC volatility");
     describe_gauge!("polyhft_active_markets", "Number of tracked markets");
+    describe_gauge!(
+        "polyhft_data_dir_bytes",
+        "Current size of the data directory in bytes"
+    );
+    describe_gauge!(
+        "polyhft_book_consistency_deviation",
+        "YES mid + NO mid - 1 by market"
+    );
+    describe_gauge!(
+        "polyhft_leader",
+        "1 if this instance holds the leader lease, 0 on standby"
+    );
+    describe_gauge!(
+        "polyhft_warm_start",
+        "1 if the spot windows were restored from a saved state at startup, 0 if cold"
+    );
+    describe_gauge!(
+        "polyhft_leader_lease_age_seconds",
+        "Seconds the current holder has held the leader lease"
+    );
+    describe_gauge!(
+        "polyhft_schedule_open",
+        "1 if the strategy's trading schedule is open, 0 otherwise"
+    );
+    describe_gauge!(
+        "polyhft_schedule_next_transition_seconds",
+        "Seconds until the strategy's schedule next opens or closes"
+    );
+    describe_gauge!(
+        "polyhft_internal_entries",
+        "Entries held by a long-lived internal structure, by component"
+    );
+    describe_gauge!(
+        "polyhft_channel_depth",
+        "Messages queued in an instrumented channel or bus subscription"
+    );
+    describe_gauge!(
+        "polyhft_config_info",
+        "Always 1, labelled with the running config's fingerprint hash"
+    );
 }
 
 /// Latency metric types
@@ -86,6 +261,10 @@ pub enum LatencyMetric {
     SignalGeneration,
     /// Order submission latency
     OrderSubmission,
+    /// Exchange time to detection loop dequeue
+    TickLag,
+    /// One Gamma market discovery cycle
+    MarketDiscovery,
 }
 
 impl LatencyMetric {
@@ -95,6 +274,8 @@ impl LatencyMetric {
             LatencyMetric::OrderBook => "polyhft_orderbook_update_latency_ms",
             LatencyMetric::SignalGeneration => "polyhft_signal_generation_latency_ms",
             LatencyMetric::OrderSubmission => "polyhft_order_submission_latency_ms",
+            LatencyMetric::TickLag => "polyhft_tick_lag_ms",
+            LatencyMetric::MarketDiscovery => "polyhft_market_discovery_ms",
         }
     }
 }
@@ -252,6 +433,354 @@ pub fn record_error(component: &str, error_type: &str) {
     .increment(1);
 }
 
+/// Record bytes written to a captured data file
+pub fn record_data_bytes_written(prefix: &str, bytes: u64) {
+    counter!(
+        "polyhft_data_bytes_written_total",
+        "prefix" => prefix.to_string()
+    )
+    .increment(bytes);
+}
+
+/// Set the records in a durable capture queue not yet written to a file
+pub fn set_capture_queue_depth(prefix: &str, depth: u64) {
+    gauge!(
+        "polyhft_capture_queue_depth",
+        "prefix" => prefix.to_string()
+    )
+    .set(depth as f64);
+}
+
+/// Set the segment files of a durable capture queue
+pub fn set_capture_queue_segments(prefix: &str, segments: usize) {
+    gauge!(
+        "polyhft_capture_queue_segments",
+        "prefix" => prefix.to_string()
+    )
+    .set(segments as f64);
+}
+
+/// Record capture queue records replayed after a restart
+pub fn record_capture_queue_replayed(prefix: &str, records: u64) {
+    counter!(
+        "polyhft_capture_queue_replayed_total",
+        "prefix" => prefix.to_string()
+    )
+    .increment(records);
+}
+
+/// Record a call made through the retry utility, with its attempts and
+/// the time they and the waits between them took
+pub fn record_retry(subsystem: &str, attempts: u32, elapsed: Duration) {
+    histogram!("polyhft_retry_attempts", "subsystem" => subsystem.to_string())
+        .record(attempts as f64);
+    histogram!("polyhft_retry_seconds", "subsystem" => subsystem.to_string())
+        .record(elapsed.as_secs_f64());
+}
+
+/// Record a failure returned without retrying for want of retry budget
+pub fn record_retry_budget_exhausted(subsystem: &str) {
+    counter!(
+        "polyhft_retry_budget_exhausted_total",
+        "subsystem" => subsystem.to_string()
+    )
+    .increment(1);
+}
+
+/// Record an order book not captured for repeating the token's last row
+pub fn record_capture_book_suppressed() {
+    counter!("polyhft_capture_books_suppressed_total").increment(1);
+}
+
+/// Set where an asset's latest YES spread ranks, 0 to 1, among recent
+/// spreads in the same minute of the window
+pub fn set_window_spread_percentile(asset: &str, percentile: f64) {
+    gauge!(
+        "polyhft_window_spread_percentile",
+        "asset" => asset.to_string()
+    )
+    .set(percentile);
+}
+
+/// Record an event stream entry dropped for a slow client
+pub fn record_stream_dropped(kind: &str) {
+    counter!(
+        "polyhft_stream_dropped_total",
+        "kind" => kind.to_string()
+    )
+    .increment(1);
+}
+
+/// Set the current data directory size
+pub fn set_data_dir_bytes(bytes: f64) {
+    gauge!("polyhft_data_dir_bytes").set(bytes);
+}
+
+/// Set the share of tracked signals whose price reached the expected level
+pub fn set_signal_convergence_rate(rate: f64) {
+    gauge!("polyhft_signal_convergence_rate").set(rate);
+}
+
+/// Set a circuit breaker's state: 0 closed, 1 half-open, 2 open
+pub fn set_circuit_state(circuit: &str, severity: u8) {
+    gauge!(
+        "polyhft_circuit_state",
+        "circuit" => circuit.to_string()
+    )
+    .set(severity as f64);
+}
+
+/// Set an asset's loss cooldown: 0 clear, 1 cooling down, 2 halted
+pub fn set_loss_cooldown(asset: &str, severity: u8) {
+    gauge!(
+        "polyhft_loss_cooldown_state",
+        "asset" => asset.to_string()
+    )
+    .set(severity as f64);
+}
+
+/// Set whether an asset is flagged for strategy review
+pub fn set_strategy_review(asset: &str, flagged: bool) {
+    gauge!(
+        "polyhft_strategy_review",
+        "asset" => asset.to_string()
+    )
+    .set(if flagged { 1.0 } else { 0.0 });
+}
+
+/// Set this instance's role and the age of the leader lease
+pub fn set_leader_state(leader: bool, lease_age_secs: f64) {
+    gauge!("polyhft_leader").set(if leader { 1.0 } else { 0.0 });
+    gauge!("polyhft_leader_lease_age_seconds").set(lease_age_secs);
+}
+
+/// Set whether the spot windows started warm from a saved state
+pub fn set_warm_start(warm: bool) {
+    gauge!("polyhft_warm_start").set(if warm { 1.0 } else { 0.0 });
+}
+
+/// Record the YES/NO mid-price deviation for a market
+pub fn record_book_consistency_deviation(market: &str, deviation: f64) {
+    const NAME: &str = "polyhft_book_consistency_deviation";
+    let policy = label_policy();
+    let label = policy.market_label(market);
+    if policy.admit(NAME, &[("market", &label)], market) {
+        gauge!(NAME, "market" => label).set(deviation);
+    }
+}
+
+/// Record a crossed or locked book rejected by a consumer
+pub fn record_crossed_book(state: &str, consumer: &str) {
+    counter!(
+        "polyhft_crossed_books_total",
+        "state" => state.to_string(),
+        "consumer" => consumer.to_string()
+    )
+    .increment(1);
+}
+
+/// Record a tick or market of another asset reaching a detector
+pub fn record_asset_mismatch(kind: &str, expected: &str, actual: &str) {
+    counter!(
+        "polyhft_asset_mismatch_total",
+        "kind" => kind.to_string(),
+        "expected" => expected.to_string(),
+        "actual" => actual.to_string()
+    )
+    .increment(1);
+}
+
+/// Record the wait from a market's open to its first order book
+pub fn record_open_to_first_book(secs: f64) {
+    histogram!("polyhft_open_to_first_book_seconds").record(secs);
+}
+
+/// Record a batch the journal writer wrote, with each entry's wait from
+/// enqueue to fsync
+pub fn record_journal_batch(latencies: impl IntoIterator<Item = Duration>) {
+    let mut size = 0;
+    for latency in latencies {
+        histogram!("polyhft_journal_durable_latency_ms").record(latency.as_secs_f64() * 1000.0);
+        size += 1;
+    }
+    histogram!("polyhft_journal_batch_size").record(size as f64);
+}
+
+/// Record the size of a tick batch drained into one detection pass
+pub fn record_tick_batch(size: usize) {
+    histogram!("polyhft_tick_batch_size").record(size as f64);
+}
+
+/// Record a book message dropped as a duplicate or out of order
+pub fn record_book_dropped(token_id: &str, reason: &str) {
+    const NAME: &str = "polyhft_book_messages_dropped_total";
+    let policy = label_policy();
+    let label = policy.market_label(token_id);
+    if policy.admit(NAME, &[("token", &label), ("reason", reason)], token_id) {
+        counter!(NAME, "token" => label, "reason" => reason.to_string()).increment(1);
+    }
+}
+
+/// Record a book staleness check
+pub fn record_book_freshness(fresh: bool) {
+    let verdict = if fresh { "fresh" } else { "stale" };
+    counter!("polyhft_book_freshness_checks_total", "verdict" => verdict).increment(1);
+}
+
+/// Set the largest book age accepted for a token
+pub fn set_book_age_threshold(token_id: &str, ms: f64) {
+    const NAME: &str = "polyhft_book_age_threshold_ms";
+    let policy = label_policy();
+    let label = policy.market_label(token_id);
+    if policy.admit(NAME, &[("token", &label)], token_id) {
+        gauge!(NAME, "token" => label).set(ms);
+    }
+}
+
+/// Record an order book update for a token no tracked market has
+pub fn record_unmapped_book() {
+    counter!("polyhft_unmapped_books_total").increment(1);
+}
+
+/// Count a Gamma response field that was missing or of an unexpected
+/// type; `kind` is `missing`, `coerced` or `unexpected`
+pub fn record_gamma_drift(entity: &str, field: &str, kind: &str) {
+    counter!(
+        "polyhft_gamma_schema_drift_total",
+        "entity" => entity.to_string(),
+        "field" => field.to_string(),
+        "kind" => kind.to_string()
+    )
+    .increment(1);
+}
+
+/// Count a feature flag evaluated for a signal, in the treated cohort
+/// when `on`
+pub fn record_flag_evaluation(flag: &str, on: bool) {
+    counter!(
+        "polyhft_flag_evaluations_total",
+        "flag" => flag.to_string(),
+        "cohort" => if on { "treated" } else { "untreated" }
+    )
+    .increment(1);
+}
+
+/// Set a strategy's trading schedule state
+pub fn set_schedule_state(strategy: &str, open: bool, next_transition_secs: Option<i64>) {
+    gauge!("polyhft_schedule_open", "strategy" => strategy.to_string()).set(if open {
+        1.0
+    } else {
+        0.0
+    });
+    if let Some(secs) = next_transition_secs {
+        gauge!(
+            "polyhft_schedule_next_transition_seconds",
+            "strategy" => strategy.to_string()
+        )
+        .set(secs as f64);
+    }
+}
+
+/// Count a signal rejected by a filter
+pub fn record_signal_rejected(reason: &str) {
+    counter!(
+        "polyhft_signals_rejected_total",
+        "reason" => reason.to_string()
+    )
+    .increment(1);
+}
+
+/// Count a detection whose fair value models disagreed; `action` is
+/// `suppressed` or `annotated`
+pub fn record_model_disagreement(action: &str) {
+    counter!(
+        "polyhft_model_disagreements_total",
+        "action" => action.to_string()
+    )
+    .increment(1);
+}
+
+/// Count a position closed before settlement for `reason`
+pub fn record_exit(reason: &str) {
+    counter!("polyhft_exits_total", "reason" => reason.to_string()).increment(1);
+}
+
+/// Count a provisional settlement finalized as `status`
+pub fn record_resolution(status: &str) {
+    counter!("polyhft_resolutions_total", "status" => status.to_string()).increment(1);
+}
+
+/// Set the booked P&L still awaiting official resolution
+pub fn set_provisional_pnl(pnl: f64) {
+    gauge!("polyhft_provisional_pnl").set(pnl);
+}
+
+/// Set the last difference between the exchange's collateral balance and
+/// the ledger's cash
+pub fn set_balance_drift(drift: f64) {
+    gauge!("polyhft_balance_drift").set(drift);
+}
+
+/// Count a wall-clock `step` or process `pause`
+pub fn record_clock_event(kind: &str) {
+    counter!("polyhft_clock_events_total", "kind" => kind.to_string()).increment(1);
+}
+
+/// Count a restart of the supervised task `component`
+pub fn record_task_restart(component: &str) {
+    counter!(
+        "polyhft_task_restarts_total",
+        "component" => component.to_string()
+    )
+    .increment(1);
+}
+
+/// Count an entry withheld by the rate cap `cap`
+pub fn record_rate_cap_hit(cap: &str) {
+    counter!(
+        "polyhft_rate_cap_hits_total",
+        "cap" => cap.to_string()
+    )
+    .increment(1);
+}
+
+/// Count price ticks skipped by the detection loop
+pub fn record_ticks_skipped(reason: &str, count: u64) {
+    counter!(
+        "polyhft_ticks_skipped_total",
+        "reason" => reason.to_string()
+    )
+    .increment(count);
+}
+
+/// Count market data events dropped for a slow bus subscriber
+pub fn record_bus_dropped(subscriber: &str, count: u64) {
+    counter!(
+        "polyhft_bus_dropped_total",
+        "subscriber" => subscriber.to_string()
+    )
+    .increment(count);
+}
+
+/// Set the entry count of the internal structure `component`
+pub fn set_internal_size(component: &str, count: f64) {
+    gauge!(
+        "polyhft_internal_entries",
+        "component" => component.to_string()
+    )
+    .set(count);
+}
+
+/// Set the messages queued in `channel`
+pub fn set_channel_depth(channel: &str, depth: f64) {
+    gauge!("polyhft_channel_depth", "channel" => channel.to_string()).set(depth);
+}
+
+/// Publish the running config's fingerprint as a label
+pub fn set_config_fingerprint(hash: &str) {
+    gauge!("polyhft_config_info", "config_hash" => hash.to_string()).set(1.0);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -434,4 +963,45 @@ mod tests {
     fn test_record_error_no_panic() {
         record_error("feed", "connection_failed");
     }
+
+    #[test]
+    fn test_record_retry_no_panic() {
+        record_retry("gamma", 3, Duration::from_millis(750));
+        record_retry_budget_exhausted("gamma");
+    }
+
+    #[test]
+    fn test_record_capture_book_suppressed_no_panic() {
+        record_capture_book_suppressed();
+    }
+
+    #[test]
+    fn test_set_window_spread_percentile_no_panic() {
+        set_window_spread_percentile("BTC", 0.75);
+    }
+
+    #[test]
+    fn test_record_journal_batch_no_panic() {
+        record_journal_batch([Duration::from_millis(3), Duration::from_millis(1)]);
+    }
+
+    #[test]
+    fn test_record_gamma_drift_no_panic() {
+        record_gamma_drift("market", "clobTokenIds", "coerced");
+    }
+
+    #[test]
+    fn test_record_flag_evaluation_no_panic() {
+        record_flag_evaluation("exit_ladder", true);
+    }
+
+    #[test]
+    fn test_record_crossed_book_no_panic() {
+        record_crossed_book("crossed", "lag");
+    }
+
+    #[test]
+    fn test_set_config_fingerprint_no_panic() {
+        set_config_fingerprint("abc123");
+    }
 }
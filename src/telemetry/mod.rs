@@ -2,15 +2,45 @@
 //!
 //! Metrics, logging, and distributed tracing
 
+mod channels;
+mod events;
+mod health;
+mod internals;
+mod labels;
 mod logging;
 mod metrics;
 mod tracing_setup;
 
-pub use logging::{init_logging, LogFormat};
+pub use channels::{channel_depths, instrumented_channel, ChannelDepth, InstrumentedSender};
+pub use events::{catalogue_markdown, fields, journald_priority, EventCode};
+pub use health::{ComponentHealth, HealthRegistry, HealthState};
+pub use internals::{
+    InternalsSnapshot, RecorderBuffers, DEFAULT_INTERNALS_INTERVAL_SECS, INTERNALS_FILE,
+};
+pub use labels::{
+    init_label_policy, label_policy, LabelConfig, LabelPolicy, DEFAULT_MAX_SERIES,
+    DEFAULT_SERIES_EXPIRY_MINS, UNKNOWN_MARKET_LABEL,
+};
+pub use logging::{
+    apply_log_directives_request, file_appender, init_logging, parse_directives,
+    request_log_directives, set_log_directives, take_log_directives_request, JournaldFormat,
+    LogFormat, LogReloadHandle, LogRotation, LoggingGuard, LOG_LEVEL_REQUEST_FILE,
+};
 pub use metrics::{
-    increment_counter, increment_counter_simple, init_metrics_server, record_error, record_fill,
-    record_latency, record_order, record_orderbook_update, record_price_tick, record_signal,
-    record_ws_reconnect, set_gauge, CounterMetric, GaugeMetric, LatencyMetric,
+    increment_counter, increment_counter_simple, init_metrics_server, record_asset_mismatch,
+    record_book_consistency_deviation, record_book_dropped, record_book_freshness,
+    record_bus_dropped, record_capture_book_suppressed, record_capture_queue_replayed,
+    record_clock_event, record_crossed_book, record_data_bytes_written, record_error, record_exit,
+    record_fill, record_flag_evaluation, record_gamma_drift, record_journal_batch, record_latency,
+    record_model_disagreement, record_open_to_first_book, record_order, record_orderbook_update,
+    record_price_tick, record_rate_cap_hit, record_resolution, record_retry,
+    record_retry_budget_exhausted, record_signal, record_signal_rejected, record_stream_dropped,
+    record_task_restart, record_tick_batch, record_ticks_skipped, record_unmapped_book,
+    record_ws_reconnect, set_balance_drift, set_book_age_threshold, set_capture_queue_depth,
+    set_capture_queue_segments, set_channel_depth, set_circuit_state, set_config_fingerprint,
+    set_data_dir_bytes, set_gauge, set_internal_size, set_leader_state, set_loss_cooldown,
+    set_provisional_pnl, set_schedule_state, set_signal_convergence_rate, set_strategy_review,
+    set_warm_start, set_window_spread_percentile, CounterMetric, GaugeMetric, LatencyMetric,
 };
 pub use tracing_setup::init_tracing;
 
@@ -18,19 +48,31 @@ use crate::config::TelemetryConfig;
 
 /// Guard that cleans up telemetry on drop
 pub struct TelemetryGuard {
-    _priv: (),
+    logging: LoggingGuard,
+}
+
+impl TelemetryGuard {
+    /// Handle for changing the log filter at runtime
+    pub fn log_reload_handle(&self) -> &LogReloadHandle {
+        self.logging.reload_handle()
+    }
 }
 
 /// Initialize all telemetry subsystems
 pub fn init_telemetry(config: &TelemetryConfig) -> anyhow::Result<TelemetryGuard> {
-    init_logging(&config.log_level)?;
+    let logging = init_logging(
+        &config.log_level,
+        config.log_format,
+        config.log_file.as_ref(),
+    )?;
 
     if let Some(ref endpoint) = config.otlp_endpoint {
         init_tracing(endpoint)?;
     }
 
-    // Start metrics server
+    // Start metrics server, its idle timeout comes from the label policy
+    init_label_policy(&config.labels, config.max_series);
     init_metrics_server(config.metrics_port)?;
 
-    Ok(TelemetryGuard { _priv: () })
+    Ok(TelemetryGuard { logging })
 }
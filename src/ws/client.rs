@@ -1,9 +1,12 @@
 //! WebSocket client with automatic reconnection
 
 use super::types::{WsConfig, WsError, WsMessage};
+use crate::retry::retry;
+use crate::supervisor::{RestartPolicy, Supervised};
+use crate::telemetry::{instrumented_channel, EventCode, InstrumentedSender};
 use futures_util::{SinkExt, StreamExt};
-use tokio::sync::mpsc;
-use tokio::time::sleep;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
 /// Reusable WebSocket client with automatic reconnection and ping/pong handling
@@ -29,18 +32,25 @@ impl WsClient {
 
     /// Connect and return a receiver for messages
     ///
-    /// This spawns a background task that handles connection management,
-    /// automatic reconnection with exponential backoff, and ping/pong keepalive.
+    /// This spawns a supervised background task that handles connection
+    /// management, automatic reconnection under the config's retry policy, and
+    /// ping/pong keepalive. A panic restarts it; giving up after the maximum
+    /// reconnection attempts does not.
     ///
     /// Returns a channel receiver that will receive all WebSocket messages
     /// including connection status events (Connected, Disconnected, Reconnecting).
     pub fn connect(&self) -> mpsc::Receiver<WsMessage> {
-        let (tx, rx) = mpsc::channel(1024);
+        let (tx, rx) = instrumented_channel("ws_messages", 1024);
         let config = self.config.clone();
 
-        tokio::spawn(async move {
-            if let Err(e) = Self::run_connection_loop(config, tx).await {
-                tracing::error!(error = %e, "WebSocket connection loop failed");
+        Supervised::spawn(&self.component(), RestartPolicy::backoff(), move || {
+            let config = config.clone();
+            let tx = tx.inner().clone();
+            async move {
+                if let Err(e) = Self::run_with_reconnects(config, tx, None).await {
+                    tracing::error!(error = %e, "WebSocket connection loop failed");
+                }
+                Ok(())
             }
         });
 
@@ -51,128 +61,85 @@ impl WsClient {
     ///
     /// The sender can be used to send messages to the WebSocket server.
     /// Returns (message_receiver, message_sender)
-    pub fn connect_bidirectional(&self) -> (mpsc::Receiver<WsMessage>, mpsc::Sender<String>) {
-        let (msg_tx, msg_rx) = mpsc::channel(1024);
-        let (send_tx, send_rx) = mpsc::channel(256);
+    pub fn connect_bidirectional(&self) -> (mpsc::Receiver<WsMessage>, InstrumentedSender<String>) {
+        let (msg_tx, msg_rx) = instrumented_channel("ws_messages", 1024);
+        let (send_tx, send_rx) = instrumented_channel("ws_outbound", 256);
         let config = self.config.clone();
 
-        tokio::spawn(async move {
-            if let Err(e) = Self::run_bidirectional_loop(config, msg_tx, send_rx).await {
-                tracing::error!(error = %e, "WebSocket bidirectional loop failed");
+        let send_rx = Arc::new(Mutex::new(send_rx));
+        Supervised::spawn(&self.component(), RestartPolicy::backoff(), move || {
+            let config = config.clone();
+            let msg_tx = msg_tx.inner().clone();
+            let send_rx = send_rx.clone();
+            async move {
+                let mut send_rx = send_rx.lock().await;
+                if let Err(e) = Self::run_with_reconnects(config, msg_tx, Some(&mut *send_rx)).await
+                {
+                    tracing::error!(error = %e, "WebSocket bidirectional loop failed");
+                }
+                Ok(())
             }
         });
 
         (msg_rx, send_tx)
     }
 
-    /// Run the connection loop with automatic reconnection
-    async fn run_connection_loop(
-        config: WsConfig,
-        tx: mpsc::Sender<WsMessage>,
-    ) -> Result<(), WsError> {
-        let mut reconnect_attempts = 0;
-        let mut reconnect_delay = config.initial_reconnect_delay;
-
-        loop {
-            match Self::connect_and_stream(&config, &tx, None).await {
-                Ok(()) => {
-                    tracing::info!("WebSocket connection closed cleanly");
-                    let _ = tx.send(WsMessage::Disconnected).await;
-                    break;
-                }
-                Err(e) => {
-                    reconnect_attempts += 1;
-                    tracing::warn!(
-                        error = %e,
-                        attempt = reconnect_attempts,
-                        "WebSocket connection error, reconnecting..."
-                    );
-
-                    // Check max reconnects (0 = infinite)
-                    if config.max_reconnect_attempts > 0
-                        && reconnect_attempts >= config.max_reconnect_attempts
-                    {
-                        tracing::error!("Max reconnection attempts reached");
-                        let _ = tx.send(WsMessage::Disconnected).await;
-                        return Err(WsError::MaxReconnectsExceeded);
-                    }
-
-                    // Check if receiver is still alive
-                    if tx.is_closed() {
-                        tracing::info!("Receiver dropped, stopping reconnection");
-                        break;
-                    }
-
-                    // Notify about reconnection attempt
-                    let _ = tx
-                        .send(WsMessage::Reconnecting {
-                            attempt: reconnect_attempts,
-                        })
-                        .await;
-
-                    sleep(reconnect_delay).await;
-                    reconnect_delay = (reconnect_delay * 2).min(config.max_reconnect_delay);
-                }
-            }
-        }
-
-        Ok(())
+    /// Supervised component name of the connection task
+    fn component(&self) -> String {
+        format!("ws:{}", self.config.url)
     }
 
-    /// Run bidirectional connection loop
-    async fn run_bidirectional_loop(
+    /// Stream from the socket, reconnecting under the config's policy until
+    /// it closes cleanly or the policy gives up
+    async fn run_with_reconnects(
         config: WsConfig,
         tx: mpsc::Sender<WsMessage>,
-        send_rx: mpsc::Receiver<String>,
+        send_rx: Option<&mut mpsc::Receiver<String>>,
     ) -> Result<(), WsError> {
-        let mut reconnect_attempts = 0;
-        let mut reconnect_delay = config.initial_reconnect_delay;
-        let mut send_rx = send_rx;
-
-        loop {
-            match Self::connect_and_stream(&config, &tx, Some(&mut send_rx)).await {
-                Ok(()) => {
-                    tracing::info!("WebSocket connection closed cleanly");
-                    let _ = tx.send(WsMessage::Disconnected).await;
-                    break;
+        let send_rx = Mutex::new(send_rx);
+        let (config, tx, send_rx) = (&config, &tx, &send_rx);
+        let result = retry(&config.reconnect, move |attempt| async move {
+            if attempt > 1 {
+                if tx.is_closed() {
+                    tracing::info!("Receiver dropped, stopping reconnection");
+                    return Ok(());
                 }
-                Err(e) => {
-                    reconnect_attempts += 1;
+                // Failures so far
+                let _ = tx
+                    .send(WsMessage::Reconnecting {
+                        attempt: attempt - 1,
+                    })
+                    .await;
+            }
+            let mut send_rx = send_rx.lock().await;
+            Self::connect_and_stream(config, tx, send_rx.as_deref_mut())
+                .await
+                .inspect_err(|e| {
                     tracing::warn!(
+                        event_code = %EventCode::WsReconnect,
                         error = %e,
-                        attempt = reconnect_attempts,
+                        attempt,
                         "WebSocket connection error, reconnecting..."
                     );
-
-                    if config.max_reconnect_attempts > 0
-                        && reconnect_attempts >= config.max_reconnect_attempts
-                    {
-                        tracing::error!("Max reconnection attempts reached");
-                        let _ = tx.send(WsMessage::Disconnected).await;
-                        return Err(WsError::MaxReconnectsExceeded);
-                    }
-
-                    if tx.is_closed() {
-                        tracing::info!("Receiver dropped, stopping reconnection");
-                        break;
-                    }
-
-                    let _ = tx
-                        .send(WsMessage::Reconnecting {
-                            attempt: reconnect_attempts,
-                        })
-                        .await;
-
-                    sleep(reconnect_delay).await;
-                    reconnect_delay = (reconnect_delay * 2).min(config.max_reconnect_delay);
-                }
+                })
+        })
+        .await;
+
+        let _ = tx.send(WsMessage::Disconnected).await;
+        match result {
+            Ok(()) => {
+                tracing::info!("WebSocket connection closed cleanly");
+                Ok(())
+            }
+            Err(e) => {
+                tracing::error!(event_code = %EventCode::WsGaveUp, error = %e, "Max reconnection attempts reached");
+                Err(WsError::MaxReconnectsExceeded)
             }
         }
-
-        Ok(())
     }
 
     /// Connect to WebSocket and stream messages
+    #[allow(clippy::collapsible_match)]
     async fn connect_and_stream(
         config: &WsConfig,
         tx: &mpsc::Sender<WsMessage>,
@@ -186,7 +153,7 @@ impl WsClient {
 
         let (mut write, mut read) = ws_stream.split();
 
-        tracing::info!("WebSocket connected");
+        tracing::info!(event_code = %EventCode::WsConnected, url = %config.url, "WebSocket connected");
 
         // Notify connected
         if tx.send(WsMessage::Connected).await.is_err() {
@@ -297,7 +264,7 @@ mod tests {
 
         let client = WsClient::new(config);
         assert_eq!(client.url(), "wss://test.com");
-        assert_eq!(client.config.max_reconnect_attempts, 5);
+        assert_eq!(client.config.reconnect.max_attempts, 5);
         assert_eq!(client.config.ping_interval, Duration::from_secs(15));
     }
 
@@ -339,9 +306,9 @@ mod tests {
             .max_delay(Duration::from_secs(10))
             .ping_interval(Duration::from_secs(20));
 
-        assert_eq!(config.max_reconnect_attempts, 3);
-        assert_eq!(config.initial_reconnect_delay, Duration::from_millis(100));
-        assert_eq!(config.max_reconnect_delay, Duration::from_secs(10));
+        assert_eq!(config.reconnect.max_attempts, 3);
+        assert_eq!(config.reconnect.base_delay, Duration::from_millis(100));
+        assert_eq!(config.reconnect.max_delay, Duration::from_secs(10));
         assert_eq!(config.ping_interval, Duration::from_secs(20));
     }
 }
@@ -1,5 +1,6 @@
 //! WebSocket types and configuration
 
+use crate::retry::{Jitter, RetryPolicy};
 use std::time::Duration;
 
 /// WebSocket client configuration
@@ -7,12 +8,9 @@ use std::time::Duration;
 pub struct WsConfig {
     /// WebSocket URL to connect to
     pub url: String,
-    /// Maximum reconnection attempts before giving up (0 = infinite)
-    pub max_reconnect_attempts: u32,
-    /// Initial delay before first reconnection attempt
-    pub initial_reconnect_delay: Duration,
-    /// Maximum delay between reconnection attempts
-    pub max_reconnect_delay: Duration,
+    /// Connection attempts before giving up (0 = infinite) and the backoff
+    /// between them; unbudgeted, as each client holds one connection
+    pub reconnect: RetryPolicy<WsError>,
     /// Interval for sending ping frames
     pub ping_interval: Duration,
     /// Timeout for pong response
@@ -23,9 +21,11 @@ impl Default for WsConfig {
     fn default() -> Self {
         Self {
             url: String::new(),
-            max_reconnect_attempts: 10,
-            initial_reconnect_delay: Duration::from_secs(1),
-            max_reconnect_delay: Duration::from_secs(60),
+            reconnect: RetryPolicy::new("ws")
+                .max_attempts(10)
+                .delays(Duration::from_secs(1), Duration::from_secs(60))
+                .jitter(Jitter::None)
+                .unbudgeted(),
             ping_interval: Duration::from_secs(30),
             pong_timeout: Duration::from_secs(10),
         }
@@ -43,19 +43,19 @@ impl WsConfig {
 
     /// Set maximum reconnection attempts
     pub fn max_reconnects(mut self, n: u32) -> Self {
-        self.max_reconnect_attempts = n;
+        self.reconnect.max_attempts = n;
         self
     }
 
     /// Set initial reconnection delay
     pub fn initial_delay(mut self, d: Duration) -> Self {
-        self.initial_reconnect_delay = d;
+        self.reconnect.base_delay = d;
         self
     }
 
     /// Set maximum reconnection delay
     pub fn max_delay(mut self, d: Duration) -> Self {
-        self.max_reconnect_delay = d;
+        self.reconnect.max_delay = d;
         self
     }
 
@@ -114,9 +114,9 @@ mod tests {
     #[test]
     fn test_ws_config_default() {
         let config = WsConfig::default();
-        assert_eq!(config.max_reconnect_attempts, 10);
-        assert_eq!(config.initial_reconnect_delay, Duration::from_secs(1));
-        assert_eq!(config.max_reconnect_delay, Duration::from_secs(60));
+        assert_eq!(config.reconnect.max_attempts, 10);
+        assert_eq!(config.reconnect.base_delay, Duration::from_secs(1));
+        assert_eq!(config.reconnect.max_delay, Duration::from_secs(60));
         assert_eq!(config.ping_interval, Duration::from_secs(30));
     }
 
@@ -129,9 +129,9 @@ mod tests {
             .ping_interval(Duration::from_secs(15));
 
         assert_eq!(config.url, "wss://example.com");
-        assert_eq!(config.max_reconnect_attempts, 5);
-        assert_eq!(config.initial_reconnect_delay, Duration::from_millis(500));
-        assert_eq!(config.max_reconnect_delay, Duration::from_secs(30));
+        assert_eq!(config.reconnect.max_attempts, 5);
+        assert_eq!(config.reconnect.base_delay, Duration::from_millis(500));
+        assert_eq!(config.reconnect.max_delay, Duration::from_secs(30));
         assert_eq!(config.ping_interval, Duration::from_secs(15));
     }
 